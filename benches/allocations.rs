@@ -0,0 +1,200 @@
+//! Building prompts and handling responses, timed and counted by an allocator that counts the
+//! allocations of its thread. The counts of one call are printed before each group, and the
+//! benchmark fails if they exceed the bounds below, 60% of the counts before the allocation
+//! pass.
+//!
+//! ```sh
+//! cargo bench --bench allocations
+//! ```
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::hint::black_box;
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use secretary::Task;
+use secretary::compiled::{CompileOptions, CompiledTask};
+use secretary::decoding::DecodingPolicy;
+use secretary::reasoning::strip_inline_reasoning;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+fn count_allocation() {
+    let _ = ALLOCATIONS.try_with(|allocations| allocations.set(allocations.get() + 1));
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count_allocation();
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        count_allocation();
+        unsafe { System.alloc_zeroed(layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count_allocation();
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Returns the number of allocations a closure makes on this thread.
+fn allocations<T>(f: impl FnOnce() -> T) -> usize {
+    let before: usize = ALLOCATIONS.with(Cell::get);
+    black_box(f());
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+/// The most allocations one call may make.
+///
+/// Each bound is 60% of the count of the same call before the allocation pass, rounded down.
+/// Those counts were 171, 27 and 31, taken with this allocator on the `Listing`, target,
+/// instructions and response below.
+const MAX_FIELD_REQUESTS: usize = 102;
+const MAX_SYSTEM_PROMPT: usize = 16;
+const MAX_RESPONSE_HANDLING: usize = 18;
+
+/// Prints the allocations of one call, and fails if they exceed `bound`.
+fn gate(name: &str, bound: usize, now: usize) {
+    println!("{}: {} allocations, at most {}", name, now, bound);
+    assert!(
+        now <= bound,
+        "{}: {} allocations, at most {} expected",
+        name,
+        now,
+        bound
+    );
+}
+
+#[derive(Task, Serialize, Deserialize, Debug)]
+struct Listing {
+    #[task(instruction = "Extract the address")]
+    pub address: String,
+    #[task(instruction = "Extract the rent per month")]
+    pub rent: u32,
+    #[task(instruction = "Extract the number of rooms")]
+    pub rooms: u8,
+    #[task(instruction = "Extract whether pets are allowed")]
+    pub pets: bool,
+    #[task(instruction = "List the amenities")]
+    pub amenities: Vec<String>,
+    #[task(instruction = "Extract the deposit, if any")]
+    pub deposit: Option<u32>,
+}
+
+const TARGET: &str = "Flat at 12 Harbour Road, 3 rooms, 1450 a month, pets welcome. \
+                      Balcony and dishwasher. Deposit of two months' rent.";
+
+fn instructions() -> Vec<String> {
+    [
+        "Answer in English",
+        "Ignore the advertisements",
+        "Prefer the figures of the latest update",
+        "Give amounts without the currency",
+        "Leave out the agent's details",
+    ]
+    .iter()
+    .map(|instruction| instruction.to_string())
+    .collect()
+}
+
+/// A chat completions response whose answer has neither reasoning nor escapes.
+fn response() -> (String, String) {
+    let content: String = json!({
+        "address": "12 Harbour Road",
+        "rent": 1450,
+        "rooms": 3,
+        "pets": true,
+        "amenities": ["balcony", "dishwasher"],
+        "deposit": 2900
+    })
+    .to_string();
+    let api_response: String = json!({
+        "id": "chatcmpl-bench",
+        "object": "chat.completion",
+        "model": "bench-model",
+        "choices": [{
+            "index": 0,
+            "message": {"role": "assistant", "content": content},
+            "finish_reason": "stop"
+        }],
+        "usage": {"prompt_tokens": 120, "completion_tokens": 40, "total_tokens": 160}
+    })
+    .to_string();
+
+    (content, api_response)
+}
+
+fn content_as_now(content: String, api_response: &str) -> String {
+    let content: String = strip_inline_reasoning(content, api_response);
+    DecodingPolicy::Repair.repair_content(content).0
+}
+
+fn bench_prompts(c: &mut Criterion) {
+    let task: Listing = Listing::new();
+    let compiled: CompiledTask<Listing> = Listing::new().compile(CompileOptions::default());
+    let additional_instructions: Vec<String> = instructions();
+
+    gate(
+        "field_requests",
+        MAX_FIELD_REQUESTS,
+        allocations(|| task.make_distributed_generation_requests(TARGET, &additional_instructions)),
+    );
+    gate(
+        "system_prompt",
+        MAX_SYSTEM_PROMPT,
+        allocations(|| task.get_system_prompt()),
+    );
+
+    let mut group = c.benchmark_group("field_requests");
+    group.bench_function("task", |b| {
+        b.iter(|| {
+            black_box(&task).make_distributed_generation_requests(TARGET, &additional_instructions)
+        })
+    });
+    group.finish();
+
+    let mut group = c.benchmark_group("system_prompt");
+    group.bench_function("task", |b| b.iter(|| black_box(&task).get_system_prompt()));
+    // Rendered once, when the Task is compiled
+    group.bench_function("compiled", |b| {
+        b.iter(|| black_box(&compiled).system_prompt())
+    });
+    group.finish();
+}
+
+fn bench_responses(c: &mut Criterion) {
+    let (content, api_response) = response();
+
+    // The copy of the content is made outside the count, as when it was recorded
+    let copy: String = content.clone();
+    gate(
+        "response_handling",
+        MAX_RESPONSE_HANDLING,
+        allocations(|| content_as_now(copy, &api_response)),
+    );
+
+    let mut group = c.benchmark_group("response_handling");
+    group.bench_function("now", |b| {
+        b.iter(|| content_as_now(content.clone(), black_box(&api_response)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_prompts, bench_responses);
+criterion_main!(benches);
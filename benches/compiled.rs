@@ -0,0 +1,74 @@
+//! Per-call request building for a Task and for the same Task compiled.
+//!
+//! ```sh
+//! cargo bench --bench compiled
+//! ```
+
+use std::hint::black_box;
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use secretary::Task;
+use secretary::compiled::{CompileOptions, CompiledTask, ExtractionPlan};
+use serde::{Deserialize, Serialize};
+
+#[derive(Task, Serialize, Deserialize, Debug)]
+struct Address {
+    #[task(instruction = "Extract the street and house number")]
+    pub street: String,
+    #[task(instruction = "Extract the city")]
+    pub city: String,
+    #[task(instruction = "Extract the postal code")]
+    pub postal_code: String,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug)]
+struct Invoice {
+    #[task(instruction = "Extract the invoice number")]
+    pub number: String,
+    #[task(instruction = "Extract the issue date as YYYY-MM-DD")]
+    pub issued_on: String,
+    #[task(instruction = "Extract the vendor name")]
+    pub vendor: String,
+    pub vendor_address: Address,
+    pub billing_address: Address,
+    #[task(instruction = "Extract the net total as a float")]
+    pub net_total: f64,
+    #[task(instruction = "Extract the tax as a float")]
+    pub tax: f64,
+    #[task(instruction = "Extract the currency code")]
+    pub currency: String,
+    #[task(instruction = "List the line item descriptions")]
+    pub items: Vec<String>,
+}
+
+const TARGET: &str = "Invoice INV-2024-0042 issued 2024-03-01 by ACME GmbH, Hauptstr. 1, 10115 Berlin, \
+    billed to Globex, 5 Main St, Springfield 12345. Consulting 10h, Travel. Net 1200.00 EUR, VAT 228.00 EUR.";
+
+fn bench_requests(c: &mut Criterion) {
+    let instructions: Vec<String> = vec!["Amounts are in the invoice currency".to_string()];
+    let task = Invoice::new();
+    let compiled: CompiledTask<Invoice> = Invoice::new()
+        .compile(CompileOptions::default().with_additional_instructions(instructions.clone()));
+    let no_instructions: Vec<String> = Vec::new();
+
+    let mut group = c.benchmark_group("prompt_messages");
+    group.bench_function("task", |b| {
+        b.iter(|| task.prompt_messages(black_box(TARGET), &instructions))
+    });
+    group.bench_function("compiled", |b| {
+        b.iter(|| compiled.prompt_messages(black_box(TARGET), &no_instructions))
+    });
+    group.finish();
+
+    let mut group = c.benchmark_group("field_requests");
+    group.bench_function("task", |b| {
+        b.iter(|| task.field_requests(black_box(TARGET), &instructions))
+    });
+    group.bench_function("compiled", |b| {
+        b.iter(|| compiled.field_requests(black_box(TARGET), &no_instructions))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_requests);
+criterion_main!(benches);
@@ -0,0 +1,89 @@
+//! Parsing a response with 1 MB of content, through a `serde_json::Value` as before and
+//! straight from the text as now.
+//!
+//! ```sh
+//! cargo bench --bench parsing
+//! ```
+
+use std::hint::black_box;
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use secretary::Task;
+use secretary::leniency::LeniencyProfile;
+use secretary::utilities::extract_text_content_from_llm_response;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+#[derive(Task, Serialize, Deserialize, Debug)]
+struct Transcript {
+    #[task(instruction = "Extract the title of the meeting")]
+    pub title: String,
+    #[task(instruction = "Summarize the meeting")]
+    pub summary: String,
+    #[task(instruction = "List the speakers")]
+    pub speakers: Vec<String>,
+}
+
+/// A chat completions response whose content is a `Transcript` with a 1 MB summary.
+fn response() -> String {
+    let sentence: &str = "Ada walked the team through the quarterly figures. ";
+    let summary: String = sentence.repeat(1024 * 1024 / sentence.len());
+    let content: String = json!({
+        "title": "Quarterly review",
+        "summary": summary,
+        "speakers": ["Ada", "Grace"],
+    })
+    .to_string();
+
+    json!({
+        "id": "chatcmpl-bench",
+        "object": "chat.completion",
+        "model": "bench-model",
+        "choices": [{
+            "index": 0,
+            "message": {"role": "assistant", "content": content},
+            "finish_reason": "stop"
+        }],
+        "usage": {"prompt_tokens": 50, "completion_tokens": 250000, "total_tokens": 250050}
+    })
+    .to_string()
+}
+
+/// The content lookup as it was, through a `Value` of the whole response.
+fn content_through_value(api_response: &str) -> String {
+    let value: Value = serde_json::from_str(api_response).unwrap();
+    value["choices"][0]["message"]["content"]
+        .as_str()
+        .unwrap()
+        .to_string()
+}
+
+fn bench_parsing(c: &mut Criterion) {
+    let response: String = response();
+    let content: String = extract_text_content_from_llm_response(&response).unwrap();
+    let strict = LeniencyProfile::strict();
+
+    let mut group = c.benchmark_group("response_content");
+    group.bench_function("value", |b| {
+        b.iter(|| content_through_value(black_box(&response)))
+    });
+    group.bench_function("envelope", |b| {
+        b.iter(|| extract_text_content_from_llm_response(black_box(&response)).unwrap())
+    });
+    group.finish();
+
+    let mut group = c.benchmark_group("task_from_content");
+    group.bench_function("value", |b| {
+        b.iter(|| {
+            let value: Value = serde_json::from_str(black_box(&content)).unwrap();
+            serde_json::from_value::<Transcript>(value).unwrap()
+        })
+    });
+    group.bench_function("str", |b| {
+        b.iter(|| strict.from_str::<Transcript>(black_box(&content)).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_parsing);
+criterion_main!(benches);
@@ -0,0 +1,56 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use futures::StreamExt;
+use secretary::llm_providers::openai::OpenAILLM;
+use secretary::traits::{AsyncGenerateData, Task};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+#[derive(Task, Serialize, Deserialize, Debug)]
+struct Contact {
+    #[task(instruction = "Extract the person's full name")]
+    pub name: String,
+
+    #[task(instruction = "Extract the email address if mentioned")]
+    pub email: Option<String>,
+
+    #[task(instruction = "Extract the company the person works for")]
+    pub company: Option<String>,
+}
+
+/// Streams the extractions of a batch into a JSONL file as each one finishes
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    println!("Secretary Batch Streaming Example - Contacts to JSONL");
+    println!("{}", "=".repeat(60));
+
+    let task = Contact::new();
+    let targets: Vec<String> = vec![
+        "Hi, I'm John Smith from Acme, reach me at john@acme.com".to_string(),
+        "Sarah Lee works as a designer at Globex".to_string(),
+        "Mike's email is mike@example.com".to_string(),
+        "Please forward this to Dana Park, our CFO at Initech".to_string(),
+    ];
+
+    let llm = OpenAILLM::from_env()?;
+
+    // Each line is written as soon as its extraction is done, with the index of its target
+    // so that the lines can be put back in the order of the targets later
+    let mut writer = BufWriter::new(File::create("contacts.jsonl")?);
+    let mut results = llm.stream_generate_data_batch(&task, targets, Vec::new(), 2);
+    while let Some((index, result)) = results.next().await {
+        let line = match result {
+            Ok(contact) => json!({"index": index, "data": contact}),
+            Err(error) => json!({"index": index, "error": error.to_string()}),
+        };
+        writeln!(writer, "{}", line)?;
+        writer.flush()?;
+        println!("Wrote target {}", index);
+    }
+
+    println!();
+    println!("Results written to contacts.jsonl");
+
+    Ok(())
+}
@@ -1,7 +1,6 @@
 use secretary::llm_providers::openai::OpenAILLM;
 use secretary::traits::{AsyncGenerateData, Task};
 use serde::{Deserialize, Serialize};
-use tokio;
 
 #[derive(Task, Serialize, Deserialize, Debug)]
 struct Details {
@@ -78,11 +77,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>
     // For demonstration, we'll show how to set up the async call
     println!("Setting up async LLM call (requires API credentials):");
 
-    let llm = OpenAILLM::new(
-        &std::env::var("SECRETARY_OPENAI_API_BASE").unwrap(),
-        &std::env::var("SECRETARY_OPENAI_API_KEY").unwrap(),
-        &std::env::var("SECRETARY_OPENAI_MODEL").unwrap(),
-    )?;
+    let llm = OpenAILLM::from_env()?;
 
     println!("Making async request to LLM...");
     let result: ProductExtraction = llm
@@ -2,7 +2,6 @@ use secretary::Task;
 use secretary::llm_providers::openai::OpenAILLM;
 use secretary::traits::AsyncGenerateData;
 use serde::{Deserialize, Serialize};
-use tokio;
 
 /// Example data structure for extracting research paper information
 /// This example demonstrates force generation for LLMs without JSON mode support
@@ -90,11 +89,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>
     // Note: This would require actual API credentials to work
     println!("Setting up async force LLM call (for reasoning models without JSON mode):");
 
-    let llm = OpenAILLM::new(
-        &std::env::var("SECRETARY_OPENAI_API_BASE").unwrap(),
-        &std::env::var("SECRETARY_OPENAI_API_KEY").unwrap(),
-        &std::env::var("SECRETARY_OPENAI_MODEL").unwrap(), // Could be o1-preview, deepseek-reasoner, etc.
-    )?;
+    // SECRETARY_OPENAI_MODEL could be o1-preview, deepseek-reasoner, etc.
+    let llm = OpenAILLM::from_env()?;
 
     println!("Making async force request to LLM (bypassing JSON mode requirement)...");
 
@@ -0,0 +1,100 @@
+//! Runs an async extraction on async-std's executor instead of tokio.
+//!
+//! The async generation methods only use `futures` primitives and `futures-timer`, so they run
+//! on any executor. reqwest's async client still needs a tokio reactor for its sockets, which
+//! async-std provides with its `tokio1` feature.
+//!
+//! A local mock server stands in for the provider, so the example needs no credentials:
+//!
+//! ```sh
+//! cargo run --example async_std --features async-std-examples
+//! ```
+
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::time::Duration;
+
+use secretary::llm_providers::openai::OpenAILLM;
+use secretary::llm_providers::rate_limit::RetryPolicy;
+use secretary::traits::{AsyncGenerateData, Task};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+#[derive(Task, Serialize, Deserialize, Debug, PartialEq)]
+struct Product {
+    #[task(instruction = "Extract the product name")]
+    pub name: String,
+
+    #[task(instruction = "Extract the price as a float")]
+    pub price: f64,
+}
+
+/// Serves one canned HTTP response per connection and returns the server address.
+fn serve(responses: Vec<(u16, String)>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = format!("http://{}", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        for (status, body) in responses {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request: Vec<u8> = Vec::new();
+            let mut buffer = [0u8; 4096];
+            loop {
+                let read = stream.read(&mut buffer).unwrap();
+                request.extend_from_slice(&buffer[..read]);
+                let text = String::from_utf8_lossy(&request).to_string();
+                if let Some(header_end) = text.find("\r\n\r\n") {
+                    let length: usize = text[..header_end]
+                        .lines()
+                        .find_map(|line| {
+                            line.to_lowercase()
+                                .strip_prefix("content-length:")
+                                .map(|value| value.trim().parse().unwrap())
+                        })
+                        .unwrap_or(0);
+                    if request.len() >= header_end + 4 + length {
+                        break;
+                    }
+                }
+            }
+            write!(
+                stream,
+                "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            )
+            .unwrap();
+        }
+    });
+    address
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    println!("Secretary async-std Example - Product Extraction");
+    println!("{}", "=".repeat(60));
+
+    let completion = json!({
+        "choices": [{"message": {"role": "assistant", "content": r#"{"name": "MacBook Pro", "price": 2499.0}"#}}]
+    })
+    .to_string();
+    // The first request is rate limited, so the retry waits on a timer before the second one
+    let address = serve(vec![(429, "{}".to_string()), (200, completion)]);
+    let llm = OpenAILLM::new(&address, "test-key", "test-model")?
+        .with_retry_policy(RetryPolicy::new(1).with_base_delay(Duration::from_millis(50)));
+
+    let product: Product = async_std::task::block_on(async {
+        llm.async_generate_data(&Product::new(), "MacBook Pro 16-inch - $2,499", vec![])
+            .await
+    })?;
+
+    assert_eq!(
+        product,
+        Product {
+            name: "MacBook Pro".to_string(),
+            price: 2499.0,
+        }
+    );
+    println!("Extracted on async-std: {:#?}", product);
+
+    Ok(())
+}
@@ -0,0 +1,73 @@
+use secretary::llm_providers::openai::OpenAILLM;
+use secretary::traits::{GenerateData, Task};
+use serde::{Deserialize, Serialize};
+
+#[derive(Task, Serialize, Deserialize, Debug)]
+struct ContactInfo {
+    #[task(instruction = "Extract the candidate's full name")]
+    pub name: String,
+
+    #[task(instruction = "Extract the candidate's email address if mentioned")]
+    pub email: Option<String>,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug)]
+struct Experience {
+    #[task(instruction = "Extract the employer's name")]
+    pub employer: String,
+
+    #[task(instruction = "Extract the dates of employment, e.g. 2019 - 2023")]
+    pub dates: String,
+
+    #[task(instruction = "List the responsibilities and achievements of the position")]
+    pub bullets: Vec<String>,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug)]
+struct Education {
+    #[task(instruction = "Extract the school or university")]
+    pub school: String,
+
+    #[task(instruction = "Extract the degree obtained")]
+    pub degree: String,
+}
+
+/// A resume with a nested Task, a list of Tasks and an optional Task
+#[derive(Task, Serialize, Deserialize, Debug)]
+struct Resume {
+    pub contact: ContactInfo,
+
+    #[task(instruction = "Extract every position held, most recent first")]
+    pub experiences: Vec<Experience>,
+
+    #[task(instruction = "Extract the highest education, or null if none is mentioned")]
+    pub education: Option<Education>,
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let text = "Jane Doe - jane.doe@email.com\n\
+                Senior Engineer, Acme Corp (2020 - 2024): led the payments team, \
+                cut checkout latency by 40%.\n\
+                Engineer, Initech (2016 - 2020): built the reporting pipeline, \
+                mentored two interns.\n\
+                BSc in Computer Science, University of Toronto.";
+
+    let llm = OpenAILLM::from_env()?;
+
+    // One request for the whole resume; the model decides how many positions there are
+    let resume: Resume = llm.generate_data(&Resume::new(), text, vec![])?;
+    println!("Generated data: {:#?}", resume);
+
+    // Field by field, one request per field. The list is asked for item by item, so the
+    // template holds as many positions as the resume above
+    let mut template = Resume::new();
+    template.experiences = resume
+        .experiences
+        .iter()
+        .map(|_| Experience::new())
+        .collect();
+    let fields: Resume = llm.fields_generate_data(&template, text, vec![])?;
+    println!("Generated data field by field: {:#?}", fields);
+
+    Ok(())
+}
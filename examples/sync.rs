@@ -39,12 +39,8 @@ fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
     let text =
         "John Smith is a 30-year-old software engineer. You can reach him at john.smith@email.com";
 
-    // Create LLM instance (you would use real API credentials)
-    let llm = OpenAILLM::new(
-        &std::env::var("SECRETARY_OPENAI_API_BASE").unwrap(),
-        &std::env::var("SECRETARY_OPENAI_API_KEY").unwrap(),
-        &std::env::var("SECRETARY_OPENAI_MODEL").unwrap(),
-    )?;
+    // Create LLM instance from the SECRETARY_OPENAI_* environment variables
+    let llm = OpenAILLM::from_env()?;
 
     // Generate structured data using the task
     let result: PersonExtraction = llm.generate_data(&task, text, &additional_instructions)?;
@@ -96,11 +96,8 @@ fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
     // Note: This would require actual API credentials to work
     println!("Setting up sync force LLM call (for reasoning models without JSON mode):");
 
-    let llm = OpenAILLM::new(
-        &std::env::var("SECRETARY_OPENAI_API_BASE").unwrap(),
-        &std::env::var("SECRETARY_OPENAI_API_KEY").unwrap(),
-        &std::env::var("SECRETARY_OPENAI_MODEL").unwrap(), // Could be o1-preview, deepseek-reasoner, etc.
-    )?;
+    // SECRETARY_OPENAI_MODEL could be o1-preview, deepseek-reasoner, etc.
+    let llm = OpenAILLM::from_env()?;
 
     println!("Making sync force request to LLM (bypassing JSON mode requirement)...");
 
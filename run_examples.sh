@@ -6,3 +6,4 @@ cargo run --example async
 cargo run --example async_force
 cargo run --example sync
 cargo run --example sync_force
+cargo run --example async_std --features async-std-examples
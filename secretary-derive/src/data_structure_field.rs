@@ -1,44 +1,119 @@
 use proc_macro::TokenStream;
-use quote::quote;
-use syn::{Data, Field, Fields};
+use quote::{quote, quote_spanned};
+use syn::{Data, Field, Fields, Ident, Index, Lit, Member, Type, spanned::Spanned};
 
 use crate::{
-    field_types::{TaskFieldType, detect_task_field_type},
-    utilities::{convert_to_json_type, get_instruction},
+    default_value::{check_default_value, default_value_requirement},
+    field_attributes::task::TaskFieldAttributes,
+    field_types::{TaskFieldType, detect_task_field_type, get_task_inner_type},
+    generics::is_type_parameter,
+    key_case::KeyCase,
+    one_of::one_of_requirement,
+    ordered::ORDER_REQUIREMENT,
+    output_language::language_requirement,
+    utilities::{
+        convert_to_json_item_kind, convert_to_json_kind, convert_to_json_type, get_lazy_inner_type,
+        get_task_attributes, is_list_type, is_option_type, is_text_type,
+    },
 };
 
 pub struct DataStructureField {
     field: Field,
+    /// The field's ident, or its position in a tuple struct.
+    member: Member,
+    /// The field name, `field_<position>` in tuple structs and empty in newtypes.
     name: String,
     instruction: String,
     json_data_type: String,
     task_field_type: TaskFieldType,
+    attributes: TaskFieldAttributes,
+    /// Whether the JSON shape of the value is unknown: a type parameter or a `plain` field.
+    is_opaque: bool,
+    /// The case of the field's key in the JSON the model sees, the struct's `key_case`.
+    key_case: KeyCase,
 }
 
 impl DataStructureField {
     pub fn new(
         field: Field,
-        name: String,
+        member: Member,
         instruction: String,
         json_data_type: String,
         task_field_type: TaskFieldType,
+        attributes: TaskFieldAttributes,
+        is_opaque: bool,
     ) -> Self {
+        let name: String = match &member {
+            Member::Named(ident) => ident.to_string(),
+            Member::Unnamed(index) => format!("field_{}", index.index),
+        };
+
         Self {
             field,
+            member,
             name,
             instruction,
             json_data_type,
             task_field_type,
+            attributes,
+            is_opaque,
+            key_case: KeyCase::Snake,
         }
     }
 
+    /// Presents the field's key in `key_case` instead of the field name's snake case.
+    pub fn with_key_case(mut self, key_case: KeyCase) -> Self {
+        self.key_case = key_case;
+        self
+    }
+
+    /// Makes this the only field of a newtype, which serde serializes as the field's value
+    /// itself, so the field has no name of its own.
+    pub fn transparent(mut self) -> Self {
+        self.name.clear();
+        self
+    }
+
     pub fn get_field_prompt(&self) -> String {
         format!(
             "{}: {}, {}\n",
-            self.name, self.instruction, self.json_data_type
+            self.get_field_label(),
+            self.instruction,
+            self.json_data_type
         )
     }
 
+    /// Returns the name prompts show for this field: its key in the struct's `key_case`, or
+    /// `value` for the field of a newtype.
+    pub fn get_field_label(&self) -> String {
+        if self.name.is_empty() {
+            "value".to_string()
+        } else if self.key_case == KeyCase::Snake {
+            self.name.clone()
+        } else {
+            self.key_case.apply(&self.name)
+        }
+    }
+
+    /// Returns the expression accessing this field on `self`, e.g. `name` or `0`.
+    pub fn get_member(&self) -> &Member {
+        &self.member
+    }
+
+    /// Generates the place of this field in the serialized JSON `template`: a key of an
+    /// object, a position of a tuple struct's array, or the whole value of a newtype.
+    pub fn get_template_slot(&self) -> proc_macro2::TokenStream {
+        let name: &str = &self.name;
+        match &self.member {
+            Member::Named(_) => quote! { template[#name] },
+            Member::Unnamed(_) if name.is_empty() => quote! { template },
+            Member::Unnamed(index) => {
+                let position: usize = index.index as usize;
+                quote! { template[#position] }
+            }
+        }
+    }
+
     pub fn get_task_field_type(&self) -> &TaskFieldType {
         &self.task_field_type
     }
@@ -46,30 +121,462 @@ impl DataStructureField {
     pub fn get_field_name(&self) -> &str {
         &self.name
     }
+
+    pub fn get_field_type(&self) -> &Type {
+        &self.field.ty
+    }
+
+    /// Generates the `secretary::schema::Importance` of this field, `Normal` when unset.
+    fn get_importance(&self) -> proc_macro2::TokenStream {
+        match self.attributes.importance.as_deref() {
+            Some("critical") => quote! { ::secretary::schema::Importance::Critical },
+            Some("low") => quote! { ::secretary::schema::Importance::Low },
+            _ => quote! { ::secretary::schema::Importance::Normal },
+        }
+    }
+
+    /// Returns the `instruction` literal as written on the field, without the requirements
+    /// the derive appends to it.
+    pub fn get_instruction_literal(&self) -> Option<&Lit> {
+        self.attributes.instruction_literal.as_ref()
+    }
+
+    /// Returns the length of the instruction, `0` for nested Tasks without one.
+    pub fn get_instruction_len(&self) -> usize {
+        self.instruction.chars().count()
+    }
+
+    /// Returns the characters this field contributes to the system prompt at compile time:
+    /// its field line, unless it is a nested Task, and its negative example lines.
+    pub fn get_static_prompt_len(&self) -> usize {
+        // Lazy fields are left out of the prompt
+        if self.is_lazy() {
+            return 0;
+        }
+        let field_line: usize = match self.task_field_type {
+            TaskFieldType::DirectTask => 0,
+            _ => self.get_field_prompt().chars().count(),
+        };
+        let negative_examples: usize = self
+            .attributes
+            .negative_examples
+            .iter()
+            .map(|(output, reason)| {
+                format!(
+                    "- {}: WRONG output: {}, because {}\n",
+                    self.get_field_label(),
+                    output,
+                    reason
+                )
+                .chars()
+                .count()
+            })
+            .sum();
+
+        field_line + negative_examples
+    }
+
+    /// Returns whether the field has negative examples.
+    pub fn has_negative_examples(&self) -> bool {
+        !self.attributes.negative_examples.is_empty()
+    }
+
+    /// Returns whether the field is a `Lazy` field, extracted on first access.
+    pub fn is_lazy(&self) -> bool {
+        self.attributes.lazy
+    }
+
+    /// Returns whether the field is serialized as a JSON array, e.g. a `Vec` or `HashSet`.
+    pub fn is_collection(&self) -> bool {
+        !self.is_opaque && convert_to_json_type(&self.field.ty).contains("JSON Array")
+    }
+
+    /// Generates the `Vec<secretary::schema::NegativeExample>` of this field.
+    fn get_negative_examples(&self) -> proc_macro2::TokenStream {
+        let examples = self
+            .attributes
+            .negative_examples
+            .iter()
+            .map(|(output, reason)| {
+                quote! {
+                    ::secretary::schema::NegativeExample::new(#output, #reason)
+                }
+            });
+
+        quote! { vec![#(#examples),*] }
+    }
+
+    /// Generates the request settings fields of a `secretary::distributed::FieldPrompt`
+    /// for this field, e.g. `temperature: Some(0.7), extra_instruction: None, always_refresh: false`.
+    pub fn get_distributed_settings(&self) -> proc_macro2::TokenStream {
+        let temperature: proc_macro2::TokenStream = match self.attributes.temperature {
+            Some(temperature) => quote! { Some(#temperature) },
+            None => quote! { None },
+        };
+        let extra_instruction: proc_macro2::TokenStream = match &self.attributes.extra_instruction {
+            Some(extra_instruction) => quote! { Some(#extra_instruction.to_string()) },
+            None => quote! { None },
+        };
+        let always_refresh: bool = self.attributes.always_refresh;
+        let importance: proc_macro2::TokenStream = self.get_importance();
+        let group: proc_macro2::TokenStream = match &self.attributes.group {
+            Some((group, _)) => quote! { Some(#group.to_string()) },
+            None => quote! { None },
+        };
+
+        let key_case: proc_macro2::TokenStream = self.key_case.to_tokens();
+
+        quote! {
+            temperature: #temperature,
+            extra_instruction: #extra_instruction,
+            always_refresh: #always_refresh,
+            importance: #importance,
+            group: #group,
+            key_case: #key_case,
+        }
+    }
+
+    /// Generates the `Option<secretary::extractors::LocalExtraction>` of this field.
+    fn get_local_extraction(&self) -> proc_macro2::TokenStream {
+        let Some(extractor) = &self.attributes.extractor else {
+            return quote! { None };
+        };
+        let extractor: proc_macro2::TokenStream = match extractor.strip_prefix("regex:") {
+            Some(pattern) => {
+                quote! { ::secretary::extractors::Extractor::Regex(#pattern.to_string()) }
+            }
+            None => match extractor.as_str() {
+                "email" => quote! { ::secretary::extractors::Extractor::Email },
+                "url" => quote! { ::secretary::extractors::Extractor::Url },
+                _ => quote! { ::secretary::extractors::Extractor::Phone },
+            },
+        };
+        let ambiguous: proc_macro2::TokenStream = match self.attributes.ambiguous.as_deref() {
+            Some("first") => quote! { ::secretary::extractors::AmbiguityPolicy::First },
+            Some("error") => quote! { ::secretary::extractors::AmbiguityPolicy::Error },
+            _ => quote! { ::secretary::extractors::AmbiguityPolicy::Llm },
+        };
+
+        quote! {
+            Some(::secretary::extractors::LocalExtraction {
+                extractor: #extractor,
+                ambiguous: #ambiguous,
+            })
+        }
+    }
+
+    /// Generates a check that the nested Task type of this field implements `Task`, spanned
+    /// at the type so that a missing implementation is reported at the field. `None` for
+    /// fields without a nested Task.
+    ///
+    /// The check calls `assert_task`, which the caller defines next to it.
+    pub fn get_task_assertion(&self) -> Option<proc_macro2::TokenStream> {
+        let inner_type: &Type = get_task_inner_type(&self.field.ty, &self.task_field_type)?;
+
+        Some(quote_spanned! {inner_type.span()=>
+            assert_task::<#inner_type>();
+        })
+    }
+
+    /// Generates the mismatches between the prompt and serde's representation of the nested
+    /// Task of this field, with their paths under the field's, e.g. `address.city` or
+    /// `items[].price`. `None` for fields without a nested Task.
+    pub fn get_nested_schema_check(&self) -> Option<proc_macro2::TokenStream> {
+        let inner_type: &Type = get_task_inner_type(&self.field.ty, &self.task_field_type)?;
+        let prefix: String = match self.task_field_type {
+            TaskFieldType::VecTask | TaskFieldType::HashMapTask | TaskFieldType::BTreeMapTask => {
+                format!("{}[]", self.name)
+            }
+            _ => self.name.clone(),
+        };
+
+        // Spanned at the type, so a type without a Task impl is reported at the field only
+        Some(quote_spanned! {inner_type.span()=>
+            mismatches.extend(::secretary::schema_check::prefixed(
+                #prefix,
+                <#inner_type as Task>::verify_schema_consistency(),
+            ));
+        })
+    }
+
+    /// Generates a `secretary::schema::FieldDescriptor` expression describing this field.
+    pub fn get_field_descriptor(&self) -> proc_macro2::TokenStream {
+        let field_type: &syn::Type = &self.field.ty;
+        let name: &str = &self.name;
+        let rust_type: String = quote!(#field_type).to_string().replace(' ', "");
+        let json_type: proc_macro2::TokenStream = if self.is_opaque {
+            quote! { ::secretary::schema::JsonType::Any }
+        } else {
+            convert_to_json_kind(field_type)
+        };
+        let item_type: proc_macro2::TokenStream = convert_to_json_item_kind(field_type);
+        // An unevaluated lazy field serializes as null
+        let optional: bool = is_option_type(field_type) || self.attributes.lazy;
+        let instruction: &str = &self.instruction;
+        let importance: proc_macro2::TokenStream = self.get_importance();
+        let negative_examples: proc_macro2::TokenStream = self.get_negative_examples();
+        let local_extraction: proc_macro2::TokenStream = self.get_local_extraction();
+        let output_language: proc_macro2::TokenStream = match &self.attributes.output_language {
+            Some(language) => quote! { Some(#language.to_string()) },
+            None => quote! { None },
+        };
+        let default_value: proc_macro2::TokenStream = match &self.attributes.default_value {
+            Some((value, _)) => {
+                let json: String = value.to_string();
+                quote! { serde_json::from_str::<serde_json::Value>(#json).ok() }
+            }
+            None => quote! { None },
+        };
+        let one_of: &Vec<String> = &self.attributes.one_of;
+        let ordered: bool = self.attributes.ordered;
+        let lazy: bool = self.attributes.lazy;
+        let date: bool = self.attributes.date;
+        let key_case: proc_macro2::TokenStream = self.key_case.to_tokens();
+
+        let kind: proc_macro2::TokenStream = match self.task_field_type {
+            TaskFieldType::Normal => quote! { Normal },
+            TaskFieldType::DirectTask => quote! { Task },
+            TaskFieldType::VecTask => quote! { VecTask },
+            TaskFieldType::OptionTask => quote! { OptionTask },
+            TaskFieldType::HashMapTask => quote! { HashMapTask },
+            TaskFieldType::BTreeMapTask => quote! { BTreeMapTask },
+        };
+
+        // Nested newtypes and tuple structs are serialized as their value and as arrays
+        let (children, representation): (proc_macro2::TokenStream, proc_macro2::TokenStream) =
+            match get_task_inner_type(field_type, &self.task_field_type) {
+                Some(inner_type) => (
+                    quote! { <#inner_type as Task>::field_descriptors() },
+                    quote! { .resolve_tuple_structs() },
+                ),
+                None => (quote! { Vec::new() }, proc_macro2::TokenStream::new()),
+            };
+
+        quote! {
+            ::secretary::schema::FieldDescriptor {
+                name: #name.to_string(),
+                rust_type: #rust_type.to_string(),
+                json_type: #json_type,
+                item_type: #item_type,
+                optional: #optional,
+                kind: ::secretary::schema::FieldKind::#kind,
+                instruction: #instruction.to_string(),
+                importance: #importance,
+                negative_examples: #negative_examples,
+                local_extraction: #local_extraction,
+                output_language: #output_language,
+                default_value: #default_value,
+                one_of: vec![#(#one_of.to_string()),*],
+                ordered: #ordered,
+                lazy: #lazy,
+                date: #date,
+                key_case: #key_case,
+                normalize_strings: None,
+                children: #children,
+            }
+            #representation
+        }
+    }
 }
 
-pub fn get_data_structure_fields(data: &Data) -> Result<Vec<DataStructureField>, TokenStream> {
+/// Collects the fields of a struct.
+///
+/// A field whose type is one of the struct's `type_parameters` is a nested Task when it has
+/// no instruction, like any other nested Task field, and a plain value when it has one.
+/// Collections of a type parameter are plain values. So is any field marked
+/// `#[task(plain)]`, which needs an instruction like other plain values.
+///
+/// The fields of a tuple struct are named by position, `field_0`, `field_1` and so on, and
+/// each needs an instruction, since a position says nothing about its content. The field of
+/// a newtype is transparent, like serde's representation of it, and follows the rules of a
+/// named field.
+///
+/// Text fields without an `output_language` take `default_output_language`, the struct's, and
+/// the language requirement is appended to the instruction of every field that has one.
+///
+/// Named fields take `key_case`, the struct's; positional fields have no key to case.
+pub fn get_data_structure_fields(
+    data: &Data,
+    type_parameters: &[Ident],
+    default_output_language: Option<&str>,
+    key_case: KeyCase,
+) -> Result<Vec<DataStructureField>, TokenStream> {
     match data {
         Data::Struct(content) => {
-            let named_fields: syn::punctuated::Punctuated<Field, syn::token::Comma> =
-                match &content.fields {
-                    Fields::Named(fields) => fields.named.to_owned(),
-                    _ => {
+            let fields: syn::punctuated::Punctuated<Field, syn::token::Comma> = match &content
+                .fields
+            {
+                Fields::Named(fields) => fields.named.to_owned(),
+                Fields::Unnamed(fields) => fields.unnamed.to_owned(),
+                Fields::Unit => {
+                    let error: syn::Error = syn::Error::new_spanned(
+                        content.struct_token,
+                        "Unit structs have no fields to extract; add a field or derive Task on the struct that contains this one",
+                    );
+                    return Err(TokenStream::from(error.to_compile_error()));
+                }
+            };
+            let is_newtype: bool =
+                matches!(content.fields, Fields::Unnamed(_)) && fields.len() == 1;
+            let is_positional: bool = matches!(content.fields, Fields::Unnamed(_)) && !is_newtype;
+
+            let mut data_structure_fields = Vec::new();
+
+            for (position, field) in fields.iter().enumerate() {
+                let mut attributes: TaskFieldAttributes = match get_task_attributes(field) {
+                    Ok(attributes) => attributes,
+                    Err(error) => return Err(TokenStream::from(error.to_compile_error())),
+                };
+
+                // A lazy field is described by the type of its value
+                let field: Field = match (get_lazy_inner_type(&field.ty), attributes.lazy) {
+                    (Some(inner_type), true) => Field {
+                        ty: inner_type.clone(),
+                        ..field.clone()
+                    },
+                    (None, false) => field.clone(),
+                    (None, true) => {
+                        let error: syn::Error = syn::Error::new_spanned(
+                            &field.ty,
+                            "lazy can only be used on Lazy<T> fields",
+                        );
+                        return Err(TokenStream::from(error.to_compile_error()));
+                    }
+                    (Some(_), false) => {
                         let error: syn::Error = syn::Error::new_spanned(
-                            &content.struct_token,
-                            "Unnamed fields are not supported",
+                            &field.ty,
+                            "Lazy fields need #[task(lazy, instruction = \"...\")]",
                         );
                         return Err(TokenStream::from(error.to_compile_error()));
                     }
                 };
+                let field: &Field = &field;
 
-            let mut data_structure_fields = Vec::new();
+                let mut json_data_type: String = convert_to_json_type(&field.ty);
+                let mut task_field_type: TaskFieldType = detect_task_field_type(&field.ty);
+                let field_is_type_parameter: bool = is_type_parameter(&field.ty, type_parameters);
+
+                if field_is_type_parameter {
+                    if attributes.instruction.is_some() {
+                        task_field_type = TaskFieldType::Normal;
+                        json_data_type = "JSON Value".to_string();
+                    }
+                } else if let Some(inner_type) = get_task_inner_type(&field.ty, &task_field_type)
+                    && is_type_parameter(inner_type, type_parameters)
+                {
+                    task_field_type = TaskFieldType::Normal;
+                }
+
+                // A plain field is a value described by its instruction, whatever its type
+                if attributes.plain {
+                    task_field_type = TaskFieldType::Normal;
+                    json_data_type = "JSON Value".to_string();
+                }
 
-            for field in named_fields.iter() {
-                let json_data_type: String = convert_to_json_type(&field.ty);
-                let task_field_type: TaskFieldType = detect_task_field_type(&field.ty);
+                // A nested Task's fields are grouped within its own struct
+                if let Some((_, literal)) = &attributes.group
+                    && task_field_type != TaskFieldType::Normal
+                {
+                    let error: syn::Error = syn::Error::new_spanned(
+                        literal,
+                        "group can only be used on fields that are not nested Tasks; group the fields inside the nested Task instead",
+                    );
+                    return Err(TokenStream::from(error.to_compile_error()));
+                }
 
-                // Extract task attribute to get instruction (only required for non-DirectTask fields)
+                // Local extractors find text, so they only fill text fields
+                if attributes.extractor.is_some() {
+                    let field_type: &Type = &field.ty;
+                    let rust_type: String = quote!(#field_type).to_string().replace(' ', "");
+                    if !matches!(rust_type.as_str(), "String" | "Option<String>") {
+                        let error: syn::Error = syn::Error::new_spanned(
+                            &field.ty,
+                            "extractor can only be used on String and Option<String> fields",
+                        );
+                        return Err(TokenStream::from(error.to_compile_error()));
+                    }
+                }
+
+                // Only a single text value can be limited to a set of values
+                if !attributes.one_of.is_empty() {
+                    let field_type: &Type = &field.ty;
+                    let rust_type: String = quote!(#field_type).to_string().replace(' ', "");
+                    if !matches!(rust_type.as_str(), "String" | "Option<String>") {
+                        let error: syn::Error = syn::Error::new_spanned(
+                            &field.ty,
+                            "one_of can only be used on String and Option<String> fields",
+                        );
+                        return Err(TokenStream::from(error.to_compile_error()));
+                    }
+                }
+
+                // A lazy field is one value, extracted by its own request
+                if attributes.lazy && task_field_type != TaskFieldType::Normal {
+                    let error: syn::Error = syn::Error::new_spanned(
+                        &field.ty,
+                        "lazy can only be used on fields that are not nested Tasks",
+                    );
+                    return Err(TokenStream::from(error.to_compile_error()));
+                }
+                if attributes.lazy && attributes.group.is_some() {
+                    let error: syn::Error = syn::Error::new_spanned(
+                        &field.ty,
+                        "lazy fields are extracted on their own and cannot be grouped",
+                    );
+                    return Err(TokenStream::from(error.to_compile_error()));
+                }
+
+                // Only lists have an order to keep
+                if attributes.ordered && !is_list_type(&field.ty) {
+                    let error: syn::Error = syn::Error::new_spanned(
+                        &field.ty,
+                        "ordered can only be used on Vec fields",
+                    );
+                    return Err(TokenStream::from(error.to_compile_error()));
+                }
+
+                // Only text is read as a date
+                if attributes.date && !is_text_type(&field.ty) {
+                    let error: syn::Error = syn::Error::new_spanned(
+                        &field.ty,
+                        "date can only be used on String fields and collections of them",
+                    );
+                    return Err(TokenStream::from(error.to_compile_error()));
+                }
+
+                // Only text can be written in a language
+                if attributes.output_language.is_some() && !is_text_type(&field.ty) {
+                    let error: syn::Error = syn::Error::new_spanned(
+                        &field.ty,
+                        "output_language can only be used on String fields and collections of them",
+                    );
+                    return Err(TokenStream::from(error.to_compile_error()));
+                }
+                if attributes.output_language.is_none() && is_text_type(&field.ty) {
+                    attributes.output_language = default_output_language.map(str::to_string);
+                }
+
+                // The default must deserialize into the field, as far as the type shows
+                if let Some((value, literal)) = &attributes.default_value
+                    && let Err(mismatch) = check_default_value(&field.ty, value)
+                {
+                    let error: syn::Error = syn::Error::new_spanned(
+                        literal,
+                        format!("default_value does not fit the field: {}", mismatch),
+                    );
+                    return Err(TokenStream::from(error.to_compile_error()));
+                }
+
+                // Positional fields need an instruction whatever their type
+                if is_positional && attributes.instruction.is_none() {
+                    let error: syn::Error = syn::Error::new_spanned(
+                        field,
+                        "Missing required #[task(instruction = \"...\")] attribute: the fields of a tuple struct are only named by position",
+                    );
+                    return Err(TokenStream::from(error.to_compile_error()));
+                }
+
+                // Instructions are only required for non-DirectTask fields
                 let instruction: String = match task_field_type {
                     TaskFieldType::DirectTask => {
                         // DirectTask fields don't need instruction attributes
@@ -77,11 +584,11 @@ pub fn get_data_structure_fields(data: &Data) -> Result<Vec<DataStructureField>,
                     }
                     _ => {
                         // All other field types require instruction attributes
-                        match get_instruction(&field) {
-                            Some(result) => result,
+                        match &attributes.instruction {
+                            Some(result) => result.clone(),
                             None => {
                                 let error: syn::Error = syn::Error::new_spanned(
-                                    &field,
+                                    field,
                                     "Missing required #[task(instruction = \"...\")] attribute",
                                 );
                                 return Err(TokenStream::from(error.to_compile_error()));
@@ -90,34 +597,66 @@ pub fn get_data_structure_fields(data: &Data) -> Result<Vec<DataStructureField>,
                     }
                 };
 
-                let name: String = match &field.ident {
-                    Some(ident) => ident.to_string(),
-                    None => {
-                        let error =
-                            syn::Error::new_spanned(&field, "Unnamed fields are not supported");
-                        return Err(TokenStream::from(error.to_compile_error()));
+                let instruction: String = match &attributes.output_language {
+                    Some(language) => {
+                        format!("{} {}", instruction, language_requirement(language))
+                    }
+                    None => instruction,
+                };
+                let instruction: String = if attributes.one_of.is_empty() {
+                    instruction
+                } else {
+                    format!("{} {}", instruction, one_of_requirement(&attributes.one_of))
+                };
+                let instruction: String = if attributes.ordered {
+                    format!("{} {}", instruction, ORDER_REQUIREMENT)
+                } else {
+                    instruction
+                };
+                let instruction: String = match &attributes.default_value {
+                    Some((value, _)) => {
+                        format!("{} {}", instruction, default_value_requirement(value))
+                            .trim_start()
+                            .to_string()
                     }
+                    None => instruction,
+                };
+
+                let member: Member = match &field.ident {
+                    Some(ident) => Member::Named(ident.clone()),
+                    None => Member::Unnamed(Index::from(position)),
                 };
 
-                data_structure_fields.push(DataStructureField::new(
+                let is_opaque: bool = attributes.plain || field_is_type_parameter;
+                let data_structure_field = DataStructureField::new(
                     field.clone(),
-                    name,
+                    member,
                     instruction,
                     json_data_type,
                     task_field_type,
-                ));
+                    attributes,
+                    is_opaque,
+                );
+                let data_structure_field = match data_structure_field.get_member() {
+                    Member::Named(_) => data_structure_field.with_key_case(key_case),
+                    Member::Unnamed(_) => data_structure_field,
+                };
+                data_structure_fields.push(if is_newtype {
+                    data_structure_field.transparent()
+                } else {
+                    data_structure_field
+                });
             }
 
             Ok(data_structure_fields)
         }
         Data::Enum(enum_data) => {
             let error =
-                syn::Error::new_spanned(&enum_data.enum_token, "Enums are not supported yet");
+                syn::Error::new_spanned(enum_data.enum_token, "Enums are not supported yet");
             Err(TokenStream::from(error.to_compile_error()))
         }
         Data::Union(union_data) => {
-            let error =
-                syn::Error::new_spanned(&union_data.union_token, "Unions are not supported");
+            let error = syn::Error::new_spanned(union_data.union_token, "Unions are not supported");
             Err(TokenStream::from(error.to_compile_error()))
         }
     }
@@ -0,0 +1,154 @@
+use serde_json::Value;
+use syn::{GenericArgument, Lit, PathArguments, Type};
+
+/// Reads a `default_value`, a string literal holding JSON.
+pub fn parse_default_value(value: &Lit) -> syn::Result<Value> {
+    let text: String = match value {
+        Lit::Str(text) => text.value(),
+        _ => return Err(syn::Error::new_spanned(value, "Expected a string literal")),
+    };
+
+    serde_json::from_str(&text).map_err(|error| {
+        syn::Error::new_spanned(
+            value,
+            format!(
+                "default_value must be JSON, e.g. \"0\" or \"\\\"unknown\\\"\": {}",
+                error
+            ),
+        )
+    })
+}
+
+/// Returns the sentence appended to the instruction of a field with a `default_value`.
+pub fn default_value_requirement(value: &Value) -> String {
+    format!(
+        "If the text does not give this information, output exactly {}.",
+        value
+    )
+}
+
+/// Checks that a `default_value` deserializes into `rust_type`, describing the mismatch
+/// otherwise.
+///
+/// Types the derive cannot see into, such as nested Tasks and type parameters, accept any
+/// value; they are checked by serde when the default is used.
+pub fn check_default_value(rust_type: &Type, value: &Value) -> Result<(), String> {
+    match rust_type {
+        Type::Reference(reference) => check_default_value(&reference.elem, value),
+        Type::Array(array) => check_items(&array.elem, value, "an array"),
+        Type::Slice(slice) => check_items(&slice.elem, value, "an array"),
+        Type::Tuple(tuple) => match value {
+            Value::Array(items) if items.len() == tuple.elems.len() => tuple
+                .elems
+                .iter()
+                .zip(items)
+                .try_for_each(|(item_type, item)| check_default_value(item_type, item)),
+            _ => Err(found(
+                value,
+                &format!("an array of {} values for a tuple", tuple.elems.len()),
+            )),
+        },
+        Type::Path(path) => {
+            let Some(segment) = path.path.segments.last() else {
+                return Ok(());
+            };
+            let type_name: String = segment.ident.to_string();
+            let arguments: Vec<&Type> = match &segment.arguments {
+                PathArguments::AngleBracketed(arguments) => arguments
+                    .args
+                    .iter()
+                    .filter_map(|argument| match argument {
+                        GenericArgument::Type(argument) => Some(argument),
+                        _ => None,
+                    })
+                    .collect(),
+                _ => Vec::new(),
+            };
+
+            match (type_name.as_str(), arguments.as_slice()) {
+                ("Option", [inner_type]) => match value {
+                    Value::Null => Ok(()),
+                    _ => check_default_value(inner_type, value),
+                },
+                ("Vec" | "HashSet" | "BTreeSet", [item_type]) => {
+                    check_items(item_type, value, &format!("an array for `{}`", type_name))
+                }
+                ("HashMap" | "BTreeMap", [_, value_type]) => match value {
+                    Value::Object(entries) => entries
+                        .values()
+                        .try_for_each(|entry| check_default_value(value_type, entry)),
+                    _ => Err(found(value, &format!("an object for `{}`", type_name))),
+                },
+                ("String", []) => expect(value, value.is_string(), "a string for `String`"),
+                ("char", []) => expect(
+                    value,
+                    value.as_str().is_some_and(|text| text.chars().count() == 1),
+                    "a string of one character for `char`",
+                ),
+                ("bool", []) => expect(value, value.is_boolean(), "a boolean for `bool`"),
+                ("f32" | "f64", []) => expect(
+                    value,
+                    value.is_number(),
+                    &format!("a number for `{}`", type_name),
+                ),
+                (integer, []) => match integer_range(integer) {
+                    Some((min, max)) => {
+                        let fits: bool = value
+                            .as_i64()
+                            .map(i128::from)
+                            .or_else(|| value.as_u64().map(i128::from))
+                            .is_some_and(|number| (min..=max).contains(&number));
+                        expect(
+                            value,
+                            fits,
+                            &format!("an integer between {} and {} for `{}`", min, max, integer),
+                        )
+                    }
+                    None => Ok(()),
+                },
+                _ => Ok(()),
+            }
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Checks that `value` is an array whose items deserialize into `item_type`.
+fn check_items(item_type: &Type, value: &Value, expected: &str) -> Result<(), String> {
+    match value {
+        Value::Array(items) => items
+            .iter()
+            .try_for_each(|item| check_default_value(item_type, item)),
+        _ => Err(found(value, expected)),
+    }
+}
+
+/// Describes what was expected and the value found instead, unless `condition` holds.
+fn expect(value: &Value, condition: bool, expected: &str) -> Result<(), String> {
+    if condition {
+        Ok(())
+    } else {
+        Err(found(value, expected))
+    }
+}
+
+fn found(value: &Value, expected: &str) -> String {
+    format!("expected {}, found {}", expected, value)
+}
+
+/// Returns the smallest and largest value of an integer type, `None` for other types.
+fn integer_range(type_name: &str) -> Option<(i128, i128)> {
+    let range: (i128, i128) = match type_name {
+        "i8" => (i8::MIN.into(), i8::MAX.into()),
+        "i16" => (i16::MIN.into(), i16::MAX.into()),
+        "i32" => (i32::MIN.into(), i32::MAX.into()),
+        "i64" | "isize" => (i64::MIN.into(), i64::MAX.into()),
+        "u8" => (0, u8::MAX.into()),
+        "u16" => (0, u16::MAX.into()),
+        "u32" => (0, u32::MAX.into()),
+        "u64" | "usize" => (0, u64::MAX.into()),
+        _ => return None,
+    };
+
+    Some(range)
+}
@@ -1,20 +1,155 @@
-use syn::{Ident, LitStr, Token, parse::Parse};
+use serde_json::Value;
+use syn::{Ident, Lit, Token, parse::Parse};
 
+use crate::{
+    default_value::parse_default_value, one_of::parse_one_of,
+    output_language::parse_output_language,
+};
+
+#[derive(Default)]
 pub struct TaskFieldAttributes {
     pub instruction: Option<String>,
+    /// The literal of `instruction`, for error spans.
+    pub instruction_literal: Option<Lit>,
+    pub temperature: Option<f64>,
+    pub extra_instruction: Option<String>,
+    pub always_refresh: bool,
+    /// Whether a field of a custom type is a value described by its instruction rather than
+    /// a nested Task.
+    pub plain: bool,
+    pub importance: Option<String>,
+    /// `(output, reason)` pairs from `negative_example` and the `negative_reason` after it.
+    pub negative_examples: Vec<(String, String)>,
+    /// The local extractor, `email`, `url`, `phone` or `regex:<pattern>`.
+    pub extractor: Option<String>,
+    /// What to do when the extractor finds several values: `llm`, `first` or `error`.
+    pub ambiguous: Option<String>,
+    /// The language of the value, `source` or an ISO 639-1 code. Text fields without one
+    /// take the struct's.
+    pub output_language: Option<String>,
+    /// The group of fields extracted by one request in distributed mode, with its literal
+    /// for error spans.
+    pub group: Option<(String, Lit)>,
+    /// The JSON value used when the model finds nothing for the field, with its literal for
+    /// error spans.
+    pub default_value: Option<(Value, Lit)>,
+    /// The values a text field is limited to, from `one_of = "high, mid, low"`.
+    pub one_of: Vec<String>,
+    /// Whether the items of a `Vec` keep their order of appearance in the target, from
+    /// `ordered`.
+    pub ordered: bool,
+    /// Whether a `Lazy` field is extracted on first access instead of with the rest of the
+    /// struct, from `lazy`.
+    pub lazy: bool,
+    /// Whether a text field holds a date, which a locale reads in its date order, from
+    /// `date`.
+    pub date: bool,
 }
 
 impl Parse for TaskFieldAttributes {
     fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
-        let mut instruction: Option<String> = None;
+        let mut attributes: TaskFieldAttributes = TaskFieldAttributes::default();
+        // A `negative_example` waiting for its `negative_reason`
+        let mut pending_example: Option<(String, Lit)> = None;
+        let mut ambiguous_value: Option<Lit> = None;
 
         while !input.is_empty() {
             let name: Ident = input.parse()?;
+
+            // Flags such as `always_refresh` take no value
+            if !input.peek(Token![=]) {
+                match name.to_string().as_str() {
+                    "always_refresh" => attributes.always_refresh = true,
+                    "plain" => attributes.plain = true,
+                    "ordered" => attributes.ordered = true,
+                    "lazy" => attributes.lazy = true,
+                    "date" => attributes.date = true,
+                    _ => return Err(syn::Error::new(name.span(), "Unknown attribute parameter")),
+                }
+
+                if !input.is_empty() {
+                    input.parse::<Token![,]>()?;
+                }
+                continue;
+            }
+
             input.parse::<Token![=]>()?;
-            let value: LitStr = input.parse()?;
+            let value: Lit = input.parse()?;
 
             match name.to_string().as_str() {
-                "instruction" => instruction = Some(value.value()),
+                "instruction" => {
+                    attributes.instruction = Some(parse_string(&value)?);
+                    attributes.instruction_literal = Some(value);
+                }
+                "extra_instruction" => attributes.extra_instruction = Some(parse_string(&value)?),
+                "group" => {
+                    let group: String = parse_string(&value)?;
+                    if group.is_empty() {
+                        return Err(syn::Error::new_spanned(value, "group must not be empty"));
+                    }
+                    attributes.group = Some((group, value));
+                }
+                "negative_example" => {
+                    if let Some((_, example)) = pending_example {
+                        return Err(missing_reason(&example));
+                    }
+                    pending_example = Some((parse_string(&value)?, value));
+                }
+                "negative_reason" => {
+                    let reason: String = parse_string(&value)?;
+                    match pending_example.take() {
+                        Some((output, _)) => attributes.negative_examples.push((output, reason)),
+                        None => {
+                            return Err(syn::Error::new_spanned(
+                                value,
+                                "negative_reason must follow a negative_example",
+                            ));
+                        }
+                    }
+                }
+                "extractor" => {
+                    let extractor: String = parse_string(&value)?;
+                    check_extractor(&extractor, &value)?;
+                    attributes.extractor = Some(extractor);
+                }
+                "ambiguous" => {
+                    let ambiguous: String = parse_string(&value)?;
+                    if !matches!(ambiguous.as_str(), "llm" | "first" | "error") {
+                        return Err(syn::Error::new_spanned(
+                            value,
+                            "ambiguous must be \"llm\", \"first\" or \"error\"",
+                        ));
+                    }
+                    attributes.ambiguous = Some(ambiguous);
+                    ambiguous_value = Some(value);
+                }
+                "output_language" => {
+                    attributes.output_language = Some(parse_output_language(&value)?)
+                }
+                "default_value" => {
+                    attributes.default_value = Some((parse_default_value(&value)?, value))
+                }
+                "one_of" => attributes.one_of = parse_one_of(&value)?,
+                "importance" => {
+                    let importance: String = parse_string(&value)?;
+                    if !matches!(importance.as_str(), "critical" | "normal" | "low") {
+                        return Err(syn::Error::new_spanned(
+                            value,
+                            "importance must be \"critical\", \"normal\" or \"low\"",
+                        ));
+                    }
+                    attributes.importance = Some(importance);
+                }
+                "temperature" => {
+                    let temperature: f64 = parse_number(&value)?;
+                    if !(0.0..=2.0).contains(&temperature) {
+                        return Err(syn::Error::new_spanned(
+                            value,
+                            "temperature must be between 0 and 2",
+                        ));
+                    }
+                    attributes.temperature = Some(temperature);
+                }
                 _ => return Err(syn::Error::new(name.span(), "Unknown attribute parameter")),
             }
 
@@ -23,6 +158,60 @@ impl Parse for TaskFieldAttributes {
             }
         }
 
-        Ok(TaskFieldAttributes { instruction })
+        if let Some((_, example)) = pending_example {
+            return Err(missing_reason(&example));
+        }
+        if let Some(ambiguous) = ambiguous_value
+            && attributes.extractor.is_none()
+        {
+            return Err(syn::Error::new_spanned(
+                ambiguous,
+                "ambiguous requires an extractor",
+            ));
+        }
+
+        Ok(attributes)
+    }
+}
+
+fn missing_reason(example: &Lit) -> syn::Error {
+    syn::Error::new_spanned(
+        example,
+        "negative_example requires a negative_reason after it",
+    )
+}
+
+/// Checks an extractor name, compiling the pattern of a `regex:` extractor.
+fn check_extractor(extractor: &str, value: &Lit) -> syn::Result<()> {
+    match extractor {
+        "email" | "url" | "phone" => Ok(()),
+        _ => match extractor.strip_prefix("regex:") {
+            Some("") => Err(syn::Error::new_spanned(
+                value,
+                "the regex extractor needs a pattern after `regex:`",
+            )),
+            Some(pattern) => regex::Regex::new(pattern).map(|_| ()).map_err(|error| {
+                syn::Error::new_spanned(value, format!("invalid regex: {}", error))
+            }),
+            None => Err(syn::Error::new_spanned(
+                value,
+                "extractor must be \"email\", \"url\", \"phone\" or \"regex:<pattern>\"",
+            )),
+        },
+    }
+}
+
+fn parse_string(value: &Lit) -> syn::Result<String> {
+    match value {
+        Lit::Str(value) => Ok(value.value()),
+        _ => Err(syn::Error::new_spanned(value, "Expected a string literal")),
+    }
+}
+
+fn parse_number(value: &Lit) -> syn::Result<f64> {
+    match value {
+        Lit::Float(value) => value.base10_parse::<f64>(),
+        Lit::Int(value) => value.base10_parse::<f64>(),
+        _ => Err(syn::Error::new_spanned(value, "Expected a number")),
     }
 }
@@ -34,9 +34,8 @@ pub fn classify_field_type(ty: &Type) -> FieldCategory {
                 "String" | "i32" | "i64" | "u32" | "u64" | "f32" | "f64" | "bool" | "char"
                 | "isize" | "usize" | "i8" | "i16" | "u8" | "u16" => FieldCategory::Primitive,
                 // Standard library types (treated as primitives for Task purposes)
-                "Vec" | "Option" | "HashMap" | "BTreeMap" | "HashSet" | "BTreeSet" => {
-                    FieldCategory::Primitive
-                }
+                "Vec" | "Option" | "HashMap" | "BTreeMap" | "HashSet" | "BTreeSet"
+                | "PhantomData" => FieldCategory::Primitive,
                 // Custom types (potential Task implementors)
                 _ if !type_name.starts_with("std::") => FieldCategory::PotentialTask,
                 _ => FieldCategory::Unknown,
@@ -57,24 +56,20 @@ pub fn detect_task_field_type(ty: &Type) -> TaskFieldType {
 
                 match type_name.as_str() {
                     "Vec" => {
-                        if let syn::PathArguments::AngleBracketed(args) = &last_segment.arguments {
-                            if let Some(syn::GenericArgument::Type(inner_type)) = args.args.first()
-                            {
-                                if classify_field_type(inner_type) == FieldCategory::PotentialTask {
-                                    return TaskFieldType::VecTask;
-                                }
-                            }
+                        if let syn::PathArguments::AngleBracketed(args) = &last_segment.arguments
+                            && let Some(syn::GenericArgument::Type(inner_type)) = args.args.first()
+                            && classify_field_type(inner_type) == FieldCategory::PotentialTask
+                        {
+                            return TaskFieldType::VecTask;
                         }
                         TaskFieldType::Normal
                     }
                     "Option" => {
-                        if let syn::PathArguments::AngleBracketed(args) = &last_segment.arguments {
-                            if let Some(syn::GenericArgument::Type(inner_type)) = args.args.first()
-                            {
-                                if classify_field_type(inner_type) == FieldCategory::PotentialTask {
-                                    return TaskFieldType::OptionTask;
-                                }
-                            }
+                        if let syn::PathArguments::AngleBracketed(args) = &last_segment.arguments
+                            && let Some(syn::GenericArgument::Type(inner_type)) = args.args.first()
+                            && classify_field_type(inner_type) == FieldCategory::PotentialTask
+                        {
+                            return TaskFieldType::OptionTask;
                         }
                         TaskFieldType::Normal
                     }
@@ -83,10 +78,9 @@ pub fn detect_task_field_type(ty: &Type) -> TaskFieldType {
                             // For HashMap<K, V>, we check the second argument (value type)
                             if let Some(syn::GenericArgument::Type(value_type)) =
                                 args.args.iter().nth(1)
+                                && classify_field_type(value_type) == FieldCategory::PotentialTask
                             {
-                                if classify_field_type(value_type) == FieldCategory::PotentialTask {
-                                    return TaskFieldType::HashMapTask;
-                                }
+                                return TaskFieldType::HashMapTask;
                             }
                         }
                         TaskFieldType::Normal
@@ -96,10 +90,9 @@ pub fn detect_task_field_type(ty: &Type) -> TaskFieldType {
                             // For BTreeMap<K, V>, we check the second argument (value type)
                             if let Some(syn::GenericArgument::Type(value_type)) =
                                 args.args.iter().nth(1)
+                                && classify_field_type(value_type) == FieldCategory::PotentialTask
                             {
-                                if classify_field_type(value_type) == FieldCategory::PotentialTask {
-                                    return TaskFieldType::BTreeMapTask;
-                                }
+                                return TaskFieldType::BTreeMapTask;
                             }
                         }
                         TaskFieldType::Normal
@@ -120,3 +113,28 @@ pub fn detect_task_field_type(ty: &Type) -> TaskFieldType {
         _ => TaskFieldType::Normal,
     }
 }
+
+/// Returns the nested Task type of a field, e.g. `Address` for `Vec<Address>`.
+/// Returns `None` for fields that do not contain a nested Task.
+pub fn get_task_inner_type<'a>(ty: &'a Type, task_field_type: &TaskFieldType) -> Option<&'a Type> {
+    if let Type::Reference(reference) = ty {
+        return get_task_inner_type(&reference.elem, task_field_type);
+    }
+
+    let argument_index: usize = match task_field_type {
+        TaskFieldType::Normal => return None,
+        TaskFieldType::DirectTask => return Some(ty),
+        TaskFieldType::VecTask | TaskFieldType::OptionTask => 0,
+        TaskFieldType::HashMapTask | TaskFieldType::BTreeMapTask => 1,
+    };
+
+    if let Type::Path(path) = ty
+        && let Some(last_segment) = path.path.segments.last()
+        && let syn::PathArguments::AngleBracketed(args) = &last_segment.arguments
+        && let Some(syn::GenericArgument::Type(inner_type)) = args.args.iter().nth(argument_index)
+    {
+        return Some(inner_type);
+    }
+
+    None
+}
@@ -0,0 +1,71 @@
+use proc_macro2::{TokenStream, TokenTree};
+use quote::quote;
+use syn::{Generics, Ident, Type, WherePredicate, parse_quote};
+
+use crate::{data_structure_field::DataStructureField, field_types::TaskFieldType};
+
+/// Returns the names of the type parameters of a struct, e.g. `T` for `Wrapper<'a, T, const N: usize>`.
+pub fn get_type_parameters(generics: &Generics) -> Vec<Ident> {
+    generics
+        .type_params()
+        .map(|type_parameter| type_parameter.ident.clone())
+        .collect()
+}
+
+/// Returns whether a type is exactly one of the struct's type parameters.
+pub fn is_type_parameter(ty: &Type, type_parameters: &[Ident]) -> bool {
+    match ty {
+        Type::Path(path) => {
+            path.qself.is_none()
+                && path
+                    .path
+                    .get_ident()
+                    .is_some_and(|ident| type_parameters.contains(ident))
+        }
+        _ => false,
+    }
+}
+
+/// Returns whether a type parameter appears anywhere in a type, e.g. `T` in `Vec<Option<T>>`.
+fn mentions_type_parameter(tokens: TokenStream, type_parameter: &Ident) -> bool {
+    tokens.into_iter().any(|token| match token {
+        TokenTree::Ident(ident) => ident == *type_parameter,
+        TokenTree::Group(group) => mentions_type_parameter(group.stream(), type_parameter),
+        _ => false,
+    })
+}
+
+/// Adds the bounds the generated impls need to the struct's generics.
+///
+/// A type parameter used directly as a nested Task field is bounded by `Task`. Any other type
+/// parameter used in a field is bounded by `Default + Serialize + DeserializeOwned`, the same
+/// requirements `Task` places on the struct itself. Lifetimes and const parameters are kept as
+/// they are.
+pub fn add_trait_bounds(generics: &Generics, fields: &[DataStructureField]) -> Generics {
+    let mut generics: Generics = generics.clone();
+    let type_parameters: Vec<Ident> = get_type_parameters(&generics);
+
+    let mut predicates: Vec<WherePredicate> = Vec::new();
+    for type_parameter in &type_parameters {
+        let is_nested_task: bool = fields.iter().any(|field| {
+            *field.get_task_field_type() == TaskFieldType::DirectTask
+                && is_type_parameter(field.get_field_type(), std::slice::from_ref(type_parameter))
+        });
+        let is_used: bool = fields.iter().any(|field| {
+            let field_type: &Type = field.get_field_type();
+            mentions_type_parameter(quote!(#field_type), type_parameter)
+        });
+
+        if is_nested_task {
+            predicates.push(parse_quote! { #type_parameter: ::secretary::traits::Task });
+        } else if is_used {
+            predicates.push(parse_quote! {
+                #type_parameter: Default + ::serde::Serialize + ::serde::de::DeserializeOwned
+            });
+        }
+    }
+
+    generics.make_where_clause().predicates.extend(predicates);
+
+    generics
+}
@@ -0,0 +1,79 @@
+use quote::{format_ident, quote};
+use syn::{Generics, Ident, Member, Visibility};
+
+use crate::data_structure_field::DataStructureField;
+
+/// Generates `hints()` and the `<Name>Hints` builder it returns, with one method per named
+/// field that sets the field's known value.
+///
+/// The builder is declared with the struct's own generics and implemented with the bounded
+/// ones, like the Task impl. Fields of tuple structs and newtypes have no method, since they
+/// have no name to call it by; they can still be hinted with `Hints::with_value`.
+pub fn implement_hints_builder(
+    name: &Ident,
+    visibility: &Visibility,
+    declared_generics: &Generics,
+    generics: &Generics,
+    data_structure_fields: &[DataStructureField],
+) -> proc_macro2::TokenStream {
+    let builder: Ident = format_ident!("{}Hints", name);
+    let (impl_generics, type_generics, where_clause) = generics.split_for_impl();
+    let (_, declared_type_generics, declared_where_clause) = declared_generics.split_for_impl();
+    let builder_doc: String = format!(
+        "Builds the known values of a `{}`, see `{}::hints`.",
+        name, name
+    );
+    let hints_doc: String = format!(
+        "Starts the known values of a `{}`, passed to an extraction with `RequestOptions::with_hints`.",
+        name
+    );
+
+    let setters: Vec<proc_macro2::TokenStream> = data_structure_fields
+        .iter()
+        .filter_map(|field| {
+            let Member::Named(ident) = field.get_member() else {
+                return None;
+            };
+            if field.get_field_name().is_empty() {
+                return None;
+            }
+            let path: &str = field.get_field_name();
+            let field_type = field.get_field_type();
+            let doc: String = format!("Sets the known value of `{}`.", path);
+
+            Some(quote! {
+                #[doc = #doc]
+                pub fn #ident(mut self, value: impl Into<#field_type>) -> Self {
+                    let value: #field_type = value.into();
+                    self.hints = self.hints.with_value(#path, &value);
+                    self
+                }
+            })
+        })
+        .collect();
+
+    quote! {
+        #[doc = #builder_doc]
+        #visibility struct #builder #declared_generics #declared_where_clause {
+            hints: ::secretary::hints::Hints<#name #declared_type_generics>,
+        }
+
+        impl #impl_generics #builder #type_generics #where_clause {
+            #(#setters)*
+
+            /// Returns the known values set so far.
+            pub fn build_hints(self) -> ::secretary::hints::Hints<#name #type_generics> {
+                self.hints
+            }
+        }
+
+        impl #impl_generics #name #type_generics #where_clause {
+            #[doc = #hints_doc]
+            pub fn hints() -> #builder #type_generics {
+                #builder {
+                    hints: ::secretary::hints::Hints::new(),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use syn::{Lit, Type};
+
+use crate::data_structure_field::DataStructureField;
+
+/// The shortest instruction `deny_generic_instructions` accepts, in characters.
+pub const MIN_INSTRUCTION_CHARS: usize = 20;
+
+/// Words that tell the model a value is a number.
+const NUMBER_WORDS: [&str; 6] = ["number", "numeric", "integer", "decimal", "float", "digit"];
+
+/// Words that tell the model a value is a boolean.
+const BOOLEAN_WORDS: [&str; 3] = ["boolean", "true", "false"];
+
+/// The JSON type of a field whose instruction must name it.
+enum ScalarKind {
+    Number,
+    Boolean,
+}
+
+/// Rejects generic instructions, for structs with `#[task(deny_generic_instructions)]`.
+///
+/// An instruction is generic when it is shorter than `MIN_INSTRUCTION_CHARS`, when it is
+/// `Extract the <field> field from the input`, or when it belongs to a number or boolean
+/// field and does not say so. Each rejected instruction is reported at its literal, with the
+/// first problem found in it.
+pub fn check_generic_instructions(data_structure_fields: &[DataStructureField]) -> syn::Result<()> {
+    let mut errors: Option<syn::Error> = None;
+
+    for field in data_structure_fields {
+        let Some(literal) = field.get_instruction_literal() else {
+            continue;
+        };
+        let Lit::Str(instruction) = literal else {
+            continue;
+        };
+        let Some(problem) = find_problem(
+            &instruction.value(),
+            &field.get_field_label(),
+            field.get_field_type(),
+        ) else {
+            continue;
+        };
+
+        let error: syn::Error = syn::Error::new_spanned(literal, problem);
+        match &mut errors {
+            Some(errors) => errors.combine(error),
+            None => errors = Some(error),
+        }
+    }
+
+    match errors {
+        Some(errors) => Err(errors),
+        None => Ok(()),
+    }
+}
+
+/// Returns why an instruction is generic, or `None` when it is descriptive enough.
+fn find_problem(instruction: &str, label: &str, field_type: &Type) -> Option<String> {
+    let instruction: &str = instruction.trim();
+    let length: usize = instruction.chars().count();
+    if length < MIN_INSTRUCTION_CHARS {
+        return Some(format!(
+            "instruction is {} chars, under the {} chars deny_generic_instructions requires; say what the value is and where to find it",
+            length, MIN_INSTRUCTION_CHARS
+        ));
+    }
+
+    let fallback: String = format!("Extract the {} field from the input", label);
+    if instruction
+        .trim_end_matches('.')
+        .eq_ignore_ascii_case(&fallback)
+    {
+        return Some(format!(
+            "instruction is the generic \"{}\"; say what the value is and where to find it",
+            fallback
+        ));
+    }
+
+    let lowercase: String = instruction.to_lowercase();
+    match scalar_kind(field_type) {
+        Some(ScalarKind::Number)
+            if !NUMBER_WORDS.iter().any(|word| lowercase.contains(word)) =>
+        {
+            Some(
+                "instruction of a number field does not say the value is a number; mention e.g. \"number\", \"integer\" or \"decimal\"".to_string(),
+            )
+        }
+        Some(ScalarKind::Boolean)
+            if !BOOLEAN_WORDS.iter().any(|word| lowercase.contains(word)) =>
+        {
+            Some(
+                "instruction of a boolean field does not say the value is a boolean; mention \"true\" and \"false\" or \"boolean\"".to_string(),
+            )
+        }
+        _ => None,
+    }
+}
+
+/// Returns whether a field, optional or not, holds a number or a boolean.
+fn scalar_kind(field_type: &Type) -> Option<ScalarKind> {
+    let Type::Path(path) = field_type else {
+        return None;
+    };
+    let last_segment: &syn::PathSegment = path.path.segments.last()?;
+
+    match last_segment.ident.to_string().as_str() {
+        "i8" | "i16" | "i32" | "i64" | "isize" | "u8" | "u16" | "u32" | "u64" | "usize" | "f32"
+        | "f64" => Some(ScalarKind::Number),
+        "bool" => Some(ScalarKind::Boolean),
+        "Option" => match &last_segment.arguments {
+            syn::PathArguments::AngleBracketed(args) => match args.args.first() {
+                Some(syn::GenericArgument::Type(inner_type)) => scalar_kind(inner_type),
+                _ => None,
+            },
+            _ => None,
+        },
+        _ => None,
+    }
+}
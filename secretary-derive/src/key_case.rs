@@ -0,0 +1,108 @@
+use quote::quote;
+use syn::Lit;
+
+/// The case of the keys of a struct's fields, from `key_case = "..."`.
+/// Kept in sync with `secretary::casing::KeyCase`.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum KeyCase {
+    #[default]
+    Snake,
+    Camel,
+    Kebab,
+    Pascal,
+}
+
+impl KeyCase {
+    /// Generates the `secretary::casing::KeyCase` of this case.
+    pub fn to_tokens(self) -> proc_macro2::TokenStream {
+        match self {
+            KeyCase::Snake => quote! { ::secretary::casing::KeyCase::Snake },
+            KeyCase::Camel => quote! { ::secretary::casing::KeyCase::Camel },
+            KeyCase::Kebab => quote! { ::secretary::casing::KeyCase::Kebab },
+            KeyCase::Pascal => quote! { ::secretary::casing::KeyCase::Pascal },
+        }
+    }
+
+    /// Writes a field name in this case.
+    /// Kept in sync with `secretary::casing::KeyCase::apply`.
+    pub fn apply(self, name: &str) -> String {
+        let words: Vec<String> = split_words(name);
+        match self {
+            KeyCase::Snake => words.join("_"),
+            KeyCase::Kebab => words.join("-"),
+            KeyCase::Camel => words
+                .iter()
+                .enumerate()
+                .map(|(index, word)| {
+                    if index == 0 {
+                        word.clone()
+                    } else {
+                        capitalize(word)
+                    }
+                })
+                .collect(),
+            KeyCase::Pascal => words.iter().map(|word| capitalize(word)).collect(),
+        }
+    }
+}
+
+/// Reads a `key_case`: `snake_case`, `camelCase`, `kebab-case` or `PascalCase`.
+pub fn parse_key_case(value: &Lit) -> syn::Result<KeyCase> {
+    let name: String = match value {
+        Lit::Str(name) => name.value(),
+        _ => return Err(syn::Error::new_spanned(value, "Expected a string literal")),
+    };
+
+    match name.as_str() {
+        "snake_case" => Ok(KeyCase::Snake),
+        "camelCase" => Ok(KeyCase::Camel),
+        "kebab-case" => Ok(KeyCase::Kebab),
+        "PascalCase" => Ok(KeyCase::Pascal),
+        _ => Err(syn::Error::new_spanned(
+            value,
+            "key_case must be one of \"snake_case\", \"camelCase\", \"kebab-case\" or \"PascalCase\"",
+        )),
+    }
+}
+
+fn split_words(name: &str) -> Vec<String> {
+    let mut words: Vec<String> = Vec::new();
+    let mut word: String = String::new();
+    let characters: Vec<char> = name.chars().collect();
+
+    for (index, &character) in characters.iter().enumerate() {
+        if matches!(character, '_' | '-' | ' ') {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            continue;
+        }
+
+        if let Some(&previous) = index.checked_sub(1).and_then(|index| characters.get(index)) {
+            let next: Option<char> = characters.get(index + 1).copied();
+            let boundary: bool = (character.is_uppercase()
+                && (previous.is_lowercase() || previous.is_ascii_digit()))
+                || (character.is_uppercase()
+                    && previous.is_uppercase()
+                    && next.is_some_and(char::is_lowercase))
+                || (character.is_ascii_digit() && previous.is_alphabetic());
+            if boundary && !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+        }
+        word.extend(character.to_lowercase());
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+
+    words
+}
+
+fn capitalize(word: &str) -> String {
+    let mut characters = word.chars();
+    match characters.next() {
+        Some(first) => first.to_uppercase().chain(characters).collect(),
+        None => String::new(),
+    }
+}
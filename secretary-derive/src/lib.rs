@@ -1,38 +1,99 @@
 mod data_structure_field;
 mod default_implementations;
+mod default_value;
 mod field_attributes;
 mod field_types;
+mod generics;
+mod hints;
+mod instruction_lint;
+mod key_case;
+mod one_of;
+mod ordered;
+mod output_language;
+mod string_normalization;
+mod struct_attributes;
+mod tagged_enum;
 mod task_implementations;
 mod utilities;
 
 use default_implementations::implement_default;
 use proc_macro::TokenStream;
-use syn::{DeriveInput, parse_macro_input};
+use syn::{Data, DeriveInput, parse_macro_input};
 
 use data_structure_field::{DataStructureField, get_data_structure_fields};
-use task_implementations::{implement_new_method, implement_task_trait};
+use generics::{add_trait_bounds, get_type_parameters};
+use hints::implement_hints_builder;
+use instruction_lint::check_generic_instructions;
+use struct_attributes::TaskStructAttributes;
+use tagged_enum::derive_tagged_enum;
+use task_implementations::{check_prompt_budget, implement_new_method, implement_task_trait};
+use utilities::get_struct_attributes;
 
 #[proc_macro_derive(Task, attributes(task))]
 pub fn derive_task(input: TokenStream) -> TokenStream {
     let input: DeriveInput = parse_macro_input!(input as DeriveInput);
+
+    // Enums are internally tagged unions of Tasks, with a derive of their own
+    if let Data::Enum(data) = &input.data {
+        return match derive_tagged_enum(&input, data) {
+            Ok(expanded) => TokenStream::from(expanded),
+            Err(error) => TokenStream::from(error.to_compile_error()),
+        };
+    }
+
     let name: &syn::Ident = &input.ident;
     let mut expanded: proc_macro2::TokenStream = proc_macro2::TokenStream::new();
 
-    let data_structure_fields: Vec<DataStructureField> =
-        match get_data_structure_fields(&input.data) {
-            Ok(fields) => fields,
-            Err(error) => {
-                return error;
-            }
-        };
+    let struct_attributes: TaskStructAttributes = match get_struct_attributes(&input.attrs) {
+        Ok(attributes) => attributes,
+        Err(error) => return TokenStream::from(error.to_compile_error()),
+    };
+
+    let data_structure_fields: Vec<DataStructureField> = match get_data_structure_fields(
+        &input.data,
+        &get_type_parameters(&input.generics),
+        struct_attributes.output_language.as_deref(),
+        struct_attributes.key_case,
+    ) {
+        Ok(fields) => fields,
+        Err(error) => {
+            return error;
+        }
+    };
+
+    if let Err(error) = check_prompt_budget(name, &data_structure_fields, &struct_attributes) {
+        return TokenStream::from(error.to_compile_error());
+    }
+
+    if struct_attributes.deny_generic_instructions
+        && let Err(error) = check_generic_instructions(&data_structure_fields)
+    {
+        return TokenStream::from(error.to_compile_error());
+    }
+
+    let generics: syn::Generics = add_trait_bounds(&input.generics, &data_structure_fields);
 
-    let default_impl = implement_default(name, &input.data);
-    let task_impl = implement_task_trait(name, data_structure_fields);
-    let new_impl = implement_new_method(name);
+    let default_impl = implement_default(
+        name,
+        &generics,
+        &input.data,
+        struct_attributes.empty_defaults,
+    );
+    let hints_impl = implement_hints_builder(
+        name,
+        &input.vis,
+        &input.generics,
+        &generics,
+        &data_structure_fields,
+    );
+    let task_impl =
+        implement_task_trait(name, &generics, data_structure_fields, &struct_attributes);
+    let new_impl = implement_new_method(name, &generics);
 
     expanded.extend(default_impl);
     expanded.extend(task_impl);
     expanded.extend(new_impl);
+    expanded.extend(hints_impl);
 
     TokenStream::from(expanded)
 }
@@ -0,0 +1,40 @@
+use syn::Lit;
+
+/// Reads a `one_of`, a string literal of comma-separated values such as `"high, mid, low"`.
+pub fn parse_one_of(value: &Lit) -> syn::Result<Vec<String>> {
+    let text: String = match value {
+        Lit::Str(text) => text.value(),
+        _ => return Err(syn::Error::new_spanned(value, "Expected a string literal")),
+    };
+
+    let mut allowed: Vec<String> = Vec::new();
+    for item in text.split(',').map(str::trim) {
+        if item.is_empty() {
+            return Err(syn::Error::new_spanned(
+                value,
+                "one_of must list values separated by commas, e.g. \"high, mid, low\"",
+            ));
+        }
+        if allowed
+            .iter()
+            .any(|existing| existing.eq_ignore_ascii_case(item))
+        {
+            return Err(syn::Error::new_spanned(
+                value,
+                format!("one_of lists \"{}\" twice", item),
+            ));
+        }
+        allowed.push(item.to_string());
+    }
+
+    Ok(allowed)
+}
+
+/// Returns the sentence appended to the instruction of a field with a `one_of`.
+pub fn one_of_requirement(allowed: &[String]) -> String {
+    let values: Vec<String> = allowed
+        .iter()
+        .map(|value| format!("\"{}\"", value))
+        .collect();
+    format!("Answer with exactly one of {}.", values.join(", "))
+}
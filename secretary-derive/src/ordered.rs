@@ -0,0 +1,3 @@
+/// The sentence appended to the instruction of a field with `ordered`.
+/// Kept in sync with `secretary::ordering::ORDER_REQUIREMENT`.
+pub const ORDER_REQUIREMENT: &str = "Preserve the original order of appearance in the source text; do not sort, merge, or deduplicate the items.";
@@ -0,0 +1,67 @@
+use syn::Lit;
+
+/// The value of `output_language` asking for the language of the target.
+pub const SOURCE_LANGUAGE: &str = "source";
+
+/// English names of common ISO 639-1 codes, used in the requirement added to instructions.
+const LANGUAGE_NAMES: [(&str, &str); 24] = [
+    ("ar", "Arabic"),
+    ("cs", "Czech"),
+    ("da", "Danish"),
+    ("de", "German"),
+    ("el", "Greek"),
+    ("en", "English"),
+    ("es", "Spanish"),
+    ("fi", "Finnish"),
+    ("fr", "French"),
+    ("he", "Hebrew"),
+    ("hi", "Hindi"),
+    ("hu", "Hungarian"),
+    ("it", "Italian"),
+    ("ja", "Japanese"),
+    ("ko", "Korean"),
+    ("nl", "Dutch"),
+    ("no", "Norwegian"),
+    ("pl", "Polish"),
+    ("pt", "Portuguese"),
+    ("ru", "Russian"),
+    ("sv", "Swedish"),
+    ("tr", "Turkish"),
+    ("uk", "Ukrainian"),
+    ("zh", "Chinese"),
+];
+
+/// Reads an `output_language`: `source` or a two-letter ISO 639-1 code such as `en`.
+pub fn parse_output_language(value: &Lit) -> syn::Result<String> {
+    let language: String = match value {
+        Lit::Str(language) => language.value(),
+        _ => return Err(syn::Error::new_spanned(value, "Expected a string literal")),
+    };
+
+    if language == SOURCE_LANGUAGE
+        || (language.len() == 2 && language.chars().all(|c| c.is_ascii_lowercase()))
+    {
+        Ok(language)
+    } else {
+        Err(syn::Error::new_spanned(
+            value,
+            "output_language must be \"source\" or a two-letter ISO 639-1 code such as \"en\"",
+        ))
+    }
+}
+
+/// Returns the sentence appended to the instruction of a field with an `output_language`.
+pub fn language_requirement(language: &str) -> String {
+    if language == SOURCE_LANGUAGE {
+        return "Keep the value in the language of the source text, do not translate it."
+            .to_string();
+    }
+
+    match LANGUAGE_NAMES.iter().find(|(code, _)| *code == language) {
+        Some((_, name)) => format!("Write the value in {}, translating it if needed.", name),
+        None => format!(
+            "Write the value in the language with ISO 639-1 code \"{}\", translating it if needed.",
+            language
+        ),
+    }
+}
@@ -0,0 +1,41 @@
+use quote::quote;
+use syn::Lit;
+
+/// How the text of a struct's string fields is normalized, from `normalize_strings`.
+/// Kept in sync with `secretary::normalization::StringNormalization`.
+#[derive(Clone, Copy, PartialEq)]
+pub enum StringNormalization {
+    Collapse,
+    Trim,
+}
+
+impl StringNormalization {
+    /// Generates the `secretary::normalization::StringNormalization` of this normalization.
+    pub fn to_tokens(self) -> proc_macro2::TokenStream {
+        match self {
+            StringNormalization::Collapse => {
+                quote! { ::secretary::normalization::StringNormalization::Collapse }
+            }
+            StringNormalization::Trim => {
+                quote! { ::secretary::normalization::StringNormalization::Trim }
+            }
+        }
+    }
+}
+
+/// Reads a `normalize_strings = "..."`: `collapse` or `trim`.
+pub fn parse_string_normalization(value: &Lit) -> syn::Result<StringNormalization> {
+    let name: String = match value {
+        Lit::Str(name) => name.value(),
+        _ => return Err(syn::Error::new_spanned(value, "Expected a string literal")),
+    };
+
+    match name.as_str() {
+        "collapse" => Ok(StringNormalization::Collapse),
+        "trim" => Ok(StringNormalization::Trim),
+        _ => Err(syn::Error::new_spanned(
+            value,
+            "normalize_strings must be one of \"collapse\" or \"trim\"",
+        )),
+    }
+}
@@ -0,0 +1,130 @@
+use syn::{Ident, Lit, Token, parse::Parse};
+
+use crate::{
+    key_case::{KeyCase, parse_key_case},
+    output_language::parse_output_language,
+    string_normalization::{StringNormalization, parse_string_normalization},
+};
+
+/// The prompt layout used when a struct does not pin one.
+/// Kept in sync with `secretary::prompt::DEFAULT_PROMPT_VERSION`.
+pub const DEFAULT_PROMPT_VERSION: u32 = 1;
+
+/// The newest prompt layout the derive can generate.
+/// Kept in sync with `secretary::prompt::LATEST_PROMPT_VERSION`.
+pub const LATEST_PROMPT_VERSION: u32 = 3;
+
+/// The `#[task(...)]` attribute placed on the struct itself.
+#[derive(Default)]
+pub struct TaskStructAttributes {
+    pub prompt_version: Option<u32>,
+    /// The budget for the static prompt size, from `max_prompt_chars = N`.
+    pub max_prompt_chars: Option<usize>,
+    /// Whether `Default` leaves Task collections empty and optional Tasks `None`, from
+    /// `empty_defaults`.
+    pub empty_defaults: bool,
+    /// Text placed before every prompt of the struct, from `preamble = "..."`.
+    pub preamble: Option<String>,
+    /// Text placed after every prompt of the struct, from `postamble = "..."`.
+    pub postamble: Option<String>,
+    /// The language of the text fields without their own, from `output_language = "..."`.
+    pub output_language: Option<String>,
+    /// Whether generic field instructions fail to compile, from `deny_generic_instructions`.
+    pub deny_generic_instructions: bool,
+    /// The case of the keys the model sees, from `key_case = "..."`.
+    pub key_case: KeyCase,
+    /// How the text of string fields is normalized before deserialization, from
+    /// `normalize_strings` or `normalize_strings = "..."`.
+    pub normalize_strings: Option<StringNormalization>,
+}
+
+impl TaskStructAttributes {
+    /// Returns the pinned prompt version, or the default layout when none is pinned.
+    pub fn get_prompt_version(&self) -> u32 {
+        self.prompt_version.unwrap_or(DEFAULT_PROMPT_VERSION)
+    }
+}
+
+impl Parse for TaskStructAttributes {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let mut attributes: TaskStructAttributes = TaskStructAttributes::default();
+
+        while !input.is_empty() {
+            let name: Ident = input.parse()?;
+
+            // Flags such as `empty_defaults` take no value
+            if !input.peek(Token![=]) {
+                match name.to_string().as_str() {
+                    "empty_defaults" => attributes.empty_defaults = true,
+                    "deny_generic_instructions" => attributes.deny_generic_instructions = true,
+                    "normalize_strings" => {
+                        attributes.normalize_strings = Some(StringNormalization::Collapse)
+                    }
+                    _ => return Err(syn::Error::new(name.span(), "Unknown attribute parameter")),
+                }
+
+                if !input.is_empty() {
+                    input.parse::<Token![,]>()?;
+                }
+                continue;
+            }
+
+            input.parse::<Token![=]>()?;
+            let value: Lit = input.parse()?;
+
+            match name.to_string().as_str() {
+                "prompt_version" => {
+                    let prompt_version: u32 = match &value {
+                        Lit::Int(version) => version.base10_parse::<u32>()?,
+                        _ => return Err(syn::Error::new_spanned(value, "Expected an integer")),
+                    };
+                    if !(1..=LATEST_PROMPT_VERSION).contains(&prompt_version) {
+                        return Err(syn::Error::new_spanned(
+                            value,
+                            format!(
+                                "prompt_version must be between 1 and {}",
+                                LATEST_PROMPT_VERSION
+                            ),
+                        ));
+                    }
+                    attributes.prompt_version = Some(prompt_version);
+                }
+                "max_prompt_chars" => {
+                    let max_prompt_chars: usize = match &value {
+                        Lit::Int(max_prompt_chars) => max_prompt_chars.base10_parse::<usize>()?,
+                        _ => return Err(syn::Error::new_spanned(value, "Expected an integer")),
+                    };
+                    attributes.max_prompt_chars = Some(max_prompt_chars);
+                }
+                "preamble" => attributes.preamble = Some(parse_framing(&value)?),
+                "postamble" => attributes.postamble = Some(parse_framing(&value)?),
+                "output_language" => {
+                    attributes.output_language = Some(parse_output_language(&value)?)
+                }
+                "key_case" => attributes.key_case = parse_key_case(&value)?,
+                "normalize_strings" => {
+                    attributes.normalize_strings = Some(parse_string_normalization(&value)?)
+                }
+                _ => return Err(syn::Error::new(name.span(), "Unknown attribute parameter")),
+            }
+
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+
+        Ok(attributes)
+    }
+}
+
+/// Reads the string of a `preamble` or `postamble`, which must not be blank.
+fn parse_framing(value: &Lit) -> syn::Result<String> {
+    match value {
+        Lit::Str(text) if !text.value().trim().is_empty() => Ok(text.value()),
+        Lit::Str(_) => Err(syn::Error::new_spanned(
+            value,
+            "Expected a non-empty string",
+        )),
+        _ => Err(syn::Error::new_spanned(value, "Expected a string")),
+    }
+}
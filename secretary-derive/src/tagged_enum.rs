@@ -0,0 +1,368 @@
+use proc_macro2::TokenStream;
+use quote::{quote, quote_spanned};
+use syn::{
+    Attribute, DataEnum, DeriveInput, Expr, ExprLit, Fields, Ident, Lit, LitStr, Meta, Token, Type,
+    Variant, punctuated::Punctuated, spanned::Spanned,
+};
+
+use crate::{
+    struct_attributes::TaskStructAttributes, task_implementations::implement_new_method,
+    utilities::get_struct_attributes,
+};
+
+/// A variant of an internally tagged enum deriving Task.
+struct TaggedVariant {
+    ident: Ident,
+    /// The type of the Task the variant holds.
+    ty: Type,
+    /// The value of the tag key that selects the variant, as serde writes it.
+    tag: String,
+    /// When to pick the variant, from `#[task(instruction = "...")]`.
+    instruction: Option<String>,
+}
+
+/// The serde representation of an enum, from its `#[serde(...)]` attributes.
+#[derive(Default)]
+struct SerdeEnumAttributes {
+    tag: Option<String>,
+    content: bool,
+    untagged: bool,
+    rename_all: Option<LitStr>,
+}
+
+/// Derives Task for an enum whose variants each hold a Task, represented by serde as an
+/// internally tagged enum (`#[serde(tag = "...")]`).
+///
+/// The system prompt lists the variants by their tag and describes each with the system
+/// prompt of its Task, asking the model to pick one and to answer with its tag and fields;
+/// the answer is deserialized by serde. The field descriptors are the tag key followed by the
+/// fields of every variant, made optional. Distributed generation is not supported.
+pub fn derive_tagged_enum(input: &DeriveInput, data: &DataEnum) -> syn::Result<TokenStream> {
+    let name: &Ident = &input.ident;
+    let struct_attributes: TaskStructAttributes = get_struct_attributes(&input.attrs)?;
+    check_enum_attributes(&input.attrs, &struct_attributes)?;
+
+    let serde_attributes: SerdeEnumAttributes = get_serde_enum_attributes(&input.attrs)?;
+    let tag: String = match serde_attributes {
+        SerdeEnumAttributes { untagged: true, .. } => {
+            return Err(syn::Error::new_spanned(
+                name,
+                "Untagged enums cannot derive Task; tag the enum with #[serde(tag = \"...\")] so that the answer names its variant",
+            ));
+        }
+        SerdeEnumAttributes { content: true, .. } => {
+            return Err(syn::Error::new_spanned(
+                name,
+                "Adjacently tagged enums cannot derive Task; remove `content` to tag the enum internally",
+            ));
+        }
+        SerdeEnumAttributes { tag: Some(tag), .. } => tag,
+        SerdeEnumAttributes { tag: None, .. } => {
+            return Err(syn::Error::new_spanned(
+                name,
+                "Enums deriving Task must be internally tagged with #[serde(tag = \"...\")], so that the answer names its variant",
+            ));
+        }
+    };
+    if data.variants.is_empty() {
+        return Err(syn::Error::new_spanned(
+            name,
+            "Enums without variants have nothing to extract",
+        ));
+    }
+
+    let variants: Vec<TaggedVariant> = data
+        .variants
+        .iter()
+        .map(|variant| get_tagged_variant(variant, serde_attributes.rename_all.as_ref()))
+        .collect::<syn::Result<_>>()?;
+
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    let first_variant: &Ident = &variants[0].ident;
+    let variant_prompts: Vec<TokenStream> = variants
+        .iter()
+        .map(|variant| {
+            let ty: &Type = &variant.ty;
+            let variant_tag: &str = &variant.tag;
+            let instruction: TokenStream = match &variant.instruction {
+                Some(instruction) => quote! { Some(#instruction) },
+                None => quote! { None },
+            };
+            quote! {
+                ::secretary::variants::VariantPrompt {
+                    tag: #variant_tag,
+                    instruction: #instruction,
+                    prompt: <#ty as Task>::get_system_prompt(&<#ty as Default>::default()),
+                }
+            }
+        })
+        .collect();
+    let variant_descriptors: Vec<TokenStream> = variants
+        .iter()
+        .map(|variant| {
+            let ty: &Type = &variant.ty;
+            quote! { <#ty as Task>::field_descriptors() }
+        })
+        .collect();
+    // Reports a variant holding a type without Task at the type, like a nested Task field
+    let task_assertions: Vec<TokenStream> = variants
+        .iter()
+        .map(|variant| {
+            let ty: &Type = &variant.ty;
+            quote_spanned! {ty.span()=>
+                assert_task::<#ty>();
+            }
+        })
+        .collect();
+    let framing: TokenStream = implement_enum_framing(&struct_attributes);
+    let new_impl: TokenStream = implement_new_method(name, &input.generics);
+
+    Ok(quote! {
+        impl #impl_generics Default for #name #type_generics #where_clause {
+            fn default() -> Self {
+                Self::#first_variant(Default::default())
+            }
+        }
+
+        impl #impl_generics Task for #name #type_generics #where_clause {
+            fn get_system_prompt(&self) -> String {
+                ::secretary::prompt::frame_prompt(
+                    ::secretary::variants::render_variants_prompt(
+                        #tag,
+                        &[#(#variant_prompts),*],
+                    ),
+                    Self::preamble(),
+                    Self::postamble(),
+                )
+            }
+
+            #framing
+
+            fn get_distributed_field_prompts(&self) -> Vec<::secretary::distributed::FieldPrompt> {
+                Vec::new()
+            }
+
+            fn supports_distributed_generation() -> bool {
+                false
+            }
+
+            fn field_descriptors() -> Vec<::secretary::schema::FieldDescriptor> {
+                fn assert_task<T: ::secretary::traits::Task>() {}
+                #(#task_assertions)*
+                ::secretary::variants::variant_field_descriptors(
+                    #tag,
+                    vec![#(#variant_descriptors),*],
+                )
+            }
+        }
+
+        #new_impl
+    })
+}
+
+/// Fails on the struct attributes that do not apply to an enum, whose prompt is made of the
+/// prompts of its variants' Tasks. Only `preamble` and `postamble` apply.
+fn check_enum_attributes(
+    attributes: &[Attribute],
+    struct_attributes: &TaskStructAttributes,
+) -> syn::Result<()> {
+    let only_framing: bool = struct_attributes.prompt_version.is_none()
+        && struct_attributes.max_prompt_chars.is_none()
+        && !struct_attributes.empty_defaults
+        && struct_attributes.output_language.is_none()
+        && !struct_attributes.deny_generic_instructions
+        && struct_attributes.key_case == Default::default()
+        && struct_attributes.normalize_strings.is_none();
+    if only_framing {
+        return Ok(());
+    }
+
+    let attribute: Option<&Attribute> = attributes
+        .iter()
+        .find(|attribute| attribute.path().is_ident("task"));
+    Err(syn::Error::new(
+        attribute.map_or_else(proc_macro2::Span::call_site, |attribute| attribute.span()),
+        "Only preamble and postamble apply to enums; set the other attributes on the Tasks of the variants",
+    ))
+}
+
+/// Generates `Task::preamble` and `Task::postamble` for the texts set on the enum.
+fn implement_enum_framing(struct_attributes: &TaskStructAttributes) -> TokenStream {
+    let preamble = struct_attributes.preamble.as_ref().map(|preamble| {
+        quote! {
+            fn preamble() -> Option<&'static str> {
+                Some(#preamble)
+            }
+        }
+    });
+    let postamble = struct_attributes.postamble.as_ref().map(|postamble| {
+        quote! {
+            fn postamble() -> Option<&'static str> {
+                Some(#postamble)
+            }
+        }
+    });
+
+    quote! {
+        #preamble
+        #postamble
+    }
+}
+
+/// Reads a variant, which must hold exactly one Task, with its tag and instruction.
+fn get_tagged_variant(
+    variant: &Variant,
+    rename_all: Option<&LitStr>,
+) -> syn::Result<TaggedVariant> {
+    let ty: Type = match &variant.fields {
+        Fields::Unnamed(fields) if fields.unnamed.len() == 1 => fields.unnamed[0].ty.clone(),
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &variant.ident,
+                format!(
+                    "Variants of a Task enum must hold one struct that derives Task, e.g. `{}({})`",
+                    variant.ident, variant.ident
+                ),
+            ));
+        }
+    };
+
+    let tag: String = match get_serde_rename(&variant.attrs)? {
+        Some(rename) => rename,
+        None => match rename_all {
+            Some(rule) => rename_variant(&variant.ident.to_string(), rule)?,
+            None => variant.ident.to_string(),
+        },
+    };
+
+    Ok(TaggedVariant {
+        ident: variant.ident.clone(),
+        ty,
+        tag,
+        instruction: get_variant_instruction(&variant.attrs)?,
+    })
+}
+
+/// Reads the `#[task(instruction = "...")]` of a variant, which says when to pick it.
+fn get_variant_instruction(attributes: &[Attribute]) -> syn::Result<Option<String>> {
+    let Some(attribute) = attributes
+        .iter()
+        .find(|attribute| attribute.path().is_ident("task"))
+    else {
+        return Ok(None);
+    };
+
+    let mut instruction: Option<String> = None;
+    attribute.parse_nested_meta(|meta| {
+        if !meta.path.is_ident("instruction") {
+            return Err(
+                meta.error("Unknown attribute parameter; variants take an instruction only")
+            );
+        }
+        let value: LitStr = meta.value()?.parse()?;
+        if value.value().trim().is_empty() {
+            return Err(syn::Error::new_spanned(
+                value,
+                "Expected a non-empty string",
+            ));
+        }
+        instruction = Some(value.value());
+        Ok(())
+    })?;
+
+    Ok(instruction)
+}
+
+/// Reads the representation of an enum from its `#[serde(...)]` attributes, ignoring the
+/// parameters that do not change it.
+fn get_serde_enum_attributes(attributes: &[Attribute]) -> syn::Result<SerdeEnumAttributes> {
+    let mut serde_attributes: SerdeEnumAttributes = SerdeEnumAttributes::default();
+    for meta in serde_metas(attributes)? {
+        match &meta {
+            Meta::Path(path) if path.is_ident("untagged") => serde_attributes.untagged = true,
+            Meta::NameValue(name_value) if name_value.path.is_ident("tag") => {
+                serde_attributes.tag = Some(string_value(&name_value.value)?.value());
+            }
+            Meta::NameValue(name_value) if name_value.path.is_ident("content") => {
+                serde_attributes.content = true;
+            }
+            Meta::NameValue(name_value) if name_value.path.is_ident("rename_all") => {
+                serde_attributes.rename_all = Some(string_value(&name_value.value)?);
+            }
+            _ => {}
+        }
+    }
+
+    Ok(serde_attributes)
+}
+
+/// Reads the `#[serde(rename = "...")]` of a variant.
+fn get_serde_rename(attributes: &[Attribute]) -> syn::Result<Option<String>> {
+    for meta in serde_metas(attributes)? {
+        if let Meta::NameValue(name_value) = &meta
+            && name_value.path.is_ident("rename")
+        {
+            return Ok(Some(string_value(&name_value.value)?.value()));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Returns the parameters of every `#[serde(...)]` attribute.
+fn serde_metas(attributes: &[Attribute]) -> syn::Result<Vec<Meta>> {
+    let mut metas: Vec<Meta> = Vec::new();
+    for attribute in attributes
+        .iter()
+        .filter(|attribute| attribute.path().is_ident("serde"))
+    {
+        metas.extend(attribute.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)?);
+    }
+
+    Ok(metas)
+}
+
+fn string_value(value: &Expr) -> syn::Result<LitStr> {
+    match value {
+        Expr::Lit(ExprLit {
+            lit: Lit::Str(text),
+            ..
+        }) => Ok(text.clone()),
+        _ => Err(syn::Error::new_spanned(value, "Expected a string literal")),
+    }
+}
+
+/// Renames a variant under serde's `rename_all` rule, as serde does for variants, which are
+/// written in PascalCase.
+fn rename_variant(variant: &str, rule: &LitStr) -> syn::Result<String> {
+    let snake: String =
+        variant
+            .char_indices()
+            .fold(String::new(), |mut snake, (index, character)| {
+                if index > 0 && character.is_uppercase() {
+                    snake.push('_');
+                }
+                snake.push(character.to_ascii_lowercase());
+                snake
+            });
+
+    let renamed: String = match rule.value().as_str() {
+        "lowercase" => variant.to_ascii_lowercase(),
+        "UPPERCASE" => variant.to_ascii_uppercase(),
+        "PascalCase" => variant.to_string(),
+        "camelCase" => {
+            let mut characters = variant.chars();
+            match characters.next() {
+                Some(first) => first.to_ascii_lowercase().to_string() + characters.as_str(),
+                None => String::new(),
+            }
+        }
+        "snake_case" => snake,
+        "SCREAMING_SNAKE_CASE" => snake.to_ascii_uppercase(),
+        "kebab-case" => snake.replace('_', "-"),
+        "SCREAMING-KEBAB-CASE" => snake.replace('_', "-").to_ascii_uppercase(),
+        _ => return Err(syn::Error::new_spanned(rule, "Unknown rename_all rule")),
+    };
+
+    Ok(renamed)
+}
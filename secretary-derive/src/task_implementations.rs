@@ -1,43 +1,391 @@
-use quote::quote;
-use syn::Ident;
+use quote::{quote, quote_spanned};
+use syn::{Generics, Ident, spanned::Spanned};
 
-use crate::{data_structure_field::DataStructureField, field_types::TaskFieldType};
+use crate::{
+    data_structure_field::DataStructureField,
+    field_types::{TaskFieldType, get_task_inner_type},
+    key_case::KeyCase,
+    struct_attributes::TaskStructAttributes,
+};
 
 pub fn implement_task_trait(
     name: &Ident,
+    generics: &Generics,
     data_structure_fields: Vec<DataStructureField>,
+    struct_attributes: &TaskStructAttributes,
 ) -> proc_macro2::TokenStream {
-    let field_implementations: Vec<proc_macro2::TokenStream> =
-        implement_get_system_prompt(&data_structure_fields);
+    let (impl_generics, type_generics, where_clause) = generics.split_for_impl();
+    let prompt_version: u32 = struct_attributes.get_prompt_version();
+    let empty_defaults: bool = struct_attributes.empty_defaults;
+    let collections_once: bool = prompt_version == 3;
+    let system_prompt: proc_macro2::TokenStream = match prompt_version {
+        1 | 3 => {
+            let field_implementations: Vec<proc_macro2::TokenStream> = implement_get_system_prompt(
+                &data_structure_fields,
+                empty_defaults,
+                collections_once,
+            );
+            let common_mistakes: proc_macro2::TokenStream =
+                implement_common_mistakes(&data_structure_fields);
+            quote! {
+                let template: String =
+                    ::secretary::prompt::prompt_template(self.prompt_template(), skipped_fields, &fields);
+                // The field lines and the template, nested Tasks grow it further
+                let mut prompt = String::with_capacity(Self::static_prompt_len() + template.len());
+                #(#field_implementations)*
+                #common_mistakes
+
+                prompt.push_str(&template);
+
+                prompt
+            }
+        }
+        _ => quote! {
+            ::secretary::prompt::render_system_prompt_v2(
+                &::secretary::extractors::descriptors_without(&fields, skipped_fields),
+                &::secretary::prompt::prompt_template(self.prompt_template(), skipped_fields, &fields),
+            )
+        },
+    };
+    let framing: proc_macro2::TokenStream = implement_framing(struct_attributes);
+    let static_prompt_len: usize = static_prompt_len(&data_structure_fields, struct_attributes);
     let distributed_field_processing: Vec<proc_macro2::TokenStream> =
-        implement_field_processing_code(&data_structure_fields);
+        implement_field_processing_code(&data_structure_fields, empty_defaults);
+    let prompt_template: proc_macro2::TokenStream = if empty_defaults || collections_once {
+        implement_prompt_template(&data_structure_fields, empty_defaults, collections_once)
+    } else {
+        proc_macro2::TokenStream::new()
+    };
+    let normalize_strings: Option<proc_macro2::TokenStream> = struct_attributes
+        .normalize_strings
+        .map(|normalization| normalization.to_tokens());
+    let field_descriptors: Vec<proc_macro2::TokenStream> = data_structure_fields
+        .iter()
+        .map(|field| {
+            let descriptor: proc_macro2::TokenStream = field.get_field_descriptor();
+            match &normalize_strings {
+                Some(normalization) => quote! {
+                    ::secretary::schema::FieldDescriptor {
+                        normalize_strings: Some(#normalization),
+                        ..#descriptor
+                    }
+                },
+                None => descriptor,
+            }
+        })
+        .collect();
+    let task_assertions: proc_macro2::TokenStream =
+        implement_task_assertions(&data_structure_fields);
+    let lazy_skipping: proc_macro2::TokenStream = implement_lazy_skipping(&data_structure_fields);
+    let lazy_binding: proc_macro2::TokenStream = implement_lazy_binding(&data_structure_fields);
+    let nested_schema_check: proc_macro2::TokenStream =
+        implement_nested_schema_check(&data_structure_fields);
+    let prompt_fields: proc_macro2::TokenStream =
+        implement_prompt_fields(&data_structure_fields, struct_attributes);
 
     quote! {
-        impl Task for #name {
+        impl #impl_generics Task for #name #type_generics #where_clause {
             fn get_system_prompt(&self) -> String {
-                let mut prompt = String::new();
-                #(#field_implementations)*
+                self.get_system_prompt_without_fields(&[])
+            }
 
-                prompt.push_str(&serde_json::to_string_pretty(&self).unwrap());
+            fn get_system_prompt_without_fields(&self, skipped_fields: &[String]) -> String {
+                #prompt_fields
+                #lazy_skipping
+                let prompt: String = { #system_prompt };
 
-                prompt
+                ::secretary::prompt::frame_prompt(prompt, Self::preamble(), Self::postamble())
+            }
+
+            #framing
+
+            fn prompt_version() -> u32 {
+                #prompt_version
+            }
+
+            fn static_prompt_len() -> usize {
+                #static_prompt_len
             }
 
-            fn get_system_prompts_for_distributed_generation(&self) -> Vec<(String, String)> {
-                let mut prompts: Vec<(String, String)> = Vec::new();
+            #prompt_template
+
+            fn get_distributed_field_prompts(&self) -> Vec<::secretary::distributed::FieldPrompt> {
+                let mut prompts: Vec<::secretary::distributed::FieldPrompt> = Vec::new();
                 let prefix = String::new();
 
                 #(#distributed_field_processing)*
 
+                for field_prompt in prompts.iter_mut() {
+                    field_prompt.prompt = ::secretary::prompt::frame_prompt(
+                        std::mem::take(&mut field_prompt.prompt),
+                        Self::preamble(),
+                        Self::postamble(),
+                    );
+                }
+
                 prompts
             }
+
+            fn field_descriptors() -> Vec<::secretary::schema::FieldDescriptor> {
+                #task_assertions
+                vec![#(#field_descriptors),*]
+            }
+
+            #lazy_binding
+
+            #nested_schema_check
+        }
+    }
+}
+
+/// Generates the line adding the paths of the `Lazy` fields to the skipped fields, for
+/// structs with `#[task(lazy)]` fields or nested Tasks that may have some, whose values also
+/// appear in the struct's JSON template.
+fn implement_lazy_skipping(
+    data_structure_fields: &[DataStructureField],
+) -> proc_macro2::TokenStream {
+    let may_have_lazy_fields: bool = data_structure_fields.iter().any(|field| {
+        field.is_lazy()
+            || matches!(
+                field.get_task_field_type(),
+                TaskFieldType::DirectTask | TaskFieldType::OptionTask
+            )
+    });
+    if !may_have_lazy_fields {
+        return proc_macro2::TokenStream::new();
+    }
+
+    quote! {
+        let skipped_fields: &[String] =
+            &::secretary::lazy::with_lazy_fields(skipped_fields, &fields);
+    }
+}
+
+/// Generates `Task::bind_lazy_fields`, which hands every `Lazy` field its distributed prompt
+/// and descriptor and recurses into nested and optional Tasks, or nothing for structs without
+/// either.
+fn implement_lazy_binding(
+    data_structure_fields: &[DataStructureField],
+) -> proc_macro2::TokenStream {
+    let bindings: Vec<proc_macro2::TokenStream> = data_structure_fields
+        .iter()
+        .filter_map(|field| {
+            let field_member = field.get_member();
+            if field.is_lazy() {
+                let processing: proc_macro2::TokenStream = implement_single_field_processing(field);
+                let descriptor: proc_macro2::TokenStream = field.get_field_descriptor();
+                return Some(quote! {
+                    {
+                        let mut prompts: Vec<::secretary::distributed::FieldPrompt> = Vec::new();
+                        let prefix = String::new();
+                        #processing
+                        if let Some(mut field_prompt) = prompts.pop() {
+                            field_prompt.prompt = ::secretary::prompt::frame_prompt(
+                                field_prompt.prompt,
+                                Self::preamble(),
+                                Self::postamble(),
+                            );
+                            self.#field_member.bind(binding, field_prompt, #descriptor);
+                        }
+                    }
+                });
+            }
+
+            // Spanned at the type, so a type without a Task impl is reported at the field only
+            let inner_type = get_task_inner_type(field.get_field_type(), field.get_task_field_type())?;
+            match field.get_task_field_type() {
+                TaskFieldType::DirectTask => Some(quote_spanned! {inner_type.span()=>
+                    <#inner_type as ::secretary::traits::Task>::bind_lazy_fields(&mut self.#field_member, binding);
+                }),
+                TaskFieldType::OptionTask => Some(quote_spanned! {inner_type.span()=>
+                    if let Some(item) = self.#field_member.as_mut() {
+                        <#inner_type as ::secretary::traits::Task>::bind_lazy_fields(item, binding);
+                    }
+                }),
+                _ => None,
+            }
+        })
+        .collect();
+    if bindings.is_empty() {
+        return proc_macro2::TokenStream::new();
+    }
+
+    quote! {
+        fn bind_lazy_fields(&mut self, binding: &::secretary::lazy::LazyBinding) {
+            #(#bindings)*
         }
     }
 }
 
-pub fn implement_new_method(name: &Ident) -> proc_macro2::TokenStream {
+/// Generates `Task::nested_schema_mismatches`, which checks the nested Tasks of every field,
+/// or nothing for structs without nested Tasks.
+fn implement_nested_schema_check(
+    data_structure_fields: &[DataStructureField],
+) -> proc_macro2::TokenStream {
+    let checks: Vec<proc_macro2::TokenStream> = data_structure_fields
+        .iter()
+        .filter_map(|field| field.get_nested_schema_check())
+        .collect();
+    if checks.is_empty() {
+        return proc_macro2::TokenStream::new();
+    }
+
     quote! {
-        impl #name {
+        fn nested_schema_mismatches() -> Vec<::secretary::schema_check::SchemaMismatch> {
+            let mut mismatches: Vec<::secretary::schema_check::SchemaMismatch> = Vec::new();
+            #(#checks)*
+            mismatches
+        }
+    }
+}
+
+/// Generates the checks that the nested Task types implement `Task`, which report a field of
+/// a type without `#[derive(Task)]` at the field instead of inside the generated code.
+///
+/// The checks live in a method rather than in a `const _` item so that they can name the
+/// struct's type parameters.
+fn implement_task_assertions(
+    data_structure_fields: &[DataStructureField],
+) -> proc_macro2::TokenStream {
+    let assertions: Vec<proc_macro2::TokenStream> = data_structure_fields
+        .iter()
+        .filter_map(|field| field.get_task_assertion())
+        .collect();
+    if assertions.is_empty() {
+        return proc_macro2::TokenStream::new();
+    }
+
+    quote! {
+        fn assert_task<T: ::secretary::traits::Task>() {}
+        #(#assertions)*
+    }
+}
+
+/// Generates the field descriptors `get_system_prompt_without_fields` reads for the lazy
+/// fields, the common mistakes and the template keys, or an empty list when a version 1 or 3
+/// prompt needs none of them, so plain structs do not build their descriptors for every prompt.
+fn implement_prompt_fields(
+    data_structure_fields: &[DataStructureField],
+    struct_attributes: &TaskStructAttributes,
+) -> proc_macro2::TokenStream {
+    let needs_fields: bool = !matches!(struct_attributes.get_prompt_version(), 1 | 3)
+        || struct_attributes.key_case != KeyCase::Snake
+        || data_structure_fields.iter().any(|field| {
+            field.is_lazy()
+                || field.has_negative_examples()
+                || *field.get_task_field_type() != TaskFieldType::Normal
+        });
+    if !needs_fields {
+        return quote! {
+            let fields: Vec<::secretary::schema::FieldDescriptor> = Vec::new();
+        };
+    }
+
+    quote! {
+        let fields: Vec<::secretary::schema::FieldDescriptor> = Self::field_descriptors();
+    }
+}
+
+/// Returns the characters of the system prompt known at compile time: the lines of the
+/// struct's own fields, the "Common mistakes to avoid" section of their negative examples and
+/// the preamble and postamble with the blank lines around them.
+pub fn static_prompt_len(
+    data_structure_fields: &[DataStructureField],
+    struct_attributes: &TaskStructAttributes,
+) -> usize {
+    let fields: usize = data_structure_fields
+        .iter()
+        .map(|field| field.get_static_prompt_len())
+        .sum();
+    let mistakes_header: usize = if data_structure_fields
+        .iter()
+        .any(|field| field.has_negative_examples())
+    {
+        "\nCommon mistakes to avoid:\n\n".len()
+    } else {
+        0
+    };
+    let framing: usize = [&struct_attributes.preamble, &struct_attributes.postamble]
+        .into_iter()
+        .flatten()
+        .map(|text| text.len() + "\n\n".len())
+        .sum();
+
+    fields + mistakes_header + framing
+}
+
+/// Generates `Task::preamble` and `Task::postamble` for the texts set on the struct.
+fn implement_framing(struct_attributes: &TaskStructAttributes) -> proc_macro2::TokenStream {
+    let preamble = struct_attributes.preamble.as_ref().map(|preamble| {
+        quote! {
+            fn preamble() -> Option<&'static str> {
+                Some(#preamble)
+            }
+        }
+    });
+    let postamble = struct_attributes.postamble.as_ref().map(|postamble| {
+        quote! {
+            fn postamble() -> Option<&'static str> {
+                Some(#postamble)
+            }
+        }
+    });
+
+    quote! {
+        #preamble
+        #postamble
+    }
+}
+
+/// Fails the derive when the static prompt is over the `max_prompt_chars` budget, naming the
+/// three longest instructions.
+pub fn check_prompt_budget(
+    name: &Ident,
+    data_structure_fields: &[DataStructureField],
+    struct_attributes: &TaskStructAttributes,
+) -> syn::Result<()> {
+    let Some(max_prompt_chars) = struct_attributes.max_prompt_chars else {
+        return Ok(());
+    };
+    let prompt_len: usize = static_prompt_len(data_structure_fields, struct_attributes);
+    if prompt_len <= max_prompt_chars {
+        return Ok(());
+    }
+
+    let mut longest: Vec<&DataStructureField> = data_structure_fields
+        .iter()
+        .filter(|field| field.get_instruction_len() > 0)
+        .collect();
+    longest.sort_by_key(|field| std::cmp::Reverse(field.get_instruction_len()));
+    let longest: Vec<String> = longest
+        .iter()
+        .take(3)
+        .map(|field| {
+            format!(
+                "{} ({} chars)",
+                field.get_field_name(),
+                field.get_instruction_len()
+            )
+        })
+        .collect();
+
+    Err(syn::Error::new(
+        name.span(),
+        format!(
+            "the static system prompt of {} is {} chars, over the max_prompt_chars budget of {}; longest instructions: {}",
+            name,
+            prompt_len,
+            max_prompt_chars,
+            longest.join(", ")
+        ),
+    ))
+}
+
+pub fn implement_new_method(name: &Ident, generics: &Generics) -> proc_macro2::TokenStream {
+    let (impl_generics, type_generics, where_clause) = generics.split_for_impl();
+
+    quote! {
+        impl #impl_generics #name #type_generics #where_clause {
             pub fn new() -> Self {
                 Self::default()
             }
@@ -45,71 +393,238 @@ pub fn implement_new_method(name: &Ident) -> proc_macro2::TokenStream {
     }
 }
 
+/// Generates `Task::prompt_template` for structs with `#[task(empty_defaults)]`, where empty
+/// Task collections and `None` optional Tasks show one example element in the template, and
+/// for version 3 prompts, where every Task collection shows exactly one.
+fn implement_prompt_template(
+    data_structure_fields: &[DataStructureField],
+    empty_defaults: bool,
+    collections_once: bool,
+) -> proc_macro2::TokenStream {
+    let examples: Vec<proc_macro2::TokenStream> = data_structure_fields
+        .iter()
+        .filter_map(|field| {
+            let field_member = field.get_member();
+            let template_slot = field.get_template_slot();
+            let task_field_type = field.get_task_field_type();
+            let inner_type = get_task_inner_type(field.get_field_type(), task_field_type)?;
+            let example = quote! {
+                serde_json::from_str::<serde_json::Value>(
+                    &<#inner_type as Task>::prompt_template(&<#inner_type as Default>::default()),
+                )
+                .unwrap_or(serde_json::Value::Null)
+            };
+
+            let (value, is_empty) = match task_field_type {
+                TaskFieldType::VecTask => (
+                    quote! { serde_json::Value::Array(vec![#example]) },
+                    quote! { self.#field_member.is_empty() },
+                ),
+                TaskFieldType::OptionTask if empty_defaults => {
+                    return Some(quote! {
+                        if self.#field_member.is_none() {
+                            #template_slot = #example;
+                        }
+                    });
+                }
+                TaskFieldType::HashMapTask | TaskFieldType::BTreeMapTask => (
+                    quote! {
+                        serde_json::Value::Object(
+                            serde_json::Map::from_iter([("key".to_string(), #example)]),
+                        )
+                    },
+                    quote! { self.#field_member.is_empty() },
+                ),
+                TaskFieldType::OptionTask | TaskFieldType::Normal | TaskFieldType::DirectTask => {
+                    return None;
+                }
+            };
+
+            // Version 3 replaces whatever the collection holds
+            if collections_once {
+                Some(quote! { #template_slot = #value; })
+            } else {
+                Some(quote! {
+                    if #is_empty {
+                        #template_slot = #value;
+                    }
+                })
+            }
+        })
+        .collect();
+
+    quote! {
+        fn prompt_template(&self) -> String {
+            let mut template: serde_json::Value =
+                serde_json::to_value(self).unwrap_or(serde_json::Value::Null);
+            #(#examples)*
+
+            serde_json::to_string_pretty(&template).unwrap_or_default()
+        }
+    }
+}
+
+/// Generates the "Common mistakes to avoid" section of a version 1 prompt, or nothing when no
+/// field has negative examples. Nested Tasks list their own in their blocks.
+fn implement_common_mistakes(
+    data_structure_fields: &[DataStructureField],
+) -> proc_macro2::TokenStream {
+    if !data_structure_fields
+        .iter()
+        .any(|field| field.has_negative_examples())
+    {
+        return proc_macro2::TokenStream::new();
+    }
+
+    quote! {
+        let own_fields: Vec<::secretary::schema::FieldDescriptor> =
+            ::secretary::extractors::descriptors_without(&fields, skipped_fields)
+            .into_iter()
+            .map(|field| ::secretary::schema::FieldDescriptor { children: Vec::new(), ..field })
+            .collect();
+        prompt.push('\n');
+        prompt.push_str(&::secretary::prompt::render_common_mistakes(&own_fields, &[]));
+        prompt.push('\n');
+    }
+}
+
+/// Generates the field lines and nested Task blocks of a version 1 or 3 prompt. With
+/// `collections_once`, as in version 3, collections of nested Tasks are described by one
+/// element built from the element type, whatever the instance holds.
 fn implement_get_system_prompt(
-    data_structure_fields: &Vec<DataStructureField>,
+    data_structure_fields: &[DataStructureField],
+    empty_defaults: bool,
+    collections_once: bool,
 ) -> Vec<proc_macro2::TokenStream> {
     data_structure_fields
         .iter()
         .map(|field| {
-            let field_name_ident =
-                syn::Ident::new(field.get_field_name(), proc_macro2::Span::call_site());
+            let field_member =
+                field.get_member();
             let field_prompt = field.get_field_prompt();
-            let field_name = field.get_field_name();
+            let field_name = field.get_field_label();
+            let field_path = field.get_field_name();
+            // Calls name the trait so that a type without it is reported at the field, once
+            let nested = get_task_inner_type(field.get_field_type(), field.get_task_field_type())
+                .map(|inner_type| quote! { <#inner_type as Task> });
+            // An element built from the element type, which describes an empty field with empty
+            // defaults, every collection from version 3 on and an absent optional task
+            let example_prompt = get_task_inner_type(field.get_field_type(), field.get_task_field_type())
+                .map(|inner_type| quote! { #nested::get_system_prompt(&<#inner_type as Default>::default()) });
+            // The block headers are known here, so they are literals rather than formatted at runtime
+            let collection_type = if matches!(field.get_task_field_type(), TaskFieldType::HashMapTask) { "HashMap" } else { "BTreeMap" };
+            let task_header = format!("\n--- {} Task Details ---\n", field_name);
+            let task_footer = format!("--- End of {} Task ---\n\n", field_name);
+            let collection_header = format!("\n--- {} Collection (any number of items) ---\n", field_name);
+            let collection_footer = format!("--- End of {} Collection ---\n\n", field_name);
+            let present_header = format!("\n--- {} Optional Task (Present) ---\n", field_name);
+            let absent_header = format!("\n--- {} Optional Task (null when absent) ---\n", field_name);
+            let optional_footer = format!("--- End of {} Optional Task ---\n\n", field_name);
+            let map_header = format!("\n--- {} {} (any number of entries) ---\n", field_name, collection_type);
+            let map_footer = format!("--- End of {} {} ---\n\n", field_name, collection_type);
+            let map_empty = format!(" ({} is empty)\n", collection_type);
 
             match field.get_task_field_type() {
                 TaskFieldType::Normal => {
                     quote! {
-                        prompt.push_str(#field_prompt);
+                        if !skipped_fields.iter().any(|skipped| skipped == #field_path) {
+                            prompt.push_str(#field_prompt);
+                        }
                     }
                 }
                 TaskFieldType::DirectTask => {
                     quote! {
-                        prompt.push_str(&format!("\n--- {} Task Details ---\n", #field_name));
-                        prompt.push_str(&self.#field_name_ident.get_system_prompt());
-                        prompt.push_str(&format!("--- End of {} Task ---\n\n", #field_name));
+                        prompt.push_str(#task_header);
+                        prompt.push_str(&#nested::get_system_prompt_without_fields(
+                            &self.#field_member,
+                            &::secretary::extractors::nested_paths(skipped_fields, #field_path),
+                        ));
+                        prompt.push_str(#task_footer);
                     }
                 }
                 TaskFieldType::VecTask => {
+                    let described_once = quote! {
+                        prompt.push_str(#collection_header);
+                        prompt.push_str(&#example_prompt);
+                        prompt.push('\n');
+                        prompt.push_str(#collection_footer);
+                    };
+                    if collections_once {
+                        return quote! {
+                            prompt.push_str(#field_prompt);
+                            #described_once
+                        };
+                    }
+                    let empty = if empty_defaults {
+                        described_once
+                    } else {
+                        quote! {
+                            prompt.push_str(" (Collection is empty)\n");
+                        }
+                    };
                     quote! {
                         prompt.push_str(#field_prompt);
-                        if !self.#field_name_ident.is_empty() {
-                            prompt.push_str(&format!("\n--- {} Collection (any number of items) ---\n", #field_name));
-                            for (index, item) in self.#field_name_ident.iter().enumerate() {
-                                prompt.push_str(&item.get_system_prompt());
+                        if !self.#field_member.is_empty() {
+                            prompt.push_str(#collection_header);
+                            for (index, item) in self.#field_member.iter().enumerate() {
+                                prompt.push_str(&#nested::get_system_prompt(item));
                                 prompt.push('\n');
                             }
-                            prompt.push_str(&format!("--- End of {} Collection ---\n\n", #field_name));
+                            prompt.push_str(#collection_footer);
                         } else {
-                            prompt.push_str(&format!(" (Collection is empty)\n"));
+                            #empty
                         }
                     }
                 }
                 TaskFieldType::OptionTask => {
                     quote! {
                         prompt.push_str(#field_prompt);
-                        if let Some(ref item) = self.#field_name_ident {
-                            prompt.push_str(&format!("\n--- {} Optional Task (Present) ---\n", #field_name));
-                            prompt.push_str(&item.get_system_prompt());
-                            prompt.push_str(&format!("--- End of {} Optional Task ---\n\n", #field_name));
+                        if let Some(ref item) = self.#field_member {
+                            prompt.push_str(#present_header);
+                            prompt.push_str(&#nested::get_system_prompt(item));
                         } else {
-                            prompt.push_str(&format!(" (Optional field is None)\n"));
+                            prompt.push_str(#absent_header);
+                            prompt.push_str(&#example_prompt);
                         }
+                        prompt.push('\n');
+                        prompt.push_str(#optional_footer);
                     }
                 }
                 TaskFieldType::HashMapTask | TaskFieldType::BTreeMapTask => {
-                    let collection_type = if matches!(field.get_task_field_type(), TaskFieldType::HashMapTask) { "HashMap" } else { "BTreeMap" };
+                    let described_once = quote! {
+                        prompt.push_str(#map_header);
+                        prompt.push_str("  Key '<key>': ");
+                        prompt.push_str(&#example_prompt);
+                        prompt.push('\n');
+                        prompt.push_str(#map_footer);
+                    };
+                    if collections_once {
+                        return quote! {
+                            prompt.push_str(#field_prompt);
+                            #described_once
+                        };
+                    }
+                    let empty = if empty_defaults {
+                        described_once
+                    } else {
+                        quote! {
+                            prompt.push_str(#map_empty);
+                        }
+                    };
                     quote! {
                         prompt.push_str(#field_prompt);
-                        if !self.#field_name_ident.is_empty() {
-                            prompt.push_str(&format!("\n--- {} {} ({} entries) ---\n", #field_name, #collection_type, self.#field_name_ident.len()));
-                            for (key, value) in &self.#field_name_ident {
-                                prompt.push_str(&format!("  Key '{}': ", key));
-                                prompt.push_str(&value.get_system_prompt());
+                        if !self.#field_member.is_empty() {
+                            use ::std::fmt::Write as _;
+
+                            let _ = write!(prompt, "\n--- {} {} ({} entries) ---\n", #field_name, #collection_type, self.#field_member.len());
+                            for (key, value) in &self.#field_member {
+                                let _ = write!(prompt, "  Key '{}': ", key);
+                                prompt.push_str(&#nested::get_system_prompt(value));
                                 prompt.push('\n');
                             }
-                            prompt.push_str(&format!("--- End of {} {} ---\n\n", #field_name, #collection_type));
+                            prompt.push_str(#map_footer);
                         } else {
-                            prompt.push_str(&format!(" ({} is empty)\n", #collection_type));
+                            #empty
                         }
                     }
                 }
@@ -119,133 +634,214 @@ fn implement_get_system_prompt(
 }
 
 pub fn implement_field_processing_code(
-    data_structure_fields: &Vec<DataStructureField>,
+    data_structure_fields: &[DataStructureField],
+    empty_defaults: bool,
 ) -> Vec<proc_macro2::TokenStream> {
     data_structure_fields
         .iter()
+        // Lazy fields are requested on first access, see `implement_lazy_binding`
+        .filter(|field| !field.is_lazy())
         .map(|field| {
-            let field_name_ident = syn::Ident::new(field.get_field_name(), proc_macro2::Span::call_site());
+            let processing: proc_macro2::TokenStream = implement_single_field_processing(field);
+            let field_member = field.get_member();
             let field_name_str = field.get_field_name();
-            let field_task_type = field.get_task_field_type();
-            
-            match field_task_type {
-                TaskFieldType::Normal => {
-                    // Handle primitive fields with their instructions
-                    let field_prompt = field.get_field_prompt();
-                    
-                    quote! {
-                        {
-                            let field_path = if prefix.is_empty() {
-                                #field_name_str.to_string()
+            let is_empty = match field.get_task_field_type() {
+                TaskFieldType::VecTask
+                | TaskFieldType::HashMapTask
+                | TaskFieldType::BTreeMapTask => {
+                    quote! { self.#field_member.is_empty() }
+                }
+                TaskFieldType::OptionTask => quote! { self.#field_member.is_none() },
+                TaskFieldType::Normal | TaskFieldType::DirectTask => return processing,
+            };
+            if !empty_defaults {
+                return processing;
+            }
+
+            // An empty collection has no elements to request one by one, so it is requested
+            // as a whole
+            let descriptor = field.get_field_descriptor();
+            let distributed_settings = field.get_distributed_settings();
+            quote! {
+                if #is_empty {
+                    let field_path = if prefix.is_empty() {
+                        #field_name_str.to_string()
+                    } else {
+                        format!("{}.{}", prefix, #field_name_str)
+                    };
+                    prompts.push(::secretary::distributed::FieldPrompt {
+                        #distributed_settings
+                        ..::secretary::distributed::whole_field_prompt(field_path, &#descriptor)
+                    });
+                } else #processing
+            }
+        })
+        .collect()
+}
+
+/// Generates the distributed prompts of one field, from its current value.
+fn implement_single_field_processing(field: &DataStructureField) -> proc_macro2::TokenStream {
+    let field_member = field.get_member();
+    let field_name_str = field.get_field_name();
+    let field_label = field.get_field_label();
+    let field_task_type = field.get_task_field_type();
+    let nested = get_task_inner_type(field.get_field_type(), field_task_type)
+        .map(|inner_type| quote! { <#inner_type as Task> });
+
+    match field_task_type {
+        TaskFieldType::Normal => {
+            // Handle primitive fields with their instructions
+            let field_line: String = format!("- {}\n", field.get_field_prompt());
+            let distributed_settings = field.get_distributed_settings();
+            // Only this field's negative examples go into its prompt
+            let common_mistakes = if field.has_negative_examples() {
+                let descriptor = field.get_field_descriptor();
+                quote! {
+                    prompt.push_str(&::secretary::prompt::render_common_mistakes(&[#descriptor], &[]));
+                }
+            } else {
+                proc_macro2::TokenStream::new()
+            };
+            // Lists of strings, numbers and booleans are asked for one item tag per value
+            let list_answer = if field.is_collection() {
+                let descriptor = field.get_field_descriptor();
+                quote! {
+                    prompt.push_str(::secretary::distributed::list_answer_instruction(&#descriptor));
+                }
+            } else {
+                proc_macro2::TokenStream::new()
+            };
+
+            quote! {
+                {
+                    let field_path = if prefix.is_empty() {
+                        #field_name_str.to_string()
+                    } else {
+                        format!("{}.{}", prefix, #field_name_str)
+                    };
+
+                    let mut prompt = String::with_capacity(
+                        ::secretary::distributed::FIELD_ANSWER_INSTRUCTION.len() + #field_line.len(),
+                    );
+                    prompt.push_str(::secretary::distributed::FIELD_ANSWER_INSTRUCTION);
+                    prompt.push_str(#field_line);
+                    #list_answer
+                    #common_mistakes
+                    prompts.push(::secretary::distributed::FieldPrompt {
+                        field_path,
+                        prompt,
+                        #distributed_settings
+                        members: Vec::new(),
+                    });
+                }
+            }
+        }
+        TaskFieldType::DirectTask => {
+            // Handle Task struct fields by delegating to their implementation
+            quote! {
+                {
+                    let field_path = if prefix.is_empty() {
+                        #field_name_str.to_string()
+                    } else {
+                        format!("{}.{}", prefix, #field_name_str)
+                    };
+
+                    // Recursively call the nested Task's distributed generation
+                    let nested_prompts = #nested::get_distributed_field_prompts(&self.#field_member);
+
+                    for mut nested_prompt in nested_prompts {
+                        // The field of a newtype has no path of its own
+                        if !field_path.is_empty() {
+                            nested_prompt.field_path = if nested_prompt.field_path.is_empty() {
+                                field_path.clone()
                             } else {
-                                format!("{}.{}", prefix, #field_name_str)
+                                format!("{}.{}", field_path, nested_prompt.field_path)
                             };
-                            
-                            let mut prompt = String::new();
-                            prompt.push_str("Output a value according to criteria and wrap them in <result></result>.\n");
-                            prompt.push_str(&format!("- {}\n", #field_prompt));
-                            prompts.push((field_path, prompt));
                         }
+                        prompts.push(nested_prompt);
                     }
-                },
-                TaskFieldType::DirectTask => {
-                    // Handle Task struct fields by delegating to their implementation
-                    quote! {
-                        {
-                            let field_path = if prefix.is_empty() {
-                                #field_name_str.to_string()
+                }
+            }
+        }
+        TaskFieldType::VecTask => {
+            // Handle Vec<Task> fields
+            quote! {
+                {
+                    let field_path = if prefix.is_empty() {
+                        #field_name_str.to_string()
+                    } else {
+                        format!("{}.{}", prefix, #field_name_str)
+                    };
+
+                    for (index, item) in self.#field_member.iter().enumerate() {
+                        let item_path = format!("{}[{}]", field_path, index);
+                        // Every element asks the same questions, so each says which item it is for
+                        let note = ::secretary::distributed::collection_item_note(#field_label, index);
+                        let nested_prompts = #nested::get_distributed_field_prompts(item);
+                        for mut nested_prompt in nested_prompts {
+                            nested_prompt.field_path = if nested_prompt.field_path.is_empty() {
+                                item_path.clone()
                             } else {
-                                format!("{}.{}", prefix, #field_name_str)
+                                format!("{}.{}", item_path, nested_prompt.field_path)
                             };
-                            
-                            // Recursively call the nested Task's distributed generation
-                            let nested_prompts = self.#field_name_ident.get_system_prompts_for_distributed_generation();
-                            
-                            for (nested_path, nested_prompt) in nested_prompts {
-                                let full_path = if nested_path.is_empty() {
+                            nested_prompt.prompt.push_str(&note);
+                            prompts.push(nested_prompt);
+                        }
+                    }
+                }
+            }
+        }
+        TaskFieldType::OptionTask => {
+            // Handle Option<Task> fields
+            quote! {
+                {
+                    let field_path = if prefix.is_empty() {
+                        #field_name_str.to_string()
+                    } else {
+                        format!("{}.{}", prefix, #field_name_str)
+                    };
+
+                    if let Some(ref item) = self.#field_member {
+                        let nested_prompts = #nested::get_distributed_field_prompts(item);
+                        for mut nested_prompt in nested_prompts {
+                            if !field_path.is_empty() {
+                                nested_prompt.field_path = if nested_prompt.field_path.is_empty() {
                                     field_path.clone()
                                 } else {
-                                    format!("{}.{}", field_path, nested_path)
+                                    format!("{}.{}", field_path, nested_prompt.field_path)
                                 };
-                                prompts.push((full_path, nested_prompt));
-                            }
-                        }
-                    }
-                },
-                TaskFieldType::VecTask => {
-                    // Handle Vec<Task> fields
-                    quote! {
-                        {
-                            let field_path = if prefix.is_empty() {
-                                #field_name_str.to_string()
-                            } else {
-                                format!("{}.{}", prefix, #field_name_str)
-                            };
-                            
-                            for (index, item) in self.#field_name_ident.iter().enumerate() {
-                                let item_path = format!("{}[{}]", field_path, index);
-                                let nested_prompts = item.get_system_prompts_for_distributed_generation();
-                                for (nested_path, nested_prompt) in nested_prompts {
-                                    let full_path = if nested_path.is_empty() {
-                                        item_path.clone()
-                                    } else {
-                                        format!("{}.{}", item_path, nested_path)
-                                    };
-                                    prompts.push((full_path, nested_prompt));
-                                }
-                            }
-                        }
-                    }
-                },
-                TaskFieldType::OptionTask => {
-                    // Handle Option<Task> fields
-                    quote! {
-                        {
-                            let field_path = if prefix.is_empty() {
-                                #field_name_str.to_string()
-                            } else {
-                                format!("{}.{}", prefix, #field_name_str)
-                            };
-                            
-                            if let Some(ref item) = self.#field_name_ident {
-                                let nested_prompts = item.get_system_prompts_for_distributed_generation();   
-                                for (nested_path, nested_prompt) in nested_prompts {
-                                    let full_path = if nested_path.is_empty() {
-                                        field_path.clone()
-                                    } else {
-                                        format!("{}.{}", field_path, nested_path)
-                                    };
-                                    prompts.push((full_path, nested_prompt));
-                                }
                             }
+                            prompts.push(nested_prompt);
                         }
                     }
-                },
-                TaskFieldType::HashMapTask | TaskFieldType::BTreeMapTask => {
-                    // Handle HashMap<K, Task> and BTreeMap<K, Task> fields
-                    quote! {
-                        {
-                            let field_path = if prefix.is_empty() {
-                                #field_name_str.to_string()
+                }
+            }
+        }
+        TaskFieldType::HashMapTask | TaskFieldType::BTreeMapTask => {
+            // Handle HashMap<K, Task> and BTreeMap<K, Task> fields
+            quote! {
+                {
+                    let field_path = if prefix.is_empty() {
+                        #field_name_str.to_string()
+                    } else {
+                        format!("{}.{}", prefix, #field_name_str)
+                    };
+                    for (key, value) in &self.#field_member {
+                        let item_path = format!("{}[{}]", field_path, key);
+                        let note = ::secretary::distributed::map_entry_note(#field_label, &key.to_string());
+                        let nested_prompts = #nested::get_distributed_field_prompts(value);
+                        for mut nested_prompt in nested_prompts {
+                            nested_prompt.field_path = if nested_prompt.field_path.is_empty() {
+                                item_path.clone()
                             } else {
-                                format!("{}.{}", prefix, #field_name_str)
+                                format!("{}.{}", item_path, nested_prompt.field_path)
                             };
-                            for (key, value) in &self.#field_name_ident {
-                                let item_path = format!("{}[{}]", field_path, key);
-                                let nested_prompts = value.get_system_prompts_for_distributed_generation();
-                                for (nested_path, nested_prompt) in nested_prompts {
-                                    let full_path = if nested_path.is_empty() {
-                                        item_path.clone()
-                                    } else {
-                                        format!("{}.{}", item_path, nested_path)
-                                    };
-                                    prompts.push((full_path, nested_prompt));
-                                }
-                            }
+                            nested_prompt.prompt.push_str(&note);
+                            prompts.push(nested_prompt);
                         }
                     }
-                },
+                }
             }
-        })
-        .collect()
+        }
+    }
 }
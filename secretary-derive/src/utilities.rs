@@ -1,83 +1,215 @@
-use syn::{Field, Type};
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Attribute, Field, Type};
 
-use crate::field_attributes::task::TaskFieldAttributes;
+use crate::{field_attributes::task::TaskFieldAttributes, struct_attributes::TaskStructAttributes};
 
-pub fn get_instruction(field: &Field) -> Option<String> {
+/// Parses the `#[task(...)]` attribute of a field.
+/// Fields without the attribute get empty attributes; malformed attributes are an error.
+pub fn get_task_attributes(field: &Field) -> syn::Result<TaskFieldAttributes> {
     for attr in field.attrs.iter() {
         if attr.path().is_ident("task") {
-            if let Ok(result) = attr.parse_args::<TaskFieldAttributes>() {
-                return result.instruction;
-            }
+            return attr.parse_args::<TaskFieldAttributes>();
         }
     }
 
-    None
+    Ok(TaskFieldAttributes::default())
 }
 
-pub fn get_field_prompt(name: String, instruction: String, json_data_type: String) -> String {
-    format!("{}: {}, {}\n", name, instruction, json_data_type)
+/// Parses the `#[task(...)]` attribute of a struct.
+/// Structs without the attribute get empty attributes; malformed attributes are an error.
+pub fn get_struct_attributes(attrs: &[Attribute]) -> syn::Result<TaskStructAttributes> {
+    for attr in attrs {
+        if attr.path().is_ident("task") {
+            return attr.parse_args::<TaskStructAttributes>();
+        }
+    }
+
+    Ok(TaskStructAttributes::default())
 }
 
 pub fn convert_to_json_type(rust_type: &Type) -> String {
     match rust_type {
-        Type::Array(_) => format!("JSON Array"),
-        Type::Slice(_) => format!("JSON Array"),
+        Type::Array(_) => "JSON Array".to_string(),
+        Type::Slice(_) => "JSON Array".to_string(),
         Type::Path(path) => {
             if let Some(last_segment) = path.path.segments.last() {
                 let type_name = last_segment.ident.to_string();
 
                 match type_name.as_str() {
                     // Primitive types
-                    "i32" | "i64" | "isize" => format!("JSON Number"),
-                    "u8" | "u16" | "u32" | "u64" | "usize" => format!("JSON Number"),
-                    "f32" | "f64" => format!("JSON Number"),
-                    "bool" => format!("JSON Boolean"),
-                    "String" => format!("JSON String"),
+                    "i32" | "i64" | "isize" => "JSON Number".to_string(),
+                    "u8" | "u16" | "u32" | "u64" | "usize" => "JSON Number".to_string(),
+                    "f32" | "f64" => "JSON Number".to_string(),
+                    "bool" => "JSON Boolean".to_string(),
+                    "String" => "JSON String".to_string(),
 
                     // Generic types
                     "Option" => {
-                        if let syn::PathArguments::AngleBracketed(args) = &last_segment.arguments {
-                            if let Some(syn::GenericArgument::Type(inner_type)) = args.args.first()
-                            {
-                                let inner_json_type = convert_to_json_type(inner_type);
-                                return format!("{} or JSON Null", inner_json_type);
-                            }
+                        if let syn::PathArguments::AngleBracketed(args) = &last_segment.arguments
+                            && let Some(syn::GenericArgument::Type(inner_type)) = args.args.first()
+                        {
+                            let inner_json_type = convert_to_json_type(inner_type);
+                            return format!("{} or JSON Null", inner_json_type);
                         }
-                        format!("JSON String or JSON Null")
+                        "JSON String or JSON Null".to_string()
                     }
                     "Vec" => {
-                        if let syn::PathArguments::AngleBracketed(args) = &last_segment.arguments {
-                            if let Some(syn::GenericArgument::Type(inner_type)) = args.args.first()
-                            {
-                                let inner_json_type = convert_to_json_type(inner_type);
-                                return format!("{}(s) in a JSON Array", inner_json_type);
-                            }
+                        if let syn::PathArguments::AngleBracketed(args) = &last_segment.arguments
+                            && let Some(syn::GenericArgument::Type(inner_type)) = args.args.first()
+                        {
+                            let inner_json_type = convert_to_json_type(inner_type);
+                            return format!("{}(s) in a JSON Array", inner_json_type);
                         }
-                        format!("JSON Array")
+                        "JSON Array".to_string()
                     }
-                    "HashMap" | "BTreeMap" => format!("JSON Object"),
+                    "HashMap" | "BTreeMap" => "JSON Object".to_string(),
                     "HashSet" | "BTreeSet" => {
-                        if let syn::PathArguments::AngleBracketed(args) = &last_segment.arguments {
-                            if let Some(syn::GenericArgument::Type(inner_type)) = args.args.first()
-                            {
-                                let inner_json_type = convert_to_json_type(inner_type);
-                                return format!("JSON Array of {}", inner_json_type.to_lowercase());
-                            }
+                        if let syn::PathArguments::AngleBracketed(args) = &last_segment.arguments
+                            && let Some(syn::GenericArgument::Type(inner_type)) = args.args.first()
+                        {
+                            let inner_json_type = convert_to_json_type(inner_type);
+                            return format!("JSON Array of {}", inner_json_type.to_lowercase());
                         }
-                        format!("JSON Array")
+                        "JSON Array".to_string()
                     }
 
                     // Custom types (potential Task implementors)
-                    _ => format!("JSON Object"),
+                    _ => "JSON Object".to_string(),
                 }
             } else {
-                format!("JSON Object")
+                "JSON Object".to_string()
             }
         }
         Type::Reference(reference) => convert_to_json_type(&reference.elem),
         Type::Tuple(_) => {
-            format!("JSON Array") // Rust tuples map to JSON arrays
+            "JSON Array".to_string() // Rust tuples map to JSON arrays
+        }
+        _ => "JSON Null".to_string(), // Default case for unknown types
+    }
+}
+
+/// Returns whether the outermost type is an `Option`.
+pub fn is_option_type(rust_type: &Type) -> bool {
+    match rust_type {
+        Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Option"),
+        Type::Reference(reference) => is_option_type(&reference.elem),
+        _ => false,
+    }
+}
+
+/// Returns whether a type is a `Vec`, optionally inside an `Option`.
+pub fn is_list_type(rust_type: &Type) -> bool {
+    let rust_type: String = quote!(#rust_type).to_string().replace(' ', "");
+    let inner: &str = rust_type
+        .strip_prefix("Option<")
+        .and_then(|wrapped| wrapped.strip_suffix('>'))
+        .unwrap_or(&rust_type);
+
+    inner.starts_with("Vec<")
+}
+
+/// Returns the value type of a `Lazy<T>` field.
+pub fn get_lazy_inner_type(rust_type: &Type) -> Option<&Type> {
+    let Type::Path(path) = rust_type else {
+        return None;
+    };
+    let last_segment: &syn::PathSegment = path.path.segments.last()?;
+    if last_segment.ident != "Lazy" {
+        return None;
+    }
+
+    match &last_segment.arguments {
+        syn::PathArguments::AngleBracketed(args) => match args.args.first() {
+            Some(syn::GenericArgument::Type(inner_type)) => Some(inner_type),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Returns whether a type holds text: a `String`, optionally inside an `Option`, a `Vec`, a
+/// `HashSet` or a `BTreeSet`.
+pub fn is_text_type(rust_type: &Type) -> bool {
+    let rust_type: String = quote!(#rust_type).to_string().replace(' ', "");
+    let mut inner: &str = rust_type.as_str();
+    for wrapper in ["Option<", "Vec<", "HashSet<", "BTreeSet<"] {
+        if let Some(wrapped) = inner
+            .strip_prefix(wrapper)
+            .and_then(|wrapped| wrapped.strip_suffix('>'))
+        {
+            inner = wrapped;
         }
-        _ => format!("JSON Null"), // Default case for unknown types
+    }
+
+    inner == "String"
+}
+
+/// Maps a Rust type to the `secretary::schema::JsonType` variant of its serialized value.
+/// `Option<T>` maps to the JSON type of `T`; optionality is tracked separately.
+pub fn convert_to_json_kind(rust_type: &Type) -> TokenStream {
+    let variant: TokenStream = match rust_type {
+        Type::Array(_) | Type::Slice(_) | Type::Tuple(_) => quote! { Array },
+        Type::Reference(reference) => return convert_to_json_kind(&reference.elem),
+        Type::Path(path) => match path.path.segments.last() {
+            Some(last_segment) => match last_segment.ident.to_string().as_str() {
+                "i8" | "i16" | "i32" | "i64" | "isize" | "u8" | "u16" | "u32" | "u64" | "usize"
+                | "f32" | "f64" => quote! { Number },
+                "bool" => quote! { Boolean },
+                "String" | "char" | "str" => quote! { String },
+                "Vec" | "HashSet" | "BTreeSet" => quote! { Array },
+                "Option" => {
+                    if let syn::PathArguments::AngleBracketed(args) = &last_segment.arguments
+                        && let Some(syn::GenericArgument::Type(inner_type)) = args.args.first()
+                    {
+                        return convert_to_json_kind(inner_type);
+                    }
+                    quote! { Any }
+                }
+                _ => quote! { Object },
+            },
+            None => quote! { Any },
+        },
+        _ => quote! { Any },
+    };
+
+    quote! { ::secretary::schema::JsonType::#variant }
+}
+
+/// Maps the element type of a collection to the `secretary::schema::JsonType` variant of its
+/// serialized elements. Non-collection types map to `Any`.
+pub fn convert_to_json_item_kind(rust_type: &Type) -> TokenStream {
+    let item_type: Option<&Type> = match rust_type {
+        Type::Array(array) => Some(&array.elem),
+        Type::Slice(slice) => Some(&slice.elem),
+        Type::Reference(reference) => return convert_to_json_item_kind(&reference.elem),
+        Type::Path(path) => match path.path.segments.last() {
+            Some(last_segment) => match last_segment.ident.to_string().as_str() {
+                "Option" | "Vec" | "HashSet" | "BTreeSet" => match &last_segment.arguments {
+                    syn::PathArguments::AngleBracketed(args) => match args.args.first() {
+                        Some(syn::GenericArgument::Type(inner_type)) => {
+                            if last_segment.ident == "Option" {
+                                return convert_to_json_item_kind(inner_type);
+                            }
+                            Some(inner_type)
+                        }
+                        _ => None,
+                    },
+                    _ => None,
+                },
+                _ => None,
+            },
+            None => None,
+        },
+        _ => None,
+    };
+
+    match item_type {
+        Some(item_type) => convert_to_json_kind(item_type),
+        None => quote! { ::secretary::schema::JsonType::Any },
     }
 }
@@ -0,0 +1,178 @@
+//! Adaptive prompting for models with small context windows.
+//!
+//! When a provider declares its `max_context_tokens`, adaptive generation estimates the size
+//! of each candidate request shape and picks the richest one that fits:
+//!
+//! 1. `Full` - the regular system prompt with every field instruction.
+//! 2. `Compact` - field names and JSON types only, with a minified JSON template.
+//! 3. `Distributed` - one small request per field, as in `fields_generate_data`.
+//!
+//! If even the largest per-field request does not fit, `SecretaryError::ContextTooSmall`
+//! is returned instead of sending a request the server would silently truncate.
+//!
+//! # Field importance
+//!
+//! Fields can be marked `#[task(importance = "critical" | "normal" | "low")]`. With the
+//! `Full` and `Compact` strategies, normal and low fields ride along in the single JSON
+//! request, while every critical field is extracted again with a focused request of its own.
+//! Partial extraction fails with `SecretaryError::CriticalFieldsMissing` rather than
+//! defaulting a critical field, and tolerates gaps in the other fields.
+//!
+//! ```no_run
+//! use secretary::Task;
+//! use secretary::SecretaryError;
+//! use secretary::llm_providers::openai::OpenAILLM;
+//! use secretary::traits::GenerateData;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! struct Invoice {
+//!     #[task(instruction = "Extract the invoice total", importance = "critical")]
+//!     pub total: f64,
+//!     #[task(instruction = "Extract the customer name")]
+//!     pub customer: String,
+//!     #[task(instruction = "Extract any notes", importance = "low")]
+//!     pub notes: Option<String>,
+//! }
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+//! let llm = OpenAILLM::new("https://api.openai.com/v1", "your-api-key", "gpt-4o-mini")?;
+//!
+//! // One JSON request for every field, and one request for the critical field only
+//! let result = llm.generate_data_adaptive(&Invoice::new(), "Invoice for ACME, total 1,250.50", &vec![])?;
+//! println!("{:?}, extracted again: {:?}", result.data, result.metadata.per_field_requests);
+//!
+//! // A missing low-importance field is tolerated, a missing critical field is not
+//! match llm.generate_partial_data(&Invoice::new(), "Invoice for ACME", &vec![]) {
+//!     Ok(partial) => println!("{:?}", partial.data),
+//!     Err(error) => match error.downcast_ref::<SecretaryError>() {
+//!         Some(SecretaryError::CriticalFieldsMissing { fields }) => println!("Missing {:?}", fields),
+//!         _ => return Err(error),
+//!     },
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use serde::Serialize;
+
+use crate::{SecretaryError, message::Message, tokens::estimate_tokens, traits::Task};
+
+/// The request shape chosen by adaptive generation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum PromptStrategy {
+    /// A single request with the full system prompt.
+    Full,
+    /// A single request with field names and types instead of instructions.
+    Compact,
+    /// One request per field.
+    Distributed,
+}
+
+/// Chooses the richest prompt strategy whose estimated size fits the context window.
+///
+/// # Arguments
+///
+/// * `task` - The Task to generate prompts for
+/// * `target` - The natural language text to extract data from
+/// * `additional_instructions` - Extra instructions to guide the extraction process
+/// * `max_context_tokens` - The provider's context window, or `None` if unknown
+///
+/// # Returns
+///
+/// The chosen strategy and the estimated prompt tokens of its largest request
+///
+/// # Errors
+///
+/// Returns `SecretaryError::ContextTooSmall` if no strategy fits.
+///
+/// # Examples
+///
+/// ```rust
+/// use secretary::Task;
+/// use secretary::adaptive::{PromptStrategy, choose_prompt_strategy};
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Task, Serialize, Deserialize, Debug)]
+/// struct Address {
+///     #[task(instruction = "The street")]
+///     pub street: String,
+///     #[task(instruction = "The city")]
+///     pub city: String,
+///     #[task(instruction = "The state")]
+///     pub state: String,
+///     #[task(instruction = "The postal code")]
+///     pub postal_code: String,
+///     #[task(instruction = "The country")]
+///     pub country: String,
+///     #[task(instruction = "The phone")]
+///     pub phone: String,
+///     #[task(instruction = "The email")]
+///     pub email: String,
+///     #[task(instruction = "The website")]
+///     pub website: String,
+/// }
+///
+/// let task = Address::new();
+/// let instructions = vec![];
+/// let choose = |limit| choose_prompt_strategy(&task, "ACME, 1 Main St", &instructions, limit);
+///
+/// // Without a declared context window, the full prompt is always used
+/// let (strategy, full_tokens) = choose(None).unwrap();
+/// assert_eq!(strategy, PromptStrategy::Full);
+/// assert_eq!(choose(Some(full_tokens)).unwrap().0, PromptStrategy::Full);
+///
+/// // Each step down the ladder is taken only when the previous one does not fit
+/// let (strategy, compact_tokens) = choose(Some(full_tokens - 1)).unwrap();
+/// assert_eq!(strategy, PromptStrategy::Compact);
+///
+/// let (strategy, distributed_tokens) = choose(Some(compact_tokens - 1)).unwrap();
+/// assert_eq!(strategy, PromptStrategy::Distributed);
+///
+/// assert!(choose(Some(distributed_tokens - 1)).is_err());
+/// ```
+pub fn choose_prompt_strategy<T: Task>(
+    task: &T,
+    target: &str,
+    additional_instructions: &[String],
+    max_context_tokens: Option<usize>,
+) -> Result<(PromptStrategy, usize), SecretaryError> {
+    let full_tokens: usize = estimate_tokens(
+        task.make_prompt(target, additional_instructions)
+            .content
+            .as_str(),
+    );
+
+    let max_context_tokens: usize = match max_context_tokens {
+        Some(max_context_tokens) => max_context_tokens,
+        None => return Ok((PromptStrategy::Full, full_tokens)),
+    };
+
+    if full_tokens <= max_context_tokens {
+        return Ok((PromptStrategy::Full, full_tokens));
+    }
+
+    let compact_tokens: usize = estimate_tokens(
+        task.make_compact_prompt(target, additional_instructions)
+            .content
+            .as_str(),
+    );
+    if compact_tokens <= max_context_tokens {
+        return Ok((PromptStrategy::Compact, compact_tokens));
+    }
+
+    let distributed_tokens: usize = task
+        .make_dstributed_generation_prompts(target, additional_instructions)
+        .iter()
+        .map(|(_, message): &(String, Message)| estimate_tokens(message.content.as_str()))
+        .max()
+        .unwrap_or(0);
+    if distributed_tokens <= max_context_tokens {
+        return Ok((PromptStrategy::Distributed, distributed_tokens));
+    }
+
+    Err(SecretaryError::ContextTooSmall {
+        required: distributed_tokens,
+        available: max_context_tokens,
+    })
+}
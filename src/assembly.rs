@@ -0,0 +1,441 @@
+//! Assembly of Tasks from per-field answers.
+//!
+//! Distributed generation and other sources that answer one field at a time produce
+//! `(field path, text)` pairs. `assemble_from_fields` parses every text with
+//! `smart_parse_value`, places it at its dotted path with `set_nested_field` and deserializes
+//! the result, returning a `FieldDeserializationError` that names the failing paths instead of
+//! panicking.
+//!
+//! The fields of tuple structs are addressed by their positional names, e.g. `range.field_0`,
+//! and the field of a newtype by the path of the newtype itself. `restore_tuple_structs`
+//! turns the objects built this way into the arrays and values serde expects.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::Task;
+//! use secretary::assembly::assemble_from_fields;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug, PartialEq)]
+//! struct Address {
+//!     #[task(instruction = "Extract the city")]
+//!     pub city: String,
+//! }
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug, PartialEq)]
+//! struct Invoice {
+//!     #[task(instruction = "Extract the customer")]
+//!     pub customer: String,
+//!     #[task(instruction = "Extract the total")]
+//!     pub total: f64,
+//!     #[task(instruction = "Is the invoice paid")]
+//!     pub paid: bool,
+//!     pub address: Address,
+//! }
+//!
+//! let fields: Vec<(String, String)> = vec![
+//!     ("customer".to_string(), "Ada".to_string()),
+//!     ("total".to_string(), "$1,200.50".to_string()),
+//!     ("paid".to_string(), "TRUE".to_string()),
+//!     ("address.city".to_string(), "London".to_string()),
+//! ];
+//!
+//! let invoice: Invoice = assemble_from_fields(fields).unwrap();
+//! assert_eq!(invoice.total, 1200.5);
+//! assert!(invoice.paid);
+//! assert_eq!(invoice.address.city, "London");
+//! ```
+
+use std::collections::BTreeMap;
+
+use serde_json::{Map, Value};
+
+use crate::{
+    SecretaryError, Task,
+    defaults::apply_default_values,
+    error::FieldDeserializationError,
+    partial::diagnose,
+    schema::{FieldDescriptor, FieldKind, is_newtype, is_tuple_struct},
+};
+
+/// Builds a Task from the text of each of its fields.
+///
+/// # Arguments
+///
+/// * `fields` - Pairs of a dotted field path, e.g. `address.city`, and the text for that field
+///
+/// # Returns
+///
+/// The assembled Task. When deserialization fails but no single field can be blamed, e.g.
+/// because a required field was not supplied, the Task's default is returned.
+///
+/// # Errors
+///
+/// Returns `SecretaryError::FieldDeserializationError` with the failing paths and the text
+/// supplied for every field when some values do not fit their fields.
+pub fn assemble_from_fields<T: Task>(fields: Vec<(String, String)>) -> Result<T, SecretaryError> {
+    let mut json_map: Map<String, Value> = Map::new();
+    for (field_path, content) in &fields {
+        set_nested_field(
+            &mut json_map,
+            field_path,
+            smart_parse_value(content, field_path),
+        );
+    }
+
+    let descriptors: Vec<FieldDescriptor> = T::field_descriptors();
+    let mut json_value: Value = Value::Object(json_map);
+    restore_tuple_structs(&mut json_value, &descriptors);
+    apply_default_values::<T>(&descriptors, &mut json_value);
+    match serde_json::from_value::<T>(json_value.clone()) {
+        Ok(result) => Ok(result),
+        Err(_) => {
+            let mut error: FieldDeserializationError = diagnose::<T>(&json_value);
+            if error.failed_fields.is_empty() {
+                return Ok(T::default());
+            }
+
+            error.raw_field_contents = fields.into_iter().collect::<BTreeMap<String, String>>();
+            Err(SecretaryError::FieldDeserializationError(error))
+        }
+    }
+}
+
+/// Parses the text a model returned for a single field into a JSON value.
+///
+/// Null-like answers become `null`, valid JSON is used as-is (a single key object named after
+/// the field is unwrapped), `true` and `false` are booleans in any case, and formatted numbers
+/// are parsed with `parse_numeric_value`. Anything else is kept as a trimmed string.
+///
+/// # Arguments
+///
+/// * `content` - The text returned for the field
+/// * `field_path` - The dotted path of the field, e.g. `address.city`
+///
+/// # Returns
+///
+/// The parsed value
+///
+/// # Examples
+///
+/// ```rust
+/// use secretary::assembly::smart_parse_value;
+/// use serde_json::{Value, json};
+///
+/// // (model output, field path, parsed value)
+/// let cases: Vec<(&str, &str, Value)> = vec![
+///     ("", "name", json!(null)),
+///     (" None ", "name", json!(null)),
+///     ("NULL", "name", json!(null)),
+///     ("Ada", "name", json!("Ada")),
+///     ("  Ada Lovelace \n", "name", json!("Ada Lovelace")),
+///     ("\"36\"", "age", json!("36")),
+///     ("36", "age", json!(36)),
+///     ("-3", "delta", json!(-3)),
+///     ("2.5", "score", json!(2.5)),
+///     ("$1,200", "total", json!(1200)),
+///     ("15%", "rate", json!(0.15)),
+///     ("True", "paid", json!(true)),
+///     ("FALSE", "paid", json!(false)),
+///     ("[1, 2]", "items", json!([1, 2])),
+///     (r#"{"city": "London"}"#, "address.city", json!("London")),
+///     (r#"{"city": "London"}"#, "address", json!({"city": "London"})),
+/// ];
+///
+/// for (content, field_path, expected) in cases {
+///     assert_eq!(smart_parse_value(content, field_path), expected, "{:?}", content);
+/// }
+/// ```
+pub fn smart_parse_value(content: &str, field_path: &str) -> Value {
+    let cleaned = content.trim();
+
+    // Handle empty or null-like values
+    if cleaned.is_empty()
+        || cleaned.eq_ignore_ascii_case("null")
+        || cleaned.eq_ignore_ascii_case("none")
+    {
+        return Value::Null;
+    }
+
+    // Try parsing as JSON first (for arrays, objects, quoted strings)
+    // This is more robust as it handles cases where LLM returns JSON strings
+    if let Ok(json_value) = serde_json::from_str::<Value>(cleaned) {
+        // If it's a JSON object with a single key that matches the field name,
+        // extract the inner value (common LLM response pattern)
+        if let Value::Object(obj) = &json_value {
+            let field_key = field_path.split('.').next_back().unwrap_or(field_path);
+            if obj.len() == 1 && obj.contains_key(field_key) {
+                return obj[field_key].clone();
+            }
+        }
+
+        return json_value;
+    }
+
+    // Handle boolean values (case-insensitive)
+    if cleaned.eq_ignore_ascii_case("true") {
+        return Value::Bool(true);
+    }
+    if cleaned.eq_ignore_ascii_case("false") {
+        return Value::Bool(false);
+    }
+
+    // Handle numeric values with currency symbols, commas, and other formatting
+    if let Some(numeric_value) = parse_numeric_value(cleaned) {
+        // Check if it's a whole number (integer)
+        if numeric_value.fract() == 0.0 && numeric_value >= 0.0 && numeric_value <= u64::MAX as f64
+        {
+            // Use integer representation for whole numbers
+            return Value::Number(serde_json::Number::from(numeric_value as u64));
+        } else {
+            // Use floating point for decimals
+            return Value::Number(
+                serde_json::Number::from_f64(numeric_value)
+                    .unwrap_or_else(|| serde_json::Number::from(0)),
+            );
+        }
+    }
+
+    // Default to string value
+    Value::String(cleaned.to_string())
+}
+
+/// Parses a number written with currency symbols, thousands separators, spaces or a percent
+/// sign.
+///
+/// # Arguments
+///
+/// * `content` - The text of the number
+///
+/// # Returns
+///
+/// The number, with percentages divided by 100, or `None` when the text is not a number
+///
+/// # Examples
+///
+/// ```rust
+/// use secretary::assembly::parse_numeric_value;
+///
+/// // (text, parsed number)
+/// let cases: Vec<(&str, Option<f64>)> = vec![
+///     ("42", Some(42.0)),
+///     ("-3.5", Some(-3.5)),
+///     ("$1,200", Some(1200.0)),
+///     ("€ 99.90", Some(99.9)),
+///     ("£5", Some(5.0)),
+///     ("¥1 000", Some(1000.0)),
+///     ("₹250", Some(250.0)),
+///     ("15%", Some(0.15)),
+///     ("1e3", Some(1000.0)),
+///     ("", None),
+///     ("forty-two", None),
+///     ("12 apples", None),
+/// ];
+///
+/// for (content, expected) in cases {
+///     assert_eq!(parse_numeric_value(content), expected, "{:?}", content);
+/// }
+/// ```
+pub fn parse_numeric_value(content: &str) -> Option<f64> {
+    let mut cleaned = content.to_string();
+
+    // Remove common currency symbols
+    cleaned = cleaned.replace('$', "");
+    cleaned = cleaned.replace('€', "");
+    cleaned = cleaned.replace('£', "");
+    cleaned = cleaned.replace('¥', "");
+    cleaned = cleaned.replace('₹', "");
+
+    // Remove commas (thousand separators)
+    cleaned = cleaned.replace(',', "");
+
+    // Remove spaces
+    cleaned = cleaned.replace(' ', "");
+
+    // Handle percentage
+    let is_percentage = cleaned.ends_with('%');
+    if is_percentage {
+        cleaned = cleaned.trim_end_matches('%').to_string();
+    }
+
+    // Try to parse as float
+    if let Ok(mut num) = cleaned.parse::<f64>() {
+        if is_percentage {
+            num /= 100.0; // Convert percentage to decimal
+        }
+        return Some(num);
+    }
+
+    None
+}
+
+/// Sets a value at a dotted field path, creating the nested objects along the way.
+///
+/// A path that runs into a value that is not an object is left unchanged. Indexed segments
+/// such as `items[0]` are objects keyed by the index, which `restore_tuple_structs` turns into
+/// the arrays of lists of nested Tasks.
+///
+/// # Arguments
+///
+/// * `json_map` - The object to set the value in
+/// * `field_path` - The dotted path of the field, e.g. `address.city` or `items[0].price`
+/// * `value` - The value to set
+///
+/// # Examples
+///
+/// ```rust
+/// use secretary::assembly::set_nested_field;
+/// use serde_json::{Map, Value, json};
+///
+/// let mut json_map: Map<String, Value> = Map::new();
+/// set_nested_field(&mut json_map, "name", json!("Ada"));
+/// set_nested_field(&mut json_map, "address.city", json!("London"));
+/// set_nested_field(&mut json_map, "address.geo.lat", json!(51.5));
+/// set_nested_field(&mut json_map, "items[1].price", json!(9.5));
+/// // `name` is not an object, so the path stops there
+/// set_nested_field(&mut json_map, "name.first", json!("Ada"));
+///
+/// assert_eq!(
+///     Value::Object(json_map),
+///     json!({
+///         "name": "Ada",
+///         "address": {"city": "London", "geo": {"lat": 51.5}},
+///         "items": {"1": {"price": 9.5}}
+///     })
+/// );
+/// ```
+pub fn set_nested_field(json_map: &mut Map<String, Value>, field_path: &str, value: Value) {
+    if field_path.contains('[') {
+        let dotted: String = field_path.replace("]", "").replace('[', ".");
+        return set_nested_field(json_map, &dotted, value);
+    }
+
+    let Some((first_part, remaining_path)) = field_path.split_once('.') else {
+        // Simple field, set directly
+        json_map.insert(field_path.to_string(), value);
+        return;
+    };
+
+    // Get or create the nested object
+    let nested_obj = json_map
+        .entry(first_part.to_string())
+        .or_insert_with(|| Value::Object(Map::new()));
+
+    if let Value::Object(nested_map) = nested_obj {
+        set_nested_field(nested_map, remaining_path, value);
+    }
+}
+
+/// Rewrites the objects keyed by positional names into serde's representation of tuple
+/// structs, newtypes and lists of nested Tasks.
+///
+/// An object with the fields `field_0`, `field_1` and so on of a tuple struct becomes an array
+/// in field order, with `null` for missing positions, and an object holding a newtype's field
+/// under the empty name becomes that field's value. An object keyed by the indices of a list
+/// of nested Tasks, as built from paths such as `items[0].price`, becomes an array in index
+/// order. Values already in serde's representation are left unchanged.
+///
+/// # Arguments
+///
+/// * `value` - The assembled JSON value
+/// * `fields` - The field descriptors of the Task, e.g. from `Task::field_descriptors()`
+///
+/// # Examples
+///
+/// ```rust
+/// use secretary::Task;
+/// use secretary::assembly::restore_tuple_structs;
+/// use serde::{Deserialize, Serialize};
+/// use serde_json::json;
+///
+/// #[derive(Task, Serialize, Deserialize, Debug, PartialEq)]
+/// struct Range(
+///     #[task(instruction = "Extract the lower bound")] u32,
+///     #[task(instruction = "Extract the upper bound")] u32,
+/// );
+///
+/// #[derive(Task, Serialize, Deserialize, Debug, PartialEq)]
+/// struct Listing {
+///     #[task(instruction = "Extract the title")]
+///     pub title: String,
+///     pub price: Range,
+/// }
+///
+/// let mut value = json!({"title": "Flat", "price": {"field_1": 900, "field_0": 700}});
+/// restore_tuple_structs(&mut value, &Listing::field_descriptors());
+/// assert_eq!(value, json!({"title": "Flat", "price": [700, 900]}));
+///
+/// let listing: Listing = serde_json::from_value(value).unwrap();
+/// assert_eq!(listing.price, Range(700, 900));
+/// ```
+pub fn restore_tuple_structs(value: &mut Value, fields: &[FieldDescriptor]) {
+    if is_newtype(fields) {
+        if let Value::Object(map) = value
+            && let Some(inner) = map.remove("")
+        {
+            *value = inner;
+        }
+        restore_field(value, &fields[0]);
+        return;
+    }
+
+    match value {
+        Value::Object(map) => {
+            for field in fields {
+                if let Some(field_value) = map.get_mut(&field.name) {
+                    restore_field(field_value, field);
+                }
+            }
+        }
+        Value::Array(items) if is_tuple_struct(fields) => {
+            for (field, item) in fields.iter().zip(items.iter_mut()) {
+                restore_field(item, field);
+            }
+        }
+        _ => {}
+    }
+
+    if is_tuple_struct(fields)
+        && let Value::Object(map) = value
+    {
+        *value = Value::Array(
+            fields
+                .iter()
+                .map(|field| map.remove(&field.name).unwrap_or(Value::Null))
+                .collect(),
+        );
+    }
+}
+
+/// Restores the tuple structs, newtypes and lists of nested Tasks in the value of one field.
+fn restore_field(value: &mut Value, field: &FieldDescriptor) {
+    match field.kind {
+        FieldKind::Normal => {}
+        FieldKind::Task | FieldKind::OptionTask => restore_tuple_structs(value, &field.children),
+        FieldKind::VecTask => {
+            if let Value::Object(map) = value
+                && map.keys().all(|key| key.parse::<usize>().is_ok())
+            {
+                let mut items: Vec<(usize, Value)> = std::mem::take(map)
+                    .into_iter()
+                    .map(|(key, item)| (key.parse::<usize>().unwrap_or_default(), item))
+                    .collect();
+                items.sort_by_key(|(index, _)| *index);
+                *value = Value::Array(items.into_iter().map(|(_, item)| item).collect());
+            }
+            if let Value::Array(items) = value {
+                for item in items {
+                    restore_tuple_structs(item, &field.children);
+                }
+            }
+        }
+        FieldKind::HashMapTask | FieldKind::BTreeMapTask => {
+            if let Value::Object(map) = value {
+                for entry in map.values_mut() {
+                    restore_tuple_structs(entry, &field.children);
+                }
+            }
+        }
+    }
+}
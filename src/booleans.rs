@@ -0,0 +1,405 @@
+//! Reading booleans that models write as words, symbols and ratings.
+//!
+//! Models answer boolean fields with `"✓"`, `"ja"`, `"oui"`, `"definitely"` or a `4/5`
+//! agreement rating as often as with `true`. A `LeniencyProfile` reads them, only in fields
+//! whose type is `bool`, so string fields keep the literal answer:
+//!
+//! - `boolean_synonyms` looks the answer up in the profile's own `BooleanSynonyms` table,
+//!   which maps words in many languages and symbols to true, false or uncertain. It is off
+//!   in every preset; `LeniencyProfile::with_boolean_synonyms` turns it on with a table,
+//!   the built-in one or one extended with `with_token`.
+//! - `booleans_from_ratings` reads ratings such as `4/5` or `4 out of 5` by the side of the
+//!   middle of their scale they fall on, counting the scale from 1: `4/5` is true, `2/5`
+//!   false and `3/5` uncertain. A bare number has no scale, so it is not read as a rating.
+//! - `booleans_from_numbers` reads any number as true unless it is zero, so `2` and `-1` are
+//!   true. Without it only 1 and 0 are read.
+//!
+//! An uncertain answer, such as `"maybe"`, `"unknown"` or a rating in the middle of its
+//! scale, is never guessed. By default it is left as it is, so deserialization fails on it;
+//! with `UncertainBooleans::UseDefault` the field gets its default instead, see
+//! `LeniencyProfile::uncertain_booleans`.
+//!
+//! Tokens are compared after trimming, lowercasing and removing trailing `.` and `!`.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::booleans::{BooleanReading, BooleanSynonyms, read_rating};
+//!
+//! let synonyms = BooleanSynonyms::builtin();
+//! assert_eq!(synonyms.lookup("Ja"), Some(BooleanReading::True));
+//! assert_eq!(synonyms.lookup("✗"), Some(BooleanReading::False));
+//! assert_eq!(synonyms.lookup("Not sure."), Some(BooleanReading::Uncertain));
+//! assert_eq!(synonyms.lookup("purple"), None);
+//!
+//! // Extended for this table only
+//! let extended = synonyms.clone().with_token("jo", BooleanReading::True);
+//! assert_eq!(extended.lookup("jo"), Some(BooleanReading::True));
+//! assert_eq!(synonyms.lookup("jo"), None);
+//!
+//! assert_eq!(read_rating("4/5"), Some(BooleanReading::True));
+//! assert_eq!(read_rating("3 out of 5"), Some(BooleanReading::Uncertain));
+//! assert_eq!(read_rating("2 of 10"), Some(BooleanReading::False));
+//! assert_eq!(read_rating("1/5"), Some(BooleanReading::False));
+//! assert_eq!(read_rating("4"), None);
+//! ```
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+/// What a boolean answer says.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum BooleanReading {
+    /// The answer is yes.
+    True,
+    /// The answer is no.
+    False,
+    /// The answer is neither clearly yes nor clearly no.
+    Uncertain,
+}
+
+impl From<bool> for BooleanReading {
+    fn from(value: bool) -> Self {
+        if value {
+            BooleanReading::True
+        } else {
+            BooleanReading::False
+        }
+    }
+}
+
+/// The words and symbols that mean yes, in the built-in table.
+const BUILTIN_TRUTHY: &[&str] = &[
+    // English
+    "yes",
+    "y",
+    "true",
+    "t",
+    "ok",
+    "okay",
+    "sure",
+    "definitely",
+    "certainly",
+    "absolutely",
+    "affirmative",
+    "correct",
+    "right",
+    "present",
+    "included",
+    "enabled",
+    "on",
+    "checked",
+    "of course",
+    "yep",
+    "yeah",
+    "indeed",
+    // Symbols
+    "✓",
+    "✔",
+    "✔️",
+    "☑",
+    "☑️",
+    "✅",
+    "👍",
+    "[x]",
+    // Other languages
+    "ja",
+    "jawohl",
+    "genau",
+    "oui",
+    "sí",
+    "si",
+    "claro",
+    "sim",
+    "sì",
+    "da",
+    "да",
+    "так",
+    "tak",
+    "ano",
+    "igen",
+    "kyllä",
+    "evet",
+    "naí",
+    "ναι",
+    "כן",
+    "نعم",
+    "بله",
+    "हाँ",
+    "हां",
+    "はい",
+    "是",
+    "是的",
+    "对",
+    "對",
+    "有",
+    "네",
+    "예",
+    "ya",
+    "iya",
+    "oo",
+    "có",
+    "ใช่",
+];
+
+/// The words and symbols that mean no, in the built-in table.
+const BUILTIN_FALSY: &[&str] = &[
+    // English
+    "no",
+    "n",
+    "false",
+    "f",
+    "never",
+    "nope",
+    "nah",
+    "negative",
+    "incorrect",
+    "wrong",
+    "absent",
+    "excluded",
+    "disabled",
+    "off",
+    "unchecked",
+    "not at all",
+    // Symbols
+    "✗",
+    "✘",
+    "❌",
+    "✕",
+    "×",
+    "☐",
+    "👎",
+    "[ ]",
+    "[]",
+    // Other languages
+    "nein",
+    "non",
+    "não",
+    "nao",
+    "нет",
+    "ні",
+    "nie",
+    "ne",
+    "nem",
+    "ei",
+    "hayır",
+    "hayir",
+    "όχι",
+    "ochi",
+    "לא",
+    "لا",
+    "نه",
+    "नहीं",
+    "いいえ",
+    "否",
+    "不",
+    "不是",
+    "没有",
+    "沒有",
+    "아니요",
+    "아니오",
+    "tidak",
+    "bukan",
+    "hindi",
+    "không",
+    "ไม่",
+    "nej",
+    "nee",
+];
+
+/// The words and symbols that say neither yes nor no, in the built-in table.
+const BUILTIN_UNCERTAIN: &[&str] = &[
+    // English
+    "maybe",
+    "perhaps",
+    "possibly",
+    "probably",
+    "unknown",
+    "unsure",
+    "not sure",
+    "unclear",
+    "uncertain",
+    "undetermined",
+    "partially",
+    "partly",
+    "sometimes",
+    "depends",
+    "it depends",
+    "n/a",
+    "na",
+    "not applicable",
+    "not mentioned",
+    "not stated",
+    // Symbols
+    "?",
+    "??",
+    "~",
+    "🤷",
+    // Other languages
+    "vielleicht",
+    "unbekannt",
+    "peut-être",
+    "peut-etre",
+    "inconnu",
+    "quizás",
+    "quizas",
+    "tal vez",
+    "talvez",
+    "desconocido",
+    "forse",
+    "может быть",
+    "możliwe",
+    "也许",
+    "可能",
+    "不确定",
+    "不知道",
+    "たぶん",
+    "不明",
+    "아마",
+    "모름",
+];
+
+/// A table of the words and symbols models answer boolean fields with, see the module
+/// documentation.
+///
+/// Each table is its own: a `LeniencyProfile` owns the one it reads answers with, and
+/// extending a clone leaves the original as it is.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(
+    from = "HashMap<String, BooleanReading>",
+    into = "HashMap<String, BooleanReading>"
+)]
+pub struct BooleanSynonyms {
+    tokens: HashMap<String, BooleanReading>,
+}
+
+impl Default for BooleanSynonyms {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+impl From<HashMap<String, BooleanReading>> for BooleanSynonyms {
+    fn from(tokens: HashMap<String, BooleanReading>) -> Self {
+        Self {
+            tokens: tokens
+                .into_iter()
+                .map(|(token, reading)| (normalize_token(&token), reading))
+                .collect(),
+        }
+    }
+}
+
+impl From<BooleanSynonyms> for HashMap<String, BooleanReading> {
+    fn from(synonyms: BooleanSynonyms) -> Self {
+        synonyms.tokens
+    }
+}
+
+impl BooleanSynonyms {
+    /// Creates a table with the built-in words and symbols.
+    pub fn builtin() -> Self {
+        let mut synonyms: Self = Self::empty();
+        for (words, reading) in [
+            (BUILTIN_TRUTHY, BooleanReading::True),
+            (BUILTIN_FALSY, BooleanReading::False),
+            (BUILTIN_UNCERTAIN, BooleanReading::Uncertain),
+        ] {
+            for word in words {
+                synonyms.add(word, reading);
+            }
+        }
+
+        synonyms
+    }
+
+    /// Creates a table without any word, in which nothing is found.
+    pub fn empty() -> Self {
+        Self {
+            tokens: HashMap::new(),
+        }
+    }
+
+    /// Adds a word or symbol, replacing its reading if the table has it.
+    ///
+    /// # Arguments
+    ///
+    /// * `token` - The word or symbol, in any case
+    /// * `reading` - What it says
+    pub fn with_token(mut self, token: &str, reading: BooleanReading) -> Self {
+        self.add(token, reading);
+        self
+    }
+
+    /// Adds a word or symbol, replacing its reading if the table has it.
+    ///
+    /// # Arguments
+    ///
+    /// * `token` - The word or symbol, in any case
+    /// * `reading` - What it says
+    pub fn add(&mut self, token: &str, reading: BooleanReading) {
+        self.tokens.insert(normalize_token(token), reading);
+    }
+
+    /// Removes a word or symbol, so it is no longer read.
+    ///
+    /// # Arguments
+    ///
+    /// * `token` - The word or symbol, in any case
+    pub fn remove(&mut self, token: &str) {
+        self.tokens.remove(&normalize_token(token));
+    }
+
+    /// Returns what an answer says, or `None` if the table does not have it.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The answer
+    pub fn lookup(&self, text: &str) -> Option<BooleanReading> {
+        self.tokens.get(&normalize_token(text)).copied()
+    }
+}
+
+/// Reads a rating such as `4/5`, `4 / 5`, `4 out of 5` or `4 of 5` as a boolean, or returns
+/// `None` if the text is not a rating.
+///
+/// The scale runs from 1 to its maximum: ratings above its middle are true, those below
+/// false, and those in the middle uncertain.
+///
+/// # Arguments
+///
+/// * `text` - The answer
+pub fn read_rating(text: &str) -> Option<BooleanReading> {
+    let text: String = normalize_token(text);
+    let (score, scale) = text
+        .split_once('/')
+        .or_else(|| text.split_once(" out of "))
+        .or_else(|| text.split_once(" of "))?;
+    let score: f64 = score.trim().parse().ok()?;
+    let scale: f64 = scale.trim().parse().ok()?;
+    if !score.is_finite() || !scale.is_finite() || scale < 2.0 || score < 0.0 || score > scale {
+        return None;
+    }
+
+    let middle: f64 = (1.0 + scale) / 2.0;
+    Some(if score > middle {
+        BooleanReading::True
+    } else if score < middle {
+        BooleanReading::False
+    } else {
+        BooleanReading::Uncertain
+    })
+}
+
+/// Returns a token as it is looked up: trimmed, lowercase, without trailing `.` and `!`, and
+/// with runs of whitespace as single spaces.
+fn normalize_token(token: &str) -> String {
+    token
+        .trim()
+        .trim_end_matches(['.', '!'])
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .to_lowercase()
+}
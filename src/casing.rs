@@ -0,0 +1,297 @@
+//! The case of the JSON keys the model sees.
+//!
+//! Rust fields are snake_case, but a style guide may require camelCase keys in everything an
+//! LLM reads, e.g. to match few-shot examples or downstream consumers. The struct-level
+//! `#[task(key_case = "camelCase")]` presents the keys of a Task's fields in another case,
+//! without `#[serde(rename_all)]` on types shared with other code:
+//!
+//! - the field lines and nested Task blocks of the system prompt, the distributed prompts,
+//!   the outline of version 2 prompts, the negative examples and the compact specification
+//!   name the fields by their cased key
+//! - the JSON template, the templates of YAML and XML answers and the JSON Schema have the
+//!   cased keys
+//! - the keys of the model's answer are converted back to the field names before it is
+//!   deserialized
+//!
+//! Each Task uses its own case: the keys of a nested Task follow the nested struct's
+//! `key_case`, and the keys of maps of Tasks are data and are never converted. Answers are
+//! converted back by the field descriptors, so every key round-trips exactly, whatever
+//! `KeyCase::apply` would make of it, e.g. of single-letter words that run together; keys
+//! that are no field's are left as they are, and so are keys already in the field's own case.
+//!
+//! `snake_case`, the case of Rust fields, leaves the keys as they are.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::casing::KeyCase;
+//!
+//! assert_eq!(KeyCase::Camel.apply("line_item_2"), "lineItem2");
+//! assert_eq!(KeyCase::Snake.apply("lineItem2"), "line_item_2");
+//! assert_eq!(KeyCase::Kebab.apply("HTTPServer"), "http-server");
+//! assert_eq!(KeyCase::Pascal.apply("http_url"), "HttpUrl");
+//! assert_eq!(KeyCase::from_name("camelCase"), Some(KeyCase::Camel));
+//! ```
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::schema::{FieldDescriptor, FieldKind};
+
+/// The case of the JSON keys of a Task's fields, from `#[task(key_case = "...")]`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum KeyCase {
+    /// `line_item_2`, the case of Rust fields, which leaves the keys as they are.
+    #[default]
+    Snake,
+    /// `lineItem2`.
+    Camel,
+    /// `line-item-2`.
+    Kebab,
+    /// `LineItem2`.
+    Pascal,
+}
+
+impl KeyCase {
+    /// Returns the case named as in `#[task(key_case = "...")]`.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - `snake_case`, `camelCase`, `kebab-case` or `PascalCase`
+    ///
+    /// # Returns
+    ///
+    /// The case, or `None` for any other name
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "snake_case" => Some(KeyCase::Snake),
+            "camelCase" => Some(KeyCase::Camel),
+            "kebab-case" => Some(KeyCase::Kebab),
+            "PascalCase" => Some(KeyCase::Pascal),
+            _ => None,
+        }
+    }
+
+    /// Returns the name of the case, as written in `#[task(key_case = "...")]`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            KeyCase::Snake => "snake_case",
+            KeyCase::Camel => "camelCase",
+            KeyCase::Kebab => "kebab-case",
+            KeyCase::Pascal => "PascalCase",
+        }
+    }
+
+    /// Returns whether keys in this case are the field names themselves.
+    pub fn is_native(&self) -> bool {
+        *self == KeyCase::Snake
+    }
+
+    /// Writes a name in this case.
+    ///
+    /// The name may be in any of the cases. Words are separated by `_`, `-` and spaces, by a
+    /// lowercase letter or digit followed by an uppercase one, by the last letter of an
+    /// uppercase run followed by a lowercase one, as in `HTTPServer`, and by a letter
+    /// followed by a digit, so that `line_item_2` and `lineItem2` convert into each other.
+    /// Generated by `#[derive(Task)]` with the same rules.
+    ///
+    /// The conversion is lossy where single-letter words meet: they run together into an
+    /// uppercase run, which reads back as one word, so `a_b` is `AB` in PascalCase and `AB`
+    /// is `ab` in snake_case. The keys of a Task's answer do not go through this, they are
+    /// converted back by the field descriptors, see the module documentation.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name to convert, e.g. a field name
+    pub fn apply(&self, name: &str) -> String {
+        let words: Vec<String> = split_words(name);
+        match self {
+            KeyCase::Snake => words.join("_"),
+            KeyCase::Kebab => words.join("-"),
+            KeyCase::Camel => words
+                .iter()
+                .enumerate()
+                .map(|(index, word)| {
+                    if index == 0 {
+                        word.clone()
+                    } else {
+                        capitalize(word)
+                    }
+                })
+                .collect(),
+            KeyCase::Pascal => words.iter().map(|word| capitalize(word)).collect(),
+        }
+    }
+}
+
+impl FieldDescriptor {
+    /// Returns the key of the field in the JSON the model sees: the field name in the case
+    /// of its Task, see the `casing` module.
+    pub fn key(&self) -> String {
+        if self.key_case.is_native() {
+            return self.name.clone();
+        }
+
+        self.key_case.apply(&self.name)
+    }
+}
+
+/// Returns whether any of the fields, nested Tasks included, has a key other than its name.
+///
+/// # Arguments
+///
+/// * `fields` - The descriptors of a Task's fields
+pub fn uses_key_case(fields: &[FieldDescriptor]) -> bool {
+    fields
+        .iter()
+        .any(|field| !field.key_case.is_native() || uses_key_case(&field.children))
+}
+
+/// Renames the keys of the fields of a JSON value, such as a Task's template, to the keys
+/// the model sees.
+///
+/// # Arguments
+///
+/// * `fields` - The descriptors of the fields of the value
+/// * `value` - The JSON object to rename the keys of, in place
+pub fn to_prompt_keys(fields: &[FieldDescriptor], value: &mut Value) {
+    rename_keys(fields, value, KeyDirection::ToPrompt);
+}
+
+/// Renames the keys of the fields in the model's answer back to the field names, so that
+/// the answer deserializes.
+///
+/// # Arguments
+///
+/// * `fields` - The descriptors of the fields of the value
+/// * `value` - The JSON object answered by the model, in place
+pub fn to_native_keys(fields: &[FieldDescriptor], value: &mut Value) {
+    rename_keys(fields, value, KeyDirection::ToNative);
+}
+
+/// Renders a JSON template with the keys the model sees, for Tasks with a `key_case`.
+/// Templates of other Tasks are returned as they are.
+///
+/// # Arguments
+///
+/// * `template` - The pretty-printed JSON template with the field names as keys
+/// * `fields` - The descriptors of the fields of the template
+pub fn template_with_prompt_keys(template: &str, fields: &[FieldDescriptor]) -> String {
+    if !uses_key_case(fields) {
+        return template.to_string();
+    }
+
+    match serde_json::from_str::<Value>(template) {
+        Ok(mut value) => {
+            to_prompt_keys(fields, &mut value);
+            serde_json::to_string_pretty(&value).unwrap_or_else(|_| template.to_string())
+        }
+        Err(_) => template.to_string(),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum KeyDirection {
+    ToPrompt,
+    ToNative,
+}
+
+fn rename_keys(fields: &[FieldDescriptor], value: &mut Value, direction: KeyDirection) {
+    let Some(object) = value.as_object_mut() else {
+        return;
+    };
+
+    if fields.iter().any(|field| !field.key_case.is_native()) {
+        let entries: Map<String, Value> = std::mem::take(object);
+        let keys: Vec<String> = entries.keys().cloned().collect();
+        for (key, entry) in entries {
+            let renamed: String = fields
+                .iter()
+                .find_map(|field| match direction {
+                    KeyDirection::ToPrompt if field.name == key => Some(field.key()),
+                    KeyDirection::ToNative if field.key() == key => Some(field.name.clone()),
+                    _ => None,
+                })
+                .unwrap_or_else(|| key.clone());
+            // A key already in the field's own case wins over the converted one
+            if direction == KeyDirection::ToNative && renamed != key && keys.contains(&renamed) {
+                continue;
+            }
+            object.insert(renamed, entry);
+        }
+    }
+
+    for field in fields.iter().filter(|field| !field.children.is_empty()) {
+        let key: String = match direction {
+            KeyDirection::ToPrompt => field.key(),
+            KeyDirection::ToNative => field.name.clone(),
+        };
+        let Some(entry) = object.get_mut(&key) else {
+            continue;
+        };
+        match field.kind {
+            FieldKind::Task | FieldKind::OptionTask => {
+                rename_keys(&field.children, entry, direction)
+            }
+            FieldKind::VecTask => {
+                if let Some(items) = entry.as_array_mut() {
+                    for item in items {
+                        rename_keys(&field.children, item, direction);
+                    }
+                }
+            }
+            // The keys of the map are data, only the Tasks they map to are renamed
+            FieldKind::HashMapTask | FieldKind::BTreeMapTask => {
+                if let Some(entries) = entry.as_object_mut() {
+                    for entry in entries.values_mut() {
+                        rename_keys(&field.children, entry, direction);
+                    }
+                }
+            }
+            FieldKind::Normal => {}
+        }
+    }
+}
+
+/// Splits a name in any case into its lowercase words.
+fn split_words(name: &str) -> Vec<String> {
+    let mut words: Vec<String> = Vec::new();
+    let mut word: String = String::new();
+    let characters: Vec<char> = name.chars().collect();
+
+    for (index, &character) in characters.iter().enumerate() {
+        if matches!(character, '_' | '-' | ' ') {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            continue;
+        }
+
+        if let Some(&previous) = index.checked_sub(1).and_then(|index| characters.get(index)) {
+            let next: Option<char> = characters.get(index + 1).copied();
+            let boundary: bool = (character.is_uppercase()
+                && (previous.is_lowercase() || previous.is_ascii_digit()))
+                || (character.is_uppercase()
+                    && previous.is_uppercase()
+                    && next.is_some_and(char::is_lowercase))
+                || (character.is_ascii_digit() && previous.is_alphabetic());
+            if boundary && !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+        }
+        word.extend(character.to_lowercase());
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+
+    words
+}
+
+fn capitalize(word: &str) -> String {
+    let mut characters = word.chars();
+    match characters.next() {
+        Some(first) => first.to_uppercase().chain(characters).collect(),
+        None => String::new(),
+    }
+}
@@ -0,0 +1,1077 @@
+//! Map-reduce extraction for inputs larger than the context window.
+//!
+//! `GenerateData::generate_data_chunked` splits the target into overlapping chunks with
+//! `split_into_chunks`, extracts every chunk concurrently, and combines the per-chunk results
+//! according to the `MergePolicy`:
+//!
+//! - `MergePolicy::Llm` serializes the results and asks the model to merge them into a
+//!   single consistent object, using the prompt of `make_reduce_prompt`.
+//! - The other policies merge locally with `merge_chunk_values`, field by field. Arrays are
+//!   concatenated with duplicates removed and nested objects are merged field by field. When
+//!   chunks give different non-default values to a field, the policy picks one:
+//!   `FirstNonDefault` the value of the earliest chunk, `HighestConfidence` the value the
+//!   model was most confident in, `MostFrequent` the value most chunks gave, and
+//!   `Adjudicate` the value the model picks in a final small request that shows it the
+//!   conflicting values with the excerpts they were read from.
+//!
+//! `ChunkOptions::with_confidence` asks every chunk request for the model's confidence in
+//! each field, under the key `_confidence`, which is removed before the result is parsed.
+//! `GenerationMetadata::chunk_sources` lists the chunks each field's value came from, and
+//! `GenerationMetadata::merge_conflicts` every field the chunks disagreed on, with the
+//! values, the winner and why it won. Conflicts are not reported under `MergePolicy::Llm`,
+//! where the model merges the results as a whole.
+//!
+//! The merge is a pure function of the per-chunk results, so `merge_chunk_values` can be
+//! called on its own, e.g. to replay a merge under another policy.
+//!
+//! Targets that fit in a single chunk are extracted with a single request and no merge.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use secretary::Task;
+//! use secretary::chunking::{ChunkOptions, MergePolicy};
+//! use secretary::llm_providers::openai::OpenAILLM;
+//! use secretary::traits::{AsyncGenerateData, GenerateData};
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! struct Contract {
+//!     #[task(instruction = "Extract the contract title")]
+//!     pub title: String,
+//!     #[task(instruction = "Extract the termination clause reference")]
+//!     pub termination_clause: Option<String>,
+//!     #[task(instruction = "Extract the parties")]
+//!     pub parties: Vec<String>,
+//! }
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+//! let document = "This Supply Agreement is made between ACME and Globex.\n\n\
+//!                 ACME delivers widgets to Globex every month at the agreed price.\n\n\
+//!                 Either party may terminate as described in Section 14.2 of Globex terms.";
+//! let llm = OpenAILLM::new("https://api.openai.com/v1", "your-api-key", "gpt-4o-mini")?;
+//! let task = Contract::new();
+//!
+//! // Three paragraphs of about 60 characters become three chunks
+//! let options = ChunkOptions::default().with_chunk_size(80).with_overlap(0);
+//! assert_eq!(options.split(document).len(), 3);
+//!
+//! // The chunk results are merged locally, field by field
+//! let local = options.clone().with_merge_policy(MergePolicy::FirstNonDefault);
+//! let result = llm.generate_data_chunked(&task, document, &vec![], &local)?;
+//! println!("{:?}", result.data);
+//! println!("Sources: {:?}", result.metadata.chunk_sources);
+//! println!("Conflicts: {:?}", result.metadata.merge_conflicts);
+//!
+//! // Or handed to the model in a reduce step
+//! let contract: Contract = llm.generate_data_chunked(&task, document, &vec![], &options)?.data;
+//! println!("{:?}", contract);
+//!
+//! tokio::runtime::Runtime::new()?.block_on(async {
+//!     let contract: Contract = llm
+//!         .async_generate_data_chunked(&task, document, &vec![], &local)
+//!         .await?
+//!         .data;
+//!     println!("{:?}", contract);
+//!     Ok(())
+//! })
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::{
+    SecretaryError,
+    classification::{clamp_confidence, read_confidence},
+    message::Message,
+    progress::{Progress, ProgressReporter},
+    provenance::locate_quote,
+    traits::Task,
+    utilities::format_additional_instructions,
+};
+
+/// The instruction of the reduce request, followed by the per-chunk results.
+const REDUCE_INSTRUCTION: &str = "The document was too long to read at once, so the fields were extracted from consecutive chunks of it. Merge the partial results below, given in document order, into a single consistent JSON object. Keep values stated in any chunk, combine list items without duplicates, and prefer the most specific value when chunks disagree.";
+
+/// The instruction of the adjudication request, followed by the conflicting values.
+const ADJUDICATION_INSTRUCTION: &str = "The document was too long to read at once, so the fields were extracted from consecutive chunks of it, and the chunks disagree on the fields below. For each field, pick the value the document as a whole supports, judging by the excerpts the values were read from. Answer with a single JSON object that maps each field to {\"choice\": <the number of the value you picked>, \"reason\": \"<one sentence>\"}.";
+
+/// The additional instruction of chunk requests under `ChunkOptions::with_confidence`.
+const CONFIDENCE_INSTRUCTION: &str = "Besides the fields, answer with the key \"_confidence\" holding an object that maps the name of every field you filled to your confidence in its value, from 0 to 1. Name the fields of nested objects by their path, such as \"address.city\".";
+
+/// The key holding the confidences of a chunk result, see `ChunkOptions::with_confidence`.
+pub const CONFIDENCE_KEY: &str = "_confidence";
+
+/// The number of characters kept on each side of a value in the excerpt it was read from.
+const EXCERPT_CONTEXT: usize = 60;
+
+/// How the results of the chunks are combined into one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MergePolicy {
+    /// The model merges the serialized results in an extra request.
+    #[default]
+    Llm,
+    /// The value of the earliest chunk that gives a field a non-default value wins.
+    FirstNonDefault,
+    /// The value the model reported the highest confidence in wins, see
+    /// `ChunkOptions::with_confidence`. Without confidences, the earliest value wins.
+    HighestConfidence,
+    /// The value given by the most chunks wins, the earliest on a tie.
+    MostFrequent,
+    /// The model picks the winner of every conflict in an extra request, shown the
+    /// conflicting values with the excerpts they were read from. No request is sent when
+    /// the chunks agree.
+    Adjudicate,
+}
+
+/// Settings for `GenerateData::generate_data_chunked`.
+///
+/// Sizes are measured in characters, roughly four per token for English text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkOptions {
+    /// The maximum number of characters in a chunk.
+    pub chunk_size: usize,
+    /// The number of characters at the end of a chunk that are repeated at the start of the
+    /// next one, so facts on a boundary are seen whole. Capped at half the chunk size.
+    pub overlap: usize,
+    /// The maximum number of chunk requests in flight at once.
+    pub concurrency: usize,
+    /// How the results of the chunks are combined.
+    pub merge_policy: MergePolicy,
+    /// Whether chunk requests ask for the model's confidence in each field.
+    pub confidence: bool,
+    /// The reporter the chunks and the merge are reported to, see the `progress` module.
+    pub progress: Option<Progress>,
+}
+
+impl Default for ChunkOptions {
+    fn default() -> Self {
+        Self {
+            chunk_size: 32_000,
+            overlap: 1_000,
+            concurrency: 4,
+            merge_policy: MergePolicy::default(),
+            confidence: false,
+            progress: None,
+        }
+    }
+}
+
+impl ChunkOptions {
+    /// Sets the maximum number of characters in a chunk.
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// Sets the number of characters repeated between consecutive chunks.
+    pub fn with_overlap(mut self, overlap: usize) -> Self {
+        self.overlap = overlap;
+        self
+    }
+
+    /// Sets the maximum number of chunk requests in flight at once.
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Sets how the results of the chunks are combined.
+    pub fn with_merge_policy(mut self, merge_policy: MergePolicy) -> Self {
+        self.merge_policy = merge_policy;
+        self
+    }
+
+    /// Sets whether chunk requests ask for the model's confidence in each field, which
+    /// `MergePolicy::HighestConfidence` picks by and conflicts report.
+    pub fn with_confidence(mut self, confidence: bool) -> Self {
+        self.confidence = confidence;
+        self
+    }
+
+    /// Reports the start and end of each chunk request and the merge, see the `progress`
+    /// module.
+    pub fn with_progress(mut self, reporter: Arc<dyn ProgressReporter>) -> Self {
+        self.progress = Some(Progress::new(reporter));
+        self
+    }
+
+    /// Returns the additional instructions of the chunk requests.
+    pub(crate) fn chunk_instructions(&self, additional_instructions: &[String]) -> Vec<String> {
+        let mut instructions: Vec<String> = additional_instructions.to_vec();
+        if self.confidence {
+            instructions.push(CONFIDENCE_INSTRUCTION.to_string());
+        }
+
+        instructions
+    }
+
+    /// Splits a target with these settings, see `split_into_chunks`.
+    pub fn split(&self, text: &str) -> Vec<String> {
+        split_into_chunks(text, self.chunk_size, self.overlap)
+    }
+}
+
+/// The result of one chunk, as merged by `merge_chunk_values`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChunkValue {
+    /// The result of the chunk, serialized.
+    pub value: Value,
+    /// The model's confidence in each field, from 0 to 1, by field path such as
+    /// `address.city`. A nested field without one has the confidence of its parent.
+    pub confidence: BTreeMap<String, f32>,
+    /// The text of the chunk, where the excerpts of conflicting values are looked up.
+    pub text: String,
+}
+
+impl ChunkValue {
+    /// Creates the result of a chunk without confidences.
+    pub fn new(value: Value, text: impl Into<String>) -> Self {
+        Self {
+            value,
+            confidence: BTreeMap::new(),
+            text: text.into(),
+        }
+    }
+
+    /// Sets the model's confidence in a field, clamped to `0..=1`.
+    pub fn with_confidence(mut self, field: impl Into<String>, confidence: f32) -> Self {
+        self.confidence
+            .insert(field.into(), clamp_confidence(confidence));
+        self
+    }
+}
+
+/// One of the values chunks gave a field they disagree on.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MergeCandidate {
+    /// The value.
+    pub value: Value,
+    /// The chunks that gave the value, by index in document order.
+    pub chunks: Vec<usize>,
+    /// The highest confidence reported for the value, if any.
+    pub confidence: Option<f32>,
+    /// The text around the value in the first chunk it was found in, if it was found.
+    pub excerpt: Option<String>,
+}
+
+/// Why the winner of a conflict won.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum MergeRationale {
+    /// It was given by the earliest chunk, under `MergePolicy::FirstNonDefault` or when the
+    /// other policies could not separate the values.
+    EarliestChunk,
+    /// The model was most confident in it.
+    HighestConfidence {
+        /// The confidence of the winner.
+        confidence: f32,
+    },
+    /// More chunks gave it than any other value.
+    MostFrequent {
+        /// The number of chunks that gave the winner.
+        chunks: usize,
+    },
+    /// The model picked it in the adjudication request.
+    Adjudicated {
+        /// The reason the model gave, if any.
+        reason: Option<String>,
+    },
+}
+
+/// A field the chunks gave different values to, reported in
+/// `GenerationMetadata::merge_conflicts`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MergeConflict {
+    /// The path of the field, e.g. `address.city`.
+    pub field: String,
+    /// The distinct values, in the order of the first chunk that gave each.
+    pub candidates: Vec<MergeCandidate>,
+    /// The index of the value that won in `candidates`.
+    pub winner: usize,
+    /// Why it won.
+    pub rationale: MergeRationale,
+}
+
+impl MergeConflict {
+    /// Returns the value that won.
+    pub fn winning_value(&self) -> &Value {
+        &self.candidates[self.winner].value
+    }
+}
+
+/// The outcome of `merge_chunk_values`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChunkMerge {
+    /// The merged result.
+    pub value: Value,
+    /// The chunks that gave each field a non-default value, by field path.
+    pub sources: BTreeMap<String, Vec<usize>>,
+    /// The fields the chunks disagreed on, in field order.
+    pub conflicts: Vec<MergeConflict>,
+}
+
+/// Splits text into chunks of at most `chunk_size` characters that overlap by up to
+/// `overlap` characters.
+///
+/// Chunks are made of whole paragraphs, separated by blank lines, whenever they fit. Longer
+/// paragraphs are split between words, and words longer than a chunk are split anywhere. The
+/// overlap is taken from the end of the previous chunk, starting at a word boundary.
+///
+/// # Arguments
+///
+/// * `text` - The text to split
+/// * `chunk_size` - The maximum number of characters in a chunk
+/// * `overlap` - The number of characters repeated between chunks, capped at half the chunk size
+///
+/// # Returns
+///
+/// The chunks in document order, empty for blank text
+///
+/// # Examples
+///
+/// ```rust
+/// use secretary::chunking::split_into_chunks;
+///
+/// let text = "First paragraph here.\n\nSecond paragraph here.\n\n\nThird paragraph here.";
+///
+/// // Paragraphs are kept whole and packed together while they fit
+/// assert_eq!(
+///     split_into_chunks(text, 50, 0),
+///     vec!["First paragraph here.\n\nSecond paragraph here.", "Third paragraph here."]
+/// );
+/// assert_eq!(split_into_chunks(text, 1000, 0), vec![text.replace("\n\n\n", "\n\n")]);
+///
+/// // The overlap repeats the end of the previous chunk from a word boundary
+/// assert_eq!(
+///     split_into_chunks(text, 40, 12),
+///     vec![
+///         "First paragraph here.",
+///         "here.\n\nSecond paragraph here.",
+///         "here.\n\nThird paragraph here.",
+///     ]
+/// );
+///
+/// // Paragraphs longer than a chunk are split between words
+/// let chunks = split_into_chunks("one two three four five six", 10, 0);
+/// assert_eq!(chunks, vec!["one two", "three four", "five six"]);
+/// assert!(chunks.iter().all(|chunk| chunk.chars().count() <= 10));
+///
+/// assert!(split_into_chunks("  \n\n ", 10, 0).is_empty());
+/// ```
+pub fn split_into_chunks(text: &str, chunk_size: usize, overlap: usize) -> Vec<String> {
+    let chunk_size: usize = chunk_size.max(1);
+    let overlap: usize = overlap.min(chunk_size / 2);
+    // Leaves room for the overlap and the paragraph separator in every chunk
+    let piece_size: usize = if overlap == 0 {
+        chunk_size
+    } else {
+        chunk_size.saturating_sub(overlap + 2).max(1)
+    };
+
+    let mut pieces: Vec<String> = Vec::new();
+    for paragraph in split_paragraphs(text) {
+        if paragraph.chars().count() <= piece_size {
+            pieces.push(paragraph);
+        } else {
+            pieces.extend(split_words(&paragraph, piece_size));
+        }
+    }
+
+    let mut chunks: Vec<String> = Vec::new();
+    let mut current: String = String::new();
+    let mut current_length: usize = 0;
+    for piece in pieces {
+        let piece_length: usize = piece.chars().count();
+        if !current.is_empty() && current_length + 2 + piece_length > chunk_size {
+            let tail: String = overlap_tail(&current, overlap);
+            chunks.push(std::mem::replace(&mut current, tail));
+            current_length = current.chars().count();
+        }
+        if !current.is_empty() {
+            current.push_str("\n\n");
+            current_length += 2;
+        }
+        current.push_str(&piece);
+        current_length += piece_length;
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+
+    chunks
+}
+
+/// Merges the chunk results of a Task locally.
+///
+/// For every field the first value that differs from the field's default wins. Arrays are
+/// concatenated in order with duplicates removed, and objects are merged field by field.
+///
+/// # Arguments
+///
+/// * `results` - The per-chunk results in document order
+///
+/// # Errors
+///
+/// Returns `SecretaryError::SerdeJsonError` if the merged value does not deserialize.
+///
+/// # Examples
+///
+/// ```rust
+/// use secretary::Task;
+/// use secretary::chunking::merge_results;
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Task, Serialize, Deserialize, Debug, PartialEq)]
+/// struct Invoice {
+///     #[task(instruction = "Extract the vendor")]
+///     pub vendor: String,
+///     #[task(instruction = "Extract the total")]
+///     pub total: Option<f64>,
+///     #[task(instruction = "Extract the line items")]
+///     pub items: Vec<String>,
+/// }
+///
+/// let chunks = vec![
+///     Invoice { vendor: "ACME".to_string(), total: None, items: vec!["bolts".to_string()] },
+///     Invoice { vendor: "ACME Corp".to_string(), total: None, items: vec!["bolts".to_string(), "nuts".to_string()] },
+///     Invoice { vendor: String::new(), total: Some(42.0), items: vec!["washers".to_string()] },
+/// ];
+/// let merged: Invoice = merge_results(&chunks).unwrap();
+/// assert_eq!(merged.vendor, "ACME");
+/// assert_eq!(merged.total, Some(42.0));
+/// assert_eq!(merged.items, vec!["bolts", "nuts", "washers"]);
+///
+/// assert_eq!(merge_results::<Invoice>(&[]).unwrap(), Invoice::default());
+/// ```
+pub fn merge_results<T: Task>(results: &[T]) -> Result<T, SecretaryError> {
+    let defaults: Value = serde_json::to_value(T::default())?;
+    let mut merged: Value = defaults.clone();
+    for result in results {
+        merge_value(&mut merged, &serde_json::to_value(result)?, &defaults);
+    }
+
+    Ok(serde_json::from_value::<T>(merged)?)
+}
+
+/// Merges the results of the chunks field by field, see the module documentation.
+///
+/// A field's value counts when it differs from the field's default. Arrays are concatenated
+/// in order with duplicates removed, objects are merged field by field, and any other field
+/// that chunks gave different values is a conflict the policy picks the winner of.
+/// `MergePolicy::Llm` and `MergePolicy::Adjudicate` pick the earliest value here; see
+/// `apply_adjudication` for the latter.
+///
+/// # Arguments
+///
+/// * `defaults` - The serialized default of the Task
+/// * `chunks` - The per-chunk results in document order
+/// * `policy` - How the winner of a conflict is picked
+///
+/// # Examples
+///
+/// ```rust
+/// use secretary::chunking::{ChunkValue, MergePolicy, MergeRationale, merge_chunk_values};
+/// use serde_json::json;
+///
+/// let defaults = json!({"invoice_number": "", "total": null, "items": []});
+/// let chunks = vec![
+///     ChunkValue::new(
+///         json!({"invoice_number": "INV-7", "total": 120.5, "items": ["desk"]}),
+///         "INVOICE INV-7",
+///     )
+///     .with_confidence("invoice_number", 0.9),
+///     ChunkValue::new(json!({"invoice_number": "", "total": null, "items": ["chair"]}), "..."),
+///     ChunkValue::new(
+///         json!({"invoice_number": "INV-9", "total": 120.5, "items": []}),
+///         "See also INV-9 in the footnotes.",
+///     )
+///     .with_confidence("invoice_number", 0.3),
+/// ];
+///
+/// let merge = merge_chunk_values(&defaults, &chunks, MergePolicy::HighestConfidence);
+/// assert_eq!(
+///     merge.value,
+///     json!({"invoice_number": "INV-7", "total": 120.5, "items": ["desk", "chair"]})
+/// );
+/// assert_eq!(merge.sources["invoice_number"], vec![0, 2]);
+/// assert_eq!(merge.sources["items"], vec![0, 1]);
+///
+/// // The chunks agree on the total, but not on the invoice number
+/// assert_eq!(merge.conflicts.len(), 1);
+/// let conflict = &merge.conflicts[0];
+/// assert_eq!(conflict.field, "invoice_number");
+/// assert_eq!(conflict.winning_value(), &json!("INV-7"));
+/// assert_eq!(conflict.rationale, MergeRationale::HighestConfidence { confidence: 0.9 });
+/// assert_eq!(conflict.candidates[1].excerpt.as_deref(), Some("See also INV-9 in the footnotes."));
+///
+/// let merge = merge_chunk_values(&defaults, &chunks, MergePolicy::FirstNonDefault);
+/// assert_eq!(merge.conflicts[0].rationale, MergeRationale::EarliestChunk);
+/// ```
+pub fn merge_chunk_values(
+    defaults: &Value,
+    chunks: &[ChunkValue],
+    policy: MergePolicy,
+) -> ChunkMerge {
+    let mut merger: Merger = Merger {
+        chunks,
+        policy,
+        sources: BTreeMap::new(),
+        conflicts: Vec::new(),
+    };
+    let values: Vec<(usize, &Value)> = chunks
+        .iter()
+        .map(|chunk| &chunk.value)
+        .enumerate()
+        .collect();
+    let value: Value = merger.merge("", defaults, values);
+
+    ChunkMerge {
+        value,
+        sources: merger.sources,
+        conflicts: merger.conflicts,
+    }
+}
+
+/// Removes the confidences from the content of a chunk response, see
+/// `ChunkOptions::with_confidence`.
+///
+/// Confidences are read from numbers, numeric strings and percentages such as `"85%"`, and
+/// clamped to `0..=1`; others are dropped. Content that is not a JSON object is returned
+/// unchanged.
+///
+/// # Returns
+///
+/// The content without the `_confidence` key, and the confidences by field path
+///
+/// # Examples
+///
+/// ```rust
+/// use secretary::chunking::take_confidence;
+///
+/// let (content, confidence) =
+///     take_confidence(r#"{"name": "Ada", "_confidence": {"name": "85%", "age": "high"}}"#);
+/// assert_eq!(content, r#"{"name":"Ada"}"#);
+/// assert_eq!(confidence.len(), 1);
+/// assert_eq!(confidence["name"], 0.85);
+/// ```
+pub fn take_confidence(content: &str) -> (String, BTreeMap<String, f32>) {
+    let Ok(Value::Object(mut object)) = serde_json::from_str::<Value>(content) else {
+        return (content.to_string(), BTreeMap::new());
+    };
+    let Some(confidences) = object.remove(CONFIDENCE_KEY) else {
+        return (content.to_string(), BTreeMap::new());
+    };
+
+    let confidence: BTreeMap<String, f32> = confidences
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter_map(|(field, value)| {
+            read_confidence(value).map(|confidence| (field.clone(), clamp_confidence(confidence)))
+        })
+        .collect();
+    (Value::Object(object).to_string(), confidence)
+}
+
+/// Applies the model's answer to the adjudication request to a merge.
+///
+/// The answer maps each field to the number of the value it picked, counting from 1, as
+/// `{"choice": 2, "reason": "..."}` or as the bare number. A conflict the answer skips, or
+/// answers with a number that is not one of its values, keeps its earliest value.
+///
+/// # Arguments
+///
+/// * `merge` - A merge under `MergePolicy::Adjudicate`
+/// * `answer` - The JSON answer of the adjudication request
+///
+/// # Examples
+///
+/// ```rust
+/// use secretary::chunking::{ChunkValue, MergePolicy, MergeRationale, apply_adjudication, merge_chunk_values};
+/// use serde_json::json;
+///
+/// let chunks = vec![
+///     ChunkValue::new(json!({"invoice_number": "INV-9"}), "See also INV-9."),
+///     ChunkValue::new(json!({"invoice_number": "INV-7"}), "INVOICE INV-7"),
+/// ];
+/// let mut merge = merge_chunk_values(&json!({"invoice_number": ""}), &chunks, MergePolicy::Adjudicate);
+/// assert_eq!(merge.value["invoice_number"], "INV-9");
+///
+/// apply_adjudication(
+///     &mut merge,
+///     &json!({"invoice_number": {"choice": 2, "reason": "The header names the invoice"}}),
+/// );
+/// assert_eq!(merge.value["invoice_number"], "INV-7");
+/// assert_eq!(
+///     merge.conflicts[0].rationale,
+///     MergeRationale::Adjudicated { reason: Some("The header names the invoice".to_string()) }
+/// );
+/// ```
+pub fn apply_adjudication(merge: &mut ChunkMerge, answer: &Value) {
+    for conflict in &mut merge.conflicts {
+        let Some(verdict) = answer.get(&conflict.field) else {
+            continue;
+        };
+        let choice: Option<usize> = read_choice(verdict.get("choice").unwrap_or(verdict));
+        let Some(winner) = choice
+            .and_then(|choice| choice.checked_sub(1))
+            .filter(|winner| *winner < conflict.candidates.len())
+        else {
+            continue;
+        };
+
+        conflict.winner = winner;
+        conflict.rationale = MergeRationale::Adjudicated {
+            reason: verdict
+                .get("reason")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+        };
+        if let Some(slot) = value_at_path(&mut merge.value, &conflict.field) {
+            *slot = conflict.candidates[winner].value.clone();
+        }
+    }
+}
+
+/// Creates the messages asking the model to merge the chunk results of a Task.
+///
+/// The first message is the same prefix as `Task::make_prompt_messages`, so it is served
+/// from the provider's prompt cache after the chunk requests.
+///
+/// # Arguments
+///
+/// * `task` - The Task whose results are merged
+/// * `results` - The per-chunk results in document order
+/// * `additional_instructions` - Extra instructions to guide the extraction process
+///
+/// # Examples
+///
+/// ```rust
+/// use secretary::Task;
+/// use secretary::chunking::make_reduce_prompt;
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Task, Serialize, Deserialize, Debug)]
+/// struct Person {
+///     #[task(instruction = "Extract the name")]
+///     pub name: String,
+/// }
+///
+/// let task = Person::new();
+/// let results = vec![Person { name: "Ada".to_string() }, Person { name: "Ada Lovelace".to_string() }];
+/// let messages = make_reduce_prompt(&task, &results, &vec![]);
+///
+/// assert_eq!(messages.len(), 2);
+/// assert_eq!(messages[0].content, task.make_prompt_messages("", &vec![])[0].content);
+/// assert!(messages[1].content.as_str().starts_with("The document was too long"));
+/// assert!(messages[1].content.as_str().contains("Merge the partial results"));
+/// assert!(messages[1].content.as_str().ends_with(
+///     "[\n  {\n    \"name\": \"Ada\"\n  },\n  {\n    \"name\": \"Ada Lovelace\"\n  }\n]"
+/// ));
+/// ```
+pub fn make_reduce_prompt<T: Task>(
+    task: &T,
+    results: &[T],
+    additional_instructions: &[String],
+) -> Vec<Message> {
+    vec![
+        Message::system(format!(
+            "{}{}",
+            task.get_system_prompt(),
+            format_additional_instructions(additional_instructions)
+        )),
+        Message::user(format!(
+            "{}\n{}",
+            REDUCE_INSTRUCTION,
+            serde_json::to_string_pretty(results).unwrap_or_default()
+        )),
+    ]
+}
+
+/// Creates the messages asking the model to pick the winner of every conflict of a merge.
+///
+/// The first message is the same prefix as `Task::make_prompt_messages`, so it is served
+/// from the provider's prompt cache after the chunk requests. The second lists every
+/// conflicting field with its values, numbered from 1, the chunks that gave each value and
+/// the excerpt it was read from.
+///
+/// # Arguments
+///
+/// * `task` - The Task whose results are merged
+/// * `conflicts` - The conflicts of the merge
+/// * `additional_instructions` - Extra instructions to guide the extraction process
+///
+/// # Examples
+///
+/// ```rust
+/// use secretary::Task;
+/// use secretary::chunking::{ChunkValue, MergePolicy, make_adjudication_prompt, merge_chunk_values};
+/// use serde::{Deserialize, Serialize};
+/// use serde_json::json;
+///
+/// #[derive(Task, Serialize, Deserialize, Debug)]
+/// struct Invoice {
+///     #[task(instruction = "Extract the invoice number")]
+///     pub invoice_number: String,
+/// }
+///
+/// let chunks = vec![
+///     ChunkValue::new(json!({"invoice_number": "INV-7"}), "INVOICE INV-7"),
+///     ChunkValue::new(json!({"invoice_number": "INV-9"}), "Unrelated text."),
+/// ];
+/// let merge = merge_chunk_values(&json!({"invoice_number": ""}), &chunks, MergePolicy::Adjudicate);
+/// let messages = make_adjudication_prompt(&Invoice::new(), &merge.conflicts, &vec![]);
+///
+/// assert!(messages[1].content.as_str().ends_with(
+///     "\"invoice_number\":\n\
+///      1. \"INV-7\", from chunk 1, read from: \"INVOICE INV-7\"\n\
+///      2. \"INV-9\", from chunk 2, not found in the text"
+/// ));
+/// ```
+pub fn make_adjudication_prompt<T: Task>(
+    task: &T,
+    conflicts: &[MergeConflict],
+    additional_instructions: &[String],
+) -> Vec<Message> {
+    let mut listing: String = String::new();
+    for conflict in conflicts {
+        listing.push_str(&format!("\n\"{}\":", conflict.field));
+        for (number, candidate) in conflict.candidates.iter().enumerate() {
+            let chunks: Vec<String> = candidate
+                .chunks
+                .iter()
+                .map(|chunk| (chunk + 1).to_string())
+                .collect();
+            let source: String = match &candidate.excerpt {
+                Some(excerpt) => format!("read from: {}", Value::from(excerpt.as_str())),
+                None => "not found in the text".to_string(),
+            };
+            listing.push_str(&format!(
+                "\n{}. {}, from chunk{} {}, {}",
+                number + 1,
+                candidate.value,
+                if chunks.len() == 1 { "" } else { "s" },
+                chunks.join(", "),
+                source
+            ));
+        }
+        listing.push('\n');
+    }
+
+    vec![
+        Message::system(format!(
+            "{}{}",
+            task.get_system_prompt(),
+            format_additional_instructions(additional_instructions)
+        )),
+        Message::user(format!(
+            "{}\n{}",
+            ADJUDICATION_INSTRUCTION,
+            listing.trim_end()
+        )),
+    ]
+}
+
+/// Splits text into paragraphs at blank lines, trimming each.
+fn split_paragraphs(text: &str) -> Vec<String> {
+    let mut paragraphs: Vec<String> = Vec::new();
+    let mut lines: Vec<&str> = Vec::new();
+    for line in text.lines().chain(std::iter::once("")) {
+        if line.trim().is_empty() {
+            if !lines.is_empty() {
+                paragraphs.push(lines.join("\n").trim().to_string());
+                lines.clear();
+            }
+        } else {
+            lines.push(line);
+        }
+    }
+
+    paragraphs
+}
+
+/// Splits a paragraph into pieces of at most `size` characters between words.
+fn split_words(paragraph: &str, size: usize) -> Vec<String> {
+    let mut pieces: Vec<String> = Vec::new();
+    let mut current: String = String::new();
+    for word in paragraph.split_inclusive(char::is_whitespace) {
+        let candidate_length: usize = current.chars().count() + word.trim_end().chars().count();
+        if !current.trim().is_empty() && candidate_length > size {
+            pieces.push(current.trim().to_string());
+            current.clear();
+        }
+        current.push_str(word);
+
+        // A word longer than a piece is split anywhere
+        while current.trim_end().chars().count() > size {
+            let head: String = current.chars().take(size).collect();
+            current = current.chars().skip(size).collect();
+            pieces.push(head.trim().to_string());
+        }
+    }
+    if !current.trim().is_empty() {
+        pieces.push(current.trim().to_string());
+    }
+
+    pieces
+}
+
+/// Returns up to `overlap` characters from the end of a chunk, starting at a word boundary.
+fn overlap_tail(chunk: &str, overlap: usize) -> String {
+    if overlap == 0 {
+        return String::new();
+    }
+
+    let length: usize = chunk.chars().count();
+    let tail: String = chunk.chars().skip(length.saturating_sub(overlap)).collect();
+    if length <= overlap {
+        return tail.trim().to_string();
+    }
+
+    match tail.find(char::is_whitespace) {
+        Some(boundary) => tail[boundary..].trim().to_string(),
+        None => String::new(),
+    }
+}
+
+/// Merges `value` into `merged`, keeping the values of `merged` that differ from `defaults`.
+fn merge_value(merged: &mut Value, value: &Value, defaults: &Value) {
+    match (merged, value) {
+        (Value::Object(merged_map), Value::Object(map)) => {
+            for (name, field) in map {
+                let default: &Value = defaults.get(name).unwrap_or(&Value::Null);
+                match merged_map.get_mut(name) {
+                    Some(existing) => merge_value(existing, field, default),
+                    None => {
+                        merged_map.insert(name.clone(), field.clone());
+                    }
+                }
+            }
+        }
+        (Value::Array(merged_items), Value::Array(items)) => {
+            for item in items {
+                if !merged_items.contains(item) {
+                    merged_items.push(item.clone());
+                }
+            }
+        }
+        (merged, value) => {
+            if merged == defaults && value != defaults {
+                *merged = value.clone();
+            }
+        }
+    }
+}
+
+/// Merges the values chunks gave a field, collecting sources and conflicts.
+struct Merger<'a> {
+    chunks: &'a [ChunkValue],
+    policy: MergePolicy,
+    sources: BTreeMap<String, Vec<usize>>,
+    conflicts: Vec<MergeConflict>,
+}
+
+impl<'a> Merger<'a> {
+    /// Merges the values of the field at `path`, given with the index of their chunk.
+    fn merge(&mut self, path: &str, default: &Value, values: Vec<(usize, &'a Value)>) -> Value {
+        let filled: Vec<(usize, &'a Value)> = values
+            .into_iter()
+            .filter(|(_, value)| *value != default && !value.is_null())
+            .collect();
+        if filled.is_empty() {
+            return default.clone();
+        }
+
+        if filled.iter().all(|(_, value)| value.is_object()) {
+            let mut names: Vec<&String> = default
+                .as_object()
+                .into_iter()
+                .flatten()
+                .map(|(name, _)| name)
+                .collect();
+            for (_, value) in &filled {
+                for name in value
+                    .as_object()
+                    .into_iter()
+                    .flatten()
+                    .map(|(name, _)| name)
+                {
+                    if !names.contains(&name) {
+                        names.push(name);
+                    }
+                }
+            }
+
+            let mut merged: Map<String, Value> = Map::new();
+            for name in names {
+                let field_default: &Value = default.get(name).unwrap_or(&Value::Null);
+                let field_values: Vec<(usize, &'a Value)> = filled
+                    .iter()
+                    .filter_map(|(chunk, value)| value.get(name).map(|field| (*chunk, field)))
+                    .collect();
+                let field_path: String = if path.is_empty() {
+                    name.clone()
+                } else {
+                    format!("{}.{}", path, name)
+                };
+                merged.insert(
+                    name.clone(),
+                    self.merge(&field_path, field_default, field_values),
+                );
+            }
+            return Value::Object(merged);
+        }
+
+        let chunks: Vec<usize> = filled.iter().map(|(chunk, _)| *chunk).collect();
+        self.sources.insert(path.to_string(), chunks);
+
+        if filled.iter().all(|(_, value)| value.is_array()) {
+            let mut items: Vec<Value> = default.as_array().cloned().unwrap_or_default();
+            for item in filled
+                .iter()
+                .flat_map(|(_, value)| value.as_array().into_iter().flatten())
+            {
+                if !items.contains(item) {
+                    items.push(item.clone());
+                }
+            }
+            return Value::Array(items);
+        }
+
+        let mut candidates: Vec<MergeCandidate> = Vec::new();
+        for (chunk, value) in filled {
+            let confidence: Option<f32> = self.confidence(chunk, path);
+            match candidates
+                .iter_mut()
+                .find(|candidate| candidate.value == *value)
+            {
+                Some(candidate) => {
+                    candidate.chunks.push(chunk);
+                    candidate.confidence = match (candidate.confidence, confidence) {
+                        (Some(existing), Some(confidence)) => Some(existing.max(confidence)),
+                        (existing, confidence) => existing.or(confidence),
+                    };
+                }
+                None => candidates.push(MergeCandidate {
+                    value: value.clone(),
+                    chunks: vec![chunk],
+                    confidence,
+                    excerpt: None,
+                }),
+            }
+        }
+        if candidates.len() == 1 {
+            return candidates.remove(0).value;
+        }
+
+        for candidate in &mut candidates {
+            candidate.excerpt = candidate
+                .chunks
+                .iter()
+                .find_map(|chunk| find_excerpt(&self.chunks[*chunk].text, &candidate.value));
+        }
+        let (winner, rationale) = pick_winner(&candidates, self.policy);
+        let value: Value = candidates[winner].value.clone();
+        self.conflicts.push(MergeConflict {
+            field: path.to_string(),
+            candidates,
+            winner,
+            rationale,
+        });
+        value
+    }
+
+    /// Returns the confidence of a chunk in a field, or else in its closest parent.
+    fn confidence(&self, chunk: usize, path: &str) -> Option<f32> {
+        let confidence: &BTreeMap<String, f32> = &self.chunks[chunk].confidence;
+        let mut path: &str = path;
+        loop {
+            if let Some(confidence) = confidence.get(path) {
+                return Some(*confidence);
+            }
+            path = &path[..path.rfind('.')?];
+        }
+    }
+}
+
+/// Picks the winner of a conflict, falling back to the earliest value when the policy
+/// cannot separate the values.
+fn pick_winner(candidates: &[MergeCandidate], policy: MergePolicy) -> (usize, MergeRationale) {
+    match policy {
+        MergePolicy::HighestConfidence => {
+            let confidences: Vec<f32> = candidates
+                .iter()
+                .map(|candidate| candidate.confidence.unwrap_or(-1.0))
+                .collect();
+            let best: f32 = confidences.iter().copied().fold(-1.0, f32::max);
+            let winners: Vec<usize> = (0..candidates.len())
+                .filter(|index| confidences[*index] == best)
+                .collect();
+            match winners[..] {
+                [winner] => (
+                    winner,
+                    MergeRationale::HighestConfidence { confidence: best },
+                ),
+                _ => (0, MergeRationale::EarliestChunk),
+            }
+        }
+        MergePolicy::MostFrequent => {
+            let most: usize = candidates
+                .iter()
+                .map(|candidate| candidate.chunks.len())
+                .max()
+                .unwrap_or(0);
+            let winners: Vec<usize> = (0..candidates.len())
+                .filter(|index| candidates[*index].chunks.len() == most)
+                .collect();
+            match winners[..] {
+                [winner] => (winner, MergeRationale::MostFrequent { chunks: most }),
+                _ => (0, MergeRationale::EarliestChunk),
+            }
+        }
+        MergePolicy::Llm | MergePolicy::FirstNonDefault | MergePolicy::Adjudicate => {
+            (0, MergeRationale::EarliestChunk)
+        }
+    }
+}
+
+/// Returns the text around a value in a chunk, if the value is a string, number or boolean
+/// found in it.
+fn find_excerpt(text: &str, value: &Value) -> Option<String> {
+    let quote: String = match value {
+        Value::String(text) => text.clone(),
+        Value::Number(_) | Value::Bool(_) => value.to_string(),
+        _ => return None,
+    };
+    let range = locate_quote(text, &quote).0?;
+
+    let start: usize = text[..range.start]
+        .char_indices()
+        .rev()
+        .nth(EXCERPT_CONTEXT - 1)
+        .map_or(0, |(index, _)| index);
+    let end: usize = text[range.end..]
+        .char_indices()
+        .nth(EXCERPT_CONTEXT)
+        .map_or(text.len(), |(index, _)| range.end + index);
+    Some(text[start..end].trim().to_string())
+}
+
+/// Reads the number of a choice from a number or a numeric string.
+fn read_choice(value: &Value) -> Option<usize> {
+    match value {
+        Value::Number(number) => number.as_u64().map(|number| number as usize),
+        Value::String(text) => text.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// Returns the value at a field path such as `address.city`.
+fn value_at_path<'a>(value: &'a mut Value, path: &str) -> Option<&'a mut Value> {
+    path.split('.')
+        .try_fold(value, |value, name| value.get_mut(name))
+}
@@ -0,0 +1,469 @@
+//! Multi-label classification against a fixed label set.
+//!
+//! Modeling classification as a Task takes a field per label, which does not scale to large
+//! label sets. `Classifier::classify_multi_label` instead lists the labels in the prompt and
+//! asks for one `{"label", "applies", "confidence"}` entry per label, and `classify` returns
+//! the most likely label of the set. Answers are checked with `parse_label_scores`:
+//!
+//! - labels are matched to the set ignoring case, surrounding whitespace, quotes and trailing
+//!   punctuation, and reported as they are listed. A label that is not in the set fails with
+//!   `SecretaryError::ValueNotAllowed`
+//! - a label answered more than once keeps its first entry
+//! - confidences are clamped to `0..=1`, and numbers given as strings or percentages such as
+//!   `"85%"` are read. A missing confidence is 1 for a label that applies and 0 otherwise, and
+//!   a missing `applies` is whether the confidence is at least 0.5
+//! - labels without an entry fail with `SecretaryError::MissingLabels` under
+//!   `MissingLabelPolicy::Error`, the default, and are filled in as not applying with
+//!   confidence 0 under `MissingLabelPolicy::Fill`
+//!
+//! The scores are returned in the order of the label set.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::SecretaryError;
+//! use secretary::classification::{LabelScore, MissingLabelPolicy, parse_label_scores};
+//!
+//! let labels = ["billing", "bug", "feature request"];
+//! let content = r#"{"labels": [
+//!     {"label": "Bug", "applies": true, "confidence": 1.4},
+//!     {"label": "billing", "applies": false, "confidence": "5%"},
+//!     {"label": "bug", "applies": false, "confidence": 0.1}
+//! ]}"#;
+//!
+//! // "feature request" has no entry
+//! assert!(matches!(
+//!     parse_label_scores(content, &labels, MissingLabelPolicy::Error),
+//!     Err(SecretaryError::MissingLabels { labels }) if labels == vec!["feature request"]
+//! ));
+//!
+//! let scores: Vec<LabelScore> =
+//!     parse_label_scores(content, &labels, MissingLabelPolicy::Fill).unwrap();
+//! assert_eq!(
+//!     scores,
+//!     vec![
+//!         LabelScore::new("billing", false, 0.05),
+//!         LabelScore::new("bug", true, 1.0),
+//!         LabelScore::new("feature request", false, 0.0),
+//!     ]
+//! );
+//!
+//! // Labels outside the set are rejected
+//! let content = r#"[{"label": "refund", "applies": true, "confidence": 0.9}]"#;
+//! assert!(matches!(
+//!     parse_label_scores(content, &labels, MissingLabelPolicy::Fill),
+//!     Err(SecretaryError::ValueNotAllowed { value, .. }) if value == "refund"
+//! ));
+//! ```
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{
+    SecretaryError,
+    message::Message,
+    refusal::detect_refusal,
+    traits::{AsyncGenerateData, GenerateData, extract_json_content},
+    utilities::{cleanup_thinking_blocks, format_additional_instructions},
+    vocabulary::{AllowedValueMatch, MatchKind, match_allowed_value},
+};
+
+/// Explains the answer format to the model, after the label list.
+const CLASSIFICATION_INSTRUCTION: &str = r#"Answer with a single JSON object of the form {"labels": [{"label": "...", "applies": true, "confidence": 0.9}, ...]} holding exactly one entry for every label above, in the same order.
+- "label" is the label exactly as it is listed. Do not add labels that are not listed.
+- "applies" tells whether the label applies to the text.
+- "confidence" is the probability, from 0 to 1, that the label applies."#;
+
+/// Added to the instruction when exactly one label applies.
+const SINGLE_LABEL_INSTRUCTION: &str = "- Exactly one label applies to the text.";
+
+/// Added to the instruction when any number of labels applies.
+const MULTI_LABEL_INSTRUCTION: &str = "- Any number of labels, including none, may apply.";
+
+/// How much a label applies to the text.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LabelScore {
+    /// The label, as it is listed in the label set.
+    pub label: String,
+    /// Whether the label applies.
+    pub applies: bool,
+    /// The probability that the label applies, from 0 to 1.
+    pub confidence: f32,
+}
+
+impl LabelScore {
+    /// Creates a score, clamping the confidence to `0..=1`.
+    pub fn new(label: impl Into<String>, applies: bool, confidence: f32) -> Self {
+        Self {
+            label: label.into(),
+            applies,
+            confidence: clamp_confidence(confidence),
+        }
+    }
+}
+
+/// What happens to labels the answer has no entry for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum MissingLabelPolicy {
+    /// Fail with `SecretaryError::MissingLabels`.
+    #[default]
+    Error,
+    /// Score them as not applying, with confidence 0.
+    Fill,
+}
+
+/// Classifies text against a fixed label set, see the module documentation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Classifier {
+    /// What happens to labels the answer has no entry for.
+    pub missing_labels: MissingLabelPolicy,
+}
+
+impl Classifier {
+    /// Creates a classifier that fails on missing labels.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets what happens to labels the answer has no entry for.
+    pub fn with_missing_labels(mut self, missing_labels: MissingLabelPolicy) -> Self {
+        self.missing_labels = missing_labels;
+        self
+    }
+
+    /// Scores every label of the set against the target in one request.
+    ///
+    /// # Arguments
+    ///
+    /// * `llm` - The provider to classify with
+    /// * `labels` - The label set
+    /// * `target` - The text to classify
+    /// * `additional_instructions` - Extra instructions to guide the classification
+    ///
+    /// # Returns
+    ///
+    /// One score per label in the order of `labels`, empty without sending a request when
+    /// there are no labels
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the request, or those of `parse_label_scores`.
+    pub fn classify_multi_label<L: GenerateData>(
+        &self,
+        llm: &L,
+        labels: &[&str],
+        target: &str,
+        additional_instructions: &[String],
+    ) -> Result<Vec<LabelScore>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        self.score(llm, labels, target, additional_instructions, false)
+    }
+
+    /// Asynchronously scores every label of the set against the target in one request.
+    ///
+    /// This is the async version of `classify_multi_label`.
+    pub async fn async_classify_multi_label<L: AsyncGenerateData + Sync>(
+        &self,
+        llm: &L,
+        labels: &[&str],
+        target: &str,
+        additional_instructions: &[String],
+    ) -> Result<Vec<LabelScore>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        self.async_score(llm, labels, target, additional_instructions, false)
+            .await
+    }
+
+    /// Returns the label of the set that most likely applies to the target.
+    ///
+    /// The model is told that exactly one label applies, and the label with the highest
+    /// confidence is returned, the first listed on a tie.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SecretaryError::BuildRequestError` when there are no labels, and the errors
+    /// of `classify_multi_label` otherwise.
+    pub fn classify<L: GenerateData>(
+        &self,
+        llm: &L,
+        labels: &[&str],
+        target: &str,
+        additional_instructions: &[String],
+    ) -> Result<LabelScore, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        require_labels(labels)?;
+        let scores: Vec<LabelScore> =
+            self.score(llm, labels, target, additional_instructions, true)?;
+
+        Ok(most_likely(scores))
+    }
+
+    /// Asynchronously returns the label of the set that most likely applies to the target.
+    ///
+    /// This is the async version of `classify`.
+    pub async fn async_classify<L: AsyncGenerateData + Sync>(
+        &self,
+        llm: &L,
+        labels: &[&str],
+        target: &str,
+        additional_instructions: &[String],
+    ) -> Result<LabelScore, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        require_labels(labels)?;
+        let scores: Vec<LabelScore> = self
+            .async_score(llm, labels, target, additional_instructions, true)
+            .await?;
+
+        Ok(most_likely(scores))
+    }
+
+    /// Sends the classification request and parses its answer.
+    fn score<L: GenerateData>(
+        &self,
+        llm: &L,
+        labels: &[&str],
+        target: &str,
+        additional_instructions: &[String],
+        single_label: bool,
+    ) -> Result<Vec<LabelScore>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        if labels.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let messages: Vec<Message> =
+            make_classification_request(labels, target, additional_instructions, single_label);
+        let response: String =
+            llm.send_messages_with_options(messages, true, &Default::default())?;
+        let content: String = extract_json_content(llm, &response)?;
+
+        Ok(parse_label_scores(&content, labels, self.missing_labels)?)
+    }
+
+    /// Asynchronously sends the classification request and parses its answer.
+    async fn async_score<L: AsyncGenerateData + Sync>(
+        &self,
+        llm: &L,
+        labels: &[&str],
+        target: &str,
+        additional_instructions: &[String],
+        single_label: bool,
+    ) -> Result<Vec<LabelScore>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        if labels.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let messages: Vec<Message> =
+            make_classification_request(labels, target, additional_instructions, single_label);
+        let response: String = llm
+            .async_send_messages_with_options(messages, true, &Default::default())
+            .await?;
+        let content: String = extract_json_content(llm, &response)?;
+
+        Ok(parse_label_scores(&content, labels, self.missing_labels)?)
+    }
+}
+
+/// Parses the model's answer to a classification request into one score per label.
+///
+/// The answer is a JSON object with a `labels` array of entries, or the array itself. See the
+/// module documentation for how sloppy entries are read.
+///
+/// # Arguments
+///
+/// * `content` - The content the model returned
+/// * `labels` - The label set
+/// * `missing_labels` - What happens to labels without an entry
+///
+/// # Errors
+///
+/// Returns `SecretaryError::JsonParsingError` if the answer holds no array of entries or an
+/// entry has no label, `SecretaryError::Refused` if it is a refusal,
+/// `SecretaryError::ValueNotAllowed` for a label outside the set, and
+/// `SecretaryError::MissingLabels` for labels without an entry under
+/// `MissingLabelPolicy::Error`.
+pub fn parse_label_scores(
+    content: &str,
+    labels: &[&str],
+    missing_labels: MissingLabelPolicy,
+) -> Result<Vec<LabelScore>, SecretaryError> {
+    let parsing_error = |message: String| match detect_refusal(content) {
+        Some(refusal) => SecretaryError::Refused { message: refusal },
+        None => SecretaryError::JsonParsingError {
+            message,
+            raw_content: content.to_string(),
+        },
+    };
+
+    let value: Value = serde_json::from_str(cleanup_thinking_blocks(content.to_string()).trim())
+        .map_err(|error| parsing_error(error.to_string()))?;
+    let entries: Vec<Value> = match value {
+        Value::Array(entries) => entries,
+        Value::Object(mut map) => match map.remove("labels") {
+            Some(Value::Array(entries)) => entries,
+            _ => {
+                return Err(parsing_error(
+                    "Expected an object with a `labels` array".to_string(),
+                ));
+            }
+        },
+        _ => {
+            return Err(parsing_error(
+                "Expected an object with a `labels` array".to_string(),
+            ));
+        }
+    };
+
+    let allowed: Vec<String> = labels.iter().map(|label| label.to_string()).collect();
+    let mut scores: Vec<Option<LabelScore>> = vec![None; labels.len()];
+    for (index, entry) in entries.iter().enumerate() {
+        let Some(entry) = entry.as_object() else {
+            return Err(parsing_error(format!("Entry {} is not an object", index)));
+        };
+        let Some(answer) = entry.get("label").and_then(Value::as_str) else {
+            return Err(parsing_error(format!("Entry {} has no label", index)));
+        };
+
+        let label: String = match match_allowed_value(answer, &allowed) {
+            AllowedValueMatch::Matched {
+                value,
+                kind: MatchKind::Exact,
+            } => value,
+            _ => {
+                return Err(SecretaryError::ValueNotAllowed {
+                    field_path: format!("labels[{}].label", index),
+                    value: answer.to_string(),
+                    allowed,
+                });
+            }
+        };
+        let position: usize = allowed
+            .iter()
+            .position(|allowed| *allowed == label)
+            .unwrap_or_default();
+        // A label answered more than once keeps its first entry
+        if scores[position].is_none() {
+            scores[position] = Some(read_entry(label, entry));
+        }
+    }
+
+    let missing: Vec<String> = allowed
+        .iter()
+        .zip(&scores)
+        .filter(|(_, score)| score.is_none())
+        .map(|(label, _)| label.clone())
+        .collect();
+    if !missing.is_empty() && missing_labels == MissingLabelPolicy::Error {
+        return Err(SecretaryError::MissingLabels { labels: missing });
+    }
+
+    Ok(allowed
+        .into_iter()
+        .zip(scores)
+        .map(|(label, score)| score.unwrap_or_else(|| LabelScore::new(label, false, 0.0)))
+        .collect())
+}
+
+/// Reads the `applies` and `confidence` of an entry, filling in the one that is missing from
+/// the other.
+fn read_entry(label: String, entry: &Map<String, Value>) -> LabelScore {
+    let applies: Option<bool> = entry.get("applies").and_then(read_bool);
+    let confidence: Option<f32> = entry.get("confidence").and_then(read_confidence);
+
+    match (applies, confidence) {
+        (Some(applies), Some(confidence)) => LabelScore::new(label, applies, confidence),
+        (Some(applies), None) => LabelScore::new(label, applies, if applies { 1.0 } else { 0.0 }),
+        (None, Some(confidence)) => LabelScore::new(label, confidence >= 0.5, confidence),
+        (None, None) => LabelScore::new(label, false, 0.0),
+    }
+}
+
+/// Reads a boolean, also from `"true"`, `"yes"`, `"false"` and `"no"`.
+fn read_bool(value: &Value) -> Option<bool> {
+    match value {
+        Value::Bool(value) => Some(*value),
+        Value::String(text) => match text.trim().to_lowercase().as_str() {
+            "true" | "yes" => Some(true),
+            "false" | "no" => Some(false),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Reads a confidence from a number, a numeric string or a percentage such as `"85%"`.
+pub(crate) fn read_confidence(value: &Value) -> Option<f32> {
+    match value {
+        Value::Number(number) => number.as_f64().map(|number| number as f32),
+        Value::String(text) => {
+            let text: &str = text.trim();
+            match text.strip_suffix('%') {
+                Some(percent) => percent
+                    .trim()
+                    .parse::<f32>()
+                    .ok()
+                    .map(|percent| percent / 100.0),
+                None => text.parse::<f32>().ok(),
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Clamps a confidence to `0..=1`, reading NaN as 0.
+pub(crate) fn clamp_confidence(confidence: f32) -> f32 {
+    if confidence.is_nan() {
+        0.0
+    } else {
+        confidence.clamp(0.0, 1.0)
+    }
+}
+
+/// Fails when there is no label to choose from.
+fn require_labels(labels: &[&str]) -> Result<(), SecretaryError> {
+    if labels.is_empty() {
+        return Err(SecretaryError::BuildRequestError(
+            "No labels to classify with".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Returns the score with the highest confidence, the first on a tie.
+fn most_likely(scores: Vec<LabelScore>) -> LabelScore {
+    scores
+        .into_iter()
+        .reduce(|best, score| {
+            if score.confidence > best.confidence {
+                score
+            } else {
+                best
+            }
+        })
+        .unwrap_or_else(|| LabelScore::new("", false, 0.0))
+}
+
+/// Creates the classification request: the label list and answer format, then the target.
+fn make_classification_request(
+    labels: &[&str],
+    target: &str,
+    additional_instructions: &[String],
+    single_label: bool,
+) -> Vec<Message> {
+    let listed: String = labels
+        .iter()
+        .map(|label| format!("- {}\n", label))
+        .collect();
+    let cardinality: &str = if single_label {
+        SINGLE_LABEL_INSTRUCTION
+    } else {
+        MULTI_LABEL_INSTRUCTION
+    };
+
+    vec![
+        Message::system(format!(
+            "Classify the text with these labels:\n{}\n{}\n{}{}",
+            listed,
+            CLASSIFICATION_INSTRUCTION,
+            cardinality,
+            format_additional_instructions(additional_instructions)
+        )),
+        Message::user(format!("This is the text to classify:\n{}", target)),
+    ]
+}
@@ -0,0 +1,816 @@
+//! Tasks compiled once and extracted many times.
+//!
+//! Every extraction renders the Task's prompts again: the system prompt walks the field
+//! metadata and serializes a template instance, and distributed generation does so for every
+//! field. `Task::compile` renders them once into a `CompiledTask`, with a placeholder where
+//! the target goes, and caches the field descriptors and JSON Schema as well. Each call then
+//! only joins the target into the rendered messages.
+//!
+//! The generate methods take any `ExtractionPlan`: a Task, which is rendered on every call,
+//! or a `CompiledTask`. The requests are byte for byte the same either way.
+//!
+//! Additional instructions given to `CompileOptions` are rendered into the compiled prompts.
+//! Instructions passed to a generate method are added after them, and since the prompts then
+//! differ from the compiled ones, they are rendered for that call.
+//!
+//! `CompileOptions::with_instruction_overrides` replaces the instructions of fields by their
+//! schema path, e.g. with the shorter ones proposed by `optimize::compress_instructions`, without
+//! editing the `#[task(instruction = "...")]` attributes. Only a `CompiledTask` compiled with
+//! them uses the replacements; the Task itself keeps its prompts.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::Task;
+//! use secretary::compiled::{CompileOptions, CompiledTask, ExtractionPlan};
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! struct Invoice {
+//!     #[task(instruction = "Extract the invoice number")]
+//!     pub number: String,
+//!     #[task(instruction = "Extract the total as a float")]
+//!     pub total: f64,
+//! }
+//!
+//! let instructions = vec!["Amounts are in EUR".to_string()];
+//! let compiled: CompiledTask<Invoice> =
+//!     Invoice::new().compile(CompileOptions::default().with_additional_instructions(instructions.clone()));
+//!
+//! // The same messages as rendering the Task with the instructions
+//! let target = "Invoice 42, total 12.50";
+//! assert_eq!(
+//!     compiled.prompt_messages(target, &vec![]),
+//!     Invoice::new().make_prompt_messages(target, &instructions)
+//! );
+//! assert_eq!(
+//!     compiled.field_requests(target, &vec![]),
+//!     Invoice::new().make_distributed_generation_requests(target, &instructions)
+//! );
+//!
+//! // Instructions given per call come after the compiled ones
+//! let more = vec!["Round to cents".to_string()];
+//! let all = vec!["Amounts are in EUR".to_string(), "Round to cents".to_string()];
+//! assert_eq!(
+//!     compiled.prompt_messages(target, &more),
+//!     Invoice::new().make_prompt_messages(target, &all)
+//! );
+//!
+//! assert_eq!(compiled.field_descriptors().len(), 2);
+//! assert_eq!(compiled.json_schema()["required"], serde_json::json!(["number", "total"]));
+//!
+//! // A shorter instruction for the total, in every prompt and in the schema
+//! let overrides = vec![("total".to_string(), "Total, float".to_string())];
+//! let shorter: CompiledTask<Invoice> =
+//!     Invoice::new().compile(CompileOptions::default().with_instruction_overrides(overrides));
+//! assert!(shorter.system_prompt().contains("total: Total, float, JSON Number"));
+//! assert!(!shorter.system_prompt().contains("Extract the total as a float"));
+//! assert_eq!(shorter.field_descriptors()[1].instruction, "Total, float");
+//! assert!(!Invoice::new().get_system_prompt().contains("Total, float"));
+//! ```
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use std::time::SystemTime;
+
+use serde_json::Value;
+
+use crate::{
+    SecretaryError,
+    distributed::FieldPrompt,
+    hints::known_values_instruction,
+    locale::ExtractionLocale,
+    message::Message,
+    request::RequestOptions,
+    schema::json_schema,
+    schema::{FieldDescriptor, FieldKind},
+    schema_check,
+    traits::{Task, make_prefixed_messages},
+    utilities::{builtin_template_vars, render_template},
+};
+
+/// Stands in for the target while the prompts are rendered.
+const TARGET_PLACEHOLDER: &str = "\u{0}secretary-target\u{0}";
+
+/// Settings for `Task::compile`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompileOptions {
+    /// The additional instructions rendered into the compiled prompts.
+    pub additional_instructions: Vec<String>,
+    /// Pairs of field path and the instruction that replaces the field's own, e.g.
+    /// `address.city` or `items[].price`. Paths of no field with an instruction are ignored.
+    pub instruction_overrides: Vec<(String, String)>,
+}
+
+impl CompileOptions {
+    /// Sets the additional instructions rendered into the compiled prompts.
+    pub fn with_additional_instructions(mut self, additional_instructions: Vec<String>) -> Self {
+        self.additional_instructions = additional_instructions;
+        self
+    }
+
+    /// Replaces the instructions of fields by their schema path in the compiled prompts,
+    /// field descriptors and JSON Schema.
+    pub fn with_instruction_overrides(
+        mut self,
+        instruction_overrides: Vec<(String, String)>,
+    ) -> Self {
+        self.instruction_overrides = instruction_overrides;
+        self
+    }
+}
+
+/// The prompts of an extraction, as used by the generate methods.
+///
+/// Implemented by every Task, which renders its prompts on every call, and by
+/// `CompiledTask`, which renders them once.
+pub trait ExtractionPlan {
+    /// The Task that is extracted.
+    type Task: Task;
+
+    /// Returns the Task that is extracted.
+    fn task(&self) -> &Self::Task;
+
+    /// Creates the messages of `Task::make_prompt_messages`.
+    fn prompt_messages(&self, target: &str, additional_instructions: &[String]) -> Vec<Message> {
+        self.task()
+            .make_prompt_messages(target, additional_instructions)
+    }
+
+    /// Creates the messages of `prompt_messages` without the fields at the given dotted paths,
+    /// which local extractors have filled.
+    fn prompt_messages_without_fields(
+        &self,
+        target: &str,
+        additional_instructions: &[String],
+        skipped_fields: &[String],
+    ) -> Vec<Message> {
+        if skipped_fields.is_empty() {
+            return self.prompt_messages(target, additional_instructions);
+        }
+
+        make_prefixed_messages(
+            self.task().get_system_prompt_without_fields(skipped_fields),
+            additional_instructions,
+            target,
+        )
+    }
+
+    /// Creates the messages of `Task::make_compact_prompt_messages`.
+    fn compact_prompt_messages(
+        &self,
+        target: &str,
+        additional_instructions: &[String],
+    ) -> Vec<Message> {
+        self.task()
+            .make_compact_prompt_messages(target, additional_instructions)
+    }
+
+    /// Creates the message of `Task::make_prompt`.
+    fn single_prompt(&self, target: &str, additional_instructions: &[String]) -> Message {
+        self.task().make_prompt(target, additional_instructions)
+    }
+
+    /// Creates the requests of `Task::make_distributed_generation_requests`.
+    fn field_requests(
+        &self,
+        target: &str,
+        additional_instructions: &[String],
+    ) -> Vec<(FieldPrompt, Message)> {
+        self.task()
+            .make_distributed_generation_requests(target, additional_instructions)
+    }
+
+    /// Returns the descriptors of `Task::field_descriptors`.
+    fn field_table(&self) -> Cow<'_, [FieldDescriptor]> {
+        Cow::Owned(Self::Task::field_descriptors())
+    }
+}
+
+impl<T: Task> ExtractionPlan for T {
+    type Task = T;
+
+    fn task(&self) -> &T {
+        self
+    }
+}
+
+/// A message rendered with a placeholder for the target, split at the placeholder.
+#[derive(Debug, Clone)]
+struct MessageTemplate {
+    /// The message without content.
+    message: Message,
+    /// The content before, between and after the places of the target.
+    segments: Vec<String>,
+}
+
+impl MessageTemplate {
+    fn new(message: Message) -> Self {
+        let segments: Vec<String> = message
+            .content
+            .as_str()
+            .split(TARGET_PLACEHOLDER)
+            .map(str::to_string)
+            .collect();
+
+        Self {
+            message: Message {
+                content: String::new().into(),
+                ..message
+            },
+            segments,
+        }
+    }
+
+    fn render(&self, target: &str) -> Message {
+        Message {
+            content: self.segments.join(target).into(),
+            ..self.message.clone()
+        }
+    }
+}
+
+/// A Task with its prompts, field descriptors and JSON Schema rendered once.
+///
+/// Created with `Task::compile`, see the module documentation.
+#[derive(Debug, Clone)]
+pub struct CompiledTask<T: Task> {
+    task: T,
+    additional_instructions: Vec<String>,
+    instruction_overrides: Vec<(String, String)>,
+    /// The field lines of the overridden fields, as rendered and as replaced.
+    field_lines: Vec<(String, String)>,
+    prompt_messages: Vec<MessageTemplate>,
+    compact_prompt_messages: Vec<MessageTemplate>,
+    single_prompt: MessageTemplate,
+    field_requests: Vec<(FieldPrompt, MessageTemplate)>,
+    field_descriptors: Vec<FieldDescriptor>,
+    json_schema: Value,
+}
+
+impl<T: Task> CompiledTask<T> {
+    /// Renders the prompts of `task` with the options.
+    pub fn new(task: T, options: CompileOptions) -> Self {
+        let mut field_descriptors: Vec<FieldDescriptor> = T::field_descriptors();
+        let mut field_lines: Vec<(String, String)> = Vec::new();
+        let mut instruction_overrides: Vec<(String, String)> = Vec::new();
+        for (path, instruction) in options.instruction_overrides {
+            if let Some(lines) =
+                override_instruction(&mut field_descriptors, "", &path, &instruction)
+            {
+                field_lines.push(lines);
+                instruction_overrides.push((path, instruction));
+            }
+        }
+
+        let instructions: &Vec<String> = &options.additional_instructions;
+        let lines: &[(String, String)] = &field_lines;
+        let templates = |messages: Vec<Message>| -> Vec<MessageTemplate> {
+            messages
+                .into_iter()
+                .map(|message| MessageTemplate::new(override_message(lines, message)))
+                .collect()
+        };
+
+        Self {
+            prompt_messages: templates(task.make_prompt_messages(TARGET_PLACEHOLDER, instructions)),
+            compact_prompt_messages: templates(
+                task.make_compact_prompt_messages(TARGET_PLACEHOLDER, instructions),
+            ),
+            single_prompt: MessageTemplate::new(override_message(
+                lines,
+                task.make_prompt(TARGET_PLACEHOLDER, instructions),
+            )),
+            field_requests: override_requests(
+                lines,
+                task.make_distributed_generation_requests(TARGET_PLACEHOLDER, instructions),
+            )
+            .into_iter()
+            .map(|(field_prompt, message)| (field_prompt, MessageTemplate::new(message)))
+            .collect(),
+            json_schema: json_schema(&field_descriptors),
+            field_descriptors,
+            additional_instructions: options.additional_instructions,
+            instruction_overrides,
+            field_lines,
+            task,
+        }
+    }
+
+    /// Returns the system prompt, including the compiled additional instructions.
+    pub fn system_prompt(&self) -> &str {
+        self.prompt_messages
+            .first()
+            .and_then(|template| template.segments.first())
+            .map(String::as_str)
+            .unwrap_or_default()
+    }
+
+    /// Returns the additional instructions rendered into the prompts.
+    pub fn additional_instructions(&self) -> &[String] {
+        &self.additional_instructions
+    }
+
+    /// Returns the instruction overrides in use, those of `CompileOptions` whose path names a
+    /// field with an instruction.
+    pub fn instruction_overrides(&self) -> &[(String, String)] {
+        &self.instruction_overrides
+    }
+
+    /// Returns the field descriptors of `T`, with the overridden instructions.
+    pub fn field_descriptors(&self) -> &[FieldDescriptor] {
+        &self.field_descriptors
+    }
+
+    /// Returns the JSON Schema of `T`, see `schema::json_schema`.
+    pub fn json_schema(&self) -> &Value {
+        &self.json_schema
+    }
+
+    /// Returns the compiled instructions followed by those of a call.
+    fn instructions_with(&self, additional_instructions: &[String]) -> Vec<String> {
+        self.additional_instructions
+            .iter()
+            .chain(additional_instructions)
+            .cloned()
+            .collect()
+    }
+
+    /// Renders messages with the placeholder for the target, replaces the overridden field
+    /// lines and joins the target.
+    fn render_overridden(&self, messages: Vec<Message>, target: &str) -> Vec<Message> {
+        messages
+            .into_iter()
+            .map(|message| {
+                MessageTemplate::new(override_message(&self.field_lines, message)).render(target)
+            })
+            .collect()
+    }
+}
+
+/// Replaces the field lines of the overridden fields in a text.
+fn override_text(field_lines: &[(String, String)], text: &str) -> String {
+    field_lines
+        .iter()
+        .fold(text.to_string(), |text, (rendered, replaced)| {
+            text.replace(rendered, replaced)
+        })
+}
+
+/// Replaces the field lines of the overridden fields in a message rendered with the placeholder
+/// for the target, so that the target itself is never changed.
+fn override_message(field_lines: &[(String, String)], message: Message) -> Message {
+    if field_lines.is_empty() {
+        return message;
+    }
+
+    Message {
+        content: override_text(field_lines, message.content.as_str()).into(),
+        ..message
+    }
+}
+
+/// Replaces the field lines of the overridden fields in field requests and their prompts.
+fn override_requests(
+    field_lines: &[(String, String)],
+    requests: Vec<(FieldPrompt, Message)>,
+) -> Vec<(FieldPrompt, Message)> {
+    if field_lines.is_empty() {
+        return requests;
+    }
+
+    requests
+        .into_iter()
+        .map(|(field_prompt, message)| {
+            (
+                override_field_prompt(field_lines, field_prompt),
+                override_message(field_lines, message),
+            )
+        })
+        .collect()
+}
+
+fn override_field_prompt(
+    field_lines: &[(String, String)],
+    field_prompt: FieldPrompt,
+) -> FieldPrompt {
+    FieldPrompt {
+        prompt: override_text(field_lines, &field_prompt.prompt),
+        members: field_prompt
+            .members
+            .into_iter()
+            .map(|member| override_field_prompt(field_lines, member))
+            .collect(),
+        ..field_prompt
+    }
+}
+
+/// Returns the schema path and instruction of every field with an instruction, e.g.
+/// `address.city` or `items[].price`, descending into nested Tasks.
+pub(crate) fn instructed_fields(fields: &[FieldDescriptor], prefix: &str) -> Vec<(String, String)> {
+    let mut instructed: Vec<(String, String)> = Vec::new();
+
+    for field in fields {
+        let path: String = if prefix.is_empty() {
+            field.name.clone()
+        } else {
+            format!("{}.{}", prefix, field.name)
+        };
+        if !field.instruction.is_empty() {
+            instructed.push((path.clone(), field.instruction.clone()));
+        }
+
+        match field.kind {
+            FieldKind::Normal => {}
+            FieldKind::Task | FieldKind::OptionTask => {
+                instructed.extend(instructed_fields(&field.children, &path))
+            }
+            FieldKind::VecTask | FieldKind::HashMapTask | FieldKind::BTreeMapTask => {
+                instructed.extend(instructed_fields(&field.children, &format!("{}[]", path)))
+            }
+        }
+    }
+
+    instructed
+}
+
+/// Sets the instruction of the field at a schema path, e.g. `address.city` or `items[].price`,
+/// and returns its field line as rendered and as replaced. Returns `None` when no field with an
+/// instruction has the path.
+fn override_instruction(
+    fields: &mut [FieldDescriptor],
+    prefix: &str,
+    path: &str,
+    instruction: &str,
+) -> Option<(String, String)> {
+    for field in fields {
+        let field_path: String = if prefix.is_empty() {
+            field.name.clone()
+        } else {
+            format!("{}.{}", prefix, field.name)
+        };
+
+        if field_path == path && !field.instruction.is_empty() {
+            // The field of a newtype is shown as `value`
+            let label: &str = if field.name.is_empty() {
+                "value"
+            } else {
+                &field.name
+            };
+            let lines: (String, String) = (
+                format!("{}: {}, ", label, field.instruction),
+                format!("{}: {}, ", label, instruction),
+            );
+            field.instruction = instruction.to_string();
+            return Some(lines);
+        }
+
+        let child_prefix: String = match field.kind {
+            FieldKind::Normal => continue,
+            FieldKind::Task | FieldKind::OptionTask => field_path,
+            FieldKind::VecTask | FieldKind::HashMapTask | FieldKind::BTreeMapTask => {
+                format!("{}[]", field_path)
+            }
+        };
+        if let Some(lines) =
+            override_instruction(&mut field.children, &child_prefix, path, instruction)
+        {
+            return Some(lines);
+        }
+    }
+
+    None
+}
+
+impl<T: Task> ExtractionPlan for CompiledTask<T> {
+    type Task = T;
+
+    fn task(&self) -> &T {
+        &self.task
+    }
+
+    fn prompt_messages(&self, target: &str, additional_instructions: &[String]) -> Vec<Message> {
+        if !additional_instructions.is_empty() {
+            return self.render_overridden(
+                self.task.make_prompt_messages(
+                    TARGET_PLACEHOLDER,
+                    &self.instructions_with(additional_instructions),
+                ),
+                target,
+            );
+        }
+
+        self.prompt_messages
+            .iter()
+            .map(|template| template.render(target))
+            .collect()
+    }
+
+    fn prompt_messages_without_fields(
+        &self,
+        target: &str,
+        additional_instructions: &[String],
+        skipped_fields: &[String],
+    ) -> Vec<Message> {
+        if skipped_fields.is_empty() {
+            return self.prompt_messages(target, additional_instructions);
+        }
+
+        self.render_overridden(
+            make_prefixed_messages(
+                self.task.get_system_prompt_without_fields(skipped_fields),
+                &self.instructions_with(additional_instructions),
+                TARGET_PLACEHOLDER,
+            ),
+            target,
+        )
+    }
+
+    fn compact_prompt_messages(
+        &self,
+        target: &str,
+        additional_instructions: &[String],
+    ) -> Vec<Message> {
+        if !additional_instructions.is_empty() {
+            return self.render_overridden(
+                self.task.make_compact_prompt_messages(
+                    TARGET_PLACEHOLDER,
+                    &self.instructions_with(additional_instructions),
+                ),
+                target,
+            );
+        }
+
+        self.compact_prompt_messages
+            .iter()
+            .map(|template| template.render(target))
+            .collect()
+    }
+
+    fn single_prompt(&self, target: &str, additional_instructions: &[String]) -> Message {
+        if !additional_instructions.is_empty() {
+            let message: Message = override_message(
+                &self.field_lines,
+                self.task.make_prompt(
+                    TARGET_PLACEHOLDER,
+                    &self.instructions_with(additional_instructions),
+                ),
+            );
+            return MessageTemplate::new(message).render(target);
+        }
+
+        self.single_prompt.render(target)
+    }
+
+    fn field_requests(
+        &self,
+        target: &str,
+        additional_instructions: &[String],
+    ) -> Vec<(FieldPrompt, Message)> {
+        if !additional_instructions.is_empty() {
+            return override_requests(
+                &self.field_lines,
+                self.task.make_distributed_generation_requests(
+                    TARGET_PLACEHOLDER,
+                    &self.instructions_with(additional_instructions),
+                ),
+            )
+            .into_iter()
+            .map(|(field_prompt, message)| {
+                (field_prompt, MessageTemplate::new(message).render(target))
+            })
+            .collect();
+        }
+
+        self.field_requests
+            .iter()
+            .map(|(field_prompt, template)| (field_prompt.clone(), template.render(target)))
+            .collect()
+    }
+
+    fn field_table(&self) -> Cow<'_, [FieldDescriptor]> {
+        Cow::Borrowed(&self.field_descriptors)
+    }
+}
+
+/// A plan with the per-call settings of the `RequestOptions` applied to its instructions:
+/// `{placeholder}`s are rendered with the template variables, see
+/// `utilities::render_template`, the known values of the hints are listed after the
+/// additional instructions, see the `hints` module, and so is the note of the locale, see
+/// the `locale` module.
+///
+/// The prompts are rendered with a placeholder for the target, so that the target is never
+/// rendered. Without template variables, hints or a locale, the prompts of the plan are used
+/// as they are.
+///
+/// The field descriptors of the plan are built on first use and kept for the rest of the
+/// call, which asks for them at every step of handling the answer.
+pub(crate) struct CallPlan<'a, P> {
+    plan: &'a P,
+    vars: Option<HashMap<String, String>>,
+    known_values: Option<String>,
+    locale_note: Option<String>,
+    fields: OnceLock<Cow<'a, [FieldDescriptor]>>,
+}
+
+impl<'a, P: ExtractionPlan> CallPlan<'a, P> {
+    /// Wraps `plan` with the template variables of `options` and the built-in ones, and the
+    /// known values of its hints.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SecretaryError::MissingTemplateVar` if a placeholder of the prompts or of
+    /// `additional_instructions` has no value, and `SecretaryError::SchemaInconsistent` under
+    /// `RequestOptions::strict_schema` if serde disagrees with the prompt of the Task.
+    pub(crate) fn new(
+        plan: &'a P,
+        options: &RequestOptions,
+        additional_instructions: &[String],
+    ) -> Result<Self, SecretaryError> {
+        if options.strict_schema {
+            schema_check::check_once::<P::Task>()?;
+        }
+        let known_values: Option<String> = known_values_instruction(&options.hints);
+        let Some(template_vars) = &options.template_vars else {
+            return Ok(Self {
+                plan,
+                vars: None,
+                known_values,
+                locale_note: None,
+                fields: OnceLock::new(),
+            });
+        };
+
+        let mut vars: HashMap<String, String> = builtin_template_vars(SystemTime::now());
+        vars.extend(template_vars.clone());
+
+        let messages = plan
+            .prompt_messages(TARGET_PLACEHOLDER, additional_instructions)
+            .into_iter()
+            .chain(
+                plan.field_requests(TARGET_PLACEHOLDER, additional_instructions)
+                    .into_iter()
+                    .map(|(_, message)| message),
+            );
+        for message in messages {
+            render_template(message.content.as_str(), &vars)?;
+        }
+
+        Ok(Self {
+            plan,
+            vars: Some(vars),
+            // Known values are data, so their braces are escaped rather than rendered
+            known_values: known_values.map(|instruction| instruction.replace('{', "{{")),
+            locale_note: None,
+            fields: OnceLock::new(),
+        })
+    }
+
+    /// Adds the note describing the conventions of the target, when there is a locale.
+    pub(crate) fn with_locale(mut self, locale: Option<&ExtractionLocale>) -> Self {
+        self.locale_note = locale.map(|locale| match self.vars {
+            Some(_) => locale.prompt_note().replace('{', "{{"),
+            None => locale.prompt_note(),
+        });
+        self
+    }
+
+    /// Returns the additional instructions followed by the known values and the note of the
+    /// locale, or the additional instructions themselves when there are neither.
+    fn instructions<'b>(&self, additional_instructions: &'b [String]) -> Cow<'b, [String]> {
+        if self.known_values.is_none() && self.locale_note.is_none() {
+            return Cow::Borrowed(additional_instructions);
+        }
+
+        let mut instructions: Vec<String> = additional_instructions.to_vec();
+        instructions.extend(self.known_values.clone());
+        instructions.extend(self.locale_note.clone());
+        Cow::Owned(instructions)
+    }
+
+    /// Renders a message created with the placeholder for the target, then joins the target.
+    fn render(&self, message: Message, target: &str, vars: &HashMap<String, String>) -> Message {
+        let content: String = message
+            .content
+            .as_str()
+            .split(TARGET_PLACEHOLDER)
+            .map(|segment| render_template(segment, vars).unwrap_or_else(|_| segment.to_string()))
+            .collect::<Vec<String>>()
+            .join(target);
+
+        Message {
+            content: content.into(),
+            ..message
+        }
+    }
+
+    fn render_all(&self, messages: Vec<Message>, target: &str) -> Vec<Message> {
+        match &self.vars {
+            Some(vars) => messages
+                .into_iter()
+                .map(|message| self.render(message, target, vars))
+                .collect(),
+            None => messages,
+        }
+    }
+}
+
+impl<P: ExtractionPlan> ExtractionPlan for CallPlan<'_, P> {
+    type Task = P::Task;
+
+    fn task(&self) -> &P::Task {
+        self.plan.task()
+    }
+
+    fn prompt_messages(&self, target: &str, additional_instructions: &[String]) -> Vec<Message> {
+        let additional_instructions: &[String] = &self.instructions(additional_instructions);
+        if self.vars.is_none() {
+            return self.plan.prompt_messages(target, additional_instructions);
+        }
+
+        self.render_all(
+            self.plan
+                .prompt_messages(TARGET_PLACEHOLDER, additional_instructions),
+            target,
+        )
+    }
+
+    fn prompt_messages_without_fields(
+        &self,
+        target: &str,
+        additional_instructions: &[String],
+        skipped_fields: &[String],
+    ) -> Vec<Message> {
+        let additional_instructions: &[String] = &self.instructions(additional_instructions);
+        if self.vars.is_none() {
+            return self.plan.prompt_messages_without_fields(
+                target,
+                additional_instructions,
+                skipped_fields,
+            );
+        }
+
+        self.render_all(
+            self.plan.prompt_messages_without_fields(
+                TARGET_PLACEHOLDER,
+                additional_instructions,
+                skipped_fields,
+            ),
+            target,
+        )
+    }
+
+    fn compact_prompt_messages(
+        &self,
+        target: &str,
+        additional_instructions: &[String],
+    ) -> Vec<Message> {
+        let additional_instructions: &[String] = &self.instructions(additional_instructions);
+        if self.vars.is_none() {
+            return self
+                .plan
+                .compact_prompt_messages(target, additional_instructions);
+        }
+
+        self.render_all(
+            self.plan
+                .compact_prompt_messages(TARGET_PLACEHOLDER, additional_instructions),
+            target,
+        )
+    }
+
+    fn single_prompt(&self, target: &str, additional_instructions: &[String]) -> Message {
+        let additional_instructions: &[String] = &self.instructions(additional_instructions);
+        match &self.vars {
+            Some(vars) => self.render(
+                self.plan
+                    .single_prompt(TARGET_PLACEHOLDER, additional_instructions),
+                target,
+                vars,
+            ),
+            None => self.plan.single_prompt(target, additional_instructions),
+        }
+    }
+
+    fn field_requests(
+        &self,
+        target: &str,
+        additional_instructions: &[String],
+    ) -> Vec<(FieldPrompt, Message)> {
+        let additional_instructions: &[String] = &self.instructions(additional_instructions);
+        match &self.vars {
+            Some(vars) => self
+                .plan
+                .field_requests(TARGET_PLACEHOLDER, additional_instructions)
+                .into_iter()
+                .map(|(field_prompt, message)| (field_prompt, self.render(message, target, vars)))
+                .collect(),
+            None => self.plan.field_requests(target, additional_instructions),
+        }
+    }
+
+    fn field_table(&self) -> Cow<'_, [FieldDescriptor]> {
+        Cow::Borrowed(self.fields.get_or_init(|| self.plan.field_table()))
+    }
+}
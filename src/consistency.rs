@@ -0,0 +1,146 @@
+//! Self-consistency extraction: sampling a prompt several times and voting on the results.
+//!
+//! On noisy inputs a model does not always read a field the same way. `generate_data_consistent`
+//! asks for `k` samples of the same prompt in one request, sent as `n`, parses every choice
+//! of the response into the Task and keeps, field by field, the value most samples agree on.
+//! Ties go to the value of the earliest choice. The samples must differ to be worth voting
+//! on, so the call needs a temperature above 0.
+//!
+//! The result carries the number of samples that were voted on in
+//! `GenerationMetadata::voted_choices`, and the share of them that agree with each field of
+//! the result in `GenerationMetadata::field_agreement`. Choices that do not parse do not
+//! vote. A provider or gateway that ignores `n` returns a single choice; the extraction
+//! then amounts to `generate_data_with_options` and reports
+//! `GenerationWarning::ChoicesIgnored`.
+//!
+//! Fields of nested Tasks are voted on one by one; any other field, lists and optional
+//! nested Tasks included, is voted on as a whole.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::Task;
+//! use secretary::consistency::vote;
+//! use serde::{Deserialize, Serialize};
+//! use serde_json::json;
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! struct Person {
+//!     #[task(instruction = "Extract the name")]
+//!     pub name: String,
+//!     #[task(instruction = "Extract the age as a number")]
+//!     pub age: u32,
+//! }
+//!
+//! let samples = vec![
+//!     json!({"name": "Ada", "age": 36}),
+//!     json!({"name": "Ada", "age": 63}),
+//!     json!({"name": "Ava", "age": 63}),
+//!     json!({"name": "Ada", "age": 36}),
+//! ];
+//! let (voted, agreement) = vote(&Person::field_descriptors(), &samples);
+//!
+//! // Three of four samples read "Ada"; the ages tie and the first choice wins
+//! assert_eq!(voted, json!({"name": "Ada", "age": 36}));
+//! assert_eq!(agreement["name"], 0.75);
+//! assert_eq!(agreement["age"], 0.5);
+//! ```
+
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+
+use crate::{
+    SecretaryError,
+    request::RequestOptions,
+    schema::{FieldDescriptor, FieldKind},
+    utilities::{get_field_path, set_field_path},
+};
+
+/// Votes on the value of every field across samples of the same Task.
+///
+/// Returns the first sample with the value of each field replaced by the most frequent
+/// value among the samples, the earliest sample's value winning ties, together with the
+/// share of samples that have the winning value by field path. A sample without a field
+/// counts as `null`. Samples that are not JSON objects, such as tuple structs, are not voted
+/// on: the first one is returned with no agreement.
+///
+/// # Arguments
+///
+/// * `fields` - The field descriptors of the Task, e.g. from `Task::field_descriptors()`
+/// * `samples` - The serialized samples, in the order of their choices
+pub fn vote(fields: &[FieldDescriptor], samples: &[Value]) -> (Value, BTreeMap<String, f64>) {
+    let mut agreement: BTreeMap<String, f64> = BTreeMap::new();
+    let Some(mut voted) = samples.first().cloned() else {
+        return (Value::Null, agreement);
+    };
+    if !voted.is_object() {
+        return (voted, agreement);
+    }
+
+    let mut paths: Vec<String> = Vec::new();
+    collect_voting_paths(fields, "", &mut paths);
+
+    for path in paths {
+        let values: Vec<&Value> = samples
+            .iter()
+            .map(|sample| get_field_path(sample, &path).unwrap_or(&Value::Null))
+            .collect();
+        let (winner, votes) = majority(&values);
+        agreement.insert(path.clone(), votes as f64 / samples.len() as f64);
+        // A path through a field the first sample left `null` cannot be set, and keeps it
+        let _ = set_field_path(&mut voted, &path, winner.clone());
+    }
+
+    (voted, agreement)
+}
+
+/// Checks that a self-consistency call asks for samples that can differ.
+pub(crate) fn check_sampling(k: usize, options: &RequestOptions) -> Result<(), SecretaryError> {
+    if k == 0 {
+        return Err(SecretaryError::BuildRequestError(
+            "self-consistency needs at least one sample".to_string(),
+        ));
+    }
+    match options.temperature {
+        Some(temperature) if temperature > 0.0 => Ok(()),
+        _ => Err(SecretaryError::BuildRequestError(
+            "self-consistency needs a temperature above 0, set with RequestOptions::with_temperature"
+                .to_string(),
+        )),
+    }
+}
+
+/// Collects the paths of the fields voted on, descending into nested Tasks.
+fn collect_voting_paths(fields: &[FieldDescriptor], prefix: &str, paths: &mut Vec<String>) {
+    for field in fields {
+        let path: String = if prefix.is_empty() {
+            field.name.clone()
+        } else {
+            format!("{}.{}", prefix, field.name)
+        };
+
+        if field.kind == FieldKind::Task && !field.children.is_empty() {
+            collect_voting_paths(&field.children, &path, paths);
+        } else {
+            paths.push(path);
+        }
+    }
+}
+
+/// Returns the most frequent value and its count, the earliest value winning ties.
+fn majority<'a>(values: &[&'a Value]) -> (&'a Value, usize) {
+    let mut best: (&Value, usize) = (values[0], 0);
+    for (index, value) in values.iter().enumerate() {
+        // Every value was counted at its first occurrence
+        if values[..index].contains(value) {
+            continue;
+        }
+        let count: usize = values.iter().filter(|other| *other == value).count();
+        if count > best.1 {
+            best = (value, count);
+        }
+    }
+
+    best
+}
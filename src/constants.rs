@@ -1,2 +1,4 @@
 pub const OPENAI_API_BASE: &str = "https://api.openai.com/v1";
 pub const OPENAI_CHAT_COMPLETION_ROUTE: &str = "/chat/completions";
+pub const OPENAI_RESPONSES_ROUTE: &str = "/responses";
+pub const JSON_ONLY_INSTRUCTION: &str = "Respond with only a JSON object, no prose.";
@@ -0,0 +1,254 @@
+//! Conversions between Tasks and the `ContextualTask` JSON envelope of earlier releases.
+//!
+//! Before `#[derive(Task)]`, extractions were stored as a `ContextualTask` envelope: the
+//! extracted fields under `data_structure`, next to the model's `reasoning`, its `notes` and
+//! optionally the `content` the data came from. `from_contextual_json` reads such a blob into
+//! a typed Task and a `ContextMeta` holding the rest of the envelope, and `to_contextual_json`
+//! writes a Task back into it, so stored blobs can be migrated one reader or writer at a time.
+//! The same conversions are available as `TryFrom<&Value>` and `From<Contextual<T>>` on
+//! `Contextual`.
+//!
+//! Older blobs stored `data_structure` as a `HashMap<String, String>`, with every value a
+//! string and nested values as JSON text. When the data does not deserialize as it is, the
+//! string values are parsed like the answers of distributed generation and coerced with
+//! `LeniencyProfile::aggressive()`: `"36"` becomes a number for a numeric field, `"yes"` a
+//! boolean, `"N/A"` a `null` in an `Option` field and `"a, b"` a list for a `Vec<String>`
+//! field, while text fields keep their strings. Keys may be dotted paths such as
+//! `address.city`.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::Task;
+//! use secretary::contextual::{ContextMeta, from_contextual_json, to_contextual_json};
+//! use serde::{Deserialize, Serialize};
+//! use serde_json::json;
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug, PartialEq)]
+//! struct Person {
+//!     #[task(instruction = "Extract the person's name")]
+//!     pub name: String,
+//!     #[task(instruction = "Extract the age as a number")]
+//!     pub age: u32,
+//!     #[task(instruction = "Extract the postal code")]
+//!     pub postal_code: String,
+//! }
+//!
+//! // A blob of the stringly era
+//! let legacy = json!({
+//!     "reasoning": "The age is stated directly.",
+//!     "notes": ["The postal code is from the letterhead"],
+//!     "data_structure": {"name": "Ada", "age": "36", "postal_code": "02134"},
+//!     "content": "Ada, 36, writes from 02134.",
+//!     "source": "crm"
+//! });
+//!
+//! let (person, meta): (Person, ContextMeta) = from_contextual_json(&legacy).unwrap();
+//! assert_eq!(person, Person { name: "Ada".to_string(), age: 36, postal_code: "02134".to_string() });
+//! assert_eq!(meta.reasoning, "The age is stated directly.");
+//! assert_eq!(meta.notes, vec!["The postal code is from the letterhead"]);
+//! assert_eq!(meta.content.as_deref(), Some("Ada, 36, writes from 02134."));
+//! assert_eq!(meta.extra["source"], json!("crm"));
+//!
+//! // Written back with typed data, keeping the rest of the envelope
+//! assert_eq!(
+//!     to_contextual_json(&person, &meta),
+//!     json!({
+//!         "reasoning": "The age is stated directly.",
+//!         "notes": ["The postal code is from the letterhead"],
+//!         "data_structure": {"name": "Ada", "age": 36, "postal_code": "02134"},
+//!         "content": "Ada, 36, writes from 02134.",
+//!         "source": "crm"
+//!     })
+//! );
+//! ```
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{
+    SecretaryError, Task,
+    leniency::LeniencyProfile,
+    partial::diagnose,
+    schema::{FieldDescriptor, JsonType},
+    utilities::{find_field_descriptor, parse_described_field_value, set_field_path},
+};
+
+/// The key of the extracted data in the envelope.
+pub const DATA_STRUCTURE_KEY: &str = "data_structure";
+
+/// Everything in a `ContextualTask` envelope besides the extracted data.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ContextMeta {
+    /// The model's explanation of the extraction, empty when the envelope had none.
+    pub reasoning: String,
+    /// The model's notes on the extraction.
+    pub notes: Vec<String>,
+    /// The text the data was extracted from, when the envelope kept it.
+    pub content: Option<String>,
+    /// Any other keys of the envelope, written back unchanged.
+    pub extra: Map<String, Value>,
+}
+
+/// A Task together with the rest of its `ContextualTask` envelope.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Contextual<T> {
+    /// The extracted data.
+    pub data: T,
+    /// The reasoning, notes, content and other keys of the envelope.
+    pub meta: ContextMeta,
+}
+
+impl<T: Task> TryFrom<&Value> for Contextual<T> {
+    type Error = SecretaryError;
+
+    /// Reads an envelope like `from_contextual_json`.
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        let (data, meta) = from_contextual_json(value)?;
+        Ok(Self { data, meta })
+    }
+}
+
+impl<T: Task> From<Contextual<T>> for Value {
+    /// Writes an envelope like `to_contextual_json`.
+    fn from(contextual: Contextual<T>) -> Self {
+        to_contextual_json(&contextual.data, &contextual.meta)
+    }
+}
+
+/// Reads a Task and the rest of the envelope from a `ContextualTask` JSON blob.
+///
+/// # Arguments
+///
+/// * `value` - The stored envelope, with the data under `data_structure`
+///
+/// # Returns
+///
+/// The Task and the reasoning, notes, content and other keys of the envelope
+///
+/// # Errors
+///
+/// Returns `SecretaryError::InvalidContextualJson` if the value is not an object with a
+/// `data_structure` object, or `SecretaryError::FieldDeserializationError` if the data does
+/// not deserialize into `T` even after lenient coercion.
+pub fn from_contextual_json<T: Task>(value: &Value) -> Result<(T, ContextMeta), SecretaryError> {
+    let Some(envelope) = value.as_object() else {
+        return Err(invalid("the envelope is not a JSON object"));
+    };
+    let data: T = match envelope.get(DATA_STRUCTURE_KEY) {
+        Some(Value::Object(data_structure)) => data_from_object(data_structure)?,
+        // Some writers stored the data as JSON text
+        Some(Value::String(text)) => match serde_json::from_str::<Value>(text) {
+            Ok(Value::Object(data_structure)) => data_from_object(&data_structure)?,
+            _ => return Err(invalid("`data_structure` is text but not a JSON object")),
+        },
+        Some(_) => return Err(invalid("`data_structure` is not a JSON object")),
+        None => return Err(invalid("the envelope has no `data_structure`")),
+    };
+
+    let mut meta: ContextMeta = ContextMeta::default();
+    for (key, field) in envelope {
+        match key.as_str() {
+            DATA_STRUCTURE_KEY => {}
+            "reasoning" => meta.reasoning = text_of(field).unwrap_or_default(),
+            "notes" => meta.notes = notes_of(field),
+            "content" => meta.content = text_of(field),
+            _ => {
+                meta.extra.insert(key.clone(), field.clone());
+            }
+        }
+    }
+
+    Ok((data, meta))
+}
+
+/// Writes a Task into a `ContextualTask` JSON envelope.
+///
+/// `reasoning` and `notes` are always written, `content` only when it is set, and the other
+/// keys of `meta.extra` as they are.
+///
+/// # Arguments
+///
+/// * `data` - The Task written under `data_structure`
+/// * `meta` - The rest of the envelope
+pub fn to_contextual_json<T: Task>(data: &T, meta: &ContextMeta) -> Value {
+    let mut envelope: Map<String, Value> = Map::new();
+    envelope.insert(
+        "reasoning".to_string(),
+        Value::String(meta.reasoning.clone()),
+    );
+    envelope.insert(
+        "notes".to_string(),
+        Value::Array(meta.notes.iter().cloned().map(Value::String).collect()),
+    );
+    envelope.insert(
+        DATA_STRUCTURE_KEY.to_string(),
+        serde_json::to_value(data).unwrap_or(Value::Null),
+    );
+    if let Some(content) = &meta.content {
+        envelope.insert("content".to_string(), Value::String(content.clone()));
+    }
+    for (key, value) in &meta.extra {
+        envelope.entry(key.clone()).or_insert_with(|| value.clone());
+    }
+
+    Value::Object(envelope)
+}
+
+/// Deserializes the data, coercing the string values of the stringly format when needed.
+fn data_from_object<T: Task>(data_structure: &Map<String, Value>) -> Result<T, SecretaryError> {
+    if let Ok(data) = serde_json::from_value::<T>(Value::Object(data_structure.clone())) {
+        return Ok(data);
+    }
+
+    let fields: Vec<FieldDescriptor> = T::field_descriptors();
+    let mut value: Value = Value::Object(Map::new());
+    for (path, field) in data_structure {
+        let coerced: Value = match field {
+            Value::String(text) => coerce_text(text, path, &fields),
+            other => other.clone(),
+        };
+        set_field_path(&mut value, path, coerced)?;
+    }
+    LeniencyProfile::aggressive().apply_to_fields(&fields, &mut value);
+
+    serde_json::from_value::<T>(value.clone()).map_err(|_| {
+        let mut error = diagnose::<T>(&value);
+        error.raw_field_contents = data_structure
+            .iter()
+            .filter_map(|(path, field)| Some((path.clone(), field.as_str()?.to_string())))
+            .collect();
+        SecretaryError::FieldDeserializationError(error)
+    })
+}
+
+/// Parses a string value of the stringly format, keeping it as text for text fields.
+fn coerce_text(text: &str, path: &str, fields: &[FieldDescriptor]) -> Value {
+    match find_field_descriptor(fields, path) {
+        Some(field) if field.json_type == JsonType::String => Value::String(text.to_string()),
+        _ => parse_described_field_value(text, path, fields),
+    }
+}
+
+/// Returns the text of a value, serializing anything that is not a string.
+fn text_of(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::String(text) => Some(text.clone()),
+        other => Some(other.to_string()),
+    }
+}
+
+/// Returns the notes of the envelope, which some writers stored as a single string.
+fn notes_of(value: &Value) -> Vec<String> {
+    match value {
+        Value::Array(notes) => notes.iter().filter_map(text_of).collect(),
+        other => text_of(other).into_iter().collect(),
+    }
+}
+
+fn invalid(message: &str) -> SecretaryError {
+    SecretaryError::InvalidContextualJson {
+        message: message.to_string(),
+    }
+}
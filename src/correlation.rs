@@ -0,0 +1,137 @@
+//! Request IDs that tie an extraction to its metrics, dead letters, errors and the
+//! provider's logs.
+//!
+//! Every call of `generate_data_with_options`, `force_generate_data`,
+//! `fields_generate_data_with_options` and `generate_data_adaptive_with_options`, of their
+//! async versions and of every method that goes through them, such as `generate_data`, runs
+//! under a request ID: the one set with `RequestOptions::with_request_id`, or a new UUID.
+//! The ID is
+//!
+//! - sent to the provider with every request of the call as the `X-Client-Request-Id`
+//!   header,
+//! - passed with every request event to `MetricsSink::record_with_request_id`,
+//! - stored in the `DeadLetter` of a failed call, with the provider's own ID of the request,
+//! - returned by adaptive generation in `GenerationMetadata::request_id`, with the provider's
+//!   ID in `provider_request_id`.
+//!
+//! The provider's ID is read from the first of `PROVIDER_REQUEST_ID_HEADERS` the response
+//! has, such as OpenAI's `x-request-id`, and is only kept for calls that take a single
+//! request. Distributed generation sends each field request under a child ID,
+//! `<request ID>/<field path>`, so the events of one field can be told apart while
+//! `is_within` still finds every event of the call.
+//!
+//! Errors are returned unchanged by default, so they can still be downcast to
+//! `SecretaryError`. A provider built `with_traced_errors(true)` wraps the errors of the
+//! calls that capture dead letters in a `TracedError` that carries both IDs, and
+//! `request_id_of` finds the request ID in the chain of any error.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::SecretaryError;
+//! use secretary::correlation::{TracedError, child_request_id, is_within, request_id_of};
+//!
+//! let child: String = child_request_id("7d1c", "address.city");
+//! assert_eq!(child, "7d1c/address.city");
+//! assert!(is_within(&child, "7d1c"));
+//! assert!(!is_within("7d1ca", "7d1c"));
+//!
+//! let error: Box<dyn std::error::Error + Send + Sync> = Box::new(TracedError {
+//!     request_id: "7d1c".to_string(),
+//!     provider_request_id: Some("req_42".to_string()),
+//!     source: Box::new(SecretaryError::NoLLMResponse),
+//! });
+//! assert_eq!(request_id_of(error.as_ref()), Some("7d1c"));
+//! assert!(error.to_string().ends_with("(request 7d1c, provider request req_42)"));
+//!
+//! let traced = error.downcast_ref::<TracedError>().unwrap();
+//! assert!(matches!(traced.secretary_error(), Some(SecretaryError::NoLLMResponse)));
+//! ```
+
+use crate::{SecretaryError, progress::Progress, request::RequestOptions};
+
+/// The header the request ID is sent in.
+pub const CLIENT_REQUEST_ID_HEADER: &str = "x-client-request-id";
+
+/// The response headers providers report their ID of a request in, in the order they are
+/// looked up: OpenAI's, Azure's and Amazon's.
+pub const PROVIDER_REQUEST_ID_HEADERS: [&str; 3] =
+    ["x-request-id", "apim-request-id", "x-amzn-requestid"];
+
+/// Returns a new request ID, a random UUID.
+pub fn new_request_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+/// Returns the ID of the request of a field within the call with the given ID.
+pub fn child_request_id(request_id: &str, field_path: &str) -> String {
+    format!("{}/{}", request_id, field_path)
+}
+
+/// Returns whether a request ID is the given ID or the ID of one of its field requests.
+pub fn is_within(request_id: &str, parent: &str) -> bool {
+    request_id
+        .strip_prefix(parent)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// An error of a call together with the IDs of its request.
+#[derive(Debug)]
+pub struct TracedError {
+    /// The request ID of the call.
+    pub request_id: String,
+    /// The provider's ID of the request, when the call took a single request and the
+    /// response reported one.
+    pub provider_request_id: Option<String>,
+    /// The error of the call.
+    pub source: Box<dyn std::error::Error + Send + Sync + 'static>,
+}
+
+impl TracedError {
+    /// Returns the `SecretaryError` of the call, if the error is one.
+    pub fn secretary_error(&self) -> Option<&SecretaryError> {
+        self.source.downcast_ref::<SecretaryError>()
+    }
+}
+
+impl std::fmt::Display for TracedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (request {}", self.source, self.request_id)?;
+        if let Some(provider_request_id) = &self.provider_request_id {
+            write!(f, ", provider request {}", provider_request_id)?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl std::error::Error for TracedError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
+/// Returns the request ID of the first `TracedError` in the chain of an error.
+pub fn request_id_of<'a>(error: &'a (dyn std::error::Error + 'static)) -> Option<&'a str> {
+    let mut current: Option<&'a (dyn std::error::Error + 'static)> = Some(error);
+    while let Some(error) = current {
+        if let Some(traced) = error.downcast_ref::<TracedError>() {
+            return Some(&traced.request_id);
+        }
+        current = error.source();
+    }
+
+    None
+}
+
+/// Returns the options of a call with a request ID, a new one unless they set one, and with
+/// the clock of its progress running.
+pub(crate) fn traced_options(options: &RequestOptions) -> RequestOptions {
+    let mut options: RequestOptions = match options.request_id {
+        Some(_) => options.clone(),
+        None => options.clone().with_request_id(new_request_id()),
+    };
+    // The call is also when the clock of its progress events starts
+    options.progress = options.progress.as_ref().map(Progress::started);
+
+    options
+}
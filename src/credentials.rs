@@ -0,0 +1,157 @@
+//! API keys that change without rebuilding the provider.
+//!
+//! A provider keeps its API key in an `ApiKey`, which it shares with its clones. Rotating
+//! the key with `rotate_api_key` is safe while requests are in flight: requests that were
+//! already sent finish with the old key, and every request built afterwards, retries
+//! included, carries the new one. Revoke the old key once the calls that started before the
+//! rotation have returned.
+//!
+//! To send a call with another key, e.g. the key of the tenant it is made for, pass
+//! `Credentials` through `RequestOptions::with_credentials`. Every request of the call,
+//! including the field requests of distributed generation, then carries that key in place
+//! of the provider's, formatted by `IsLLM::get_authorization_for_key`. The provider, its
+//! connection pools and its request queue are shared by all tenants.
+//!
+//! Each request sent with `Credentials` records a `MetricEvent::CredentialsOverridden`
+//! with the tenant and the `key_id` of the key. The key itself never leaves the
+//! `Authorization` header: neither `Debug` nor metrics show it.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::credentials::{ApiKey, Credentials, key_id};
+//! use secretary::request::RequestOptions;
+//!
+//! let api_key = ApiKey::new("sk-old");
+//! let shared = api_key.clone();
+//! api_key.rotate("sk-new");
+//! assert_eq!(shared.get(), "sk-new");
+//! assert!(!format!("{:?}", shared).contains("sk-new"));
+//!
+//! let credentials = Credentials::new("sk-tenant-a").with_tenant("tenant-a");
+//! assert_eq!(credentials.key_id(), key_id("sk-tenant-a"));
+//! assert!(credentials.key_id().starts_with("key-"));
+//! assert_ne!(credentials.key_id(), key_id("sk-tenant-b"));
+//! assert!(!format!("{:?}", credentials).contains("sk-tenant-a"));
+//!
+//! let options = RequestOptions::default().with_credentials(credentials);
+//! assert_eq!(options.credentials.unwrap().tenant(), Some("tenant-a"));
+//! ```
+
+use std::fmt;
+use std::sync::{Arc, RwLock};
+
+use crate::schema::fnv1a_64;
+
+/// Returns a stable identifier for an API key, safe to log and to use as a metric label.
+///
+/// The identifier is a hash of the key. It tells keys apart but is not meant to keep a
+/// low-entropy secret from being guessed.
+///
+/// # Arguments
+///
+/// * `api_key` - The API key
+pub fn key_id(api_key: &str) -> String {
+    format!("key-{:016x}", fnv1a_64(api_key.as_bytes()))
+}
+
+/// A provider's API key, shared with the provider's clones and replaceable while requests
+/// are in flight.
+#[derive(Clone)]
+pub struct ApiKey {
+    key: Arc<RwLock<String>>,
+}
+
+impl ApiKey {
+    /// Creates a key holder with the given key.
+    pub fn new(api_key: &str) -> Self {
+        Self {
+            key: Arc::new(RwLock::new(api_key.to_string())),
+        }
+    }
+
+    /// Returns the current key.
+    pub fn get(&self) -> String {
+        self.key
+            .read()
+            .unwrap_or_else(|error| error.into_inner())
+            .clone()
+    }
+
+    /// Replaces the key for every request built from now on, by this holder and its clones.
+    ///
+    /// # Arguments
+    ///
+    /// * `api_key` - The new key
+    pub fn rotate(&self, api_key: &str) {
+        *self.key.write().unwrap_or_else(|error| error.into_inner()) = api_key.to_string();
+    }
+
+    /// Returns the `key_id` of the current key.
+    pub fn key_id(&self) -> String {
+        key_id(&self.get())
+    }
+}
+
+impl fmt::Debug for ApiKey {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("ApiKey")
+            .field("key_id", &self.key_id())
+            .finish()
+    }
+}
+
+/// An API key sent with a single call in place of the provider's, see
+/// `RequestOptions::with_credentials`.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Credentials {
+    api_key: String,
+    tenant: Option<String>,
+}
+
+impl Credentials {
+    /// Creates credentials for the given API key.
+    pub fn new(api_key: &str) -> Self {
+        Self {
+            api_key: api_key.to_string(),
+            tenant: None,
+        }
+    }
+
+    /// Names the tenant the key belongs to, recorded with the metrics of the call.
+    ///
+    /// # Arguments
+    ///
+    /// * `tenant` - An identifier of the tenant; it is recorded as it is, so it should not
+    ///   be a secret
+    pub fn with_tenant(mut self, tenant: &str) -> Self {
+        self.tenant = Some(tenant.to_string());
+        self
+    }
+
+    /// Returns the API key.
+    pub fn api_key(&self) -> &str {
+        &self.api_key
+    }
+
+    /// Returns the tenant, if one was named.
+    pub fn tenant(&self) -> Option<&str> {
+        self.tenant.as_deref()
+    }
+
+    /// Returns the `key_id` of the API key.
+    pub fn key_id(&self) -> String {
+        key_id(&self.api_key)
+    }
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("Credentials")
+            .field("key_id", &self.key_id())
+            .field("tenant", &self.tenant)
+            .finish()
+    }
+}
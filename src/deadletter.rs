@@ -0,0 +1,324 @@
+//! Capturing failed extractions for later replay.
+//!
+//! A provider with a `DeadLetterSink`, set with `with_dead_letter_sink`, hands every failed
+//! `generate_data_with_options`, `force_generate_data` and `fields_generate_data_with_options`
+//! call to the sink as a `DeadLetter`, and so every call that goes through them, such as
+//! `generate_data`, `generate` and `fields_generate_data`, and their async versions. A dead
+//! letter holds the target, the additional instructions, the Task type and the generation
+//! mode, the error chain, and what the model returned when the error carries it: the raw
+//! content of a malformed response, or the content of each field of a distributed
+//! extraction with the fields that failed. It also holds the request ID of the call and the
+//! provider's ID of its request, see the `correlation` module.
+//!
+//! `FileDeadLetterSink` writes one JSON file per failure to a directory, and
+//! `replay_dead_letter` loads such a file and runs the extraction again. Capturing never
+//! fails the call: when the sink cannot store a dead letter, its error is recorded as a
+//! `MetricEvent::DeadLetterFailed` and the caller gets the original error.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::deadletter::{DeadLetter, DeadLetterSink, FileDeadLetterSink};
+//! use secretary::metrics::GenerationMode;
+//!
+//! let directory = std::env::temp_dir().join(format!("secretary-dead-letters-{}", std::process::id()));
+//! let sink = FileDeadLetterSink::new(&directory);
+//!
+//! sink.capture(DeadLetter {
+//!     task: "my_crate::Invoice".to_string(),
+//!     mode: GenerationMode::Json,
+//!     input: "Invoice INV-7 over 120.50 EUR".to_string(),
+//!     additional_instructions: vec![],
+//!     error: vec!["No response is retrieved from the LLM".to_string()],
+//!     raw_response: None,
+//!     failed_fields: vec![],
+//!     raw_field_contents: Default::default(),
+//!     request_id: Some("7d1c".to_string()),
+//!     provider_request_id: None,
+//!     captured_at: 1_700_000_000_000,
+//! })
+//! .unwrap();
+//!
+//! assert_eq!(sink.captured(), 1);
+//! let path = std::fs::read_dir(&directory).unwrap().next().unwrap().unwrap().path();
+//! let dead_letter = DeadLetter::load(&path).unwrap();
+//! assert_eq!(dead_letter.input, "Invoice INV-7 over 120.50 EUR");
+//! # std::fs::remove_dir_all(&directory).unwrap();
+//! ```
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    SecretaryError,
+    correlation::TracedError,
+    metrics::{GenerationMode, MetricEvent},
+    request::RequestOptions,
+    traits::{GenerateData, IsLLM, Task},
+};
+
+/// A failed extraction, with what is needed to understand and replay it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeadLetter {
+    /// The type name of the Task, as given by `std::any::type_name`.
+    pub task: String,
+    /// The generation method that failed.
+    pub mode: GenerationMode,
+    /// The target, as passed to the call.
+    pub input: String,
+    /// The additional instructions of the call.
+    pub additional_instructions: Vec<String>,
+    /// The error followed by its sources, outermost first.
+    pub error: Vec<String>,
+    /// The content the model returned, when the error carries it.
+    pub raw_response: Option<String>,
+    /// The paths of the fields that failed to deserialize in a distributed extraction.
+    pub failed_fields: Vec<String>,
+    /// The content the model returned for each field of a distributed extraction, by path.
+    pub raw_field_contents: BTreeMap<String, String>,
+    /// The request ID of the call, see the `correlation` module.
+    #[serde(default)]
+    pub request_id: Option<String>,
+    /// The provider's ID of the request, when the call took a single request and the
+    /// response reported one.
+    #[serde(default)]
+    pub provider_request_id: Option<String>,
+    /// When the failure was captured, in milliseconds since the Unix epoch.
+    pub captured_at: u64,
+}
+
+impl DeadLetter {
+    /// Describes a failed call from its error.
+    pub(crate) fn from_error<T: Task>(
+        mode: GenerationMode,
+        input: &str,
+        additional_instructions: &[String],
+        error: &(dyn std::error::Error + 'static),
+    ) -> Self {
+        let mut chain: Vec<String> = vec![error.to_string()];
+        let mut source = error.source();
+        while let Some(cause) = source {
+            chain.push(cause.to_string());
+            source = cause.source();
+        }
+
+        let mut dead_letter = DeadLetter {
+            task: std::any::type_name::<T>().to_string(),
+            mode,
+            input: input.to_string(),
+            additional_instructions: additional_instructions.to_vec(),
+            error: chain,
+            raw_response: None,
+            failed_fields: Vec::new(),
+            raw_field_contents: BTreeMap::new(),
+            request_id: None,
+            provider_request_id: None,
+            captured_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_millis() as u64)
+                .unwrap_or(0),
+        };
+        match error.downcast_ref::<SecretaryError>() {
+            Some(SecretaryError::JsonParsingError { raw_content, .. })
+            | Some(SecretaryError::SchemaViolation { raw_content, .. }) => {
+                dead_letter.raw_response = Some(raw_content.clone());
+            }
+            Some(SecretaryError::Refused { message }) => {
+                dead_letter.raw_response = Some(message.clone());
+            }
+            Some(SecretaryError::FieldDeserializationError(error)) => {
+                dead_letter.failed_fields = error.failed_fields.clone();
+                dead_letter.raw_field_contents = error.raw_field_contents.clone();
+            }
+            _ => {}
+        }
+
+        dead_letter
+    }
+
+    /// Reads a dead letter written by `FileDeadLetterSink`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SecretaryError::InputRead` if the file cannot be read, or
+    /// `SecretaryError::SerdeJsonError` if it does not hold a dead letter.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SecretaryError> {
+        let content: String =
+            std::fs::read_to_string(path.as_ref()).map_err(SecretaryError::InputRead)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+}
+
+/// Receives the failed extractions of a provider.
+///
+/// Implementations must not panic and should handle their own errors, since capturing
+/// never fails the call.
+pub trait DeadLetterSink: Send + Sync + std::fmt::Debug {
+    /// Stores a single failed extraction.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error that kept the dead letter from being stored. The provider records it
+    /// as a `MetricEvent::DeadLetterFailed`, it never fails the call.
+    fn capture(
+        &self,
+        item: DeadLetter,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>>;
+}
+
+/// A sink that writes every dead letter to a JSON file of its own in a directory.
+///
+/// Files are named after the capture time, a sequence number and the Task, such as
+/// `1700000000000-0-Invoice.json`. The directory is created when the first dead letter is
+/// written.
+#[derive(Debug)]
+pub struct FileDeadLetterSink {
+    directory: PathBuf,
+    captured: AtomicUsize,
+}
+
+impl FileDeadLetterSink {
+    /// Creates a sink that writes to the given directory.
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+            captured: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the directory dead letters are written to.
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Returns how many dead letters were written.
+    pub fn captured(&self) -> usize {
+        self.captured.load(Ordering::SeqCst)
+    }
+
+    /// Writes a dead letter to a file of its own.
+    fn write(&self, item: &DeadLetter, sequence: usize) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.directory)?;
+        let task_name: &str = item.task.rsplit("::").next().unwrap_or(&item.task);
+        let task_name: String = task_name
+            .chars()
+            .map(|c| if c.is_alphanumeric() { c } else { '_' })
+            .collect();
+        let path: PathBuf = self.directory.join(format!(
+            "{}-{}-{}.json",
+            item.captured_at, sequence, task_name
+        ));
+        std::fs::write(&path, serde_json::to_string_pretty(item)?)
+    }
+}
+
+impl DeadLetterSink for FileDeadLetterSink {
+    fn capture(
+        &self,
+        item: DeadLetter,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        // Numbered before writing so concurrent failures get distinct files
+        static SEQUENCE: AtomicUsize = AtomicUsize::new(0);
+        let sequence: usize = SEQUENCE.fetch_add(1, Ordering::SeqCst);
+        self.write(&item, sequence).map_err(|error| {
+            format!(
+                "could not write a dead letter to {}: {}",
+                self.directory.display(),
+                error
+            )
+        })?;
+        self.captured.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+/// Hands a failed call to the provider's dead letter sink, if one is set, and returns the
+/// result, with the error wrapped in a `TracedError` when the provider traces errors.
+pub(crate) fn capture_failure<L: IsLLM + ?Sized, T: Task>(
+    llm: &L,
+    mode: GenerationMode,
+    input: &str,
+    additional_instructions: &[String],
+    request_id: &str,
+    provider_request_id: Option<String>,
+    result: Result<T, Box<dyn std::error::Error + Send + Sync + 'static>>,
+) -> Result<T, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let Err(error) = result else {
+        return result;
+    };
+
+    if let Some(sink) = llm.get_dead_letter_sink() {
+        let mut dead_letter: DeadLetter =
+            DeadLetter::from_error::<T>(mode, input, additional_instructions, error.as_ref());
+        dead_letter.request_id = Some(request_id.to_string());
+        dead_letter.provider_request_id = provider_request_id.clone();
+        if let Err(sink_error) = sink.capture(dead_letter) {
+            llm.get_metrics_sink().record_with_request_id(
+                MetricEvent::DeadLetterFailed {
+                    error: sink_error.to_string(),
+                },
+                request_id,
+            );
+        }
+    }
+
+    if !llm.get_traced_errors() {
+        return Err(error);
+    }
+    Err(Box::new(TracedError {
+        request_id: request_id.to_string(),
+        provider_request_id,
+        source: error,
+    }))
+}
+
+/// Loads a dead letter and runs its extraction again with the same target, instructions
+/// and generation mode.
+///
+/// A replay that fails again is captured again by the provider's sink.
+///
+/// # Arguments
+///
+/// * `llm` - The provider to extract with
+/// * `path` - The file written by `FileDeadLetterSink`
+///
+/// # Errors
+///
+/// Returns the errors of `DeadLetter::load`, `SecretaryError::BuildRequestError` if the
+/// dead letter was captured for another Task than `T`, or the error of the extraction.
+pub fn replay_dead_letter<T: Task, L: GenerateData>(
+    llm: &L,
+    path: impl AsRef<Path>,
+) -> Result<T, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let dead_letter: DeadLetter = DeadLetter::load(path)?;
+    if dead_letter.task != std::any::type_name::<T>() {
+        return Err(Box::new(SecretaryError::BuildRequestError(format!(
+            "the dead letter was captured for {}, not {}",
+            dead_letter.task,
+            std::any::type_name::<T>()
+        ))));
+    }
+
+    let task: T = T::default();
+    let input: &str = &dead_letter.input;
+    let additional_instructions: Vec<String> = dead_letter.additional_instructions;
+    match dead_letter.mode {
+        GenerationMode::Json => llm.generate_data_with_options(
+            &task,
+            input,
+            additional_instructions,
+            &RequestOptions::default(),
+        ),
+        GenerationMode::Force => llm.force_generate_data(&task, input, additional_instructions),
+        GenerationMode::Distributed => llm.fields_generate_data_with_options(
+            &task,
+            input,
+            additional_instructions,
+            &RequestOptions::default(),
+        ),
+    }
+}
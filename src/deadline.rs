@@ -0,0 +1,102 @@
+//! Overall time limits for extractions.
+//!
+//! A `Deadline` bounds a whole extraction, including retries, rate-limit waits and every
+//! request of distributed generation. It is set per call with
+//! `RequestOptions::with_deadline`. Each request's timeout is capped at the time remaining,
+//! retries are not scheduled when their wait would pass the deadline, and field requests are
+//! no longer sent once it has passed. Fields that did not complete in time are reported in
+//! `SecretaryError::DeadlineExceeded`, or in `PartialData::incomplete_fields` when partial
+//! results are requested.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::time::Duration;
+//!
+//! use secretary::Task;
+//! use secretary::SecretaryError;
+//! use secretary::deadline::Deadline;
+//! use secretary::llm_providers::openai::OpenAILLM;
+//! use secretary::partial::PartialData;
+//! use secretary::request::RequestOptions;
+//! use secretary::traits::{AsyncGenerateData, GenerateData};
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! struct Contact {
+//!     #[task(instruction = "Extract the name")]
+//!     pub name: String,
+//!     #[task(instruction = "Extract the email address")]
+//!     pub email: Option<String>,
+//! }
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+//! let llm = OpenAILLM::new("https://api.openai.com/v1", "your-api-key", "gpt-4o-mini")?;
+//! let task = Contact::new();
+//!
+//! // Without partial results the call fails once the deadline passes
+//! let options = RequestOptions::default().with_deadline(Deadline::after(Duration::from_secs(10)));
+//! match llm.fields_generate_data_with_options(&task, "Ada, ada@example.com", &vec![], &options) {
+//!     Ok(contact) => println!("{:?}", contact),
+//!     Err(error) => match error.downcast_ref::<SecretaryError>() {
+//!         Some(SecretaryError::DeadlineExceeded { incomplete_fields }) => {
+//!             println!("Not extracted in time: {:?}", incomplete_fields);
+//!         }
+//!         _ => return Err(error),
+//!     },
+//! }
+//!
+//! // With partial results the fields that completed in time are returned
+//! let options = RequestOptions::default().with_deadline(Deadline::after(Duration::from_secs(10)));
+//! let partial: PartialData<Contact> =
+//!     llm.fields_generate_partial_data_with_options(&task, "Ada, ada@example.com", &vec![], &options)?;
+//! println!("{:?}, incomplete: {:?}", partial.data, partial.incomplete_fields);
+//!
+//! // The async fields mode abandons slow requests at the deadline
+//! tokio::runtime::Runtime::new()?.block_on(async {
+//!     let options = RequestOptions::default().with_deadline(Deadline::after(Duration::from_secs(10)));
+//!     let partial: PartialData<Contact> = llm
+//!         .async_fields_generate_partial_data_with_options(&task, "Ada", &vec![], &options)
+//!         .await?;
+//!     println!("{:?}, incomplete: {:?}", partial.data, partial.incomplete_fields);
+//!     Ok(())
+//! })
+//! # }
+//! ```
+
+use std::time::{Duration, Instant};
+
+/// A point in time by which an extraction must finish.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Deadline {
+    instant: Instant,
+}
+
+impl Deadline {
+    /// Creates a deadline the given duration from now.
+    pub fn after(duration: Duration) -> Self {
+        Self {
+            instant: Instant::now() + duration,
+        }
+    }
+
+    /// Creates a deadline at the given instant.
+    pub fn at(instant: Instant) -> Self {
+        Self { instant }
+    }
+
+    /// Returns the instant of the deadline.
+    pub fn instant(&self) -> Instant {
+        self.instant
+    }
+
+    /// Returns the time left until the deadline, zero once it has passed.
+    pub fn remaining(&self) -> Duration {
+        self.instant.saturating_duration_since(Instant::now())
+    }
+
+    /// Returns whether the deadline has passed.
+    pub fn is_expired(&self) -> bool {
+        self.remaining().is_zero()
+    }
+}
@@ -0,0 +1,103 @@
+//! Decoding of responses that are not clean UTF-8 JSON.
+//!
+//! A gateway occasionally injects bytes that are not valid UTF-8 into a response body, and
+//! models emit JSON escapes that no parser accepts, such as the lone surrogate `\ud83d` of an
+//! emoji clipped in the middle. Under the default `DecodingPolicy::Repair`, so that one bad
+//! character does not lose a whole extraction:
+//!
+//! - invalid byte sequences of the response body are decoded as U+FFFD, see
+//!   `utilities::decode_utf8_lossy`
+//! - invalid escapes of the response body, and of the JSON the model answered with, are
+//!   replaced by `\ufffd`, see `utilities::repair_json_escapes`
+//!
+//! `generate_data_adaptive` reports the number of sequences repaired for its single request
+//! in `metadata.repaired_sequences`, so that the corruption is visible. The answers of
+//! distributed generation are plain text and only their response bodies are repaired.
+//!
+//! Under `DecodingPolicy::Strict`, a body that is not valid UTF-8 fails with
+//! `SecretaryError::ResponseDecode` and invalid escapes fail parsing. Set the policy on a
+//! provider with `with_decoding_policy`.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::SecretaryError;
+//! use secretary::decoding::DecodingPolicy;
+//!
+//! let body = b"{\"content\": \"Zo\xC3 \\ud83d\"}";
+//!
+//! let (text, repaired) = DecodingPolicy::Repair.decode_body(body).unwrap();
+//! assert_eq!(text, "{\"content\": \"Zo\u{FFFD} \\ufffd\"}");
+//! assert_eq!(repaired, 2);
+//!
+//! assert!(matches!(
+//!     DecodingPolicy::Strict.decode_body(body),
+//!     Err(SecretaryError::ResponseDecode { .. })
+//! ));
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    SecretaryError,
+    utilities::{decode_utf8_lossy, repair_json_escapes},
+};
+
+/// How responses that are not clean UTF-8 JSON are handled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DecodingPolicy {
+    /// Replaces invalid byte sequences and invalid escapes with U+FFFD.
+    #[default]
+    Repair,
+    /// Fails on invalid byte sequences and leaves invalid escapes to fail parsing.
+    Strict,
+}
+
+impl DecodingPolicy {
+    /// Decodes a response body.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The response body as received
+    ///
+    /// # Returns
+    ///
+    /// The body text and the number of sequences repaired, always 0 under `Strict`
+    ///
+    /// # Errors
+    ///
+    /// Returns `SecretaryError::ResponseDecode` under `Strict` if the body is not valid
+    /// UTF-8.
+    pub fn decode_body(self, bytes: &[u8]) -> Result<(String, usize), SecretaryError> {
+        match self {
+            DecodingPolicy::Repair => {
+                let (text, invalid_sequences) = decode_utf8_lossy(bytes);
+                let (text, invalid_escapes) = repair_json_escapes(&text);
+                Ok((text, invalid_sequences + invalid_escapes))
+            }
+            DecodingPolicy::Strict => match std::str::from_utf8(bytes) {
+                Ok(text) => Ok((text.to_string(), 0)),
+                Err(error) => Err(SecretaryError::ResponseDecode {
+                    message: error.to_string(),
+                }),
+            },
+        }
+    }
+
+    /// Repairs the invalid escapes of the JSON a model answered with.
+    ///
+    /// # Arguments
+    ///
+    /// * `content` - The content of the response
+    ///
+    /// # Returns
+    ///
+    /// The content and the number of escapes repaired, unchanged under `Strict`
+    pub fn repair_content(self, content: String) -> (String, usize) {
+        match self {
+            // Content without escapes has none to repair, and is kept rather than copied
+            DecodingPolicy::Repair if content.contains('\\') => repair_json_escapes(&content),
+            DecodingPolicy::Repair | DecodingPolicy::Strict => (content, 0),
+        }
+    }
+}
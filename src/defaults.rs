@@ -0,0 +1,184 @@
+//! Values fields fall back to when the model finds nothing for them.
+//!
+//! A field with `#[task(default_value = "...")]` asks the model to output that JSON value when
+//! the text does not give the information, and is set to it when the model's output lacks
+//! the field, has `null` for it, or has a value that does not deserialize into it. This runs
+//! before a failure is reported for the whole Task, so a required field that the text does
+//! not mention no longer fails the extraction.
+//!
+//! The generate methods apply the defaults when parsing with any `LeniencyProfile` and when
+//! assembling the results of distributed generation. The partial methods apply them first
+//! and only replace the fields without a default by their `Default`.
+//!
+//! The derive checks that a default fits the field's type, as far as the type shows: a
+//! `"none"` default on a `u32` field does not compile.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::Task;
+//! use secretary::defaults::{apply_default_values, from_value_with_defaults};
+//! use serde::{Deserialize, Serialize};
+//! use serde_json::json;
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug, PartialEq)]
+//! struct Order {
+//!     #[task(instruction = "Extract the order number")]
+//!     pub number: String,
+//!     #[task(instruction = "Extract the quantity", default_value = "1")]
+//!     pub quantity: u32,
+//!     #[task(instruction = "List the gift wrap options", default_value = "[\"none\"]")]
+//!     pub wrapping: Vec<String>,
+//! }
+//!
+//! let descriptors = Order::field_descriptors();
+//! assert!(descriptors[1].instruction.ends_with("output exactly 1."));
+//!
+//! // Missing, null and malformed values take the default
+//! let order: Order = from_value_with_defaults(
+//!     &descriptors,
+//!     json!({"number": "A-1", "quantity": "a few", "wrapping": null}),
+//! )
+//! .unwrap();
+//! assert_eq!(order.quantity, 1);
+//! assert_eq!(order.wrapping, vec!["none"]);
+//!
+//! // Fields without a default still fail
+//! assert!(from_value_with_defaults::<Order>(&descriptors, json!({"quantity": 2})).is_err());
+//!
+//! let mut value = json!({"number": "A-1", "quantity": 3});
+//! assert_eq!(apply_default_values::<Order>(&descriptors, &mut value), vec!["wrapping"]);
+//! assert_eq!(value["quantity"], json!(3));
+//! ```
+
+use serde_json::Value;
+
+use crate::{
+    normalization::{normalize_strings, uses_string_normalization},
+    partial::diagnose,
+    schema::{FieldDescriptor, FieldKind},
+    traits::Task,
+    utilities::{find_field_descriptor, set_field_path},
+};
+
+/// Returns whether any of the fields, nested ones included, has a default value.
+pub fn has_default_values(fields: &[FieldDescriptor]) -> bool {
+    fields
+        .iter()
+        .any(|field| field.default_value.is_some() || has_default_values(&field.children))
+}
+
+/// Sets the fields of a JSON object returned for `T` that have a default value and no usable
+/// value to their default.
+///
+/// Fields that are absent or `null` are set first. If `T` then still does not deserialize,
+/// the fields that `partial::diagnose` reports as failed are set as well, when they have a
+/// default.
+///
+/// # Arguments
+///
+/// * `fields` - The descriptors of the fields of `T`
+/// * `value` - The JSON object to complete
+///
+/// # Returns
+///
+/// The dotted paths that were set to their default
+pub fn apply_default_values<T: Task>(fields: &[FieldDescriptor], value: &mut Value) -> Vec<String> {
+    if !value.is_object() || !has_default_values(fields) {
+        return Vec::new();
+    }
+
+    let mut defaulted: Vec<String> = Vec::new();
+    fill_absent(fields, value, "", &mut defaulted);
+
+    if serde_json::from_value::<T>(value.clone()).is_err() {
+        for path in diagnose::<T>(value).failed_fields {
+            if let Some(default) =
+                find_field_descriptor(fields, &path).and_then(|field| field.default_value.clone())
+                && set_field_path(value, &path, default).is_ok()
+            {
+                defaulted.push(path);
+            }
+        }
+    }
+
+    defaulted
+}
+
+/// Deserializes `T` from a JSON object after `apply_default_values`, with the strings of
+/// Tasks with `normalize_strings` normalized, see the `normalization` module.
+///
+/// Without default values or normalized strings this is exactly `serde_json::from_value`.
+///
+/// # Arguments
+///
+/// * `fields` - The descriptors of the fields of `T`
+/// * `value` - The JSON object returned for `T`
+///
+/// # Errors
+///
+/// Returns the serde error if `T` does not deserialize even with the defaults.
+pub fn from_value_with_defaults<T: Task>(
+    fields: &[FieldDescriptor],
+    mut value: Value,
+) -> Result<T, serde_json::Error> {
+    apply_default_values::<T>(fields, &mut value);
+    if uses_string_normalization(fields) {
+        normalize_strings(fields, &mut value);
+    }
+    serde_json::from_value::<T>(value)
+}
+
+/// Sets the absent and `null` fields with a default value, descending into nested Tasks.
+fn fill_absent(
+    fields: &[FieldDescriptor],
+    value: &mut Value,
+    prefix: &str,
+    defaulted: &mut Vec<String>,
+) {
+    let Some(map) = value.as_object_mut() else {
+        return;
+    };
+
+    for field in fields {
+        let path: String = if prefix.is_empty() {
+            field.name.clone()
+        } else {
+            format!("{}.{}", prefix, field.name)
+        };
+
+        if let Some(default) = &field.default_value
+            && matches!(map.get(&field.name), None | Some(Value::Null))
+        {
+            map.insert(field.name.clone(), default.clone());
+            defaulted.push(path);
+            continue;
+        }
+
+        let Some(field_value) = map.get_mut(&field.name) else {
+            continue;
+        };
+        match field.kind {
+            FieldKind::Task | FieldKind::OptionTask => {
+                fill_absent(&field.children, field_value, &path, defaulted);
+            }
+            FieldKind::VecTask => {
+                if let Some(items) = field_value.as_array_mut() {
+                    for (index, item) in items.iter_mut().enumerate() {
+                        let item_path: String = format!("{}[{}]", path, index);
+                        fill_absent(&field.children, item, &item_path, defaulted);
+                    }
+                }
+            }
+            FieldKind::HashMapTask | FieldKind::BTreeMapTask => {
+                if let Some(entries) = field_value.as_object_mut() {
+                    for (key, entry) in entries.iter_mut() {
+                        let entry_path: String = format!("{}.{}", path, key);
+                        fill_absent(&field.children, entry, &entry_path, defaulted);
+                    }
+                }
+            }
+            FieldKind::Normal => {}
+        }
+    }
+}
@@ -0,0 +1,598 @@
+//! Extraction tasks defined in configuration files.
+//!
+//! A `TaskDefinition` describes the fields of an extraction in plain data, so it can be
+//! written in YAML or JSON by people who do not work in Rust and loaded at runtime. It can be
+//! generated from a derived Task with `TaskDefinition::from_task` as a starting point, and is
+//! used like a Task through `DynTask`: it renders system prompts, distributed prompts and a
+//! JSON Schema, and extracts `serde_json::Value`s with `generate_value`.
+//!
+//! ```yaml
+//! name: Invoice
+//! fields:
+//!   - name: vendor
+//!     type: string
+//!     instruction: Extract the vendor name
+//!     examples: [ACME Corp]
+//!   - name: total
+//!     type: number
+//!     instruction: Extract the grand total
+//!   - name: due_date
+//!     type: string
+//!     instruction: Extract the due date as YYYY-MM-DD
+//!     optional: true
+//!   - name: lines
+//!     type: task_list
+//!     instruction: Extract every line item
+//!     fields:
+//!       - name: product
+//!         type: string
+//!         instruction: Extract the product
+//! ```
+//!
+//! Field types are `string`, `number`, `boolean`, `array` (with an optional `items` type),
+//! `object`, `any`, and for nested definitions `task`, `task_list` and `task_map`.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use std::collections::HashMap;
+//!
+//! use secretary::Task;
+//! use secretary::definition::{FieldType, TaskDefinition};
+//! use secretary::dynamic::DynTask;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! struct Address {
+//!     #[task(instruction = "Extract the city")]
+//!     pub city: String,
+//! }
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! struct Person {
+//!     #[task(instruction = "Extract the name")]
+//!     pub name: String,
+//!     #[task(instruction = "Extract the age")]
+//!     pub age: Option<u32>,
+//!     #[task(instruction = "Extract the nicknames")]
+//!     pub nicknames: Vec<String>,
+//!     pub address: Address,
+//!     #[task(instruction = "Extract the other addresses by label")]
+//!     pub other_addresses: HashMap<String, Address>,
+//! }
+//!
+//! // Derive -> definition -> YAML -> definition gives the same prompts and schema
+//! let definition = TaskDefinition::from_task::<Person>();
+//! assert_eq!(definition.name, "Person");
+//! assert_eq!(definition.fields[1].field_type, FieldType::Number);
+//! assert!(definition.fields[1].optional);
+//! assert_eq!(definition.fields[2].items, Some(FieldType::String));
+//! assert_eq!(definition.fields[3].field_type, FieldType::Task);
+//! assert_eq!(definition.fields[4].field_type, FieldType::TaskMap);
+//!
+//! let yaml: String = definition.to_yaml().unwrap();
+//! let loaded = TaskDefinition::load_yaml(&yaml).unwrap();
+//! assert_eq!(loaded, definition);
+//! assert_eq!(loaded.system_prompt(), definition.system_prompt());
+//! assert_eq!(loaded.distributed_field_prompts(), definition.distributed_field_prompts());
+//! assert_eq!(loaded.json_schema(), Person::new().json_schema());
+//! assert_eq!(TaskDefinition::load_json(&definition.to_json().unwrap()).unwrap(), definition);
+//!
+//! // Plain fields are described exactly as the derive describes them
+//! assert_eq!(definition.distributed_field_prompts()[0], Person::new().distributed_field_prompts()[0]);
+//!
+//! // A hand-written definition is described like a derived Task
+//! let definition = TaskDefinition::load_yaml(
+//!     r#"
+//! name: Invoice
+//! fields:
+//!   - name: vendor
+//!     type: string
+//!     instruction: Extract the vendor name
+//!     examples: [ACME Corp]
+//!   - name: total
+//!     type: number
+//!     instruction: Extract the grand total
+//!   - name: due_date
+//!     type: string
+//!     instruction: Extract the due date as YYYY-MM-DD
+//!     optional: true
+//! "#,
+//! )
+//! .unwrap();
+//! let prompt: String = definition.system_prompt();
+//! assert!(prompt.contains("vendor: Extract the vendor name, JSON String (e.g. \"ACME Corp\")"));
+//! assert!(prompt.contains("due_date: Extract the due date as YYYY-MM-DD, JSON String or JSON Null"));
+//! ```
+//!
+//! A definition extracts a `serde_json::Value` like a Task extracts itself:
+//!
+//! ```no_run
+//! # use secretary::definition::TaskDefinition;
+//! # use secretary::llm_providers::openai::OpenAILLM;
+//! # use secretary::traits::GenerateData;
+//! # use serde_json::Value;
+//! #
+//! # fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+//! let definition = TaskDefinition::load_yaml(&std::fs::read_to_string("invoice.yaml")?)?;
+//! let llm = OpenAILLM::new("https://api.openai.com/v1", "your-api-key", "gpt-4o-mini")?;
+//!
+//! let value: Value = llm.generate_value(&definition, "Globex billed 120.50", &vec![])?;
+//! println!("{}", value);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Fields take `negative_examples` of wrong values, and `with_negative_example` adds wrong
+//! outputs of the whole task. Both are listed in a "Common mistakes to avoid" section after
+//! the fields; each distributed prompt only lists its own field's.
+//!
+//! ```rust
+//! use secretary::definition::TaskDefinition;
+//! use secretary::dynamic::DynTask;
+//! use secretary::schema::NegativeExample;
+//!
+//! let definition = TaskDefinition::load_yaml(
+//!     r#"
+//! fields:
+//!   - name: vendor
+//!     type: string
+//!     instruction: Extract the vendor name
+//!     examples: [ACME Corp]
+//!   - name: due_date
+//!     type: string
+//!     instruction: Extract the due date as YYYY-MM-DD
+//!     optional: true
+//!     negative_examples:
+//!       - output: "2024-01-01"
+//!         reason: no due date is given, so the answer is null
+//! "#,
+//! )
+//! .unwrap()
+//! .with_negative_example(
+//!     NegativeExample::new(r#"{"vendor": "Globex", "due_date": "net 30"}"#, "due_date is a date, not payment terms")
+//!         .with_input("Globex, payable net 30"),
+//! );
+//!
+//! assert_eq!(
+//!     definition.system_prompt(),
+//!     "vendor: Extract the vendor name, JSON String (e.g. \"ACME Corp\")\n\
+//!      due_date: Extract the due date as YYYY-MM-DD, JSON String or JSON Null\n\
+//!      \n\
+//!      Common mistakes to avoid:\n\
+//!      - due_date: WRONG output: 2024-01-01, because no due date is given, so the answer is null\n\
+//!      - Input: Globex, payable net 30 -> WRONG output: {\"vendor\": \"Globex\", \"due_date\": \"net 30\"}, \
+//!        because due_date is a date, not payment terms\n\
+//!      \n\
+//!      {\n  \"due_date\": null,\n  \"vendor\": \"\"\n}"
+//! );
+//!
+//! let prompts = definition.distributed_field_prompts();
+//! assert!(!prompts[0].prompt.contains("Common mistakes"));
+//! assert!(prompts[1].prompt.ends_with(
+//!     "Common mistakes to avoid:\n\
+//!      - due_date: WRONG output: 2024-01-01, because no due date is given, so the answer is null\n"
+//! ));
+//! ```
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{
+    SecretaryError,
+    casing::KeyCase,
+    distributed::{FIELD_ANSWER_INSTRUCTION, FieldPrompt, list_answer_instruction},
+    dynamic::DynTask,
+    prompt::render_common_mistakes,
+    schema::{FieldDescriptor, FieldKind, Importance, JsonType, NegativeExample},
+    traits::Task,
+};
+
+/// The type of a field in a `TaskDefinition`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldType {
+    String,
+    Number,
+    Boolean,
+    /// A JSON array, with its element type in `FieldDefinition::items`.
+    Array,
+    Object,
+    /// Any JSON value.
+    Any,
+    /// A nested definition, described by `FieldDefinition::fields`.
+    Task,
+    /// An array of nested definitions.
+    TaskList,
+    /// An object whose values are nested definitions.
+    TaskMap,
+}
+
+impl FieldType {
+    fn from_json_type(json_type: JsonType) -> Self {
+        match json_type {
+            JsonType::String => FieldType::String,
+            JsonType::Number => FieldType::Number,
+            JsonType::Boolean => FieldType::Boolean,
+            JsonType::Array => FieldType::Array,
+            JsonType::Object => FieldType::Object,
+            JsonType::Null | JsonType::Any => FieldType::Any,
+        }
+    }
+
+    fn json_type(&self) -> JsonType {
+        match self {
+            FieldType::String => JsonType::String,
+            FieldType::Number => JsonType::Number,
+            FieldType::Boolean => JsonType::Boolean,
+            FieldType::Array | FieldType::TaskList => JsonType::Array,
+            FieldType::Object | FieldType::Task | FieldType::TaskMap => JsonType::Object,
+            FieldType::Any => JsonType::Any,
+        }
+    }
+
+    /// The type as described to the LLM, e.g. `JSON Number`.
+    fn label(&self) -> &'static str {
+        match self {
+            FieldType::String => "JSON String",
+            FieldType::Number => "JSON Number",
+            FieldType::Boolean => "JSON Boolean",
+            FieldType::Array => "JSON Array",
+            FieldType::Object | FieldType::Task | FieldType::TaskMap => "JSON Object",
+            FieldType::Any => "JSON Value",
+            FieldType::TaskList => "JSON Object(s) in a JSON Array",
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            FieldType::String => "string",
+            FieldType::Number => "number",
+            FieldType::Boolean => "boolean",
+            FieldType::Array => "array",
+            FieldType::Object => "object",
+            FieldType::Any => "any",
+            FieldType::Task => "task",
+            FieldType::TaskList => "task_list",
+            FieldType::TaskMap => "task_map",
+        }
+    }
+}
+
+/// A single field of a `TaskDefinition`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldDefinition {
+    /// The field name in the extracted JSON.
+    pub name: String,
+    /// The type of the field's value.
+    #[serde(rename = "type")]
+    pub field_type: FieldType,
+    /// The extraction instruction for the field.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub instruction: String,
+    /// Whether the field may be `null`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub optional: bool,
+    /// The element type of `array` fields.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub items: Option<FieldType>,
+    /// The fields of `task`, `task_list` and `task_map` fields.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<FieldDefinition>,
+    /// Example values shown to the LLM.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub examples: Vec<Value>,
+    /// How much the field matters, see `schema::Importance`.
+    #[serde(default, skip_serializing_if = "is_normal_importance")]
+    pub importance: Importance,
+    /// Wrong values shown to the LLM with the reason they are wrong.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub negative_examples: Vec<NegativeExample>,
+}
+
+fn is_normal_importance(importance: &Importance) -> bool {
+    *importance == Importance::Normal
+}
+
+impl FieldDefinition {
+    pub(crate) fn from_descriptor(descriptor: &FieldDescriptor) -> Self {
+        let (field_type, optional) = match descriptor.kind {
+            FieldKind::Normal => (
+                FieldType::from_json_type(descriptor.json_type),
+                descriptor.optional,
+            ),
+            FieldKind::Task => (FieldType::Task, false),
+            FieldKind::OptionTask => (FieldType::Task, true),
+            FieldKind::VecTask => (FieldType::TaskList, descriptor.optional),
+            FieldKind::HashMapTask | FieldKind::BTreeMapTask => {
+                (FieldType::TaskMap, descriptor.optional)
+            }
+        };
+        let items: Option<FieldType> = match descriptor.item_type {
+            JsonType::Any => None,
+            item_type if field_type == FieldType::Array => {
+                Some(FieldType::from_json_type(item_type))
+            }
+            _ => None,
+        };
+
+        Self {
+            name: descriptor.name.clone(),
+            field_type,
+            instruction: descriptor.instruction.clone(),
+            optional,
+            items,
+            fields: descriptor
+                .children
+                .iter()
+                .map(FieldDefinition::from_descriptor)
+                .collect(),
+            examples: Vec::new(),
+            importance: descriptor.importance,
+            negative_examples: descriptor.negative_examples.clone(),
+        }
+    }
+
+    fn descriptor(&self) -> FieldDescriptor {
+        let kind: FieldKind = match self.field_type {
+            FieldType::Task if self.optional => FieldKind::OptionTask,
+            FieldType::Task => FieldKind::Task,
+            FieldType::TaskList => FieldKind::VecTask,
+            FieldType::TaskMap => FieldKind::HashMapTask,
+            _ => FieldKind::Normal,
+        };
+
+        FieldDescriptor {
+            name: self.name.clone(),
+            rust_type: self.field_type.name().to_string(),
+            json_type: self.field_type.json_type(),
+            item_type: self
+                .items
+                .map(|items| items.json_type())
+                .unwrap_or_default(),
+            optional: self.optional,
+            kind,
+            instruction: self.instruction.clone(),
+            importance: self.importance,
+            negative_examples: self.negative_examples.clone(),
+            local_extraction: None,
+            output_language: None,
+            default_value: None,
+            one_of: Vec::new(),
+            ordered: false,
+            lazy: false,
+            date: false,
+            key_case: KeyCase::default(),
+            normalize_strings: None,
+            children: self
+                .fields
+                .iter()
+                .map(FieldDefinition::descriptor)
+                .collect(),
+        }
+    }
+
+    /// The field's line in the prompts, e.g. `total: Extract the total, JSON Number\n`.
+    fn prompt_line(&self) -> String {
+        let mut label: String = match (self.field_type, self.items) {
+            (FieldType::Array, Some(items)) => format!("{}(s) in a JSON Array", items.label()),
+            (field_type, _) => field_type.label().to_string(),
+        };
+        if self.optional {
+            label.push_str(" or JSON Null");
+        }
+        if !self.examples.is_empty() {
+            let examples: Vec<String> = self.examples.iter().map(Value::to_string).collect();
+            label.push_str(&format!(" (e.g. {})", examples.join(", ")));
+        }
+
+        format!("{}: {}, {}\n", self.name, self.instruction, label)
+    }
+
+    /// Renders the distributed prompt requesting the field as a whole, listing the fields of
+    /// nested definitions.
+    pub(crate) fn distributed_prompt(&self) -> String {
+        let mut prompt: String = FIELD_ANSWER_INSTRUCTION.to_string();
+        prompt.push_str(&format!("- {}\n", self.prompt_line()));
+        prompt.push_str(list_answer_instruction(&self.descriptor()));
+        if !self.fields.is_empty() {
+            prompt.push_str(match self.field_type {
+                FieldType::Task => "It has these fields:\n",
+                _ => "Each item has these fields:\n",
+            });
+            for nested in &self.fields {
+                prompt.push_str(&format!("  - {}", nested.prompt_line()));
+            }
+        }
+        if !self.negative_examples.is_empty() {
+            if !prompt.ends_with("\n\n") {
+                prompt.push('\n');
+            }
+            prompt.push_str(&render_common_mistakes(&[self.descriptor()], &[]));
+        }
+
+        prompt
+    }
+
+    /// The value shown for the field in the JSON template of the system prompt.
+    fn template_value(&self) -> Value {
+        if self.optional && !matches!(self.field_type, FieldType::Task) {
+            return Value::Null;
+        }
+
+        match self.field_type {
+            FieldType::String => Value::String(String::new()),
+            FieldType::Number => Value::from(0),
+            FieldType::Boolean => Value::Bool(false),
+            FieldType::Array => Value::Array(Vec::new()),
+            FieldType::Object => Value::Object(Map::new()),
+            FieldType::Any => Value::Null,
+            FieldType::Task => template(&self.fields),
+            FieldType::TaskList => Value::Array(vec![template(&self.fields)]),
+            FieldType::TaskMap => Value::Object(Map::from_iter([(
+                "key".to_string(),
+                template(&self.fields),
+            )])),
+        }
+    }
+}
+
+/// A serializable description of an extraction task.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TaskDefinition {
+    /// A name for the task, for reference only.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    /// The fields to extract.
+    pub fields: Vec<FieldDefinition>,
+    /// Wrong outputs of the whole task shown to the LLM, see `with_negative_example`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub negative_examples: Vec<NegativeExample>,
+}
+
+impl TaskDefinition {
+    /// Builds a definition from the field metadata of a derived Task.
+    pub fn from_task<T: Task>() -> Self {
+        let type_name: &str = std::any::type_name::<T>();
+        let type_name: &str = type_name.split('<').next().unwrap_or(type_name);
+
+        Self {
+            name: type_name
+                .rsplit("::")
+                .next()
+                .unwrap_or(type_name)
+                .to_string(),
+            fields: T::field_descriptors()
+                .iter()
+                .map(FieldDefinition::from_descriptor)
+                .collect(),
+            negative_examples: Vec::new(),
+        }
+    }
+
+    /// Adds a wrong output of the whole task, listed after the field negative examples in
+    /// the "Common mistakes to avoid" section of the system prompt.
+    ///
+    /// Distributed prompts only carry the negative examples of their own field.
+    pub fn with_negative_example(mut self, example: NegativeExample) -> Self {
+        self.negative_examples.push(example);
+        self
+    }
+
+    /// Loads a definition from YAML.
+    pub fn load_yaml(yaml: &str) -> Result<Self, SecretaryError> {
+        Ok(serde_yaml::from_str(yaml)?)
+    }
+
+    /// Loads a definition from JSON.
+    pub fn load_json(json: &str) -> Result<Self, SecretaryError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Serializes the definition to YAML.
+    pub fn to_yaml(&self) -> Result<String, SecretaryError> {
+        Ok(serde_yaml::to_string(self)?)
+    }
+
+    /// Serializes the definition to pretty-printed JSON.
+    pub fn to_json(&self) -> Result<String, SecretaryError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+impl DynTask for TaskDefinition {
+    fn system_prompt(&self) -> String {
+        let mut prompt: String = system_prompt(&self.fields);
+        let mistakes: String = render_common_mistakes(&self.fields(), &self.negative_examples);
+        if !mistakes.is_empty() {
+            prompt.push('\n');
+            prompt.push_str(&mistakes);
+            prompt.push('\n');
+        }
+        prompt.push_str(&serde_json::to_string_pretty(&template(&self.fields)).unwrap_or_default());
+
+        prompt
+    }
+
+    fn distributed_field_prompts(&self) -> Vec<FieldPrompt> {
+        let mut prompts: Vec<FieldPrompt> = Vec::new();
+        push_distributed_prompts(&self.fields, "", &mut prompts);
+
+        prompts
+    }
+
+    fn fields(&self) -> Vec<FieldDescriptor> {
+        self.fields
+            .iter()
+            .map(FieldDefinition::descriptor)
+            .collect()
+    }
+}
+
+/// Renders the field descriptions of the system prompt, in the layout of the derived prompts.
+fn system_prompt(fields: &[FieldDefinition]) -> String {
+    let mut prompt: String = String::new();
+
+    for field in fields {
+        let (header, footer): (&str, &str) = match field.field_type {
+            FieldType::Task if field.optional => ("Optional Task", "Optional Task"),
+            FieldType::Task => ("Task Details", "Task"),
+            FieldType::TaskList => ("Collection (any number of items)", "Collection"),
+            FieldType::TaskMap => ("Map (any number of entries)", "Map"),
+            _ => {
+                prompt.push_str(&field.prompt_line());
+                continue;
+            }
+        };
+
+        // Like the derive, a required nested task is introduced by its block alone
+        if field.field_type != FieldType::Task || field.optional {
+            prompt.push_str(&field.prompt_line());
+        }
+        prompt.push_str(&format!("\n--- {} {} ---\n", field.name, header));
+        prompt.push_str(&system_prompt(&field.fields));
+        prompt.push_str(&format!("--- End of {} {} ---\n\n", field.name, footer));
+    }
+
+    prompt
+}
+
+/// Builds the JSON template of the system prompt.
+fn template(fields: &[FieldDefinition]) -> Value {
+    Value::Object(
+        fields
+            .iter()
+            .map(|field| (field.name.clone(), field.template_value()))
+            .collect(),
+    )
+}
+
+/// Adds one distributed prompt per field. Fields of nested tasks get their own prompts; lists
+/// and maps of nested tasks are requested as a whole.
+fn push_distributed_prompts(
+    fields: &[FieldDefinition],
+    prefix: &str,
+    prompts: &mut Vec<FieldPrompt>,
+) {
+    for field in fields {
+        let field_path: String = if prefix.is_empty() {
+            field.name.clone()
+        } else {
+            format!("{}.{}", prefix, field.name)
+        };
+
+        if field.field_type == FieldType::Task {
+            push_distributed_prompts(&field.fields, &field_path, prompts);
+            continue;
+        }
+
+        let prompt: String = field.distributed_prompt();
+
+        prompts.push(FieldPrompt {
+            field_path,
+            prompt,
+            importance: field.importance,
+            ..FieldPrompt::default()
+        });
+    }
+}
@@ -0,0 +1,352 @@
+//! Field-by-field comparison of two extractions.
+//!
+//! Re-extracting a document after changing a prompt or a model rarely changes everything.
+//! `compare` walks two extractions of the same Task with its field metadata and reports every
+//! field that differs by dotted path, and `diff_report` renders the result for humans.
+//! Fields are visited in declaration order, `Vec` elements by position, and map entries in
+//! key order, so the output is stable across runs.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use std::collections::HashMap;
+//!
+//! use secretary::Task;
+//! use secretary::diff::{ChangeKind, FieldDiff, compare, diff_report};
+//! use serde::{Deserialize, Serialize};
+//! use serde_json::json;
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug, Clone)]
+//! struct Address {
+//!     #[task(instruction = "Extract the city")]
+//!     pub city: String,
+//!     #[task(instruction = "Extract the postal code")]
+//!     pub postal_code: Option<String>,
+//! }
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug, Clone)]
+//! struct LineItem {
+//!     #[task(instruction = "Extract the product")]
+//!     pub product: String,
+//!     #[task(instruction = "Extract the quantity")]
+//!     pub quantity: u32,
+//! }
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug, Clone)]
+//! struct Order {
+//!     #[task(instruction = "Extract the customer name")]
+//!     pub customer: String,
+//!     pub address: Address,
+//!     #[task(instruction = "Extract the line items")]
+//!     pub items: Vec<LineItem>,
+//!     #[task(instruction = "Extract the tags")]
+//!     pub tags: Vec<String>,
+//!     #[task(instruction = "Extract the discount codes by product")]
+//!     pub discounts: HashMap<String, u32>,
+//!     #[task(instruction = "Extract the delivery note")]
+//!     pub note: Option<String>,
+//! }
+//!
+//! let before = Order {
+//!     customer: "Ada".to_string(),
+//!     address: Address { city: "Oslo".to_string(), postal_code: None },
+//!     items: vec![LineItem { product: "Tea".to_string(), quantity: 1 }],
+//!     tags: vec!["urgent".to_string(), "gift".to_string()],
+//!     discounts: HashMap::from([("tea".to_string(), 10)]),
+//!     note: Some("Leave at door".to_string()),
+//! };
+//!
+//! // No change
+//! assert!(compare(&before, &before.clone()).is_empty());
+//! assert_eq!(diff_report(&[]), "No changes");
+//!
+//! let mut after = before.clone();
+//! after.address.postal_code = Some("0150".to_string());
+//! after.items[0].quantity = 2;
+//! after.items.push(LineItem { product: "Cake".to_string(), quantity: 1 });
+//! after.tags.pop();
+//! after.discounts.insert("cake".to_string(), 5);
+//! after.note = None;
+//!
+//! let diffs: Vec<FieldDiff> = compare(&before, &after);
+//! let summary: Vec<(&str, ChangeKind)> = diffs
+//!     .iter()
+//!     .map(|diff| (diff.path.as_str(), diff.kind))
+//!     .collect();
+//! assert_eq!(
+//!     summary,
+//!     vec![
+//!         ("address.postal_code", ChangeKind::Added),
+//!         ("items[0].quantity", ChangeKind::Changed),
+//!         ("items[1]", ChangeKind::Added),
+//!         ("tags[1]", ChangeKind::Removed),
+//!         ("discounts[cake]", ChangeKind::Added),
+//!         ("note", ChangeKind::Removed),
+//!     ]
+//! );
+//! assert_eq!(diffs[1].old, json!(1));
+//! assert_eq!(diffs[1].new, json!(2));
+//! assert_eq!(diffs[2].new, json!({"product": "Cake", "quantity": 1}));
+//!
+//! let report: String = diff_report(&diffs);
+//! assert!(report.starts_with("6 field(s) differ: 3 added, 2 removed, 1 changed"));
+//! assert!(report.contains("~ items[0].quantity: 1 -> 2"));
+//! assert!(report.contains("- note: \"Leave at door\""));
+//!
+//! // Everything changed
+//! let other = Order {
+//!     customer: "Grace".to_string(),
+//!     address: Address { city: "Bergen".to_string(), postal_code: Some("5003".to_string()) },
+//!     items: vec![LineItem { product: "Coffee".to_string(), quantity: 3 }],
+//!     tags: vec!["standard".to_string(), "repeat".to_string()],
+//!     discounts: HashMap::from([("tea".to_string(), 20)]),
+//!     note: Some("Ring twice".to_string()),
+//! };
+//! let paths: Vec<String> = compare(&before, &other).into_iter().map(|diff| diff.path).collect();
+//! assert_eq!(
+//!     paths,
+//!     vec![
+//!         "customer",
+//!         "address.city",
+//!         "address.postal_code",
+//!         "items[0].product",
+//!         "items[0].quantity",
+//!         "tags[0]",
+//!         "tags[1]",
+//!         "discounts[tea]",
+//!         "note",
+//!     ]
+//! );
+//! ```
+
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    schema::{FieldDescriptor, FieldKind},
+    traits::Task,
+};
+
+/// How a field differs between two extractions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ChangeKind {
+    /// The field was `null` or absent before and has a value now.
+    Added,
+    /// The field had a value before and is `null` or absent now.
+    Removed,
+    /// The field has a different value.
+    Changed,
+}
+
+/// A single difference between two extractions.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldDiff {
+    /// The dotted path of the field, e.g. `address.city`, `items[0].price` or `scores[math]`.
+    pub path: String,
+    /// The value before, `null` for added fields.
+    pub old: Value,
+    /// The value after, `null` for removed fields.
+    pub new: Value,
+    /// How the field differs.
+    pub kind: ChangeKind,
+}
+
+/// Compares two extractions of the same Task field by field.
+///
+/// # Arguments
+///
+/// * `a` - The earlier extraction
+/// * `b` - The later extraction
+///
+/// # Returns
+///
+/// One `FieldDiff` per differing leaf field, collection element or map entry, empty when the
+/// extractions are equal
+pub fn compare<T: Task>(a: &T, b: &T) -> Vec<FieldDiff> {
+    let old: Value = serde_json::to_value(a).unwrap_or(Value::Null);
+    let new: Value = serde_json::to_value(b).unwrap_or(Value::Null);
+
+    let mut diffs: Vec<FieldDiff> = Vec::new();
+    diff_fields(&T::field_descriptors(), &old, &new, "", &mut diffs);
+
+    diffs
+}
+
+/// Renders diffs as a readable summary.
+///
+/// The first line counts the differences; each following line shows one difference, prefixed
+/// with `+` for added, `-` for removed and `~` for changed fields.
+///
+/// # Arguments
+///
+/// * `diffs` - The differences returned by `compare`
+///
+/// # Returns
+///
+/// The report, or `No changes` when there are no differences
+pub fn diff_report(diffs: &[FieldDiff]) -> String {
+    if diffs.is_empty() {
+        return "No changes".to_string();
+    }
+
+    let count = |kind: ChangeKind| diffs.iter().filter(|diff| diff.kind == kind).count();
+    let mut report: String = format!(
+        "{} field(s) differ: {} added, {} removed, {} changed\n",
+        diffs.len(),
+        count(ChangeKind::Added),
+        count(ChangeKind::Removed),
+        count(ChangeKind::Changed)
+    );
+
+    for diff in diffs {
+        let line: String = match diff.kind {
+            ChangeKind::Added => format!("+ {}: {}\n", diff.path, diff.new),
+            ChangeKind::Removed => format!("- {}: {}\n", diff.path, diff.old),
+            ChangeKind::Changed => format!("~ {}: {} -> {}\n", diff.path, diff.old, diff.new),
+        };
+        report.push_str(&line);
+    }
+
+    report
+}
+
+fn push_diff(path: String, old: &Value, new: &Value, diffs: &mut Vec<FieldDiff>) {
+    let kind: ChangeKind = match (old.is_null(), new.is_null()) {
+        (true, _) => ChangeKind::Added,
+        (_, true) => ChangeKind::Removed,
+        _ => ChangeKind::Changed,
+    };
+
+    diffs.push(FieldDiff {
+        path,
+        old: old.clone(),
+        new: new.clone(),
+        kind,
+    });
+}
+
+fn diff_fields(
+    fields: &[FieldDescriptor],
+    old: &Value,
+    new: &Value,
+    prefix: &str,
+    diffs: &mut Vec<FieldDiff>,
+) {
+    for field in fields {
+        let path: String = if prefix.is_empty() {
+            field.name.clone()
+        } else {
+            format!("{}.{}", prefix, field.name)
+        };
+
+        let old_value: &Value = old.get(&field.name).unwrap_or(&Value::Null);
+        let new_value: &Value = new.get(&field.name).unwrap_or(&Value::Null);
+        diff_field(field, path, old_value, new_value, diffs);
+    }
+}
+
+fn diff_field(
+    field: &FieldDescriptor,
+    path: String,
+    old: &Value,
+    new: &Value,
+    diffs: &mut Vec<FieldDiff>,
+) {
+    if old == new {
+        return;
+    }
+    if old.is_null() || new.is_null() {
+        push_diff(path, old, new, diffs);
+        return;
+    }
+
+    match field.kind {
+        FieldKind::Task | FieldKind::OptionTask => {
+            diff_fields(&field.children, old, new, &path, diffs)
+        }
+        FieldKind::VecTask => diff_arrays(Some(&field.children), old, new, path, diffs),
+        FieldKind::HashMapTask | FieldKind::BTreeMapTask => {
+            diff_maps(Some(&field.children), old, new, path, diffs)
+        }
+        FieldKind::Normal => diff_values(old, new, path, diffs),
+    }
+}
+
+/// Compares values without field metadata, descending into arrays and objects.
+fn diff_values(old: &Value, new: &Value, path: String, diffs: &mut Vec<FieldDiff>) {
+    if old == new {
+        return;
+    }
+
+    match (old, new) {
+        (Value::Array(_), Value::Array(_)) => diff_arrays(None, old, new, path, diffs),
+        (Value::Object(_), Value::Object(_)) => diff_maps(None, old, new, path, diffs),
+        _ => push_diff(path, old, new, diffs),
+    }
+}
+
+/// Compares array elements by position.
+fn diff_arrays(
+    children: Option<&[FieldDescriptor]>,
+    old: &Value,
+    new: &Value,
+    path: String,
+    diffs: &mut Vec<FieldDiff>,
+) {
+    let (old_items, new_items) = match (old.as_array(), new.as_array()) {
+        (Some(old_items), Some(new_items)) => (old_items, new_items),
+        _ => return push_diff(path, old, new, diffs),
+    };
+
+    for index in 0..old_items.len().max(new_items.len()) {
+        let item_path: String = format!("{}[{}]", path, index);
+        let old_item: &Value = old_items.get(index).unwrap_or(&Value::Null);
+        let new_item: &Value = new_items.get(index).unwrap_or(&Value::Null);
+        diff_entry(children, old_item, new_item, item_path, diffs);
+    }
+}
+
+/// Compares map entries in key order.
+fn diff_maps(
+    children: Option<&[FieldDescriptor]>,
+    old: &Value,
+    new: &Value,
+    path: String,
+    diffs: &mut Vec<FieldDiff>,
+) {
+    let (old_entries, new_entries) = match (old.as_object(), new.as_object()) {
+        (Some(old_entries), Some(new_entries)) => (old_entries, new_entries),
+        _ => return push_diff(path, old, new, diffs),
+    };
+
+    let keys: BTreeSet<&String> = old_entries.keys().chain(new_entries.keys()).collect();
+    for key in keys {
+        let entry_path: String = format!("{}[{}]", path, key);
+        let old_entry: &Value = old_entries.get(key).unwrap_or(&Value::Null);
+        let new_entry: &Value = new_entries.get(key).unwrap_or(&Value::Null);
+        diff_entry(children, old_entry, new_entry, entry_path, diffs);
+    }
+}
+
+/// Compares a collection element, using the nested Task's fields when there are any.
+fn diff_entry(
+    children: Option<&[FieldDescriptor]>,
+    old: &Value,
+    new: &Value,
+    path: String,
+    diffs: &mut Vec<FieldDiff>,
+) {
+    if old == new {
+        return;
+    }
+    if old.is_null() || new.is_null() {
+        return push_diff(path, old, new, diffs);
+    }
+
+    match children {
+        Some(children) => diff_fields(children, old, new, &path, diffs),
+        None => diff_values(old, new, path, diffs),
+    }
+}
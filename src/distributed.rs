@@ -0,0 +1,373 @@
+//! Per-field prompts for distributed generation.
+//!
+//! In distributed mode every field is extracted by its own request, or by the request of its
+//! group. Fields can tune that request with `#[task(temperature = ...)]` and add guidance
+//! that only their request sees with `#[task(extra_instruction = "...")]`.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::Task;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! struct Article {
+//!     #[task(instruction = "Extract the title", temperature = 0)]
+//!     pub title: String,
+//!     #[task(
+//!         instruction = "Write a one-sentence summary",
+//!         temperature = 0.7,
+//!         extra_instruction = "Do not exceed 20 words"
+//!     )]
+//!     pub summary: String,
+//! }
+//!
+//! let article = Article::default();
+//! let prompts = article.get_distributed_field_prompts();
+//! assert_eq!(prompts[0].field_path, "title");
+//! assert_eq!(prompts[0].temperature, Some(0.0));
+//! assert_eq!(prompts[0].extra_instruction, None);
+//! assert_eq!(prompts[1].temperature, Some(0.7));
+//! assert_eq!(prompts[1].extra_instruction.as_deref(), Some("Do not exceed 20 words"));
+//!
+//! // The extra instruction only reaches the summary's request
+//! let requests = article.make_distributed_generation_requests("Some article", &vec![]);
+//! assert!(!requests[0].1.content.as_str().contains("Do not exceed 20 words"));
+//! assert!(requests[1].1.content.as_str().contains("Do not exceed 20 words"));
+//! ```
+//!
+//! Fields marked with the same `#[task(group = "...")]` are extracted by one request, which
+//! asks for a JSON object holding all of them. The answer is split back into the fields, see
+//! `group_field_prompts`:
+//!
+//! ```rust
+//! use secretary::Task;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! struct Filing {
+//!     #[task(instruction = "Extract the company name")]
+//!     pub company: String,
+//!     #[task(instruction = "Extract the revenue", group = "finance")]
+//!     pub revenue: f64,
+//!     #[task(instruction = "Extract the currency code", group = "finance")]
+//!     pub currency: String,
+//! }
+//!
+//! let requests = Filing::new().get_distributed_request_prompts();
+//! assert_eq!(requests.len(), 2);
+//! assert_eq!(requests[1].field_path, "finance");
+//! assert_eq!(requests[1].field_paths(), vec!["revenue", "currency"]);
+//! assert!(requests[1].prompt.contains(r#"exactly the keys "revenue", "currency""#));
+//! ```
+//!
+//! Temperatures outside `0..=2` are rejected at compile time:
+//!
+//! ```compile_fail
+//! use secretary::Task;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! struct Article {
+//!     #[task(instruction = "Extract the title", temperature = 3.0)]
+//!     pub title: String,
+//! }
+//! ```
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{
+    casing::KeyCase,
+    definition::FieldDefinition,
+    schema::{FieldDescriptor, Importance},
+    utilities::find_json_object_candidates,
+};
+
+/// The first line of the prompt of a single field, asking for its value in `<result>` tags.
+///
+/// Generated by `#[derive(Task)]` into the prompts of fields. Groups replace it with a line
+/// asking for the values of all their fields.
+pub const FIELD_ANSWER_INSTRUCTION: &str =
+    "Output a value according to criteria and wrap them in <result></result>.\n";
+
+/// The prompt and request settings for extracting a single field in distributed generation.
+///
+/// Generated by `#[derive(Task)]` through `Task::get_distributed_field_prompts()`. Field-level
+/// attributes such as `#[task(temperature = 0.7)]` and `#[task(extra_instruction = "...")]`
+/// are carried here so they only affect the request for that field.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FieldPrompt {
+    /// The dotted path of the field, e.g. `info.email` or `items[0].price`.
+    pub field_path: String,
+    /// The system prompt describing the field.
+    pub prompt: String,
+    /// The sampling temperature for this field's request, or `None` for the provider default.
+    pub temperature: Option<f64>,
+    /// An instruction appended to this field's prompt only.
+    pub extra_instruction: Option<String>,
+    /// Whether update mode asks for this field even when it already has a value.
+    #[serde(default)]
+    pub always_refresh: bool,
+    /// The importance of the field, from `#[task(importance = "...")]`.
+    #[serde(default)]
+    pub importance: Importance,
+    /// The group of the field, from `#[task(group = "...")]`.
+    #[serde(default)]
+    pub group: Option<String>,
+    /// The case of the field's key in the JSON answering its group's request, from the
+    /// struct's `#[task(key_case = "...")]`, see the `casing` module.
+    #[serde(default)]
+    pub key_case: KeyCase,
+    /// The fields extracted by the request of a group, empty for the request of a single
+    /// field.
+    #[serde(default)]
+    pub members: Vec<FieldPrompt>,
+}
+
+impl FieldPrompt {
+    /// Returns whether this is the request of a group of fields.
+    pub fn is_group(&self) -> bool {
+        !self.members.is_empty()
+    }
+
+    /// Returns the paths of the fields this request extracts: the members of a group, or the
+    /// field itself.
+    pub fn field_paths(&self) -> Vec<String> {
+        if self.is_group() {
+            self.members
+                .iter()
+                .map(|member| member.field_path.clone())
+                .collect()
+        } else {
+            vec![self.field_path.clone()]
+        }
+    }
+
+    /// Keeps the fields accepted by `keep`, the members of a group one by one, and returns
+    /// whether any field is left to request.
+    pub(crate) fn retain_fields(&mut self, keep: impl Fn(&FieldPrompt) -> bool) -> bool {
+        if !self.is_group() {
+            return keep(self);
+        }
+        self.members.retain(|member| keep(member));
+
+        !self.members.is_empty()
+    }
+}
+
+/// Builds the prompt requesting a field of nested Tasks as a whole, e.g. a list of line
+/// items, describing the fields of its elements.
+///
+/// Generated by `#[derive(Task)]` for structs with `#[task(empty_defaults)]`, whose empty
+/// collections and `None` nested Tasks have no elements to request one by one.
+///
+/// # Arguments
+///
+/// * `field_path` - The dotted path of the field
+/// * `field` - The descriptor of the field, with the descriptors of its elements
+pub fn whole_field_prompt(field_path: String, field: &FieldDescriptor) -> FieldPrompt {
+    let definition: FieldDefinition = FieldDefinition::from_descriptor(field);
+
+    FieldPrompt {
+        field_path,
+        prompt: definition.distributed_prompt(),
+        importance: field.importance,
+        ..FieldPrompt::default()
+    }
+}
+
+/// Merges the prompts of the fields that share a group into one request per group, in the
+/// place of the group's first field.
+///
+/// Groups are formed within the struct that declares the fields, so the same group name in
+/// two nested Tasks makes two requests. The request of a group is named after the group,
+/// e.g. `finance` or `company.finance` for a group of a nested Task, and asks for a JSON
+/// object with one key per member, which `split_group_content` turns back into the answers
+/// of the members. A group of a single field keeps the field's own request.
+///
+/// # Arguments
+///
+/// * `prompts` - The prompts of single fields, as returned by
+///   `Task::get_distributed_field_prompts()`
+pub fn group_field_prompts(prompts: Vec<FieldPrompt>) -> Vec<FieldPrompt> {
+    let mut requests: Vec<FieldPrompt> = Vec::new();
+    // The index in `requests` of the request of each group, by the group's path
+    let mut groups: HashMap<String, usize> = HashMap::new();
+    for prompt in prompts {
+        let Some(group) = prompt.group.clone() else {
+            requests.push(prompt);
+            continue;
+        };
+
+        let group_path: String = match prompt.field_path.rsplit_once('.') {
+            Some((parent, _)) => format!("{}.{}", parent, group),
+            None => group.clone(),
+        };
+        match groups.get(&group_path) {
+            Some(&index) => requests[index].members.push(prompt),
+            None => {
+                groups.insert(group_path.clone(), requests.len());
+                requests.push(FieldPrompt {
+                    field_path: group_path,
+                    group: Some(group),
+                    members: vec![prompt],
+                    ..FieldPrompt::default()
+                });
+            }
+        }
+    }
+
+    requests
+        .into_iter()
+        .map(|mut request| match request.members.len() {
+            0 => request,
+            1 => request.members.remove(0),
+            _ => make_group_request(request),
+        })
+        .collect()
+}
+
+/// Fills in the prompt and request settings of a group's request from its members.
+///
+/// The first temperature among the members applies to the whole request, and the request
+/// is as important as its most important member.
+fn make_group_request(mut request: FieldPrompt) -> FieldPrompt {
+    let keys: Vec<String> = request
+        .members
+        .iter()
+        .map(|member| format!("\"{}\"", member_key(member)))
+        .collect();
+    let mut prompt: String = format!(
+        "Output a JSON object with exactly the keys {}, holding the value of each field according to its criteria, and wrap it in <result></result>.\n",
+        keys.join(", ")
+    );
+    for member in &request.members {
+        prompt.push_str(
+            member
+                .prompt
+                .strip_prefix(FIELD_ANSWER_INSTRUCTION)
+                .unwrap_or(&member.prompt),
+        );
+        if let Some(extra_instruction) = &member.extra_instruction {
+            prompt.push_str(&format!("- {}\n", extra_instruction));
+        }
+    }
+
+    request.prompt = prompt;
+    request.temperature = request.members.iter().find_map(|member| member.temperature);
+    request.always_refresh = request.members.iter().any(|member| member.always_refresh);
+    request.importance = if request
+        .members
+        .iter()
+        .any(|member| member.importance == Importance::Critical)
+    {
+        Importance::Critical
+    } else if request
+        .members
+        .iter()
+        .all(|member| member.importance == Importance::Low)
+    {
+        Importance::Low
+    } else {
+        Importance::Normal
+    };
+
+    request
+}
+
+/// Returns the key of a member in the JSON object answering its group's request: the last
+/// segment of its path, in the case of its struct's keys.
+fn member_key(member: &FieldPrompt) -> String {
+    let name: &str = match member.field_path.rsplit_once('.') {
+        Some((_, name)) => name,
+        None => &member.field_path,
+    };
+    if member.key_case.is_native() {
+        return name.to_string();
+    }
+
+    member.key_case.apply(name)
+}
+
+/// Splits the answer to a group's request into the answers of its members, in the format
+/// of the answer to a member's own request: strings as they are and other values as JSON.
+///
+/// Returns the answers by member path. Members the answer has no value for, or a `null`,
+/// get no answer, which is all of them when the answer holds no JSON object.
+pub(crate) fn split_group_content(request: &FieldPrompt, content: &str) -> Vec<(String, String)> {
+    let object: Map<String, Value> = match serde_json::from_str::<Value>(content) {
+        Ok(Value::Object(object)) => object,
+        _ => find_json_object_candidates(content)
+            .into_iter()
+            .rev()
+            .find_map(|candidate| serde_json::from_str::<Map<String, Value>>(candidate).ok())
+            .unwrap_or_default(),
+    };
+
+    request
+        .members
+        .iter()
+        .filter_map(|member| match object.get(&member_key(member))? {
+            Value::Null => None,
+            Value::String(text) => Some((member.field_path.clone(), text.clone())),
+            value => Some((member.field_path.clone(), value.to_string())),
+        })
+        .collect()
+}
+
+/// Returns the line asking for one `<item></item>` tag per value, for lists of strings,
+/// numbers or booleans such as `Vec<String>`, and an empty string for other fields.
+///
+/// Generated by `#[derive(Task)]` into the prompts of collection fields. The answers are
+/// parsed by `utilities::parse_list_value`, which also accepts JSON arrays and plain lists.
+///
+/// # Arguments
+///
+/// * `field` - The descriptor of the field
+pub fn list_answer_instruction(field: &FieldDescriptor) -> &'static str {
+    if !field.is_primitive_list() {
+        return "";
+    }
+
+    if field.is_set() {
+        "Put each distinct value in its own <item></item> tag inside the result, or answer with a JSON array.\n"
+    } else {
+        "Put each value in its own <item></item> tag inside the result, or answer with a JSON array.\n"
+    }
+}
+
+/// Returns the line telling the request of a field inside an element of a collection of
+/// nested Tasks which element it is for, e.g. the employer of the second of several
+/// experiences.
+///
+/// Generated by `#[derive(Task)]` into the prompts of the fields of each element. A field
+/// in nested collections gets one line per collection, the innermost first.
+///
+/// # Arguments
+///
+/// * `collection` - The name of the collection field
+/// * `index` - The index of the element
+pub fn collection_item_note(collection: &str, index: usize) -> String {
+    format!(
+        "This field belongs to item {} of {}, counting the items in the order they appear in the text.\n",
+        index + 1,
+        collection
+    )
+}
+
+/// Returns the line telling the request of a field inside a value of a map of nested Tasks
+/// which entry it is for.
+///
+/// # Arguments
+///
+/// * `collection` - The name of the map field
+/// * `key` - The key of the entry
+pub fn map_entry_note(collection: &str, key: &str) -> String {
+    format!(
+        "This field belongs to the entry \"{}\" of {}.\n",
+        key, collection
+    )
+}
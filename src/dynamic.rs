@@ -0,0 +1,47 @@
+//! Object-safe access to extraction tasks.
+//!
+//! `Task` is tied to a Rust struct: it requires `Serialize`, `Deserialize` and `Default`, and
+//! describes its fields without an instance. `DynTask` covers what extraction needs from a task
+//! as plain values instead, so tasks can be chosen at runtime and used behind `&dyn DynTask`.
+//! Every `Task` is a `DynTask`, and so is a `TaskDefinition` loaded from a configuration file.
+//! The `generate_value` family of `GenerateData` and `AsyncGenerateData` extracts a
+//! `serde_json::Value` from any `DynTask`.
+
+use serde_json::Value;
+
+use crate::{
+    distributed::FieldPrompt,
+    schema::{FieldDescriptor, json_schema},
+    traits::Task,
+};
+
+/// An extraction task whose prompts and schema are known at runtime.
+pub trait DynTask {
+    /// Returns the system prompt describing every field and the expected JSON.
+    fn system_prompt(&self) -> String;
+
+    /// Returns one prompt per field for distributed generation.
+    fn distributed_field_prompts(&self) -> Vec<FieldPrompt>;
+
+    /// Returns the descriptors of the task's fields.
+    fn fields(&self) -> Vec<FieldDescriptor>;
+
+    /// Returns a JSON Schema of the objects the task extracts.
+    fn json_schema(&self) -> Value {
+        json_schema(&self.fields())
+    }
+}
+
+impl<T: Task> DynTask for T {
+    fn system_prompt(&self) -> String {
+        self.get_system_prompt()
+    }
+
+    fn distributed_field_prompts(&self) -> Vec<FieldPrompt> {
+        self.get_distributed_field_prompts()
+    }
+
+    fn fields(&self) -> Vec<FieldDescriptor> {
+        T::field_descriptors()
+    }
+}
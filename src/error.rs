@@ -1,3 +1,68 @@
+//! Errors returned by the library.
+//!
+//! Parse failures carry the content the model returned, so it can be logged, retried or
+//! handed to a person: `SecretaryError::JsonParsingError::raw_content` for single requests
+//! and `FieldDeserializationError::raw_field_contents` for distributed generation. Neither is
+//! truncated. `redacted()` describes an error without them.
+//!
+//! Answers in which the model declines the request fail with `SecretaryError::Refused`
+//! instead, see the `refusal` module.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use secretary::Task;
+//! use secretary::SecretaryError;
+//! use secretary::llm_providers::openai::OpenAILLM;
+//! use secretary::traits::GenerateData;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! struct Person {
+//!     #[task(instruction = "Extract the name")]
+//!     pub name: String,
+//!     #[task(instruction = "Extract the age as a number")]
+//!     pub age: u32,
+//! }
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+//! let llm = OpenAILLM::new("https://api.openai.com/v1", "your-api-key", "gpt-4o-mini")?;
+//! let task = Person::new();
+//!
+//! // Single requests keep the whole answer
+//! if let Err(error) = llm.generate_data(&task, "Ada, 36", &vec![]) {
+//!     match error.downcast_ref::<SecretaryError>() {
+//!         Some(error @ SecretaryError::JsonParsingError { raw_content, .. }) => {
+//!             eprintln!("{}", error.redacted());
+//!             println!("The model answered: {}", raw_content);
+//!         }
+//!         _ => return Err(error),
+//!     }
+//! }
+//!
+//! // Distributed generation keeps the content of every field
+//! if let Err(error) = llm.fields_generate_data(&task, "Ada, 36", &vec![]) {
+//!     match error.downcast_ref::<SecretaryError>() {
+//!         Some(SecretaryError::FieldDeserializationError(details)) => {
+//!             for field in &details.failed_fields {
+//!                 println!("{}: {:?}", field, details.raw_field_contents[field]);
+//!             }
+//!         }
+//!         _ => return Err(error),
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use crate::{
+    guardrail::InjectionFinding, limits::OutputLimit, llm_providers::env::ConfigurationIssue,
+    schema_check::SchemaMismatch, validation::SchemaViolation,
+};
+
 /// Custom error type for the `secretary` library.
 ///
 /// This enum consolidates all possible errors that can occur during the data extraction process,
@@ -6,7 +71,14 @@
 pub enum SecretaryError {
     TokioRuntime(std::io::Error),
     SerdeJsonError(serde_json::Error),
-    JsonParsingError(String),
+    SerdeYamlError(serde_yaml::Error),
+    /// Indicates that the LLM's output could not be parsed into the requested type.
+    JsonParsingError {
+        /// What went wrong while parsing.
+        message: String,
+        /// The content returned by the LLM, verbatim.
+        raw_content: String,
+    },
     NoLLMResponse,
     BuildRequestError(String),
     /// Indicates a failure during the deserialization of individual fields from the LLM's response.
@@ -14,13 +86,187 @@ pub enum SecretaryError {
     /// This error is particularly useful for debugging issues with distributed generation,
     /// as it provides detailed information about which fields were successfully parsed and which failed.
     FieldDeserializationError(FieldDeserializationError),
+    /// Indicates that a field path such as `address.city` or `items[0]` does not lead to a
+    /// value that can be set.
+    InvalidFieldPath(String),
+    /// Indicates that even the smallest request shape does not fit the model's context window.
+    ContextTooSmall {
+        /// Estimated prompt tokens of the smallest request that could be built.
+        required: usize,
+        /// The context window declared by the provider.
+        available: usize,
+    },
+    /// Indicates that the deadline set with `RequestOptions::with_deadline` passed before the
+    /// extraction completed.
+    DeadlineExceeded {
+        /// The paths of the fields that did not complete in time, empty for single requests.
+        incomplete_fields: Vec<String>,
+    },
+    /// Indicates that partial extraction could not produce a value for a field marked
+    /// `#[task(importance = "critical")]`.
+    CriticalFieldsMissing {
+        /// The paths of the critical fields without a usable value.
+        fields: Vec<String>,
+    },
+    /// Indicates that the LLM's output does not match the Task's JSON Schema, see the
+    /// `validation` module.
+    SchemaViolation {
+        /// Every violation found, each located by a JSON pointer.
+        violations: Vec<SchemaViolation>,
+        /// The content returned by the LLM, verbatim.
+        raw_content: String,
+    },
+    /// Indicates that the input could not be read from its reader or file.
+    InputRead(std::io::Error),
+    /// Indicates that the input bytes could not be decoded as text, see the `input` module.
+    InputDecode {
+        /// Why the bytes could not be decoded.
+        message: String,
+    },
+    /// Indicates that a response body is not valid UTF-8 under `DecodingPolicy::Strict`, see
+    /// the `decoding` module.
+    ResponseDecode {
+        /// Why the bytes could not be decoded.
+        message: String,
+    },
+    /// Indicates that the input is larger than the limit set with
+    /// `InputOptions::with_max_bytes`.
+    InputTooLarge {
+        /// The largest accepted input, in bytes.
+        limit: usize,
+    },
+    /// Indicates that the local extractor of a field with `#[task(ambiguous = "error")]`
+    /// found several values, see the `extractors` module.
+    AmbiguousLocalExtraction {
+        /// The dotted path of the field.
+        field_path: String,
+        /// The distinct values found, in the order they appear in the target.
+        candidates: Vec<String>,
+    },
+    /// Indicates that the model answered a field with a `one_of` with a value that is not
+    /// close to exactly one allowed value, under `OneOfPolicy::Error`, see the `vocabulary`
+    /// module.
+    ValueNotAllowed {
+        /// The dotted path of the field.
+        field_path: String,
+        /// The model's answer.
+        value: String,
+        /// The allowed values.
+        allowed: Vec<String>,
+    },
+    /// Indicates that the provider's guardrail found prompt injection in the target under
+    /// `GuardrailPolicy::Reject`, see the `guardrail` module.
+    PromptInjectionDetected {
+        /// Everything the detector found, in the order it appears in the target.
+        findings: Vec<InjectionFinding>,
+    },
+    /// Indicates that a JSON value is not a `ContextualTask` envelope, see the `contextual`
+    /// module.
+    InvalidContextualJson {
+        /// What is wrong with the envelope.
+        message: String,
+    },
+    /// Indicates that the model's output is over one of the provider's or the call's
+    /// `OutputLimits`, see the `limits` module.
+    OutputLimitExceeded {
+        /// The limit that was exceeded.
+        limit: OutputLimit,
+        /// The path of the value over the limit, empty for the whole output.
+        path: String,
+        /// The limit.
+        max: usize,
+        /// The size found, counted like the limit. For a response body without a
+        /// `Content-Length`, the bytes read before reading stopped.
+        actual: usize,
+    },
+    /// Indicates that an instruction has a `{placeholder}` without a value, see
+    /// `utilities::render_template`.
+    MissingTemplateVar {
+        /// The name of the placeholder.
+        name: String,
+    },
+    /// Indicates that the model refused the request, as reported by the provider or found by
+    /// `refusal::detect_refusal`, see the `refusal` module.
+    Refused {
+        /// The model's answer.
+        message: String,
+    },
+    /// Indicates that the answer to a classification request has no entry for some labels
+    /// under `MissingLabelPolicy::Error`, see the `classification` module.
+    MissingLabels {
+        /// The labels without an entry, in the order they are listed.
+        labels: Vec<String>,
+    },
+    /// Indicates that an item of a list with `#[task(ordered)]` appears in the target before
+    /// the item preceding it, under `OrderCheck::Error`, see the `ordering` module.
+    OrderViolated {
+        /// The path of the item, e.g. `steps[2]`.
+        path: String,
+        /// The text the item was located by.
+        item: String,
+    },
+    /// Indicates that a `Lazy` field was read before an extraction bound it to a provider,
+    /// e.g. a Task built by hand or deserialized from JSON, see the `lazy` module.
+    LazyFieldUnbound,
+    /// Indicates that the environment variables of a provider are missing or malformed, see
+    /// the `llm_providers::env` module.
+    MissingConfiguration {
+        /// Every missing or malformed variable, in the order they are read.
+        issues: Vec<ConfigurationIssue>,
+    },
+    /// Indicates that a strict `ReplayLLM` has no recorded response for a request, see the
+    /// `llm_providers::record` module.
+    UnmatchedRequest {
+        /// The hash of the request body, as in the names of the recorded files.
+        hash: String,
+    },
+    /// Indicates that a Task cannot be extracted with the requested generation method, e.g.
+    /// an enum Task with distributed generation, see the `variants` module.
+    UnsupportedGeneration {
+        /// Why the Task cannot be extracted this way.
+        message: String,
+    },
+    /// Indicates that a tenant reached the hard cap set on its requests by the provider's
+    /// `QuotaPartitioner`, see the `llm_providers::quota` module.
+    TenantQuotaExceeded {
+        /// The tenant of the call.
+        tenant: String,
+        /// The most requests of the tenant per window.
+        max_requests: usize,
+        /// The length of the window.
+        window: Duration,
+    },
+    /// Indicates that a provider was built with a retired model from a strict
+    /// `ModelRegistry`, see the `llm_providers::models` module.
+    RetiredModel {
+        /// The configured model.
+        model: String,
+        /// The day the model stopped being served, as `YYYY-MM-DD`.
+        retired_on: String,
+        /// The model recommended instead.
+        replacement: Option<String>,
+    },
+    /// Indicates that `ModelRegistry::refresh_from_api` could not read the provider's list of
+    /// models.
+    ModelListUnavailable {
+        /// Why the list could not be read.
+        message: String,
+    },
+    /// Indicates that serde writes or reads other keys than the prompt of a Task asks for,
+    /// under `RequestOptions::strict_schema`, see the `schema_check` module.
+    SchemaInconsistent {
+        /// The type name of the Task.
+        task: String,
+        /// Every mismatch, by schema path.
+        mismatches: Vec<SchemaMismatch>,
+    },
 }
 
 /// A detailed error report for field-level deserialization failures.
 ///
-/// This struct is returned when `generate_from_tuples!` fails to construct the target struct
-/// from the key-value pairs returned by the LLM. It captures which fields succeeded, which failed,
-/// and the underlying deserialization error.
+/// This struct is returned when `assembly::assemble_from_fields` fails to construct the target
+/// struct from the key-value pairs returned by the LLM. It captures which fields succeeded, which
+/// failed, and the underlying deserialization error.
 #[derive(Debug, Clone)]
 pub struct FieldDeserializationError {
     /// A list of field names that could not be successfully deserialized.
@@ -29,6 +275,12 @@ pub struct FieldDeserializationError {
     pub successful_fields: Vec<String>,
     /// The original `serde_json::Error` message that caused the failure, converted to a string.
     pub original_error: String,
+    /// The content the LLM returned for each field by path, as found in its result tags and
+    /// otherwise unchanged. Empty when the fields were not requested separately.
+    pub raw_field_contents: BTreeMap<String, String>,
+    /// The fields whose request the model refused, also listed in `failed_fields`. Their raw
+    /// content is the refusal.
+    pub refused_fields: Vec<String>,
 }
 
 impl std::fmt::Display for SecretaryError {
@@ -36,32 +288,339 @@ impl std::fmt::Display for SecretaryError {
         match self {
             SecretaryError::TokioRuntime(e) => write!(f, "Tokio runtime error: {}", e),
             SecretaryError::SerdeJsonError(e) => write!(f, "Serde JSON error: {}", e),
+            SecretaryError::SerdeYamlError(e) => write!(f, "Serde YAML error: {}", e),
             SecretaryError::NoLLMResponse => write!(f, "No response is retrieved from the LLM"),
             SecretaryError::BuildRequestError(e) => write!(f, "Failed to build request: {}", e),
-            SecretaryError::JsonParsingError(e) => {
-                write!(f, "LLM generated a malformed json. Error message: {}", e)
-            }
+            SecretaryError::JsonParsingError {
+                message,
+                raw_content,
+            } => write!(
+                f,
+                "LLM generated a malformed json. Error message: {}. Raw content: {}",
+                message, raw_content
+            ),
             SecretaryError::FieldDeserializationError(e) => {
                 write!(f, "Field deserialization failed: {}", e)
             }
+            SecretaryError::InvalidFieldPath(path) => {
+                write!(f, "The field path `{}` cannot be set", path)
+            }
+            SecretaryError::ContextTooSmall {
+                required,
+                available,
+            } => write!(
+                f,
+                "The prompt needs about {} tokens but the model's context window is {} tokens",
+                required, available
+            ),
+            SecretaryError::DeadlineExceeded { incomplete_fields } => {
+                if incomplete_fields.is_empty() {
+                    write!(f, "The deadline passed before the request completed")
+                } else {
+                    write!(
+                        f,
+                        "The deadline passed before {} field(s) completed: [{}]",
+                        incomplete_fields.len(),
+                        incomplete_fields.join(", ")
+                    )
+                }
+            }
+            SecretaryError::CriticalFieldsMissing { fields } => write!(
+                f,
+                "No usable value for {} critical field(s): [{}]",
+                fields.len(),
+                fields.join(", ")
+            ),
+            SecretaryError::SchemaViolation {
+                violations,
+                raw_content,
+            } => {
+                let violations: Vec<String> = violations.iter().map(ToString::to_string).collect();
+                write!(
+                    f,
+                    "LLM output violates the schema in {} place(s): [{}]. Raw content: {}",
+                    violations.len(),
+                    violations.join("; "),
+                    raw_content
+                )
+            }
+            SecretaryError::InputRead(e) => write!(f, "Failed to read the input: {}", e),
+            SecretaryError::InputDecode { message } => {
+                write!(f, "Failed to decode the input as text: {}", message)
+            }
+            SecretaryError::ResponseDecode { message } => {
+                write!(f, "Failed to decode the response as UTF-8: {}", message)
+            }
+            SecretaryError::InputTooLarge { limit } => {
+                write!(f, "The input is larger than the limit of {} bytes", limit)
+            }
+            SecretaryError::AmbiguousLocalExtraction {
+                field_path,
+                candidates,
+            } => write!(
+                f,
+                "The extractor of `{}` found {} values: [{}]",
+                field_path,
+                candidates.len(),
+                candidates.join(", ")
+            ),
+            SecretaryError::ValueNotAllowed {
+                field_path,
+                value,
+                allowed,
+            } => write!(
+                f,
+                "The value {:?} of `{}` is not one of [{}]",
+                value,
+                field_path,
+                allowed.join(", ")
+            ),
+            SecretaryError::PromptInjectionDetected { findings } => {
+                let findings: Vec<String> = findings
+                    .iter()
+                    .map(|finding| format!("{:?} {:?}", finding.signal, finding.text))
+                    .collect();
+                write!(
+                    f,
+                    "The target looks like a prompt injection, {} finding(s): [{}]",
+                    findings.len(),
+                    findings.join(", ")
+                )
+            }
+            SecretaryError::InvalidContextualJson { message } => {
+                write!(f, "Not a ContextualTask envelope: {}", message)
+            }
+            SecretaryError::OutputLimitExceeded {
+                limit,
+                path,
+                max,
+                actual,
+            } => {
+                if path.is_empty() {
+                    write!(
+                        f,
+                        "The output is over its {:?} limit: {} > {}",
+                        limit, actual, max
+                    )
+                } else {
+                    write!(
+                        f,
+                        "The output is over its {:?} limit at `{}`: {} > {}",
+                        limit, path, actual, max
+                    )
+                }
+            }
+            SecretaryError::MissingTemplateVar { name } => {
+                write!(f, "The instruction placeholder {{{}}} has no value", name)
+            }
+            SecretaryError::Refused { message } => {
+                write!(f, "The model refused the request: {}", message)
+            }
+            SecretaryError::MissingLabels { labels } => write!(
+                f,
+                "The answer has no entry for {} label(s): [{}]",
+                labels.len(),
+                labels.join(", ")
+            ),
+            SecretaryError::OrderViolated { path, item } => write!(
+                f,
+                "The item `{}` ({:?}) appears in the target before the item preceding it",
+                path, item
+            ),
+            SecretaryError::LazyFieldUnbound => write!(
+                f,
+                "The lazy field has no value and no extraction to request it from"
+            ),
+            SecretaryError::MissingConfiguration { issues } => {
+                let issues: Vec<String> = issues.iter().map(ToString::to_string).collect();
+                write!(
+                    f,
+                    "The provider configuration has {} issue(s): [{}]",
+                    issues.len(),
+                    issues.join("; ")
+                )
+            }
+            SecretaryError::UnmatchedRequest { hash } => write!(
+                f,
+                "No recorded response matches the request with hash {}",
+                hash
+            ),
+            SecretaryError::UnsupportedGeneration { message } => {
+                write!(f, "Unsupported generation: {}", message)
+            }
+            SecretaryError::TenantQuotaExceeded {
+                tenant,
+                max_requests,
+                window,
+            } => write!(
+                f,
+                "Tenant {} reached its quota of {} request(s) per {:?}",
+                tenant, max_requests, window
+            ),
+            SecretaryError::RetiredModel {
+                model,
+                retired_on,
+                replacement,
+            } => {
+                write!(f, "The model {} was retired on {}", model, retired_on)?;
+                match replacement {
+                    Some(replacement) => write!(f, ", use {} instead", replacement),
+                    None => Ok(()),
+                }
+            }
+            SecretaryError::ModelListUnavailable { message } => {
+                write!(f, "Failed to list the provider's models: {}", message)
+            }
+            SecretaryError::SchemaInconsistent { task, mismatches } => {
+                let mismatches: Vec<String> = mismatches.iter().map(ToString::to_string).collect();
+                write!(
+                    f,
+                    "The serde representation of {} differs from its prompt in {} place(s): [{}]",
+                    task,
+                    mismatches.len(),
+                    mismatches.join("; ")
+                )
+            }
         }
     }
 }
 
-impl std::fmt::Display for FieldDeserializationError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "Failed to deserialize {} field(s): [{}]. Successfully parsed {} field(s): [{}]. Original error: {}",
+impl SecretaryError {
+    /// Describes the error without any text that may come from the LLM.
+    ///
+    /// Raw output is replaced by its length and parser messages, which can quote the output,
+    /// are left out. Meant for logs that must not contain the extracted text.
+    pub fn redacted(&self) -> String {
+        match self {
+            SecretaryError::JsonParsingError { raw_content, .. } => format!(
+                "LLM generated a malformed json. Raw content: {}",
+                redact(raw_content)
+            ),
+            SecretaryError::FieldDeserializationError(e) => {
+                format!("Field deserialization failed: {}", e.redacted())
+            }
+            SecretaryError::SchemaViolation {
+                violations,
+                raw_content,
+            } => {
+                let violations: Vec<String> =
+                    violations.iter().map(SchemaViolation::redacted).collect();
+                format!(
+                    "LLM output violates the schema in {} place(s): [{}]. Raw content: {}",
+                    violations.len(),
+                    violations.join("; "),
+                    redact(raw_content)
+                )
+            }
+            SecretaryError::AmbiguousLocalExtraction {
+                field_path,
+                candidates,
+            } => format!(
+                "The extractor of `{}` found {} values",
+                field_path,
+                candidates.len()
+            ),
+            SecretaryError::ValueNotAllowed {
+                field_path,
+                value,
+                allowed,
+            } => format!(
+                "The value of `{}` ({}) is not one of [{}]",
+                field_path,
+                redact(value),
+                allowed.join(", ")
+            ),
+            SecretaryError::Refused { message } => {
+                format!("The model refused the request: {}", redact(message))
+            }
+            SecretaryError::OrderViolated { path, item } => format!(
+                "The item `{}` ({}) appears in the target before the item preceding it",
+                path,
+                redact(item)
+            ),
+            SecretaryError::PromptInjectionDetected { findings } => {
+                let signals: Vec<String> = findings
+                    .iter()
+                    .map(|finding| format!("{:?}", finding.signal))
+                    .collect();
+                format!(
+                    "The target looks like a prompt injection, {} finding(s): [{}]",
+                    findings.len(),
+                    signals.join(", ")
+                )
+            }
+            _ => self.to_string(),
+        }
+    }
+}
+
+impl FieldDeserializationError {
+    /// Describes the error without any text that may come from the LLM.
+    ///
+    /// The raw field contents are replaced by their lengths and the original error, which
+    /// can quote them, is left out.
+    pub fn redacted(&self) -> String {
+        let mut description: String = self.describe_fields();
+        if !self.raw_field_contents.is_empty() {
+            let raw: Vec<String> = self
+                .raw_field_contents
+                .iter()
+                .map(|(path, content)| format!("{}: {}", path, redact(content)))
+                .collect();
+            description.push_str(&format!(". Raw field contents: {{{}}}", raw.join(", ")));
+        }
+
+        description
+    }
+
+    /// Describes the failed, refused and successful fields.
+    fn describe_fields(&self) -> String {
+        let refused: String = if self.refused_fields.is_empty() {
+            String::new()
+        } else {
+            format!(
+                ". The model refused {} field(s): [{}]",
+                self.refused_fields.len(),
+                self.refused_fields.join(", ")
+            )
+        };
+        format!(
+            "Failed to deserialize {} field(s): [{}]{}. Successfully parsed {} field(s): [{}]",
             self.failed_fields.len(),
             self.failed_fields.join(", "),
+            refused,
             self.successful_fields.len(),
             self.successful_fields.join(", "),
-            self.original_error
         )
     }
 }
 
+/// Replaces raw LLM output with a note of its length.
+fn redact(content: &str) -> String {
+    format!("<{} characters redacted>", content.chars().count())
+}
+
+impl std::fmt::Display for FieldDeserializationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}. Original error: {}",
+            self.describe_fields(),
+            self.original_error
+        )?;
+        if !self.raw_field_contents.is_empty() {
+            let raw: Vec<String> = self
+                .raw_field_contents
+                .iter()
+                .map(|(path, content)| format!("{}: {:?}", path, content))
+                .collect();
+            write!(f, ". Raw field contents: {{{}}}", raw.join(", "))?;
+        }
+
+        Ok(())
+    }
+}
+
 impl std::error::Error for FieldDeserializationError {}
 
 impl std::error::Error for SecretaryError {}
@@ -72,6 +631,12 @@ impl From<serde_json::Error> for SecretaryError {
     }
 }
 
+impl From<serde_yaml::Error> for SecretaryError {
+    fn from(e: serde_yaml::Error) -> Self {
+        SecretaryError::SerdeYamlError(e)
+    }
+}
+
 impl From<std::io::Error> for SecretaryError {
     fn from(e: std::io::Error) -> Self {
         SecretaryError::TokioRuntime(e)
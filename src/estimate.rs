@@ -0,0 +1,228 @@
+//! Pre-flight estimates of what an extraction will cost before it is sent.
+//!
+//! `GenerateData::plan` builds the prompts an extraction would send in a given
+//! `GenerationMode` without sending them, and returns an `ExtractionEstimate` with:
+//!
+//! - every request and its prompt tokens, measured with `tokens::estimate_tokens`; in
+//!   `GenerationMode::Fields` there is one request per field or group, labelled with its
+//!   field path, so the expensive fields stand out
+//! - the total number of requests and prompt tokens
+//! - the cost of the prompt tokens, when a `Pricing` is configured on the provider with
+//!   `with_pricing`
+//! - the wall-clock time, assuming every request takes `request_latency` and that at most
+//!   the mode's concurrency of them run at once
+//!
+//! Fields filled by local extractors are left out like the extraction leaves them out. The
+//! provider's guardrail is not run, so the target is measured as given. The latency of a
+//! request is an assumption, `DEFAULT_REQUEST_LATENCY` unless set with
+//! `ExtractionEstimate::with_request_latency`.
+//!
+//! The estimate implements `Display` for a readable summary and `Serialize` for tooling.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use std::time::Duration;
+//!
+//! use secretary::Task;
+//! use secretary::estimate::{ExtractionEstimate, Pricing};
+//! use secretary::llm_providers::openai::OpenAILLM;
+//! use secretary::mode::GenerationMode;
+//! use secretary::traits::GenerateData;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! struct Contact {
+//!     #[task(instruction = "Extract the person's full name")]
+//!     pub name: String,
+//!     #[task(instruction = "Extract the email address")]
+//!     pub email: String,
+//!     #[task(instruction = "Extract the phone number")]
+//!     pub phone: String,
+//! }
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+//! let llm = OpenAILLM::new("https://api.openai.com/v1", "your-api-key", "gpt-4o")?
+//!     .with_pricing(Pricing::new(2.5));
+//! let target = "John Smith, john@acme.com, +1 555 0100";
+//!
+//! let json: ExtractionEstimate =
+//!     llm.plan(&Contact::new(), target, vec![], GenerationMode::JsonMode)?;
+//! let fields: ExtractionEstimate = llm
+//!     .plan(&Contact::new(), target, vec![], GenerationMode::Fields { concurrency: Some(2) })?
+//!     .with_request_latency(Duration::from_secs(1));
+//!
+//! assert_eq!(json.request_count, 1);
+//! assert_eq!(fields.request_count, 3);
+//! assert_eq!(fields.requests[1].label, "email");
+//! // Two rounds of at most two requests
+//! assert_eq!(fields.estimated_latency, Duration::from_secs(2));
+//! assert!(fields.estimated_cost.is_some());
+//! println!("{}", fields);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::mode::GenerationMode;
+
+/// The latency assumed for one request unless the estimate is given another.
+pub const DEFAULT_REQUEST_LATENCY: Duration = Duration::from_secs(2);
+
+/// The price of a model's prompt tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Pricing {
+    /// The price of one million prompt tokens, in the currency of the provider's bill.
+    pub prompt_per_million_tokens: f64,
+}
+
+impl Pricing {
+    /// Creates a pricing from the price of one million prompt tokens.
+    pub fn new(prompt_per_million_tokens: f64) -> Self {
+        Self {
+            prompt_per_million_tokens,
+        }
+    }
+
+    /// Returns the price of a number of prompt tokens.
+    pub fn prompt_cost(&self, prompt_tokens: usize) -> f64 {
+        prompt_tokens as f64 * self.prompt_per_million_tokens / 1_000_000.0
+    }
+}
+
+/// One request an extraction would send.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RequestEstimate {
+    /// The field path of the request in fields mode, `task` for the single request of the
+    /// other modes.
+    pub label: String,
+    /// The estimated tokens of the prompt.
+    pub prompt_tokens: usize,
+}
+
+/// What an extraction would send in one mode, see the module documentation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExtractionEstimate {
+    /// The mode the extraction would be sent in.
+    pub mode: GenerationMode,
+    /// Every request, in the order they would be sent.
+    pub requests: Vec<RequestEstimate>,
+    /// The number of requests.
+    pub request_count: usize,
+    /// The estimated prompt tokens of all requests.
+    pub prompt_tokens: usize,
+    /// The cost of the prompt tokens, when the provider has a pricing.
+    pub estimated_cost: Option<f64>,
+    /// The most requests in flight at once.
+    pub concurrency: usize,
+    /// The latency assumed for one request.
+    pub request_latency: Duration,
+    /// The wall-clock time of the extraction, one `request_latency` for every round of
+    /// `concurrency` requests.
+    pub estimated_latency: Duration,
+}
+
+impl ExtractionEstimate {
+    /// Creates the estimate of a list of requests.
+    ///
+    /// # Arguments
+    ///
+    /// * `mode` - The mode the requests would be sent in
+    /// * `requests` - The requests
+    /// * `pricing` - The pricing of the provider, if any
+    pub(crate) fn new(
+        mode: GenerationMode,
+        requests: Vec<RequestEstimate>,
+        pricing: Option<Pricing>,
+    ) -> Self {
+        let request_count: usize = requests.len();
+        let prompt_tokens: usize = requests.iter().map(|request| request.prompt_tokens).sum();
+        let concurrency: usize = match mode {
+            GenerationMode::Fields {
+                concurrency: Some(concurrency),
+            } => concurrency.clamp(1, request_count.max(1)),
+            _ => request_count.max(1),
+        };
+
+        Self {
+            mode,
+            requests,
+            request_count,
+            prompt_tokens,
+            estimated_cost: pricing.map(|pricing| pricing.prompt_cost(prompt_tokens)),
+            concurrency,
+            request_latency: DEFAULT_REQUEST_LATENCY,
+            estimated_latency: round_latency(request_count, concurrency, DEFAULT_REQUEST_LATENCY),
+        }
+    }
+
+    /// Sets the latency assumed for one request, and estimates the wall-clock time again.
+    ///
+    /// # Arguments
+    ///
+    /// * `request_latency` - The latency of one request, `DEFAULT_REQUEST_LATENCY` by default
+    pub fn with_request_latency(mut self, request_latency: Duration) -> Self {
+        self.request_latency = request_latency;
+        self.estimated_latency =
+            round_latency(self.request_count, self.concurrency, request_latency);
+        self
+    }
+
+    /// Returns the request with the most prompt tokens, if there is any.
+    pub fn most_expensive(&self) -> Option<&RequestEstimate> {
+        self.requests
+            .iter()
+            .rev()
+            .max_by_key(|request| request.prompt_tokens)
+    }
+}
+
+fn round_latency(request_count: usize, concurrency: usize, request_latency: Duration) -> Duration {
+    let rounds: u32 = request_count.div_ceil(concurrency.max(1)) as u32;
+    request_latency * rounds
+}
+
+impl fmt::Display for ExtractionEstimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mode: &str = match self.mode {
+            GenerationMode::JsonMode => "JSON mode",
+            GenerationMode::Force => "force mode",
+            GenerationMode::Fields { .. } => "fields mode",
+        };
+        write!(
+            f,
+            "{}: {} request(s), ~{} prompt tokens, ~{:.1?} at {:.1?} per request and {} at once",
+            mode,
+            self.request_count,
+            self.prompt_tokens,
+            self.estimated_latency,
+            self.request_latency,
+            self.concurrency
+        )?;
+        if let Some(cost) = self.estimated_cost {
+            write!(f, ", ~{:.4} for the prompts", cost)?;
+        }
+
+        let width: usize = self
+            .requests
+            .iter()
+            .map(|request| request.label.len())
+            .max()
+            .unwrap_or(0);
+        for request in &self.requests {
+            write!(
+                f,
+                "\n  {:<width$}  {:>6} tokens",
+                request.label,
+                request.prompt_tokens,
+                width = width
+            )?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,301 @@
+//! Explaining extracted values to the people who review them.
+//!
+//! A reviewer looking at an extracted struct often wants to know why a field has its value.
+//! `GenerateData::explain_field` sends the model one focused request with the original text,
+//! the field's instruction and the extracted value, and asks it to justify the value with
+//! verbatim quotes and to say whether, on reflection, the value looks wrong. The answer is an
+//! `Explanation`, which is `Serialize` so it can be stored next to the data in review tooling:
+//!
+//! - `quotes` are the model's quotes, each located in the text like the evidence of the
+//!   `provenance` module, so made-up quotes show as `QuoteMatch::NotFound`
+//! - `justification` is the model's reasoning in its own words
+//! - `looks_correct` is the model's assessment of the value
+//! - `suggested_correction` is the value the model would give the field instead, if any
+//!
+//! Field paths are checked against the Task's descriptors: `address.city`, an element such
+//! as `items[0].price` or `tags[1]`, or a whole list or map. `explain_all` explains every
+//! field whose value differs from the Task's default, with a bounded number of requests in
+//! flight; lists and maps of nested Tasks are explained as a whole.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::Task;
+//! use secretary::explanation::explainable_paths;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! struct Terms {
+//!     #[task(instruction = "Extract the notice period in days")]
+//!     pub notice_days: u32,
+//!     #[task(instruction = "Extract whether the contract renews automatically")]
+//!     pub auto_renews: bool,
+//! }
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! struct Contract {
+//!     #[task(instruction = "Extract the name of the supplier")]
+//!     pub supplier: String,
+//!     #[task(instruction = "Extract the name of the customer")]
+//!     pub customer: Option<String>,
+//!     pub terms: Terms,
+//! }
+//!
+//! let contract = Contract {
+//!     supplier: "Acme".to_string(),
+//!     customer: None,
+//!     terms: Terms { notice_days: 0, auto_renews: true },
+//! };
+//!
+//! // Fields left at their default are not explained
+//! assert_eq!(
+//!     explainable_paths(&Contract::field_descriptors(), &contract),
+//!     vec!["supplier", "terms.auto_renews"]
+//! );
+//! ```
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    SecretaryError,
+    message::Message,
+    provenance::{Provenance, locate_quote},
+    schema::{FieldDescriptor, FieldKind, JsonType},
+    traits::Task,
+    utilities::{format_additional_instructions, get_field_path, split_field_path},
+};
+
+/// Why a field has its value, as the model explains it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Explanation {
+    /// The path of the field, e.g. `terms.auto_renews`.
+    pub field_path: String,
+    /// The instruction of the field, empty for nested Tasks without one.
+    pub instruction: String,
+    /// The extracted value that was explained.
+    pub value: Value,
+    /// The quotes the model gave as evidence, located in the text.
+    pub quotes: Vec<Provenance>,
+    /// The model's reasoning.
+    pub justification: String,
+    /// Whether the model, on reflection, considers the value correct.
+    pub looks_correct: bool,
+    /// The value the model would give the field instead, `None` when it keeps the value or
+    /// gave no other.
+    pub suggested_correction: Option<Value>,
+}
+
+impl Explanation {
+    /// Returns whether the model doubts the value or gave no quote that is in the text.
+    pub fn needs_review(&self) -> bool {
+        !self.looks_correct
+            || self.suggested_correction.is_some()
+            || !self.quotes.iter().any(Provenance::is_located)
+    }
+}
+
+/// Returns the paths of the fields whose value differs from the Task's default, in the order
+/// of the descriptors.
+///
+/// Nested Tasks are descended into, while lists and maps of nested Tasks are listed as a
+/// whole. Fields that are `null` are left out.
+///
+/// # Arguments
+///
+/// * `fields` - The descriptors of the Task's fields
+/// * `extracted` - The extracted data
+pub fn explainable_paths<T: Task>(fields: &[FieldDescriptor], extracted: &T) -> Vec<String> {
+    let value: Value = serde_json::to_value(extracted).unwrap_or(Value::Null);
+    let default: Value = serde_json::to_value(T::default()).unwrap_or(Value::Null);
+    let mut paths: Vec<String> = Vec::new();
+    collect_paths(fields, &value, &default, "", &mut paths);
+
+    paths
+}
+
+fn collect_paths(
+    fields: &[FieldDescriptor],
+    value: &Value,
+    default: &Value,
+    prefix: &str,
+    paths: &mut Vec<String>,
+) {
+    for field in fields {
+        let Some(current) = value.get(&field.name) else {
+            continue;
+        };
+        if current.is_null() {
+            continue;
+        }
+
+        let path: String = join_path(prefix, &field.name);
+        let default: &Value = default.get(&field.name).unwrap_or(&Value::Null);
+        if matches!(field.kind, FieldKind::Task | FieldKind::OptionTask)
+            && !field.children.is_empty()
+            && current.is_object()
+        {
+            collect_paths(&field.children, current, default, &path, paths);
+        } else if current != default {
+            paths.push(path);
+        }
+    }
+}
+
+fn join_path(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", prefix, name)
+    }
+}
+
+/// Returns the descriptor of the field at a path, that of the list or map for the path of
+/// one of its elements.
+///
+/// # Arguments
+///
+/// * `fields` - The descriptors of the Task's fields
+/// * `field_path` - A path such as `address.city`, `items[0].price` or `tags[1]`
+pub fn field_at_path<'a>(
+    fields: &'a [FieldDescriptor],
+    field_path: &str,
+) -> Option<&'a FieldDescriptor> {
+    let segments: Vec<String> = split_field_path(field_path)?;
+    let mut segments = segments.iter();
+    let mut fields: &[FieldDescriptor] = fields;
+    let mut found: Option<&FieldDescriptor> = None;
+
+    while let Some(segment) = segments.next() {
+        let field: &FieldDescriptor = fields.iter().find(|field| field.name == *segment)?;
+        let is_collection: bool = match field.kind {
+            FieldKind::VecTask | FieldKind::HashMapTask | FieldKind::BTreeMapTask => true,
+            FieldKind::Normal => matches!(field.json_type, JsonType::Array | JsonType::Object),
+            FieldKind::Task | FieldKind::OptionTask => false,
+        };
+        if is_collection {
+            // The key of an element, if the path goes on
+            segments.next();
+        }
+        found = Some(field);
+        fields = &field.children;
+    }
+
+    found
+}
+
+/// A field of extracted data to explain, checked against the descriptors.
+#[derive(Debug, Clone)]
+pub(crate) struct ExplanationRequest {
+    field_path: String,
+    instruction: String,
+    value: Value,
+}
+
+/// The answer the model is asked for.
+#[derive(Deserialize)]
+struct ExplanationAnswer {
+    #[serde(default)]
+    quotes: Vec<String>,
+    #[serde(default)]
+    justification: String,
+    #[serde(default = "answer_looks_correct")]
+    looks_correct: bool,
+    #[serde(default)]
+    suggested_correction: Option<Value>,
+}
+
+fn answer_looks_correct() -> bool {
+    true
+}
+
+impl ExplanationRequest {
+    /// Finds the field and its value in the extracted data.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SecretaryError::InvalidFieldPath` if the path names no field of the
+    /// descriptors or no value of the data.
+    pub(crate) fn new<T: Task>(
+        fields: &[FieldDescriptor],
+        extracted: &T,
+        field_path: &str,
+    ) -> Result<Self, SecretaryError> {
+        let invalid_path = || SecretaryError::InvalidFieldPath(field_path.to_string());
+        let field: &FieldDescriptor = field_at_path(fields, field_path).ok_or_else(invalid_path)?;
+        let extracted: Value = serde_json::to_value(extracted)?;
+        let value: Value = get_field_path(&extracted, field_path)
+            .cloned()
+            .ok_or_else(invalid_path)?;
+
+        Ok(Self {
+            field_path: field_path.to_string(),
+            instruction: field.instruction.clone(),
+            value,
+        })
+    }
+
+    /// Creates the messages asking the model to explain the value.
+    pub(crate) fn messages(
+        &self,
+        target: &str,
+        additional_instructions: &[String],
+    ) -> Vec<Message> {
+        let mut prompt: String = String::from(
+            "You review a value that was extracted from a text. Justify the value with short verbatim quotes from the text, then consider whether the value is wrong, incomplete or not supported by the text.\n\n",
+        );
+        prompt.push_str(&format!("Field: {}\n", self.field_path));
+        if !self.instruction.is_empty() {
+            prompt.push_str(&format!("Instruction: {}\n", self.instruction));
+        }
+        prompt.push_str(&format!("Extracted value: {}\n", self.value));
+        prompt.push_str(
+            "\nRespond with only a JSON object:\n{\n  \"quotes\": [\"a verbatim quote from the text\"],\n  \"justification\": \"why the text supports the value\",\n  \"looks_correct\": true,\n  \"suggested_correction\": null\n}\nWhen the value is wrong, set \"looks_correct\" to false and \"suggested_correction\" to the value the field should have, of the same JSON type.\n",
+        );
+        prompt.push_str(&format_additional_instructions(additional_instructions));
+
+        vec![
+            Message::system(prompt),
+            Message::user(format!(
+                "This is the text the value was extracted from:\n{}",
+                target
+            )),
+        ]
+    }
+
+    /// Parses the model's answer and locates its quotes in the target.
+    pub(crate) fn parse(
+        &self,
+        content: &str,
+        target: &str,
+    ) -> Result<Explanation, serde_json::Error> {
+        let answer: ExplanationAnswer = serde_json::from_str(content)?;
+        let quotes: Vec<Provenance> = answer
+            .quotes
+            .into_iter()
+            .filter(|quote| !quote.trim().is_empty())
+            .map(|quote| {
+                let (char_range, match_kind) = locate_quote(target, &quote);
+                Provenance {
+                    field_path: self.field_path.clone(),
+                    quote,
+                    char_range,
+                    match_kind,
+                }
+            })
+            .collect();
+        let suggested_correction: Option<Value> = answer
+            .suggested_correction
+            .filter(|correction| !correction.is_null() && *correction != self.value);
+
+        Ok(Explanation {
+            field_path: self.field_path.clone(),
+            instruction: self.instruction.clone(),
+            value: self.value.clone(),
+            quotes,
+            justification: answer.justification,
+            looks_correct: answer.looks_correct,
+            suggested_correction,
+        })
+    }
+}
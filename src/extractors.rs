@@ -0,0 +1,419 @@
+//! Local extractors that fill trivial fields without asking the LLM.
+//!
+//! Some fields are found perfectly by a pattern, such as an email address, and asking the LLM
+//! for them costs tokens in a single request and a whole request in distributed generation.
+//! `#[task(extractor = "...")]` on a `String` or `Option<String>` field names an extractor that
+//! runs over the target first:
+//!
+//! - `"email"` finds email addresses
+//! - `"url"` finds `http://`, `https://` and `www.` links
+//! - `"phone"` finds phone numbers written with a leading `+` or with separators
+//! - `"regex:<pattern>"` finds the matches of the pattern, or of its first capture group
+//!
+//! When the extractor finds exactly one value, the field is filled with it: `generate_data`
+//! leaves the field out of the prompt and merges the value into the parsed response, and
+//! `fields_generate_data` sends no request for it. When it finds nothing, the LLM is asked as
+//! usual. When it finds several distinct values, `#[task(ambiguous = "...")]` decides: `"llm"`
+//! (the default) asks the LLM, `"first"` takes the first one in the text and `"error"` fails
+//! with `SecretaryError::AmbiguousLocalExtraction`. Adaptive generation reports the fields
+//! filled locally in `GenerationMetadata::locally_extracted`.
+//!
+//! Extractors apply to the fields of the struct and of nested Tasks, but not to the elements
+//! of collections of nested Tasks, which have no single place in the text.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::Task;
+//! use secretary::extractors::{Extractor, extract_local_fields};
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! struct Lead {
+//!     #[task(instruction = "Extract the name")]
+//!     pub name: String,
+//!     #[task(instruction = "Extract the email address", extractor = "email")]
+//!     pub email: Option<String>,
+//!     #[task(instruction = "Extract the phone number", extractor = "phone", ambiguous = "first")]
+//!     pub phone: Option<String>,
+//!     #[task(instruction = "Extract the ticket", extractor = r"regex:TICKET-(\d+)")]
+//!     pub ticket: String,
+//! }
+//!
+//! let target = "Ada Lovelace (ada@example.com) called from +44 20 7946 0958, \
+//!               then from (020) 7946-0000, about TICKET-4521.";
+//! let values = extract_local_fields(&Lead::field_descriptors(), target).unwrap();
+//! assert_eq!(
+//!     values,
+//!     vec![
+//!         ("email".to_string(), "ada@example.com".to_string()),
+//!         ("phone".to_string(), "+44 20 7946 0958".to_string()),
+//!         ("ticket".to_string(), "4521".to_string()),
+//!     ]
+//! );
+//!
+//! // The fields found locally are left out of the prompt
+//! let prompt: String = Lead::new().get_system_prompt_without_fields(&["email".to_string()]);
+//! assert!(!prompt.contains("email"));
+//! assert!(prompt.contains("Extract the phone number"));
+//!
+//! // Extractors can be used on their own
+//! let urls = Extractor::Url.candidates("See https://example.com/docs, or www.example.org.");
+//! assert_eq!(urls, vec!["https://example.com/docs", "www.example.org"]);
+//! ```
+//!
+//! Extractors are checked at compile time: they only apply to text fields, and regular
+//! expressions must be valid.
+//!
+//! ```compile_fail
+//! use secretary::Task;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! struct Lead {
+//!     #[task(instruction = "Extract the age", extractor = "regex:\\d+")]
+//!     pub age: u32,
+//! }
+//! ```
+//!
+//! ```compile_fail
+//! use secretary::Task;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! struct Lead {
+//!     #[task(instruction = "Extract the ticket", extractor = "regex:TICKET-(\\d+")]
+//!     pub ticket: String,
+//! }
+//! ```
+
+use std::str::FromStr;
+use std::sync::LazyLock;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    SecretaryError,
+    schema::{FieldDescriptor, FieldKind},
+    utilities::{remove_field_path, set_field_path},
+};
+
+static EMAIL: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\b[a-z0-9._%+-]+@[a-z0-9-]+(?:\.[a-z0-9-]+)*\.[a-z]{2,}\b").unwrap()
+});
+
+static URL: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?i)\b(?:https?://|www\.)[^\s<>"']+"#).unwrap());
+
+static PHONE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?:^|[^\w+])(\+?[(\d][\d ().-]{5,}\d)").unwrap());
+
+static DATE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?:\d{4}[./-]\d{1,2}[./-]\d{1,2}|\d{1,2}[./-]\d{1,2}[./-]\d{2,4})$").unwrap()
+});
+
+/// A local extractor, from `#[task(extractor = "...")]`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Extractor {
+    /// Email addresses.
+    Email,
+    /// Links starting with `http://`, `https://` or `www.`.
+    Url,
+    /// Phone numbers of 7 to 15 digits, written with a leading `+` or with separators.
+    Phone,
+    /// The matches of a regular expression, or of its first capture group when it has one.
+    Regex(String),
+}
+
+impl Extractor {
+    /// Returns the distinct values the extractor finds in the text, in the order they appear.
+    ///
+    /// A regular expression that does not compile finds nothing.
+    pub fn candidates(&self, text: &str) -> Vec<String> {
+        let found: Vec<String> = match self {
+            Extractor::Email => EMAIL
+                .find_iter(text)
+                .map(|found| found.as_str().to_string())
+                .collect(),
+            Extractor::Url => URL
+                .find_iter(text)
+                .map(|found| trim_url(found.as_str()).to_string())
+                .collect(),
+            Extractor::Phone => PHONE
+                .captures_iter(text)
+                .filter_map(|captures| normalize_phone(&captures[1]))
+                .collect(),
+            Extractor::Regex(pattern) => match Regex::new(pattern) {
+                Ok(regex) => regex
+                    .captures_iter(text)
+                    .filter_map(|captures| captures.get(1).or_else(|| captures.get(0)))
+                    .map(|found| found.as_str().to_string())
+                    .filter(|found| !found.is_empty())
+                    .collect(),
+                Err(_) => Vec::new(),
+            },
+        };
+
+        let mut candidates: Vec<String> = Vec::new();
+        for value in found {
+            if !candidates.contains(&value) {
+                candidates.push(value);
+            }
+        }
+
+        candidates
+    }
+}
+
+impl FromStr for Extractor {
+    type Err = String;
+
+    /// Parses `email`, `url`, `phone` or `regex:<pattern>`.
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        match spec {
+            "email" => Ok(Extractor::Email),
+            "url" => Ok(Extractor::Url),
+            "phone" => Ok(Extractor::Phone),
+            _ => match spec.strip_prefix("regex:") {
+                Some("") => Err("the regex extractor needs a pattern after `regex:`".to_string()),
+                Some(pattern) => match Regex::new(pattern) {
+                    Ok(_) => Ok(Extractor::Regex(pattern.to_string())),
+                    Err(error) => Err(format!("invalid regex `{}`: {}", pattern, error)),
+                },
+                None => Err(format!(
+                    "unknown extractor `{}`, expected \"email\", \"url\", \"phone\" or \"regex:<pattern>\"",
+                    spec
+                )),
+            },
+        }
+    }
+}
+
+/// What to do when an extractor finds several distinct values, from
+/// `#[task(ambiguous = "...")]`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AmbiguityPolicy {
+    /// Ask the LLM for the field.
+    #[default]
+    Llm,
+    /// Take the value that appears first in the text.
+    First,
+    /// Fail with `SecretaryError::AmbiguousLocalExtraction`.
+    Error,
+}
+
+/// The local extraction of a field: its extractor and what to do with several values.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LocalExtraction {
+    /// The extractor run over the target.
+    pub extractor: Extractor,
+    /// What to do when the extractor finds several distinct values.
+    #[serde(default)]
+    pub ambiguous: AmbiguityPolicy,
+}
+
+impl LocalExtraction {
+    /// Runs the extractor over the target and applies the ambiguity policy.
+    ///
+    /// # Arguments
+    ///
+    /// * `field_path` - The dotted path of the field, for the error
+    /// * `target` - The text the data is extracted from
+    ///
+    /// # Returns
+    ///
+    /// The value of the field, or `None` if the LLM has to be asked for it
+    ///
+    /// # Errors
+    ///
+    /// Returns `SecretaryError::AmbiguousLocalExtraction` if several values were found and the
+    /// policy is `AmbiguityPolicy::Error`.
+    pub fn resolve(
+        &self,
+        field_path: &str,
+        target: &str,
+    ) -> Result<Option<String>, SecretaryError> {
+        let mut candidates: Vec<String> = self.extractor.candidates(target);
+        if candidates.len() <= 1 {
+            return Ok(candidates.pop());
+        }
+
+        match self.ambiguous {
+            AmbiguityPolicy::Llm => Ok(None),
+            AmbiguityPolicy::First => Ok(Some(candidates.swap_remove(0))),
+            AmbiguityPolicy::Error => Err(SecretaryError::AmbiguousLocalExtraction {
+                field_path: field_path.to_string(),
+                candidates,
+            }),
+        }
+    }
+}
+
+/// Runs the local extractors of the fields, and of the fields of nested Tasks, over the target.
+///
+/// # Arguments
+///
+/// * `fields` - The descriptors returned by `Task::field_descriptors()`
+/// * `target` - The text the data is extracted from
+///
+/// # Returns
+///
+/// The dotted paths and values of the fields filled locally, in field order
+///
+/// # Errors
+///
+/// Returns `SecretaryError::AmbiguousLocalExtraction` for the first field whose extractor
+/// found several values under `AmbiguityPolicy::Error`.
+pub fn extract_local_fields(
+    fields: &[FieldDescriptor],
+    target: &str,
+) -> Result<Vec<(String, String)>, SecretaryError> {
+    let mut values: Vec<(String, String)> = Vec::new();
+    collect_local_fields(fields, "", target, &mut values)?;
+
+    Ok(values)
+}
+
+fn collect_local_fields(
+    fields: &[FieldDescriptor],
+    prefix: &str,
+    target: &str,
+    values: &mut Vec<(String, String)>,
+) -> Result<(), SecretaryError> {
+    for field in fields {
+        let path: String = if prefix.is_empty() {
+            field.name.clone()
+        } else {
+            format!("{}.{}", prefix, field.name)
+        };
+
+        match (&field.local_extraction, field.kind) {
+            (Some(local_extraction), FieldKind::Normal) => {
+                if let Some(value) = local_extraction.resolve(&path, target)? {
+                    values.push((path, value));
+                }
+            }
+            (_, FieldKind::Task) => collect_local_fields(&field.children, &path, target, values)?,
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the descriptors without the fields at the given dotted paths.
+///
+/// Used by `#[derive(Task)]` to render a prompt without the fields filled locally.
+pub fn descriptors_without(
+    fields: &[FieldDescriptor],
+    skipped_fields: &[String],
+) -> Vec<FieldDescriptor> {
+    if skipped_fields.is_empty() {
+        return fields.to_vec();
+    }
+
+    fields
+        .iter()
+        .filter(|field| !skipped_fields.contains(&field.name))
+        .map(|field| FieldDescriptor {
+            children: descriptors_without(
+                &field.children,
+                &nested_paths(skipped_fields, &field.name),
+            ),
+            ..field.clone()
+        })
+        .collect()
+}
+
+/// Returns the pretty-printed JSON template without the fields at the given dotted paths.
+///
+/// Used by `#[derive(Task)]` to render a prompt without the fields filled locally.
+pub fn template_without(template: &str, skipped_fields: &[String]) -> String {
+    if skipped_fields.is_empty() {
+        return template.to_string();
+    }
+    let Ok(mut value) = serde_json::from_str::<Value>(template) else {
+        return template.to_string();
+    };
+
+    for path in skipped_fields {
+        remove_field_path(&mut value, path);
+    }
+
+    serde_json::to_string_pretty(&value).unwrap_or_else(|_| template.to_string())
+}
+
+/// Returns the paths below `field`, relative to it.
+///
+/// Used by `#[derive(Task)]` to pass the skipped fields of a nested Task on to it.
+pub fn nested_paths(skipped_fields: &[String], field: &str) -> Vec<String> {
+    skipped_fields
+        .iter()
+        .filter_map(|path| path.strip_prefix(field)?.strip_prefix('.'))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Sets the values found locally in the JSON returned by the LLM.
+///
+/// The content is returned unchanged if it is not a JSON object, so that parsing it reports
+/// the response as returned.
+pub(crate) fn merge_local_values(content: String, values: &[(String, String)]) -> String {
+    if values.is_empty() {
+        return content;
+    }
+    let Ok(mut value) = serde_json::from_str::<Value>(&content) else {
+        return content;
+    };
+    if !value.is_object() {
+        return content;
+    }
+
+    set_local_values(&mut value, values);
+    value.to_string()
+}
+
+/// Sets the values found locally as strings at their paths.
+pub(crate) fn set_local_values(value: &mut Value, values: &[(String, String)]) {
+    for (field_path, field_value) in values {
+        // Paths come from the descriptors, so they always lead to an object
+        let _ = set_field_path(value, field_path, Value::String(field_value.clone()));
+    }
+}
+
+/// Drops trailing punctuation and an unbalanced closing parenthesis from a link.
+fn trim_url(url: &str) -> &str {
+    let mut url: &str = url.trim_end_matches(['.', ',', ';', ':', '!', '?', '\'', '"']);
+    while url.ends_with(')') && url.matches('(').count() < url.matches(')').count() {
+        url = url[..url.len() - 1].trim_end_matches(['.', ',', ';', ':', '!', '?']);
+    }
+
+    url
+}
+
+/// Returns the phone number in a match, or `None` if it is a date, a bare number or too short
+/// or long to be one.
+fn normalize_phone(found: &str) -> Option<String> {
+    let mut phone: &str = found.trim();
+    if phone.starts_with('(') && !phone.contains(')') {
+        phone = &phone[1..];
+    }
+    while phone.ends_with(')') && phone.matches('(').count() < phone.matches(')').count() {
+        phone = phone[..phone.len() - 1].trim_end();
+    }
+
+    let digits: usize = phone.chars().filter(char::is_ascii_digit).count();
+    let has_separators: bool = phone.contains([' ', '-', '.', '(']);
+    if !(7..=15).contains(&digits)
+        || DATE.is_match(phone)
+        || !(phone.starts_with('+') || has_separators)
+    {
+        return None;
+    }
+
+    Some(phone.to_string())
+}
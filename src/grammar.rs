@@ -0,0 +1,365 @@
+//! GBNF grammars that constrain a model's output to the JSON of a Task.
+//!
+//! llama.cpp's server samples only tokens that a GBNF grammar, sent as the `grammar` field of
+//! the request, allows, so its answer cannot depart from the grammar at all. `gbnf_grammar`
+//! writes the grammar of the JSON that `schema::json_schema` describes, with the keys of the
+//! fields in the order of the prompt's template:
+//!
+//! * Nested Tasks get a rule of their own, named after their path, e.g. `address` or
+//!   `lines-item`; the Task itself is the `root` rule.
+//! * Optional fields are always written, and accept `null`, so the keys keep their order.
+//! * A field with a `one_of` only accepts its allowed values, as written.
+//! * Integer fields only accept integers, and unsigned ones no minus sign.
+//! * Keys and allowed values are escaped twice: as JSON strings, then as GBNF literals.
+//!
+//! Providers that declare `ConstrainedDecoding::Gbnf` in their capabilities, such as
+//! `LlamaCppLLM`, are sent the grammar of the Task by `generate_data_with_options` and
+//! `async_generate_data_with_options`, in place of `response_format`, and their answers are
+//! deserialized without the coercions of the provider's `LeniencyProfile`. A grammar of the
+//! call's own, set with `RequestOptions::with_grammar`, is sent instead. Other providers
+//! ignore grammars.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::Task;
+//! use secretary::grammar::gbnf_grammar;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Task, Serialize, Deserialize)]
+//! struct Ticket {
+//!     #[task(instruction = "Extract the title")]
+//!     pub title: String,
+//!     #[task(instruction = "Rate the priority", one_of = "high, low")]
+//!     pub priority: Option<String>,
+//! }
+//!
+//! let grammar: String = gbnf_grammar(&Ticket::field_descriptors());
+//! assert!(grammar.starts_with(
+//!     r#"root ::= "{" ws "\"title\":" ws string ws "," ws "\"priority\":" ws ( ( "\"high\"" | "\"low\"" ) | null ) ws "}""#
+//! ));
+//! assert!(grammar.contains("\nnull ::= \"null\"\n"));
+//! ```
+
+use std::collections::HashSet;
+
+use crate::schema::{FieldDescriptor, FieldKind, JsonType, is_newtype, is_tuple_struct};
+
+/// The rules every grammar may refer to, in the order they are written, with the rules they
+/// refer to in turn.
+const PRIMITIVES: [(&str, &str, &[&str]); 10] = [
+    (
+        "value",
+        "object | array | string | number | boolean | null",
+        &["object", "array", "string", "number", "boolean", "null"],
+    ),
+    (
+        "object",
+        r#""{" ws ( string ws ":" ws value ws ( "," ws string ws ":" ws value ws )* )? "}""#,
+        &["string", "value", "ws"],
+    ),
+    (
+        "array",
+        r#""[" ws ( value ws ( "," ws value ws )* )? "]""#,
+        &["value", "ws"],
+    ),
+    (
+        "string",
+        r#""\"" ( [^"\\\x7F\x00-\x1F] | "\\" ( ["\\/bfnrt] | "u" [0-9a-fA-F]{4} ) )* "\"""#,
+        &[],
+    ),
+    (
+        "number",
+        r#""-"? ( "0" | [1-9] [0-9]{0,15} ) ( "." [0-9]+ )? ( [eE] [-+]? [0-9]{1,3} )?"#,
+        &[],
+    ),
+    ("integer", r#""-"? ( "0" | [1-9] [0-9]{0,15} )"#, &[]),
+    ("unsigned", r#""0" | [1-9] [0-9]{0,15}"#, &[]),
+    ("boolean", r#""true" | "false""#, &[]),
+    ("null", r#""null""#, &[]),
+    ("ws", r#"| " " | "\n" [ \t]{0,20}"#, &[]),
+];
+
+const SIGNED_INTEGERS: [&str; 6] = ["i8", "i16", "i32", "i64", "i128", "isize"];
+
+const UNSIGNED_INTEGERS: [&str; 6] = ["u8", "u16", "u32", "u64", "u128", "usize"];
+
+/// Returns the GBNF grammar of the JSON of a Task, whose start rule is `root`.
+///
+/// # Arguments
+///
+/// * `fields` - The descriptors of the fields of the Task
+pub fn gbnf_grammar(fields: &[FieldDescriptor]) -> String {
+    gbnf_grammar_without_fields(fields, &[])
+}
+
+/// Returns the GBNF grammar of the JSON of a Task without some of its fields, such as the
+/// fields the prompt leaves out.
+///
+/// # Arguments
+///
+/// * `fields` - The descriptors of the fields of the Task
+/// * `skipped_fields` - The dotted paths of the fields to leave out, e.g. `address.street`
+pub fn gbnf_grammar_without_fields(
+    fields: &[FieldDescriptor],
+    skipped_fields: &[String],
+) -> String {
+    let mut grammar: GrammarBuilder = GrammarBuilder::default();
+    let root: String = grammar.task_expression(fields, "", "", skipped_fields);
+    grammar.rules.insert(0, ("root".to_string(), root));
+
+    grammar.finish()
+}
+
+/// The rules of a grammar as they are written.
+#[derive(Default)]
+struct GrammarBuilder {
+    /// The rules of nested Tasks, in the order they were met.
+    rules: Vec<(String, String)>,
+    /// The rule names in use.
+    names: HashSet<String>,
+    /// The primitive rules referred to.
+    primitives: HashSet<&'static str>,
+}
+
+impl GrammarBuilder {
+    /// Returns the expression of the JSON of a Task.
+    ///
+    /// # Arguments
+    ///
+    /// * `fields` - The descriptors of its fields
+    /// * `rule` - The name of its rule, the prefix of the names of nested rules
+    /// * `path` - Its dotted path, which `skipped_fields` are matched against
+    /// * `skipped_fields` - The dotted paths of the fields to leave out
+    fn task_expression(
+        &mut self,
+        fields: &[FieldDescriptor],
+        rule: &str,
+        path: &str,
+        skipped_fields: &[String],
+    ) -> String {
+        // Newtypes and tuple structs are serialized as their field and as arrays
+        if is_newtype(fields) {
+            return self.field_expression(&fields[0], rule, path, skipped_fields);
+        }
+        if is_tuple_struct(fields) {
+            self.primitive("ws");
+            let items: Vec<String> = fields
+                .iter()
+                .map(|field| {
+                    let name: String = join(rule, &field.name, '-');
+                    let path: String = join(path, &field.name, '.');
+                    self.field_expression(field, &name, &path, skipped_fields)
+                })
+                .collect();
+            return format!(r#""[" ws {} ws "]""#, items.join(r#" ws "," ws "#));
+        }
+
+        self.primitive("ws");
+        let members: Vec<String> = fields
+            .iter()
+            .filter_map(|field| {
+                let path: String = join(path, &field.name, '.');
+                if skipped_fields.contains(&path) {
+                    return None;
+                }
+
+                let name: String = join(rule, &field.name, '-');
+                let key: String = serde_json::to_string(&field.key()).unwrap_or_default();
+                let value: String = self.field_expression(field, &name, &path, skipped_fields);
+                Some(format!("{} ws {} ws", literal(&format!("{}:", key)), value))
+            })
+            .collect();
+
+        if members.is_empty() {
+            return r#""{" ws "}""#.to_string();
+        }
+        format!(r#""{{" ws {} "}}""#, members.join(r#" "," ws "#))
+    }
+
+    /// Returns the expression of the value of a field, `null` included if it is optional.
+    fn field_expression(
+        &mut self,
+        field: &FieldDescriptor,
+        rule: &str,
+        path: &str,
+        skipped_fields: &[String],
+    ) -> String {
+        let expression: String = match field.kind {
+            FieldKind::Normal if field.json_type == JsonType::Array => {
+                let item: String = self.plain_expression(field, field.item_type);
+                self.array_expression(&item)
+            }
+            FieldKind::Normal => self.plain_expression(field, field.json_type),
+            FieldKind::Task | FieldKind::OptionTask => {
+                self.task_rule(&field.children, rule, path, skipped_fields)
+            }
+            FieldKind::VecTask => {
+                let item: String =
+                    self.task_rule(&field.children, &format!("{}-item", rule), "", &[]);
+                self.array_expression(&item)
+            }
+            FieldKind::HashMapTask | FieldKind::BTreeMapTask => {
+                let value: String =
+                    self.task_rule(&field.children, &format!("{}-value", rule), "", &[]);
+                self.primitive("string");
+                format!(
+                    r#""{{" ws ( string ws ":" ws {0} ws ( "," ws string ws ":" ws {0} ws )* )? "}}""#,
+                    value
+                )
+            }
+        };
+
+        if field.optional {
+            self.primitive("null");
+            return format!("( {} | null )", expression);
+        }
+        expression
+    }
+
+    /// Returns the expression of a value of a plain JSON type, the allowed values of a
+    /// `one_of` field in place of any string.
+    fn plain_expression(&mut self, field: &FieldDescriptor, json_type: JsonType) -> String {
+        match json_type {
+            JsonType::String if !field.one_of.is_empty() => {
+                let values: Vec<String> = field
+                    .one_of
+                    .iter()
+                    .map(|value| literal(&serde_json::to_string(value).unwrap_or_default()))
+                    .collect();
+                format!("( {} )", values.join(" | "))
+            }
+            JsonType::String => self.primitive("string"),
+            JsonType::Number => match innermost_type(&field.rust_type) {
+                name if SIGNED_INTEGERS.contains(&name) => self.primitive("integer"),
+                name if UNSIGNED_INTEGERS.contains(&name) => self.primitive("unsigned"),
+                _ => self.primitive("number"),
+            },
+            JsonType::Boolean => self.primitive("boolean"),
+            JsonType::Array => self.primitive("array"),
+            JsonType::Object => self.primitive("object"),
+            JsonType::Null => self.primitive("null"),
+            JsonType::Any => self.primitive("value"),
+        }
+    }
+
+    /// Returns the expression of an array of items.
+    fn array_expression(&mut self, item: &str) -> String {
+        self.primitive("ws");
+        format!(r#""[" ws ( {0} ws ( "," ws {0} ws )* )? "]""#, item)
+    }
+
+    /// Adds the rule of a nested Task and returns its name.
+    fn task_rule(
+        &mut self,
+        fields: &[FieldDescriptor],
+        rule: &str,
+        path: &str,
+        skipped_fields: &[String],
+    ) -> String {
+        let name: String = self.unique_name(rule);
+        // Reserved before the fields are visited, so that nested rules come after it
+        let position: usize = self.rules.len();
+        self.rules.push((name.clone(), String::new()));
+        self.rules[position].1 = self.task_expression(fields, &name, path, skipped_fields);
+
+        name
+    }
+
+    /// Returns a rule name for a path of keys that is not in use yet.
+    fn unique_name(&mut self, rule: &str) -> String {
+        let mut base: String = String::new();
+        for character in rule.chars() {
+            if character.is_ascii_alphanumeric() {
+                base.push(character.to_ascii_lowercase());
+            } else if !base.is_empty() && !base.ends_with('-') {
+                base.push('-');
+            }
+        }
+        let base: String = match base.trim_end_matches('-') {
+            "" => "task".to_string(),
+            base => base.to_string(),
+        };
+
+        let mut name: String = base.clone();
+        let mut suffix: usize = 2;
+        while name == "root"
+            || PRIMITIVES
+                .iter()
+                .any(|(primitive, _, _)| *primitive == name)
+            || self.names.contains(&name)
+        {
+            name = format!("{}-{}", base, suffix);
+            suffix += 1;
+        }
+        self.names.insert(name.clone());
+
+        name
+    }
+
+    /// Marks a primitive rule, and the rules it refers to, as used, and returns its name.
+    fn primitive(&mut self, name: &'static str) -> String {
+        if self.primitives.insert(name)
+            && let Some((_, _, references)) = PRIMITIVES.iter().find(|(rule, _, _)| *rule == name)
+        {
+            for reference in references.iter() {
+                self.primitive(reference);
+            }
+        }
+
+        name.to_string()
+    }
+
+    /// Writes the rules, the primitive ones last.
+    fn finish(self) -> String {
+        let mut grammar: String = String::new();
+        for (name, expression) in &self.rules {
+            grammar.push_str(&format!("{} ::= {}\n", name, expression));
+        }
+        for (name, expression, _) in PRIMITIVES {
+            if self.primitives.contains(name) {
+                grammar.push_str(&format!("{} ::= {}\n", name, expression));
+            }
+        }
+
+        grammar
+    }
+}
+
+/// Returns a GBNF string literal that matches the text exactly.
+fn literal(text: &str) -> String {
+    let mut literal: String = String::from('"');
+    for character in text.chars() {
+        match character {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            '\r' => literal.push_str("\\r"),
+            '\t' => literal.push_str("\\t"),
+            character if character.is_control() => {
+                literal.push_str(&format!("\\x{:02X}", character as u32))
+            }
+            character => literal.push(character),
+        }
+    }
+    literal.push('"');
+
+    literal
+}
+
+/// Returns the innermost type of a Rust type without its path, e.g. `u32` for
+/// `Option<Vec<std::primitive::u32>>`.
+fn innermost_type(rust_type: &str) -> &str {
+    let inner: &str = rust_type.rsplit('<').next().unwrap_or(rust_type);
+    let inner: &str = inner.split(['>', ',']).next().unwrap_or(inner);
+
+    inner.rsplit("::").next().unwrap_or(inner)
+}
+
+fn join(prefix: &str, name: &str, separator: char) -> String {
+    if prefix.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}{}{}", prefix, separator, name)
+    }
+}
@@ -0,0 +1,260 @@
+//! Field values known before the extraction.
+//!
+//! When some values are known from elsewhere, such as the vendor of an invoice from the
+//! upload it came with, pass them as `Hints` instead of describing them in an additional
+//! instruction. `#[derive(Task)]` generates a builder with one method per field:
+//! `Invoice::hints().vendor("Acme").build_hints()`. Pass the hints with
+//! `RequestOptions::with_hints`:
+//!
+//! - The prompt lists them after the additional instructions as "Known values (do not
+//!   change these): ...", so the other fields can be read in their light.
+//! - The hinted values replace whatever the model returned for their fields. A model value
+//!   that differs is reported in `GenerationMetadata::hint_conflicts` by adaptive generation.
+//! - Distributed generation sends no request for a hinted field, or for the fields of a
+//!   hinted nested Task.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::Task;
+//! use secretary::hints::Hints;
+//! use secretary::request::RequestOptions;
+//! use serde::{Deserialize, Serialize};
+//! use serde_json::json;
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! struct Address {
+//!     #[task(instruction = "Extract the city")]
+//!     pub city: String,
+//! }
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! struct Invoice {
+//!     #[task(instruction = "Extract the vendor")]
+//!     pub vendor: String,
+//!     #[task(instruction = "Extract the total amount")]
+//!     pub total: f64,
+//!     pub address: Address,
+//! }
+//!
+//! let hints: Hints<Invoice> = Invoice::hints()
+//!     .vendor("Acme")
+//!     .address(Address { city: "Paris".to_string() })
+//!     .build_hints();
+//! assert_eq!(hints.values()["vendor"], json!("Acme"));
+//! assert_eq!(hints.values()["address"], json!({"city": "Paris"}));
+//!
+//! // Paths into nested Tasks can be hinted without the builder
+//! let hints: Hints<Invoice> = Hints::new().with_value("address.city", "Lyon");
+//! let options = RequestOptions::default().with_hints(hints);
+//! assert_eq!(options.hints["address.city"], json!("Lyon"));
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::marker::PhantomData;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    distributed::FieldPrompt,
+    message::Message,
+    utilities::{get_field_path, set_field_path},
+};
+
+/// Known values of the fields of `T`, by dotted field path.
+///
+/// Built with the `hints()` builder that `#[derive(Task)]` generates, or with `with_value`.
+pub struct Hints<T> {
+    values: BTreeMap<String, Value>,
+    task: PhantomData<fn() -> T>,
+}
+
+impl<T> Hints<T> {
+    /// Creates empty hints.
+    pub fn new() -> Self {
+        Self {
+            values: BTreeMap::new(),
+            task: PhantomData,
+        }
+    }
+
+    /// Sets the known value of a field.
+    ///
+    /// A value that cannot be serialized to JSON, such as a map with non-string keys, is
+    /// left out.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The dotted path of the field, as in `FieldPrompt::field_path`, e.g.
+    ///   `address.city`
+    /// * `value` - The value of the field
+    pub fn with_value(mut self, path: &str, value: impl Serialize) -> Self {
+        if let Ok(value) = serde_json::to_value(value) {
+            self.values.insert(path.to_string(), value);
+        }
+        self
+    }
+
+    /// Returns the known values by field path.
+    pub fn values(&self) -> &BTreeMap<String, Value> {
+        &self.values
+    }
+
+    /// Returns the known values by field path, consuming the hints.
+    pub fn into_values(self) -> BTreeMap<String, Value> {
+        self.values
+    }
+
+    /// Returns whether no value is known.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+impl<T> Default for Hints<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Clone for Hints<T> {
+    fn clone(&self) -> Self {
+        Self {
+            values: self.values.clone(),
+            task: PhantomData,
+        }
+    }
+}
+
+impl<T> PartialEq for Hints<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.values == other.values
+    }
+}
+
+impl<T> fmt::Debug for Hints<T> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.debug_tuple("Hints").field(&self.values).finish()
+    }
+}
+
+/// A hinted field for which the model returned another value, reported in
+/// `GenerationMetadata::hint_conflicts`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HintConflict {
+    /// The dotted path of the field.
+    pub field_path: String,
+    /// The known value, which the result has.
+    pub hinted: Value,
+    /// The value the model returned.
+    pub returned: Value,
+}
+
+/// Returns the additional instruction that lists the known values, `None` without any.
+///
+/// # Arguments
+///
+/// * `hints` - The known values by field path
+pub fn known_values_instruction(hints: &BTreeMap<String, Value>) -> Option<String> {
+    if hints.is_empty() {
+        return None;
+    }
+
+    let values: Vec<String> = hints
+        .iter()
+        .map(|(path, value)| format!("{} = {}", path, value))
+        .collect();
+
+    Some(format!(
+        "Known values (do not change these): {}",
+        values.join("; ")
+    ))
+}
+
+/// Returns whether a field path is hinted, itself or through a nested Task that contains it.
+pub(crate) fn is_hinted(hints: &BTreeMap<String, Value>, field_path: &str) -> bool {
+    hints.keys().any(|path| {
+        field_path
+            .strip_prefix(path.as_str())
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with(['.', '[']))
+    })
+}
+
+/// Drops the field requests of hinted fields, and the hinted members of groups.
+pub(crate) fn without_hinted_fields(
+    requests: Vec<(FieldPrompt, Message)>,
+    hints: &BTreeMap<String, Value>,
+) -> Vec<(FieldPrompt, Message)> {
+    if hints.is_empty() {
+        return requests;
+    }
+
+    requests
+        .into_iter()
+        .filter_map(|(mut field_prompt, message)| {
+            field_prompt
+                .retain_fields(|field_prompt| !is_hinted(hints, &field_prompt.field_path))
+                .then_some((field_prompt, message))
+        })
+        .collect()
+}
+
+/// Sets the hinted values in the JSON returned by the LLM, returning the fields for which
+/// it had another value.
+///
+/// The content is returned unchanged if it is not a JSON object, so that parsing it reports
+/// the response as returned.
+pub(crate) fn merge_hint_values(
+    content: String,
+    hints: &BTreeMap<String, Value>,
+) -> (String, Vec<HintConflict>) {
+    if hints.is_empty() {
+        return (content, Vec::new());
+    }
+    let Ok(mut value) = serde_json::from_str::<Value>(&content) else {
+        return (content, Vec::new());
+    };
+    if !value.is_object() {
+        return (content, Vec::new());
+    }
+
+    let conflicts: Vec<HintConflict> = set_hint_values(&mut value, hints);
+    (value.to_string(), conflicts)
+}
+
+/// Sets the hinted values at their paths, returning the fields that had another value.
+///
+/// Fields that were absent or `null` are not conflicts.
+pub(crate) fn set_hint_values(
+    value: &mut Value,
+    hints: &BTreeMap<String, Value>,
+) -> Vec<HintConflict> {
+    let mut conflicts: Vec<HintConflict> = Vec::new();
+    for (field_path, hinted) in hints {
+        if let Some(returned) = get_field_path(value, field_path)
+            && !returned.is_null()
+            && !same_value(returned, hinted)
+        {
+            conflicts.push(HintConflict {
+                field_path: field_path.clone(),
+                hinted: hinted.clone(),
+                returned: returned.clone(),
+            });
+        }
+        // A path that leads through a value that is not an object is left as returned
+        let _ = set_field_path(value, field_path, hinted.clone());
+    }
+
+    conflicts
+}
+
+/// Compares two values, taking numbers that are equal as floats as the same, e.g. `2` and
+/// `2.0`.
+fn same_value(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::Number(left), Value::Number(right)) => left.as_f64() == right.as_f64(),
+        _ => left == right,
+    }
+}
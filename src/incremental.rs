@@ -0,0 +1,105 @@
+//! Incremental re-extraction of an edited document.
+//!
+//! Documents are often re-processed after small edits. `GenerateData::regenerate_data` takes
+//! the previous and the current version of the target together with the result extracted
+//! from the previous one, and compares the versions line by line with
+//! `textdiff::diff_lines`. What happens next depends on how much changed:
+//!
+//! - Nothing: the previous result is returned without a request.
+//! - At most `RegenerateOptions::max_changed_ratio` of the lines: the model gets the previous
+//!   result and only the changed excerpts, with `RegenerateOptions::context_lines` unchanged
+//!   lines around each, and returns the updated result.
+//! - More: the current version is extracted in full like `generate_data`.
+//!
+//! `GenerationMetadata::regeneration_path` tells which path was taken and
+//! `GenerationMetadata::changed_ratio` how much of the text changed.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::incremental::{RegenerateOptions, RegenerationPath};
+//! use secretary::textdiff::diff_lines;
+//!
+//! let options = RegenerateOptions::default().with_max_changed_ratio(0.3);
+//!
+//! let old = "Invoice INV-7\nVendor: Acme\nTotal: 120.50 EUR\nThank you";
+//! let edited = "Invoice INV-7\nVendor: Acme\nTotal: 99.00 EUR\nThank you";
+//! let rewritten = "Credit note CN-2\nRefund of 20 EUR";
+//!
+//! assert_eq!(options.choose_path(&diff_lines(old, old)), RegenerationPath::Unchanged);
+//! assert_eq!(options.choose_path(&diff_lines(old, edited)), RegenerationPath::Incremental);
+//! assert_eq!(options.choose_path(&diff_lines(old, rewritten)), RegenerationPath::Full);
+//! ```
+
+use serde::Serialize;
+
+use crate::textdiff::TextDiff;
+
+/// The request to update the previous result, followed by it and the changed excerpts.
+const UPDATE_INSTRUCTION: &str = "The fields were extracted before from a previous version of the document, with the result below. The document has been edited since. Only the changed excerpts of the current version follow, with some unchanged lines around each change: lines starting with \"- \" were removed, lines starting with \"+ \" were added and lines starting with two spaces are unchanged. Return the complete updated JSON object: change the values affected by the edits and keep every other value of the previous result as it is.";
+
+/// How `GenerateData::regenerate_data` obtained its result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum RegenerationPath {
+    /// The text did not change, and the previous result was returned without a request.
+    Unchanged,
+    /// The model updated the previous result from the changed excerpts.
+    Incremental,
+    /// The changes were too large, and the current version was extracted in full.
+    Full,
+}
+
+/// Settings for `GenerateData::regenerate_data`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RegenerateOptions {
+    /// The largest share of changed lines, see `TextDiff::changed_ratio`, that is extracted
+    /// incrementally. Larger changes are extracted in full.
+    pub max_changed_ratio: f64,
+    /// The number of unchanged lines sent before and after each change.
+    pub context_lines: usize,
+}
+
+impl Default for RegenerateOptions {
+    fn default() -> Self {
+        Self {
+            max_changed_ratio: 0.2,
+            context_lines: 3,
+        }
+    }
+}
+
+impl RegenerateOptions {
+    /// Sets the largest share of changed lines that is extracted incrementally.
+    pub fn with_max_changed_ratio(mut self, max_changed_ratio: f64) -> Self {
+        self.max_changed_ratio = max_changed_ratio;
+        self
+    }
+
+    /// Sets the number of unchanged lines sent around each change.
+    pub fn with_context_lines(mut self, context_lines: usize) -> Self {
+        self.context_lines = context_lines;
+        self
+    }
+
+    /// Returns how a target with these changes is re-extracted.
+    pub fn choose_path(&self, diff: &TextDiff) -> RegenerationPath {
+        if diff.is_unchanged() {
+            RegenerationPath::Unchanged
+        } else if diff.changed_ratio() <= self.max_changed_ratio {
+            RegenerationPath::Incremental
+        } else {
+            RegenerationPath::Full
+        }
+    }
+}
+
+/// Formats the target of an incremental request from the previous result and the changed
+/// excerpts, wrapped by the guardrail when one is set.
+pub(crate) fn make_incremental_target<T: Serialize>(previous: &T, excerpts: &str) -> String {
+    format!(
+        "{}\n\nPrevious result:\n{}\n\nChanged excerpts:\n{}",
+        UPDATE_INSTRUCTION,
+        serde_json::to_string_pretty(previous).unwrap_or_default(),
+        excerpts
+    )
+}
@@ -0,0 +1,235 @@
+//! Reading targets from readers and files.
+//!
+//! `read_text` reads a target into a `String` the way the extraction methods expect it:
+//! byte order marks are stripped, and control characters other than newlines, carriage
+//! returns and tabs are removed, since some providers reject them. Inputs larger than
+//! `InputOptions::max_bytes` are refused with `SecretaryError::InputTooLarge` before they are
+//! decoded.
+//!
+//! Without the `encoding` feature the bytes must be UTF-8. With it, UTF-16 is recognized by
+//! its byte order mark and bytes that are not valid UTF-8 are decoded as Windows-1252, the
+//! usual encoding of text exported from older Windows software.
+//!
+//! `GenerateData::generate_data_from_reader`, `GenerateData::generate_data_from_path` and
+//! `AsyncGenerateData::async_generate_data_from_reader` read their target this way. Read
+//! failures are reported as `SecretaryError::InputRead` and undecodable bytes as
+//! `SecretaryError::InputDecode`, so they can be told apart from extraction failures.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::SecretaryError;
+//! use secretary::input::{InputOptions, read_path, read_text};
+//!
+//! // Plain UTF-8
+//! let text = read_text("Invoice №42\r\n".as_bytes(), &InputOptions::default()).unwrap();
+//! assert_eq!(text, "Invoice №42\r\n");
+//!
+//! // UTF-8 with a byte order mark and a stray control character, read from a file
+//! let path = std::env::temp_dir().join(format!("secretary-input-{}.txt", std::process::id()));
+//! std::fs::write(&path, b"\xEF\xBB\xBFTotal:\x07 12 EUR").unwrap();
+//! assert_eq!(read_path(&path, &InputOptions::default()).unwrap(), "Total: 12 EUR");
+//!
+//! // Files over the size limit are refused
+//! let options = InputOptions::default().with_max_bytes(8);
+//! match read_path(&path, &options) {
+//!     Err(SecretaryError::InputTooLarge { limit }) => assert_eq!(limit, 8),
+//!     other => panic!("unexpected result: {:?}", other),
+//! }
+//! std::fs::remove_file(&path).unwrap();
+//!
+//! // Missing files are read failures
+//! let missing = std::env::temp_dir().join("secretary-input-missing.txt");
+//! assert!(matches!(
+//!     read_path(&missing, &InputOptions::default()),
+//!     Err(SecretaryError::InputRead(_))
+//! ));
+//! ```
+//!
+//! Windows-1252 needs the `encoding` feature; without it the same bytes are a decode failure:
+//!
+//! ```rust
+//! use secretary::SecretaryError;
+//! use secretary::input::{InputOptions, read_text};
+//!
+//! // "Café – 5 €" as exported by an older Windows application
+//! let bytes: &[u8] = b"Caf\xE9 \x96 5 \x80";
+//! let result = read_text(bytes, &InputOptions::default());
+//!
+//! # #[cfg(feature = "encoding")]
+//! assert_eq!(result.unwrap(), "Café – 5 €");
+//! # #[cfg(not(feature = "encoding"))]
+//! assert!(matches!(result, Err(SecretaryError::InputDecode { .. })));
+//! ```
+//!
+//! The extraction entry points read the target first and then extract as usual:
+//!
+//! ```no_run
+//! use secretary::Task;
+//! use secretary::llm_providers::openai::OpenAILLM;
+//! use secretary::traits::{AsyncGenerateData, GenerateData};
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug, PartialEq)]
+//! struct Invoice {
+//!     #[task(instruction = "Extract the invoice total as a float")]
+//!     pub total: f64,
+//! }
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+//! let llm = OpenAILLM::new("https://api.openai.com/v1", "your-api-key", "gpt-4o-mini")?;
+//!
+//! let invoice: Invoice = llm.generate_data_from_path(&Invoice::new(), "invoice.txt", &vec![])?;
+//! println!("{:?}", invoice);
+//!
+//! let reader = futures::io::Cursor::new(b"Total: 12.50 EUR".to_vec());
+//! let invoice: Invoice = tokio::runtime::Runtime::new()?.block_on(async {
+//!     llm.async_generate_data_from_reader(&Invoice::new(), reader, &vec![]).await
+//! })?;
+//! println!("{:?}", invoice);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::io::Read;
+use std::path::Path;
+
+use futures::io::{AsyncRead, AsyncReadExt};
+
+use crate::SecretaryError;
+
+/// The default `InputOptions::max_bytes`, 10 MiB.
+pub const DEFAULT_MAX_INPUT_BYTES: usize = 10 * 1024 * 1024;
+
+/// The UTF-8 encoding of the byte order mark.
+#[cfg(not(feature = "encoding"))]
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Settings for reading a target from a reader or file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputOptions {
+    /// The largest input accepted, in bytes before decoding.
+    pub max_bytes: usize,
+}
+
+impl Default for InputOptions {
+    fn default() -> Self {
+        Self {
+            max_bytes: DEFAULT_MAX_INPUT_BYTES,
+        }
+    }
+}
+
+impl InputOptions {
+    /// Sets the largest input accepted, in bytes before decoding.
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+}
+
+/// Reads a target from a reader, see the module documentation.
+///
+/// At most `max_bytes + 1` bytes are read, so an oversized input is detected without reading
+/// all of it.
+///
+/// # Errors
+///
+/// Returns `SecretaryError::InputRead` if the reader fails, `SecretaryError::InputTooLarge`
+/// if the input is over the limit and `SecretaryError::InputDecode` if it is not text.
+pub fn read_text<R: Read>(reader: R, options: &InputOptions) -> Result<String, SecretaryError> {
+    let mut bytes: Vec<u8> = Vec::new();
+    reader
+        .take(options.max_bytes as u64 + 1)
+        .read_to_end(&mut bytes)
+        .map_err(SecretaryError::InputRead)?;
+
+    decode_text(&bytes, options)
+}
+
+/// Reads a target from an async reader, see `read_text`.
+///
+/// # Errors
+///
+/// Returns the same errors as `read_text`.
+pub async fn async_read_text<R: AsyncRead + Unpin>(
+    reader: R,
+    options: &InputOptions,
+) -> Result<String, SecretaryError> {
+    let mut bytes: Vec<u8> = Vec::new();
+    reader
+        .take(options.max_bytes as u64 + 1)
+        .read_to_end(&mut bytes)
+        .await
+        .map_err(SecretaryError::InputRead)?;
+
+    decode_text(&bytes, options)
+}
+
+/// Reads a target from a file, see `read_text`.
+///
+/// # Errors
+///
+/// Returns the same errors as `read_text`, with `SecretaryError::InputRead` if the file
+/// cannot be opened.
+pub fn read_path<P: AsRef<Path>>(
+    path: P,
+    options: &InputOptions,
+) -> Result<String, SecretaryError> {
+    let file = std::fs::File::open(path).map_err(SecretaryError::InputRead)?;
+    read_text(file, options)
+}
+
+/// Decodes the bytes of a target and removes the characters providers may reject.
+fn decode_text(bytes: &[u8], options: &InputOptions) -> Result<String, SecretaryError> {
+    if bytes.len() > options.max_bytes {
+        return Err(SecretaryError::InputTooLarge {
+            limit: options.max_bytes,
+        });
+    }
+
+    let text: String = decode_bytes(bytes)?;
+
+    Ok(text
+        .trim_start_matches('\u{FEFF}')
+        .chars()
+        .filter(|character| !character.is_control() || matches!(character, '\n' | '\r' | '\t'))
+        .collect())
+}
+
+#[cfg(feature = "encoding")]
+fn decode_bytes(bytes: &[u8]) -> Result<String, SecretaryError> {
+    if let Some((encoding, bom_length)) = encoding_rs::Encoding::for_bom(bytes) {
+        return match encoding
+            .decode_without_bom_handling_and_without_replacement(&bytes[bom_length..])
+        {
+            Some(text) => Ok(text.into_owned()),
+            None => Err(SecretaryError::InputDecode {
+                message: format!("the input is not valid {}", encoding.name()),
+            }),
+        };
+    }
+
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Ok(text.to_string()),
+        Err(_) => {
+            let (text, _) = encoding_rs::WINDOWS_1252.decode_without_bom_handling(bytes);
+            Ok(text.into_owned())
+        }
+    }
+}
+
+#[cfg(not(feature = "encoding"))]
+fn decode_bytes(bytes: &[u8]) -> Result<String, SecretaryError> {
+    let bytes: &[u8] = bytes.strip_prefix(UTF8_BOM).unwrap_or(bytes);
+
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Ok(text.to_string()),
+        Err(error) => Err(SecretaryError::InputDecode {
+            message: format!(
+                "the input is not valid UTF-8 ({}); enable the `encoding` feature to decode other encodings",
+                error
+            ),
+        }),
+    }
+}
@@ -0,0 +1,282 @@
+//! Additional instructions gathered from several layers.
+//!
+//! Every generate method accepts its additional instructions as `impl Into<InstructionSet>`.
+//! A `Vec<String>`, a `&Vec<String>` and a slice of `&str` convert without losing or
+//! reordering anything, so a plain list of strings still works. Use an `InstructionSet`
+//! when instructions come from several places, such as a global policy, the rules of a
+//! tenant and the hints of a single call. Each entry records its text, where it came from
+//! and a priority.
+//!
+//! `InstructionSet::merge` combines two sets. It orders the entries by descending priority,
+//! keeping the insertion order among equal priorities, and drops entries that repeat an
+//! earlier one once whitespace is normalized. The set renders with the same bullet list as
+//! `format_additional_instructions`, so merged instructions produce the prompts a plain
+//! list would.
+//!
+//! `InstructionSet::conflicts` finds pairs where one entry says "always X" and another
+//! "never X". Adaptive generation reports each pair as
+//! `GenerationWarning::ConflictingInstructions`. The check is a heuristic on the wording,
+//! not an understanding of the instructions.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::instructions::InstructionSet;
+//!
+//! let policy = InstructionSet::new()
+//!     .with_instruction("Always include the currency.", "policy", 10)
+//!     .with_instruction("Use ISO 8601 dates.", "policy", 10);
+//! let tenant = InstructionSet::new()
+//!     .with_instruction("Prefer the  billing address.", "tenant", 5)
+//!     .with_instruction("Use ISO 8601   dates.", "tenant", 5);
+//! let call = InstructionSet::from(&["Never include the currency.", "Prefer the billing address."][..]);
+//!
+//! let merged = policy.merge(tenant).merge(call);
+//! assert_eq!(
+//!     merged.to_vec(),
+//!     vec![
+//!         "Always include the currency.",
+//!         "Use ISO 8601 dates.",
+//!         "Prefer the  billing address.",
+//!         "Never include the currency.",
+//!     ]
+//! );
+//! assert_eq!(merged.entries()[2].source, "tenant");
+//! assert!(merged.render().starts_with("\nAdditional instructions:\n- Always include the currency.\n"));
+//!
+//! let conflicts = merged.conflicts();
+//! assert_eq!(conflicts.len(), 1);
+//! assert_eq!(conflicts[0].0.text, "Always include the currency.");
+//! assert_eq!(conflicts[0].1.text, "Never include the currency.");
+//! ```
+
+use serde::Serialize;
+
+use crate::utilities::format_additional_instructions;
+
+/// The source recorded for instructions converted from a list of strings.
+pub const DEFAULT_SOURCE: &str = "additional_instructions";
+
+/// One additional instruction.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Instruction {
+    /// The text added to the prompt.
+    pub text: String,
+    /// Where the instruction came from, such as "policy" or "tenant".
+    pub source: String,
+    /// The precedence of the instruction; higher priorities come first after a merge.
+    pub priority: i32,
+}
+
+impl Instruction {
+    /// Creates an instruction.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text added to the prompt
+    /// * `source` - Where the instruction came from
+    /// * `priority` - The precedence of the instruction
+    pub fn new(text: impl Into<String>, source: impl Into<String>, priority: i32) -> Self {
+        Self {
+            text: text.into(),
+            source: source.into(),
+            priority,
+        }
+    }
+}
+
+/// An ordered list of additional instructions.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct InstructionSet {
+    entries: Vec<Instruction>,
+}
+
+impl InstructionSet {
+    /// Creates an empty set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends an instruction.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text added to the prompt
+    /// * `source` - Where the instruction came from
+    /// * `priority` - The precedence of the instruction
+    pub fn with_instruction(
+        mut self,
+        text: impl Into<String>,
+        source: impl Into<String>,
+        priority: i32,
+    ) -> Self {
+        self.push(Instruction::new(text, source, priority));
+        self
+    }
+
+    /// Appends an instruction.
+    pub fn push(&mut self, instruction: Instruction) {
+        self.entries.push(instruction);
+    }
+
+    /// Combines this set with another one.
+    ///
+    /// The entries of both sets are ordered by descending priority, entries of equal
+    /// priority keep their order with the entries of `self` first. An entry whose text
+    /// equals an earlier one, ignoring leading, trailing and repeated whitespace, is
+    /// dropped.
+    pub fn merge(mut self, other: impl Into<InstructionSet>) -> Self {
+        self.entries.extend(other.into().entries);
+        self.entries
+            .sort_by_key(|instruction| std::cmp::Reverse(instruction.priority));
+
+        let mut seen: Vec<String> = Vec::new();
+        self.entries.retain(|instruction| {
+            let normalized: String = normalize_whitespace(&instruction.text);
+            if seen.contains(&normalized) {
+                false
+            } else {
+                seen.push(normalized);
+                true
+            }
+        });
+
+        self
+    }
+
+    /// Returns the instructions in order.
+    pub fn entries(&self) -> &[Instruction] {
+        &self.entries
+    }
+
+    /// Returns the texts of the instructions in order.
+    pub fn to_vec(&self) -> Vec<String> {
+        self.entries
+            .iter()
+            .map(|instruction| instruction.text.clone())
+            .collect()
+    }
+
+    /// Returns the texts of the instructions in order, without copying them.
+    pub fn into_vec(self) -> Vec<String> {
+        self.entries
+            .into_iter()
+            .map(|instruction| instruction.text)
+            .collect()
+    }
+
+    /// Renders the instructions as the prompt section `format_additional_instructions`
+    /// produces, or an empty string when there are none.
+    pub fn render(&self) -> String {
+        format_additional_instructions(&self.to_vec())
+    }
+
+    /// Returns the pairs of instructions where the first says "always X" and the second
+    /// "never X", in the order of the "always" instructions.
+    ///
+    /// X is the rest of the clause after the keyword, compared without case, punctuation or
+    /// repeated whitespace.
+    pub fn conflicts(&self) -> Vec<(&Instruction, &Instruction)> {
+        let mut conflicts: Vec<(&Instruction, &Instruction)> = Vec::new();
+        for always in &self.entries {
+            let required: Vec<String> = clauses_after(&always.text, "always");
+            for never in &self.entries {
+                let forbidden: Vec<String> = clauses_after(&never.text, "never");
+                if required.iter().any(|clause| forbidden.contains(clause)) {
+                    conflicts.push((always, never));
+                }
+            }
+        }
+
+        conflicts
+    }
+
+    /// Returns the number of instructions.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether the set has no instructions.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl From<Vec<String>> for InstructionSet {
+    fn from(texts: Vec<String>) -> Self {
+        Self {
+            entries: texts
+                .into_iter()
+                .map(|text| Instruction::new(text, DEFAULT_SOURCE, 0))
+                .collect(),
+        }
+    }
+}
+
+impl From<&Vec<String>> for InstructionSet {
+    fn from(texts: &Vec<String>) -> Self {
+        Self::from(texts.clone())
+    }
+}
+
+impl From<&[&str]> for InstructionSet {
+    fn from(texts: &[&str]) -> Self {
+        Self {
+            entries: texts
+                .iter()
+                .map(|text| Instruction::new(*text, DEFAULT_SOURCE, 0))
+                .collect(),
+        }
+    }
+}
+
+impl<const N: usize> From<&[&str; N]> for InstructionSet {
+    fn from(texts: &[&str; N]) -> Self {
+        Self::from(&texts[..])
+    }
+}
+
+impl From<&InstructionSet> for InstructionSet {
+    fn from(instructions: &InstructionSet) -> Self {
+        instructions.clone()
+    }
+}
+
+/// Trims the text and collapses every run of whitespace into a single space.
+fn normalize_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+/// Returns the normalized clauses that follow each occurrence of the keyword in the text.
+///
+/// A clause ends at the next punctuation mark that ends a sentence or a list item.
+fn clauses_after(text: &str, keyword: &str) -> Vec<String> {
+    let words: Vec<String> = text
+        .split_whitespace()
+        .map(|word| word.to_lowercase())
+        .collect();
+
+    let mut clauses: Vec<String> = Vec::new();
+    for (index, word) in words.iter().enumerate() {
+        if word.trim_matches(|c: char| !c.is_alphanumeric()) != keyword {
+            continue;
+        }
+
+        let mut clause: Vec<&str> = Vec::new();
+        for word in &words[index + 1..] {
+            let ends_clause: bool = word.ends_with(['.', ',', ';', ':', '!', '?']);
+            let trimmed: &str = word.trim_matches(|c: char| !c.is_alphanumeric());
+            if !trimmed.is_empty() {
+                clause.push(trimmed);
+            }
+            if ends_clause {
+                break;
+            }
+        }
+        if !clause.is_empty() {
+            clauses.push(clause.join(" "));
+        }
+    }
+
+    clauses
+}
@@ -0,0 +1,307 @@
+//! Language requirements for extracted text.
+//!
+//! `#[task(output_language = "en")]` on a `String` field, or on a collection of them, asks for
+//! the value in English, translated if the target is written in another language, and
+//! `output_language = "source"` asks for the language of the target, untranslated. On the
+//! struct, `output_language` applies to every text field without one of its own. The
+//! requirement is appended to the field's instruction, so the single request and the
+//! per-field requests of distributed generation state it alike, and it is recorded in
+//! `FieldDescriptor::output_language`.
+//!
+//! With the `language-detection` feature, `generate_data_adaptive` detects the language of
+//! every such value after the extraction and lists the values written in another language in
+//! `GenerationMetadata::language_violations`, so they can be requested again. Detection is a
+//! heuristic that needs a few words to be reliable: short values such as labels are not
+//! checked.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::Task;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! #[task(output_language = "en")]
+//! struct Listing {
+//!     #[task(instruction = "Categorize the product")]
+//!     pub category: String,
+//!     #[task(instruction = "Extract the seller's address", output_language = "source")]
+//!     pub address: String,
+//!     #[task(instruction = "Extract the price")]
+//!     pub price: f64,
+//! }
+//!
+//! let fields = Listing::field_descriptors();
+//! assert_eq!(fields[0].output_language.as_deref(), Some("en"));
+//! assert_eq!(
+//!     fields[0].instruction,
+//!     "Categorize the product Write the value in English, translating it if needed."
+//! );
+//! assert_eq!(fields[1].output_language.as_deref(), Some("source"));
+//! assert!(fields[1].instruction.ends_with("do not translate it."));
+//! // Numbers have no language
+//! assert_eq!(fields[2].output_language, None);
+//! ```
+
+use serde::Serialize;
+#[cfg(feature = "language-detection")]
+use serde_json::Value;
+
+use crate::schema::FieldDescriptor;
+#[cfg(feature = "language-detection")]
+use crate::{schema::FieldKind, utilities::get_field_path};
+
+/// The `output_language` asking for the language of the target.
+pub const SOURCE_LANGUAGE: &str = "source";
+
+/// An extracted value written in another language than its field requires.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LanguageViolation {
+    /// The path of the value, e.g. `category` or `tags[1]`.
+    pub path: String,
+    /// The ISO 639-1 code of the required language, that of the target for `source`.
+    pub expected: String,
+    /// The code of the detected language, ISO 639-1 when it has one and ISO 639-3 otherwise.
+    pub detected: String,
+}
+
+/// ISO 639-1 codes of the languages whatlang detects, by their ISO 639-3 code.
+#[cfg(feature = "language-detection")]
+const ISO_639_1_CODES: [(&str, &str); 24] = [
+    ("ara", "ar"),
+    ("ces", "cs"),
+    ("cmn", "zh"),
+    ("dan", "da"),
+    ("deu", "de"),
+    ("ell", "el"),
+    ("eng", "en"),
+    ("fin", "fi"),
+    ("fra", "fr"),
+    ("heb", "he"),
+    ("hin", "hi"),
+    ("hun", "hu"),
+    ("ita", "it"),
+    ("jpn", "ja"),
+    ("kor", "ko"),
+    ("nld", "nl"),
+    ("nob", "no"),
+    ("pol", "pl"),
+    ("por", "pt"),
+    ("rus", "ru"),
+    ("spa", "es"),
+    ("swe", "sv"),
+    ("tur", "tr"),
+    ("ukr", "uk"),
+];
+
+/// Detects the language of a text.
+///
+/// # Arguments
+///
+/// * `text` - The text, a few words at least
+///
+/// # Returns
+///
+/// The ISO 639-1 code of the language, or its ISO 639-3 code when it has none, or `None` when
+/// the detection is not reliable
+///
+/// # Examples
+///
+/// ```rust
+/// use secretary::language::detect_language;
+///
+/// assert_eq!(
+///     detect_language("Die Wohnung liegt in einer ruhigen Seitenstraße nahe dem Bahnhof.").as_deref(),
+///     Some("de")
+/// );
+/// assert_eq!(
+///     detect_language("The flat is on a quiet side street close to the station.").as_deref(),
+///     Some("en")
+/// );
+/// assert_eq!(detect_language("Ok"), None);
+/// ```
+#[cfg(feature = "language-detection")]
+pub fn detect_language(text: &str) -> Option<String> {
+    let info: whatlang::Info = whatlang::detect(text).filter(whatlang::Info::is_reliable)?;
+    let code: &str = info.lang().code();
+
+    Some(
+        ISO_639_1_CODES
+            .iter()
+            .find(|(iso_639_3, _)| *iso_639_3 == code)
+            .map_or(code, |(_, iso_639_1)| iso_639_1)
+            .to_string(),
+    )
+}
+
+/// Checks the language of the values of the fields with an `output_language`.
+///
+/// Values whose language cannot be detected reliably are skipped, and so are the `source`
+/// fields when the language of the target cannot be.
+///
+/// # Arguments
+///
+/// * `fields` - The field descriptors, e.g. from `Task::field_descriptors()`
+/// * `value` - The extracted data as JSON
+/// * `target` - The text the data was extracted from
+///
+/// # Returns
+///
+/// The values written in another language than required, in field order
+///
+/// # Examples
+///
+/// ```rust
+/// use secretary::Task;
+/// use secretary::language::check_output_languages;
+/// use serde::{Deserialize, Serialize};
+/// use serde_json::json;
+///
+/// #[derive(Task, Serialize, Deserialize, Debug)]
+/// struct Listing {
+///     #[task(instruction = "Describe the flat", output_language = "en")]
+///     pub description: String,
+///     #[task(instruction = "Quote the directions", output_language = "source")]
+///     pub directions: String,
+/// }
+///
+/// let target = "Helle Wohnung mit Balkon. Die Wohnung liegt in einer ruhigen Seitenstraße nahe dem Bahnhof.";
+/// let extracted = json!({
+///     "description": "Helle Wohnung mit Balkon und einer großen Küche im dritten Stock.",
+///     "directions": "The flat is on a quiet side street close to the station.",
+/// });
+///
+/// let violations = check_output_languages(&Listing::field_descriptors(), &extracted, target);
+/// assert_eq!(violations.len(), 2);
+/// assert_eq!(violations[0].path, "description");
+/// assert_eq!((violations[0].expected.as_str(), violations[0].detected.as_str()), ("en", "de"));
+/// assert_eq!(violations[1].path, "directions");
+/// assert_eq!((violations[1].expected.as_str(), violations[1].detected.as_str()), ("de", "en"));
+/// ```
+#[cfg(feature = "language-detection")]
+pub fn check_output_languages(
+    fields: &[FieldDescriptor],
+    value: &Value,
+    target: &str,
+) -> Vec<LanguageViolation> {
+    let source_language: Option<String> = detect_language(target);
+    let mut violations: Vec<LanguageViolation> = Vec::new();
+    check_fields(fields, value, "", &source_language, &mut violations);
+
+    violations
+}
+
+#[cfg(feature = "language-detection")]
+fn check_fields(
+    fields: &[FieldDescriptor],
+    value: &Value,
+    prefix: &str,
+    source_language: &Option<String>,
+    violations: &mut Vec<LanguageViolation>,
+) {
+    for field in fields {
+        let Some(field_value) = get_field_path(value, &field.name) else {
+            continue;
+        };
+        let path: String = match (prefix.is_empty(), field.name.is_empty()) {
+            (true, _) => field.name.clone(),
+            (false, true) => prefix.to_string(),
+            (false, false) => format!("{}.{}", prefix, field.name),
+        };
+
+        match (field.kind, field_value) {
+            (FieldKind::Normal, _) => {
+                let expected: Option<&String> = match field.output_language.as_deref() {
+                    Some(SOURCE_LANGUAGE) => source_language.as_ref(),
+                    _ => field.output_language.as_ref(),
+                };
+                if let Some(expected) = expected {
+                    check_text(field_value, &path, expected, violations);
+                }
+            }
+            (FieldKind::Task | FieldKind::OptionTask, _) => {
+                check_fields(
+                    &field.children,
+                    field_value,
+                    &path,
+                    source_language,
+                    violations,
+                );
+            }
+            (FieldKind::VecTask, Value::Array(items)) => {
+                for (index, item) in items.iter().enumerate() {
+                    let item_path: String = format!("{}[{}]", path, index);
+                    check_fields(
+                        &field.children,
+                        item,
+                        &item_path,
+                        source_language,
+                        violations,
+                    );
+                }
+            }
+            (FieldKind::HashMapTask | FieldKind::BTreeMapTask, Value::Object(entries)) => {
+                for (key, entry) in entries {
+                    let entry_path: String = format!("{}[{}]", path, key);
+                    check_fields(
+                        &field.children,
+                        entry,
+                        &entry_path,
+                        source_language,
+                        violations,
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Checks a text value, or each text of a list, against the expected language.
+#[cfg(feature = "language-detection")]
+fn check_text(value: &Value, path: &str, expected: &str, violations: &mut Vec<LanguageViolation>) {
+    match value {
+        Value::String(text) => {
+            if let Some(detected) = detect_language(text)
+                && detected != expected
+            {
+                violations.push(LanguageViolation {
+                    path: path.to_string(),
+                    expected: expected.to_string(),
+                    detected,
+                });
+            }
+        }
+        Value::Array(items) => {
+            for (index, item) in items.iter().enumerate() {
+                check_text(item, &format!("{}[{}]", path, index), expected, violations);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Returns the language violations of extracted data for the result metadata, always empty
+/// without the `language-detection` feature.
+#[cfg(feature = "language-detection")]
+pub(crate) fn language_violations<T: Serialize>(
+    fields: &[FieldDescriptor],
+    data: &T,
+    target: &str,
+) -> Vec<LanguageViolation> {
+    match serde_json::to_value(data) {
+        Ok(value) => check_output_languages(fields, &value, target),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Returns the language violations of extracted data for the result metadata, always empty
+/// without the `language-detection` feature.
+#[cfg(not(feature = "language-detection"))]
+pub(crate) fn language_violations<T: Serialize>(
+    _fields: &[FieldDescriptor],
+    _data: &T,
+    _target: &str,
+) -> Vec<LanguageViolation> {
+    Vec::new()
+}
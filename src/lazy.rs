@@ -0,0 +1,455 @@
+//! Fields extracted on first access.
+//!
+//! Some fields, e.g. a long summary, are expensive to generate and rarely read. A field of
+//! type `Lazy<T>` marked `#[task(lazy)]` is left out of the extraction: its line and its place
+//! in the JSON template are dropped from the system prompt, and distributed generation sends
+//! no request for it. Its descriptor has `FieldDescriptor::lazy` set and counts as optional.
+//!
+//! Instead, the extraction binds the field to a `LazyBinding`: the provider, the target and
+//! the additional instructions of the call. `Lazy::get` and `Lazy::get_async` then send the
+//! field's distributed prompt, the same request `fields_generate_data` would send for it,
+//! parse the answer and keep the value. Later calls, and calls racing the first one, return
+//! the kept value without another request.
+//!
+//! Lazy fields are bound by the single-request, forced and distributed extractions, in the
+//! struct itself and in its nested and optional Tasks. A `Lazy` built by hand, deserialized
+//! from `null` or inside a Task of a collection has no binding, and reading it fails with
+//! `SecretaryError::LazyFieldUnbound`; `Lazy::new` builds one that already has its value.
+//!
+//! A `Lazy` serializes as its value, or as the marker `{"lazy": "unevaluated"}` while it has
+//! none, so an unread field is told apart from an extracted `null`; the field's descriptor
+//! marks it as lazy. The marker, `null` and a missing field read back as a `Lazy` without a
+//! value. An extracted `None` of a `Lazy<Option<T>>` is written as `null`, so it reads back
+//! without its value too, and a `Lazy<Value>` cannot hold the marker itself as its value.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::Task;
+//! use secretary::lazy::Lazy;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! struct Report {
+//!     #[task(instruction = "Extract the title")]
+//!     pub title: String,
+//!     #[task(instruction = "Summarize the report in a few paragraphs", lazy)]
+//!     pub summary: Lazy<String>,
+//! }
+//!
+//! let fields = Report::field_descriptors();
+//! assert!(fields[1].lazy);
+//!
+//! let report = Report::new();
+//! assert!(!report.get_system_prompt().contains("Summarize"));
+//! assert!(report.summary.peek().is_none());
+//! assert_eq!(
+//!     serde_json::to_string(&report).unwrap(),
+//!     r#"{"title":"","summary":{"lazy":"unevaluated"}}"#
+//! );
+//! ```
+
+use std::sync::{Arc, OnceLock};
+
+use futures::future::BoxFuture;
+use futures::lock::Mutex;
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::DeserializeOwned};
+
+use crate::{
+    distributed::FieldPrompt,
+    error::SecretaryError,
+    message::Message,
+    request::RequestOptions,
+    schema::{FieldDescriptor, FieldKind},
+    traits::{IsLLM, field_request_options, field_response_content, make_field_message},
+    utilities::{
+        cleanup_thinking_blocks, extract_result_content, format_additional_instructions,
+        parse_described_field_value,
+    },
+};
+
+/// A field value extracted on first access, see the module documentation.
+pub struct Lazy<T> {
+    value: OnceLock<T>,
+    field: Option<Arc<LazyField>>,
+    /// Held while the value is requested, so that concurrent readers send one request.
+    extraction: Mutex<()>,
+}
+
+/// What a bound `Lazy` needs to request its value.
+struct LazyField {
+    binding: LazyBinding,
+    field_prompt: FieldPrompt,
+    descriptor: FieldDescriptor,
+}
+
+impl<T> Lazy<T> {
+    /// Creates a `Lazy` that already has its value.
+    pub fn new(value: T) -> Self {
+        Self {
+            value: OnceLock::from(value),
+            field: None,
+            extraction: Mutex::new(()),
+        }
+    }
+
+    /// Returns the value if it has been extracted, without requesting it.
+    pub fn peek(&self) -> Option<&T> {
+        self.value.get()
+    }
+
+    /// Returns whether the value has been extracted.
+    pub fn is_extracted(&self) -> bool {
+        self.value.get().is_some()
+    }
+
+    /// Returns whether an extraction bound this `Lazy`, so that its value can be requested.
+    pub fn is_bound(&self) -> bool {
+        self.field.is_some()
+    }
+
+    /// Returns the value, if it has been extracted.
+    pub fn into_inner(self) -> Option<T> {
+        self.value.into_inner()
+    }
+
+    /// Binds the field to an extraction. Called by the code `#[derive(Task)]` generates for
+    /// `Task::bind_lazy_fields`; a `Lazy` that has its value is left as it is.
+    #[doc(hidden)]
+    pub fn bind(
+        &mut self,
+        binding: &LazyBinding,
+        field_prompt: FieldPrompt,
+        descriptor: FieldDescriptor,
+    ) {
+        if self.is_extracted() {
+            return;
+        }
+
+        self.field = Some(Arc::new(LazyField {
+            binding: binding.clone(),
+            field_prompt,
+            descriptor,
+        }));
+    }
+}
+
+impl<T: DeserializeOwned> Lazy<T> {
+    /// Returns the value, requesting it on the first call.
+    ///
+    /// Blocks while another thread or task requests the value, then returns the value it got.
+    ///
+    /// # Panics
+    ///
+    /// Sends a blocking request, so it must not be called from async code: inside a tokio
+    /// runtime the blocking HTTP client panics, and waiting for a `get_async` running on the
+    /// same executor thread never returns. Use `get_async` there.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SecretaryError::LazyFieldUnbound` if the `Lazy` has no value and no binding,
+    /// and otherwise the errors of the request and of parsing its answer. A failed request is
+    /// sent again by the next call.
+    pub fn get(&self) -> Result<&T, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        if let Some(value) = self.value.get() {
+            return Ok(value);
+        }
+        let field: &LazyField = self
+            .field
+            .as_deref()
+            .ok_or(SecretaryError::LazyFieldUnbound)?;
+
+        let _extraction = futures::executor::block_on(self.extraction.lock());
+        if let Some(value) = self.value.get() {
+            return Ok(value);
+        }
+        let content: String = field
+            .binding
+            .source
+            .request_field(field.message(), &field.options())?;
+
+        let value: T = field.parse(&content)?;
+
+        Ok(self.value.get_or_init(|| value))
+    }
+
+    /// Returns the value like `get`, requesting it asynchronously on the first call.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `get`.
+    pub async fn get_async(
+        &self,
+    ) -> Result<&T, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        if let Some(value) = self.value.get() {
+            return Ok(value);
+        }
+        let field: &LazyField = self
+            .field
+            .as_deref()
+            .ok_or(SecretaryError::LazyFieldUnbound)?;
+
+        let _extraction = self.extraction.lock().await;
+        if let Some(value) = self.value.get() {
+            return Ok(value);
+        }
+        let content: String = field
+            .binding
+            .source
+            .async_request_field(field.message(), &field.options())
+            .await?;
+
+        let value: T = field.parse(&content)?;
+
+        Ok(self.value.get_or_init(|| value))
+    }
+}
+
+impl LazyField {
+    /// Returns the field's request: its distributed prompt with the target.
+    fn message(&self) -> Message {
+        make_field_message(
+            &self.field_prompt,
+            None,
+            &self.binding.target,
+            &format_additional_instructions(&self.binding.additional_instructions),
+        )
+    }
+
+    /// Returns the options of the field's request, with the field's temperature.
+    fn options(&self) -> RequestOptions {
+        field_request_options(&self.field_prompt, &self.binding.options)
+    }
+
+    /// Parses the content of the answer into the field's value.
+    fn parse<T: DeserializeOwned>(&self, content: &str) -> Result<T, SecretaryError> {
+        let value: serde_json::Value = parse_described_field_value(
+            content,
+            &self.descriptor.name,
+            std::slice::from_ref(&self.descriptor),
+        );
+
+        serde_json::from_value(value).map_err(|error| SecretaryError::JsonParsingError {
+            message: format!("The lazy field `{}`: {}", self.descriptor.name, error),
+            raw_content: content.to_string(),
+        })
+    }
+}
+
+impl<T> Default for Lazy<T> {
+    fn default() -> Self {
+        Self {
+            value: OnceLock::new(),
+            field: None,
+            extraction: Mutex::new(()),
+        }
+    }
+}
+
+impl<T: Clone> Clone for Lazy<T> {
+    /// Clones the value, if any, and the binding; the clone requests its value on its own.
+    fn clone(&self) -> Self {
+        Self {
+            value: self.value.clone(),
+            field: self.field.clone(),
+            extraction: Mutex::new(()),
+        }
+    }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for Lazy<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.value.get() {
+            Some(value) => f.debug_tuple("Lazy").field(value).finish(),
+            None => f.write_str("Lazy(<not extracted>)"),
+        }
+    }
+}
+
+impl<T: PartialEq> PartialEq for Lazy<T> {
+    /// Compares the values; two `Lazy`s without one are equal.
+    fn eq(&self, other: &Self) -> bool {
+        self.value.get() == other.value.get()
+    }
+}
+
+impl<T> From<T> for Lazy<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<T: Serialize> Serialize for Lazy<T> {
+    /// Writes the value, or `{"lazy": "unevaluated"}` while there is none.
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.value.get() {
+            Some(value) => value.serialize(serializer),
+            None => Unevaluated {
+                lazy: UnevaluatedMarker::Unevaluated,
+            }
+            .serialize(serializer),
+        }
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Lazy<T> {
+    /// Reads a value, or the unevaluated marker, `null` and a missing field as a `Lazy`
+    /// without one.
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(
+            match Option::<SerializedLazy<T>>::deserialize(deserializer)? {
+                Some(SerializedLazy::Value(value)) => Self::new(value),
+                Some(SerializedLazy::Unevaluated(_)) | None => Self::default(),
+            },
+        )
+    }
+}
+
+/// The form a `Lazy` without a value is serialized in.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Unevaluated {
+    lazy: UnevaluatedMarker,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum UnevaluatedMarker {
+    Unevaluated,
+}
+
+/// A serialized `Lazy`: the unevaluated marker or a value.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SerializedLazy<T> {
+    Unevaluated(Unevaluated),
+    Value(T),
+}
+
+/// The extraction `Lazy` fields are bound to: the provider, the target and the additional
+/// instructions of the call, shared by every field of the result.
+#[derive(Clone)]
+pub struct LazyBinding {
+    source: Arc<dyn LazySource>,
+    target: Arc<str>,
+    additional_instructions: Arc<Vec<String>>,
+    options: Arc<RequestOptions>,
+}
+
+impl LazyBinding {
+    /// Creates the binding of a call. The deadline, hints and response format of the call's
+    /// options do not apply to the fields requested later.
+    pub(crate) fn new(
+        source: Arc<dyn LazySource>,
+        target: &str,
+        additional_instructions: &[String],
+        options: &RequestOptions,
+    ) -> Self {
+        Self {
+            source,
+            target: Arc::from(target),
+            additional_instructions: Arc::new(additional_instructions.to_vec()),
+            options: Arc::new(RequestOptions {
+                deadline: None,
+                hints: Default::default(),
+                response_format: Default::default(),
+                grammar: None,
+                progress: None,
+                ..options.clone()
+            }),
+        }
+    }
+}
+
+/// A provider `Lazy` fields request their values from, see `IsLLM::get_lazy_source`.
+///
+/// Implemented for every provider that is `Send + Sync`.
+pub trait LazySource: Send + Sync {
+    /// Sends a field's request and returns the content of its answer, without thinking
+    /// blocks and result tags.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of the request, and `SecretaryError::Refused` if the model refused
+    /// it.
+    fn request_field(
+        &self,
+        message: Message,
+        options: &RequestOptions,
+    ) -> Result<String, Box<dyn std::error::Error + Send + Sync + 'static>>;
+
+    /// Sends a field's request like `request_field`, asynchronously.
+    fn async_request_field<'a>(
+        &'a self,
+        message: Message,
+        options: &'a RequestOptions,
+    ) -> BoxFuture<'a, Result<String, Box<dyn std::error::Error + Send + Sync + 'static>>>;
+}
+
+impl<L: IsLLM + Send + Sync> LazySource for L {
+    fn request_field(
+        &self,
+        message: Message,
+        options: &RequestOptions,
+    ) -> Result<String, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let response: String = self.send_message_with_options(message, false, options)?;
+
+        field_content(self, &response)
+    }
+
+    fn async_request_field<'a>(
+        &'a self,
+        message: Message,
+        options: &'a RequestOptions,
+    ) -> BoxFuture<'a, Result<String, Box<dyn std::error::Error + Send + Sync + 'static>>> {
+        Box::pin(async move {
+            let response: String = self
+                .async_send_message_with_options(message, false, options)
+                .await?;
+
+            field_content(self, &response)
+        })
+    }
+}
+
+/// Returns the content of the answer to a field's request, like distributed generation.
+fn field_content<L: IsLLM + ?Sized>(
+    llm: &L,
+    response: &str,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let (content, refusal): (String, Option<String>) = field_response_content(llm, response)?;
+    if let Some(message) = refusal {
+        return Err(Box::new(SecretaryError::Refused { message }));
+    }
+
+    Ok(extract_result_content(&cleanup_thinking_blocks(content)))
+}
+
+/// Returns the skipped fields with the dotted paths of the lazy fields, in the Task and in its
+/// nested and optional Tasks.
+///
+/// Used by `#[derive(Task)]` to leave lazy fields out of the system prompt.
+pub fn with_lazy_fields(skipped_fields: &[String], fields: &[FieldDescriptor]) -> Vec<String> {
+    let mut paths: Vec<String> = skipped_fields.to_vec();
+    collect_lazy_fields(fields, "", &mut paths);
+
+    paths
+}
+
+fn collect_lazy_fields(fields: &[FieldDescriptor], prefix: &str, paths: &mut Vec<String>) {
+    for field in fields {
+        let path: String = if prefix.is_empty() {
+            field.name.clone()
+        } else {
+            format!("{}.{}", prefix, field.name)
+        };
+
+        match field.kind {
+            _ if field.lazy => paths.push(path),
+            FieldKind::Task | FieldKind::OptionTask => {
+                collect_lazy_fields(&field.children, &path, paths)
+            }
+            _ => {}
+        }
+    }
+}
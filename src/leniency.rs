@@ -0,0 +1,550 @@
+//! Lenient coercion of LLM output before deserialization.
+//!
+//! Models routinely return numbers as strings (`"42"`), booleans as `"yes"`/`"no"`, a single
+//! value where an array is expected, or `"N/A"` where a value is missing. Strict serde rejects
+//! all of these. A `LeniencyProfile` rewrites the parsed JSON using the field metadata from
+//! `Task::field_descriptors()`, and only where the target type calls for it: a `"42"` in a
+//! `String` field stays a string, and `"N/A"` only becomes `null` in an `Option` field.
+//!
+//! Leniency is off by default. Enable it on a provider with `with_leniency`. Numbers and
+//! dates written in the conventions of the target, such as `1.234,56`, are read by the
+//! `locale` module before a profile applies. Booleans written as words in other languages,
+//! symbols such as `"✓"`, or ratings are read by the `booleans` module.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::Task;
+//! use secretary::booleans::BooleanSynonyms;
+//! use secretary::leniency::{LeniencyProfile, UncertainBooleans};
+//! use serde::{Deserialize, Serialize};
+//! use serde_json::{Value, json};
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! struct Listing {
+//!     #[task(instruction = "Extract the listing title")]
+//!     pub title: String,
+//!     #[task(instruction = "Extract the number of rooms")]
+//!     pub rooms: u32,
+//!     #[task(instruction = "Extract the price")]
+//!     pub price: f64,
+//!     #[task(instruction = "Is the listing furnished")]
+//!     pub furnished: bool,
+//!     #[task(instruction = "Extract the floor, if mentioned")]
+//!     pub floor: Option<i32>,
+//!     #[task(instruction = "Extract the agent name, if mentioned")]
+//!     pub agent: Option<String>,
+//!     #[task(instruction = "List the amenities")]
+//!     pub amenities: Vec<String>,
+//!     #[task(instruction = "List the room sizes in square meters")]
+//!     pub room_sizes: Vec<f64>,
+//! }
+//!
+//! let strict = LeniencyProfile::strict();
+//! let standard = LeniencyProfile::standard();
+//! let aggressive = LeniencyProfile::aggressive();
+//! let words = LeniencyProfile::standard().with_boolean_synonyms(BooleanSynonyms::builtin());
+//! let uncertain = words.clone().with_uncertain_booleans(UncertainBooleans::UseDefault);
+//!
+//! // (profile, field, model output, coerced value)
+//! let cases: Vec<(&LeniencyProfile, &str, Value, Value)> = vec![
+//!     // Numbers from strings
+//!     (&standard, "rooms", json!("42"), json!(42)),
+//!     (&standard, "rooms", json!(" 3 "), json!(3)),
+//!     (&standard, "price", json!("1999.5"), json!(1999.5)),
+//!     (&standard, "floor", json!("-2"), json!(-2)),
+//!     (&standard, "price", json!("$1,999"), json!("$1,999")),
+//!     (&aggressive, "price", json!("$1,999"), json!(1999)),
+//!     (&aggressive, "price", json!("€ 2,500.75"), json!(2500.75)),
+//!     (&standard, "rooms", json!("many"), json!("many")),
+//!     (&standard, "title", json!("42"), json!("42")),
+//!     // Booleans from strings
+//!     (&standard, "furnished", json!("yes"), json!(true)),
+//!     (&standard, "furnished", json!("No"), json!(false)),
+//!     (&standard, "furnished", json!("TRUE"), json!(true)),
+//!     (&standard, "furnished", json!("false"), json!(false)),
+//!     (&standard, "furnished", json!("1"), json!(true)),
+//!     (&standard, "furnished", json!("0"), json!(false)),
+//!     (&standard, "furnished", json!("maybe"), json!("maybe")),
+//!     (&standard, "furnished", json!(1), json!(true)),
+//!     (&standard, "furnished", json!("Ja."), json!("Ja.")),
+//!     (&words, "furnished", json!("Ja."), json!(true)),
+//!     (&words, "furnished", json!("✗"), json!(false)),
+//!     (&standard, "furnished", json!("4/5"), json!("4/5")),
+//!     (&aggressive, "furnished", json!("4/5"), json!(true)),
+//!     (&standard, "furnished", json!(2), json!(2)),
+//!     (&aggressive, "furnished", json!(2), json!(true)),
+//!     (&uncertain, "furnished", json!("maybe"), json!(false)),
+//!     (&standard, "title", json!("yes"), json!("yes")),
+//!     // Null strings, only for optional fields
+//!     (&standard, "floor", json!("N/A"), json!(null)),
+//!     (&standard, "agent", json!("null"), json!(null)),
+//!     (&standard, "agent", json!("none"), json!(null)),
+//!     (&standard, "title", json!("N/A"), json!("N/A")),
+//!     // Scalars to single-element arrays, with element coercion
+//!     (&standard, "amenities", json!("balcony"), json!("balcony")),
+//!     (&aggressive, "amenities", json!("balcony"), json!(["balcony"])),
+//!     (&aggressive, "room_sizes", json!("12.5"), json!([12.5])),
+//!     (&standard, "room_sizes", json!(["12", 14]), json!([12, 14])),
+//!     (&aggressive, "amenities", json!(null), json!(null)),
+//!     // Strict leaves everything alone
+//!     (&strict, "rooms", json!("42"), json!("42")),
+//!     (&strict, "furnished", json!("yes"), json!("yes")),
+//!     (&strict, "floor", json!("N/A"), json!("N/A")),
+//!     (&strict, "amenities", json!("balcony"), json!("balcony")),
+//! ];
+//!
+//! for (profile, field, input, expected) in cases {
+//!     let mut value = json!({ field: input.clone() });
+//!     profile.apply::<Listing>(&mut value);
+//!     assert_eq!(value[field], expected, "{:?} {} {}", profile, field, input);
+//! }
+//!
+//! let output = r#"{
+//!     "title": "Loft", "rooms": "3", "price": "$1,200", "furnished": "yes",
+//!     "floor": "N/A", "agent": "Kim", "amenities": "balcony", "room_sizes": ["20", "12.5"]
+//! }"#;
+//!
+//! // Strict mode is plain serde_json
+//! assert!(strict.from_str::<Listing>(output).is_err());
+//! let valid = r#"{"title": "Loft", "rooms": 3, "price": 1200.0, "furnished": true,
+//!     "floor": null, "agent": null, "amenities": [], "room_sizes": []}"#;
+//! assert_eq!(
+//!     strict.from_str::<Listing>(valid).unwrap().title,
+//!     serde_json::from_str::<Listing>(valid).unwrap().title
+//! );
+//!
+//! let listing: Listing = aggressive.from_str(output).unwrap();
+//! assert_eq!(listing.rooms, 3);
+//! assert_eq!(listing.price, 1200.0);
+//! assert!(listing.furnished);
+//! assert_eq!(listing.floor, None);
+//! assert_eq!(listing.amenities, vec!["balcony".to_string()]);
+//! assert_eq!(listing.room_sizes, vec![20.0, 12.5]);
+//! ```
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Number, Value};
+
+use crate::{
+    booleans::{BooleanReading, BooleanSynonyms, read_rating},
+    casing::{to_native_keys, uses_key_case},
+    defaults::{from_value_with_defaults, has_default_values},
+    normalization::{normalize_strings, uses_string_normalization},
+    schema::{FieldDescriptor, FieldKind, JsonType},
+    traits::Task,
+};
+
+/// What to do with an answer in a boolean field that is neither true nor false, such as
+/// `"maybe"` or a rating in the middle of its scale, see the `booleans` module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum UncertainBooleans {
+    /// Leave the answer as it is, so deserialization fails on it.
+    #[default]
+    Reject,
+    /// Use the default of the field instead: its `default_value`, `null` in an optional
+    /// field, and `false` otherwise, also for the items of lists.
+    UseDefault,
+}
+
+/// Which coercions to apply to LLM output before deserializing it into a `Task`.
+///
+/// The default profile is `strict()`, which applies none and leaves parsing unchanged.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct LeniencyProfile {
+    /// Parse plain numeric strings such as `"42"` or `"-1.5"` in number fields.
+    pub numbers_from_strings: bool,
+    /// Also parse formatted numbers such as `"$1,200"` in number fields.
+    pub formatted_numbers: bool,
+    /// Parse `yes`/`no`/`true`/`false`/`1`/`0` strings, and the numbers 1 and 0, in boolean
+    /// fields.
+    pub booleans_from_strings: bool,
+    /// Read the words and symbols of this table, such as `"ja"`, `"oui"` or `"✓"`, in
+    /// boolean fields, see the `booleans` module. No preset sets it.
+    #[serde(default)]
+    pub boolean_synonyms: Option<BooleanSynonyms>,
+    /// Read any number, or numeric string, in boolean fields as true unless it is zero, such
+    /// as `2` or `"-1"`. Without it only 1 and 0 are read, under `booleans_from_strings`.
+    #[serde(default)]
+    pub booleans_from_numbers: bool,
+    /// Read ratings such as `"4/5"` in boolean fields by the side of the middle of their
+    /// scale they fall on.
+    #[serde(default)]
+    pub booleans_from_ratings: bool,
+    /// What to do with answers in boolean fields that are neither true nor false.
+    #[serde(default)]
+    pub uncertain_booleans: UncertainBooleans,
+    /// Turn `"null"`, `"none"`, `"N/A"` and `"NA"` strings into `null` in optional fields.
+    pub null_strings: bool,
+    /// Wrap a single value in an array when the field is an array.
+    pub scalars_to_arrays: bool,
+}
+
+impl LeniencyProfile {
+    /// No coercion; the output must match the schema exactly.
+    pub fn strict() -> Self {
+        Self::default()
+    }
+
+    /// Coerces unambiguous numeric, boolean and null strings.
+    pub fn standard() -> Self {
+        Self {
+            numbers_from_strings: true,
+            booleans_from_strings: true,
+            null_strings: true,
+            ..Self::default()
+        }
+    }
+
+    /// Everything in `standard()`, plus formatted numbers, nonzero numbers and ratings in
+    /// boolean fields, and scalar to array wrapping.
+    pub fn aggressive() -> Self {
+        Self {
+            numbers_from_strings: true,
+            formatted_numbers: true,
+            booleans_from_strings: true,
+            boolean_synonyms: None,
+            booleans_from_numbers: true,
+            booleans_from_ratings: true,
+            uncertain_booleans: UncertainBooleans::Reject,
+            null_strings: true,
+            scalars_to_arrays: true,
+        }
+    }
+
+    /// Reads the words and symbols of a table in boolean fields.
+    ///
+    /// # Arguments
+    ///
+    /// * `synonyms` - The table, such as `BooleanSynonyms::builtin()`
+    pub fn with_boolean_synonyms(mut self, synonyms: BooleanSynonyms) -> Self {
+        self.boolean_synonyms = Some(synonyms);
+        self
+    }
+
+    /// Sets what to do with answers in boolean fields that are neither true nor false.
+    ///
+    /// # Arguments
+    ///
+    /// * `uncertain_booleans` - Whether to reject them or use the field's default
+    pub fn with_uncertain_booleans(mut self, uncertain_booleans: UncertainBooleans) -> Self {
+        self.uncertain_booleans = uncertain_booleans;
+        self
+    }
+
+    /// Returns whether this profile applies no coercion.
+    pub fn is_strict(&self) -> bool {
+        *self == Self::strict()
+    }
+
+    /// Coerces a JSON object in place to fit the fields of `T`.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The parsed LLM output
+    pub fn apply<T: Task>(&self, value: &mut Value) {
+        self.apply_to_fields(&T::field_descriptors(), value);
+    }
+
+    /// Coerces a JSON object in place to fit the given field descriptors.
+    ///
+    /// Values that are not objects, fields without a descriptor, and values that cannot be
+    /// coerced are left as they are, so deserialization reports them as usual.
+    ///
+    /// # Arguments
+    ///
+    /// * `fields` - The descriptors of the target fields
+    /// * `value` - The JSON object to coerce
+    pub fn apply_to_fields(&self, fields: &[FieldDescriptor], value: &mut Value) {
+        if self.is_strict() {
+            return;
+        }
+
+        let map = match value.as_object_mut() {
+            Some(map) => map,
+            None => return,
+        };
+
+        for field in fields {
+            if let Some(field_value) = map.get_mut(&field.name) {
+                self.coerce_field(field, field_value);
+            }
+        }
+    }
+
+    /// Deserializes a Task from JSON text, applying this profile first.
+    ///
+    /// Fields with a `default_value` are then set to it when they have no usable value, see
+    /// the `defaults` module. In strict mode, output that deserializes as it is goes through
+    /// `serde_json::from_str` alone. The keys of Tasks with a `key_case` are converted back
+    /// to the field names first, see the `casing` module, and the strings of Tasks with
+    /// `normalize_strings` are normalized, see the `normalization` module.
+    ///
+    /// # Arguments
+    ///
+    /// * `content` - The JSON text returned by the LLM
+    pub fn from_str<T: Task>(&self, content: &str) -> Result<T, serde_json::Error> {
+        self.from_str_with_fields::<T>(&T::field_descriptors(), content)
+    }
+
+    /// Deserializes a Task from JSON text like `from_str`, with the field descriptors given
+    /// instead of taken from `T`, e.g. those cached by a `CompiledTask`.
+    ///
+    /// # Arguments
+    ///
+    /// * `fields` - The descriptors of the fields of `T`
+    /// * `content` - The JSON text returned by the LLM
+    pub fn from_str_with_fields<T: Task>(
+        &self,
+        fields: &[FieldDescriptor],
+        content: &str,
+    ) -> Result<T, serde_json::Error> {
+        if uses_key_case(fields) || uses_string_normalization(fields) {
+            let mut value: Value = serde_json::from_str(content)?;
+            to_native_keys(fields, &mut value);
+            normalize_strings(fields, &mut value);
+            return self.value_with_fields::<T>(fields, value);
+        }
+
+        if self.is_strict() {
+            return serde_json::from_str::<T>(content)
+                .or_else(|error| from_str_with_defaults(fields, content, error));
+        }
+
+        let mut value: Value = serde_json::from_str(content)?;
+        self.apply_to_fields(fields, &mut value);
+        from_value_with_defaults::<T>(fields, value)
+    }
+
+    /// Deserializes a Task from a parsed JSON value like `from_str_with_fields`, with its keys
+    /// already converted back to the field names.
+    fn value_with_fields<T: Task>(
+        &self,
+        fields: &[FieldDescriptor],
+        mut value: Value,
+    ) -> Result<T, serde_json::Error> {
+        if self.is_strict() {
+            return T::deserialize(&value).or_else(|error| {
+                if !has_default_values(fields) {
+                    return Err(error);
+                }
+                from_value_with_defaults::<T>(fields, value).map_err(|_| error)
+            });
+        }
+
+        self.apply_to_fields(fields, &mut value);
+        from_value_with_defaults::<T>(fields, value)
+    }
+
+    fn coerce_field(&self, field: &FieldDescriptor, value: &mut Value) {
+        if self.null_strings
+            && field.optional
+            && let Value::String(text) = value
+            && is_null_string(text)
+        {
+            *value = Value::Null;
+            return;
+        }
+
+        if value.is_null() {
+            return;
+        }
+
+        match field.kind {
+            FieldKind::Task | FieldKind::OptionTask => {
+                self.apply_to_fields(&field.children, value);
+            }
+            FieldKind::VecTask => {
+                self.wrap_scalar(value);
+                if let Some(items) = value.as_array_mut() {
+                    for item in items {
+                        self.apply_to_fields(&field.children, item);
+                    }
+                }
+            }
+            FieldKind::HashMapTask | FieldKind::BTreeMapTask => {
+                if let Some(entries) = value.as_object_mut() {
+                    for entry in entries.values_mut() {
+                        self.apply_to_fields(&field.children, entry);
+                    }
+                }
+            }
+            FieldKind::Normal => {
+                if field.json_type == JsonType::Array {
+                    self.wrap_scalar(value);
+                    if let Some(items) = value.as_array_mut() {
+                        for item in items {
+                            self.coerce_scalar(field.item_type, item, || Value::Bool(false));
+                        }
+                    }
+                } else {
+                    self.coerce_scalar(field.json_type, value, || field_default(field));
+                }
+            }
+        }
+    }
+
+    fn wrap_scalar(&self, value: &mut Value) {
+        if self.scalars_to_arrays && !value.is_array() && !value.is_null() {
+            *value = Value::Array(vec![value.take()]);
+        }
+    }
+
+    /// Coerces a value to its JSON type, with `default` the value an uncertain boolean is
+    /// replaced with under `UncertainBooleans::UseDefault`.
+    fn coerce_scalar(
+        &self,
+        json_type: JsonType,
+        value: &mut Value,
+        default: impl FnOnce() -> Value,
+    ) {
+        if json_type == JsonType::Boolean {
+            match self.read_boolean(value) {
+                Some(BooleanReading::True) => *value = Value::Bool(true),
+                Some(BooleanReading::False) => *value = Value::Bool(false),
+                Some(BooleanReading::Uncertain)
+                    if self.uncertain_booleans == UncertainBooleans::UseDefault =>
+                {
+                    *value = default()
+                }
+                _ => {}
+            }
+            return;
+        }
+
+        let text: &str = match value {
+            Value::String(text) => text,
+            _ => return,
+        };
+
+        let coerced: Option<Value> = match json_type {
+            JsonType::Number if self.numbers_from_strings => {
+                parse_number(text, self.formatted_numbers).map(Value::Number)
+            }
+            _ => None,
+        };
+
+        if let Some(coerced) = coerced {
+            *value = coerced;
+        }
+    }
+
+    /// Returns what the value of a boolean field says, by the coercions of this profile, or
+    /// `None` if it is a boolean already or they cannot read it.
+    fn read_boolean(&self, value: &Value) -> Option<BooleanReading> {
+        match value {
+            Value::Number(number) => {
+                let number: f64 = number.as_f64()?;
+                if self.booleans_from_numbers {
+                    return Some(BooleanReading::from(number != 0.0));
+                }
+                self.booleans_from_strings
+                    .then(|| read_flag(number))
+                    .flatten()
+                    .map(BooleanReading::from)
+            }
+            Value::String(text) => {
+                if self.booleans_from_strings
+                    && let Some(boolean) = parse_boolean(text)
+                {
+                    return Some(BooleanReading::from(boolean));
+                }
+                if self.booleans_from_numbers
+                    && let Ok(number) = text.trim().parse::<f64>()
+                    && number.is_finite()
+                {
+                    return Some(BooleanReading::from(number != 0.0));
+                }
+                if let Some(synonyms) = &self.boolean_synonyms
+                    && let Some(reading) = synonyms.lookup(text)
+                {
+                    return Some(reading);
+                }
+                if !self.booleans_from_ratings {
+                    return None;
+                }
+
+                read_rating(text)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Returns the value an uncertain boolean field is replaced with under
+/// `UncertainBooleans::UseDefault`.
+fn field_default(field: &FieldDescriptor) -> Value {
+    match &field.default_value {
+        Some(default) => default.clone(),
+        None if field.optional => Value::Null,
+        None => Value::Bool(false),
+    }
+}
+
+/// Deserializes a Task that failed with `error` again with the default values of its fields,
+/// returning `error` if that fails too.
+fn from_str_with_defaults<T: Task>(
+    fields: &[FieldDescriptor],
+    content: &str,
+    error: serde_json::Error,
+) -> Result<T, serde_json::Error> {
+    if !has_default_values(fields) {
+        return Err(error);
+    }
+
+    match serde_json::from_str::<Value>(content) {
+        Ok(value) => from_value_with_defaults::<T>(fields, value).map_err(|_| error),
+        Err(_) => Err(error),
+    }
+}
+
+fn is_null_string(text: &str) -> bool {
+    matches!(
+        text.trim().to_lowercase().as_str(),
+        "null" | "none" | "n/a" | "na"
+    )
+}
+
+/// Reads the numbers 1 and 0 as flags; other numbers may be ratings on an unknown scale, so
+/// they are only read under `booleans_from_numbers`.
+fn read_flag(number: f64) -> Option<bool> {
+    if number == 1.0 {
+        Some(true)
+    } else if number == 0.0 {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+fn parse_boolean(text: &str) -> Option<bool> {
+    match text.trim().to_lowercase().as_str() {
+        "yes" | "true" | "1" => Some(true),
+        "no" | "false" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+fn parse_number(text: &str, formatted: bool) -> Option<Number> {
+    let mut text: String = text.trim().to_string();
+
+    if formatted {
+        text = text
+            .trim_start_matches(['$', '€', '£', '¥'])
+            .chars()
+            .filter(|character| *character != ',' && !character.is_whitespace())
+            .collect();
+    }
+
+    if let Ok(number) = text.parse::<i64>() {
+        return Some(Number::from(number));
+    }
+    if let Ok(number) = text.parse::<u64>() {
+        return Some(Number::from(number));
+    }
+
+    text.parse::<f64>()
+        .ok()
+        .filter(|number| number.is_finite())
+        .and_then(Number::from_f64)
+}
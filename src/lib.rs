@@ -10,14 +10,77 @@
 //! cannot be successfully parsed into your target struct. This error includes lists of both failed and successful fields,
 //! making it easier to debug extraction failures, especially in distributed generation mode.
 
+pub mod adaptive;
+pub mod assembly;
+pub mod booleans;
+pub mod casing;
+pub mod chunking;
+pub mod classification;
+pub mod compiled;
+pub mod consistency;
 pub mod constants;
+pub mod contextual;
+pub mod correlation;
+pub mod credentials;
+pub mod deadletter;
+pub mod deadline;
+pub mod decoding;
+pub mod defaults;
+pub mod definition;
+pub mod diff;
+pub mod distributed;
+pub mod dynamic;
 pub mod error;
+pub mod estimate;
+pub mod explanation;
+pub mod extractors;
+pub mod grammar;
+pub mod guardrail;
+pub mod hints;
+pub mod incremental;
+pub mod input;
+pub mod instructions;
+pub mod language;
+pub mod lazy;
+pub mod leniency;
+pub mod limits;
 pub mod llm_providers;
+pub mod locale;
 pub mod message;
+pub mod metadata;
+pub mod metrics;
+pub mod mode;
+pub mod normalization;
+pub mod optimize;
+pub mod ordering;
+pub mod partial;
+pub mod progress;
+pub mod prompt;
+pub mod provenance;
+pub mod reasoning;
+pub mod refusal;
+pub mod reproducibility;
+pub mod request;
+pub mod response_format;
+pub mod review;
+pub mod schema;
+pub mod schema_check;
+pub mod session;
+pub mod sharding;
+pub mod sse;
+pub mod streaming;
+pub mod tabular;
+pub mod textdiff;
+pub mod tokens;
+pub mod trace;
 pub mod traits;
+pub mod trimming;
+pub mod utilities;
+pub mod validation;
+pub mod variants;
+pub mod vocabulary;
 
 mod macros;
-mod utilities;
 
 // Re-export the main traits and derive macro for easy access
 pub use traits::{AsyncGenerateData, GenerateData, IsLLM, Task};
@@ -27,3 +90,6 @@ pub use secretary_derive::Task as TaskDerive;
 
 // Re-export the errors
 pub use error::SecretaryError;
+
+// Re-export the field type of `#[task(lazy)]`
+pub use lazy::Lazy;
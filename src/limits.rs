@@ -0,0 +1,328 @@
+//! Limits on the size of the model's output.
+//!
+//! A confused or prompt-injected model can answer with a list of forty thousand keywords or a
+//! string of several megabytes, which would otherwise be deserialized and passed on as is.
+//! `OutputLimits` bounds what an extraction accepts:
+//!
+//! - `max_response_bytes` bounds the HTTP response body, which is read no further than that
+//! - `max_total_json_bytes` bounds the JSON returned for the Task
+//! - `max_nesting_depth` bounds how deeply objects and arrays nest
+//! - `max_array_len` bounds the number of elements of every array
+//! - `max_string_len` bounds the number of characters of every string
+//!
+//! Set them on a provider with `with_output_limits`, or for one call with
+//! `RequestOptions::with_output_limits`, which takes precedence. They are checked on the
+//! parsed JSON before it is deserialized, in every generate method, and on the fields
+//! assembled by distributed generation. A value over a limit fails with
+//! `SecretaryError::OutputLimitExceeded`, naming the limit and the field path.
+//!
+//! Under `LimitPolicy::Truncate`, arrays and strings that are too long are clipped to the
+//! limit instead, and `generate_data_adaptive` lists what was clipped in
+//! `metadata.clipped_values`. The sizes of the response and of the JSON and the nesting depth
+//! cannot be clipped and always fail.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::SecretaryError;
+//! use secretary::limits::{LimitPolicy, OutputLimit, OutputLimits};
+//! use serde_json::json;
+//!
+//! let limits = OutputLimits::default()
+//!     .with_max_array_len(2)
+//!     .with_max_string_len(5);
+//!
+//! let mut value = json!({"name": "Ada", "offers": [{"keywords": ["a", "b", "c"]}]});
+//! match limits.enforce(&mut value).unwrap_err() {
+//!     SecretaryError::OutputLimitExceeded { limit, path, max, actual } => {
+//!         assert_eq!(limit, OutputLimit::ArrayLength);
+//!         assert_eq!(path, "offers[0].keywords");
+//!         assert_eq!((max, actual), (2, 3));
+//!     }
+//!     other => panic!("unexpected error: {}", other),
+//! }
+//!
+//! let limits = limits.with_policy(LimitPolicy::Truncate);
+//! let mut value = json!({"name": "Ada Lovelace", "offers": [{"keywords": ["a", "b", "c"]}]});
+//! let clipped = limits.enforce(&mut value).unwrap();
+//! assert_eq!(value, json!({"name": "Ada L", "offers": [{"keywords": ["a", "b"]}]}));
+//! assert_eq!(clipped[0].path, "name");
+//! assert_eq!(clipped[1].path, "offers[0].keywords");
+//! assert_eq!((clipped[1].original_len, clipped[1].kept_len), (3, 2));
+//! ```
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::SecretaryError;
+
+/// What happens to arrays and strings over their limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum LimitPolicy {
+    /// Fail with `SecretaryError::OutputLimitExceeded`.
+    #[default]
+    Error,
+    /// Keep the first elements or characters that fit the limit.
+    Truncate,
+}
+
+/// One of the limits of `OutputLimits`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum OutputLimit {
+    /// `max_response_bytes`, in bytes.
+    ResponseBytes,
+    /// `max_total_json_bytes`, in bytes.
+    TotalJsonBytes,
+    /// `max_nesting_depth`, in levels.
+    NestingDepth,
+    /// `max_array_len`, in elements.
+    ArrayLength,
+    /// `max_string_len`, in characters.
+    StringLength,
+}
+
+/// Bounds on the size of the model's output, see the module documentation.
+///
+/// Every limit is unset by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct OutputLimits {
+    /// The most elements an array may have.
+    pub max_array_len: Option<usize>,
+    /// The most characters a string may have.
+    pub max_string_len: Option<usize>,
+    /// The most bytes the JSON returned for the Task may have.
+    pub max_total_json_bytes: Option<usize>,
+    /// How deeply objects and arrays may nest; the top-level object is at depth 1.
+    pub max_nesting_depth: Option<usize>,
+    /// The most bytes the HTTP response body may have.
+    pub max_response_bytes: Option<usize>,
+    /// What happens to arrays and strings over their limit.
+    pub policy: LimitPolicy,
+}
+
+/// An array or string clipped under `LimitPolicy::Truncate`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClippedValue {
+    /// The path of the value, e.g. `offers[0].keywords`.
+    pub path: String,
+    /// `OutputLimit::ArrayLength` or `OutputLimit::StringLength`.
+    pub limit: OutputLimit,
+    /// The number of elements or characters the model returned.
+    pub original_len: usize,
+    /// The number of elements or characters kept.
+    pub kept_len: usize,
+}
+
+impl OutputLimits {
+    /// Sets the most elements an array may have.
+    pub fn with_max_array_len(mut self, max_array_len: usize) -> Self {
+        self.max_array_len = Some(max_array_len);
+        self
+    }
+
+    /// Sets the most characters a string may have.
+    pub fn with_max_string_len(mut self, max_string_len: usize) -> Self {
+        self.max_string_len = Some(max_string_len);
+        self
+    }
+
+    /// Sets the most bytes the JSON returned for the Task may have.
+    pub fn with_max_total_json_bytes(mut self, max_total_json_bytes: usize) -> Self {
+        self.max_total_json_bytes = Some(max_total_json_bytes);
+        self
+    }
+
+    /// Sets how deeply objects and arrays may nest.
+    pub fn with_max_nesting_depth(mut self, max_nesting_depth: usize) -> Self {
+        self.max_nesting_depth = Some(max_nesting_depth);
+        self
+    }
+
+    /// Sets the most bytes the HTTP response body may have.
+    pub fn with_max_response_bytes(mut self, max_response_bytes: usize) -> Self {
+        self.max_response_bytes = Some(max_response_bytes);
+        self
+    }
+
+    /// Sets what happens to arrays and strings over their limit.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - `LimitPolicy::Error` by default
+    pub fn with_policy(mut self, policy: LimitPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Returns whether no limit is set.
+    pub fn is_unlimited(&self) -> bool {
+        self.max_array_len.is_none()
+            && self.max_string_len.is_none()
+            && self.max_total_json_bytes.is_none()
+            && self.max_nesting_depth.is_none()
+            && self.max_response_bytes.is_none()
+    }
+
+    /// Checks a JSON value against the limits, clipping it under `LimitPolicy::Truncate`.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The parsed output, changed in place when something is clipped
+    ///
+    /// # Returns
+    ///
+    /// The arrays and strings that were clipped, visiting object keys in alphabetical order
+    ///
+    /// # Errors
+    ///
+    /// Returns `SecretaryError::OutputLimitExceeded` for the first value over a limit that
+    /// is not clipped.
+    pub fn enforce(&self, value: &mut Value) -> Result<Vec<ClippedValue>, SecretaryError> {
+        let mut clipped: Vec<ClippedValue> = Vec::new();
+        if self.is_unlimited() {
+            return Ok(clipped);
+        }
+
+        self.enforce_at(value, "", 1, &mut clipped)?;
+        if let Some(max) = self.max_total_json_bytes {
+            let actual: usize = serde_json::to_vec(value)?.len();
+            check(OutputLimit::TotalJsonBytes, "", max, actual)?;
+        }
+
+        Ok(clipped)
+    }
+
+    /// Checks JSON text against the limits like `enforce`.
+    ///
+    /// The size is checked before the text is parsed. Text that is not valid JSON is returned
+    /// unchanged, so the parser reports it as usual, and clipped values are written back as
+    /// compact JSON.
+    ///
+    /// # Arguments
+    ///
+    /// * `content` - The JSON returned by the model
+    ///
+    /// # Returns
+    ///
+    /// The content, clipped under `LimitPolicy::Truncate`, and the values that were clipped
+    pub fn enforce_content(
+        &self,
+        content: String,
+    ) -> Result<(String, Vec<ClippedValue>), SecretaryError> {
+        if self.is_unlimited() {
+            return Ok((content, Vec::new()));
+        }
+        if let Some(max) = self.max_total_json_bytes {
+            check(OutputLimit::TotalJsonBytes, "", max, content.len())?;
+        }
+
+        let mut value: Value = match serde_json::from_str(&content) {
+            Ok(value) => value,
+            Err(_) => return Ok((content, Vec::new())),
+        };
+        let clipped: Vec<ClippedValue> = self.enforce(&mut value)?;
+        if clipped.is_empty() {
+            return Ok((content, clipped));
+        }
+
+        Ok((serde_json::to_string(&value)?, clipped))
+    }
+
+    /// Checks the size of an HTTP response body.
+    pub(crate) fn check_response_bytes(&self, actual: usize) -> Result<(), SecretaryError> {
+        match self.max_response_bytes {
+            Some(max) => check(OutputLimit::ResponseBytes, "", max, actual),
+            None => Ok(()),
+        }
+    }
+
+    fn enforce_at(
+        &self,
+        value: &mut Value,
+        path: &str,
+        depth: usize,
+        clipped: &mut Vec<ClippedValue>,
+    ) -> Result<(), SecretaryError> {
+        match value {
+            Value::Object(map) => {
+                self.check_depth(path, depth)?;
+                for (key, child) in map.iter_mut() {
+                    let child_path: String = if path.is_empty() {
+                        key.clone()
+                    } else {
+                        format!("{}.{}", path, key)
+                    };
+                    self.enforce_at(child, &child_path, depth + 1, clipped)?;
+                }
+            }
+            Value::Array(items) => {
+                self.check_depth(path, depth)?;
+                if let Some(max) = self.max_array_len
+                    && items.len() > max
+                {
+                    self.clip(OutputLimit::ArrayLength, path, max, items.len(), clipped)?;
+                    items.truncate(max);
+                }
+                for (index, item) in items.iter_mut().enumerate() {
+                    let item_path: String = format!("{}[{}]", path, index);
+                    self.enforce_at(item, &item_path, depth + 1, clipped)?;
+                }
+            }
+            Value::String(text) => {
+                if let Some(max) = self.max_string_len {
+                    let actual: usize = text.chars().count();
+                    if actual > max {
+                        self.clip(OutputLimit::StringLength, path, max, actual, clipped)?;
+                        *text = text.chars().take(max).collect();
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn check_depth(&self, path: &str, depth: usize) -> Result<(), SecretaryError> {
+        match self.max_nesting_depth {
+            Some(max) => check(OutputLimit::NestingDepth, path, max, depth),
+            None => Ok(()),
+        }
+    }
+
+    /// Records a clipped value, or fails under `LimitPolicy::Error`.
+    fn clip(
+        &self,
+        limit: OutputLimit,
+        path: &str,
+        max: usize,
+        actual: usize,
+        clipped: &mut Vec<ClippedValue>,
+    ) -> Result<(), SecretaryError> {
+        if self.policy == LimitPolicy::Error {
+            return check(limit, path, max, actual);
+        }
+
+        clipped.push(ClippedValue {
+            path: path.to_string(),
+            limit,
+            original_len: actual,
+            kept_len: max,
+        });
+        Ok(())
+    }
+}
+
+/// Fails with `SecretaryError::OutputLimitExceeded` when `actual` is over `max`.
+fn check(limit: OutputLimit, path: &str, max: usize, actual: usize) -> Result<(), SecretaryError> {
+    if actual <= max {
+        return Ok(());
+    }
+
+    Err(SecretaryError::OutputLimitExceeded {
+        limit,
+        path: path.to_string(),
+        max,
+        actual,
+    })
+}
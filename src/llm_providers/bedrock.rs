@@ -0,0 +1,674 @@
+//! Amazon Bedrock through its Converse API.
+//!
+//! Bedrock authenticates requests with AWS Signature Version 4 rather than a bearer token, so
+//! `BedrockLLM` takes a signer: a closure that receives each request as it is about to be
+//! sent, body included, and returns the headers that sign it. Any AWS SDK or signing crate
+//! can be plugged in this way, with whatever credentials provider it uses.
+//!
+//! Conversations are sent to the `converse` route of the model, with system messages in
+//! `system` and consecutive messages of the same role merged into one turn. Converse has no
+//! JSON mode, so JSON is always asked for in the prompt. `RequestOptions` are written to
+//! `inferenceConfig`, and the answer is read from `output.message.content`.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use std::sync::{Arc, Mutex};
+//!
+//! use secretary::llm_providers::bedrock::{BedrockLLM, SignableRequest};
+//! use secretary::llm_providers::http::PreparedRequest;
+//! use secretary::message::Message;
+//! use secretary::request::RequestOptions;
+//! use secretary::traits::IsLLM;
+//! use reqwest::header::{AUTHORIZATION, HeaderMap, HeaderValue};
+//! use serde_json::{Value, json};
+//!
+//! let signed: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(Vec::new()));
+//! let recorder = signed.clone();
+//! let llm = BedrockLLM::new(
+//!     "us-east-1",
+//!     "anthropic.claude-3-haiku-20240307-v1:0",
+//!     move |request: &SignableRequest<'_>| {
+//!         // A real signer computes an AWS4-HMAC-SHA256 signature over the request
+//!         recorder.lock().unwrap().push(String::from_utf8_lossy(request.body).to_string());
+//!         let mut headers = HeaderMap::new();
+//!         headers.insert(AUTHORIZATION, HeaderValue::from_static("AWS4-HMAC-SHA256 Credential=..."));
+//!         headers.insert("x-amz-date", HeaderValue::from_static("20240101T000000Z"));
+//!         Ok(headers)
+//!     },
+//! );
+//!
+//! assert_eq!(
+//!     llm.get_chat_completion_request_url(),
+//!     "https://bedrock-runtime.us-east-1.amazonaws.com/model/anthropic.claude-3-haiku-20240307-v1%3A0/converse"
+//! );
+//!
+//! // System messages go to `system`, the options to `inferenceConfig`
+//! let mut body: Value = llm.get_conversation_body(
+//!     vec![Message::system("Extract the name"), Message::user("Ada is 36.")],
+//!     true,
+//! );
+//! llm.apply_request_options(&mut body, &RequestOptions::default().with_temperature(0.0));
+//! assert_eq!(body, json!({
+//!     "system": [{"text": "Extract the name"}],
+//!     "messages": [{"role": "user", "content": [{"text": "Ada is 36."}]}],
+//!     "inferenceConfig": {"temperature": 0.0}
+//! }));
+//!
+//! // The signer sees the body exactly as it is sent
+//! let payload: Vec<u8> = serde_json::to_vec(&body).unwrap();
+//! let url: String = llm.get_chat_completion_request_url();
+//! let headers = llm
+//!     .get_request_headers(&PreparedRequest { method: "POST", url: &url, body: &payload })
+//!     .unwrap();
+//! assert!(headers.contains_key("x-amz-date"));
+//! assert_eq!(signed.lock().unwrap()[0].as_bytes(), payload.as_slice());
+//!
+//! // The answer is read from the Converse output
+//! let response = json!({
+//!     "output": {"message": {"role": "assistant", "content": [{"text": r#"{"name": "Ada"}"#}]}},
+//!     "stopReason": "end_turn"
+//! });
+//! assert_eq!(
+//!     llm.extract_response_content(&response.to_string()).unwrap(),
+//!     r#"{"name": "Ada"}"#
+//! );
+//! ```
+
+use std::sync::Arc;
+
+use reqwest::header::{ACCEPT, CONTENT_TYPE, HeaderMap, HeaderValue};
+use serde_json::{Value, json};
+
+use crate::{
+    SecretaryError,
+    deadletter::DeadLetterSink,
+    decoding::DecodingPolicy,
+    estimate::Pricing,
+    guardrail::Guardrail,
+    lazy::LazySource,
+    leniency::LeniencyProfile,
+    limits::OutputLimits,
+    llm_providers::{
+        capabilities::ProviderCapabilities,
+        http::{CompressionConfig, HttpClients, PoolConfig, PreparedRequest},
+        json_mode::{JsonMode, JsonModeStrategy},
+        queue::RequestQueue,
+        quota::QuotaPartitioner,
+        rate_limit::RetryPolicy,
+    },
+    locale::ExtractionLocale,
+    message::{Message, Role, SystemRoleStrategy},
+    metrics::{MetricsSink, NoopSink},
+    request::{RequestOptions, merge_extra_body},
+    traits::{AsyncGenerateData, GenerateData, IsLLM},
+    trimming::UnknownKeys,
+    vocabulary::OneOfPolicy,
+};
+
+/// A request to be signed, as passed to the signer of a `BedrockLLM`.
+#[derive(Debug, Clone, Copy)]
+pub struct SignableRequest<'a> {
+    /// The HTTP method, `GET` or `POST`.
+    pub method: &'a str,
+    /// The full URL, with the model ID percent-encoded in its path.
+    pub url: &'a str,
+    /// The headers sent besides those the signer returns.
+    pub headers: &'a HeaderMap,
+    /// The body exactly as it is sent, empty for `GET` requests.
+    pub body: &'a [u8],
+}
+
+/// Signs a request, returning the headers to add to it, such as `Authorization`,
+/// `X-Amz-Date` and `X-Amz-Security-Token`.
+pub type RequestSigner = dyn Fn(
+        &SignableRequest<'_>,
+    ) -> Result<HeaderMap, Box<dyn std::error::Error + Send + Sync + 'static>>
+    + Send
+    + Sync;
+
+/// A model served by Amazon Bedrock, called through the Converse API.
+#[derive(Clone)]
+pub struct BedrockLLM {
+    model_id: String,
+    region: String,
+    endpoint: String,
+    signer: Arc<RequestSigner>,
+    capabilities: ProviderCapabilities,
+    leniency: LeniencyProfile,
+    metrics_sink: Arc<dyn MetricsSink>,
+    json_mode: JsonMode,
+    retry_policy: RetryPolicy,
+    http_clients: HttpClients,
+    extra_body: Option<Value>,
+    request_queue: Option<RequestQueue>,
+    quota_partitioner: Option<QuotaPartitioner>,
+    guardrail: Option<Guardrail>,
+    output_limits: Option<OutputLimits>,
+    decoding_policy: DecodingPolicy,
+    unknown_keys: UnknownKeys,
+    one_of_policy: OneOfPolicy,
+    locale: Option<ExtractionLocale>,
+    system_role_strategy: Option<SystemRoleStrategy>,
+    pricing: Option<Pricing>,
+    dead_letter_sink: Option<Arc<dyn DeadLetterSink>>,
+    traced_errors: bool,
+}
+
+impl BedrockLLM {
+    /// Creates a new instance of the BedrockLLM struct.
+    ///
+    /// # Arguments
+    ///
+    /// * `region` - The AWS region, e.g. `us-east-1`.
+    /// * `model_id` - The model ID, inference profile ID or ARN to call.
+    /// * `signer` - Signs each request for the `bedrock` service in `region`, see `RequestSigner`.
+    pub fn new<F>(region: &str, model_id: &str, signer: F) -> Self
+    where
+        F: Fn(
+                &SignableRequest<'_>,
+            ) -> Result<HeaderMap, Box<dyn std::error::Error + Send + Sync + 'static>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        Self {
+            model_id: model_id.to_string(),
+            region: region.to_string(),
+            endpoint: format!("https://bedrock-runtime.{}.amazonaws.com", region),
+            signer: Arc::new(signer),
+            capabilities: ProviderCapabilities::default(),
+            leniency: LeniencyProfile::default(),
+            metrics_sink: Arc::new(NoopSink),
+            json_mode: JsonMode::new(JsonModeStrategy::PromptOnly),
+            retry_policy: RetryPolicy::default(),
+            http_clients: HttpClients::default(),
+            extra_body: None,
+            request_queue: None,
+            quota_partitioner: None,
+            guardrail: None,
+            output_limits: None,
+            decoding_policy: DecodingPolicy::default(),
+            unknown_keys: UnknownKeys::default(),
+            one_of_policy: OneOfPolicy::default(),
+            locale: None,
+            system_role_strategy: None,
+            pricing: None,
+            dead_letter_sink: None,
+            traced_errors: false,
+        }
+    }
+
+    /// Returns the AWS region requests are sent to.
+    pub fn region(&self) -> &str {
+        &self.region
+    }
+
+    /// Sets the runtime endpoint, e.g. a VPC or FIPS endpoint.
+    ///
+    /// # Arguments
+    ///
+    /// * `endpoint` - The base URL, `https://bedrock-runtime.{region}.amazonaws.com` by default
+    pub fn with_endpoint(mut self, endpoint: &str) -> Self {
+        self.endpoint = endpoint.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Declares the capabilities of the configured model.
+    ///
+    /// # Arguments
+    ///
+    /// * `capabilities` - What the model can handle, such as its context window
+    pub fn with_capabilities(mut self, capabilities: ProviderCapabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Sets how leniently the model's output is coerced before deserialization.
+    ///
+    /// # Arguments
+    ///
+    /// * `leniency` - The coercions to apply, `LeniencyProfile::strict()` by default
+    pub fn with_leniency(mut self, leniency: LeniencyProfile) -> Self {
+        self.leniency = leniency;
+        self
+    }
+
+    /// Sets the sink that receives metric events for requests and parse failures.
+    ///
+    /// # Arguments
+    ///
+    /// * `metrics_sink` - The sink to record events to, a `NoopSink` by default
+    pub fn with_metrics_sink(mut self, metrics_sink: Arc<dyn MetricsSink>) -> Self {
+        self.metrics_sink = metrics_sink;
+        self
+    }
+
+    /// Sets how requests throttled with a 429 are retried.
+    ///
+    /// # Arguments
+    ///
+    /// * `retry_policy` - The retry policy, `RetryPolicy::NONE` by default
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Sets the connection pool settings of the provider's HTTP clients.
+    ///
+    /// # Arguments
+    ///
+    /// * `pool_config` - The pool settings, `reqwest` defaults when unset
+    pub fn with_pool_config(mut self, pool_config: PoolConfig) -> Self {
+        self.http_clients =
+            HttpClients::new(pool_config).with_compression(self.http_clients.compression());
+        self
+    }
+
+    /// Sets how request and response bodies are compressed, see the `http` module.
+    ///
+    /// Like `with_pool_config`, this gives the provider new, empty pools. Compressed request
+    /// bodies are signed as they are sent.
+    ///
+    /// # Arguments
+    ///
+    /// * `compression` - Response decompression and the request compression threshold
+    pub fn with_compression(mut self, compression: CompressionConfig) -> Self {
+        self.http_clients = self.http_clients.with_compression(compression);
+        self
+    }
+
+    /// Sets extra JSON to deep-merge into every request body, such as
+    /// `{"additionalModelRequestFields": {"top_k": 50}}`.
+    ///
+    /// # Arguments
+    ///
+    /// * `extra_body` - A JSON object merged over the Converse request body
+    pub fn with_extra_body(mut self, extra_body: Value) -> Self {
+        self.extra_body = Some(extra_body);
+        self
+    }
+
+    /// Sends the provider's requests through a queue that caps the requests in flight and
+    /// admits them by priority, see the `queue` module.
+    ///
+    /// Clones of the queue share it, so the same queue can be given to several providers.
+    ///
+    /// # Arguments
+    ///
+    /// * `request_queue` - The queue, `None` by default
+    pub fn with_request_queue(mut self, request_queue: RequestQueue) -> Self {
+        self.request_queue = Some(request_queue);
+        self
+    }
+
+    /// Splits the provider's requests per window between tenants by weight, see the `quota`
+    /// module.
+    ///
+    /// Clones of the partitioner share it, so the same partitioner can be given to several
+    /// providers.
+    ///
+    /// # Arguments
+    ///
+    /// * `quota_partitioner` - The partitioner, `None` by default
+    pub fn with_quota_partitioner(mut self, quota_partitioner: QuotaPartitioner) -> Self {
+        self.quota_partitioner = Some(quota_partitioner);
+        self
+    }
+
+    /// Runs a guardrail against prompt injection over every target before it is sent, see
+    /// the `guardrail` module.
+    ///
+    /// # Arguments
+    ///
+    /// * `guardrail` - The detector policy and prompt hardening, `None` by default
+    pub fn with_guardrail(mut self, guardrail: Guardrail) -> Self {
+        self.guardrail = Some(guardrail);
+        self
+    }
+
+    /// Limits the size of the model's output, see the `limits` module.
+    ///
+    /// # Arguments
+    ///
+    /// * `output_limits` - The limits, `None` by default
+    pub fn with_output_limits(mut self, output_limits: OutputLimits) -> Self {
+        self.output_limits = Some(output_limits);
+        self
+    }
+
+    /// Sets how responses that are not clean UTF-8 JSON are handled, see the `decoding`
+    /// module.
+    ///
+    /// # Arguments
+    ///
+    /// * `decoding_policy` - The policy, `DecodingPolicy::Repair` by default
+    pub fn with_decoding_policy(mut self, decoding_policy: DecodingPolicy) -> Self {
+        self.decoding_policy = decoding_policy;
+        self
+    }
+
+    /// Sets what happens to keys of the model's JSON that are not fields of the Task, see
+    /// the `trimming` module.
+    ///
+    /// # Arguments
+    ///
+    /// * `unknown_keys` - The policy, `UnknownKeys::Keep` by default
+    pub fn with_unknown_keys(mut self, unknown_keys: UnknownKeys) -> Self {
+        self.unknown_keys = unknown_keys;
+        self
+    }
+
+    /// Sets what happens to answers of `one_of` fields that match no allowed value, see the
+    /// `vocabulary` module.
+    ///
+    /// # Arguments
+    ///
+    /// * `one_of_policy` - The policy, `OneOfPolicy::Error` by default
+    pub fn with_one_of_policy(mut self, one_of_policy: OneOfPolicy) -> Self {
+        self.one_of_policy = one_of_policy;
+        self
+    }
+
+    /// Sets the number and date conventions of the targets, see the `locale` module.
+    ///
+    /// # Arguments
+    ///
+    /// * `locale` - The conventions, e.g. `ExtractionLocale::de_de()`
+    pub fn with_locale(mut self, locale: ExtractionLocale) -> Self {
+        self.locale = Some(locale);
+        self
+    }
+
+    /// Sets how system messages are sent to the model, instead of the strategy chosen from
+    /// the model name, see `SystemRoleStrategy`.
+    ///
+    /// # Arguments
+    ///
+    /// * `system_role_strategy` - The strategy
+    pub fn with_system_role_strategy(mut self, system_role_strategy: SystemRoleStrategy) -> Self {
+        self.system_role_strategy = Some(system_role_strategy);
+        self
+    }
+
+    /// Sets the price of the model's prompt tokens, which `plan` estimates the cost of an
+    /// extraction with, see the `estimate` module.
+    ///
+    /// # Arguments
+    ///
+    /// * `pricing` - The pricing
+    pub fn with_pricing(mut self, pricing: Pricing) -> Self {
+        self.pricing = Some(pricing);
+        self
+    }
+
+    /// Captures every failed extraction to a sink, see the `deadletter` module.
+    ///
+    /// # Arguments
+    ///
+    /// * `dead_letter_sink` - The sink to hand failures to, `None` by default
+    pub fn with_dead_letter_sink(mut self, dead_letter_sink: Arc<dyn DeadLetterSink>) -> Self {
+        self.dead_letter_sink = Some(dead_letter_sink);
+        self
+    }
+
+    /// Wraps the errors of failed extractions in a `TracedError` that carries their request
+    /// IDs, see the `correlation` module.
+    ///
+    /// # Arguments
+    ///
+    /// * `traced_errors` - Whether to wrap errors, `false` by default so errors can be
+    ///   downcast to `SecretaryError`
+    pub fn with_traced_errors(mut self, traced_errors: bool) -> Self {
+        self.traced_errors = traced_errors;
+        self
+    }
+}
+
+impl std::fmt::Debug for BedrockLLM {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BedrockLLM")
+            .field("model_id", &self.model_id)
+            .field("region", &self.region)
+            .field("endpoint", &self.endpoint)
+            .field("capabilities", &self.capabilities)
+            .field("leniency", &self.leniency)
+            .field("retry_policy", &self.retry_policy)
+            .field("extra_body", &self.extra_body)
+            .finish_non_exhaustive()
+    }
+}
+
+impl IsLLM for BedrockLLM {
+    /// Bedrock requests carry no static credentials; they are signed by the signer instead.
+    fn get_authorization_credentials(&self) -> String {
+        String::new()
+    }
+
+    /// Bedrock requests are signed, so calls cannot carry their own API key.
+    fn get_authorization_for_key(&self, _api_key: &str) -> Option<String> {
+        None
+    }
+
+    fn get_model_ref(&self) -> &str {
+        &self.model_id
+    }
+
+    fn get_capabilities(&self) -> ProviderCapabilities {
+        self.capabilities.clone()
+    }
+
+    fn get_leniency(&self) -> &LeniencyProfile {
+        &self.leniency
+    }
+
+    fn get_metrics_sink(&self) -> &dyn MetricsSink {
+        self.metrics_sink.as_ref()
+    }
+
+    fn get_json_mode(&self) -> &JsonMode {
+        &self.json_mode
+    }
+
+    fn get_retry_policy(&self) -> RetryPolicy {
+        self.retry_policy
+    }
+
+    fn http_client(&self) -> &reqwest::Client {
+        self.http_clients.client()
+    }
+
+    fn blocking_http_client(&self) -> &reqwest::blocking::Client {
+        self.http_clients.blocking_client()
+    }
+
+    fn get_http_clients(&self) -> &HttpClients {
+        &self.http_clients
+    }
+
+    fn get_extra_body(&self) -> Option<&Value> {
+        self.extra_body.as_ref()
+    }
+
+    fn get_request_queue(&self) -> Option<&RequestQueue> {
+        self.request_queue.as_ref()
+    }
+
+    fn get_quota_partitioner(&self) -> Option<&QuotaPartitioner> {
+        self.quota_partitioner.as_ref()
+    }
+
+    fn get_guardrail(&self) -> Option<&Guardrail> {
+        self.guardrail.as_ref()
+    }
+
+    fn get_output_limits(&self) -> Option<&OutputLimits> {
+        self.output_limits.as_ref()
+    }
+
+    fn get_decoding_policy(&self) -> DecodingPolicy {
+        self.decoding_policy
+    }
+
+    fn get_unknown_keys(&self) -> UnknownKeys {
+        self.unknown_keys
+    }
+
+    fn get_one_of_policy(&self) -> OneOfPolicy {
+        self.one_of_policy
+    }
+
+    fn get_locale(&self) -> Option<&ExtractionLocale> {
+        self.locale.as_ref()
+    }
+
+    fn get_system_role_strategy(&self) -> SystemRoleStrategy {
+        self.system_role_strategy
+            .unwrap_or_else(|| SystemRoleStrategy::for_model(self.get_model_ref()))
+    }
+
+    fn get_pricing(&self) -> Option<Pricing> {
+        self.pricing
+    }
+
+    fn get_dead_letter_sink(&self) -> Option<&dyn DeadLetterSink> {
+        self.dead_letter_sink.as_deref()
+    }
+
+    fn get_traced_errors(&self) -> bool {
+        self.traced_errors
+    }
+
+    fn get_lazy_source(&self) -> Option<Arc<dyn LazySource>> {
+        Some(Arc::new(self.clone()))
+    }
+
+    fn get_chat_completion_request_url(&self) -> String {
+        format!(
+            "{}/model/{}/converse",
+            self.endpoint,
+            encode_path_segment(&self.model_id)
+        )
+    }
+
+    fn get_request_body(&self, message: Message, return_json: bool) -> Value {
+        self.get_conversation_body(vec![message], return_json)
+    }
+
+    fn get_conversation_body(&self, messages: Vec<Message>, _return_json: bool) -> Value {
+        let mut system: Vec<Value> = Vec::new();
+        let mut turns: Vec<Value> = Vec::new();
+        for message in messages {
+            let block: Value = json!({"text": message.content.as_str()});
+            let role: &str = match message.role {
+                Role::System | Role::Developer => {
+                    system.push(block);
+                    continue;
+                }
+                Role::Assistant => "assistant",
+                Role::User | Role::Tool => "user",
+            };
+
+            match turns.last_mut() {
+                Some(turn) if turn["role"] == role => {
+                    if let Some(content) = turn["content"].as_array_mut() {
+                        content.push(block);
+                    }
+                }
+                _ => turns.push(json!({"role": role, "content": [block]})),
+            }
+        }
+
+        let mut body: Value = json!({"messages": turns});
+        if !system.is_empty() {
+            body["system"] = Value::Array(system);
+        }
+
+        body
+    }
+
+    fn apply_request_options(&self, body: &mut Value, options: &RequestOptions) {
+        if let Some(temperature) = options.temperature {
+            body["inferenceConfig"]["temperature"] = Value::from(temperature);
+        }
+        if let Some(max_tokens) = options.max_tokens {
+            body["inferenceConfig"]["maxTokens"] = Value::from(max_tokens);
+        }
+        if let Some(extra_body) = &options.extra_body {
+            merge_extra_body(body, extra_body);
+        }
+    }
+
+    fn get_request_headers(
+        &self,
+        request: &PreparedRequest<'_>,
+    ) -> Result<HeaderMap, SecretaryError> {
+        let mut headers: HeaderMap = HeaderMap::new();
+        headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
+        if !request.body.is_empty() {
+            headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        }
+
+        let signature: HeaderMap = (self.signer)(&SignableRequest {
+            method: request.method,
+            url: request.url,
+            headers: &headers,
+            body: request.body,
+        })
+        .map_err(|error| {
+            SecretaryError::BuildRequestError(format!("failed to sign the request: {}", error))
+        })?;
+        headers.extend(signature);
+
+        Ok(headers)
+    }
+
+    fn extract_response_content(
+        &self,
+        api_response: &str,
+    ) -> Result<String, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let value: Value = serde_json::from_str(api_response)?;
+        let texts: Vec<&str> = value["output"]["message"]["content"]
+            .as_array()
+            .map(|blocks| {
+                blocks
+                    .iter()
+                    .filter_map(|block| block["text"].as_str())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        if texts.is_empty() {
+            return Err(SecretaryError::NoLLMResponse.into());
+        }
+
+        Ok(texts.concat())
+    }
+
+    fn extract_response_contents(
+        &self,
+        api_response: &str,
+    ) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        Ok(vec![self.extract_response_content(api_response)?])
+    }
+}
+
+impl GenerateData for BedrockLLM {}
+
+impl AsyncGenerateData for BedrockLLM {}
+
+#[cfg(feature = "local")]
+impl crate::traits::AsyncGenerateDataLocal for BedrockLLM {}
+
+/// Percent-encodes a path segment, so that model ARNs keep their `:` and `/` inside it.
+fn encode_path_segment(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
@@ -0,0 +1,66 @@
+use crate::llm_providers::prompt_cache::PromptCaching;
+
+/// Declares what a provider's model can handle.
+///
+/// Capabilities are configured by the caller when constructing a provider, since most
+/// OpenAI-compatible endpoints do not report them. Unset values mean "unknown" and features
+/// that depend on them fall back to their default behavior.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProviderCapabilities {
+    /// The maximum number of tokens the model accepts in a single request, prompt included.
+    pub max_context_tokens: Option<usize>,
+    /// How the provider caches the stable prefix of a prompt.
+    pub prompt_caching: PromptCaching,
+    /// How the provider keeps the state of a multi-turn conversation.
+    pub conversation_state: ConversationState,
+    /// How the provider constrains its output to the JSON of a Task.
+    pub constrained_decoding: ConstrainedDecoding,
+}
+
+impl ProviderCapabilities {
+    /// Sets the maximum context window of the model in tokens.
+    pub fn with_max_context_tokens(mut self, max_context_tokens: usize) -> Self {
+        self.max_context_tokens = Some(max_context_tokens);
+        self
+    }
+
+    /// Sets how the provider caches the stable prefix of a prompt.
+    pub fn with_prompt_caching(mut self, prompt_caching: PromptCaching) -> Self {
+        self.prompt_caching = prompt_caching;
+        self
+    }
+
+    /// Sets how the provider keeps the state of a multi-turn conversation.
+    pub fn with_conversation_state(mut self, conversation_state: ConversationState) -> Self {
+        self.conversation_state = conversation_state;
+        self
+    }
+
+    /// Sets how the provider constrains its output to the JSON of a Task.
+    pub fn with_constrained_decoding(mut self, constrained_decoding: ConstrainedDecoding) -> Self {
+        self.constrained_decoding = constrained_decoding;
+        self
+    }
+}
+
+/// How a provider keeps the state of a multi-turn conversation, see the `session` module.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConversationState {
+    /// The client resends the whole conversation on every turn.
+    #[default]
+    ClientHistory,
+    /// The server keeps the conversation, and a turn sends only its new messages together
+    /// with the `previous_response_id` of the last response, as in OpenAI's Responses API.
+    PreviousResponseId,
+}
+
+/// How a provider constrains its output to the JSON of a Task, see the `grammar` module.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConstrainedDecoding {
+    /// The output is not constrained, and grammars are not sent.
+    #[default]
+    Unsupported,
+    /// The provider takes a GBNF grammar in the `grammar` field of the request, as
+    /// llama.cpp's server does, and its output always matches the grammar.
+    Gbnf,
+}
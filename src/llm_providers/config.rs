@@ -0,0 +1,675 @@
+//! Provider settings loaded from configuration files.
+//!
+//! A `ProviderConfig` holds everything needed to build a provider: its endpoint, the model or
+//! deployment, the name of the environment variable that holds the API key, and the options
+//! otherwise set with the provider's `with_*` methods, such as the retry policy, the connection
+//! pool and sampling parameters sent in the request body. It derives `Serialize` and
+//! `Deserialize`, so it can be read from TOML, JSON or YAML, and `PartialEq`, so a reloaded
+//! file can be compared with the running configuration to decide whether the provider must be
+//! rebuilt.
+//!
+//! The key itself is never part of the configuration. `ProviderConfig::build` reads it from the
+//! environment, `build_with` from any lookup, and both check the URLs, returning a
+//! `ProviderConfigError` that names the missing variable or the malformed field. Durations are
+//! written in milliseconds.
+//!
+//! The built provider is a `ConfiguredLLM`, which implements `GenerateData` and
+//! `AsyncGenerateData` by calling the provider of the configured kind.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::llm_providers::config::{ProviderConfig, ProviderConfigError};
+//! use secretary::traits::IsLLM;
+//!
+//! let config: ProviderConfig = serde_json::from_str(r#"{
+//!     "provider": "azure",
+//!     "endpoint": "https://contoso.openai.azure.com",
+//!     "deployment": "gpt-4o",
+//!     "api_version": "2024-06-01",
+//!     "api_key_env": "AZURE_OPENAI_KEY",
+//!     "retry": {"max_retries": 3, "base_delay_ms": 250},
+//!     "extra_body": {"temperature": 0.2}
+//! }"#).unwrap();
+//!
+//! let lookup = |name: &str| (name == "AZURE_OPENAI_KEY").then(|| "key".to_string());
+//! let llm = config.build_with(lookup).unwrap();
+//! assert_eq!(llm.get_model_ref(), "gpt-4o");
+//! assert_eq!(llm.get_retry_policy().max_retries, 3);
+//!
+//! // Configurations compare by value, so a reload can tell whether anything changed
+//! let reloaded: ProviderConfig = serde_json::from_value(serde_json::to_value(&config).unwrap()).unwrap();
+//! assert_eq!(reloaded, config);
+//!
+//! assert_eq!(
+//!     config.build_with(|_| None).unwrap_err(),
+//!     ProviderConfigError::MissingEnvVar {
+//!         variable: "AZURE_OPENAI_KEY".to_string()
+//!     }
+//! );
+//! ```
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use reqwest::header::HeaderMap;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    SecretaryError,
+    constants::OPENAI_API_BASE,
+    deadletter::DeadLetterSink,
+    decoding::DecodingPolicy,
+    estimate::Pricing,
+    guardrail::Guardrail,
+    lazy::LazySource,
+    leniency::LeniencyProfile,
+    limits::OutputLimits,
+    llm_providers::{
+        azure::AzureOpenAILLM,
+        capabilities::ProviderCapabilities,
+        health::HealthProbe,
+        http::{PoolConfig, PreparedRequest},
+        json_mode::{JsonMode, JsonModeStrategy},
+        openai::OpenAILLM,
+        queue::RequestQueue,
+        quota::QuotaPartitioner,
+        rate_limit::RetryPolicy,
+        responses::ResponsesApiLLM,
+    },
+    locale::ExtractionLocale,
+    message::{Message, SystemRoleStrategy},
+    metrics::MetricsSink,
+    request::RequestOptions,
+    traits::{AsyncGenerateData, GenerateData, IsLLM},
+    trimming::UnknownKeys,
+    vocabulary::OneOfPolicy,
+};
+
+/// The settings of a provider, tagged by `provider` in configuration files.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "provider", rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum ProviderConfig {
+    /// The OpenAI chat completions API or a compatible server, built as an `OpenAILLM`.
+    #[serde(rename = "openai")]
+    OpenAI(OpenAIConfig),
+    /// An Azure OpenAI deployment, built as an `AzureOpenAILLM`.
+    Azure(AzureConfig),
+    /// OpenAI's Responses API, built as a `ResponsesApiLLM`.
+    #[serde(rename = "openai_responses")]
+    Responses(OpenAIConfig),
+}
+
+/// The settings of an OpenAI-style provider.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OpenAIConfig {
+    /// The base URL of the API, OpenAI's when unset.
+    #[serde(default = "default_api_base")]
+    pub api_base: String,
+    /// The model to use.
+    pub model: String,
+    /// The environment variable that holds the API key.
+    pub api_key_env: String,
+    /// The options applied to the provider.
+    #[serde(flatten)]
+    pub options: ProviderOptions,
+}
+
+/// The settings of an Azure OpenAI deployment.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AzureConfig {
+    /// The endpoint of the Azure OpenAI resource, e.g. `https://contoso.openai.azure.com`.
+    pub endpoint: String,
+    /// The deployment to use.
+    pub deployment: String,
+    /// The API version, e.g. `2024-06-01`.
+    pub api_version: String,
+    /// The environment variable that holds the API key.
+    pub api_key_env: String,
+    /// The options applied to the provider.
+    #[serde(flatten)]
+    pub options: ProviderOptions,
+}
+
+/// The options of a provider that can be written in a configuration file.
+///
+/// Each option that is set is applied with the provider's `with_*` method of the same name;
+/// unset options keep the provider's defaults.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProviderOptions {
+    /// How throttled requests are retried.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry: Option<RetryConfig>,
+    /// The connection pool of the provider's HTTP clients.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pool: Option<PoolSettings>,
+    /// Extra fields merged into every request body, such as sampling parameters.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extra_body: Option<Value>,
+    /// How the provider asks for JSON output.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub json_mode: Option<JsonModeStrategy>,
+    /// Which coercions to apply to the model's output.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub leniency: Option<LeniencyProfile>,
+    /// Bounds on the size of the model's output.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_limits: Option<OutputLimits>,
+    /// How responses that are not clean UTF-8 JSON are handled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decoding_policy: Option<DecodingPolicy>,
+    /// What happens to keys of the model's JSON that are not fields of the Task.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unknown_keys: Option<UnknownKeys>,
+    /// What happens to answers of `one_of` fields that match no allowed value.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub one_of_policy: Option<OneOfPolicy>,
+    /// How system messages are sent, chosen from the model name when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_role_strategy: Option<SystemRoleStrategy>,
+    /// The price of the model's prompt tokens.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pricing: Option<Pricing>,
+    /// Whether errors are wrapped in a `TracedError`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub traced_errors: bool,
+}
+
+/// A `RetryPolicy` in a configuration file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetryConfig {
+    /// The number of retries after the first attempt.
+    pub max_retries: u32,
+    /// The backoff before the first retry, in milliseconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_delay_ms: Option<u64>,
+    /// The longest backoff between retries, in milliseconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_delay_ms: Option<u64>,
+    /// Whether refused requests are retried too.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub retry_refusals: bool,
+}
+
+impl RetryConfig {
+    /// Returns the policy, with `RetryPolicy`'s defaults for the unset delays.
+    pub fn policy(&self) -> RetryPolicy {
+        let mut policy: RetryPolicy = RetryPolicy::new(self.max_retries);
+        if let Some(base_delay_ms) = self.base_delay_ms {
+            policy = policy.with_base_delay(Duration::from_millis(base_delay_ms));
+        }
+        if let Some(max_delay_ms) = self.max_delay_ms {
+            policy = policy.with_max_delay(Duration::from_millis(max_delay_ms));
+        }
+
+        policy.with_retry_refusals(self.retry_refusals)
+    }
+}
+
+/// A `PoolConfig` in a configuration file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoolSettings {
+    /// The maximum number of idle connections kept per host.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_idle_per_host: Option<usize>,
+    /// How long an idle connection is kept before it is closed, in milliseconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idle_timeout_ms: Option<u64>,
+    /// The interval of HTTP/2 keep-alive pings, in milliseconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http2_keep_alive_interval_ms: Option<u64>,
+}
+
+impl PoolSettings {
+    /// Returns the pool configuration.
+    pub fn pool_config(&self) -> PoolConfig {
+        PoolConfig {
+            max_idle_per_host: self.max_idle_per_host,
+            idle_timeout: self.idle_timeout_ms.map(Duration::from_millis),
+            http2_keep_alive_interval: self.http2_keep_alive_interval_ms.map(Duration::from_millis),
+        }
+    }
+}
+
+/// Why a provider could not be built from its configuration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ProviderConfigError {
+    /// The environment variable named by `api_key_env` is not set.
+    MissingEnvVar {
+        /// The name of the variable.
+        variable: String,
+    },
+    /// A URL of the configuration is not an absolute `http` or `https` URL.
+    InvalidUrl {
+        /// The field that holds the URL, e.g. `api_base`.
+        field: &'static str,
+        /// The value of the field.
+        value: String,
+        /// What is wrong with it.
+        reason: String,
+    },
+    /// A required field is empty.
+    EmptyField {
+        /// The name of the field.
+        field: &'static str,
+    },
+}
+
+impl fmt::Display for ProviderConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingEnvVar { variable } => {
+                write!(f, "the environment variable {} is not set", variable)
+            }
+            Self::InvalidUrl {
+                field,
+                value,
+                reason,
+            } => write!(f, "{} is not a valid URL ({}): {}", field, reason, value),
+            Self::EmptyField { field } => write!(f, "{} must not be empty", field),
+        }
+    }
+}
+
+impl std::error::Error for ProviderConfigError {}
+
+/// A provider built from a `ProviderConfig`.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum ConfiguredLLM {
+    /// Built from `ProviderConfig::OpenAI`.
+    OpenAI(OpenAILLM),
+    /// Built from `ProviderConfig::Azure`.
+    Azure(AzureOpenAILLM),
+    /// Built from `ProviderConfig::Responses`.
+    Responses(ResponsesApiLLM),
+}
+
+/// Applies the options that are set with the provider's `with_*` methods.
+macro_rules! apply_options {
+    ($llm:expr, $options:expr) => {{
+        let options: &ProviderOptions = $options;
+        let mut llm = $llm;
+        if let Some(retry) = &options.retry {
+            llm = llm.with_retry_policy(retry.policy());
+        }
+        if let Some(pool) = &options.pool {
+            llm = llm.with_pool_config(pool.pool_config());
+        }
+        if let Some(extra_body) = &options.extra_body {
+            llm = llm.with_extra_body(extra_body.clone());
+        }
+        if let Some(json_mode) = options.json_mode {
+            llm = llm.with_json_mode_strategy(json_mode);
+        }
+        if let Some(leniency) = &options.leniency {
+            llm = llm.with_leniency(leniency.clone());
+        }
+        if let Some(output_limits) = options.output_limits {
+            llm = llm.with_output_limits(output_limits);
+        }
+        if let Some(decoding_policy) = options.decoding_policy {
+            llm = llm.with_decoding_policy(decoding_policy);
+        }
+        if let Some(unknown_keys) = options.unknown_keys {
+            llm = llm.with_unknown_keys(unknown_keys);
+        }
+        if let Some(one_of_policy) = options.one_of_policy {
+            llm = llm.with_one_of_policy(one_of_policy);
+        }
+        if let Some(system_role_strategy) = options.system_role_strategy {
+            llm = llm.with_system_role_strategy(system_role_strategy);
+        }
+        if let Some(pricing) = options.pricing {
+            llm = llm.with_pricing(pricing);
+        }
+        llm.with_traced_errors(options.traced_errors)
+    }};
+}
+
+impl ProviderConfig {
+    /// Builds the provider, reading the API key from the environment.
+    pub fn build(&self) -> Result<ConfiguredLLM, ProviderConfigError> {
+        self.build_with(|variable| std::env::var(variable).ok())
+    }
+
+    /// Builds the provider, reading the API key with the given lookup instead of from the
+    /// environment.
+    ///
+    /// # Arguments
+    ///
+    /// * `lookup` - Returns the value of an environment variable, or `None` when it is unset
+    pub fn build_with<F>(&self, lookup: F) -> Result<ConfiguredLLM, ProviderConfigError>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        match self {
+            Self::OpenAI(config) => {
+                let api_key: String = config.check(&lookup)?;
+                let llm = OpenAILLM::new(&config.api_base, &api_key, &config.model)
+                    .expect("creating an OpenAILLM does not fail");
+                Ok(ConfiguredLLM::OpenAI(apply_options!(llm, &config.options)))
+            }
+            Self::Azure(config) => {
+                let api_key: String = config.check(&lookup)?;
+                let llm = AzureOpenAILLM::new(
+                    &config.endpoint,
+                    &api_key,
+                    &config.deployment,
+                    &config.api_version,
+                );
+                Ok(ConfiguredLLM::Azure(apply_options!(llm, &config.options)))
+            }
+            Self::Responses(config) => {
+                let api_key: String = config.check(&lookup)?;
+                let llm = ResponsesApiLLM::new(&config.api_base, &api_key, &config.model)
+                    .expect("creating a ResponsesApiLLM does not fail");
+                Ok(ConfiguredLLM::Responses(apply_options!(
+                    llm,
+                    &config.options
+                )))
+            }
+        }
+    }
+
+    /// Returns the options of the provider.
+    pub fn options(&self) -> &ProviderOptions {
+        match self {
+            Self::OpenAI(config) | Self::Responses(config) => &config.options,
+            Self::Azure(config) => &config.options,
+        }
+    }
+}
+
+impl OpenAIConfig {
+    /// Creates the settings of a model at OpenAI's API.
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - The model to use
+    /// * `api_key_env` - The environment variable that holds the API key
+    pub fn new(model: &str, api_key_env: &str) -> Self {
+        Self {
+            api_base: default_api_base(),
+            model: model.to_string(),
+            api_key_env: api_key_env.to_string(),
+            options: ProviderOptions::default(),
+        }
+    }
+
+    /// Sets the base URL of the API.
+    pub fn with_api_base(mut self, api_base: &str) -> Self {
+        self.api_base = api_base.to_string();
+        self
+    }
+
+    /// Sets the options applied to the provider.
+    pub fn with_options(mut self, options: ProviderOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Checks the fields and returns the API key.
+    fn check<F>(&self, lookup: &F) -> Result<String, ProviderConfigError>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        check_url("api_base", &self.api_base)?;
+        check_not_empty("model", &self.model)?;
+        api_key(&self.api_key_env, lookup)
+    }
+}
+
+impl AzureConfig {
+    /// Creates the settings of an Azure OpenAI deployment.
+    ///
+    /// # Arguments
+    ///
+    /// * `endpoint` - The endpoint of the Azure OpenAI resource
+    /// * `deployment` - The deployment to use
+    /// * `api_version` - The API version
+    /// * `api_key_env` - The environment variable that holds the API key
+    pub fn new(endpoint: &str, deployment: &str, api_version: &str, api_key_env: &str) -> Self {
+        Self {
+            endpoint: endpoint.to_string(),
+            deployment: deployment.to_string(),
+            api_version: api_version.to_string(),
+            api_key_env: api_key_env.to_string(),
+            options: ProviderOptions::default(),
+        }
+    }
+
+    /// Sets the options applied to the provider.
+    pub fn with_options(mut self, options: ProviderOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Checks the fields and returns the API key.
+    fn check<F>(&self, lookup: &F) -> Result<String, ProviderConfigError>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        check_url("endpoint", &self.endpoint)?;
+        check_not_empty("deployment", &self.deployment)?;
+        check_not_empty("api_version", &self.api_version)?;
+        api_key(&self.api_key_env, lookup)
+    }
+}
+
+fn default_api_base() -> String {
+    OPENAI_API_BASE.to_string()
+}
+
+/// Checks that a URL is absolute and uses `http` or `https`.
+pub(crate) fn check_url(field: &'static str, value: &str) -> Result<(), ProviderConfigError> {
+    let invalid = |reason: String| ProviderConfigError::InvalidUrl {
+        field,
+        value: value.to_string(),
+        reason,
+    };
+    let url: reqwest::Url =
+        reqwest::Url::parse(value).map_err(|error| invalid(error.to_string()))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(invalid(format!("unsupported scheme {}", url.scheme())));
+    }
+
+    Ok(())
+}
+
+fn check_not_empty(field: &'static str, value: &str) -> Result<(), ProviderConfigError> {
+    if value.trim().is_empty() {
+        return Err(ProviderConfigError::EmptyField { field });
+    }
+
+    Ok(())
+}
+
+/// Reads the API key from the variable named by `api_key_env`.
+fn api_key<F>(api_key_env: &str, lookup: &F) -> Result<String, ProviderConfigError>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    check_not_empty("api_key_env", api_key_env)?;
+    lookup(api_key_env)
+        .filter(|api_key| !api_key.is_empty())
+        .ok_or_else(|| ProviderConfigError::MissingEnvVar {
+            variable: api_key_env.to_string(),
+        })
+}
+
+/// Calls the same method on the provider that was built.
+macro_rules! delegate {
+    ($self:ident, $llm:ident => $call:expr) => {
+        match $self {
+            ConfiguredLLM::OpenAI($llm) => $call,
+            ConfiguredLLM::Azure($llm) => $call,
+            ConfiguredLLM::Responses($llm) => $call,
+        }
+    };
+}
+
+impl ConfiguredLLM {
+    /// Replaces the API key used by every clone of the provider, see `OpenAILLM::rotate_api_key`.
+    pub fn rotate_api_key(&self, api_key: &str) {
+        delegate!(self, llm => llm.rotate_api_key(api_key))
+    }
+}
+
+impl IsLLM for ConfiguredLLM {
+    fn get_authorization_credentials(&self) -> String {
+        delegate!(self, llm => llm.get_authorization_credentials())
+    }
+
+    fn get_authorization_for_key(&self, api_key: &str) -> Option<String> {
+        delegate!(self, llm => llm.get_authorization_for_key(api_key))
+    }
+
+    fn get_request_body(&self, message: Message, return_json: bool) -> Value {
+        delegate!(self, llm => llm.get_request_body(message, return_json))
+    }
+
+    fn get_conversation_body(&self, messages: Vec<Message>, return_json: bool) -> Value {
+        delegate!(self, llm => llm.get_conversation_body(messages, return_json))
+    }
+
+    fn apply_request_options(&self, body: &mut Value, options: &RequestOptions) {
+        delegate!(self, llm => llm.apply_request_options(body, options))
+    }
+
+    fn get_request_headers(
+        &self,
+        request: &PreparedRequest<'_>,
+    ) -> Result<HeaderMap, SecretaryError> {
+        delegate!(self, llm => llm.get_request_headers(request))
+    }
+
+    fn extract_response_content(
+        &self,
+        api_response: &str,
+    ) -> Result<String, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        delegate!(self, llm => llm.extract_response_content(api_response))
+    }
+
+    fn extract_response_contents(
+        &self,
+        api_response: &str,
+    ) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        delegate!(self, llm => llm.extract_response_contents(api_response))
+    }
+
+    fn extract_response_id(&self, api_response: &str) -> Option<String> {
+        delegate!(self, llm => llm.extract_response_id(api_response))
+    }
+
+    fn get_chat_completion_request_url(&self) -> String {
+        delegate!(self, llm => llm.get_chat_completion_request_url())
+    }
+
+    fn get_model_ref(&self) -> &str {
+        delegate!(self, llm => llm.get_model_ref())
+    }
+
+    fn get_capabilities(&self) -> ProviderCapabilities {
+        delegate!(self, llm => llm.get_capabilities())
+    }
+
+    fn get_system_role_strategy(&self) -> SystemRoleStrategy {
+        delegate!(self, llm => llm.get_system_role_strategy())
+    }
+
+    fn get_leniency(&self) -> &LeniencyProfile {
+        delegate!(self, llm => llm.get_leniency())
+    }
+
+    fn get_metrics_sink(&self) -> &dyn MetricsSink {
+        delegate!(self, llm => llm.get_metrics_sink())
+    }
+
+    fn get_json_mode(&self) -> &JsonMode {
+        delegate!(self, llm => llm.get_json_mode())
+    }
+
+    fn get_retry_policy(&self) -> RetryPolicy {
+        delegate!(self, llm => llm.get_retry_policy())
+    }
+
+    fn get_extra_body(&self) -> Option<&Value> {
+        delegate!(self, llm => llm.get_extra_body())
+    }
+
+    fn get_request_queue(&self) -> Option<&RequestQueue> {
+        delegate!(self, llm => llm.get_request_queue())
+    }
+
+    fn get_quota_partitioner(&self) -> Option<&QuotaPartitioner> {
+        delegate!(self, llm => llm.get_quota_partitioner())
+    }
+
+    fn get_guardrail(&self) -> Option<&Guardrail> {
+        delegate!(self, llm => llm.get_guardrail())
+    }
+
+    fn get_output_limits(&self) -> Option<&OutputLimits> {
+        delegate!(self, llm => llm.get_output_limits())
+    }
+
+    fn get_decoding_policy(&self) -> DecodingPolicy {
+        delegate!(self, llm => llm.get_decoding_policy())
+    }
+
+    fn get_unknown_keys(&self) -> UnknownKeys {
+        delegate!(self, llm => llm.get_unknown_keys())
+    }
+
+    fn get_one_of_policy(&self) -> OneOfPolicy {
+        delegate!(self, llm => llm.get_one_of_policy())
+    }
+
+    fn get_locale(&self) -> Option<&ExtractionLocale> {
+        delegate!(self, llm => llm.get_locale())
+    }
+
+    fn get_pricing(&self) -> Option<Pricing> {
+        delegate!(self, llm => llm.get_pricing())
+    }
+
+    fn get_dead_letter_sink(&self) -> Option<&dyn DeadLetterSink> {
+        delegate!(self, llm => llm.get_dead_letter_sink())
+    }
+
+    fn get_traced_errors(&self) -> bool {
+        delegate!(self, llm => llm.get_traced_errors())
+    }
+
+    fn get_lazy_source(&self) -> Option<Arc<dyn LazySource>> {
+        delegate!(self, llm => llm.get_lazy_source())
+    }
+
+    fn get_health_probe(&self) -> HealthProbe {
+        delegate!(self, llm => llm.get_health_probe())
+    }
+
+    fn get_models_url(&self) -> Option<String> {
+        delegate!(self, llm => llm.get_models_url())
+    }
+
+    fn http_client(&self) -> &reqwest::Client {
+        delegate!(self, llm => llm.http_client())
+    }
+
+    fn blocking_http_client(&self) -> &reqwest::blocking::Client {
+        delegate!(self, llm => llm.blocking_http_client())
+    }
+}
+
+impl GenerateData for ConfiguredLLM {}
+
+impl AsyncGenerateData for ConfiguredLLM {}
+
+#[cfg(feature = "local")]
+impl crate::traits::AsyncGenerateDataLocal for ConfiguredLLM {}
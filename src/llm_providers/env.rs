@@ -0,0 +1,302 @@
+//! Providers built from environment variables.
+//!
+//! `OpenAILLM::from_env` and `AzureOpenAILLM::from_env` read the provider's settings from the
+//! `SECRETARY_*` variables, and `provider_from_env` builds whichever provider they describe:
+//!
+//! | Provider | Variable | |
+//! |---|---|---|
+//! | OpenAI | `SECRETARY_OPENAI_API_BASE` | The base URL, OpenAI's API when unset |
+//! | | `SECRETARY_OPENAI_API_KEY` | The API key |
+//! | | `SECRETARY_OPENAI_MODEL` | The model |
+//! | Azure OpenAI | `SECRETARY_AZURE_OPENAI_ENDPOINT` | The endpoint of the resource |
+//! | | `SECRETARY_AZURE_OPENAI_API_KEY` | The API key |
+//! | | `SECRETARY_AZURE_OPENAI_DEPLOYMENT_ID` | The deployment |
+//! | | `SECRETARY_AZURE_OPENAI_API_VERSION` | The API version, e.g. `2024-06-01` |
+//!
+//! Every variable is checked before the provider is built: URLs must be absolute `http` or
+//! `https` URLs, and the others must not be empty, since an empty variable is as good as an
+//! unset one. Everything wrong is reported at once, by
+//! `SecretaryError::MissingConfiguration` with one `ConfigurationIssue` per variable.
+//!
+//! `provider_from_env` builds the first provider whose variables are all set, OpenAI then
+//! Azure OpenAI. When neither is complete, it reports the issues of the provider with the
+//! most variables set, OpenAI when none is.
+//!
+//! Applications with several configurations give each a prefix with the `_with_prefix`
+//! functions: the prefix `BILLING` reads `BILLING_OPENAI_API_KEY` and so on, and an empty
+//! prefix reads `OPENAI_API_KEY`. Options such as the retry policy are set on the built
+//! provider with its `with_*` methods, or come from a configuration file, see the `config`
+//! module.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use secretary::llm_providers::env::provider_from_env;
+//! use secretary::llm_providers::openai::OpenAILLM;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+//! // SECRETARY_OPENAI_API_KEY, SECRETARY_OPENAI_MODEL and optionally SECRETARY_OPENAI_API_BASE
+//! let llm = OpenAILLM::from_env()?;
+//!
+//! // BILLING_OPENAI_API_KEY and so on
+//! let billing = OpenAILLM::from_env_with_prefix("BILLING")?;
+//!
+//! // OpenAI or Azure OpenAI, depending on the variables that are set
+//! let any = provider_from_env()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+
+use crate::{
+    SecretaryError,
+    constants::OPENAI_API_BASE,
+    llm_providers::{
+        azure::AzureOpenAILLM,
+        config::{ConfiguredLLM, ProviderConfigError, check_url},
+        openai::OpenAILLM,
+    },
+};
+
+/// The prefix of the variables read by `from_env` and `provider_from_env`.
+pub const DEFAULT_PREFIX: &str = "SECRETARY";
+
+/// A variable that is missing or malformed, see `SecretaryError::MissingConfiguration`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigurationIssue {
+    /// The name of the variable, e.g. `SECRETARY_OPENAI_API_KEY`.
+    pub variable: String,
+    /// What the variable should hold, and what is wrong with its value when it is set.
+    pub hint: String,
+}
+
+impl fmt::Display for ConfigurationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.variable, self.hint)
+    }
+}
+
+/// The settings of an OpenAI-style provider read from the environment.
+pub(crate) struct OpenAIEnv {
+    api_base: String,
+    api_key: String,
+    model: String,
+}
+
+/// The settings of an Azure OpenAI deployment read from the environment.
+pub(crate) struct AzureEnv {
+    endpoint: String,
+    api_key: String,
+    deployment: String,
+    api_version: String,
+}
+
+/// Builds the provider described by the `SECRETARY_*` variables, see the module
+/// documentation.
+///
+/// # Errors
+///
+/// Returns `SecretaryError::MissingConfiguration` with every missing or malformed variable of
+/// the provider with the most variables set.
+pub fn provider_from_env() -> Result<ConfiguredLLM, SecretaryError> {
+    provider_from_env_with_prefix(DEFAULT_PREFIX)
+}
+
+/// Builds the provider described by the variables with the given prefix, like
+/// `provider_from_env`.
+///
+/// # Arguments
+///
+/// * `prefix` - The prefix of the variables, e.g. `BILLING` for `BILLING_OPENAI_API_KEY`
+///
+/// # Errors
+///
+/// Returns the same errors as `provider_from_env`.
+pub fn provider_from_env_with_prefix(prefix: &str) -> Result<ConfiguredLLM, SecretaryError> {
+    let lookup = process_lookup;
+    let openai: EnvReader<_> = EnvReader::new(prefix, &lookup);
+    let azure: EnvReader<_> = EnvReader::new(prefix, &lookup);
+    let openai_set: usize = openai.count_set(OPENAI_VARIABLES);
+    let azure_set: usize = azure.count_set(AZURE_VARIABLES);
+
+    let openai: Result<OpenAIEnv, SecretaryError> = read_openai(openai);
+    let azure: Result<AzureEnv, SecretaryError> = read_azure(azure);
+    match (openai, azure) {
+        (Ok(env), _) => Ok(ConfiguredLLM::OpenAI(env.build())),
+        (_, Ok(env)) => Ok(ConfiguredLLM::Azure(env.build())),
+        (Err(error), _) if openai_set >= azure_set => Err(error),
+        (_, Err(error)) => Err(error),
+    }
+}
+
+/// Reads the settings of an OpenAI-style provider from the variables with the given prefix.
+pub(crate) fn openai_from_env(prefix: &str) -> Result<OpenAIEnv, SecretaryError> {
+    read_openai(EnvReader::new(prefix, &process_lookup))
+}
+
+/// Reads the settings of an Azure OpenAI deployment from the variables with the given prefix.
+pub(crate) fn azure_from_env(prefix: &str) -> Result<AzureEnv, SecretaryError> {
+    read_azure(EnvReader::new(prefix, &process_lookup))
+}
+
+impl OpenAIEnv {
+    pub(crate) fn build(self) -> OpenAILLM {
+        OpenAILLM::new(&self.api_base, &self.api_key, &self.model)
+            .expect("creating an OpenAILLM does not fail")
+    }
+}
+
+impl AzureEnv {
+    pub(crate) fn build(self) -> AzureOpenAILLM {
+        AzureOpenAILLM::new(
+            &self.endpoint,
+            &self.api_key,
+            &self.deployment,
+            &self.api_version,
+        )
+    }
+}
+
+const OPENAI_VARIABLES: &[&str] = &["OPENAI_API_BASE", "OPENAI_API_KEY", "OPENAI_MODEL"];
+
+const AZURE_VARIABLES: &[&str] = &[
+    "AZURE_OPENAI_ENDPOINT",
+    "AZURE_OPENAI_API_KEY",
+    "AZURE_OPENAI_DEPLOYMENT_ID",
+    "AZURE_OPENAI_API_VERSION",
+];
+
+fn read_openai<F>(mut reader: EnvReader<F>) -> Result<OpenAIEnv, SecretaryError>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let api_base: String = reader.url("OPENAI_API_BASE", Some(OPENAI_API_BASE));
+    let api_key: String = reader.required("OPENAI_API_KEY", "the API key");
+    let model: String = reader.required("OPENAI_MODEL", "the model, e.g. gpt-4o");
+    reader.finish()?;
+
+    Ok(OpenAIEnv {
+        api_base,
+        api_key,
+        model,
+    })
+}
+
+fn read_azure<F>(mut reader: EnvReader<F>) -> Result<AzureEnv, SecretaryError>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let endpoint: String = reader.url("AZURE_OPENAI_ENDPOINT", None);
+    let api_key: String = reader.required("AZURE_OPENAI_API_KEY", "the API key");
+    let deployment: String =
+        reader.required("AZURE_OPENAI_DEPLOYMENT_ID", "the deployment of the model");
+    let api_version: String = reader.required(
+        "AZURE_OPENAI_API_VERSION",
+        "the API version, e.g. 2024-06-01",
+    );
+    reader.finish()?;
+
+    Ok(AzureEnv {
+        endpoint,
+        api_key,
+        deployment,
+        api_version,
+    })
+}
+
+fn process_lookup(variable: &str) -> Option<String> {
+    std::env::var(variable).ok()
+}
+
+/// Reads the variables with a prefix and collects their issues.
+struct EnvReader<'a, F> {
+    prefix: &'a str,
+    lookup: &'a F,
+    issues: Vec<ConfigurationIssue>,
+}
+
+impl<'a, F> EnvReader<'a, F>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    fn new(prefix: &'a str, lookup: &'a F) -> Self {
+        Self {
+            prefix: prefix.trim_end_matches('_'),
+            lookup,
+            issues: Vec::new(),
+        }
+    }
+
+    /// Returns the full name of a variable.
+    fn variable(&self, name: &str) -> String {
+        if self.prefix.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}_{}", self.prefix, name)
+        }
+    }
+
+    /// Returns the value of a variable, `None` when it is unset or blank.
+    fn value(&self, name: &str) -> Option<String> {
+        (self.lookup)(&self.variable(name))
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    }
+
+    /// Counts the variables that are set.
+    fn count_set(&self, names: &[&str]) -> usize {
+        names
+            .iter()
+            .filter(|name| self.value(name).is_some())
+            .count()
+    }
+
+    /// Returns the value of a required variable, recording an issue when it is unset.
+    fn required(&mut self, name: &str, hint: &str) -> String {
+        let value: Option<String> = self.value(name);
+        if value.is_none() {
+            self.issues.push(ConfigurationIssue {
+                variable: self.variable(name),
+                hint: format!("{}, is not set", hint),
+            });
+        }
+
+        value.unwrap_or_default()
+    }
+
+    /// Returns the value of a URL variable, or `default` when it is unset, recording an issue
+    /// when it is required and unset or is not an absolute `http` or `https` URL.
+    fn url(&mut self, name: &str, default: Option<&str>) -> String {
+        let Some(value) = self.value(name).or(default.map(str::to_string)) else {
+            self.issues.push(ConfigurationIssue {
+                variable: self.variable(name),
+                hint: "an http or https URL, is not set".to_string(),
+            });
+            return String::new();
+        };
+        if let Err(error) = check_url("url", &value) {
+            let reason: String = match error {
+                ProviderConfigError::InvalidUrl { reason, .. } => reason,
+                error => error.to_string(),
+            };
+            self.issues.push(ConfigurationIssue {
+                variable: self.variable(name),
+                hint: format!("an http or https URL, is not valid: {}", reason),
+            });
+        }
+
+        value
+    }
+
+    /// Returns the issues found, if any.
+    fn finish(self) -> Result<(), SecretaryError> {
+        if self.issues.is_empty() {
+            return Ok(());
+        }
+
+        Err(SecretaryError::MissingConfiguration {
+            issues: self.issues,
+        })
+    }
+}
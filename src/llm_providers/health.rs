@@ -0,0 +1,303 @@
+//! Checking that a provider is usable before sending it work.
+//!
+//! `IsLLM::health_check` and `IsLLM::async_health_check` send one minimal request and report
+//! whether the endpoint answered, whether the model is available and how long it took. Each
+//! provider chooses its probe with `IsLLM::get_health_probe`:
+//!
+//! * `HealthProbe::ModelLookup` fetches the model's metadata, as OpenAI's `GET /models/{model}`
+//!   does, without spending tokens. Servers that do not implement the route are probed with
+//!   a chat completion instead.
+//! * `HealthProbe::Chat` sends a one-token chat completion. It is the default, and the probe
+//!   Azure OpenAI uses, since a deployment is only addressable through its chat route.
+//!
+//! A failed check is described by `HealthFailure`, which tells network failures apart from
+//! rejected credentials and unknown models.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use secretary::llm_providers::health::{HealthFailure, HealthReport};
+//! use secretary::llm_providers::openai::OpenAILLM;
+//! use secretary::traits::IsLLM;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+//! let llm = OpenAILLM::new("https://api.openai.com/v1", "your-api-key", "gpt-4o-mini")?;
+//!
+//! let report: HealthReport = llm.health_check()?;
+//! if report.is_healthy() {
+//!     println!("Ready in {:?}", report.latency);
+//! } else {
+//!     match report.failure {
+//!         Some(HealthFailure::Authentication) => eprintln!("Check the API key"),
+//!         Some(HealthFailure::UnknownModel) => eprintln!("Check the model name"),
+//!         _ => eprintln!("Unavailable: {:?}", report.detail),
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::time::{Duration, Instant};
+
+use reqwest::header::HeaderMap;
+use serde_json::Value;
+
+use crate::{
+    SecretaryError, llm_providers::http::PreparedRequest, message::Message,
+    request::RequestOptions, traits::IsLLM,
+};
+
+/// How long a health check waits for the provider before reporting a network failure.
+pub const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The request a provider is probed with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HealthProbe {
+    /// A one-token chat completion sent to the provider's chat route.
+    Chat,
+    /// A `GET` of the model's metadata at the given URL. A 404 or 405 that does not name
+    /// the model as missing falls back to `Chat`.
+    ModelLookup(String),
+}
+
+/// Why a health check failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HealthFailure {
+    /// The provider could not be reached or did not answer in time.
+    Network,
+    /// The provider rejected the credentials with a 401 or 403.
+    Authentication,
+    /// The provider does not know the model or deployment.
+    UnknownModel,
+    /// The provider answered with another unsuccessful status code.
+    Status(u16),
+}
+
+/// The outcome of a health check.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HealthReport {
+    /// Whether the provider answered at all.
+    pub reachable: bool,
+    /// Whether the provider accepted the credentials and knows the model.
+    pub model_available: bool,
+    /// The time from sending the probe to receiving the answer, or to the failure.
+    pub latency: Duration,
+    /// The network error or the provider's error message, if the check failed.
+    pub detail: Option<String>,
+    /// Why the check failed, `None` when it succeeded.
+    pub failure: Option<HealthFailure>,
+}
+
+impl HealthReport {
+    /// Returns whether the provider is usable.
+    pub fn is_healthy(&self) -> bool {
+        self.failure.is_none()
+    }
+}
+
+/// What a single probe request returned.
+enum ProbeOutcome {
+    Answered { status: u16, body: String },
+    Unreachable(String),
+}
+
+/// Probes a provider with blocking requests.
+pub(crate) fn check<L: IsLLM + ?Sized>(llm: &L) -> Result<HealthReport, SecretaryError> {
+    let started: Instant = Instant::now();
+    if let HealthProbe::ModelLookup(url) = llm.get_health_probe() {
+        let headers: HeaderMap = llm.get_request_headers(&PreparedRequest {
+            method: "GET",
+            url: &url,
+            body: &[],
+        })?;
+        let request = llm
+            .blocking_http_client()
+            .get(url)
+            .headers(headers)
+            .timeout(HEALTH_CHECK_TIMEOUT);
+        let outcome: ProbeOutcome = match request.send() {
+            Ok(response) => {
+                let status: u16 = response.status().as_u16();
+                ProbeOutcome::Answered {
+                    status,
+                    body: response.text().unwrap_or_default(),
+                }
+            }
+            Err(error) => unreachable_outcome(error)?,
+        };
+        if !needs_chat_fallback(&outcome) {
+            return Ok(make_report(outcome, started));
+        }
+    }
+
+    let (url, payload, headers) = prepare_ping(llm)?;
+    let started: Instant = Instant::now();
+    let request = llm
+        .blocking_http_client()
+        .post(url)
+        .headers(headers)
+        .timeout(HEALTH_CHECK_TIMEOUT)
+        .body(payload);
+    let outcome: ProbeOutcome = match request.send() {
+        Ok(response) => {
+            let status: u16 = response.status().as_u16();
+            ProbeOutcome::Answered {
+                status,
+                body: response.text().unwrap_or_default(),
+            }
+        }
+        Err(error) => unreachable_outcome(error)?,
+    };
+
+    Ok(make_report(outcome, started))
+}
+
+/// Probes a provider with asynchronous requests.
+pub(crate) async fn async_check<L: IsLLM + Sync + ?Sized>(
+    llm: &L,
+) -> Result<HealthReport, SecretaryError> {
+    let started: Instant = Instant::now();
+    if let HealthProbe::ModelLookup(url) = llm.get_health_probe() {
+        let headers: HeaderMap = llm.get_request_headers(&PreparedRequest {
+            method: "GET",
+            url: &url,
+            body: &[],
+        })?;
+        let request = llm
+            .http_client()
+            .get(url)
+            .headers(headers)
+            .timeout(HEALTH_CHECK_TIMEOUT);
+        let outcome: ProbeOutcome = match request.send().await {
+            Ok(response) => {
+                let status: u16 = response.status().as_u16();
+                ProbeOutcome::Answered {
+                    status,
+                    body: response.text().await.unwrap_or_default(),
+                }
+            }
+            Err(error) => unreachable_outcome(error)?,
+        };
+        if !needs_chat_fallback(&outcome) {
+            return Ok(make_report(outcome, started));
+        }
+    }
+
+    let (url, payload, headers) = prepare_ping(llm)?;
+    let started: Instant = Instant::now();
+    let request = llm
+        .http_client()
+        .post(url)
+        .headers(headers)
+        .timeout(HEALTH_CHECK_TIMEOUT)
+        .body(payload);
+    let outcome: ProbeOutcome = match request.send().await {
+        Ok(response) => {
+            let status: u16 = response.status().as_u16();
+            ProbeOutcome::Answered {
+                status,
+                body: response.text().await.unwrap_or_default(),
+            }
+        }
+        Err(error) => unreachable_outcome(error)?,
+    };
+
+    Ok(make_report(outcome, started))
+}
+
+/// Builds a chat completion that asks for a single token, and returns its URL, body and
+/// headers.
+fn prepare_ping<L: IsLLM + ?Sized>(
+    llm: &L,
+) -> Result<(String, Vec<u8>, HeaderMap), SecretaryError> {
+    let mut body: Value = llm.get_conversation_body(vec![Message::user("ping")], false);
+    llm.apply_request_options(&mut body, &RequestOptions::default().with_max_tokens(1));
+
+    let url: String = llm.get_chat_completion_request_url();
+    let payload: Vec<u8> = serde_json::to_vec(&body)
+        .map_err(|error| SecretaryError::BuildRequestError(error.to_string()))?;
+    let headers: HeaderMap = llm.get_request_headers(&PreparedRequest {
+        method: "POST",
+        url: &url,
+        body: &payload,
+    })?;
+
+    Ok((url, payload, headers))
+}
+
+/// Reports a request that never got an answer. Requests that could not be built, e.g.
+/// because of an invalid URL, are configuration errors rather than health failures.
+fn unreachable_outcome(error: reqwest::Error) -> Result<ProbeOutcome, SecretaryError> {
+    if error.is_builder() {
+        return Err(SecretaryError::BuildRequestError(error.to_string()));
+    }
+
+    Ok(ProbeOutcome::Unreachable(error.to_string()))
+}
+
+/// Returns whether a model lookup hit a server without the route rather than a missing model.
+fn needs_chat_fallback(outcome: &ProbeOutcome) -> bool {
+    match outcome {
+        ProbeOutcome::Answered { status, body } => {
+            (*status == 404 || *status == 405) && !names_missing_model(body)
+        }
+        ProbeOutcome::Unreachable(_) => false,
+    }
+}
+
+/// Returns whether an error body says that the model or deployment does not exist.
+fn names_missing_model(body: &str) -> bool {
+    let code: Option<String> = serde_json::from_str::<Value>(body)
+        .ok()
+        .and_then(|value| value["error"]["code"].as_str().map(str::to_string));
+
+    matches!(
+        code.as_deref(),
+        Some("model_not_found") | Some("DeploymentNotFound")
+    )
+}
+
+/// Reads the message of an OpenAI-style error body, or the body itself.
+fn error_detail(body: &str) -> String {
+    serde_json::from_str::<Value>(body)
+        .ok()
+        .and_then(|value| value["error"]["message"].as_str().map(str::to_string))
+        .unwrap_or_else(|| body.to_string())
+}
+
+fn make_report(outcome: ProbeOutcome, started: Instant) -> HealthReport {
+    let latency: Duration = started.elapsed();
+    let (status, body) = match outcome {
+        ProbeOutcome::Answered { status, body } => (status, body),
+        ProbeOutcome::Unreachable(detail) => {
+            return HealthReport {
+                reachable: false,
+                model_available: false,
+                latency,
+                detail: Some(detail),
+                failure: Some(HealthFailure::Network),
+            };
+        }
+    };
+
+    let failure: Option<HealthFailure> = match status {
+        200..=299 => None,
+        401 | 403 => Some(HealthFailure::Authentication),
+        404 => Some(HealthFailure::UnknownModel),
+        _ if names_missing_model(&body) => Some(HealthFailure::UnknownModel),
+        status => Some(HealthFailure::Status(status)),
+    };
+
+    HealthReport {
+        reachable: true,
+        // Client errors such as a rejected parameter come after the model was resolved
+        model_available: match failure {
+            None => true,
+            Some(HealthFailure::Status(status)) => status < 500,
+            Some(_) => false,
+        },
+        latency,
+        detail: failure.map(|_| error_detail(&body)),
+        failure,
+    }
+}
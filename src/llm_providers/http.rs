@@ -0,0 +1,255 @@
+//! Shared HTTP clients with connection pooling and compression.
+//!
+//! Each provider owns an `HttpClients`, which builds its `reqwest` clients on first use and
+//! reuses them for every request, so connections are kept alive and pooled instead of being
+//! opened for each call. Clones of a provider share the same pools. The async and blocking
+//! clients are separate pools, because a blocking client runs its own runtime.
+//!
+//! `CompressionConfig`, set with the provider's `with_compression`, saves bandwidth on large
+//! payloads. Responses are requested with `Accept-Encoding: gzip, br, deflate` and decoded
+//! transparently, which is on by default. Request bodies of at least
+//! `gzip_requests_above` bytes are sent gzip-compressed with `Content-Encoding: gzip`, which
+//! is off by default. A server that answers a compressed request with 415 Unsupported Media
+//! Type gets the request again uncompressed, and the provider and its clones stop
+//! compressing requests. Each compressed request is reported as a
+//! `MetricEvent::RequestCompressed` with its size before and after.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::time::Duration;
+//!
+//! use secretary::Task;
+//! use secretary::llm_providers::http::PoolConfig;
+//! use secretary::llm_providers::openai::OpenAILLM;
+//! use secretary::traits::GenerateData;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! struct Person {
+//!     #[task(instruction = "Extract the name")]
+//!     pub name: String,
+//! }
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+//! let llm = OpenAILLM::new("https://api.openai.com/v1", "your-api-key", "gpt-4o-mini")?
+//!     .with_pool_config(PoolConfig::default().with_idle_timeout(Duration::from_secs(30)));
+//!
+//! // Sequential requests, including those from a clone, reuse one connection
+//! let task = Person::new();
+//! for target in ["Ada", "Grace", "Hedy"] {
+//!     println!("{:?}", llm.generate_data(&task, target, &vec![])?);
+//! }
+//! let cloned = llm.clone();
+//! println!("{:?}", cloned.generate_data(&task, "Katherine", &vec![])?);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use flate2::Compression;
+use flate2::write::GzEncoder;
+
+/// A request about to be sent to a provider, as passed to `IsLLM::get_request_headers`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PreparedRequest<'a> {
+    /// The HTTP method, `GET` or `POST`.
+    pub method: &'a str,
+    /// The full URL.
+    pub url: &'a str,
+    /// The body exactly as it is sent, empty for `GET` requests.
+    pub body: &'a [u8],
+}
+
+/// Connection pool settings for a provider's HTTP clients.
+///
+/// Unset values use the `reqwest` defaults.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoolConfig {
+    /// The maximum number of idle connections kept per host.
+    pub max_idle_per_host: Option<usize>,
+    /// How long an idle connection is kept before it is closed.
+    pub idle_timeout: Option<Duration>,
+    /// The interval of HTTP/2 keep-alive pings. Only applies to the async client.
+    pub http2_keep_alive_interval: Option<Duration>,
+}
+
+impl PoolConfig {
+    /// Sets the maximum number of idle connections kept per host.
+    pub fn with_max_idle_per_host(mut self, max_idle_per_host: usize) -> Self {
+        self.max_idle_per_host = Some(max_idle_per_host);
+        self
+    }
+
+    /// Sets how long an idle connection is kept before it is closed.
+    pub fn with_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = Some(idle_timeout);
+        self
+    }
+
+    /// Sets the interval of HTTP/2 keep-alive pings.
+    pub fn with_http2_keep_alive_interval(mut self, interval: Duration) -> Self {
+        self.http2_keep_alive_interval = Some(interval);
+        self
+    }
+}
+
+/// Compression settings for a provider's requests and responses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompressionConfig {
+    /// Whether gzip, brotli and deflate responses are requested and decoded. On by default.
+    pub decompress_responses: bool,
+    /// The size in bytes from which request bodies are sent gzip-compressed, `None` to never
+    /// compress them. `None` by default.
+    pub gzip_requests_above: Option<usize>,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            decompress_responses: true,
+            gzip_requests_above: None,
+        }
+    }
+}
+
+impl CompressionConfig {
+    /// Sets whether gzip, brotli and deflate responses are requested and decoded.
+    pub fn with_response_decompression(mut self, decompress_responses: bool) -> Self {
+        self.decompress_responses = decompress_responses;
+        self
+    }
+
+    /// Sends request bodies of at least `threshold` bytes gzip-compressed.
+    pub fn with_gzip_requests_above(mut self, threshold: usize) -> Self {
+        self.gzip_requests_above = Some(threshold);
+        self
+    }
+}
+
+/// Lazily built HTTP clients shared by a provider and its clones.
+#[derive(Debug, Clone, Default)]
+pub struct HttpClients {
+    config: PoolConfig,
+    compression: CompressionConfig,
+    client: Arc<OnceLock<reqwest::Client>>,
+    blocking_client: Arc<OnceLock<reqwest::blocking::Client>>,
+    /// Set once the server rejected a compressed request.
+    gzip_rejected: Arc<AtomicBool>,
+}
+
+impl HttpClients {
+    /// Creates new, empty pools with the given settings.
+    pub fn new(config: PoolConfig) -> Self {
+        Self {
+            config,
+            ..Self::default()
+        }
+    }
+
+    /// Returns new, empty pools with the same pool settings and the given compression.
+    pub fn with_compression(self, compression: CompressionConfig) -> Self {
+        Self {
+            config: self.config,
+            compression,
+            ..Self::default()
+        }
+    }
+
+    /// Returns the pool settings.
+    pub fn config(&self) -> &PoolConfig {
+        &self.config
+    }
+
+    /// Returns the compression settings.
+    pub fn compression(&self) -> CompressionConfig {
+        self.compression
+    }
+
+    /// Returns the gzip-compressed request body, or `None` when the body is below the
+    /// compression threshold, requests are not compressed or the server rejected them.
+    ///
+    /// # Arguments
+    ///
+    /// * `payload` - The request body
+    pub fn encode_request(&self, payload: &[u8]) -> Option<Vec<u8>> {
+        let threshold: usize = self.compression.gzip_requests_above?;
+        if payload.len() < threshold || self.gzip_rejected.load(Ordering::Relaxed) {
+            return None;
+        }
+
+        let mut encoder: GzEncoder<Vec<u8>> = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(payload).ok()?;
+        encoder.finish().ok()
+    }
+
+    /// Stops compressing requests, for this value and every clone, after the server
+    /// rejected a compressed request.
+    pub fn reject_gzip(&self) {
+        self.gzip_rejected.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns the async client, building it on first use.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `reqwest` cannot build the client, for example when no TLS backend can be
+    /// initialized, rather than falling back to a client without the configured settings.
+    pub fn client(&self) -> &reqwest::Client {
+        self.client.get_or_init(|| {
+            let mut builder: reqwest::ClientBuilder = reqwest::Client::builder();
+            if let Some(max_idle_per_host) = self.config.max_idle_per_host {
+                builder = builder.pool_max_idle_per_host(max_idle_per_host);
+            }
+            if let Some(idle_timeout) = self.config.idle_timeout {
+                builder = builder.pool_idle_timeout(idle_timeout);
+            }
+            if let Some(interval) = self.config.http2_keep_alive_interval {
+                builder = builder.http2_keep_alive_interval(interval);
+            }
+            let decompress: bool = self.compression.decompress_responses;
+            builder = builder
+                .gzip(decompress)
+                .brotli(decompress)
+                .deflate(decompress);
+
+            builder
+                .build()
+                .expect("the async HTTP client could not be built with the configured pool and compression settings")
+        })
+    }
+
+    /// Returns the blocking client, building it on first use.
+    ///
+    /// Like any blocking `reqwest` client, it must not be first used from within an async
+    /// runtime.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `reqwest` cannot build the client, like `client`.
+    pub fn blocking_client(&self) -> &reqwest::blocking::Client {
+        self.blocking_client.get_or_init(|| {
+            let mut builder: reqwest::blocking::ClientBuilder =
+                reqwest::blocking::Client::builder();
+            if let Some(max_idle_per_host) = self.config.max_idle_per_host {
+                builder = builder.pool_max_idle_per_host(max_idle_per_host);
+            }
+            if let Some(idle_timeout) = self.config.idle_timeout {
+                builder = builder.pool_idle_timeout(idle_timeout);
+            }
+            let decompress: bool = self.compression.decompress_responses;
+            builder = builder
+                .gzip(decompress)
+                .brotli(decompress)
+                .deflate(decompress);
+
+            builder
+                .build()
+                .expect("the blocking HTTP client could not be built with the configured pool and compression settings")
+        })
+    }
+}
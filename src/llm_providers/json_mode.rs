@@ -0,0 +1,112 @@
+//! How JSON output is requested from a provider.
+//!
+//! OpenAI-compatible APIs enable JSON mode with `"response_format": {"type": "json_object"}`.
+//! Some self-hosted servers reject that parameter with a 400, even though their models emit
+//! JSON when asked to in the prompt. `JsonModeStrategy` selects between the two request shapes,
+//! or lets the provider find out on its own.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use secretary::Task;
+//! use secretary::llm_providers::json_mode::JsonModeStrategy;
+//! use secretary::llm_providers::openai::OpenAILLM;
+//! use secretary::traits::GenerateData;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! struct Person {
+//!     #[task(instruction = "Extract the name")]
+//!     pub name: String,
+//! }
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+//! // A self-hosted server that may not accept response_format
+//! let llm = OpenAILLM::new("http://localhost:8000/v1", "your-api-key", "local-model")?
+//!     .with_json_mode_strategy(JsonModeStrategy::Auto);
+//!
+//! // The first rejection of response_format switches the provider to prompting for JSON
+//! let person: Person = llm.generate_data(&Person::new(), "Ada", &vec![])?;
+//! println!("{:?}", person);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde::{Deserialize, Serialize};
+
+/// How a provider asks for JSON output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum JsonModeStrategy {
+    /// Send `response_format` in the request body.
+    #[default]
+    Native,
+    /// Leave `response_format` out and ask for a JSON object in the prompt.
+    PromptOnly,
+    /// Use `Native` until the server rejects `response_format`, then `PromptOnly` from then on.
+    Auto,
+}
+
+/// A provider's JSON mode strategy, together with what `Auto` has learned about the server.
+///
+/// The learned state is shared by every request made through the same provider instance,
+/// including concurrent ones. Cloning a provider copies what has been learned so far.
+#[derive(Debug, Default)]
+pub struct JsonMode {
+    strategy: JsonModeStrategy,
+    native_rejected: AtomicBool,
+}
+
+impl JsonMode {
+    /// Creates a JSON mode with the given strategy and nothing learned yet.
+    pub const fn new(strategy: JsonModeStrategy) -> Self {
+        Self {
+            strategy,
+            native_rejected: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns the configured strategy.
+    pub fn strategy(&self) -> JsonModeStrategy {
+        self.strategy
+    }
+
+    /// Returns whether the next JSON request should send `response_format`.
+    pub fn uses_native(&self) -> bool {
+        match self.strategy {
+            JsonModeStrategy::Native => true,
+            JsonModeStrategy::PromptOnly => false,
+            JsonModeStrategy::Auto => !self.is_native_rejected(),
+        }
+    }
+
+    /// Returns whether the server has rejected `response_format`.
+    pub fn is_native_rejected(&self) -> bool {
+        self.native_rejected.load(Ordering::Relaxed)
+    }
+
+    /// Records that the server rejects `response_format`.
+    pub fn mark_native_rejected(&self) {
+        self.native_rejected.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Clone for JsonMode {
+    fn clone(&self) -> Self {
+        Self {
+            strategy: self.strategy,
+            native_rejected: AtomicBool::new(self.is_native_rejected()),
+        }
+    }
+}
+
+/// Returns whether a response is a 400 complaining about the `response_format` parameter.
+///
+/// # Arguments
+///
+/// * `status` - The HTTP status code of the response
+/// * `response` - The response body
+pub fn is_response_format_rejection(status: u16, response: &str) -> bool {
+    status == 400 && response.to_lowercase().contains("response_format")
+}
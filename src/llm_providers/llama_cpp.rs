@@ -0,0 +1,130 @@
+//! A provider for llama.cpp's server that constrains the model's output to the JSON of the
+//! Task with a GBNF grammar.
+//!
+//! The server samples only tokens the grammar allows, so its answer always parses, which
+//! prompt-side JSON mode cannot promise for small local models. `LlamaCppLLM` is an
+//! `OpenAILLM` for the server's OpenAI-compatible API that declares
+//! `ConstrainedDecoding::Gbnf`, so `generate_data_with_options` and
+//! `async_generate_data_with_options` send the grammar of the Task as the request's
+//! `grammar` field instead of `response_format`, see the `grammar` module. Other kinds of
+//! requests, such as those of distributed generation, are sent as they are by `OpenAILLM`.
+//!
+//! The provider is configured like `OpenAILLM`, which it is created from with `from_openai`.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::leniency::LeniencyProfile;
+//! use secretary::llm_providers::capabilities::ConstrainedDecoding;
+//! use secretary::llm_providers::llama_cpp::LlamaCppLLM;
+//! use secretary::llm_providers::openai::OpenAILLM;
+//! use secretary::traits::IsLLM;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+//! let llm = LlamaCppLLM::new("http://localhost:8080/v1", "", "qwen2.5-7b-instruct")?;
+//! assert_eq!(
+//!     llm.get_capabilities().constrained_decoding,
+//!     ConstrainedDecoding::Gbnf
+//! );
+//!
+//! // Configured like any OpenAI-compatible provider
+//! let openai = OpenAILLM::new("http://localhost:8080/v1", "", "qwen2.5-7b-instruct")?
+//!     .with_leniency(LeniencyProfile::standard());
+//! let llm = LlamaCppLLM::from_openai(openai);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::Arc;
+
+use reqwest::header::HeaderMap;
+use serde_json::Value;
+
+use crate::{
+    SecretaryError,
+    deadletter::DeadLetterSink,
+    decoding::DecodingPolicy,
+    estimate::Pricing,
+    guardrail::Guardrail,
+    lazy::LazySource,
+    leniency::LeniencyProfile,
+    limits::OutputLimits,
+    llm_providers::{
+        capabilities::{ConstrainedDecoding, ProviderCapabilities},
+        health::HealthProbe,
+        http::{HttpClients, PreparedRequest},
+        json_mode::JsonMode,
+        openai::OpenAILLM,
+        queue::RequestQueue,
+        quota::QuotaPartitioner,
+        rate_limit::RetryPolicy,
+    },
+    locale::ExtractionLocale,
+    message::{Message, SystemRoleStrategy},
+    metrics::MetricsSink,
+    request::RequestOptions,
+    traits::{AsyncGenerateData, GenerateData, IsLLM},
+    trimming::UnknownKeys,
+    vocabulary::OneOfPolicy,
+};
+
+/// A model served by llama.cpp's server, whose output is constrained to the JSON of the Task
+/// with a GBNF grammar.
+#[derive(Debug, Clone)]
+pub struct LlamaCppLLM {
+    llm: OpenAILLM,
+}
+
+impl LlamaCppLLM {
+    /// Creates a provider for a llama.cpp server.
+    ///
+    /// # Arguments
+    ///
+    /// * `api_base` - The base URL of the server's OpenAI-compatible API, e.g.
+    ///   `http://localhost:8080/v1`
+    /// * `api_key` - The key the server was started with by `--api-key`, or an empty string
+    /// * `model` - The model to use
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of `OpenAILLM::new`.
+    pub fn new(
+        api_base: &str,
+        api_key: &str,
+        model: &str,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        Ok(Self::from_openai(OpenAILLM::new(api_base, api_key, model)?))
+    }
+
+    /// Creates a provider from an OpenAI-compatible one pointed at a llama.cpp server, keeping
+    /// its configuration and declaring `ConstrainedDecoding::Gbnf`.
+    ///
+    /// # Arguments
+    ///
+    /// * `llm` - The provider
+    pub fn from_openai(llm: OpenAILLM) -> Self {
+        let capabilities: ProviderCapabilities = llm
+            .get_capabilities()
+            .with_constrained_decoding(ConstrainedDecoding::Gbnf);
+
+        Self {
+            llm: llm.with_capabilities(capabilities),
+        }
+    }
+
+    /// Returns the OpenAI-compatible provider requests are sent with.
+    pub fn openai(&self) -> &OpenAILLM {
+        &self.llm
+    }
+}
+
+impl IsLLM for LlamaCppLLM {
+    delegate_is_llm!(openai);
+}
+
+impl GenerateData for LlamaCppLLM {}
+
+impl AsyncGenerateData for LlamaCppLLM {}
+
+#[cfg(feature = "local")]
+impl crate::traits::AsyncGenerateDataLocal for LlamaCppLLM {}
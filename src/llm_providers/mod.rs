@@ -1,2 +1,175 @@
+/// Implements the getters of `IsLLM` by calling those of the provider returned by the given
+/// method, for providers that wrap others.
+macro_rules! delegate_is_llm {
+    ($inner:ident) => {
+        fn get_authorization_credentials(&self) -> String {
+            self.$inner().get_authorization_credentials()
+        }
+
+        fn get_authorization_for_key(&self, api_key: &str) -> Option<String> {
+            self.$inner().get_authorization_for_key(api_key)
+        }
+
+        fn get_request_body(&self, message: Message, return_json: bool) -> Value {
+            self.$inner().get_request_body(message, return_json)
+        }
+
+        fn get_conversation_body(&self, messages: Vec<Message>, return_json: bool) -> Value {
+            self.$inner().get_conversation_body(messages, return_json)
+        }
+
+        fn apply_request_options(&self, body: &mut Value, options: &RequestOptions) {
+            self.$inner().apply_request_options(body, options)
+        }
+
+        fn get_request_headers(
+            &self,
+            request: &PreparedRequest<'_>,
+        ) -> Result<HeaderMap, SecretaryError> {
+            self.$inner().get_request_headers(request)
+        }
+
+        fn extract_response_content(
+            &self,
+            api_response: &str,
+        ) -> Result<String, Box<dyn std::error::Error + Send + Sync + 'static>> {
+            self.$inner().extract_response_content(api_response)
+        }
+
+        fn extract_response_contents(
+            &self,
+            api_response: &str,
+        ) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+            self.$inner().extract_response_contents(api_response)
+        }
+
+        fn extract_response_id(&self, api_response: &str) -> Option<String> {
+            self.$inner().extract_response_id(api_response)
+        }
+
+        fn get_chat_completion_request_url(&self) -> String {
+            self.$inner().get_chat_completion_request_url()
+        }
+
+        fn get_model_ref(&self) -> &str {
+            self.$inner().get_model_ref()
+        }
+
+        fn get_capabilities(&self) -> ProviderCapabilities {
+            self.$inner().get_capabilities()
+        }
+
+        fn get_system_role_strategy(&self) -> SystemRoleStrategy {
+            self.$inner().get_system_role_strategy()
+        }
+
+        fn get_leniency(&self) -> &LeniencyProfile {
+            self.$inner().get_leniency()
+        }
+
+        fn get_metrics_sink(&self) -> &dyn MetricsSink {
+            self.$inner().get_metrics_sink()
+        }
+
+        fn get_json_mode(&self) -> &JsonMode {
+            self.$inner().get_json_mode()
+        }
+
+        fn get_retry_policy(&self) -> RetryPolicy {
+            self.$inner().get_retry_policy()
+        }
+
+        fn get_extra_body(&self) -> Option<&Value> {
+            self.$inner().get_extra_body()
+        }
+
+        fn get_request_queue(&self) -> Option<&RequestQueue> {
+            self.$inner().get_request_queue()
+        }
+
+        fn get_quota_partitioner(&self) -> Option<&QuotaPartitioner> {
+            self.$inner().get_quota_partitioner()
+        }
+
+        fn get_guardrail(&self) -> Option<&Guardrail> {
+            self.$inner().get_guardrail()
+        }
+
+        fn get_output_limits(&self) -> Option<&OutputLimits> {
+            self.$inner().get_output_limits()
+        }
+
+        fn get_decoding_policy(&self) -> DecodingPolicy {
+            self.$inner().get_decoding_policy()
+        }
+
+        fn get_unknown_keys(&self) -> UnknownKeys {
+            self.$inner().get_unknown_keys()
+        }
+
+        fn get_one_of_policy(&self) -> OneOfPolicy {
+            self.$inner().get_one_of_policy()
+        }
+
+        fn get_locale(&self) -> Option<&ExtractionLocale> {
+            self.$inner().get_locale()
+        }
+
+        fn get_pricing(&self) -> Option<Pricing> {
+            self.$inner().get_pricing()
+        }
+
+        fn get_dead_letter_sink(&self) -> Option<&dyn DeadLetterSink> {
+            self.$inner().get_dead_letter_sink()
+        }
+
+        fn get_traced_errors(&self) -> bool {
+            self.$inner().get_traced_errors()
+        }
+
+        fn get_lazy_source(&self) -> Option<Arc<dyn LazySource>> {
+            self.$inner().get_lazy_source()
+        }
+
+        fn get_health_probe(&self) -> HealthProbe {
+            self.$inner().get_health_probe()
+        }
+
+        fn get_models_url(&self) -> Option<String> {
+            self.$inner().get_models_url()
+        }
+
+        fn http_client(&self) -> &reqwest::Client {
+            self.$inner().http_client()
+        }
+
+        fn blocking_http_client(&self) -> &reqwest::blocking::Client {
+            self.$inner().blocking_http_client()
+        }
+
+        fn get_http_clients(&self) -> &HttpClients {
+            self.$inner().get_http_clients()
+        }
+    };
+}
+
 pub mod azure;
+#[cfg(feature = "aws")]
+pub mod bedrock;
+pub mod capabilities;
+pub mod config;
+pub mod env;
+pub mod health;
+pub mod http;
+pub mod json_mode;
+pub mod llama_cpp;
+pub mod models;
 pub mod openai;
+pub mod prompt_cache;
+pub mod queue;
+pub mod quota;
+pub mod rate_limit;
+#[cfg(feature = "record")]
+pub mod record;
+pub mod responses;
+pub mod router;
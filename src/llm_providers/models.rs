@@ -0,0 +1,645 @@
+//! Known models, their aliases and their lifecycle.
+//!
+//! Providers retire models on a schedule, and a provider configured with a retired model
+//! fails every request with a 404 that does not say why. A `ModelRegistry` holds a table of
+//! known model names and families with their status:
+//!
+//! * `ModelStatus::Active` models are served.
+//! * `ModelStatus::Deprecated` models are served until their shutdown date. Once the date
+//!   has passed they are reported as retired.
+//! * `ModelStatus::Retired` models are no longer served.
+//! * `ModelStatus::Unknown` is reported for names that are not in the table, such as the
+//!   models of a local server or models newer than the table.
+//!
+//! A pattern ending in `*` matches every name starting with the rest, so `gpt-4o*` covers the
+//! dated snapshots of `gpt-4o`; an exact name takes precedence over the patterns, and a longer
+//! pattern over a shorter one. Aliases such as `gpt-4o` resolve to the snapshot they point to
+//! before the table is consulted.
+//!
+//! `OpenAILLM::new` checks its model against the shared registry and keeps the warnings,
+//! which `OpenAILLM::config_warnings` returns. `OpenAILLM::new_with_registry` returns them
+//! alongside the provider, and fails with `SecretaryError::RetiredModel` for a retired model
+//! when the registry is strict. The built-in table, `REGISTRY_VERSION`, is extended with
+//! `with_model` and `with_alias`, or at runtime with `refresh_from_api`, which adds the models
+//! listed by a provider's `/models` route that the table does not know.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::SecretaryError;
+//! use secretary::llm_providers::models::{ConfigWarning, ModelEntry, ModelRegistry, ModelStatus};
+//! use secretary::llm_providers::openai::OpenAILLM;
+//!
+//! let registry = ModelRegistry::builtin();
+//!
+//! // Aliases resolve to their snapshot
+//! let info = registry.lookup_on("gpt-4o", "2025-01-01");
+//! assert_eq!(info.canonical, "gpt-4o-2024-08-06");
+//! assert_eq!(info.status, ModelStatus::Active);
+//!
+//! // Deprecated models are retired once their shutdown date passed
+//! assert!(matches!(registry.lookup_on("gpt-4.5-preview", "2025-06-01").status, ModelStatus::Deprecated { .. }));
+//! assert!(matches!(registry.lookup_on("gpt-4.5-preview", "2025-08-01").status, ModelStatus::Retired { .. }));
+//!
+//! // The provider keeps the warnings of its model
+//! let (llm, warnings) = OpenAILLM::new_with_registry(
+//!     "https://api.openai.com/v1",
+//!     "key",
+//!     "my-fine-tune",
+//!     &registry,
+//! )
+//! .unwrap();
+//! assert!(matches!(&warnings[..], [ConfigWarning::UnknownModel { .. }]));
+//! assert_eq!(llm.config_warnings(), &warnings[..]);
+//!
+//! // A strict registry refuses retired models
+//! let strict = registry.clone().with_strict(true);
+//! let error = OpenAILLM::new_with_registry("https://api.openai.com/v1", "key", "gpt-4-0314", &strict)
+//!     .unwrap_err();
+//! assert!(matches!(error, SecretaryError::RetiredModel { replacement: Some(_), .. }));
+//!
+//! // Models of your own are added to the table
+//! let registry = registry.with_model(ModelEntry::active("my-fine-tune"));
+//! assert_eq!(registry.check("my-fine-tune").unwrap(), vec![]);
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Arc, LazyLock, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::SystemTime;
+
+use reqwest::header::HeaderMap;
+use serde_json::Value;
+
+use crate::{
+    SecretaryError,
+    llm_providers::{health::HEALTH_CHECK_TIMEOUT, http::PreparedRequest},
+    traits::IsLLM,
+    utilities::builtin_template_vars,
+};
+
+/// The date the built-in table was last reviewed against the providers' deprecation notices.
+pub const REGISTRY_VERSION: &str = "2025-06-15";
+
+/// A row of the built-in table: the name or pattern, and for deprecated and retired models
+/// the shutdown date and the recommended replacement.
+enum Row {
+    Active(&'static str),
+    Deprecated(&'static str, &'static str, &'static str),
+    Retired(&'static str, &'static str, &'static str),
+}
+
+/// The built-in table. Add a row when a provider announces a deprecation, and update
+/// `REGISTRY_VERSION`.
+const BUILTIN_MODELS: &[Row] = &[
+    // OpenAI
+    Row::Active("gpt-5*"),
+    Row::Active("gpt-4.1*"),
+    Row::Active("gpt-4o*"),
+    Row::Active("gpt-4-turbo*"),
+    Row::Active("gpt-4*"),
+    Row::Active("gpt-3.5-turbo*"),
+    Row::Active("chatgpt-4o-latest"),
+    Row::Active("o1*"),
+    Row::Active("o3*"),
+    Row::Active("o4-mini*"),
+    Row::Deprecated("gpt-4.5-preview*", "2025-07-14", "gpt-4.1"),
+    Row::Deprecated("o1-preview*", "2025-07-28", "o3"),
+    Row::Deprecated("o1-mini*", "2025-10-27", "o4-mini"),
+    Row::Retired("gpt-4-0314", "2024-06-13", "gpt-4o"),
+    Row::Retired("gpt-4-32k*", "2025-06-06", "gpt-4o"),
+    Row::Retired("gpt-4-vision-preview", "2024-12-06", "gpt-4o"),
+    Row::Retired("gpt-4-1106-vision-preview", "2024-12-06", "gpt-4o"),
+    Row::Retired("gpt-3.5-turbo-0301", "2024-09-13", "gpt-4o-mini"),
+    Row::Retired("gpt-3.5-turbo-0613", "2024-09-13", "gpt-4o-mini"),
+    Row::Retired("gpt-3.5-turbo-16k-0613", "2024-09-13", "gpt-4o-mini"),
+    Row::Retired("text-davinci-003", "2024-01-04", "gpt-3.5-turbo-instruct"),
+    Row::Retired("text-davinci-002", "2024-01-04", "gpt-3.5-turbo-instruct"),
+    Row::Retired("code-davinci-002", "2024-01-04", "gpt-3.5-turbo-instruct"),
+];
+
+/// The built-in aliases and the snapshots they point to.
+const BUILTIN_ALIASES: &[(&str, &str)] = &[
+    ("gpt-4o", "gpt-4o-2024-08-06"),
+    ("gpt-4o-mini", "gpt-4o-mini-2024-07-18"),
+    ("gpt-4.1", "gpt-4.1-2025-04-14"),
+    ("gpt-4.1-mini", "gpt-4.1-mini-2025-04-14"),
+    ("gpt-4.1-nano", "gpt-4.1-nano-2025-04-14"),
+    ("gpt-4.5-preview", "gpt-4.5-preview-2025-02-27"),
+    ("gpt-4-turbo", "gpt-4-turbo-2024-04-09"),
+    ("gpt-4-turbo-preview", "gpt-4-0125-preview"),
+    ("gpt-4", "gpt-4-0613"),
+    ("gpt-4-32k", "gpt-4-32k-0613"),
+    ("gpt-3.5-turbo", "gpt-3.5-turbo-0125"),
+    ("o1", "o1-2024-12-17"),
+    ("o1-preview", "o1-preview-2024-09-12"),
+    ("o1-mini", "o1-mini-2024-09-12"),
+    ("o3", "o3-2025-04-16"),
+    ("o3-mini", "o3-mini-2025-01-31"),
+    ("o4-mini", "o4-mini-2025-04-16"),
+];
+
+/// The lifecycle status of a model.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModelStatus {
+    /// The model is served.
+    Active,
+    /// The model is served until its shutdown date.
+    Deprecated {
+        /// The day the model stops being served, as `YYYY-MM-DD`.
+        shutdown_date: String,
+        /// The model recommended instead.
+        replacement: Option<String>,
+    },
+    /// The model is no longer served.
+    Retired {
+        /// The day the model stopped being served, as `YYYY-MM-DD`.
+        retired_on: String,
+        /// The model recommended instead.
+        replacement: Option<String>,
+    },
+    /// The model is not in the registry.
+    Unknown,
+}
+
+/// A model or family of models in a `ModelRegistry`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModelEntry {
+    /// The name of the model, or a prefix followed by `*` for a family.
+    pub pattern: String,
+    /// The status of the models matched by the pattern.
+    pub status: ModelStatus,
+}
+
+impl ModelEntry {
+    /// Creates an entry for served models.
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - The name of the model, or a prefix followed by `*`
+    pub fn active(pattern: &str) -> Self {
+        Self {
+            pattern: pattern.to_string(),
+            status: ModelStatus::Active,
+        }
+    }
+
+    /// Creates an entry for models served until a shutdown date.
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - The name of the model, or a prefix followed by `*`
+    /// * `shutdown_date` - The day the models stop being served, as `YYYY-MM-DD`
+    /// * `replacement` - The model recommended instead, if any
+    pub fn deprecated(pattern: &str, shutdown_date: &str, replacement: Option<&str>) -> Self {
+        Self {
+            pattern: pattern.to_string(),
+            status: ModelStatus::Deprecated {
+                shutdown_date: shutdown_date.to_string(),
+                replacement: replacement.map(str::to_string),
+            },
+        }
+    }
+
+    /// Creates an entry for models that are no longer served.
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - The name of the model, or a prefix followed by `*`
+    /// * `retired_on` - The day the models stopped being served, as `YYYY-MM-DD`
+    /// * `replacement` - The model recommended instead, if any
+    pub fn retired(pattern: &str, retired_on: &str, replacement: Option<&str>) -> Self {
+        Self {
+            pattern: pattern.to_string(),
+            status: ModelStatus::Retired {
+                retired_on: retired_on.to_string(),
+                replacement: replacement.map(str::to_string),
+            },
+        }
+    }
+
+    /// Returns the length of the pattern's match of a name, exact names ranking above every
+    /// prefix, or `None` if the pattern does not match.
+    fn rank(&self, name: &str) -> Option<usize> {
+        match self.pattern.strip_suffix('*') {
+            Some(prefix) => name.starts_with(prefix).then_some(prefix.len()),
+            None => (self.pattern == name).then_some(usize::MAX),
+        }
+    }
+}
+
+impl From<&Row> for ModelEntry {
+    fn from(row: &Row) -> Self {
+        match *row {
+            Row::Active(pattern) => ModelEntry::active(pattern),
+            Row::Deprecated(pattern, shutdown_date, replacement) => {
+                ModelEntry::deprecated(pattern, shutdown_date, Some(replacement))
+            }
+            Row::Retired(pattern, retired_on, replacement) => {
+                ModelEntry::retired(pattern, retired_on, Some(replacement))
+            }
+        }
+    }
+}
+
+/// What a `ModelRegistry` knows of a model name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModelInfo {
+    /// The name that was looked up.
+    pub model: String,
+    /// The snapshot the name is an alias of, or the name itself.
+    pub canonical: String,
+    /// The status of the snapshot on the day of the lookup.
+    pub status: ModelStatus,
+}
+
+/// A problem with a provider's configuration that does not prevent building it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConfigWarning {
+    /// The model is deprecated and will stop being served.
+    DeprecatedModel {
+        /// The configured model.
+        model: String,
+        /// The day the model stops being served, as `YYYY-MM-DD`.
+        shutdown_date: String,
+        /// The model recommended instead.
+        replacement: Option<String>,
+    },
+    /// The model is no longer served, and requests will fail.
+    RetiredModel {
+        /// The configured model.
+        model: String,
+        /// The day the model stopped being served, as `YYYY-MM-DD`.
+        retired_on: String,
+        /// The model recommended instead.
+        replacement: Option<String>,
+    },
+    /// The model is not in the registry, e.g. a local model or one newer than the table.
+    UnknownModel {
+        /// The configured model.
+        model: String,
+        /// The `REGISTRY_VERSION` of the built-in table.
+        registry_version: &'static str,
+    },
+}
+
+impl fmt::Display for ConfigWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigWarning::DeprecatedModel {
+                model,
+                shutdown_date,
+                replacement,
+            } => {
+                write!(
+                    f,
+                    "The model {} is deprecated and shuts down on {}",
+                    model, shutdown_date
+                )?;
+                write_replacement(f, replacement)
+            }
+            ConfigWarning::RetiredModel {
+                model,
+                retired_on,
+                replacement,
+            } => {
+                write!(f, "The model {} was retired on {}", model, retired_on)?;
+                write_replacement(f, replacement)
+            }
+            ConfigWarning::UnknownModel {
+                model,
+                registry_version,
+            } => write!(
+                f,
+                "The model {} is not in the model registry of {}",
+                model, registry_version
+            ),
+        }
+    }
+}
+
+fn write_replacement(f: &mut fmt::Formatter<'_>, replacement: &Option<String>) -> fmt::Result {
+    match replacement {
+        Some(replacement) => write!(f, ", use {} instead", replacement),
+        None => Ok(()),
+    }
+}
+
+/// The registry `OpenAILLM::new` checks its model against.
+static SHARED_REGISTRY: LazyLock<ModelRegistry> = LazyLock::new(ModelRegistry::builtin);
+
+/// A table of known models and aliases, see the module documentation.
+///
+/// Clones share the same table; strictness belongs to each handle.
+#[derive(Debug, Clone)]
+pub struct ModelRegistry {
+    strict: bool,
+    state: Arc<RwLock<RegistryState>>,
+}
+
+#[derive(Debug, Default)]
+struct RegistryState {
+    models: Vec<ModelEntry>,
+    aliases: BTreeMap<String, String>,
+}
+
+impl Default for ModelRegistry {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+impl ModelRegistry {
+    /// Creates a registry with the built-in table, `REGISTRY_VERSION`.
+    pub fn builtin() -> Self {
+        let registry: Self = Self::empty();
+        {
+            let mut state = registry.write();
+            state.models = BUILTIN_MODELS.iter().map(ModelEntry::from).collect();
+            state.aliases = BUILTIN_ALIASES
+                .iter()
+                .map(|(alias, canonical)| (alias.to_string(), canonical.to_string()))
+                .collect();
+        }
+
+        registry
+    }
+
+    /// Creates a registry without any model, to which every name is unknown.
+    pub fn empty() -> Self {
+        Self {
+            strict: false,
+            state: Arc::new(RwLock::new(RegistryState::default())),
+        }
+    }
+
+    /// Returns the registry shared by the process, which `OpenAILLM::new` checks its model
+    /// against.
+    ///
+    /// Models added to it, by `with_model`, `with_alias` or `refresh_from_api`, are known to
+    /// every provider built afterwards.
+    pub fn shared() -> Self {
+        SHARED_REGISTRY.clone()
+    }
+
+    /// Sets whether `check` fails for retired models instead of warning about them.
+    ///
+    /// # Arguments
+    ///
+    /// * `strict` - Whether retired models are refused, `false` by default
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Adds a model or family to the table, replacing the entry with the same pattern.
+    ///
+    /// # Arguments
+    ///
+    /// * `entry` - The model or family and its status
+    pub fn with_model(self, entry: ModelEntry) -> Self {
+        self.add_model(entry);
+        self
+    }
+
+    /// Adds an alias that resolves to a snapshot, replacing an alias of the same name.
+    ///
+    /// # Arguments
+    ///
+    /// * `alias` - The name the model is configured with
+    /// * `canonical` - The snapshot the alias points to
+    pub fn with_alias(self, alias: &str, canonical: &str) -> Self {
+        self.write()
+            .aliases
+            .insert(alias.to_string(), canonical.to_string());
+        self
+    }
+
+    /// Adds a model or family to the table shared by the clones of this registry, replacing
+    /// the entry with the same pattern.
+    ///
+    /// # Arguments
+    ///
+    /// * `entry` - The model or family and its status
+    pub fn add_model(&self, entry: ModelEntry) {
+        let mut state = self.write();
+        state.models.retain(|model| model.pattern != entry.pattern);
+        state.models.push(entry);
+    }
+
+    /// Returns whether the registry refuses retired models.
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Looks a model up as of today, in UTC.
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - The configured name of the model
+    pub fn lookup(&self, model: &str) -> ModelInfo {
+        self.lookup_on(model, &today())
+    }
+
+    /// Looks a model up as of a given day, on which deprecated models whose shutdown date
+    /// passed are retired.
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - The configured name of the model
+    /// * `date` - The day, as `YYYY-MM-DD`
+    pub fn lookup_on(&self, model: &str, date: &str) -> ModelInfo {
+        let state = self.read();
+        let canonical: String = state
+            .aliases
+            .get(model)
+            .cloned()
+            .unwrap_or_else(|| model.to_string());
+        let status: ModelStatus = state
+            .models
+            .iter()
+            .filter_map(|entry| entry.rank(&canonical).map(|rank| (rank, entry)))
+            .max_by_key(|(rank, _)| *rank)
+            .map(|(_, entry)| entry.status.clone())
+            .unwrap_or(ModelStatus::Unknown);
+
+        let status: ModelStatus = match status {
+            ModelStatus::Deprecated {
+                shutdown_date,
+                replacement,
+            } if shutdown_date.as_str() <= date => ModelStatus::Retired {
+                retired_on: shutdown_date,
+                replacement,
+            },
+            status => status,
+        };
+
+        ModelInfo {
+            model: model.to_string(),
+            canonical,
+            status,
+        }
+    }
+
+    /// Checks a model as of today, in UTC.
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - The configured name of the model
+    ///
+    /// # Returns
+    ///
+    /// The warnings about the model, empty for an active model
+    ///
+    /// # Errors
+    ///
+    /// Returns `SecretaryError::RetiredModel` for a retired model if the registry is strict.
+    pub fn check(&self, model: &str) -> Result<Vec<ConfigWarning>, SecretaryError> {
+        self.check_on(model, &today())
+    }
+
+    /// Checks a model as of a given day, like `check`.
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - The configured name of the model
+    /// * `date` - The day, as `YYYY-MM-DD`
+    pub fn check_on(&self, model: &str, date: &str) -> Result<Vec<ConfigWarning>, SecretaryError> {
+        let model: String = model.to_string();
+        let warning: ConfigWarning = match self.lookup_on(&model, date).status {
+            ModelStatus::Active => return Ok(Vec::new()),
+            ModelStatus::Deprecated {
+                shutdown_date,
+                replacement,
+            } => ConfigWarning::DeprecatedModel {
+                model,
+                shutdown_date,
+                replacement,
+            },
+            ModelStatus::Retired {
+                retired_on,
+                replacement,
+            } => {
+                if self.strict {
+                    return Err(SecretaryError::RetiredModel {
+                        model,
+                        retired_on,
+                        replacement,
+                    });
+                }
+                ConfigWarning::RetiredModel {
+                    model,
+                    retired_on,
+                    replacement,
+                }
+            }
+            ModelStatus::Unknown => ConfigWarning::UnknownModel {
+                model,
+                registry_version: REGISTRY_VERSION,
+            },
+        };
+
+        Ok(vec![warning])
+    }
+
+    /// Adds the models a provider lists that the registry does not know, as active models.
+    ///
+    /// The list is read from the provider's `IsLLM::get_models_url`, e.g. OpenAI's
+    /// `GET /models`. Models the registry knows keep their status, since providers keep
+    /// listing deprecated models until they are shut down.
+    ///
+    /// # Arguments
+    ///
+    /// * `llm` - The provider whose models to list
+    ///
+    /// # Returns
+    ///
+    /// The names of the added models, sorted
+    ///
+    /// # Errors
+    ///
+    /// Returns `SecretaryError::ModelListUnavailable` if the provider has no model list or
+    /// did not return one.
+    pub async fn refresh_from_api<L: IsLLM + Sync + ?Sized>(
+        &self,
+        llm: &L,
+    ) -> Result<Vec<String>, SecretaryError> {
+        let Some(url) = llm.get_models_url() else {
+            return Err(SecretaryError::ModelListUnavailable {
+                message: "the provider does not list its models".to_string(),
+            });
+        };
+        let headers: HeaderMap = llm.get_request_headers(&PreparedRequest {
+            method: "GET",
+            url: &url,
+            body: &[],
+        })?;
+        let response = llm
+            .http_client()
+            .get(&url)
+            .headers(headers)
+            .timeout(HEALTH_CHECK_TIMEOUT)
+            .send()
+            .await
+            .map_err(|error| {
+                if error.is_builder() {
+                    return SecretaryError::BuildRequestError(error.to_string());
+                }
+                SecretaryError::ModelListUnavailable {
+                    message: error.to_string(),
+                }
+            })?;
+        let status: u16 = response.status().as_u16();
+        let body: String = response.text().await.unwrap_or_default();
+        if !(200..300).contains(&status) {
+            return Err(SecretaryError::ModelListUnavailable {
+                message: format!("{} returned status {}: {}", url, status, body),
+            });
+        }
+
+        let listed: Vec<String> = serde_json::from_str::<Value>(&body)
+            .ok()
+            .and_then(|value| {
+                value["data"].as_array().map(|models| {
+                    models
+                        .iter()
+                        .filter_map(|model| model["id"].as_str().map(str::to_string))
+                        .collect()
+                })
+            })
+            .ok_or_else(|| SecretaryError::ModelListUnavailable {
+                message: format!("{} did not return a list of models: {}", url, body),
+            })?;
+
+        let date: String = today();
+        let mut added: Vec<String> = listed
+            .into_iter()
+            .filter(|model| self.lookup_on(model, &date).status == ModelStatus::Unknown)
+            .collect();
+        added.sort();
+        added.dedup();
+        for model in &added {
+            self.add_model(ModelEntry::active(model));
+        }
+
+        Ok(added)
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, RegistryState> {
+        self.state.read().unwrap_or_else(|error| error.into_inner())
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, RegistryState> {
+        self.state
+            .write()
+            .unwrap_or_else(|error| error.into_inner())
+    }
+}
+
+/// Returns today's date in UTC as `YYYY-MM-DD`.
+fn today() -> String {
+    builtin_template_vars(SystemTime::now())
+        .remove("today")
+        .unwrap_or_default()
+}
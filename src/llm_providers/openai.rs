@@ -1,9 +1,35 @@
+use std::sync::Arc;
+
 use serde_json::{Value, json};
 
 use crate::{
+    SecretaryError,
     constants::OPENAI_CHAT_COMPLETION_ROUTE,
-    message::Message,
+    credentials::ApiKey,
+    deadletter::DeadLetterSink,
+    decoding::DecodingPolicy,
+    estimate::Pricing,
+    guardrail::Guardrail,
+    lazy::LazySource,
+    leniency::LeniencyProfile,
+    limits::OutputLimits,
+    llm_providers::{
+        capabilities::ProviderCapabilities,
+        env::{DEFAULT_PREFIX, openai_from_env},
+        health::HealthProbe,
+        http::{CompressionConfig, HttpClients, PoolConfig},
+        json_mode::{JsonMode, JsonModeStrategy},
+        models::{ConfigWarning, ModelRegistry},
+        queue::RequestQueue,
+        quota::QuotaPartitioner,
+        rate_limit::RetryPolicy,
+    },
+    locale::ExtractionLocale,
+    message::{Message, SystemRoleStrategy},
+    metrics::{MetricsSink, NoopSink},
     traits::{AsyncGenerateData, GenerateData, IsLLM},
+    trimming::UnknownKeys,
+    vocabulary::OneOfPolicy,
 };
 
 /// Represents a Large Language Model (LLM) that is compatible with OpenAI API.
@@ -11,13 +37,37 @@ use crate::{
 #[derive(Debug, Clone)]
 pub struct OpenAILLM {
     model: String,
-    api_key: String,
+    api_key: ApiKey,
     api_base: String,
+    capabilities: ProviderCapabilities,
+    leniency: LeniencyProfile,
+    metrics_sink: Arc<dyn MetricsSink>,
+    json_mode: JsonMode,
+    retry_policy: RetryPolicy,
+    http_clients: HttpClients,
+    extra_body: Option<Value>,
+    request_queue: Option<RequestQueue>,
+    quota_partitioner: Option<QuotaPartitioner>,
+    guardrail: Option<Guardrail>,
+    output_limits: Option<OutputLimits>,
+    decoding_policy: DecodingPolicy,
+    unknown_keys: UnknownKeys,
+    one_of_policy: OneOfPolicy,
+    locale: Option<ExtractionLocale>,
+    system_role_strategy: Option<SystemRoleStrategy>,
+    pricing: Option<Pricing>,
+    dead_letter_sink: Option<Arc<dyn DeadLetterSink>>,
+    traced_errors: bool,
+    config_warnings: Vec<ConfigWarning>,
 }
 
 impl OpenAILLM {
     /// Creates a new instance of the LLM struct.
     ///
+    /// The model is checked against `ModelRegistry::shared()`, and the warnings about it, e.g.
+    /// that it is deprecated, are returned by `config_warnings`, see the
+    /// `llm_providers::models` module.
+    ///
     /// # Arguments
     ///
     /// * `api_base` - A string slice that holds the base URL for the OpenAI API.
@@ -32,23 +82,440 @@ impl OpenAILLM {
         api_key: &str,
         model: &str,
     ) -> Result<Self, Box<dyn std::error::Error + Send + Sync + 'static>> {
-        Ok(Self {
+        let (llm, _) = Self::new_with_registry(api_base, api_key, model, &ModelRegistry::shared())?;
+        Ok(llm)
+    }
+
+    /// Creates a provider whose model is checked against the given registry.
+    ///
+    /// # Arguments
+    ///
+    /// * `api_base` - The base URL of the API, e.g. `https://api.openai.com/v1`
+    /// * `api_key` - The API key
+    /// * `model` - The model to use
+    /// * `registry` - The models the configured one is looked up in
+    ///
+    /// # Returns
+    ///
+    /// The provider and the warnings about its model, which `config_warnings` returns too
+    ///
+    /// # Errors
+    ///
+    /// Returns `SecretaryError::RetiredModel` if the model is retired and the registry is
+    /// strict.
+    pub fn new_with_registry(
+        api_base: &str,
+        api_key: &str,
+        model: &str,
+        registry: &ModelRegistry,
+    ) -> Result<(Self, Vec<ConfigWarning>), SecretaryError> {
+        let config_warnings: Vec<ConfigWarning> = registry.check(model)?;
+        let llm: Self = Self {
             model: model.to_string(),
             api_base: api_base.to_string(),
-            api_key: api_key.to_string(),
-        })
+            api_key: ApiKey::new(api_key),
+            capabilities: ProviderCapabilities::default(),
+            leniency: LeniencyProfile::default(),
+            metrics_sink: Arc::new(NoopSink),
+            json_mode: JsonMode::default(),
+            retry_policy: RetryPolicy::default(),
+            http_clients: HttpClients::default(),
+            extra_body: None,
+            request_queue: None,
+            quota_partitioner: None,
+            guardrail: None,
+            output_limits: None,
+            decoding_policy: DecodingPolicy::default(),
+            unknown_keys: UnknownKeys::default(),
+            one_of_policy: OneOfPolicy::default(),
+            locale: None,
+            system_role_strategy: None,
+            pricing: None,
+            dead_letter_sink: None,
+            traced_errors: false,
+            config_warnings: config_warnings.clone(),
+        };
+
+        Ok((llm, config_warnings))
+    }
+
+    /// Creates a provider from the `SECRETARY_OPENAI_*` environment variables, see the
+    /// `llm_providers::env` module.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SecretaryError::MissingConfiguration` with every missing or malformed variable.
+    pub fn from_env() -> Result<Self, SecretaryError> {
+        Self::from_env_with_prefix(DEFAULT_PREFIX)
+    }
+
+    /// Creates a provider from the environment variables with the given prefix, like
+    /// `from_env`.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - The prefix of the variables, e.g. `BILLING` for `BILLING_OPENAI_API_KEY`
+    pub fn from_env_with_prefix(prefix: &str) -> Result<Self, SecretaryError> {
+        Ok(openai_from_env(prefix)?.build())
+    }
+
+    /// Declares the capabilities of the configured model.
+    ///
+    /// # Arguments
+    ///
+    /// * `capabilities` - What the model can handle, such as its context window
+    pub fn with_capabilities(mut self, capabilities: ProviderCapabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Sets how leniently the model's output is coerced before deserialization.
+    ///
+    /// # Arguments
+    ///
+    /// * `leniency` - The coercions to apply, `LeniencyProfile::strict()` by default
+    pub fn with_leniency(mut self, leniency: LeniencyProfile) -> Self {
+        self.leniency = leniency;
+        self
+    }
+
+    /// Sets the sink that receives metric events for requests and parse failures.
+    ///
+    /// # Arguments
+    ///
+    /// * `metrics_sink` - The sink to record events to, a `NoopSink` by default
+    pub fn with_metrics_sink(mut self, metrics_sink: Arc<dyn MetricsSink>) -> Self {
+        self.metrics_sink = metrics_sink;
+        self
+    }
+
+    /// Sets how JSON output is requested from the model.
+    ///
+    /// Use `JsonModeStrategy::PromptOnly` for servers that reject `response_format`, or
+    /// `JsonModeStrategy::Auto` to detect them on the first JSON request.
+    ///
+    /// # Arguments
+    ///
+    /// * `strategy` - The JSON mode strategy, `JsonModeStrategy::Native` by default
+    pub fn with_json_mode_strategy(mut self, strategy: JsonModeStrategy) -> Self {
+        self.json_mode = JsonMode::new(strategy);
+        self
+    }
+
+    /// Sets how requests throttled with a 429 are retried.
+    ///
+    /// The wait the server asks for in its rate-limit headers is preferred over the policy's
+    /// exponential backoff.
+    ///
+    /// # Arguments
+    ///
+    /// * `retry_policy` - The retry policy, `RetryPolicy::NONE` by default
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Sets the connection pool settings of the provider's HTTP clients.
+    ///
+    /// The provider and its clones share pooled connections. Calling this gives the provider
+    /// new, empty pools that are no longer shared with earlier clones.
+    ///
+    /// # Arguments
+    ///
+    /// * `pool_config` - The pool settings, `reqwest` defaults when unset
+    pub fn with_pool_config(mut self, pool_config: PoolConfig) -> Self {
+        self.http_clients =
+            HttpClients::new(pool_config).with_compression(self.http_clients.compression());
+        self
+    }
+
+    /// Sets how request and response bodies are compressed, see the `http` module.
+    ///
+    /// Like `with_pool_config`, this gives the provider new, empty pools.
+    ///
+    /// # Arguments
+    ///
+    /// * `compression` - Response decompression and the request compression threshold
+    pub fn with_compression(mut self, compression: CompressionConfig) -> Self {
+        self.http_clients = self.http_clients.with_compression(compression);
+        self
+    }
+
+    /// Sets extra JSON to deep-merge into every request body, for vendor extensions such
+    /// as `{"reasoning_effort": "low"}`.
+    ///
+    /// Values passed per call through `RequestOptions::with_extra_body` take precedence. The
+    /// request's `messages` are never replaced.
+    ///
+    /// # Arguments
+    ///
+    /// * `extra_body` - A JSON object merged over the body built by `get_request_body`
+    pub fn with_extra_body(mut self, extra_body: Value) -> Self {
+        self.extra_body = Some(extra_body);
+        self
+    }
+
+    /// Sends the provider's requests through a queue that caps the requests in flight and
+    /// admits them by priority, see the `queue` module.
+    ///
+    /// Clones of the queue share it, so the same queue can be given to several providers.
+    ///
+    /// # Arguments
+    ///
+    /// * `request_queue` - The queue, `None` by default
+    pub fn with_request_queue(mut self, request_queue: RequestQueue) -> Self {
+        self.request_queue = Some(request_queue);
+        self
+    }
+
+    /// Splits the provider's requests per window between tenants by weight, see the `quota`
+    /// module.
+    ///
+    /// Clones of the partitioner share it, so the same partitioner can be given to several
+    /// providers.
+    ///
+    /// # Arguments
+    ///
+    /// * `quota_partitioner` - The partitioner, `None` by default
+    pub fn with_quota_partitioner(mut self, quota_partitioner: QuotaPartitioner) -> Self {
+        self.quota_partitioner = Some(quota_partitioner);
+        self
+    }
+
+    /// Runs a guardrail against prompt injection over every target before it is sent, see
+    /// the `guardrail` module.
+    ///
+    /// # Arguments
+    ///
+    /// * `guardrail` - The detector policy and prompt hardening, `None` by default
+    pub fn with_guardrail(mut self, guardrail: Guardrail) -> Self {
+        self.guardrail = Some(guardrail);
+        self
+    }
+
+    /// Limits the size of the model's output, see the `limits` module.
+    ///
+    /// # Arguments
+    ///
+    /// * `output_limits` - The limits, `None` by default
+    pub fn with_output_limits(mut self, output_limits: OutputLimits) -> Self {
+        self.output_limits = Some(output_limits);
+        self
+    }
+
+    /// Sets how responses that are not clean UTF-8 JSON are handled, see the `decoding`
+    /// module.
+    ///
+    /// # Arguments
+    ///
+    /// * `decoding_policy` - The policy, `DecodingPolicy::Repair` by default
+    pub fn with_decoding_policy(mut self, decoding_policy: DecodingPolicy) -> Self {
+        self.decoding_policy = decoding_policy;
+        self
+    }
+
+    /// Sets what happens to keys of the model's JSON that are not fields of the Task, see
+    /// the `trimming` module.
+    ///
+    /// # Arguments
+    ///
+    /// * `unknown_keys` - The policy, `UnknownKeys::Keep` by default
+    pub fn with_unknown_keys(mut self, unknown_keys: UnknownKeys) -> Self {
+        self.unknown_keys = unknown_keys;
+        self
+    }
+
+    /// Sets what happens to answers of `one_of` fields that match no allowed value, see the
+    /// `vocabulary` module.
+    ///
+    /// # Arguments
+    ///
+    /// * `one_of_policy` - The policy, `OneOfPolicy::Error` by default
+    pub fn with_one_of_policy(mut self, one_of_policy: OneOfPolicy) -> Self {
+        self.one_of_policy = one_of_policy;
+        self
+    }
+
+    /// Sets the number and date conventions of the targets, see the `locale` module.
+    ///
+    /// # Arguments
+    ///
+    /// * `locale` - The conventions, e.g. `ExtractionLocale::de_de()`
+    pub fn with_locale(mut self, locale: ExtractionLocale) -> Self {
+        self.locale = Some(locale);
+        self
+    }
+
+    /// Sets how system messages are sent to the model, instead of the strategy chosen from
+    /// the model name, see `SystemRoleStrategy`.
+    ///
+    /// # Arguments
+    ///
+    /// * `system_role_strategy` - The strategy
+    pub fn with_system_role_strategy(mut self, system_role_strategy: SystemRoleStrategy) -> Self {
+        self.system_role_strategy = Some(system_role_strategy);
+        self
+    }
+
+    /// Sets the price of the model's prompt tokens, which `plan` estimates the cost of an
+    /// extraction with, see the `estimate` module.
+    ///
+    /// # Arguments
+    ///
+    /// * `pricing` - The pricing
+    pub fn with_pricing(mut self, pricing: Pricing) -> Self {
+        self.pricing = Some(pricing);
+        self
+    }
+
+    /// Captures every failed extraction to a sink, see the `deadletter` module.
+    ///
+    /// # Arguments
+    ///
+    /// * `dead_letter_sink` - The sink to hand failures to, `None` by default
+    pub fn with_dead_letter_sink(mut self, dead_letter_sink: Arc<dyn DeadLetterSink>) -> Self {
+        self.dead_letter_sink = Some(dead_letter_sink);
+        self
+    }
+
+    /// Wraps the errors of failed extractions in a `TracedError` that carries their request
+    /// IDs, see the `correlation` module.
+    ///
+    /// # Arguments
+    ///
+    /// * `traced_errors` - Whether to wrap errors, `false` by default so errors can be
+    ///   downcast to `SecretaryError`
+    pub fn with_traced_errors(mut self, traced_errors: bool) -> Self {
+        self.traced_errors = traced_errors;
+        self
+    }
+
+    /// Replaces the API key for every request built from now on, see the `credentials`
+    /// module.
+    ///
+    /// Requests already sent finish with the old key. Clones of the provider share the key,
+    /// so they use the new one as well.
+    ///
+    /// # Arguments
+    ///
+    /// * `api_key` - The new API key
+    pub fn rotate_api_key(&self, api_key: &str) {
+        self.api_key.rotate(api_key);
+    }
+
+    /// Returns the warnings about the provider's configuration found when it was created,
+    /// such as a deprecated or unknown model, see the `llm_providers::models` module.
+    pub fn config_warnings(&self) -> &[ConfigWarning] {
+        &self.config_warnings
     }
 }
 
 impl IsLLM for OpenAILLM {
     fn get_authorization_credentials(&self) -> String {
-        format!("Bearer {}", self.api_key)
+        format!("Bearer {}", self.api_key.get())
     }
 
     fn get_model_ref(&self) -> &str {
         &self.model
     }
 
+    fn get_capabilities(&self) -> ProviderCapabilities {
+        self.capabilities.clone()
+    }
+
+    fn get_leniency(&self) -> &LeniencyProfile {
+        &self.leniency
+    }
+
+    fn get_metrics_sink(&self) -> &dyn MetricsSink {
+        self.metrics_sink.as_ref()
+    }
+
+    fn get_json_mode(&self) -> &JsonMode {
+        &self.json_mode
+    }
+
+    fn get_retry_policy(&self) -> RetryPolicy {
+        self.retry_policy
+    }
+
+    fn http_client(&self) -> &reqwest::Client {
+        self.http_clients.client()
+    }
+
+    fn blocking_http_client(&self) -> &reqwest::blocking::Client {
+        self.http_clients.blocking_client()
+    }
+
+    fn get_http_clients(&self) -> &HttpClients {
+        &self.http_clients
+    }
+
+    fn get_extra_body(&self) -> Option<&Value> {
+        self.extra_body.as_ref()
+    }
+
+    fn get_request_queue(&self) -> Option<&RequestQueue> {
+        self.request_queue.as_ref()
+    }
+
+    fn get_quota_partitioner(&self) -> Option<&QuotaPartitioner> {
+        self.quota_partitioner.as_ref()
+    }
+
+    fn get_guardrail(&self) -> Option<&Guardrail> {
+        self.guardrail.as_ref()
+    }
+
+    fn get_output_limits(&self) -> Option<&OutputLimits> {
+        self.output_limits.as_ref()
+    }
+
+    fn get_decoding_policy(&self) -> DecodingPolicy {
+        self.decoding_policy
+    }
+
+    fn get_unknown_keys(&self) -> UnknownKeys {
+        self.unknown_keys
+    }
+
+    fn get_one_of_policy(&self) -> OneOfPolicy {
+        self.one_of_policy
+    }
+
+    fn get_locale(&self) -> Option<&ExtractionLocale> {
+        self.locale.as_ref()
+    }
+
+    fn get_system_role_strategy(&self) -> SystemRoleStrategy {
+        self.system_role_strategy
+            .unwrap_or_else(|| SystemRoleStrategy::for_model(self.get_model_ref()))
+    }
+
+    fn get_pricing(&self) -> Option<Pricing> {
+        self.pricing
+    }
+
+    fn get_dead_letter_sink(&self) -> Option<&dyn DeadLetterSink> {
+        self.dead_letter_sink.as_deref()
+    }
+
+    fn get_traced_errors(&self) -> bool {
+        self.traced_errors
+    }
+
+    fn get_lazy_source(&self) -> Option<Arc<dyn LazySource>> {
+        Some(Arc::new(self.clone()))
+    }
+
+    fn get_health_probe(&self) -> HealthProbe {
+        HealthProbe::ModelLookup(format!("{}/models/{}", self.api_base, self.model))
+    }
+
+    fn get_models_url(&self) -> Option<String> {
+        Some(format!("{}/models", self.api_base))
+    }
+
     fn get_chat_completion_request_url(&self) -> String {
         format!("{}{}", self.api_base, OPENAI_CHAT_COMPLETION_ROUTE)
     }
@@ -64,15 +531,18 @@ impl IsLLM for OpenAILLM {
             );
         }
 
-        return json!(
+        json!(
             {
                 "model": self.get_model_ref(),
                 "messages": [message],
             }
-        );
+        )
     }
 }
 
 impl GenerateData for OpenAILLM {}
 
 impl AsyncGenerateData for OpenAILLM {}
+
+#[cfg(feature = "local")]
+impl crate::traits::AsyncGenerateDataLocal for OpenAILLM {}
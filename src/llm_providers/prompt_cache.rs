@@ -0,0 +1,78 @@
+//! Provider prompt caching.
+//!
+//! Single-request extraction sends the prompt as two messages: a prefix holding the system
+//! prompt and the additional instructions, and a trailing message holding the target text.
+//! The prefix depends only on the Task and the instructions, so it is byte-identical across
+//! targets and can be served from the provider's prompt cache.
+//!
+//! OpenAI caches stable prefixes automatically. Anthropic-style endpoints only cache content
+//! marked with `cache_control`, which is added to the prefix when the provider declares
+//! `PromptCaching::CacheControl` in its `ProviderCapabilities`. The cached tokens reported in
+//! the response usage are available as `GenerationMetadata::cached_prompt_tokens`.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use secretary::Task;
+//! use secretary::llm_providers::capabilities::ProviderCapabilities;
+//! use secretary::llm_providers::openai::OpenAILLM;
+//! use secretary::llm_providers::prompt_cache::PromptCaching;
+//! use secretary::traits::GenerateData;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! struct Person {
+//!     #[task(instruction = "Extract the name")]
+//!     pub name: String,
+//! }
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+//! // An Anthropic-style endpoint that only caches marked content
+//! let llm = OpenAILLM::new("https://gateway.example.com/v1", "your-api-key", "claude-model")?
+//!     .with_capabilities(ProviderCapabilities::default().with_prompt_caching(PromptCaching::CacheControl));
+//! let task = Person::new();
+//! let instructions = vec!["Use the full name".to_string()];
+//!
+//! // The second request reuses the cached prefix of the first
+//! for target in ["Ada Lovelace", "Grace Hopper"] {
+//!     let result = llm.generate_data_adaptive(&task, target, &instructions)?;
+//!     println!("{:?}, cached tokens: {:?}", result.data, result.metadata.cached_prompt_tokens);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use serde_json::{Value, json};
+
+/// How a provider caches the stable prefix of a prompt.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PromptCaching {
+    /// The provider caches stable prefixes without any markers, as OpenAI does.
+    #[default]
+    Automatic,
+    /// The provider caches content marked with `cache_control`, as Anthropic does.
+    CacheControl,
+}
+
+/// Marks the last prefix message of a request body with an ephemeral `cache_control`.
+///
+/// The prefix is every message but the last one. The marked message's content is turned into
+/// a single text part, since the marker is set per content part. Bodies with a single message
+/// have no prefix and are left unchanged.
+pub(crate) fn annotate_cache_control(body: &mut Value) {
+    let Some(messages) = body["messages"].as_array_mut() else {
+        return;
+    };
+    if messages.len() < 2 {
+        return;
+    }
+
+    let prefix_index: usize = messages.len() - 2;
+    if let Some(text) = messages[prefix_index]["content"].as_str() {
+        messages[prefix_index]["content"] = json!([{
+            "type": "text",
+            "text": text,
+            "cache_control": {"type": "ephemeral"}
+        }]);
+    }
+}
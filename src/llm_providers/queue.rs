@@ -0,0 +1,351 @@
+//! A priority queue for requests, shared across extraction calls.
+//!
+//! Without coordination, a backfill that queues hundreds of calls on a provider delays an
+//! interactive request until the backlog ahead of it has been sent. A `RequestQueue` caps
+//! the number of requests in flight and admits waiting requests by `Priority`: an
+//! `Interactive` request goes before every waiting `Normal` and `Bulk` one, and requests of
+//! the same priority go in the order they arrived.
+//!
+//! Attach a queue with a provider's `with_request_queue`; clones of the queue share it, so
+//! one queue can cap several providers together. The priority of a call is set with
+//! `RequestOptions::with_priority` and applies to every request of the call, including the
+//! field requests of distributed generation. Blocking calls wait on the queue's condition
+//! variable and async calls are parked until they are admitted, so both can share a queue.
+//! A call's deadline also bounds its wait in the queue. Every wait is reported to the
+//! provider's `MetricsSink` as `MetricEvent::RequestQueued` and `MetricEvent::RequestDequeued`.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use std::time::Duration;
+//!
+//! use secretary::llm_providers::queue::{Priority, RequestQueue};
+//!
+//! let queue = RequestQueue::new(1);
+//! let running = queue.acquire(Priority::Bulk);
+//!
+//! // While the slot is taken, a bulk request waits...
+//! let bulk_queue = queue.clone();
+//! let bulk = std::thread::spawn(move || {
+//!     let _permit = bulk_queue.acquire(Priority::Bulk);
+//!     std::time::Instant::now()
+//! });
+//! while queue.depth(Priority::Bulk) == 0 {
+//!     std::thread::yield_now();
+//! }
+//!
+//! // ...and an interactive request that arrives later is admitted first
+//! let interactive_queue = queue.clone();
+//! let interactive = std::thread::spawn(move || {
+//!     let _permit = interactive_queue.acquire(Priority::Interactive);
+//!     std::thread::sleep(Duration::from_millis(20));
+//!     std::time::Instant::now()
+//! });
+//! while queue.depth(Priority::Interactive) == 0 {
+//!     std::thread::yield_now();
+//! }
+//!
+//! drop(running);
+//! assert!(interactive.join().unwrap() < bulk.join().unwrap());
+//! assert_eq!(queue.running(), 0);
+//!
+//! // Waiting can be bounded
+//! let _held = queue.acquire(Priority::Normal);
+//! assert!(queue.acquire_timeout(Priority::Interactive, Duration::from_millis(10)).is_none());
+//! assert_eq!(queue.depth(Priority::Interactive), 0);
+//! ```
+
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+/// How urgently a request is sent when it has to wait in a `RequestQueue`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+pub enum Priority {
+    /// A request someone is waiting for, admitted before all others.
+    Interactive,
+    /// A regular request. This is the default.
+    #[default]
+    Normal,
+    /// Background work such as a backfill, admitted when nothing else is waiting.
+    Bulk,
+}
+
+impl Priority {
+    /// Every priority, from the most to the least urgent.
+    pub const ALL: [Priority; 3] = [Priority::Interactive, Priority::Normal, Priority::Bulk];
+
+    /// Returns a stable snake_case name for the priority, suitable as a metric label.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Priority::Interactive => "interactive",
+            Priority::Normal => "normal",
+            Priority::Bulk => "bulk",
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Caps the requests in flight and admits waiting requests by priority.
+///
+/// Clones share the same queue. See the module documentation.
+#[derive(Debug, Clone)]
+pub struct RequestQueue {
+    shared: Arc<Shared>,
+}
+
+#[derive(Debug)]
+struct Shared {
+    max_concurrency: usize,
+    state: Mutex<State>,
+    admitted: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    running: usize,
+    next_ticket: u64,
+    waiting: [VecDeque<Ticket>; 3],
+}
+
+/// A request waiting in the queue.
+#[derive(Debug)]
+struct Ticket {
+    id: u64,
+    waker: Option<Waker>,
+}
+
+impl State {
+    /// Returns the ticket admitted next: the oldest of the most urgent priority.
+    fn head_mut(&mut self) -> Option<&mut Ticket> {
+        self.waiting
+            .iter_mut()
+            .find_map(|tickets| tickets.front_mut())
+    }
+
+    /// Admits the ticket if it is next and a slot is free.
+    fn try_admit(&mut self, max_concurrency: usize, priority: Priority, id: u64) -> bool {
+        if self.running >= max_concurrency || self.head_mut().map(|ticket| ticket.id) != Some(id) {
+            return false;
+        }
+
+        self.waiting[priority.index()].pop_front();
+        self.running += 1;
+        true
+    }
+
+    /// Removes a ticket that gave up waiting.
+    fn remove(&mut self, priority: Priority, id: u64) {
+        self.waiting[priority.index()].retain(|ticket| ticket.id != id);
+    }
+
+    /// Wakes the async waiter that is admitted next, if it is parked.
+    fn wake_head(&mut self) {
+        if let Some(waker) = self.head_mut().and_then(|ticket| ticket.waker.take()) {
+            waker.wake();
+        }
+    }
+}
+
+impl RequestQueue {
+    /// Creates a queue that lets at most `max_concurrency` requests run at once.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_concurrency` - The number of requests in flight, at least 1
+    pub fn new(max_concurrency: usize) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                max_concurrency: max_concurrency.max(1),
+                state: Mutex::new(State::default()),
+                admitted: Condvar::new(),
+            }),
+        }
+    }
+
+    /// Returns the number of requests that may run at once.
+    pub fn max_concurrency(&self) -> usize {
+        self.shared.max_concurrency
+    }
+
+    /// Returns the number of requests holding a slot.
+    pub fn running(&self) -> usize {
+        self.lock().running
+    }
+
+    /// Returns the number of requests waiting with the given priority.
+    pub fn depth(&self, priority: Priority) -> usize {
+        self.lock().waiting[priority.index()].len()
+    }
+
+    /// Blocks until a slot is free and no more urgent or older request is waiting.
+    pub fn acquire(&self, priority: Priority) -> QueuePermit {
+        self.acquire_until(priority, None)
+            .expect("waiting without a timeout always ends with a permit")
+    }
+
+    /// Blocks like `acquire`, but gives up after `timeout`.
+    ///
+    /// # Returns
+    ///
+    /// The permit, or `None` if the request was not admitted in time
+    pub fn acquire_timeout(&self, priority: Priority, timeout: Duration) -> Option<QueuePermit> {
+        self.acquire_until(priority, Some(Instant::now() + timeout))
+    }
+
+    /// Returns a future that resolves to a permit once the request is admitted.
+    ///
+    /// Dropping the future before it resolves leaves the queue.
+    pub fn async_acquire(&self, priority: Priority) -> Acquire {
+        Acquire {
+            queue: self.clone(),
+            priority,
+            ticket: None,
+            started: Instant::now(),
+        }
+    }
+
+    fn acquire_until(&self, priority: Priority, until: Option<Instant>) -> Option<QueuePermit> {
+        let started: Instant = Instant::now();
+        let mut state = self.lock();
+        let id: u64 = enqueue(&mut state, priority, None);
+
+        loop {
+            if state.try_admit(self.shared.max_concurrency, priority, id) {
+                self.notify(&mut state);
+                return Some(self.permit(started));
+            }
+
+            state = match until {
+                None => self
+                    .shared
+                    .admitted
+                    .wait(state)
+                    .unwrap_or_else(|error| error.into_inner()),
+                Some(until) => {
+                    let now: Instant = Instant::now();
+                    if now >= until {
+                        state.remove(priority, id);
+                        self.notify(&mut state);
+                        return None;
+                    }
+                    self.shared
+                        .admitted
+                        .wait_timeout(state, until - now)
+                        .unwrap_or_else(|error| error.into_inner())
+                        .0
+                }
+            };
+        }
+    }
+
+    fn permit(&self, started: Instant) -> QueuePermit {
+        QueuePermit {
+            queue: self.clone(),
+            waited: started.elapsed(),
+        }
+    }
+
+    /// Lets the next waiter check whether it is admitted now.
+    fn notify(&self, state: &mut State) {
+        state.wake_head();
+        self.shared.admitted.notify_all();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.shared
+            .state
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+    }
+}
+
+/// Adds a ticket to the end of its priority's line and returns its id.
+fn enqueue(state: &mut State, priority: Priority, waker: Option<Waker>) -> u64 {
+    let id: u64 = state.next_ticket;
+    state.next_ticket += 1;
+    state.waiting[priority.index()].push_back(Ticket { id, waker });
+    id
+}
+
+/// A slot in a `RequestQueue`, released when dropped.
+#[derive(Debug)]
+pub struct QueuePermit {
+    queue: RequestQueue,
+    waited: Duration,
+}
+
+impl QueuePermit {
+    /// Returns how long the request waited before it was admitted.
+    pub fn waited(&self) -> Duration {
+        self.waited
+    }
+}
+
+impl Drop for QueuePermit {
+    fn drop(&mut self) {
+        let mut state = self.queue.lock();
+        state.running -= 1;
+        self.queue.notify(&mut state);
+    }
+}
+
+/// The future returned by `RequestQueue::async_acquire`.
+#[derive(Debug)]
+pub struct Acquire {
+    queue: RequestQueue,
+    priority: Priority,
+    ticket: Option<u64>,
+    started: Instant,
+}
+
+impl Future for Acquire {
+    type Output = QueuePermit;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<QueuePermit> {
+        let queue: RequestQueue = self.queue.clone();
+        let priority: Priority = self.priority;
+        let mut state = queue.lock();
+
+        let id: u64 = match self.ticket {
+            Some(id) => id,
+            None => {
+                let id: u64 = enqueue(&mut state, priority, None);
+                self.ticket = Some(id);
+                id
+            }
+        };
+
+        if state.try_admit(queue.shared.max_concurrency, priority, id) {
+            self.ticket = None;
+            queue.notify(&mut state);
+            return Poll::Ready(queue.permit(self.started));
+        }
+
+        if let Some(ticket) = state.waiting[priority.index()]
+            .iter_mut()
+            .find(|ticket| ticket.id == id)
+        {
+            ticket.waker = Some(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for Acquire {
+    fn drop(&mut self) {
+        if let Some(id) = self.ticket {
+            let mut state = self.queue.lock();
+            state.remove(self.priority, id);
+            self.queue.notify(&mut state);
+        }
+    }
+}
@@ -0,0 +1,577 @@
+//! Partitions a provider's request quota between tenants.
+//!
+//! A multi-tenant service that shares one API key also shares its rate limit, so a tenant
+//! running a backfill can use the whole quota and leave nothing to the others. A
+//! `QuotaPartitioner` admits at most `max_requests` requests per sliding `window` and splits
+//! them between tenants by weight: a tenant of weight 3 gets three times the requests of a
+//! tenant of weight 1 while both have work waiting.
+//!
+//! * In `QuotaMode::WorkConserving`, the default, the quota is split between the tenants
+//!   active in the window, those that sent or are waiting to send a request. A tenant may use
+//!   the capacity the others leave idle, but once another tenant below its share is waiting,
+//!   it waits until that tenant caught up.
+//! * In `QuotaMode::Isolated`, the quota is split between every configured tenant, active or
+//!   not, and every other tenant that sent a request, and a tenant never uses more than its
+//!   share, even when the rest is idle.
+//!
+//! A `TenantQuota` may also set a hard cap on a tenant's requests per window. A request over
+//! the cap is not queued but fails at once with `SecretaryError::TenantQuotaExceeded`.
+//! Tenants that are not configured get the default quota, of weight 1 and without a cap.
+//!
+//! Attach a partitioner with a provider's `with_quota_partitioner`; clones share it, so one
+//! partitioner can split the quota of several providers using the same key. The tenant of a
+//! call is set with `RequestOptions::with_tenant`, or else is the tenant of its
+//! `Credentials`, or `default`. A throttled request sleeps until it is admitted, bounded by
+//! the call's deadline, and every wait is reported to the provider's `MetricsSink` as
+//! `MetricEvent::TenantThrottled`. `usage` reports what each tenant consumed, and
+//! `is_waiting` whether it is waiting for its share.
+//!
+//! The decisions are made by `admit_at` at the instant it is given, so the scheduling can be
+//! checked with a simulated clock.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use std::time::{Duration, Instant};
+//!
+//! use secretary::llm_providers::quota::{Admission, QuotaPartitioner, TenantQuota};
+//!
+//! let partitioner = QuotaPartitioner::new(4, Duration::from_secs(60))
+//!     .with_tenant("search", TenantQuota::weighted(3.0))
+//!     .with_tenant("backfill", TenantQuota::weighted(1.0).with_max_requests(2));
+//! let start = Instant::now();
+//!
+//! // Alone, the backfill uses the idle capacity, up to its cap
+//! assert_eq!(partitioner.admit_at("backfill", start), Admission::Admitted);
+//! assert_eq!(partitioner.admit_at("backfill", start), Admission::Admitted);
+//! assert_eq!(
+//!     partitioner.admit_at("backfill", start),
+//!     Admission::Rejected { max_requests: 2 }
+//! );
+//!
+//! // The rest goes to the search tenant, then both wait for the window to slide
+//! assert_eq!(partitioner.admit_at("search", start), Admission::Admitted);
+//! assert_eq!(partitioner.admit_at("search", start), Admission::Admitted);
+//! assert_eq!(
+//!     partitioner.admit_at("search", start),
+//!     Admission::Wait(Duration::from_secs(60))
+//! );
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::SecretaryError;
+
+/// The tenant of calls that name none.
+pub const DEFAULT_TENANT: &str = "default";
+
+/// How the quota is split between tenants, see the module documentation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum QuotaMode {
+    /// Splits the quota between the active tenants and lends idle capacity.
+    #[default]
+    WorkConserving,
+    /// Splits the quota between every configured tenant and never lends it.
+    Isolated,
+}
+
+/// The share of a tenant in a `QuotaPartitioner`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TenantQuota {
+    /// The weight of the tenant, relative to the others. Weights of 0 or less count as 0.
+    pub weight: f64,
+    /// The most requests of the tenant per window, whatever its share. Uncapped when unset.
+    pub max_requests: Option<usize>,
+}
+
+impl Default for TenantQuota {
+    fn default() -> Self {
+        Self {
+            weight: 1.0,
+            max_requests: None,
+        }
+    }
+}
+
+impl TenantQuota {
+    /// Creates a quota of the given weight, without a cap.
+    ///
+    /// # Arguments
+    ///
+    /// * `weight` - The weight of the tenant, relative to the others
+    pub fn weighted(weight: f64) -> Self {
+        Self {
+            weight,
+            ..Self::default()
+        }
+    }
+
+    /// Sets the most requests of the tenant per window.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_requests` - The cap, over which requests fail with
+    ///   `SecretaryError::TenantQuotaExceeded`
+    pub fn with_max_requests(mut self, max_requests: usize) -> Self {
+        self.max_requests = Some(max_requests);
+        self
+    }
+
+    fn weight(&self) -> f64 {
+        self.weight.max(0.0)
+    }
+}
+
+/// What a `QuotaPartitioner` decided for a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Admission {
+    /// The request may be sent, and counts against the tenant's quota.
+    Admitted,
+    /// The request may be sent after the given time at the earliest.
+    Wait(Duration),
+    /// The tenant reached its hard cap for the window.
+    Rejected {
+        /// The cap of the tenant.
+        max_requests: usize,
+    },
+}
+
+/// What a tenant consumed, as reported by `QuotaPartitioner::usage`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TenantUsage {
+    /// The name of the tenant.
+    pub tenant: String,
+    /// The weight of the tenant.
+    pub weight: f64,
+    /// The requests of the tenant in the current window.
+    pub in_window: usize,
+    /// The requests of the tenant admitted since the partitioner was created.
+    pub admitted: u64,
+    /// The times the tenant started waiting, after being admitted or withdrawing.
+    pub throttled: u64,
+    /// The requests of the tenant rejected by its hard cap.
+    pub rejected: u64,
+}
+
+/// Splits the requests per window of a provider between tenants by weight.
+///
+/// Clones share the same window. See the module documentation.
+#[derive(Debug, Clone)]
+pub struct QuotaPartitioner {
+    max_requests: usize,
+    window: Duration,
+    mode: QuotaMode,
+    quotas: BTreeMap<String, TenantQuota>,
+    default_quota: TenantQuota,
+    state: Arc<Mutex<BTreeMap<String, TenantState>>>,
+}
+
+#[derive(Debug, Default)]
+struct TenantState {
+    /// When the requests in the window were admitted, oldest first.
+    admissions: VecDeque<Instant>,
+    /// When a request of the tenant was last told to wait, if none was admitted since.
+    throttled_at: Option<Instant>,
+    /// The requests of the tenant blocked in `acquire_until` after being told to wait.
+    waiting: usize,
+    admitted: u64,
+    throttled: u64,
+    rejected: u64,
+}
+
+impl QuotaPartitioner {
+    /// Creates a work-conserving partitioner without configured tenants.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_requests` - The requests admitted per window across all tenants, at least 1
+    /// * `window` - The length of the sliding window, e.g. `Duration::from_secs(60)` for a
+    ///   requests-per-minute limit
+    pub fn new(max_requests: usize, window: Duration) -> Self {
+        Self {
+            max_requests: max_requests.max(1),
+            window,
+            mode: QuotaMode::default(),
+            quotas: BTreeMap::new(),
+            default_quota: TenantQuota::default(),
+            state: Arc::new(Mutex::new(BTreeMap::new())),
+        }
+    }
+
+    /// Sets how the quota is split between tenants.
+    pub fn with_mode(mut self, mode: QuotaMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Configures the share of a tenant.
+    ///
+    /// # Arguments
+    ///
+    /// * `tenant` - The name of the tenant, as set with `RequestOptions::with_tenant`
+    /// * `quota` - Its weight and cap
+    pub fn with_tenant(mut self, tenant: &str, quota: TenantQuota) -> Self {
+        self.quotas.insert(tenant.to_string(), quota);
+        self
+    }
+
+    /// Sets the share of tenants that are not configured, of weight 1 and uncapped by
+    /// default.
+    pub fn with_default_quota(mut self, quota: TenantQuota) -> Self {
+        self.default_quota = quota;
+        self
+    }
+
+    /// Returns the requests admitted per window across all tenants.
+    pub fn max_requests(&self) -> usize {
+        self.max_requests
+    }
+
+    /// Returns the length of the sliding window.
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Returns how the quota is split between tenants.
+    pub fn mode(&self) -> QuotaMode {
+        self.mode
+    }
+
+    /// Returns the share of a tenant, the default quota if it is not configured.
+    pub fn quota(&self, tenant: &str) -> TenantQuota {
+        self.quotas
+            .get(tenant)
+            .copied()
+            .unwrap_or(self.default_quota)
+    }
+
+    /// Decides whether a request of the tenant may be sent now.
+    pub fn admit(&self, tenant: &str) -> Admission {
+        self.admit_at(tenant, Instant::now())
+    }
+
+    /// Decides whether a request of the tenant may be sent at the given instant.
+    ///
+    /// An admitted request counts against the quota from `now`, and a request told to wait
+    /// marks its tenant as waiting until one of its requests is admitted or it withdraws.
+    /// Instants must not go back between calls.
+    pub fn admit_at(&self, tenant: &str, now: Instant) -> Admission {
+        self.admit_request(tenant, now, None)
+    }
+
+    /// Returns whether a request of the tenant is blocked waiting for its share, or was told
+    /// to wait in the window and not admitted since.
+    pub fn is_waiting(&self, tenant: &str) -> bool {
+        let now: Instant = Instant::now();
+        self.lock()
+            .get(tenant)
+            .is_some_and(|state| self.waits(state, now))
+    }
+
+    /// Decides like `admit_at`, for a request that retries until it is admitted.
+    ///
+    /// `waiting` tells whether the request is counted among the waiting requests of its
+    /// tenant, which keep the tenant waiting while its other requests are admitted.
+    fn admit_request(&self, tenant: &str, now: Instant, waiting: Option<&mut bool>) -> Admission {
+        let mut tenants = self.lock();
+        for state in tenants.values_mut() {
+            state.expire(now, self.window);
+        }
+        let admission: Admission = self.decide(&tenants, tenant, now);
+
+        let state: &mut TenantState = tenants.entry(tenant.to_string()).or_default();
+        match admission {
+            Admission::Admitted => {
+                state.admissions.push_back(now);
+                state.throttled_at = None;
+                state.admitted += 1;
+            }
+            Admission::Wait(_) => {
+                if state.throttled_at.replace(now).is_none() {
+                    state.throttled += 1;
+                }
+            }
+            Admission::Rejected { .. } => state.rejected += 1,
+        }
+        if let Some(waiting) = waiting {
+            let told_to_wait: bool = matches!(admission, Admission::Wait(_));
+            if told_to_wait && !*waiting {
+                state.waiting += 1;
+            } else if !told_to_wait && *waiting {
+                state.waiting = state.waiting.saturating_sub(1);
+            }
+            *waiting = told_to_wait;
+        }
+        admission
+    }
+
+    /// Stops counting the tenant as waiting, when a throttled request gives up.
+    pub fn withdraw(&self, tenant: &str) {
+        self.withdraw_request(tenant, false);
+    }
+
+    /// Withdraws a request, which stops counting among the waiting requests of its tenant if
+    /// it was.
+    fn withdraw_request(&self, tenant: &str, waiting: bool) {
+        if let Some(state) = self.lock().get_mut(tenant) {
+            state.throttled_at = None;
+            if waiting {
+                state.waiting = state.waiting.saturating_sub(1);
+            }
+        }
+    }
+
+    /// Blocks until a request of the tenant is admitted.
+    ///
+    /// # Returns
+    ///
+    /// How long the request waited, or `SecretaryError::TenantQuotaExceeded` if the tenant
+    /// reached its hard cap. `None` if `until` passed before the request was admitted.
+    pub fn acquire_until(
+        &self,
+        tenant: &str,
+        until: Option<Instant>,
+    ) -> Result<Option<Duration>, SecretaryError> {
+        let started: Instant = Instant::now();
+        let mut request: PendingRequest<'_> = PendingRequest::new(self, tenant);
+        loop {
+            let now: Instant = Instant::now();
+            match request.admit(now) {
+                Admission::Admitted => return Ok(Some(started.elapsed())),
+                Admission::Rejected { max_requests } => {
+                    return Err(self.quota_exceeded(tenant, max_requests));
+                }
+                Admission::Wait(wait) => match until {
+                    Some(until) if now + wait > until => {
+                        std::thread::sleep(until.saturating_duration_since(now));
+                        if request.admit(Instant::now()) == Admission::Admitted {
+                            return Ok(Some(started.elapsed()));
+                        }
+                        request.withdraw();
+                        return Ok(None);
+                    }
+                    _ => std::thread::sleep(wait),
+                },
+            }
+        }
+    }
+
+    /// Waits like `acquire_until`, without blocking the thread.
+    pub async fn async_acquire_until(
+        &self,
+        tenant: &str,
+        until: Option<Instant>,
+    ) -> Result<Option<Duration>, SecretaryError> {
+        let started: Instant = Instant::now();
+        let mut request: PendingRequest<'_> = PendingRequest::new(self, tenant);
+        loop {
+            let now: Instant = Instant::now();
+            match request.admit(now) {
+                Admission::Admitted => return Ok(Some(started.elapsed())),
+                Admission::Rejected { max_requests } => {
+                    return Err(self.quota_exceeded(tenant, max_requests));
+                }
+                Admission::Wait(wait) => match until {
+                    Some(until) if now + wait > until => {
+                        futures_timer::Delay::new(until.saturating_duration_since(now)).await;
+                        if request.admit(Instant::now()) == Admission::Admitted {
+                            return Ok(Some(started.elapsed()));
+                        }
+                        request.withdraw();
+                        return Ok(None);
+                    }
+                    _ => futures_timer::Delay::new(wait).await,
+                },
+            }
+        }
+    }
+
+    /// Returns what each tenant that sent a request consumed, by name.
+    pub fn usage(&self) -> Vec<TenantUsage> {
+        let now: Instant = Instant::now();
+        let mut tenants = self.lock();
+        tenants
+            .iter_mut()
+            .map(|(tenant, state)| {
+                state.expire(now, self.window);
+                TenantUsage {
+                    tenant: tenant.clone(),
+                    weight: self.quota(tenant).weight(),
+                    in_window: state.admissions.len(),
+                    admitted: state.admitted,
+                    throttled: state.throttled,
+                    rejected: state.rejected,
+                }
+            })
+            .collect()
+    }
+
+    /// Decides for a request of the tenant, on a window already expired to `now`.
+    fn decide(
+        &self,
+        tenants: &BTreeMap<String, TenantState>,
+        tenant: &str,
+        now: Instant,
+    ) -> Admission {
+        let used = |name: &str| tenants.get(name).map_or(0, |state| state.admissions.len());
+        let quota: TenantQuota = self.quota(tenant);
+        if let Some(max_requests) = quota.max_requests
+            && used(tenant) >= max_requests
+        {
+            return Admission::Rejected { max_requests };
+        }
+
+        let total: usize = tenants.values().map(|state| state.admissions.len()).sum();
+        if total >= self.max_requests {
+            return self.wait_for_expiry(tenants.values(), now);
+        }
+
+        match self.mode {
+            QuotaMode::Isolated => {
+                // Configured tenants, and the others once they sent a request
+                let names: BTreeSet<&str> = self
+                    .quotas
+                    .keys()
+                    .chain(tenants.keys())
+                    .map(String::as_str)
+                    .chain([tenant])
+                    .collect();
+                let weights: f64 = names
+                    .into_iter()
+                    .map(|name| self.quota(name).weight())
+                    .sum();
+                let share: usize = self.share(quota.weight(), weights).floor().max(1.0) as usize;
+                if used(tenant) < share {
+                    Admission::Admitted
+                } else {
+                    self.wait_for_expiry(tenants.get(tenant), now)
+                }
+            }
+            QuotaMode::WorkConserving => {
+                let active = |name: &str, state: &TenantState| {
+                    name == tenant || !state.admissions.is_empty() || self.waits(state, now)
+                };
+                let weights: f64 = tenants
+                    .iter()
+                    .filter(|(name, state)| name.as_str() != tenant && active(name, state))
+                    .map(|(name, _)| self.quota(name).weight())
+                    .sum::<f64>()
+                    + quota.weight();
+                if (used(tenant) as f64) < self.share(quota.weight(), weights) {
+                    return Admission::Admitted;
+                }
+
+                // Over its share, the tenant only borrows capacity no waiting tenant is owed
+                let owed: bool = tenants.iter().any(|(name, state)| {
+                    name != tenant
+                        && self.waits(state, now)
+                        && (state.admissions.len() as f64)
+                            < self.share(self.quota(name).weight(), weights)
+                });
+                if owed {
+                    self.wait_for_expiry(tenants.values(), now)
+                } else {
+                    Admission::Admitted
+                }
+            }
+        }
+    }
+
+    /// Waits until the oldest of the given tenants' requests leaves the window.
+    fn wait_for_expiry<'a>(
+        &self,
+        states: impl IntoIterator<Item = &'a TenantState>,
+        now: Instant,
+    ) -> Admission {
+        let expiry: Option<Instant> = states
+            .into_iter()
+            .filter_map(|state| state.admissions.front())
+            .min()
+            .map(|admitted| *admitted + self.window);
+        Admission::Wait(expiry.map_or(self.window, |expiry| expiry.saturating_duration_since(now)))
+    }
+
+    /// Returns the requests per window of a tenant of the given weight, when the weights of
+    /// the tenants sharing the quota sum to `weights`.
+    fn share(&self, weight: f64, weights: f64) -> f64 {
+        if weights <= 0.0 {
+            return self.max_requests as f64;
+        }
+        self.max_requests as f64 * weight / weights
+    }
+
+    /// Returns whether a request of the tenant is blocked waiting, or was told to wait in the
+    /// window and not admitted since.
+    fn waits(&self, state: &TenantState, now: Instant) -> bool {
+        state.waiting > 0
+            || state
+                .throttled_at
+                .is_some_and(|throttled| now.saturating_duration_since(throttled) < self.window)
+    }
+
+    fn quota_exceeded(&self, tenant: &str, max_requests: usize) -> SecretaryError {
+        SecretaryError::TenantQuotaExceeded {
+            tenant: tenant.to_string(),
+            max_requests,
+            window: self.window,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<String, TenantState>> {
+        self.state.lock().unwrap_or_else(|error| error.into_inner())
+    }
+}
+
+/// A request retrying until it is admitted, counted among the waiting requests of its tenant
+/// while it was last told to wait.
+///
+/// Dropping it withdraws the request, so a call abandoned mid-wait, e.g. by a deadline or a
+/// failed sibling request, does not leave its tenant waiting.
+struct PendingRequest<'a> {
+    partitioner: &'a QuotaPartitioner,
+    tenant: &'a str,
+    waiting: bool,
+}
+
+impl<'a> PendingRequest<'a> {
+    fn new(partitioner: &'a QuotaPartitioner, tenant: &'a str) -> Self {
+        Self {
+            partitioner,
+            tenant,
+            waiting: false,
+        }
+    }
+
+    fn admit(&mut self, now: Instant) -> Admission {
+        self.partitioner
+            .admit_request(self.tenant, now, Some(&mut self.waiting))
+    }
+
+    /// Gives up waiting, which stops counting the tenant as waiting.
+    fn withdraw(mut self) {
+        self.partitioner.withdraw_request(self.tenant, self.waiting);
+        self.waiting = false;
+    }
+}
+
+impl Drop for PendingRequest<'_> {
+    fn drop(&mut self) {
+        if self.waiting {
+            self.partitioner.withdraw_request(self.tenant, true);
+        }
+    }
+}
+
+impl TenantState {
+    /// Forgets the requests admitted before the window.
+    fn expire(&mut self, now: Instant, window: Duration) {
+        while let Some(admitted) = self.admissions.front() {
+            if now.saturating_duration_since(*admitted) < window {
+                break;
+            }
+            self.admissions.pop_front();
+        }
+    }
+}
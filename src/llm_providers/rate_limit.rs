@@ -0,0 +1,318 @@
+//! Rate-limit headers and retrying throttled requests.
+//!
+//! OpenAI reports its rate limits in `x-ratelimit-*` headers on every response and tells a
+//! throttled client how long to wait with `Retry-After`. Azure OpenAI sends the remaining
+//! counts under the same names and the wait as `retry-after-ms` or `x-ms-retry-after-ms`.
+//! `RateLimitInfo` reads both families, and `RetryPolicy` uses the server's wait for a 429
+//! instead of guessing with exponential backoff. Retrying is off by default.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use std::collections::BTreeMap;
+//! use std::time::Duration;
+//!
+//! use secretary::llm_providers::rate_limit::{RateLimitInfo, RetryPolicy};
+//!
+//! // OpenAI headers
+//! let headers = BTreeMap::from([
+//!     ("x-ratelimit-limit-requests".to_string(), "60".to_string()),
+//!     ("x-ratelimit-remaining-requests".to_string(), "0".to_string()),
+//!     ("x-ratelimit-reset-requests".to_string(), "1m30s".to_string()),
+//!     ("x-ratelimit-remaining-tokens".to_string(), "1200".to_string()),
+//!     ("x-ratelimit-reset-tokens".to_string(), "250ms".to_string()),
+//! ]);
+//! let info = RateLimitInfo::from_headers(&headers).unwrap();
+//! assert_eq!(info.limit_requests, Some(60));
+//! assert_eq!(info.reset_requests, Some(Duration::from_secs(90)));
+//! assert_eq!(info.reset_tokens, Some(Duration::from_millis(250)));
+//! // Requests are exhausted, so wait for them to reset
+//! assert_eq!(info.suggested_wait(), Some(Duration::from_secs(90)));
+//!
+//! // Retry-After takes precedence
+//! let mut with_retry_after = headers.clone();
+//! with_retry_after.insert("retry-after".to_string(), "20".to_string());
+//! let info = RateLimitInfo::from_headers(&with_retry_after).unwrap();
+//! assert_eq!(info.suggested_wait(), Some(Duration::from_secs(20)));
+//!
+//! // Azure headers
+//! let headers = BTreeMap::from([
+//!     ("x-ms-retry-after-ms".to_string(), "1500".to_string()),
+//!     ("x-ratelimit-remaining-tokens".to_string(), "0".to_string()),
+//! ]);
+//! let info = RateLimitInfo::from_headers(&headers).unwrap();
+//! assert_eq!(info.suggested_wait(), Some(Duration::from_millis(1500)));
+//! assert_eq!(RateLimitInfo::from_headers(&BTreeMap::new()), None);
+//!
+//! // The server's wait is preferred over exponential backoff
+//! let policy = RetryPolicy::new(3).with_base_delay(Duration::from_millis(100));
+//! assert_eq!(policy.delay_for(1, None), Duration::from_millis(100));
+//! assert_eq!(policy.delay_for(3, None), Duration::from_millis(400));
+//! assert_eq!(policy.delay_for(3, Some(&info)), Duration::from_millis(1500));
+//! ```
+//!
+//! A throttled request is retried after the wait the server asked for, and the rate limits of
+//! the final response are reported in the metadata:
+//!
+//! ```no_run
+//! use secretary::Task;
+//! use secretary::llm_providers::openai::OpenAILLM;
+//! use secretary::llm_providers::rate_limit::RetryPolicy;
+//! use secretary::traits::GenerateData;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! struct Person {
+//!     #[task(instruction = "Extract the name")]
+//!     pub name: String,
+//! }
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+//! let llm = OpenAILLM::new("https://api.openai.com/v1", "your-api-key", "gpt-4o-mini")?
+//!     .with_retry_policy(RetryPolicy::new(2));
+//!
+//! let result = llm.generate_data_adaptive(&Person::new(), "Ada", &vec![])?;
+//! if let Some(rate_limit) = result.metadata.rate_limit {
+//!     println!("{:?} requests left", rate_limit.remaining_requests);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::correlation::PROVIDER_REQUEST_ID_HEADERS;
+
+/// An HTTP response from a provider: status code, headers and body text.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResponseEnvelope {
+    /// The HTTP status code.
+    pub status: u16,
+    /// The response headers, with lowercase names. Values that are not valid text are left out.
+    pub headers: BTreeMap<String, String>,
+    /// The response body.
+    pub body: String,
+    /// The number of sequences repaired while decoding the body, see the `decoding` module.
+    pub repaired_sequences: usize,
+}
+
+impl ResponseEnvelope {
+    /// Returns the rate limits reported in the headers, if there are any.
+    pub fn rate_limit(&self) -> Option<RateLimitInfo> {
+        RateLimitInfo::from_headers(&self.headers)
+    }
+
+    /// Returns the provider's ID of the request, from the first of the
+    /// `correlation::PROVIDER_REQUEST_ID_HEADERS` the response has.
+    pub fn provider_request_id(&self) -> Option<String> {
+        PROVIDER_REQUEST_ID_HEADERS
+            .iter()
+            .find_map(|name| self.headers.get(*name))
+            .cloned()
+    }
+}
+
+/// The rate limits a provider reported on a response.
+///
+/// Every value is optional, since providers and gateways send different subsets.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RateLimitInfo {
+    /// The maximum number of requests in the current window.
+    pub limit_requests: Option<u64>,
+    /// The maximum number of tokens in the current window.
+    pub limit_tokens: Option<u64>,
+    /// The number of requests left in the current window.
+    pub remaining_requests: Option<u64>,
+    /// The number of tokens left in the current window.
+    pub remaining_tokens: Option<u64>,
+    /// The time until the request limit resets.
+    pub reset_requests: Option<Duration>,
+    /// The time until the token limit resets.
+    pub reset_tokens: Option<Duration>,
+    /// How long the server asked the client to wait before retrying.
+    pub retry_after: Option<Duration>,
+}
+
+impl RateLimitInfo {
+    /// Reads the OpenAI and Azure rate-limit headers.
+    ///
+    /// `Retry-After` is read in seconds; the HTTP-date form is not supported. Azure's
+    /// `retry-after-ms` and `x-ms-retry-after-ms` are preferred over it when present.
+    /// Negative durations and durations too long for a `Duration` are left out.
+    ///
+    /// # Arguments
+    ///
+    /// * `headers` - Response headers with lowercase names
+    ///
+    /// # Returns
+    ///
+    /// The parsed limits, or `None` if none of the headers are present
+    pub fn from_headers(headers: &BTreeMap<String, String>) -> Option<Self> {
+        let number = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.trim().parse().ok())
+        };
+        let duration = |name: &str| headers.get(name).and_then(|value| parse_duration(value));
+        let milliseconds = |name: &str| number(name).map(Duration::from_millis);
+        let seconds = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.trim().parse::<f64>().ok())
+                .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+        };
+
+        let info: RateLimitInfo = RateLimitInfo {
+            limit_requests: number("x-ratelimit-limit-requests"),
+            limit_tokens: number("x-ratelimit-limit-tokens"),
+            remaining_requests: number("x-ratelimit-remaining-requests"),
+            remaining_tokens: number("x-ratelimit-remaining-tokens"),
+            reset_requests: duration("x-ratelimit-reset-requests"),
+            reset_tokens: duration("x-ratelimit-reset-tokens"),
+            retry_after: milliseconds("retry-after-ms")
+                .or_else(|| milliseconds("x-ms-retry-after-ms"))
+                .or_else(|| seconds("retry-after")),
+        };
+
+        if info == RateLimitInfo::default() {
+            return None;
+        }
+
+        Some(info)
+    }
+
+    /// Returns how long to wait before the next request, according to the server.
+    ///
+    /// This is the `Retry-After` wait when there is one. Otherwise it is the longest reset
+    /// time of the limits that are exhausted.
+    pub fn suggested_wait(&self) -> Option<Duration> {
+        if self.retry_after.is_some() {
+            return self.retry_after;
+        }
+
+        let requests: Option<Duration> = self
+            .reset_requests
+            .filter(|_| self.remaining_requests == Some(0));
+        let tokens: Option<Duration> = self
+            .reset_tokens
+            .filter(|_| self.remaining_tokens == Some(0));
+
+        requests.max(tokens)
+    }
+}
+
+/// Parses a duration such as `1s`, `6m0s`, `1h30m`, `250ms` or `0.5s`.
+///
+/// A bare number is read as seconds. Returns `None` for a duration too long for a `Duration`.
+fn parse_duration(value: &str) -> Option<Duration> {
+    let value: &str = value.trim();
+    if let Ok(seconds) = value.parse::<f64>() {
+        return Duration::try_from_secs_f64(seconds).ok();
+    }
+
+    let mut total: f64 = 0.0;
+    let mut rest: &str = value;
+    while !rest.is_empty() {
+        let number_end: usize = rest
+            .find(|character: char| !character.is_ascii_digit() && character != '.')
+            .unwrap_or(rest.len());
+        let number: f64 = rest[..number_end].parse().ok()?;
+        rest = &rest[number_end..];
+
+        let unit_end: usize = rest
+            .find(|character: char| character.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let scale: f64 = match &rest[..unit_end] {
+            "h" => 3600.0,
+            "m" => 60.0,
+            "s" => 1.0,
+            "ms" => 0.001,
+            "us" | "µs" => 0.000_001,
+            "ns" => 0.000_000_001,
+            _ => return None,
+        };
+        rest = &rest[unit_end..];
+
+        total += number * scale;
+    }
+
+    Duration::try_from_secs_f64(total).ok()
+}
+
+/// How throttled requests are retried.
+///
+/// Only responses with status 429 are retried, and refusals when `retry_refusals` is set,
+/// see the `refusal` module. The wait before each retry is the one the server asked for when
+/// its headers say so, and exponential backoff from `base_delay` otherwise. Both are capped
+/// at `max_delay`, so a wrong header cannot stall a request indefinitely.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The number of retries after the first attempt. `0` disables retrying.
+    pub max_retries: u32,
+    /// The backoff before the first retry, doubled for every further retry.
+    pub base_delay: Duration,
+    /// The longest wait between retries, the server's included.
+    pub max_delay: Duration,
+    /// Whether a successful response whose answer is a refusal is retried. Off by default.
+    pub retry_refusals: bool,
+}
+
+impl RetryPolicy {
+    /// A policy that never retries. This is the default.
+    pub const NONE: RetryPolicy = RetryPolicy {
+        max_retries: 0,
+        base_delay: Duration::from_millis(500),
+        max_delay: Duration::from_secs(60),
+        retry_refusals: false,
+    };
+
+    /// Creates a policy that retries throttled requests up to `max_retries` times.
+    pub fn new(max_retries: u32) -> Self {
+        Self {
+            max_retries,
+            ..Self::NONE
+        }
+    }
+
+    /// Sets the backoff before the first retry.
+    pub fn with_base_delay(mut self, base_delay: Duration) -> Self {
+        self.base_delay = base_delay;
+        self
+    }
+
+    /// Sets the longest wait between retries.
+    pub fn with_max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// Sets whether refused requests are retried like throttled ones.
+    pub fn with_retry_refusals(mut self, retry_refusals: bool) -> Self {
+        self.retry_refusals = retry_refusals;
+        self
+    }
+
+    /// Returns how long to wait before a retry.
+    ///
+    /// # Arguments
+    ///
+    /// * `attempt` - The number of the upcoming retry, starting at 1
+    /// * `rate_limit` - The rate limits reported on the throttled response, if any
+    pub fn delay_for(&self, attempt: u32, rate_limit: Option<&RateLimitInfo>) -> Duration {
+        if let Some(wait) = rate_limit.and_then(RateLimitInfo::suggested_wait) {
+            return wait.min(self.max_delay);
+        }
+
+        let factor: u32 = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.base_delay.saturating_mul(factor).min(self.max_delay)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::NONE
+    }
+}
@@ -0,0 +1,664 @@
+//! Recording provider traffic to files and replaying it, with the `record` feature.
+//!
+//! `RecordingLLM` wraps a provider and passes every request through to it, writing the
+//! request body and the raw response of each to numbered files in a directory:
+//! `0001-<hash>.request.json` holds the URL, the model and the body, and
+//! `0001-<hash>.response.json` the status code, the headers and the body text of the
+//! response. The hash is that of the request body, so the files of a directory can be
+//! matched with the requests that produced them. Numbering continues after the files already
+//! in the directory, so a directory can be recorded into over several runs.
+//!
+//! `ReplayLLM` serves the responses of such a directory instead of sending the requests: it
+//! builds each request body with the provider it wraps, hashes it and returns the response
+//! recorded for that hash. A request sent several times, e.g. by self-consistency voting,
+//! gets its recorded responses in the order they were recorded, and the last one after
+//! that. Under `ReplayMode::Strict`, the default, a request without a recorded response
+//! fails with `SecretaryError::UnmatchedRequest`; under `ReplayMode::Lenient` it is sent to
+//! the wrapped provider. Together they turn live traffic into regression suites that run
+//! without network access or API keys.
+//!
+//! Secrets are redacted before anything is written: values of keys such as `api_key`,
+//! `authorization`, `password` or `access_token` in the request body and the URL's query,
+//! and cookie headers of the response, are replaced by `<redacted>`. Request headers, which
+//! carry the credentials, are never written. Bodies are hashed after redaction, so a replay
+//! matches with other credentials.
+//!
+//! Both wrappers record and replay the requests of `send_messages_envelope` and
+//! `async_send_messages_envelope`, which every extraction goes through. The attempts of a
+//! request retried under the `RetryPolicy` are not recorded, only the response the retries
+//! ended with. Writing never fails the call: a recording that cannot be written is recorded
+//! as a `MetricEvent::RecordingFailed` with the wrapped provider's `MetricsSink`.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::SecretaryError;
+//! use secretary::llm_providers::openai::OpenAILLM;
+//! use secretary::llm_providers::record::{RecordingLLM, ReplayLLM, ReplayMode};
+//! use secretary::message::Message;
+//! use secretary::traits::IsLLM;
+//!
+//! let directory = std::env::temp_dir().join(format!("secretary-fixtures-{}", std::process::id()));
+//! let llm = OpenAILLM::new("http://127.0.0.1:9", "test-key", "gpt-4o").unwrap();
+//!
+//! let recording = RecordingLLM::new(llm.clone(), &directory);
+//! assert_eq!(recording.recorded(), 0);
+//!
+//! // Nothing was recorded, so a strict replay has no response to serve
+//! std::fs::create_dir_all(&directory).unwrap();
+//! let replay = ReplayLLM::new(llm, &directory).unwrap().with_mode(ReplayMode::Strict);
+//! let error = replay
+//!     .send_message(Message::user("Hello"), false)
+//!     .unwrap_err();
+//! assert!(matches!(
+//!     error.downcast_ref::<SecretaryError>(),
+//!     Some(SecretaryError::UnmatchedRequest { .. })
+//! ));
+//! # std::fs::remove_dir_all(&directory).unwrap();
+//! ```
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use reqwest::header::HeaderMap;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{
+    SecretaryError,
+    deadletter::DeadLetterSink,
+    decoding::DecodingPolicy,
+    estimate::Pricing,
+    guardrail::Guardrail,
+    lazy::LazySource,
+    leniency::LeniencyProfile,
+    limits::OutputLimits,
+    llm_providers::{
+        capabilities::ProviderCapabilities,
+        health::HealthProbe,
+        http::{HttpClients, PreparedRequest},
+        json_mode::{JsonMode, JsonModeStrategy, is_response_format_rejection},
+        queue::RequestQueue,
+        quota::QuotaPartitioner,
+        rate_limit::{ResponseEnvelope, RetryPolicy},
+    },
+    locale::ExtractionLocale,
+    message::{Message, SystemRoleStrategy},
+    metrics::{MetricEvent, MetricsSink},
+    request::RequestOptions,
+    schema::fnv1a_64,
+    traits::{
+        AsyncGenerateData, GenerateData, IsLLM, async_post_request, build_request_body,
+        post_request,
+    },
+    trimming::UnknownKeys,
+    vocabulary::OneOfPolicy,
+};
+
+/// The value written in place of a secret.
+pub const REDACTED: &str = "<redacted>";
+
+/// Keys whose values are secrets, compared in lowercase with `-` read as `_`.
+const SECRET_KEYS: [&str; 13] = [
+    "api_key",
+    "apikey",
+    "authorization",
+    "proxy_authorization",
+    "password",
+    "secret",
+    "client_secret",
+    "token",
+    "access_token",
+    "refresh_token",
+    "id_token",
+    "cookie",
+    "set_cookie",
+];
+
+/// The request of a recorded exchange, as written to `NNNN-<hash>.request.json`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FixtureRequest {
+    /// The hash of the redacted request body, see `request_hash`.
+    pub hash: String,
+    /// The URL the request was sent to, with secrets in its query redacted.
+    pub url: String,
+    /// The model of the provider.
+    pub model: String,
+    /// The request body, with secrets redacted.
+    pub body: Value,
+}
+
+/// The response of a recorded exchange, as written to `NNNN-<hash>.response.json`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FixtureResponse {
+    /// The hash of the request body the response answered.
+    pub hash: String,
+    /// The HTTP status code.
+    pub status: u16,
+    /// The response headers, with cookies redacted.
+    pub headers: BTreeMap<String, String>,
+    /// The body text, exactly as the provider returned it.
+    pub body: String,
+    /// How many malformed UTF-8 sequences were replaced when the body was decoded.
+    #[serde(default)]
+    pub repaired_sequences: usize,
+}
+
+impl FixtureResponse {
+    /// Returns the response as the envelope it was recorded from.
+    pub fn to_envelope(&self) -> ResponseEnvelope {
+        ResponseEnvelope {
+            status: self.status,
+            headers: self.headers.clone(),
+            body: self.body.clone(),
+            repaired_sequences: self.repaired_sequences,
+        }
+    }
+}
+
+/// Returns a copy of a request body with the values of secret keys replaced by `REDACTED`.
+///
+/// # Arguments
+///
+/// * `body` - The request body, or any other JSON value
+pub fn redact_secrets(body: &Value) -> Value {
+    match body {
+        Value::Object(object) => Value::Object(
+            object
+                .iter()
+                .map(|(key, value)| {
+                    let value: Value = if is_secret_key(key) && !value.is_object() {
+                        Value::String(REDACTED.to_string())
+                    } else {
+                        redact_secrets(value)
+                    };
+                    (key.clone(), value)
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(redact_secrets).collect()),
+        _ => body.clone(),
+    }
+}
+
+/// Returns the hash recordings are keyed by: 16 hex digits of a stable hash of the redacted
+/// request body, with object keys sorted so that the order they were inserted in does not
+/// matter.
+///
+/// # Arguments
+///
+/// * `body` - The request body, before or after redaction
+pub fn request_hash(body: &Value) -> String {
+    let canonical: Value = sort_keys(&redact_secrets(body));
+    format!("{:016x}", fnv1a_64(canonical.to_string().as_bytes()))
+}
+
+/// Whether requests without a recorded response fail or are sent to the wrapped provider.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReplayMode {
+    /// Fails with `SecretaryError::UnmatchedRequest`, so that a suite notices requests that
+    /// changed.
+    #[default]
+    Strict,
+    /// Sends the request to the wrapped provider.
+    Lenient,
+}
+
+/// A provider that passes every request through to the provider it wraps and writes each
+/// request and its response to a directory, see the module documentation.
+#[derive(Debug)]
+pub struct RecordingLLM<L: IsLLM> {
+    inner: L,
+    directory: PathBuf,
+    next: Arc<AtomicUsize>,
+    recorded: Arc<AtomicUsize>,
+}
+
+impl<L: IsLLM> RecordingLLM<L> {
+    /// Wraps a provider to record its requests into the given directory.
+    ///
+    /// The directory is created when the first request is written. Files are numbered after
+    /// the highest number already in it.
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - The provider requests are sent with
+    /// * `directory` - The directory the files are written to
+    pub fn new(inner: L, directory: impl Into<PathBuf>) -> Self {
+        let directory: PathBuf = directory.into();
+        let next: usize = highest_number(&directory) + 1;
+        Self {
+            inner,
+            directory,
+            next: Arc::new(AtomicUsize::new(next)),
+            recorded: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Returns the wrapped provider.
+    pub fn inner(&self) -> &L {
+        &self.inner
+    }
+
+    /// Returns the directory requests are written to.
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Returns how many requests were written.
+    pub fn recorded(&self) -> usize {
+        self.recorded.load(Ordering::SeqCst)
+    }
+
+    /// Writes a request and its response, recording failures as metric events.
+    fn record(&self, body: &Value, response: &ResponseEnvelope) {
+        // Numbered before writing so concurrent requests get distinct files
+        let number: usize = self.next.fetch_add(1, Ordering::SeqCst);
+        match self.write(number, body, response) {
+            Ok(_) => {
+                self.recorded.fetch_add(1, Ordering::SeqCst);
+            }
+            Err(error) => self
+                .inner
+                .get_metrics_sink()
+                .record(MetricEvent::RecordingFailed {
+                    error: format!(
+                        "could not record a request to {}: {}",
+                        self.directory.display(),
+                        error
+                    ),
+                }),
+        }
+    }
+
+    fn write(
+        &self,
+        number: usize,
+        body: &Value,
+        response: &ResponseEnvelope,
+    ) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.directory)?;
+        let hash: String = request_hash(body);
+        let request: FixtureRequest = FixtureRequest {
+            hash: hash.clone(),
+            url: redact_query(&self.inner.get_chat_completion_request_url()),
+            model: self.inner.get_model_ref().to_string(),
+            body: redact_secrets(body),
+        };
+        let response: FixtureResponse = FixtureResponse {
+            hash: hash.clone(),
+            status: response.status,
+            headers: response
+                .headers
+                .iter()
+                .map(|(name, value)| {
+                    let value: String = if is_secret_key(name) {
+                        REDACTED.to_string()
+                    } else {
+                        value.clone()
+                    };
+                    (name.clone(), value)
+                })
+                .collect(),
+            body: response.body.clone(),
+            repaired_sequences: response.repaired_sequences,
+        };
+
+        let stem: String = format!("{:04}-{}", number, hash);
+        std::fs::write(
+            self.directory.join(format!("{}.request.json", stem)),
+            serde_json::to_string_pretty(&request)?,
+        )?;
+        std::fs::write(
+            self.directory.join(format!("{}.response.json", stem)),
+            serde_json::to_string_pretty(&response)?,
+        )
+    }
+}
+
+/// A provider that serves the responses recorded by a `RecordingLLM` instead of sending
+/// requests, see the module documentation.
+#[derive(Debug)]
+pub struct ReplayLLM<L: IsLLM> {
+    inner: L,
+    directory: PathBuf,
+    responses: HashMap<String, Vec<FixtureResponse>>,
+    served: Arc<Mutex<HashMap<String, usize>>>,
+    mode: ReplayMode,
+}
+
+impl<L: IsLLM> ReplayLLM<L> {
+    /// Loads the responses recorded in a directory, to be served in strict mode.
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - The provider request bodies are built with, and requests without a
+    ///   recorded response are sent with in lenient mode
+    /// * `directory` - The directory a `RecordingLLM` wrote to
+    ///
+    /// # Errors
+    ///
+    /// Returns `SecretaryError::InputRead` if the directory or one of its response files
+    /// cannot be read, or `SecretaryError::SerdeJsonError` if a response file does not hold
+    /// a recorded response.
+    pub fn new(inner: L, directory: impl Into<PathBuf>) -> Result<Self, SecretaryError> {
+        let directory: PathBuf = directory.into();
+        let mut paths: Vec<PathBuf> = std::fs::read_dir(&directory)
+            .map_err(SecretaryError::InputRead)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.ends_with(".response.json"))
+            })
+            .collect();
+        // Numbered names sort in recording order
+        paths.sort();
+
+        let mut responses: HashMap<String, Vec<FixtureResponse>> = HashMap::new();
+        for path in paths {
+            let content: String =
+                std::fs::read_to_string(&path).map_err(SecretaryError::InputRead)?;
+            let response: FixtureResponse = serde_json::from_str(&content)?;
+            responses
+                .entry(response.hash.clone())
+                .or_default()
+                .push(response);
+        }
+
+        Ok(Self {
+            inner,
+            directory,
+            responses,
+            served: Arc::new(Mutex::new(HashMap::new())),
+            mode: ReplayMode::default(),
+        })
+    }
+
+    /// Sets what happens to requests without a recorded response.
+    pub fn with_mode(mut self, mode: ReplayMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Returns the wrapped provider.
+    pub fn inner(&self) -> &L {
+        &self.inner
+    }
+
+    /// Returns the directory the responses were loaded from.
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Returns how many responses were loaded.
+    pub fn len(&self) -> usize {
+        self.responses.values().map(Vec::len).sum()
+    }
+
+    /// Returns whether no response was loaded.
+    pub fn is_empty(&self) -> bool {
+        self.responses.is_empty()
+    }
+
+    /// Returns the next response recorded for a request body, or `None` if there is none.
+    fn recorded_response(&self, body: &Value) -> Result<Option<ResponseEnvelope>, SecretaryError> {
+        let hash: String = request_hash(body);
+        let Some(responses) = self.responses.get(&hash) else {
+            return match self.mode {
+                ReplayMode::Strict => Err(SecretaryError::UnmatchedRequest { hash }),
+                ReplayMode::Lenient => Ok(None),
+            };
+        };
+
+        let mut served = self
+            .served
+            .lock()
+            .unwrap_or_else(|error| error.into_inner());
+        let count: &mut usize = served.entry(hash).or_insert(0);
+        let response: &FixtureResponse = &responses[(*count).min(responses.len() - 1)];
+        *count += 1;
+
+        Ok(Some(response.to_envelope()))
+    }
+}
+
+impl<L: IsLLM + Clone> Clone for RecordingLLM<L> {
+    /// Clones share the numbering of the files and the count of written requests.
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            directory: self.directory.clone(),
+            next: Arc::clone(&self.next),
+            recorded: Arc::clone(&self.recorded),
+        }
+    }
+}
+
+impl<L: IsLLM + Clone> Clone for ReplayLLM<L> {
+    /// Clones share which responses were served.
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            directory: self.directory.clone(),
+            responses: self.responses.clone(),
+            served: Arc::clone(&self.served),
+            mode: self.mode,
+        }
+    }
+}
+
+/// Builds the body of the first attempt at a request, and whether it asks for native JSON
+/// mode, as `send_messages_envelope` does.
+fn first_request_body<L: IsLLM + ?Sized>(
+    llm: &L,
+    messages: Vec<Message>,
+    return_json: bool,
+    options: &RequestOptions,
+) -> (Value, bool) {
+    let native: bool = return_json && llm.get_json_mode().uses_native();
+    (
+        build_request_body(llm, messages, return_json, native, options),
+        native,
+    )
+}
+
+/// Returns the body to send again without `response_format` when the provider rejected it
+/// under `JsonModeStrategy::Auto`, as `send_messages_envelope` does.
+fn fallback_request_body<L: IsLLM + ?Sized>(
+    llm: &L,
+    messages: Vec<Message>,
+    return_json: bool,
+    native: bool,
+    options: &RequestOptions,
+    response: &ResponseEnvelope,
+) -> Option<Value> {
+    let json_mode: &JsonMode = llm.get_json_mode();
+    if !native
+        || json_mode.strategy() != JsonModeStrategy::Auto
+        || !is_response_format_rejection(response.status, &response.body)
+    {
+        return None;
+    }
+
+    json_mode.mark_native_rejected();
+    Some(build_request_body(
+        llm,
+        messages,
+        return_json,
+        false,
+        options,
+    ))
+}
+
+#[async_trait]
+impl<L: IsLLM + Send + Sync> IsLLM for RecordingLLM<L> {
+    fn send_messages_envelope(
+        &self,
+        messages: Vec<Message>,
+        return_json: bool,
+        options: &RequestOptions,
+    ) -> Result<ResponseEnvelope, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let (body, native) = first_request_body(self, messages.clone(), return_json, options);
+        let response: ResponseEnvelope = post_request(self, &body, options)?;
+        self.record(&body, &response);
+
+        match fallback_request_body(self, messages, return_json, native, options, &response) {
+            Some(body) => {
+                let response: ResponseEnvelope = post_request(self, &body, options)?;
+                self.record(&body, &response);
+                Ok(response)
+            }
+            None => Ok(response),
+        }
+    }
+
+    async fn async_send_messages_envelope(
+        &self,
+        messages: Vec<Message>,
+        return_json: bool,
+        options: &RequestOptions,
+    ) -> Result<ResponseEnvelope, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let (body, native) = first_request_body(self, messages.clone(), return_json, options);
+        let response: ResponseEnvelope = async_post_request(self, &body, options).await?;
+        self.record(&body, &response);
+
+        match fallback_request_body(self, messages, return_json, native, options, &response) {
+            Some(body) => {
+                let response: ResponseEnvelope = async_post_request(self, &body, options).await?;
+                self.record(&body, &response);
+                Ok(response)
+            }
+            None => Ok(response),
+        }
+    }
+
+    delegate_is_llm!(inner);
+}
+
+#[async_trait]
+impl<L: IsLLM + Send + Sync> IsLLM for ReplayLLM<L> {
+    fn send_messages_envelope(
+        &self,
+        messages: Vec<Message>,
+        return_json: bool,
+        options: &RequestOptions,
+    ) -> Result<ResponseEnvelope, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let (body, native) = first_request_body(self, messages.clone(), return_json, options);
+        let response: ResponseEnvelope = match self.recorded_response(&body)? {
+            Some(response) => response,
+            None => post_request(self, &body, options)?,
+        };
+
+        match fallback_request_body(self, messages, return_json, native, options, &response) {
+            Some(body) => match self.recorded_response(&body)? {
+                Some(response) => Ok(response),
+                None => post_request(self, &body, options),
+            },
+            None => Ok(response),
+        }
+    }
+
+    async fn async_send_messages_envelope(
+        &self,
+        messages: Vec<Message>,
+        return_json: bool,
+        options: &RequestOptions,
+    ) -> Result<ResponseEnvelope, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let (body, native) = first_request_body(self, messages.clone(), return_json, options);
+        let response: ResponseEnvelope = match self.recorded_response(&body)? {
+            Some(response) => response,
+            None => async_post_request(self, &body, options).await?,
+        };
+
+        match fallback_request_body(self, messages, return_json, native, options, &response) {
+            Some(body) => match self.recorded_response(&body)? {
+                Some(response) => Ok(response),
+                None => async_post_request(self, &body, options).await,
+            },
+            None => Ok(response),
+        }
+    }
+
+    delegate_is_llm!(inner);
+}
+
+impl<L: IsLLM + Send + Sync> GenerateData for RecordingLLM<L> {}
+
+impl<L: IsLLM + Send + Sync> AsyncGenerateData for RecordingLLM<L> {}
+
+impl<L: IsLLM + Send + Sync> GenerateData for ReplayLLM<L> {}
+
+impl<L: IsLLM + Send + Sync> AsyncGenerateData for ReplayLLM<L> {}
+
+#[cfg(feature = "local")]
+impl<L: IsLLM + Send + Sync> crate::traits::AsyncGenerateDataLocal for RecordingLLM<L> {}
+
+#[cfg(feature = "local")]
+impl<L: IsLLM + Send + Sync> crate::traits::AsyncGenerateDataLocal for ReplayLLM<L> {}
+
+fn is_secret_key(key: &str) -> bool {
+    let key: String = key.to_lowercase().replace('-', "_");
+    let key: &str = key.strip_prefix("x_").unwrap_or(&key);
+    SECRET_KEYS.contains(&key)
+        || key.ends_with("_api_key")
+        || key.ends_with("_secret")
+        || key.ends_with("_password")
+}
+
+/// Redacts the values of secret query parameters, and of `key`, which some providers take
+/// the API key in.
+fn redact_query(url: &str) -> String {
+    let Some((base, query)) = url.split_once('?') else {
+        return url.to_string();
+    };
+
+    let parameters: Vec<String> = query
+        .split('&')
+        .map(|parameter| match parameter.split_once('=') {
+            Some((name, _)) if name == "key" || is_secret_key(name) => {
+                format!("{}={}", name, REDACTED)
+            }
+            _ => parameter.to_string(),
+        })
+        .collect();
+    format!("{}?{}", base, parameters.join("&"))
+}
+
+fn sort_keys(value: &Value) -> Value {
+    match value {
+        Value::Object(object) => {
+            let sorted: BTreeMap<&String, Value> = object
+                .iter()
+                .map(|(key, value)| (key, sort_keys(value)))
+                .collect();
+            Value::Object(
+                sorted
+                    .into_iter()
+                    .map(|(key, value)| (key.clone(), value))
+                    .collect::<Map<String, Value>>(),
+            )
+        }
+        Value::Array(items) => Value::Array(items.iter().map(sort_keys).collect()),
+        _ => value.clone(),
+    }
+}
+
+/// Returns the highest number of the files in a directory, or 0 if it has none.
+fn highest_number(directory: &Path) -> usize {
+    let Ok(entries) = std::fs::read_dir(directory) else {
+        return 0;
+    };
+
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name: String = entry.file_name().to_string_lossy().into_owned();
+            name.split('-').next()?.parse::<usize>().ok()
+        })
+        .max()
+        .unwrap_or(0)
+}
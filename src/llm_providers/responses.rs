@@ -0,0 +1,610 @@
+//! OpenAI's Responses API, which keeps conversations on the server.
+//!
+//! `ResponsesApiLLM` sends requests to the `/responses` route instead of `/chat/completions`.
+//! Messages are sent as `input`, JSON mode is requested with `text.format`, and
+//! `RequestOptions::max_tokens` is written as `max_output_tokens`. The answer is read from the
+//! `output_text` blocks of the `message` items in `output`, skipping reasoning items. An
+//! answer with only a `refusal` block is `SecretaryError::Refused`.
+//!
+//! The provider declares `ConversationState::PreviousResponseId`, so an `ExtractionSession`
+//! refines an extraction by sending only the feedback of each turn together with the ID of
+//! the previous response, see the `session` module. Single requests work as with `OpenAILLM`.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::llm_providers::capabilities::ConversationState;
+//! use secretary::llm_providers::responses::ResponsesApiLLM;
+//! use secretary::message::Message;
+//! use secretary::request::RequestOptions;
+//! use secretary::traits::IsLLM;
+//! use serde_json::{Value, json};
+//!
+//! let llm = ResponsesApiLLM::new("https://api.openai.com/v1", "sk-...", "gpt-4.1-mini").unwrap();
+//! assert_eq!(llm.get_chat_completion_request_url(), "https://api.openai.com/v1/responses");
+//! assert_eq!(
+//!     llm.get_capabilities().conversation_state,
+//!     ConversationState::PreviousResponseId
+//! );
+//!
+//! let mut body: Value = llm.get_conversation_body(
+//!     vec![Message::system("Extract the name"), Message::user("Ada is 36.")],
+//!     true,
+//! );
+//! llm.apply_request_options(&mut body, &RequestOptions::default().with_max_tokens(200));
+//! assert_eq!(body, json!({
+//!     "model": "gpt-4.1-mini",
+//!     "input": [
+//!         {"role": "system", "content": "Extract the name"},
+//!         {"role": "user", "content": "Ada is 36."}
+//!     ],
+//!     "text": {"format": {"type": "json_object"}},
+//!     "max_output_tokens": 200
+//! }));
+//!
+//! let response = json!({
+//!     "id": "resp_123",
+//!     "object": "response",
+//!     "status": "completed",
+//!     "output": [
+//!         {"type": "reasoning", "id": "rs_1", "summary": []},
+//!         {
+//!             "type": "message",
+//!             "role": "assistant",
+//!             "content": [{"type": "output_text", "text": r#"{"name": "Ada"}"#, "annotations": []}]
+//!         }
+//!     ]
+//! })
+//! .to_string();
+//! assert_eq!(llm.extract_response_content(&response).unwrap(), r#"{"name": "Ada"}"#);
+//! assert_eq!(llm.extract_response_id(&response).as_deref(), Some("resp_123"));
+//! ```
+
+use std::sync::Arc;
+
+use serde_json::{Value, json};
+
+use crate::{
+    SecretaryError,
+    constants::OPENAI_RESPONSES_ROUTE,
+    credentials::ApiKey,
+    deadletter::DeadLetterSink,
+    decoding::DecodingPolicy,
+    estimate::Pricing,
+    guardrail::Guardrail,
+    lazy::LazySource,
+    leniency::LeniencyProfile,
+    limits::OutputLimits,
+    llm_providers::{
+        capabilities::{ConversationState, ProviderCapabilities},
+        health::HealthProbe,
+        http::{CompressionConfig, HttpClients, PoolConfig},
+        json_mode::{JsonMode, JsonModeStrategy},
+        queue::RequestQueue,
+        quota::QuotaPartitioner,
+        rate_limit::RetryPolicy,
+    },
+    locale::ExtractionLocale,
+    message::{Message, Role, SystemRoleStrategy},
+    metrics::{MetricsSink, NoopSink},
+    request::{RequestOptions, merge_extra_body},
+    traits::{AsyncGenerateData, GenerateData, IsLLM},
+    trimming::UnknownKeys,
+    vocabulary::OneOfPolicy,
+};
+
+/// A model called through OpenAI's Responses API, which keeps conversation state on the
+/// server.
+#[derive(Debug, Clone)]
+pub struct ResponsesApiLLM {
+    model: String,
+    api_key: ApiKey,
+    api_base: String,
+    capabilities: ProviderCapabilities,
+    leniency: LeniencyProfile,
+    metrics_sink: Arc<dyn MetricsSink>,
+    json_mode: JsonMode,
+    retry_policy: RetryPolicy,
+    http_clients: HttpClients,
+    extra_body: Option<Value>,
+    request_queue: Option<RequestQueue>,
+    quota_partitioner: Option<QuotaPartitioner>,
+    guardrail: Option<Guardrail>,
+    output_limits: Option<OutputLimits>,
+    decoding_policy: DecodingPolicy,
+    unknown_keys: UnknownKeys,
+    one_of_policy: OneOfPolicy,
+    locale: Option<ExtractionLocale>,
+    system_role_strategy: Option<SystemRoleStrategy>,
+    pricing: Option<Pricing>,
+    dead_letter_sink: Option<Arc<dyn DeadLetterSink>>,
+    traced_errors: bool,
+}
+
+impl ResponsesApiLLM {
+    /// Creates a new instance of the ResponsesApiLLM struct.
+    ///
+    /// # Arguments
+    ///
+    /// * `api_base` - The base URL of the API, e.g. `https://api.openai.com/v1`.
+    /// * `api_key` - The API key for authenticating with the API.
+    /// * `model` - The model to be used.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Self, Box<dyn std::error::Error + Send + Sync + 'static>>` - The provider.
+    pub fn new(
+        api_base: &str,
+        api_key: &str,
+        model: &str,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        Ok(Self {
+            model: model.to_string(),
+            api_base: api_base.to_string(),
+            api_key: ApiKey::new(api_key),
+            capabilities: ProviderCapabilities::default(),
+            leniency: LeniencyProfile::default(),
+            metrics_sink: Arc::new(NoopSink),
+            json_mode: JsonMode::default(),
+            retry_policy: RetryPolicy::default(),
+            http_clients: HttpClients::default(),
+            extra_body: None,
+            request_queue: None,
+            quota_partitioner: None,
+            guardrail: None,
+            output_limits: None,
+            decoding_policy: DecodingPolicy::default(),
+            unknown_keys: UnknownKeys::default(),
+            one_of_policy: OneOfPolicy::default(),
+            locale: None,
+            system_role_strategy: None,
+            pricing: None,
+            dead_letter_sink: None,
+            traced_errors: false,
+        })
+    }
+
+    /// Declares the capabilities of the configured model.
+    ///
+    /// The conversation state is always `ConversationState::PreviousResponseId`.
+    ///
+    /// # Arguments
+    ///
+    /// * `capabilities` - What the model can handle, such as its context window
+    pub fn with_capabilities(mut self, capabilities: ProviderCapabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Sets how leniently the model's output is coerced before deserialization.
+    ///
+    /// # Arguments
+    ///
+    /// * `leniency` - The coercions to apply, `LeniencyProfile::strict()` by default
+    pub fn with_leniency(mut self, leniency: LeniencyProfile) -> Self {
+        self.leniency = leniency;
+        self
+    }
+
+    /// Sets the sink that receives metric events for requests and parse failures.
+    ///
+    /// # Arguments
+    ///
+    /// * `metrics_sink` - The sink to record events to, a `NoopSink` by default
+    pub fn with_metrics_sink(mut self, metrics_sink: Arc<dyn MetricsSink>) -> Self {
+        self.metrics_sink = metrics_sink;
+        self
+    }
+
+    /// Sets how JSON output is requested from the model.
+    ///
+    /// # Arguments
+    ///
+    /// * `strategy` - The JSON mode strategy, `JsonModeStrategy::Native` by default
+    pub fn with_json_mode_strategy(mut self, strategy: JsonModeStrategy) -> Self {
+        self.json_mode = JsonMode::new(strategy);
+        self
+    }
+
+    /// Sets how requests throttled with a 429 are retried.
+    ///
+    /// # Arguments
+    ///
+    /// * `retry_policy` - The retry policy, `RetryPolicy::NONE` by default
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Sets the connection pool settings of the provider's HTTP clients.
+    ///
+    /// # Arguments
+    ///
+    /// * `pool_config` - The pool settings, `reqwest` defaults when unset
+    pub fn with_pool_config(mut self, pool_config: PoolConfig) -> Self {
+        self.http_clients =
+            HttpClients::new(pool_config).with_compression(self.http_clients.compression());
+        self
+    }
+
+    /// Sets how request and response bodies are compressed, see the `http` module.
+    ///
+    /// Like `with_pool_config`, this gives the provider new, empty pools.
+    ///
+    /// # Arguments
+    ///
+    /// * `compression` - Response decompression and the request compression threshold
+    pub fn with_compression(mut self, compression: CompressionConfig) -> Self {
+        self.http_clients = self.http_clients.with_compression(compression);
+        self
+    }
+
+    /// Sets extra JSON to deep-merge into every request body, such as
+    /// `{"reasoning": {"effort": "low"}}`.
+    ///
+    /// # Arguments
+    ///
+    /// * `extra_body` - A JSON object merged over the Responses request body
+    pub fn with_extra_body(mut self, extra_body: Value) -> Self {
+        self.extra_body = Some(extra_body);
+        self
+    }
+
+    /// Sends the provider's requests through a queue that caps the requests in flight and
+    /// admits them by priority, see the `queue` module.
+    ///
+    /// # Arguments
+    ///
+    /// * `request_queue` - The queue, `None` by default
+    pub fn with_request_queue(mut self, request_queue: RequestQueue) -> Self {
+        self.request_queue = Some(request_queue);
+        self
+    }
+
+    /// Splits the provider's requests per window between tenants by weight, see the `quota`
+    /// module.
+    ///
+    /// Clones of the partitioner share it, so the same partitioner can be given to several
+    /// providers.
+    ///
+    /// # Arguments
+    ///
+    /// * `quota_partitioner` - The partitioner, `None` by default
+    pub fn with_quota_partitioner(mut self, quota_partitioner: QuotaPartitioner) -> Self {
+        self.quota_partitioner = Some(quota_partitioner);
+        self
+    }
+
+    /// Runs a guardrail against prompt injection over every target before it is sent, see
+    /// the `guardrail` module.
+    ///
+    /// # Arguments
+    ///
+    /// * `guardrail` - The detector policy and prompt hardening, `None` by default
+    pub fn with_guardrail(mut self, guardrail: Guardrail) -> Self {
+        self.guardrail = Some(guardrail);
+        self
+    }
+
+    /// Limits the size of the model's output, see the `limits` module.
+    ///
+    /// # Arguments
+    ///
+    /// * `output_limits` - The limits, `None` by default
+    pub fn with_output_limits(mut self, output_limits: OutputLimits) -> Self {
+        self.output_limits = Some(output_limits);
+        self
+    }
+
+    /// Sets how responses that are not clean UTF-8 JSON are handled, see the `decoding`
+    /// module.
+    ///
+    /// # Arguments
+    ///
+    /// * `decoding_policy` - The policy, `DecodingPolicy::Repair` by default
+    pub fn with_decoding_policy(mut self, decoding_policy: DecodingPolicy) -> Self {
+        self.decoding_policy = decoding_policy;
+        self
+    }
+
+    /// Sets what happens to keys of the model's JSON that are not fields of the Task, see
+    /// the `trimming` module.
+    ///
+    /// # Arguments
+    ///
+    /// * `unknown_keys` - The policy, `UnknownKeys::Keep` by default
+    pub fn with_unknown_keys(mut self, unknown_keys: UnknownKeys) -> Self {
+        self.unknown_keys = unknown_keys;
+        self
+    }
+
+    /// Sets what happens to answers of `one_of` fields that match no allowed value, see the
+    /// `vocabulary` module.
+    ///
+    /// # Arguments
+    ///
+    /// * `one_of_policy` - The policy, `OneOfPolicy::Error` by default
+    pub fn with_one_of_policy(mut self, one_of_policy: OneOfPolicy) -> Self {
+        self.one_of_policy = one_of_policy;
+        self
+    }
+
+    /// Sets the number and date conventions of the targets, see the `locale` module.
+    ///
+    /// # Arguments
+    ///
+    /// * `locale` - The conventions, e.g. `ExtractionLocale::de_de()`
+    pub fn with_locale(mut self, locale: ExtractionLocale) -> Self {
+        self.locale = Some(locale);
+        self
+    }
+
+    /// Sets how system messages are sent to the model, instead of the strategy chosen from
+    /// the model name, see `SystemRoleStrategy`.
+    ///
+    /// # Arguments
+    ///
+    /// * `system_role_strategy` - The strategy
+    pub fn with_system_role_strategy(mut self, system_role_strategy: SystemRoleStrategy) -> Self {
+        self.system_role_strategy = Some(system_role_strategy);
+        self
+    }
+
+    /// Sets the price of the model's prompt tokens, which `plan` estimates the cost of an
+    /// extraction with, see the `estimate` module.
+    ///
+    /// # Arguments
+    ///
+    /// * `pricing` - The pricing
+    pub fn with_pricing(mut self, pricing: Pricing) -> Self {
+        self.pricing = Some(pricing);
+        self
+    }
+
+    /// Captures every failed extraction to a sink, see the `deadletter` module.
+    ///
+    /// # Arguments
+    ///
+    /// * `dead_letter_sink` - The sink to hand failures to, `None` by default
+    pub fn with_dead_letter_sink(mut self, dead_letter_sink: Arc<dyn DeadLetterSink>) -> Self {
+        self.dead_letter_sink = Some(dead_letter_sink);
+        self
+    }
+
+    /// Wraps the errors of failed extractions in a `TracedError` that carries their request
+    /// IDs, see the `correlation` module.
+    ///
+    /// # Arguments
+    ///
+    /// * `traced_errors` - Whether to wrap errors, `false` by default so errors can be
+    ///   downcast to `SecretaryError`
+    pub fn with_traced_errors(mut self, traced_errors: bool) -> Self {
+        self.traced_errors = traced_errors;
+        self
+    }
+
+    /// Replaces the API key for every request built from now on, see the `credentials`
+    /// module.
+    ///
+    /// Requests already sent finish with the old key. Clones of the provider share the key,
+    /// so they use the new one as well.
+    ///
+    /// # Arguments
+    ///
+    /// * `api_key` - The new API key
+    pub fn rotate_api_key(&self, api_key: &str) {
+        self.api_key.rotate(api_key);
+    }
+}
+
+impl IsLLM for ResponsesApiLLM {
+    fn get_authorization_credentials(&self) -> String {
+        format!("Bearer {}", self.api_key.get())
+    }
+
+    fn get_model_ref(&self) -> &str {
+        &self.model
+    }
+
+    fn get_capabilities(&self) -> ProviderCapabilities {
+        self.capabilities
+            .clone()
+            .with_conversation_state(ConversationState::PreviousResponseId)
+    }
+
+    fn get_leniency(&self) -> &LeniencyProfile {
+        &self.leniency
+    }
+
+    fn get_metrics_sink(&self) -> &dyn MetricsSink {
+        self.metrics_sink.as_ref()
+    }
+
+    fn get_json_mode(&self) -> &JsonMode {
+        &self.json_mode
+    }
+
+    fn get_retry_policy(&self) -> RetryPolicy {
+        self.retry_policy
+    }
+
+    fn http_client(&self) -> &reqwest::Client {
+        self.http_clients.client()
+    }
+
+    fn blocking_http_client(&self) -> &reqwest::blocking::Client {
+        self.http_clients.blocking_client()
+    }
+
+    fn get_http_clients(&self) -> &HttpClients {
+        &self.http_clients
+    }
+
+    fn get_extra_body(&self) -> Option<&Value> {
+        self.extra_body.as_ref()
+    }
+
+    fn get_request_queue(&self) -> Option<&RequestQueue> {
+        self.request_queue.as_ref()
+    }
+
+    fn get_quota_partitioner(&self) -> Option<&QuotaPartitioner> {
+        self.quota_partitioner.as_ref()
+    }
+
+    fn get_guardrail(&self) -> Option<&Guardrail> {
+        self.guardrail.as_ref()
+    }
+
+    fn get_output_limits(&self) -> Option<&OutputLimits> {
+        self.output_limits.as_ref()
+    }
+
+    fn get_decoding_policy(&self) -> DecodingPolicy {
+        self.decoding_policy
+    }
+
+    fn get_unknown_keys(&self) -> UnknownKeys {
+        self.unknown_keys
+    }
+
+    fn get_one_of_policy(&self) -> OneOfPolicy {
+        self.one_of_policy
+    }
+
+    fn get_locale(&self) -> Option<&ExtractionLocale> {
+        self.locale.as_ref()
+    }
+
+    fn get_system_role_strategy(&self) -> SystemRoleStrategy {
+        self.system_role_strategy
+            .unwrap_or_else(|| SystemRoleStrategy::for_model(self.get_model_ref()))
+    }
+
+    fn get_pricing(&self) -> Option<Pricing> {
+        self.pricing
+    }
+
+    fn get_dead_letter_sink(&self) -> Option<&dyn DeadLetterSink> {
+        self.dead_letter_sink.as_deref()
+    }
+
+    fn get_traced_errors(&self) -> bool {
+        self.traced_errors
+    }
+
+    fn get_lazy_source(&self) -> Option<Arc<dyn LazySource>> {
+        Some(Arc::new(self.clone()))
+    }
+
+    fn get_health_probe(&self) -> HealthProbe {
+        HealthProbe::ModelLookup(format!("{}/models/{}", self.api_base, self.model))
+    }
+
+    fn get_models_url(&self) -> Option<String> {
+        Some(format!("{}/models", self.api_base))
+    }
+
+    fn get_chat_completion_request_url(&self) -> String {
+        format!("{}{}", self.api_base, OPENAI_RESPONSES_ROUTE)
+    }
+
+    fn get_request_body(&self, message: Message, return_json: bool) -> Value {
+        self.get_conversation_body(vec![message], return_json)
+    }
+
+    /// System messages stay in `input` rather than going to `instructions`, which the server
+    /// does not carry over to the next turn of a conversation.
+    fn get_conversation_body(&self, messages: Vec<Message>, return_json: bool) -> Value {
+        let input: Vec<Value> = messages
+            .iter()
+            .map(|message| {
+                let role: &str = match message.role {
+                    Role::Tool => Role::User.as_str(),
+                    role => role.as_str(),
+                };
+                json!({"role": role, "content": message.content.as_str()})
+            })
+            .collect();
+
+        let mut body: Value = json!({"model": self.get_model_ref(), "input": input});
+        if return_json {
+            body["text"] = json!({"format": {"type": "json_object"}});
+        }
+
+        body
+    }
+
+    fn apply_request_options(&self, body: &mut Value, options: &RequestOptions) {
+        if let Some(temperature) = options.temperature {
+            body["temperature"] = Value::from(temperature);
+        }
+        if let Some(max_tokens) = options.max_tokens {
+            body["max_output_tokens"] = Value::from(max_tokens);
+        }
+        if let Some(reasoning_effort) = options.reasoning_effort {
+            body["reasoning"]["effort"] = Value::from(reasoning_effort.as_str());
+        }
+        if let Some(extra_body) = &options.extra_body {
+            merge_extra_body(body, extra_body);
+        }
+    }
+
+    fn extract_response_content(
+        &self,
+        api_response: &str,
+    ) -> Result<String, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let value: Value = serde_json::from_str(api_response)?;
+        let blocks: Vec<&Value> = value["output"]
+            .as_array()
+            .map(|items| {
+                items
+                    .iter()
+                    .filter(|item| item["type"] == "message")
+                    .filter_map(|item| item["content"].as_array())
+                    .flatten()
+                    .collect()
+            })
+            .unwrap_or_default();
+        let texts: Vec<&str> = blocks
+            .iter()
+            .filter(|block| block["type"] == "output_text")
+            .filter_map(|block| block["text"].as_str())
+            .collect();
+
+        if texts.is_empty() {
+            let refusal: Option<&str> = blocks
+                .iter()
+                .filter(|block| block["type"] == "refusal")
+                .find_map(|block| block["refusal"].as_str());
+            return Err(match refusal {
+                Some(message) => SecretaryError::Refused {
+                    message: message.to_string(),
+                },
+                None => SecretaryError::NoLLMResponse,
+            }
+            .into());
+        }
+
+        Ok(texts.concat())
+    }
+
+    fn extract_response_contents(
+        &self,
+        api_response: &str,
+    ) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        Ok(vec![self.extract_response_content(api_response)?])
+    }
+
+    fn extract_response_id(&self, api_response: &str) -> Option<String> {
+        let value: Value = serde_json::from_str(api_response).ok()?;
+        value["id"].as_str().map(str::to_string)
+    }
+}
+
+impl GenerateData for ResponsesApiLLM {}
+
+impl AsyncGenerateData for ResponsesApiLLM {}
+
+#[cfg(feature = "local")]
+impl crate::traits::AsyncGenerateDataLocal for ResponsesApiLLM {}
@@ -0,0 +1,688 @@
+//! Routing extractions between models of different cost tiers by their recent health.
+//!
+//! `Router` owns providers of the same kind, called tiers, listed from the cheapest model to
+//! the most capable. Every extraction goes to the default tier of its `RoutingPolicy`, the
+//! first one unless set, and is escalated to the next tier when:
+//!
+//! * The tier is failing: at least `min_samples` of its extractions were recorded within the
+//!   decay window, and the share of them whose answer failed to parse or that got no answer
+//!   from the provider is above `failure_threshold`. A failing tier is skipped for the
+//!   sticky window after it was last found failing, so that routing does not flap between
+//!   tiers while the rate hovers around the threshold.
+//! * The Task has critical fields, see `schema::Importance`, unless turned off with
+//!   `RoutingPolicy::with_critical_escalation`.
+//!
+//! Routing recovers on its own: outcomes older than the decay window are forgotten, so once
+//! the failures of a tier have decayed and its sticky window has passed, extractions return
+//! to it.
+//!
+//! The outcome of every routed extraction is recorded for its tier as a `CallOutcome`, in a
+//! ring buffer of the last `window` outcomes per tier that the clones of a router share.
+//! `Router::stats` reports the counts. Errors that say nothing about the model, such as a
+//! target rejected by the guardrail, are not recorded. With
+//! `RoutingPolicy::with_parse_failure_retry`, an extraction whose answer fails to parse is
+//! sent again to the next tiers, in order, before its error is returned. Each of these
+//! retries is reported to the call's progress reporter as a `RepairRoundStarted` event, see
+//! the `progress` module.
+//!
+//! The JSON mode, force and field-by-field methods of `GenerateData` and
+//! `AsyncGenerateData` are routed, and so are the adaptive ones, which report the model the
+//! extraction was sent to in `GenerationMetadata::routed_model`. The requests of the other
+//! methods are sent to the tier routing picks for a Task without critical fields, and
+//! record nothing.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use std::time::Duration;
+//!
+//! use secretary::llm_providers::openai::OpenAILLM;
+//! use secretary::llm_providers::router::{CallOutcome, Router, RoutingPolicy};
+//!
+//! let llm = |model: &str| OpenAILLM::new("http://127.0.0.1:9", "test-key", model).unwrap();
+//! let router = Router::new(llm("small-model"))
+//!     .with_tier(llm("medium-model"))
+//!     .with_tier(llm("large-model"))
+//!     .with_policy(
+//!         RoutingPolicy::new()
+//!             .with_failure_threshold(0.5)
+//!             .with_min_samples(2)
+//!             .with_decay(Duration::from_secs(60)),
+//!     );
+//!
+//! // Healthy: the cheapest tier, or the next one for Tasks with critical fields
+//! assert_eq!(router.select_tier(false), 0);
+//! assert_eq!(router.select_tier(true), 1);
+//!
+//! // The small model's answers stop parsing
+//! router.record(0, CallOutcome::ParseFailure);
+//! router.record(0, CallOutcome::ParseFailure);
+//! assert_eq!(router.select_tier(false), 1);
+//!
+//! let stats = router.stats();
+//! assert_eq!(stats[0].model, "small-model");
+//! assert_eq!(stats[0].parse_failures, 2);
+//! assert!(stats[0].escalated);
+//! ```
+
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use reqwest::header::HeaderMap;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    SecretaryError,
+    compiled::ExtractionPlan,
+    deadletter::DeadLetterSink,
+    decoding::DecodingPolicy,
+    estimate::Pricing,
+    guardrail::Guardrail,
+    instructions::InstructionSet,
+    lazy::LazySource,
+    leniency::LeniencyProfile,
+    limits::OutputLimits,
+    llm_providers::{
+        capabilities::ProviderCapabilities,
+        health::HealthProbe,
+        http::{HttpClients, PreparedRequest},
+        json_mode::JsonMode,
+        queue::RequestQueue,
+        quota::QuotaPartitioner,
+        rate_limit::{ResponseEnvelope, RetryPolicy},
+    },
+    locale::ExtractionLocale,
+    message::{Message, SystemRoleStrategy},
+    metadata::GenerationResult,
+    metrics::MetricsSink,
+    progress::{ProgressKind, report},
+    request::RequestOptions,
+    schema::critical_field_paths,
+    traits::{AsyncGenerateData, GenerateData, IsLLM, Task},
+    trimming::UnknownKeys,
+    vocabulary::OneOfPolicy,
+};
+
+/// How a routed extraction ended, as recorded for the tier it was sent to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum CallOutcome {
+    /// The extraction returned its data.
+    Success,
+    /// The answer did not parse into the Task or did not match its schema.
+    ParseFailure,
+    /// The provider could not be reached, or answered without a message.
+    HttpError,
+}
+
+impl CallOutcome {
+    /// Classifies the result of an extraction.
+    ///
+    /// # Returns
+    ///
+    /// The outcome, or `None` for errors that say nothing about the model, such as a target
+    /// that is too large or a refusal
+    pub fn of<T>(
+        result: &Result<T, Box<dyn std::error::Error + Send + Sync + 'static>>,
+    ) -> Option<Self> {
+        let error = match result {
+            Ok(_) => return Some(CallOutcome::Success),
+            Err(error) => error,
+        };
+
+        let mut current: Option<&(dyn std::error::Error + 'static)> = Some(error.as_ref());
+        while let Some(error) = current {
+            if error.is::<reqwest::Error>() {
+                return Some(CallOutcome::HttpError);
+            }
+            if let Some(error) = error.downcast_ref::<SecretaryError>() {
+                return match error {
+                    SecretaryError::JsonParsingError { .. }
+                    | SecretaryError::FieldDeserializationError(_)
+                    | SecretaryError::SchemaViolation { .. }
+                    | SecretaryError::SerdeJsonError(_) => Some(CallOutcome::ParseFailure),
+                    SecretaryError::NoLLMResponse | SecretaryError::ResponseDecode { .. } => {
+                        Some(CallOutcome::HttpError)
+                    }
+                    _ => None,
+                };
+            }
+            current = error.source();
+        }
+
+        None
+    }
+}
+
+/// When a `Router` escalates an extraction to the next tier.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RoutingPolicy {
+    /// The tier extractions go to while it is healthy. `0`, the cheapest, by default.
+    pub default_tier: usize,
+    /// The share of failed extractions above which a tier is failing. `0.5` by default.
+    pub failure_threshold: f64,
+    /// The number of outcomes within the decay window below which a tier is never failing.
+    /// `5` by default.
+    pub min_samples: usize,
+    /// The number of outcomes kept per tier. `50` by default.
+    pub window: usize,
+    /// How long an outcome counts towards the failure rate. Five minutes by default.
+    pub decay: Duration,
+    /// How long a tier is skipped after it was last found failing. One minute by default.
+    pub sticky: Duration,
+    /// Whether Tasks with critical fields start one tier above the default. On by default.
+    pub escalate_critical: bool,
+    /// Whether an extraction whose answer fails to parse is sent again to the next tiers.
+    /// Off by default.
+    pub retry_parse_failures: bool,
+}
+
+impl RoutingPolicy {
+    /// Creates the default policy.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the tier extractions go to while it is healthy, counted from the cheapest.
+    pub fn with_default_tier(mut self, default_tier: usize) -> Self {
+        self.default_tier = default_tier;
+        self
+    }
+
+    /// Sets the share of failed extractions, between 0 and 1, above which a tier is failing.
+    pub fn with_failure_threshold(mut self, failure_threshold: f64) -> Self {
+        self.failure_threshold = failure_threshold.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Sets the number of recent outcomes a tier needs before it can be found failing.
+    pub fn with_min_samples(mut self, min_samples: usize) -> Self {
+        self.min_samples = min_samples.max(1);
+        self
+    }
+
+    /// Sets the number of outcomes kept per tier.
+    pub fn with_window(mut self, window: usize) -> Self {
+        self.window = window.max(1);
+        self
+    }
+
+    /// Sets how long an outcome counts towards the failure rate.
+    pub fn with_decay(mut self, decay: Duration) -> Self {
+        self.decay = decay;
+        self
+    }
+
+    /// Sets how long a tier is skipped after it was last found failing.
+    pub fn with_sticky_window(mut self, sticky: Duration) -> Self {
+        self.sticky = sticky;
+        self
+    }
+
+    /// Sets whether Tasks with critical fields start one tier above the default.
+    pub fn with_critical_escalation(mut self, escalate_critical: bool) -> Self {
+        self.escalate_critical = escalate_critical;
+        self
+    }
+
+    /// Sets whether an extraction whose answer fails to parse is sent again to the next
+    /// tiers before its error is returned.
+    pub fn with_parse_failure_retry(mut self, retry_parse_failures: bool) -> Self {
+        self.retry_parse_failures = retry_parse_failures;
+        self
+    }
+}
+
+impl Default for RoutingPolicy {
+    fn default() -> Self {
+        Self {
+            default_tier: 0,
+            failure_threshold: 0.5,
+            min_samples: 5,
+            window: 50,
+            decay: Duration::from_secs(300),
+            sticky: Duration::from_secs(60),
+            escalate_critical: true,
+            retry_parse_failures: false,
+        }
+    }
+}
+
+/// The recent outcomes of a tier, as reported by `Router::stats`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TierStats {
+    /// The model of the tier.
+    pub model: String,
+    /// The extractions that returned their data, within the decay window.
+    pub successes: usize,
+    /// The extractions whose answer failed to parse, within the decay window.
+    pub parse_failures: usize,
+    /// The extractions that got no answer from the provider, within the decay window.
+    pub http_errors: usize,
+    /// Whether extractions skip the tier, because it is failing or within its sticky window.
+    pub escalated: bool,
+}
+
+impl TierStats {
+    /// Returns the share of the recent extractions that failed, or 0 if there were none.
+    pub fn failure_rate(&self) -> f64 {
+        let total: usize = self.successes + self.parse_failures + self.http_errors;
+        if total == 0 {
+            return 0.0;
+        }
+
+        (self.parse_failures + self.http_errors) as f64 / total as f64
+    }
+}
+
+/// The outcomes of a tier in a ring buffer of fixed size, and the end of its sticky window.
+#[derive(Debug)]
+struct TierHealth {
+    outcomes: Vec<Option<(Instant, CallOutcome)>>,
+    next: usize,
+    escalated_until: Option<Instant>,
+}
+
+impl TierHealth {
+    fn new(window: usize) -> Self {
+        Self {
+            outcomes: vec![None; window],
+            next: 0,
+            escalated_until: None,
+        }
+    }
+
+    /// Records an outcome in place of the oldest one.
+    fn record(&mut self, outcome: CallOutcome, now: Instant) {
+        self.outcomes[self.next] = Some((now, outcome));
+        self.next = (self.next + 1) % self.outcomes.len();
+    }
+
+    /// Returns the outcomes recorded within the decay window.
+    fn recent(&self, now: Instant, decay: Duration) -> impl Iterator<Item = CallOutcome> + '_ {
+        self.outcomes
+            .iter()
+            .flatten()
+            .filter(move |(at, _)| now.saturating_duration_since(*at) <= decay)
+            .map(|(_, outcome)| *outcome)
+    }
+
+    fn is_failing(&self, now: Instant, policy: &RoutingPolicy) -> bool {
+        let (total, failures) =
+            self.recent(now, policy.decay)
+                .fold((0usize, 0usize), |(total, failures), outcome| {
+                    (
+                        total + 1,
+                        failures + usize::from(outcome != CallOutcome::Success),
+                    )
+                });
+
+        total >= policy.min_samples && failures as f64 / total as f64 > policy.failure_threshold
+    }
+
+    fn is_sticky(&self, now: Instant) -> bool {
+        self.escalated_until.is_some_and(|until| now < until)
+    }
+}
+
+/// A provider that sends each extraction to one of several tiers by their recent health,
+/// see the module documentation.
+#[derive(Debug)]
+pub struct Router<L: IsLLM> {
+    tiers: Vec<L>,
+    policy: RoutingPolicy,
+    health: Arc<Vec<Mutex<TierHealth>>>,
+}
+
+impl<L: IsLLM> Router<L> {
+    /// Creates a router with one tier, the cheapest.
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - The provider of the cheapest model
+    pub fn new(provider: L) -> Self {
+        let policy: RoutingPolicy = RoutingPolicy::default();
+        Self {
+            tiers: vec![provider],
+            health: new_health(1, policy.window),
+            policy,
+        }
+    }
+
+    /// Adds a tier above the existing ones.
+    ///
+    /// Like `with_policy`, it starts the recorded outcomes afresh, so it is called before the
+    /// router is used or cloned.
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - The provider of a more capable model than those of the existing tiers
+    pub fn with_tier(mut self, provider: L) -> Self {
+        self.tiers.push(provider);
+        self.health = new_health(self.tiers.len(), self.policy.window);
+        self
+    }
+
+    /// Sets when extractions are escalated.
+    pub fn with_policy(mut self, policy: RoutingPolicy) -> Self {
+        self.policy = policy;
+        self.health = new_health(self.tiers.len(), self.policy.window);
+        self
+    }
+
+    /// Returns the providers of the tiers, from the cheapest.
+    pub fn tiers(&self) -> &[L] {
+        &self.tiers
+    }
+
+    /// Returns the routing policy.
+    pub fn policy(&self) -> &RoutingPolicy {
+        &self.policy
+    }
+
+    /// Returns the provider of the default tier, whose settings the router reports as its
+    /// own.
+    pub fn default_provider(&self) -> &L {
+        &self.tiers[self.default_tier()]
+    }
+
+    /// Returns the tier the next extraction goes to, skipping failing tiers.
+    ///
+    /// # Arguments
+    ///
+    /// * `critical` - Whether the Task has critical fields
+    pub fn select_tier(&self, critical: bool) -> usize {
+        let now: Instant = Instant::now();
+        let last: usize = self.tiers.len() - 1;
+        let mut tier: usize = self.default_tier();
+        if critical && self.policy.escalate_critical {
+            tier = (tier + 1).min(last);
+        }
+
+        while tier < last {
+            let mut health = self.health_of(tier);
+            if health.is_failing(now, &self.policy) {
+                health.escalated_until = Some(now + self.policy.sticky);
+            } else if !health.is_sticky(now) {
+                break;
+            }
+            tier += 1;
+        }
+
+        tier
+    }
+
+    /// Records the outcome of an extraction sent to a tier outside the router.
+    ///
+    /// # Arguments
+    ///
+    /// * `tier` - The tier, counted from the cheapest
+    /// * `outcome` - How the extraction ended
+    pub fn record(&self, tier: usize, outcome: CallOutcome) {
+        if tier < self.tiers.len() {
+            self.health_of(tier).record(outcome, Instant::now());
+        }
+    }
+
+    /// Returns the recent outcomes of every tier, from the cheapest.
+    pub fn stats(&self) -> Vec<TierStats> {
+        let now: Instant = Instant::now();
+        self.tiers
+            .iter()
+            .enumerate()
+            .map(|(tier, provider)| {
+                let health = self.health_of(tier);
+                let mut stats: TierStats = TierStats {
+                    model: provider.get_model_ref().to_string(),
+                    successes: 0,
+                    parse_failures: 0,
+                    http_errors: 0,
+                    escalated: tier + 1 < self.tiers.len()
+                        && (health.is_failing(now, &self.policy) || health.is_sticky(now)),
+                };
+                for outcome in health.recent(now, self.policy.decay) {
+                    match outcome {
+                        CallOutcome::Success => stats.successes += 1,
+                        CallOutcome::ParseFailure => stats.parse_failures += 1,
+                        CallOutcome::HttpError => stats.http_errors += 1,
+                    }
+                }
+                stats
+            })
+            .collect()
+    }
+
+    fn default_tier(&self) -> usize {
+        self.policy.default_tier.min(self.tiers.len() - 1)
+    }
+
+    fn health_of(&self, tier: usize) -> MutexGuard<'_, TierHealth> {
+        self.health[tier]
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+    }
+
+    /// Returns the first tier of an extraction of a plan.
+    fn route<P: ExtractionPlan + ?Sized>(&self, task: &P) -> usize {
+        self.select_tier(!critical_field_paths(&task.field_table()).is_empty())
+    }
+
+    /// Records the result of an extraction sent to a tier, and returns the tier to send it
+    /// to again, if any.
+    fn settle<T>(
+        &self,
+        tier: usize,
+        result: &Result<T, Box<dyn std::error::Error + Send + Sync + 'static>>,
+    ) -> Option<usize> {
+        let outcome: CallOutcome = CallOutcome::of(result)?;
+        self.record(tier, outcome);
+
+        let retry: bool = self.policy.retry_parse_failures
+            && outcome == CallOutcome::ParseFailure
+            && tier + 1 < self.tiers.len();
+        retry.then_some(tier + 1)
+    }
+}
+
+impl<L: IsLLM + Clone> Clone for Router<L> {
+    /// Clones share the recorded outcomes.
+    fn clone(&self) -> Self {
+        Self {
+            tiers: self.tiers.clone(),
+            policy: self.policy,
+            health: Arc::clone(&self.health),
+        }
+    }
+}
+
+fn new_health(tiers: usize, window: usize) -> Arc<Vec<Mutex<TierHealth>>> {
+    Arc::new(
+        (0..tiers)
+            .map(|_| Mutex::new(TierHealth::new(window)))
+            .collect(),
+    )
+}
+
+/// Sends an extraction to the tier `route` picks, records its outcome and, under the
+/// parse-failure retry, sends it again to the next tiers, reporting each retry as a repair
+/// round to the progress of the options.
+macro_rules! routed {
+    ($router:ident, $task:expr, $options:ident, |$llm:ident| $call:expr) => {{
+        // Every round is timed from the start of the first
+        let started: Option<RequestOptions> =
+            $options.progress.as_ref().map(|progress| RequestOptions {
+                progress: Some(progress.started()),
+                ..$options.clone()
+            });
+        let $options: &RequestOptions = started.as_ref().unwrap_or($options);
+        let mut tier: usize = $router.route($task);
+        let mut round: u32 = 0;
+        loop {
+            let $llm: &L = &$router.tiers[tier];
+            let result = $call;
+            match $router.settle(tier, &result) {
+                Some(next) => {
+                    tier = next;
+                    round += 1;
+                    report($options.progress.as_ref(), || {
+                        ProgressKind::RepairRoundStarted { round }
+                    });
+                }
+                None => break result,
+            }
+        }
+    }};
+}
+
+#[async_trait]
+impl<L: IsLLM + Send + Sync> IsLLM for Router<L> {
+    fn send_messages_envelope(
+        &self,
+        messages: Vec<Message>,
+        return_json: bool,
+        options: &RequestOptions,
+    ) -> Result<ResponseEnvelope, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        self.tiers[self.select_tier(false)].send_messages_envelope(messages, return_json, options)
+    }
+
+    async fn async_send_messages_envelope(
+        &self,
+        messages: Vec<Message>,
+        return_json: bool,
+        options: &RequestOptions,
+    ) -> Result<ResponseEnvelope, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        self.tiers[self.select_tier(false)]
+            .async_send_messages_envelope(messages, return_json, options)
+            .await
+    }
+
+    delegate_is_llm!(default_provider);
+}
+
+impl<L: GenerateData + Send + Sync> GenerateData for Router<L> {
+    fn generate_data_with_options<T: Task>(
+        &self,
+        task: &impl ExtractionPlan<Task = T>,
+        target: &str,
+        additional_instructions: impl Into<InstructionSet>,
+        options: &RequestOptions,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let instructions: InstructionSet = additional_instructions.into();
+        routed!(self, task, options, |llm| llm.generate_data_with_options(
+            task,
+            target,
+            instructions.clone(),
+            options
+        ))
+    }
+
+    fn force_generate_data_with_options<T: Task>(
+        &self,
+        task: &impl ExtractionPlan<Task = T>,
+        target: &str,
+        additional_instructions: impl Into<InstructionSet>,
+        options: &RequestOptions,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let instructions: InstructionSet = additional_instructions.into();
+        routed!(self, task, options, |llm| llm
+            .force_generate_data_with_options(
+                task,
+                target,
+                instructions.clone(),
+                options
+            ))
+    }
+
+    fn generate_data_adaptive_with_options<T: Task>(
+        &self,
+        task: &impl ExtractionPlan<Task = T>,
+        target: &str,
+        additional_instructions: impl Into<InstructionSet>,
+        options: &RequestOptions,
+    ) -> Result<GenerationResult<T>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let instructions: InstructionSet = additional_instructions.into();
+        routed!(self, task, options, |llm| llm
+            .generate_data_adaptive_with_options(task, target, instructions.clone(), options)
+            .map(|result| with_routed_model(result, llm)))
+    }
+
+    fn fields_generate_data_with_options<T: Task>(
+        &self,
+        task: &impl ExtractionPlan<Task = T>,
+        target: &str,
+        additional_instructions: impl Into<InstructionSet>,
+        options: &RequestOptions,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let instructions: InstructionSet = additional_instructions.into();
+        routed!(self, task, options, |llm| llm
+            .fields_generate_data_with_options(
+                task,
+                target,
+                instructions.clone(),
+                options
+            ))
+    }
+}
+
+#[async_trait]
+impl<L: AsyncGenerateData + Send + Sync> AsyncGenerateData for Router<L> {
+    async fn async_generate_data_with_options<T: Task + Sync + Send>(
+        &self,
+        task: &(impl ExtractionPlan<Task = T> + Sync),
+        target: &str,
+        additional_instructions: impl Into<InstructionSet> + Send,
+        options: &RequestOptions,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let instructions: InstructionSet = additional_instructions.into();
+        routed!(self, task, options, |llm| llm
+            .async_generate_data_with_options(task, target, instructions.clone(), options)
+            .await)
+    }
+
+    async fn async_force_generate_data_with_options<T: Task + Sync + Send>(
+        &self,
+        task: &(impl ExtractionPlan<Task = T> + Sync),
+        target: &str,
+        additional_instructions: impl Into<InstructionSet> + Send,
+        options: &RequestOptions,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let instructions: InstructionSet = additional_instructions.into();
+        routed!(self, task, options, |llm| llm
+            .async_force_generate_data_with_options(task, target, instructions.clone(), options)
+            .await)
+    }
+
+    async fn async_generate_data_adaptive_with_options<T: Task + Sync + Send>(
+        &self,
+        task: &(impl ExtractionPlan<Task = T> + Sync),
+        target: &str,
+        additional_instructions: impl Into<InstructionSet> + Send,
+        options: &RequestOptions,
+    ) -> Result<GenerationResult<T>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let instructions: InstructionSet = additional_instructions.into();
+        routed!(self, task, options, |llm| llm
+            .async_generate_data_adaptive_with_options(task, target, instructions.clone(), options)
+            .await
+            .map(|result| with_routed_model(result, llm)))
+    }
+
+    async fn async_fields_generate_data_with_options<T: Task + Sync + Send>(
+        &self,
+        task: &(impl ExtractionPlan<Task = T> + Sync),
+        target: &str,
+        additional_instructions: impl Into<InstructionSet> + Send,
+        options: &RequestOptions,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let instructions: InstructionSet = additional_instructions.into();
+        routed!(self, task, options, |llm| llm
+            .async_fields_generate_data_with_options(task, target, instructions.clone(), options)
+            .await)
+    }
+}
+
+/// Reports the model of the tier an extraction was sent to in its metadata.
+fn with_routed_model<T, L: IsLLM>(mut result: GenerationResult<T>, llm: &L) -> GenerationResult<T> {
+    result.metadata.routed_model = Some(llm.get_model_ref().to_string());
+    result
+}
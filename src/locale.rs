@@ -0,0 +1,514 @@
+//! The number and date conventions of the text an extraction reads.
+//!
+//! A German invoice writes `1.234,56` and `13.04.2024`, an American one `1,234.56` and
+//! `04/13/2024`. Neither the model nor the parser can tell `1.234` or `04/05/2024` apart
+//! without knowing where the text comes from. An `ExtractionLocale`, set on a provider with
+//! `with_locale` or for one call with `RequestOptions::with_locale`, describes the source:
+//!
+//! - the prompt gets a short note with the conventions of the text and those of the answer:
+//!   numbers with a decimal point and no thousands separators, dates as `YYYY-MM-DD`
+//! - text the model still returns in the source conventions is read with them before the
+//!   answer is deserialized: numeric strings in number fields become numbers, so `"1.234"`
+//!   is 1234 under a decimal comma, and the text of fields marked `#[task(date)]` that is a
+//!   whole numeric date, such as `"13.04.2024"`, is written as `2024-04-13`
+//!
+//! JSON numbers are never changed, and values that do not follow the conventions are left
+//! as they are. Text fields that are not marked as dates keep their text, so a part number
+//! such as `12-05-10` is not taken for a date. Setting a locale is the opt-in, so it applies under every leniency profile,
+//! the strict one included. The locale of an extraction is recorded in
+//! `GenerationMetadata::locale`. It applies to JSON mode, distributed and adaptive
+//! generation.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::locale::{DateOrder, ExtractionLocale};
+//! use serde_json::json;
+//!
+//! let german = ExtractionLocale::de_de();
+//! let american = ExtractionLocale::en_us();
+//!
+//! assert_eq!(german.parse_number("1.234,56"), Some(json!(1234.56)));
+//! assert_eq!(german.parse_number("1.234"), Some(json!(1234)));
+//! assert_eq!(american.parse_number("1.234"), Some(json!(1.234)));
+//! assert_eq!(american.parse_number("$1,234.56"), Some(json!(1234.56)));
+//!
+//! assert_eq!(german.parse_date("13.04.2024").as_deref(), Some("2024-04-13"));
+//! assert_eq!(american.parse_date("04/13/2024").as_deref(), Some("2024-04-13"));
+//! // There is no 13th month
+//! assert_eq!(american.parse_date("13/04/2024"), None);
+//!
+//! let locale = ExtractionLocale::default()
+//!     .with_decimal_comma(true)
+//!     .with_date_order(DateOrder::Dmy)
+//!     .with_timezone("Europe/Vienna")
+//!     .with_currency_default("EUR");
+//! assert!(locale.prompt_note().contains("Europe/Vienna"));
+//! ```
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Number, Value};
+
+use crate::{
+    schema::{FieldDescriptor, FieldKind, JsonType},
+    utilities::{find_field_descriptor, parse_described_field_value},
+};
+
+/// The order of the day, month and year in the dates of a text.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DateOrder {
+    /// Day, month, year, e.g. `13/04/2024` or `13.04.2024`.
+    Dmy,
+    /// Month, day, year, e.g. `04/13/2024`.
+    Mdy,
+    /// Year, month, day, e.g. `2024-04-13` or `2024/04/13`.
+    #[default]
+    Ymd,
+}
+
+impl DateOrder {
+    /// Returns how the order is described in the prompt, with an example.
+    fn description(&self) -> &'static str {
+        match self {
+            DateOrder::Dmy => "day, month, year, e.g. 13/04/2024",
+            DateOrder::Mdy => "month, day, year, e.g. 04/13/2024",
+            DateOrder::Ymd => "year, month, day, e.g. 2024/04/13",
+        }
+    }
+}
+
+/// The conventions of the text an extraction reads, see the module documentation.
+///
+/// The default locale writes numbers with a decimal point and dates year first.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ExtractionLocale {
+    /// Whether numbers are written with a decimal comma, e.g. `1.234,56`.
+    pub decimal_comma: bool,
+    /// The order of the day, month and year in dates.
+    pub date_order: DateOrder,
+    /// The IANA time zone of the times in the text, e.g. `Europe/Berlin`.
+    pub timezone: Option<String>,
+    /// The currency of amounts written without one, e.g. `EUR`.
+    pub currency_default: Option<String>,
+}
+
+impl ExtractionLocale {
+    /// The conventions of American English: `1,234.56`, `04/13/2024` and US dollars.
+    pub fn en_us() -> Self {
+        Self {
+            date_order: DateOrder::Mdy,
+            currency_default: Some("USD".to_string()),
+            ..Self::default()
+        }
+    }
+
+    /// The conventions of British English: `1,234.56`, `13/04/2024` and pounds sterling.
+    pub fn en_gb() -> Self {
+        Self {
+            date_order: DateOrder::Dmy,
+            currency_default: Some("GBP".to_string()),
+            ..Self::default()
+        }
+    }
+
+    /// The conventions of German: `1.234,56`, `13.04.2024` and euros.
+    pub fn de_de() -> Self {
+        Self {
+            decimal_comma: true,
+            date_order: DateOrder::Dmy,
+            currency_default: Some("EUR".to_string()),
+            ..Self::default()
+        }
+    }
+
+    /// Sets whether numbers are written with a decimal comma.
+    pub fn with_decimal_comma(mut self, decimal_comma: bool) -> Self {
+        self.decimal_comma = decimal_comma;
+        self
+    }
+
+    /// Sets the order of the day, month and year in dates.
+    pub fn with_date_order(mut self, date_order: DateOrder) -> Self {
+        self.date_order = date_order;
+        self
+    }
+
+    /// Sets the time zone of the times in the text.
+    ///
+    /// # Arguments
+    ///
+    /// * `timezone` - An IANA time zone, e.g. `Europe/Berlin`
+    pub fn with_timezone(mut self, timezone: &str) -> Self {
+        self.timezone = Some(timezone.to_string());
+        self
+    }
+
+    /// Sets the currency of amounts written without one.
+    ///
+    /// # Arguments
+    ///
+    /// * `currency` - An ISO 4217 code, e.g. `EUR`
+    pub fn with_currency_default(mut self, currency: &str) -> Self {
+        self.currency_default = Some(currency.to_string());
+        self
+    }
+
+    /// Returns the note added to the prompt, with the conventions of the text and those the
+    /// answer must follow.
+    pub fn prompt_note(&self) -> String {
+        let numbers: &str = if self.decimal_comma {
+            "numbers use a decimal comma and dots or spaces between thousands, e.g. 1.234,56"
+        } else {
+            "numbers use a decimal point and commas between thousands, e.g. 1,234.56"
+        };
+        let mut note: String = format!(
+            "The text follows these conventions: {}; dates are written {}",
+            numbers,
+            self.date_order.description()
+        );
+        if let Some(timezone) = &self.timezone {
+            note.push_str(&format!("; times are in the {} time zone", timezone));
+        }
+        if let Some(currency) = &self.currency_default {
+            note.push_str(&format!("; amounts without a currency are in {}", currency));
+        }
+        note.push_str(
+            ". Answer with numbers that use a decimal point and no thousands separators, e.g. 1234.56, and with dates as YYYY-MM-DD.",
+        );
+
+        note
+    }
+
+    /// Reads a number written in the conventions of the locale, or already with a decimal
+    /// point.
+    ///
+    /// Currency symbols, the default currency and spaces around the number are ignored.
+    /// Under a decimal comma, dots followed by groups of three digits separate thousands,
+    /// and a single dot otherwise is a decimal point; with a decimal point, commas must
+    /// separate groups of three digits.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text of a number field
+    ///
+    /// # Returns
+    ///
+    /// The number, an integer when it has no decimals, or `None` if the text is not one
+    pub fn parse_number(&self, text: &str) -> Option<Value> {
+        let mut text: &str = text.trim();
+        if let Some(currency) = &self.currency_default {
+            text = text
+                .strip_prefix(currency.as_str())
+                .or_else(|| text.strip_suffix(currency.as_str()))
+                .unwrap_or(text);
+        }
+        let text: &str = text
+            .trim_matches(|character: char| {
+                matches!(character, '$' | '€' | '£' | '¥') || character.is_whitespace()
+            })
+            .trim();
+        let (negative, text) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text.strip_prefix('+').unwrap_or(text)),
+        };
+
+        let (integer, fraction) = if self.decimal_comma {
+            split_decimal_comma(text)?
+        } else {
+            split_decimal_point(text)?
+        };
+        let digits: String = format!(
+            "{}{}{}",
+            if negative { "-" } else { "" },
+            integer,
+            fraction
+                .map(|fraction| format!(".{}", fraction))
+                .unwrap_or_default()
+        );
+
+        if fraction.is_none()
+            && let Ok(number) = digits.parse::<i64>()
+        {
+            return Some(Value::Number(Number::from(number)));
+        }
+        digits
+            .parse::<f64>()
+            .ok()
+            .filter(|number| number.is_finite())
+            .and_then(Number::from_f64)
+            .map(Value::Number)
+    }
+
+    /// Reads a numeric date written in the date order of the locale, or year first.
+    ///
+    /// The day, month and year are separated by the same `/`, `.` or `-` twice. A year of
+    /// four digits first is always read year, month, day. Two-digit years are in 1970 to
+    /// 2069.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text of a string field
+    ///
+    /// # Returns
+    ///
+    /// The date as `YYYY-MM-DD`, or `None` if the text is not a valid date
+    pub fn parse_date(&self, text: &str) -> Option<String> {
+        let text: &str = text.trim();
+        let separator: char = text.chars().find(|character| !character.is_ascii_digit())?;
+        if !matches!(separator, '/' | '.' | '-') {
+            return None;
+        }
+        let text: &str = if separator == '.' {
+            text.strip_suffix('.').unwrap_or(text)
+        } else {
+            text
+        };
+        let parts: Vec<&str> = text.split(separator).collect();
+        let [first, second, third] = parts[..] else {
+            return None;
+        };
+        if [first, second, third]
+            .iter()
+            .any(|part| part.is_empty() || !part.bytes().all(|byte| byte.is_ascii_digit()))
+        {
+            return None;
+        }
+
+        let (year, month, day) = match (first.len(), self.date_order) {
+            (4, _) | (2, DateOrder::Ymd) => (first, second, third),
+            (_, DateOrder::Dmy) => (third, second, first),
+            (_, DateOrder::Mdy) => (third, first, second),
+            (_, DateOrder::Ymd) => return None,
+        };
+        if month.len() > 2 || day.len() > 2 {
+            return None;
+        }
+        let year: u32 = match year.len() {
+            4 => year.parse().ok()?,
+            2 => match year.parse::<u32>().ok()? {
+                year @ 70.. => 1900 + year,
+                year => 2000 + year,
+            },
+            _ => return None,
+        };
+        let month: u32 = month.parse().ok()?;
+        let day: u32 = day.parse().ok()?;
+        if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+            return None;
+        }
+
+        Some(format!("{:04}-{:02}-{:02}", year, month, day))
+    }
+
+    /// Reads the numbers and dates of a JSON object in the conventions of the locale, in
+    /// place, descending into nested Tasks.
+    ///
+    /// Strings in number fields are read with `parse_number`, and strings of the string
+    /// fields and lists of strings marked `#[task(date)]` with `parse_date`. Other string
+    /// fields, fields with a `one_of`, values that are not strings, and strings that cannot
+    /// be read are left as they are.
+    ///
+    /// # Arguments
+    ///
+    /// * `fields` - The descriptors of the fields of the value
+    /// * `value` - The JSON object answered by the model
+    ///
+    /// # Returns
+    ///
+    /// Whether any value was changed
+    pub fn localize_values(&self, fields: &[FieldDescriptor], value: &mut Value) -> bool {
+        let Some(object) = value.as_object_mut() else {
+            return false;
+        };
+
+        let mut changed: bool = false;
+        for field in fields {
+            let Some(entry) = object.get_mut(&field.name) else {
+                continue;
+            };
+            changed |= match field.kind {
+                FieldKind::Task | FieldKind::OptionTask => {
+                    self.localize_values(&field.children, entry)
+                }
+                FieldKind::VecTask => entry.as_array_mut().is_some_and(|items| {
+                    items.iter_mut().fold(false, |changed, item| {
+                        self.localize_values(&field.children, item) | changed
+                    })
+                }),
+                FieldKind::HashMapTask | FieldKind::BTreeMapTask => {
+                    entry.as_object_mut().is_some_and(|entries| {
+                        entries.values_mut().fold(false, |changed, entry| {
+                            self.localize_values(&field.children, entry) | changed
+                        })
+                    })
+                }
+                FieldKind::Normal => self.localize_field(field, entry),
+            };
+        }
+
+        changed
+    }
+
+    /// Reads the numbers and dates of a JSON answer like `localize_values`.
+    ///
+    /// # Arguments
+    ///
+    /// * `fields` - The descriptors of the fields of the Task
+    /// * `content` - The JSON returned by the model
+    ///
+    /// # Returns
+    ///
+    /// The content, unchanged when it is not JSON or nothing was read
+    pub fn localize_content(&self, fields: &[FieldDescriptor], content: String) -> String {
+        let mut value: Value = match serde_json::from_str(&content) {
+            Ok(value) => value,
+            Err(_) => return content,
+        };
+        if !self.localize_values(fields, &mut value) {
+            return content;
+        }
+
+        value.to_string()
+    }
+
+    /// Reads the numbers and dates of the answers of distributed generation, by field path,
+    /// like `localize_values`. The answer of a number field is read with `parse_number` as
+    /// it was written.
+    ///
+    /// # Arguments
+    ///
+    /// * `fields` - The descriptors of the fields of the Task
+    /// * `results` - The text returned for each field, by field path
+    pub fn localize_field_results(
+        &self,
+        fields: &[FieldDescriptor],
+        results: &mut [(String, String)],
+    ) {
+        for (field_path, content) in results.iter_mut() {
+            let Some(field) = find_field_descriptor(fields, field_path)
+                .filter(|field| field.kind == FieldKind::Normal)
+            else {
+                continue;
+            };
+
+            // The text of a number is read as it is, so that `1.234` is not taken for JSON
+            let mut value: Value = match field.json_type {
+                JsonType::Number => Value::String(content.trim().trim_matches('"').to_string()),
+                _ => parse_described_field_value(content, field_path, fields),
+            };
+            if self.localize_field(field, &mut value) {
+                *content = value.to_string();
+            }
+        }
+    }
+
+    /// Reads the value of a field of a plain type, or the items of a list of them.
+    fn localize_field(&self, field: &FieldDescriptor, value: &mut Value) -> bool {
+        if !field.one_of.is_empty() {
+            return false;
+        }
+
+        match (field.json_type, value) {
+            (JsonType::Array, Value::Array(items)) => {
+                items.iter_mut().fold(false, |changed, item| {
+                    self.localize_scalar(field.item_type, field.date, item) | changed
+                })
+            }
+            (json_type, value) => self.localize_scalar(json_type, field.date, value),
+        }
+    }
+
+    fn localize_scalar(&self, json_type: JsonType, date: bool, value: &mut Value) -> bool {
+        let Value::String(text) = value else {
+            return false;
+        };
+        let localized: Option<Value> = match json_type {
+            JsonType::Number => self.parse_number(text),
+            JsonType::String if date => self
+                .parse_date(text)
+                .filter(|date| date != text)
+                .map(Value::String),
+            _ => None,
+        };
+
+        match localized {
+            Some(localized) => {
+                *value = localized;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Splits a number written with a decimal comma into its digits before and after the
+/// decimal separator, without the thousands separators.
+fn split_decimal_comma(text: &str) -> Option<(String, Option<&str>)> {
+    if let Some((integer, fraction)) = text.rsplit_once(',') {
+        let integer: String = grouped_digits(integer, &['.', ' ', '\u{00A0}', '\u{202F}', '\''])?;
+        return Some((integer, Some(digits(fraction)?)));
+    }
+    if let Some(integer) = grouped_digits(text, &['.', ' ', '\u{00A0}', '\u{202F}', '\'']) {
+        return Some((integer, None));
+    }
+
+    // A single dot that separates no thousands is a decimal point, as the answer should be
+    match text.split_once('.') {
+        Some((integer, fraction)) => Some((digits(integer)?.to_string(), Some(digits(fraction)?))),
+        None => None,
+    }
+}
+
+/// Splits a number written with a decimal point like `split_decimal_comma`.
+fn split_decimal_point(text: &str) -> Option<(String, Option<&str>)> {
+    let separators: &[char] = &[',', ' ', '\u{00A0}', '\u{202F}', '\''];
+    match text.rsplit_once('.') {
+        Some((integer, fraction)) => Some((
+            grouped_digits(integer, separators)?,
+            Some(digits(fraction)?),
+        )),
+        None => Some((grouped_digits(text, separators)?, None)),
+    }
+}
+
+/// Returns the digits of an integer part whose thousands are separated by one of
+/// `separators`, or that has no separator.
+fn grouped_digits(text: &str, separators: &[char]) -> Option<String> {
+    let Some(separator) = text
+        .chars()
+        .find(|character| separators.contains(character))
+    else {
+        return digits(text).map(str::to_string);
+    };
+
+    let mut groups = text.split(separator);
+    let first: &str = digits(groups.next()?)?;
+    if first.len() > 3 {
+        return None;
+    }
+    let mut integer: String = first.to_string();
+    for group in groups {
+        if group.len() != 3 {
+            return None;
+        }
+        integer.push_str(digits(group)?);
+    }
+
+    Some(integer)
+}
+
+/// Returns the text if it is a non-empty run of ASCII digits.
+fn digits(text: &str) -> Option<&str> {
+    (!text.is_empty() && text.bytes().all(|byte| byte.is_ascii_digit())).then_some(text)
+}
+
+fn days_in_month(year: u32, month: u32) -> u32 {
+    match month {
+        2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => {
+            29
+        }
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
@@ -1,7 +1,309 @@
+//! Chat messages in the wire format of the chat completion APIs.
+//!
+//! A `Message` is built with one of its role constructors. `name` and `tool_call_id` are only
+//! serialized when they are set, so plain messages keep the `{"role", "content"}` shape every
+//! provider accepts.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::message::{Message, Role};
+//! use serde_json::json;
+//!
+//! // The messages of a captured OpenAI request
+//! let captured = json!([
+//!     {"role": "system", "content": "You extract invoices.", "name": "extractor"},
+//!     {"role": "user", "content": "Invoice 42, total 12.50 EUR"},
+//!     {"role": "assistant", "content": "{\"total\": 12.5}"},
+//!     {"role": "tool", "content": "{\"rate\": 1.08}", "tool_call_id": "call_Qa1Zk9"}
+//! ]);
+//!
+//! let messages: Vec<Message> = serde_json::from_value(captured.clone()).unwrap();
+//! assert_eq!(
+//!     messages,
+//!     vec![
+//!         Message::system("You extract invoices.").with_name("extractor"),
+//!         Message::user("Invoice 42, total 12.50 EUR"),
+//!         Message::assistant("{\"total\": 12.5}"),
+//!         Message::tool("call_Qa1Zk9", "{\"rate\": 1.08}"),
+//!     ]
+//! );
+//! assert_eq!(serde_json::to_value(&messages).unwrap(), captured);
+//!
+//! // The message of a captured OpenAI response, whose extra keys are ignored
+//! let response = json!({
+//!     "role": "assistant",
+//!     "content": "{\"total\": 12.5}",
+//!     "refusal": null,
+//!     "annotations": []
+//! });
+//! let message: Message = serde_json::from_value(response).unwrap();
+//! assert_eq!(message.role, Role::Assistant);
+//! assert_eq!(message.content.as_str(), "{\"total\": 12.5}");
+//!
+//! // Roles the APIs do not know are rejected
+//! assert!(serde_json::from_value::<Message>(json!({"role": "bot", "content": "Hi"})).is_err());
+//! ```
+
+use std::fmt;
+
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// The author of a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Instructions for the model.
+    System,
+    /// Instructions for the model, in place of `System` for OpenAI's reasoning models.
+    Developer,
+    /// Input from the user.
+    User,
+    /// A reply of the model.
+    Assistant,
+    /// The result of a tool call.
+    Tool,
+}
+
+impl Role {
+    /// Returns the name of the role in the wire format.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::System => "system",
+            Role::Developer => "developer",
+            Role::User => "user",
+            Role::Assistant => "assistant",
+            Role::Tool => "tool",
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The content of a message.
+///
+/// Text content is serialized as a plain string.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Content {
+    /// Plain text.
+    Text(String),
+}
+
+impl Content {
+    /// Returns the text of the content.
+    pub fn as_str(&self) -> &str {
+        match self {
+            Content::Text(text) => text,
+        }
+    }
+}
+
+impl fmt::Display for Content {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl From<String> for Content {
+    fn from(text: String) -> Self {
+        Content::Text(text)
+    }
+}
+
+impl From<&str> for Content {
+    fn from(text: &str) -> Self {
+        Content::Text(text.to_string())
+    }
+}
+
+/// A chat message.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Message {
-    pub role: String,
-    pub content: String,
+    /// The author of the message.
+    pub role: Role,
+    /// The content of the message.
+    pub content: Content,
+    /// An optional name distinguishing participants with the same role.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// The id of the tool call a `Role::Tool` message answers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+}
+
+impl Message {
+    /// Creates a message with the given role and text content.
+    pub fn new(role: Role, content: impl Into<String>) -> Self {
+        Self {
+            role,
+            content: Content::Text(content.into()),
+            name: None,
+            tool_call_id: None,
+        }
+    }
+
+    /// Creates a system message.
+    pub fn system(content: impl Into<String>) -> Self {
+        Self::new(Role::System, content)
+    }
+
+    /// Creates a user message.
+    pub fn user(content: impl Into<String>) -> Self {
+        Self::new(Role::User, content)
+    }
+
+    /// Creates an assistant message.
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Self::new(Role::Assistant, content)
+    }
+
+    /// Creates a message with the result of the tool call `tool_call_id`.
+    pub fn tool(tool_call_id: impl Into<String>, content: impl Into<String>) -> Self {
+        let mut message: Message = Self::new(Role::Tool, content);
+        message.tool_call_id = Some(tool_call_id.into());
+        message
+    }
+
+    /// Sets the name of the participant.
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+}
+
+/// How system messages are sent to the model.
+///
+/// Some models reject the `system` role, such as `o1-mini`, and others ignore it. The strategy
+/// of a provider is chosen from its model name unless it is set with
+/// `with_system_role_strategy`, see `SystemRoleStrategy::for_model`, and applies to every
+/// request the provider sends.
+///
+/// # Examples
+///
+/// ```rust
+/// use secretary::message::{Message, SystemRoleStrategy};
+///
+/// let messages = vec![
+///     Message::system("You extract invoices."),
+///     Message::user("Invoice 42, total 12.50 EUR"),
+/// ];
+///
+/// assert_eq!(SystemRoleStrategy::System.apply(messages.clone()), messages);
+/// assert_eq!(
+///     SystemRoleStrategy::Developer.apply(messages.clone()),
+///     vec![
+///         Message::new(secretary::message::Role::Developer, "You extract invoices."),
+///         Message::user("Invoice 42, total 12.50 EUR"),
+///     ]
+/// );
+/// assert_eq!(
+///     SystemRoleStrategy::PrependToUser.apply(messages),
+///     vec![Message::user("You extract invoices.\n\nInvoice 42, total 12.50 EUR")]
+/// );
+///
+/// assert_eq!(SystemRoleStrategy::for_model("o1-mini-2024-09-12"), SystemRoleStrategy::PrependToUser);
+/// assert_eq!(SystemRoleStrategy::for_model("o3-mini"), SystemRoleStrategy::Developer);
+/// assert_eq!(SystemRoleStrategy::for_model("gpt-4o"), SystemRoleStrategy::System);
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SystemRoleStrategy {
+    /// Sends system messages with the `system` role.
+    #[default]
+    System,
+    /// Sends system messages with the `developer` role, which OpenAI's o1 and o3 models
+    /// expect.
+    Developer,
+    /// Merges every system message into the user message after it, separated by a blank line,
+    /// for models that accept neither role.
+    PrependToUser,
+}
+
+/// The model name prefixes whose strategy is not `SystemRoleStrategy::System`, the more
+/// specific prefixes first.
+const MODEL_STRATEGIES: &[(&str, SystemRoleStrategy)] = &[
+    ("o1-mini", SystemRoleStrategy::PrependToUser),
+    ("o1-preview", SystemRoleStrategy::PrependToUser),
+    ("gemma", SystemRoleStrategy::PrependToUser),
+    ("o1", SystemRoleStrategy::Developer),
+    ("o3", SystemRoleStrategy::Developer),
+    ("o4", SystemRoleStrategy::Developer),
+];
+
+impl SystemRoleStrategy {
+    /// Returns the strategy known to work for a model.
+    ///
+    /// The `o1-mini` and `o1-preview` models and Gemma take `PrependToUser`, and the other o1,
+    /// o3 and o4 models take `Developer`. A route prefix such as `openai/` is ignored, and
+    /// every other model takes `System`.
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - The model name, e.g. `o1-mini-2024-09-12`
+    pub fn for_model(model: &str) -> Self {
+        let name: String = model
+            .rsplit('/')
+            .next()
+            .unwrap_or(model)
+            .to_ascii_lowercase();
+        MODEL_STRATEGIES
+            .iter()
+            .find(|(prefix, _)| {
+                name.strip_prefix(prefix)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('-'))
+            })
+            .map(|(_, strategy)| *strategy)
+            .unwrap_or_default()
+    }
+
+    /// Rewrites the system messages of a conversation under this strategy.
+    ///
+    /// Under `PrependToUser`, system messages that no user message follows become user
+    /// messages.
+    ///
+    /// # Arguments
+    ///
+    /// * `messages` - The messages to send, in order
+    pub fn apply(self, messages: Vec<Message>) -> Vec<Message> {
+        match self {
+            SystemRoleStrategy::System => messages,
+            SystemRoleStrategy::Developer => messages
+                .into_iter()
+                .map(|mut message| {
+                    if message.role == Role::System {
+                        message.role = Role::Developer;
+                    }
+                    message
+                })
+                .collect(),
+            SystemRoleStrategy::PrependToUser => {
+                let mut merged: Vec<Message> = Vec::with_capacity(messages.len());
+                let mut pending: Vec<String> = Vec::new();
+                for mut message in messages {
+                    match message.role {
+                        Role::System | Role::Developer => {
+                            pending.push(message.content.as_str().to_string());
+                            continue;
+                        }
+                        Role::User if !pending.is_empty() => {
+                            pending.push(message.content.as_str().to_string());
+                            message.content = pending.join("\n\n").into();
+                            pending.clear();
+                        }
+                        _ => {}
+                    }
+                    merged.push(message);
+                }
+                if !pending.is_empty() {
+                    merged.push(Message::user(pending.join("\n\n")));
+                }
+
+                merged
+            }
+        }
+    }
 }
@@ -0,0 +1,204 @@
+//! Results that carry information about how an extraction was performed.
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    adaptive::PromptStrategy, chunking::MergeConflict, guardrail::InjectionVerdict,
+    hints::HintConflict, incremental::RegenerationPath, instructions::InstructionSet,
+    language::LanguageViolation, limits::ClippedValue, llm_providers::rate_limit::RateLimitInfo,
+    locale::ExtractionLocale, ordering::OrderViolation, reasoning::TokenUsage,
+    trimming::TrimmedKey, vocabulary::NormalizedValue,
+};
+
+/// Describes how an extraction was carried out.
+///
+/// Fields are populated by the generation methods that return a `GenerationResult`;
+/// anything a method does not track is left as `None`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct GenerationMetadata {
+    /// The prompt strategy chosen by adaptive generation.
+    pub prompt_strategy: Option<PromptStrategy>,
+    /// The estimated prompt tokens of the largest request that was sent.
+    pub estimated_prompt_tokens: Option<usize>,
+    /// The rate limits reported on the response, when the extraction took a single request.
+    pub rate_limit: Option<RateLimitInfo>,
+    /// The version of the system prompt layout, see `Task::prompt_version`.
+    pub prompt_version: Option<u32>,
+    /// The prompt tokens the provider served from its prompt cache, when the extraction took
+    /// a single request and the response reported them.
+    pub cached_prompt_tokens: Option<u64>,
+    /// The paths of the fields that adaptive generation extracted with a request of their own
+    /// after the single request, because they are marked critical.
+    pub per_field_requests: Vec<String>,
+    /// The paths of the fields filled by their local extractors instead of the LLM, see the
+    /// `extractors` module.
+    pub locally_extracted: Vec<String>,
+    /// The hinted fields for which the model returned another value than the known one,
+    /// see the `hints` module.
+    pub hint_conflicts: Vec<HintConflict>,
+    /// What the provider's guardrail found in the target and did about it, when one is set,
+    /// see the `guardrail` module.
+    pub injection_verdict: Option<InjectionVerdict>,
+    /// The sampling seed sent with every request, see `RequestOptions::with_seed`.
+    pub seed: Option<u64>,
+    /// The backend configuration the provider reported as `system_fingerprint`, from the
+    /// first response that had one.
+    pub system_fingerprint: Option<String>,
+    /// The number and date conventions the target was read with, see the `locale` module.
+    pub locale: Option<ExtractionLocale>,
+    /// Conditions that make the extraction harder to reproduce or its instructions
+    /// contradictory.
+    pub warnings: Vec<GenerationWarning>,
+    /// The values written in another language than their field's `output_language`, found
+    /// with the `language-detection` feature, see the `language` module.
+    pub language_violations: Vec<LanguageViolation>,
+    /// The number of samples self-consistency extraction parsed and voted on, see the
+    /// `consistency` module.
+    pub voted_choices: Option<usize>,
+    /// The share of the voted samples that agree with the result, by field path, see the
+    /// `consistency` module.
+    pub field_agreement: BTreeMap<String, f64>,
+    /// How incremental re-extraction obtained the result, see the `incremental` module.
+    pub regeneration_path: Option<RegenerationPath>,
+    /// The share of the lines of the two versions of the target that changed, see the
+    /// `incremental` module.
+    pub changed_ratio: Option<f64>,
+    /// The number of invalid byte sequences and JSON escapes replaced with U+FFFD in the
+    /// response of the single request, see the `decoding` module.
+    pub repaired_sequences: usize,
+    /// The arrays and strings clipped to the output limits under `LimitPolicy::Truncate`,
+    /// see the `limits` module.
+    pub clipped_values: Vec<ClippedValue>,
+    /// The answers of `one_of` fields replaced by an allowed value, see the `vocabulary`
+    /// module.
+    pub normalized_values: Vec<NormalizedValue>,
+    /// The items of lists with `#[task(ordered)]` found in the target before the item
+    /// preceding them, under `OrderCheck::Flag`, see the `ordering` module.
+    pub order_violations: Vec<OrderViolation>,
+    /// The request ID of the call, see the `correlation` module.
+    pub request_id: Option<String>,
+    /// The provider's ID of the request, from a header such as `x-request-id`, when the
+    /// extraction took a single request and the response reported one.
+    pub provider_request_id: Option<String>,
+    /// The tokens the response reported it used, reasoning tokens included, when the
+    /// extraction took a single request, see the `reasoning` module.
+    pub usage: Option<TokenUsage>,
+    /// The reasoning the model reported apart from its answer, when the extraction took a
+    /// single request and the reasoning is not hidden, see the `reasoning` module.
+    pub reasoning: Option<String>,
+    /// The model a `Router` sent the extraction to, after any escalation, see the `router`
+    /// module.
+    pub routed_model: Option<String>,
+    /// The chunks that gave each field a non-default value, by field path, when the target
+    /// was extracted in several chunks, see the `chunking` module.
+    pub chunk_sources: BTreeMap<String, Vec<usize>>,
+    /// The fields the chunks of a chunked extraction gave different values, with the value
+    /// that won and why, see the `chunking` module.
+    pub merge_conflicts: Vec<MergeConflict>,
+}
+
+/// A condition reported in `GenerationMetadata::warnings`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum GenerationWarning {
+    /// A seed was sent but no response reported a `system_fingerprint`, so the provider or
+    /// the model most likely ignored it.
+    SeedIgnored {
+        /// The seed that was sent.
+        seed: u64,
+    },
+    /// The requests of the extraction were served by different backend configurations, so
+    /// a seed does not make them reproducible.
+    FingerprintsDiffer {
+        /// The distinct fingerprints in the order they were first reported.
+        fingerprints: Vec<String>,
+    },
+    /// One additional instruction says "always X" and another "never X", see
+    /// `InstructionSet::conflicts`.
+    ConflictingInstructions {
+        /// The text of the instruction that requires X.
+        always: String,
+        /// The text of the instruction that forbids X.
+        never: String,
+    },
+    /// Fewer samples came back than self-consistency extraction asked for, most likely
+    /// because the provider or a gateway ignored `n`, so the vote had fewer voters.
+    ChoicesIgnored {
+        /// The number of samples asked for.
+        requested: usize,
+        /// The number of samples the response had.
+        returned: usize,
+    },
+    /// A key that is not a field of the Task was removed from the model's JSON under
+    /// `UnknownKeys::Trim`, see the `trimming` module.
+    UnknownKeyTrimmed {
+        /// The path of the key, e.g. `offers[1].currency`.
+        path: String,
+        /// The value the model gave it.
+        value: Value,
+    },
+}
+
+impl GenerationWarning {
+    /// Returns the warnings for the seed of an extraction and the fingerprints its responses
+    /// reported.
+    pub(crate) fn for_fingerprints(seed: Option<u64>, fingerprints: &[String]) -> Vec<Self> {
+        let mut distinct: Vec<String> = Vec::new();
+        for fingerprint in fingerprints {
+            if !distinct.contains(fingerprint) {
+                distinct.push(fingerprint.clone());
+            }
+        }
+
+        match seed {
+            Some(seed) if distinct.is_empty() => vec![GenerationWarning::SeedIgnored { seed }],
+            _ if distinct.len() > 1 => vec![GenerationWarning::FingerprintsDiffer {
+                fingerprints: distinct,
+            }],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Returns a warning for every pair of conflicting instructions in the set.
+    pub(crate) fn for_instructions(instructions: &InstructionSet) -> Vec<Self> {
+        instructions
+            .conflicts()
+            .into_iter()
+            .map(
+                |(always, never)| GenerationWarning::ConflictingInstructions {
+                    always: always.text.clone(),
+                    never: never.text.clone(),
+                },
+            )
+            .collect()
+    }
+
+    /// Returns a warning for every key trimmed from the model's JSON.
+    pub(crate) fn for_trimmed_keys(trimmed_keys: Vec<TrimmedKey>) -> Vec<Self> {
+        trimmed_keys
+            .into_iter()
+            .map(|trimmed| GenerationWarning::UnknownKeyTrimmed {
+                path: trimmed.path,
+                value: trimmed.value,
+            })
+            .collect()
+    }
+}
+
+/// Extracted data together with metadata describing the extraction.
+#[derive(Debug, Clone, Serialize)]
+pub struct GenerationResult<T> {
+    /// The extracted data.
+    pub data: T,
+    /// Information about how the data was extracted.
+    pub metadata: GenerationMetadata,
+}
+
+impl<T> GenerationResult<T> {
+    /// Discards the metadata and returns the extracted data.
+    pub fn into_data(self) -> T {
+        self.data
+    }
+}
@@ -0,0 +1,308 @@
+//! Metrics events emitted while generating data.
+//!
+//! Every request sent to the LLM and every response that fails to parse is reported to the
+//! provider's `MetricsSink`. The default `NoopSink` discards events. Configure a sink on a
+//! provider with `with_metrics_sink`, and use `CountingSink` to inspect events in tests.
+//! The events of a request are recorded with its request ID, see the `correlation` module.
+//!
+//! # Adapting to a metrics backend
+//!
+//! A sink only needs to translate events into the backend's instruments. With the
+//! [`metrics`](https://docs.rs/metrics) crate, which also feeds Prometheus exporters:
+//!
+//! ```ignore
+//! use secretary::metrics::{MetricEvent, MetricsSink};
+//!
+//! #[derive(Debug)]
+//! struct MetricsRsSink;
+//!
+//! impl MetricsSink for MetricsRsSink {
+//!     fn record(&self, event: MetricEvent) {
+//!         metrics::counter!(format!("secretary_{}_total", event.name())).increment(1);
+//!
+//!         if let MetricEvent::RequestCompleted { latency, tokens, .. } = event {
+//!             metrics::histogram!("secretary_request_latency_seconds").record(latency.as_secs_f64());
+//!             if let Some(tokens) = tokens {
+//!                 metrics::counter!("secretary_tokens_total").increment(tokens);
+//!             }
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! With the `prometheus` crate, register the counters and histograms once, keep them in the
+//! sink struct, and call `inc()`/`observe()` on them from `record` in the same way.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//!
+//! use secretary::Task;
+//! use secretary::llm_providers::openai::OpenAILLM;
+//! use secretary::metrics::{CountingSink, MetricEvent};
+//! use secretary::traits::GenerateData;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! struct Person {
+//!     #[task(instruction = "Extract the name")]
+//!     pub name: String,
+//!     #[task(instruction = "Extract the age as a number")]
+//!     pub age: u32,
+//! }
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+//! let sink = Arc::new(CountingSink::default());
+//! let llm = OpenAILLM::new("https://api.openai.com/v1", "your-api-key", "gpt-4o-mini")?
+//!     .with_metrics_sink(sink.clone());
+//!
+//! let _ = llm.generate_data(&Person::new(), "Ada is 36", &vec![]);
+//!
+//! for event in sink.events() {
+//!     if let MetricEvent::RequestCompleted { status, latency, tokens } = event {
+//!         println!("{:?} in {:?}, {:?} tokens", status, latency, tokens);
+//!     }
+//! }
+//! println!("{} parse failures", sink.count("parse_failed"));
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    correlation::is_within,
+    guardrail::{GuardrailAction, InjectionSignal},
+    llm_providers::queue::Priority,
+};
+
+/// The generation method a parse failure happened in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum GenerationMode {
+    /// A single request in JSON mode, as in `generate_data`.
+    Json,
+    /// A single request parsed from free-form text, as in `force_generate_data`.
+    Force,
+    /// One request per field, as in `fields_generate_data`.
+    Distributed,
+}
+
+/// An observable event during data generation.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MetricEvent {
+    /// A request is about to be sent to the LLM.
+    RequestStarted,
+    /// A request finished, successfully or not.
+    RequestCompleted {
+        /// The HTTP status code, or `None` if no response was received.
+        status: Option<u16>,
+        /// The time from sending the request to reading the full response.
+        latency: Duration,
+        /// The total tokens reported in the response's `usage`, if any.
+        tokens: Option<u64>,
+    },
+    /// The LLM responded but the response could not be deserialized into the Task.
+    ParseFailed {
+        /// The generation method that failed.
+        mode: GenerationMode,
+        /// The number of fields that failed to deserialize. When the whole response is
+        /// rejected, this is the number of top-level fields of the Task.
+        field_count_failed: usize,
+    },
+    /// A throttled request will be retried according to the provider's `RetryPolicy`.
+    RetryScheduled {
+        /// The attempt number of the upcoming retry, starting at 1.
+        attempt: u32,
+    },
+    /// A result was served from a cache without contacting the LLM.
+    CacheHit,
+    /// A cache lookup found nothing and the LLM will be contacted.
+    CacheMiss,
+    /// A request started waiting in the provider's `RequestQueue`.
+    RequestQueued {
+        /// The priority of the request.
+        priority: Priority,
+        /// The number of requests waiting with this priority, including this one.
+        depth: usize,
+    },
+    /// A request left the provider's `RequestQueue`, admitted or because its deadline passed.
+    RequestDequeued {
+        /// The priority of the request.
+        priority: Priority,
+        /// The number of requests still waiting with this priority.
+        depth: usize,
+        /// How long the request waited.
+        waited: Duration,
+    },
+    /// The provider's `Guardrail` found prompt injection in the target.
+    InjectionDetected {
+        /// The distinct kinds of injection found.
+        signals: Vec<InjectionSignal>,
+        /// What the guardrail did with the target.
+        action: GuardrailAction,
+    },
+    /// A request is sent with the `Credentials` of the call instead of the provider's key.
+    CredentialsOverridden {
+        /// The `credentials::key_id` of the key, never the key itself.
+        key_id: String,
+        /// The tenant named with `Credentials::with_tenant`, if any.
+        tenant: Option<String>,
+    },
+    /// A request waited for its tenant's share of the provider's `QuotaPartitioner`.
+    TenantThrottled {
+        /// The tenant of the call.
+        tenant: String,
+        /// How long the request waited.
+        waited: Duration,
+        /// Whether the request was admitted, rather than its deadline passing.
+        admitted: bool,
+    },
+    /// A request was rejected by the hard cap of its tenant in the provider's
+    /// `QuotaPartitioner`.
+    TenantQuotaExceeded {
+        /// The tenant of the call.
+        tenant: String,
+        /// The most requests of the tenant per window.
+        max_requests: usize,
+    },
+    /// A request body is sent gzip-compressed, see `CompressionConfig`.
+    RequestCompressed {
+        /// The size of the body in bytes before compression.
+        original_bytes: usize,
+        /// The size of the body in bytes as sent.
+        compressed_bytes: usize,
+    },
+    /// The provider's `DeadLetterSink` could not store a failed call. The call still fails
+    /// with its own error.
+    DeadLetterFailed {
+        /// The error of the sink.
+        error: String,
+    },
+    /// A `RecordingLLM` could not write a request and its response. The call still returns
+    /// the response.
+    RecordingFailed {
+        /// The error of the write.
+        error: String,
+    },
+}
+
+impl MetricEvent {
+    /// Returns a stable snake_case name for the event, suitable as a metric name.
+    pub fn name(&self) -> &'static str {
+        match self {
+            MetricEvent::RequestStarted => "request_started",
+            MetricEvent::RequestCompleted { .. } => "request_completed",
+            MetricEvent::ParseFailed { .. } => "parse_failed",
+            MetricEvent::RetryScheduled { .. } => "retry_scheduled",
+            MetricEvent::CacheHit => "cache_hit",
+            MetricEvent::CacheMiss => "cache_miss",
+            MetricEvent::RequestQueued { .. } => "request_queued",
+            MetricEvent::RequestDequeued { .. } => "request_dequeued",
+            MetricEvent::InjectionDetected { .. } => "injection_detected",
+            MetricEvent::CredentialsOverridden { .. } => "credentials_overridden",
+            MetricEvent::TenantThrottled { .. } => "tenant_throttled",
+            MetricEvent::TenantQuotaExceeded { .. } => "tenant_quota_exceeded",
+            MetricEvent::RequestCompressed { .. } => "request_compressed",
+            MetricEvent::DeadLetterFailed { .. } => "dead_letter_failed",
+            MetricEvent::RecordingFailed { .. } => "recording_failed",
+        }
+    }
+}
+
+/// Receives metric events from a provider.
+///
+/// Events are recorded from the generating thread or task, including the worker threads of
+/// distributed generation, so implementations must be thread-safe and should not block.
+pub trait MetricsSink: Send + Sync + std::fmt::Debug {
+    /// Records a single event.
+    fn record(&self, event: MetricEvent);
+
+    /// Records an event of the request with the given ID, see the `correlation` module.
+    ///
+    /// The default records the event alone, so sinks that do not track requests only need
+    /// `record`.
+    fn record_with_request_id(&self, event: MetricEvent, _request_id: &str) {
+        self.record(event);
+    }
+}
+
+/// A sink that discards every event. This is the default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopSink;
+
+impl MetricsSink for NoopSink {
+    fn record(&self, _event: MetricEvent) {}
+}
+
+/// A sink that keeps every event in memory, in the order they were recorded, with the
+/// request ID it was recorded with.
+#[derive(Debug, Default)]
+pub struct CountingSink {
+    events: Mutex<Vec<(MetricEvent, Option<String>)>>,
+}
+
+impl CountingSink {
+    /// Returns a copy of every recorded event.
+    pub fn events(&self) -> Vec<MetricEvent> {
+        self.events
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(event, _)| event.clone())
+            .collect()
+    }
+
+    /// Returns how many events with the given `MetricEvent::name` were recorded.
+    pub fn count(&self, name: &str) -> usize {
+        self.events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(event, _)| event.name() == name)
+            .count()
+    }
+
+    /// Returns the events recorded for a request ID or the IDs of its field requests, see
+    /// `correlation::is_within`.
+    pub fn events_for_request(&self, request_id: &str) -> Vec<MetricEvent> {
+        self.events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, id)| id.as_deref().is_some_and(|id| is_within(id, request_id)))
+            .map(|(event, _)| event.clone())
+            .collect()
+    }
+
+    /// Returns the distinct request IDs events were recorded with, in the order they were
+    /// first seen.
+    pub fn request_ids(&self) -> Vec<String> {
+        let mut request_ids: Vec<String> = Vec::new();
+        for (_, id) in self.events.lock().unwrap().iter() {
+            if let Some(id) = id
+                && !request_ids.contains(id)
+            {
+                request_ids.push(id.clone());
+            }
+        }
+
+        request_ids
+    }
+}
+
+impl MetricsSink for CountingSink {
+    fn record(&self, event: MetricEvent) {
+        self.events.lock().unwrap().push((event, None));
+    }
+
+    fn record_with_request_id(&self, event: MetricEvent, request_id: &str) {
+        self.events
+            .lock()
+            .unwrap()
+            .push((event, Some(request_id.to_string())));
+    }
+}
@@ -0,0 +1,93 @@
+//! The way an extraction is sent to the model, for the `generate` entry points.
+//!
+//! `GenerateData::generate` and `AsyncGenerateData::async_generate` take a `GenerationMode`
+//! instead of having one method per mode. Options that only make sense for one mode are
+//! fields of its variant, such as the concurrency of `GenerationMode::Fields`, so adding one
+//! does not change any signature. `generate_data`, `force_generate_data` and
+//! `fields_generate_data` remain and send exactly the requests `generate` sends in the
+//! matching mode.
+//!
+//! Each mode maps to the `metrics::GenerationMode` its parse failures are reported under,
+//! so metrics are keyed the same way whichever method started the extraction.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::metrics;
+//! use secretary::mode::GenerationMode;
+//!
+//! let mode = GenerationMode::Fields { concurrency: Some(4) };
+//! assert_eq!(mode.metric_mode(), metrics::GenerationMode::Distributed);
+//! assert_eq!(GenerationMode::default(), GenerationMode::JsonMode);
+//! ```
+//!
+//! ```no_run
+//! # use secretary::Task;
+//! # use secretary::llm_providers::openai::OpenAILLM;
+//! # use secretary::mode::GenerationMode;
+//! # use secretary::traits::GenerateData;
+//! # use serde::{Serialize, Deserialize};
+//! #
+//! # #[derive(Task, Serialize, Deserialize, Debug)]
+//! # struct ProductInfo {
+//! #     #[task(instruction = "Extract the product name")]
+//! #     pub name: String,
+//! # }
+//! #
+//! # fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+//! let llm = OpenAILLM::new("https://api.openai.com/v1", "your-api-key", "gpt-4o")?;
+//! let input = "Apple MacBook Pro 16-inch costs $2,499";
+//!
+//! let product: ProductInfo = llm.generate(
+//!     &ProductInfo::new(),
+//!     input,
+//!     vec![],
+//!     GenerationMode::Fields { concurrency: Some(2) },
+//! )?;
+//! # Ok(())
+//! # }
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+use crate::{metrics, request::RequestOptions};
+
+/// How `generate` sends an extraction to the model.
+///
+/// More modes may be added, so matches on it need a wildcard arm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[non_exhaustive]
+pub enum GenerationMode {
+    /// A single request in JSON mode, as `generate_data` sends.
+    #[default]
+    JsonMode,
+    /// A single request parsed from free-form text, as `force_generate_data` sends, for
+    /// models without a JSON mode.
+    Force,
+    /// One request per field, as `fields_generate_data` sends.
+    Fields {
+        /// The most field requests in flight at once, all of them when `None`.
+        concurrency: Option<usize>,
+    },
+}
+
+impl GenerationMode {
+    /// Returns the mode that metric events of this mode are reported under.
+    pub fn metric_mode(&self) -> metrics::GenerationMode {
+        match self {
+            GenerationMode::JsonMode => metrics::GenerationMode::Json,
+            GenerationMode::Force => metrics::GenerationMode::Force,
+            GenerationMode::Fields { .. } => metrics::GenerationMode::Distributed,
+        }
+    }
+
+    /// Returns the request options of the mode.
+    pub(crate) fn request_options(&self) -> RequestOptions {
+        match self {
+            GenerationMode::Fields {
+                concurrency: Some(concurrency),
+            } => RequestOptions::default().with_concurrency(*concurrency),
+            _ => RequestOptions::default(),
+        }
+    }
+}
@@ -0,0 +1,232 @@
+//! Normalizing the text the model returns in string fields.
+//!
+//! Models return the same value with trailing whitespace, non-breaking spaces or combining
+//! accents one time and without them the next, so equality checks and deduplication of the
+//! extracted data fail on values that read the same. The struct-level
+//! `#[task(normalize_strings)]` normalizes the text of every string field of a Task in the
+//! model's answer before it is deserialized, in every generation mode:
+//!
+//! - whitespace, ASCII or Unicode, is trimmed from both ends
+//! - non-breaking spaces become plain spaces
+//! - runs of whitespace inside the text, line breaks included, collapse to one space; with
+//!   `#[task(normalize_strings = "trim")]` they are kept as they are
+//! - with the `unicode` feature, the text is put in Unicode Normalization Form C, so that an
+//!   `é` written as `e` and a combining accent equals the precomposed `é`
+//!
+//! The fields are found by their descriptors: `String` and `Option<String>` fields, the items
+//! of lists and sets of strings, and the values of maps of strings. Fields of other types
+//! and keys of maps are never changed. Each Task uses its own setting: the strings of a
+//! nested Task are normalized when the nested struct has the attribute. Normalizing text
+//! twice changes nothing.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::Task;
+//! use secretary::leniency::LeniencyProfile;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! #[task(normalize_strings)]
+//! struct Contact {
+//!     #[task(instruction = "Extract the person's name")]
+//!     pub name: String,
+//!     #[task(instruction = "Extract the person's nicknames")]
+//!     pub nicknames: Vec<String>,
+//!     #[task(instruction = "Extract the person's age")]
+//!     pub age: u32,
+//! }
+//!
+//! let answer = r#"{"name": "  Ada  Lovelace\n", "nicknames": [" Enchantress "], "age": 36}"#;
+//! let contact: Contact = LeniencyProfile::strict().from_str(answer).unwrap();
+//!
+//! assert_eq!(contact.name, "Ada Lovelace");
+//! assert_eq!(contact.nicknames, vec!["Enchantress".to_string()]);
+//! ```
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::schema::{FieldDescriptor, FieldKind, JsonType};
+
+/// How the text of string fields is normalized, from `#[task(normalize_strings)]`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum StringNormalization {
+    /// Trims the text and collapses the whitespace inside it to single spaces, the
+    /// `normalize_strings` flag or `normalize_strings = "collapse"`.
+    #[default]
+    Collapse,
+    /// Trims the text and keeps the whitespace inside it, `normalize_strings = "trim"`.
+    Trim,
+}
+
+impl StringNormalization {
+    /// Returns the normalization named as in `#[task(normalize_strings = "...")]`.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - `collapse` or `trim`
+    ///
+    /// # Returns
+    ///
+    /// The normalization, or `None` for any other name
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "collapse" => Some(StringNormalization::Collapse),
+            "trim" => Some(StringNormalization::Trim),
+            _ => None,
+        }
+    }
+
+    /// Returns the name of the normalization, as written in
+    /// `#[task(normalize_strings = "...")]`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            StringNormalization::Collapse => "collapse",
+            StringNormalization::Trim => "trim",
+        }
+    }
+
+    /// Normalizes a text, see the module documentation.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text of a string field
+    pub fn apply(&self, text: &str) -> String {
+        let trimmed: &str = text.trim();
+        let mut normalized: String = String::with_capacity(trimmed.len());
+        let mut in_whitespace: bool = false;
+        for character in trimmed.chars() {
+            if !character.is_whitespace() {
+                in_whitespace = false;
+                normalized.push(character);
+                continue;
+            }
+
+            match self {
+                StringNormalization::Collapse if in_whitespace => {}
+                StringNormalization::Collapse => normalized.push(' '),
+                StringNormalization::Trim if is_non_breaking_space(character) => {
+                    normalized.push(' ')
+                }
+                StringNormalization::Trim => normalized.push(character),
+            }
+            in_whitespace = true;
+        }
+
+        compose(normalized)
+    }
+}
+
+/// Returns whether any of the fields, nested Tasks included, has its strings normalized.
+///
+/// # Arguments
+///
+/// * `fields` - The descriptors of a Task's fields
+pub fn uses_string_normalization(fields: &[FieldDescriptor]) -> bool {
+    fields.iter().any(|field| {
+        field.normalize_strings.is_some() || uses_string_normalization(&field.children)
+    })
+}
+
+/// Normalizes the strings of the fields of a JSON value that have a `normalize_strings`, in
+/// place, descending into nested Tasks.
+///
+/// Values of other types than the field's are left as they are, so deserialization reports
+/// them as usual.
+///
+/// # Arguments
+///
+/// * `fields` - The descriptors of the fields of the value, with the field names as keys
+/// * `value` - The JSON object answered by the model
+pub fn normalize_strings(fields: &[FieldDescriptor], value: &mut Value) {
+    let Some(object) = value.as_object_mut() else {
+        return;
+    };
+
+    for field in fields {
+        let Some(entry) = object.get_mut(&field.name) else {
+            continue;
+        };
+        match field.kind {
+            FieldKind::Task | FieldKind::OptionTask => normalize_strings(&field.children, entry),
+            FieldKind::VecTask => {
+                if let Some(items) = entry.as_array_mut() {
+                    for item in items {
+                        normalize_strings(&field.children, item);
+                    }
+                }
+            }
+            FieldKind::HashMapTask | FieldKind::BTreeMapTask => {
+                if let Some(entries) = entry.as_object_mut() {
+                    for entry in entries.values_mut() {
+                        normalize_strings(&field.children, entry);
+                    }
+                }
+            }
+            FieldKind::Normal => {
+                if let Some(normalization) = field.normalize_strings {
+                    normalize_field(field, normalization, entry);
+                }
+            }
+        }
+    }
+}
+
+/// Normalizes the strings of a field of a plain type: the value of a text field, the items
+/// of a list of strings, or the values of a map of strings.
+fn normalize_field(field: &FieldDescriptor, normalization: StringNormalization, value: &mut Value) {
+    let strings: Vec<&mut Value> = match (field.json_type, value) {
+        (JsonType::String, value) => vec![value],
+        (JsonType::Array, Value::Array(items)) if field.item_type == JsonType::String => {
+            items.iter_mut().collect()
+        }
+        (JsonType::Object, Value::Object(entries)) if maps_to_strings(&field.rust_type) => {
+            entries.values_mut().collect()
+        }
+        _ => return,
+    };
+
+    for value in strings {
+        if let Value::String(text) = value {
+            *text = normalization.apply(text);
+        }
+    }
+}
+
+/// Returns whether a Rust type, such as `Option<HashMap<String,String>>`, is a map whose
+/// values are strings.
+fn maps_to_strings(rust_type: &str) -> bool {
+    let rust_type: &str = rust_type.strip_prefix("Option<").unwrap_or(rust_type);
+    let outer: &str = rust_type.split('<').next().unwrap_or(rust_type);
+    if !(outer.ends_with("HashMap") || outer.ends_with("BTreeMap")) {
+        return false;
+    }
+
+    rust_type
+        .trim_end_matches('>')
+        .rsplit(',')
+        .next()
+        .is_some_and(|value_type| value_type == "String")
+}
+
+fn is_non_breaking_space(character: char) -> bool {
+    matches!(character, '\u{00A0}' | '\u{2007}' | '\u{202F}')
+}
+
+/// Puts a text in Unicode Normalization Form C.
+#[cfg(feature = "unicode")]
+fn compose(text: String) -> String {
+    use unicode_normalization::{IsNormalized, UnicodeNormalization, is_nfc_quick};
+
+    if is_nfc_quick(text.chars()) == IsNormalized::Yes {
+        return text;
+    }
+    text.nfc().collect()
+}
+
+/// Leaves a text as it is without the `unicode` feature.
+#[cfg(not(feature = "unicode"))]
+fn compose(text: String) -> String {
+    text
+}
@@ -0,0 +1,649 @@
+//! Measuring and improving field instructions with labeled examples.
+//!
+//! `evaluate` extracts every labeled example with the current Task and reports how often each
+//! field matches its label exactly. `suggest_instructions` goes a step further: it picks the
+//! fields that match least often and asks the LLM to rewrite their instructions, showing it the
+//! inputs, expected values and actual values of the failures. Instructions live in
+//! `#[task(instruction = "...")]` attributes, so suggestions are returned rather than applied.
+//! Run `evaluate` again after updating the attributes to compare.
+//!
+//! `compress_instructions` shortens verbose instructions instead: it sends every instruction
+//! to the LLM in a single request, asks for equivalent phrasings of about `target_ratio` of
+//! their length and keeps those that the token estimator finds shorter. It is meant to be run
+//! once, not per extraction. The replacements are returned for a human to review and paste
+//! back into the attributes, or to compile into a `CompiledTask` with
+//! `CompileOptions::with_instruction_overrides`; nothing changes the prompts otherwise.
+//! `CompressionReport::measure` reports the tokens a set of replacements saves.
+//!
+//! Fields are identified by their schema path: `address.city` for nested Tasks and
+//! `items[].price` for fields of Tasks in collections.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use secretary::Task;
+//! use secretary::llm_providers::openai::OpenAILLM;
+//! use secretary::optimize::{AccuracyReport, evaluate, suggest_instructions};
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+//! struct Invoice {
+//!     #[task(instruction = "Extract the vendor name")]
+//!     pub vendor: String,
+//!     #[task(instruction = "Extract the total")]
+//!     pub total: f64,
+//! }
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+//! let examples = vec![
+//!     (
+//!         "ACME invoice. Subtotal 90.00, tax 10.00, total 100.00".to_string(),
+//!         Invoice { vendor: "ACME".to_string(), total: 100.0 },
+//!     ),
+//!     (
+//!         "Globex invoice. Subtotal 20.00, total 20.00".to_string(),
+//!         Invoice { vendor: "Globex".to_string(), total: 20.0 },
+//!     ),
+//! ];
+//! let llm = OpenAILLM::new("https://api.openai.com/v1", "your-api-key", "gpt-4o-mini")?;
+//!
+//! tokio::runtime::Runtime::new()?.block_on(async {
+//!     let report: AccuracyReport = evaluate(&llm, &examples).await?;
+//!     println!("{:.0}% of the fields match", report.overall_rate() * 100.0);
+//!
+//!     for (field, instruction) in suggest_instructions(&llm, &examples).await? {
+//!         println!("{}: {}", field, instruction);
+//!     }
+//!     Ok(())
+//! })
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+
+use futures::{StreamExt, stream};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    SecretaryError,
+    compiled::{CompileOptions, CompiledTask, ExtractionPlan, instructed_fields},
+    diff::{FieldDiff, compare},
+    message::Message,
+    schema::{FieldDescriptor, FieldKind},
+    tokens::estimate_tokens,
+    traits::{AsyncGenerateData, Task},
+    utilities::{cleanup_thinking_blocks, extract_result_content},
+};
+
+/// The number of characters of an example's input shown to the LLM when asking for a revision.
+const SNIPPET_LENGTH: usize = 300;
+
+/// Settings for `evaluate_with_options` and `suggest_instructions_with_options`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OptimizeOptions {
+    /// The maximum number of requests in flight at once.
+    pub concurrency: usize,
+    /// The number of examples to extract, starting from the first. `None` uses all of them.
+    pub sample_size: Option<usize>,
+    /// The number of worst-performing fields to ask revisions for.
+    pub max_fields: usize,
+    /// The number of failures shown to the LLM for each field.
+    pub max_failures_shown: usize,
+}
+
+impl Default for OptimizeOptions {
+    fn default() -> Self {
+        Self {
+            concurrency: 4,
+            sample_size: None,
+            max_fields: 3,
+            max_failures_shown: 5,
+        }
+    }
+}
+
+impl OptimizeOptions {
+    /// Sets the maximum number of requests in flight at once.
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Limits the evaluation to the first `sample_size` examples.
+    pub fn with_sample_size(mut self, sample_size: usize) -> Self {
+        self.sample_size = Some(sample_size);
+        self
+    }
+
+    /// Sets the number of worst-performing fields to ask revisions for.
+    pub fn with_max_fields(mut self, max_fields: usize) -> Self {
+        self.max_fields = max_fields;
+        self
+    }
+
+    /// Sets the number of failures shown to the LLM for each field.
+    pub fn with_max_failures_shown(mut self, max_failures_shown: usize) -> Self {
+        self.max_failures_shown = max_failures_shown;
+        self
+    }
+}
+
+/// How often a single field matched its label.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct FieldAccuracy {
+    /// The number of examples where the field matched exactly.
+    pub matched: usize,
+    /// The number of examples evaluated.
+    pub total: usize,
+}
+
+impl FieldAccuracy {
+    /// Returns the exact-match rate between 0 and 1, or 1 when nothing was evaluated.
+    pub fn rate(&self) -> f64 {
+        if self.total == 0 {
+            return 1.0;
+        }
+
+        self.matched as f64 / self.total as f64
+    }
+}
+
+/// Per-field exact-match rates of a Task over a set of labeled examples.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct AccuracyReport {
+    /// The number of examples evaluated.
+    pub examples: usize,
+    /// The number of examples whose extraction failed entirely. Every field counts as
+    /// mismatched for them.
+    pub failed_extractions: usize,
+    /// The accuracy of every leaf field by schema path, e.g. `address.city` or `items[].price`.
+    pub fields: BTreeMap<String, FieldAccuracy>,
+}
+
+impl AccuracyReport {
+    /// Returns the exact-match rate over every field of every example.
+    pub fn overall_rate(&self) -> f64 {
+        let matched: usize = self.fields.values().map(|field| field.matched).sum();
+        let total: usize = self.fields.values().map(|field| field.total).sum();
+
+        FieldAccuracy { matched, total }.rate()
+    }
+}
+
+/// The tokens saved by replacing one field's instruction.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct InstructionSavings {
+    /// The schema path of the field, e.g. `address.city` or `items[].price`.
+    pub path: String,
+    /// The instruction of the field's attribute.
+    pub original: String,
+    /// The replacement.
+    pub compressed: String,
+    /// The estimated tokens of the original instruction.
+    pub original_tokens: usize,
+    /// The estimated tokens of the replacement.
+    pub compressed_tokens: usize,
+}
+
+/// The tokens a set of instruction replacements saves, measured with
+/// `tokens::estimate_tokens`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct CompressionReport {
+    /// Every replacement of a field with an instruction, in the order given.
+    pub fields: Vec<InstructionSavings>,
+    /// The estimated tokens of the system prompt with the original instructions.
+    pub system_prompt_tokens: usize,
+    /// The estimated tokens of the system prompt with the replacements.
+    pub compressed_system_prompt_tokens: usize,
+    /// The estimated tokens of every distributed field prompt with the original instructions.
+    pub field_prompt_tokens: usize,
+    /// The estimated tokens of every distributed field prompt with the replacements.
+    pub compressed_field_prompt_tokens: usize,
+}
+
+impl CompressionReport {
+    /// Measures the prompts of `T`'s default instance with and without the replacements.
+    ///
+    /// # Arguments
+    ///
+    /// * `replacements` - Pairs of field path and replacement instruction, as returned by
+    ///   `compress_instructions`. Paths of no field with an instruction are left out.
+    pub fn measure<T: Task>(replacements: &[(String, String)]) -> Self {
+        let original: CompiledTask<T> = T::default().compile(CompileOptions::default());
+        let compressed: CompiledTask<T> = T::default()
+            .compile(CompileOptions::default().with_instruction_overrides(replacements.to_vec()));
+        let instructions: BTreeMap<String, String> =
+            instructed_fields(original.field_descriptors(), "")
+                .into_iter()
+                .collect();
+        let field_prompt_tokens = |compiled: &CompiledTask<T>| -> usize {
+            compiled
+                .field_requests("", &Vec::new())
+                .iter()
+                .map(|(field_prompt, _)| estimate_tokens(&field_prompt.prompt))
+                .sum()
+        };
+
+        Self {
+            fields: compressed
+                .instruction_overrides()
+                .iter()
+                .map(|(path, compressed)| {
+                    let original: String = instructions[path].clone();
+                    InstructionSavings {
+                        path: path.clone(),
+                        original_tokens: estimate_tokens(&original),
+                        compressed_tokens: estimate_tokens(compressed),
+                        original,
+                        compressed: compressed.clone(),
+                    }
+                })
+                .collect(),
+            system_prompt_tokens: estimate_tokens(original.system_prompt()),
+            compressed_system_prompt_tokens: estimate_tokens(compressed.system_prompt()),
+            field_prompt_tokens: field_prompt_tokens(&original),
+            compressed_field_prompt_tokens: field_prompt_tokens(&compressed),
+        }
+    }
+
+    /// Returns the pairs of field path and replacement instruction.
+    pub fn replacements(&self) -> Vec<(String, String)> {
+        self.fields
+            .iter()
+            .map(|field| (field.path.clone(), field.compressed.clone()))
+            .collect()
+    }
+
+    /// Returns the estimated tokens the replacements save on every system prompt.
+    pub fn saved_tokens(&self) -> usize {
+        self.system_prompt_tokens
+            .saturating_sub(self.compressed_system_prompt_tokens)
+    }
+
+    /// Returns the estimated tokens of the compressed system prompt relative to the original,
+    /// e.g. `0.6` when it is 40% shorter.
+    pub fn ratio(&self) -> f64 {
+        if self.system_prompt_tokens == 0 {
+            return 1.0;
+        }
+
+        self.compressed_system_prompt_tokens as f64 / self.system_prompt_tokens as f64
+    }
+}
+
+/// A field value that did not match its label, kept to show the LLM.
+struct FieldFailure {
+    snippet: String,
+    expected: String,
+    actual: String,
+}
+
+/// Measures how often each field of `T` is extracted exactly as labeled.
+///
+/// # Arguments
+///
+/// * `llm` - The provider to extract with
+/// * `examples` - Pairs of input text and the expected extraction
+///
+/// # Returns
+///
+/// The per-field exact-match rates
+pub async fn evaluate<L, T>(
+    llm: &L,
+    examples: &[(String, T)],
+) -> Result<AccuracyReport, Box<dyn std::error::Error + Send + Sync + 'static>>
+where
+    L: AsyncGenerateData + Sync,
+    T: Task + Send + Sync,
+{
+    evaluate_with_options(llm, examples, &OptimizeOptions::default()).await
+}
+
+/// Measures how often each field of `T` is extracted exactly as labeled, with custom settings.
+///
+/// # Arguments
+///
+/// * `llm` - The provider to extract with
+/// * `examples` - Pairs of input text and the expected extraction
+/// * `options` - The concurrency and sample size
+///
+/// # Returns
+///
+/// The per-field exact-match rates
+pub async fn evaluate_with_options<L, T>(
+    llm: &L,
+    examples: &[(String, T)],
+    options: &OptimizeOptions,
+) -> Result<AccuracyReport, Box<dyn std::error::Error + Send + Sync + 'static>>
+where
+    L: AsyncGenerateData + Sync,
+    T: Task + Send + Sync,
+{
+    Ok(run_evaluation(llm, examples, options).await.0)
+}
+
+/// Suggests revised instructions for the fields of `T` that are extracted least accurately.
+///
+/// # Arguments
+///
+/// * `llm` - The provider to extract with and to ask for revisions
+/// * `examples` - Pairs of input text and the expected extraction
+///
+/// # Returns
+///
+/// Pairs of field path and suggested instruction, worst field first. Fields that always
+/// match are left out.
+pub async fn suggest_instructions<L, T>(
+    llm: &L,
+    examples: &[(String, T)],
+) -> Result<Vec<(String, String)>, Box<dyn std::error::Error + Send + Sync + 'static>>
+where
+    L: AsyncGenerateData + Sync,
+    T: Task + Send + Sync,
+{
+    suggest_instructions_with_options(llm, examples, &OptimizeOptions::default()).await
+}
+
+/// Suggests revised instructions for the worst fields of `T`, with custom settings.
+///
+/// # Arguments
+///
+/// * `llm` - The provider to extract with and to ask for revisions
+/// * `examples` - Pairs of input text and the expected extraction
+/// * `options` - The concurrency, sample size and number of fields to revise
+///
+/// # Returns
+///
+/// Pairs of field path and suggested instruction, worst field first
+pub async fn suggest_instructions_with_options<L, T>(
+    llm: &L,
+    examples: &[(String, T)],
+    options: &OptimizeOptions,
+) -> Result<Vec<(String, String)>, Box<dyn std::error::Error + Send + Sync + 'static>>
+where
+    L: AsyncGenerateData + Sync,
+    T: Task + Send + Sync,
+{
+    let (report, mut failures) = run_evaluation(llm, examples, options).await;
+    let instructions: BTreeMap<String, String> = leaf_fields(&T::field_descriptors(), "")
+        .into_iter()
+        .collect();
+
+    let mut worst: Vec<(&String, &FieldAccuracy)> = report
+        .fields
+        .iter()
+        .filter(|(_, accuracy)| accuracy.matched < accuracy.total)
+        .collect();
+    worst.sort_by(|a, b| a.1.rate().total_cmp(&b.1.rate()));
+    worst.truncate(options.max_fields);
+
+    let requests = worst.into_iter().map(|(path, _)| {
+        let message: Message = make_revision_prompt(
+            path,
+            instructions
+                .get(path)
+                .map(String::as_str)
+                .unwrap_or_default(),
+            &failures.remove(path).unwrap_or_default(),
+            options.max_failures_shown,
+        );
+
+        async move {
+            let response: String = llm.async_send_message(message, false).await?;
+            let content: String = llm.extract_response_content(&response)?;
+            let suggestion: String = extract_result_content(&cleanup_thinking_blocks(content));
+
+            Ok::<(String, String), Box<dyn std::error::Error + Send + Sync + 'static>>((
+                path.clone(),
+                suggestion.trim().to_string(),
+            ))
+        }
+    });
+
+    stream::iter(requests)
+        .buffered(options.concurrency.max(1))
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect()
+}
+
+/// Asks the LLM for shorter phrasings of the field instructions of `T`, in a single request.
+///
+/// Replacements are kept when the token estimator finds them shorter than the original;
+/// fields the LLM leaves out or does not shorten are left out. The prompts of `T` are not
+/// changed: review the replacements and paste them into the attributes, or compile them into
+/// a `CompiledTask` with `CompileOptions::with_instruction_overrides`.
+///
+/// # Arguments
+///
+/// * `llm` - The provider to ask
+/// * `target_ratio` - The length to aim for relative to each instruction, between 0.1 and 1,
+///   e.g. `0.5` for half. Values outside the range are clamped.
+///
+/// # Returns
+///
+/// Pairs of field path and shorter instruction, in field order
+///
+/// # Errors
+///
+/// Returns `SecretaryError::JsonParsingError` if the answer is not a JSON object of strings,
+/// and the errors of the request.
+pub async fn compress_instructions<T, L>(
+    llm: &L,
+    target_ratio: f32,
+) -> Result<Vec<(String, String)>, Box<dyn std::error::Error + Send + Sync + 'static>>
+where
+    T: Task,
+    L: AsyncGenerateData + Sync,
+{
+    let instructions: Vec<(String, String)> = instructed_fields(&T::field_descriptors(), "");
+    if instructions.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let message: Message = make_compression_prompt(&instructions, target_ratio.clamp(0.1, 1.0));
+    let response: String = llm.async_send_message(message, false).await?;
+    let content: String = llm.extract_response_content(&response)?;
+    let content: String = extract_result_content(&cleanup_thinking_blocks(content));
+    let mut compressed: BTreeMap<String, String> =
+        serde_json::from_str(content.trim()).map_err(|error| SecretaryError::JsonParsingError {
+            message: error.to_string(),
+            raw_content: content.clone(),
+        })?;
+
+    Ok(instructions
+        .into_iter()
+        .filter_map(|(path, original)| {
+            let replacement: String = compressed.remove(&path)?.trim().to_string();
+            (!replacement.is_empty() && estimate_tokens(&replacement) < estimate_tokens(&original))
+                .then_some((path, replacement))
+        })
+        .collect())
+}
+
+/// Builds the request asking the LLM to shorten every instruction.
+fn make_compression_prompt(instructions: &[(String, String)], target_ratio: f32) -> Message {
+    let fields: BTreeMap<&str, &str> = instructions
+        .iter()
+        .map(|(path, instruction)| (path.as_str(), instruction.as_str()))
+        .collect();
+
+    Message::user(format!(
+        "An extraction task asks a language model to fill each of these fields, keyed by field path, using the instruction given for it:\n{}\n\nRewrite every instruction to about {}% of its length with the same meaning. Keep the allowed values, languages, defaults and formats they require, and drop filler words. Answer with a JSON object of the same field paths and the rewritten instructions, wrapped in <result></result>.",
+        serde_json::to_string_pretty(&fields).unwrap_or_default(),
+        (target_ratio * 100.0).round()
+    ))
+}
+
+/// Extracts the sampled examples and compares them with their labels.
+async fn run_evaluation<L, T>(
+    llm: &L,
+    examples: &[(String, T)],
+    options: &OptimizeOptions,
+) -> (AccuracyReport, BTreeMap<String, Vec<FieldFailure>>)
+where
+    L: AsyncGenerateData + Sync,
+    T: Task + Send + Sync,
+{
+    let sample_size: usize = options
+        .sample_size
+        .unwrap_or(examples.len())
+        .min(examples.len());
+    let fields: Vec<String> = leaf_fields(&T::field_descriptors(), "")
+        .into_iter()
+        .map(|(path, _)| path)
+        .collect();
+
+    let task: T = T::default();
+    let additional_instructions: Vec<String> = Vec::new();
+    let extractions: Vec<_> = stream::iter(&examples[..sample_size])
+        .map(|(input, _)| llm.async_generate_data(&task, input, &additional_instructions))
+        .buffered(options.concurrency.max(1))
+        .collect()
+        .await;
+
+    let mut report: AccuracyReport = AccuracyReport {
+        examples: sample_size,
+        ..AccuracyReport::default()
+    };
+    let mut failures: BTreeMap<String, Vec<FieldFailure>> = BTreeMap::new();
+
+    for ((input, expected), extraction) in examples.iter().zip(extractions) {
+        let snippet: String = input.chars().take(SNIPPET_LENGTH).collect();
+        let diffs: Result<Vec<FieldDiff>, String> = extraction
+            .map(|actual: T| compare(expected, &actual))
+            .map_err(|error| error.to_string());
+        if diffs.is_err() {
+            report.failed_extractions += 1;
+        }
+
+        for field in &fields {
+            let accuracy: &mut FieldAccuracy = report.fields.entry(field.clone()).or_default();
+            accuracy.total += 1;
+
+            let failure: Option<FieldFailure> = match &diffs {
+                Ok(diffs) => {
+                    let mismatches: Vec<&FieldDiff> = diffs
+                        .iter()
+                        .filter(|diff| affects(&schema_path(&diff.path), field))
+                        .collect();
+                    (!mismatches.is_empty()).then(|| FieldFailure {
+                        snippet: snippet.clone(),
+                        expected: describe(&mismatches, |diff| &diff.old),
+                        actual: describe(&mismatches, |diff| &diff.new),
+                    })
+                }
+                Err(error) => Some(FieldFailure {
+                    snippet: snippet.clone(),
+                    expected: String::new(),
+                    actual: format!("extraction failed: {}", error),
+                }),
+            };
+
+            match failure {
+                Some(failure) => failures.entry(field.clone()).or_default().push(failure),
+                None => accuracy.matched += 1,
+            }
+        }
+    }
+
+    (report, failures)
+}
+
+/// Builds the request asking the LLM to revise a field's instruction.
+fn make_revision_prompt(
+    path: &str,
+    instruction: &str,
+    failures: &[FieldFailure],
+    max_failures_shown: usize,
+) -> Message {
+    let mut content: String = format!(
+        "An extraction task asks a language model to fill the field `{}` using this instruction:\n{}\n\nThe model got the field wrong on these inputs:\n",
+        path, instruction
+    );
+
+    for failure in failures.iter().take(max_failures_shown) {
+        content.push_str(&format!(
+            "\nInput: {}\nExpected: {}\nActual: {}\n",
+            failure.snippet, failure.expected, failure.actual
+        ));
+    }
+
+    content.push_str(
+        "\nPropose a revised instruction that would lead to the expected values. Keep it to one or two sentences and wrap it in <result></result>.",
+    );
+
+    Message::user(content)
+}
+
+/// Returns the schema path and instruction of every leaf field, descending into nested Tasks.
+fn leaf_fields(fields: &[FieldDescriptor], prefix: &str) -> Vec<(String, String)> {
+    let mut leaves: Vec<(String, String)> = Vec::new();
+
+    for field in fields {
+        let path: String = if prefix.is_empty() {
+            field.name.clone()
+        } else {
+            format!("{}.{}", prefix, field.name)
+        };
+
+        match field.kind {
+            FieldKind::Normal => leaves.push((path, field.instruction.clone())),
+            FieldKind::Task | FieldKind::OptionTask => {
+                leaves.extend(leaf_fields(&field.children, &path))
+            }
+            FieldKind::VecTask | FieldKind::HashMapTask | FieldKind::BTreeMapTask => {
+                leaves.extend(leaf_fields(&field.children, &format!("{}[]", path)))
+            }
+        }
+    }
+
+    leaves
+}
+
+/// Turns a value path such as `items[0].price` into its schema path `items[].price`.
+fn schema_path(path: &str) -> String {
+    let mut schema_path: String = String::new();
+    let mut in_brackets: bool = false;
+
+    for character in path.chars() {
+        match character {
+            '[' => {
+                in_brackets = true;
+                schema_path.push_str("[]");
+            }
+            ']' => in_brackets = false,
+            _ if !in_brackets => schema_path.push(character),
+            _ => {}
+        }
+    }
+
+    schema_path
+}
+
+/// Returns whether a difference at one schema path concerns a leaf field, i.e. one of them
+/// contains the other.
+fn affects(diff_path: &str, field: &str) -> bool {
+    let contains = |outer: &str, inner: &str| {
+        inner
+            .strip_prefix(outer)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('.') || rest.starts_with('['))
+    };
+
+    contains(diff_path, field) || contains(field, diff_path)
+}
+
+/// Formats one side of the differences for a field, e.g. `20` or `items[1]: {"price": 2}`.
+fn describe(diffs: &[&FieldDiff], side: fn(&FieldDiff) -> &Value) -> String {
+    if let [diff] = diffs {
+        return side(diff).to_string();
+    }
+
+    diffs
+        .iter()
+        .map(|diff| format!("{}: {}", diff.path, side(diff)))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
@@ -0,0 +1,308 @@
+//! Keeping the items of lists in their order of appearance.
+//!
+//! Models asked for a list, e.g. the steps of a procedure or the transactions of a statement,
+//! like to sort, merge or deduplicate its items. `#[task(ordered)]` on a `Vec` field appends
+//! `ORDER_REQUIREMENT` to its instruction, so the single request and the field requests of
+//! distributed generation that ask for the list as a whole both ask for the order of the
+//! target, and records it in `FieldDescriptor::ordered`.
+//!
+//! The order of the answer can be verified with `RequestOptions::with_order_check`. After the
+//! extraction, `generate_data_adaptive` locates every item of an ordered list in the target
+//! and checks that the items appear one after the other:
+//!
+//! - `OrderCheck::Flag` lists the items found before the item preceding them in
+//!   `GenerationMetadata::order_violations`
+//! - `OrderCheck::Error` fails with `SecretaryError::OrderViolated` on the first of them
+//!
+//! An item is located with `locate_items`, first as a whole, exactly or ignoring case and runs
+//! of whitespace like `provenance::locate_quote`, and then by its longest word found in the
+//! target, so a lightly reworded item is still located. Items of nested Tasks are located by
+//! their longest text value. Each item is looked for after the previous one first, so
+//! repeated items are matched to successive occurrences, and a repeated item the target has
+//! only once is not a violation. Items that cannot be located, e.g. because they are
+//! paraphrased, are skipped.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::Task;
+//! use secretary::ordering::{ORDER_REQUIREMENT, check_item_order};
+//! use serde::{Deserialize, Serialize};
+//! use serde_json::json;
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! struct Recipe {
+//!     #[task(instruction = "List the steps of the recipe", ordered)]
+//!     pub steps: Vec<String>,
+//! }
+//!
+//! let fields = Recipe::field_descriptors();
+//! assert!(fields[0].ordered);
+//! assert!(fields[0].instruction.ends_with(ORDER_REQUIREMENT));
+//!
+//! let target = "First preheat the oven. Then mix the flour and the eggs. Finally bake for an hour.";
+//! let answer = json!({"steps": ["Mix the flour and the eggs", "Preheat the oven", "Bake for an hour"]});
+//!
+//! let violations = check_item_order(&fields, &answer, target);
+//! assert_eq!(violations.len(), 1);
+//! assert_eq!(violations[0].path, "steps[1]");
+//! assert_eq!(violations[0].item, "Preheat the oven");
+//! assert!(violations[0].position < violations[0].previous_position);
+//! ```
+
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    error::SecretaryError,
+    provenance::{QuoteMatch, locate_quote},
+    schema::{FieldDescriptor, FieldKind},
+    utilities::get_field_path,
+};
+
+/// The sentence appended to the instruction of a field with `#[task(ordered)]`.
+/// Kept in sync with the derive's copy.
+pub const ORDER_REQUIREMENT: &str = "Preserve the original order of appearance in the source text; do not sort, merge, or deduplicate the items.";
+
+/// The shortest word an item is located by when the whole item cannot be.
+const MIN_WORD_CHARS: usize = 4;
+
+/// What happens when the items of an ordered list are out of order, set with
+/// `RequestOptions::with_order_check`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderCheck {
+    /// List the items out of order in `GenerationMetadata::order_violations`.
+    Flag,
+    /// Fail with `SecretaryError::OrderViolated`.
+    Error,
+}
+
+/// An item of an ordered list found in the target before the item preceding it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OrderViolation {
+    /// The path of the item, e.g. `steps[2]`.
+    pub path: String,
+    /// The text the item was located by.
+    pub item: String,
+    /// The byte offset of the item in the target.
+    pub position: usize,
+    /// The byte offset of the preceding item in the target.
+    pub previous_position: usize,
+}
+
+/// Locates the items of a list in the source text, each preferably after the previous one.
+///
+/// # Arguments
+///
+/// * `source` - The text the list was extracted from
+/// * `items` - The texts of the items, in the order of the list
+///
+/// # Returns
+///
+/// The byte range of each item in `source`, `None` for the items that cannot be located
+///
+/// # Examples
+///
+/// ```rust
+/// use secretary::ordering::locate_items;
+///
+/// let source = "Stir. Wait. Stir again.";
+/// let items = ["Stir", "Wait", "stir"].map(String::from);
+/// assert_eq!(locate_items(source, &items), vec![Some(0..4), Some(6..10), Some(12..16)]);
+///
+/// // A reworded item is located by its longest word, a paraphrased one is not located
+/// let items = ["Wait a minute".to_string(), "Mix well".to_string()];
+/// assert_eq!(locate_items(source, &items), vec![Some(6..10), None]);
+/// ```
+pub fn locate_items(source: &str, items: &[String]) -> Vec<Option<Range<usize>>> {
+    let mut previous_start: Option<usize> = None;
+
+    items
+        .iter()
+        .map(|item| {
+            let range: Option<Range<usize>> = locate_item(source, item, previous_start);
+            if let Some(range) = &range
+                && previous_start.is_none_or(|previous_start| range.start >= previous_start)
+            {
+                previous_start = Some(range.start);
+            }
+            range
+        })
+        .collect()
+}
+
+/// Checks that the items of the ordered lists of extracted data appear in the target in the
+/// order of the lists.
+///
+/// # Arguments
+///
+/// * `fields` - The field descriptors, e.g. from `Task::field_descriptors()`
+/// * `value` - The extracted data as JSON
+/// * `target` - The text the data was extracted from
+///
+/// # Returns
+///
+/// The items found before the item preceding them, in field order
+pub fn check_item_order(
+    fields: &[FieldDescriptor],
+    value: &Value,
+    target: &str,
+) -> Vec<OrderViolation> {
+    let mut violations: Vec<OrderViolation> = Vec::new();
+    check_fields(fields, value, "", target, &mut violations);
+
+    violations
+}
+
+/// Returns the order violations of extracted data for the result metadata under the order
+/// check of the call, none without one, and `SecretaryError::OrderViolated` for the first
+/// under `OrderCheck::Error`.
+pub(crate) fn order_violations<T: Serialize>(
+    fields: &[FieldDescriptor],
+    data: &T,
+    target: &str,
+    order_check: Option<OrderCheck>,
+) -> Result<Vec<OrderViolation>, SecretaryError> {
+    let Some(order_check) = order_check else {
+        return Ok(Vec::new());
+    };
+    let mut violations: Vec<OrderViolation> =
+        check_item_order(fields, &serde_json::to_value(data)?, target);
+
+    match order_check {
+        OrderCheck::Error if !violations.is_empty() => {
+            let violation: OrderViolation = violations.remove(0);
+            Err(SecretaryError::OrderViolated {
+                path: violation.path,
+                item: violation.item,
+            })
+        }
+        _ => Ok(violations),
+    }
+}
+
+fn check_fields(
+    fields: &[FieldDescriptor],
+    value: &Value,
+    prefix: &str,
+    target: &str,
+    violations: &mut Vec<OrderViolation>,
+) {
+    for field in fields {
+        let Some(field_value) = get_field_path(value, &field.name) else {
+            continue;
+        };
+        let path: String = match (prefix.is_empty(), field.name.is_empty()) {
+            (true, _) => field.name.clone(),
+            (false, true) => prefix.to_string(),
+            (false, false) => format!("{}.{}", prefix, field.name),
+        };
+
+        if field.ordered
+            && let Value::Array(items) = field_value
+        {
+            check_list(items, &path, target, violations);
+        }
+
+        match (field.kind, field_value) {
+            (FieldKind::Task | FieldKind::OptionTask, _) => {
+                check_fields(&field.children, field_value, &path, target, violations);
+            }
+            (FieldKind::VecTask, Value::Array(items)) => {
+                for (index, item) in items.iter().enumerate() {
+                    let item_path: String = format!("{}[{}]", path, index);
+                    check_fields(&field.children, item, &item_path, target, violations);
+                }
+            }
+            (FieldKind::HashMapTask | FieldKind::BTreeMapTask, Value::Object(entries)) => {
+                for (key, entry) in entries {
+                    let entry_path: String = format!("{}[{}]", path, key);
+                    check_fields(&field.children, entry, &entry_path, target, violations);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Checks the items of one list, skipping those without text.
+fn check_list(items: &[Value], path: &str, target: &str, violations: &mut Vec<OrderViolation>) {
+    let texts: Vec<(usize, String)> = items
+        .iter()
+        .enumerate()
+        .filter_map(|(index, item)| item_text(item).map(|text| (index, text)))
+        .collect();
+    let ranges: Vec<Option<Range<usize>>> = locate_items(
+        target,
+        &texts
+            .iter()
+            .map(|(_, text)| text.clone())
+            .collect::<Vec<String>>(),
+    );
+
+    let mut previous_position: Option<usize> = None;
+    for ((index, text), range) in texts.into_iter().zip(ranges) {
+        let Some(range) = range else {
+            continue;
+        };
+        match previous_position {
+            Some(previous_position) if range.start < previous_position => {
+                violations.push(OrderViolation {
+                    path: format!("{}[{}]", path, index),
+                    item: text,
+                    position: range.start,
+                    previous_position,
+                });
+            }
+            _ => previous_position = Some(range.start),
+        }
+    }
+}
+
+/// Returns the text an item is located by: a string, a number or a boolean as written, and
+/// the longest text value of an object.
+fn item_text(item: &Value) -> Option<String> {
+    match item {
+        Value::String(text) if !text.trim().is_empty() => Some(text.clone()),
+        Value::Number(number) => Some(number.to_string()),
+        Value::Bool(flag) => Some(flag.to_string()),
+        Value::Object(map) => map
+            .values()
+            .filter_map(item_text)
+            .filter(|text| text.parse::<f64>().is_err())
+            .max_by_key(|text| text.chars().count()),
+        _ => None,
+    }
+}
+
+/// Locates one item, after `previous_start` when it can be found there and anywhere else
+/// otherwise, as a whole and then by its longest word.
+fn locate_item(source: &str, item: &str, previous_start: Option<usize>) -> Option<Range<usize>> {
+    let mut candidates: Vec<&str> = item
+        .split(|character: char| !character.is_alphanumeric())
+        .filter(|word| word.chars().count() >= MIN_WORD_CHARS)
+        .collect();
+    candidates.sort_by_key(|word| std::cmp::Reverse(word.chars().count()));
+    candidates.insert(0, item);
+
+    let after: usize = match previous_start {
+        Some(start) => start + source[start..].chars().next().map_or(0, char::len_utf8),
+        None => 0,
+    };
+    candidates.iter().find_map(|candidate| {
+        locate_from(source, candidate, after).or_else(|| locate_from(source, candidate, 0))
+    })
+}
+
+/// Locates text in the source from a byte offset on.
+fn locate_from(source: &str, text: &str, offset: usize) -> Option<Range<usize>> {
+    match locate_quote(&source[offset..], text) {
+        (Some(range), QuoteMatch::Exact | QuoteMatch::Normalized) => {
+            Some(offset + range.start..offset + range.end)
+        }
+        _ => None,
+    }
+}
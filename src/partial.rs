@@ -0,0 +1,344 @@
+//! Locating and working around fields that fail to deserialize.
+//!
+//! When the JSON returned for a Task does not deserialize, `diagnose` finds the precise
+//! paths that are at fault by substituting each supplied value, one at a time, into the
+//! Task's `Default`-serialized shape. Nested objects are descended into, so a bad zip code
+//! is reported as `company.address.zip` rather than as the whole `company`. The paths end up
+//! in `FieldDeserializationError::failed_fields`.
+//!
+//! `deserialize_partial` goes one step further and replaces the failed subtrees with their
+//! defaults, returning the data that did parse together with the paths that were replaced.
+//! `GenerateData::generate_partial_data` and `fields_generate_partial_data` extract data
+//! this way.
+//!
+//! Arrays are not descended into; a bad element is reported at the path of its array.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::Task;
+//! use secretary::partial::{PartialData, deserialize_partial, diagnose};
+//! use serde::{Deserialize, Serialize};
+//! use serde_json::json;
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug, PartialEq)]
+//! struct Address {
+//!     #[task(instruction = "Extract the city")]
+//!     pub city: String,
+//!     #[task(instruction = "Extract the zip code as a number")]
+//!     pub zip: u32,
+//! }
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug, PartialEq)]
+//! struct Company {
+//!     #[task(instruction = "Extract the company name")]
+//!     pub name: String,
+//!     pub address: Address,
+//! }
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug, PartialEq)]
+//! struct Lead {
+//!     #[task(instruction = "Extract the lead score")]
+//!     pub score: u32,
+//!     pub company: Company,
+//! }
+//!
+//! // A failure at the leaf
+//! let leaf = json!({"score": 7, "company": {"name": "ACME", "address": {"city": "Oslo", "zip": "N/A"}}});
+//! let error = diagnose::<Lead>(&leaf);
+//! assert_eq!(error.failed_fields, vec!["company.address.zip"]);
+//! assert_eq!(error.successful_fields, vec!["company.address.city", "company.name", "score"]);
+//!
+//! // A failure in the middle
+//! let middle = json!({"score": 7, "company": {"name": "ACME", "address": "Oslo"}});
+//! assert_eq!(diagnose::<Lead>(&middle).failed_fields, vec!["company.address"]);
+//!
+//! // A failure at the root
+//! let root = json!({"score": "high", "company": {"name": "ACME", "address": {"city": "Oslo", "zip": 150}}});
+//! let error = diagnose::<Lead>(&root);
+//! assert_eq!(error.failed_fields, vec!["score"]);
+//! assert_eq!(error.successful_fields, vec!["company"]);
+//!
+//! // The broken subtree is replaced by its default and the rest is kept
+//! let partial: PartialData<Lead> = deserialize_partial(&leaf).unwrap();
+//! assert_eq!(partial.failed_fields, vec!["company.address.zip"]);
+//! assert_eq!(partial.data.company.address.city, "Oslo");
+//! assert_eq!(partial.data.company.address.zip, 0);
+//! assert_eq!(partial.data.score, 7);
+//! ```
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{
+    error::FieldDeserializationError,
+    schema::critical_field_paths,
+    traits::Task,
+    utilities::{get_field_path, remove_field_path, set_field_path},
+};
+
+/// Data that was deserialized after replacing the fields that failed with their defaults.
+#[derive(Debug, Clone, Serialize)]
+pub struct PartialData<T> {
+    /// The deserialized data.
+    pub data: T,
+    /// The dotted paths that failed and were replaced by their defaults, empty when
+    /// everything parsed.
+    pub failed_fields: Vec<String>,
+    /// The paths of the fields that did not complete before the deadline and were left at
+    /// their defaults.
+    pub incomplete_fields: Vec<String>,
+}
+
+impl<T> PartialData<T> {
+    /// Returns whether every field completed and parsed without substitution.
+    pub fn is_complete(&self) -> bool {
+        self.failed_fields.is_empty() && self.incomplete_fields.is_empty()
+    }
+}
+
+/// Finds the paths of a JSON object that keep it from deserializing into `T`.
+///
+/// Each supplied value is tried alone in the `Default`-serialized shape of `T`. Values that
+/// fail and are objects where `T` expects an object are descended into, so the deepest
+/// failing paths are reported. Fields `T` does not have are reported as failed too.
+///
+/// # Arguments
+///
+/// * `value` - The JSON object that failed to deserialize
+///
+/// # Returns
+///
+/// A `FieldDeserializationError` with the failed paths and the paths that parsed
+pub fn diagnose<T>(value: &Value) -> FieldDeserializationError
+where
+    T: Serialize + for<'de> Deserialize<'de> + Default,
+{
+    let original_error: String = match serde_json::from_value::<T>(value.clone()) {
+        Ok(_) => String::new(),
+        Err(error) => error.to_string(),
+    };
+    let defaults: Value = serde_json::to_value(T::default()).unwrap_or(Value::Null);
+
+    let mut failed_fields: Vec<String> = Vec::new();
+    let mut successful_fields: Vec<String> = Vec::new();
+    if let Value::Object(map) = value {
+        locate_failures::<T>(
+            &defaults,
+            map,
+            "",
+            &mut failed_fields,
+            &mut successful_fields,
+        );
+    }
+
+    FieldDeserializationError {
+        failed_fields,
+        successful_fields,
+        original_error,
+        raw_field_contents: BTreeMap::new(),
+        refused_fields: Vec::new(),
+    }
+}
+
+/// Deserializes a JSON object into `T`, replacing the paths that fail with their defaults.
+///
+/// Fields that `T` requires but the object lacks are filled in from the defaults as well.
+///
+/// # Arguments
+///
+/// * `value` - The JSON object to deserialize
+///
+/// # Errors
+///
+/// Returns the `FieldDeserializationError` from `diagnose` if the data still does not
+/// deserialize after substitution.
+pub fn deserialize_partial<T>(value: &Value) -> Result<PartialData<T>, FieldDeserializationError>
+where
+    T: Serialize + for<'de> Deserialize<'de> + Default,
+{
+    if let Ok(data) = serde_json::from_value::<T>(value.clone()) {
+        return Ok(PartialData {
+            data,
+            failed_fields: Vec::new(),
+            incomplete_fields: Vec::new(),
+        });
+    }
+
+    let report: FieldDeserializationError = diagnose::<T>(value);
+    let defaults: Value = serde_json::to_value(T::default()).unwrap_or(Value::Null);
+
+    let mut repaired: Value = value.clone();
+    for path in &report.failed_fields {
+        match get_field_path(&defaults, path) {
+            Some(default) => {
+                if set_field_path(&mut repaired, path, default.clone()).is_err() {
+                    return Err(report);
+                }
+            }
+            None => remove_field_path(&mut repaired, path),
+        }
+    }
+    fill_missing(&mut repaired, &defaults);
+
+    match serde_json::from_value::<T>(repaired) {
+        Ok(data) => Ok(PartialData {
+            data,
+            failed_fields: report.failed_fields,
+            incomplete_fields: Vec::new(),
+        }),
+        Err(_) => Err(report),
+    }
+}
+
+/// Returns the critical fields of `T` that have no usable value.
+///
+/// A critical field is missing when it is absent or `null` in `value`, or when it, a field
+/// containing it, or a field inside it failed or did not complete.
+///
+/// # Arguments
+///
+/// * `value` - The JSON object returned for `T`
+/// * `failed_fields` - The paths that failed to deserialize
+/// * `incomplete_fields` - The paths that did not complete before the deadline
+///
+/// # Returns
+///
+/// The dotted paths of the missing critical fields, see `schema::critical_field_paths`
+pub fn missing_critical_fields<T: Task>(
+    value: &Value,
+    failed_fields: &[String],
+    incomplete_fields: &[String],
+) -> Vec<String> {
+    let related = |path: &str, other: &str| {
+        path == other
+            || path.starts_with(&format!("{}.", other))
+            || other.starts_with(&format!("{}.", path))
+            || other.starts_with(&format!("{}[", path))
+    };
+
+    critical_field_paths(&T::field_descriptors())
+        .into_iter()
+        .filter(|path| {
+            matches!(get_field_path(value, path), None | Some(Value::Null))
+                || failed_fields
+                    .iter()
+                    .chain(incomplete_fields)
+                    .any(|other| related(path, other))
+        })
+        .collect()
+}
+
+/// Returns the paths of the fields that `T` requires but `value` lacks, descending into the
+/// nested objects it has.
+///
+/// Distributed extraction answers every field, except the members of a group that the
+/// group's answer left out, so these are the members that were missing.
+pub(crate) fn absent_required_fields<T>(value: &Value) -> Vec<String>
+where
+    T: Serialize + for<'de> Deserialize<'de> + Default,
+{
+    let defaults: Value = serde_json::to_value(T::default()).unwrap_or(Value::Null);
+    let mut absent: Vec<String> = Vec::new();
+    if let (Value::Object(expected), Value::Object(supplied)) = (&defaults, value) {
+        locate_absent::<T>(&defaults, expected, supplied, "", &mut absent);
+    }
+
+    absent
+}
+
+/// Records the entries of `expected` that `supplied` lacks and `T` cannot do without.
+fn locate_absent<T>(
+    defaults: &Value,
+    expected: &Map<String, Value>,
+    supplied: &Map<String, Value>,
+    prefix: &str,
+    absent: &mut Vec<String>,
+) where
+    T: for<'de> Deserialize<'de>,
+{
+    for (name, default_value) in expected {
+        let path: String = if prefix.is_empty() {
+            name.clone()
+        } else {
+            format!("{}.{}", prefix, name)
+        };
+
+        match (supplied.get(name), default_value) {
+            (None, _) => {
+                let mut test: Value = defaults.clone();
+                remove_field_path(&mut test, &path);
+                if serde_json::from_value::<T>(test).is_err() {
+                    absent.push(path);
+                }
+            }
+            (Some(Value::Object(nested)), Value::Object(expected_nested)) => {
+                locate_absent::<T>(defaults, expected_nested, nested, &path, absent)
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Tries each supplied value of an object alone and records the paths that fail.
+fn locate_failures<T>(
+    defaults: &Value,
+    supplied: &Map<String, Value>,
+    prefix: &str,
+    failed_fields: &mut Vec<String>,
+    successful_fields: &mut Vec<String>,
+) where
+    T: for<'de> Deserialize<'de>,
+{
+    for (name, supplied_value) in supplied {
+        let path: String = if prefix.is_empty() {
+            name.clone()
+        } else {
+            format!("{}.{}", prefix, name)
+        };
+
+        let default_value: &Value = match get_field_path(defaults, &path) {
+            Some(default_value) => default_value,
+            None => {
+                failed_fields.push(path);
+                continue;
+            }
+        };
+
+        let mut test: Value = defaults.clone();
+        let parses: bool = set_field_path(&mut test, &path, supplied_value.clone()).is_ok()
+            && serde_json::from_value::<T>(test).is_ok();
+        if parses {
+            successful_fields.push(path);
+            continue;
+        }
+
+        match (supplied_value, default_value) {
+            (Value::Object(nested), Value::Object(_)) => {
+                let known_failures: usize = failed_fields.len();
+                locate_failures::<T>(defaults, nested, &path, failed_fields, successful_fields);
+                // The object fails as a whole, e.g. because a required field is missing
+                if failed_fields.len() == known_failures {
+                    failed_fields.push(path);
+                }
+            }
+            _ => failed_fields.push(path),
+        }
+    }
+}
+
+/// Adds the entries of `defaults` that `value` lacks, recursing into nested objects.
+fn fill_missing(value: &mut Value, defaults: &Value) {
+    if let (Value::Object(map), Value::Object(default_map)) = (value, defaults) {
+        for (name, default) in default_map {
+            match map.get_mut(name) {
+                Some(existing) => fill_missing(existing, default),
+                None => {
+                    map.insert(name.clone(), default.clone());
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,401 @@
+//! Progress events of long extractions, for interfaces that show more than a spinner.
+//!
+//! Chunked and distributed generation send many requests and can run for minutes. With a
+//! `ProgressReporter` attached, by `RequestOptions::with_progress` or
+//! `ChunkOptions::with_progress`, they report each stage as it happens:
+//!
+//! - `ChunkStarted` and `ChunkFinished` around the request of each chunk of
+//!   `generate_data_chunked`, then `MergeStarted` before the chunks are merged,
+//! - `FieldStarted` and `FieldFinished` around the request of each field of
+//!   `fields_generate_data_with_options`, by dotted path,
+//! - `RetryScheduled` before a throttled or refused request is sent again, from any method,
+//! - `RepairRoundStarted` when a `Router` sends an answer that failed to parse to its next
+//!   tier,
+//! - `Completed` when a chunked or distributed extraction succeeds.
+//!
+//! Every event carries the time since its extraction started, and the counts that place it
+//! in the whole, e.g. `ChunkFinished { completed: 3, total: 8, .. }`. Events are reported
+//! from the generating thread or task, including the worker threads of chunked and
+//! distributed generation, in the order the requests start and finish. Nothing is built or
+//! reported when no reporter is attached.
+//!
+//! `RecordingReporter` keeps the events in memory. With the `progress-channel` feature,
+//! `ChannelReporter` sends them into a `tokio::sync::mpsc` channel for async interfaces to
+//! consume as a stream, and with the `progress-bar` feature, `ProgressBar` draws a bar on
+//! stderr for command-line tools.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use std::sync::Arc;
+//!
+//! use secretary::chunking::ChunkOptions;
+//! use secretary::progress::{ProgressKind, RecordingReporter};
+//! use secretary::request::RequestOptions;
+//!
+//! let reporter = Arc::new(RecordingReporter::default());
+//!
+//! // Attached to the calls whose progress is wanted
+//! let options = RequestOptions::default().with_progress(reporter.clone());
+//! let chunk_options = ChunkOptions::default()
+//!     .with_chunk_size(4_000)
+//!     .with_progress(reporter.clone());
+//! assert_eq!(options.progress, chunk_options.progress);
+//!
+//! // ... llm.generate_data_chunked(&task, &document, vec![], &chunk_options) ...
+//!
+//! for event in reporter.events() {
+//!     if let ProgressKind::ChunkFinished { completed, total, .. } = event.kind {
+//!         println!("{}/{} chunks after {:?}", completed, total, event.elapsed);
+//!     }
+//! }
+//! ```
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+/// A stage of an extraction, see the module documentation for when each is reported.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum ProgressKind {
+    /// The request of a chunk is about to be sent.
+    ChunkStarted {
+        /// The index of the chunk, from 0.
+        index: usize,
+        /// The number of chunks.
+        total: usize,
+    },
+    /// The answer of a chunk arrived.
+    ChunkFinished {
+        /// The index of the chunk, from 0.
+        index: usize,
+        /// The number of chunks answered so far, including this one.
+        completed: usize,
+        /// The number of chunks.
+        total: usize,
+    },
+    /// The request of a field is about to be sent.
+    FieldStarted {
+        /// The dotted path of the field.
+        path: String,
+        /// The number of fields requested.
+        total: usize,
+    },
+    /// The answer of a field arrived.
+    FieldFinished {
+        /// The dotted path of the field.
+        path: String,
+        /// The number of fields answered so far, including this one.
+        completed: usize,
+        /// The number of fields requested.
+        total: usize,
+    },
+    /// A request will be sent again according to the provider's `RetryPolicy`.
+    RetryScheduled {
+        /// The attempt number of the upcoming retry, starting at 1.
+        attempt: u32,
+    },
+    /// An extraction whose answer failed to parse is sent again.
+    RepairRoundStarted {
+        /// The number of the round, starting at 1.
+        round: u32,
+    },
+    /// The results of the chunks are about to be merged.
+    MergeStarted {
+        /// The number of chunks merged.
+        chunks: usize,
+    },
+    /// The extraction succeeded.
+    Completed,
+}
+
+impl ProgressKind {
+    /// Returns a stable snake_case name for the stage.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProgressKind::ChunkStarted { .. } => "chunk_started",
+            ProgressKind::ChunkFinished { .. } => "chunk_finished",
+            ProgressKind::FieldStarted { .. } => "field_started",
+            ProgressKind::FieldFinished { .. } => "field_finished",
+            ProgressKind::RetryScheduled { .. } => "retry_scheduled",
+            ProgressKind::RepairRoundStarted { .. } => "repair_round_started",
+            ProgressKind::MergeStarted { .. } => "merge_started",
+            ProgressKind::Completed => "completed",
+        }
+    }
+}
+
+/// A stage of an extraction and when it was reached.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProgressEvent {
+    /// The stage.
+    pub kind: ProgressKind,
+    /// The time since the extraction started.
+    pub elapsed: Duration,
+}
+
+/// Receives the progress events of the extractions it is attached to.
+///
+/// Events are reported from the generating thread or task, including worker threads, so
+/// implementations must be thread-safe and should not block.
+pub trait ProgressReporter: Send + Sync + std::fmt::Debug {
+    /// Reports a single event.
+    fn report(&self, event: ProgressEvent);
+}
+
+/// A reporter attached to a call, with the time its extraction started.
+///
+/// Two are equal when they report to the same reporter.
+#[derive(Debug, Clone)]
+pub struct Progress {
+    reporter: Arc<dyn ProgressReporter>,
+    started: Option<Instant>,
+}
+
+impl Progress {
+    /// Attaches a reporter. The extraction's clock starts when the call does.
+    pub fn new(reporter: Arc<dyn ProgressReporter>) -> Self {
+        Self {
+            reporter,
+            started: None,
+        }
+    }
+
+    /// Returns the reporter.
+    pub fn reporter(&self) -> &Arc<dyn ProgressReporter> {
+        &self.reporter
+    }
+
+    /// Returns a copy whose clock runs from now, unless it already runs, so the events of
+    /// nested calls are timed from the start of the outermost one.
+    pub(crate) fn started(&self) -> Self {
+        Self {
+            reporter: Arc::clone(&self.reporter),
+            started: Some(self.started.unwrap_or_else(Instant::now)),
+        }
+    }
+
+    /// Reports a stage, timed from the start of the extraction.
+    pub(crate) fn report(&self, kind: ProgressKind) {
+        self.reporter.report(ProgressEvent {
+            kind,
+            elapsed: self
+                .started
+                .map(|started| started.elapsed())
+                .unwrap_or_default(),
+        });
+    }
+}
+
+impl PartialEq for Progress {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.reporter, &other.reporter)
+    }
+}
+
+impl Eq for Progress {}
+
+/// Reports a stage if a reporter is attached, only building the stage then.
+pub(crate) fn report(progress: Option<&Progress>, kind: impl FnOnce() -> ProgressKind) {
+    if let Some(progress) = progress {
+        progress.report(kind());
+    }
+}
+
+/// A reporter that keeps every event in memory, in the order they were reported.
+#[derive(Debug, Default)]
+pub struct RecordingReporter {
+    events: Mutex<Vec<ProgressEvent>>,
+}
+
+impl RecordingReporter {
+    /// Returns a copy of every reported event.
+    pub fn events(&self) -> Vec<ProgressEvent> {
+        self.events.lock().unwrap().clone()
+    }
+
+    /// Returns the stages of every reported event, without their times.
+    pub fn kinds(&self) -> Vec<ProgressKind> {
+        self.events
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|event| event.kind.clone())
+            .collect()
+    }
+}
+
+impl ProgressReporter for RecordingReporter {
+    fn report(&self, event: ProgressEvent) {
+        self.events.lock().unwrap().push(event);
+    }
+}
+
+/// A reporter that sends every event into an unbounded `tokio::sync::mpsc` channel.
+///
+/// Events reported after the receiver is dropped are discarded.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::sync::Arc;
+///
+/// use secretary::progress::ChannelReporter;
+/// use secretary::request::RequestOptions;
+///
+/// let (reporter, mut events) = ChannelReporter::new();
+/// let options = RequestOptions::default().with_progress(Arc::new(reporter));
+///
+/// // ... spawn llm.async_fields_generate_data_with_options(&task, target, vec![], &options) ...
+///
+/// # drop(options);
+/// while let Some(event) = events.blocking_recv() {
+///     println!("{} after {:?}", event.kind.name(), event.elapsed);
+/// }
+/// ```
+#[cfg(feature = "progress-channel")]
+#[derive(Debug, Clone)]
+pub struct ChannelReporter {
+    sender: tokio::sync::mpsc::UnboundedSender<ProgressEvent>,
+}
+
+#[cfg(feature = "progress-channel")]
+impl ChannelReporter {
+    /// Returns a reporter and the receiver of its events.
+    pub fn new() -> (Self, tokio::sync::mpsc::UnboundedReceiver<ProgressEvent>) {
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        (Self { sender }, receiver)
+    }
+
+    /// Returns a reporter that sends into an existing channel.
+    ///
+    /// # Arguments
+    ///
+    /// * `sender` - The sending half of the channel
+    pub fn from_sender(sender: tokio::sync::mpsc::UnboundedSender<ProgressEvent>) -> Self {
+        Self { sender }
+    }
+}
+
+#[cfg(feature = "progress-channel")]
+impl ProgressReporter for ChannelReporter {
+    fn report(&self, event: ProgressEvent) {
+        let _ = self.sender.send(event);
+    }
+}
+
+/// A reporter that draws a progress bar of the chunks or fields answered on stderr.
+///
+/// The bar is redrawn in place on each answer, and the line is ended when the extraction
+/// completes.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::time::Duration;
+///
+/// use secretary::progress::{ProgressBar, ProgressEvent, ProgressKind};
+///
+/// let bar = ProgressBar::default().with_width(10);
+/// let event = ProgressEvent {
+///     kind: ProgressKind::FieldFinished {
+///         path: "address.city".to_string(),
+///         completed: 3,
+///         total: 4,
+///     },
+///     elapsed: Duration::from_millis(1500),
+/// };
+/// assert_eq!(
+///     bar.render(&event).unwrap(),
+///     "[#######   ] 3/4 fields, address.city (1.5s)"
+/// );
+/// ```
+#[cfg(feature = "progress-bar")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProgressBar {
+    /// The number of characters between the brackets.
+    pub width: usize,
+}
+
+#[cfg(feature = "progress-bar")]
+impl Default for ProgressBar {
+    fn default() -> Self {
+        Self { width: 30 }
+    }
+}
+
+#[cfg(feature = "progress-bar")]
+impl ProgressBar {
+    /// Sets the number of characters between the brackets.
+    pub fn with_width(mut self, width: usize) -> Self {
+        self.width = width.max(1);
+        self
+    }
+
+    /// Returns the line drawn for an event, or `None` for events that leave the bar as it
+    /// is.
+    pub fn render(&self, event: &ProgressEvent) -> Option<String> {
+        let seconds: f64 = event.elapsed.as_secs_f64();
+        match &event.kind {
+            ProgressKind::ChunkFinished {
+                completed, total, ..
+            } => Some(format!(
+                "{} {}/{} chunks ({:.1}s)",
+                self.bar(*completed, *total),
+                completed,
+                total,
+                seconds
+            )),
+            ProgressKind::FieldFinished {
+                path,
+                completed,
+                total,
+            } => Some(format!(
+                "{} {}/{} fields, {} ({:.1}s)",
+                self.bar(*completed, *total),
+                completed,
+                total,
+                path,
+                seconds
+            )),
+            ProgressKind::RetryScheduled { attempt } => {
+                Some(format!("retrying, attempt {} ({:.1}s)", attempt, seconds))
+            }
+            ProgressKind::RepairRoundStarted { round } => {
+                Some(format!("repairing, round {} ({:.1}s)", round, seconds))
+            }
+            ProgressKind::MergeStarted { chunks } => {
+                Some(format!("merging {} chunks ({:.1}s)", chunks, seconds))
+            }
+            ProgressKind::Completed => Some(format!("done ({:.1}s)", seconds)),
+            ProgressKind::ChunkStarted { .. } | ProgressKind::FieldStarted { .. } => None,
+        }
+    }
+
+    fn bar(&self, completed: usize, total: usize) -> String {
+        let filled: usize = (completed * self.width)
+            .checked_div(total)
+            .unwrap_or(self.width)
+            .min(self.width);
+        format!(
+            "[{}{}]",
+            "#".repeat(filled),
+            " ".repeat(self.width - filled)
+        )
+    }
+}
+
+#[cfg(feature = "progress-bar")]
+impl ProgressReporter for ProgressBar {
+    fn report(&self, event: ProgressEvent) {
+        let Some(line) = self.render(&event) else {
+            return;
+        };
+        // Clears the rest of the previous line, which may have been longer
+        match event.kind {
+            ProgressKind::Completed => eprintln!("\r{}\x1b[K", line),
+            _ => eprint!("\r{}\x1b[K", line),
+        }
+    }
+}
@@ -0,0 +1,618 @@
+//! Versioned layouts of the generated system prompts.
+//!
+//! Extraction quality depends on the wording of the system prompt, so the layout the derive
+//! macro generates is versioned. A struct renders `DEFAULT_PROMPT_VERSION` unless it pins a
+//! version with `#[task(prompt_version = N)]`, and the default only moves when the crate
+//! intentionally changes the generated wording. The version is available as
+//! `Task::prompt_version()` and is recorded in `GenerationMetadata::prompt_version`.
+//!
+//! - Version 1 lists each field as `name: instruction, JSON type`, with nested Tasks in
+//!   `--- ... ---` blocks, followed by the JSON of the struct.
+//! - Version 2 opens with the task, lists the fields as an indented outline of names, types
+//!   and instructions, and ends with the JSON template.
+//! - Version 3 is version 1 with every `Vec` and map of nested Tasks described once, by an
+//!   element built from the element type, whatever the instance holds. The template shows
+//!   that one element too, so a struct holding hundreds of example elements renders the
+//!   same prompt as an empty one.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::Task;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! struct Address {
+//!     #[task(instruction = "Extract the city")]
+//!     pub city: String,
+//! }
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! struct Person {
+//!     #[task(instruction = "Extract the name")]
+//!     pub name: String,
+//!     #[task(instruction = "Extract the age")]
+//!     pub age: Option<u32>,
+//!     #[task(instruction = "Extract the nicknames")]
+//!     pub nicknames: Vec<String>,
+//!     pub address: Address,
+//! }
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! #[task(prompt_version = 2)]
+//! struct PersonV2 {
+//!     #[task(instruction = "Extract the name")]
+//!     pub name: String,
+//!     #[task(instruction = "Extract the age")]
+//!     pub age: Option<u32>,
+//!     #[task(instruction = "Extract the nicknames")]
+//!     pub nicknames: Vec<String>,
+//!     pub address: Address,
+//! }
+//!
+//! assert_eq!(Person::prompt_version(), 1);
+//! assert_eq!(
+//!     Person::new().get_system_prompt(),
+//!     "name: Extract the name, JSON String\n\
+//!      age: Extract the age, JSON Number or JSON Null\n\
+//!      nicknames: Extract the nicknames, JSON String(s) in a JSON Array\n\
+//!      \n\
+//!      --- address Task Details ---\n\
+//!      city: Extract the city, JSON String\n\
+//!      {\n  \"city\": \"\"\n}\
+//!      --- End of address Task ---\n\
+//!      \n\
+//!      {\n  \"name\": \"\",\n  \"age\": null,\n  \"nicknames\": [],\n  \"address\": {\n    \"city\": \"\"\n  }\n}"
+//! );
+//!
+//! assert_eq!(PersonV2::prompt_version(), 2);
+//! assert_eq!(
+//!     PersonV2::new().get_system_prompt(),
+//!     "Extract the fields below from the text and answer with a single JSON object \
+//!      shaped like the template at the end.\n\
+//!      \n\
+//!      Fields:\n\
+//!      - name (string): Extract the name\n\
+//!      - age (number, optional): Extract the age\n\
+//!      - nicknames (array of string): Extract the nicknames\n\
+//!      - address (object):\n  \
+//!        - city (string): Extract the city\n\
+//!      \n\
+//!      Use null for optional fields that are not mentioned.\n\
+//!      \n\
+//!      Template:\n\
+//!      {\n  \"name\": \"\",\n  \"age\": null,\n  \"nicknames\": [],\n  \"address\": {\n    \"city\": \"\"\n  }\n}"
+//! );
+//! ```
+//!
+//! # Negative examples
+//!
+//! A field can show the LLM a wrong value and why it is wrong with
+//! `#[task(negative_example = "...", negative_reason = "...")]`; the pair can be repeated.
+//! Both layouts list them in a "Common mistakes to avoid" section before the template, and
+//! a distributed prompt lists only the negative examples of its own field. Prompts without
+//! negative examples have no such section.
+//!
+//! ```rust
+//! use secretary::Task;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! struct Contact {
+//!     #[task(instruction = "Extract the name")]
+//!     pub name: String,
+//!     #[task(
+//!         instruction = "Extract the email",
+//!         negative_example = "unknown@example.com",
+//!         negative_reason = "the text has no email, so the answer is null"
+//!     )]
+//!     pub email: Option<String>,
+//! }
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! #[task(prompt_version = 2)]
+//! struct ContactV2 {
+//!     #[task(
+//!         instruction = "Extract the name",
+//!         negative_example = "Mr. Smith",
+//!         negative_reason = "titles are not part of the name",
+//!         negative_example = "SMITH",
+//!         negative_reason = "the name keeps the casing of the text"
+//!     )]
+//!     pub name: String,
+//! }
+//!
+//! assert_eq!(
+//!     Contact::new().get_system_prompt(),
+//!     "name: Extract the name, JSON String\n\
+//!      email: Extract the email, JSON String or JSON Null\n\
+//!      \n\
+//!      Common mistakes to avoid:\n\
+//!      - email: WRONG output: unknown@example.com, because the text has no email, so the answer is null\n\
+//!      \n\
+//!      {\n  \"name\": \"\",\n  \"email\": null\n}"
+//! );
+//!
+//! let template: String = serde_json::to_string_pretty(&Contact::new()).unwrap();
+//! assert_eq!(
+//!     Contact::static_prompt_len() + template.len(),
+//!     Contact::new().get_system_prompt().len()
+//! );
+//!
+//! let prompts = Contact::new().get_distributed_field_prompts();
+//! assert!(!prompts[0].prompt.contains("Common mistakes"));
+//! assert_eq!(
+//!     prompts[1].prompt,
+//!     "Output a value according to criteria and wrap them in <result></result>.\n\
+//!      - email: Extract the email, JSON String or JSON Null\n\
+//!      \n\
+//!      Common mistakes to avoid:\n\
+//!      - email: WRONG output: unknown@example.com, because the text has no email, so the answer is null\n"
+//! );
+//!
+//! assert_eq!(
+//!     ContactV2::new().get_system_prompt(),
+//!     "Extract the fields below from the text and answer with a single JSON object \
+//!      shaped like the template at the end.\n\
+//!      \n\
+//!      Fields:\n\
+//!      - name (string): Extract the name\n\
+//!      \n\
+//!      Use null for optional fields that are not mentioned.\n\
+//!      \n\
+//!      Common mistakes to avoid:\n\
+//!      - name: WRONG output: Mr. Smith, because titles are not part of the name\n\
+//!      - name: WRONG output: SMITH, because the name keeps the casing of the text\n\
+//!      \n\
+//!      Template:\n\
+//!      {\n  \"name\": \"\"\n}"
+//! );
+//! ```
+//!
+//! A negative example without a reason is rejected at compile time:
+//!
+//! ```compile_fail
+//! use secretary::Task;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! struct Contact {
+//!     #[task(instruction = "Extract the email", negative_example = "N/A")]
+//!     pub email: Option<String>,
+//! }
+//! ```
+//!
+//! # Empty defaults
+//!
+//! By default the derived `Default` fills `Vec`s and maps of nested Tasks with example entries
+//! and wraps optional nested Tasks in `Some`, which is how the prompt shows their shape.
+//! `#[task(empty_defaults)]` on the struct makes `Default` return empty collections and
+//! `None` instead. The prompt then describes an empty field with one example element built
+//! from the element type, and `Task::prompt_template()` shows that element in the template,
+//! without touching the instance. In distributed mode an empty field is requested as a whole.
+//!
+//! ```rust
+//! use std::collections::HashMap;
+//!
+//! use secretary::Task;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug, PartialEq)]
+//! struct Line {
+//!     #[task(instruction = "Extract the product")]
+//!     pub product: String,
+//! }
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug, PartialEq)]
+//! #[task(empty_defaults)]
+//! struct Order {
+//!     #[task(instruction = "Extract every line item")]
+//!     pub lines: Vec<Line>,
+//!     #[task(instruction = "Extract the gift wrapping line, if any")]
+//!     pub gift: Option<Line>,
+//!     #[task(instruction = "Extract the lines by SKU")]
+//!     pub by_sku: HashMap<String, Line>,
+//! }
+//!
+//! let order = Order::new();
+//! assert!(order.lines.is_empty());
+//! assert_eq!(order.gift, None);
+//! assert!(order.by_sku.is_empty());
+//!
+//! let line_prompt: String = Line::new().get_system_prompt();
+//! assert_eq!(
+//!     order.get_system_prompt(),
+//!     format!(
+//!         "lines: Extract every line item, JSON Object(s) in a JSON Array\n\
+//!          \n\
+//!          --- lines Collection (any number of items) ---\n\
+//!          {line_prompt}\n\
+//!          --- End of lines Collection ---\n\
+//!          \n\
+//!          gift: Extract the gift wrapping line, if any, JSON Object or JSON Null\n\
+//!          \n\
+//!          --- gift Optional Task (null when absent) ---\n\
+//!          {line_prompt}\n\
+//!          --- End of gift Optional Task ---\n\
+//!          \n\
+//!          by_sku: Extract the lines by SKU, JSON Object\n\
+//!          \n\
+//!          --- by_sku HashMap (any number of entries) ---\n  \
+//!          Key '<key>': {line_prompt}\n\
+//!          --- End of by_sku HashMap ---\n\
+//!          \n\
+//!          {{\n  \"by_sku\": {{\n    \"key\": {{\n      \"product\": \"\"\n    }}\n  }},\n  \
+//!          \"gift\": {{\n    \"product\": \"\"\n  }},\n  \
+//!          \"lines\": [\n    {{\n      \"product\": \"\"\n    }}\n  ]\n}}"
+//!     )
+//! );
+//!
+//! // Each empty field is requested as a whole, with the fields of its elements
+//! let prompts = order.get_distributed_field_prompts();
+//! let paths: Vec<&str> = prompts.iter().map(|prompt| prompt.field_path.as_str()).collect();
+//! assert_eq!(paths, vec!["lines", "gift", "by_sku"]);
+//! assert_eq!(
+//!     prompts[0].prompt,
+//!     "Output a value according to criteria and wrap them in <result></result>.\n\
+//!      - lines: Extract every line item, JSON Object(s) in a JSON Array\n\n\
+//!      Each item has these fields:\n  \
+//!      - product: Extract the product, JSON String\n"
+//! );
+//! assert!(prompts[1].prompt.contains("It has these fields:\n  - product: Extract the product"));
+//! ```
+//!
+//! # Preamble and postamble
+//!
+//! `#[task(preamble = "...")]` and `#[task(postamble = "...")]` on the struct frame its
+//! prompts with fixed text, such as data handling notices or the language of the answer.
+//! The system prompt, the compact system prompt and every distributed field prompt open with
+//! the preamble and end with the postamble, each separated by a blank line; they are available
+//! as `Task::preamble()` and `Task::postamble()`. Multiline and raw strings are kept as
+//! written. A nested Task's framing stays inside its block and field prompts.
+//!
+//! The framing is the struct's own text, not part of the generated layout, so it does not
+//! change `Task::prompt_version()`. It is counted by `Task::static_prompt_len()`. Prompts of
+//! structs without framing are unchanged.
+//!
+//! ```rust
+//! use secretary::Task;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! #[task(
+//!     preamble = "Do not retain this data.",
+//!     postamble = "Answer in English, even when the text is not."
+//! )]
+//! struct Consent {
+//!     #[task(instruction = "Extract the name")]
+//!     pub name: String,
+//! }
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! #[task(
+//!     prompt_version = 2,
+//!     preamble = r#"The text is a "claim form".
+//! A claimant is the person filing it."#
+//! )]
+//! struct Claim {
+//!     #[task(instruction = "Extract the claimant")]
+//!     pub claimant: String,
+//! }
+//!
+//! assert_eq!(Consent::preamble(), Some("Do not retain this data."));
+//! assert_eq!(Consent::prompt_version(), 1);
+//! assert_eq!(
+//!     Consent::new().get_system_prompt(),
+//!     "Do not retain this data.\n\
+//!      \n\
+//!      name: Extract the name, JSON String\n\
+//!      {\n  \"name\": \"\"\n}\n\
+//!      \n\
+//!      Answer in English, even when the text is not."
+//! );
+//! assert_eq!(
+//!     Consent::new().get_distributed_field_prompts()[0].prompt,
+//!     "Do not retain this data.\n\
+//!      \n\
+//!      Output a value according to criteria and wrap them in <result></result>.\n\
+//!      - name: Extract the name, JSON String\n\
+//!      \n\
+//!      Answer in English, even when the text is not."
+//! );
+//! assert_eq!(
+//!     Consent::new().get_compact_system_prompt(),
+//!     "Do not retain this data.\n\nname: string\n{\"name\":\"\"}\n\nAnswer in English, even when the text is not."
+//! );
+//!
+//! let template: String = serde_json::to_string_pretty(&Consent::new()).unwrap();
+//! assert_eq!(
+//!     Consent::static_prompt_len() + template.len(),
+//!     Consent::new().get_system_prompt().len()
+//! );
+//!
+//! assert_eq!(Claim::prompt_version(), 2);
+//! assert_eq!(Claim::postamble(), None);
+//! assert_eq!(
+//!     Claim::new().get_system_prompt(),
+//!     "The text is a \"claim form\".\n\
+//!      A claimant is the person filing it.\n\
+//!      \n\
+//!      Extract the fields below from the text and answer with a single JSON object \
+//!      shaped like the template at the end.\n\
+//!      \n\
+//!      Fields:\n\
+//!      - claimant (string): Extract the claimant\n\
+//!      \n\
+//!      Use null for optional fields that are not mentioned.\n\
+//!      \n\
+//!      Template:\n\
+//!      {\n  \"claimant\": \"\"\n}"
+//! );
+//! assert!(
+//!     Claim::new().get_distributed_field_prompts()[0]
+//!         .prompt
+//!         .starts_with("The text is a \"claim form\".\nA claimant is the person filing it.\n\nOutput a value")
+//! );
+//! ```
+//!
+//! An empty preamble or postamble is rejected at compile time:
+//!
+//! ```compile_fail
+//! use secretary::Task;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! #[task(preamble = "")]
+//! struct Person {
+//!     #[task(instruction = "Extract the name")]
+//!     pub name: String,
+//! }
+//! ```
+//!
+//! # Prompt size budget
+//!
+//! `Task::static_prompt_len()` is the part of the system prompt known at compile time: the
+//! lines of the struct's own fields and the section of their negative examples. Nested Tasks
+//! and the JSON template are rendered at runtime and are not counted.
+//! `#[task(max_prompt_chars = N)]` on the struct makes the derive fail when the count is
+//! over `N`, with a message such as `the static system prompt of Invoice is 199 chars, over
+//! the max_prompt_chars budget of 60; longest instructions: vendor (56 chars), currency (25
+//! chars), total (17 chars)`.
+//!
+//! ```rust
+//! use secretary::Task;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! #[task(max_prompt_chars = 4000)]
+//! struct Invoice {
+//!     #[task(instruction = "Extract the vendor name")]
+//!     pub vendor: String,
+//!     #[task(instruction = "Extract the total")]
+//!     pub total: f64,
+//! }
+//!
+//! let template: String = serde_json::to_string_pretty(&Invoice::new()).unwrap();
+//! assert_eq!(Invoice::static_prompt_len(), 83);
+//! assert_eq!(
+//!     Invoice::static_prompt_len() + template.len(),
+//!     Invoice::new().get_system_prompt().len()
+//! );
+//! ```
+//!
+//! ```compile_fail
+//! use secretary::Task;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! #[task(max_prompt_chars = 60)]
+//! struct Invoice {
+//!     #[task(instruction = "Extract the vendor name as written on the invoice header")]
+//!     pub vendor: String,
+//!     #[task(instruction = "Extract the total")]
+//!     pub total: f64,
+//! }
+//! ```
+//!
+//! Versions the crate cannot render are rejected at compile time:
+//!
+//! ```compile_fail
+//! use secretary::Task;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! #[task(prompt_version = 4)]
+//! struct Person {
+//!     #[task(instruction = "Extract the name")]
+//!     pub name: String,
+//! }
+//! ```
+
+use crate::casing::{template_with_prompt_keys, uses_key_case};
+use crate::extractors::template_without;
+use crate::schema::{FieldDescriptor, FieldKind, JsonType, NegativeExample};
+
+/// The prompt layout rendered by structs that do not pin a version.
+pub const DEFAULT_PROMPT_VERSION: u32 = 1;
+
+/// The newest prompt layout that can be pinned with `#[task(prompt_version = N)]`.
+pub const LATEST_PROMPT_VERSION: u32 = 3;
+
+/// Renders the version 2 system prompt from field descriptors and the JSON template.
+///
+/// Generated by `#[derive(Task)]` for structs with `#[task(prompt_version = 2)]`.
+///
+/// # Arguments
+///
+/// * `fields` - The descriptors returned by `Task::field_descriptors()`
+/// * `template` - The pretty-printed JSON the model should fill in
+pub fn render_system_prompt_v2(fields: &[FieldDescriptor], template: &str) -> String {
+    let mut prompt: String = String::from(
+        "Extract the fields below from the text and answer with a single JSON object shaped like the template at the end.\n\nFields:\n",
+    );
+    write_field_outline(fields, 0, &mut prompt);
+    prompt.push_str("\nUse null for optional fields that are not mentioned.\n");
+    let mistakes: String = render_common_mistakes(fields, &[]);
+    if !mistakes.is_empty() {
+        prompt.push('\n');
+        prompt.push_str(&mistakes);
+    }
+    prompt.push_str("\nTemplate:\n");
+    prompt.push_str(template);
+
+    prompt
+}
+
+/// Returns a Task's JSON template as its system prompt shows it: without the skipped fields,
+/// see `extractors::template_without`, and with the keys the model sees, see
+/// `casing::template_with_prompt_keys`.
+///
+/// Generated by `#[derive(Task)]`. The template is returned as it is, without being parsed
+/// or copied, when there are no skipped fields and no key cases.
+///
+/// # Arguments
+///
+/// * `template` - The template of `Task::prompt_template()`
+/// * `skipped_fields` - The dotted paths of the fields to leave out
+/// * `fields` - The descriptors returned by `Task::field_descriptors()`
+pub fn prompt_template(
+    template: String,
+    skipped_fields: &[String],
+    fields: &[FieldDescriptor],
+) -> String {
+    let template: String = if skipped_fields.is_empty() {
+        template
+    } else {
+        template_without(&template, skipped_fields)
+    };
+    if !uses_key_case(fields) {
+        return template;
+    }
+
+    template_with_prompt_keys(&template, fields)
+}
+
+/// Places a Task's preamble before a prompt and its postamble after it.
+///
+/// Each is separated from the prompt by one blank line. The prompt is returned unchanged when
+/// the Task has neither, so framing never alters the prompts of other Tasks.
+///
+/// # Arguments
+///
+/// * `prompt` - The rendered prompt
+/// * `preamble` - The text of `Task::preamble()`
+/// * `postamble` - The text of `Task::postamble()`
+pub fn frame_prompt(prompt: String, preamble: Option<&str>, postamble: Option<&str>) -> String {
+    if preamble.is_none() && postamble.is_none() {
+        return prompt;
+    }
+
+    let mut framed: String = String::new();
+    if let Some(preamble) = preamble {
+        framed.push_str(preamble);
+        framed.push_str("\n\n");
+    }
+    match postamble {
+        Some(postamble) => {
+            framed.push_str(prompt.trim_end_matches('\n'));
+            framed.push_str("\n\n");
+            framed.push_str(postamble);
+        }
+        None => framed.push_str(&prompt),
+    }
+
+    framed
+}
+
+/// Renders the "Common mistakes to avoid" section of a prompt.
+///
+/// Lists the negative examples of the fields, labelled with their dotted paths, followed by
+/// the whole-output negative examples. Returns an empty string when there are none, so
+/// prompts without negative examples are unchanged.
+///
+/// # Arguments
+///
+/// * `fields` - The descriptors whose negative examples are listed, nested Tasks included
+/// * `examples` - Negative examples of the whole output
+pub fn render_common_mistakes(fields: &[FieldDescriptor], examples: &[NegativeExample]) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    collect_field_mistakes(fields, "", &mut lines);
+    lines.extend(examples.iter().map(describe_mistake));
+
+    if lines.is_empty() {
+        return String::new();
+    }
+
+    let mut section: String = String::from("Common mistakes to avoid:\n");
+    for line in lines {
+        section.push_str(&format!("- {}\n", line));
+    }
+
+    section
+}
+
+/// Adds one line per negative example of the fields and their nested Tasks.
+fn collect_field_mistakes(fields: &[FieldDescriptor], prefix: &str, lines: &mut Vec<String>) {
+    for field in fields {
+        let path: String = if prefix.is_empty() {
+            field.key()
+        } else {
+            format!("{}.{}", prefix, field.key())
+        };
+        for example in &field.negative_examples {
+            lines.push(format!("{}: {}", path, describe_mistake(example)));
+        }
+        collect_field_mistakes(&field.children, &path, lines);
+    }
+}
+
+/// Describes a negative example, e.g. `WRONG output: N/A, because the text has no email`.
+fn describe_mistake(example: &NegativeExample) -> String {
+    let mistake: String = format!(
+        "WRONG output: {}, because {}",
+        example.output, example.reason
+    );
+
+    match &example.input {
+        Some(input) => format!("Input: {} -> {}", input, mistake),
+        None => mistake,
+    }
+}
+
+/// Writes one outline line per field, indenting the fields of nested Tasks.
+fn write_field_outline(fields: &[FieldDescriptor], depth: usize, output: &mut String) {
+    for field in fields {
+        output.push_str(&"  ".repeat(depth));
+        output.push_str(&format!("- {} ({})", field.key(), describe_type(field)));
+        if !field.instruction.is_empty() {
+            output.push_str(&format!(": {}", field.instruction));
+        } else if !field.children.is_empty() {
+            output.push(':');
+        }
+        output.push('\n');
+        write_field_outline(&field.children, depth + 1, output);
+    }
+}
+
+/// Describes a field's type, e.g. `array of string` or `object, optional`.
+fn describe_type(field: &FieldDescriptor) -> String {
+    let (description, optional): (String, bool) = match field.kind {
+        FieldKind::Normal => match (field.json_type, field.item_type) {
+            (JsonType::Array, JsonType::Any) => ("array".to_string(), field.optional),
+            (JsonType::Array, item_type) => (format!("array of {}", item_type), field.optional),
+            (json_type, _) => (json_type.to_string(), field.optional),
+        },
+        FieldKind::Task => ("object".to_string(), false),
+        FieldKind::OptionTask => ("object".to_string(), true),
+        FieldKind::VecTask => ("array of objects".to_string(), field.optional),
+        FieldKind::HashMapTask | FieldKind::BTreeMapTask => {
+            ("object of objects by key".to_string(), field.optional)
+        }
+    };
+
+    if optional {
+        format!("{}, optional", description)
+    } else {
+        description
+    }
+}
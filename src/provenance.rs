@@ -0,0 +1,333 @@
+//! Locating the source text each extracted value came from.
+//!
+//! `GenerateData::generate_data_with_provenance` asks the model to answer every field as
+//! `{"value": ..., "evidence": "..."}`, where the evidence is a short verbatim quote from the
+//! target supporting the value. The wrapped template is generated from
+//! `Task::field_descriptors()`: the fields of nested Tasks are wrapped one by one, while lists
+//! and maps of nested Tasks are wrapped as a whole.
+//!
+//! The wrappers are stripped before deserializing `T`, and each quote is looked up in the
+//! target, first exactly and then ignoring case and runs of whitespace. Quotes that cannot
+//! be found are kept with `QuoteMatch::NotFound` so fabricated evidence can be flagged, and
+//! fields answered without evidence are listed with `QuoteMatch::Missing`.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::Task;
+//! use secretary::provenance::provenance_system_prompt;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! struct Address {
+//!     #[task(instruction = "Extract the city")]
+//!     pub city: String,
+//! }
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! struct Order {
+//!     #[task(instruction = "Extract the customer name")]
+//!     pub customer: String,
+//!     #[task(instruction = "Extract the order total")]
+//!     pub total: f64,
+//!     #[task(instruction = "Extract the coupon code")]
+//!     pub coupon: Option<String>,
+//!     #[task(instruction = "Extract the delivery date")]
+//!     pub delivery: Option<String>,
+//!     pub address: Address,
+//! }
+//!
+//! // The template asks for a value and a quote per field
+//! let prompt: String = provenance_system_prompt(&Order::new());
+//! assert!(prompt.starts_with(&Order::new().get_system_prompt()));
+//! assert!(prompt.contains("\"customer\": {\n    \"evidence\": \"\",\n    \"value\": \"\"\n  }"));
+//! assert!(prompt.contains("\"city\": {\n      \"evidence\": \"\",\n      \"value\": \"\"\n    }"));
+//! ```
+//!
+//! The quotes are located in the target and reported with the data:
+//!
+//! ```no_run
+//! # use secretary::Task;
+//! # use serde::{Deserialize, Serialize};
+//! #
+//! # #[derive(Task, Serialize, Deserialize, Debug)]
+//! # struct Order {
+//! #     #[task(instruction = "Extract the customer name")]
+//! #     pub customer: String,
+//! # }
+//! #
+//! use secretary::llm_providers::openai::OpenAILLM;
+//! use secretary::provenance::{ProvenanceResult, QuoteMatch};
+//! use secretary::traits::GenerateData;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+//! let llm = OpenAILLM::new("https://api.openai.com/v1", "your-api-key", "gpt-4o-mini")?;
+//! let target = "Order from Ada Lovelace, shipping to London.\nTotal:   120.50 EUR";
+//!
+//! let result: ProvenanceResult<Order> = llm.generate_data_with_provenance(&Order::new(), target, &vec![])?;
+//! for provenance in &result.provenance {
+//!     match (&provenance.match_kind, &provenance.char_range) {
+//!         (QuoteMatch::Exact | QuoteMatch::Normalized, Some(range)) => {
+//!             println!("{}: {:?}", provenance.field_path, &target[range.clone()]);
+//!         }
+//!         _ => println!("{}: no evidence found", provenance.field_path),
+//!     }
+//! }
+//!
+//! // Quotes that are not in the target may have been made up
+//! let unlocated: Vec<&str> = result.unlocated().map(|provenance| provenance.field_path.as_str()).collect();
+//! println!("Check {:?}", unlocated);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{
+    schema::{FieldDescriptor, FieldKind},
+    traits::Task,
+};
+
+/// How the evidence quote of a field was found in the target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuoteMatch {
+    /// The quote appears verbatim in the target.
+    Exact,
+    /// The quote appears in the target when case and runs of whitespace are ignored.
+    Normalized,
+    /// The quote does not appear in the target, e.g. because the model made it up.
+    NotFound,
+    /// The model gave no quote for the field.
+    Missing,
+}
+
+/// Where the value of a field came from in the target.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Provenance {
+    /// The dotted path of the field, e.g. `address.city`.
+    pub field_path: String,
+    /// The quote the model gave as evidence, empty when it gave none.
+    pub quote: String,
+    /// The byte range of the quote in the target, so `&target[range]` is the matched text.
+    /// `None` when the quote was not found or is missing.
+    pub char_range: Option<Range<usize>>,
+    /// How the quote was found.
+    pub match_kind: QuoteMatch,
+}
+
+impl Provenance {
+    /// Returns whether the quote was found in the target.
+    pub fn is_located(&self) -> bool {
+        self.char_range.is_some()
+    }
+}
+
+/// The data extracted by `generate_data_with_provenance` with the evidence of each field.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProvenanceResult<T> {
+    /// The extracted data, with the evidence wrappers stripped.
+    pub data: T,
+    /// The evidence of each field, in the order of `Task::field_descriptors()`.
+    pub provenance: Vec<Provenance>,
+}
+
+impl<T> ProvenanceResult<T> {
+    /// Returns the fields whose quote was given but could not be found in the target.
+    pub fn unlocated(&self) -> impl Iterator<Item = &Provenance> {
+        self.provenance
+            .iter()
+            .filter(|provenance| provenance.match_kind == QuoteMatch::NotFound)
+    }
+}
+
+/// Renders the system prompt asking for a value and a supporting quote per field.
+///
+/// The regular system prompt of the task describes the fields; the wrapped template that
+/// follows it replaces its JSON template.
+pub fn provenance_system_prompt<T: Task>(task: &T) -> String {
+    let template: Value = serde_json::to_value(task).unwrap_or(Value::Null);
+
+    let mut prompt: String = task.get_system_prompt();
+    prompt.push_str(
+        "\n\nAnswer with the JSON below instead of the JSON above. Every field is an object whose \"value\" is the value described above and whose \"evidence\" is a short verbatim quote from the text supporting it, or \"\" when the text does not mention the field:\n",
+    );
+    prompt.push_str(
+        &serde_json::to_string_pretty(&wrap_fields(&template, &T::field_descriptors()))
+            .unwrap_or_default(),
+    );
+
+    prompt
+}
+
+/// Strips the evidence wrappers from a response and locates each quote in the target.
+///
+/// Fields answered without a wrapper are kept as they are and reported as
+/// `QuoteMatch::Missing`.
+///
+/// # Arguments
+///
+/// * `value` - The JSON object returned for the wrapped template
+/// * `target` - The text the data was extracted from
+///
+/// # Returns
+///
+/// The plain JSON for `T` and the provenance of each field
+pub fn strip_evidence<T: Task>(value: &Value, target: &str) -> (Value, Vec<Provenance>) {
+    let mut provenance: Vec<Provenance> = Vec::new();
+    let value: Value = unwrap_fields(value, &T::field_descriptors(), "", target, &mut provenance);
+
+    (value, provenance)
+}
+
+/// Finds a quote in the source text, exactly or ignoring case and runs of whitespace.
+///
+/// # Returns
+///
+/// The byte range of the match in `source` and how it was found. An empty quote is
+/// `QuoteMatch::Missing`.
+pub fn locate_quote(source: &str, quote: &str) -> (Option<Range<usize>>, QuoteMatch) {
+    if quote.trim().is_empty() {
+        return (None, QuoteMatch::Missing);
+    }
+    if let Some(start) = source.find(quote) {
+        return (Some(start..start + quote.len()), QuoteMatch::Exact);
+    }
+
+    let source: Vec<(char, Range<usize>)> = normalize(source);
+    let quote: Vec<char> = normalize(quote)
+        .into_iter()
+        .map(|(character, _)| character)
+        .collect();
+    let found = source.windows(quote.len()).find(|window| {
+        window
+            .iter()
+            .map(|(character, _)| *character)
+            .eq(quote.iter().copied())
+    });
+
+    match found {
+        Some(window) => (
+            Some(window[0].1.start..window[window.len() - 1].1.end),
+            QuoteMatch::Normalized,
+        ),
+        None => (None, QuoteMatch::NotFound),
+    }
+}
+
+/// Lowercases text and collapses runs of whitespace into one space, keeping the byte range
+/// of the original text each character came from.
+fn normalize(text: &str) -> Vec<(char, Range<usize>)> {
+    let mut characters: Vec<(char, Range<usize>)> = Vec::new();
+    let mut in_whitespace: bool = false;
+    let offset: usize = text.len() - text.trim_start().len();
+
+    for (index, character) in text.trim().char_indices() {
+        let range: Range<usize> = offset + index..offset + index + character.len_utf8();
+        if character.is_whitespace() {
+            if !in_whitespace {
+                characters.push((' ', range));
+            }
+            in_whitespace = true;
+            continue;
+        }
+
+        in_whitespace = false;
+        characters.extend(character.to_lowercase().map(|lower| (lower, range.clone())));
+    }
+
+    characters
+}
+
+/// Returns whether the fields of a nested Task are wrapped one by one.
+fn is_nested_task(field: &FieldDescriptor) -> bool {
+    matches!(field.kind, FieldKind::Task | FieldKind::OptionTask) && !field.children.is_empty()
+}
+
+/// Replaces each field of the template with a `{"value": ..., "evidence": ""}` wrapper,
+/// descending into nested Tasks.
+fn wrap_fields(template: &Value, fields: &[FieldDescriptor]) -> Value {
+    let mut wrapped: Map<String, Value> = Map::new();
+    for field in fields {
+        let value: Value = template.get(&field.name).cloned().unwrap_or(Value::Null);
+        let field_value: Value = if is_nested_task(field) && value.is_object() {
+            wrap_fields(&value, &field.children)
+        } else {
+            let mut wrapper: Map<String, Value> = Map::new();
+            wrapper.insert("value".to_string(), value);
+            wrapper.insert("evidence".to_string(), Value::String(String::new()));
+            Value::Object(wrapper)
+        };
+        wrapped.insert(field.name.clone(), field_value);
+    }
+
+    Value::Object(wrapped)
+}
+
+/// Undoes `wrap_fields` on a response, recording the provenance of each wrapped field.
+fn unwrap_fields(
+    value: &Value,
+    fields: &[FieldDescriptor],
+    prefix: &str,
+    target: &str,
+    provenance: &mut Vec<Provenance>,
+) -> Value {
+    let Value::Object(map) = value else {
+        return value.clone();
+    };
+
+    let mut unwrapped: Map<String, Value> = map.clone();
+    for field in fields {
+        let Some(field_value) = map.get(&field.name) else {
+            continue;
+        };
+        let field_path: String = if prefix.is_empty() {
+            field.name.clone()
+        } else {
+            format!("{}.{}", prefix, field.name)
+        };
+
+        let wrapper: Option<&Map<String, Value>> = field_value
+            .as_object()
+            .filter(|object| object.contains_key("value"));
+        let plain: Value = match wrapper {
+            Some(wrapper) => {
+                let quote: String = wrapper
+                    .get("evidence")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_string();
+                let (char_range, match_kind) = locate_quote(target, &quote);
+                provenance.push(Provenance {
+                    field_path,
+                    quote,
+                    char_range,
+                    match_kind,
+                });
+                wrapper.get("value").cloned().unwrap_or(Value::Null)
+            }
+            None if is_nested_task(field) && field_value.is_object() => unwrap_fields(
+                field_value,
+                &field.children,
+                &field_path,
+                target,
+                provenance,
+            ),
+            None => {
+                provenance.push(Provenance {
+                    field_path,
+                    quote: String::new(),
+                    char_range: None,
+                    match_kind: QuoteMatch::Missing,
+                });
+                field_value.clone()
+            }
+        };
+        unwrapped.insert(field.name.clone(), plain);
+    }
+
+    Value::Object(unwrapped)
+}
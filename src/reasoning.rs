@@ -0,0 +1,194 @@
+//! Reasoning that models report apart from their answer.
+//!
+//! Reasoning models such as OpenAI's o-series and DeepSeek's R1 spend completion tokens on
+//! reasoning before they answer. DeepSeek returns the reasoning as
+//! `message.reasoning_content`, and some OpenAI-compatible servers as `message.reasoning`,
+//! next to the answer in `message.content`; OpenAI's Responses API returns a summary of it as
+//! `reasoning` output items. The reasoning tokens are counted in
+//! `usage.completion_tokens_details.reasoning_tokens`, or
+//! `usage.output_tokens_details.reasoning_tokens` for the Responses API.
+//!
+//! The reasoning is never parsed as the answer: `generate_data_adaptive` reports it in
+//! `metadata.reasoning`, and the token counts of the response in `metadata.usage`, for its
+//! single request. `RequestOptions::with_hidden_reasoning` leaves the reasoning text out of
+//! the metadata, for reasoning that must not be logged; the token counts are still reported.
+//!
+//! Models that reason inline, in `<think>` blocks of their answer, have their blocks removed
+//! by `utilities::cleanup_thinking_blocks` instead. When a response reports its reasoning
+//! apart, the answer is used as it is, since it holds no reasoning to remove.
+//!
+//! `RequestOptions::with_reasoning_effort` asks the model to reason more or less, sent as
+//! `reasoning_effort`, or `reasoning.effort` by the Responses API provider. Bedrock does not
+//! send it.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::reasoning::{TokenUsage, extract_reasoning_from_llm_response, strip_inline_reasoning};
+//! use serde_json::json;
+//!
+//! let response = json!({
+//!     "choices": [{"message": {
+//!         "role": "assistant",
+//!         "reasoning_content": "The name is in the first sentence.",
+//!         "content": "{\"name\": \"Ada\"}"
+//!     }}],
+//!     "usage": {
+//!         "prompt_tokens": 50,
+//!         "completion_tokens": 120,
+//!         "total_tokens": 170,
+//!         "completion_tokens_details": {"reasoning_tokens": 100}
+//!     }
+//! })
+//! .to_string();
+//!
+//! assert_eq!(
+//!     extract_reasoning_from_llm_response(&response).as_deref(),
+//!     Some("The name is in the first sentence.")
+//! );
+//! let usage = TokenUsage::from_response(&response).unwrap();
+//! assert_eq!(usage.reasoning_tokens, Some(100));
+//! assert_eq!(usage.answer_tokens(), Some(20));
+//!
+//! // Inline reasoning is only removed from answers without reasoning of their own
+//! let inline = "<think>\nThe name is Ada.\n</think>\n{\"name\": \"Ada\"}";
+//! assert_eq!(strip_inline_reasoning(inline.to_string(), "{}"), "{\"name\": \"Ada\"}");
+//! ```
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::utilities::{cleanup_thinking_blocks, needs_thinking_cleanup};
+
+/// How much a reasoning model reasons before it answers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReasoningEffort {
+    /// Answers sooner, with fewer reasoning tokens.
+    Low,
+    /// The default of OpenAI's reasoning models.
+    Medium,
+    /// Reasons the longest, for the hardest extractions.
+    High,
+}
+
+impl ReasoningEffort {
+    /// Returns the value sent as `reasoning_effort`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReasoningEffort::Low => "low",
+            ReasoningEffort::Medium => "medium",
+            ReasoningEffort::High => "high",
+        }
+    }
+}
+
+/// The tokens a response reports it used.
+///
+/// Counts the response does not report are `None`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct TokenUsage {
+    /// The tokens of the prompt.
+    pub prompt_tokens: Option<u64>,
+    /// The tokens the model generated, reasoning included.
+    pub completion_tokens: Option<u64>,
+    /// The tokens the model spent on reasoning, which are billed as completion tokens but
+    /// are not part of the answer.
+    pub reasoning_tokens: Option<u64>,
+    /// The prompt and completion tokens.
+    pub total_tokens: Option<u64>,
+}
+
+impl TokenUsage {
+    /// Reads the usage of a chat completions or Responses API response.
+    ///
+    /// # Arguments
+    ///
+    /// * `api_response` - The raw JSON response body
+    ///
+    /// # Returns
+    ///
+    /// The usage, or `None` when the response reports none
+    pub fn from_response(api_response: &str) -> Option<Self> {
+        let value: Value = serde_json::from_str(api_response).ok()?;
+        let usage: &Value = value.get("usage")?;
+        let count = |keys: &[&str]| keys.iter().find_map(|key| usage[*key].as_u64());
+
+        Some(TokenUsage {
+            prompt_tokens: count(&["prompt_tokens", "input_tokens"]),
+            completion_tokens: count(&["completion_tokens", "output_tokens"]),
+            reasoning_tokens: usage["completion_tokens_details"]["reasoning_tokens"]
+                .as_u64()
+                .or_else(|| usage["output_tokens_details"]["reasoning_tokens"].as_u64()),
+            total_tokens: count(&["total_tokens"]),
+        })
+    }
+
+    /// Returns the completion tokens that are not reasoning, the tokens of the answer.
+    ///
+    /// # Returns
+    ///
+    /// The answer tokens, or `None` when the response does not report its completion tokens
+    pub fn answer_tokens(&self) -> Option<u64> {
+        let completion_tokens: u64 = self.completion_tokens?;
+        Some(completion_tokens.saturating_sub(self.reasoning_tokens.unwrap_or(0)))
+    }
+}
+
+/// Extracts the reasoning a response reports apart from its answer.
+///
+/// Reads `message.reasoning_content` of the first choice, as reported by DeepSeek, falling
+/// back to `message.reasoning`, and then to the summaries of the `reasoning` output items of
+/// a Responses API response.
+///
+/// # Arguments
+///
+/// * `api_response` - The raw JSON response body
+///
+/// # Returns
+///
+/// The reasoning, or `None` when the response has none or it is empty
+pub fn extract_reasoning_from_llm_response(api_response: &str) -> Option<String> {
+    let value: Value = serde_json::from_str(api_response).ok()?;
+    let message: &Value = &value["choices"][0]["message"];
+    let reasoning: String = match message["reasoning_content"]
+        .as_str()
+        .or_else(|| message["reasoning"].as_str())
+    {
+        Some(reasoning) => reasoning.to_string(),
+        None => value["output"]
+            .as_array()?
+            .iter()
+            .filter(|item| item["type"] == "reasoning")
+            .filter_map(|item| item["summary"].as_array())
+            .flatten()
+            .filter_map(|summary| summary["text"].as_str())
+            .collect::<Vec<&str>>()
+            .join("\n\n"),
+    };
+
+    Some(reasoning).filter(|reasoning| !reasoning.trim().is_empty())
+}
+
+/// Removes the `<think>` blocks of an answer, unless the response reports its reasoning
+/// apart.
+///
+/// # Arguments
+///
+/// * `content` - The answer extracted from the response
+/// * `api_response` - The raw JSON response body the answer was extracted from
+///
+/// # Returns
+///
+/// The answer without inline reasoning
+pub fn strip_inline_reasoning(content: String, api_response: &str) -> String {
+    // Whether the response reports its reasoning apart takes parsing all of it, which is
+    // only worth it when there is something to remove
+    if !needs_thinking_cleanup(&content)
+        || extract_reasoning_from_llm_response(api_response).is_some()
+    {
+        return content;
+    }
+
+    cleanup_thinking_blocks(content)
+}
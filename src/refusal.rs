@@ -0,0 +1,124 @@
+//! Refusals of the model to answer.
+//!
+//! A model that declines a request, for example under its content policy, answers in prose
+//! where JSON was asked for. Such answers fail with `SecretaryError::Refused` instead of a
+//! parse error, so they can be told apart from malformed output:
+//!
+//! - a chat completion whose message has a `refusal` and no content, or a Responses API
+//!   answer with a `refusal` block and no text, is refused with the provider's message
+//! - content that does not parse is refused when `detect_refusal` finds it to be one
+//!
+//! `detect_refusal` is conservative, since an answer taken for a refusal is lost. The text
+//! must be short, hold no JSON object, array or `<result>` tags, open with an apology or a
+//! first-person statement, and decline in so many words: "I can't help with that", "I'm
+//! unable to assist", "I must decline". An answer that only apologizes, or says it cannot
+//! find something, is not a refusal.
+//!
+//! Refused requests are not retried, since asking again rarely changes the answer. A
+//! `RetryPolicy` built with `with_retry_refusals(true)` retries them like throttled
+//! requests. In distributed generation each field is judged on its own: the fields whose
+//! answer is a refusal are listed in `FieldDeserializationError::refused_fields`, and in the
+//! `failed_fields` of partial data.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::refusal::detect_refusal;
+//!
+//! assert_eq!(
+//!     detect_refusal("I'm sorry, but I can't help with extracting personal data."),
+//!     Some("I'm sorry, but I can't help with extracting personal data.".to_string())
+//! );
+//! assert!(detect_refusal("I cannot assist with that request.").is_some());
+//! assert!(detect_refusal("Unfortunately, I must decline this request.").is_some());
+//!
+//! // Answers that apologize or miss a value without declining
+//! assert_eq!(detect_refusal("I'm sorry, I can only answer in prose: Ada is 36."), None);
+//! assert_eq!(detect_refusal("I cannot find a date in the text."), None);
+//! // Answers with data in them
+//! assert_eq!(detect_refusal(r#"{"note": "Sorry, I can't help with that"}"#), None);
+//! assert_eq!(detect_refusal("<result>I can't help with that</result>"), None);
+//! ```
+
+use crate::utilities::cleanup_thinking_blocks;
+
+/// The longest text, in characters, that is taken for a refusal.
+const MAX_REFUSAL_CHARS: usize = 500;
+
+/// How a refusal opens.
+const OPENINGS: [&str; 9] = [
+    "i'm sorry",
+    "i am sorry",
+    "sorry",
+    "i apologize",
+    "i apologise",
+    "apologies",
+    "unfortunately",
+    "as an ai",
+    "i ",
+];
+
+/// The phrases that negate an ability or a willingness.
+const NEGATIONS: [&str; 9] = [
+    "can't ",
+    "cannot ",
+    "can not ",
+    "won't ",
+    "will not ",
+    "unable to ",
+    "not able to ",
+    "not allowed to ",
+    "not permitted to ",
+];
+
+/// The requests a refusal declines, following a negation.
+const DECLINED: [&str; 10] = [
+    "help",
+    "assist",
+    "comply",
+    "fulfill",
+    "fulfil",
+    "do that",
+    "do this",
+    "provide that",
+    "provide this",
+    "engage",
+];
+
+/// Phrases that decline on their own.
+const DECLINES: [&str; 3] = ["must decline", "have to decline", "i decline"];
+
+/// Returns the text of a refusal, trimmed, or `None` when the content is not one.
+///
+/// Thinking blocks are left out. See the module documentation for what counts as a refusal.
+///
+/// # Arguments
+///
+/// * `content` - The content the model returned
+pub fn detect_refusal(content: &str) -> Option<String> {
+    let text: String = cleanup_thinking_blocks(content.to_string())
+        .trim()
+        .to_string();
+    if text.is_empty()
+        || text.chars().count() > MAX_REFUSAL_CHARS
+        || text.contains(['{', '['])
+        || text.contains("<result")
+    {
+        return None;
+    }
+
+    let lowered: String = text.to_lowercase().replace('\u{2019}', "'");
+    if !OPENINGS.iter().any(|opening| lowered.starts_with(opening)) {
+        return None;
+    }
+
+    let declines: bool = DECLINES.iter().any(|phrase| lowered.contains(phrase))
+        || NEGATIONS.iter().any(|negation| {
+            lowered.match_indices(negation).any(|(index, _)| {
+                let rest: &str = &lowered[index + negation.len()..];
+                DECLINED.iter().any(|request| rest.starts_with(request))
+            })
+        });
+
+    declines.then_some(text)
+}
@@ -0,0 +1,162 @@
+//! Checks that an extraction can be reproduced.
+//!
+//! OpenAI-compatible providers sample deterministically, on a best-effort basis, when a request
+//! carries a `seed`, and report the backend configuration that served it as
+//! `system_fingerprint`. Set the seed with `RequestOptions::with_seed` and extract with
+//! `generate_data_adaptive_with_options`: every request of the extraction, including the
+//! per-field requests of distributed generation, carries the seed, and the metadata records
+//! the seed, the fingerprint and a `GenerationWarning::SeedIgnored` when no response reported
+//! a fingerprint.
+//!
+//! `ReproducibilityReport::compare` then checks two runs: whether they used the same seed and
+//! fingerprint, and which fields differ. Outputs that differ although seed and fingerprint
+//! match point at nondeterminism in the prompt or the provider, which is what a prompt
+//! regression test wants to catch.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::Task;
+//! use secretary::metadata::{GenerationMetadata, GenerationResult};
+//! use secretary::reproducibility::ReproducibilityReport;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug, Clone)]
+//! struct Person {
+//!     #[task(instruction = "Extract the person's name")]
+//!     pub name: String,
+//!     #[task(instruction = "Extract the age as a number")]
+//!     pub age: u32,
+//! }
+//!
+//! let run = |age: u32, fingerprint: &str| GenerationResult {
+//!     data: Person { name: "Ada".to_string(), age },
+//!     metadata: GenerationMetadata {
+//!         seed: Some(42),
+//!         system_fingerprint: Some(fingerprint.to_string()),
+//!         ..GenerationMetadata::default()
+//!     },
+//! };
+//!
+//! // Same seed, same backend, same output
+//! let report = ReproducibilityReport::compare(&run(36, "fp_1"), &run(36, "fp_1"));
+//! assert!(report.is_reproducible());
+//! assert!(!report.is_nondeterministic());
+//!
+//! // Same seed and backend, but the output changed
+//! let report = ReproducibilityReport::compare(&run(36, "fp_1"), &run(37, "fp_1"));
+//! assert!(report.is_nondeterministic());
+//! assert_eq!(report.diffs[0].path, "age");
+//! assert_eq!(
+//!     report.report(),
+//!     concat!(
+//!         "Seed: 42 in both runs\n",
+//!         "Fingerprint: fp_1 in both runs\n",
+//!         "Nondeterministic: 1 field(s) differ: 0 added, 0 removed, 1 changed\n",
+//!         "~ age: 36 -> 37\n",
+//!     )
+//! );
+//!
+//! // A backend change explains the difference
+//! let report = ReproducibilityReport::compare(&run(36, "fp_1"), &run(37, "fp_2"));
+//! assert!(!report.same_fingerprint);
+//! assert!(!report.is_nondeterministic());
+//! assert!(report.report().starts_with("Seed: 42 in both runs\nFingerprint: fp_1 vs fp_2\n"));
+//! ```
+
+use serde::Serialize;
+
+use crate::{
+    diff::{FieldDiff, compare, diff_report},
+    metadata::GenerationResult,
+    traits::Task,
+};
+
+/// The comparison of two runs of the same extraction.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ReproducibilityReport {
+    /// The seed of the first run.
+    pub first_seed: Option<u64>,
+    /// The seed of the second run.
+    pub second_seed: Option<u64>,
+    /// The `system_fingerprint` of the first run.
+    pub first_fingerprint: Option<String>,
+    /// The `system_fingerprint` of the second run.
+    pub second_fingerprint: Option<String>,
+    /// Whether both runs were sent with the same seed.
+    pub same_seed: bool,
+    /// Whether both runs reported the same fingerprint.
+    pub same_fingerprint: bool,
+    /// The fields whose values differ, see the `diff` module.
+    pub diffs: Vec<FieldDiff>,
+}
+
+impl ReproducibilityReport {
+    /// Compares two runs of the same extraction.
+    ///
+    /// # Arguments
+    ///
+    /// * `first` - The earlier run
+    /// * `second` - The later run
+    pub fn compare<T: Task>(first: &GenerationResult<T>, second: &GenerationResult<T>) -> Self {
+        let first_seed: Option<u64> = first.metadata.seed;
+        let second_seed: Option<u64> = second.metadata.seed;
+        let first_fingerprint: Option<String> = first.metadata.system_fingerprint.clone();
+        let second_fingerprint: Option<String> = second.metadata.system_fingerprint.clone();
+
+        Self {
+            same_seed: first_seed.is_some() && first_seed == second_seed,
+            same_fingerprint: first_fingerprint.is_some()
+                && first_fingerprint == second_fingerprint,
+            first_seed,
+            second_seed,
+            first_fingerprint,
+            second_fingerprint,
+            diffs: compare(&first.data, &second.data),
+        }
+    }
+
+    /// Returns whether both runs extracted the same data.
+    pub fn is_reproducible(&self) -> bool {
+        self.diffs.is_empty()
+    }
+
+    /// Returns whether the data differs although both runs used the same seed and were served
+    /// by the same backend configuration.
+    pub fn is_nondeterministic(&self) -> bool {
+        self.same_seed && self.same_fingerprint && !self.diffs.is_empty()
+    }
+
+    /// Renders the comparison as a readable summary.
+    ///
+    /// The first two lines compare the seeds and the fingerprints, and the rest is the
+    /// `diff_report` of the data, prefixed with `Nondeterministic:` when
+    /// `is_nondeterministic` holds.
+    pub fn report(&self) -> String {
+        let outputs: String = diff_report(&self.diffs);
+        format!(
+            "Seed: {}\nFingerprint: {}\n{}",
+            describe_pair(&self.first_seed, &self.second_seed),
+            describe_pair(&self.first_fingerprint, &self.second_fingerprint),
+            if self.is_nondeterministic() {
+                format!("Nondeterministic: {}", outputs)
+            } else {
+                outputs
+            }
+        )
+    }
+}
+
+/// Describes the values of a setting in two runs.
+fn describe_pair<V: PartialEq + std::fmt::Display>(
+    first: &Option<V>,
+    second: &Option<V>,
+) -> String {
+    match (first, second) {
+        (Some(first), Some(second)) if first == second => format!("{} in both runs", first),
+        (Some(first), Some(second)) => format!("{} vs {}", first, second),
+        (Some(first), None) => format!("{} vs none", first),
+        (None, Some(second)) => format!("none vs {}", second),
+        (None, None) => "none".to_string(),
+    }
+}
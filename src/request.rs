@@ -0,0 +1,527 @@
+//! Settings applied to individual LLM requests.
+//!
+//! Extra body parameters are deep-merged into the body built by `IsLLM::get_request_body`,
+//! so vendor extensions such as `reasoning_effort` or OpenRouter's `provider` routing block
+//! can be sent without a provider change. Provider-level values from `with_extra_body` are
+//! merged first and per-call values from `RequestOptions::with_extra_body` last, so the
+//! per-call values win. The `messages` of a body are never replaced.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use serde_json::Value;
+
+use crate::credentials::Credentials;
+use crate::deadline::Deadline;
+use crate::hints::Hints;
+use crate::limits::OutputLimits;
+use crate::llm_providers::queue::Priority;
+use crate::locale::ExtractionLocale;
+use crate::ordering::OrderCheck;
+use crate::progress::{Progress, ProgressReporter};
+use crate::reasoning::ReasoningEffort;
+use crate::response_format::ResponseFormat;
+use crate::streaming::Streaming;
+use crate::trimming::UnknownKeys;
+#[cfg(feature = "schema-validation")]
+use crate::validation::SchemaValidation;
+use crate::vocabulary::OneOfPolicy;
+
+/// Optional request body settings for a single request.
+///
+/// Unset values are left out of the request body so the provider's defaults apply.
+///
+/// # Examples
+///
+/// ```rust
+/// use secretary::request::RequestOptions;
+/// use serde_json::json;
+///
+/// let mut body = json!({"model": "gpt-4o", "messages": []});
+/// RequestOptions::default().apply_to_body(&mut body);
+/// assert_eq!(body, json!({"model": "gpt-4o", "messages": []}));
+///
+/// RequestOptions::default().with_temperature(0.7).apply_to_body(&mut body);
+/// assert_eq!(body["temperature"], json!(0.7));
+///
+/// RequestOptions::default().with_max_tokens(256).apply_to_body(&mut body);
+/// assert_eq!(body["max_tokens"], json!(256));
+///
+/// RequestOptions::default().with_seed(42).apply_to_body(&mut body);
+/// assert_eq!(body["seed"], json!(42));
+///
+/// RequestOptions::default().with_choices(5).apply_to_body(&mut body);
+/// assert_eq!(body["n"], json!(5));
+///
+/// RequestOptions::default()
+///     .with_extra_body(json!({"reasoning_effort": "low", "messages": "ignored"}))
+///     .apply_to_body(&mut body);
+/// assert_eq!(body["reasoning_effort"], json!("low"));
+/// assert_eq!(body["messages"], json!([]));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RequestOptions {
+    /// The sampling temperature.
+    pub temperature: Option<f64>,
+    /// The maximum number of tokens the model may generate.
+    pub max_tokens: Option<u32>,
+    /// The sampling seed, for providers that support reproducible outputs, see the
+    /// `reproducibility` module.
+    pub seed: Option<u64>,
+    /// The number of samples to generate from the prompt, sent as `n`, see the `consistency`
+    /// module.
+    pub choices: Option<usize>,
+    /// How much a reasoning model reasons, sent as `reasoning_effort`, see the `reasoning`
+    /// module.
+    pub reasoning_effort: Option<ReasoningEffort>,
+    /// Whether to leave the reasoning the model reports out of the metadata, see the
+    /// `reasoning` module.
+    pub hide_reasoning: bool,
+    /// Extra JSON deep-merged into the request body, e.g. `{"reasoning_effort": "low"}`.
+    pub extra_body: Option<Value>,
+    /// The time by which the whole extraction must finish, see the `deadline` module.
+    pub deadline: Option<Deadline>,
+    /// The priority of the requests in the provider's `RequestQueue`, see the `queue` module.
+    pub priority: Priority,
+    /// Limits on the size of the output, instead of the provider's, see the `limits` module.
+    pub output_limits: Option<OutputLimits>,
+    /// What happens to keys of the model's JSON that are not fields of the Task, instead of
+    /// the provider's policy, see the `trimming` module.
+    pub unknown_keys: Option<UnknownKeys>,
+    /// What happens to answers of `one_of` fields that match no allowed value, instead of
+    /// the provider's policy, see the `vocabulary` module.
+    pub one_of_policy: Option<OneOfPolicy>,
+    /// The number and date conventions of the target, instead of the provider's locale, see
+    /// the `locale` module.
+    pub locale: Option<ExtractionLocale>,
+    /// Whether `generate_data_adaptive` verifies the order of lists with `#[task(ordered)]`,
+    /// and what happens to items out of order, see the `ordering` module. Not verified when
+    /// unset.
+    pub order_check: Option<OrderCheck>,
+    /// Whether to check once per Task type that serde writes and reads the keys the prompt
+    /// asks for before the first request, see the `schema_check` module.
+    pub strict_schema: bool,
+    /// The wire format the model answers in, JSON by default, see the `response_format`
+    /// module.
+    pub response_format: ResponseFormat,
+    /// The GBNF grammar the answer is constrained to by providers that take one, instead of
+    /// the grammar of the Task, see the `grammar` module.
+    pub grammar: Option<String>,
+    /// The most field requests of distributed generation in flight at once, all of them by
+    /// default.
+    pub concurrency: Option<usize>,
+    /// The values of the `{placeholder}`s in the instructions, which are only rendered when
+    /// set, see `utilities::render_template`.
+    pub template_vars: Option<HashMap<String, String>>,
+    /// The API key sent with every request of the call in place of the provider's, see the
+    /// `credentials` module.
+    pub credentials: Option<Credentials>,
+    /// The tenant the requests of the call count against in the provider's
+    /// `QuotaPartitioner`, instead of the tenant of the credentials, see the `quota` module.
+    pub tenant: Option<String>,
+    /// The known values of fields by dotted path, see the `hints` module.
+    pub hints: BTreeMap<String, Value>,
+    /// The ID the requests of the call are sent and reported under, a new one when unset,
+    /// see the `correlation` module.
+    pub request_id: Option<String>,
+    /// Whether to stream the responses and check them against the Task's fields as they
+    /// arrive, see the `streaming` module. Not streamed when unset.
+    pub streaming: Option<Streaming>,
+    /// The reporter the stages of the call are reported to, see the `progress` module.
+    pub progress: Option<Progress>,
+    /// Whether to validate the response against the Task's JSON Schema before deserializing
+    /// it, see the `validation` module.
+    #[cfg(feature = "schema-validation")]
+    pub schema_validation: Option<SchemaValidation>,
+}
+
+impl RequestOptions {
+    /// Sets the sampling temperature.
+    pub fn with_temperature(mut self, temperature: f64) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    /// Sets the maximum number of tokens the model may generate.
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    /// Sets the sampling seed, sent with every request of the call.
+    ///
+    /// OpenAI-compatible providers sample deterministically, on a best-effort basis, for a
+    /// fixed seed and report the backend configuration as `system_fingerprint`. Providers
+    /// without a seed parameter, such as Bedrock and the Responses API, do not send it.
+    ///
+    /// # Arguments
+    ///
+    /// * `seed` - The seed; repeat it to reproduce an extraction
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Asks for several samples of the completion in one request, sent as `n`.
+    ///
+    /// Providers without an `n` parameter, such as Bedrock and the Responses API, do not
+    /// send it and return a single sample.
+    ///
+    /// # Arguments
+    ///
+    /// * `choices` - The number of samples; sampling needs a temperature above 0 for them to
+    ///   differ
+    pub fn with_choices(mut self, choices: usize) -> Self {
+        self.choices = Some(choices);
+        self
+    }
+
+    /// Sets how much a reasoning model reasons before it answers, sent as `reasoning_effort`.
+    ///
+    /// Models that do not reason may reject the request. Values of the extra body win.
+    ///
+    /// # Arguments
+    ///
+    /// * `reasoning_effort` - Low for faster and cheaper answers, high for harder targets
+    pub fn with_reasoning_effort(mut self, reasoning_effort: ReasoningEffort) -> Self {
+        self.reasoning_effort = Some(reasoning_effort);
+        self
+    }
+
+    /// Leaves the reasoning the model reports out of `GenerationMetadata::reasoning`, for
+    /// reasoning that must not be logged or stored. The reasoning tokens are still reported.
+    pub fn with_hidden_reasoning(mut self) -> Self {
+        self.hide_reasoning = true;
+        self
+    }
+
+    /// Sets extra JSON to deep-merge into the request body.
+    ///
+    /// # Arguments
+    ///
+    /// * `extra_body` - A JSON object; its values win over the provider's extra body
+    pub fn with_extra_body(mut self, extra_body: Value) -> Self {
+        self.extra_body = Some(extra_body);
+        self
+    }
+
+    /// Sets the time by which the whole extraction must finish, including retries and every
+    /// field request.
+    ///
+    /// # Arguments
+    ///
+    /// * `deadline` - The deadline, e.g. `Deadline::after(Duration::from_secs(20))`
+    pub fn with_deadline(mut self, deadline: Deadline) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Sets the priority of the requests in the provider's `RequestQueue`.
+    ///
+    /// # Arguments
+    ///
+    /// * `priority` - The priority, `Priority::Normal` by default
+    pub fn with_priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Limits the size of the output of this call, instead of the provider's limits.
+    ///
+    /// # Arguments
+    ///
+    /// * `output_limits` - The limits, see the `limits` module
+    pub fn with_output_limits(mut self, output_limits: OutputLimits) -> Self {
+        self.output_limits = Some(output_limits);
+        self
+    }
+
+    /// Sets what happens to keys of the model's JSON that are not fields of the Task for this
+    /// call, instead of the provider's policy.
+    ///
+    /// # Arguments
+    ///
+    /// * `unknown_keys` - The policy, see the `trimming` module
+    pub fn with_unknown_keys(mut self, unknown_keys: UnknownKeys) -> Self {
+        self.unknown_keys = Some(unknown_keys);
+        self
+    }
+
+    /// Sets what happens to answers of `one_of` fields that match no allowed value for this
+    /// call, instead of the provider's policy.
+    ///
+    /// # Arguments
+    ///
+    /// * `one_of_policy` - The policy, see the `vocabulary` module
+    pub fn with_one_of_policy(mut self, one_of_policy: OneOfPolicy) -> Self {
+        self.one_of_policy = Some(one_of_policy);
+        self
+    }
+
+    /// Sets the number and date conventions of the target for this call, instead of the
+    /// provider's locale.
+    ///
+    /// # Arguments
+    ///
+    /// * `locale` - The conventions, see the `locale` module
+    pub fn with_locale(mut self, locale: ExtractionLocale) -> Self {
+        self.locale = Some(locale);
+        self
+    }
+
+    /// Verifies after `generate_data_adaptive` that the items of lists with
+    /// `#[task(ordered)]` appear in the target in the order of the lists.
+    ///
+    /// # Arguments
+    ///
+    /// * `order_check` - What happens to items out of order, see the `ordering` module
+    pub fn with_order_check(mut self, order_check: OrderCheck) -> Self {
+        self.order_check = Some(order_check);
+        self
+    }
+
+    /// Checks before the first request that serde writes and reads the keys the prompt of
+    /// the Task asks for, and fails with `SecretaryError::SchemaInconsistent` if not. Each
+    /// Task type is only checked once per process, see the `schema_check` module.
+    ///
+    /// # Arguments
+    ///
+    /// * `strict_schema` - Whether to check the Task
+    pub fn with_strict_schema(mut self, strict_schema: bool) -> Self {
+        self.strict_schema = strict_schema;
+        self
+    }
+
+    /// Asks the model to answer in another wire format than JSON, which is converted into
+    /// JSON before deserialization.
+    ///
+    /// # Arguments
+    ///
+    /// * `response_format` - The format, see the `response_format` module
+    pub fn with_response_format(mut self, response_format: ResponseFormat) -> Self {
+        self.response_format = response_format;
+        self
+    }
+
+    /// Constrains the answer to a GBNF grammar of the caller's own, instead of the grammar of
+    /// the Task, on providers that declare `ConstrainedDecoding::Gbnf`. Other providers
+    /// ignore it, see the `grammar` module.
+    ///
+    /// # Arguments
+    ///
+    /// * `grammar` - The grammar, whose start rule is `root`
+    pub fn with_grammar(mut self, grammar: impl Into<String>) -> Self {
+        self.grammar = Some(grammar.into());
+        self
+    }
+
+    /// Limits how many field requests of distributed generation are in flight at once.
+    ///
+    /// # Arguments
+    ///
+    /// * `concurrency` - The most requests in flight; `0` is treated as `1`
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = Some(concurrency);
+        self
+    }
+
+    /// Renders the `{placeholder}`s of the attribute and additional instructions.
+    ///
+    /// `{today}` and `{now_utc}` are filled in with the current UTC date and time unless
+    /// `vars` has them. The target is never rendered.
+    ///
+    /// # Arguments
+    ///
+    /// * `vars` - The value of each placeholder; may be empty to use only the built-in ones
+    pub fn with_template_vars(mut self, vars: HashMap<String, String>) -> Self {
+        self.template_vars = Some(vars);
+        self
+    }
+
+    /// Sends every request of the call with another API key than the provider's.
+    ///
+    /// Fails with `SecretaryError::BuildRequestError` on providers that do not authenticate
+    /// with an API key, such as Bedrock.
+    ///
+    /// # Arguments
+    ///
+    /// * `credentials` - The key, and optionally the tenant it belongs to
+    pub fn with_credentials(mut self, credentials: Credentials) -> Self {
+        self.credentials = Some(credentials);
+        self
+    }
+
+    /// Counts every request of the call against a tenant's share of the provider's
+    /// `QuotaPartitioner`.
+    ///
+    /// Without it, the requests count against the tenant of the credentials, or `default`.
+    ///
+    /// # Arguments
+    ///
+    /// * `tenant` - The name of the tenant
+    pub fn with_tenant(mut self, tenant: &str) -> Self {
+        self.tenant = Some(tenant.to_string());
+        self
+    }
+
+    /// Sets the values of fields that are known before the extraction.
+    ///
+    /// The values are listed in the prompt and replace what the model returns for their
+    /// fields, and distributed generation sends no request for them.
+    ///
+    /// # Arguments
+    ///
+    /// * `hints` - The known values, e.g. `Invoice::hints().vendor("Acme").build_hints()`
+    pub fn with_hints<T>(mut self, hints: Hints<T>) -> Self {
+        self.hints = hints.into_values();
+        self
+    }
+
+    /// Sets the request ID of the call, instead of a new UUID.
+    ///
+    /// # Arguments
+    ///
+    /// * `request_id` - An ID from the caller's logs, sent as a header so it must be
+    ///   printable ASCII
+    pub fn with_request_id(mut self, request_id: impl Into<String>) -> Self {
+        self.request_id = Some(request_id.into());
+        self
+    }
+
+    /// Streams the responses, abandoning a stream as soon as the answer's top-level keys or
+    /// value types depart from the Task's fields.
+    ///
+    /// Sends `"stream": true`. The departure is reported as `SecretaryError::SchemaViolation`.
+    /// Only answers of `generate_data_with_options`, `generate_data_adaptive_with_options`
+    /// and their async versions are checked; other requests are streamed without checks.
+    pub fn with_streaming(mut self) -> Self {
+        self.streaming = Some(Streaming::default());
+        self
+    }
+
+    /// Reports the stages of the call, such as the start and end of each field request, see
+    /// the `progress` module.
+    ///
+    /// # Arguments
+    ///
+    /// * `reporter` - The reporter, e.g. a `RecordingReporter` shared with the caller
+    pub fn with_progress(mut self, reporter: Arc<dyn ProgressReporter>) -> Self {
+        self.progress = Some(Progress::new(reporter));
+        self
+    }
+
+    /// Validates the response against the Task's JSON Schema before deserializing it.
+    ///
+    /// Violations are reported as `SecretaryError::SchemaViolation`. Only applies to
+    /// `generate_data_with_options` and `async_generate_data_with_options`.
+    ///
+    /// # Arguments
+    ///
+    /// * `validation` - Whether keys the Task does not have fail the request
+    #[cfg(feature = "schema-validation")]
+    pub fn with_schema_validation(mut self, validation: SchemaValidation) -> Self {
+        self.schema_validation = Some(validation);
+        self
+    }
+
+    /// Writes the configured settings into a request body produced by `IsLLM::get_request_body`.
+    ///
+    /// # Arguments
+    ///
+    /// * `body` - The JSON request body to modify
+    pub fn apply_to_body(&self, body: &mut Value) {
+        if let (Some(temperature), Some(body)) = (self.temperature, body.as_object_mut()) {
+            body.insert("temperature".to_string(), Value::from(temperature));
+        }
+        if let (Some(max_tokens), Some(body)) = (self.max_tokens, body.as_object_mut()) {
+            body.insert("max_tokens".to_string(), Value::from(max_tokens));
+        }
+        if let (Some(seed), Some(body)) = (self.seed, body.as_object_mut()) {
+            body.insert("seed".to_string(), Value::from(seed));
+        }
+        if let (Some(choices), Some(body)) = (self.choices, body.as_object_mut()) {
+            body.insert("n".to_string(), Value::from(choices));
+        }
+        if let (Some(reasoning_effort), Some(body)) = (self.reasoning_effort, body.as_object_mut())
+        {
+            body.insert(
+                "reasoning_effort".to_string(),
+                Value::from(reasoning_effort.as_str()),
+            );
+        }
+        if let (Some(_), Some(body)) = (&self.streaming, body.as_object_mut()) {
+            body.insert("stream".to_string(), Value::Bool(true));
+        }
+        if let Some(extra_body) = &self.extra_body {
+            merge_extra_body(body, extra_body);
+        }
+    }
+}
+
+/// Deep-merges extra JSON into a request body, leaving its `messages` untouched.
+///
+/// Objects are merged key by key; any other value in `extra_body` replaces the value in the
+/// body, and `null` sets the key to `null`. Extra bodies that are not objects are ignored.
+///
+/// # Arguments
+///
+/// * `body` - The JSON request body to modify
+/// * `extra_body` - The JSON object to merge into it
+///
+/// # Examples
+///
+/// ```rust
+/// use secretary::request::merge_extra_body;
+/// use serde_json::json;
+///
+/// let mut body = json!({
+///     "model": "gpt-4o",
+///     "messages": [{"role": "user", "content": "Hi"}],
+///     "response_format": {"type": "json_object"},
+///     "provider": {"order": ["openai"], "allow_fallbacks": true}
+/// });
+///
+/// // Nested objects are merged; conflicting values are replaced
+/// merge_extra_body(&mut body, &json!({
+///     "provider": {"order": ["anthropic", "openai"], "data_collection": "deny"},
+///     "chat_template_kwargs": {"enable_thinking": false},
+///     "messages": []
+/// }));
+/// assert_eq!(body["provider"], json!({
+///     "order": ["anthropic", "openai"],
+///     "allow_fallbacks": true,
+///     "data_collection": "deny"
+/// }));
+/// assert_eq!(body["chat_template_kwargs"], json!({"enable_thinking": false}));
+/// assert_eq!(body["response_format"], json!({"type": "json_object"}));
+/// assert_eq!(body["messages"], json!([{"role": "user", "content": "Hi"}]));
+///
+/// // A non-object value replaces an object
+/// merge_extra_body(&mut body, &json!({"response_format": null}));
+/// assert_eq!(body["response_format"], json!(null));
+/// ```
+pub fn merge_extra_body(body: &mut Value, extra_body: &Value) {
+    let (body, extra_body) = match (body.as_object_mut(), extra_body.as_object()) {
+        (Some(body), Some(extra_body)) => (body, extra_body),
+        _ => return,
+    };
+
+    for (key, value) in extra_body {
+        if key == "messages" {
+            continue;
+        }
+        merge_value(body.entry(key.clone()).or_insert(Value::Null), value);
+    }
+}
+
+/// Merges `extra` into `target`, recursing into objects present on both sides.
+fn merge_value(target: &mut Value, extra: &Value) {
+    match (target.as_object_mut(), extra.as_object()) {
+        (Some(target), Some(extra)) => {
+            for (key, value) in extra {
+                merge_value(target.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+        _ => *target = extra.clone(),
+    }
+}
@@ -0,0 +1,501 @@
+//! Answers in YAML or XML instead of JSON.
+//!
+//! Some models write better YAML or XML than JSON, e.g. for long multiline texts that JSON
+//! needs to escape. `RequestOptions::with_response_format` asks for another wire format:
+//! the field list of the prompt stays the same, only its framing changes. The JSON template
+//! is shown in the requested format with an instruction to answer in it, and JSON mode is not
+//! requested. The answer is converted into JSON before the usual trimming, limits and
+//! deserialization, so everything downstream works as for a JSON answer.
+//!
+//! - `ResponseFormat::Yaml` reads a YAML mapping with `serde_yaml`
+//! - `ResponseFormat::Xml`, behind the `xml` feature, reads a constrained XML convention with
+//!   `quick-xml`: a root element holding one element per field, named after the field, with
+//!   nested elements for the fields of nested Tasks, an element per entry of a map, and an
+//!   `<item>` element per item of a list. Attributes are ignored.
+//!
+//! The answer is found in a ```` ```yaml ```` or ```` ```xml ```` code block, then between
+//! `---` document markers or around the root element, and otherwise in the whole answer, so
+//! prose around it and `<think>` blocks are tolerated. The last candidate that converts into
+//! a mapping is used. The scalars are then matched to the field descriptors: XML text, and
+//! YAML values such as a `12345` postcode, become numbers, booleans or strings as the field
+//! calls for, and an empty value of a required text or list field becomes empty.
+//!
+//! The formats apply to `generate_data_with_options` and `force_generate_data_with_options`
+//! and their async versions.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::Task;
+//! use secretary::response_format::ResponseFormat;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! struct Note {
+//!     #[task(instruction = "Extract the title")]
+//!     pub title: String,
+//!     #[task(instruction = "Extract the body, keeping its line breaks")]
+//!     pub body: String,
+//!     #[task(instruction = "Extract the postcode")]
+//!     pub postcode: String,
+//! }
+//!
+//! let answer = "Here you go:\n\
+//!     ```yaml\n\
+//!     title: Groceries\n\
+//!     body: |\n  Milk\n  Eggs\n\
+//!     postcode: 12345\n\
+//!     ```";
+//! let json = ResponseFormat::Yaml
+//!     .to_json_content(answer, &Note::field_descriptors())
+//!     .unwrap();
+//! let note: Note = serde_json::from_str(&json).unwrap();
+//! assert_eq!(note.body, "Milk\nEggs\n");
+//! assert_eq!(note.postcode, "12345");
+//! ```
+
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "xml")]
+use serde_json::Map;
+use serde_json::Value;
+
+use crate::{
+    casing::to_native_keys,
+    error::SecretaryError,
+    message::Message,
+    refusal::detect_refusal,
+    schema::{FieldDescriptor, FieldKind, JsonType},
+    utilities::cleanup_thinking_blocks,
+};
+
+/// The wire format the model answers in, set with `RequestOptions::with_response_format`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum ResponseFormat {
+    /// A JSON object, in JSON mode where the provider supports it.
+    #[default]
+    Json,
+    /// A YAML mapping.
+    Yaml,
+    /// An XML document with an element per field.
+    #[cfg(feature = "xml")]
+    Xml,
+}
+
+impl ResponseFormat {
+    /// Returns whether the answer is JSON, so JSON mode can be requested.
+    pub fn is_json(&self) -> bool {
+        *self == ResponseFormat::Json
+    }
+
+    /// Returns the instruction appended to the prompt, with the JSON template rendered in the
+    /// format, and `None` for JSON, which the prompt already asks for.
+    ///
+    /// # Arguments
+    ///
+    /// * `template` - The JSON template of the Task, e.g. `serde_json::to_value(&task)`
+    pub fn instruction(&self, template: &Value) -> Option<String> {
+        match self {
+            ResponseFormat::Json => None,
+            ResponseFormat::Yaml => Some(format!(
+                "Answer with a YAML document instead of JSON, with the keys and nesting of the \
+                 JSON template, in a ```yaml code block. Write multiline texts as block \
+                 scalars:\n```yaml\n{}```",
+                serde_yaml::to_string(template).unwrap_or_default()
+            )),
+            #[cfg(feature = "xml")]
+            ResponseFormat::Xml => Some(format!(
+                "Answer with an XML document instead of JSON, in a ```xml code block: a \
+                 <response> element holding one element per field, named after the field, \
+                 with nested elements for nested objects and an <item> element for each item \
+                 of a list. Escape &, < and > in the values:\n```xml\n{}\n```",
+                render_xml("response", template, 0)
+            )),
+        }
+    }
+
+    /// Appends the instruction of the format to a message.
+    fn frame_message(&self, mut message: Message, template: &Value) -> Message {
+        if let Some(instruction) = self.instruction(template) {
+            message.content = format!("{}\n\n{}", message.content, instruction).into();
+        }
+
+        message
+    }
+
+    /// Appends the instruction of the format to the last of the messages.
+    pub(crate) fn frame_messages(
+        &self,
+        mut messages: Vec<Message>,
+        template: &Value,
+    ) -> Vec<Message> {
+        if let Some(last) = messages.pop() {
+            messages.push(self.frame_message(last, template));
+        }
+
+        messages
+    }
+
+    /// Converts an answer in the format into the JSON of the Task.
+    ///
+    /// # Arguments
+    ///
+    /// * `content` - The content of the model's answer
+    /// * `fields` - The field descriptors, e.g. from `Task::field_descriptors()`
+    ///
+    /// # Returns
+    ///
+    /// The JSON text, the content unchanged for `ResponseFormat::Json`
+    ///
+    /// # Errors
+    ///
+    /// Returns `SecretaryError::Refused` when the answer is a refusal and
+    /// `SecretaryError::JsonParsingError` when no candidate converts into a mapping.
+    pub fn to_json_content(
+        &self,
+        content: &str,
+        fields: &[FieldDescriptor],
+    ) -> Result<String, SecretaryError> {
+        let converted: Option<Value> = match self {
+            ResponseFormat::Json => return Ok(content.to_string()),
+            ResponseFormat::Yaml => {
+                let cleaned: String = cleanup_thinking_blocks(content.to_string());
+                yaml_candidates(&cleaned)
+                    .into_iter()
+                    .rev()
+                    .find_map(|candidate| match serde_yaml::from_str::<Value>(candidate) {
+                        Ok(value @ Value::Object(_)) => Some(value),
+                        _ => None,
+                    })
+            }
+            #[cfg(feature = "xml")]
+            ResponseFormat::Xml => {
+                let cleaned: String = cleanup_thinking_blocks(content.to_string());
+                xml_candidates(&cleaned)
+                    .into_iter()
+                    .rev()
+                    .find_map(|candidate| parse_xml(candidate).ok())
+                    .map(|root| element_value(&root, fields))
+            }
+        };
+
+        match converted {
+            Some(mut value) => {
+                to_native_keys(fields, &mut value);
+                conform_fields(fields, &mut value);
+                Ok(serde_json::to_string(&value)?)
+            }
+            None => match detect_refusal(content) {
+                Some(message) => Err(SecretaryError::Refused { message }),
+                None => Err(SecretaryError::JsonParsingError {
+                    message: format!("No {} document found in the LLM response", self.name()),
+                    raw_content: content.to_string(),
+                }),
+            },
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            ResponseFormat::Json => "JSON",
+            ResponseFormat::Yaml => "YAML",
+            #[cfg(feature = "xml")]
+            ResponseFormat::Xml => "XML",
+        }
+    }
+}
+
+/// Returns the bodies of the code blocks whose language is one of `languages`, an empty
+/// language standing for blocks without one.
+fn fenced_blocks<'a>(content: &'a str, languages: &[&str]) -> Vec<&'a str> {
+    let mut blocks: Vec<&str> = Vec::new();
+    let mut open: Option<(usize, bool)> = None;
+    let mut offset: usize = 0;
+
+    for line in content.split_inclusive('\n') {
+        let trimmed: &str = line.trim();
+        if let Some(language) = trimmed.strip_prefix("```") {
+            match open.take() {
+                Some((start, true)) => blocks.push(&content[start..offset]),
+                Some((_, false)) => {}
+                None => {
+                    let language: String = language.trim().to_lowercase();
+                    open = Some((offset + line.len(), languages.contains(&language.as_str())));
+                }
+            }
+        }
+        offset += line.len();
+    }
+
+    blocks
+}
+
+/// Returns the YAML documents of an answer, in order: the code blocks, otherwise the
+/// documents between `---` markers, otherwise the whole answer.
+fn yaml_candidates(content: &str) -> Vec<&str> {
+    let blocks: Vec<&str> = fenced_blocks(content, &["yaml", "yml", ""]);
+    if !blocks.is_empty() {
+        return blocks;
+    }
+
+    let mut documents: Vec<&str> = Vec::new();
+    let mut start: usize = 0;
+    let mut offset: usize = 0;
+    for line in content.split_inclusive('\n') {
+        let marker: &str = line.trim_end();
+        if marker == "---" || marker == "..." {
+            documents.push(&content[start..offset]);
+            start = offset + line.len();
+        }
+        offset += line.len();
+    }
+    documents.push(&content[start..]);
+    documents.retain(|document| !document.trim().is_empty());
+
+    documents
+}
+
+/// Returns the XML documents of an answer, in order: the code blocks, otherwise the text
+/// from the first element to the last `>`.
+#[cfg(feature = "xml")]
+fn xml_candidates(content: &str) -> Vec<&str> {
+    let blocks: Vec<&str> = fenced_blocks(content, &["xml", ""]);
+    if !blocks.is_empty() {
+        return blocks;
+    }
+
+    let start: Option<usize> = content.char_indices().find_map(|(index, character)| {
+        (character == '<'
+            && content[index + 1..]
+                .chars()
+                .next()
+                .is_some_and(|next| next.is_alphabetic() || next == '?'))
+        .then_some(index)
+    });
+    match (start, content.rfind('>')) {
+        (Some(start), Some(end)) if start < end => vec![&content[start..=end]],
+        _ => Vec::new(),
+    }
+}
+
+/// An element of an XML answer, with its unescaped text and child elements.
+#[cfg(feature = "xml")]
+#[derive(Debug, Default)]
+struct XmlElement {
+    name: String,
+    text: String,
+    children: Vec<XmlElement>,
+}
+
+/// Parses the root element of an XML document.
+#[cfg(feature = "xml")]
+fn parse_xml(document: &str) -> Result<XmlElement, String> {
+    use quick_xml::{Reader, events::Event};
+
+    let element = |start: &quick_xml::events::BytesStart| XmlElement {
+        name: String::from_utf8_lossy(start.name().as_ref()).to_string(),
+        ..XmlElement::default()
+    };
+
+    let mut reader = Reader::from_str(document);
+    reader.config_mut().trim_text(true);
+    let mut stack: Vec<XmlElement> = Vec::new();
+    loop {
+        let closed: XmlElement = match reader.read_event().map_err(|error| error.to_string())? {
+            Event::Start(start) => {
+                stack.push(element(&start));
+                continue;
+            }
+            Event::Empty(start) => element(&start),
+            Event::End(_) => stack.pop().ok_or("Unexpected end tag")?,
+            Event::Text(text) => {
+                if let Some(current) = stack.last_mut() {
+                    current
+                        .text
+                        .push_str(&text.unescape().map_err(|error| error.to_string())?);
+                }
+                continue;
+            }
+            Event::CData(data) => {
+                if let Some(current) = stack.last_mut() {
+                    current
+                        .text
+                        .push_str(&String::from_utf8_lossy(&data.into_inner()));
+                }
+                continue;
+            }
+            Event::Eof => return Err("Unexpected end of the document".to_string()),
+            _ => continue,
+        };
+
+        match stack.last_mut() {
+            Some(parent) => parent.children.push(closed),
+            None => return Ok(closed),
+        }
+    }
+}
+
+/// Converts an element into the object of the fields, keeping elements that are not fields.
+#[cfg(feature = "xml")]
+fn element_value(element: &XmlElement, fields: &[FieldDescriptor]) -> Value {
+    let mut map: Map<String, Value> = Map::new();
+    for child in &element.children {
+        let value: Value = match fields
+            .iter()
+            .find(|field| field.name == child.name || field.key() == child.name)
+        {
+            Some(field) => field_value(child, field),
+            None => plain_value(child),
+        };
+        map.insert(child.name.clone(), value);
+    }
+
+    Value::Object(map)
+}
+
+/// Converts the element of a field, by the shape of the field.
+#[cfg(feature = "xml")]
+fn field_value(element: &XmlElement, field: &FieldDescriptor) -> Value {
+    if element.children.is_empty() && element.text.is_empty() {
+        return Value::Null;
+    }
+
+    match field.kind {
+        FieldKind::Task | FieldKind::OptionTask => element_value(element, &field.children),
+        FieldKind::VecTask => Value::Array(
+            element
+                .children
+                .iter()
+                .map(|item| element_value(item, &field.children))
+                .collect(),
+        ),
+        FieldKind::HashMapTask | FieldKind::BTreeMapTask => Value::Object(
+            element
+                .children
+                .iter()
+                .map(|entry| (entry.name.clone(), element_value(entry, &field.children)))
+                .collect(),
+        ),
+        FieldKind::Normal if field.json_type == JsonType::Array => {
+            Value::Array(element.children.iter().map(plain_value).collect())
+        }
+        FieldKind::Normal => plain_value(element),
+    }
+}
+
+/// Converts an element without a field: text as a string, `<item>` children as an array
+/// and other children as an object.
+#[cfg(feature = "xml")]
+fn plain_value(element: &XmlElement) -> Value {
+    if element.children.is_empty() {
+        return match element.text.is_empty() {
+            true => Value::Null,
+            false => Value::String(element.text.clone()),
+        };
+    }
+
+    if element.children.iter().all(|child| child.name == "item") {
+        Value::Array(element.children.iter().map(plain_value).collect())
+    } else {
+        Value::Object(
+            element
+                .children
+                .iter()
+                .map(|child| (child.name.clone(), plain_value(child)))
+                .collect(),
+        )
+    }
+}
+
+/// Renders a JSON value as the XML the model is asked for.
+#[cfg(feature = "xml")]
+fn render_xml(name: &str, value: &Value, depth: usize) -> String {
+    let indent: String = "  ".repeat(depth);
+    match value {
+        Value::Object(map) if !map.is_empty() => format!(
+            "{indent}<{name}>\n{}\n{indent}</{name}>",
+            map.iter()
+                .map(|(key, value)| render_xml(key, value, depth + 1))
+                .collect::<Vec<String>>()
+                .join("\n")
+        ),
+        Value::Array(items) if !items.is_empty() => format!(
+            "{indent}<{name}>\n{}\n{indent}</{name}>",
+            items
+                .iter()
+                .map(|item| render_xml("item", item, depth + 1))
+                .collect::<Vec<String>>()
+                .join("\n")
+        ),
+        Value::Object(_) | Value::Array(_) | Value::Null => format!("{indent}<{name}></{name}>"),
+        Value::String(text) => format!(
+            "{indent}<{name}>{}</{name}>",
+            text.replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;")
+        ),
+        other => format!("{indent}<{name}>{}</{name}>", other),
+    }
+}
+
+/// Matches the scalars of the fields of an object to their JSON types.
+fn conform_fields(fields: &[FieldDescriptor], value: &mut Value) {
+    let Value::Object(map) = value else {
+        return;
+    };
+
+    for field in fields {
+        if let Some(field_value) = map.get_mut(&field.name) {
+            conform_field(field, field_value);
+        }
+    }
+}
+
+fn conform_field(field: &FieldDescriptor, value: &mut Value) {
+    if value.is_null() && !field.optional {
+        match (field.kind, field.json_type) {
+            (FieldKind::VecTask, _) | (_, JsonType::Array) => *value = Value::Array(Vec::new()),
+            (FieldKind::Normal, JsonType::String) => *value = Value::String(String::new()),
+            _ => {}
+        }
+        return;
+    }
+
+    match (field.kind, value) {
+        (FieldKind::Task | FieldKind::OptionTask, value) => conform_fields(&field.children, value),
+        (FieldKind::VecTask, Value::Array(items)) => items
+            .iter_mut()
+            .for_each(|item| conform_fields(&field.children, item)),
+        (FieldKind::HashMapTask | FieldKind::BTreeMapTask, Value::Object(entries)) => entries
+            .values_mut()
+            .for_each(|entry| conform_fields(&field.children, entry)),
+        (FieldKind::Normal, Value::Array(items)) => items
+            .iter_mut()
+            .for_each(|item| conform_scalar(field.item_type, item)),
+        (FieldKind::Normal, value) => conform_scalar(field.json_type, value),
+        _ => {}
+    }
+}
+
+/// Converts a scalar of the wrong JSON type when it reads as the right one.
+fn conform_scalar(json_type: JsonType, value: &mut Value) {
+    let converted: Value = match (json_type, &*value) {
+        (JsonType::String, Value::Number(number)) => Value::String(number.to_string()),
+        (JsonType::String, Value::Bool(flag)) => Value::String(flag.to_string()),
+        (JsonType::Number, Value::String(text)) => {
+            match serde_json::from_str::<Value>(text.trim()) {
+                Ok(number @ Value::Number(_)) => number,
+                _ => return,
+            }
+        }
+        (JsonType::Boolean, Value::String(text)) => match text.trim() {
+            "true" => Value::Bool(true),
+            "false" => Value::Bool(false),
+            _ => return,
+        },
+        _ => return,
+    };
+
+    *value = converted;
+}
@@ -0,0 +1,282 @@
+//! Review queue items for extractions that need a human decision.
+//!
+//! When an extraction partially fails, `generate_data_or_review` returns a `ReviewItem`
+//! instead of an error. The item carries the input text, the raw model output, the data that
+//! could be recovered, and per-field flags that tell a reviewer where to look. Items are
+//! plain `Serialize`/`Deserialize` values, so they can be stored in a database table or sent
+//! through a message queue. A reviewer's corrections are applied with `apply_corrections`,
+//! which deserializes the corrected data again before accepting it.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use std::collections::BTreeMap;
+//!
+//! use secretary::Task;
+//! use secretary::review::ReviewItem;
+//! use serde::{Deserialize, Serialize};
+//! use serde_json::json;
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! struct Address {
+//!     #[task(instruction = "Extract the city")]
+//!     pub city: String,
+//!     #[task(instruction = "Extract the postal code as a number")]
+//!     pub postal_code: u32,
+//! }
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! struct Customer {
+//!     #[task(instruction = "Extract the customer's name")]
+//!     pub name: String,
+//!     pub address: Address,
+//! }
+//!
+//! let raw_output = r#"{"name": "Ada", "address": {"postal_code": "unknown"}}"#;
+//! let error = serde_json::from_str::<Customer>(raw_output).unwrap_err();
+//! let mut item: ReviewItem<Customer> =
+//!     ReviewItem::from_error("Ada lives in Oslo", raw_output, &error);
+//!
+//! assert_eq!(item.extracted.name, "Ada");
+//! assert!(item.field_flags["address.postal_code"].failed);
+//! assert!(item.field_flags["address.city"].defaulted);
+//! assert!(!item.field_flags.contains_key("name"));
+//! assert!(!item.is_resolved());
+//!
+//! // Round trip through a queue
+//! let stored: String = serde_json::to_string(&item).unwrap();
+//! let restored: ReviewItem<Customer> = serde_json::from_str(&stored).unwrap();
+//! assert_eq!(restored.raw_output, item.raw_output);
+//! assert_eq!(restored.field_flags, item.field_flags);
+//! assert_eq!(restored.created_at, item.created_at);
+//!
+//! // An invalid correction is rejected and leaves the item unchanged
+//! let invalid = BTreeMap::from([("address.postal_code".to_string(), json!("N/A"))]);
+//! assert!(item.apply_corrections(invalid).is_err());
+//! assert!(item.field_flags["address.postal_code"].failed);
+//!
+//! let corrections = BTreeMap::from([
+//!     ("address.city".to_string(), json!("Oslo")),
+//!     ("address.postal_code".to_string(), json!(150)),
+//! ]);
+//! item.apply_corrections(corrections).unwrap();
+//!
+//! assert!(item.is_resolved());
+//! assert_eq!(item.extracted.address.city, "Oslo");
+//! assert_eq!(item.extracted.address.postal_code, 150);
+//! ```
+
+use std::collections::BTreeMap;
+use std::time::SystemTime;
+
+pub use either::Either;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    SecretaryError,
+    schema::{FieldDescriptor, FieldKind},
+    traits::Task,
+    utilities::{cleanup_thinking_blocks, find_json_object_candidates, set_field_path},
+};
+
+/// What a reviewer should know about a single field.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldFlags {
+    /// The model returned a value of the wrong shape for this field.
+    pub failed: bool,
+    /// The field holds its default value because the model did not provide a usable one.
+    pub defaulted: bool,
+    /// The model provided a value but it should be double-checked.
+    pub low_confidence: bool,
+}
+
+impl FieldFlags {
+    /// Returns whether any flag is set.
+    pub fn needs_review(&self) -> bool {
+        self.failed || self.defaulted || self.low_confidence
+    }
+}
+
+/// An extraction waiting for a human reviewer.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReviewItem<T> {
+    /// The natural language input of the extraction.
+    pub target: String,
+    /// The data recovered from the model output, with defaults for flagged fields.
+    pub extracted: T,
+    /// Flags by field path, e.g. `address.city` or `items[0].price`. Only flagged fields are listed.
+    pub field_flags: BTreeMap<String, FieldFlags>,
+    /// The text content returned by the model.
+    pub raw_output: String,
+    /// The error that sent the extraction to review.
+    pub error: String,
+    /// When the item was created.
+    pub created_at: SystemTime,
+    /// When the item was last changed.
+    pub updated_at: SystemTime,
+}
+
+impl<T: Task> ReviewItem<T> {
+    /// Builds a review item from a failed extraction.
+    ///
+    /// The last JSON object in the raw output is compared against the schema of `T`. Fields
+    /// with a value of the wrong shape are flagged as failed, and missing fields as defaulted.
+    /// Every field that has an acceptable value is kept in `extracted`.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The natural language input of the extraction
+    /// * `raw_output` - The text content returned by the model
+    /// * `error` - The error the extraction failed with
+    pub fn from_error(target: &str, raw_output: &str, error: &dyn std::error::Error) -> Self {
+        let fields: Vec<FieldDescriptor> = T::field_descriptors();
+        let content: String = cleanup_thinking_blocks(raw_output.to_string());
+        let candidate: Option<Value> = find_json_object_candidates(&content)
+            .into_iter()
+            .rev()
+            .find_map(|candidate| serde_json::from_str::<Value>(candidate).ok());
+
+        let mut field_flags: BTreeMap<String, FieldFlags> = BTreeMap::new();
+        let mut merged: Value = serde_json::to_value(T::default()).unwrap_or(Value::Null);
+
+        match &candidate {
+            Some(candidate) => {
+                flag_fields(&fields, candidate, "", &mut field_flags);
+                merge_accepted_fields(&fields, candidate, &mut merged);
+            }
+            None => {
+                for field in &fields {
+                    field_flags.insert(
+                        field.name.clone(),
+                        FieldFlags {
+                            failed: true,
+                            defaulted: true,
+                            ..FieldFlags::default()
+                        },
+                    );
+                }
+            }
+        }
+
+        let now: SystemTime = SystemTime::now();
+
+        Self {
+            target: target.to_string(),
+            extracted: serde_json::from_value(merged).unwrap_or_default(),
+            field_flags,
+            raw_output: raw_output.to_string(),
+            error: error.to_string(),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    /// Returns whether no field is flagged as failed or defaulted anymore.
+    pub fn is_resolved(&self) -> bool {
+        self.field_flags
+            .values()
+            .all(|flags| !flags.failed && !flags.defaulted)
+    }
+
+    /// Applies a reviewer's corrections and deserializes the result again.
+    ///
+    /// Corrected fields lose their flags. If the corrected data does not deserialize into
+    /// `T`, the item is left unchanged.
+    ///
+    /// # Arguments
+    ///
+    /// * `corrections` - New values by field path, e.g. `address.city` or `items[0].price`
+    ///
+    /// # Errors
+    ///
+    /// Returns `SecretaryError::InvalidFieldPath` for a path that cannot be set, and
+    /// `SecretaryError::SerdeJsonError` if the corrected data does not fit `T`.
+    pub fn apply_corrections(
+        &mut self,
+        corrections: BTreeMap<String, Value>,
+    ) -> Result<(), SecretaryError> {
+        let mut value: Value = serde_json::to_value(&self.extracted)?;
+        for (path, correction) in &corrections {
+            set_field_path(&mut value, path, correction.clone())?;
+        }
+
+        self.extracted = serde_json::from_value(value)?;
+        for path in corrections.keys() {
+            self.field_flags.remove(path);
+        }
+        self.updated_at = SystemTime::now();
+
+        Ok(())
+    }
+}
+
+fn join_path(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", prefix, name)
+    }
+}
+
+/// Flags missing and mistyped fields, descending into nested Tasks.
+fn flag_fields(
+    fields: &[FieldDescriptor],
+    value: &Value,
+    prefix: &str,
+    field_flags: &mut BTreeMap<String, FieldFlags>,
+) {
+    for field in fields {
+        let path: String = join_path(prefix, &field.name);
+
+        let flags: FieldFlags = match value.get(&field.name) {
+            None => FieldFlags {
+                defaulted: true,
+                ..FieldFlags::default()
+            },
+            Some(field_value) if !field.accepts(field_value) => FieldFlags {
+                failed: true,
+                defaulted: true,
+                ..FieldFlags::default()
+            },
+            Some(field_value) => {
+                if matches!(field.kind, FieldKind::Task | FieldKind::OptionTask) {
+                    flag_fields(&field.children, field_value, &path, field_flags);
+                }
+                continue;
+            }
+        };
+
+        if matches!(field.kind, FieldKind::Task) {
+            // A missing or mistyped nested Task leaves every one of its fields defaulted
+            flag_fields(&field.children, &Value::Null, &path, field_flags);
+        }
+        field_flags.insert(path, flags);
+    }
+}
+
+/// Copies every acceptable field value of `source` over the defaults in `target`.
+fn merge_accepted_fields(fields: &[FieldDescriptor], source: &Value, target: &mut Value) {
+    for field in fields {
+        let field_value: &Value = match source.get(&field.name) {
+            Some(field_value) if field.accepts(field_value) => field_value,
+            _ => continue,
+        };
+
+        let nested: bool = matches!(field.kind, FieldKind::Task | FieldKind::OptionTask)
+            && field_value.is_object()
+            && target.get(&field.name).is_some_and(Value::is_object);
+
+        match target.as_object_mut() {
+            Some(target_map) if nested => {
+                if let Some(nested_target) = target_map.get_mut(&field.name) {
+                    merge_accepted_fields(&field.children, field_value, nested_target);
+                }
+            }
+            Some(target_map) => {
+                target_map.insert(field.name.clone(), field_value.clone());
+            }
+            None => {}
+        }
+    }
+}
@@ -0,0 +1,768 @@
+//! Runtime schema metadata for `Task` implementations.
+//!
+//! The `#[derive(Task)]` macro records the name, type, optionality and instruction of every
+//! field it sees and exposes them through `Task::field_descriptors()`. This module defines
+//! those descriptors together with helpers that use them to detect schema drift between
+//! stored extraction JSON and the current shape of a `Task` struct.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::Task;
+//! use secretary::schema::{check_compatibility, schema_fingerprint};
+//! use serde::{Deserialize, Serialize};
+//! use serde_json::json;
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! struct Contact {
+//!     #[task(instruction = "Extract the person's full name")]
+//!     pub name: String,
+//!     #[task(instruction = "Extract the age as a number")]
+//!     pub age: u32,
+//!     #[task(instruction = "Extract the email address if mentioned")]
+//!     pub email: Option<String>,
+//! }
+//!
+//! // A row stored before `age` was added, with a field that has since been removed
+//! // and a field whose type has since changed.
+//! let old_row = json!({"name": "John", "email": 42, "phone": "555-0100"});
+//! let report = check_compatibility::<Contact>(&old_row);
+//!
+//! assert_eq!(report.missing_fields, vec!["age".to_string()]);
+//! assert_eq!(report.extra_fields, vec!["phone".to_string()]);
+//! assert_eq!(report.type_mismatches.len(), 1);
+//! assert_eq!(report.type_mismatches[0].path, "email");
+//! assert!(!report.is_compatible());
+//!
+//! let current_row = json!({"name": "John", "age": 30, "email": null});
+//! assert!(check_compatibility::<Contact>(&current_row).is_compatible());
+//!
+//! // The fingerprint only depends on names, types and optionality.
+//! assert_eq!(schema_fingerprint::<Contact>(), schema_fingerprint::<Contact>());
+//! assert_eq!(schema_fingerprint::<Contact>().len(), 16);
+//! ```
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
+
+use crate::{
+    casing::KeyCase, extractors::LocalExtraction, normalization::StringNormalization, traits::Task,
+};
+
+/// The JSON shape a field is expected to take in the LLM's output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum JsonType {
+    String,
+    Number,
+    Boolean,
+    Array,
+    Object,
+    Null,
+    /// The derive could not determine a JSON shape for the Rust type.
+    #[default]
+    Any,
+}
+
+impl JsonType {
+    /// Returns the `JsonType` of a concrete JSON value.
+    pub fn of(value: &Value) -> Self {
+        match value {
+            Value::Null => JsonType::Null,
+            Value::Bool(_) => JsonType::Boolean,
+            Value::Number(_) => JsonType::Number,
+            Value::String(_) => JsonType::String,
+            Value::Array(_) => JsonType::Array,
+            Value::Object(_) => JsonType::Object,
+        }
+    }
+
+    /// Returns whether a JSON value has this shape.
+    pub fn accepts(&self, value: &Value) -> bool {
+        *self == JsonType::Any || *self == JsonType::of(value)
+    }
+}
+
+impl std::fmt::Display for JsonType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            JsonType::String => "string",
+            JsonType::Number => "number",
+            JsonType::Boolean => "boolean",
+            JsonType::Array => "array",
+            JsonType::Object => "object",
+            JsonType::Null => "null",
+            JsonType::Any => "any",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// How a field relates to nested `Task` implementations, mirroring the derive's classification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum FieldKind {
+    /// A regular field that does not contain a nested Task.
+    #[default]
+    Normal,
+    /// `field: SomeTaskType`
+    Task,
+    /// `field: Vec<SomeTaskType>`
+    VecTask,
+    /// `field: Option<SomeTaskType>`
+    OptionTask,
+    /// `field: HashMap<K, SomeTaskType>`
+    HashMapTask,
+    /// `field: BTreeMap<K, SomeTaskType>`
+    BTreeMapTask,
+}
+
+/// How much a field matters to the caller, from `#[task(importance = "...")]`.
+///
+/// Adaptive generation extracts critical fields with a request of their own, and partial
+/// extraction fails instead of substituting a default for a critical field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Importance {
+    /// A field that must be right, e.g. an invoice total.
+    Critical,
+    /// A regular field.
+    #[default]
+    Normal,
+    /// A nice-to-have field, e.g. free-form notes.
+    Low,
+}
+
+/// A wrong output shown to the LLM together with the reason it is wrong.
+///
+/// Field-level negative examples come from `#[task(negative_example = "...",
+/// negative_reason = "...")]`; whole-output ones are added to a `TaskDefinition` with
+/// `with_negative_example`. They are rendered in a "Common mistakes to avoid" section of the
+/// prompt, see `prompt::render_common_mistakes`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NegativeExample {
+    /// The input the mistake was made on, if it matters for the mistake.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input: Option<String>,
+    /// The wrong output, as the LLM would write it.
+    pub output: String,
+    /// Why the output is wrong.
+    pub reason: String,
+}
+
+impl NegativeExample {
+    /// Creates a negative example from a wrong output and the reason it is wrong.
+    pub fn new(output: impl Into<String>, reason: impl Into<String>) -> Self {
+        Self {
+            input: None,
+            output: output.into(),
+            reason: reason.into(),
+        }
+    }
+
+    /// Sets the input the mistake was made on.
+    pub fn with_input(mut self, input: impl Into<String>) -> Self {
+        self.input = Some(input.into());
+        self
+    }
+}
+
+/// Structured metadata about a single field of a `Task` struct.
+///
+/// Descriptors are generated by `#[derive(Task)]` and returned by `Task::field_descriptors()`.
+/// Nested Task fields carry the descriptors of the nested type in `children`.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct FieldDescriptor {
+    /// The field name as it appears in the serialized JSON.
+    pub name: String,
+    /// The Rust type of the field, with whitespace removed (e.g. `Option<String>`).
+    pub rust_type: String,
+    /// The JSON shape of the field's value, ignoring optionality.
+    pub json_type: JsonType,
+    /// The JSON shape of each element for array fields, `Any` for other fields.
+    pub item_type: JsonType,
+    /// Whether the field is an `Option` and therefore accepts `null`.
+    pub optional: bool,
+    /// The nested Task classification of the field.
+    pub kind: FieldKind,
+    /// The extraction instruction from `#[task(instruction = "...")]`, empty for nested Tasks.
+    pub instruction: String,
+    /// The importance from `#[task(importance = "...")]`, `Normal` when unset.
+    #[serde(default)]
+    pub importance: Importance,
+    /// The negative examples from `#[task(negative_example = "...", negative_reason = "...")]`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub negative_examples: Vec<NegativeExample>,
+    /// The local extractor from `#[task(extractor = "...")]`, see the `extractors` module.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_extraction: Option<LocalExtraction>,
+    /// The language the value must be written in from `#[task(output_language = "...")]`,
+    /// an ISO 639-1 code or `source`, see the `language` module. The instruction already
+    /// states the requirement.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_language: Option<String>,
+    /// The value from `#[task(default_value = "...")]` used when the model finds nothing for
+    /// the field, see the `defaults` module. The instruction already asks for it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_value: Option<Value>,
+    /// The values from `#[task(one_of = "...")]` a text field is limited to, see the
+    /// `vocabulary` module. The instruction already lists them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub one_of: Vec<String>,
+    /// Whether the items of the list keep their order of appearance in the target, from
+    /// `#[task(ordered)]`, see the `ordering` module. The instruction already asks for it.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub ordered: bool,
+    /// Whether the field is a `Lazy` field, left out of the extraction and requested on first
+    /// access, from `#[task(lazy)]`, see the `lazy` module.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub lazy: bool,
+    /// Whether the text of the field is a date, which an `ExtractionLocale` reads in its date
+    /// order, from `#[task(date)]`, see the `locale` module.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub date: bool,
+    /// The case of the field's key in the JSON the model sees, from the struct's
+    /// `#[task(key_case = "...")]`, see the `casing` module.
+    #[serde(default, skip_serializing_if = "KeyCase::is_native")]
+    pub key_case: KeyCase,
+    /// How the text of the field is normalized before deserialization, from the struct's
+    /// `#[task(normalize_strings)]`, see the `normalization` module.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub normalize_strings: Option<StringNormalization>,
+    /// Descriptors of the nested Task type, empty for normal fields.
+    pub children: Vec<FieldDescriptor>,
+}
+
+impl FieldDescriptor {
+    /// Returns whether a JSON value is acceptable for this field, taking optionality into account.
+    pub fn accepts(&self, value: &Value) -> bool {
+        (self.optional && value.is_null()) || self.json_type.accepts(value)
+    }
+
+    /// Returns whether the field is a list of strings, numbers or booleans, e.g. a
+    /// `Vec<String>`, which distributed generation requests as `<item></item>` tags.
+    pub fn is_primitive_list(&self) -> bool {
+        self.kind == FieldKind::Normal
+            && self.json_type == JsonType::Array
+            && matches!(
+                self.item_type,
+                JsonType::String | JsonType::Number | JsonType::Boolean
+            )
+    }
+
+    /// Returns whether the field is a `HashSet` or `BTreeSet`, whose elements are distinct.
+    pub fn is_set(&self) -> bool {
+        let rust_type: &str = self
+            .rust_type
+            .strip_prefix("Option<")
+            .unwrap_or(&self.rust_type);
+        let outer: &str = rust_type.split('<').next().unwrap_or(rust_type);
+
+        outer.ends_with("HashSet") || outer.ends_with("BTreeSet")
+    }
+
+    /// Adapts the descriptor of a nested Task field to serde's representation of newtypes and
+    /// tuple structs.
+    ///
+    /// A newtype, e.g. `struct Summary(String)`, is serialized as its field, so a field of a
+    /// newtype around a value takes the shape, instruction and local extractor of that value,
+    /// and a newtype around a Task is replaced by the Task's fields. A tuple struct is
+    /// serialized as an array, which the field's JSON type then says.
+    ///
+    /// The derive calls this on the descriptors of nested Task fields.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use secretary::Task;
+    /// use secretary::schema::{FieldKind, JsonType};
+    /// use serde::{Deserialize, Serialize};
+    ///
+    /// #[derive(Task, Serialize, Deserialize, Debug, Clone)]
+    /// struct Summary(#[task(instruction = "Summarize the text in one sentence")] String);
+    ///
+    /// #[derive(Task, Serialize, Deserialize, Debug, Clone)]
+    /// struct Range(
+    ///     #[task(instruction = "Extract the lower bound")] u32,
+    ///     #[task(instruction = "Extract the upper bound")] u32,
+    /// );
+    ///
+    /// #[derive(Task, Serialize, Deserialize, Debug, Clone)]
+    /// struct Report {
+    ///     pub summary: Summary,
+    ///     pub range: Range,
+    /// }
+    ///
+    /// let fields = Report::field_descriptors();
+    /// assert_eq!(fields[0].name, "summary");
+    /// assert_eq!(fields[0].kind, FieldKind::Normal);
+    /// assert_eq!(fields[0].json_type, JsonType::String);
+    /// assert_eq!(fields[0].instruction, "Summarize the text in one sentence");
+    ///
+    /// assert_eq!(fields[1].kind, FieldKind::Task);
+    /// assert_eq!(fields[1].json_type, JsonType::Array);
+    /// assert_eq!(fields[1].children[1].name, "field_1");
+    /// ```
+    pub fn resolve_tuple_structs(mut self) -> Self {
+        if is_newtype(&self.children) {
+            let inner: FieldDescriptor = self.children.remove(0);
+            if inner.kind != FieldKind::Normal {
+                self.children = inner.children;
+                return self;
+            }
+
+            return match self.kind {
+                FieldKind::Task | FieldKind::OptionTask => FieldDescriptor {
+                    name: self.name,
+                    rust_type: self.rust_type,
+                    key_case: self.key_case,
+                    optional: self.optional || inner.optional,
+                    importance: if self.importance == Importance::Normal {
+                        inner.importance
+                    } else {
+                        self.importance
+                    },
+                    ..inner
+                },
+                _ => FieldDescriptor {
+                    kind: FieldKind::Normal,
+                    item_type: inner.json_type,
+                    instruction: inner.instruction,
+                    ..self
+                },
+            };
+        }
+
+        if is_tuple_struct(&self.children) {
+            match self.kind {
+                FieldKind::Task | FieldKind::OptionTask => self.json_type = JsonType::Array,
+                FieldKind::VecTask => self.item_type = JsonType::Array,
+                _ => {}
+            }
+        }
+
+        self
+    }
+
+    fn write_canonical(&self, output: &mut String) {
+        output.push_str(&self.name);
+        output.push(':');
+        output.push_str(&self.rust_type);
+        output.push(':');
+        output.push_str(if self.optional {
+            "optional"
+        } else {
+            "required"
+        });
+
+        if !self.children.is_empty() {
+            output.push('{');
+            write_canonical_fields(&self.children, output);
+            output.push('}');
+        }
+    }
+}
+
+/// A field whose stored JSON value does not match the current schema.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TypeMismatch {
+    /// The dotted path of the field, e.g. `company.address.zip` or `items[0].price`.
+    pub path: String,
+    /// The JSON shape the current schema expects.
+    pub expected: JsonType,
+    /// The JSON shape found in the stored data.
+    pub found: JsonType,
+}
+
+/// The result of comparing stored JSON against the current schema of a `Task`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CompatReport {
+    /// Fields present in the current schema but absent from the stored JSON.
+    pub missing_fields: Vec<String>,
+    /// Fields present in the stored JSON but absent from the current schema.
+    pub extra_fields: Vec<String>,
+    /// Fields present in both whose JSON shape differs.
+    pub type_mismatches: Vec<TypeMismatch>,
+    /// The subset of `missing_fields` that are not optional and will fail deserialization.
+    pub missing_required_fields: Vec<String>,
+}
+
+impl CompatReport {
+    /// Returns whether the stored JSON matches the current schema exactly.
+    pub fn is_identical(&self) -> bool {
+        self.missing_fields.is_empty()
+            && self.extra_fields.is_empty()
+            && self.type_mismatches.is_empty()
+    }
+
+    /// Returns whether the stored JSON can still be deserialized into the current schema.
+    ///
+    /// Extra fields and missing optional fields are tolerated; missing required fields and
+    /// type mismatches are not.
+    pub fn is_compatible(&self) -> bool {
+        self.missing_required_fields.is_empty() && self.type_mismatches.is_empty()
+    }
+}
+
+/// Computes a stable fingerprint of a Task's schema.
+///
+/// The fingerprint is a 64-bit FNV-1a hash, rendered as 16 hexadecimal characters, of the
+/// field names, Rust types and optionality of the Task and all nested Tasks. Fields are sorted
+/// by name first, so reordering fields does not change the fingerprint, while adding, removing,
+/// renaming or retyping a field does. Instructions are not part of the fingerprint.
+///
+/// # Returns
+///
+/// The fingerprint as a lowercase hexadecimal string
+///
+/// # Examples
+///
+/// ```rust
+/// use secretary::Task;
+/// use secretary::schema::schema_fingerprint;
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Task, Serialize, Deserialize)]
+/// struct Before {
+///     #[task(instruction = "Extract the name")]
+///     pub name: String,
+///     #[task(instruction = "Extract the age")]
+///     pub age: u32,
+/// }
+///
+/// #[derive(Task, Serialize, Deserialize)]
+/// struct Reordered {
+///     #[task(instruction = "Extract the age as a number")]
+///     pub age: u32,
+///     #[task(instruction = "Extract the full name")]
+///     pub name: String,
+/// }
+///
+/// #[derive(Task, Serialize, Deserialize)]
+/// struct Retyped {
+///     #[task(instruction = "Extract the name")]
+///     pub name: String,
+///     #[task(instruction = "Extract the age")]
+///     pub age: Option<u32>,
+/// }
+///
+/// assert_eq!(schema_fingerprint::<Before>(), schema_fingerprint::<Reordered>());
+/// assert_ne!(schema_fingerprint::<Before>(), schema_fingerprint::<Retyped>());
+/// ```
+pub fn schema_fingerprint<T: Task>() -> String {
+    let mut canonical: String = String::new();
+    write_canonical_fields(&T::field_descriptors(), &mut canonical);
+
+    format!("{:016x}", fnv1a_64(canonical.as_bytes()))
+}
+
+/// Compares stored extraction JSON against the current schema of a Task.
+///
+/// Nested Task fields are checked recursively, including every element of `Vec<Task>` fields
+/// and every value of map-of-Task fields.
+///
+/// # Arguments
+///
+/// * `old_json` - Previously stored extraction output
+///
+/// # Returns
+///
+/// A `CompatReport` listing missing fields, extra fields and type mismatches by dotted path
+pub fn check_compatibility<T: Task>(old_json: &Value) -> CompatReport {
+    let mut report: CompatReport = CompatReport::default();
+    compare_object(&T::field_descriptors(), old_json, "", &mut report);
+
+    report
+}
+
+/// Returns the dotted paths of the fields marked `#[task(importance = "critical")]`.
+///
+/// Nested Task fields are descended into, except for collections of Tasks, whose paths
+/// depend on the data. A critical nested Task field is listed itself.
+///
+/// # Arguments
+///
+/// * `fields` - The descriptors of a Task, e.g. from `Task::field_descriptors()`
+///
+/// # Examples
+///
+/// ```rust
+/// use secretary::Task;
+/// use secretary::schema::{Importance, critical_field_paths};
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Task, Serialize, Deserialize, Debug)]
+/// struct Totals {
+///     #[task(instruction = "Extract the total amount", importance = "critical")]
+///     pub total: f64,
+///     #[task(instruction = "Extract the currency")]
+///     pub currency: String,
+/// }
+///
+/// #[derive(Task, Serialize, Deserialize, Debug)]
+/// struct Invoice {
+///     #[task(instruction = "Extract the invoice number", importance = "critical")]
+///     pub number: String,
+///     #[task(instruction = "Extract any notes", importance = "low")]
+///     pub notes: Option<String>,
+///     pub totals: Totals,
+/// }
+///
+/// let fields = Invoice::field_descriptors();
+/// assert_eq!(fields[0].importance, Importance::Critical);
+/// assert_eq!(fields[1].importance, Importance::Low);
+/// assert_eq!(fields[2].importance, Importance::Normal);
+/// assert_eq!(critical_field_paths(&fields), vec!["number", "totals.total"]);
+/// ```
+pub fn critical_field_paths(fields: &[FieldDescriptor]) -> Vec<String> {
+    let mut paths: Vec<String> = Vec::new();
+    collect_critical_paths(fields, "", &mut paths);
+
+    paths
+}
+
+fn collect_critical_paths(fields: &[FieldDescriptor], prefix: &str, paths: &mut Vec<String>) {
+    for field in fields {
+        let path: String = if prefix.is_empty() {
+            field.name.clone()
+        } else {
+            format!("{}.{}", prefix, field.name)
+        };
+
+        if field.importance == Importance::Critical {
+            paths.push(path);
+        } else if matches!(field.kind, FieldKind::Task | FieldKind::OptionTask) {
+            collect_critical_paths(&field.children, &path, paths);
+        }
+    }
+}
+
+/// Builds a JSON Schema describing the objects a set of fields serializes to.
+///
+/// Nested Tasks become nested object schemas, `Vec` fields of Tasks become arrays of them and
+/// map fields become objects whose values follow the nested schema. Instructions are used as
+/// descriptions, and optional fields also accept `null`. Tuple structs become arrays with one
+/// item schema per position and newtypes the schema of their field.
+///
+/// # Arguments
+///
+/// * `fields` - The field descriptors, e.g. from `Task::field_descriptors()`
+///
+/// # Returns
+///
+/// An object schema listing every field as a property and the non-optional ones as required,
+/// or the array or value schema of a tuple struct or newtype
+///
+/// # Examples
+///
+/// ```rust
+/// use secretary::Task;
+/// use secretary::schema::json_schema;
+/// use serde::{Deserialize, Serialize};
+/// use serde_json::json;
+///
+/// #[derive(Task, Serialize, Deserialize)]
+/// struct Person {
+///     #[task(instruction = "Extract the name")]
+///     pub name: String,
+///     #[task(instruction = "Extract the nicknames")]
+///     pub nicknames: Option<Vec<String>>,
+/// }
+///
+/// assert_eq!(
+///     json_schema(&Person::field_descriptors()),
+///     json!({
+///         "type": "object",
+///         "properties": {
+///             "name": {"type": "string", "description": "Extract the name"},
+///             "nicknames": {
+///                 "anyOf": [{"type": "array", "items": {"type": "string"}}, {"type": "null"}],
+///                 "description": "Extract the nicknames"
+///             }
+///         },
+///         "required": ["name"]
+///     })
+/// );
+/// ```
+pub fn json_schema(fields: &[FieldDescriptor]) -> Value {
+    // Newtypes and tuple structs are serialized as their field and as arrays
+    if is_newtype(fields) {
+        return field_schema(&fields[0]);
+    }
+    if is_tuple_struct(fields) {
+        let items: Vec<Value> = fields.iter().map(field_schema).collect();
+        return json!({
+            "type": "array",
+            "prefixItems": items,
+            "minItems": fields.len(),
+            "maxItems": fields.len()
+        });
+    }
+
+    let mut properties: Map<String, Value> = Map::new();
+    let mut required: Vec<Value> = Vec::new();
+
+    for field in fields {
+        if !field.optional {
+            required.push(Value::String(field.key()));
+        }
+        properties.insert(field.key(), field_schema(field));
+    }
+
+    json!({"type": "object", "properties": properties, "required": required})
+}
+
+/// Returns the JSON Schema of a single field, described by its instruction.
+fn field_schema(field: &FieldDescriptor) -> Value {
+    let mut schema: Value = match field.kind {
+        FieldKind::Normal if field.json_type == JsonType::Array => {
+            match json_type_schema(field.item_type) {
+                Value::Object(items) if items.is_empty() => json!({"type": "array"}),
+                items => json!({"type": "array", "items": items}),
+            }
+        }
+        FieldKind::Normal => json_type_schema(field.json_type),
+        FieldKind::Task | FieldKind::OptionTask => json_schema(&field.children),
+        FieldKind::VecTask => json!({"type": "array", "items": json_schema(&field.children)}),
+        FieldKind::HashMapTask | FieldKind::BTreeMapTask => {
+            json!({"type": "object", "additionalProperties": json_schema(&field.children)})
+        }
+    };
+
+    if field.optional {
+        schema = json!({"anyOf": [schema, {"type": "null"}]});
+    }
+    if !field.instruction.is_empty()
+        && let Some(schema) = schema.as_object_mut()
+    {
+        schema.insert(
+            "description".to_string(),
+            Value::String(field.instruction.clone()),
+        );
+    }
+
+    schema
+}
+
+/// Returns whether the fields are the field of a newtype, which has no name.
+pub(crate) fn is_newtype(fields: &[FieldDescriptor]) -> bool {
+    matches!(fields, [field] if field.name.is_empty())
+}
+
+/// Returns whether the fields are those of a tuple struct, named `field_0`, `field_1` and so
+/// on by position.
+pub(crate) fn is_tuple_struct(fields: &[FieldDescriptor]) -> bool {
+    fields.len() > 1
+        && fields
+            .iter()
+            .enumerate()
+            .all(|(position, field)| field.name == format!("field_{}", position))
+}
+
+/// Returns the JSON Schema of a plain JSON type. `Any` accepts every value.
+fn json_type_schema(json_type: JsonType) -> Value {
+    match json_type {
+        JsonType::String => json!({"type": "string"}),
+        JsonType::Number => json!({"type": "number"}),
+        JsonType::Boolean => json!({"type": "boolean"}),
+        JsonType::Array => json!({"type": "array"}),
+        JsonType::Object => json!({"type": "object"}),
+        JsonType::Null => json!({"type": "null"}),
+        JsonType::Any => json!({}),
+    }
+}
+
+fn write_canonical_fields(fields: &[FieldDescriptor], output: &mut String) {
+    let mut sorted: Vec<&FieldDescriptor> = fields.iter().collect();
+    sorted.sort_by(|a, b| a.name.cmp(&b.name));
+
+    for field in sorted {
+        field.write_canonical(output);
+        output.push(';');
+    }
+}
+
+pub(crate) fn fnv1a_64(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+
+    hash
+}
+
+fn join_path(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", prefix, name)
+    }
+}
+
+fn compare_object(
+    fields: &[FieldDescriptor],
+    value: &Value,
+    prefix: &str,
+    report: &mut CompatReport,
+) {
+    let map = match value.as_object() {
+        Some(map) => map,
+        None => return,
+    };
+
+    for field in fields {
+        let path: String = join_path(prefix, &field.name);
+
+        let field_value = match map.get(&field.name) {
+            Some(field_value) => field_value,
+            None => {
+                if !field.optional {
+                    report.missing_required_fields.push(path.clone());
+                }
+                report.missing_fields.push(path);
+                continue;
+            }
+        };
+
+        if !field.accepts(field_value) {
+            report.type_mismatches.push(TypeMismatch {
+                path,
+                expected: field.json_type,
+                found: JsonType::of(field_value),
+            });
+            continue;
+        }
+
+        if field.children.is_empty() {
+            continue;
+        }
+
+        match field.kind {
+            FieldKind::Task | FieldKind::OptionTask => {
+                compare_object(&field.children, field_value, &path, report);
+            }
+            FieldKind::VecTask => {
+                if let Some(items) = field_value.as_array() {
+                    for (index, item) in items.iter().enumerate() {
+                        let item_path: String = format!("{}[{}]", path, index);
+                        compare_object(&field.children, item, &item_path, report);
+                    }
+                }
+            }
+            FieldKind::HashMapTask | FieldKind::BTreeMapTask => {
+                if let Some(entries) = field_value.as_object() {
+                    for (key, entry) in entries {
+                        let entry_path: String = format!("{}[{}]", path, key);
+                        compare_object(&field.children, entry, &entry_path, report);
+                    }
+                }
+            }
+            FieldKind::Normal => {}
+        }
+    }
+
+    for key in map.keys() {
+        if !fields.iter().any(|field| &field.name == key) {
+            report.extra_fields.push(join_path(prefix, key));
+        }
+    }
+}
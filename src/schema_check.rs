@@ -0,0 +1,467 @@
+//! Checking that serde writes and reads the keys the prompt asks for.
+//!
+//! The prompt of a Task names its fields after the field descriptors, while the model's
+//! answer is deserialized by serde. The derive does not see `#[serde(...)]` attributes, so a
+//! `rename`, a `skip` or a `flatten` makes the two disagree, and every answer either fails to
+//! deserialize or silently loses a field. `Task::verify_schema_consistency` finds such
+//! disagreements before the first request: it serializes the Task's default, compares the
+//! keys serde writes with the descriptors and checks the nested Tasks the same way.
+//!
+//! Every `SchemaMismatch` has the schema path of the field, e.g. `address.city` or
+//! `items[].price`, and a `MismatchKind`:
+//!
+//! - `Renamed`: serde writes the field under another key
+//! - `Skipped`: serde neither writes nor reads the field
+//! - `Unexpected`: serde writes a key that the prompt never asks for
+//! - `Flattened`: serde writes the fields of a nested Task into its parent
+//! - `FlattenCollision`: a key of a flattened Task is also the key of another field
+//! - `ShapeMismatch`: serde writes the field in another JSON shape than the prompt asks for
+//!
+//! Fields that serde only leaves out while serializing, e.g. with
+//! `#[serde(skip_serializing_if = "Option::is_none")]`, are still read and are not reported.
+//! Keys in a `#[task(key_case)]` are converted back before deserialization, so fields are
+//! compared by name. Enums, newtypes and tuple structs, whose keys are not the names of their
+//! fields, are not compared themselves, only the Tasks nested in them.
+//!
+//! `assert_schema_consistency` panics with the mismatches, for tests and startup code.
+//! `RequestOptions::with_strict_schema` makes the generate methods run the check before the
+//! first request and fail with `SecretaryError::SchemaInconsistent`; the result is kept for
+//! the rest of the process, so each Task type is only checked once.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::Task;
+//! use secretary::schema_check::{MismatchKind, SchemaMismatch};
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! struct Contact {
+//!     #[task(instruction = "Extract the person's full name")]
+//!     #[serde(rename = "fullName")]
+//!     pub name: String,
+//!     #[task(instruction = "Extract the email address if mentioned")]
+//!     #[serde(skip_serializing_if = "Option::is_none")]
+//!     pub email: Option<String>,
+//! }
+//!
+//! assert_eq!(
+//!     Contact::verify_schema_consistency(),
+//!     Err(vec![SchemaMismatch {
+//!         path: "name".to_string(),
+//!         kind: MismatchKind::Renamed {
+//!             key: "fullName".to_string()
+//!         },
+//!     }])
+//! );
+//! ```
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::{LazyLock, Mutex};
+
+use serde::de::{self, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    SecretaryError,
+    schema::{FieldDescriptor, FieldKind, JsonType},
+    traits::Task,
+};
+
+/// A disagreement between the prompt of a Task and serde's representation of it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SchemaMismatch {
+    /// The schema path of the field the prompt asks for, e.g. `address.city` or
+    /// `items[].price`, or of the key serde writes for `MismatchKind::Unexpected`.
+    pub path: String,
+    /// How serde's representation differs.
+    pub kind: MismatchKind,
+}
+
+/// How serde's representation of a field differs from the prompt.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MismatchKind {
+    /// Serde writes and reads the field under another key, e.g. `#[serde(rename)]`.
+    Renamed {
+        /// The key serde uses.
+        key: String,
+    },
+    /// Serde neither writes nor reads the field, e.g. `#[serde(skip)]`.
+    Skipped,
+    /// Serde writes a key that is no field of the prompt.
+    Unexpected,
+    /// Serde writes the fields of the nested Task into its parent, `#[serde(flatten)]`.
+    Flattened,
+    /// A key of the flattened Task is also written by another field, so one of them is lost.
+    FlattenCollision {
+        /// The key both write.
+        key: String,
+    },
+    /// Serde writes the field in another JSON shape, e.g. `#[serde(with)]`.
+    ShapeMismatch {
+        /// The shape the prompt asks for.
+        expected: JsonType,
+        /// The shape serde writes.
+        found: JsonType,
+    },
+}
+
+impl fmt::Display for SchemaMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            MismatchKind::Renamed { key } => {
+                write!(f, "`{}` is written as `{}`", self.path, key)
+            }
+            MismatchKind::Skipped => write!(f, "`{}` is neither written nor read", self.path),
+            MismatchKind::Unexpected => {
+                write!(
+                    f,
+                    "`{}` is written but is no field of the prompt",
+                    self.path
+                )
+            }
+            MismatchKind::Flattened => write!(
+                f,
+                "`{}` is written as the keys of its fields in its parent",
+                self.path
+            ),
+            MismatchKind::FlattenCollision { key } => write!(
+                f,
+                "`{}` is flattened into the key `{}`, which another field also writes",
+                self.path, key
+            ),
+            MismatchKind::ShapeMismatch { expected, found } => write!(
+                f,
+                "`{}` is written as {}, the prompt asks for {}",
+                self.path, found, expected
+            ),
+        }
+    }
+}
+
+/// Panics with every mismatch between the prompt of `T` and serde's representation of it.
+///
+/// Meant for tests and for startup code that should not run with a misconfigured Task.
+///
+/// # Panics
+///
+/// Panics if `T::verify_schema_consistency` finds a mismatch.
+pub fn assert_schema_consistency<T: Task>() {
+    if let Err(mismatches) = T::verify_schema_consistency() {
+        panic!(
+            "{}",
+            SecretaryError::SchemaInconsistent {
+                task: std::any::type_name::<T>().to_string(),
+                mismatches,
+            }
+        );
+    }
+}
+
+/// Prefixes the paths of the mismatches of a nested Task with the path of its field, e.g.
+/// `address` or `items[]`.
+///
+/// The derive calls this in `Task::nested_schema_mismatches`.
+pub fn prefixed(prefix: &str, result: Result<(), Vec<SchemaMismatch>>) -> Vec<SchemaMismatch> {
+    let mismatches: Vec<SchemaMismatch> = result.err().unwrap_or_default();
+    if prefix.is_empty() {
+        return mismatches;
+    }
+
+    mismatches
+        .into_iter()
+        .map(|mismatch| SchemaMismatch {
+            path: format!("{}.{}", prefix, mismatch.path),
+            ..mismatch
+        })
+        .collect()
+}
+
+/// The results of the checks run by strict generation, by Task type.
+static CHECKED: LazyLock<Mutex<HashMap<&'static str, Vec<SchemaMismatch>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Checks `T` on its first use in the process and returns the result of that check after.
+///
+/// # Errors
+///
+/// Returns `SecretaryError::SchemaInconsistent` with the mismatches of `T`.
+pub(crate) fn check_once<T: Task>() -> Result<(), SecretaryError> {
+    let task: &'static str = std::any::type_name::<T>();
+    let mismatches: Vec<SchemaMismatch> = {
+        let mut checked = CHECKED
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        checked
+            .entry(task)
+            .or_insert_with(|| T::verify_schema_consistency().err().unwrap_or_default())
+            .clone()
+    };
+
+    if mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(SecretaryError::SchemaInconsistent {
+            task: task.to_string(),
+            mismatches,
+        })
+    }
+}
+
+/// Compares the keys serde writes for the default of `T` with the descriptors of its own
+/// fields, without descending into nested Tasks.
+///
+/// Fields serde does not write are probed by deserializing the default with a value of the
+/// wrong shape under the field's key: a field serde reads fails to deserialize.
+pub(crate) fn own_mismatches<T: Task>() -> Vec<SchemaMismatch> {
+    let fields: Vec<FieldDescriptor> = T::field_descriptors();
+    if fields.is_empty() || !T::supports_distributed_generation() {
+        return Vec::new();
+    }
+    let Ok(serialized) = serde_json::to_string(&T::default()) else {
+        return Vec::new();
+    };
+    let Ok(written) = serde_json::from_str::<Written>(&serialized) else {
+        return Vec::new();
+    };
+    if written.shape != JsonType::Object {
+        return Vec::new();
+    }
+    let Ok(default) = serde_json::from_str::<Value>(&serialized) else {
+        return Vec::new();
+    };
+    let round_trips: bool = serde_json::from_value::<T>(default.clone()).is_ok();
+    let is_read = |field: &FieldDescriptor| {
+        if !round_trips {
+            return false;
+        }
+        let mut probe: Value = default.clone();
+        probe[field.name.as_str()] = match field.json_type {
+            JsonType::Boolean => Value::from("probe"),
+            _ => Value::Bool(true),
+        };
+        match serde_json::from_value::<T>(probe) {
+            Ok(_) => false,
+            Err(error) => !error.to_string().starts_with("unknown field"),
+        }
+    };
+
+    compare_keys(&fields, &written.entries, is_read)
+}
+
+/// Pairs the descriptors with the keys serde writes, in the order of both.
+///
+/// Keys that are the name of a field anchor the two orders. Between two anchors, the fields
+/// serde does not write are paired with the keys that are no field's in order, as renames.
+fn compare_keys(
+    fields: &[FieldDescriptor],
+    entries: &[(String, Written)],
+    is_read: impl Fn(&FieldDescriptor) -> bool,
+) -> Vec<SchemaMismatch> {
+    let names: HashSet<&str> = fields.iter().map(|field| field.name.as_str()).collect();
+    let mut keys: Vec<(&str, &Written)> = Vec::new();
+    let mut duplicates: HashSet<&str> = HashSet::new();
+    for (key, written) in entries {
+        if keys.iter().any(|(seen, _)| seen == key) {
+            duplicates.insert(key.as_str());
+        } else {
+            keys.push((key.as_str(), written));
+        }
+    }
+
+    let mut mismatches: Vec<SchemaMismatch> = Vec::new();
+    // The fields and keys without a counterpart, with the number of anchors before them
+    let mut missing: Vec<(usize, &FieldDescriptor)> = Vec::new();
+    let mut anchors: usize = 0;
+    for field in fields {
+        match keys.iter().find(|(key, _)| *key == field.name) {
+            Some((_, written)) => {
+                mismatches.extend(shape_mismatch(field, &field.name, written));
+                anchors += 1;
+            }
+            None => missing.push((anchors, field)),
+        }
+    }
+    let mut extra: Vec<(usize, &str, &Written)> = Vec::new();
+    let mut anchors: usize = 0;
+    for &(key, written) in &keys {
+        if names.contains(key) {
+            anchors += 1;
+        } else {
+            extra.push((anchors, key, written));
+        }
+    }
+
+    // Nested Tasks whose every field is written in their parent are flattened
+    missing.retain(|(_, field)| {
+        let flattened: bool = matches!(field.kind, FieldKind::Task | FieldKind::OptionTask)
+            && !field.children.is_empty()
+            && field.children.iter().all(|child| {
+                names.contains(child.name.as_str())
+                    || extra.iter().any(|(_, key, _)| *key == child.name)
+            });
+        if !flattened {
+            return true;
+        }
+
+        mismatches.push(SchemaMismatch {
+            path: field.name.clone(),
+            kind: MismatchKind::Flattened,
+        });
+        for child in &field.children {
+            let key: &str = child.name.as_str();
+            if names.contains(key) || duplicates.contains(key) {
+                mismatches.push(SchemaMismatch {
+                    path: field.name.clone(),
+                    kind: MismatchKind::FlattenCollision {
+                        key: key.to_string(),
+                    },
+                });
+            }
+            extra.retain(|(_, extra_key, _)| *extra_key != key);
+        }
+        false
+    });
+    // Fields serde reads but did not write, e.g. `None` with `skip_serializing_if`
+    missing.retain(|(_, field)| !is_read(field));
+
+    let mut extra = extra.into_iter().peekable();
+    for (gap, field) in missing {
+        while let Some((_, key, _)) = extra.next_if(|(extra_gap, _, _)| *extra_gap < gap) {
+            mismatches.push(unexpected(key));
+        }
+        match extra.next_if(|(extra_gap, _, _)| *extra_gap == gap) {
+            Some((_, key, written)) => {
+                mismatches.push(SchemaMismatch {
+                    path: field.name.clone(),
+                    kind: MismatchKind::Renamed {
+                        key: key.to_string(),
+                    },
+                });
+                mismatches.extend(shape_mismatch(field, &field.name, written));
+            }
+            None => mismatches.push(SchemaMismatch {
+                path: field.name.clone(),
+                kind: MismatchKind::Skipped,
+            }),
+        }
+    }
+    mismatches.extend(extra.map(|(_, key, _)| unexpected(key)));
+
+    mismatches
+}
+
+fn unexpected(key: &str) -> SchemaMismatch {
+    SchemaMismatch {
+        path: key.to_string(),
+        kind: MismatchKind::Unexpected,
+    }
+}
+
+/// Returns the mismatch of a field serde writes in another shape than the prompt asks for.
+///
+/// The derive describes every type it does not know as an object, so only maps and nested
+/// Tasks are expected to be written as objects; an enum or a date may be written as a string.
+fn shape_mismatch(
+    field: &FieldDescriptor,
+    path: &str,
+    written: &Written,
+) -> Option<SchemaMismatch> {
+    let expected: JsonType = field.json_type;
+    if expected == JsonType::Object && field.kind == FieldKind::Normal && !is_map(field) {
+        return None;
+    }
+    let found: JsonType = written.shape;
+    let accepted: bool = matches!(expected, JsonType::Any | JsonType::Null)
+        || expected == found
+        || (found == JsonType::Null && (field.optional || field.kind == FieldKind::OptionTask));
+
+    (!accepted).then(|| SchemaMismatch {
+        path: path.to_string(),
+        kind: MismatchKind::ShapeMismatch { expected, found },
+    })
+}
+
+fn is_map(field: &FieldDescriptor) -> bool {
+    let rust_type: &str = field
+        .rust_type
+        .strip_prefix("Option<")
+        .unwrap_or(&field.rust_type);
+    let outer: &str = rust_type.split('<').next().unwrap_or(rust_type);
+
+    outer.ends_with("HashMap") || outer.ends_with("BTreeMap")
+}
+
+/// The JSON serde writes for a value: its shape and, for objects, the keys in the order they
+/// are written, duplicates included.
+struct Written {
+    shape: JsonType,
+    entries: Vec<(String, Written)>,
+}
+
+impl Written {
+    fn of(shape: JsonType) -> Self {
+        Self {
+            shape,
+            entries: Vec::new(),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Written {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(WrittenVisitor)
+    }
+}
+
+struct WrittenVisitor;
+
+impl<'de> Visitor<'de> for WrittenVisitor {
+    type Value = Written;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a JSON value")
+    }
+
+    fn visit_bool<E: de::Error>(self, _: bool) -> Result<Written, E> {
+        Ok(Written::of(JsonType::Boolean))
+    }
+
+    fn visit_i64<E: de::Error>(self, _: i64) -> Result<Written, E> {
+        Ok(Written::of(JsonType::Number))
+    }
+
+    fn visit_u64<E: de::Error>(self, _: u64) -> Result<Written, E> {
+        Ok(Written::of(JsonType::Number))
+    }
+
+    fn visit_f64<E: de::Error>(self, _: f64) -> Result<Written, E> {
+        Ok(Written::of(JsonType::Number))
+    }
+
+    fn visit_str<E: de::Error>(self, _: &str) -> Result<Written, E> {
+        Ok(Written::of(JsonType::String))
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Written, E> {
+        Ok(Written::of(JsonType::Null))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Written, A::Error> {
+        while seq.next_element::<IgnoredAny>()?.is_some() {}
+        Ok(Written::of(JsonType::Array))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Written, A::Error> {
+        let mut entries: Vec<(String, Written)> = Vec::new();
+        while let Some(entry) = map.next_entry::<String, Written>()? {
+            entries.push(entry);
+        }
+        Ok(Written {
+            shape: JsonType::Object,
+            entries,
+        })
+    }
+}
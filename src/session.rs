@@ -0,0 +1,292 @@
+//! Multi-turn refinement of an extraction.
+//!
+//! An `ExtractionSession` extracts a Task from a text, then refines it turn by turn with
+//! feedback such as "the date is the invoice date, not the due date". Every turn answers
+//! with the whole Task again, parsed like `generate_data` parses its response.
+//!
+//! How the conversation is carried depends on the provider's
+//! `ProviderCapabilities::conversation_state`:
+//!
+//! - `ConversationState::PreviousResponseId`, declared by `ResponsesApiLLM`: the server keeps
+//!   the conversation, so a refinement sends only the feedback together with the
+//!   `previous_response_id` of the last response. The system prompt and the text are sent
+//!   once, on the first turn.
+//! - `ConversationState::ClientHistory`, the default of other providers: every turn resends
+//!   the whole conversation, the model's earlier answers included.
+//!
+//! The session keeps the conversation on the client either way, so a turn whose response
+//! carries no ID makes the next turn fall back to sending it whole.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use secretary::Task;
+//! use secretary::llm_providers::responses::ResponsesApiLLM;
+//! use secretary::session::ExtractionSession;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! struct Invoice {
+//!     #[task(instruction = "Extract the invoice number")]
+//!     pub number: String,
+//!     #[task(instruction = "Extract the date, as YYYY-MM-DD")]
+//!     pub date: String,
+//! }
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+//! let llm = ResponsesApiLLM::new("https://api.openai.com/v1", "sk-...", "gpt-4.1-mini")?;
+//! let task = Invoice::new();
+//! let mut session = ExtractionSession::new(&llm, &task);
+//!
+//! let invoice: Invoice = session.extract("Invoice 42, issued 2024-03-01, due 2024-03-31.", &vec![])?;
+//! // Only this line and the ID of the previous response are sent
+//! let invoice: Invoice = session.refine("The date is the issue date, not the due date.")?;
+//! assert_eq!(session.turns(), 2);
+//! # Ok(())
+//! # }
+//! ```
+
+use serde_json::{Value, json};
+
+use crate::{
+    compiled::ExtractionPlan,
+    guardrail::InjectionVerdict,
+    llm_providers::capabilities::ConversationState,
+    message::Message,
+    request::{RequestOptions, merge_extra_body},
+    traits::{
+        GuardedRequest, IsLLM, extract_json_content, guard_request, limit_content,
+        parse_plan_content,
+    },
+};
+
+/// Appended to the feedback of a refinement.
+const REFINE_INSTRUCTION: &str =
+    "Apply these corrections and return the complete JSON object again.";
+
+/// A conversation that extracts a Task and refines it with feedback, see the module docs.
+pub struct ExtractionSession<'a, L: IsLLM + ?Sized, P: ExtractionPlan> {
+    llm: &'a L,
+    plan: &'a P,
+    options: RequestOptions,
+    history: Vec<Message>,
+    response_id: Option<String>,
+    turns: usize,
+    injection_verdict: Option<InjectionVerdict>,
+}
+
+impl<'a, L: IsLLM + ?Sized, P: ExtractionPlan> ExtractionSession<'a, L, P> {
+    /// Creates a session that extracts the plan's Task with the provider.
+    ///
+    /// # Arguments
+    ///
+    /// * `llm` - The provider to converse with
+    /// * `plan` - A Task, or a `CompiledTask` of one, that provides the system prompt
+    pub fn new(llm: &'a L, plan: &'a P) -> Self {
+        Self {
+            llm,
+            plan,
+            options: RequestOptions::default(),
+            history: Vec::new(),
+            response_id: None,
+            turns: 0,
+            injection_verdict: None,
+        }
+    }
+
+    /// Sets the request settings of every turn.
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - Request settings such as the temperature or extra body parameters
+    pub fn with_options(mut self, options: RequestOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Returns the conversation so far, the model's answers included.
+    pub fn history(&self) -> &[Message] {
+        &self.history
+    }
+
+    /// Returns the ID of the last response, when the provider keeps the conversation.
+    pub fn response_id(&self) -> Option<&str> {
+        self.response_id.as_deref()
+    }
+
+    /// Returns the number of turns answered so far.
+    pub fn turns(&self) -> usize {
+        self.turns
+    }
+
+    /// Returns the verdict of the provider's guardrail on the text of the last extraction,
+    /// when one is set, see the `guardrail` module.
+    ///
+    /// Feedback given to `refine` is not checked.
+    pub fn injection_verdict(&self) -> Option<&InjectionVerdict> {
+        self.injection_verdict.as_ref()
+    }
+
+    /// Returns whether the next turn sends only its new message with the previous response ID.
+    pub fn uses_server_state(&self) -> bool {
+        self.response_id.is_some()
+            && self.llm.get_capabilities().conversation_state
+                == ConversationState::PreviousResponseId
+    }
+
+    /// Extracts the Task from a text, starting a new conversation.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The natural language text to extract data from
+    /// * `additional_instructions` - Extra instructions to guide the extraction process
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `GenerateData::generate_data`.
+    pub fn extract(
+        &mut self,
+        target: &str,
+        additional_instructions: &Vec<String>,
+    ) -> Result<P::Task, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        self.reset();
+        let guarded: GuardedRequest = guard_request(self.llm, target, additional_instructions)?;
+        let messages: Vec<Message> = self
+            .plan
+            .prompt_messages(&guarded.target, guarded.instructions());
+        self.injection_verdict = guarded.verdict;
+        let (request, options) = self.turn_request(&messages);
+        let response: String = self
+            .llm
+            .send_messages_with_options(request, true, &options)?;
+
+        self.finish_turn(messages, &response)
+    }
+
+    /// Refines the last extraction with feedback, returning the whole Task again.
+    ///
+    /// # Arguments
+    ///
+    /// * `feedback` - What to correct, e.g. "the total includes tax"
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `GenerateData::generate_data`.
+    pub fn refine(
+        &mut self,
+        feedback: &str,
+    ) -> Result<P::Task, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let messages: Vec<Message> = vec![refine_message(feedback)];
+        let (request, options) = self.turn_request(&messages);
+        let response: String = self
+            .llm
+            .send_messages_with_options(request, true, &options)?;
+
+        self.finish_turn(messages, &response)
+    }
+
+    /// Forgets the conversation, so the next turn starts a new one.
+    pub fn reset(&mut self) {
+        self.history.clear();
+        self.response_id = None;
+        self.turns = 0;
+        self.injection_verdict = None;
+    }
+
+    /// Returns the messages and options of a turn that adds `messages` to the conversation.
+    fn turn_request(&self, messages: &[Message]) -> (Vec<Message>, RequestOptions) {
+        match &self.response_id {
+            Some(response_id) if self.uses_server_state() => {
+                let mut extra_body: Value =
+                    self.options.extra_body.clone().unwrap_or_else(|| json!({}));
+                merge_extra_body(
+                    &mut extra_body,
+                    &json!({"previous_response_id": response_id}),
+                );
+
+                (
+                    messages.to_vec(),
+                    self.options.clone().with_extra_body(extra_body),
+                )
+            }
+            _ => {
+                let mut request: Vec<Message> = self.history.clone();
+                request.extend_from_slice(messages);
+                (request, self.options.clone())
+            }
+        }
+    }
+
+    /// Records the turn and the model's answer, then parses the answer.
+    ///
+    /// The turn is recorded before parsing, so an answer that does not parse can be corrected
+    /// by the next refinement.
+    fn finish_turn(
+        &mut self,
+        messages: Vec<Message>,
+        response: &str,
+    ) -> Result<P::Task, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let (content, _) = limit_content(
+            self.llm,
+            &self.options,
+            extract_json_content(self.llm, response)?,
+        )?;
+        self.history.extend(messages);
+        self.history.push(Message::assistant(content.clone()));
+        self.response_id = self.llm.extract_response_id(response);
+        self.turns += 1;
+
+        parse_plan_content(self.llm, self.plan, &content)
+    }
+}
+
+impl<'a, L, P> ExtractionSession<'a, L, P>
+where
+    L: IsLLM + Sync + ?Sized,
+    P: ExtractionPlan + Sync,
+{
+    /// Extracts the Task from a text, starting a new conversation.
+    ///
+    /// This is the asynchronous version of `extract`.
+    pub async fn async_extract(
+        &mut self,
+        target: &str,
+        additional_instructions: &Vec<String>,
+    ) -> Result<P::Task, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        self.reset();
+        let guarded: GuardedRequest = guard_request(self.llm, target, additional_instructions)?;
+        let messages: Vec<Message> = self
+            .plan
+            .prompt_messages(&guarded.target, guarded.instructions());
+        self.injection_verdict = guarded.verdict;
+        let (request, options) = self.turn_request(&messages);
+        let response: String = self
+            .llm
+            .async_send_messages_with_options(request, true, &options)
+            .await?;
+
+        self.finish_turn(messages, &response)
+    }
+
+    /// Refines the last extraction with feedback, returning the whole Task again.
+    ///
+    /// This is the asynchronous version of `refine`.
+    pub async fn async_refine(
+        &mut self,
+        feedback: &str,
+    ) -> Result<P::Task, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let messages: Vec<Message> = vec![refine_message(feedback)];
+        let (request, options) = self.turn_request(&messages);
+        let response: String = self
+            .llm
+            .async_send_messages_with_options(request, true, &options)
+            .await?;
+
+        self.finish_turn(messages, &response)
+    }
+}
+
+/// Builds the user message of a refinement.
+fn refine_message(feedback: &str) -> Message {
+    Message::user(format!("{}\n\n{}", feedback.trim(), REFINE_INSTRUCTION))
+}
@@ -0,0 +1,178 @@
+//! Splitting a batch of targets between workers that do not talk to each other.
+//!
+//! Workers that run the same binary over the same list of items each take the items of
+//! their `Shard`. An item belongs to exactly one of `count` shards, chosen from its ID alone,
+//! so every worker selects its items independently, items added to the list mid-run are
+//! picked up by exactly one worker, and removing items moves no other item.
+//!
+//! The shard of an ID is the jump consistent hash (Lamping and Veach, 2014) of the 64-bit
+//! FNV-1a hash of its UTF-8 bytes. Both are fixed here rather than taken from the standard
+//! library, whose `DefaultHasher` may change between Rust releases, so an ID keeps its shard
+//! across builds, platforms and releases of this crate.
+//!
+//! The jump hash also keeps rebalancing small: growing from `k` to `n` shards only moves
+//! about `1 - k / n` of the items, all of them into the new shards, and shrinking only moves
+//! the items of the removed shards. `moved_items` lists the items that change shard, e.g. to
+//! hand over their partial results before the workers restart.
+//!
+//! `Shard::select` takes the items of a shard from a list, such as the targets passed to
+//! `AsyncGenerateData::stream_generate_data_batch`.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::sharding::{MovedItem, Shard, moved_items, shard_of};
+//!
+//! let ids = ["invoice-1", "invoice-2", "invoice-3", "invoice-4"];
+//!
+//! // Each of three workers takes its own items, and together they take every item once
+//! let shards: Vec<Shard> = (0..3).map(|index| Shard::of(3, index)).collect();
+//! for id in ids {
+//!     assert_eq!(shards.iter().filter(|shard| shard.assign(id)).count(), 1);
+//!     assert!(shards[shard_of(id, 3)].assign(id));
+//! }
+//!
+//! // A fourth worker only takes items from the others
+//! assert_eq!(
+//!     moved_items(3, 4, &ids),
+//!     vec![MovedItem {
+//!         id: "invoice-1".to_string(),
+//!         from: 2,
+//!         to: 3,
+//!     }]
+//! );
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+use crate::schema::fnv1a_64;
+
+/// One of the `count` shards of a batch, the share of the items one worker processes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Shard {
+    count: usize,
+    index: usize,
+}
+
+impl Shard {
+    /// Returns the shard `index` of `count` shards.
+    ///
+    /// # Arguments
+    ///
+    /// * `count` - The number of shards, e.g. the number of workers
+    /// * `index` - The shard of this worker, from `0` to `count - 1`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `count` is zero or `index` is not below `count`.
+    pub fn of(count: usize, index: usize) -> Self {
+        assert!(
+            index < count,
+            "shard index {} is out of range for {} shard(s)",
+            index,
+            count
+        );
+
+        Self { count, index }
+    }
+
+    /// Returns the number of shards.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Returns the index of this shard.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns whether the item with this ID belongs to this shard.
+    pub fn assign(&self, item_id: &str) -> bool {
+        shard_of(item_id, self.count) == self.index
+    }
+
+    /// Returns the items of this shard, in their order.
+    ///
+    /// # Arguments
+    ///
+    /// * `items` - The items of the whole batch
+    /// * `item_id` - Returns the ID of an item, e.g. a document ID or the target itself
+    pub fn select<T>(
+        &self,
+        items: impl IntoIterator<Item = T>,
+        item_id: impl Fn(&T) -> &str,
+    ) -> Vec<T> {
+        items
+            .into_iter()
+            .filter(|item| self.assign(item_id(item)))
+            .collect()
+    }
+}
+
+/// An item that belongs to another shard after the number of shards changed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MovedItem {
+    /// The ID of the item.
+    pub id: String,
+    /// The index of its shard before.
+    pub from: usize,
+    /// The index of its shard after.
+    pub to: usize,
+}
+
+/// Returns the index of the shard of an item among `count` shards.
+///
+/// # Arguments
+///
+/// * `item_id` - The ID of the item
+/// * `count` - The number of shards
+///
+/// # Panics
+///
+/// Panics if `count` is zero.
+pub fn shard_of(item_id: &str, count: usize) -> usize {
+    assert!(count > 0, "there must be at least one shard");
+
+    jump_consistent_hash(fnv1a_64(item_id.as_bytes()), count)
+}
+
+/// Returns the items that change shard when the number of shards changes, in their order.
+///
+/// # Arguments
+///
+/// * `old_count` - The number of shards before
+/// * `new_count` - The number of shards after
+/// * `ids` - The IDs of the items
+///
+/// # Panics
+///
+/// Panics if either count is zero.
+pub fn moved_items<S: AsRef<str>>(old_count: usize, new_count: usize, ids: &[S]) -> Vec<MovedItem> {
+    ids.iter()
+        .filter_map(|id| {
+            let id: &str = id.as_ref();
+            let from: usize = shard_of(id, old_count);
+            let to: usize = shard_of(id, new_count);
+
+            (from != to).then(|| MovedItem {
+                id: id.to_string(),
+                from,
+                to,
+            })
+        })
+        .collect()
+}
+
+/// The jump consistent hash of a key among `buckets` buckets, as published by Lamping and
+/// Veach.
+fn jump_consistent_hash(mut key: u64, buckets: usize) -> usize {
+    let mut bucket: i64 = -1;
+    let mut next: i64 = 0;
+    while next < buckets as i64 {
+        bucket = next;
+        key = key.wrapping_mul(2862933555777941757).wrapping_add(1);
+        next = ((bucket + 1) as f64 * ((1u64 << 31) as f64 / ((key >> 33) + 1) as f64)) as i64;
+    }
+
+    bucket as usize
+}
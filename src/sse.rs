@@ -0,0 +1,186 @@
+//! Parsing server-sent events, the format streamed chat completions are sent in.
+//!
+//! `SseParser` reads an event stream as the HTML standard defines it, fed the bytes of a
+//! response in chunks of any size:
+//!
+//! * Lines end with CRLF, LF or a lone CR. A CRLF split between two chunks ends one line.
+//! * Lines starting with `:` are comments and are skipped, such as the `: keep-alive` lines
+//!   gateways send to hold idle connections open.
+//! * The `data:` lines of an event are joined with `\n`, and a blank line ends the event.
+//!   A blank line without data before it dispatches nothing, so runs of blank lines and
+//!   events made only of comments are coalesced away.
+//! * `event:` names the event and `id:` sets its ID. Other fields, such as `retry:`, are
+//!   ignored, and one space after the colon is removed from values.
+//! * A UTF-8 byte order mark at the start of the stream is skipped.
+//!
+//! Unlike the standard, `finish` also dispatches an event whose data was read when the
+//! connection closed, without a blank line after it, since some gateways leave it out of
+//! the last event. The parser never fails: any bytes are a stream, possibly without events.
+//! Data is kept as bytes, to be decoded under the provider's `DecodingPolicy`.
+//!
+//! What the events mean is up to the caller. Streamed chat completions, see the `streaming`
+//! module, skip events named other than `message` or `error`, and data that is not JSON,
+//! such as an empty `data:`. They end at the first `[DONE]`, ignoring any repeated after
+//! it, or when the connection closes without one.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::sse::SseParser;
+//!
+//! let mut parser = SseParser::new();
+//! // A keep-alive comment, CRLF line ends, and an event split between two chunks
+//! let mut events = parser.feed(b": keep-alive\r\n\r\ndata: {\"a\":\r\ndata:");
+//! assert!(events.is_empty());
+//! events.extend(parser.feed(b" 1}\r\n\r\nevent: ping\ndata: {}\n\n"));
+//!
+//! assert_eq!(events.len(), 2);
+//! assert_eq!(events[0].data, b"{\"a\":\n1}");
+//! assert_eq!(events[0].name(), "message");
+//! assert_eq!(events[1].name(), "ping");
+//!
+//! // The last event is dispatched when the connection closes without a blank line
+//! parser.feed(b"data: [DONE]");
+//! assert_eq!(parser.finish().unwrap().data, b"[DONE]");
+//! ```
+
+/// The byte order mark skipped at the start of a stream.
+const BYTE_ORDER_MARK: &[u8] = b"\xEF\xBB\xBF";
+
+/// An event read from a stream.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SseEvent {
+    /// The name set by the event's `event:` field, if any.
+    pub event: Option<String>,
+    /// The values of the event's `data:` lines, joined with `\n`.
+    pub data: Vec<u8>,
+    /// The last ID set by an `id:` field of this event or an earlier one.
+    pub id: Option<String>,
+}
+
+impl SseEvent {
+    /// Returns the name of the event, `message` unless it set another one.
+    pub fn name(&self) -> &str {
+        match self.event.as_deref() {
+            Some(name) if !name.is_empty() => name,
+            _ => "message",
+        }
+    }
+}
+
+/// Reads server-sent events from the bytes of a stream, see the module documentation.
+#[derive(Debug, Clone, Default)]
+pub struct SseParser {
+    line: Vec<u8>,
+    after_cr: bool,
+    started: bool,
+    data: Vec<u8>,
+    has_data: bool,
+    event: Option<String>,
+    id: Option<String>,
+}
+
+impl SseParser {
+    /// Creates a parser at the start of a stream.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the next bytes of the stream.
+    ///
+    /// # Returns
+    ///
+    /// The events completed by the bytes, in order
+    pub fn feed(&mut self, bytes: &[u8]) -> Vec<SseEvent> {
+        let mut events: Vec<SseEvent> = Vec::new();
+        for &byte in bytes {
+            if std::mem::take(&mut self.after_cr) && byte == b'\n' {
+                continue;
+            }
+            match byte {
+                b'\n' => self.end_line(&mut events),
+                b'\r' => {
+                    self.after_cr = true;
+                    self.end_line(&mut events);
+                }
+                _ => self.line.push(byte),
+            }
+        }
+
+        events
+    }
+
+    /// Ends the stream, as when the connection closes.
+    ///
+    /// # Returns
+    ///
+    /// The event whose data was read without a blank line after it, if any
+    pub fn finish(&mut self) -> Option<SseEvent> {
+        if !self.line.is_empty() {
+            let line: Vec<u8> = std::mem::take(&mut self.line);
+            self.read_field(&line);
+        }
+        self.after_cr = false;
+
+        self.dispatch()
+    }
+
+    fn end_line(&mut self, events: &mut Vec<SseEvent>) {
+        let line: Vec<u8> = std::mem::take(&mut self.line);
+        if line.is_empty() {
+            self.started = true;
+            events.extend(self.dispatch());
+        } else {
+            self.read_field(&line);
+        }
+    }
+
+    fn read_field(&mut self, line: &[u8]) {
+        let line: &[u8] = if std::mem::replace(&mut self.started, true) {
+            line
+        } else {
+            line.strip_prefix(BYTE_ORDER_MARK).unwrap_or(line)
+        };
+        if line.is_empty() || line[0] == b':' {
+            return;
+        }
+
+        let (field, value) = match line.iter().position(|&byte| byte == b':') {
+            Some(colon) => {
+                let value: &[u8] = &line[colon + 1..];
+                (&line[..colon], value.strip_prefix(b" ").unwrap_or(value))
+            }
+            None => (line, &[][..]),
+        };
+        match field {
+            b"data" => {
+                if self.has_data {
+                    self.data.push(b'\n');
+                }
+                self.data.extend_from_slice(value);
+                self.has_data = true;
+            }
+            b"event" => self.event = Some(String::from_utf8_lossy(value).into_owned()),
+            // IDs with NUL are ignored, as the standard says
+            b"id" if !value.contains(&0) => {
+                self.id = Some(String::from_utf8_lossy(value).into_owned())
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns the event read since the last blank line, if it has data, and starts the
+    /// next one.
+    fn dispatch(&mut self) -> Option<SseEvent> {
+        let event: Option<String> = self.event.take();
+        if !std::mem::take(&mut self.has_data) {
+            return None;
+        }
+
+        Some(SseEvent {
+            event,
+            data: std::mem::take(&mut self.data),
+            id: self.id.clone(),
+        })
+    }
+}
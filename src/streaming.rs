@@ -0,0 +1,644 @@
+//! Streaming responses and checking them as they arrive.
+//!
+//! With `RequestOptions::with_streaming`, chat completions are requested with
+//! `"stream": true` and read as server-sent events while the model generates them. The answers
+//! of `generate_data_with_options`, `generate_data_adaptive_with_options` and their async
+//! versions are checked against the Task's fields as they stream: an `IncrementalValidator`
+//! follows the JSON and the stream is abandoned, its connection dropped, at the first
+//! top-level key the Task does not have or the first value of a field that starts with the
+//! wrong JSON type. The call fails with `SecretaryError::SchemaViolation` at the key's
+//! pointer, e.g. `/pricee`, so a model that goes off-schema at its 50th token is not read,
+//! and billed, to the end of its answer.
+//!
+//! The validator keeps the same small state whatever the length of the answer: where it is
+//! in the top-level object, the nesting depth of the current value, whether it is inside a
+//! string, and the key being read, of which at most 256 characters are kept. Keys split
+//! across chunks and strings containing braces or escaped quotes are followed exactly. Text
+//! before the first `{`, such as a code fence, is skipped, and checking stops at anything
+//! that is not JSON, leaving it to the parser. Values are judged under the provider's
+//! `LeniencyProfile`, so a `"42"` in a number field passes when the parser would coerce it,
+//! and `null` passes for optional fields and fields with a default value.
+//!
+//! Streams are read with `sse::SseParser`, which skips the keep-alive comments, empty
+//! events and repeated `[DONE]`s some gateways add, see the `sse` module, and assembled into
+//! the response body the request would have returned without streaming, so the rest of the
+//! extraction is unchanged. Providers whose responses are not event streams, such as
+//! Bedrock and the Responses API, are read as usual. Usage is only reported when the
+//! provider streams it, e.g. with
+//! `with_extra_body(json!({"stream_options": {"include_usage": true}}))` for OpenAI.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::Task;
+//! use secretary::leniency::LeniencyProfile;
+//! use secretary::streaming::IncrementalValidator;
+//! use secretary::validation::ViolationKind;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! struct Product {
+//!     #[task(instruction = "Extract the product name")]
+//!     pub name: String,
+//!     #[task(instruction = "Extract the price as a number")]
+//!     pub price: f64,
+//! }
+//!
+//! // Chunks end anywhere, even inside a key, and braces in strings are not structure
+//! let mut validator = IncrementalValidator::new(&Product::field_descriptors(), LeniencyProfile::strict());
+//! validator.feed(r#"{"name": "Lamp {large}", "pri"#).unwrap();
+//! validator.feed(r#"ce": 12.5}"#).unwrap();
+//! assert!(validator.is_complete());
+//!
+//! // An unknown key is reported as soon as it is read
+//! let mut validator = IncrementalValidator::new(&Product::field_descriptors(), LeniencyProfile::strict());
+//! let violation = validator.feed(r#"{"name": "Lamp", "pricee""#).unwrap_err();
+//! assert_eq!(violation.pointer, "/pricee");
+//! assert_eq!(violation.kind, ViolationKind::AdditionalProperty);
+//!
+//! // So is a value that starts with the wrong type
+//! let mut validator = IncrementalValidator::new(&Product::field_descriptors(), LeniencyProfile::strict());
+//! let violation = validator.feed(r#"{"price": "twelve"#).unwrap_err();
+//! assert_eq!(violation.pointer, "/price");
+//! assert_eq!(violation.kind, ViolationKind::Type);
+//!
+//! // Unless the parser would coerce it
+//! let mut validator = IncrementalValidator::new(&Product::field_descriptors(), LeniencyProfile::standard());
+//! assert!(validator.feed(r#"{"price": "12.5"}"#).is_ok());
+//! ```
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+
+use serde_json::{Map, Value, json};
+
+use crate::{
+    SecretaryError,
+    decoding::DecodingPolicy,
+    leniency::LeniencyProfile,
+    limits::OutputLimits,
+    request::RequestOptions,
+    schema::{FieldDescriptor, JsonType},
+    sse::{SseEvent, SseParser},
+    validation::{SchemaViolation, ViolationKind, child_pointer},
+};
+
+/// The most characters of a key the validator keeps; longer keys are reported truncated.
+const MAX_KEY_CHARS: usize = 256;
+
+/// Streaming of a call's responses, set with `RequestOptions::with_streaming`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Streaming {
+    /// The fields the top-level keys of an answer are checked against. The generation
+    /// methods that check answers fill in those of their Task; nothing is checked when empty.
+    pub fields: Vec<FieldDescriptor>,
+}
+
+/// What a top-level key must hold.
+#[derive(Debug, Clone)]
+struct ExpectedField {
+    json_type: JsonType,
+    nullable: bool,
+}
+
+/// Where the validator is in the answer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Position {
+    /// Before the opening brace of the top-level object.
+    BeforeObject,
+    /// Where a key or the closing brace comes next.
+    BeforeKey,
+    /// Inside a key.
+    Key,
+    /// Where the colon after a key comes next.
+    BeforeColon,
+    /// Where the value of a key comes next.
+    BeforeValue,
+    /// Inside a value.
+    Value,
+    /// Where a comma or the closing brace comes next.
+    AfterValue,
+    /// After the top-level object, at anything that is not JSON, or after a violation.
+    Done,
+}
+
+/// An escape sequence being read in a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Escape {
+    None,
+    Backslash,
+    /// A `\u` escape, with the hex digits read so far and their value.
+    Unicode {
+        digits: u8,
+        code: u32,
+    },
+}
+
+/// Checks the top-level keys and value types of a JSON answer against a Task's fields as the
+/// answer arrives in chunks, with constant state, see the module documentation.
+#[derive(Debug, Clone)]
+pub struct IncrementalValidator {
+    fields: HashMap<String, ExpectedField>,
+    leniency: LeniencyProfile,
+    position: Position,
+    key: String,
+    key_chars: usize,
+    escape: Escape,
+    /// The nesting depth inside the current value, 0 for strings and scalars.
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+    complete: bool,
+}
+
+impl IncrementalValidator {
+    /// Creates a validator for the answers of a Task.
+    ///
+    /// # Arguments
+    ///
+    /// * `fields` - The descriptors of the Task's fields; keys are accepted in the Task's
+    ///   `key_case` and as field names
+    /// * `leniency` - The coercions the parser applies, which decide what a value may start
+    ///   with
+    pub fn new(fields: &[FieldDescriptor], leniency: LeniencyProfile) -> Self {
+        let mut expected: HashMap<String, ExpectedField> = HashMap::new();
+        for field in fields {
+            let expected_field: ExpectedField = ExpectedField {
+                json_type: field.json_type,
+                nullable: field.optional || field.default_value.is_some(),
+            };
+            expected.insert(field.key(), expected_field.clone());
+            expected.insert(field.name.clone(), expected_field);
+        }
+
+        Self {
+            fields: expected,
+            leniency,
+            position: Position::BeforeObject,
+            key: String::new(),
+            key_chars: 0,
+            escape: Escape::None,
+            depth: 0,
+            in_string: false,
+            escaped: false,
+            complete: false,
+        }
+    }
+
+    /// Returns whether the top-level object was read to its closing brace.
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+
+    /// Reads the next chunk of the answer.
+    ///
+    /// # Arguments
+    ///
+    /// * `chunk` - The text that follows the chunks read so far
+    ///
+    /// # Errors
+    ///
+    /// Returns the first violation in the chunk: an `AdditionalProperty` for a top-level
+    /// key the Task does not have, or a `Type` for a value of a field that starts with a
+    /// JSON type the field cannot take. Nothing is checked after a violation.
+    pub fn feed(&mut self, chunk: &str) -> Result<(), SchemaViolation> {
+        for character in chunk.chars() {
+            if self.position == Position::Done {
+                break;
+            }
+            self.read(character)?;
+        }
+
+        Ok(())
+    }
+
+    fn read(&mut self, character: char) -> Result<(), SchemaViolation> {
+        match self.position {
+            Position::BeforeObject => {
+                if character == '{' {
+                    self.position = Position::BeforeKey;
+                }
+            }
+            Position::BeforeKey => match character {
+                '"' => {
+                    self.key.clear();
+                    self.key_chars = 0;
+                    self.escape = Escape::None;
+                    self.position = Position::Key;
+                }
+                '}' => self.complete(),
+                character if character.is_whitespace() => {}
+                _ => self.stop(),
+            },
+            Position::Key => return self.read_key(character),
+            Position::BeforeColon => match character {
+                ':' => self.position = Position::BeforeValue,
+                character if character.is_whitespace() => {}
+                _ => self.stop(),
+            },
+            Position::BeforeValue => {
+                if !character.is_whitespace() {
+                    return self.start_value(character);
+                }
+            }
+            Position::Value => self.read_value(character),
+            Position::AfterValue => match character {
+                ',' => self.position = Position::BeforeKey,
+                '}' => self.complete(),
+                character if character.is_whitespace() => {}
+                _ => self.stop(),
+            },
+            Position::Done => {}
+        }
+
+        Ok(())
+    }
+
+    fn read_key(&mut self, character: char) -> Result<(), SchemaViolation> {
+        let decoded: Option<char> = match self.escape {
+            Escape::None => match character {
+                '\\' => {
+                    self.escape = Escape::Backslash;
+                    None
+                }
+                '"' => return self.end_key(),
+                character => Some(character),
+            },
+            Escape::Backslash => {
+                self.escape = Escape::None;
+                match character {
+                    'u' => {
+                        self.escape = Escape::Unicode { digits: 0, code: 0 };
+                        None
+                    }
+                    'n' => Some('\n'),
+                    't' => Some('\t'),
+                    'r' => Some('\r'),
+                    'b' => Some('\u{8}'),
+                    'f' => Some('\u{c}'),
+                    character => Some(character),
+                }
+            }
+            Escape::Unicode { digits, code } => {
+                let Some(digit) = character.to_digit(16) else {
+                    self.stop();
+                    return Ok(());
+                };
+                let code: u32 = code * 16 + digit;
+                if digits < 3 {
+                    self.escape = Escape::Unicode {
+                        digits: digits + 1,
+                        code,
+                    };
+                    None
+                } else {
+                    self.escape = Escape::None;
+                    Some(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER))
+                }
+            }
+        };
+
+        if let Some(character) = decoded {
+            self.key_chars += 1;
+            if self.key_chars <= MAX_KEY_CHARS {
+                self.key.push(character);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn end_key(&mut self) -> Result<(), SchemaViolation> {
+        if !self.fields.contains_key(&self.key) {
+            self.stop();
+            return Err(SchemaViolation {
+                pointer: child_pointer("", &self.key),
+                kind: ViolationKind::AdditionalProperty,
+                message: format!("The key `{}` is not defined by the schema", self.key),
+            });
+        }
+
+        self.position = Position::BeforeColon;
+        Ok(())
+    }
+
+    fn start_value(&mut self, character: char) -> Result<(), SchemaViolation> {
+        let found: JsonType = match character {
+            '"' => JsonType::String,
+            '{' => JsonType::Object,
+            '[' => JsonType::Array,
+            't' | 'f' => JsonType::Boolean,
+            'n' => JsonType::Null,
+            '-' | '0'..='9' => JsonType::Number,
+            _ => {
+                self.stop();
+                return Ok(());
+            }
+        };
+
+        let Some(field) = self.fields.get(&self.key) else {
+            self.stop();
+            return Ok(());
+        };
+        if !self.accepts(field, found) {
+            let expected: JsonType = field.json_type;
+            self.stop();
+            return Err(SchemaViolation {
+                pointer: child_pointer("", &self.key),
+                kind: ViolationKind::Type,
+                message: format!(
+                    "The value of `{}` has type {}, but the field expects type {}",
+                    self.key, found, expected
+                ),
+            });
+        }
+
+        self.position = Position::Value;
+        self.in_string = character == '"';
+        self.escaped = false;
+        self.depth = usize::from(matches!(character, '{' | '['));
+
+        Ok(())
+    }
+
+    fn read_value(&mut self, character: char) {
+        if self.in_string {
+            if self.escaped {
+                self.escaped = false;
+            } else if character == '\\' {
+                self.escaped = true;
+            } else if character == '"' {
+                self.in_string = false;
+                if self.depth == 0 {
+                    self.position = Position::AfterValue;
+                }
+            }
+            return;
+        }
+
+        // A scalar ends at the first character that is not part of it
+        if self.depth == 0 {
+            match character {
+                ',' => self.position = Position::BeforeKey,
+                '}' => self.complete(),
+                character if character.is_whitespace() => self.position = Position::AfterValue,
+                _ => {}
+            }
+            return;
+        }
+
+        match character {
+            '"' => self.in_string = true,
+            '{' | '[' => self.depth += 1,
+            '}' | ']' => {
+                self.depth -= 1;
+                if self.depth == 0 {
+                    self.position = Position::AfterValue;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns whether a value starting with the given type can become the field's value.
+    fn accepts(&self, field: &ExpectedField, found: JsonType) -> bool {
+        if field.json_type == JsonType::Any || field.json_type == found {
+            return true;
+        }
+
+        let scalar_in_array: bool = field.json_type == JsonType::Array
+            && self.leniency.scalars_to_arrays
+            && matches!(
+                found,
+                JsonType::String | JsonType::Number | JsonType::Boolean
+            );
+        match found {
+            JsonType::Null => field.nullable,
+            JsonType::String => {
+                scalar_in_array
+                    || (field.json_type == JsonType::Number && self.leniency.numbers_from_strings)
+                    || (field.json_type == JsonType::Boolean && self.leniency.booleans_from_strings)
+                    || (field.nullable && self.leniency.null_strings)
+            }
+            _ => scalar_in_array,
+        }
+    }
+
+    fn complete(&mut self) {
+        self.position = Position::Done;
+        self.complete = true;
+    }
+
+    fn stop(&mut self) {
+        self.position = Position::Done;
+    }
+}
+
+/// A choice of a streamed chat completion, as far as it was read.
+#[derive(Debug)]
+struct StreamedChoice {
+    content: String,
+    reasoning: String,
+    finish_reason: Value,
+    validator: Option<IncrementalValidator>,
+}
+
+/// Reads a chat completion streamed as server-sent events, checking the content of each
+/// choice as it arrives, and assembles the body the request would have returned without
+/// streaming.
+///
+/// Fed the bytes of the response as they are received, so that the caller can drop the
+/// connection as soon as a chunk fails.
+#[derive(Debug)]
+pub(crate) struct EventStream {
+    validator: Option<IncrementalValidator>,
+    limits: OutputLimits,
+    decoding: DecodingPolicy,
+    parser: SseParser,
+    received_bytes: usize,
+    repaired_sequences: usize,
+    done: bool,
+    metadata: Map<String, Value>,
+    choices: BTreeMap<u64, StreamedChoice>,
+    usage: Option<Value>,
+    error: Option<Value>,
+}
+
+impl EventStream {
+    pub(crate) fn new(
+        streaming: &Streaming,
+        leniency: LeniencyProfile,
+        limits: OutputLimits,
+        decoding: DecodingPolicy,
+    ) -> Self {
+        Self {
+            validator: (!streaming.fields.is_empty())
+                .then(|| IncrementalValidator::new(&streaming.fields, leniency)),
+            limits,
+            decoding,
+            parser: SseParser::new(),
+            received_bytes: 0,
+            repaired_sequences: 0,
+            done: false,
+            metadata: Map::new(),
+            choices: BTreeMap::new(),
+            usage: None,
+            error: None,
+        }
+    }
+
+    /// Reads the next bytes of the response.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SecretaryError::SchemaViolation` when the content of a choice departs from
+    /// the Task's fields, `SecretaryError::OutputLimitExceeded` when the response grows past
+    /// `max_response_bytes`, or the decoding error of a line under `DecodingPolicy::Strict`.
+    pub(crate) fn feed(&mut self, bytes: &[u8]) -> Result<(), SecretaryError> {
+        self.received_bytes += bytes.len();
+        self.limits.check_response_bytes(self.received_bytes)?;
+
+        for event in self.parser.feed(bytes) {
+            self.read_sse_event(event)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the assembled response body, with the number of sequences repaired while
+    /// decoding it. A streamed error is returned as it was sent.
+    pub(crate) fn finish(mut self) -> Result<(String, usize), SecretaryError> {
+        if let Some(event) = self.parser.finish() {
+            self.read_sse_event(event)?;
+        }
+        if let Some(error) = self.error {
+            return Ok((error.to_string(), self.repaired_sequences));
+        }
+
+        let choices: Vec<Value> = self
+            .choices
+            .into_iter()
+            .map(|(index, choice)| {
+                let mut message: Value = json!({"role": "assistant", "content": choice.content});
+                if !choice.reasoning.is_empty() {
+                    message["reasoning_content"] = Value::String(choice.reasoning);
+                }
+                json!({"index": index, "message": message, "finish_reason": choice.finish_reason})
+            })
+            .collect();
+        let mut body: Map<String, Value> = self.metadata;
+        body.insert("object".to_string(), json!("chat.completion"));
+        body.insert("choices".to_string(), Value::Array(choices));
+        if let Some(usage) = self.usage {
+            body.insert("usage".to_string(), usage);
+        }
+
+        Ok((Value::Object(body).to_string(), self.repaired_sequences))
+    }
+
+    fn read_sse_event(&mut self, event: SseEvent) -> Result<(), SecretaryError> {
+        if self.done || !matches!(event.name(), "message" | "error") {
+            return Ok(());
+        }
+
+        let (data, repaired_sequences) = self.decoding.decode_body(&event.data)?;
+        self.repaired_sequences += repaired_sequences;
+        if let Ok(event) = serde_json::from_str::<Value>(&data) {
+            return self.read_event(event);
+        }
+
+        // Each line on its own, for servers that leave out the blank lines between events
+        for line in data.lines().map(str::trim) {
+            if line == "[DONE]" {
+                self.done = true;
+                return Ok(());
+            }
+            if let Ok(event) = serde_json::from_str::<Value>(line) {
+                self.read_event(event)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read_event(&mut self, event: Value) -> Result<(), SecretaryError> {
+        if event.get("error").is_some() {
+            self.error = Some(event);
+            return Ok(());
+        }
+
+        for key in ["id", "created", "model", "system_fingerprint"] {
+            if let Some(value) = event.get(key).filter(|value| !value.is_null()) {
+                self.metadata.insert(key.to_string(), value.clone());
+            }
+        }
+        if let Some(usage) = event.get("usage").filter(|usage| !usage.is_null()) {
+            self.usage = Some(usage.clone());
+        }
+
+        for choice in event["choices"].as_array().into_iter().flatten() {
+            let index: u64 = choice["index"].as_u64().unwrap_or(0);
+            let validator: &Option<IncrementalValidator> = &self.validator;
+            let streamed: &mut StreamedChoice =
+                self.choices.entry(index).or_insert_with(|| StreamedChoice {
+                    content: String::new(),
+                    reasoning: String::new(),
+                    finish_reason: Value::Null,
+                    validator: validator.clone(),
+                });
+            if let Some(finish_reason) = choice
+                .get("finish_reason")
+                .filter(|reason| !reason.is_null())
+            {
+                streamed.finish_reason = finish_reason.clone();
+            }
+
+            let delta: &Value = &choice["delta"];
+            if let Some(reasoning) = delta["reasoning_content"]
+                .as_str()
+                .or_else(|| delta["reasoning"].as_str())
+            {
+                streamed.reasoning.push_str(reasoning);
+            }
+            let Some(content) = delta["content"].as_str() else {
+                continue;
+            };
+            streamed.content.push_str(content);
+            if let Some(validator) = &mut streamed.validator
+                && let Err(violation) = validator.feed(content)
+            {
+                return Err(SecretaryError::SchemaViolation {
+                    violations: vec![violation],
+                    raw_content: streamed.content.clone(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns whether a response is an event stream, from its headers.
+pub(crate) fn is_event_stream(headers: &BTreeMap<String, String>) -> bool {
+    headers
+        .get("content-type")
+        .is_some_and(|content_type| content_type.starts_with("text/event-stream"))
+}
+
+/// Returns the options of a request for a whole Task: when streaming without fields of its
+/// own, the options with the Task's fields, so that its answer is checked as it arrives.
+pub(crate) fn with_stream_fields<'a>(
+    options: &'a RequestOptions,
+    fields: &[FieldDescriptor],
+) -> Cow<'a, RequestOptions> {
+    match &options.streaming {
+        Some(streaming) if streaming.fields.is_empty() && options.response_format.is_json() => {
+            let mut options: RequestOptions = options.clone();
+            options.streaming = Some(Streaming {
+                fields: fields.to_vec(),
+            });
+            Cow::Owned(options)
+        }
+        _ => Cow::Borrowed(options),
+    }
+}
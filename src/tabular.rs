@@ -0,0 +1,495 @@
+//! Extracting tables into one Task per row.
+//!
+//! Inputs such as markdown tables, aligned text columns or CSV pasted into an email are
+//! extracted with `extract_table`, which tells the model that the target contains a table
+//! and asks for `{"rows": [...]}` with one object per data row following the Row schema.
+//! Header rows are used to map columns and are not emitted, merged cells are applied to each
+//! row they span, and footnote and total rows are skipped unless
+//! `TableOptions::with_footnotes` asks for them.
+//!
+//! Before sending, markdown tables are normalized locally with `normalize_markdown_tables`
+//! to save tokens. Tables longer than `TableOptions::chunk_size` are split between rows with
+//! `split_table`, repeating the header line at the start of every chunk, and the chunks are
+//! extracted concurrently. Cells missing from a row are left at the Row's defaults.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use secretary::Task;
+//! use secretary::llm_providers::openai::OpenAILLM;
+//! use secretary::tabular::{TableOptions, async_extract_table, extract_table};
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug, PartialEq)]
+//! struct LineItem {
+//!     #[task(instruction = "The item name")]
+//!     pub item: String,
+//!     #[task(instruction = "The quantity as a number")]
+//!     pub quantity: u32,
+//!     #[task(instruction = "The unit, if given")]
+//!     pub unit: Option<String>,
+//! }
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+//! let llm = OpenAILLM::new("https://api.openai.com/v1", "your-api-key", "gpt-4o-mini")?;
+//!
+//! // A markdown table is normalized before it is sent
+//! let table = "Order 1042:\n\n\
+//!              | Item   |  Quantity |\n\
+//!              |:-------|----------:|\n\
+//!              | Bolts  |        20 |\n\
+//!              | Nuts   |         5 |";
+//! let rows: Vec<LineItem> = extract_table(&llm, table, &vec![], &TableOptions::default())?;
+//! println!("{:?}", rows);
+//!
+//! // A long table is split between rows, with the header repeated in every chunk
+//! let long_table = std::fs::read_to_string("parts.txt")?;
+//! let options = TableOptions::default().with_chunk_size(1000).with_concurrency(3);
+//! let rows: Vec<LineItem> = tokio::runtime::Runtime::new()?
+//!     .block_on(async_extract_table(&llm, &long_table, &vec![], &options))?;
+//! println!("{} rows", rows.len());
+//! # Ok(())
+//! # }
+//! ```
+
+use futures::{StreamExt, stream};
+use serde_json::Value;
+
+use crate::{
+    SecretaryError,
+    defaults::apply_default_values,
+    message::Message,
+    partial::deserialize_partial,
+    traits::{
+        AsyncGenerateData, GenerateData, IsLLM, Task, extract_json_content, limit_content,
+        send_chunk_requests,
+    },
+    utilities::format_additional_instructions,
+};
+
+/// Explains the table to the model, followed by the footnote rule and the Row schema.
+const TABLE_INSTRUCTION: &str = r#"The text contains a table. Extract every data row of the table as a JSON object with the fields described below, and answer with a single JSON object of the form {"rows": [...]} holding one object per data row, in the order the rows appear.
+- Header rows name the columns. Use them to map columns to fields and do not emit them as rows, including headers repeated further down.
+- A merged cell spanning several rows or columns applies to every row it spans.
+- When a row has no cell for a field, leave the field at its template value.
+- If the table has no data rows, answer {"rows": []}."#;
+
+/// The footnote rule when footnote rows are skipped.
+const SKIP_FOOTNOTES: &str =
+    "- Footnotes, notes and summary rows such as totals are not data rows. Skip them.";
+
+/// The footnote rule when footnote rows are included.
+const INCLUDE_FOOTNOTES: &str = "- Footnotes, notes and summary rows such as totals are emitted as rows too, with the fields they do not fill left at their template values.";
+
+/// Settings for `extract_table`.
+///
+/// Sizes are measured in characters, roughly four per token for English text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TableOptions {
+    /// Whether footnote and total rows are emitted as rows.
+    pub include_footnotes: bool,
+    /// Whether markdown tables are normalized before sending.
+    pub normalize_markdown: bool,
+    /// The maximum number of characters of the table sent in one request.
+    pub chunk_size: usize,
+    /// The maximum number of chunk requests in flight at once.
+    pub concurrency: usize,
+}
+
+impl Default for TableOptions {
+    fn default() -> Self {
+        Self {
+            include_footnotes: false,
+            normalize_markdown: true,
+            chunk_size: 32_000,
+            concurrency: 4,
+        }
+    }
+}
+
+impl TableOptions {
+    /// Sets whether footnote and total rows are emitted as rows.
+    pub fn with_footnotes(mut self, include_footnotes: bool) -> Self {
+        self.include_footnotes = include_footnotes;
+        self
+    }
+
+    /// Sets whether markdown tables are normalized before sending.
+    pub fn with_markdown_normalization(mut self, normalize_markdown: bool) -> Self {
+        self.normalize_markdown = normalize_markdown;
+        self
+    }
+
+    /// Sets the maximum number of characters of the table sent in one request.
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// Sets the maximum number of chunk requests in flight at once.
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+}
+
+/// Extracts the data rows of a table in the target.
+///
+/// # Arguments
+///
+/// * `llm` - The provider to extract with
+/// * `target` - The text containing the table
+/// * `additional_instructions` - Extra instructions to guide the extraction process
+/// * `options` - Footnote handling, normalization and chunking
+///
+/// # Returns
+///
+/// One Row per data row in table order, empty when the table has none
+///
+/// # Errors
+///
+/// Returns the first error of the chunk requests, `SecretaryError::JsonParsingError` if an
+/// answer is not a JSON object with a `rows` array, or
+/// `SecretaryError::FieldDeserializationError` if a row does not deserialize even with its
+/// failing cells left at their defaults.
+pub fn extract_table<L, Row>(
+    llm: &L,
+    target: &str,
+    additional_instructions: &[String],
+    options: &TableOptions,
+) -> Result<Vec<Row>, Box<dyn std::error::Error + Send + Sync + 'static>>
+where
+    L: GenerateData,
+    Row: Task,
+{
+    let requests: Vec<Vec<Message>> = make_table_requests::<Row>(
+        &prepare_table(target, options),
+        additional_instructions,
+        options,
+    );
+    if requests.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut rows: Vec<Row> = Vec::new();
+    for content in send_chunk_requests(llm, requests, options.concurrency, None)? {
+        let (content, _) = limit_content(llm, &Default::default(), content)?;
+        rows.extend(parse_rows::<L, Row>(llm, &content)?);
+    }
+
+    Ok(rows)
+}
+
+/// Asynchronously extracts the data rows of a table in the target.
+///
+/// This is the async version of `extract_table`.
+pub async fn async_extract_table<L, Row>(
+    llm: &L,
+    target: &str,
+    additional_instructions: &[String],
+    options: &TableOptions,
+) -> Result<Vec<Row>, Box<dyn std::error::Error + Send + Sync + 'static>>
+where
+    L: AsyncGenerateData + Sync,
+    Row: Task,
+{
+    let requests: Vec<Vec<Message>> = make_table_requests::<Row>(
+        &prepare_table(target, options),
+        additional_instructions,
+        options,
+    );
+
+    let request_options = Default::default();
+    let responses: Vec<Result<String, Box<dyn std::error::Error + Send + Sync + 'static>>> =
+        stream::iter(requests)
+            .map(|messages| llm.async_send_messages_with_options(messages, true, &request_options))
+            .buffered(options.concurrency.max(1))
+            .collect()
+            .await;
+
+    let mut rows: Vec<Row> = Vec::new();
+    for response in responses {
+        let (content, _) = limit_content(
+            llm,
+            &request_options,
+            extract_json_content(llm, &response?)?,
+        )?;
+        rows.extend(parse_rows::<L, Row>(llm, &content)?);
+    }
+
+    Ok(rows)
+}
+
+/// Normalizes the markdown tables in a text to save tokens.
+///
+/// A markdown table is a run of lines containing `|` whose second line is an alignment row
+/// such as `|:---|--:|`. The alignment rows are removed, the outer pipes are stripped, and the
+/// whitespace inside each cell is collapsed, so each row becomes `cell | cell | cell`.
+/// Escaped pipes (`\|`) stay inside their cell. Everything outside tables is left unchanged.
+///
+/// # Examples
+///
+/// ```rust
+/// use secretary::tabular::normalize_markdown_tables;
+///
+/// let text = "Prices:\n\
+///             \n\
+///             | Product      |   Price |\n\
+///             | :----------- | ------: |\n\
+///             | Widget   A   |   $1.00 |\n\
+///             | Gadget \\| B |  $12.50 |\n\
+///             \n\
+///             Prices exclude tax | see terms";
+/// assert_eq!(
+///     normalize_markdown_tables(text),
+///     "Prices:\n\nProduct | Price\nWidget A | $1.00\nGadget \\| B | $12.50\n\nPrices exclude tax | see terms"
+/// );
+///
+/// // Empty cells are kept so columns stay aligned
+/// assert_eq!(normalize_markdown_tables("|a|b|c|\n|-|-|-|\n|1||3|"), "a | b | c\n1 |  | 3");
+///
+/// // Pipes without an alignment row are not a table
+/// assert_eq!(normalize_markdown_tables("a | b\nc | d"), "a | b\nc | d");
+/// assert_eq!(normalize_markdown_tables(""), "");
+/// ```
+pub fn normalize_markdown_tables(text: &str) -> String {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut output: Vec<String> = Vec::with_capacity(lines.len());
+
+    let mut index: usize = 0;
+    while index < lines.len() {
+        let starts_table: bool = is_table_line(lines[index])
+            && lines
+                .get(index + 1)
+                .is_some_and(|line| is_alignment_row(line));
+        if !starts_table {
+            output.push(lines[index].to_string());
+            index += 1;
+            continue;
+        }
+
+        output.push(normalize_row(lines[index]));
+        index += 2;
+        while index < lines.len() && is_table_line(lines[index]) {
+            if !is_alignment_row(lines[index]) {
+                output.push(normalize_row(lines[index]));
+            }
+            index += 1;
+        }
+    }
+
+    let mut normalized: String = output.join("\n");
+    if text.ends_with('\n') {
+        normalized.push('\n');
+    }
+
+    normalized
+}
+
+/// Splits a table into chunks of whole lines of at most `chunk_size` characters.
+///
+/// Every chunk after the first starts with the table's header line: the first line that
+/// shares its number of `|`, tab or `,` separators with the next line, or the first line
+/// when no such pair exists. Lines are never split, so a single line longer than
+/// `chunk_size` makes a longer chunk.
+///
+/// # Examples
+///
+/// ```rust
+/// use secretary::tabular::split_table;
+///
+/// let table = "Shipment list\nsku,qty\nA-1,5\nB-2,7\nC-3,9";
+/// assert_eq!(split_table(table, 100), vec![table]);
+/// assert_eq!(
+///     split_table(table, 26),
+///     vec!["Shipment list\nsku,qty\nA-1,5", "sku,qty\nB-2,7\nC-3,9"]
+/// );
+/// ```
+pub fn split_table(text: &str, chunk_size: usize) -> Vec<String> {
+    if text.chars().count() <= chunk_size {
+        return vec![text.to_string()];
+    }
+
+    let lines: Vec<&str> = text.lines().collect();
+    let header_index: usize = find_header_line(&lines);
+    let header: &str = lines.get(header_index).copied().unwrap_or_default();
+
+    let mut chunks: Vec<String> = Vec::new();
+    let mut current: String = String::new();
+    let mut current_length: usize = 0;
+    for (index, line) in lines.iter().enumerate() {
+        let line_length: usize = line.chars().count();
+        if index > header_index + 1
+            && !current.is_empty()
+            && current_length + 1 + line_length > chunk_size
+        {
+            chunks.push(std::mem::replace(&mut current, header.to_string()));
+            current_length = header.chars().count();
+        }
+        if !current.is_empty() {
+            current.push('\n');
+            current_length += 1;
+        }
+        current.push_str(line);
+        current_length += line_length;
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+
+    chunks
+}
+
+/// Normalizes the target when asked to.
+fn prepare_table(target: &str, options: &TableOptions) -> String {
+    if options.normalize_markdown {
+        normalize_markdown_tables(target)
+    } else {
+        target.to_string()
+    }
+}
+
+/// Creates one request per chunk of the table, none for blank text.
+fn make_table_requests<Row: Task>(
+    table: &str,
+    additional_instructions: &[String],
+    options: &TableOptions,
+) -> Vec<Vec<Message>> {
+    if table.trim().is_empty() {
+        return Vec::new();
+    }
+
+    let footnotes: &str = if options.include_footnotes {
+        INCLUDE_FOOTNOTES
+    } else {
+        SKIP_FOOTNOTES
+    };
+    let prefix: Message = Message::system(format!(
+        "{}\n{}\n\nRow fields:\n{}{}",
+        TABLE_INSTRUCTION,
+        footnotes,
+        Row::default().get_system_prompt(),
+        format_additional_instructions(additional_instructions)
+    ));
+
+    split_table(table, options.chunk_size)
+        .into_iter()
+        .map(|chunk| {
+            vec![
+                prefix.clone(),
+                Message::user(format!("This is the text containing the table:\n{}", chunk)),
+            ]
+        })
+        .collect()
+}
+
+/// Parses the rows of an answer, leaving cells that are missing or fail at their defaults.
+fn parse_rows<L: IsLLM + ?Sized, Row: Task>(
+    llm: &L,
+    content: &str,
+) -> Result<Vec<Row>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let parsing_error = |message: String| SecretaryError::JsonParsingError {
+        message,
+        raw_content: content.to_string(),
+    };
+
+    let value: Value =
+        serde_json::from_str(content).map_err(|error| parsing_error(error.to_string()))?;
+    let rows: Vec<Value> = match value {
+        Value::Object(mut map) => match map.remove("rows") {
+            Some(Value::Array(rows)) => rows,
+            _ => {
+                return Err(Box::new(parsing_error(
+                    "Expected an object with a `rows` array".to_string(),
+                )));
+            }
+        },
+        Value::Array(rows) => rows,
+        _ => {
+            return Err(Box::new(parsing_error(
+                "Expected an object with a `rows` array".to_string(),
+            )));
+        }
+    };
+
+    let mut parsed: Vec<Row> = Vec::with_capacity(rows.len());
+    for mut row in rows {
+        llm.get_leniency().apply::<Row>(&mut row);
+        apply_default_values::<Row>(&Row::field_descriptors(), &mut row);
+        parsed.push(
+            deserialize_partial::<Row>(&row)
+                .map_err(SecretaryError::FieldDeserializationError)?
+                .data,
+        );
+    }
+
+    Ok(parsed)
+}
+
+/// Returns whether a line can belong to a markdown table.
+fn is_table_line(line: &str) -> bool {
+    line.contains('|')
+}
+
+/// Returns whether a line is a markdown alignment row such as `|:---|--:|`.
+fn is_alignment_row(line: &str) -> bool {
+    let cells: Vec<String> = split_cells(line);
+    !cells.is_empty()
+        && cells.iter().all(|cell| {
+            let dashes: &str = cell.trim_start_matches(':').trim_end_matches(':');
+            !dashes.is_empty() && dashes.chars().all(|character| character == '-')
+        })
+}
+
+/// Rewrites a markdown table row as its collapsed cells joined by ` | `.
+fn normalize_row(line: &str) -> String {
+    split_cells(line).join(" | ")
+}
+
+/// Splits a markdown table row at unescaped pipes, collapsing the whitespace in each cell.
+fn split_cells(line: &str) -> Vec<String> {
+    let trimmed: &str = line.trim();
+    let trimmed: &str = trimmed.strip_prefix('|').unwrap_or(trimmed);
+    let trimmed: &str = match trimmed.strip_suffix('|') {
+        Some(inner) if !inner.ends_with('\\') => inner,
+        _ => trimmed,
+    };
+
+    let mut cells: Vec<String> = Vec::new();
+    let mut cell: String = String::new();
+    let mut escaped: bool = false;
+    for character in trimmed.chars() {
+        if character == '|' && !escaped {
+            cells.push(collapse_whitespace(&cell));
+            cell.clear();
+        } else {
+            cell.push(character);
+        }
+        escaped = character == '\\' && !escaped;
+    }
+    cells.push(collapse_whitespace(&cell));
+
+    cells
+}
+
+/// Replaces every run of whitespace with a single space and trims the ends.
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+/// Finds the header line of a table, see `split_table`.
+fn find_header_line(lines: &[&str]) -> usize {
+    for (index, pair) in lines.windows(2).enumerate() {
+        for separator in ['|', '\t', ','] {
+            let count: usize = pair[0].matches(separator).count();
+            if count > 0 && pair[1].matches(separator).count() == count {
+                return index;
+            }
+        }
+    }
+
+    lines
+        .iter()
+        .position(|line| !line.trim().is_empty())
+        .unwrap_or(0)
+}
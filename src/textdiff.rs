@@ -0,0 +1,228 @@
+//! Line-based comparison of two versions of a text.
+//!
+//! `diff_lines` finds the lines removed from and added to a text as a list of `Hunk`s, from
+//! the longest common subsequence of lines. `TextDiff::changed_ratio` measures how much of
+//! the text changed and `TextDiff::excerpts` renders the changes with the lines around them,
+//! which is what incremental re-extraction sends instead of the whole text, see the
+//! `incremental` module.
+//!
+//! Lines are compared exactly. Very large changed regions, beyond a few million pairs of
+//! lines, are reported as a single hunk instead of being aligned line by line.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::textdiff::diff_lines;
+//!
+//! let old = "Invoice INV-7\nVendor: Acme\nTotal: 120.50 EUR\nThank you";
+//! let new = "Invoice INV-7\nVendor: Acme\nTotal: 99.00 EUR\nThank you";
+//! let diff = diff_lines(old, new);
+//!
+//! assert_eq!(diff.hunks().len(), 1);
+//! assert_eq!(diff.hunks()[0].removed, vec!["Total: 120.50 EUR"]);
+//! assert_eq!(diff.hunks()[0].added, vec!["Total: 99.00 EUR"]);
+//! // 2 of the 8 lines of both versions were removed or added
+//! assert_eq!(diff.changed_ratio(), 0.25);
+//! assert_eq!(
+//!     diff.excerpts(1),
+//!     "@@ line 2 @@\n  Vendor: Acme\n- Total: 120.50 EUR\n+ Total: 99.00 EUR\n  Thank you\n"
+//! );
+//! assert!(diff_lines(old, old).is_unchanged());
+//! ```
+
+/// The largest number of line pairs aligned line by line.
+const MAX_ALIGNED_PAIRS: usize = 4_000_000;
+
+/// A run of lines removed from the old text and replaced by lines of the new text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+    /// The index of the first removed line in the old text, or where lines were added.
+    pub old_start: usize,
+    /// The lines removed from the old text.
+    pub removed: Vec<String>,
+    /// The index of the first added line in the new text, or where lines were removed.
+    pub new_start: usize,
+    /// The lines added to the new text.
+    pub added: Vec<String>,
+}
+
+impl Hunk {
+    /// Returns the index of the first line after the hunk in the new text.
+    fn new_end(&self) -> usize {
+        self.new_start + self.added.len()
+    }
+}
+
+/// The changes between two versions of a text, see `diff_lines`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextDiff {
+    old_line_count: usize,
+    new_lines: Vec<String>,
+    hunks: Vec<Hunk>,
+}
+
+impl TextDiff {
+    /// Returns the changes in the order of the text.
+    pub fn hunks(&self) -> &[Hunk] {
+        &self.hunks
+    }
+
+    /// Returns whether both versions have the same lines.
+    pub fn is_unchanged(&self) -> bool {
+        self.hunks.is_empty()
+    }
+
+    /// Returns the share of the lines of both versions that were removed or added, from 0
+    /// for the same text to 1 for a text with no line in common.
+    pub fn changed_ratio(&self) -> f64 {
+        let total: usize = self.old_line_count + self.new_lines.len();
+        if total == 0 {
+            return 0.0;
+        }
+        let changed: usize = self
+            .hunks
+            .iter()
+            .map(|hunk| hunk.removed.len() + hunk.added.len())
+            .sum();
+
+        changed as f64 / total as f64
+    }
+
+    /// Renders the changes with up to `context` unchanged lines of the new text around them.
+    ///
+    /// Every excerpt starts with `@@ line N @@`, N being the 1-based number of its first line
+    /// in the new text, followed by its lines prefixed with two spaces when unchanged, `- `
+    /// when removed and `+ ` when added. Changes closer than twice the context share an
+    /// excerpt.
+    pub fn excerpts(&self, context: usize) -> String {
+        let mut rendered: String = String::new();
+        let mut index: usize = 0;
+        while index < self.hunks.len() {
+            // Gather the hunks whose context overlaps into one excerpt
+            let mut last: usize = index;
+            while last + 1 < self.hunks.len()
+                && self.hunks[last + 1].new_start <= self.hunks[last].new_end() + 2 * context
+            {
+                last += 1;
+            }
+
+            let start: usize = self.hunks[index].new_start.saturating_sub(context);
+            let end: usize = (self.hunks[last].new_end() + context).min(self.new_lines.len());
+            rendered.push_str(&format!("@@ line {} @@\n", start + 1));
+            let mut cursor: usize = start;
+            for hunk in &self.hunks[index..=last] {
+                self.push_unchanged(&mut rendered, cursor, hunk.new_start);
+                for line in &hunk.removed {
+                    rendered.push_str(&format!("- {}\n", line));
+                }
+                for line in &hunk.added {
+                    rendered.push_str(&format!("+ {}\n", line));
+                }
+                cursor = hunk.new_end();
+            }
+            self.push_unchanged(&mut rendered, cursor, end);
+
+            index = last + 1;
+        }
+
+        rendered
+    }
+
+    /// Appends the unchanged lines of the new text from `start` to `end`.
+    fn push_unchanged(&self, rendered: &mut String, start: usize, end: usize) {
+        for line in &self.new_lines[start..end] {
+            rendered.push_str(&format!("  {}\n", line));
+        }
+    }
+}
+
+/// Compares two versions of a text line by line.
+///
+/// # Arguments
+///
+/// * `old` - The previous version of the text
+/// * `new` - The current version of the text
+pub fn diff_lines(old: &str, new: &str) -> TextDiff {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+
+    // Only the lines between the common prefix and the common suffix need aligning
+    let prefix: usize = old_lines
+        .iter()
+        .zip(&new_lines)
+        .take_while(|(old_line, new_line)| old_line == new_line)
+        .count();
+    let suffix: usize = old_lines[prefix..]
+        .iter()
+        .rev()
+        .zip(new_lines[prefix..].iter().rev())
+        .take_while(|(old_line, new_line)| old_line == new_line)
+        .count();
+    let old_middle: &[&str] = &old_lines[prefix..old_lines.len() - suffix];
+    let new_middle: &[&str] = &new_lines[prefix..new_lines.len() - suffix];
+
+    let mut hunks: Vec<Hunk> = Vec::new();
+    if old_middle.len().saturating_mul(new_middle.len()) > MAX_ALIGNED_PAIRS {
+        hunks.push(Hunk {
+            old_start: prefix,
+            removed: to_strings(old_middle),
+            new_start: prefix,
+            added: to_strings(new_middle),
+        });
+    } else {
+        align(old_middle, new_middle, prefix, &mut hunks);
+    }
+
+    TextDiff {
+        old_line_count: old_lines.len(),
+        new_lines: to_strings(&new_lines),
+        hunks,
+    }
+}
+
+/// Aligns two runs of lines on their longest common subsequence and collects the hunks
+/// between the common lines, with indices offset by `offset`.
+fn align(old: &[&str], new: &[&str], offset: usize, hunks: &mut Vec<Hunk>) {
+    // common[i][j] is the length of the longest common subsequence of old[i..] and new[j..]
+    let mut common: Vec<Vec<usize>> = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j): (usize, usize) = (0, 0);
+    let mut current: Option<Hunk> = None;
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            hunks.extend(current.take());
+            i += 1;
+            j += 1;
+            continue;
+        }
+
+        let hunk: &mut Hunk = current.get_or_insert_with(|| Hunk {
+            old_start: offset + i,
+            removed: Vec::new(),
+            new_start: offset + j,
+            added: Vec::new(),
+        });
+        if j == new.len() || (i < old.len() && common[i + 1][j] >= common[i][j + 1]) {
+            hunk.removed.push(old[i].to_string());
+            i += 1;
+        } else {
+            hunk.added.push(new[j].to_string());
+            j += 1;
+        }
+    }
+    hunks.extend(current);
+}
+
+/// Copies borrowed lines.
+fn to_strings(lines: &[&str]) -> Vec<String> {
+    lines.iter().map(|line| line.to_string()).collect()
+}
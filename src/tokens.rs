@@ -0,0 +1,41 @@
+//! Lightweight token estimation.
+//!
+//! The estimator does not depend on any model-specific tokenizer. It follows the common rule
+//! of thumb of roughly four characters per token for ASCII text, and counts every non-ASCII
+//! character (CJK ideographs, emoji, accented letters) as a full token, which errs on the side
+//! of over-estimating. It is meant for budgeting prompts, not for billing.
+
+/// Estimates the number of tokens a piece of text will consume.
+///
+/// # Arguments
+///
+/// * `text` - The text to estimate
+///
+/// # Returns
+///
+/// The estimated token count
+///
+/// # Examples
+///
+/// ```rust
+/// use secretary::tokens::estimate_tokens;
+///
+/// assert_eq!(estimate_tokens(""), 0);
+/// assert_eq!(estimate_tokens("abcd"), 1);
+/// assert_eq!(estimate_tokens("abcde"), 2);
+/// assert_eq!(estimate_tokens("你好"), 2);
+/// ```
+pub fn estimate_tokens(text: &str) -> usize {
+    let mut ascii_characters: usize = 0;
+    let mut other_characters: usize = 0;
+
+    for character in text.chars() {
+        if character.is_ascii() {
+            ascii_characters += 1;
+        } else {
+            other_characters += 1;
+        }
+    }
+
+    ascii_characters.div_ceil(4) + other_characters
+}
@@ -0,0 +1,141 @@
+//! Traces of distributed generation, for debugging and offline replay.
+//!
+//! `fields_generate_data` sends one request per field and keeps only the extracted struct.
+//! When that struct looks wrong, `fields_generate_data_traced` (and its async twin) returns
+//! a `FieldTrace` with it: for every field path, the prompt that was sent, the model's raw
+//! answer, the content of its `<result>` tags, the value parsed from that content and the
+//! latency of the request. The trace also records the values found by local extractors and
+//! the provider's `LeniencyProfile`.
+//!
+//! A trace is serializable, so it can be written to disk. `assemble_from_trace` runs a saved
+//! trace through the same parsing and assembly as the extraction did, without sending any
+//! request, which makes it possible to iterate on parsing and repair against real answers.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use std::collections::BTreeMap;
+//! use std::time::Duration;
+//!
+//! use secretary::Task;
+//! use secretary::trace::{FieldTrace, FieldTraceEntry, assemble_from_trace};
+//! use serde::{Deserialize, Serialize};
+//! use serde_json::json;
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug, PartialEq)]
+//! struct Person {
+//!     #[task(instruction = "Extract the person's name")]
+//!     pub name: String,
+//!     #[task(instruction = "Extract the age as a number")]
+//!     pub age: u32,
+//! }
+//!
+//! let entry = |content: &str| FieldTraceEntry {
+//!     prompt: "...".to_string(),
+//!     raw_response: format!("<result>{}</result>", content),
+//!     content: content.to_string(),
+//!     value: json!(null),
+//!     latency: Duration::from_millis(250),
+//! };
+//! let trace = FieldTrace {
+//!     fields: BTreeMap::from([
+//!         ("name".to_string(), entry("Ada")),
+//!         ("age".to_string(), entry("36")),
+//!     ]),
+//!     ..FieldTrace::default()
+//! };
+//!
+//! // Saved and loaded again
+//! let saved: String = serde_json::to_string(&trace).unwrap();
+//! let loaded: FieldTrace = serde_json::from_str(&saved).unwrap();
+//!
+//! let person: Person = assemble_from_trace(&loaded).unwrap();
+//! assert_eq!(person, Person { name: "Ada".to_string(), age: 36 });
+//! assert_eq!(loaded.total_latency(), Duration::from_millis(500));
+//! ```
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    SecretaryError, leniency::LeniencyProfile, limits::OutputLimits, traits::Task,
+    traits::assemble_field_results,
+};
+
+/// What happened to one field of a distributed extraction.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldTraceEntry {
+    /// The prompt sent for the field.
+    pub prompt: String,
+    /// The text the model answered, before thinking blocks and `<result>` tags are removed.
+    pub raw_response: String,
+    /// The content of the `<result>` tags, which is what gets parsed.
+    pub content: String,
+    /// The value parsed from the content, before the leniency profile is applied.
+    pub value: Value,
+    /// How long the request for the field took.
+    pub latency: Duration,
+}
+
+/// The per-field record of a distributed extraction, see the module documentation.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FieldTrace {
+    /// The fields answered by the model, by field path.
+    pub fields: BTreeMap<String, FieldTraceEntry>,
+    /// The fields filled by their local extractors instead of the model, by field path.
+    pub local_values: BTreeMap<String, String>,
+    /// The leniency profile of the provider that ran the extraction.
+    pub leniency: LeniencyProfile,
+}
+
+impl FieldTrace {
+    /// Returns the field paths with the content of their `<result>` tags.
+    pub fn results(&self) -> Vec<(String, String)> {
+        self.fields
+            .iter()
+            .map(|(field_path, entry)| (field_path.clone(), entry.content.clone()))
+            .collect()
+    }
+
+    /// Returns the sum of the latencies of the field requests.
+    ///
+    /// The requests run concurrently, so the extraction itself took less.
+    pub fn total_latency(&self) -> Duration {
+        self.fields.values().map(|entry| entry.latency).sum()
+    }
+}
+
+/// Assembles a Task from a saved trace without sending any request.
+///
+/// The content of every field is parsed again, so changes to the parsing since the trace was
+/// recorded take effect. The values parsed at the time stay in the trace for comparison.
+///
+/// # Arguments
+///
+/// * `trace` - A trace returned by `fields_generate_data_traced`
+///
+/// # Errors
+///
+/// Returns `SecretaryError::FieldDeserializationError` with the failing paths and the
+/// content of every field when the values do not fit `T`.
+pub fn assemble_from_trace<T: Task>(trace: &FieldTrace) -> Result<T, SecretaryError> {
+    let local_values: Vec<(String, String)> = trace
+        .local_values
+        .iter()
+        .map(|(field_path, value)| (field_path.clone(), value.clone()))
+        .collect();
+
+    let (data, _) = assemble_field_results::<T>(
+        &trace.leniency,
+        &OutputLimits::default(),
+        &T::field_descriptors(),
+        trace.results(),
+        &local_values,
+        &BTreeMap::new(),
+    )?;
+
+    Ok(data)
+}
@@ -1,10 +1,17 @@
-use std::panic;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::io::Read;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, LazyLock};
+use std::time::{Duration, Instant};
 
 use async_trait::async_trait;
-use futures::future;
+use futures::{Stream, StreamExt, TryStreamExt, future, stream};
+use futures_timer::Delay;
 use reqwest::{
     Response,
-    header::{AUTHORIZATION, CONTENT_TYPE},
+    header::{AUTHORIZATION, CONTENT_ENCODING, CONTENT_TYPE, HeaderMap, HeaderValue},
 };
 use serde::{Deserialize, Serialize};
 
@@ -13,13 +20,85 @@ use serde_json::Value;
 // Re-export the derive macro
 pub use secretary_derive::Task;
 
+#[cfg(feature = "schema-validation")]
+use crate::validation::check_content;
 use crate::{
-    SecretaryError, generate_from_tuples,
-    message::Message,
+    SecretaryError,
+    adaptive::{PromptStrategy, choose_prompt_strategy},
+    assembly::restore_tuple_structs,
+    casing::to_prompt_keys,
+    chunking::{
+        ChunkMerge, ChunkOptions, ChunkValue, MergePolicy, apply_adjudication,
+        make_adjudication_prompt, make_reduce_prompt, merge_chunk_values, take_confidence,
+    },
+    compiled::{CallPlan, CompileOptions, CompiledTask, ExtractionPlan},
+    consistency::{check_sampling, vote},
+    constants::JSON_ONLY_INSTRUCTION,
+    correlation::{CLIENT_REQUEST_ID_HEADER, child_request_id, traced_options},
+    deadletter::{DeadLetterSink, capture_failure},
+    deadline::Deadline,
+    decoding::DecodingPolicy,
+    defaults::{apply_default_values, from_value_with_defaults},
+    distributed::{FieldPrompt, group_field_prompts, split_group_content},
+    dynamic::DynTask,
+    error::FieldDeserializationError,
+    estimate::{ExtractionEstimate, Pricing, RequestEstimate},
+    explanation::{Explanation, ExplanationRequest, explainable_paths},
+    extractors::{descriptors_without, extract_local_fields, merge_local_values, set_local_values},
+    grammar::gbnf_grammar_without_fields,
+    guardrail::{Guardrail, GuardrailAction, InjectionVerdict},
+    hints::{HintConflict, merge_hint_values, set_hint_values, without_hinted_fields},
+    incremental::{RegenerateOptions, RegenerationPath, make_incremental_target},
+    input::{InputOptions, async_read_text, read_path, read_text},
+    instructions::InstructionSet,
+    language::{LanguageViolation, language_violations},
+    lazy::{LazyBinding, LazySource, with_lazy_fields},
+    leniency::LeniencyProfile,
+    limits::{ClippedValue, OutputLimits},
+    llm_providers::{
+        capabilities::{ConstrainedDecoding, ProviderCapabilities},
+        health::{self, HealthProbe, HealthReport},
+        http::{HttpClients, PreparedRequest},
+        json_mode::{JsonMode, JsonModeStrategy, is_response_format_rejection},
+        prompt_cache::{PromptCaching, annotate_cache_control},
+        queue::{Priority, QueuePermit, RequestQueue},
+        quota::{DEFAULT_TENANT, QuotaPartitioner},
+        rate_limit::{RateLimitInfo, ResponseEnvelope, RetryPolicy},
+    },
+    locale::ExtractionLocale,
+    message::{Message, SystemRoleStrategy},
+    metadata::{GenerationMetadata, GenerationResult, GenerationWarning},
+    metrics::{GenerationMode as MetricMode, MetricEvent, MetricsSink, NoopSink},
+    mode::GenerationMode,
+    normalization::normalize_strings,
+    ordering::{OrderViolation, order_violations},
+    partial::{
+        PartialData, absent_required_fields, deserialize_partial, diagnose, missing_critical_fields,
+    },
+    progress::{Progress, ProgressKind, report},
+    prompt::{DEFAULT_PROMPT_VERSION, frame_prompt},
+    provenance::{ProvenanceResult, provenance_system_prompt, strip_evidence},
+    reasoning::{TokenUsage, extract_reasoning_from_llm_response, strip_inline_reasoning},
+    refusal::detect_refusal,
+    request::{RequestOptions, merge_extra_body},
+    response_format::ResponseFormat,
+    review::{Either, ReviewItem},
+    schema::{FieldDescriptor, Importance, critical_field_paths},
+    schema_check::{self, SchemaMismatch},
+    streaming::{EventStream, is_event_stream, with_stream_fields},
+    textdiff::{TextDiff, diff_lines},
+    tokens::estimate_tokens,
+    trace::{FieldTrace, FieldTraceEntry},
+    trimming::{TrimmedKey, UnknownKeys},
     utilities::{
-        cleanup_thinking_blocks, extract_result_content, extract_text_content_from_llm_response,
-        format_additional_instructions,
+        extract_cached_tokens_from_llm_response, extract_result_content,
+        extract_system_fingerprint_from_llm_response, extract_text_content_from_llm_response,
+        extract_text_contents_from_llm_response, extract_total_tokens_from_llm_response,
+        format_additional_instructions, format_compact_field_specification, get_field_path,
+        parse_described_field_value, parse_task_from_answer, remove_field_path, set_field_path,
+        write_additional_instructions,
     },
+    vocabulary::{NormalizedValue, OneOfPolicy, normalize_field_results, normalize_one_of},
 };
 
 /// Core trait for implementing LLM providers that are compatible with OpenAI-style APIs.
@@ -67,777 +146,6768 @@ pub trait IsLLM {
         message: Message,
         return_json: bool,
     ) -> Result<String, Box<dyn std::error::Error + Send + Sync + 'static>> {
-        let request: reqwest::blocking::Response = reqwest::blocking::Client::new()
-            .post(self.get_chat_completion_request_url())
-            .header(AUTHORIZATION, self.get_authorization_credentials())
-            .header(CONTENT_TYPE, "application/json")
-            .json(&self.get_request_body(message, return_json))
-            .send()?;
-
-        Ok(request.text()?)
+        self.send_message_with_options(message, return_json, &RequestOptions::default())
     }
 
-    /// Sends an asynchronous message to the LLM and returns the raw response.
+    /// Sends a synchronous message to the LLM with per-request settings applied to the body.
     ///
     /// # Arguments
     ///
     /// * `message` - The message to send to the LLM
     /// * `return_json` - Whether to request JSON format response (enables JSON mode if supported)
+    /// * `options` - Request settings such as the sampling temperature
     ///
     /// # Returns
     ///
     /// Raw response string from the LLM API
-    async fn async_send_message(
+    fn send_message_with_options(
         &self,
         message: Message,
         return_json: bool,
+        options: &RequestOptions,
     ) -> Result<String, Box<dyn std::error::Error + Send + Sync + 'static>> {
-        let request: Response = reqwest::Client::new()
-            .post(self.get_chat_completion_request_url())
-            .header(AUTHORIZATION, self.get_authorization_credentials())
-            .header(CONTENT_TYPE, "application/json")
-            .json(&self.get_request_body(message, return_json))
-            .send()
-            .await?;
-
-        Ok(request.text().await?)
+        Ok(self
+            .send_message_envelope(message, return_json, options)?
+            .body)
     }
 
-    /// Returns the authorization credentials for the LLM provider.
+    /// Sends a synchronous message to the LLM and returns the full HTTP response.
+    ///
+    /// Throttled requests are retried according to `get_retry_policy`. The envelope is the
+    /// response to the last attempt.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - The message to send to the LLM
+    /// * `return_json` - Whether to request JSON format response (enables JSON mode if supported)
+    /// * `options` - Request settings such as the sampling temperature
     ///
     /// # Returns
     ///
-    /// A tuple of (header_name, header_value) for authentication
-    fn get_authorization_credentials(&self) -> String;
+    /// The status code, headers and body of the response
+    fn send_message_envelope(
+        &self,
+        message: Message,
+        return_json: bool,
+        options: &RequestOptions,
+    ) -> Result<ResponseEnvelope, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        self.send_messages_envelope(vec![message], return_json, options)
+    }
 
-    /// Constructs the request body for the LLM API call.
+    /// Sends a synchronous conversation to the LLM with per-request settings applied to the
+    /// body and returns the raw response.
     ///
     /// # Arguments
     ///
-    /// * `message` - The message to include in the request
-    /// * `return_json` - Whether to enable JSON mode in the request
+    /// * `messages` - The messages to send, in order
+    /// * `return_json` - Whether to request JSON format response (enables JSON mode if supported)
+    /// * `options` - Request settings such as the sampling temperature
     ///
     /// # Returns
     ///
-    /// JSON value representing the request body
-    fn get_request_body(&self, message: Message, return_json: bool) -> Value;
+    /// Raw response string from the LLM API
+    fn send_messages_with_options(
+        &self,
+        messages: Vec<Message>,
+        return_json: bool,
+        options: &RequestOptions,
+    ) -> Result<String, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        Ok(self
+            .send_messages_envelope(messages, return_json, options)?
+            .body)
+    }
 
-    /// Returns the complete URL for the chat completion endpoint.
+    /// Sends a synchronous conversation to the LLM and returns the full HTTP response.
+    ///
+    /// Every message but the last is treated as the stable prompt prefix, which is marked for
+    /// caching when the provider declares `PromptCaching::CacheControl`. Throttled requests
+    /// are retried according to `get_retry_policy`.
+    ///
+    /// # Arguments
+    ///
+    /// * `messages` - The messages to send, in order
+    /// * `return_json` - Whether to request JSON format response (enables JSON mode if supported)
+    /// * `options` - Request settings such as the sampling temperature
     ///
     /// # Returns
     ///
-    /// The full URL string for making API requests
-    fn get_chat_completion_request_url(&self) -> String;
+    /// The status code, headers and body of the response
+    fn send_messages_envelope(
+        &self,
+        messages: Vec<Message>,
+        return_json: bool,
+        options: &RequestOptions,
+    ) -> Result<ResponseEnvelope, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let json_mode: &JsonMode = self.get_json_mode();
+        let native: bool = return_json && json_mode.uses_native();
 
-    /// Returns a reference to the model identifier being used.
+        let body: Value = build_request_body(self, messages.clone(), return_json, native, options);
+        let response: ResponseEnvelope = post_request(self, &body, options)?;
+
+        if native
+            && json_mode.strategy() == JsonModeStrategy::Auto
+            && is_response_format_rejection(response.status, &response.body)
+        {
+            json_mode.mark_native_rejected();
+            let body: Value = build_request_body(self, messages, return_json, false, options);
+            return post_request(self, &body, options);
+        }
+
+        Ok(response)
+    }
+
+    /// Sends an asynchronous message to the LLM and returns the raw response.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - The message to send to the LLM
+    /// * `return_json` - Whether to request JSON format response (enables JSON mode if supported)
     ///
     /// # Returns
     ///
-    /// String slice containing the model name or deployment ID
-    fn get_model_ref(&self) -> &str;
-}
+    /// Raw response string from the LLM API
+    async fn async_send_message(
+        &self,
+        message: Message,
+        return_json: bool,
+    ) -> Result<String, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        self.async_send_message_with_options(message, return_json, &RequestOptions::default())
+            .await
+    }
 
-/// The main `Task` trait for defining data extraction schemas and system prompts.
-///
-/// This trait should be implemented using the `#[derive(Task)]` macro for user-defined structs.
-/// It combines data model definition, system prompt generation, and serialization capabilities
-/// into a single, cohesive interface for LLM-based data extraction.
-///
-/// # Examples
-///
-/// ```rust
-/// use secretary::Task;
-/// use serde::{Serialize, Deserialize};
-///
-/// #[derive(Task, Serialize, Deserialize, Debug)]
-/// struct PersonInfo {
-///     #[task(instruction = "Extract the person's full name")]
-///     pub name: String,
-///     
-///     #[task(instruction = "Extract age as a number")]
-///     pub age: u32,
-///     
-///     #[task(instruction = "Extract email address if mentioned")]
-///     pub email: Option<String>,
-/// }
-///
-/// let task = PersonInfo::new();
-/// let system_prompt = task.get_system_prompt();
-/// println!("Generated prompt: {}", system_prompt);
-/// ```
-///
-/// # Derive Macro
-///
-/// The `#[derive(Task)]` macro automatically implements this trait and generates:
-/// - System prompts based on field instructions
-/// - JSON schema definitions
-/// - A `new()` constructor method
-///
-/// Use `#[task(instruction = "...")]` attributes on fields to provide extraction guidance.
-///
-/// # Error Handling
-///
-/// Implementations of this trait should be mindful of potential deserialization errors.
-/// If the LLM returns data that does not match the expected schema (e.g., a string for a number field),
-/// a `SecretaryError::FieldDeserializationError` will be returned, detailing which fields failed.
-pub trait Task: Serialize + for<'de> Deserialize<'de> + Default {
-    /// Generates a system prompt for the LLM based on the struct's field instructions.
+    /// Sends an asynchronous message to the LLM with per-request settings applied to the body.
     ///
-    /// This method creates a comprehensive prompt that includes:
-    /// - JSON structure specifications
-    /// - Field-specific extraction instructions
-    /// - Response format requirements
+    /// # Arguments
+    ///
+    /// * `message` - The message to send to the LLM
+    /// * `return_json` - Whether to request JSON format response (enables JSON mode if supported)
+    /// * `options` - Request settings such as the sampling temperature
     ///
     /// # Returns
     ///
-    /// A formatted string containing the complete system prompt.
-    fn get_system_prompt(&self) -> String;
+    /// Raw response string from the LLM API
+    async fn async_send_message_with_options(
+        &self,
+        message: Message,
+        return_json: bool,
+        options: &RequestOptions,
+    ) -> Result<String, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        Ok(self
+            .async_send_message_envelope(message, return_json, options)
+            .await?
+            .body)
+    }
 
-    /// Returns a list of field names and their corresponding system prompts for distributed generation.
+    /// Sends an asynchronous message to the LLM and returns the full HTTP response.
     ///
-    /// This method is used by `fields_generate_data` and `async_fields_generate_data` to
-    /// generate each field's value independently.
+    /// Throttled requests are retried according to `get_retry_policy`. The envelope is the
+    /// response to the last attempt.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - The message to send to the LLM
+    /// * `return_json` - Whether to request JSON format response (enables JSON mode if supported)
+    /// * `options` - Request settings such as the sampling temperature
     ///
     /// # Returns
     ///
-    /// A `Vec` of tuples, where each tuple contains a field name and its system prompt.
-    fn get_system_prompts_for_distributed_generation(&self) -> Vec<(String, String)>;
+    /// The status code, headers and body of the response
+    async fn async_send_message_envelope(
+        &self,
+        message: Message,
+        return_json: bool,
+        options: &RequestOptions,
+    ) -> Result<ResponseEnvelope, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        self.async_send_messages_envelope(vec![message], return_json, options)
+            .await
+    }
 
-    /// Create a prompt that will be sending to the LLM for generating a structural data
-    /// Creates a `Message` object for the LLM, combining the system prompt, user input, and additional instructions.
+    /// Sends an asynchronous conversation to the LLM with per-request settings applied to the
+    /// body and returns the raw response.
     ///
     /// # Arguments
     ///
-    /// * `target` - The natural language input to be processed.
-    /// * `additional_instructions` - A list of extra instructions to guide the LLM.
+    /// * `messages` - The messages to send, in order
+    /// * `return_json` - Whether to request JSON format response (enables JSON mode if supported)
+    /// * `options` - Request settings such as the sampling temperature
     ///
     /// # Returns
     ///
-    /// A `Message` struct ready to be sent to the LLM.
-    fn make_prompt(&self, target: &str, additional_instructions: &Vec<String>) -> Message {
-        Message {
-            role: "user".to_string(),
-            content: format!(
-                "{}{}\nThis is the basis for generating a json:\n{}",
-                self.get_system_prompt(),
-                format_additional_instructions(additional_instructions),
-                target
-            ),
-        }
+    /// Raw response string from the LLM API
+    async fn async_send_messages_with_options(
+        &self,
+        messages: Vec<Message>,
+        return_json: bool,
+        options: &RequestOptions,
+    ) -> Result<String, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        Ok(self
+            .async_send_messages_envelope(messages, return_json, options)
+            .await?
+            .body)
     }
 
-    /// Create a prompt that will be sending to the LLM for generating a structural data
-    fn make_dstributed_generation_prompts(
+    /// Sends an asynchronous conversation to the LLM and returns the full HTTP response.
+    ///
+    /// Every message but the last is treated as the stable prompt prefix, which is marked for
+    /// caching when the provider declares `PromptCaching::CacheControl`. Throttled requests
+    /// are retried according to `get_retry_policy`.
+    ///
+    /// # Arguments
+    ///
+    /// * `messages` - The messages to send, in order
+    /// * `return_json` - Whether to request JSON format response (enables JSON mode if supported)
+    /// * `options` - Request settings such as the sampling temperature
+    ///
+    /// # Returns
+    ///
+    /// The status code, headers and body of the response
+    async fn async_send_messages_envelope(
         &self,
-        target: &str,
-        additional_instructions: &Vec<String>,
-    ) -> Vec<(String, Message)> {
-        let mut messages: Vec<(String, Message)> = Vec::new();
-
-        for prompt in self.get_system_prompts_for_distributed_generation() {
-            messages.push((
-                prompt.0,
-                Message {
-                    role: "user".to_string(),
-                    content: format!(
-                        "{}{}\nThis is the basis for generating the result:\n{}",
-                        prompt.1,
-                        format_additional_instructions(additional_instructions),
-                        target
-                    ),
-                },
-            ));
+        messages: Vec<Message>,
+        return_json: bool,
+        options: &RequestOptions,
+    ) -> Result<ResponseEnvelope, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let json_mode: &JsonMode = self.get_json_mode();
+        let native: bool = return_json && json_mode.uses_native();
+
+        let body: Value = build_request_body(self, messages.clone(), return_json, native, options);
+        let response: ResponseEnvelope = async_post_request(self, &body, options).await?;
+
+        if native
+            && json_mode.strategy() == JsonModeStrategy::Auto
+            && is_response_format_rejection(response.status, &response.body)
+        {
+            json_mode.mark_native_rejected();
+            let body: Value = build_request_body(self, messages, return_json, false, options);
+            return async_post_request(self, &body, options).await;
         }
 
-        messages
+        Ok(response)
     }
-}
 
-/// Trait for synchronous data generation from LLMs.
-///
-/// This trait provides methods for extracting structured data from natural language text
-/// using LLM providers. It includes both standard JSON mode generation and force generation
-/// for reasoning models that don't support JSON mode.
-///
-/// # Examples
-///
-/// ```no_run
-/// use secretary::Task;
-/// use secretary::llm_providers::openai::OpenAILLM;
-/// use secretary::traits::GenerateData;
-/// use serde::{Serialize, Deserialize};
-///
-/// #[derive(Task, Serialize, Deserialize, Debug)]
-/// struct ProductInfo {
-///     #[task(instruction = "Extract the product name")]
-///     pub name: String,
-///     #[task(instruction = "Extract price as a number")]
-///     pub price: f64,
-/// }
-///
-/// # fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
-/// let llm = OpenAILLM::new(
-///     "https://api.openai.com/v1",
-///     "your-api-key",
-///     "gpt-4"
-/// )?;
-///
-/// let task = ProductInfo::new();
-/// let additional_instructions = vec!["Be precise with pricing".to_string()];
-///
-/// let input = "Apple MacBook Pro 16-inch costs $2,499";
-/// let result: ProductInfo = llm.generate_data(&task, input, &additional_instructions)?;
-///
-/// println!("Extracted: {:#?}", result);
-/// # Ok(())
-/// # }
-/// ```
-pub trait GenerateData
-where
-    Self: IsLLM + Sync,
-{
-    /// Generates structured data from natural language using JSON mode.
-    ///
-    /// This method uses the LLM's JSON mode (if available) to ensure structured output.
-    /// For models without JSON mode support, use `force_generate_data` instead.
-    ///
-    /// # Arguments
+    /// Checks that the endpoint is reachable, accepts the credentials and serves the model.
     ///
-    /// * `task` - A Task implementation that provides the system prompt and schema
-    /// * `target` - The natural language text to extract data from
-    /// * `additional_instructions` - Extra instructions to guide the extraction process
+    /// Sends the single request chosen by `get_health_probe`, see the `health` module. Failures
+    /// of the provider are reported in the `HealthReport` rather than as errors.
     ///
     /// # Returns
     ///
-    /// A Result containing the extracted data as the specified type T
+    /// Whether the provider answered, whether the model is available and how long it took
     ///
     /// # Errors
     ///
-    /// Returns an error if:
-    /// - The LLM API call fails
-    /// - The response cannot be parsed as valid JSON
-    /// - The JSON doesn't match the expected schema, potentially returning a `FieldDeserializationError`.
-    fn generate_data<T: Task>(
-        &self,
-        task: &T,
-        target: &str,
-        additional_instructions: &Vec<String>,
-    ) -> Result<T, Box<dyn std::error::Error + Send + Sync + 'static>> {
-        let request: String =
-            self.send_message(task.make_prompt(target, additional_instructions), true)?;
-
-        let result: String = extract_text_content_from_llm_response(&request)?;
+    /// Returns `SecretaryError::BuildRequestError` if the probe cannot be built, e.g. because
+    /// the configured URL is invalid.
+    fn health_check(&self) -> Result<HealthReport, SecretaryError> {
+        health::check(self)
+    }
 
-        match serde_json::from_str::<T>(&result) {
-            Ok(result) => Ok(result),
-            Err(error) => Err(Box::new(SecretaryError::SerdeJsonError(error))),
-        }
+    /// Asynchronously checks that the endpoint is reachable, accepts the credentials and
+    /// serves the model.
+    ///
+    /// This is the asynchronous version of `health_check`.
+    async fn async_health_check(&self) -> Result<HealthReport, SecretaryError> {
+        health::async_check(self).await
     }
 
-    /// Generates structured data from natural language without JSON mode (for reasoning models).
+    /// Returns the authorization credentials for the LLM provider.
     ///
-    /// This method is designed for reasoning models like o1, deepseek, and others that don't
-    /// support JSON mode. It uses text parsing to extract JSON from the model's response.
+    /// # Returns
+    ///
+    /// A tuple of (header_name, header_value) for authentication
+    fn get_authorization_credentials(&self) -> String;
+
+    /// Returns the `Authorization` header value for an API key passed with
+    /// `RequestOptions::with_credentials`.
     ///
     /// # Arguments
     ///
-    /// * `task` - A Task implementation that provides the system prompt and schema
-    /// * `target` - The natural language text to extract data from
-    /// * `additional_instructions` - Extra instructions to guide the extraction process
+    /// * `api_key` - The API key of the call
     ///
     /// # Returns
     ///
-    /// A Result containing the extracted data as the specified type T
+    /// A bearer token by default, or `None` if the provider does not authenticate with an
+    /// API key
+    fn get_authorization_for_key(&self, api_key: &str) -> Option<String> {
+        Some(format!("Bearer {}", api_key))
+    }
+
+    /// Constructs the request body for the LLM API call.
     ///
-    /// # Errors
+    /// # Arguments
     ///
-    /// Returns `SecretaryError` if the LLM response cannot be parsed into the target struct `T`.
+    /// * `message` - The message to include in the request
+    /// * `return_json` - Whether to enable JSON mode in the request
     ///
-    /// # Examples
+    /// # Returns
     ///
-    /// ```no_run
-    /// # use secretary::Task;
-    /// # use secretary::llm_providers::openai::OpenAILLM;
-    /// # use secretary::traits::GenerateData;
-    /// # use serde::{Serialize, Deserialize};
-    /// #
-    /// # #[derive(Task, Serialize, Deserialize, Debug)]
-    /// # struct ProductInfo {
-    /// #     #[task(instruction = "Extract the product name")]
-    /// #     pub name: String,
-    /// #     #[task(instruction = "Extract price as a number")]
-    /// #     pub price: f64,
-    /// # }
-    /// #
-    /// # fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
-    /// // For reasoning models like o1-preview
-    /// let llm = OpenAILLM::new(
-    ///     "https://api.openai.com/v1",
-    ///     "your-api-key",
-    ///     "o1-preview"  // Reasoning model without JSON mode
-    /// )?;
-    ///
-    /// let task = ProductInfo::new();
-    /// let additional_instructions = vec!["Think step by step".to_string()];
-    ///
-    /// let input = "Apple MacBook Pro 16-inch costs $2,499";
-    /// let result: ProductInfo = llm.force_generate_data(&task, input, &additional_instructions)?;
-    /// # Ok(())
-    /// # }
-    /// ```
-    fn force_generate_data<T: Task>(
-        &self,
-        task: &T,
-        target: &str,
-        additional_instructions: &Vec<String>,
-    ) -> Result<T, Box<dyn std::error::Error + Send + Sync + 'static>> {
-        let response: String =
-            self.send_message(task.make_prompt(target, additional_instructions), false)?;
-
-        let result: String = extract_text_content_from_llm_response(&response)?;
-
-        match surfing::serde::from_mixed_text(&result) {
-            Ok(result) => Ok(result),
-            Err(error) => Err(Box::new(SecretaryError::JsonParsingError(
-                error.to_string(),
-            ))),
-        }
-    }
+    /// JSON value representing the request body
+    fn get_request_body(&self, message: Message, return_json: bool) -> Value;
 
-    /// Generates structured data by breaking down the task into individual field requests.
-    ///
-    /// Instead of generating a complete JSON object in a single request, this method breaks
-    /// the task down into individual field extractions. Each field is processed in parallel
-    /// using separate threads, and the results are combined into the final structured object.
-    /// This approach can improve accuracy for complex extractions and provides better error
-    /// isolation per field.
+    /// Constructs the request body for a conversation.
     ///
-    /// # Benefits
-    ///
-    /// - **Improved accuracy**: Each field gets focused attention from the LLM
-    /// - **Parallel processing**: Multiple fields extracted simultaneously using threads
-    /// - **Error isolation**: Failure in one field doesn't affect others
-    /// - **Reasoning model support**: Works well with models that don't support JSON mode
+    /// The default builds the body around the first message with `get_request_body` and
+    /// replaces its messages with the whole conversation, marking the stable prefix for caching
+    /// when the provider declares `PromptCaching::CacheControl`. Providers whose API does not
+    /// take OpenAI-style messages build the body themselves.
     ///
     /// # Arguments
     ///
-    /// * `task` - A Task implementation that provides field-specific prompts
-    /// * `target` - The natural language text to extract data from
-    /// * `additional_instructions` - Extra instructions to guide the extraction process
+    /// * `messages` - The messages to send, in order
+    /// * `return_json` - Whether to enable JSON mode in the request
     ///
     /// # Returns
     ///
-    /// A Result containing the extracted data as the specified type T
-    ///
-    /// # Examples
-    ///
-    /// ```no_run
-    /// use secretary::Task;
-    /// use secretary::llm_providers::openai::OpenAILLM;
-    /// use secretary::traits::GenerateData;
-    /// use serde::{Serialize, Deserialize};
-    ///
-    /// #[derive(Task, Serialize, Deserialize, Debug)]
-    /// struct PersonProfile {
-    ///     #[task(instruction = "Extract the person's full name")]
-    ///     pub name: String,
-    ///     #[task(instruction = "Extract age as a number")]
-    ///     pub age: u32,
-    ///     #[task(instruction = "Extract email address if mentioned")]
-    ///     pub email: Option<String>,
-    ///     #[task(instruction = "Extract job title or profession")]
-    ///     pub profession: Option<String>,
-    /// }
-    ///
-    /// # fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
-    /// let llm = OpenAILLM::new(
-    ///     "https://api.openai.com/v1",
-    ///     "your-api-key",
-    ///     "gpt-4"
-    /// )?;
+    /// JSON value representing the request body
+    fn get_conversation_body(&self, messages: Vec<Message>, return_json: bool) -> Value {
+        let first: Message = messages.first().cloned().unwrap_or(Message::user(""));
+        let mut body: Value = self.get_request_body(first, return_json);
+        if messages.len() > 1 {
+            body["messages"] = serde_json::to_value(&messages).unwrap_or_default();
+            if self.get_capabilities().prompt_caching == PromptCaching::CacheControl {
+                annotate_cache_control(&mut body);
+            }
+        }
+
+        body
+    }
+
+    /// Writes per-request settings into a request body built by `get_conversation_body`.
     ///
-    /// let task = PersonProfile::new();
-    /// let additional_instructions = vec![
-    ///     "Be precise with personal information".to_string(),
-    ///     "Use null for missing information".to_string(),
-    /// ];
+    /// The default also sends the grammar of the options as the `grammar` field, in place of
+    /// `response_format`, when the provider declares `ConstrainedDecoding::Gbnf`, see the
+    /// `grammar` module.
     ///
-    /// let input = "John Smith is a 35-year-old software engineer. You can reach him at john.smith@email.com";
+    /// # Arguments
     ///
-    /// // Each field will be extracted in parallel
-    /// let result: PersonProfile = llm.fields_generate_data(&task, input, &additional_instructions)?;
+    /// * `body` - The JSON request body to modify
+    /// * `options` - Request settings such as the sampling temperature
+    fn apply_request_options(&self, body: &mut Value, options: &RequestOptions) {
+        options.apply_to_body(body);
+        if let (Some(grammar), Some(body)) =
+            (constrained_grammar(self, options), body.as_object_mut())
+        {
+            body.remove("response_format");
+            body.insert("grammar".to_string(), Value::String(grammar.to_string()));
+        }
+    }
+
+    /// Returns the headers sent with a request.
     ///
-    /// println!("Extracted profile: {:#?}", result);
-    /// # Ok(())
-    /// # }
-    /// ```
+    /// Called with the body exactly as it is sent, so providers that sign their requests can
+    /// sign it. The default sends `get_authorization_credentials` as the `Authorization`
+    /// header, and a JSON content type with a body.
     ///
-    /// # Performance Considerations
+    /// # Arguments
     ///
-    /// - **Thread overhead**: Creates one thread per field, so best for structs with moderate field counts
-    /// - **API calls**: Makes one API call per field, which may increase costs but improve accuracy
-    /// - **Parallel execution**: Faster than sequential field extraction for multi-field structs
+    /// * `request` - The method, URL and body of the request
     ///
     /// # Errors
     ///
-    /// Returns `SecretaryError` if the final assembly of fields into the struct `T` fails.
-    /// This is particularly useful for catching `FieldDeserializationError`.
-    fn fields_generate_data<T: Task>(
+    /// Returns `SecretaryError::BuildRequestError` if the headers cannot be built
+    fn get_request_headers(
         &self,
-        task: &T,
-        target: &str,
-        additional_instructions: &Vec<String>,
-    ) -> Result<T, Box<dyn std::error::Error + Send + Sync + 'static>> {
-        let messages: Vec<(String, Message)> =
-            task.make_dstributed_generation_prompts(target, additional_instructions);
-
-        let distributed_tasks_results: Vec<(String, String)> = std::thread::scope(|s| {
-            let mut distributed_tasks = Vec::new();
-            for (field_name, message) in messages {
-                let handler = s.spawn(move || {
-                    let content: String = extract_text_content_from_llm_response(
-                        &self.send_message(message, false)?,
-                    )?;
-
-                    Ok::<(String, String), Box<dyn std::error::Error + Send + Sync + 'static>>((
-                        field_name,
-                        extract_result_content(&cleanup_thinking_blocks(content)),
-                    ))
-                });
-
-                distributed_tasks.push(handler);
-            }
-
-            let mut distributed_tasks_results: Vec<(String, String)> = Vec::new();
-            for distributed_task in distributed_tasks {
-                match distributed_task.join() {
-                    Ok(result) => match result {
-                        Ok(result) => distributed_tasks_results.push(result),
-                        Err(error) => return Err(error),
-                    },
-                    Err(error) => panic!(),
-                }
-            }
-
-            Ok(distributed_tasks_results)
-        })?;
-
-        // Use panic::catch_unwind to handle potential FieldDeserializationError panics from the macro
-        match panic::catch_unwind(|| generate_from_tuples!(T, distributed_tasks_results)) {
-            Ok(result) => Ok(result),
-            Err(panic_payload) => {
-                // Try to extract the FieldDeserializationError from the panic
-                if let Some(error_msg) = panic_payload.downcast_ref::<String>() {
-                    // Parse the error message to reconstruct the FieldDeserializationError
-                    if error_msg.contains("Failed to deserialize") {
-                        return Err(Box::new(SecretaryError::JsonParsingError(
-                            error_msg.clone(),
-                        )));
-                    }
-                }
-                // Fallback for other panics
-                Err(Box::new(SecretaryError::JsonParsingError(
-                    "Field deserialization failed with unknown error".to_string(),
-                )))
-            }
+        request: &PreparedRequest<'_>,
+    ) -> Result<HeaderMap, SecretaryError> {
+        let mut headers: HeaderMap = HeaderMap::new();
+        let credentials: HeaderValue = HeaderValue::from_str(&self.get_authorization_credentials())
+            .map_err(|error| SecretaryError::BuildRequestError(error.to_string()))?;
+        headers.insert(AUTHORIZATION, credentials);
+        if !request.body.is_empty() {
+            headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
         }
+
+        Ok(headers)
     }
-}
 
-/// Trait for asynchronous data generation from LLMs.
-///
-/// This trait provides async methods for extracting structured data from natural language text
-/// using LLM providers. It includes both standard JSON mode generation and force generation
-/// for reasoning models that don't support JSON mode.
-///
-/// # Examples
-///
-/// ```no_run
-/// use secretary::Task;
-/// use secretary::llm_providers::openai::OpenAILLM;
-/// use secretary::traits::AsyncGenerateData;
-/// use serde::{Deserialize, Serialize};
-///
-/// #[derive(Task, Debug, Serialize, Deserialize)]
-/// struct ProductInfo {
-///     #[task(instruction = "Extract the product name")]
-///     pub name: String,
-///     #[task(instruction = "Extract price as a number")]
-///     pub price: f64,
-/// }
-///
-/// #[tokio::main]
-/// async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
-///     let llm = OpenAILLM::new(
-///         "https://api.openai.com/v1",
-///         "your-api-key",
-///         "gpt-4"
-///     )?;
-///     
-///     let task = ProductInfo::new();
-///     let additional_instructions = vec!["Be precise with pricing".to_string()];
-///     
-///     let input = "Apple MacBook Pro 16-inch costs $2,499";
-///     let result: ProductInfo = llm.async_generate_data(&task, input, &additional_instructions).await?;
-///     
-///     println!("Extracted: {:#?}", result);
-///     Ok(())
-/// }
-/// ```
-#[async_trait]
-pub trait AsyncGenerateData
-where
-    Self: IsLLM,
-{
-    /// Asynchronously generates structured data from natural language using JSON mode.
-    ///
-    /// This is the asynchronous version of `generate_data` that can be used in async contexts.
-    /// It uses the LLM's JSON mode (if available) to ensure structured output.
+    /// Extracts the text the model answered with from a response body.
     ///
     /// # Arguments
     ///
-    /// * `task` - A Task implementation that provides the system prompt and schema
-    /// * `target` - The natural language text to extract data from
-    /// * `additional_instructions` - Extra instructions to guide the extraction process
+    /// * `api_response` - The raw JSON response from the LLM API
     ///
     /// # Returns
     ///
-    /// A Result containing the extracted data as the specified type T
+    /// The message content of the first choice by default
     ///
     /// # Errors
     ///
-    /// Returns `SecretaryError` if the LLM response cannot be parsed into the target struct `T`,
-    /// including `FieldDeserializationError` if specific fields fail.
-    async fn async_generate_data<T: Task + Sync + Send>(
+    /// Returns `SecretaryError::NoLLMResponse` if the response carries no content
+    fn extract_response_content(
         &self,
-        task: &T,
-        target: &str,
-        additional_instructions: &Vec<String>,
-    ) -> Result<T, Box<dyn std::error::Error + Send + Sync + 'static>> {
-        let request: Result<String, Box<dyn std::error::Error + Send + Sync>> = self
-            .async_send_message(task.make_prompt(target, additional_instructions), true)
-            .await;
-
-        let result = match request {
-            Ok(result) => extract_text_content_from_llm_response(&result)?,
-            Err(error) => return Err(SecretaryError::BuildRequestError(error.to_string()).into()),
-        };
-
-        match serde_json::from_str::<T>(&result) {
-            Ok(result) => Ok(result),
-            Err(error) => Err(Box::new(SecretaryError::SerdeJsonError(error))),
-        }
+        api_response: &str,
+    ) -> Result<String, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        extract_text_content_from_llm_response(api_response)
     }
 
-    /// Asynchronously generates structured data from natural language without JSON mode (for reasoning models).
-    ///
-    /// This is the asynchronous version of `force_generate_data` designed for reasoning models
-    /// like o1, deepseek, and others that don't support JSON mode. It uses text parsing to
-    /// extract JSON from the model's response.
+    /// Extracts the text of every sample from a response body, for requests sent with
+    /// `RequestOptions::with_choices`.
     ///
     /// # Arguments
     ///
-    /// * `task` - A Task implementation that provides the system prompt and schema
-    /// * `target` - The natural language text to extract data from
-    /// * `additional_instructions` - Extra instructions to guide the extraction process
+    /// * `api_response` - The raw JSON response from the LLM API
     ///
     /// # Returns
     ///
-    /// A Result containing the extracted data as the specified type T
+    /// The message content of each choice that has one by default. Providers that return a
+    /// single sample per response return `extract_response_content` alone.
     ///
-    /// # Examples
+    /// # Errors
     ///
-    /// ```no_run
-    /// # use secretary::Task;
-    /// # use secretary::llm_providers::openai::OpenAILLM;
-    /// # use secretary::traits::AsyncGenerateData;
-    /// # use serde::{Serialize, Deserialize};
-    /// #
-    /// # #[derive(Task, Serialize, Deserialize, Debug)]
-    /// # struct ProductInfo {
-    /// #     #[task(instruction = "Extract the product name")]
-    /// #     pub name: String,
-    /// #     #[task(instruction = "Extract price as a number")]
-    /// #     pub price: f64,
-    /// # }
-    /// #
-    /// # #[tokio::main]
-    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
-    /// // For reasoning models like o1-preview
-    /// let llm = OpenAILLM::new(
-    ///     "https://api.openai.com/v1",
-    ///     "your-api-key",
-    ///     "o1-preview"  // Reasoning model without JSON mode
-    /// )?;
+    /// Returns `SecretaryError::NoLLMResponse` if the response carries no content
+    fn extract_response_contents(
+        &self,
+        api_response: &str,
+    ) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        extract_text_contents_from_llm_response(api_response)
+    }
+
+    /// Extracts the ID the provider gave a response, for providers that keep conversations
+    /// on the server.
     ///
-    /// let task = ProductInfo::new();
-    /// let additional_instructions = vec!["Think step by step".to_string()];
+    /// # Arguments
     ///
-    /// let input = "Apple MacBook Pro 16-inch costs $2,499";
-    /// let result: ProductInfo = llm.async_force_generate_data(&task, input, &additional_instructions).await?;
-    /// # Ok(())
-    /// # }
-    /// ```
+    /// * `_api_response` - The raw JSON response from the LLM API
     ///
-    /// # Errors
+    /// # Returns
     ///
-    /// Returns `SecretaryError` if the LLM response cannot be parsed into the target struct `T`.
-    async fn async_force_generate_data<T: Task + Sync + Send>(
-        &self,
-        task: &T,
-        target: &str,
-        additional_instructions: &Vec<String>,
-    ) -> Result<T, Box<dyn std::error::Error + Send + Sync + 'static>> {
-        let request: Result<String, Box<dyn std::error::Error + Send + Sync>> = self
-            .async_send_message(task.make_prompt(target, additional_instructions), false)
-            .await;
-
-        let result: String = match request {
-            Ok(result) => extract_text_content_from_llm_response(&result)?,
-            Err(error) => return Err(SecretaryError::BuildRequestError(error.to_string()).into()),
-        };
-
-        match surfing::serde::from_mixed_text(&result) {
-            Ok(result) => Ok(result),
-            Err(error) => Err(Box::new(SecretaryError::JsonParsingError(
-                error.to_string(),
-            ))),
-        }
+    /// The ID to send as `previous_response_id` on the next turn, `None` by default
+    fn extract_response_id(&self, _api_response: &str) -> Option<String> {
+        None
     }
 
-    /// Asynchronously generates structured data by breaking down the task into individual field requests.
+    /// Returns the complete URL for the chat completion endpoint.
     ///
-    /// This is the async version of `fields_generate_data` that uses concurrent futures instead of threads.
-    /// Instead of generating a complete JSON object in a single request, this method breaks the task
-    /// down into individual field extractions. Each field is processed concurrently using async tasks,
-    /// and the results are combined into the final structured object.
+    /// # Returns
     ///
-    /// # Benefits
+    /// The full URL string for making API requests
+    fn get_chat_completion_request_url(&self) -> String;
+
+    /// Returns a reference to the model identifier being used.
     ///
-    /// - **Improved accuracy**: Each field gets focused attention from the LLM
-    /// - **Concurrent processing**: Multiple fields extracted simultaneously using async tasks
-    /// - **Error isolation**: Failure in one field doesn't affect others
-    /// - **Async-friendly**: Integrates seamlessly with async codebases
-    /// - **Resource efficient**: Uses async I/O instead of blocking threads
+    /// # Returns
     ///
-    /// # Arguments
+    /// String slice containing the model name or deployment ID
+    fn get_model_ref(&self) -> &str;
+
+    /// Returns the declared capabilities of the provider's model.
     ///
-    /// * `task` - A Task implementation that provides field-specific prompts
-    /// * `target` - The natural language text to extract data from
-    /// * `additional_instructions` - Extra instructions to guide the extraction process
+    /// # Returns
+    ///
+    /// The `ProviderCapabilities` configured on the provider, all unknown by default
+    fn get_capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities::default()
+    }
+
+    /// Returns how system messages are sent to the model, see `SystemRoleStrategy`.
     ///
     /// # Returns
     ///
-    /// A Result containing the extracted data as the specified type T
+    /// The strategy known to work for the model of `get_model_ref`
+    fn get_system_role_strategy(&self) -> SystemRoleStrategy {
+        SystemRoleStrategy::for_model(self.get_model_ref())
+    }
+
+    /// Returns the coercions applied to the model's output before deserialization.
     ///
-    /// # Examples
+    /// # Returns
     ///
-    /// ```no_run
-    /// use secretary::Task;
-    /// use secretary::llm_providers::openai::OpenAILLM;
-    /// use secretary::traits::AsyncGenerateData;
-    /// use serde::{Serialize, Deserialize};
+    /// The `LeniencyProfile` configured on the provider, `LeniencyProfile::strict()` by default
+    fn get_leniency(&self) -> &LeniencyProfile {
+        static STRICT: LazyLock<LeniencyProfile> = LazyLock::new(LeniencyProfile::strict);
+        &STRICT
+    }
+
+    /// Returns the sink that receives metric events for requests and parse failures.
     ///
-    /// #[derive(Task, Serialize, Deserialize, Debug)]
-    /// struct CompanyInfo {
-    ///     #[task(instruction = "Extract the company name")]
-    ///     pub name: String,
-    ///     #[task(instruction = "Extract the founding year as a number")]
-    ///     pub founded: u32,
-    ///     #[task(instruction = "Extract the industry or sector")]
-    ///     pub industry: String,
-    ///     #[task(instruction = "Extract the headquarters location")]
-    ///     pub headquarters: Option<String>,
-    ///     #[task(instruction = "Extract the CEO or founder name")]
-    ///     pub ceo: Option<String>,
-    /// }
+    /// # Returns
     ///
-    /// #[tokio::main]
-    /// async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
-    ///     let llm = OpenAILLM::new(
-    ///         "https://api.openai.com/v1",
-    ///         "your-api-key",
-    ///         "gpt-4"
-    ///     )?;
+    /// The `MetricsSink` configured on the provider, a `NoopSink` by default
+    fn get_metrics_sink(&self) -> &dyn MetricsSink {
+        &NoopSink
+    }
+
+    /// Returns how JSON output is requested from the provider.
     ///
-    ///     let task = CompanyInfo::new();
-    ///     let additional_instructions = vec![
-    ///         "Be precise with dates and numbers".to_string(),
-    ///         "Use null for missing information".to_string(),
-    ///     ];
+    /// # Returns
     ///
-    ///     let input = "Apple Inc. was founded in 1976 by Steve Jobs. The company is headquartered in Cupertino, California and operates in the technology sector.";
-    ///     
-    ///     // Each field will be extracted concurrently
-    ///     let result: CompanyInfo = llm.async_fields_generate_data(&task, input, &additional_instructions).await?;
+    /// The `JsonMode` configured on the provider, `JsonModeStrategy::Native` by default
+    fn get_json_mode(&self) -> &JsonMode {
+        static NATIVE: JsonMode = JsonMode::new(JsonModeStrategy::Native);
+        &NATIVE
+    }
+
+    /// Returns how throttled requests are retried.
     ///
-    ///     println!("Extracted company info: {:#?}", result);
-    ///     Ok(())
-    /// }
-    /// ```
+    /// # Returns
     ///
-    /// # Performance Considerations
+    /// The `RetryPolicy` configured on the provider, `RetryPolicy::NONE` by default
+    fn get_retry_policy(&self) -> RetryPolicy {
+        RetryPolicy::NONE
+    }
+
+    /// Returns extra JSON deep-merged into every request body.
     ///
-    /// - **Concurrent execution**: All field extractions happen simultaneously
-    /// - **API calls**: Makes one API call per field, which may increase costs but improve accuracy
-    /// - **Memory efficient**: Uses async tasks instead of OS threads
-    /// - **Early termination**: Stops all remaining requests if any field extraction fails
+    /// # Returns
     ///
-    /// # Errors
+    /// The extra body configured on the provider, `None` by default
+    fn get_extra_body(&self) -> Option<&Value> {
+        None
+    }
+
+    /// Returns the queue that admits the provider's requests by priority.
     ///
-    /// Returns `SecretaryError` if the final assembly of fields into the struct `T` fails.
-    /// This is particularly useful for catching `FieldDeserializationError`.
-    async fn async_fields_generate_data<T: Task + Sync + Send>(
-        &self,
-        task: &T,
-        target: &str,
-        additional_instructions: &Vec<String>,
-    ) -> Result<T, Box<dyn std::error::Error + Send + Sync + 'static>> {
-        let messages: Vec<(String, Message)> =
-            task.make_dstributed_generation_prompts(target, additional_instructions);
+    /// # Returns
+    ///
+    /// The `RequestQueue` configured on the provider, `None` by default
+    fn get_request_queue(&self) -> Option<&RequestQueue> {
+        None
+    }
 
-        let mut distributed_tasks = Vec::new();
+    /// Returns the partitioner that splits the provider's requests between tenants.
+    ///
+    /// # Returns
+    ///
+    /// The `QuotaPartitioner` configured on the provider, `None` by default
+    fn get_quota_partitioner(&self) -> Option<&QuotaPartitioner> {
+        None
+    }
 
-        for (field_name, message) in messages {
-            let task_future = async move {
-                let content: String = extract_text_content_from_llm_response(
-                    &self.async_send_message(message, false).await?,
-                )?;
+    /// Returns the guardrail run over every target before it is sent.
+    ///
+    /// # Returns
+    ///
+    /// The `Guardrail` configured on the provider, `None` by default
+    fn get_guardrail(&self) -> Option<&Guardrail> {
+        None
+    }
 
-                Ok::<(String, String), Box<dyn std::error::Error + Send + Sync>>((
-                    field_name,
-                    extract_result_content(&cleanup_thinking_blocks(content)),
-                ))
-            };
+    /// Returns the limits on the size of the model's output.
+    ///
+    /// # Returns
+    ///
+    /// The `OutputLimits` configured on the provider, `None` by default
+    fn get_output_limits(&self) -> Option<&OutputLimits> {
+        None
+    }
 
-            distributed_tasks.push(task_future);
-        }
+    /// Returns how responses that are not clean UTF-8 JSON are handled, see the `decoding`
+    /// module.
+    ///
+    /// # Returns
+    ///
+    /// The `DecodingPolicy` configured on the provider, `DecodingPolicy::Repair` by default
+    fn get_decoding_policy(&self) -> DecodingPolicy {
+        DecodingPolicy::Repair
+    }
 
-        let distributed_tasks_results: Result<
-            Vec<(String, String)>,
-            Box<dyn std::error::Error + Send + Sync + 'static>,
-        > = future::try_join_all(distributed_tasks).await;
+    /// Returns what happens to keys of the model's JSON that are not fields of the Task, see
+    /// the `trimming` module.
+    ///
+    /// # Returns
+    ///
+    /// The `UnknownKeys` policy configured on the provider, `UnknownKeys::Keep` by default
+    fn get_unknown_keys(&self) -> UnknownKeys {
+        UnknownKeys::Keep
+    }
 
-        let distributed_tasks_results: Vec<(String, String)> = distributed_tasks_results?;
+    /// Returns what happens to answers of `one_of` fields that match no allowed value, see
+    /// the `vocabulary` module.
+    ///
+    /// # Returns
+    ///
+    /// The `OneOfPolicy` configured on the provider, `OneOfPolicy::Error` by default
+    fn get_one_of_policy(&self) -> OneOfPolicy {
+        OneOfPolicy::Error
+    }
 
-        // Use panic::catch_unwind to handle potential FieldDeserializationError panics from the macro
-        match panic::catch_unwind(|| generate_from_tuples!(T, distributed_tasks_results)) {
-            Ok(result) => Ok(result),
-            Err(panic_payload) => {
-                // Try to extract the FieldDeserializationError from the panic
-                if let Some(error_msg) = panic_payload.downcast_ref::<String>() {
-                    // Parse the error message to reconstruct the FieldDeserializationError
-                    if error_msg.contains("Failed to deserialize") {
-                        return Err(Box::new(SecretaryError::JsonParsingError(
-                            error_msg.clone(),
-                        )));
-                    }
-                }
-                // Fallback for other panics
-                Err(Box::new(SecretaryError::JsonParsingError(
-                    "Field deserialization failed with unknown error".to_string(),
-                )))
-            }
-        }
+    /// Returns the number and date conventions of the targets, see the `locale` module.
+    ///
+    /// # Returns
+    ///
+    /// The `ExtractionLocale` configured on the provider, `None` by default
+    fn get_locale(&self) -> Option<&ExtractionLocale> {
+        None
+    }
+
+    /// Returns the price of the model's prompt tokens, see the `estimate` module.
+    ///
+    /// # Returns
+    ///
+    /// The `Pricing` configured on the provider, `None` by default
+    fn get_pricing(&self) -> Option<Pricing> {
+        None
+    }
+
+    /// Returns the sink failed extractions are captured to, see the `deadletter` module.
+    ///
+    /// # Returns
+    ///
+    /// The `DeadLetterSink` configured on the provider, `None` by default
+    fn get_dead_letter_sink(&self) -> Option<&dyn DeadLetterSink> {
+        None
+    }
+
+    /// Returns whether the errors of failed extractions are wrapped in a `TracedError`, see
+    /// the `correlation` module.
+    ///
+    /// # Returns
+    ///
+    /// The value set with the provider's `with_traced_errors`, `false` by default
+    fn get_traced_errors(&self) -> bool {
+        false
+    }
+
+    /// Returns the request `health_check` probes the provider with.
+    ///
+    /// # Returns
+    ///
+    /// `HealthProbe::Chat` by default, a one-token chat completion
+    fn get_health_probe(&self) -> HealthProbe {
+        HealthProbe::Chat
+    }
+
+    /// Returns the URL that lists the provider's models, which
+    /// `ModelRegistry::refresh_from_api` reads, see the `llm_providers::models` module.
+    ///
+    /// # Returns
+    ///
+    /// The URL of an OpenAI-style `GET /models` route, `None` by default
+    fn get_models_url(&self) -> Option<String> {
+        None
+    }
+
+    /// Returns the provider the `Lazy` fields of extracted data request their values from,
+    /// see the `lazy` module.
+    ///
+    /// # Returns
+    ///
+    /// A shared copy of the provider, `None` by default, which leaves lazy fields unbound
+    fn get_lazy_source(&self) -> Option<Arc<dyn LazySource>> {
+        None
+    }
+
+    /// Returns the async HTTP client requests are sent with.
+    ///
+    /// # Returns
+    ///
+    /// The provider's pooled client, or a client shared by every provider that does not
+    /// own one
+    fn http_client(&self) -> &reqwest::Client {
+        SHARED_HTTP_CLIENTS.client()
+    }
+
+    /// Returns the blocking HTTP client requests are sent with.
+    ///
+    /// # Returns
+    ///
+    /// The provider's pooled client, or a client shared by every provider that does not
+    /// own one
+    fn blocking_http_client(&self) -> &reqwest::blocking::Client {
+        SHARED_HTTP_CLIENTS.blocking_client()
+    }
+
+    /// Returns the HTTP clients of the provider, whose compression settings apply to its
+    /// requests.
+    ///
+    /// # Returns
+    ///
+    /// The provider's `HttpClients`, or those shared by every provider that does not own any
+    fn get_http_clients(&self) -> &HttpClients {
+        &SHARED_HTTP_CLIENTS
+    }
+}
+
+/// The clients used by providers that do not own `HttpClients`.
+static SHARED_HTTP_CLIENTS: LazyLock<HttpClients> = LazyLock::new(HttpClients::default);
+
+/// The main `Task` trait for defining data extraction schemas and system prompts.
+///
+/// This trait should be implemented using the `#[derive(Task)]` macro for user-defined structs.
+/// It combines data model definition, system prompt generation, and serialization capabilities
+/// into a single, cohesive interface for LLM-based data extraction.
+///
+/// # Examples
+///
+/// ```rust
+/// use secretary::Task;
+/// use serde::{Serialize, Deserialize};
+///
+/// #[derive(Task, Serialize, Deserialize, Debug)]
+/// struct PersonInfo {
+///     #[task(instruction = "Extract the person's full name")]
+///     pub name: String,
+///     
+///     #[task(instruction = "Extract age as a number")]
+///     pub age: u32,
+///     
+///     #[task(instruction = "Extract email address if mentioned")]
+///     pub email: Option<String>,
+/// }
+///
+/// let task = PersonInfo::new();
+/// let system_prompt = task.get_system_prompt();
+/// println!("Generated prompt: {}", system_prompt);
+/// ```
+///
+/// # Derive Macro
+///
+/// The `#[derive(Task)]` macro automatically implements this trait and generates:
+/// - System prompts based on field instructions
+/// - JSON schema definitions
+/// - A `new()` constructor method
+///
+/// Use `#[task(instruction = "...")]` attributes on fields to provide extraction guidance.
+///
+/// # Generic Tasks
+///
+/// The derive supports type parameters, lifetimes and const generics. A field whose type is a
+/// type parameter is a nested Task when it has no instruction, and bounds the parameter by
+/// `Task`. With an instruction it is a plain value, and the parameter is bounded by
+/// `Default + Serialize + DeserializeOwned`. Collections of a type parameter are plain values.
+///
+/// ```rust
+/// use std::marker::PhantomData;
+///
+/// use secretary::Task;
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Task, Serialize, Deserialize, Debug)]
+/// struct Address {
+///     #[task(instruction = "Extract the city")]
+///     pub city: String,
+/// }
+///
+/// // A plain value payload
+/// #[derive(Task, Serialize, Deserialize, Debug)]
+/// struct Measurement<T> {
+///     #[task(instruction = "Extract the unit")]
+///     pub unit: String,
+///     #[task(instruction = "Extract the measured value")]
+///     pub value: T,
+///     #[task(instruction = "Extract every reading")]
+///     pub readings: Vec<T>,
+/// }
+///
+/// // A nested Task payload
+/// #[derive(Task, Serialize, Deserialize, Debug)]
+/// struct Labeled<T> {
+///     #[task(instruction = "Extract a short label")]
+///     pub label: String,
+///     pub payload: T,
+/// }
+///
+/// // Lifetimes, const generics and where clauses are carried over
+/// #[derive(Task, Serialize, Deserialize, Debug)]
+/// struct Bounded<'a, T, const N: usize>
+/// where
+///     T: Clone,
+/// {
+///     #[task(instruction = "Extract the value")]
+///     pub value: T,
+///     #[serde(skip)]
+///     #[task(instruction = "Unused")]
+///     pub marker: PhantomData<&'a ()>,
+/// }
+///
+/// let measurement: Measurement<f64> = Measurement::new();
+/// assert!(measurement.get_system_prompt().contains("value: Extract the measured value"));
+///
+/// let labeled: Labeled<Address> = Labeled::new();
+/// assert!(labeled.get_system_prompt().contains("city: Extract the city"));
+/// let paths: Vec<String> = labeled
+///     .get_distributed_field_prompts()
+///     .into_iter()
+///     .map(|prompt| prompt.field_path)
+///     .collect();
+/// assert_eq!(paths, vec!["label".to_string(), "payload.city".to_string()]);
+///
+/// let bounded: Bounded<u8, 4> = Bounded::new();
+/// assert_eq!(bounded.value, 0);
+/// ```
+///
+/// # Error Handling
+///
+/// Implementations of this trait should be mindful of potential deserialization errors.
+/// If the LLM returns data that does not match the expected schema (e.g., a string for a number field),
+/// a `SecretaryError::FieldDeserializationError` will be returned, detailing which fields failed.
+pub trait Task: Serialize + for<'de> Deserialize<'de> + Default {
+    /// Generates a system prompt for the LLM based on the struct's field instructions.
+    ///
+    /// This method creates a comprehensive prompt that includes:
+    /// - JSON structure specifications
+    /// - Field-specific extraction instructions
+    /// - Response format requirements
+    ///
+    /// # Returns
+    ///
+    /// A formatted string containing the complete system prompt.
+    fn get_system_prompt(&self) -> String;
+
+    /// Returns the system prompt without the fields at the given dotted paths.
+    ///
+    /// Used when local extractors have already filled some fields, see the `extractors`
+    /// module. The derive macro leaves the fields out of the field list and the JSON
+    /// template; the default returns the whole system prompt.
+    ///
+    /// # Arguments
+    ///
+    /// * `skipped_fields` - The dotted paths of the fields to leave out
+    fn get_system_prompt_without_fields(&self, _skipped_fields: &[String]) -> String {
+        self.get_system_prompt()
+    }
+
+    /// Returns the prompt and request settings of every field for distributed generation.
+    ///
+    /// This method is used by `fields_generate_data` and `async_fields_generate_data` to
+    /// generate each field's value independently. Nested Task fields are expanded into the
+    /// prompts of their own fields.
+    ///
+    /// # Returns
+    ///
+    /// A `Vec` of `FieldPrompt`s, one per field to extract.
+    fn get_distributed_field_prompts(&self) -> Vec<FieldPrompt>;
+
+    /// Returns the prompts of the requests of distributed generation: one per field, except
+    /// for the fields marked `#[task(group = "...")]`, which share one request per group.
+    ///
+    /// The request of a group lists its fields in `FieldPrompt::members`, see
+    /// `distributed::group_field_prompts`.
+    ///
+    /// # Returns
+    ///
+    /// A `Vec` of `FieldPrompt`s, one per request.
+    fn get_distributed_request_prompts(&self) -> Vec<FieldPrompt> {
+        group_field_prompts(self.get_distributed_field_prompts())
+    }
+
+    /// Returns a list of field names and their corresponding system prompts for distributed generation.
+    ///
+    /// This is a simplified view of `get_distributed_request_prompts` without the per-field
+    /// request settings. A group of fields has one entry, named after the group; its member
+    /// paths are listed by `get_distributed_request_prompts`.
+    ///
+    /// # Returns
+    ///
+    /// A `Vec` of tuples, where each tuple contains a field name and its system prompt.
+    fn get_system_prompts_for_distributed_generation(&self) -> Vec<(String, String)> {
+        self.get_distributed_request_prompts()
+            .into_iter()
+            .map(|field_prompt| (field_prompt.field_path, field_prompt.prompt))
+            .collect()
     }
+
+    /// Returns structured metadata describing each field of the Task.
+    ///
+    /// The derive macro generates this from the same information it uses to build prompts:
+    /// field names, Rust types, optionality, instructions and nested Task descriptors.
+    /// It is used by the `schema` module to fingerprint and compare schemas at runtime.
+    ///
+    /// # Returns
+    ///
+    /// A `Vec` of `FieldDescriptor`s in field declaration order.
+    fn field_descriptors() -> Vec<FieldDescriptor> {
+        Vec::new()
+    }
+
+    /// Returns whether the Task can be extracted field by field with distributed generation.
+    ///
+    /// The derive macro returns `false` for enums, whose fields depend on the variant the
+    /// model picks, see the `variants` module. Distributed generation of such Tasks fails with
+    /// `SecretaryError::UnsupportedGeneration`.
+    fn supports_distributed_generation() -> bool {
+        true
+    }
+
+    /// Binds the `Lazy` fields of extracted data, and those of its nested and optional Tasks,
+    /// to the extraction, so that they request their values on first access, see the `lazy`
+    /// module.
+    ///
+    /// The derive macro generates this for structs with `#[task(lazy)]` fields or nested
+    /// Tasks; the default binds nothing.
+    fn bind_lazy_fields(&mut self, _binding: &LazyBinding) {}
+
+    /// Checks that serde writes and reads the keys the prompt asks for, in this Task and its
+    /// nested Tasks, see the `schema_check` module.
+    ///
+    /// # Errors
+    ///
+    /// Returns every `SchemaMismatch` found, by schema path.
+    fn verify_schema_consistency() -> Result<(), Vec<SchemaMismatch>> {
+        let mut mismatches: Vec<SchemaMismatch> = schema_check::own_mismatches::<Self>();
+        mismatches.extend(Self::nested_schema_mismatches());
+
+        if mismatches.is_empty() {
+            Ok(())
+        } else {
+            Err(mismatches)
+        }
+    }
+
+    /// Returns the mismatches of the nested Tasks, with paths under their fields, see the
+    /// `schema_check` module.
+    ///
+    /// The derive macro generates this for structs with nested Tasks; the default has none.
+    fn nested_schema_mismatches() -> Vec<SchemaMismatch> {
+        Vec::new()
+    }
+
+    /// Returns the version of the layout `get_system_prompt` renders.
+    ///
+    /// The derive macro generates this from `#[task(prompt_version = N)]` on the struct, or
+    /// `prompt::DEFAULT_PROMPT_VERSION` when no version is pinned. The default only changes
+    /// when the crate intentionally changes the generated wording.
+    ///
+    /// # Returns
+    ///
+    /// The prompt layout version, recorded in `GenerationMetadata::prompt_version`.
+    fn prompt_version() -> u32 {
+        DEFAULT_PROMPT_VERSION
+    }
+
+    /// Returns the text placed before every prompt of this Task.
+    ///
+    /// The derive macro generates this from `#[task(preamble = "...")]` on the struct. The
+    /// system prompt, the compact system prompt and every distributed field prompt open with
+    /// it, see `prompt::frame_prompt`.
+    fn preamble() -> Option<&'static str> {
+        None
+    }
+
+    /// Returns the text placed after every prompt of this Task.
+    ///
+    /// The derive macro generates this from `#[task(postamble = "...")]` on the struct.
+    fn postamble() -> Option<&'static str> {
+        None
+    }
+
+    /// Returns the pretty-printed JSON template at the end of the system prompt.
+    ///
+    /// This is the Task itself, serialized. Structs deriving Task with
+    /// `#[task(empty_defaults)]` fill empty collections and `None` nested Tasks with one
+    /// example element here, so the template shows the element shape while the instance
+    /// stays empty. With `#[task(prompt_version = 3)]` every collection of nested Tasks
+    /// shows exactly one example element.
+    fn prompt_template(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+
+    /// Returns the number of characters of the system prompt known at compile time.
+    ///
+    /// The derive macro counts the lines the struct's own fields contribute: their names,
+    /// instructions and type hints, and the "Common mistakes to avoid" section of their
+    /// negative examples, as well as the preamble and postamble. Nested Tasks and the JSON template are only rendered at runtime and
+    /// are not counted. `#[task(max_prompt_chars = N)]` on the struct turns a count above `N`
+    /// into a compile error.
+    ///
+    /// # Returns
+    ///
+    /// The static prompt size in characters, `0` for Tasks not generated by the derive.
+    fn static_prompt_len() -> usize {
+        0
+    }
+
+    /// Renders the prompts of this Task once for repeated extractions.
+    ///
+    /// The returned `CompiledTask` can be passed to the generate methods in place of the
+    /// Task, see the `compiled` module.
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - The additional instructions to render into the prompts
+    fn compile(self, options: CompileOptions) -> CompiledTask<Self>
+    where
+        Self: Sized,
+    {
+        CompiledTask::new(self, options)
+    }
+
+    /// Create a prompt that will be sending to the LLM for generating a structural data
+    /// Creates a `Message` object for the LLM, combining the system prompt, user input, and additional instructions.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The natural language input to be processed.
+    /// * `additional_instructions` - A list of extra instructions to guide the LLM.
+    ///
+    /// # Returns
+    ///
+    /// A `Message` struct ready to be sent to the LLM.
+    fn make_prompt(&self, target: &str, additional_instructions: &[String]) -> Message {
+        Message::user(append_target(
+            self.get_system_prompt(),
+            additional_instructions,
+            target,
+        ))
+    }
+
+    /// Creates the messages sent to the LLM for generating structured data.
+    ///
+    /// The first message holds the system prompt and the additional instructions and does not
+    /// depend on the target, so it can be served from the provider's prompt cache. The target
+    /// follows in a separate message.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The natural language text to extract data from
+    /// * `additional_instructions` - Extra instructions to guide the extraction process
+    fn make_prompt_messages(
+        &self,
+        target: &str,
+        additional_instructions: &[String],
+    ) -> Vec<Message> {
+        make_prefixed_messages(self.get_system_prompt(), additional_instructions, target)
+    }
+
+    /// Returns a shortened system prompt listing only field paths and JSON types.
+    ///
+    /// Used by adaptive generation when the full system prompt does not fit the model's
+    /// context window. The JSON template is minified instead of pretty-printed.
+    ///
+    /// # Returns
+    ///
+    /// A formatted string containing the compact system prompt.
+    fn get_compact_system_prompt(&self) -> String {
+        // Lazy fields are left out like in the full system prompt
+        let fields: Vec<FieldDescriptor> = Self::field_descriptors();
+        let lazy_fields: Vec<String> = with_lazy_fields(&[], &fields);
+        let mut template: Value = serde_json::to_value(self).unwrap_or_default();
+        for path in &lazy_fields {
+            remove_field_path(&mut template, path);
+        }
+        to_prompt_keys(&fields, &mut template);
+        let prompt: String = format!(
+            "{}{}",
+            format_compact_field_specification(&descriptors_without(&fields, &lazy_fields), ""),
+            serde_json::to_string(&template).unwrap_or_default()
+        );
+
+        frame_prompt(prompt, Self::preamble(), Self::postamble())
+    }
+
+    /// Creates a `Message` like `make_prompt`, but using the compact system prompt.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The natural language input to be processed.
+    /// * `additional_instructions` - A list of extra instructions to guide the LLM.
+    ///
+    /// # Returns
+    ///
+    /// A `Message` struct ready to be sent to the LLM.
+    fn make_compact_prompt(&self, target: &str, additional_instructions: &[String]) -> Message {
+        Message::user(append_target(
+            self.get_compact_system_prompt(),
+            additional_instructions,
+            target,
+        ))
+    }
+
+    /// Creates the messages of `make_prompt_messages` with the compact system prompt.
+    fn make_compact_prompt_messages(
+        &self,
+        target: &str,
+        additional_instructions: &[String],
+    ) -> Vec<Message> {
+        make_prefixed_messages(
+            self.get_compact_system_prompt(),
+            additional_instructions,
+            target,
+        )
+    }
+
+    /// Create a prompt that will be sending to the LLM for generating a structural data
+    fn make_dstributed_generation_prompts(
+        &self,
+        target: &str,
+        additional_instructions: &[String],
+    ) -> Vec<(String, Message)> {
+        self.make_distributed_generation_requests(target, additional_instructions)
+            .into_iter()
+            .map(|(field_prompt, message)| (field_prompt.field_path, message))
+            .collect()
+    }
+
+    /// Creates one `Message` per field for distributed generation, paired with the field's
+    /// `FieldPrompt` so its request settings can be applied when sending.
+    ///
+    /// A field's `extra_instruction` is added to that field's message only, ahead of the
+    /// additional instructions shared by all fields. Fields of the same group share one
+    /// message, see `get_distributed_request_prompts`.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The natural language input to be processed.
+    /// * `additional_instructions` - A list of extra instructions to guide the LLM.
+    ///
+    /// # Returns
+    ///
+    /// A `Vec` of field prompts and their messages.
+    fn make_distributed_generation_requests(
+        &self,
+        target: &str,
+        additional_instructions: &[String],
+    ) -> Vec<(FieldPrompt, Message)> {
+        // Formatted once for every field rather than per field
+        let instructions: String = format_additional_instructions(additional_instructions);
+        self.get_distributed_request_prompts()
+            .into_iter()
+            .map(|field_prompt| {
+                let message: Message =
+                    make_field_message(&field_prompt, None, target, &instructions);
+                (field_prompt, message)
+            })
+            .collect()
+    }
+
+    /// Creates distributed generation requests for the fields of `self` that are still empty.
+    ///
+    /// A field is empty when it is `null`, an empty string or collection, or equal to its
+    /// value in `Self::default()`. Fields marked `#[task(always_refresh)]` are requested even
+    /// when they have a value. Every request shows the values that are already known as
+    /// context, without the fields being requested.
+    /// The empty fields of a group share one request.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The natural language input to be processed.
+    /// * `additional_instructions` - A list of extra instructions to guide the LLM.
+    ///
+    /// # Returns
+    ///
+    /// A `Vec` of field prompts and their messages, empty when every field is filled.
+    fn make_update_requests(
+        &self,
+        target: &str,
+        additional_instructions: &[String],
+    ) -> Vec<(FieldPrompt, Message)> {
+        let existing: Value = serde_json::to_value(self).unwrap_or(Value::Null);
+        let defaults: Value = serde_json::to_value(Self::default()).unwrap_or(Value::Null);
+
+        let missing: Vec<FieldPrompt> = self
+            .get_distributed_field_prompts()
+            .into_iter()
+            .filter(|field_prompt| {
+                field_prompt.always_refresh
+                    || is_unfilled(
+                        get_field_path(&existing, &field_prompt.field_path),
+                        get_field_path(&defaults, &field_prompt.field_path),
+                    )
+            })
+            .collect();
+
+        let mut known_values: Value = existing.clone();
+        for field_prompt in &missing {
+            remove_field_path(&mut known_values, &field_prompt.field_path);
+        }
+        let known_values: String = serde_json::to_string_pretty(&known_values).unwrap_or_default();
+        let instructions: String = format_additional_instructions(additional_instructions);
+
+        group_field_prompts(missing)
+            .into_iter()
+            .map(|field_prompt| {
+                let message: Message =
+                    make_field_message(&field_prompt, Some(&known_values), target, &instructions);
+                (field_prompt, message)
+            })
+            .collect()
+    }
+}
+
+/// Trait for synchronous data generation from LLMs.
+///
+/// This trait provides methods for extracting structured data from natural language text
+/// using LLM providers. It includes both standard JSON mode generation and force generation
+/// for reasoning models that don't support JSON mode.
+///
+/// # Examples
+///
+/// ```no_run
+/// use secretary::Task;
+/// use secretary::llm_providers::openai::OpenAILLM;
+/// use secretary::traits::GenerateData;
+/// use serde::{Serialize, Deserialize};
+///
+/// #[derive(Task, Serialize, Deserialize, Debug)]
+/// struct ProductInfo {
+///     #[task(instruction = "Extract the product name")]
+///     pub name: String,
+///     #[task(instruction = "Extract price as a number")]
+///     pub price: f64,
+/// }
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+/// let llm = OpenAILLM::new(
+///     "https://api.openai.com/v1",
+///     "your-api-key",
+///     "gpt-4"
+/// )?;
+///
+/// let task = ProductInfo::new();
+/// let additional_instructions = vec!["Be precise with pricing".to_string()];
+///
+/// let input = "Apple MacBook Pro 16-inch costs $2,499";
+/// let result: ProductInfo = llm.generate_data(&task, input, &additional_instructions)?;
+///
+/// println!("Extracted: {:#?}", result);
+/// # Ok(())
+/// # }
+/// ```
+pub trait GenerateData
+where
+    Self: IsLLM + Sync,
+{
+    /// Generates structured data from natural language in the given mode.
+    ///
+    /// The single entry point for the ways an extraction can be sent, see the `mode` module.
+    /// It sends the same requests as the method named after the mode.
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - A Task, or a `CompiledTask` of one, that provides the system prompt and schema
+    /// * `target` - The natural language text to extract data from
+    /// * `additional_instructions` - Extra instructions to guide the extraction process
+    /// * `mode` - How the extraction is sent, with the options of that mode
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `generate_data`, `force_generate_data` or
+    /// `fields_generate_data`, depending on the mode.
+    fn generate<T: Task>(
+        &self,
+        task: &impl ExtractionPlan<Task = T>,
+        target: &str,
+        additional_instructions: impl Into<InstructionSet>,
+        mode: GenerationMode,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        match mode {
+            GenerationMode::JsonMode => self.generate_data_with_options(
+                task,
+                target,
+                additional_instructions,
+                &mode.request_options(),
+            ),
+            GenerationMode::Force => {
+                self.force_generate_data(task, target, additional_instructions)
+            }
+            GenerationMode::Fields { .. } => self.fields_generate_data_with_options(
+                task,
+                target,
+                additional_instructions,
+                &mode.request_options(),
+            ),
+        }
+    }
+
+    /// Estimates the requests, prompt tokens, cost and latency of an extraction in the given
+    /// mode, without sending anything, see the `estimate` module.
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - A Task, or a `CompiledTask` of one, that provides the prompts
+    /// * `target` - The natural language text the extraction would read
+    /// * `additional_instructions` - Extra instructions to guide the extraction process
+    /// * `mode` - How the extraction would be sent, with the options of that mode
+    ///
+    /// # Returns
+    ///
+    /// An `ExtractionEstimate` with every request the mode would send
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when a local extractor of the Task fails on the target.
+    fn plan<T: Task>(
+        &self,
+        task: &impl ExtractionPlan<Task = T>,
+        target: &str,
+        additional_instructions: impl Into<InstructionSet>,
+        mode: GenerationMode,
+    ) -> Result<ExtractionEstimate, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let additional_instructions: &Vec<String> = &additional_instructions.into().into_vec();
+        let local_values: Vec<(String, String)> =
+            extract_local_fields(&task.field_table(), target)?;
+        let single_request = |messages: Vec<Message>| RequestEstimate {
+            label: "task".to_string(),
+            prompt_tokens: self
+                .get_system_role_strategy()
+                .apply(messages)
+                .iter()
+                .map(|message| estimate_tokens(message.content.as_str()))
+                .sum(),
+        };
+
+        let requests: Vec<RequestEstimate> = match mode {
+            GenerationMode::JsonMode => vec![single_request(task.prompt_messages_without_fields(
+                target,
+                additional_instructions,
+                &local_paths(&local_values),
+            ))],
+            GenerationMode::Force => vec![single_request(vec![
+                task.single_prompt(target, additional_instructions),
+            ])],
+            GenerationMode::Fields { .. } => without_local_fields(
+                task.field_requests(target, additional_instructions),
+                &local_values,
+            )
+            .into_iter()
+            .map(|(field_prompt, message)| RequestEstimate {
+                label: field_prompt.field_path,
+                prompt_tokens: estimate_tokens(message.content.as_str()),
+            })
+            .collect(),
+        };
+
+        Ok(ExtractionEstimate::new(mode, requests, self.get_pricing()))
+    }
+
+    /// Generates structured data from natural language using JSON mode.
+    ///
+    /// This method uses the LLM's JSON mode (if available) to ensure structured output.
+    /// For models without JSON mode support, use `force_generate_data` instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - A Task, or a `CompiledTask` of one, that provides the system prompt and schema
+    /// * `target` - The natural language text to extract data from
+    /// * `additional_instructions` - Extra instructions to guide the extraction process
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the extracted data as the specified type T
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - The LLM API call fails
+    /// - The response cannot be parsed as valid JSON
+    /// - The JSON doesn't match the expected schema, potentially returning a `FieldDeserializationError`.
+    fn generate_data<T: Task>(
+        &self,
+        task: &impl ExtractionPlan<Task = T>,
+        target: &str,
+        additional_instructions: impl Into<InstructionSet>,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        self.generate(
+            task,
+            target,
+            additional_instructions,
+            GenerationMode::JsonMode,
+        )
+    }
+
+    /// Generates structured data like `generate_data`, with request settings for this call.
+    ///
+    /// The options are applied last, so their temperature and extra body win over the
+    /// provider's `with_extra_body` values.
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - A Task, or a `CompiledTask` of one, that provides the system prompt and schema
+    /// * `target` - The natural language text to extract data from
+    /// * `additional_instructions` - Extra instructions to guide the extraction process
+    /// * `options` - Request settings such as the temperature or extra body parameters
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use secretary::Task;
+    /// use secretary::llm_providers::openai::OpenAILLM;
+    /// use secretary::request::RequestOptions;
+    /// use secretary::traits::GenerateData;
+    /// use serde::{Deserialize, Serialize};
+    /// use serde_json::json;
+    ///
+    /// #[derive(Task, Serialize, Deserialize, Debug)]
+    /// struct Person {
+    ///     #[task(instruction = "Extract the name")]
+    ///     pub name: String,
+    /// }
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    /// // An OpenRouter-style routing block set on the provider is passed through
+    /// let llm = OpenAILLM::new("https://openrouter.ai/api/v1", "your-api-key", "openai/gpt-4o-mini")?
+    ///     .with_extra_body(json!({
+    ///         "provider": {"order": ["openai", "azure"], "allow_fallbacks": false},
+    ///         "reasoning_effort": "low"
+    ///     }));
+    ///
+    /// // Per-call values win over the provider's, and the messages cannot be replaced
+    /// let options = RequestOptions::default().with_extra_body(json!({
+    ///     "provider": {"allow_fallbacks": true},
+    ///     "reasoning_effort": "high"
+    /// }));
+    /// let person: Person = llm.generate_data_with_options(&Person::new(), "Ada", &vec![], &options)?;
+    /// println!("{:?}", person);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `generate_data`.
+    fn generate_data_with_options<T: Task>(
+        &self,
+        task: &impl ExtractionPlan<Task = T>,
+        target: &str,
+        additional_instructions: impl Into<InstructionSet>,
+        options: &RequestOptions,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let additional_instructions: &Vec<String> = &additional_instructions.into().into_vec();
+        let options: &RequestOptions = &traced_options(options);
+        let mut provider_request_id: Option<String> = None;
+        let result = (|| -> Result<T, Box<dyn std::error::Error + Send + Sync + 'static>> {
+            let guarded: GuardedRequest = guard_request(self, target, additional_instructions)?;
+            let task = &CallPlan::new(task, options, guarded.instructions())?
+                .with_locale(extraction_locale(self, options));
+            let local_values: Vec<(String, String)> =
+                extract_local_fields(&task.field_table(), &guarded.text)?;
+            let options: &RequestOptions =
+                &with_task_grammar(self, options, task, &local_paths(&local_values));
+            let response: ResponseEnvelope = self.send_messages_envelope(
+                frame_for_format(
+                    task,
+                    task.prompt_messages_without_fields(
+                        &guarded.target,
+                        guarded.instructions(),
+                        &local_paths(&local_values),
+                    ),
+                    options.response_format,
+                ),
+                options.response_format.is_json(),
+                &with_stream_fields(options, &task.field_table()),
+            )?;
+            provider_request_id = response.provider_request_id();
+            let request: String = response.body;
+
+            let (content, _) = trim_content(
+                self,
+                options,
+                task,
+                merge_hint_values(
+                    merge_local_values(
+                        extract_formatted_content(self, task, &request, options.response_format)?,
+                        &local_values,
+                    ),
+                    &options.hints,
+                )
+                .0,
+            );
+            let (content, _) = normalize_content(self, options, task, content)?;
+            let content: String = localize_content(self, options, task, content);
+            let (result, _) = limit_content(self, options, content)?;
+
+            #[cfg(feature = "schema-validation")]
+            if let Some(validation) = options.schema_validation {
+                check_content::<T>(&result, self.get_leniency(), validation)?;
+            }
+
+            parse_answer_content(self, task, &result, options)
+                .map(|data| bind_lazy(self, data, &guarded, options))
+        })();
+
+        capture_failure(
+            self,
+            MetricMode::Json,
+            target,
+            additional_instructions,
+            request_id(options),
+            provider_request_id,
+            result,
+        )
+    }
+
+    /// Generates structured data from natural language without JSON mode (for reasoning models).
+    ///
+    /// This method is designed for reasoning models like o1, deepseek, and others that don't
+    /// support JSON mode. It uses text parsing to extract JSON from the model's response.
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - A Task, or a `CompiledTask` of one, that provides the system prompt and schema
+    /// * `target` - The natural language text to extract data from
+    /// * `additional_instructions` - Extra instructions to guide the extraction process
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the extracted data as the specified type T
+    ///
+    /// # Errors
+    ///
+    /// Returns `SecretaryError` if the LLM response cannot be parsed into the target struct `T`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use secretary::Task;
+    /// # use secretary::llm_providers::openai::OpenAILLM;
+    /// # use secretary::traits::GenerateData;
+    /// # use serde::{Serialize, Deserialize};
+    /// #
+    /// # #[derive(Task, Serialize, Deserialize, Debug)]
+    /// # struct ProductInfo {
+    /// #     #[task(instruction = "Extract the product name")]
+    /// #     pub name: String,
+    /// #     #[task(instruction = "Extract price as a number")]
+    /// #     pub price: f64,
+    /// # }
+    /// #
+    /// # fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    /// // For reasoning models like o1-preview
+    /// let llm = OpenAILLM::new(
+    ///     "https://api.openai.com/v1",
+    ///     "your-api-key",
+    ///     "o1-preview"  // Reasoning model without JSON mode
+    /// )?;
+    ///
+    /// let task = ProductInfo::new();
+    /// let additional_instructions = vec!["Think step by step".to_string()];
+    ///
+    /// let input = "Apple MacBook Pro 16-inch costs $2,499";
+    /// let result: ProductInfo = llm.force_generate_data(&task, input, &additional_instructions)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn force_generate_data<T: Task>(
+        &self,
+        task: &impl ExtractionPlan<Task = T>,
+        target: &str,
+        additional_instructions: impl Into<InstructionSet>,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        self.force_generate_data_with_options(
+            task,
+            target,
+            additional_instructions,
+            &RequestOptions::default(),
+        )
+    }
+
+    /// Generates structured data like `force_generate_data`, with request settings for this
+    /// call.
+    ///
+    /// With `RequestOptions::with_response_format`, the answer is looked for in the code
+    /// blocks and document markers of the format, see the `response_format` module.
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - A Task, or a `CompiledTask` of one, that provides the system prompt and schema
+    /// * `target` - The natural language text to extract data from
+    /// * `additional_instructions` - Extra instructions to guide the extraction process
+    /// * `options` - Request settings such as the temperature or the response format
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `force_generate_data`.
+    fn force_generate_data_with_options<T: Task>(
+        &self,
+        task: &impl ExtractionPlan<Task = T>,
+        target: &str,
+        additional_instructions: impl Into<InstructionSet>,
+        options: &RequestOptions,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let additional_instructions: &Vec<String> = &additional_instructions.into().into_vec();
+        let options: &RequestOptions = &traced_options(options);
+        let mut provider_request_id: Option<String> = None;
+        let result = (|| -> Result<T, Box<dyn std::error::Error + Send + Sync + 'static>> {
+            if options.strict_schema {
+                schema_check::check_once::<T>()?;
+            }
+            let guarded: GuardedRequest = guard_request(self, target, additional_instructions)?;
+            let response: ResponseEnvelope = self.send_messages_envelope(
+                frame_for_format(
+                    task,
+                    vec![task.single_prompt(&guarded.target, guarded.instructions())],
+                    options.response_format,
+                ),
+                false,
+                options,
+            )?;
+            provider_request_id = response.provider_request_id();
+
+            let content: String =
+                extract_formatted_content(self, task, &response.body, options.response_format)?;
+            let (result, _) = limit_content(self, options, content)?;
+
+            match parse_task_from_answer(&result, self.get_leniency()) {
+                Ok(result) => Ok(bind_lazy(
+                    self,
+                    limit_data(self, result)?,
+                    &guarded,
+                    options,
+                )),
+                Err(error) => {
+                    record_parse_failed::<T>(self.get_metrics_sink(), MetricMode::Force, None);
+                    Err(Box::new(error))
+                }
+            }
+        })();
+
+        capture_failure(
+            self,
+            MetricMode::Force,
+            target,
+            additional_instructions,
+            request_id(options),
+            provider_request_id,
+            result,
+        )
+    }
+
+    /// Generates structured data, or a review item when the response cannot be fully parsed.
+    ///
+    /// Works like `generate_data`, but a response that does not deserialize into `T` is
+    /// returned as a `ReviewItem` holding the recovered fields and per-field flags, instead of
+    /// an error. Failures to reach the LLM are still returned as errors.
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - A Task, or a `CompiledTask` of one, that provides the system prompt and schema
+    /// * `target` - The natural language text to extract data from
+    /// * `additional_instructions` - Extra instructions to guide the extraction process
+    ///
+    /// # Returns
+    ///
+    /// `Either::Left` with the extracted data, or `Either::Right` with a `ReviewItem`
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use secretary::Task;
+    /// # use secretary::llm_providers::openai::OpenAILLM;
+    /// # use secretary::review::Either;
+    /// # use secretary::traits::GenerateData;
+    /// # use serde::{Serialize, Deserialize};
+    /// #
+    /// # #[derive(Task, Serialize, Deserialize, Debug)]
+    /// # struct ProductInfo {
+    /// #     #[task(instruction = "Extract the product name")]
+    /// #     pub name: String,
+    /// #     #[task(instruction = "Extract price as a number")]
+    /// #     pub price: f64,
+    /// # }
+    /// #
+    /// # fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    /// let llm = OpenAILLM::new("https://api.openai.com/v1", "your-api-key", "gpt-4o")?;
+    ///
+    /// let input = "Apple MacBook Pro 16-inch, price on request";
+    /// match llm.generate_data_or_review(&ProductInfo::new(), input, &vec![])? {
+    ///     Either::Left(product) => println!("Extracted: {:?}", product),
+    ///     Either::Right(item) => println!("Needs review: {}", serde_json::to_string(&item)?),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn generate_data_or_review<T: Task>(
+        &self,
+        task: &impl ExtractionPlan<Task = T>,
+        target: &str,
+        additional_instructions: impl Into<InstructionSet>,
+    ) -> Result<Either<T, ReviewItem<T>>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let additional_instructions: &Vec<String> = &additional_instructions.into().into_vec();
+        let guarded: GuardedRequest = guard_request(self, target, additional_instructions)?;
+        let response: String = self.send_messages_with_options(
+            task.prompt_messages(&guarded.target, guarded.instructions()),
+            true,
+            &RequestOptions::default(),
+        )?;
+
+        let (result, _) = limit_content(
+            self,
+            &RequestOptions::default(),
+            extract_json_content(self, &response)?,
+        )?;
+
+        match self.get_leniency().from_str::<T>(&result) {
+            Ok(result) => Ok(Either::Left(result)),
+            Err(error) => {
+                record_parse_failed::<T>(self.get_metrics_sink(), MetricMode::Json, None);
+                Ok(Either::Right(ReviewItem::from_error(
+                    target, &result, &error,
+                )))
+            }
+        }
+    }
+
+    /// Generates structured data together with the source text each field came from.
+    ///
+    /// The model answers every field with its value and a short verbatim quote from the
+    /// target, see the `provenance` module. The quotes are located in `target` and returned
+    /// next to the data; quotes that cannot be found are flagged with `QuoteMatch::NotFound`.
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - A Task, or a `CompiledTask` of one, that provides the system prompt and schema
+    /// * `target` - The natural language text to extract data from
+    /// * `additional_instructions` - Extra instructions to guide the extraction process
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `generate_data`.
+    fn generate_data_with_provenance<T: Task>(
+        &self,
+        task: &impl ExtractionPlan<Task = T>,
+        target: &str,
+        additional_instructions: impl Into<InstructionSet>,
+    ) -> Result<ProvenanceResult<T>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let additional_instructions: &Vec<String> = &additional_instructions.into().into_vec();
+        let guarded: GuardedRequest = guard_request(self, target, additional_instructions)?;
+        let response: String = self.send_messages_with_options(
+            make_prefixed_messages(
+                provenance_system_prompt(task.task()),
+                guarded.instructions(),
+                &guarded.target,
+            ),
+            true,
+            &RequestOptions::default(),
+        )?;
+
+        let (result, _) = limit_content(
+            self,
+            &RequestOptions::default(),
+            extract_json_content(self, &response)?,
+        )?;
+
+        parse_provenance_content::<Self, T>(self, &result, target)
+    }
+
+    /// Asks the model why a field of extracted data has its value, see the `explanation`
+    /// module.
+    ///
+    /// One request sends the target, the field's instruction and its value, and the model
+    /// justifies the value with quotes from the target and says whether it looks wrong.
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - A Task, or a `CompiledTask` of one, whose descriptors the path is checked
+    ///   against
+    /// * `target` - The natural language text the data was extracted from
+    /// * `extracted` - The extracted data
+    /// * `field_path` - The path of the field, e.g. `terms.auto_renews` or `items[0].price`
+    ///
+    /// # Errors
+    ///
+    /// Returns `SecretaryError::InvalidFieldPath` if the path names no field of the Task or
+    /// no value of the data, and otherwise the errors of the request and of parsing its
+    /// answer.
+    fn explain_field<T: Task>(
+        &self,
+        task: &impl ExtractionPlan<Task = T>,
+        target: &str,
+        extracted: &T,
+        field_path: &str,
+    ) -> Result<Explanation, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let request: ExplanationRequest =
+            ExplanationRequest::new(&task.field_table(), extracted, field_path)?;
+        let additional_instructions: &Vec<String> = &Vec::new();
+        let guarded: GuardedRequest = guard_request(self, target, additional_instructions)?;
+        let response: String = self.send_messages_with_options(
+            request.messages(&guarded.target, guarded.instructions()),
+            true,
+            &RequestOptions::default(),
+        )?;
+
+        parse_explanation_content(
+            self,
+            &request,
+            extract_json_content(self, &response)?,
+            target,
+        )
+    }
+
+    /// Asks the model why each field of extracted data that is not at its default has its
+    /// value, like `explain_field`.
+    ///
+    /// The fields are those of `explanation::explainable_paths`, explained from at most
+    /// `concurrency` threads at once.
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - A Task, or a `CompiledTask` of one, that provides the descriptors
+    /// * `target` - The natural language text the data was extracted from
+    /// * `extracted` - The extracted data
+    /// * `concurrency` - The number of requests in flight, at least 1
+    ///
+    /// # Returns
+    ///
+    /// The explanation of each field, in the order of the descriptors
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error of the requests and of parsing their answers.
+    fn explain_all<T: Task>(
+        &self,
+        task: &impl ExtractionPlan<Task = T>,
+        target: &str,
+        extracted: &T,
+        concurrency: usize,
+    ) -> Result<Vec<Explanation>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let fields: Cow<'_, [FieldDescriptor]> = task.field_table();
+        let requests: Vec<ExplanationRequest> = explainable_paths(&fields, extracted)
+            .iter()
+            .map(|path| ExplanationRequest::new(&fields, extracted, path))
+            .collect::<Result<_, _>>()?;
+        let additional_instructions: &Vec<String> = &Vec::new();
+        let guarded: GuardedRequest = guard_request(self, target, additional_instructions)?;
+        let messages: Vec<Vec<Message>> = requests
+            .iter()
+            .map(|request| request.messages(&guarded.target, guarded.instructions()))
+            .collect();
+
+        let contents: Vec<String> = send_chunk_requests(self, messages, concurrency, None)?;
+        requests
+            .iter()
+            .zip(contents)
+            .map(|(request, content)| parse_explanation_content(self, request, content, target))
+            .collect()
+    }
+
+    /// Generates structured data from a target read from a reader.
+    ///
+    /// The target is read with `input::read_text` and the default `InputOptions`, then
+    /// extracted with `generate_data`. To use a different size limit, read the target with
+    /// `input::read_text` yourself.
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - A Task, or a `CompiledTask` of one, that provides the system prompt and schema
+    /// * `reader` - The reader the natural language text is read from
+    /// * `additional_instructions` - Extra instructions to guide the extraction process
+    ///
+    /// # Errors
+    ///
+    /// Returns `SecretaryError::InputRead`, `SecretaryError::InputDecode` or
+    /// `SecretaryError::InputTooLarge` if the target cannot be read, and otherwise the same
+    /// errors as `generate_data`.
+    fn generate_data_from_reader<T: Task, R: std::io::Read>(
+        &self,
+        task: &impl ExtractionPlan<Task = T>,
+        reader: R,
+        additional_instructions: impl Into<InstructionSet>,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let target: String = read_text(reader, &InputOptions::default())?;
+
+        self.generate_data(task, &target, additional_instructions)
+    }
+
+    /// Generates structured data from a target read from a file.
+    ///
+    /// The file is read like in `generate_data_from_reader`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `generate_data_from_reader`.
+    fn generate_data_from_path<T: Task, P: AsRef<std::path::Path>>(
+        &self,
+        task: &impl ExtractionPlan<Task = T>,
+        path: P,
+        additional_instructions: impl Into<InstructionSet>,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let target: String = read_path(path, &InputOptions::default())?;
+
+        self.generate_data(task, &target, additional_instructions)
+    }
+
+    /// Generates structured data with a prompt shaped to fit the model's context window.
+    ///
+    /// Uses the provider's declared `max_context_tokens` to pick the richest request shape
+    /// that fits: the full prompt, a compact prompt with field names and types only, or
+    /// per-field distributed requests. Without a declared limit this behaves like
+    /// `generate_data`. The chosen strategy is reported in the result metadata.
+    ///
+    /// When a single request is used, fields marked `#[task(importance = "critical")]` are
+    /// extracted again with a request of their own, whose values replace those of the single
+    /// request. They are listed in `GenerationMetadata::per_field_requests`.
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - A Task, or a `CompiledTask` of one, that provides the system prompt and schema
+    /// * `target` - The natural language text to extract data from
+    /// * `additional_instructions` - Extra instructions to guide the extraction process
+    ///
+    /// # Returns
+    ///
+    /// A `GenerationResult` containing the extracted data and the chosen `PromptStrategy`
+    ///
+    /// # Errors
+    ///
+    /// Returns `SecretaryError::ContextTooSmall` if no request shape fits, in addition to the
+    /// errors of the underlying generation method.
+    fn generate_data_adaptive<T: Task>(
+        &self,
+        task: &impl ExtractionPlan<Task = T>,
+        target: &str,
+        additional_instructions: impl Into<InstructionSet>,
+    ) -> Result<GenerationResult<T>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        self.generate_data_adaptive_with_options(
+            task,
+            target,
+            additional_instructions,
+            &RequestOptions::default(),
+        )
+    }
+
+    /// Generates structured data like `generate_data_adaptive`, with request settings for
+    /// this call.
+    ///
+    /// The options apply to every request of the extraction, including the per-field requests
+    /// of distributed generation and of critical fields. The seed of the options and the
+    /// `system_fingerprint` the provider reported are recorded in the metadata, with a
+    /// `GenerationWarning` when the seed appears to have been ignored, see the
+    /// `reproducibility` module.
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - A Task, or a `CompiledTask` of one, that provides the system prompt and schema
+    /// * `target` - The natural language text to extract data from
+    /// * `additional_instructions` - Extra instructions to guide the extraction process
+    /// * `options` - Request settings such as the seed or the temperature
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `generate_data_adaptive`.
+    fn generate_data_adaptive_with_options<T: Task>(
+        &self,
+        task: &impl ExtractionPlan<Task = T>,
+        target: &str,
+        additional_instructions: impl Into<InstructionSet>,
+        options: &RequestOptions,
+    ) -> Result<GenerationResult<T>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let instructions: InstructionSet = additional_instructions.into();
+        let options: &RequestOptions = &traced_options(options);
+        let additional_instructions: &Vec<String> = &instructions.to_vec();
+        let guarded: GuardedRequest = guard_request(self, target, additional_instructions)?;
+        let task = &CallPlan::new(task, options, guarded.instructions())?
+            .with_locale(extraction_locale(self, options));
+        let (strategy, estimated_prompt_tokens) = choose_prompt_strategy(
+            task.task(),
+            &guarded.target,
+            guarded.instructions(),
+            self.get_capabilities().max_context_tokens,
+        )?;
+
+        let mut rate_limit: Option<RateLimitInfo> = None;
+        let mut provider_request_id: Option<String> = None;
+        let mut cached_prompt_tokens: Option<u64> = None;
+        let mut usage: Option<TokenUsage> = None;
+        let mut reasoning: Option<String> = None;
+        let mut repaired_sequences: usize = 0;
+        let mut fingerprints: Vec<String> = Vec::new();
+        let mut per_field_requests: Vec<String> = Vec::new();
+        let mut clipped_values: Vec<ClippedValue> = Vec::new();
+        let mut trimmed_keys: Vec<TrimmedKey> = Vec::new();
+        let mut normalized_values: Vec<NormalizedValue> = Vec::new();
+        let mut hint_conflicts: Vec<HintConflict> = Vec::new();
+        let local_values: Vec<(String, String)> =
+            extract_local_fields(&task.field_table(), &guarded.text)?;
+        let data: T = match strategy {
+            PromptStrategy::Full | PromptStrategy::Compact => {
+                let messages: Vec<Message> = if strategy == PromptStrategy::Full {
+                    task.prompt_messages_without_fields(
+                        &guarded.target,
+                        guarded.instructions(),
+                        &local_paths(&local_values),
+                    )
+                } else {
+                    task.compact_prompt_messages(&guarded.target, guarded.instructions())
+                };
+                let response: ResponseEnvelope = self.send_messages_envelope(
+                    messages,
+                    true,
+                    &with_stream_fields(options, &task.field_table()),
+                )?;
+                rate_limit = response.rate_limit();
+                provider_request_id = response.provider_request_id();
+                cached_prompt_tokens = extract_cached_tokens_from_llm_response(&response.body);
+                usage = TokenUsage::from_response(&response.body);
+                reasoning = reported_reasoning(options, &response.body);
+                fingerprints.extend(extract_system_fingerprint_from_llm_response(&response.body));
+
+                let content: String = strip_inline_reasoning(
+                    self.extract_response_content(&response.body)?,
+                    &response.body,
+                );
+                let (content, repaired) = self.get_decoding_policy().repair_content(content);
+                repaired_sequences = response.repaired_sequences + repaired;
+                let (content, conflicts) =
+                    merge_hint_values(merge_local_values(content, &local_values), &options.hints);
+                hint_conflicts = conflicts;
+                let (content, trimmed) = trim_content(self, options, task, content);
+                trimmed_keys = trimmed;
+                let (content, normalized) = normalize_content(self, options, task, content)?;
+                normalized_values.extend(normalized);
+                let content: String = localize_content(self, options, task, content);
+                let (result, clipped) = limit_content(self, options, content)?;
+                clipped_values.extend(clipped);
+                let critical_requests: Vec<(FieldPrompt, Message)> = without_hinted_fields(
+                    without_local_fields(
+                        critical_field_requests(task, &guarded.target, guarded.instructions()),
+                        &local_values,
+                    ),
+                    &options.hints,
+                );
+                if critical_requests.is_empty() {
+                    parse_json_content::<Self, T>(self, &result)?
+                } else {
+                    per_field_requests = critical_requests
+                        .iter()
+                        .flat_map(|(field_prompt, _)| field_prompt.field_paths())
+                        .collect();
+                    let results: Vec<(String, String)> =
+                        send_field_requests(self, critical_requests, options)?
+                            .collect_fingerprints(&mut fingerprints)
+                            .require_complete()?;
+                    let (data, clipped, normalized) =
+                        overlay_field_results::<Self, T>(self, options, &result, results)?;
+                    clipped_values.extend(clipped);
+                    normalized_values.extend(normalized);
+                    data
+                }
+            }
+            PromptStrategy::Distributed => {
+                require_distributed_generation::<T>()?;
+                let messages: Vec<(FieldPrompt, Message)> = without_hinted_fields(
+                    without_local_fields(
+                        task.field_requests(&guarded.target, guarded.instructions()),
+                        &local_values,
+                    ),
+                    &options.hints,
+                );
+                let results: Vec<(String, String)> = send_field_requests(self, messages, options)?
+                    .collect_fingerprints(&mut fingerprints)
+                    .require_complete()?;
+                let (data, clipped, normalized) = fields_from_results::<Self, T>(
+                    self,
+                    options,
+                    &task.field_table(),
+                    results,
+                    &local_values,
+                )?;
+                clipped_values.extend(clipped);
+                normalized_values.extend(normalized);
+                data
+            }
+        };
+
+        let language_violations: Vec<LanguageViolation> =
+            language_violations(&task.field_table(), &data, &guarded.text);
+        let order_violations: Vec<OrderViolation> = order_violations(
+            &task.field_table(),
+            &data,
+            &guarded.text,
+            options.order_check,
+        )?;
+
+        Ok(GenerationResult {
+            data,
+            metadata: GenerationMetadata {
+                prompt_strategy: Some(strategy),
+                estimated_prompt_tokens: Some(estimated_prompt_tokens),
+                rate_limit,
+                prompt_version: Some(T::prompt_version()),
+                cached_prompt_tokens,
+                per_field_requests,
+                locally_extracted: local_paths(&local_values),
+                hint_conflicts,
+                injection_verdict: guarded.verdict,
+                seed: options.seed,
+                system_fingerprint: fingerprints.first().cloned(),
+                locale: extraction_locale(self, options).cloned(),
+                warnings: [
+                    GenerationWarning::for_fingerprints(options.seed, &fingerprints),
+                    GenerationWarning::for_instructions(&instructions),
+                    GenerationWarning::for_trimmed_keys(trimmed_keys),
+                ]
+                .concat(),
+                language_violations,
+                voted_choices: None,
+                field_agreement: BTreeMap::new(),
+                regeneration_path: None,
+                changed_ratio: None,
+                repaired_sequences,
+                clipped_values,
+                normalized_values,
+                order_violations,
+                request_id: options.request_id.clone(),
+                provider_request_id,
+                usage,
+                reasoning,
+                routed_model: None,
+                chunk_sources: BTreeMap::new(),
+                merge_conflicts: Vec::new(),
+            },
+        })
+    }
+
+    /// Generates structured data by majority vote over several samples of the same prompt.
+    ///
+    /// Sends a single request asking for `k` choices and votes, field by field, on the
+    /// choices that parse, see the `consistency` module. The metadata holds the number of
+    /// samples voted on and the agreement of each field, with
+    /// `GenerationWarning::ChoicesIgnored` when fewer than `k` samples came back.
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - A Task, or a `CompiledTask` of one, that provides the system prompt and schema
+    /// * `target` - The natural language text to extract data from
+    /// * `additional_instructions` - Extra instructions to guide the extraction process
+    /// * `k` - The number of samples to vote on
+    /// * `options` - Request settings, which must set a temperature above 0
+    ///
+    /// # Errors
+    ///
+    /// Returns `SecretaryError::BuildRequestError` if `k` is 0 or the options do not set a
+    /// temperature above 0, and the parse error of the first choice if no choice parses.
+    /// Otherwise returns the same errors as `generate_data_with_options`.
+    fn generate_data_consistent<T: Task>(
+        &self,
+        task: &impl ExtractionPlan<Task = T>,
+        target: &str,
+        additional_instructions: impl Into<InstructionSet>,
+        k: usize,
+        options: &RequestOptions,
+    ) -> Result<GenerationResult<T>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        check_sampling(k, options)?;
+        let additional_instructions: &Vec<String> = &additional_instructions.into().into_vec();
+        let guarded: GuardedRequest = guard_request(self, target, additional_instructions)?;
+        let task = &CallPlan::new(task, options, guarded.instructions())?
+            .with_locale(extraction_locale(self, options));
+        let local_values: Vec<(String, String)> =
+            extract_local_fields(&task.field_table(), &guarded.text)?;
+        let response: String = self.send_messages_with_options(
+            task.prompt_messages_without_fields(
+                &guarded.target,
+                guarded.instructions(),
+                &local_paths(&local_values),
+            ),
+            true,
+            &options.clone().with_choices(k),
+        )?;
+
+        vote_on_choices(
+            self,
+            task,
+            options,
+            &local_values,
+            &response,
+            k,
+            guarded.verdict,
+        )
+    }
+
+    /// Re-extracts an edited target from the result extracted from its previous version.
+    ///
+    /// The two versions are compared line by line. An unchanged target returns the previous
+    /// result without a request; when at most `options.max_changed_ratio` of the lines
+    /// changed, a single request sends the previous result and the changed excerpts only,
+    /// and the model returns the updated result; larger changes are extracted in full like
+    /// `generate_data`. The metadata holds the path taken and the share of changed lines.
+    /// See the `incremental` module.
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - A Task, or a `CompiledTask` of one, that provides the system prompt and schema
+    /// * `old_target` - The previous version of the text, which `previous` was extracted from
+    /// * `new_target` - The current version of the text
+    /// * `previous` - The result extracted from `old_target`
+    /// * `additional_instructions` - Extra instructions to guide the extraction process
+    /// * `options` - The largest share of changed lines extracted incrementally and the
+    ///   context sent around each change
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `generate_data`.
+    fn regenerate_data<T: Task>(
+        &self,
+        task: &impl ExtractionPlan<Task = T>,
+        old_target: &str,
+        new_target: &str,
+        previous: &T,
+        additional_instructions: impl Into<InstructionSet>,
+        options: &RegenerateOptions,
+    ) -> Result<GenerationResult<T>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let diff: TextDiff = diff_lines(old_target, new_target);
+        let path: RegenerationPath = options.choose_path(&diff);
+        let (data, injection_verdict): (T, Option<InjectionVerdict>) = match path {
+            RegenerationPath::Unchanged => (
+                serde_json::from_value(serde_json::to_value(previous)?)?,
+                None,
+            ),
+            RegenerationPath::Full => (
+                self.generate_data(task, new_target, additional_instructions)?,
+                None,
+            ),
+            RegenerationPath::Incremental => {
+                let additional_instructions: &Vec<String> =
+                    &additional_instructions.into().into_vec();
+                let excerpts: String = diff.excerpts(options.context_lines);
+                let guarded: GuardedRequest =
+                    guard_request(self, &excerpts, additional_instructions)?;
+                let response: String = self.send_messages_with_options(
+                    task.prompt_messages(
+                        &make_incremental_target(previous, &guarded.target),
+                        guarded.instructions(),
+                    ),
+                    true,
+                    &RequestOptions::default(),
+                )?;
+                let (content, _) = limit_content(
+                    self,
+                    &RequestOptions::default(),
+                    extract_json_content(self, &response)?,
+                )?;
+                (parse_plan_content(self, task, &content)?, guarded.verdict)
+            }
+        };
+
+        Ok(GenerationResult {
+            data,
+            metadata: regeneration_metadata::<T>(path, &diff, injection_verdict),
+        })
+    }
+
+    /// Generates structured data from a target larger than the context window.
+    ///
+    /// The target is split into overlapping chunks, each chunk is extracted with a request of
+    /// its own, with at most `options.concurrency` requests in flight, and the results are
+    /// merged according to `options.merge_policy`. The metadata reports the chunks each
+    /// field's value came from and the fields the chunks disagreed on. See the `chunking`
+    /// module.
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - A Task, or a `CompiledTask` of one, that provides the system prompt and schema
+    /// * `target` - The natural language text to extract data from
+    /// * `additional_instructions` - Extra instructions to guide the extraction process
+    /// * `options` - The chunk size, overlap, concurrency, merge policy and confidence
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error of the chunk requests, or the error of the merge.
+    fn generate_data_chunked<T: Task>(
+        &self,
+        task: &impl ExtractionPlan<Task = T>,
+        target: &str,
+        additional_instructions: impl Into<InstructionSet>,
+        options: &ChunkOptions,
+    ) -> Result<GenerationResult<T>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let progress: Option<Progress> = options.progress.as_ref().map(Progress::started);
+        let chunks: Vec<String> = options.split(target);
+        if chunks.len() <= 1 {
+            report(progress.as_ref(), || ProgressKind::ChunkStarted {
+                index: 0,
+                total: 1,
+            });
+            let data: T = self.generate_data_with_options(
+                task,
+                target,
+                additional_instructions,
+                &chunk_request_options(progress.as_ref()),
+            )?;
+            report_single_chunk_finished(progress.as_ref());
+            return Ok(GenerationResult {
+                data,
+                metadata: GenerationMetadata::default(),
+            });
+        }
+
+        let additional_instructions: &Vec<String> = &additional_instructions.into().into_vec();
+        let guarded: GuardedRequest = guard_request(self, target, additional_instructions)?;
+        let texts: Vec<String> = options.split(&guarded.text);
+        let instructions: Vec<String> = options.chunk_instructions(guarded.instructions());
+        let requests: Vec<Vec<Message>> = texts
+            .iter()
+            .map(|chunk| task.prompt_messages(&guarded.wrap(chunk), &instructions))
+            .collect();
+        let contents: Vec<String> =
+            send_chunk_requests(self, requests, options.concurrency, progress.as_ref())?;
+        let (results, chunks) = parse_chunk_results::<Self, T>(self, contents, texts, options)?;
+        report(progress.as_ref(), || ProgressKind::MergeStarted {
+            chunks: chunks.len(),
+        });
+        let mut merge: ChunkMerge = merge_chunk_values(
+            &serde_json::to_value(T::default())?,
+            &chunks,
+            options.merge_policy,
+        );
+
+        let data: T = match options.merge_policy {
+            MergePolicy::Llm => {
+                merge.conflicts.clear();
+                let response: String = self.send_messages_with_options(
+                    make_reduce_prompt(task.task(), &results, additional_instructions),
+                    true,
+                    &RequestOptions::default(),
+                )?;
+                let (content, _) = limit_content(
+                    self,
+                    &RequestOptions::default(),
+                    extract_json_content(self, &response)?,
+                )?;
+                parse_json_content::<Self, T>(self, &content)?
+            }
+            MergePolicy::Adjudicate if !merge.conflicts.is_empty() => {
+                let response: String = self.send_messages_with_options(
+                    make_adjudication_prompt(
+                        task.task(),
+                        &merge.conflicts,
+                        additional_instructions,
+                    ),
+                    true,
+                    &RequestOptions::default(),
+                )?;
+                let (content, _) = limit_content(
+                    self,
+                    &RequestOptions::default(),
+                    extract_json_content(self, &response)?,
+                )?;
+                apply_adjudication(&mut merge, &serde_json::from_str::<Value>(&content)?);
+                merged_data(&merge)?
+            }
+            _ => merged_data(&merge)?,
+        };
+        report(progress.as_ref(), || ProgressKind::Completed);
+
+        Ok(chunked_result(data, merge))
+    }
+
+    /// Generates structured data by breaking down the task into individual field requests.
+    ///
+    /// Instead of generating a complete JSON object in a single request, this method breaks
+    /// the task down into individual field extractions. Each field is processed in parallel
+    /// using separate threads, and the results are combined into the final structured object.
+    /// This approach can improve accuracy for complex extractions and provides better error
+    /// isolation per field.
+    ///
+    /// # Benefits
+    ///
+    /// - **Improved accuracy**: Each field gets focused attention from the LLM
+    /// - **Parallel processing**: Multiple fields extracted simultaneously using threads
+    /// - **Error isolation**: Failure in one field doesn't affect others
+    /// - **Reasoning model support**: Works well with models that don't support JSON mode
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - A Task, or a `CompiledTask` of one, that provides field-specific prompts
+    /// * `target` - The natural language text to extract data from
+    /// * `additional_instructions` - Extra instructions to guide the extraction process
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the extracted data as the specified type T
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use secretary::Task;
+    /// use secretary::llm_providers::openai::OpenAILLM;
+    /// use secretary::traits::GenerateData;
+    /// use serde::{Serialize, Deserialize};
+    ///
+    /// #[derive(Task, Serialize, Deserialize, Debug)]
+    /// struct PersonProfile {
+    ///     #[task(instruction = "Extract the person's full name")]
+    ///     pub name: String,
+    ///     #[task(instruction = "Extract age as a number")]
+    ///     pub age: u32,
+    ///     #[task(instruction = "Extract email address if mentioned")]
+    ///     pub email: Option<String>,
+    ///     #[task(instruction = "Extract job title or profession")]
+    ///     pub profession: Option<String>,
+    /// }
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    /// let llm = OpenAILLM::new(
+    ///     "https://api.openai.com/v1",
+    ///     "your-api-key",
+    ///     "gpt-4"
+    /// )?;
+    ///
+    /// let task = PersonProfile::new();
+    /// let additional_instructions = vec![
+    ///     "Be precise with personal information".to_string(),
+    ///     "Use null for missing information".to_string(),
+    /// ];
+    ///
+    /// let input = "John Smith is a 35-year-old software engineer. You can reach him at john.smith@email.com";
+    ///
+    /// // Each field will be extracted in parallel
+    /// let result: PersonProfile = llm.fields_generate_data(&task, input, &additional_instructions)?;
+    ///
+    /// println!("Extracted profile: {:#?}", result);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Performance Considerations
+    ///
+    /// - **Thread overhead**: Creates one thread per field, so best for structs with moderate field counts
+    /// - **API calls**: Makes one API call per field, which may increase costs but improve accuracy
+    /// - **Parallel execution**: Faster than sequential field extraction for multi-field structs
+    ///
+    /// # Errors
+    ///
+    /// Returns `SecretaryError` if the final assembly of fields into the struct `T` fails.
+    /// This is particularly useful for catching `FieldDeserializationError`.
+    fn fields_generate_data<T: Task>(
+        &self,
+        task: &impl ExtractionPlan<Task = T>,
+        target: &str,
+        additional_instructions: impl Into<InstructionSet>,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        self.generate(
+            task,
+            target,
+            additional_instructions,
+            GenerationMode::Fields { concurrency: None },
+        )
+    }
+
+    /// Generates structured data field by field like `fields_generate_data`, with request
+    /// settings for this call.
+    ///
+    /// The options apply to every field's request; a field's own `temperature` attribute
+    /// takes precedence. With a deadline, no field is sent after it passes and requests still
+    /// running time out at it.
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - A Task, or a `CompiledTask` of one, that provides the distributed prompts
+    /// * `target` - The natural language text to extract data from
+    /// * `additional_instructions` - Extra instructions to guide the extraction process
+    /// * `options` - Request settings such as extra body parameters or a deadline
+    ///
+    /// # Errors
+    ///
+    /// Returns `SecretaryError::DeadlineExceeded` with the incomplete fields if the deadline
+    /// passes, and otherwise the same errors as `fields_generate_data`.
+    fn fields_generate_data_with_options<T: Task>(
+        &self,
+        task: &impl ExtractionPlan<Task = T>,
+        target: &str,
+        additional_instructions: impl Into<InstructionSet>,
+        options: &RequestOptions,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let additional_instructions: &Vec<String> = &additional_instructions.into().into_vec();
+        let options: &RequestOptions = &traced_options(options);
+        let result = (|| -> Result<T, Box<dyn std::error::Error + Send + Sync + 'static>> {
+            require_distributed_generation::<T>()?;
+            let guarded: GuardedRequest = guard_request(self, target, additional_instructions)?;
+            let task = &CallPlan::new(task, options, guarded.instructions())?
+                .with_locale(extraction_locale(self, options));
+            let local_values: Vec<(String, String)> =
+                extract_local_fields(&task.field_table(), &guarded.text)?;
+            let messages: Vec<(FieldPrompt, Message)> = without_hinted_fields(
+                without_local_fields(
+                    task.field_requests(&guarded.target, guarded.instructions()),
+                    &local_values,
+                ),
+                &options.hints,
+            );
+
+            let distributed_tasks_results: Vec<(String, String)> =
+                send_field_requests(self, messages, options)?.require_complete()?;
+
+            let (data, _, _) = fields_from_results::<Self, T>(
+                self,
+                options,
+                &task.field_table(),
+                distributed_tasks_results,
+                &local_values,
+            )?;
+            report(options.progress.as_ref(), || ProgressKind::Completed);
+
+            Ok(bind_lazy(self, data, &guarded, options))
+        })();
+
+        capture_failure(
+            self,
+            MetricMode::Distributed,
+            target,
+            additional_instructions,
+            request_id(options),
+            None,
+            result,
+        )
+    }
+
+    /// Generates structured data field by field like `fields_generate_data`, and returns a
+    /// trace of every field's request with it.
+    ///
+    /// The trace holds each field's prompt, raw response, result content, parsed value and
+    /// latency. It can be serialized and replayed with `trace::assemble_from_trace`, see the
+    /// `trace` module.
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - A Task, or a `CompiledTask` of one, that provides the distributed prompts
+    /// * `target` - The natural language text to extract data from
+    /// * `additional_instructions` - Extra instructions to guide the extraction process
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `fields_generate_data`.
+    fn fields_generate_data_traced<T: Task>(
+        &self,
+        task: &impl ExtractionPlan<Task = T>,
+        target: &str,
+        additional_instructions: impl Into<InstructionSet>,
+    ) -> Result<(T, FieldTrace), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let additional_instructions: &Vec<String> = &additional_instructions.into().into_vec();
+        require_distributed_generation::<T>()?;
+        let guarded: GuardedRequest = guard_request(self, target, additional_instructions)?;
+        let local_values: Vec<(String, String)> =
+            extract_local_fields(&task.field_table(), &guarded.text)?;
+        let messages: Vec<(FieldPrompt, Message)> = without_local_fields(
+            task.field_requests(&guarded.target, guarded.instructions()),
+            &local_values,
+        );
+
+        let answers: Vec<FieldAnswer> =
+            send_field_requests(self, messages, &RequestOptions::default())?
+                .require_complete_answers()?;
+        let trace: FieldTrace = trace_answers(
+            answers,
+            &task.field_table(),
+            &local_values,
+            self.get_leniency(),
+        );
+
+        let (data, _, _) = fields_from_results::<Self, T>(
+            self,
+            &RequestOptions::default(),
+            &task.field_table(),
+            trace.results(),
+            &local_values,
+        )?;
+
+        Ok((data, trace))
+    }
+
+    /// Generates structured data like `generate_data`, but replaces the fields that fail to
+    /// deserialize with their defaults instead of failing the whole extraction.
+    ///
+    /// Failing fields are located down to the deepest nested path, see the `partial` module.
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - A Task, or a `CompiledTask` of one, that provides the system prompt and schema
+    /// * `target` - The natural language text to extract data from
+    /// * `additional_instructions` - Extra instructions to guide the extraction process
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the data and the paths that were replaced by their defaults
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails, `SecretaryError::JsonParsingError` if the response
+    /// is not JSON, or `SecretaryError::FieldDeserializationError` if the data does not
+    /// deserialize even after substitution.
+    fn generate_partial_data<T: Task>(
+        &self,
+        task: &impl ExtractionPlan<Task = T>,
+        target: &str,
+        additional_instructions: impl Into<InstructionSet>,
+    ) -> Result<PartialData<T>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let additional_instructions: &Vec<String> = &additional_instructions.into().into_vec();
+        let guarded: GuardedRequest = guard_request(self, target, additional_instructions)?;
+        let request: String = self.send_messages_with_options(
+            task.prompt_messages(&guarded.target, guarded.instructions()),
+            true,
+            &RequestOptions::default(),
+        )?;
+
+        let (result, _) = limit_content(
+            self,
+            &RequestOptions::default(),
+            extract_json_content(self, &request)?,
+        )?;
+
+        let mut value: Value = match serde_json::from_str(&result) {
+            Ok(value) => value,
+            Err(error) => {
+                record_parse_failed::<T>(self.get_metrics_sink(), MetricMode::Json, None);
+                return Err(Box::new(json_parsing_error(error, &result)));
+            }
+        };
+        self.get_leniency().apply::<T>(&mut value);
+
+        partial_from_value(self, MetricMode::Json, &value, Vec::new(), Vec::new())
+    }
+
+    /// Generates structured data field by field like `fields_generate_data`, but replaces the
+    /// fields that fail to deserialize with their defaults instead of failing.
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - A Task, or a `CompiledTask` of one, that provides the distributed prompts
+    /// * `target` - The natural language text to extract data from
+    /// * `additional_instructions` - Extra instructions to guide the extraction process
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the data and the paths that were replaced by their defaults
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a request fails, or `SecretaryError::FieldDeserializationError`
+    /// if the data does not deserialize even after substitution.
+    fn fields_generate_partial_data<T: Task>(
+        &self,
+        task: &impl ExtractionPlan<Task = T>,
+        target: &str,
+        additional_instructions: impl Into<InstructionSet>,
+    ) -> Result<PartialData<T>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        self.fields_generate_partial_data_with_options(
+            task,
+            target,
+            additional_instructions,
+            &RequestOptions::default(),
+        )
+    }
+
+    /// Generates structured data field by field like `fields_generate_partial_data`, with
+    /// request settings for this call.
+    ///
+    /// Fields that do not complete before the deadline of `options` are left at their
+    /// defaults and listed in `PartialData::incomplete_fields` instead of failing the call.
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - A Task, or a `CompiledTask` of one, that provides the distributed prompts
+    /// * `target` - The natural language text to extract data from
+    /// * `additional_instructions` - Extra instructions to guide the extraction process
+    /// * `options` - Request settings such as extra body parameters or a deadline
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `fields_generate_partial_data`.
+    fn fields_generate_partial_data_with_options<T: Task>(
+        &self,
+        task: &impl ExtractionPlan<Task = T>,
+        target: &str,
+        additional_instructions: impl Into<InstructionSet>,
+        options: &RequestOptions,
+    ) -> Result<PartialData<T>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let additional_instructions: &Vec<String> = &additional_instructions.into().into_vec();
+        require_distributed_generation::<T>()?;
+        let guarded: GuardedRequest = guard_request(self, target, additional_instructions)?;
+        let task = &CallPlan::new(task, options, guarded.instructions())?
+            .with_locale(extraction_locale(self, options));
+        let messages: Vec<(FieldPrompt, Message)> = without_hinted_fields(
+            task.field_requests(&guarded.target, guarded.instructions()),
+            &options.hints,
+        );
+
+        let results: FieldResults = send_field_requests(self, messages, options)?;
+
+        let mut value: Value = collect_field_results(
+            self.get_leniency(),
+            &task.field_table(),
+            &results.completed(),
+        )?;
+        normalize_one_of(
+            &task.field_table(),
+            &mut value,
+            one_of_policy(self, options),
+        )?;
+        set_hint_values(&mut value, &options.hints);
+        output_limits(self, options).enforce(&mut value)?;
+
+        let refused: Vec<String> = results.refused();
+        partial_from_value(
+            self,
+            MetricMode::Distributed,
+            &value,
+            results.incomplete,
+            refused,
+        )
+    }
+
+    /// Fills in the empty fields of an existing struct, leaving the other fields as they are.
+    ///
+    /// Only the fields that `Task::make_update_requests` considers empty, plus any field marked
+    /// `#[task(always_refresh)]`, are requested, one request per field as in
+    /// `fields_generate_data`. The known values are included in every request as context. The
+    /// returned values are merged into a copy of `existing`; nothing else is changed. When every
+    /// field is filled, no request is sent.
+    ///
+    /// # Arguments
+    ///
+    /// * `existing` - The partially filled struct
+    /// * `target` - The natural language text to extract the missing data from
+    /// * `additional_instructions` - Extra instructions to guide the extraction process
+    ///
+    /// # Returns
+    ///
+    /// A Result containing a copy of `existing` with the missing fields filled in
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use secretary::Task;
+    /// use secretary::llm_providers::openai::OpenAILLM;
+    /// use secretary::traits::GenerateData;
+    /// use serde::{Deserialize, Serialize};
+    ///
+    /// #[derive(Task, Serialize, Deserialize, Debug, Clone)]
+    /// struct Contact {
+    ///     #[task(instruction = "Extract the name")]
+    ///     pub name: String,
+    ///     #[task(instruction = "Extract the email address")]
+    ///     pub email: Option<String>,
+    ///     #[task(instruction = "Extract the age as a number")]
+    ///     pub age: u32,
+    ///     #[task(instruction = "Summarize the latest interaction", always_refresh)]
+    ///     pub notes: String,
+    /// }
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    /// let llm = OpenAILLM::new("https://api.openai.com/v1", "your-api-key", "gpt-4o-mini")?;
+    /// let existing = Contact {
+    ///     name: "Ada Lovelace".to_string(),
+    ///     email: None,
+    ///     age: 0,
+    ///     notes: "Signed up".to_string(),
+    /// };
+    ///
+    /// // Only the missing fields and the always refreshed one are requested, with the known
+    /// // name as context
+    /// let updated: Contact = llm.update_data(&existing, "Ada (36, ada@example.com) asked about pricing", &vec![])?;
+    /// println!("{:?}", updated);
+    ///
+    /// // A filled struct only asks for the always refreshed field
+    /// let requests = updated.make_update_requests("Ada renewed her plan", &vec![]);
+    /// assert_eq!(requests.len(), 1);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a request fails, or `SecretaryError::SerdeJsonError` if the merged
+    /// data does not deserialize into `T`.
+    fn update_data<T: Task>(
+        &self,
+        existing: &T,
+        target: &str,
+        additional_instructions: impl Into<InstructionSet>,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let additional_instructions: &Vec<String> = &additional_instructions.into().into_vec();
+        require_distributed_generation::<T>()?;
+        let guarded: GuardedRequest = guard_request(self, target, additional_instructions)?;
+        let messages: Vec<(FieldPrompt, Message)> =
+            existing.make_update_requests(&guarded.target, guarded.instructions());
+
+        let distributed_tasks_results: Vec<(String, String)> =
+            send_field_requests(self, messages, &RequestOptions::default())?.require_complete()?;
+
+        merge_field_results(self, existing, distributed_tasks_results)
+    }
+
+    /// Extracts a `serde_json::Value` for a task known only at runtime, such as a
+    /// `TaskDefinition` loaded from YAML, using JSON mode like `generate_data`.
+    ///
+    /// The output is coerced to the task's fields with the provider's leniency profile, but
+    /// not checked against them; use `DynTask::json_schema` to validate it.
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - The task that provides the system prompt and fields
+    /// * `target` - The natural language text to extract data from
+    /// * `additional_instructions` - Extra instructions to guide the extraction process
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails, or `SecretaryError::JsonParsingError` if the
+    /// response is not valid JSON.
+    fn generate_value(
+        &self,
+        task: &dyn DynTask,
+        target: &str,
+        additional_instructions: impl Into<InstructionSet>,
+    ) -> Result<Value, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let additional_instructions: &Vec<String> = &additional_instructions.into().into_vec();
+        let guarded: GuardedRequest = guard_request(self, target, additional_instructions)?;
+        let request: String = self.send_messages_with_options(
+            make_value_prompt(task, &guarded.target, guarded.instructions()),
+            true,
+            &RequestOptions::default(),
+        )?;
+
+        let (result, _) = limit_content(
+            self,
+            &RequestOptions::default(),
+            extract_json_content(self, &request)?,
+        )?;
+
+        parse_value(self, task, &result)
+    }
+
+    /// Extracts a `serde_json::Value` for a task known only at runtime with one request per
+    /// field, like `fields_generate_data`.
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - The task that provides the distributed prompts and fields
+    /// * `target` - The natural language text to extract data from
+    /// * `additional_instructions` - Extra instructions to guide the extraction process
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a request fails, or `SecretaryError::InvalidFieldPath` if a field
+    /// path cannot be set.
+    fn fields_generate_value(
+        &self,
+        task: &dyn DynTask,
+        target: &str,
+        additional_instructions: impl Into<InstructionSet>,
+    ) -> Result<Value, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let additional_instructions: &Vec<String> = &additional_instructions.into().into_vec();
+        let guarded: GuardedRequest = guard_request(self, target, additional_instructions)?;
+        let messages: Vec<(FieldPrompt, Message)> =
+            make_value_field_requests(task, &guarded.target, guarded.instructions());
+
+        let distributed_tasks_results: Vec<(String, String)> =
+            send_field_requests(self, messages, &RequestOptions::default())?.require_complete()?;
+
+        assemble_field_values(self, task, distributed_tasks_results)
+    }
+}
+
+/// Defines a trait for asynchronous data generation with the given bounds on the Task, the
+/// values borrowed across awaits and the values moved into the futures.
+///
+/// `AsyncGenerateData` is defined with `Send` bounds, and `AsyncGenerateDataLocal` without
+/// them from the same methods.
+macro_rules! async_generate_data_trait {
+    (
+        $(#[$attribute:meta])*
+        pub trait $name:ident;
+        task: [$($task_bounds:tt)*],
+        shared: [$($shared_bounds:tt)*],
+        moved: [$($moved_bounds:tt)*],
+        dyn_task: $dyn_task:ty $(,)?
+    ) => {
+$(#[$attribute])*
+pub trait $name
+where
+    Self: IsLLM + Sync,
+{
+    /// Asynchronously generates structured data from natural language in the given mode.
+    ///
+    /// This is the asynchronous version of `GenerateData::generate`, see the `mode` module.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `async_generate_data`, `async_force_generate_data` or
+    /// `async_fields_generate_data`, depending on the mode.
+    async fn async_generate<T: Task $($task_bounds)*>(
+        &self,
+        task: &(impl ExtractionPlan<Task = T> $($shared_bounds)*),
+        target: &str,
+        additional_instructions: impl Into<InstructionSet> $($moved_bounds)*,
+        mode: GenerationMode,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        match mode {
+            GenerationMode::JsonMode => {
+                self.async_generate_data_with_options(
+                    task,
+                    target,
+                    additional_instructions,
+                    &mode.request_options(),
+                )
+                .await
+            }
+            GenerationMode::Force => {
+                self.async_force_generate_data(task, target, additional_instructions)
+                    .await
+            }
+            GenerationMode::Fields { .. } => {
+                self.async_fields_generate_data_with_options(
+                    task,
+                    target,
+                    additional_instructions,
+                    &mode.request_options(),
+                )
+                .await
+            }
+        }
+    }
+
+    /// Asynchronously generates structured data from natural language using JSON mode.
+    ///
+    /// This is the asynchronous version of `generate_data` that can be used in async contexts.
+    /// It uses the LLM's JSON mode (if available) to ensure structured output.
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - A Task, or a `CompiledTask` of one, that provides the system prompt and schema
+    /// * `target` - The natural language text to extract data from
+    /// * `additional_instructions` - Extra instructions to guide the extraction process
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the extracted data as the specified type T
+    ///
+    /// # Errors
+    ///
+    /// Returns `SecretaryError` if the LLM response cannot be parsed into the target struct `T`,
+    /// including `FieldDeserializationError` if specific fields fail.
+    async fn async_generate_data<T: Task $($task_bounds)*>(
+        &self,
+        task: &(impl ExtractionPlan<Task = T> $($shared_bounds)*),
+        target: &str,
+        additional_instructions: impl Into<InstructionSet> $($moved_bounds)*,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        self.async_generate(
+            task,
+            target,
+            additional_instructions,
+            GenerationMode::JsonMode,
+        )
+        .await
+    }
+
+    /// Asynchronously generates structured data with request settings for this call.
+    ///
+    /// This is the asynchronous version of `generate_data_with_options`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `async_generate_data`.
+    async fn async_generate_data_with_options<T: Task $($task_bounds)*>(
+        &self,
+        task: &(impl ExtractionPlan<Task = T> $($shared_bounds)*),
+        target: &str,
+        additional_instructions: impl Into<InstructionSet> $($moved_bounds)*,
+        options: &RequestOptions,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let additional_instructions: &Vec<String> = &additional_instructions.into().into_vec();
+        let options: &RequestOptions = &traced_options(options);
+        let mut provider_request_id: Option<String> = None;
+        let result: Result<T, Box<dyn std::error::Error + Send + Sync + 'static>> = async {
+            let guarded: GuardedRequest = guard_request(self, target, additional_instructions)?;
+            let task = &CallPlan::new(task, options, guarded.instructions())?
+                .with_locale(extraction_locale(self, options));
+            let local_values: Vec<(String, String)> =
+                extract_local_fields(&task.field_table(), &guarded.text)?;
+            let options: &RequestOptions =
+                &with_task_grammar(self, options, task, &local_paths(&local_values));
+            let request: Result<ResponseEnvelope, Box<dyn std::error::Error + Send + Sync>> = self
+                .async_send_messages_envelope(
+                    frame_for_format(
+                        task,
+                        task.prompt_messages_without_fields(
+                            &guarded.target,
+                            guarded.instructions(),
+                            &local_paths(&local_values),
+                        ),
+                        options.response_format,
+                    ),
+                    options.response_format.is_json(),
+                    &with_stream_fields(options, &task.field_table()),
+                )
+                .await;
+
+            let result = match request {
+                Ok(response) => {
+                    provider_request_id = response.provider_request_id();
+                    let (content, _) = trim_content(
+                        self,
+                        options,
+                        task,
+                        merge_hint_values(
+                            merge_local_values(
+                                extract_formatted_content(
+                                    self,
+                                    task,
+                                    &response.body,
+                                    options.response_format,
+                                )?,
+                                &local_values,
+                            ),
+                            &options.hints,
+                        )
+                        .0,
+                    );
+                    let (content, _) = normalize_content(self, options, task, content)?;
+                    let content: String = localize_content(self, options, task, content);
+                    limit_content(self, options, content)?.0
+                }
+                Err(error) => return Err(async_request_error(error)),
+            };
+
+            #[cfg(feature = "schema-validation")]
+            if let Some(validation) = options.schema_validation {
+                check_content::<T>(&result, self.get_leniency(), validation)?;
+            }
+
+            parse_answer_content(self, task, &result, options)
+                .map(|data| bind_lazy(self, data, &guarded, options))
+        }
+        .await;
+
+        capture_failure(
+            self,
+            MetricMode::Json,
+            target,
+            additional_instructions,
+            request_id(options),
+            provider_request_id,
+            result,
+        )
+    }
+
+    /// Asynchronously generates structured data from natural language without JSON mode (for reasoning models).
+    ///
+    /// This is the asynchronous version of `force_generate_data` designed for reasoning models
+    /// like o1, deepseek, and others that don't support JSON mode. It uses text parsing to
+    /// extract JSON from the model's response.
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - A Task, or a `CompiledTask` of one, that provides the system prompt and schema
+    /// * `target` - The natural language text to extract data from
+    /// * `additional_instructions` - Extra instructions to guide the extraction process
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the extracted data as the specified type T
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use secretary::Task;
+    /// # use secretary::llm_providers::openai::OpenAILLM;
+    /// # use secretary::traits::AsyncGenerateData;
+    /// # use serde::{Serialize, Deserialize};
+    /// #
+    /// # #[derive(Task, Serialize, Deserialize, Debug)]
+    /// # struct ProductInfo {
+    /// #     #[task(instruction = "Extract the product name")]
+    /// #     pub name: String,
+    /// #     #[task(instruction = "Extract price as a number")]
+    /// #     pub price: f64,
+    /// # }
+    /// #
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    /// // For reasoning models like o1-preview
+    /// let llm = OpenAILLM::new(
+    ///     "https://api.openai.com/v1",
+    ///     "your-api-key",
+    ///     "o1-preview"  // Reasoning model without JSON mode
+    /// )?;
+    ///
+    /// let task = ProductInfo::new();
+    /// let additional_instructions = vec!["Think step by step".to_string()];
+    ///
+    /// let input = "Apple MacBook Pro 16-inch costs $2,499";
+    /// let result: ProductInfo = llm.async_force_generate_data(&task, input, &additional_instructions).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns `SecretaryError` if the LLM response cannot be parsed into the target struct `T`.
+    async fn async_force_generate_data<T: Task $($task_bounds)*>(
+        &self,
+        task: &(impl ExtractionPlan<Task = T> $($shared_bounds)*),
+        target: &str,
+        additional_instructions: impl Into<InstructionSet> $($moved_bounds)*,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        self.async_force_generate_data_with_options(
+            task,
+            target,
+            additional_instructions,
+            &RequestOptions::default(),
+        )
+        .await
+    }
+
+    /// Asynchronously generates structured data like `async_force_generate_data`, with
+    /// request settings for this call.
+    ///
+    /// This is the asynchronous version of `force_generate_data_with_options`.
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - A Task, or a `CompiledTask` of one, that provides the system prompt and schema
+    /// * `target` - The natural language text to extract data from
+    /// * `additional_instructions` - Extra instructions to guide the extraction process
+    /// * `options` - Request settings such as the temperature or the response format
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `async_force_generate_data`.
+    async fn async_force_generate_data_with_options<T: Task $($task_bounds)*>(
+        &self,
+        task: &(impl ExtractionPlan<Task = T> $($shared_bounds)*),
+        target: &str,
+        additional_instructions: impl Into<InstructionSet> $($moved_bounds)*,
+        options: &RequestOptions,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let additional_instructions: &Vec<String> = &additional_instructions.into().into_vec();
+        let options: &RequestOptions = &traced_options(options);
+        let mut provider_request_id: Option<String> = None;
+        let result: Result<T, Box<dyn std::error::Error + Send + Sync + 'static>> = async {
+            if options.strict_schema {
+                schema_check::check_once::<T>()?;
+            }
+            let guarded: GuardedRequest = guard_request(self, target, additional_instructions)?;
+            let request: Result<ResponseEnvelope, Box<dyn std::error::Error + Send + Sync>> = self
+                .async_send_messages_envelope(
+                    frame_for_format(
+                        task,
+                        vec![task.single_prompt(&guarded.target, guarded.instructions())],
+                        options.response_format,
+                    ),
+                    false,
+                    options,
+                )
+                .await;
+
+            let result: String = match request {
+                Ok(response) => {
+                    provider_request_id = response.provider_request_id();
+                    extract_formatted_content(self, task, &response.body, options.response_format)?
+                }
+                Err(error) => {
+                    return Err(SecretaryError::BuildRequestError(error.to_string()).into());
+                }
+            };
+
+            match parse_task_from_answer(&result, self.get_leniency()) {
+                Ok(result) => Ok(bind_lazy(self, limit_data(self, result)?, &guarded, options)),
+                Err(error) => {
+                    record_parse_failed::<T>(self.get_metrics_sink(), MetricMode::Force, None);
+                    Err(error.into())
+                }
+            }
+        }
+        .await;
+
+        capture_failure(
+            self,
+            MetricMode::Force,
+            target,
+            additional_instructions,
+            request_id(options),
+            provider_request_id,
+            result,
+        )
+    }
+
+    /// Asynchronously generates structured data, or a review item when the response cannot be fully parsed.
+    ///
+    /// This is the asynchronous version of `generate_data_or_review`.
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - A Task, or a `CompiledTask` of one, that provides the system prompt and schema
+    /// * `target` - The natural language text to extract data from
+    /// * `additional_instructions` - Extra instructions to guide the extraction process
+    ///
+    /// # Returns
+    ///
+    /// `Either::Left` with the extracted data, or `Either::Right` with a `ReviewItem`
+    async fn async_generate_data_or_review<T: Task $($task_bounds)*>(
+        &self,
+        task: &(impl ExtractionPlan<Task = T> $($shared_bounds)*),
+        target: &str,
+        additional_instructions: impl Into<InstructionSet> $($moved_bounds)*,
+    ) -> Result<Either<T, ReviewItem<T>>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let additional_instructions: &Vec<String> = &additional_instructions.into().into_vec();
+        let guarded: GuardedRequest = guard_request(self, target, additional_instructions)?;
+        let response: String = self
+            .async_send_messages_with_options(
+                task.prompt_messages(&guarded.target, guarded.instructions()),
+                true,
+                &RequestOptions::default(),
+            )
+            .await?;
+
+        let (result, _) = limit_content(
+            self,
+            &RequestOptions::default(),
+            extract_json_content(self, &response)?,
+        )?;
+
+        match self.get_leniency().from_str::<T>(&result) {
+            Ok(result) => Ok(Either::Left(result)),
+            Err(error) => {
+                record_parse_failed::<T>(self.get_metrics_sink(), MetricMode::Json, None);
+                Ok(Either::Right(ReviewItem::from_error(
+                    target, &result, &error,
+                )))
+            }
+        }
+    }
+
+    /// Generates structured data together with the source text each field came from.
+    ///
+    /// The async version of `GenerateData::generate_data_with_provenance`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `async_generate_data`.
+    async fn async_generate_data_with_provenance<T: Task $($task_bounds)*>(
+        &self,
+        task: &(impl ExtractionPlan<Task = T> $($shared_bounds)*),
+        target: &str,
+        additional_instructions: impl Into<InstructionSet> $($moved_bounds)*,
+    ) -> Result<ProvenanceResult<T>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let additional_instructions: &Vec<String> = &additional_instructions.into().into_vec();
+        let guarded: GuardedRequest = guard_request(self, target, additional_instructions)?;
+        let response: String = self
+            .async_send_messages_with_options(
+                make_prefixed_messages(
+                    provenance_system_prompt(task.task()),
+                    guarded.instructions(),
+                    &guarded.target,
+                ),
+                true,
+                &RequestOptions::default(),
+            )
+            .await?;
+
+        let (result, _) = limit_content(
+            self,
+            &RequestOptions::default(),
+            extract_json_content(self, &response)?,
+        )?;
+
+        parse_provenance_content::<Self, T>(self, &result, target)
+    }
+
+    /// Asynchronously asks the model why a field of extracted data has its value.
+    ///
+    /// The async version of `GenerateData::explain_field`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `GenerateData::explain_field`.
+    async fn async_explain_field<T: Task $($task_bounds)*>(
+        &self,
+        task: &(impl ExtractionPlan<Task = T> $($shared_bounds)*),
+        target: &str,
+        extracted: &T,
+        field_path: &str,
+    ) -> Result<Explanation, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let request: ExplanationRequest =
+            ExplanationRequest::new(&task.field_table(), extracted, field_path)?;
+        let additional_instructions: &Vec<String> = &Vec::new();
+        let guarded: GuardedRequest = guard_request(self, target, additional_instructions)?;
+        let response: String = self
+            .async_send_messages_with_options(
+                request.messages(&guarded.target, guarded.instructions()),
+                true,
+                &RequestOptions::default(),
+            )
+            .await?;
+
+        parse_explanation_content(self, &request, extract_json_content(self, &response)?, target)
+    }
+
+    /// Asynchronously asks the model why each field of extracted data that is not at its
+    /// default has its value, with at most `concurrency` requests in flight.
+    ///
+    /// The async version of `GenerateData::explain_all`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `GenerateData::explain_all`.
+    async fn async_explain_all<T: Task $($task_bounds)*>(
+        &self,
+        task: &(impl ExtractionPlan<Task = T> $($shared_bounds)*),
+        target: &str,
+        extracted: &T,
+        concurrency: usize,
+    ) -> Result<Vec<Explanation>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let requests: Vec<ExplanationRequest> = {
+            let fields: Cow<'_, [FieldDescriptor]> = task.field_table();
+            explainable_paths(&fields, extracted)
+                .iter()
+                .map(|path| ExplanationRequest::new(&fields, extracted, path))
+                .collect::<Result<_, _>>()?
+        };
+        let additional_instructions: &Vec<String> = &Vec::new();
+        let guarded: GuardedRequest = guard_request(self, target, additional_instructions)?;
+        let request_options: RequestOptions = RequestOptions::default();
+        let sends: Vec<_> = requests
+            .iter()
+            .map(|request| {
+                self.async_send_messages_with_options(
+                    request.messages(&guarded.target, guarded.instructions()),
+                    true,
+                    &request_options,
+                )
+            })
+            .collect();
+        let responses: Vec<Result<String, Box<dyn std::error::Error + Send + Sync + 'static>>> =
+            stream::iter(sends)
+                .buffered(concurrency.max(1))
+                .collect()
+                .await;
+
+        let mut explanations: Vec<Explanation> = Vec::with_capacity(requests.len());
+        for (request, response) in requests.iter().zip(responses) {
+            let content: String = extract_json_content(self, &response?)?;
+            explanations.push(parse_explanation_content(self, request, content, target)?);
+        }
+
+        Ok(explanations)
+    }
+
+    /// Asynchronously generates structured data from a target read from an async reader.
+    ///
+    /// The async version of `GenerateData::generate_data_from_reader`. The reader is a
+    /// `futures` `AsyncRead`; tokio readers can be adapted with `tokio-util`'s `compat`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `GenerateData::generate_data_from_reader`.
+    async fn async_generate_data_from_reader<T, R>(
+        &self,
+        task: &(impl ExtractionPlan<Task = T> $($shared_bounds)*),
+        reader: R,
+        additional_instructions: impl Into<InstructionSet> $($moved_bounds)*,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync + 'static>>
+    where
+        T: Task $($task_bounds)*,
+        R: futures::io::AsyncRead + Unpin $($moved_bounds)*,
+    {
+        let target: String = async_read_text(reader, &InputOptions::default()).await?;
+
+        self.async_generate_data(task, &target, additional_instructions)
+            .await
+    }
+
+    /// Asynchronously generates structured data with a prompt shaped to fit the model's context window.
+    ///
+    /// This is the asynchronous version of `generate_data_adaptive`.
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - A Task, or a `CompiledTask` of one, that provides the system prompt and schema
+    /// * `target` - The natural language text to extract data from
+    /// * `additional_instructions` - Extra instructions to guide the extraction process
+    ///
+    /// # Returns
+    ///
+    /// A `GenerationResult` containing the extracted data and the chosen `PromptStrategy`
+    ///
+    /// # Errors
+    ///
+    /// Returns `SecretaryError::ContextTooSmall` if no request shape fits, in addition to the
+    /// errors of the underlying generation method.
+    async fn async_generate_data_adaptive<T: Task $($task_bounds)*>(
+        &self,
+        task: &(impl ExtractionPlan<Task = T> $($shared_bounds)*),
+        target: &str,
+        additional_instructions: impl Into<InstructionSet> $($moved_bounds)*,
+    ) -> Result<GenerationResult<T>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        self.async_generate_data_adaptive_with_options(
+            task,
+            target,
+            additional_instructions,
+            &RequestOptions::default(),
+        )
+        .await
+    }
+
+    /// Asynchronously generates structured data like `generate_data_adaptive_with_options`.
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - A Task, or a `CompiledTask` of one, that provides the system prompt and schema
+    /// * `target` - The natural language text to extract data from
+    /// * `additional_instructions` - Extra instructions to guide the extraction process
+    /// * `options` - Request settings such as the seed or the temperature
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `generate_data_adaptive`.
+    async fn async_generate_data_adaptive_with_options<T: Task $($task_bounds)*>(
+        &self,
+        task: &(impl ExtractionPlan<Task = T> $($shared_bounds)*),
+        target: &str,
+        additional_instructions: impl Into<InstructionSet> $($moved_bounds)*,
+        options: &RequestOptions,
+    ) -> Result<GenerationResult<T>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let instructions: InstructionSet = additional_instructions.into();
+        let options: &RequestOptions = &traced_options(options);
+        let additional_instructions: &Vec<String> = &instructions.to_vec();
+        let guarded: GuardedRequest = guard_request(self, target, additional_instructions)?;
+        let task = &CallPlan::new(task, options, guarded.instructions())?
+                .with_locale(extraction_locale(self, options));
+        let (strategy, estimated_prompt_tokens) = choose_prompt_strategy(
+            task.task(),
+            &guarded.target,
+            guarded.instructions(),
+            self.get_capabilities().max_context_tokens,
+        )?;
+
+        let mut rate_limit: Option<RateLimitInfo> = None;
+        let mut provider_request_id: Option<String> = None;
+        let mut cached_prompt_tokens: Option<u64> = None;
+        let mut usage: Option<TokenUsage> = None;
+        let mut reasoning: Option<String> = None;
+        let mut repaired_sequences: usize = 0;
+        let mut fingerprints: Vec<String> = Vec::new();
+        let mut per_field_requests: Vec<String> = Vec::new();
+        let mut clipped_values: Vec<ClippedValue> = Vec::new();
+        let mut trimmed_keys: Vec<TrimmedKey> = Vec::new();
+        let mut normalized_values: Vec<NormalizedValue> = Vec::new();
+        let mut hint_conflicts: Vec<HintConflict> = Vec::new();
+        let local_values: Vec<(String, String)> =
+            extract_local_fields(&task.field_table(), &guarded.text)?;
+        let data: T = match strategy {
+            PromptStrategy::Full | PromptStrategy::Compact => {
+                let messages: Vec<Message> = if strategy == PromptStrategy::Full {
+                    task.prompt_messages_without_fields(
+                        &guarded.target,
+                        guarded.instructions(),
+                        &local_paths(&local_values),
+                    )
+                } else {
+                    task.compact_prompt_messages(&guarded.target, guarded.instructions())
+                };
+                let response: ResponseEnvelope = self
+                    .async_send_messages_envelope(messages, true, &with_stream_fields(options, &task.field_table()))
+                    .await?;
+                rate_limit = response.rate_limit();
+                provider_request_id = response.provider_request_id();
+                cached_prompt_tokens = extract_cached_tokens_from_llm_response(&response.body);
+                usage = TokenUsage::from_response(&response.body);
+                reasoning = reported_reasoning(options, &response.body);
+                fingerprints.extend(extract_system_fingerprint_from_llm_response(&response.body));
+
+                let content: String = strip_inline_reasoning(
+                    self.extract_response_content(&response.body)?,
+                    &response.body,
+                );
+                let (content, repaired) = self.get_decoding_policy().repair_content(content);
+                repaired_sequences = response.repaired_sequences + repaired;
+                let (content, conflicts) = merge_hint_values(
+                    merge_local_values(content, &local_values),
+                    &options.hints,
+                );
+                hint_conflicts = conflicts;
+                let (content, trimmed) = trim_content(self, options, task, content);
+                trimmed_keys = trimmed;
+                let (content, normalized) = normalize_content(self, options, task, content)?;
+                normalized_values.extend(normalized);
+                let content: String = localize_content(self, options, task, content);
+                let (result, clipped) = limit_content(self, options, content)?;
+                clipped_values.extend(clipped);
+                let critical_requests: Vec<(FieldPrompt, Message)> = without_hinted_fields(
+                    without_local_fields(
+                        critical_field_requests(task, &guarded.target, guarded.instructions()),
+                        &local_values,
+                    ),
+                    &options.hints,
+                );
+                if critical_requests.is_empty() {
+                    parse_json_content::<Self, T>(self, &result)?
+                } else {
+                    per_field_requests = critical_requests
+                        .iter()
+                        .flat_map(|(field_prompt, _)| field_prompt.field_paths())
+                        .collect();
+                    let results: Vec<(String, String)> =
+                        async_send_field_requests(self, critical_requests, options)
+                            .await?
+                            .collect_fingerprints(&mut fingerprints)
+                            .require_complete()?;
+                    let (data, clipped, normalized) =
+                        overlay_field_results::<Self, T>(self, options, &result, results)?;
+                    clipped_values.extend(clipped);
+                    normalized_values.extend(normalized);
+                    data
+                }
+            }
+            PromptStrategy::Distributed => {
+                require_distributed_generation::<T>()?;
+                let messages: Vec<(FieldPrompt, Message)> = without_hinted_fields(
+                    without_local_fields(
+                        task.field_requests(&guarded.target, guarded.instructions()),
+                        &local_values,
+                    ),
+                    &options.hints,
+                );
+                let results: Vec<(String, String)> =
+                    async_send_field_requests(self, messages, options)
+                        .await?
+                        .collect_fingerprints(&mut fingerprints)
+                        .require_complete()?;
+                let (data, clipped, normalized) = fields_from_results::<Self, T>(
+                    self,
+                    options,
+                    &task.field_table(),
+                    results,
+                    &local_values,
+                )?;
+                clipped_values.extend(clipped);
+                normalized_values.extend(normalized);
+                data
+            }
+        };
+
+        let language_violations: Vec<LanguageViolation> =
+            language_violations(&task.field_table(), &data, &guarded.text);
+        let order_violations: Vec<OrderViolation> = order_violations(
+            &task.field_table(),
+            &data,
+            &guarded.text,
+            options.order_check,
+        )?;
+
+        Ok(GenerationResult {
+            data,
+            metadata: GenerationMetadata {
+                prompt_strategy: Some(strategy),
+                estimated_prompt_tokens: Some(estimated_prompt_tokens),
+                rate_limit,
+                prompt_version: Some(T::prompt_version()),
+                cached_prompt_tokens,
+                per_field_requests,
+                locally_extracted: local_paths(&local_values),
+                hint_conflicts,
+                injection_verdict: guarded.verdict,
+                seed: options.seed,
+                system_fingerprint: fingerprints.first().cloned(),
+                locale: extraction_locale(self, options).cloned(),
+                warnings: [
+                    GenerationWarning::for_fingerprints(options.seed, &fingerprints),
+                    GenerationWarning::for_instructions(&instructions),
+                    GenerationWarning::for_trimmed_keys(trimmed_keys),
+                ]
+                .concat(),
+                language_violations,
+                voted_choices: None,
+                field_agreement: BTreeMap::new(),
+                regeneration_path: None,
+                changed_ratio: None,
+                repaired_sequences,
+                clipped_values,
+                normalized_values,
+                order_violations,
+                request_id: options.request_id.clone(),
+                provider_request_id,
+                usage,
+                reasoning,
+                routed_model: None,
+                chunk_sources: BTreeMap::new(),
+                merge_conflicts: Vec::new(),
+            },
+        })
+    }
+
+    /// Asynchronously generates structured data by majority vote over several samples of the
+    /// same prompt.
+    ///
+    /// This is the async version of `generate_data_consistent`.
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - A Task, or a `CompiledTask` of one, that provides the system prompt and schema
+    /// * `target` - The natural language text to extract data from
+    /// * `additional_instructions` - Extra instructions to guide the extraction process
+    /// * `k` - The number of samples to vote on
+    /// * `options` - Request settings, which must set a temperature above 0
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `generate_data_consistent`.
+    async fn async_generate_data_consistent<T: Task $($task_bounds)*>(
+        &self,
+        task: &(impl ExtractionPlan<Task = T> $($shared_bounds)*),
+        target: &str,
+        additional_instructions: impl Into<InstructionSet> $($moved_bounds)*,
+        k: usize,
+        options: &RequestOptions,
+    ) -> Result<GenerationResult<T>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        check_sampling(k, options)?;
+        let additional_instructions: &Vec<String> = &additional_instructions.into().into_vec();
+        let guarded: GuardedRequest = guard_request(self, target, additional_instructions)?;
+        let task = &CallPlan::new(task, options, guarded.instructions())?
+                .with_locale(extraction_locale(self, options));
+        let local_values: Vec<(String, String)> =
+            extract_local_fields(&task.field_table(), &guarded.text)?;
+        let response: String = self
+            .async_send_messages_with_options(
+                task.prompt_messages_without_fields(
+                    &guarded.target,
+                    guarded.instructions(),
+                    &local_paths(&local_values),
+                ),
+                true,
+                &options.clone().with_choices(k),
+            )
+            .await?;
+
+        vote_on_choices(
+            self,
+            task,
+            options,
+            &local_values,
+            &response,
+            k,
+            guarded.verdict,
+        )
+    }
+
+    /// Asynchronously re-extracts an edited target from the result extracted from its
+    /// previous version.
+    ///
+    /// This is the async version of `regenerate_data`.
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - A Task, or a `CompiledTask` of one, that provides the system prompt and schema
+    /// * `old_target` - The previous version of the text, which `previous` was extracted from
+    /// * `new_target` - The current version of the text
+    /// * `previous` - The result extracted from `old_target`
+    /// * `additional_instructions` - Extra instructions to guide the extraction process
+    /// * `options` - The largest share of changed lines extracted incrementally and the
+    ///   context sent around each change
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `async_generate_data`.
+    async fn async_regenerate_data<T: Task $($task_bounds)*>(
+        &self,
+        task: &(impl ExtractionPlan<Task = T> $($shared_bounds)*),
+        old_target: &str,
+        new_target: &str,
+        previous: &T,
+        additional_instructions: impl Into<InstructionSet> $($moved_bounds)*,
+        options: &RegenerateOptions,
+    ) -> Result<GenerationResult<T>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let diff: TextDiff = diff_lines(old_target, new_target);
+        let path: RegenerationPath = options.choose_path(&diff);
+        let (data, injection_verdict): (T, Option<InjectionVerdict>) = match path {
+            RegenerationPath::Unchanged => (
+                serde_json::from_value(serde_json::to_value(previous)?)?,
+                None,
+            ),
+            RegenerationPath::Full => (
+                self.async_generate_data(task, new_target, additional_instructions)
+                    .await?,
+                None,
+            ),
+            RegenerationPath::Incremental => {
+                let additional_instructions: &Vec<String> =
+                    &additional_instructions.into().into_vec();
+                let excerpts: String = diff.excerpts(options.context_lines);
+                let guarded: GuardedRequest =
+                    guard_request(self, &excerpts, additional_instructions)?;
+                let response: String = self
+                    .async_send_messages_with_options(
+                        task.prompt_messages(
+                            &make_incremental_target(previous, &guarded.target),
+                            guarded.instructions(),
+                        ),
+                        true,
+                        &RequestOptions::default(),
+                    )
+                    .await?;
+                let (content, _) = limit_content(
+                    self,
+                    &RequestOptions::default(),
+                    extract_json_content(self, &response)?,
+                )?;
+                (parse_plan_content(self, task, &content)?, guarded.verdict)
+            }
+        };
+
+        Ok(GenerationResult {
+            data,
+            metadata: regeneration_metadata::<T>(path, &diff, injection_verdict),
+        })
+    }
+
+    /// Extracts data from each of many targets, yielding every result as soon as it is done.
+    ///
+    /// At most `concurrency` targets are extracted at once, each like `async_generate_data`,
+    /// so that requests go through the provider's rate limiter and retries. Results arrive in
+    /// the order they finish rather than the order of `targets`, with the index of their
+    /// target. Dropping the stream cancels the requests in flight and starts no others.
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - A Task, or a `CompiledTask` of one, that provides the system prompt and schema
+    /// * `targets` - The natural language texts to extract data from
+    /// * `additional_instructions` - Extra instructions to guide every extraction
+    /// * `concurrency` - The number of targets extracted at once, at least 1
+    ///
+    /// # Returns
+    ///
+    /// A stream of the index of each target and the result of its extraction
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use futures::StreamExt;
+    /// use secretary::Task;
+    /// use secretary::llm_providers::openai::OpenAILLM;
+    /// use secretary::traits::AsyncGenerateData;
+    /// use serde::{Deserialize, Serialize};
+    ///
+    /// #[derive(Task, Debug, Serialize, Deserialize)]
+    /// struct Person {
+    ///     #[task(instruction = "Extract the name")]
+    ///     pub name: String,
+    /// }
+    ///
+    /// # async fn run() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    /// let llm = OpenAILLM::new("https://api.openai.com/v1", "your-api-key", "gpt-4")?;
+    /// let task = Person::new();
+    /// let targets = vec!["I'm Ada".to_string(), "Grace here".to_string()];
+    ///
+    /// let mut results = llm.stream_generate_data_batch(&task, targets, Vec::new(), 8);
+    /// while let Some((index, result)) = results.next().await {
+    ///     match result {
+    ///         Ok(person) => println!("{}: {:?}", index, person),
+    ///         Err(error) => eprintln!("{}: {}", index, error),
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn stream_generate_data_batch<'a, T: Task $($task_bounds)* + 'a, P>(
+        &'a self,
+        task: &'a P,
+        targets: Vec<String>,
+        additional_instructions: impl Into<InstructionSet> $($moved_bounds)*,
+        concurrency: usize,
+    ) -> impl Stream<Item = (usize, Result<T, Box<dyn std::error::Error + Send + Sync + 'static>>)>
+           $($moved_bounds)* + 'a
+    where
+        P: ExtractionPlan<Task = T> $($shared_bounds)* + 'a,
+    {
+        let additional_instructions: InstructionSet = additional_instructions.into();
+        stream::iter(targets.into_iter().enumerate())
+            .map(move |(index, target)| {
+                let additional_instructions: InstructionSet = additional_instructions.clone();
+                async move {
+                    let result: Result<T, Box<dyn std::error::Error + Send + Sync + 'static>> =
+                        self.async_generate_data(task, &target, additional_instructions)
+                            .await;
+                    (index, result)
+                }
+            })
+            .buffer_unordered(concurrency.max(1))
+    }
+
+    /// Asynchronously generates structured data from a target larger than the context window.
+    ///
+    /// This is the async version of `generate_data_chunked`.
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - A Task, or a `CompiledTask` of one, that provides the system prompt and schema
+    /// * `target` - The natural language text to extract data from
+    /// * `additional_instructions` - Extra instructions to guide the extraction process
+    /// * `options` - The chunk size, overlap, concurrency, merge policy and confidence
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error of the chunk requests, or the error of the merge.
+    async fn async_generate_data_chunked<T: Task $($task_bounds)*>(
+        &self,
+        task: &(impl ExtractionPlan<Task = T> $($shared_bounds)*),
+        target: &str,
+        additional_instructions: impl Into<InstructionSet> $($moved_bounds)*,
+        options: &ChunkOptions,
+    ) -> Result<GenerationResult<T>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let progress: Option<Progress> = options.progress.as_ref().map(Progress::started);
+        let chunks: Vec<String> = options.split(target);
+        if chunks.len() <= 1 {
+            report(progress.as_ref(), || ProgressKind::ChunkStarted {
+                index: 0,
+                total: 1,
+            });
+            let data: T = self
+                .async_generate_data_with_options(
+                    task,
+                    target,
+                    additional_instructions,
+                    &chunk_request_options(progress.as_ref()),
+                )
+                .await?;
+            report_single_chunk_finished(progress.as_ref());
+            return Ok(GenerationResult {
+                data,
+                metadata: GenerationMetadata::default(),
+            });
+        }
+
+        let additional_instructions: &Vec<String> = &additional_instructions.into().into_vec();
+        let guarded: GuardedRequest = guard_request(self, target, additional_instructions)?;
+        let texts: Vec<String> = options.split(&guarded.text);
+        let instructions: Vec<String> = options.chunk_instructions(guarded.instructions());
+        let request_options: RequestOptions = chunk_request_options(progress.as_ref());
+        let total: usize = texts.len();
+        let completed: AtomicUsize = AtomicUsize::new(0);
+        let requests: Vec<_> = texts
+            .iter()
+            .enumerate()
+            .map(|(index, chunk)| {
+                let messages: Vec<Message> =
+                    task.prompt_messages(&guarded.wrap(chunk), &instructions);
+                let (progress, request_options, completed) =
+                    (progress.as_ref(), &request_options, &completed);
+                async move {
+                    report(progress, || ProgressKind::ChunkStarted { index, total });
+                    let response: String = self
+                        .async_send_messages_with_options(messages, true, request_options)
+                        .await?;
+                    report(progress, || ProgressKind::ChunkFinished {
+                        index,
+                        completed: completed.fetch_add(1, Ordering::Relaxed) + 1,
+                        total,
+                    });
+                    Ok::<String, Box<dyn std::error::Error + Send + Sync + 'static>>(response)
+                }
+            })
+            .collect();
+        let responses: Vec<Result<String, Box<dyn std::error::Error + Send + Sync + 'static>>> =
+            stream::iter(requests)
+                .buffered(options.concurrency.max(1))
+                .collect()
+                .await;
+        let mut contents: Vec<String> = Vec::with_capacity(responses.len());
+        for response in responses {
+            contents.push(extract_json_content(self, &response?)?);
+        }
+        let (results, chunks) = parse_chunk_results::<Self, T>(self, contents, texts, options)?;
+        report(progress.as_ref(), || ProgressKind::MergeStarted {
+            chunks: chunks.len(),
+        });
+        let mut merge: ChunkMerge = merge_chunk_values(
+            &serde_json::to_value(T::default())?,
+            &chunks,
+            options.merge_policy,
+        );
+
+        let data: T = match options.merge_policy {
+            MergePolicy::Llm => {
+                merge.conflicts.clear();
+                let response: String = self
+                    .async_send_messages_with_options(
+                        make_reduce_prompt(task.task(), &results, additional_instructions),
+                        true,
+                        &RequestOptions::default(),
+                    )
+                    .await?;
+                let (content, _) = limit_content(
+                    self,
+                    &RequestOptions::default(),
+                    extract_json_content(self, &response)?,
+                )?;
+                parse_json_content::<Self, T>(self, &content)?
+            }
+            MergePolicy::Adjudicate if !merge.conflicts.is_empty() => {
+                let response: String = self
+                    .async_send_messages_with_options(
+                        make_adjudication_prompt(
+                            task.task(),
+                            &merge.conflicts,
+                            additional_instructions,
+                        ),
+                        true,
+                        &RequestOptions::default(),
+                    )
+                    .await?;
+                let (content, _) = limit_content(
+                    self,
+                    &RequestOptions::default(),
+                    extract_json_content(self, &response)?,
+                )?;
+                apply_adjudication(&mut merge, &serde_json::from_str::<Value>(&content)?);
+                merged_data(&merge)?
+            }
+            _ => merged_data(&merge)?,
+        };
+        report(progress.as_ref(), || ProgressKind::Completed);
+
+        Ok(chunked_result(data, merge))
+    }
+
+    /// Asynchronously generates structured data by breaking down the task into individual field requests.
+    ///
+    /// This is the async version of `fields_generate_data` that uses concurrent futures instead of threads.
+    /// Instead of generating a complete JSON object in a single request, this method breaks the task
+    /// down into individual field extractions. Each field is processed concurrently using async tasks,
+    /// and the results are combined into the final structured object.
+    ///
+    /// # Benefits
+    ///
+    /// - **Improved accuracy**: Each field gets focused attention from the LLM
+    /// - **Concurrent processing**: Multiple fields extracted simultaneously using async tasks
+    /// - **Error isolation**: Failure in one field doesn't affect others
+    /// - **Async-friendly**: Integrates seamlessly with async codebases
+    /// - **Resource efficient**: Uses async I/O instead of blocking threads
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - A Task, or a `CompiledTask` of one, that provides field-specific prompts
+    /// * `target` - The natural language text to extract data from
+    /// * `additional_instructions` - Extra instructions to guide the extraction process
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the extracted data as the specified type T
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use secretary::Task;
+    /// use secretary::llm_providers::openai::OpenAILLM;
+    /// use secretary::traits::AsyncGenerateData;
+    /// use serde::{Serialize, Deserialize};
+    ///
+    /// #[derive(Task, Serialize, Deserialize, Debug)]
+    /// struct CompanyInfo {
+    ///     #[task(instruction = "Extract the company name")]
+    ///     pub name: String,
+    ///     #[task(instruction = "Extract the founding year as a number")]
+    ///     pub founded: u32,
+    ///     #[task(instruction = "Extract the industry or sector")]
+    ///     pub industry: String,
+    ///     #[task(instruction = "Extract the headquarters location")]
+    ///     pub headquarters: Option<String>,
+    ///     #[task(instruction = "Extract the CEO or founder name")]
+    ///     pub ceo: Option<String>,
+    /// }
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    ///     let llm = OpenAILLM::new(
+    ///         "https://api.openai.com/v1",
+    ///         "your-api-key",
+    ///         "gpt-4"
+    ///     )?;
+    ///
+    ///     let task = CompanyInfo::new();
+    ///     let additional_instructions = vec![
+    ///         "Be precise with dates and numbers".to_string(),
+    ///         "Use null for missing information".to_string(),
+    ///     ];
+    ///
+    ///     let input = "Apple Inc. was founded in 1976 by Steve Jobs. The company is headquartered in Cupertino, California and operates in the technology sector.";
+    ///
+    ///     // Each field will be extracted concurrently
+    ///     let result: CompanyInfo = llm.async_fields_generate_data(&task, input, &additional_instructions).await?;
+    ///
+    ///     println!("Extracted company info: {:#?}", result);
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// # Performance Considerations
+    ///
+    /// - **Concurrent execution**: All field extractions happen simultaneously
+    /// - **API calls**: Makes one API call per field, which may increase costs but improve accuracy
+    /// - **Memory efficient**: Uses async tasks instead of OS threads
+    /// - **Early termination**: Stops all remaining requests if any field extraction fails
+    ///
+    /// # Errors
+    ///
+    /// Returns `SecretaryError` if the final assembly of fields into the struct `T` fails.
+    /// This is particularly useful for catching `FieldDeserializationError`.
+    async fn async_fields_generate_data<T: Task $($task_bounds)*>(
+        &self,
+        task: &(impl ExtractionPlan<Task = T> $($shared_bounds)*),
+        target: &str,
+        additional_instructions: impl Into<InstructionSet> $($moved_bounds)*,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        self.async_generate(
+            task,
+            target,
+            additional_instructions,
+            GenerationMode::Fields { concurrency: None },
+        )
+        .await
+    }
+
+    /// Asynchronously generates structured data field by field with request settings for
+    /// this call.
+    ///
+    /// This is the asynchronous version of `fields_generate_data_with_options`. Requests
+    /// still running at the deadline are cancelled.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `fields_generate_data_with_options`.
+    async fn async_fields_generate_data_with_options<T: Task $($task_bounds)*>(
+        &self,
+        task: &(impl ExtractionPlan<Task = T> $($shared_bounds)*),
+        target: &str,
+        additional_instructions: impl Into<InstructionSet> $($moved_bounds)*,
+        options: &RequestOptions,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let additional_instructions: &Vec<String> = &additional_instructions.into().into_vec();
+        let options: &RequestOptions = &traced_options(options);
+        let result: Result<T, Box<dyn std::error::Error + Send + Sync + 'static>> = async {
+            require_distributed_generation::<T>()?;
+            let guarded: GuardedRequest = guard_request(self, target, additional_instructions)?;
+            let task = &CallPlan::new(task, options, guarded.instructions())?
+                .with_locale(extraction_locale(self, options));
+            let local_values: Vec<(String, String)> =
+                extract_local_fields(&task.field_table(), &guarded.text)?;
+            let messages: Vec<(FieldPrompt, Message)> = without_hinted_fields(
+                without_local_fields(
+                    task.field_requests(&guarded.target, guarded.instructions()),
+                    &local_values,
+                ),
+                &options.hints,
+            );
+
+            let distributed_tasks_results: Vec<(String, String)> =
+                async_send_field_requests(self, messages, options)
+                    .await?
+                    .require_complete()?;
+
+            let (data, _, _) = fields_from_results::<Self, T>(
+                self,
+                options,
+                &task.field_table(),
+                distributed_tasks_results,
+                &local_values,
+            )?;
+            report(options.progress.as_ref(), || ProgressKind::Completed);
+
+            Ok(bind_lazy(self, data, &guarded, options))
+        }
+        .await;
+
+        capture_failure(
+            self,
+            MetricMode::Distributed,
+            target,
+            additional_instructions,
+            request_id(options),
+            None,
+            result,
+        )
+    }
+
+    /// Asynchronously generates structured data field by field and returns a trace of every
+    /// field's request with it.
+    ///
+    /// This is the asynchronous version of `fields_generate_data_traced`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `fields_generate_data`.
+    async fn async_fields_generate_data_traced<T: Task $($task_bounds)*>(
+        &self,
+        task: &(impl ExtractionPlan<Task = T> $($shared_bounds)*),
+        target: &str,
+        additional_instructions: impl Into<InstructionSet> $($moved_bounds)*,
+    ) -> Result<(T, FieldTrace), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let additional_instructions: &Vec<String> = &additional_instructions.into().into_vec();
+        require_distributed_generation::<T>()?;
+        let guarded: GuardedRequest = guard_request(self, target, additional_instructions)?;
+        let local_values: Vec<(String, String)> =
+            extract_local_fields(&task.field_table(), &guarded.text)?;
+        let messages: Vec<(FieldPrompt, Message)> = without_local_fields(
+            task.field_requests(&guarded.target, guarded.instructions()),
+            &local_values,
+        );
+
+        let answers: Vec<FieldAnswer> =
+            async_send_field_requests(self, messages, &RequestOptions::default())
+                .await?
+                .require_complete_answers()?;
+        let trace: FieldTrace = trace_answers(
+            answers,
+            &task.field_table(),
+            &local_values,
+            self.get_leniency(),
+        );
+
+        let (data, _, _) = fields_from_results::<Self, T>(
+            self,
+            &RequestOptions::default(),
+            &task.field_table(),
+            trace.results(),
+            &local_values,
+        )?;
+
+        Ok((data, trace))
+    }
+
+    /// Asynchronously generates structured data, replacing the fields that fail to
+    /// deserialize with their defaults.
+    ///
+    /// This is the asynchronous version of `generate_partial_data`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `generate_partial_data`.
+    async fn async_generate_partial_data<T: Task $($task_bounds)*>(
+        &self,
+        task: &(impl ExtractionPlan<Task = T> $($shared_bounds)*),
+        target: &str,
+        additional_instructions: impl Into<InstructionSet> $($moved_bounds)*,
+    ) -> Result<PartialData<T>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let additional_instructions: &Vec<String> = &additional_instructions.into().into_vec();
+        let guarded: GuardedRequest = guard_request(self, target, additional_instructions)?;
+        let request: String = self
+            .async_send_messages_with_options(
+                task.prompt_messages(&guarded.target, guarded.instructions()),
+                true,
+                &RequestOptions::default(),
+            )
+            .await?;
+
+        let (result, _) = limit_content(
+            self,
+            &RequestOptions::default(),
+            extract_json_content(self, &request)?,
+        )?;
+
+        let mut value: Value = match serde_json::from_str(&result) {
+            Ok(value) => value,
+            Err(error) => {
+                record_parse_failed::<T>(self.get_metrics_sink(), MetricMode::Json, None);
+                return Err(Box::new(json_parsing_error(error, &result)));
+            }
+        };
+        self.get_leniency().apply::<T>(&mut value);
+
+        partial_from_value(self, MetricMode::Json, &value, Vec::new(), Vec::new())
+    }
+
+    /// Asynchronously generates structured data field by field, replacing the fields that
+    /// fail to deserialize with their defaults.
+    ///
+    /// This is the asynchronous version of `fields_generate_partial_data`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `fields_generate_partial_data`.
+    async fn async_fields_generate_partial_data<T: Task $($task_bounds)*>(
+        &self,
+        task: &(impl ExtractionPlan<Task = T> $($shared_bounds)*),
+        target: &str,
+        additional_instructions: impl Into<InstructionSet> $($moved_bounds)*,
+    ) -> Result<PartialData<T>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        self.async_fields_generate_partial_data_with_options(
+            task,
+            target,
+            additional_instructions,
+            &RequestOptions::default(),
+        )
+        .await
+    }
+
+    /// Asynchronously generates structured data field by field with request settings for
+    /// this call, keeping the fields that completed before the deadline.
+    ///
+    /// This is the asynchronous version of `fields_generate_partial_data_with_options`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `fields_generate_partial_data`.
+    async fn async_fields_generate_partial_data_with_options<T: Task $($task_bounds)*>(
+        &self,
+        task: &(impl ExtractionPlan<Task = T> $($shared_bounds)*),
+        target: &str,
+        additional_instructions: impl Into<InstructionSet> $($moved_bounds)*,
+        options: &RequestOptions,
+    ) -> Result<PartialData<T>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let additional_instructions: &Vec<String> = &additional_instructions.into().into_vec();
+        require_distributed_generation::<T>()?;
+        let guarded: GuardedRequest = guard_request(self, target, additional_instructions)?;
+        let task = &CallPlan::new(task, options, guarded.instructions())?
+                .with_locale(extraction_locale(self, options));
+        let messages: Vec<(FieldPrompt, Message)> = without_hinted_fields(
+            task.field_requests(&guarded.target, guarded.instructions()),
+            &options.hints,
+        );
+
+        let results: FieldResults = async_send_field_requests(self, messages, options).await?;
+
+        let mut value: Value = collect_field_results(
+            self.get_leniency(),
+            &task.field_table(),
+            &results.completed(),
+        )?;
+        normalize_one_of(
+            &task.field_table(),
+            &mut value,
+            one_of_policy(self, options),
+        )?;
+        set_hint_values(&mut value, &options.hints);
+        output_limits(self, options).enforce(&mut value)?;
+
+        let refused: Vec<String> = results.refused();
+        partial_from_value(
+            self,
+            MetricMode::Distributed,
+            &value,
+            results.incomplete,
+            refused,
+        )
+    }
+
+    /// Asynchronously fills in the empty fields of an existing struct.
+    ///
+    /// This is the asynchronous version of `update_data`. Requests for the missing fields are
+    /// sent concurrently.
+    ///
+    /// # Arguments
+    ///
+    /// * `existing` - The partially filled struct
+    /// * `target` - The natural language text to extract the missing data from
+    /// * `additional_instructions` - Extra instructions to guide the extraction process
+    ///
+    /// # Returns
+    ///
+    /// A Result containing a copy of `existing` with the missing fields filled in
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a request fails, or `SecretaryError::SerdeJsonError` if the merged
+    /// data does not deserialize into `T`.
+    async fn async_update_data<T: Task $($task_bounds)*>(
+        &self,
+        existing: &T,
+        target: &str,
+        additional_instructions: impl Into<InstructionSet> $($moved_bounds)*,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let additional_instructions: &Vec<String> = &additional_instructions.into().into_vec();
+        require_distributed_generation::<T>()?;
+        let guarded: GuardedRequest = guard_request(self, target, additional_instructions)?;
+        let messages: Vec<(FieldPrompt, Message)> =
+            existing.make_update_requests(&guarded.target, guarded.instructions());
+
+        let distributed_tasks_results: Vec<(String, String)> =
+            async_send_field_requests(self, messages, &RequestOptions::default())
+                .await?
+                .require_complete()?;
+
+        merge_field_results(self, existing, distributed_tasks_results)
+    }
+
+    /// Asynchronously extracts a `serde_json::Value` for a task known only at runtime.
+    ///
+    /// This is the asynchronous version of `generate_value`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails, or `SecretaryError::JsonParsingError` if the
+    /// response is not valid JSON.
+    async fn async_generate_value(
+        &self,
+        task: &$dyn_task,
+        target: &str,
+        additional_instructions: impl Into<InstructionSet> $($moved_bounds)*,
+    ) -> Result<Value, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let additional_instructions: &Vec<String> = &additional_instructions.into().into_vec();
+        let guarded: GuardedRequest = guard_request(self, target, additional_instructions)?;
+        let request: String = self
+            .async_send_messages_with_options(
+                make_value_prompt(task, &guarded.target, guarded.instructions()),
+                true,
+                &RequestOptions::default(),
+            )
+            .await?;
+
+        let (result, _) = limit_content(
+            self,
+            &RequestOptions::default(),
+            extract_json_content(self, &request)?,
+        )?;
+
+        parse_value(self, task, &result)
+    }
+
+    /// Asynchronously extracts a `serde_json::Value` for a task known only at runtime with
+    /// one request per field.
+    ///
+    /// This is the asynchronous version of `fields_generate_value`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a request fails, or `SecretaryError::InvalidFieldPath` if a field
+    /// path cannot be set.
+    async fn async_fields_generate_value(
+        &self,
+        task: &$dyn_task,
+        target: &str,
+        additional_instructions: impl Into<InstructionSet> $($moved_bounds)*,
+    ) -> Result<Value, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let additional_instructions: &Vec<String> = &additional_instructions.into().into_vec();
+        let guarded: GuardedRequest = guard_request(self, target, additional_instructions)?;
+        let messages: Vec<(FieldPrompt, Message)> =
+            make_value_field_requests(task, &guarded.target, guarded.instructions());
+
+        let distributed_tasks_results: Vec<(String, String)> =
+            async_send_field_requests(self, messages, &RequestOptions::default())
+                .await?
+                .require_complete()?;
+
+        assemble_field_values(self, task, distributed_tasks_results)
+    }
+}
+    };
+}
+
+async_generate_data_trait! {
+    /// Trait for asynchronous data generation from LLMs.
+    ///
+    /// This trait provides async methods for extracting structured data from natural language text
+    /// using LLM providers. It includes both standard JSON mode generation and force generation
+    /// for reasoning models that don't support JSON mode.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use secretary::Task;
+    /// use secretary::llm_providers::openai::OpenAILLM;
+    /// use secretary::traits::AsyncGenerateData;
+    /// use serde::{Deserialize, Serialize};
+    ///
+    /// #[derive(Task, Debug, Serialize, Deserialize)]
+    /// struct ProductInfo {
+    ///     #[task(instruction = "Extract the product name")]
+    ///     pub name: String,
+    ///     #[task(instruction = "Extract price as a number")]
+    ///     pub price: f64,
+    /// }
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    ///     let llm = OpenAILLM::new(
+    ///         "https://api.openai.com/v1",
+    ///         "your-api-key",
+    ///         "gpt-4"
+    ///     )?;
+    ///
+    ///     let task = ProductInfo::new();
+    ///     let additional_instructions = vec!["Be precise with pricing".to_string()];
+    ///
+    ///     let input = "Apple MacBook Pro 16-inch costs $2,499";
+    ///     let result: ProductInfo = llm.async_generate_data(&task, input, &additional_instructions).await?;
+    ///
+    ///     println!("Extracted: {:#?}", result);
+    ///     Ok(())
+    /// }
+    /// ```
+    #[async_trait]
+    pub trait AsyncGenerateData;
+    task: [+ Sync + Send],
+    shared: [+ Sync],
+    moved: [+ Send],
+    dyn_task: (dyn DynTask + Sync),
+}
+
+#[cfg(feature = "local")]
+async_generate_data_trait! {
+    /// Trait for asynchronous data generation on single-threaded executors.
+    ///
+    /// This trait has the methods of `AsyncGenerateData`, but neither its futures nor its
+    /// Tasks, plans and arguments need to be `Send` or `Sync`. Use it with Tasks that hold
+    /// `Rc`s or other values bound to a thread, on a current-thread runtime or a tokio
+    /// `LocalSet`; concurrent field requests run on the same thread. Import only one of the
+    /// two traits in a module, since their methods have the same names.
+    ///
+    /// Available with the `local` feature.
+    #[async_trait(?Send)]
+    pub trait AsyncGenerateDataLocal;
+    task: [],
+    shared: [],
+    moved: [],
+    dyn_task: dyn DynTask,
+}
+
+/// Formats the message for a single field in distributed generation.
+///
+/// `known_values` is the JSON of the values that are already known, shown as context in
+/// update mode. `instructions` are the additional instructions as formatted by
+/// `format_additional_instructions`, once for all the fields of a request. The message is
+/// written into one string of the size it needs.
+pub(crate) fn make_field_message(
+    field_prompt: &FieldPrompt,
+    known_values: Option<&str>,
+    target: &str,
+    instructions: &str,
+) -> Message {
+    const KNOWN_VALUES: &str = "These values are already known and are given for context only:\n";
+    const BASIS: &str = "\nThis is the basis for generating the result:\n";
+
+    let extra_instruction: Option<&str> = field_prompt.extra_instruction.as_deref();
+    let mut content: String = String::with_capacity(
+        field_prompt.prompt.len()
+            + extra_instruction.map_or(0, |extra_instruction| extra_instruction.len() + 3)
+            + known_values.map_or(0, |known_values| {
+                KNOWN_VALUES.len() + known_values.len() + 1
+            })
+            + instructions.len()
+            + BASIS.len()
+            + target.len(),
+    );
+    content.push_str(&field_prompt.prompt);
+    if let Some(extra_instruction) = extra_instruction {
+        let _ = writeln!(content, "- {}", extra_instruction);
+    }
+    if let Some(known_values) = known_values {
+        content.push_str(KNOWN_VALUES);
+        content.push_str(known_values);
+        content.push('\n');
+    }
+    content.push_str(instructions);
+    content.push_str(BASIS);
+    content.push_str(target);
+
+    Message::user(content)
+}
+
+/// Appends the additional instructions and the target to a system prompt, in the layout of
+/// `Task::make_prompt`, reusing the prompt's string.
+fn append_target(mut prompt: String, additional_instructions: &[String], target: &str) -> String {
+    const BASIS: &str = "\nThis is the basis for generating a json:\n";
+
+    write_additional_instructions(&mut prompt, additional_instructions);
+    prompt.reserve(BASIS.len() + target.len());
+    prompt.push_str(BASIS);
+    prompt.push_str(target);
+
+    prompt
+}
+
+/// Returns whether a field value counts as not filled in yet for update mode.
+fn is_unfilled(value: Option<&Value>, default: Option<&Value>) -> bool {
+    let value: &Value = match value {
+        Some(value) => value,
+        None => return true,
+    };
+
+    let is_empty: bool = match value {
+        Value::Null => true,
+        Value::String(text) => text.is_empty(),
+        Value::Array(items) => items.is_empty(),
+        Value::Object(entries) => entries.is_empty(),
+        _ => false,
+    };
+
+    is_empty || default == Some(value)
+}
+
+/// The results of distributed generation: the answers of the completed fields and the paths
+/// of the fields that did not complete before the deadline.
+///
+/// Refused fields are among the answers, with the refusal as their content.
+struct FieldResults {
+    answers: Vec<FieldAnswer>,
+    incomplete: Vec<String>,
+}
+
+/// The answer to a field's request.
+struct FieldAnswer {
+    field_path: String,
+    /// The content of the message sent for the field.
+    prompt: String,
+    /// The response content before thinking blocks and `<result>` tags are removed.
+    raw_response: String,
+    /// The content of the `<result>` tags.
+    content: String,
+    /// The `system_fingerprint` the response reported.
+    fingerprint: Option<String>,
+    latency: Duration,
+    /// The model's refusal, when it refused the field's request.
+    refusal: Option<String>,
+}
+
+impl FieldResults {
+    /// Returns the field paths of the completed fields that were not refused with the content
+    /// of their `<result>` tags.
+    fn completed(&self) -> Vec<(String, String)> {
+        self.answers
+            .iter()
+            .filter(|answer| answer.refusal.is_none())
+            .map(|answer| (answer.field_path.clone(), answer.content.clone()))
+            .collect()
+    }
+
+    /// Returns the paths of the fields the model refused.
+    fn refused(&self) -> Vec<String> {
+        self.answers
+            .iter()
+            .filter(|answer| answer.refusal.is_some())
+            .map(|answer| answer.field_path.clone())
+            .collect()
+    }
+
+    /// Returns the completed fields, or `SecretaryError::DeadlineExceeded` if any field did not
+    /// complete and `SecretaryError::FieldDeserializationError` if any was refused.
+    fn require_complete(
+        self,
+    ) -> Result<Vec<(String, String)>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        Ok(self
+            .require_complete_answers()?
+            .into_iter()
+            .map(|answer| (answer.field_path, answer.content))
+            .collect())
+    }
+
+    /// Returns the answers of the fields, or `SecretaryError::DeadlineExceeded` if any field
+    /// did not complete.
+    ///
+    /// When the model refused any field, the error is a
+    /// `SecretaryError::FieldDeserializationError` that lists the refused fields as failed
+    /// and in `refused_fields`, with the content of every answer.
+    fn require_complete_answers(
+        self,
+    ) -> Result<Vec<FieldAnswer>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        if !self.incomplete.is_empty() {
+            return Err(Box::new(SecretaryError::DeadlineExceeded {
+                incomplete_fields: self.incomplete,
+            }));
+        }
+
+        let refused_fields: Vec<String> = self.refused();
+        if !refused_fields.is_empty() {
+            return Err(Box::new(SecretaryError::FieldDeserializationError(
+                FieldDeserializationError {
+                    failed_fields: refused_fields.clone(),
+                    successful_fields: self
+                        .completed()
+                        .into_iter()
+                        .map(|(field_path, _)| field_path)
+                        .collect(),
+                    original_error: format!(
+                        "The model refused the request for {} field(s)",
+                        refused_fields.len()
+                    ),
+                    raw_field_contents: self
+                        .answers
+                        .into_iter()
+                        .map(|answer| (answer.field_path, answer.content))
+                        .collect(),
+                    refused_fields,
+                },
+            )));
+        }
+
+        Ok(self.answers)
+    }
+
+    /// Copies the fingerprints the responses reported into `fingerprints`.
+    fn collect_fingerprints(self, fingerprints: &mut Vec<String>) -> Self {
+        fingerprints.extend(
+            self.answers
+                .iter()
+                .filter_map(|answer| answer.fingerprint.clone()),
+        );
+        self
+    }
+}
+
+/// Records the answers of a distributed extraction, and the values found by local
+/// extractors, as a `FieldTrace`.
+fn trace_answers(
+    answers: Vec<FieldAnswer>,
+    fields: &[FieldDescriptor],
+    local_values: &[(String, String)],
+    leniency: &LeniencyProfile,
+) -> FieldTrace {
+    FieldTrace {
+        fields: answers
+            .into_iter()
+            .map(|answer| {
+                let value: Value =
+                    parse_described_field_value(&answer.content, &answer.field_path, fields);
+                let entry: FieldTraceEntry = FieldTraceEntry {
+                    prompt: answer.prompt,
+                    raw_response: answer.raw_response,
+                    content: answer.content,
+                    value,
+                    latency: answer.latency,
+                };
+                (answer.field_path, entry)
+            })
+            .collect(),
+        local_values: local_values.iter().cloned().collect(),
+        leniency: leniency.clone(),
+    }
+}
+
+/// Turns the response to a field's request into the answers of its fields: one answer for a
+/// single field, and one per member found in the JSON object answering a group.
+///
+/// The members a group's answer has no value for get no answer; assembling the fields
+/// reports those that the Task requires as failed. A refusal answers every member, with the
+/// refusal as the content.
+fn field_answers(
+    field_prompt: &FieldPrompt,
+    prompt: String,
+    response: &str,
+    (raw_response, refusal): (String, Option<String>),
+    latency: Duration,
+) -> Vec<FieldAnswer> {
+    let content: String =
+        extract_result_content(&strip_inline_reasoning(raw_response.clone(), response));
+    let fingerprint: Option<String> = extract_system_fingerprint_from_llm_response(response);
+    if !field_prompt.is_group() {
+        return vec![FieldAnswer {
+            field_path: field_prompt.field_path.clone(),
+            prompt,
+            content,
+            raw_response,
+            fingerprint,
+            latency,
+            refusal,
+        }];
+    }
+    if let Some(refusal) = refusal {
+        return field_prompt
+            .field_paths()
+            .into_iter()
+            .enumerate()
+            .map(|(index, field_path)| FieldAnswer {
+                field_path,
+                prompt: prompt.clone(),
+                content: refusal.clone(),
+                raw_response: raw_response.clone(),
+                fingerprint: if index == 0 {
+                    fingerprint.clone()
+                } else {
+                    None
+                },
+                latency,
+                refusal: Some(refusal.clone()),
+            })
+            .collect();
+    }
+
+    split_group_content(field_prompt, &content)
+        .into_iter()
+        .enumerate()
+        .map(|(index, (field_path, content))| FieldAnswer {
+            field_path,
+            prompt: prompt.clone(),
+            content,
+            raw_response: raw_response.clone(),
+            // The response is reported once, with the first member
+            fingerprint: if index == 0 {
+                fingerprint.clone()
+            } else {
+                None
+            },
+            latency,
+            refusal: None,
+        })
+        .collect()
+}
+
+/// Returns the content of the response to a field's request together with the refusal it
+/// holds, if any: the one the provider reported in place of content, which becomes the
+/// content, or the content itself when `detect_refusal` takes it for one.
+pub(crate) fn field_response_content<L: IsLLM + ?Sized>(
+    llm: &L,
+    response: &str,
+) -> Result<(String, Option<String>), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    match llm.extract_response_content(response) {
+        Ok(content) => {
+            let refusal: Option<String> = detect_refusal(&content);
+            Ok((content, refusal))
+        }
+        Err(error) => match error.downcast_ref::<SecretaryError>() {
+            Some(SecretaryError::Refused { message }) => {
+                Ok((message.clone(), Some(message.clone())))
+            }
+            _ => Err(error),
+        },
+    }
+}
+
+/// Returns the options for a field's request: the call's options with the field's
+/// temperature, when it has one, a child of the call's request ID, and no grammar, since the
+/// field is answered in text.
+pub(crate) fn field_request_options(
+    field_prompt: &FieldPrompt,
+    options: &RequestOptions,
+) -> RequestOptions {
+    RequestOptions {
+        temperature: field_prompt.temperature.or(options.temperature),
+        grammar: None,
+        request_id: options
+            .request_id
+            .as_ref()
+            .map(|request_id| child_request_id(request_id, &field_prompt.field_path)),
+        ..options.clone()
+    }
+}
+
+/// Fails the distributed generation of Tasks that cannot be extracted field by field, such
+/// as enums, see `Task::supports_distributed_generation`.
+fn require_distributed_generation<T: Task>() -> Result<(), SecretaryError> {
+    if T::supports_distributed_generation() {
+        return Ok(());
+    }
+
+    Err(SecretaryError::UnsupportedGeneration {
+        message: format!(
+            "{} picks one of several variants and cannot be extracted field by field; use generate_data instead",
+            std::any::type_name::<T>()
+        ),
+    })
+}
+
+/// Reports a failed async request as `SecretaryError::BuildRequestError` with its message,
+/// except a stream abandoned at a schema violation, which keeps its violations.
+fn async_request_error(
+    error: Box<dyn std::error::Error + Send + Sync + 'static>,
+) -> Box<dyn std::error::Error + Send + Sync + 'static> {
+    if matches!(
+        error.downcast_ref::<SecretaryError>(),
+        Some(SecretaryError::SchemaViolation { .. })
+    ) {
+        return error;
+    }
+
+    SecretaryError::BuildRequestError(error.to_string()).into()
+}
+
+/// Returns whether an error means that the deadline passed.
+fn is_deadline_exceeded(error: &(dyn std::error::Error + Send + Sync + 'static)) -> bool {
+    matches!(
+        error.downcast_ref::<SecretaryError>(),
+        Some(SecretaryError::DeadlineExceeded { .. })
+    )
+}
+
+/// Sends one request per field on scoped threads, at most `options.concurrency` at once.
+///
+/// Fields are not sent once the deadline of `options` has passed, and requests still running
+/// at the deadline time out; both are reported as incomplete.
+fn send_field_requests<L: IsLLM + Sync + ?Sized>(
+    llm: &L,
+    messages: Vec<(FieldPrompt, Message)>,
+    options: &RequestOptions,
+) -> Result<FieldResults, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let concurrency: usize = options.concurrency.unwrap_or(usize::MAX).max(1);
+    let counter: FieldCounter = FieldCounter::new(&messages, options);
+    let mut messages = messages.into_iter().peekable();
+    let mut results: FieldResults = FieldResults {
+        answers: Vec::new(),
+        incomplete: Vec::new(),
+    };
+    // Each batch of at most `concurrency` requests finishes before the next one starts
+    while messages.peek().is_some() {
+        let batch: Vec<(FieldPrompt, Message)> = messages.by_ref().take(concurrency).collect();
+        let batch_results: FieldResults = send_field_request_batch(llm, batch, options, &counter)?;
+        results.answers.extend(batch_results.answers);
+        results.incomplete.extend(batch_results.incomplete);
+    }
+
+    Ok(results)
+}
+
+/// Sends one request per field of a batch from a thread of its own.
+fn send_field_request_batch<L: IsLLM + Sync + ?Sized>(
+    llm: &L,
+    messages: Vec<(FieldPrompt, Message)>,
+    options: &RequestOptions,
+    counter: &FieldCounter,
+) -> Result<FieldResults, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    std::thread::scope(|s| {
+        let mut distributed_tasks = Vec::new();
+        let mut incomplete: Vec<String> = Vec::new();
+        for (field_prompt, message) in messages {
+            if options
+                .deadline
+                .is_some_and(|deadline| deadline.is_expired())
+            {
+                incomplete.extend(field_prompt.field_paths());
+                continue;
+            }
+
+            let field_paths: Vec<String> = field_prompt.field_paths();
+            counter.report_started(options, &field_paths);
+            let handler = s.spawn(move || {
+                let options: RequestOptions = field_request_options(&field_prompt, options);
+                let prompt: String = message.content.as_str().to_string();
+                let started: Instant = Instant::now();
+                let response: String = llm.send_message_with_options(message, false, &options)?;
+                let latency: Duration = started.elapsed();
+                let raw_response: (String, Option<String>) =
+                    field_response_content(llm, &response)?;
+
+                Ok::<Vec<FieldAnswer>, Box<dyn std::error::Error + Send + Sync + 'static>>(
+                    field_answers(&field_prompt, prompt, &response, raw_response, latency),
+                )
+            });
+
+            distributed_tasks.push((field_paths, handler));
+        }
+
+        let mut answers: Vec<FieldAnswer> = Vec::new();
+        for (field_paths, distributed_task) in distributed_tasks {
+            match distributed_task.join() {
+                Ok(result) => match result {
+                    Ok(field_answers) => {
+                        counter.report_finished(options, &field_paths);
+                        answers.extend(field_answers)
+                    }
+                    Err(error) if is_deadline_exceeded(error.as_ref()) => {
+                        incomplete.extend(field_paths)
+                    }
+                    Err(error) => return Err(error),
+                },
+                Err(_error) => panic!(),
+            }
+        }
+
+        Ok(FieldResults {
+            answers,
+            incomplete,
+        })
+    })
+}
+
+/// Sends one request per field concurrently, at most `options.concurrency` at once, like
+/// `send_field_requests`.
+///
+/// Requests still running at the deadline of `options` are cancelled.
+async fn async_send_field_requests<L: IsLLM + Sync + ?Sized>(
+    llm: &L,
+    messages: Vec<(FieldPrompt, Message)>,
+    options: &RequestOptions,
+) -> Result<FieldResults, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let counter: &FieldCounter = &FieldCounter::new(&messages, options);
+    let mut distributed_tasks = Vec::new();
+
+    for (field_prompt, message) in messages {
+        let task_future = async move {
+            let field_paths: Vec<String> = field_prompt.field_paths();
+            counter.report_started(options, &field_paths);
+            let request = async {
+                let options: RequestOptions = field_request_options(&field_prompt, options);
+                let prompt: String = message.content.as_str().to_string();
+                let started: Instant = Instant::now();
+                let response: String = llm
+                    .async_send_message_with_options(message, false, &options)
+                    .await?;
+                let latency: Duration = started.elapsed();
+                let raw_response: (String, Option<String>) =
+                    field_response_content(llm, &response)?;
+
+                Ok::<Vec<FieldAnswer>, Box<dyn std::error::Error + Send + Sync>>(field_answers(
+                    &field_prompt,
+                    prompt,
+                    &response,
+                    raw_response,
+                    latency,
+                ))
+            };
+
+            let result = match options.deadline {
+                Some(deadline) => {
+                    let timer = Delay::new(deadline.remaining());
+                    match future::select(Box::pin(request), timer).await {
+                        future::Either::Left((result, _)) => result,
+                        future::Either::Right(((), _)) => return Ok(Either::Right(field_paths)),
+                    }
+                }
+                None => request.await,
+            };
+
+            match result {
+                Ok(answers) => {
+                    counter.report_finished(options, &field_paths);
+                    Ok(Either::Left(answers))
+                }
+                Err(error) if is_deadline_exceeded(error.as_ref()) => {
+                    Ok(Either::Right(field_paths))
+                }
+                Err(error) => Err(error),
+            }
+        };
+
+        distributed_tasks.push(task_future);
+    }
+
+    let outcomes = match options.concurrency {
+        Some(concurrency) => {
+            stream::iter(distributed_tasks)
+                .buffered(concurrency.max(1))
+                .try_collect::<Vec<_>>()
+                .await?
+        }
+        None => future::try_join_all(distributed_tasks).await?,
+    };
+
+    let mut results: FieldResults = FieldResults {
+        answers: Vec::new(),
+        incomplete: Vec::new(),
+    };
+    for outcome in outcomes {
+        match outcome {
+            Either::Left(answers) => results.answers.extend(answers),
+            Either::Right(field_paths) => results.incomplete.extend(field_paths),
+        }
+    }
+
+    Ok(results)
+}
+
+/// Counts the field requests of distributed generation that were answered, for their
+/// progress events.
+struct FieldCounter {
+    total: usize,
+    completed: AtomicUsize,
+}
+
+impl FieldCounter {
+    /// Counts the fields of the requests, only when they are reported.
+    fn new(messages: &[(FieldPrompt, Message)], options: &RequestOptions) -> Self {
+        let total: usize = match options.progress {
+            Some(_) => messages
+                .iter()
+                .map(|(field_prompt, _)| field_prompt.field_paths().len())
+                .sum(),
+            None => 0,
+        };
+
+        Self {
+            total,
+            completed: AtomicUsize::new(0),
+        }
+    }
+
+    /// Reports that the request of the fields is about to be sent.
+    fn report_started(&self, options: &RequestOptions, field_paths: &[String]) {
+        let Some(progress) = &options.progress else {
+            return;
+        };
+        for path in field_paths {
+            progress.report(ProgressKind::FieldStarted {
+                path: path.clone(),
+                total: self.total,
+            });
+        }
+    }
+
+    /// Reports that the answer of the fields arrived.
+    fn report_finished(&self, options: &RequestOptions, field_paths: &[String]) {
+        let Some(progress) = &options.progress else {
+            return;
+        };
+        for path in field_paths {
+            progress.report(ProgressKind::FieldFinished {
+                path: path.clone(),
+                completed: self.completed.fetch_add(1, Ordering::Relaxed) + 1,
+                total: self.total,
+            });
+        }
+    }
+}
+
+/// Merges the field results of update mode into a copy of `existing`.
+fn merge_field_results<L: IsLLM + ?Sized, T: Task>(
+    llm: &L,
+    existing: &T,
+    distributed_tasks_results: Vec<(String, String)>,
+) -> Result<T, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let mut value: Value = serde_json::to_value(existing)?;
+    let fields: Vec<FieldDescriptor> = T::field_descriptors();
+    for (field_path, content) in distributed_tasks_results {
+        set_field_path(
+            &mut value,
+            &field_path,
+            parse_described_field_value(&content, &field_path, &fields),
+        )?;
+    }
+    llm.get_leniency().apply::<T>(&mut value);
+
+    match from_value_with_defaults::<T>(&fields, value) {
+        Ok(result) => Ok(result),
+        Err(error) => {
+            record_parse_failed::<T>(llm.get_metrics_sink(), MetricMode::Distributed, None);
+            Err(Box::new(SecretaryError::SerdeJsonError(error)))
+        }
+    }
+}
+
+/// Sends the requests of chunked generation from at most `concurrency` threads and returns
+/// the text content of each response, in request order.
+///
+/// The start and end of each request are reported to `progress` as chunks.
+pub(crate) fn send_chunk_requests<L: IsLLM + Sync + ?Sized>(
+    llm: &L,
+    requests: Vec<Vec<Message>>,
+    concurrency: usize,
+    progress: Option<&Progress>,
+) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let next: AtomicUsize = AtomicUsize::new(0);
+    let completed: AtomicUsize = AtomicUsize::new(0);
+    let requests: &Vec<Vec<Message>> = &requests;
+    let total: usize = requests.len();
+    let options: &RequestOptions = &chunk_request_options(progress);
+
+    let mut responses: Vec<(usize, String)> = std::thread::scope(|s| {
+        let workers: Vec<_> = (0..concurrency.clamp(1, requests.len().max(1)))
+            .map(|_| {
+                s.spawn(|| {
+                    let mut responses: Vec<(usize, String)> = Vec::new();
+                    loop {
+                        let index: usize = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(messages) = requests.get(index) else {
+                            break;
+                        };
+                        report(progress, || ProgressKind::ChunkStarted { index, total });
+                        let response: String =
+                            llm.send_messages_with_options(messages.clone(), true, options)?;
+                        responses.push((index, extract_json_content(llm, &response)?));
+                        report(progress, || ProgressKind::ChunkFinished {
+                            index,
+                            completed: completed.fetch_add(1, Ordering::Relaxed) + 1,
+                            total,
+                        });
+                    }
+
+                    Ok::<Vec<(usize, String)>, Box<dyn std::error::Error + Send + Sync + 'static>>(
+                        responses,
+                    )
+                })
+            })
+            .collect();
+
+        let mut responses: Vec<(usize, String)> = Vec::new();
+        for worker in workers {
+            match worker.join() {
+                Ok(result) => responses.extend(result?),
+                Err(_error) => panic!(),
+            }
+        }
+
+        Ok::<Vec<(usize, String)>, Box<dyn std::error::Error + Send + Sync + 'static>>(responses)
+    })?;
+    responses.sort_by_key(|(index, _)| *index);
+
+    Ok(responses.into_iter().map(|(_, content)| content).collect())
+}
+
+/// Parses the contents of the chunk responses of chunked generation, with the text of each
+/// chunk, into the results of the chunks and their inputs to the merge.
+fn parse_chunk_results<L: IsLLM + ?Sized, T: Task>(
+    llm: &L,
+    contents: Vec<String>,
+    texts: Vec<String>,
+    options: &ChunkOptions,
+) -> Result<(Vec<T>, Vec<ChunkValue>), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let mut results: Vec<T> = Vec::with_capacity(contents.len());
+    let mut chunks: Vec<ChunkValue> = Vec::with_capacity(contents.len());
+    for (content, text) in contents.into_iter().zip(texts) {
+        let (content, _) = limit_content(llm, &RequestOptions::default(), content)?;
+        let (content, confidence) = if options.confidence {
+            take_confidence(&content)
+        } else {
+            (content, BTreeMap::new())
+        };
+        let result: T = parse_json_content::<L, T>(llm, &content)?;
+        chunks.push(ChunkValue {
+            value: serde_json::to_value(&result)?,
+            confidence,
+            text,
+        });
+        results.push(result);
+    }
+
+    Ok((results, chunks))
+}
+
+/// Returns the options of the requests of chunked generation, which report their retries to
+/// `progress`.
+fn chunk_request_options(progress: Option<&Progress>) -> RequestOptions {
+    RequestOptions {
+        progress: progress.cloned(),
+        ..RequestOptions::default()
+    }
+}
+
+/// Reports the end of chunked generation for a target that fit in a single chunk.
+fn report_single_chunk_finished(progress: Option<&Progress>) {
+    report(progress, || ProgressKind::ChunkFinished {
+        index: 0,
+        completed: 1,
+        total: 1,
+    });
+    report(progress, || ProgressKind::Completed);
+}
+
+/// Deserializes the value of a local merge.
+fn merged_data<T: Task>(merge: &ChunkMerge) -> Result<T, SecretaryError> {
+    Ok(serde_json::from_value::<T>(merge.value.clone())?)
+}
+
+/// Returns the result of a chunked extraction, with the sources and conflicts of its merge.
+fn chunked_result<T>(data: T, merge: ChunkMerge) -> GenerationResult<T> {
+    GenerationResult {
+        data,
+        metadata: GenerationMetadata {
+            chunk_sources: merge.sources,
+            merge_conflicts: merge.conflicts,
+            ..GenerationMetadata::default()
+        },
+    }
+}
+
+/// Returns the output limits of a call: those of the options, or else the provider's.
+fn output_limits<L: IsLLM + ?Sized>(llm: &L, options: &RequestOptions) -> OutputLimits {
+    options
+        .output_limits
+        .or_else(|| llm.get_output_limits().copied())
+        .unwrap_or_default()
+}
+
+/// Checks JSON returned by the LLM against the output limits of a call, see
+/// `OutputLimits::enforce_content`.
+pub(crate) fn limit_content<L: IsLLM + ?Sized>(
+    llm: &L,
+    options: &RequestOptions,
+    content: String,
+) -> Result<(String, Vec<ClippedValue>), SecretaryError> {
+    output_limits(llm, options).enforce_content(content)
+}
+
+/// Removes the keys of the model's JSON that are not fields of the plan's Task under the
+/// unknown key policy of the call, or else of the provider, see the `trimming` module.
+fn trim_content<L: IsLLM + ?Sized, P: ExtractionPlan + ?Sized>(
+    llm: &L,
+    options: &RequestOptions,
+    plan: &P,
+    content: String,
+) -> (String, Vec<TrimmedKey>) {
+    options
+        .unknown_keys
+        .unwrap_or_else(|| llm.get_unknown_keys())
+        .trim_content(&plan.field_table(), content)
+}
+
+/// Returns the locale of the call, or the provider's when the call has none.
+fn extraction_locale<'a, L: IsLLM + ?Sized>(
+    llm: &'a L,
+    options: &'a RequestOptions,
+) -> Option<&'a ExtractionLocale> {
+    options.locale.as_ref().or_else(|| llm.get_locale())
+}
+
+/// Reads the numbers and dates of the model's JSON in the conventions of the call's locale,
+/// see the `locale` module.
+fn localize_content<L: IsLLM + ?Sized, P: ExtractionPlan + ?Sized>(
+    llm: &L,
+    options: &RequestOptions,
+    plan: &P,
+    content: String,
+) -> String {
+    match extraction_locale(llm, options) {
+        Some(locale) => locale.localize_content(&plan.field_table(), content),
+        None => content,
+    }
+}
+
+/// Normalizes the answers of `one_of` fields under the call's `OneOfPolicy`, or the
+/// provider's when the call has none.
+fn normalize_content<L: IsLLM + ?Sized, P: ExtractionPlan + ?Sized>(
+    llm: &L,
+    options: &RequestOptions,
+    plan: &P,
+    content: String,
+) -> Result<(String, Vec<NormalizedValue>), SecretaryError> {
+    one_of_policy(llm, options).normalize_content(&plan.field_table(), content)
+}
+
+/// Returns the `OneOfPolicy` of the call, or the provider's when the call has none.
+fn one_of_policy<L: IsLLM + ?Sized>(llm: &L, options: &RequestOptions) -> OneOfPolicy {
+    options
+        .one_of_policy
+        .unwrap_or_else(|| llm.get_one_of_policy())
+}
+
+/// Returns the request ID of the options of a call, set by `traced_options`.
+fn request_id(options: &RequestOptions) -> &str {
+    options.request_id.as_deref().unwrap_or_default()
+}
+
+/// Checks data parsed from mixed text against the provider's output limits, clipping it
+/// under `LimitPolicy::Truncate`.
+fn limit_data<L: IsLLM + ?Sized, T: Task>(llm: &L, data: T) -> Result<T, SecretaryError> {
+    let limits: OutputLimits = output_limits(llm, &RequestOptions::default());
+    if limits.is_unlimited() {
+        return Ok(data);
+    }
+
+    let mut value: Value = serde_json::to_value(&data)?;
+    if limits.enforce(&mut value)?.is_empty() {
+        return Ok(data);
+    }
+
+    Ok(serde_json::from_value(value)?)
+}
+
+/// Extracts the JSON the model answered with from a response body, without inline reasoning,
+/// repairing its invalid escapes under the provider's `DecodingPolicy`, see the `decoding`
+/// module.
+pub(crate) fn extract_json_content<L: IsLLM + ?Sized>(
+    llm: &L,
+    response: &str,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let content: String = strip_inline_reasoning(llm.extract_response_content(response)?, response);
+    Ok(llm.get_decoding_policy().repair_content(content).0)
+}
+
+/// Returns the reasoning a response reports apart from its answer, unless the options hide
+/// it, see the `reasoning` module.
+fn reported_reasoning(options: &RequestOptions, response: &str) -> Option<String> {
+    if options.hide_reasoning {
+        return None;
+    }
+
+    extract_reasoning_from_llm_response(response)
+}
+
+/// Extracts the content of a response like `extract_json_content`, converting an answer in
+/// another response format into JSON.
+pub(crate) fn extract_formatted_content<L: IsLLM + ?Sized, P: ExtractionPlan + ?Sized>(
+    llm: &L,
+    plan: &P,
+    response: &str,
+    format: ResponseFormat,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    if format.is_json() {
+        return extract_json_content(llm, response);
+    }
+
+    let content: String = llm.extract_response_content(response)?;
+    Ok(format.to_json_content(&content, &plan.field_table())?)
+}
+
+/// Binds the `Lazy` fields of extracted data to the call, see the `lazy` module. The data of
+/// Tasks without lazy fields, or from providers without a lazy source, is returned as it is.
+fn bind_lazy<L: IsLLM + ?Sized, T: Task>(
+    llm: &L,
+    mut data: T,
+    guarded: &GuardedRequest,
+    options: &RequestOptions,
+) -> T {
+    if with_lazy_fields(&[], &T::field_descriptors()).is_empty() {
+        return data;
+    }
+    if let Some(source) = llm.get_lazy_source() {
+        data.bind_lazy_fields(&LazyBinding::new(
+            source,
+            &guarded.target,
+            guarded.instructions(),
+            options,
+        ));
+    }
+
+    data
+}
+
+/// Appends the instruction of a response format other than JSON to the messages of a plan,
+/// with the JSON template of its Task rendered in the format.
+fn frame_for_format<P: ExtractionPlan + ?Sized>(
+    plan: &P,
+    messages: Vec<Message>,
+    format: ResponseFormat,
+) -> Vec<Message> {
+    if format.is_json() {
+        return messages;
+    }
+
+    let fields: Cow<'_, [FieldDescriptor]> = plan.field_table();
+    let mut template: Value = serde_json::to_value(plan.task()).unwrap_or_default();
+    for path in with_lazy_fields(&[], &fields) {
+        remove_field_path(&mut template, &path);
+    }
+    to_prompt_keys(&fields, &mut template);
+    format.frame_messages(messages, &template)
+}
+
+/// Parses JSON returned by the LLM into `T`, recording a parse failure on error.
+fn parse_json_content<L: IsLLM + ?Sized, T: Task>(
+    llm: &L,
+    content: &str,
+) -> Result<T, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    match llm.get_leniency().from_str::<T>(content) {
+        Ok(result) => Ok(result),
+        Err(error) => {
+            record_parse_failed::<T>(llm.get_metrics_sink(), MetricMode::Json, None);
+            Err(Box::new(json_parsing_error(error, content)))
+        }
+    }
+}
+
+/// Parses JSON returned by the LLM like `parse_json_content`, coercing it with the field
+/// descriptors of the plan.
+pub(crate) fn parse_plan_content<L: IsLLM + ?Sized, P: ExtractionPlan + ?Sized>(
+    llm: &L,
+    plan: &P,
+    content: &str,
+) -> Result<P::Task, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    parse_plan_content_with(llm, plan, content, llm.get_leniency())
+}
+
+/// Parses the JSON of a plan's Task like `parse_plan_content`, without the coercions of the
+/// provider's leniency when the answer was constrained to a grammar, see the `grammar`
+/// module.
+fn parse_answer_content<L: IsLLM + ?Sized, P: ExtractionPlan + ?Sized>(
+    llm: &L,
+    plan: &P,
+    content: &str,
+    options: &RequestOptions,
+) -> Result<P::Task, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let strict: LeniencyProfile = LeniencyProfile::strict();
+    let leniency: &LeniencyProfile = match constrained_grammar(llm, options) {
+        Some(_) => &strict,
+        None => llm.get_leniency(),
+    };
+
+    parse_plan_content_with(llm, plan, content, leniency)
+}
+
+fn parse_plan_content_with<L: IsLLM + ?Sized, P: ExtractionPlan + ?Sized>(
+    llm: &L,
+    plan: &P,
+    content: &str,
+    leniency: &LeniencyProfile,
+) -> Result<P::Task, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let parsed: Result<P::Task, serde_json::Error> =
+        leniency.from_str_with_fields::<P::Task>(&plan.field_table(), content);
+
+    match parsed {
+        Ok(result) => Ok(result),
+        Err(error) => {
+            record_parse_failed::<P::Task>(llm.get_metrics_sink(), MetricMode::Json, None);
+            Err(Box::new(json_parsing_error(error, content)))
+        }
+    }
+}
+
+/// Describes a re-extraction, see the `incremental` module.
+fn regeneration_metadata<T: Task>(
+    path: RegenerationPath,
+    diff: &TextDiff,
+    injection_verdict: Option<InjectionVerdict>,
+) -> GenerationMetadata {
+    GenerationMetadata {
+        prompt_version: Some(T::prompt_version()),
+        injection_verdict,
+        regeneration_path: Some(path),
+        changed_ratio: Some(diff.changed_ratio()),
+        ..GenerationMetadata::default()
+    }
+}
+
+/// Parses every choice of a response to a self-consistency request and votes on the
+/// samples that parse, see the `consistency` module.
+fn vote_on_choices<L: IsLLM + ?Sized, P: ExtractionPlan + ?Sized>(
+    llm: &L,
+    plan: &P,
+    options: &RequestOptions,
+    local_values: &[(String, String)],
+    response: &str,
+    k: usize,
+    injection_verdict: Option<InjectionVerdict>,
+) -> Result<GenerationResult<P::Task>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let contents: Vec<String> = llm.extract_response_contents(response)?;
+    let returned: usize = contents.len();
+
+    let mut samples: Vec<Value> = Vec::new();
+    let mut first_error: Option<Box<dyn std::error::Error + Send + Sync + 'static>> = None;
+    for content in contents {
+        let content: String = llm.get_decoding_policy().repair_content(content).0;
+        let content: String =
+            merge_hint_values(merge_local_values(content, local_values), &options.hints).0;
+        let content: String = trim_content(llm, options, plan, content).0;
+        let content: String = localize_content(llm, options, plan, content);
+        let parsed: Result<P::Task, Box<dyn std::error::Error + Send + Sync + 'static>> =
+            normalize_content(llm, options, plan, content)
+                .and_then(|(content, _)| limit_content(llm, options, content))
+                .map_err(Into::into)
+                .and_then(|(content, _)| parse_plan_content(llm, plan, &content));
+        match parsed {
+            Ok(sample) => samples.push(serde_json::to_value(&sample)?),
+            Err(error) => {
+                first_error.get_or_insert(error);
+            }
+        }
+    }
+    if samples.is_empty() {
+        return Err(first_error.unwrap_or_else(|| SecretaryError::NoLLMResponse.into()));
+    }
+
+    let (voted, field_agreement) = vote(&plan.field_table(), &samples);
+    let data: P::Task = serde_json::from_value(voted.clone())
+        .map_err(|error| json_parsing_error(error, &voted.to_string()))?;
+
+    let fingerprints: Vec<String> = extract_system_fingerprint_from_llm_response(response)
+        .into_iter()
+        .collect();
+    let mut warnings: Vec<GenerationWarning> =
+        GenerationWarning::for_fingerprints(options.seed, &fingerprints);
+    if returned < k {
+        warnings.push(GenerationWarning::ChoicesIgnored {
+            requested: k,
+            returned,
+        });
+    }
+
+    Ok(GenerationResult {
+        data,
+        metadata: GenerationMetadata {
+            prompt_version: Some(P::Task::prompt_version()),
+            locally_extracted: local_paths(local_values),
+            injection_verdict,
+            seed: options.seed,
+            system_fingerprint: fingerprints.first().cloned(),
+            locale: extraction_locale(llm, options).cloned(),
+            warnings,
+            voted_choices: Some(samples.len()),
+            field_agreement,
+            request_id: options.request_id.clone(),
+            usage: TokenUsage::from_response(response),
+            ..GenerationMetadata::default()
+        },
+    })
+}
+
+/// Deserializes `T` from a response to the provenance prompt, locating the evidence quotes
+/// in `target`. Parse errors carry the response as returned, wrappers included.
+fn parse_provenance_content<L: IsLLM + ?Sized, T: Task>(
+    llm: &L,
+    content: &str,
+    target: &str,
+) -> Result<ProvenanceResult<T>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let value: Value = match serde_json::from_str(content) {
+        Ok(value) => value,
+        Err(error) => {
+            record_parse_failed::<T>(llm.get_metrics_sink(), MetricMode::Json, None);
+            return Err(Box::new(json_parsing_error(error, content)));
+        }
+    };
+    let (mut value, provenance) = strip_evidence::<T>(&value, target);
+    llm.get_leniency().apply::<T>(&mut value);
+
+    match from_value_with_defaults::<T>(&T::field_descriptors(), value) {
+        Ok(data) => Ok(ProvenanceResult { data, provenance }),
+        Err(error) => {
+            record_parse_failed::<T>(llm.get_metrics_sink(), MetricMode::Json, None);
+            Err(Box::new(json_parsing_error(error, content)))
+        }
+    }
+}
+
+/// Parses the answer to an explanation request, after the provider's output limits.
+fn parse_explanation_content<L: IsLLM + ?Sized>(
+    llm: &L,
+    request: &ExplanationRequest,
+    content: String,
+    target: &str,
+) -> Result<Explanation, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let (content, _) = limit_content(llm, &RequestOptions::default(), content)?;
+
+    request
+        .parse(&content, target)
+        .map_err(|error| Box::new(json_parsing_error(error, &content)).into())
+}
+
+/// Creates the distributed generation requests of the critical fields of `task`, including
+/// the fields of nested Tasks marked critical.
+fn critical_field_requests<P: ExtractionPlan>(
+    task: &P,
+    target: &str,
+    additional_instructions: &[String],
+) -> Vec<(FieldPrompt, Message)> {
+    let critical_paths: Vec<String> = critical_field_paths(&task.field_table());
+
+    task.field_requests(target, additional_instructions)
+        .into_iter()
+        .filter_map(|(mut field_prompt, message)| {
+            let is_critical = |field_prompt: &FieldPrompt| {
+                field_prompt.importance == Importance::Critical
+                    || critical_paths.iter().any(|path| {
+                        field_prompt.field_path == *path
+                            || field_prompt.field_path.starts_with(&format!("{}.", path))
+                            || field_prompt.field_path.starts_with(&format!("{}[", path))
+                    })
+            };
+            field_prompt
+                .retain_fields(is_critical)
+                .then_some((field_prompt, message))
+        })
+        .collect()
+}
+
+/// Removes the requests of the fields that local extractors have filled.
+///
+/// A group's request keeps asking for all of its fields, but only the others are taken from
+/// its answer.
+fn without_local_fields(
+    requests: Vec<(FieldPrompt, Message)>,
+    local_values: &[(String, String)],
+) -> Vec<(FieldPrompt, Message)> {
+    requests
+        .into_iter()
+        .filter_map(|(mut field_prompt, message)| {
+            field_prompt
+                .retain_fields(|field_prompt| {
+                    !local_values
+                        .iter()
+                        .any(|(field_path, _)| *field_path == field_prompt.field_path)
+                })
+                .then_some((field_prompt, message))
+        })
+        .collect()
+}
+
+/// Returns the options of a request for a whole Task: on providers that take a grammar, and
+/// unless the options have one, the options with the grammar of the Task without the skipped
+/// and lazy fields, see the `grammar` module.
+fn with_task_grammar<'a, L: IsLLM + ?Sized, P: ExtractionPlan + ?Sized>(
+    llm: &L,
+    options: &'a RequestOptions,
+    plan: &P,
+    skipped_fields: &[String],
+) -> Cow<'a, RequestOptions> {
+    if options.grammar.is_some()
+        || !options.response_format.is_json()
+        || llm.get_capabilities().constrained_decoding != ConstrainedDecoding::Gbnf
+    {
+        return Cow::Borrowed(options);
+    }
+
+    let fields: Cow<'_, [FieldDescriptor]> = plan.field_table();
+    let mut options: RequestOptions = options.clone();
+    options.grammar = Some(gbnf_grammar_without_fields(
+        &fields,
+        &with_lazy_fields(skipped_fields, &fields),
+    ));
+    Cow::Owned(options)
+}
+
+/// Returns the grammar the answer to a request is constrained to, if the options have one
+/// and the provider takes it.
+fn constrained_grammar<'a, L: IsLLM + ?Sized>(
+    llm: &L,
+    options: &'a RequestOptions,
+) -> Option<&'a str> {
+    options
+        .grammar
+        .as_deref()
+        .filter(|_| llm.get_capabilities().constrained_decoding == ConstrainedDecoding::Gbnf)
+}
+
+/// Returns the paths of the fields that local extractors have filled.
+fn local_paths(local_values: &[(String, String)]) -> Vec<String> {
+    local_values
+        .iter()
+        .map(|(field_path, _)| field_path.clone())
+        .collect()
+}
+
+/// Deserialized data with the values clipped by the output limits and the answers
+/// normalized to the allowed values of `one_of` fields.
+type CheckedData<T> = (T, Vec<ClippedValue>, Vec<NormalizedValue>);
+
+/// Parses the JSON of a single request and replaces the given fields with the results of
+/// their own requests before deserializing `T`, returning the values clipped by the output
+/// limits.
+fn overlay_field_results<L: IsLLM + ?Sized, T: Task>(
+    llm: &L,
+    options: &RequestOptions,
+    content: &str,
+    mut field_results: Vec<(String, String)>,
+) -> Result<CheckedData<T>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let mut value: Value = match serde_json::from_str(content) {
+        Ok(value) => value,
+        Err(error) => {
+            record_parse_failed::<T>(llm.get_metrics_sink(), MetricMode::Json, None);
+            return Err(Box::new(json_parsing_error(error, content)));
+        }
+    };
+    let fields: Vec<FieldDescriptor> = T::field_descriptors();
+    let normalized: Vec<NormalizedValue> =
+        normalize_field_results(&fields, &mut field_results, one_of_policy(llm, options))?;
+    if let Some(locale) = extraction_locale(llm, options) {
+        locale.localize_field_results(&fields, &mut field_results);
+    }
+    for (field_path, field_content) in field_results {
+        set_field_path(
+            &mut value,
+            &field_path,
+            parse_described_field_value(&field_content, &field_path, &fields),
+        )?;
+    }
+    let clipped: Vec<ClippedValue> = output_limits(llm, options).enforce(&mut value)?;
+    llm.get_leniency().apply::<T>(&mut value);
+    apply_default_values::<T>(&fields, &mut value);
+
+    match serde_json::from_value::<T>(value.clone()) {
+        Ok(result) => Ok((result, clipped, normalized)),
+        Err(_) => {
+            let error: FieldDeserializationError = diagnose::<T>(&value);
+            record_parse_failed::<T>(
+                llm.get_metrics_sink(),
+                MetricMode::Json,
+                Some(error.failed_fields.len()),
+            );
+            Err(Box::new(SecretaryError::FieldDeserializationError(error)))
+        }
+    }
+}
+
+/// Wraps a parse failure together with the content that failed to parse, or returns
+/// `SecretaryError::Refused` when the content is a refusal.
+fn json_parsing_error(error: serde_json::Error, content: &str) -> SecretaryError {
+    if let Some(message) = detect_refusal(content) {
+        return SecretaryError::Refused { message };
+    }
+
+    SecretaryError::JsonParsingError {
+        message: error.to_string(),
+        raw_content: content.to_string(),
+    }
+}
+
+/// Deserializes `T` from the field results of distributed generation and the values found
+/// by local extractors.
+///
+/// On failure the error reports the failing paths together with the raw content returned
+/// for every field. On success the values clipped by the output limits are returned with `T`.
+fn fields_from_results<L: IsLLM + ?Sized, T: Task>(
+    llm: &L,
+    options: &RequestOptions,
+    fields: &[FieldDescriptor],
+    mut distributed_tasks_results: Vec<(String, String)>,
+    local_values: &[(String, String)],
+) -> Result<CheckedData<T>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let normalized: Vec<NormalizedValue> = normalize_field_results(
+        fields,
+        &mut distributed_tasks_results,
+        one_of_policy(llm, options),
+    )?;
+    if let Some(locale) = extraction_locale(llm, options) {
+        locale.localize_field_results(fields, &mut distributed_tasks_results);
+    }
+    match assemble_field_results::<T>(
+        llm.get_leniency(),
+        &output_limits(llm, options),
+        fields,
+        distributed_tasks_results,
+        local_values,
+        &options.hints,
+    ) {
+        Err(SecretaryError::FieldDeserializationError(error)) => {
+            record_parse_failed::<T>(
+                llm.get_metrics_sink(),
+                MetricMode::Distributed,
+                Some(error.failed_fields.len()),
+            );
+            Err(Box::new(SecretaryError::FieldDeserializationError(error)))
+        }
+        Ok((data, clipped)) => Ok((data, clipped, normalized)),
+        Err(error) => Err(Box::new(error)),
+    }
+}
+
+/// Deserializes `T` from field results, the values found by local extractors and the hinted
+/// values, like `fields_from_results` without recording metrics.
+pub(crate) fn assemble_field_results<T: Task>(
+    leniency: &LeniencyProfile,
+    limits: &OutputLimits,
+    fields: &[FieldDescriptor],
+    distributed_tasks_results: Vec<(String, String)>,
+    local_values: &[(String, String)],
+    hints: &BTreeMap<String, Value>,
+) -> Result<(T, Vec<ClippedValue>), SecretaryError> {
+    let mut value: Value = collect_field_results(leniency, fields, &distributed_tasks_results)?;
+    set_local_values(&mut value, local_values);
+    set_hint_values(&mut value, hints);
+    let clipped: Vec<ClippedValue> = limits.enforce(&mut value)?;
+    apply_default_values::<T>(fields, &mut value);
+
+    match T::deserialize(&value) {
+        Ok(result) => Ok((result, clipped)),
+        Err(_) => {
+            let mut error: FieldDeserializationError = diagnose::<T>(&value);
+            error
+                .failed_fields
+                .extend(absent_required_fields::<T>(&value));
+            error.raw_field_contents = distributed_tasks_results.into_iter().collect();
+            Err(SecretaryError::FieldDeserializationError(error))
+        }
+    }
+}
+
+/// The target and additional instructions of an extraction after the provider's guardrail.
+pub(crate) struct GuardedRequest<'a> {
+    guardrail: Option<&'a Guardrail>,
+    /// The target after the guardrail's policy, without delimiters, for local extractors.
+    /// Borrowed from the call without a guardrail.
+    pub(crate) text: Cow<'a, str>,
+    /// The target as it is sent, wrapped in delimiters when the prompt is hardened. Borrowed
+    /// from the call without a guardrail.
+    pub(crate) target: Cow<'a, str>,
+    additional_instructions: &'a Vec<String>,
+    hardened_instructions: Option<Vec<String>>,
+    /// The guardrail's verdict, `None` without a guardrail.
+    pub(crate) verdict: Option<InjectionVerdict>,
+}
+
+impl GuardedRequest<'_> {
+    /// Returns the additional instructions, with the hardening instruction when the prompt is
+    /// hardened.
+    pub(crate) fn instructions(&self) -> &Vec<String> {
+        self.hardened_instructions
+            .as_ref()
+            .unwrap_or(self.additional_instructions)
+    }
+
+    /// Wraps a part of the target, such as a chunk, like the whole target.
+    fn wrap(&self, text: &str) -> String {
+        match self.guardrail {
+            Some(guardrail) => guardrail.wrap_target(text),
+            None => text.to_string(),
+        }
+    }
+}
+
+/// Runs the provider's guardrail over the target, if one is set.
+///
+/// Findings are reported to the metrics sink as `MetricEvent::InjectionDetected`.
+///
+/// # Errors
+///
+/// Returns `SecretaryError::PromptInjectionDetected` if the guardrail rejects the target.
+pub(crate) fn guard_request<'a, L: IsLLM + ?Sized>(
+    llm: &'a L,
+    target: &'a str,
+    additional_instructions: &'a Vec<String>,
+) -> Result<GuardedRequest<'a>, SecretaryError> {
+    let Some(guardrail) = llm.get_guardrail() else {
+        return Ok(GuardedRequest {
+            guardrail: None,
+            text: Cow::Borrowed(target),
+            target: Cow::Borrowed(target),
+            additional_instructions,
+            hardened_instructions: None,
+            verdict: None,
+        });
+    };
+
+    let guarded = guardrail.inspect(target);
+    if guarded.verdict.is_suspicious() {
+        llm.get_metrics_sink()
+            .record(MetricEvent::InjectionDetected {
+                signals: guarded.verdict.signals(),
+                action: guarded.verdict.action,
+            });
+    }
+    if guarded.verdict.action == GuardrailAction::Rejected {
+        return Err(SecretaryError::PromptInjectionDetected {
+            findings: guarded.verdict.findings,
+        });
+    }
+
+    Ok(GuardedRequest {
+        guardrail: Some(guardrail),
+        target: Cow::Owned(guardrail.wrap_target(&guarded.text)),
+        additional_instructions,
+        hardened_instructions: Some(guardrail.harden_instructions(additional_instructions)),
+        text: Cow::Owned(guarded.text),
+        verdict: Some(guarded.verdict),
+    })
+}
+
+/// Formats a cacheable prefix message holding the system prompt and the additional
+/// instructions, followed by a message holding the target.
+pub(crate) fn make_prefixed_messages(
+    mut system_prompt: String,
+    additional_instructions: &[String],
+    target: &str,
+) -> Vec<Message> {
+    write_additional_instructions(&mut system_prompt, additional_instructions);
+
+    vec![
+        Message::system(system_prompt),
+        Message::user(format!(
+            "This is the basis for generating a json:\n{}",
+            target
+        )),
+    ]
+}
+
+/// Formats the messages for a runtime task in the layout of `Task::make_prompt_messages`.
+fn make_value_prompt(
+    task: &dyn DynTask,
+    target: &str,
+    additional_instructions: &[String],
+) -> Vec<Message> {
+    make_prefixed_messages(task.system_prompt(), additional_instructions, target)
+}
+
+/// Formats one message per field of a runtime task, like
+/// `Task::make_distributed_generation_requests`.
+fn make_value_field_requests(
+    task: &dyn DynTask,
+    target: &str,
+    additional_instructions: &[String],
+) -> Vec<(FieldPrompt, Message)> {
+    let instructions: String = format_additional_instructions(additional_instructions);
+    task.distributed_field_prompts()
+        .into_iter()
+        .map(|field_prompt| {
+            let message: Message = make_field_message(&field_prompt, None, target, &instructions);
+            (field_prompt, message)
+        })
+        .collect()
+}
+
+/// Parses the JSON returned for a runtime task and coerces it to the task's fields.
+fn parse_value<L: IsLLM + ?Sized>(
+    llm: &L,
+    task: &dyn DynTask,
+    content: &str,
+) -> Result<Value, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let fields: Vec<FieldDescriptor> = task.fields();
+    let mut value: Value = match serde_json::from_str::<Value>(content) {
+        Ok(value) => value,
+        Err(error) => {
+            llm.get_metrics_sink().record(MetricEvent::ParseFailed {
+                mode: MetricMode::Json,
+                field_count_failed: fields.len(),
+            });
+            return Err(Box::new(json_parsing_error(error, content)));
+        }
+    };
+    llm.get_leniency().apply_to_fields(&fields, &mut value);
+
+    Ok(value)
+}
+
+/// Assembles the field results of a runtime task into a JSON object.
+fn assemble_field_values<L: IsLLM + ?Sized>(
+    llm: &L,
+    task: &dyn DynTask,
+    distributed_tasks_results: Vec<(String, String)>,
+) -> Result<Value, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let fields: Vec<FieldDescriptor> = task.fields();
+    let mut value: Value = Value::Object(serde_json::Map::new());
+    for (field_path, content) in distributed_tasks_results {
+        set_field_path(
+            &mut value,
+            &field_path,
+            parse_described_field_value(&content, &field_path, &fields),
+        )?;
+    }
+    llm.get_leniency().apply_to_fields(&fields, &mut value);
+
+    Ok(value)
+}
+
+/// Collects the field results of distributed generation into a JSON object with the given
+/// fields, with the strings of Tasks with `normalize_strings` normalized.
+fn collect_field_results(
+    leniency: &LeniencyProfile,
+    fields: &[FieldDescriptor],
+    distributed_tasks_results: &[(String, String)],
+) -> Result<Value, SecretaryError> {
+    let mut value: Value = Value::Object(serde_json::Map::new());
+    for (field_path, content) in distributed_tasks_results {
+        set_field_path(
+            &mut value,
+            field_path,
+            parse_described_field_value(content, field_path, fields),
+        )?;
+    }
+    leniency.apply_to_fields(fields, &mut value);
+    normalize_strings(fields, &mut value);
+    restore_tuple_structs(&mut value, fields);
+
+    Ok(value)
+}
+
+/// Deserializes `T` with defaults substituted for the failing paths, recording the failures.
+///
+/// Fields with a `default_value` are set to it first, so only the fields without one are
+/// replaced by their `Default` and reported as failed.
+///
+/// The fields the model refused, left out of `value`, are reported as failed.
+///
+/// Fails with `SecretaryError::CriticalFieldsMissing` instead if a critical field failed, did
+/// not complete or has no value.
+fn partial_from_value<L: IsLLM + ?Sized, T: Task>(
+    llm: &L,
+    mode: MetricMode,
+    value: &Value,
+    incomplete_fields: Vec<String>,
+    refused_fields: Vec<String>,
+) -> Result<PartialData<T>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let mut value: Value = value.clone();
+    apply_default_values::<T>(&T::field_descriptors(), &mut value);
+
+    match deserialize_partial::<T>(&value) {
+        Ok(mut partial) => {
+            partial.incomplete_fields = incomplete_fields;
+            // Group members left out of their group's answer were filled in from the defaults
+            if mode == MetricMode::Distributed {
+                let absent: Vec<String> = absent_required_fields::<T>(&value)
+                    .into_iter()
+                    .filter(|path| {
+                        !partial.incomplete_fields.iter().any(|incomplete| {
+                            incomplete == path
+                                || incomplete.starts_with(&format!("{}.", path))
+                                || incomplete.starts_with(&format!("{}[", path))
+                        })
+                    })
+                    .collect();
+                partial.failed_fields.extend(absent);
+            }
+            for path in refused_fields {
+                if !partial.failed_fields.contains(&path) {
+                    partial.failed_fields.push(path);
+                }
+            }
+            if !partial.is_complete() {
+                record_parse_failed::<T>(
+                    llm.get_metrics_sink(),
+                    mode,
+                    Some(partial.failed_fields.len()),
+                );
+            }
+
+            let missing: Vec<String> = missing_critical_fields::<T>(
+                &value,
+                &partial.failed_fields,
+                &partial.incomplete_fields,
+            );
+            if !missing.is_empty() {
+                return Err(Box::new(SecretaryError::CriticalFieldsMissing {
+                    fields: missing,
+                }));
+            }
+
+            Ok(partial)
+        }
+        Err(error) => {
+            record_parse_failed::<T>(
+                llm.get_metrics_sink(),
+                mode,
+                Some(error.failed_fields.len()),
+            );
+            Err(Box::new(SecretaryError::FieldDeserializationError(error)))
+        }
+    }
+}
+
+/// Builds the request body for a conversation, asking for JSON in the last message when JSON
+/// is requested but `response_format` is not used.
+///
+/// System messages are first rewritten under the provider's `SystemRoleStrategy`.
+pub(crate) fn build_request_body<L: IsLLM + ?Sized>(
+    llm: &L,
+    messages: Vec<Message>,
+    return_json: bool,
+    native: bool,
+    options: &RequestOptions,
+) -> Value {
+    let mut messages: Vec<Message> = llm.get_system_role_strategy().apply(messages);
+    if return_json
+        && !native
+        && let Some(last) = messages.last_mut()
+    {
+        last.content = format!("{}\n\n{}", last.content, JSON_ONLY_INSTRUCTION).into();
+    }
+
+    let mut body: Value = llm.get_conversation_body(messages, native);
+    if let Some(extra_body) = llm.get_extra_body() {
+        merge_extra_body(&mut body, extra_body);
+    }
+    llm.apply_request_options(&mut body, options);
+
+    body
+}
+
+/// Posts a request body to the provider, retrying throttled requests, and refusals when the
+/// policy says so, according to the provider's `RetryPolicy`.
+///
+/// With a deadline, each attempt times out when the deadline passes, and a retry whose wait
+/// would pass the deadline is not scheduled.
+pub(crate) fn post_request<L: IsLLM + ?Sized>(
+    llm: &L,
+    body: &Value,
+    options: &RequestOptions,
+) -> Result<ResponseEnvelope, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let retry_policy: RetryPolicy = llm.get_retry_policy();
+    let mut attempt: u32 = 0;
+
+    loop {
+        let response: ResponseEnvelope = post_request_once(llm, body, options)?;
+        let retryable: bool =
+            response.status == 429 || (retry_policy.retry_refusals && is_refusal(llm, &response));
+        if !retryable || attempt >= retry_policy.max_retries {
+            return Ok(response);
+        }
+
+        attempt += 1;
+        let delay: Duration = retry_policy.delay_for(attempt, response.rate_limit().as_ref());
+        check_retry_fits(options.deadline, delay)?;
+        record_event(
+            llm.get_metrics_sink(),
+            options,
+            MetricEvent::RetryScheduled { attempt },
+        );
+        report(options.progress.as_ref(), || ProgressKind::RetryScheduled {
+            attempt,
+        });
+        std::thread::sleep(delay);
+    }
+}
+
+/// Asynchronously posts a request body to the provider, retrying throttled requests, and
+/// refusals when the policy says so, according to the provider's `RetryPolicy`.
+pub(crate) async fn async_post_request<L: IsLLM + Sync + ?Sized>(
+    llm: &L,
+    body: &Value,
+    options: &RequestOptions,
+) -> Result<ResponseEnvelope, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let retry_policy: RetryPolicy = llm.get_retry_policy();
+    let mut attempt: u32 = 0;
+
+    loop {
+        let response: ResponseEnvelope = async_post_request_once(llm, body, options).await?;
+        let retryable: bool =
+            response.status == 429 || (retry_policy.retry_refusals && is_refusal(llm, &response));
+        if !retryable || attempt >= retry_policy.max_retries {
+            return Ok(response);
+        }
+
+        attempt += 1;
+        let delay: Duration = retry_policy.delay_for(attempt, response.rate_limit().as_ref());
+        check_retry_fits(options.deadline, delay)?;
+        record_event(
+            llm.get_metrics_sink(),
+            options,
+            MetricEvent::RetryScheduled { attempt },
+        );
+        report(options.progress.as_ref(), || ProgressKind::RetryScheduled {
+            attempt,
+        });
+        Delay::new(delay).await;
+    }
+}
+
+/// Returns whether a successful response is a refusal, reported by the provider or found in
+/// its content by `detect_refusal`.
+fn is_refusal<L: IsLLM + ?Sized>(llm: &L, response: &ResponseEnvelope) -> bool {
+    if !(200..300).contains(&response.status) {
+        return false;
+    }
+
+    match llm.extract_response_content(&response.body) {
+        Ok(content) => detect_refusal(&content).is_some(),
+        Err(error) => matches!(
+            error.downcast_ref::<SecretaryError>(),
+            Some(SecretaryError::Refused { .. })
+        ),
+    }
+}
+
+/// Posts a request body to the provider once and returns the response.
+///
+/// A body over the provider's compression threshold is sent gzip-compressed. When the server
+/// rejects it with 415, it is sent again uncompressed and the provider stops compressing.
+fn post_request_once<L: IsLLM + ?Sized>(
+    llm: &L,
+    body: &Value,
+    options: &RequestOptions,
+) -> Result<ResponseEnvelope, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let payload: Vec<u8> = serde_json::to_vec(body)?;
+    if let Some(compressed) = compress_request(llm, options, &payload) {
+        let response: ResponseEnvelope = post_payload_once(llm, compressed, true, options)?;
+        if response.status != 415 {
+            return Ok(response);
+        }
+        llm.get_http_clients().reject_gzip();
+    }
+
+    post_payload_once(llm, payload, false, options)
+}
+
+/// Posts a serialized request body to the provider once and returns the response.
+fn post_payload_once<L: IsLLM + ?Sized>(
+    llm: &L,
+    payload: Vec<u8>,
+    gzipped: bool,
+    options: &RequestOptions,
+) -> Result<ResponseEnvelope, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let deadline: Option<Deadline> = options.deadline;
+    acquire_tenant_quota(llm, options)?;
+    let _permit: Option<QueuePermit> = acquire_queue_slot(llm, options)?;
+    let timeout: Option<Duration> = remaining_time(deadline)?;
+    let url: String = llm.get_chat_completion_request_url();
+    let mut request_headers: HeaderMap = llm.get_request_headers(&PreparedRequest {
+        method: "POST",
+        url: &url,
+        body: &payload,
+    })?;
+    apply_credentials(llm, &mut request_headers, options)?;
+    apply_request_id(&mut request_headers, options)?;
+    apply_content_encoding(&mut request_headers, gzipped);
+
+    let metrics_sink: &dyn MetricsSink = llm.get_metrics_sink();
+    record_event(metrics_sink, options, MetricEvent::RequestStarted);
+    let started: Instant = Instant::now();
+
+    let mut request_builder: reqwest::blocking::RequestBuilder = llm
+        .blocking_http_client()
+        .post(url)
+        .headers(request_headers)
+        .body(payload);
+    if let Some(timeout) = timeout {
+        request_builder = request_builder.timeout(timeout);
+    }
+
+    let request: reqwest::blocking::Response = match request_builder.send() {
+        Ok(request) => request,
+        Err(error) => {
+            record_request_completed(metrics_sink, options, None, started, None);
+            return Err(request_error(error, deadline));
+        }
+    };
+
+    let status: u16 = request.status().as_u16();
+    let headers: BTreeMap<String, String> = collect_headers(request.headers());
+    let response: Result<(String, usize), Box<dyn std::error::Error + Send + Sync + 'static>> =
+        match event_stream(llm, options, &headers) {
+            Some(stream) => read_event_stream(request, stream),
+            None => read_response_body(
+                request,
+                &output_limits(llm, options),
+                llm.get_decoding_policy(),
+                deadline,
+            ),
+        };
+    record_request_completed(
+        metrics_sink,
+        options,
+        Some(status),
+        started,
+        response.as_ref().ok().map(|(body, _)| body.as_str()),
+    );
+    let (body, repaired_sequences) = response?;
+
+    Ok(ResponseEnvelope {
+        status,
+        headers,
+        body,
+        repaired_sequences,
+    })
+}
+
+/// Asynchronously posts a request body to the provider once and returns the response.
+///
+/// Compresses the body like `post_request_once`.
+async fn async_post_request_once<L: IsLLM + Sync + ?Sized>(
+    llm: &L,
+    body: &Value,
+    options: &RequestOptions,
+) -> Result<ResponseEnvelope, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let payload: Vec<u8> = serde_json::to_vec(body)?;
+    if let Some(compressed) = compress_request(llm, options, &payload) {
+        let response: ResponseEnvelope =
+            async_post_payload_once(llm, compressed, true, options).await?;
+        if response.status != 415 {
+            return Ok(response);
+        }
+        llm.get_http_clients().reject_gzip();
+    }
+
+    async_post_payload_once(llm, payload, false, options).await
+}
+
+/// Asynchronously posts a serialized request body to the provider once and returns the
+/// response.
+async fn async_post_payload_once<L: IsLLM + Sync + ?Sized>(
+    llm: &L,
+    payload: Vec<u8>,
+    gzipped: bool,
+    options: &RequestOptions,
+) -> Result<ResponseEnvelope, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let deadline: Option<Deadline> = options.deadline;
+    async_acquire_tenant_quota(llm, options).await?;
+    let _permit: Option<QueuePermit> = async_acquire_queue_slot(llm, options).await?;
+    let timeout: Option<Duration> = remaining_time(deadline)?;
+    let url: String = llm.get_chat_completion_request_url();
+    let mut request_headers: HeaderMap = llm.get_request_headers(&PreparedRequest {
+        method: "POST",
+        url: &url,
+        body: &payload,
+    })?;
+    apply_credentials(llm, &mut request_headers, options)?;
+    apply_request_id(&mut request_headers, options)?;
+    apply_content_encoding(&mut request_headers, gzipped);
+
+    let metrics_sink: &dyn MetricsSink = llm.get_metrics_sink();
+    record_event(metrics_sink, options, MetricEvent::RequestStarted);
+    let started: Instant = Instant::now();
+
+    let mut request_builder: reqwest::RequestBuilder = llm
+        .http_client()
+        .post(url)
+        .headers(request_headers)
+        .body(payload);
+    if let Some(timeout) = timeout {
+        request_builder = request_builder.timeout(timeout);
+    }
+
+    let request: Response = match request_builder.send().await {
+        Ok(request) => request,
+        Err(error) => {
+            record_request_completed(metrics_sink, options, None, started, None);
+            return Err(request_error(error, deadline));
+        }
+    };
+
+    let status: u16 = request.status().as_u16();
+    let headers: BTreeMap<String, String> = collect_headers(request.headers());
+    let response: Result<(String, usize), Box<dyn std::error::Error + Send + Sync + 'static>> =
+        match event_stream(llm, options, &headers) {
+            Some(stream) => async_read_event_stream(request, stream, deadline).await,
+            None => {
+                async_read_response_body(
+                    request,
+                    &output_limits(llm, options),
+                    llm.get_decoding_policy(),
+                    deadline,
+                )
+                .await
+            }
+        };
+    record_request_completed(
+        metrics_sink,
+        options,
+        Some(status),
+        started,
+        response.as_ref().ok().map(|(body, _)| body.as_str()),
+    );
+    let (body, repaired_sequences) = response?;
+
+    Ok(ResponseEnvelope {
+        status,
+        headers,
+        body,
+        repaired_sequences,
+    })
+}
+
+/// Replaces the `Authorization` header with the call's `Credentials`, if it has any, and
+/// records the tenant and key the request is sent with.
+fn apply_credentials<L: IsLLM + ?Sized>(
+    llm: &L,
+    headers: &mut HeaderMap,
+    options: &RequestOptions,
+) -> Result<(), SecretaryError> {
+    let Some(credentials) = &options.credentials else {
+        return Ok(());
+    };
+
+    let authorization: String = llm
+        .get_authorization_for_key(credentials.api_key())
+        .ok_or_else(|| {
+            SecretaryError::BuildRequestError(
+                "the provider does not authenticate with an API key".to_string(),
+            )
+        })?;
+    let authorization: HeaderValue = HeaderValue::from_str(&authorization)
+        .map_err(|error| SecretaryError::BuildRequestError(error.to_string()))?;
+    headers.insert(AUTHORIZATION, authorization);
+
+    record_event(
+        llm.get_metrics_sink(),
+        options,
+        MetricEvent::CredentialsOverridden {
+            key_id: credentials.key_id(),
+            tenant: credentials.tenant().map(str::to_string),
+        },
+    );
+
+    Ok(())
+}
+
+/// Sends the request ID of the options in the `X-Client-Request-Id` header, see the
+/// `correlation` module.
+fn apply_request_id(
+    headers: &mut HeaderMap,
+    options: &RequestOptions,
+) -> Result<(), SecretaryError> {
+    let Some(request_id) = &options.request_id else {
+        return Ok(());
+    };
+
+    let value: HeaderValue = HeaderValue::from_str(request_id).map_err(|_| {
+        SecretaryError::BuildRequestError(format!(
+            "the request ID {:?} is not a valid header value",
+            request_id
+        ))
+    })?;
+    headers.insert(CLIENT_REQUEST_ID_HEADER, value);
+
+    Ok(())
+}
+
+/// Reads a response body, reading no further than the `max_response_bytes` of the output
+/// limits, and decodes it under the provider's `DecodingPolicy`.
+///
+/// Returns the body with the number of sequences repaired while decoding it.
+fn read_response_body(
+    response: reqwest::blocking::Response,
+    limits: &OutputLimits,
+    decoding: DecodingPolicy,
+    deadline: Option<Deadline>,
+) -> Result<(String, usize), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let Some(max) = limits.max_response_bytes else {
+        let body = response
+            .bytes()
+            .map_err(|error| request_error(error, deadline))?;
+        return Ok(decoding.decode_body(&body)?);
+    };
+    limits.check_response_bytes(content_length(response.content_length()))?;
+
+    let mut body: Vec<u8> = Vec::new();
+    response
+        .take(u64::try_from(max).unwrap_or(u64::MAX).saturating_add(1))
+        .read_to_end(&mut body)?;
+    limits.check_response_bytes(body.len())?;
+
+    Ok(decoding.decode_body(&body)?)
+}
+
+/// Asynchronously reads a response body, reading no further than the `max_response_bytes`
+/// of the output limits, and decodes it like `read_response_body`.
+async fn async_read_response_body(
+    mut response: Response,
+    limits: &OutputLimits,
+    decoding: DecodingPolicy,
+    deadline: Option<Deadline>,
+) -> Result<(String, usize), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    if limits.max_response_bytes.is_none() {
+        let body = response
+            .bytes()
+            .await
+            .map_err(|error| request_error(error, deadline))?;
+        return Ok(decoding.decode_body(&body)?);
+    }
+    limits.check_response_bytes(content_length(response.content_length()))?;
+
+    let mut body: Vec<u8> = Vec::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|error| request_error(error, deadline))?
+    {
+        body.extend_from_slice(&chunk);
+        limits.check_response_bytes(body.len())?;
+    }
+
+    Ok(decoding.decode_body(&body)?)
+}
+
+/// Returns the reader of a streamed response, if the call streams and the provider answered
+/// with an event stream, see the `streaming` module.
+fn event_stream<L: IsLLM + ?Sized>(
+    llm: &L,
+    options: &RequestOptions,
+    headers: &BTreeMap<String, String>,
+) -> Option<EventStream> {
+    let streaming = options.streaming.as_ref()?;
+    if !is_event_stream(headers) {
+        return None;
+    }
+
+    Some(EventStream::new(
+        streaming,
+        llm.get_leniency().clone(),
+        output_limits(llm, options),
+        llm.get_decoding_policy(),
+    ))
+}
+
+/// Reads a streamed response as it arrives and returns the assembled body, with the number
+/// of sequences repaired while decoding it.
+///
+/// Returns as soon as the stream fails its checks, dropping the response and its connection
+/// without reading the rest.
+fn read_event_stream(
+    mut response: reqwest::blocking::Response,
+    mut stream: EventStream,
+) -> Result<(String, usize), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let mut buffer: [u8; 8192] = [0; 8192];
+    loop {
+        let read: usize = response.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        stream.feed(&buffer[..read])?;
+    }
+
+    Ok(stream.finish()?)
+}
+
+/// Asynchronously reads a streamed response as it arrives, like `read_event_stream`.
+async fn async_read_event_stream(
+    mut response: Response,
+    mut stream: EventStream,
+    deadline: Option<Deadline>,
+) -> Result<(String, usize), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|error| request_error(error, deadline))?
+    {
+        stream.feed(&chunk)?;
+    }
+
+    Ok(stream.finish()?)
+}
+
+/// Converts a `Content-Length` to a byte count, `0` when the header is missing.
+fn content_length(content_length: Option<u64>) -> usize {
+    content_length
+        .map(|length| usize::try_from(length).unwrap_or(usize::MAX))
+        .unwrap_or_default()
+}
+
+/// Waits for the call's tenant to be admitted by the provider's `QuotaPartitioner`, if it
+/// has one, and reports any wait to the metrics sink.
+///
+/// Returns `SecretaryError::TenantQuotaExceeded` if the tenant reached its hard cap, or
+/// `SecretaryError::DeadlineExceeded` if the deadline passes while waiting.
+fn acquire_tenant_quota<L: IsLLM + ?Sized>(
+    llm: &L,
+    options: &RequestOptions,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let Some(partitioner) = llm.get_quota_partitioner() else {
+        return Ok(());
+    };
+    let tenant: &str = call_tenant(options);
+    let until: Option<Instant> = options
+        .deadline
+        .map(|deadline| Instant::now() + deadline.remaining());
+    let started: Instant = Instant::now();
+
+    let admitted = partitioner.acquire_until(tenant, until);
+    report_tenant_quota(llm.get_metrics_sink(), options, tenant, started, admitted)
+}
+
+/// Asynchronously waits for the call's tenant to be admitted by the provider's
+/// `QuotaPartitioner`, if it has one, and reports any wait to the metrics sink.
+async fn async_acquire_tenant_quota<L: IsLLM + Sync + ?Sized>(
+    llm: &L,
+    options: &RequestOptions,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let Some(partitioner) = llm.get_quota_partitioner() else {
+        return Ok(());
+    };
+    let tenant: &str = call_tenant(options);
+    let until: Option<Instant> = options
+        .deadline
+        .map(|deadline| Instant::now() + deadline.remaining());
+    let started: Instant = Instant::now();
+
+    let admitted = partitioner.async_acquire_until(tenant, until).await;
+    report_tenant_quota(llm.get_metrics_sink(), options, tenant, started, admitted)
+}
+
+/// Returns the tenant a call's requests count against: the one set in its options, else the
+/// one of its credentials, else `DEFAULT_TENANT`.
+fn call_tenant(options: &RequestOptions) -> &str {
+    options
+        .tenant
+        .as_deref()
+        .or_else(|| {
+            options
+                .credentials
+                .as_ref()
+                .and_then(|credentials| credentials.tenant())
+        })
+        .unwrap_or(DEFAULT_TENANT)
+}
+
+/// Reports the outcome of waiting for a tenant's quota and converts it to the result of the
+/// request.
+fn report_tenant_quota(
+    metrics_sink: &dyn MetricsSink,
+    options: &RequestOptions,
+    tenant: &str,
+    started: Instant,
+    admitted: Result<Option<Duration>, SecretaryError>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    match admitted {
+        Ok(Some(waited)) => {
+            if !waited.is_zero() {
+                record_event(
+                    metrics_sink,
+                    options,
+                    MetricEvent::TenantThrottled {
+                        tenant: tenant.to_string(),
+                        waited,
+                        admitted: true,
+                    },
+                );
+            }
+            Ok(())
+        }
+        Ok(None) => {
+            record_event(
+                metrics_sink,
+                options,
+                MetricEvent::TenantThrottled {
+                    tenant: tenant.to_string(),
+                    waited: started.elapsed(),
+                    admitted: false,
+                },
+            );
+            Err(Box::new(deadline_exceeded()))
+        }
+        Err(error) => {
+            if let SecretaryError::TenantQuotaExceeded { max_requests, .. } = &error {
+                record_event(
+                    metrics_sink,
+                    options,
+                    MetricEvent::TenantQuotaExceeded {
+                        tenant: tenant.to_string(),
+                        max_requests: *max_requests,
+                    },
+                );
+            }
+            Err(Box::new(error))
+        }
+    }
+}
+
+/// Waits for a slot in the provider's `RequestQueue`, if it has one, and reports the wait to
+/// the metrics sink.
+///
+/// Returns `SecretaryError::DeadlineExceeded` if the deadline passes while waiting.
+fn acquire_queue_slot<L: IsLLM + ?Sized>(
+    llm: &L,
+    options: &RequestOptions,
+) -> Result<Option<QueuePermit>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let Some(queue) = llm.get_request_queue() else {
+        return Ok(None);
+    };
+    let priority: Priority = options.priority;
+    let metrics_sink: &dyn MetricsSink = llm.get_metrics_sink();
+    record_event(
+        metrics_sink,
+        options,
+        MetricEvent::RequestQueued {
+            priority,
+            depth: queue.depth(priority) + 1,
+        },
+    );
+    let started: Instant = Instant::now();
+
+    let permit: Option<QueuePermit> = match options.deadline {
+        Some(deadline) => queue.acquire_timeout(priority, deadline.remaining()),
+        None => Some(queue.acquire(priority)),
+    };
+    record_event(
+        metrics_sink,
+        options,
+        MetricEvent::RequestDequeued {
+            priority,
+            depth: queue.depth(priority),
+            waited: started.elapsed(),
+        },
+    );
+
+    match permit {
+        Some(permit) => Ok(Some(permit)),
+        None => Err(Box::new(deadline_exceeded())),
+    }
+}
+
+/// Asynchronously waits for a slot in the provider's `RequestQueue`, if it has one, and
+/// reports the wait to the metrics sink.
+async fn async_acquire_queue_slot<L: IsLLM + Sync + ?Sized>(
+    llm: &L,
+    options: &RequestOptions,
+) -> Result<Option<QueuePermit>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let Some(queue) = llm.get_request_queue() else {
+        return Ok(None);
+    };
+    let priority: Priority = options.priority;
+    let metrics_sink: &dyn MetricsSink = llm.get_metrics_sink();
+    record_event(
+        metrics_sink,
+        options,
+        MetricEvent::RequestQueued {
+            priority,
+            depth: queue.depth(priority) + 1,
+        },
+    );
+    let started: Instant = Instant::now();
+
+    let permit: Option<QueuePermit> = match options.deadline {
+        Some(deadline) => {
+            let timer = Delay::new(deadline.remaining());
+            match future::select(queue.async_acquire(priority), timer).await {
+                future::Either::Left((permit, _)) => Some(permit),
+                future::Either::Right(((), _)) => None,
+            }
+        }
+        None => Some(queue.async_acquire(priority).await),
+    };
+    record_event(
+        metrics_sink,
+        options,
+        MetricEvent::RequestDequeued {
+            priority,
+            depth: queue.depth(priority),
+            waited: started.elapsed(),
+        },
+    );
+
+    match permit {
+        Some(permit) => Ok(Some(permit)),
+        None => Err(Box::new(deadline_exceeded())),
+    }
+}
+
+/// Returns the time left before the deadline to use as a request timeout, or
+/// `SecretaryError::DeadlineExceeded` if it has passed.
+fn remaining_time(
+    deadline: Option<Deadline>,
+) -> Result<Option<Duration>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    match deadline {
+        Some(deadline) if deadline.is_expired() => Err(Box::new(deadline_exceeded())),
+        Some(deadline) => Ok(Some(deadline.remaining())),
+        None => Ok(None),
+    }
+}
+
+/// Returns `SecretaryError::DeadlineExceeded` if waiting `delay` before a retry would pass the
+/// deadline.
+fn check_retry_fits(
+    deadline: Option<Deadline>,
+    delay: Duration,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    match deadline {
+        Some(deadline) if delay >= deadline.remaining() => Err(Box::new(deadline_exceeded())),
+        _ => Ok(()),
+    }
+}
+
+/// Converts a request error, reporting timeouts caused by the deadline as
+/// `SecretaryError::DeadlineExceeded`.
+fn request_error(
+    error: reqwest::Error,
+    deadline: Option<Deadline>,
+) -> Box<dyn std::error::Error + Send + Sync + 'static> {
+    match deadline {
+        Some(deadline) if error.is_timeout() && deadline.is_expired() => {
+            Box::new(deadline_exceeded())
+        }
+        _ => error.into(),
+    }
+}
+
+/// The error for a single request that did not complete before the deadline.
+fn deadline_exceeded() -> SecretaryError {
+    SecretaryError::DeadlineExceeded {
+        incomplete_fields: Vec::new(),
+    }
+}
+
+/// Copies response headers into a map, leaving out values that are not valid text.
+fn collect_headers(headers: &HeaderMap) -> BTreeMap<String, String> {
+    headers
+        .iter()
+        .filter_map(|(name, value)| {
+            value
+                .to_str()
+                .ok()
+                .map(|value| (name.as_str().to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// Records an event with the request ID of the options, when they have one.
+fn record_event(metrics_sink: &dyn MetricsSink, options: &RequestOptions, event: MetricEvent) {
+    match &options.request_id {
+        Some(request_id) => metrics_sink.record_with_request_id(event, request_id),
+        None => metrics_sink.record(event),
+    }
+}
+
+/// Records the completion of a request, with the token usage reported in its response.
+fn record_request_completed(
+    metrics_sink: &dyn MetricsSink,
+    options: &RequestOptions,
+    status: Option<u16>,
+    started: Instant,
+    response: Option<&str>,
+) {
+    record_event(
+        metrics_sink,
+        options,
+        MetricEvent::RequestCompleted {
+            status,
+            latency: started.elapsed(),
+            tokens: response.and_then(extract_total_tokens_from_llm_response),
+        },
+    );
+}
+
+/// Returns the gzip-compressed request body when the provider compresses bodies of its size,
+/// recording the sizes before and after.
+fn compress_request<L: IsLLM + ?Sized>(
+    llm: &L,
+    options: &RequestOptions,
+    payload: &[u8],
+) -> Option<Vec<u8>> {
+    let compressed: Vec<u8> = llm.get_http_clients().encode_request(payload)?;
+    record_event(
+        llm.get_metrics_sink(),
+        options,
+        MetricEvent::RequestCompressed {
+            original_bytes: payload.len(),
+            compressed_bytes: compressed.len(),
+        },
+    );
+
+    Some(compressed)
+}
+
+/// Marks a gzip-compressed request body with `Content-Encoding: gzip`.
+fn apply_content_encoding(headers: &mut HeaderMap, gzipped: bool) {
+    if gzipped {
+        headers.insert(CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+    }
+}
+
+/// Records a parse failure. Without a known field count, every top-level field counts as failed.
+fn record_parse_failed<T: Task>(
+    metrics_sink: &dyn MetricsSink,
+    mode: MetricMode,
+    field_count_failed: Option<usize>,
+) {
+    metrics_sink.record(MetricEvent::ParseFailed {
+        mode,
+        field_count_failed: field_count_failed.unwrap_or_else(|| T::field_descriptors().len()),
+    });
 }
@@ -0,0 +1,226 @@
+//! Trimming of keys the model added to the JSON of a Task.
+//!
+//! Models like to add a chatty `"explanation"` or `"confidence"` key next to the fields they
+//! were asked for. Serde ignores such keys, unless the Task has
+//! `#[serde(deny_unknown_fields)]`, in which case the whole extraction fails although every
+//! field is there. Under `UnknownKeys::Trim`, the keys that are not fields of the Task are
+//! removed from the parsed JSON before it is deserialized, using the field metadata from
+//! `Task::field_descriptors()`:
+//!
+//! - nested Task fields are trimmed too, every element of a `Vec` of Tasks and every value of
+//!   a map of Tasks
+//! - other fields are left as they are, so that the arbitrary keys of a
+//!   `HashMap<String, String>` field survive
+//!
+//! Trimming is off by default. Enable it on a provider with `with_unknown_keys`, or for one
+//! call with `RequestOptions::with_unknown_keys`, which takes precedence. It applies to
+//! `generate_data`, `generate_data_adaptive` and `generate_data_consistent` and their async
+//! versions, and `generate_data_adaptive` reports every removed key and its value as a
+//! `GenerationWarning::UnknownKeyTrimmed` in `metadata.warnings`.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use std::collections::HashMap;
+//!
+//! use secretary::Task;
+//! use secretary::trimming::{TrimmedKey, UnknownKeys, trim_unknown_keys};
+//! use serde::{Deserialize, Serialize};
+//! use serde_json::json;
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! #[serde(deny_unknown_fields)]
+//! struct Offer {
+//!     #[task(instruction = "Extract the price")]
+//!     pub price: f64,
+//! }
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! #[serde(deny_unknown_fields)]
+//! struct Listing {
+//!     #[task(instruction = "Extract the listing title")]
+//!     pub title: String,
+//!     #[task(instruction = "Extract the attributes as key-value pairs")]
+//!     pub attributes: HashMap<String, String>,
+//!     #[task(instruction = "Extract every offer")]
+//!     pub offers: Vec<Offer>,
+//! }
+//!
+//! let mut value = json!({
+//!     "title": "Loft",
+//!     "explanation": "The title is in the first line",
+//!     "attributes": {"floor": "3", "view": "park"},
+//!     "offers": [{"price": 1200.0}, {"price": 1100.0, "currency": "EUR"}]
+//! });
+//! assert!(serde_json::from_value::<Listing>(value.clone()).is_err());
+//!
+//! let trimmed = trim_unknown_keys(&Listing::field_descriptors(), &mut value);
+//! assert_eq!(
+//!     trimmed,
+//!     vec![
+//!         TrimmedKey {
+//!             path: "explanation".to_string(),
+//!             value: json!("The title is in the first line"),
+//!         },
+//!         TrimmedKey {
+//!             path: "offers[1].currency".to_string(),
+//!             value: json!("EUR"),
+//!         },
+//!     ]
+//! );
+//! let listing: Listing = serde_json::from_value(value).unwrap();
+//! assert_eq!(listing.attributes["view"], "park");
+//!
+//! // The policy works on the JSON text the model answered with
+//! let content = r#"{"title": "Loft", "attributes": {}, "offers": [], "note": "ok"}"#;
+//! let (content, trimmed) =
+//!     UnknownKeys::Trim.trim_content(&Listing::field_descriptors(), content.to_string());
+//! assert_eq!(trimmed[0].path, "note");
+//! assert!(serde_json::from_str::<Listing>(&content).is_ok());
+//! ```
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::schema::{FieldDescriptor, FieldKind};
+
+/// What happens to keys of the model's JSON that are not fields of the Task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum UnknownKeys {
+    /// Leaves them to serde, which ignores them unless the Task denies unknown fields.
+    #[default]
+    Keep,
+    /// Removes them before deserializing.
+    Trim,
+}
+
+/// A key removed from the model's JSON under `UnknownKeys::Trim`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrimmedKey {
+    /// The path of the key, e.g. `offers[1].currency`.
+    pub path: String,
+    /// The value the model gave it.
+    pub value: Value,
+}
+
+impl UnknownKeys {
+    /// Trims the JSON text the model answered with under this policy.
+    ///
+    /// Content that is not JSON is returned as it is, to fail parsing later.
+    ///
+    /// # Arguments
+    ///
+    /// * `fields` - The descriptors of the fields of the Task
+    /// * `content` - The JSON returned by the model
+    ///
+    /// # Returns
+    ///
+    /// The content without its unknown keys, and the keys that were removed
+    pub fn trim_content(
+        self,
+        fields: &[FieldDescriptor],
+        content: String,
+    ) -> (String, Vec<TrimmedKey>) {
+        if self == UnknownKeys::Keep {
+            return (content, Vec::new());
+        }
+
+        let mut value: Value = match serde_json::from_str(&content) {
+            Ok(value) => value,
+            Err(_) => return (content, Vec::new()),
+        };
+        let trimmed: Vec<TrimmedKey> = trim_unknown_keys(fields, &mut value);
+        if trimmed.is_empty() {
+            return (content, trimmed);
+        }
+
+        (value.to_string(), trimmed)
+    }
+}
+
+/// Removes the keys of a Task's JSON that are not among its fields, recursively for nested
+/// Task fields.
+///
+/// A value that is not an object, such as the array of a tuple struct, is left as it is, as
+/// are the values of fields that are not nested Tasks.
+///
+/// # Arguments
+///
+/// * `fields` - The descriptors of the fields of the Task
+/// * `value` - The JSON of the Task
+///
+/// # Returns
+///
+/// The keys that were removed
+pub fn trim_unknown_keys(fields: &[FieldDescriptor], value: &mut Value) -> Vec<TrimmedKey> {
+    let mut trimmed: Vec<TrimmedKey> = Vec::new();
+    trim_at(fields, value, "", &mut trimmed);
+    trimmed
+}
+
+fn trim_at(
+    fields: &[FieldDescriptor],
+    value: &mut Value,
+    path: &str,
+    trimmed: &mut Vec<TrimmedKey>,
+) {
+    // Without descriptors, or for a newtype whose only field has no name, the keys cannot be
+    // told apart from the fields
+    if fields.is_empty() || fields.iter().any(|field| field.name.is_empty()) {
+        return;
+    }
+    let Some(map) = value.as_object_mut() else {
+        return;
+    };
+
+    let unknown: Vec<String> = map
+        .keys()
+        .filter(|key| !fields.iter().any(|field| &field.name == *key))
+        .cloned()
+        .collect();
+    for key in unknown {
+        if let Some(value) = map.remove(&key) {
+            trimmed.push(TrimmedKey {
+                path: child_path(path, &key),
+                value,
+            });
+        }
+    }
+
+    for field in fields {
+        let Some(field_value) = map.get_mut(&field.name) else {
+            continue;
+        };
+        let field_path: String = child_path(path, &field.name);
+        match field.kind {
+            FieldKind::Task | FieldKind::OptionTask => {
+                trim_at(&field.children, field_value, &field_path, trimmed);
+            }
+            FieldKind::VecTask => {
+                if let Some(items) = field_value.as_array_mut() {
+                    for (index, item) in items.iter_mut().enumerate() {
+                        let item_path: String = format!("{}[{}]", field_path, index);
+                        trim_at(&field.children, item, &item_path, trimmed);
+                    }
+                }
+            }
+            FieldKind::HashMapTask | FieldKind::BTreeMapTask => {
+                if let Some(entries) = field_value.as_object_mut() {
+                    for (key, entry) in entries.iter_mut() {
+                        let entry_path: String = format!("{}.{}", field_path, key);
+                        trim_at(&field.children, entry, &entry_path, trimmed);
+                    }
+                }
+            }
+            FieldKind::Normal => {}
+        }
+    }
+}
+
+fn child_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
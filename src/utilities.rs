@@ -1,6 +1,21 @@
-use serde_json::Value;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use crate::SecretaryError;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::{
+    SecretaryError,
+    assembly::smart_parse_value,
+    casing::to_native_keys,
+    leniency::LeniencyProfile,
+    normalization::normalize_strings,
+    refusal::detect_refusal,
+    schema::{FieldDescriptor, FieldKind, JsonType},
+    traits::Task,
+};
 
 /// Removes thinking blocks from LLM responses, particularly useful for reasoning models.
 ///
@@ -17,6 +32,10 @@ use crate::SecretaryError;
 /// A cleaned string with all content between `<think>` and `</think>` tags removed
 ///
 pub fn cleanup_thinking_blocks(content: String) -> String {
+    if !needs_thinking_cleanup(&content) {
+        return content;
+    }
+
     let mut is_thinking: bool = false;
     let mut result: String = String::new();
     let mut first_line = true;
@@ -44,14 +63,20 @@ pub fn cleanup_thinking_blocks(content: String) -> String {
     result
 }
 
+/// Returns whether `cleanup_thinking_blocks` changes a text: whether it may have a line
+/// opening or closing a thinking block, or line endings the cleanup rewrites. Other texts are
+/// returned as they are, without being copied.
+pub(crate) fn needs_thinking_cleanup(content: &str) -> bool {
+    content.contains("think>") || content.contains('\r') || content.ends_with('\n')
+}
+
 // Helper function to extract content from <result></result> tags
 pub fn extract_result_content(content: &str) -> String {
-    if let Some(start) = content.find("<result>") {
-        if let Some(end) = content.find("</result>") {
-            if start < end {
-                return content[start + 8..end].trim().to_string();
-            }
-        }
+    if let Some(start) = content.find("<result>")
+        && let Some(end) = content.find("</result>")
+        && start < end
+    {
+        return content[start + 8..end].trim().to_string();
     }
     content.trim().to_string()
 }
@@ -70,17 +95,721 @@ pub fn extract_result_content(content: &str) -> String {
 ///
 /// A formatted string with instructions as bullet points, or empty string if no instructions
 ///
-pub fn format_additional_instructions(additional_instructions: &Vec<String>) -> String {
+pub fn format_additional_instructions(additional_instructions: &[String]) -> String {
     let mut prompt: String = String::new();
-    // Add additional instructions
-    if !additional_instructions.is_empty() {
-        prompt.push_str("\nAdditional instructions:\n");
-        for instruction in additional_instructions {
-            prompt.push_str(&format!("- {}\n", instruction));
+    write_additional_instructions(&mut prompt, additional_instructions);
+
+    prompt
+}
+
+/// Appends the additional instructions to a prompt, formatted like
+/// `format_additional_instructions`, without allocating a string of their own.
+pub(crate) fn write_additional_instructions(
+    prompt: &mut String,
+    additional_instructions: &[String],
+) {
+    if additional_instructions.is_empty() {
+        return;
+    }
+
+    prompt.reserve(
+        "\nAdditional instructions:\n".len()
+            + additional_instructions
+                .iter()
+                .map(|instruction| instruction.len() + "- \n".len())
+                .sum::<usize>(),
+    );
+    prompt.push_str("\nAdditional instructions:\n");
+    for instruction in additional_instructions {
+        let _ = writeln!(prompt, "- {}", instruction);
+    }
+}
+
+/// Substitutes the `{placeholder}`s of an instruction template.
+///
+/// A placeholder is a name of ASCII letters, digits and underscores in braces, not starting
+/// with a digit. `{{` writes a literal `{`, so `{{name}}` writes `{name}`. Any other brace is
+/// kept as it is, which leaves JSON in a prompt unchanged.
+///
+/// # Arguments
+///
+/// * `template` - The text to render
+/// * `vars` - The value of each placeholder
+///
+/// # Returns
+///
+/// The rendered text
+///
+/// # Errors
+///
+/// Returns `SecretaryError::MissingTemplateVar` for the first placeholder without a value.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::collections::HashMap;
+/// use secretary::SecretaryError;
+/// use secretary::utilities::render_template;
+///
+/// let vars = HashMap::from([("tenant_currency".to_string(), "EUR".to_string())]);
+///
+/// assert_eq!(
+///     render_template("Amounts are in {tenant_currency}", &vars).unwrap(),
+///     "Amounts are in EUR"
+/// );
+///
+/// // Escaped braces and JSON are kept
+/// assert_eq!(
+///     render_template("Write {{tenant_currency}} as {{ or }}", &vars).unwrap(),
+///     "Write {tenant_currency} as { or }}"
+/// );
+/// assert_eq!(
+///     render_template(r#"{"price": {"amount": 0.0}}"#, &vars).unwrap(),
+///     r#"{"price": {"amount": 0.0}}"#
+/// );
+///
+/// assert!(matches!(
+///     render_template("Dates are relative to {today}", &vars),
+///     Err(SecretaryError::MissingTemplateVar { name }) if name == "today"
+/// ));
+/// ```
+pub fn render_template(
+    template: &str,
+    vars: &HashMap<String, String>,
+) -> Result<String, SecretaryError> {
+    let mut rendered: String = String::with_capacity(template.len());
+    let mut rest: &str = template;
+
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        if let Some(escaped) = rest.strip_prefix("{{") {
+            match placeholder_name(escaped) {
+                Some(name) if escaped[name.len()..].starts_with("}}") => {
+                    rendered.push('{');
+                    rendered.push_str(name);
+                    rendered.push('}');
+                    rest = &escaped[name.len() + 2..];
+                }
+                _ => {
+                    rendered.push('{');
+                    rest = escaped;
+                }
+            }
+            continue;
+        }
+
+        match placeholder_name(&rest[1..]) {
+            Some(name) if rest[1 + name.len()..].starts_with('}') => {
+                let value: &String =
+                    vars.get(name)
+                        .ok_or_else(|| SecretaryError::MissingTemplateVar {
+                            name: name.to_string(),
+                        })?;
+                rendered.push_str(value);
+                rest = &rest[name.len() + 2..];
+            }
+            _ => {
+                rendered.push('{');
+                rest = &rest[1..];
+            }
         }
     }
+    rendered.push_str(rest);
 
-    prompt
+    Ok(rendered)
+}
+
+/// Returns the placeholder name at the start of `text`, if any.
+fn placeholder_name(text: &str) -> Option<&str> {
+    let end: usize = text
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(text.len());
+    let name: &str = &text[..end];
+
+    match name.chars().next() {
+        Some(first) if !first.is_ascii_digit() => Some(name),
+        _ => None,
+    }
+}
+
+/// Returns the placeholders every template can use: `today`, the UTC date of `now` as
+/// `YYYY-MM-DD`, and `now_utc`, its UTC time as `YYYY-MM-DDTHH:MM:SSZ`.
+///
+/// # Arguments
+///
+/// * `now` - The time to render
+///
+/// # Examples
+///
+/// ```rust
+/// use std::time::{Duration, UNIX_EPOCH};
+/// use secretary::utilities::builtin_template_vars;
+///
+/// let vars = builtin_template_vars(UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+/// assert_eq!(vars["today"], "2023-11-14");
+/// assert_eq!(vars["now_utc"], "2023-11-14T22:13:20Z");
+/// ```
+pub fn builtin_template_vars(now: SystemTime) -> HashMap<String, String> {
+    let seconds: u64 = now
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0);
+    let (year, month, day) = civil_date(seconds / 86_400);
+    let today: String = format!("{:04}-{:02}-{:02}", year, month, day);
+    let time_of_day: u64 = seconds % 86_400;
+    let now_utc: String = format!(
+        "{}T{:02}:{:02}:{:02}Z",
+        today,
+        time_of_day / 3600,
+        time_of_day / 60 % 60,
+        time_of_day % 60
+    );
+
+    HashMap::from([
+        ("today".to_string(), today),
+        ("now_utc".to_string(), now_utc),
+    ])
+}
+
+/// Converts days since 1970-01-01 to a proleptic Gregorian `(year, month, day)`.
+fn civil_date(days: u64) -> (u64, u64, u64) {
+    // Counted from 0000-03-01, so that leap days end each 400, 100 and 4 year cycle
+    let days: u64 = days + 719_468;
+    let era: u64 = days / 146_097;
+    let day_of_era: u64 = days % 146_097;
+    let year_of_era: u64 =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year: u64 = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index: u64 = (5 * day_of_year + 2) / 153;
+    let day: u64 = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month: u64 = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year: u64 = year_of_era + era * 400 + u64::from(month <= 2);
+
+    (year, month, day)
+}
+
+/// Formats field descriptors into a compact field specification.
+///
+/// Each line holds a dotted field path and its JSON type, without the field instructions.
+/// Nested Task fields are expanded into their own fields, using `[]` for collection elements.
+/// This is the prompt used when the full instructions do not fit the context window.
+///
+/// # Arguments
+///
+/// * `fields` - The field descriptors to format
+/// * `prefix` - The path prefix for nested fields, empty at the top level
+///
+/// # Returns
+///
+/// One `path: type` line per leaf field
+pub fn format_compact_field_specification(fields: &[FieldDescriptor], prefix: &str) -> String {
+    let mut specification: String = String::new();
+
+    for field in fields {
+        let path: String = if prefix.is_empty() {
+            field.key()
+        } else {
+            format!("{}.{}", prefix, field.key())
+        };
+
+        let nested_prefix: String = match field.kind {
+            FieldKind::Normal => {
+                let nullable: &str = if field.optional { " or null" } else { "" };
+                specification.push_str(&format!("{}: {}{}\n", path, field.json_type, nullable));
+                continue;
+            }
+            FieldKind::Task | FieldKind::OptionTask => path,
+            FieldKind::VecTask | FieldKind::HashMapTask | FieldKind::BTreeMapTask => {
+                format!("{}[]", path)
+            }
+        };
+
+        specification.push_str(&format_compact_field_specification(
+            &field.children,
+            &nested_prefix,
+        ));
+    }
+
+    specification
+}
+
+/// Parses the text a model returned for a single field into a JSON value.
+///
+/// Used by distributed generation, where every field is requested separately and the model
+/// answers in plain text. Null-like answers become `null`, valid JSON is used as-is (a single
+/// key object named after the field is unwrapped), and formatted numbers such as `$1,200` or
+/// `15%` are parsed. Anything else is kept as a string. This is `assembly::smart_parse_value`.
+///
+/// # Arguments
+///
+/// * `content` - The text returned for the field
+/// * `field_path` - The dotted path of the field, e.g. `address.city`
+///
+/// # Returns
+///
+/// The parsed value
+pub fn parse_field_value(content: &str, field_path: &str) -> Value {
+    smart_parse_value(content, field_path)
+}
+
+/// Parses the text a model returned for a list field into a JSON array.
+///
+/// Distributed prompts ask for one `<item></item>` tag per value, but models also answer with
+/// a JSON array, a bulleted or numbered list, one value per line, or values separated by
+/// commas or semicolons. All of these become an array. Elements are kept as strings for
+/// `JsonType::String` items, so `2024` stays `"2024"` in a `Vec<String>`, and are parsed like
+/// `parse_field_value` otherwise. Null-like answers become `null` and empty items are dropped.
+///
+/// # Arguments
+///
+/// * `content` - The text returned for the field, without its `<result>` tags
+/// * `item_type` - The JSON shape of the elements
+/// * `unique` - Whether repeated elements are dropped, as for `HashSet` and `BTreeSet`
+///
+/// # Returns
+///
+/// A JSON array in the order the elements were given, or `null`
+///
+/// # Examples
+///
+/// ```rust
+/// use secretary::schema::JsonType;
+/// use secretary::utilities::parse_list_value;
+/// use serde_json::{Value, json};
+///
+/// // (model output, element type, unique, parsed value)
+/// let cases: Vec<(&str, JsonType, bool, Value)> = vec![
+///     // Item tags, on one line or several, with stray whitespace
+///     ("<item>rust</item><item>go</item>", JsonType::String, false, json!(["rust", "go"])),
+///     ("<item> rust </item>\n<item>go</item>\n", JsonType::String, false, json!(["rust", "go"])),
+///     ("<item>a, b</item><item></item>", JsonType::String, false, json!(["a, b"])),
+///     ("<item>3</item><item>$1,200</item>", JsonType::Number, false, json!([3, 1200])),
+///     // JSON arrays, also under a single key
+///     (r#"["rust", "go"]"#, JsonType::String, false, json!(["rust", "go"])),
+///     (r#"{"keywords": ["rust"]}"#, JsonType::String, false, json!(["rust"])),
+///     ("[2024, 2025]", JsonType::String, false, json!(["2024", "2025"])),
+///     ("[1, 2.5]", JsonType::Number, false, json!([1, 2.5])),
+///     (r#"["yes", null, "no"]"#, JsonType::String, false, json!(["yes", "no"])),
+///     // Bulleted and numbered lists, with a heading
+///     ("- rust\n- go", JsonType::String, false, json!(["rust", "go"])),
+///     ("* rust\n* go\n", JsonType::String, false, json!(["rust", "go"])),
+///     ("• rust\n• go", JsonType::String, false, json!(["rust", "go"])),
+///     ("1. rust\n2. go\n10) zig", JsonType::String, false, json!(["rust", "go", "zig"])),
+///     ("Keywords:\n- rust\n- go", JsonType::String, false, json!(["rust", "go"])),
+///     ("- \"rust\",\n- 'go'", JsonType::String, false, json!(["rust", "go"])),
+///     ("- -5\n- 1.5", JsonType::Number, false, json!([-5, 1.5])),
+///     // One value per line
+///     ("rust\n\ngo\n", JsonType::String, false, json!(["rust", "go"])),
+///     // Separated values
+///     ("rust, go, zig", JsonType::String, false, json!(["rust", "go", "zig"])),
+///     ("rust,go", JsonType::String, false, json!(["rust", "go"])),
+///     ("rust, go and zig.", JsonType::String, false, json!(["rust", "go and zig"])),
+///     ("rust, go, and zig.", JsonType::String, false, json!(["rust", "go", "zig"])),
+///     ("rust, go, or zig", JsonType::String, false, json!(["rust", "go", "zig"])),
+///     ("New York, NY; Paris", JsonType::String, false, json!(["New York, NY", "Paris"])),
+///     ("rust, , go,", JsonType::String, false, json!(["rust", "go"])),
+///     ("Node.js", JsonType::String, false, json!(["Node.js"])),
+///     ("true, false", JsonType::Boolean, false, json!([true, false])),
+///     // Thousands separators in number lists
+///     ("1,200", JsonType::Number, false, json!([1200])),
+///     ("1,200, 3,400", JsonType::Number, false, json!([1200, 3400])),
+///     ("1,2,3", JsonType::Number, false, json!([1, 2, 3])),
+///     ("1,2,3", JsonType::String, false, json!(["1", "2", "3"])),
+///     // Duplicates are dropped for sets only
+///     ("rust, go, rust", JsonType::String, false, json!(["rust", "go", "rust"])),
+///     ("rust, go, rust", JsonType::String, true, json!(["rust", "go"])),
+///     ("<item>go</item><item> go</item>", JsonType::String, true, json!(["go"])),
+///     // Null-like answers
+///     ("", JsonType::String, false, json!(null)),
+///     ("None", JsonType::String, false, json!(null)),
+///     ("[]", JsonType::String, false, json!([])),
+/// ];
+///
+/// for (content, item_type, unique, expected) in cases {
+///     assert_eq!(parse_list_value(content, item_type, unique), expected, "{:?}", content);
+/// }
+/// ```
+pub fn parse_list_value(content: &str, item_type: JsonType, unique: bool) -> Value {
+    let cleaned = content.trim();
+    if cleaned.is_empty()
+        || cleaned.eq_ignore_ascii_case("null")
+        || cleaned.eq_ignore_ascii_case("none")
+    {
+        return Value::Null;
+    }
+
+    let items: Vec<Value> = if let Some(tagged) = item_tag_contents(cleaned) {
+        tagged
+            .iter()
+            .filter_map(|item| clean_list_item(item))
+            .map(|item| list_item_value(item, item_type))
+            .collect()
+    } else {
+        match serde_json::from_str::<Value>(cleaned) {
+            Ok(Value::Array(values)) => json_list_items(values, item_type),
+            Ok(Value::Object(object)) if object.len() == 1 => match object.into_iter().next() {
+                Some((_, Value::Array(values))) => json_list_items(values, item_type),
+                _ => split_list_text(cleaned, item_type),
+            },
+            _ => split_list_text(cleaned, item_type),
+        }
+    };
+
+    let mut elements: Vec<Value> = Vec::new();
+    for item in items {
+        if !(unique && elements.contains(&item)) {
+            elements.push(item);
+        }
+    }
+
+    Value::Array(elements)
+}
+
+/// Parses the text a model returned for a field like `parse_field_value`, using the field's
+/// descriptor to parse lists of strings, numbers and booleans with `parse_list_value`, and to
+/// keep answers of `JsonType::String` fields that read as numbers or booleans as text, so a
+/// postal code `12345` stays `"12345"`.
+///
+/// # Arguments
+///
+/// * `content` - The text returned for the field
+/// * `field_path` - The dotted path of the field, e.g. `address.city` or `items[0].tags`
+/// * `fields` - The descriptors of the Task the path starts from
+///
+/// # Returns
+///
+/// The parsed value
+pub fn parse_described_field_value(
+    content: &str,
+    field_path: &str,
+    fields: &[FieldDescriptor],
+) -> Value {
+    match find_field_descriptor(fields, field_path) {
+        Some(field) if field.is_primitive_list() => {
+            parse_list_value(content, field.item_type, field.is_set())
+        }
+        Some(field) if field.json_type == JsonType::String => text_field_value(content, field_path),
+        _ => parse_field_value(content, field_path),
+    }
+}
+
+/// Parses the answer of a text field like `parse_field_value`, keeping a number or boolean
+/// as the text the model wrote, or as its JSON text when it was wrapped in an object.
+fn text_field_value(content: &str, field_path: &str) -> Value {
+    let cleaned: &str = content.trim();
+    match parse_field_value(content, field_path) {
+        value @ (Value::Number(_) | Value::Bool(_)) if cleaned.starts_with('{') => {
+            Value::String(value.to_string())
+        }
+        Value::Number(_) | Value::Bool(_) => Value::String(cleaned.to_string()),
+        value => value,
+    }
+}
+
+/// Finds the descriptor of a dotted field path, ignoring collection indices.
+pub(crate) fn find_field_descriptor<'a>(
+    fields: &'a [FieldDescriptor],
+    field_path: &str,
+) -> Option<&'a FieldDescriptor> {
+    let mut fields: &[FieldDescriptor] = fields;
+    let mut found: Option<&FieldDescriptor> = None;
+    for part in field_path.split('.') {
+        let name: &str = part.split('[').next().unwrap_or(part);
+        let field: &FieldDescriptor = fields.iter().find(|field| field.name == name)?;
+        fields = &field.children;
+        found = Some(field);
+    }
+
+    found
+}
+
+/// Returns the contents of the `<item></item>` tags in a response, or `None` when it has none.
+fn item_tag_contents(content: &str) -> Option<Vec<&str>> {
+    let mut items: Vec<&str> = Vec::new();
+    let mut rest: &str = content;
+    while let Some(start) = rest.find("<item>") {
+        let after: &str = &rest[start + 6..];
+        let Some(end) = after.find("</item>") else {
+            break;
+        };
+        items.push(&after[..end]);
+        rest = &after[end + 7..];
+    }
+
+    if items.is_empty() { None } else { Some(items) }
+}
+
+/// Converts the elements of a JSON array answer, turning numbers into strings for string items.
+fn json_list_items(values: Vec<Value>, item_type: JsonType) -> Vec<Value> {
+    values
+        .into_iter()
+        .filter_map(|value| match value {
+            Value::String(text) => {
+                clean_list_item(&text).map(|item| list_item_value(item, item_type))
+            }
+            Value::Number(number) if item_type == JsonType::String => {
+                Some(Value::String(number.to_string()))
+            }
+            Value::Null => None,
+            value => Some(value),
+        })
+        .collect()
+}
+
+/// Splits a plain text list into its items.
+///
+/// When several lines are given, or a line starts with a list marker, each line is an item
+/// and a heading such as `Keywords:` before a marked list is skipped. A single line is split
+/// on semicolons, or else on commas, dropping a trailing period and an `and` or `or` before
+/// the last item. Number lists written with `, ` between values are split there only, so
+/// `1,200, 3,400` keeps its thousands separators, and `1,200` is not split at all.
+fn split_list_text(text: &str, item_type: JsonType) -> Vec<Value> {
+    let lines: Vec<&str> = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect();
+    let marked: bool = lines.iter().any(|line| strip_list_marker(line).is_some());
+
+    let items: Vec<&str> = if lines.len() > 1 || marked {
+        lines
+            .iter()
+            .filter_map(|line| match strip_list_marker(line) {
+                Some(item) => Some(item),
+                None if marked => None,
+                None => Some(*line),
+            })
+            .collect()
+    } else {
+        let line: &str = text.strip_suffix('.').unwrap_or(text);
+        let mut items: Vec<&str> = if line.contains(';') {
+            line.split(';').collect()
+        } else if item_type == JsonType::Number && line.contains(", ") {
+            line.split(", ").collect()
+        } else if item_type == JsonType::Number {
+            split_number_list(line)
+        } else {
+            line.split(',').collect()
+        };
+        if items.len() > 1
+            && let Some(last) = items.last_mut()
+        {
+            let trimmed: &str = last.trim_start();
+            *last = trimmed
+                .strip_prefix("and ")
+                .or_else(|| trimmed.strip_prefix("or "))
+                .unwrap_or(trimmed);
+        }
+        items
+    };
+
+    items
+        .into_iter()
+        .filter_map(clean_list_item)
+        .map(|item| list_item_value(item, item_type))
+        .collect()
+}
+
+/// Splits numbers written without spaces, keeping `1,200` whole and splitting `1,2,3`.
+fn split_number_list(line: &str) -> Vec<&str> {
+    let parts: Vec<&str> = line.split(',').collect();
+    let thousands: bool = parts.len() > 1
+        && parts[1..]
+            .iter()
+            .all(|part| part.len() >= 3 && part[..3].chars().all(|c| c.is_ascii_digit()));
+
+    if thousands { vec![line] } else { parts }
+}
+
+/// Returns the text after a leading `-`, `*`, `+`, `•` or `1.`/`1)` list marker.
+fn strip_list_marker(line: &str) -> Option<&str> {
+    for marker in ["- ", "* ", "+ ", "• ", "– "] {
+        if let Some(item) = line.strip_prefix(marker) {
+            return Some(item);
+        }
+    }
+
+    let digits: usize = line.chars().take_while(char::is_ascii_digit).count();
+    if digits == 0 {
+        return None;
+    }
+    let rest: &str = &line[digits..];
+    rest.strip_prefix(". ").or_else(|| rest.strip_prefix(") "))
+}
+
+/// Trims an item and its surrounding quotes and separators, returning `None` when empty.
+fn clean_list_item(item: &str) -> Option<&str> {
+    let mut item: &str = item.trim().trim_end_matches([',', ';']).trim();
+    for quote in ['"', '\'', '`'] {
+        if item.len() >= 2 && item.starts_with(quote) && item.ends_with(quote) {
+            item = item[1..item.len() - 1].trim();
+        }
+    }
+
+    if item.is_empty() { None } else { Some(item) }
+}
+
+/// Converts one list item to the JSON value of its element type.
+fn list_item_value(item: &str, item_type: JsonType) -> Value {
+    match item_type {
+        JsonType::String => Value::String(item.to_string()),
+        _ => parse_field_value(item, ""),
+    }
+}
+
+/// Splits a field path such as `items[0].price` or `scores[math]` into its segments.
+pub(crate) fn split_field_path(path: &str) -> Option<Vec<String>> {
+    let mut segments: Vec<String> = Vec::new();
+    for part in path.split('.') {
+        let (name, indices) = match part.find('[') {
+            Some(start) => (&part[..start], &part[start..]),
+            None => (part, ""),
+        };
+        if !name.is_empty() {
+            segments.push(name.to_string());
+        }
+        for index in indices.split('[').skip(1) {
+            segments.push(index.strip_suffix(']')?.to_string());
+        }
+    }
+
+    if segments.is_empty() {
+        return None;
+    }
+
+    Some(segments)
+}
+
+/// Returns the array position a path segment names: an index such as `0`, or the positional
+/// name of a tuple struct field such as `field_0`.
+fn array_index(segment: &str) -> Option<usize> {
+    segment
+        .strip_prefix("field_")
+        .unwrap_or(segment)
+        .parse::<usize>()
+        .ok()
+}
+
+/// Returns the value at a field path such as `address.city`, `items[0].price` or `scores[math]`.
+///
+/// The empty path of a newtype's field is the whole value.
+pub(crate) fn get_field_path<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    if path.is_empty() {
+        return Some(value);
+    }
+
+    let mut current: &Value = value;
+    for segment in split_field_path(path)? {
+        current = match current {
+            Value::Object(map) => map.get(&segment)?,
+            Value::Array(items) => items.get(array_index(&segment)?)?,
+            _ => return None,
+        };
+    }
+
+    Some(current)
+}
+
+/// Sets the value at a field path, creating missing objects along the way.
+///
+/// The empty path of a newtype's field replaces the whole value.
+pub(crate) fn set_field_path(
+    value: &mut Value,
+    path: &str,
+    new_value: Value,
+) -> Result<(), SecretaryError> {
+    if path.is_empty() {
+        *value = new_value;
+        return Ok(());
+    }
+
+    let invalid_path = || SecretaryError::InvalidFieldPath(path.to_string());
+
+    let segments: Vec<String> = split_field_path(path).ok_or_else(invalid_path)?;
+    let (last, parents) = segments.split_last().ok_or_else(invalid_path)?;
+
+    let mut current: &mut Value = value;
+    for segment in parents {
+        current = match current {
+            Value::Object(map) => map
+                .entry(segment.clone())
+                .or_insert_with(|| Value::Object(Map::new())),
+            Value::Array(items) => {
+                let index: usize = array_index(segment).ok_or_else(invalid_path)?;
+                items.get_mut(index).ok_or_else(invalid_path)?
+            }
+            _ => return Err(invalid_path()),
+        };
+    }
+
+    match current {
+        Value::Object(map) => {
+            map.insert(last.clone(), new_value);
+        }
+        Value::Array(items) => {
+            let index: usize = array_index(last).ok_or_else(invalid_path)?;
+            *items.get_mut(index).ok_or_else(invalid_path)? = new_value;
+        }
+        _ => return Err(invalid_path()),
+    }
+
+    Ok(())
+}
+
+/// Removes the object entry at a field path, if there is one.
+pub(crate) fn remove_field_path(value: &mut Value, path: &str) {
+    let segments: Vec<String> = match split_field_path(path) {
+        Some(segments) => segments,
+        None => return,
+    };
+    let (last, parents) = match segments.split_last() {
+        Some(split) => split,
+        None => return,
+    };
+
+    let mut current: &mut Value = value;
+    for segment in parents {
+        let next: Option<&mut Value> = match current {
+            Value::Object(map) => map.get_mut(segment),
+            Value::Array(items) => array_index(segment).and_then(|index| items.get_mut(index)),
+            _ => None,
+        };
+        current = match next {
+            Some(next) => next,
+            None => return,
+        };
+    }
+
+    if let Value::Object(map) = current {
+        map.remove(last);
+    }
+}
+
+/// The parts of a chat completions response that hold the content, borrowed from the body.
+#[derive(Deserialize)]
+struct ChatCompletionEnvelope<'a> {
+    #[serde(borrow, default)]
+    choices: Vec<ChatCompletionChoice<'a>>,
+}
+
+#[derive(Deserialize)]
+struct ChatCompletionChoice<'a> {
+    #[serde(borrow, default)]
+    message: Option<ChatCompletionMessage<'a>>,
+}
+
+#[derive(Deserialize)]
+struct ChatCompletionMessage<'a> {
+    #[serde(borrow, default)]
+    content: Option<Cow<'a, str>>,
+    #[serde(borrow, default)]
+    refusal: Option<Cow<'a, str>>,
 }
 
 /// Extract texts from the API response from LLM
@@ -89,6 +818,11 @@ pub fn format_additional_instructions(additional_instructions: &Vec<String>) ->
 /// from the first choice's message. It returns the extracted content as a string or
 /// an error if the content is not found.
 ///
+/// The other members of the response are skipped without being materialized, and content
+/// without escape sequences is copied once, straight from the body. A response of an
+/// unexpected shape, such as one whose content is not a string, is looked up through a
+/// `serde_json::Value` instead, so it is treated exactly as before.
+///
 /// # Arguments
 ///
 /// * `api_response` - A string slice containing the raw JSON response from the LLLM API
@@ -97,13 +831,587 @@ pub fn format_additional_instructions(additional_instructions: &Vec<String>) ->
 ///
 /// A Result containing:
 ///   - Ok(String): The extracted text content
-///   - Err: An error if the content cannot be extracted (e.g., invalid JSON or missing field)
+///   - Err: `SecretaryError::Refused` if the message has a `refusal` and no content, or
+///     another error if the content cannot be extracted (e.g., invalid JSON or missing field)
 pub fn extract_text_content_from_llm_response(
     api_response: &str,
 ) -> Result<String, Box<dyn std::error::Error + Send + Sync + 'static>> {
-    let value: Value = serde_json::from_str(&api_response)?;
-    match value["choices"][0]["message"]["content"].as_str() {
-        Some(result) => Ok(result.to_string()),
-        None => return Err(SecretaryError::NoLLMResponse.into()),
+    if let Ok(envelope) = serde_json::from_str::<ChatCompletionEnvelope>(api_response) {
+        let message: Option<ChatCompletionMessage> = envelope
+            .choices
+            .into_iter()
+            .next()
+            .and_then(|choice| choice.message);
+        return match message {
+            Some(ChatCompletionMessage {
+                content: Some(content),
+                ..
+            }) => Ok(content.into_owned()),
+            Some(ChatCompletionMessage {
+                refusal: Some(refusal),
+                ..
+            }) if !refusal.is_empty() => Err(refused(&refusal)),
+            _ => Err(SecretaryError::NoLLMResponse.into()),
+        };
+    }
+
+    let value: Value = serde_json::from_str(api_response)?;
+    let message: &Value = &value["choices"][0]["message"];
+    match (message["content"].as_str(), message["refusal"].as_str()) {
+        (Some(result), _) => Ok(result.to_string()),
+        (None, Some(refusal)) if !refusal.is_empty() => Err(refused(refusal)),
+        _ => Err(SecretaryError::NoLLMResponse.into()),
+    }
+}
+
+/// Wraps the refusal a provider reported as `SecretaryError::Refused`.
+fn refused(refusal: &str) -> Box<dyn std::error::Error + Send + Sync + 'static> {
+    Box::new(SecretaryError::Refused {
+        message: refusal.to_string(),
+    })
+}
+
+/// Extracts the texts of every choice from the API response of the LLM, in order.
+///
+/// Responses to a request with `n` above 1, see `RequestOptions::with_choices`, carry one
+/// choice per sample. Choices without text content, such as refusals, are skipped.
+///
+/// # Arguments
+///
+/// * `api_response` - A string slice containing the raw JSON response from the LLM API
+///
+/// # Returns
+///
+/// A Result containing:
+///   - Ok(Vec<String>): The text content of each choice that has one
+///   - Err: `SecretaryError::NoLLMResponse` if no choice has text content, or the parse error
+///     if the response is not JSON
+///
+/// # Examples
+///
+/// ```rust
+/// use secretary::utilities::extract_text_contents_from_llm_response;
+/// use serde_json::json;
+///
+/// let response = json!({"choices": [
+///     {"index": 0, "message": {"role": "assistant", "content": "first"}},
+///     {"index": 1, "message": {"role": "assistant", "content": null}},
+///     {"index": 2, "message": {"role": "assistant", "content": "third"}}
+/// ]});
+/// let contents = extract_text_contents_from_llm_response(&response.to_string()).unwrap();
+/// assert_eq!(contents, vec!["first", "third"]);
+/// ```
+pub fn extract_text_contents_from_llm_response(
+    api_response: &str,
+) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let contents: Vec<String> = match serde_json::from_str::<ChatCompletionEnvelope>(api_response) {
+        Ok(envelope) => envelope
+            .choices
+            .into_iter()
+            .filter_map(|choice| choice.message?.content)
+            .map(Cow::into_owned)
+            .collect(),
+        Err(_) => {
+            let value: Value = serde_json::from_str(api_response)?;
+            value["choices"]
+                .as_array()
+                .map(|choices| {
+                    choices
+                        .iter()
+                        .filter_map(|choice| choice["message"]["content"].as_str())
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default()
+        }
+    };
+
+    if contents.is_empty() {
+        return Err(SecretaryError::NoLLMResponse.into());
+    }
+
+    Ok(contents)
+}
+
+/// Extracts the total token usage from the API response of the LLM.
+///
+/// # Arguments
+///
+/// * `api_response` - A string slice containing the raw JSON response from the LLM API
+///
+/// # Returns
+///
+/// The `usage.total_tokens` value, or `None` if the response does not report usage
+pub fn extract_total_tokens_from_llm_response(api_response: &str) -> Option<u64> {
+    let value: Value = serde_json::from_str(api_response).ok()?;
+    value["usage"]["total_tokens"].as_u64()
+}
+
+/// Extracts the number of prompt tokens served from the provider's prompt cache.
+///
+/// Reads `usage.prompt_tokens_details.cached_tokens` as reported by OpenAI, falling back to
+/// `usage.cache_read_input_tokens` as reported by Anthropic-style endpoints.
+///
+/// # Arguments
+///
+/// * `api_response` - A string slice containing the raw JSON response from the LLM API
+///
+/// # Returns
+///
+/// The cached prompt tokens, or `None` if the response does not report them
+pub fn extract_cached_tokens_from_llm_response(api_response: &str) -> Option<u64> {
+    let value: Value = serde_json::from_str(api_response).ok()?;
+    value["usage"]["prompt_tokens_details"]["cached_tokens"]
+        .as_u64()
+        .or_else(|| value["usage"]["cache_read_input_tokens"].as_u64())
+}
+
+/// Extracts the `system_fingerprint` from a chat completions response.
+///
+/// OpenAI reports the backend configuration that served a request as `system_fingerprint`;
+/// two responses to the same seed are only expected to match when their fingerprints do.
+///
+/// # Arguments
+///
+/// * `api_response` - The raw JSON response body
+///
+/// # Returns
+///
+/// The fingerprint, or `None` when the response has none
+pub fn extract_system_fingerprint_from_llm_response(api_response: &str) -> Option<String> {
+    let value: Value = serde_json::from_str(api_response).ok()?;
+    value["system_fingerprint"].as_str().map(str::to_string)
+}
+
+/// Decodes bytes as UTF-8, replacing every invalid sequence with U+FFFD.
+///
+/// Like `String::from_utf8_lossy`, and also counts the sequences it replaced so that the
+/// corruption is not silent.
+///
+/// # Arguments
+///
+/// * `bytes` - The bytes to decode, e.g. a response body
+///
+/// # Returns
+///
+/// The decoded text and the number of invalid sequences replaced
+///
+/// # Examples
+///
+/// ```rust
+/// use secretary::utilities::decode_utf8_lossy;
+///
+/// assert_eq!(decode_utf8_lossy("Zoë".as_bytes()), ("Zoë".to_string(), 0));
+/// // A truncated two-byte sequence and a stray continuation byte
+/// assert_eq!(
+///     decode_utf8_lossy(b"Zo\xC3 and \x80!"),
+///     ("Zo\u{FFFD} and \u{FFFD}!".to_string(), 2)
+/// );
+/// ```
+pub fn decode_utf8_lossy(bytes: &[u8]) -> (String, usize) {
+    let mut text: String = String::with_capacity(bytes.len());
+    let mut replaced: usize = 0;
+    for chunk in bytes.utf8_chunks() {
+        text.push_str(chunk.valid());
+        if !chunk.invalid().is_empty() {
+            text.push(char::REPLACEMENT_CHARACTER);
+            replaced += 1;
+        }
+    }
+
+    (text, replaced)
+}
+
+/// Replaces the escape sequences in the strings of JSON text that no JSON parser accepts
+/// with `\ufffd`, the escape of U+FFFD.
+///
+/// Models clip emoji in the middle of a surrogate pair, leaving a lone `\ud83d`, and
+/// occasionally write escapes JSON does not have, such as `\x41` or `\d`. These are
+/// repaired:
+///
+/// - a high surrogate escape that is not followed by a low surrogate escape, and a low
+///   surrogate escape that does not follow a high one
+/// - `\u` followed by fewer than four hex digits, which keep their place after the repair
+/// - a backslash before any character but `"`, `\`, `/`, `b`, `f`, `n`, `r`, `t` and `u`,
+///   which is replaced together with that character
+///
+/// Text outside strings and valid escapes are left as they are.
+///
+/// # Arguments
+///
+/// * `text` - The JSON text to repair
+///
+/// # Returns
+///
+/// The repaired text and the number of sequences replaced
+///
+/// # Examples
+///
+/// ```rust
+/// use secretary::utilities::repair_json_escapes;
+///
+/// // A clipped emoji
+/// let (repaired, count) = repair_json_escapes(r#"{"note": "great \ud83d"}"#);
+/// assert_eq!(repaired, r#"{"note": "great \ufffd"}"#);
+/// assert_eq!(count, 1);
+/// let value: serde_json::Value = serde_json::from_str(&repaired).unwrap();
+/// assert_eq!(value["note"], "great \u{FFFD}");
+///
+/// // Complete pairs and other valid escapes are kept
+/// let valid = r#"{"note": "😀 \"quoted\" \\d \n"}"#;
+/// assert_eq!(repair_json_escapes(valid), (valid.to_string(), 0));
+///
+/// // Invalid escapes, and backslashes outside strings
+/// assert_eq!(
+///     repair_json_escapes(r#"{"a": "\x41\u12", "b": "\udc00"} \d"#),
+///     (r#"{"a": "\ufffd41\ufffd12", "b": "\ufffd"} \d"#.to_string(), 3)
+/// );
+/// ```
+pub fn repair_json_escapes(text: &str) -> (String, usize) {
+    if !text.contains('\\') {
+        return (text.to_string(), 0);
+    }
+
+    let chars: Vec<char> = text.chars().collect();
+    let mut repaired: String = String::with_capacity(text.len());
+    let mut count: usize = 0;
+    let mut in_string: bool = false;
+    let mut index: usize = 0;
+    while index < chars.len() {
+        let c: char = chars[index];
+        if !in_string || c != '\\' {
+            if c == '"' {
+                in_string = !in_string;
+            }
+            repaired.push(c);
+            index += 1;
+            continue;
+        }
+
+        match chars.get(index + 1) {
+            Some('"' | '\\' | '/' | 'b' | 'f' | 'n' | 'r' | 't') => {
+                repaired.extend(&chars[index..index + 2]);
+                index += 2;
+            }
+            Some('u') => match unicode_escape(&chars, index) {
+                // A high surrogate is only valid with the low surrogate after it
+                Some(0xD800..=0xDBFF) => match unicode_escape(&chars, index + 6) {
+                    Some(0xDC00..=0xDFFF) => {
+                        repaired.extend(&chars[index..index + 12]);
+                        index += 12;
+                    }
+                    _ => {
+                        repaired.push_str("\\ufffd");
+                        count += 1;
+                        index += 6;
+                    }
+                },
+                Some(0xDC00..=0xDFFF) => {
+                    repaired.push_str("\\ufffd");
+                    count += 1;
+                    index += 6;
+                }
+                Some(_) => {
+                    repaired.extend(&chars[index..index + 6]);
+                    index += 6;
+                }
+                None => {
+                    repaired.push_str("\\ufffd");
+                    count += 1;
+                    index += 2;
+                }
+            },
+            Some(_) => {
+                repaired.push_str("\\ufffd");
+                count += 1;
+                index += 2;
+            }
+            None => {
+                repaired.push_str("\\ufffd");
+                count += 1;
+                index += 1;
+            }
+        }
+    }
+
+    (repaired, count)
+}
+
+/// Returns the code unit of the `\uXXXX` escape at `index`, if there is a complete one.
+fn unicode_escape(chars: &[char], index: usize) -> Option<u32> {
+    if chars.get(index) != Some(&'\\') || chars.get(index + 1) != Some(&'u') {
+        return None;
+    }
+    let digits: &[char] = chars.get(index + 2..index + 6)?;
+    if !digits.iter().all(char::is_ascii_hexdigit) {
+        return None;
+    }
+
+    u32::from_str_radix(&digits.iter().collect::<String>(), 16).ok()
+}
+
+/// Finds every balanced top-level JSON object in a piece of mixed text.
+///
+/// Reasoning models often surround their answer with prose, markdown fences, or illustrative
+/// JSON fragments. This scanner matches braces while respecting string literals and escape
+/// sequences, so braces inside JSON strings do not affect nesting. Objects nested inside
+/// another candidate are not reported separately, and an opening brace that is never closed
+/// is skipped so that later candidates are still found.
+///
+/// # Arguments
+///
+/// * `content` - The text to scan
+///
+/// # Returns
+///
+/// The candidate object slices in order of appearance
+///
+/// # Examples
+///
+/// ```rust
+/// use secretary::utilities::find_json_object_candidates;
+///
+/// // Braces inside strings and escaped quotes do not confuse the scanner
+/// let text = r#"Answer: {"note": "use {braces} and \"quotes\"", "n": {"x": 1}} done"#;
+/// assert_eq!(
+///     find_json_object_candidates(text),
+///     vec![r#"{"note": "use {braces} and \"quotes\"", "n": {"x": 1}}"#]
+/// );
+///
+/// // Fenced code blocks
+/// let text = "Here you go:\n```json\n{\"name\": \"Ada\"}\n```";
+/// assert_eq!(find_json_object_candidates(text), vec![r#"{"name": "Ada"}"#]);
+///
+/// // Two competing objects, and an unclosed brace in the prose
+/// let text = r#"For example {"name": "example"} ... so { the final answer is {"name": "Ada"}"#;
+/// assert_eq!(
+///     find_json_object_candidates(text),
+///     vec![r#"{"name": "example"}"#, r#"{"name": "Ada"}"#]
+/// );
+///
+/// assert!(find_json_object_candidates("no json here").is_empty());
+/// assert!(find_json_object_candidates(r#"{"unterminated": "}"#).is_empty());
+/// ```
+pub fn find_json_object_candidates(content: &str) -> Vec<&str> {
+    let bytes: &[u8] = content.as_bytes();
+    let mut candidates: Vec<&str> = Vec::new();
+    let mut position: usize = 0;
+
+    while position < bytes.len() {
+        if bytes[position] != b'{' {
+            position += 1;
+            continue;
+        }
+
+        match find_matching_brace(bytes, position) {
+            Some(end) => {
+                candidates.push(&content[position..=end]);
+                position = end + 1;
+            }
+            None => position += 1,
+        }
     }
+
+    candidates
+}
+
+/// Returns the index of the brace closing the object that starts at `start`, if any.
+fn find_matching_brace(bytes: &[u8], start: usize) -> Option<usize> {
+    let mut depth: usize = 0;
+    let mut in_string: bool = false;
+    let mut escaped: bool = false;
+
+    for (index, byte) in bytes.iter().enumerate().skip(start) {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if *byte == b'\\' {
+                escaped = true;
+            } else if *byte == b'"' {
+                in_string = false;
+            }
+            continue;
+        }
+
+        match byte {
+            b'"' => in_string = true,
+            b'{' => depth += 1,
+            b'}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(index);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Parses a Task from free-form model output that may contain several JSON objects.
+///
+/// Thinking blocks are removed first. Every balanced JSON object is then collected and
+/// deserialized into `T`, starting from the last one, since models tend to put their
+/// final answer after any illustrative fragments. If no candidate deserializes as-is, the
+/// candidate sharing the most top-level keys with `T` is merged over `T::default()` and
+/// deserialized leniently. The leniency profile is applied to every candidate before it is
+/// deserialized.
+///
+/// # Arguments
+///
+/// * `content` - The raw text content returned by the LLM
+/// * `leniency` - The coercions to apply, `LeniencyProfile::strict()` for none
+///
+/// # Returns
+///
+/// The parsed Task, or `SecretaryError::JsonParsingError` listing every candidate when
+/// none of them could be used, with the content as its `raw_content`. Content without any
+/// candidate that `refusal::detect_refusal` takes for a refusal is `SecretaryError::Refused`.
+///
+/// # Examples
+///
+/// ```rust
+/// use secretary::Task;
+/// use secretary::leniency::LeniencyProfile;
+/// use secretary::utilities::parse_task_from_mixed_text;
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Task, Serialize, Deserialize, Debug)]
+/// struct Person {
+///     #[task(instruction = "Extract the name")]
+///     pub name: String,
+///     #[task(instruction = "Extract the age as a number")]
+///     pub age: u32,
+/// }
+///
+/// // The final answer wins over an illustrative fragment in the reasoning
+/// let content = r#"<think>
+/// The output should look like {"name": "...", "age": "?"}
+/// </think>
+/// {"name": "Ada", "age": 36}"#;
+/// let strict = LeniencyProfile::strict();
+/// let person: Person = parse_task_from_mixed_text(content, &strict).unwrap();
+/// assert_eq!((person.name.as_str(), person.age), ("Ada", 36));
+///
+/// // A partial object is merged over the defaults
+/// let person: Person = parse_task_from_mixed_text(r#"Result: {"name": "Ada"}"#, &strict).unwrap();
+/// assert_eq!((person.name.as_str(), person.age), ("Ada", 0));
+///
+/// // Leniency applies to the candidates
+/// let content = r#"{"name": "Ada", "age": "36"}"#;
+/// assert!(parse_task_from_mixed_text::<Person>(content, &strict).is_err());
+/// let person: Person = parse_task_from_mixed_text(content, &LeniencyProfile::standard()).unwrap();
+/// assert_eq!(person.age, 36);
+///
+/// // Nothing usable lists every candidate
+/// let error = parse_task_from_mixed_text::<Person>(r#"{"a": 1} {"b": 2}"#, &strict).unwrap_err();
+/// assert!(error.to_string().contains(r#"[2] {"b": 2}"#));
+/// ```
+pub fn parse_task_from_mixed_text<T: Task>(
+    content: &str,
+    leniency: &LeniencyProfile,
+) -> Result<T, SecretaryError> {
+    parse_task_from_candidates(
+        &cleanup_thinking_blocks(content.to_string()),
+        content,
+        leniency,
+    )
+}
+
+/// Parses a Task like `parse_task_from_mixed_text`, from an answer whose reasoning was
+/// already removed, see `reasoning::strip_inline_reasoning`.
+pub(crate) fn parse_task_from_answer<T: Task>(
+    content: &str,
+    leniency: &LeniencyProfile,
+) -> Result<T, SecretaryError> {
+    parse_task_from_candidates(content, content, leniency)
+}
+
+/// Parses a Task from the JSON object candidates of `content`, reporting `raw_content` when
+/// none can be used.
+fn parse_task_from_candidates<T: Task>(
+    content: &str,
+    raw_content: &str,
+    leniency: &LeniencyProfile,
+) -> Result<T, SecretaryError> {
+    let candidates: Vec<&str> = find_json_object_candidates(content);
+
+    if candidates.is_empty() {
+        if let Some(message) = detect_refusal(raw_content) {
+            return Err(SecretaryError::Refused { message });
+        }
+        return Err(SecretaryError::JsonParsingError {
+            message: "No JSON object found in the LLM response".to_string(),
+            raw_content: raw_content.to_string(),
+        });
+    }
+
+    let mut last_error: Option<serde_json::Error> = None;
+    for candidate in candidates.iter().rev() {
+        match leniency.from_str::<T>(candidate) {
+            Ok(result) => return Ok(result),
+            Err(error) => last_error = Some(error),
+        }
+    }
+
+    // None of the candidates fully deserialized, so merge the best match over the defaults
+    let default_map: Map<String, Value> = match serde_json::to_value(T::default()) {
+        Ok(Value::Object(map)) => map,
+        _ => Map::new(),
+    };
+
+    let fields: Vec<FieldDescriptor> = T::field_descriptors();
+    let mut best_match: Option<(usize, Map<String, Value>)> = None;
+    for candidate in candidates.iter().rev() {
+        if let Ok(mut value) = serde_json::from_str::<Value>(candidate) {
+            to_native_keys(&fields, &mut value);
+            let Value::Object(map) = value else {
+                continue;
+            };
+            let score: usize = map
+                .keys()
+                .filter(|key| default_map.contains_key(*key))
+                .count();
+            if score > 0 && best_match.as_ref().is_none_or(|(best, _)| score > *best) {
+                best_match = Some((score, map));
+            }
+        }
+    }
+
+    if let Some((_, candidate_map)) = best_match {
+        let mut merged: Map<String, Value> = default_map;
+        for (key, value) in candidate_map {
+            if merged.contains_key(&key) {
+                merged.insert(key, value);
+            }
+        }
+
+        let mut merged: Value = Value::Object(merged);
+        leniency.apply::<T>(&mut merged);
+        normalize_strings(&fields, &mut merged);
+
+        match serde_json::from_value::<T>(merged) {
+            Ok(result) => return Ok(result),
+            Err(error) => last_error = Some(error),
+        }
+    }
+
+    let listed: Vec<String> = candidates
+        .iter()
+        .enumerate()
+        .map(|(index, candidate)| format!("[{}] {}", index + 1, candidate))
+        .collect();
+
+    Err(SecretaryError::JsonParsingError {
+        message: format!(
+            "None of the {} JSON candidate(s) matched the target schema. Last error: {}. Candidates: {}",
+            candidates.len(),
+            last_error
+                .map(|error| error.to_string())
+                .unwrap_or_default(),
+            listed.join("; ")
+        ),
+        raw_content: raw_content.to_string(),
+    })
 }
@@ -0,0 +1,340 @@
+//! Validating LLM output against a Task's JSON Schema.
+//!
+//! Serde stops at the first field it cannot deserialize and describes it in terms of Rust
+//! types. Schema validation instead reports every violation of the response at once, each
+//! located by a JSON pointer, which is usually more useful when debugging a prompt. It runs
+//! on the parsed response before deserialization when `RequestOptions::with_schema_validation`
+//! is set, and a failing response is reported as `SecretaryError::SchemaViolation`.
+//!
+//! The schema is the one from `schema::json_schema`, made strict with `strict_schema` so that
+//! keys the Task does not have are reported. `SchemaValidation::AllowAdditionalProperties`
+//! still deserializes responses whose only violations are such keys.
+//!
+//! Validation requires the `schema-validation` feature.
+//!
+//! # Examples
+//!
+//! ```rust
+//! # #[cfg(feature = "schema-validation")]
+//! # {
+//! use secretary::Task;
+//! use secretary::validation::{ViolationKind, validate};
+//! use serde::{Deserialize, Serialize};
+//! use serde_json::json;
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! struct Product {
+//!     #[task(instruction = "Extract the product name")]
+//!     pub name: String,
+//!     #[task(instruction = "Extract the price as a number")]
+//!     pub price: f64,
+//!     #[task(instruction = "Extract the age of the product in years")]
+//!     pub age: u32,
+//! }
+//!
+//! // A typo'd key
+//! let violations = validate::<Product>(&json!({"name": "Lamp", "pricee": 12.5, "price": 12.5, "age": 2}));
+//! assert_eq!(violations.len(), 1);
+//! assert_eq!(violations[0].pointer, "/pricee");
+//! assert_eq!(violations[0].kind, ViolationKind::AdditionalProperty);
+//!
+//! // A wrong-typed value
+//! let violations = validate::<Product>(&json!({"name": "Lamp", "price": 12.5, "age": "two"}));
+//! assert_eq!(violations.len(), 1);
+//! assert_eq!(violations[0].pointer, "/age");
+//! assert_eq!(violations[0].kind, ViolationKind::Type);
+//!
+//! // A missing required key
+//! let violations = validate::<Product>(&json!({"name": "Lamp", "age": 2}));
+//! assert_eq!(violations.len(), 1);
+//! assert_eq!(violations[0].pointer, "/price");
+//! assert_eq!(violations[0].kind, ViolationKind::Required);
+//!
+//! // Every violation is reported at once
+//! assert_eq!(validate::<Product>(&json!({"name": 1, "pricee": 12.5, "age": "two"})).len(), 4);
+//! # }
+//! ```
+//!
+//! Through a provider, a failing response is reported with all of its violations:
+//!
+//! ```no_run
+//! # #[cfg(feature = "schema-validation")]
+//! # fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+//! # use secretary::Task;
+//! # use serde::{Deserialize, Serialize};
+//! #
+//! # #[derive(Task, Serialize, Deserialize, Debug)]
+//! # struct Product {
+//! #     #[task(instruction = "Extract the product name")]
+//! #     pub name: String,
+//! # }
+//! #
+//! use secretary::SecretaryError;
+//! use secretary::llm_providers::openai::OpenAILLM;
+//! use secretary::request::RequestOptions;
+//! use secretary::traits::GenerateData;
+//! use secretary::validation::SchemaValidation;
+//!
+//! let llm = OpenAILLM::new("https://api.openai.com/v1", "your-api-key", "gpt-4o-mini")?;
+//!
+//! // Keys the Task does not have can be let through
+//! let options = RequestOptions::default().with_schema_validation(SchemaValidation::AllowAdditionalProperties);
+//! match llm.generate_data_with_options(&Product::new(), "Desk lamp, 12.50", &vec![], &options) {
+//!     Ok(product) => println!("{:?}", product),
+//!     Err(error) => match error.downcast_ref::<SecretaryError>() {
+//!         Some(SecretaryError::SchemaViolation { violations, .. }) => {
+//!             for violation in violations {
+//!                 println!("{}: {:?}", violation.pointer, violation.kind);
+//!             }
+//!         }
+//!         _ => return Err(error),
+//!     },
+//! }
+//! # Ok(())
+//! # }
+//! # #[cfg(not(feature = "schema-validation"))]
+//! # fn main() {}
+//! ```
+
+use serde_json::Value;
+
+#[cfg(feature = "schema-validation")]
+use crate::{error::SecretaryError, leniency::LeniencyProfile, schema::json_schema, traits::Task};
+
+/// How a response is validated against the Task's JSON Schema.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SchemaValidation {
+    /// Any violation fails the request.
+    #[default]
+    Strict,
+    /// Responses whose only violations are keys the Task does not have are deserialized
+    /// anyway; any other violation fails the request.
+    AllowAdditionalProperties,
+}
+
+/// The category of a schema violation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ViolationKind {
+    /// A key the schema does not define.
+    AdditionalProperty,
+    /// A value of the wrong JSON type.
+    Type,
+    /// A required key that is missing.
+    Required,
+    /// Any other violation, e.g. of an `enum`.
+    Other,
+}
+
+/// A single violation of a JSON Schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaViolation {
+    /// The JSON pointer of the offending value, e.g. `/address/zip`. For additional and
+    /// missing keys, the pointer of the key itself.
+    pub pointer: String,
+    /// The category of the violation.
+    pub kind: ViolationKind,
+    /// The validator's description of the violation, which may quote the value.
+    pub message: String,
+}
+
+impl std::fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", display_pointer(&self.pointer), self.message)
+    }
+}
+
+impl SchemaViolation {
+    /// Describes the violation by its pointer and kind only, without the message.
+    pub fn redacted(&self) -> String {
+        let kind: &str = match self.kind {
+            ViolationKind::AdditionalProperty => "additional property",
+            ViolationKind::Type => "wrong type",
+            ViolationKind::Required => "missing required property",
+            ViolationKind::Other => "invalid value",
+        };
+        format!("{}: {}", display_pointer(&self.pointer), kind)
+    }
+}
+
+/// Shows the empty root pointer as `/`.
+fn display_pointer(pointer: &str) -> &str {
+    if pointer.is_empty() { "/" } else { pointer }
+}
+
+/// Forbids additional properties in every object schema that lists its properties.
+///
+/// `schema::json_schema` leaves additional properties open, so keys the Task does not have
+/// would pass unnoticed. Schemas that already set `additionalProperties`, such as those of
+/// maps, are kept as they are.
+///
+/// # Arguments
+///
+/// * `schema` - The JSON Schema to tighten
+///
+/// # Examples
+///
+/// ```rust
+/// use secretary::validation::strict_schema;
+/// use serde_json::json;
+///
+/// let schema = json!({
+///     "type": "object",
+///     "properties": {
+///         "tags": {"type": "object", "additionalProperties": {"type": "string"}},
+///         "owner": {"anyOf": [{"type": "object", "properties": {"name": {"type": "string"}}}, {"type": "null"}]}
+///     }
+/// });
+/// let strict = strict_schema(&schema);
+/// assert_eq!(strict["additionalProperties"], json!(false));
+/// assert_eq!(strict["properties"]["tags"]["additionalProperties"], json!({"type": "string"}));
+/// assert_eq!(strict["properties"]["owner"]["anyOf"][0]["additionalProperties"], json!(false));
+/// ```
+pub fn strict_schema(schema: &Value) -> Value {
+    let mut strict: Value = schema.clone();
+    forbid_additional_properties(&mut strict);
+    strict
+}
+
+fn forbid_additional_properties(schema: &mut Value) {
+    let Some(map) = schema.as_object_mut() else {
+        return;
+    };
+
+    if map.contains_key("properties") && !map.contains_key("additionalProperties") {
+        map.insert("additionalProperties".to_string(), Value::Bool(false));
+    }
+    for key in [
+        "properties",
+        "items",
+        "prefixItems",
+        "anyOf",
+        "additionalProperties",
+    ] {
+        match map.get_mut(key) {
+            Some(Value::Object(children)) if key == "properties" => {
+                children.values_mut().for_each(forbid_additional_properties);
+            }
+            Some(Value::Array(children)) => {
+                children.iter_mut().for_each(forbid_additional_properties);
+            }
+            Some(child) => forbid_additional_properties(child),
+            None => {}
+        }
+    }
+}
+
+/// Validates a JSON value against the strict JSON Schema of `T`.
+///
+/// # Arguments
+///
+/// * `value` - The parsed LLM output
+///
+/// # Returns
+///
+/// Every violation found ordered by pointer, empty when the value is valid
+#[cfg(feature = "schema-validation")]
+pub fn validate<T: Task>(value: &Value) -> Vec<SchemaViolation> {
+    validate_against(&strict_schema(&json_schema(&T::field_descriptors())), value)
+}
+
+/// Validates a JSON value against a JSON Schema.
+///
+/// # Arguments
+///
+/// * `schema` - The JSON Schema, used as given
+/// * `value` - The value to validate
+///
+/// # Returns
+///
+/// Every violation found ordered by pointer, empty when the value is valid. A schema that cannot be compiled
+/// is reported as a single violation at the root.
+#[cfg(feature = "schema-validation")]
+pub fn validate_against(schema: &Value, value: &Value) -> Vec<SchemaViolation> {
+    let validator = match jsonschema::validator_for(schema) {
+        Ok(validator) => validator,
+        Err(error) => {
+            return vec![SchemaViolation {
+                pointer: String::new(),
+                kind: ViolationKind::Other,
+                message: format!("The schema is invalid: {}", error),
+            }];
+        }
+    };
+
+    let mut violations: Vec<SchemaViolation> = Vec::new();
+    for error in validator.iter_errors(value) {
+        let pointer: String = error.instance_path().to_string();
+        let message: String = error.to_string();
+        match error.kind() {
+            jsonschema::error::ValidationErrorKind::AdditionalProperties { unexpected } => {
+                violations.extend(unexpected.iter().map(|name| SchemaViolation {
+                    pointer: child_pointer(&pointer, name),
+                    kind: ViolationKind::AdditionalProperty,
+                    message: format!("The key `{}` is not defined by the schema", name),
+                }));
+            }
+            jsonschema::error::ValidationErrorKind::Required { property } => {
+                violations.push(SchemaViolation {
+                    pointer: child_pointer(&pointer, property.as_str().unwrap_or_default()),
+                    kind: ViolationKind::Required,
+                    message,
+                });
+            }
+            jsonschema::error::ValidationErrorKind::Type { .. } => {
+                violations.push(SchemaViolation {
+                    pointer,
+                    kind: ViolationKind::Type,
+                    message,
+                });
+            }
+            _ => violations.push(SchemaViolation {
+                pointer,
+                kind: ViolationKind::Other,
+                message,
+            }),
+        }
+    }
+    violations.sort_by(|a, b| a.pointer.cmp(&b.pointer));
+
+    violations
+}
+
+/// Appends an escaped key to a JSON pointer.
+pub(crate) fn child_pointer(pointer: &str, name: &str) -> String {
+    format!("{}/{}", pointer, name.replace('~', "~0").replace('/', "~1"))
+}
+
+/// Validates LLM output for `T` before it is deserialized.
+///
+/// Content that is not JSON passes, so the parser reports it as usual. Leniency coercions
+/// are applied first, so values the parser would accept are not reported.
+///
+/// # Errors
+///
+/// Returns `SecretaryError::SchemaViolation` if the content violates the schema in a way
+/// `validation` does not allow.
+#[cfg(feature = "schema-validation")]
+pub(crate) fn check_content<T: Task>(
+    content: &str,
+    leniency: &LeniencyProfile,
+    validation: SchemaValidation,
+) -> Result<(), SecretaryError> {
+    let Ok(mut value) = serde_json::from_str::<Value>(content) else {
+        return Ok(());
+    };
+    leniency.apply::<T>(&mut value);
+
+    let violations: Vec<SchemaViolation> = validate::<T>(&value);
+    let allowed: bool = validation == SchemaValidation::AllowAdditionalProperties
+        && violations
+            .iter()
+            .all(|violation| violation.kind == ViolationKind::AdditionalProperty);
+    if violations.is_empty() || allowed {
+        return Ok(());
+    }
+
+    Err(SecretaryError::SchemaViolation {
+        violations,
+        raw_content: content.to_string(),
+    })
+}
@@ -0,0 +1,154 @@
+//! Tasks on enums of alternative document types.
+//!
+//! Mixed inputs, such as a folder of invoices, receipts and purchase orders, need the type of
+//! a document before its fields can be extracted. `#[derive(Task)]` on an enum whose variants
+//! each hold a Task does both in one extraction: the system prompt lists the variants by their
+//! tag, describes each with the prompt of its Task, and asks the model to pick exactly one and
+//! answer with its tag and fields. The enum must be internally tagged with
+//! `#[serde(tag = "...")]`, and the answer is deserialized by serde like any tagged enum, so
+//! the tags are the variant names under serde's `rename` and `rename_all`.
+//!
+//! `#[task(instruction = "...")]` on a variant says when to pick it. Only `preamble` and
+//! `postamble` apply to the enum itself; the other attributes go on the variants' Tasks.
+//!
+//! The field descriptors of an enum are the tag key followed by the fields of every variant,
+//! made optional, since each answer only has those of its variant; a field in several
+//! variants is listed once, as `Any` when their types differ. Distributed generation, which
+//! extracts each field with a request of its own, cannot pick a variant and fails with
+//! `SecretaryError::UnsupportedGeneration`.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::Task;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug, PartialEq)]
+//! struct Invoice {
+//!     #[task(instruction = "Extract the invoice number")]
+//!     pub invoice_number: String,
+//! }
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug, PartialEq)]
+//! struct Receipt {
+//!     #[task(instruction = "Extract the merchant name")]
+//!     pub merchant: String,
+//! }
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug, PartialEq)]
+//! #[serde(tag = "doc_type", rename_all = "snake_case")]
+//! enum Document {
+//!     #[task(instruction = "A bill from a supplier that asks for payment")]
+//!     Invoice(Invoice),
+//!     Receipt(Receipt),
+//! }
+//!
+//! let prompt = Document::new().get_system_prompt();
+//! assert!(prompt.contains("- \"invoice\": A bill from a supplier that asks for payment"));
+//! assert!(prompt.contains("merchant: Extract the merchant name"));
+//!
+//! let document: Document =
+//!     serde_json::from_str(r#"{"doc_type": "receipt", "merchant": "Corner Shop"}"#).unwrap();
+//! assert_eq!(document, Document::Receipt(Receipt { merchant: "Corner Shop".to_string() }));
+//!
+//! let descriptors = Document::field_descriptors();
+//! assert_eq!(descriptors[0].name, "doc_type");
+//! assert!(descriptors[1].optional);
+//! ```
+
+use crate::schema::{FieldDescriptor, Importance, JsonType};
+
+/// The part of an enum Task's system prompt describing one variant.
+///
+/// Generated by `#[derive(Task)]` for every variant of an enum.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariantPrompt {
+    /// The value of the tag key that selects the variant.
+    pub tag: &'static str,
+    /// When to pick the variant, from `#[task(instruction = "...")]`.
+    pub instruction: Option<&'static str>,
+    /// The system prompt of the variant's Task.
+    pub prompt: String,
+}
+
+/// Renders the system prompt of an enum Task: the list of variants to pick from, followed by
+/// the prompt of each variant's Task.
+///
+/// # Arguments
+///
+/// * `tag_key` - The key holding the tag, from `#[serde(tag = "...")]`
+/// * `variants` - The variants, in declaration order
+pub fn render_variants_prompt(tag_key: &str, variants: &[VariantPrompt]) -> String {
+    let mut prompt: String = format!(
+        "The text is one of the following {} types. Pick exactly one of them, and extract the fields of that type only:\n",
+        variants.len()
+    );
+    for variant in variants {
+        match variant.instruction {
+            Some(instruction) => {
+                prompt.push_str(&format!("- \"{}\": {}\n", variant.tag, instruction))
+            }
+            None => prompt.push_str(&format!("- \"{}\"\n", variant.tag)),
+        }
+    }
+    prompt.push_str(&format!(
+        "\nAnswer with one JSON object holding the key \"{}\", set to the tag of the type you picked, and the fields of that type as described in its section.\n",
+        tag_key
+    ));
+
+    for variant in variants {
+        prompt.push_str(&format!(
+            "\n--- \"{}\" Type, with \"{}\": \"{}\" ---\n{}\n--- End of \"{}\" Type ---\n",
+            variant.tag,
+            tag_key,
+            variant.tag,
+            variant.prompt.trim_end(),
+            variant.tag
+        ));
+    }
+
+    prompt
+}
+
+/// Returns the field descriptors of an enum Task: the tag key, then the fields of every
+/// variant, made optional.
+///
+/// A field in several variants is listed once, with the type `Any` when the variants disagree
+/// on it. Fields are never critical, since a variant that is not picked has none of them.
+///
+/// # Arguments
+///
+/// * `tag_key` - The key holding the tag, from `#[serde(tag = "...")]`
+/// * `variants` - The field descriptors of each variant's Task, in declaration order
+pub fn variant_field_descriptors(
+    tag_key: &str,
+    variants: Vec<Vec<FieldDescriptor>>,
+) -> Vec<FieldDescriptor> {
+    let mut descriptors: Vec<FieldDescriptor> = vec![FieldDescriptor {
+        name: tag_key.to_string(),
+        rust_type: "String".to_string(),
+        json_type: JsonType::String,
+        instruction: "The tag of the type of the text".to_string(),
+        ..FieldDescriptor::default()
+    }];
+
+    for field in variants.into_iter().flatten() {
+        match descriptors
+            .iter_mut()
+            .find(|existing| existing.key() == field.key())
+        {
+            Some(existing) => {
+                if existing.json_type != field.json_type {
+                    existing.json_type = JsonType::Any;
+                }
+            }
+            None => descriptors.push(FieldDescriptor {
+                optional: true,
+                importance: Importance::Normal,
+                ..field
+            }),
+        }
+    }
+
+    descriptors
+}
@@ -0,0 +1,471 @@
+//! Closed vocabularies for text fields.
+//!
+//! A `String` or `Option<String>` field with `#[task(one_of = "high, mid, low")]` only takes
+//! one of the listed values. The instruction of the field lists them, in JSON mode and in
+//! the field requests of distributed mode alike, and the model's answer is normalized to the
+//! closest allowed value before it is deserialized. `match_allowed_value` tries, in order:
+//!
+//! 1. an exact match, ignoring case, surrounding whitespace, quotes and trailing
+//!    punctuation, so `"LOW."` is `low`
+//! 2. a prefix match, the answer starting with an allowed value or the other way around, so
+//!    `mid-level` is `mid`
+//! 3. a substring match, so `very high` is `high`
+//! 4. an edit distance of at most one for values of up to four characters and two for
+//!    longer ones, so `meduim` is `medium`
+//!
+//! The prefix and substring stages first look for values within the answer, keeping the
+//! longest, and then for values that hold the answer, such as `pos` for `positive`. The
+//! edit distance keeps the closest values. When several values remain at a stage, the answer
+//! is ambiguous and the next stages are not tried. An answer that is ambiguous or matches nothing is handled by the `OneOfPolicy` of
+//! the provider, set with `with_one_of_policy`, or of the call, set with
+//! `RequestOptions::with_one_of_policy`:
+//!
+//! - `OneOfPolicy::Error` fails with `SecretaryError::ValueNotAllowed`
+//! - `OneOfPolicy::Default` sets the field's `default_value`, `null` for an optional field
+//!   without one, and the first allowed value otherwise
+//!
+//! Every change is recorded as a `NormalizedValue`, which `generate_data_adaptive` reports in
+//! `GenerationMetadata::normalized_values`. `null` is left alone, and so are values that are
+//! not strings, which fail deserialization as before.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use secretary::Task;
+//! use secretary::vocabulary::{
+//!     AllowedValueMatch, MatchKind, NormalizedValue, OneOfPolicy, match_allowed_value,
+//! };
+//! use serde::{Deserialize, Serialize};
+//! use serde_json::json;
+//!
+//! #[derive(Task, Serialize, Deserialize, Debug)]
+//! struct Review {
+//!     #[task(instruction = "Extract the reviewer's name")]
+//!     pub reviewer: String,
+//!     #[task(instruction = "Rate the sentiment", one_of = "high, mid, low")]
+//!     pub sentiment: String,
+//! }
+//!
+//! let descriptors = Review::field_descriptors();
+//! assert_eq!(descriptors[1].one_of, vec!["high", "mid", "low"]);
+//! assert!(descriptors[1].instruction.ends_with(r#"one of "high", "mid", "low"."#));
+//!
+//! let allowed: Vec<String> = descriptors[1].one_of.clone();
+//! assert_eq!(
+//!     match_allowed_value("Mid-level", &allowed),
+//!     AllowedValueMatch::Matched {
+//!         value: "mid".to_string(),
+//!         kind: MatchKind::Prefix,
+//!     }
+//! );
+//!
+//! let (content, normalized) = OneOfPolicy::Error
+//!     .normalize_content(&descriptors, r#"{"reviewer": "Ann", "sentiment": "LOW."}"#.to_string())
+//!     .unwrap();
+//! assert_eq!(content, r#"{"reviewer":"Ann","sentiment":"low"}"#);
+//! assert_eq!(
+//!     normalized,
+//!     vec![NormalizedValue {
+//!         path: "sentiment".to_string(),
+//!         original: "LOW.".to_string(),
+//!         normalized: json!("low"),
+//!     }]
+//! );
+//!
+//! // Nothing is close enough to "furious"
+//! let content = r#"{"reviewer": "Ann", "sentiment": "furious"}"#.to_string();
+//! assert!(OneOfPolicy::Error.normalize_content(&descriptors, content.clone()).is_err());
+//! let (content, _) = OneOfPolicy::Default.normalize_content(&descriptors, content).unwrap();
+//! assert!(content.contains(r#""sentiment":"high""#));
+//! ```
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    SecretaryError,
+    schema::{FieldDescriptor, FieldKind},
+    utilities::{find_field_descriptor, parse_described_field_value},
+};
+
+/// What happens to an answer that is not close to any allowed value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum OneOfPolicy {
+    /// Fail with `SecretaryError::ValueNotAllowed`.
+    #[default]
+    Error,
+    /// Use the field's default, see the module documentation.
+    Default,
+}
+
+/// How an answer was matched to an allowed value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MatchKind {
+    /// The answer is the value, ignoring case and punctuation.
+    Exact,
+    /// The answer starts with the value, or the value with the answer.
+    Prefix,
+    /// The answer contains the value, or the value the answer.
+    Substring,
+    /// The answer is a few edits away from the value.
+    EditDistance,
+}
+
+/// The result of `match_allowed_value`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AllowedValueMatch {
+    /// The answer matches one value.
+    Matched {
+        /// The allowed value, as it is listed.
+        value: String,
+        /// The stage that matched it.
+        kind: MatchKind,
+    },
+    /// The answer matches several values equally well.
+    Ambiguous {
+        /// The values, in the order they are listed.
+        candidates: Vec<String>,
+    },
+    /// The answer is not close to any value.
+    NoMatch,
+}
+
+/// An answer replaced by an allowed value.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NormalizedValue {
+    /// The path of the field, e.g. `reviews[1].sentiment`.
+    pub path: String,
+    /// The model's answer.
+    pub original: String,
+    /// The value the field was set to, `null` when an optional field was defaulted.
+    pub normalized: Value,
+}
+
+impl OneOfPolicy {
+    /// Normalizes the values of the fields with a `one_of` in the JSON text the model
+    /// answered with.
+    ///
+    /// Content that is not JSON is returned as it is, to fail parsing later.
+    ///
+    /// # Arguments
+    ///
+    /// * `fields` - The descriptors of the fields of the Task
+    /// * `content` - The JSON returned by the model
+    ///
+    /// # Returns
+    ///
+    /// The content with allowed values only, and the values that were replaced
+    ///
+    /// # Errors
+    ///
+    /// Returns `SecretaryError::ValueNotAllowed` under `OneOfPolicy::Error` for the first
+    /// answer that does not match exactly one allowed value.
+    pub fn normalize_content(
+        self,
+        fields: &[FieldDescriptor],
+        content: String,
+    ) -> Result<(String, Vec<NormalizedValue>), SecretaryError> {
+        if !has_one_of(fields) {
+            return Ok((content, Vec::new()));
+        }
+
+        let mut value: Value = match serde_json::from_str(&content) {
+            Ok(value) => value,
+            Err(_) => return Ok((content, Vec::new())),
+        };
+        let normalized: Vec<NormalizedValue> = normalize_one_of(fields, &mut value, self)?;
+        if normalized.is_empty() {
+            return Ok((content, normalized));
+        }
+
+        Ok((value.to_string(), normalized))
+    }
+}
+
+/// Returns whether any of the fields, nested ones included, has a `one_of`.
+pub fn has_one_of(fields: &[FieldDescriptor]) -> bool {
+    fields
+        .iter()
+        .any(|field| !field.one_of.is_empty() || has_one_of(&field.children))
+}
+
+/// Finds the allowed value closest to an answer, see the module documentation.
+///
+/// # Arguments
+///
+/// * `answer` - The model's answer
+/// * `allowed` - The allowed values
+///
+/// # Returns
+///
+/// The allowed value and how it was matched, the values it is ambiguous between, or
+/// `AllowedValueMatch::NoMatch`
+pub fn match_allowed_value(answer: &str, allowed: &[String]) -> AllowedValueMatch {
+    let answer: String = clean(answer);
+    if answer.is_empty() {
+        return AllowedValueMatch::NoMatch;
+    }
+    let cleaned: Vec<String> = allowed.iter().map(|value| clean(value)).collect();
+
+    if let Some(index) = cleaned.iter().position(|value| *value == answer) {
+        return AllowedValueMatch::Matched {
+            value: allowed[index].clone(),
+            kind: MatchKind::Exact,
+        };
+    }
+
+    // Each stage checks whether the answer holds a value, then whether a value holds the
+    // answer, such as an abbreviation
+    for kind in [MatchKind::Prefix, MatchKind::Substring] {
+        let holds = |outer: &str, inner: &str| match kind {
+            MatchKind::Prefix => outer.starts_with(inner),
+            _ => outer.contains(inner),
+        };
+        let in_answer: Vec<usize> = (0..allowed.len())
+            .filter(|&index| !cleaned[index].is_empty() && holds(&answer, &cleaned[index]))
+            .collect();
+        // The longest value in the answer is the most specific one
+        let longest: usize = in_answer
+            .iter()
+            .map(|&index| cleaned[index].chars().count())
+            .max()
+            .unwrap_or(0);
+        let best: Vec<usize> = in_answer
+            .into_iter()
+            .filter(|&index| cleaned[index].chars().count() == longest)
+            .collect();
+        if let Some(result) = pick(allowed, best, kind) {
+            return result;
+        }
+
+        let holding_answer: Vec<usize> = (0..allowed.len())
+            .filter(|&index| holds(&cleaned[index], &answer))
+            .collect();
+        if let Some(result) = pick(allowed, holding_answer, kind) {
+            return result;
+        }
+    }
+
+    let distances: Vec<Option<usize>> = cleaned
+        .iter()
+        .map(|value| {
+            let distance: usize = edit_distance(&answer, value);
+            (distance <= max_edits(value)).then_some(distance)
+        })
+        .collect();
+    let closest: Option<usize> = distances.iter().flatten().min().copied();
+    let best: Vec<usize> = (0..allowed.len())
+        .filter(|&index| closest.is_some() && distances[index] == closest)
+        .collect();
+
+    pick(allowed, best, MatchKind::EditDistance).unwrap_or(AllowedValueMatch::NoMatch)
+}
+
+/// Normalizes the values of the fields with a `one_of` in the JSON of a Task, recursively for
+/// nested Task fields.
+///
+/// # Arguments
+///
+/// * `fields` - The descriptors of the fields of the Task
+/// * `value` - The JSON of the Task
+/// * `policy` - What happens to answers without a match
+///
+/// # Returns
+///
+/// The values that were replaced
+///
+/// # Errors
+///
+/// Returns `SecretaryError::ValueNotAllowed` under `OneOfPolicy::Error` for the first
+/// answer that does not match exactly one allowed value.
+pub fn normalize_one_of(
+    fields: &[FieldDescriptor],
+    value: &mut Value,
+    policy: OneOfPolicy,
+) -> Result<Vec<NormalizedValue>, SecretaryError> {
+    let mut normalized: Vec<NormalizedValue> = Vec::new();
+    normalize_at(fields, value, "", policy, &mut normalized)?;
+    Ok(normalized)
+}
+
+/// Normalizes the field results of distributed generation in place, replacing the content
+/// of every field with a `one_of` that is not an allowed value.
+pub(crate) fn normalize_field_results(
+    fields: &[FieldDescriptor],
+    results: &mut [(String, String)],
+    policy: OneOfPolicy,
+) -> Result<Vec<NormalizedValue>, SecretaryError> {
+    let mut normalized: Vec<NormalizedValue> = Vec::new();
+    if !has_one_of(fields) {
+        return Ok(normalized);
+    }
+
+    for (field_path, content) in results.iter_mut() {
+        let Some(field) = find_field_descriptor(fields, field_path)
+            .filter(|field| field.kind == FieldKind::Normal && !field.one_of.is_empty())
+        else {
+            continue;
+        };
+
+        let mut value: Value = parse_described_field_value(content, field_path, fields);
+        if let Some(change) = normalize_value(field, &mut value, field_path, policy)? {
+            normalized.push(change);
+            *content = value.to_string();
+        }
+    }
+
+    Ok(normalized)
+}
+
+fn normalize_at(
+    fields: &[FieldDescriptor],
+    value: &mut Value,
+    path: &str,
+    policy: OneOfPolicy,
+    normalized: &mut Vec<NormalizedValue>,
+) -> Result<(), SecretaryError> {
+    let Some(map) = value.as_object_mut() else {
+        return Ok(());
+    };
+
+    for field in fields {
+        let Some(field_value) = map.get_mut(&field.name) else {
+            continue;
+        };
+        let field_path: String = child_path(path, &field.name);
+        match field.kind {
+            FieldKind::Normal => {
+                if let Some(change) = normalize_value(field, field_value, &field_path, policy)? {
+                    normalized.push(change);
+                }
+            }
+            FieldKind::Task | FieldKind::OptionTask => {
+                normalize_at(
+                    &field.children,
+                    field_value,
+                    &field_path,
+                    policy,
+                    normalized,
+                )?;
+            }
+            FieldKind::VecTask => {
+                if let Some(items) = field_value.as_array_mut() {
+                    for (index, item) in items.iter_mut().enumerate() {
+                        let item_path: String = format!("{}[{}]", field_path, index);
+                        normalize_at(&field.children, item, &item_path, policy, normalized)?;
+                    }
+                }
+            }
+            FieldKind::HashMapTask | FieldKind::BTreeMapTask => {
+                if let Some(entries) = field_value.as_object_mut() {
+                    for (key, entry) in entries.iter_mut() {
+                        let entry_path: String = format!("{}.{}", field_path, key);
+                        normalize_at(&field.children, entry, &entry_path, policy, normalized)?;
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Replaces the string value of a field with a `one_of` by its allowed value.
+fn normalize_value(
+    field: &FieldDescriptor,
+    value: &mut Value,
+    path: &str,
+    policy: OneOfPolicy,
+) -> Result<Option<NormalizedValue>, SecretaryError> {
+    if field.one_of.is_empty() {
+        return Ok(None);
+    }
+    let Value::String(answer) = value else {
+        return Ok(None);
+    };
+
+    let replacement: Value = match match_allowed_value(answer, &field.one_of) {
+        AllowedValueMatch::Matched { value: allowed, .. } if allowed == *answer => {
+            return Ok(None);
+        }
+        AllowedValueMatch::Matched { value: allowed, .. } => Value::String(allowed),
+        _ if policy == OneOfPolicy::Default => default_for(field),
+        _ => {
+            return Err(SecretaryError::ValueNotAllowed {
+                field_path: path.to_string(),
+                value: answer.clone(),
+                allowed: field.one_of.clone(),
+            });
+        }
+    };
+
+    let original: String = answer.clone();
+    *value = replacement.clone();
+    Ok(Some(NormalizedValue {
+        path: path.to_string(),
+        original,
+        normalized: replacement,
+    }))
+}
+
+/// The value an unmatched answer is replaced with under `OneOfPolicy::Default`.
+fn default_for(field: &FieldDescriptor) -> Value {
+    match &field.default_value {
+        Some(default) => default.clone(),
+        None if field.optional => Value::Null,
+        None => Value::String(field.one_of[0].clone()),
+    }
+}
+
+/// Returns the match of the only index, `Ambiguous` for several, or `None` for none.
+fn pick(allowed: &[String], indices: Vec<usize>, kind: MatchKind) -> Option<AllowedValueMatch> {
+    match indices.as_slice() {
+        [] => None,
+        [index] => Some(AllowedValueMatch::Matched {
+            value: allowed[*index].clone(),
+            kind,
+        }),
+        _ => Some(AllowedValueMatch::Ambiguous {
+            candidates: indices
+                .into_iter()
+                .map(|index| allowed[index].clone())
+                .collect(),
+        }),
+    }
+}
+
+/// Lowercases an answer and removes surrounding whitespace, quotes and punctuation.
+fn clean(text: &str) -> String {
+    text.trim()
+        .trim_matches(|c: char| c.is_whitespace() || "\"'`*.,;:!?".contains(c))
+        .to_lowercase()
+}
+
+/// The most edits an answer may be away from an allowed value.
+fn max_edits(value: &str) -> usize {
+    if value.chars().count() <= 4 { 1 } else { 2 }
+}
+
+/// The Levenshtein distance between two strings, counted in characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current: Vec<usize> = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution: usize = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+fn child_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
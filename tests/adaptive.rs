@@ -0,0 +1,96 @@
+//! Critical fields are extracted again with a focused request of their own, and partial
+//! extraction tolerates gaps in every field but the critical ones.
+
+#![allow(clippy::needless_borrows_for_generic_args)]
+
+mod support;
+
+use secretary::SecretaryError;
+use secretary::Task;
+use secretary::traits::GenerateData;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use support::fixtures::{field_result, success};
+use support::{MockServer, RecordedRequest, secretary_error};
+
+#[derive(Task, Serialize, Deserialize, Debug)]
+struct Invoice {
+    #[task(instruction = "Extract the invoice total", importance = "critical")]
+    pub total: f64,
+    #[task(instruction = "Extract the customer name")]
+    pub customer: String,
+    #[task(instruction = "Extract any notes", importance = "low")]
+    pub notes: Option<String>,
+}
+
+/// Returns whether a request asks for a single field.
+fn is_field_request(request: &RecordedRequest) -> bool {
+    request.prompt().contains("<result></result>")
+}
+
+#[test]
+fn critical_fields_are_extracted_again() {
+    // The single request misreads the total, which its own request gets right
+    let server = MockServer::start(|request| {
+        if is_field_request(request) {
+            field_result("1250.5")
+        } else {
+            success(&json!({"total": 125.0, "customer": "ACME", "notes": null}).to_string())
+        }
+    });
+
+    let result = server
+        .llm()
+        .generate_data_adaptive(&Invoice::new(), "Invoice for ACME, total 1,250.50", &vec![])
+        .unwrap();
+
+    assert_eq!(result.data.total, 1250.5);
+    assert_eq!(result.data.customer, "ACME");
+    assert_eq!(result.metadata.per_field_requests, vec!["total"]);
+
+    // One JSON request for every field, and one request for the critical field only
+    let requests: Vec<RecordedRequest> = server.requests();
+    assert_eq!(requests.len(), 2);
+    let field_requests: Vec<String> = requests
+        .iter()
+        .filter(|request| is_field_request(request))
+        .map(RecordedRequest::prompt)
+        .collect();
+    assert_eq!(field_requests.len(), 1);
+    assert!(field_requests[0].contains("Extract the invoice total"));
+    assert!(!field_requests[0].contains("Extract the customer name"));
+    assert!(!field_requests[0].contains("Extract any notes"));
+}
+
+#[test]
+fn partial_extraction_tolerates_missing_low_fields() {
+    let server = MockServer::always(success(
+        &json!({"total": 99.0, "customer": "ACME"}).to_string(),
+    ));
+
+    let partial = server
+        .llm()
+        .generate_partial_data(&Invoice::new(), "Invoice for ACME", &vec![])
+        .unwrap();
+
+    assert_eq!(partial.data.total, 99.0);
+    assert_eq!(partial.data.notes, None);
+}
+
+#[test]
+fn partial_extraction_fails_on_a_missing_critical_field() {
+    let server = MockServer::always(success(
+        &json!({"total": "unknown", "customer": "ACME", "notes": "Net 30"}).to_string(),
+    ));
+
+    let error = server
+        .llm()
+        .generate_partial_data(&Invoice::new(), "Invoice for ACME", &vec![])
+        .unwrap_err();
+
+    match secretary_error(&error) {
+        SecretaryError::CriticalFieldsMissing { fields } => assert_eq!(fields, &vec!["total"]),
+        other => panic!("unexpected error: {}", other),
+    }
+}
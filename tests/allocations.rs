@@ -0,0 +1,191 @@
+//! Building the requests of distributed generation, rendering the system prompt and handling a
+//! response stay within 60% of the allocations they made before, counted by an allocator that
+//! counts the allocations of its thread.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use secretary::Task;
+use secretary::decoding::DecodingPolicy;
+use secretary::distributed::FieldPrompt;
+use secretary::message::Message;
+use secretary::reasoning::strip_inline_reasoning;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+fn count_allocation() {
+    let _ = ALLOCATIONS.try_with(|allocations| allocations.set(allocations.get() + 1));
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count_allocation();
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        count_allocation();
+        unsafe { System.alloc_zeroed(layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count_allocation();
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Returns what a closure returns and the number of allocations it made on this thread.
+fn counted<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before: usize = ALLOCATIONS.with(Cell::get);
+    let output: T = f();
+    (output, ALLOCATIONS.with(Cell::get) - before)
+}
+
+/// The most allocations one call may make: the field requests of distributed generation, the
+/// derived system prompt, and the handling of an answer without reasoning or escapes.
+///
+/// Each bound is 60% of the count of the same call before the allocation pass, rounded down.
+/// Those counts were 151, 20 and 30, taken with this allocator on the `Listing`, target,
+/// instructions and response below. A change that allocates more than that
+/// undoes the pass and fails here, while any further saving passes without editing the bounds.
+const MAX_FIELD_REQUESTS: usize = 90;
+const MAX_SYSTEM_PROMPT: usize = 12;
+const MAX_RESPONSE_HANDLING: usize = 18;
+
+const TARGET: &str = "Flat at 12 Harbour Road, 3 rooms, 1450 a month, pets welcome.";
+
+/// Asserts that a call made at most `bound` allocations.
+fn assert_at_most(bound: usize, allocations: usize) {
+    assert!(
+        allocations <= bound,
+        "{} allocations, at most {} expected",
+        allocations,
+        bound
+    );
+}
+
+#[derive(Task, Serialize, Deserialize, Debug)]
+struct Listing {
+    #[task(instruction = "Extract the address")]
+    pub address: String,
+    #[task(instruction = "Extract the rent per month")]
+    pub rent: u32,
+    #[task(instruction = "Extract the number of rooms")]
+    pub rooms: u8,
+    #[task(instruction = "Extract whether pets are allowed")]
+    pub pets: bool,
+}
+
+fn instructions() -> Vec<String> {
+    [
+        "Answer in English",
+        "Ignore the advertisements",
+        "Prefer the figures of the latest update",
+        "Give amounts without the currency",
+        "Leave out the agent's details",
+        "Read abbreviations as written out",
+        "Ignore the comments of visitors",
+        "Use the landlord's figures",
+    ]
+    .iter()
+    .map(|instruction| instruction.to_string())
+    .collect()
+}
+
+#[test]
+fn distributed_requests_allocate_less() {
+    let task: Listing = Listing::new();
+    let additional_instructions: Vec<String> = instructions();
+
+    let (requests, allocations) =
+        counted(|| task.make_distributed_generation_requests(TARGET, &additional_instructions));
+
+    assert_eq!(requests.len(), 4);
+    for (field_prompt, message) in &requests {
+        let (field_prompt, message): (&FieldPrompt, &Message) = (field_prompt, message);
+        let content: &str = message.content.as_str();
+        assert!(content.starts_with(&field_prompt.prompt));
+        for instruction in &additional_instructions {
+            assert!(content.contains(&format!("- {}\n", instruction)));
+        }
+        assert!(content.ends_with(&format!(
+            "\nThis is the basis for generating the result:\n{}",
+            TARGET
+        )));
+    }
+    assert_at_most(MAX_FIELD_REQUESTS, allocations);
+}
+
+#[test]
+fn the_system_prompt_allocates_less() {
+    let task: Listing = Listing::new();
+
+    let (prompt, allocations) = counted(|| task.get_system_prompt());
+
+    assert!(prompt.contains("address: Extract the address"));
+    assert!(prompt.contains("pets: Extract whether pets are allowed"));
+    assert_at_most(MAX_SYSTEM_PROMPT, allocations);
+}
+
+#[test]
+fn response_handling_allocates_less() {
+    let content: String = json!({
+        "address": "12 Harbour Road",
+        "rent": 1450,
+        "rooms": 3,
+        "pets": true
+    })
+    .to_string();
+    let api_response: String = json!({
+        "id": "chatcmpl-allocations",
+        "object": "chat.completion",
+        "model": "test-model",
+        "choices": [{
+            "index": 0,
+            "message": {"role": "assistant", "content": content},
+            "finish_reason": "stop"
+        }],
+        "usage": {"prompt_tokens": 50, "completion_tokens": 20, "total_tokens": 70}
+    })
+    .to_string();
+
+    let copy: String = content.clone();
+    let (cleaned, allocations) = counted(|| {
+        let content: String = strip_inline_reasoning(copy, &api_response);
+        DecodingPolicy::Repair.repair_content(content).0
+    });
+
+    assert_eq!(cleaned, content);
+    assert_at_most(MAX_RESPONSE_HANDLING, allocations);
+}
+
+#[test]
+fn answers_with_reasoning_or_escapes_are_still_cleaned() {
+    let api_response: String = json!({
+        "choices": [{"message": {"role": "assistant", "content": ""}}]
+    })
+    .to_string();
+    let content: String =
+        "<think>\nThe rent is 1450.\n</think>\n{\"address\": \"C:\\Temp\"}\n".to_string();
+
+    let cleaned: String = strip_inline_reasoning(content, &api_response);
+    let (repaired, repairs) = DecodingPolicy::Repair.repair_content(cleaned);
+
+    let value: Value = serde_json::from_str(&repaired).unwrap();
+    assert_eq!(value["address"], "C:\u{FFFD}emp");
+    assert!(repaired.starts_with("{\"address\""));
+    assert_eq!(repairs, 1);
+}
@@ -0,0 +1,131 @@
+//! Tasks are assembled from the text of each of their fields.
+
+mod support;
+
+use secretary::SecretaryError;
+use secretary::Task;
+use secretary::assembly::assemble_from_fields;
+use serde::{Deserialize, Serialize};
+
+use support::{Person, ada};
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Geo {
+    #[task(instruction = "Extract the latitude")]
+    pub lat: f64,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Address {
+    #[task(instruction = "Extract the city")]
+    pub city: String,
+    pub geo: Geo,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Order {
+    #[task(instruction = "Extract the customer")]
+    pub customer: String,
+    #[task(instruction = "Extract the quantity")]
+    pub quantity: u32,
+    #[task(instruction = "Extract the discount")]
+    pub discount: f64,
+    #[task(instruction = "Is the order paid")]
+    pub paid: bool,
+    #[task(instruction = "Extract the coupon, if any")]
+    pub coupon: Option<String>,
+    #[task(instruction = "List the items")]
+    pub items: Vec<String>,
+    pub address: Address,
+}
+
+/// The answers for an order, as a distributed extraction returns them.
+const ORDER_FIELDS: [(&str, &str); 8] = [
+    ("customer", " Ada "),
+    ("quantity", "1,200"),
+    ("discount", "15%"),
+    ("paid", "TRUE"),
+    ("coupon", "None"),
+    ("items", r#"["tea", "milk"]"#),
+    ("address.city", "London"),
+    ("address.geo.lat", "51.5"),
+];
+
+fn fields(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+    pairs
+        .iter()
+        .map(|(path, content)| (path.to_string(), content.to_string()))
+        .collect()
+}
+
+fn order() -> Order {
+    Order {
+        customer: "Ada".to_string(),
+        quantity: 1200,
+        discount: 0.15,
+        paid: true,
+        coupon: None,
+        items: vec!["tea".to_string(), "milk".to_string()],
+        address: Address {
+            city: "London".to_string(),
+            geo: Geo { lat: 51.5 },
+        },
+    }
+}
+
+#[test]
+fn fields_are_parsed_and_nested() {
+    let assembled: Order = assemble_from_fields(fields(&ORDER_FIELDS)).unwrap();
+    assert_eq!(assembled, order());
+}
+
+#[test]
+fn values_that_do_not_fit_are_errors() {
+    let mut pairs: Vec<(&str, &str)> = ORDER_FIELDS.to_vec();
+    pairs[1] = ("quantity", "a dozen");
+    pairs[7] = ("address.geo.lat", "north");
+
+    match assemble_from_fields::<Order>(fields(&pairs)).unwrap_err() {
+        SecretaryError::FieldDeserializationError(error) => {
+            assert_eq!(error.failed_fields, vec!["address.geo.lat", "quantity"]);
+            assert!(error.successful_fields.contains(&"customer".to_string()));
+            assert!(
+                error
+                    .successful_fields
+                    .contains(&"address.city".to_string())
+            );
+            assert_eq!(error.raw_field_contents["quantity"], "a dozen");
+            assert_eq!(error.raw_field_contents["address.geo.lat"], "north");
+            assert_eq!(error.raw_field_contents.len(), ORDER_FIELDS.len());
+        }
+        other => panic!("unexpected error: {}", other),
+    }
+}
+
+#[test]
+fn missing_required_fields_fall_back_to_the_default() {
+    let person: Person = assemble_from_fields(fields(&[("name", "Ada")])).unwrap();
+    assert_eq!(person, Person::default());
+}
+
+#[test]
+fn later_fields_overwrite_earlier_ones() {
+    let person: Person =
+        assemble_from_fields(fields(&[("name", "Ada"), ("age", "35"), ("age", "36")])).unwrap();
+    assert_eq!(person, ada());
+}
+
+#[test]
+#[allow(deprecated)]
+fn the_macro_delegates_to_the_function() {
+    let person: Result<Person, SecretaryError> =
+        secretary::generate_from_tuples!(Person, fields(&[("name", "Ada"), ("age", "36")]));
+    assert_eq!(person.unwrap(), ada());
+
+    let error: Result<Person, SecretaryError> =
+        secretary::generate_from_tuples!(Person, fields(&[("name", "Ada"), ("age", "old")]));
+    assert!(matches!(
+        error,
+        Err(SecretaryError::FieldDeserializationError(_))
+    ));
+}
@@ -0,0 +1,335 @@
+//! The asynchronous generate methods against the canonical response shapes.
+
+#![allow(clippy::needless_borrows_for_generic_args)]
+
+mod support;
+
+use secretary::adaptive::PromptStrategy;
+use secretary::chunking::{ChunkOptions, MergePolicy};
+use secretary::provenance::QuoteMatch;
+use secretary::request::RequestOptions;
+use secretary::review::Either;
+use secretary::traits::AsyncGenerateData;
+use serde_json::json;
+
+use support::fixtures::{
+    content_filter, empty_choices, error_body, fenced_json, field_result, success, think_blocks,
+    truncated,
+};
+use support::{
+    ADA_JSON, MockServer, Person, TARGET, ada, assert_age_failed, assert_malformed_json,
+    assert_no_response, fields_server,
+};
+
+#[tokio::test]
+async fn async_generate_data_requests_json_mode() {
+    let server = MockServer::always(success(ADA_JSON));
+
+    let person: Person = server
+        .llm()
+        .async_generate_data(&Person::new(), TARGET, &vec![])
+        .await
+        .unwrap();
+
+    assert_eq!(person, ada());
+    let requests = server.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].path, "/chat/completions");
+    assert!(requests[0].is_json_mode());
+}
+
+#[tokio::test]
+async fn async_generate_data_reports_failures() {
+    for response in [empty_choices(), error_body(), content_filter()] {
+        let server = MockServer::always(response);
+        assert_no_response(
+            server
+                .llm()
+                .async_generate_data(&Person::new(), TARGET, &vec![])
+                .await,
+        );
+    }
+
+    for response in [truncated(), think_blocks(ADA_JSON), fenced_json(ADA_JSON)] {
+        let server = MockServer::always(response);
+        assert_malformed_json(
+            server
+                .llm()
+                .async_generate_data(&Person::new(), TARGET, &vec![])
+                .await,
+        );
+    }
+}
+
+#[tokio::test]
+async fn async_generate_data_with_options_applies_the_options() {
+    let server = MockServer::always(success(ADA_JSON));
+    let options = RequestOptions::default().with_temperature(0.2);
+
+    let person: Person = server
+        .llm()
+        .async_generate_data_with_options(&Person::new(), TARGET, &vec![], &options)
+        .await
+        .unwrap();
+
+    assert_eq!(person, ada());
+    assert_eq!(server.requests()[0].body["temperature"], json!(0.2));
+}
+
+#[tokio::test]
+async fn async_force_generate_data_finds_json_in_mixed_text() {
+    for response in [
+        success(ADA_JSON),
+        think_blocks(ADA_JSON),
+        fenced_json(ADA_JSON),
+    ] {
+        let server = MockServer::always(response);
+        let person: Person = server
+            .llm()
+            .async_force_generate_data(&Person::new(), TARGET, &vec![])
+            .await
+            .unwrap();
+        assert_eq!(person, ada());
+        assert!(!server.requests()[0].is_json_mode());
+    }
+}
+
+#[tokio::test]
+async fn async_force_generate_data_reports_failures() {
+    for response in [empty_choices(), error_body(), content_filter()] {
+        let server = MockServer::always(response);
+        assert_no_response(
+            server
+                .llm()
+                .async_force_generate_data(&Person::new(), TARGET, &vec![])
+                .await,
+        );
+    }
+
+    let server = MockServer::always(truncated());
+    assert_malformed_json(
+        server
+            .llm()
+            .async_force_generate_data(&Person::new(), TARGET, &vec![])
+            .await,
+    );
+}
+
+#[tokio::test]
+async fn async_fields_generate_data_sends_one_request_per_field() {
+    let server = fields_server(field_result("36"));
+
+    let person: Person = server
+        .llm()
+        .async_fields_generate_data(&Person::new(), TARGET, &vec![])
+        .await
+        .unwrap();
+
+    assert_eq!(person, ada());
+    assert_eq!(server.requests().len(), 2);
+
+    let person: Person = server
+        .llm()
+        .async_fields_generate_data_with_options(
+            &Person::new(),
+            TARGET,
+            &vec![],
+            &RequestOptions::default(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(person, ada());
+}
+
+#[tokio::test]
+async fn async_fields_generate_data_reports_failures() {
+    for response in [empty_choices(), error_body(), content_filter()] {
+        let server = fields_server(response);
+        assert_no_response(
+            server
+                .llm()
+                .async_fields_generate_data(&Person::new(), TARGET, &vec![])
+                .await,
+        );
+    }
+
+    for response in [truncated(), think_blocks("36"), fenced_json("36")] {
+        let server = fields_server(response);
+        assert_age_failed(
+            server
+                .llm()
+                .async_fields_generate_data(&Person::new(), TARGET, &vec![])
+                .await,
+        );
+    }
+}
+
+#[tokio::test]
+async fn async_generate_partial_data_replaces_failed_fields() {
+    let server = MockServer::always(success(r#"{"name": "Ada", "age": "unknown"}"#));
+    let partial = server
+        .llm()
+        .async_generate_partial_data(&Person::new(), TARGET, &vec![])
+        .await
+        .unwrap();
+    assert_eq!(partial.failed_fields, vec!["age"]);
+    assert_eq!(partial.data.name, "Ada");
+
+    let server = fields_server(truncated());
+    let partial = server
+        .llm()
+        .async_fields_generate_partial_data(&Person::new(), TARGET, &vec![])
+        .await
+        .unwrap();
+    assert_eq!(partial.failed_fields, vec!["age"]);
+
+    let partial = server
+        .llm()
+        .async_fields_generate_partial_data_with_options(
+            &Person::new(),
+            TARGET,
+            &vec![],
+            &RequestOptions::default(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(partial.failed_fields, vec!["age"]);
+}
+
+#[tokio::test]
+async fn async_generate_data_or_review_returns_review_items() {
+    let server = MockServer::always(success(r#"{"name": "Ada", "age": "unknown"}"#));
+
+    match server
+        .llm()
+        .async_generate_data_or_review(&Person::new(), TARGET, &vec![])
+        .await
+        .unwrap()
+    {
+        Either::Left(person) => panic!("unexpected data: {:?}", person),
+        Either::Right(item) => assert_eq!(item.extracted.name, "Ada"),
+    }
+}
+
+#[tokio::test]
+async fn async_generate_data_with_provenance_locates_the_evidence() {
+    let content = json!({
+        "name": {"value": "Ada", "evidence": "Ada"},
+        "age": {"value": 36, "evidence": "36 years old"}
+    });
+    let server = MockServer::always(success(&content.to_string()));
+
+    let result = server
+        .llm()
+        .async_generate_data_with_provenance(&Person::new(), TARGET, &vec![])
+        .await
+        .unwrap();
+
+    assert_eq!(result.data, ada());
+    assert!(
+        result
+            .provenance
+            .iter()
+            .all(|provenance| provenance.match_kind == QuoteMatch::Exact)
+    );
+}
+
+#[tokio::test]
+async fn async_generate_data_from_reader_reads_the_target() {
+    let server = MockServer::always(success(ADA_JSON));
+    let reader = futures::io::Cursor::new(format!("\u{FEFF}{}", TARGET).into_bytes());
+
+    let person: Person = server
+        .llm()
+        .async_generate_data_from_reader(&Person::new(), reader, &vec![])
+        .await
+        .unwrap();
+
+    assert_eq!(person, ada());
+    assert!(
+        server.requests()[0]
+            .prompt()
+            .contains(&format!("json:\n{}", TARGET))
+    );
+}
+
+#[tokio::test]
+async fn async_generate_data_adaptive_reports_the_strategy() {
+    let server = MockServer::always(success(ADA_JSON));
+
+    let result = server
+        .llm()
+        .async_generate_data_adaptive(&Person::new(), TARGET, &vec![])
+        .await
+        .unwrap();
+
+    assert_eq!(result.data, ada());
+    assert_eq!(result.metadata.prompt_strategy, Some(PromptStrategy::Full));
+}
+
+#[tokio::test]
+async fn async_generate_data_chunked_merges_the_chunks() {
+    let server = MockServer::start(|request| {
+        if request.prompt().contains("Ada is") {
+            success(r#"{"name": "Ada", "age": 0}"#)
+        } else {
+            success(r#"{"name": "", "age": 36}"#)
+        }
+    });
+    let options = ChunkOptions::default()
+        .with_chunk_size(30)
+        .with_overlap(0)
+        .with_merge_policy(MergePolicy::FirstNonDefault);
+
+    let person: Person = server
+        .llm()
+        .async_generate_data_chunked(
+            &Person::new(),
+            "Ada is a mathematician.\n\nShe turned 36 this year.",
+            &vec![],
+            &options,
+        )
+        .await
+        .unwrap()
+        .data;
+
+    assert_eq!(person, ada());
+    assert_eq!(server.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn async_update_data_only_requests_missing_fields() {
+    let server = fields_server(field_result("36"));
+    let existing = Person {
+        name: "Ada".to_string(),
+        age: 0,
+    };
+
+    let person: Person = server
+        .llm()
+        .async_update_data(&existing, TARGET, &vec![])
+        .await
+        .unwrap();
+
+    assert_eq!(person, ada());
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn async_generate_value_returns_json() {
+    let server = MockServer::always(success(ADA_JSON));
+    let value = server
+        .llm()
+        .async_generate_value(&Person::new(), TARGET, &vec![])
+        .await
+        .unwrap();
+    assert_eq!(value, json!({"name": "Ada", "age": 36}));
+
+    let server = fields_server(field_result("36"));
+    let value = server
+        .llm()
+        .async_fields_generate_value(&Person::new(), TARGET, &vec![])
+        .await
+        .unwrap();
+    assert_eq!(value, json!({"name": "Ada", "age": 36}));
+}
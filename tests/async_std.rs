@@ -0,0 +1,77 @@
+//! The async methods run on async-std's executor, whose `tokio1` feature provides the
+//! reactor reqwest needs, with retries and deadlines waiting on `futures-timer`.
+
+#![allow(clippy::needless_borrows_for_generic_args)]
+
+mod support;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use secretary::deadline::Deadline;
+use secretary::llm_providers::rate_limit::RetryPolicy;
+use secretary::partial::PartialData;
+use secretary::request::RequestOptions;
+use secretary::traits::AsyncGenerateData;
+use serde_json::json;
+
+use support::fixtures::{field_result, success};
+use support::{ADA_JSON, MockResponse, MockServer, Person, TARGET, ada};
+
+#[test]
+fn generate_data_retries_on_async_std() {
+    let attempts = Arc::new(AtomicUsize::new(0));
+    let counter = attempts.clone();
+    let server = MockServer::start(move |_| {
+        if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+            MockResponse::new(429, json!({"error": {"message": "slow down"}}))
+                .with_header("retry-after-ms", "100")
+        } else {
+            success(ADA_JSON)
+        }
+    });
+    let llm = server.llm().with_retry_policy(RetryPolicy::new(1));
+
+    let started = Instant::now();
+    let person: Person = async_std::task::block_on(async {
+        llm.async_generate_data(&Person::new(), TARGET, &vec![])
+            .await
+    })
+    .unwrap();
+
+    assert!(started.elapsed() >= Duration::from_millis(100));
+    assert_eq!(person, ada());
+    assert_eq!(server.requests().len(), 2);
+}
+
+#[test]
+fn field_requests_are_abandoned_at_the_deadline_on_async_std() {
+    let server = MockServer::start(|request| {
+        if request.prompt().contains("Extract the age as a number") {
+            std::thread::sleep(Duration::from_secs(5));
+            field_result("36")
+        } else {
+            field_result("Ada")
+        }
+    });
+    let llm = server.llm();
+    let options =
+        RequestOptions::default().with_deadline(Deadline::after(Duration::from_millis(500)));
+
+    let started = Instant::now();
+    let partial: PartialData<Person> = async_std::task::block_on(async {
+        llm.async_fields_generate_partial_data_with_options(
+            &Person::new(),
+            TARGET,
+            &vec![],
+            &options,
+        )
+        .await
+    })
+    .unwrap();
+
+    assert!(started.elapsed() < Duration::from_secs(2));
+    assert_eq!(partial.data.name, "Ada");
+    assert_eq!(partial.incomplete_fields, vec!["age"]);
+}
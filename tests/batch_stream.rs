@@ -0,0 +1,149 @@
+//! `stream_generate_data_batch` yields each result as soon as its target is extracted.
+
+mod support;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use futures::StreamExt;
+use secretary::traits::AsyncGenerateData;
+
+use support::fixtures::{error_body, success};
+use support::{BoxedError, MockResponse, MockServer, Person};
+
+/// The targets, each naming the person the server answers with.
+fn targets() -> Vec<String> {
+    ["Ada", "Grace", "Edsger", "Barbara"]
+        .iter()
+        .map(|name| format!("{} is 36 years old.", name))
+        .collect()
+}
+
+/// Answers with the person named in the prompt, after `delay` of them.
+fn answer(prompt: &str, delay: impl Fn(&str) -> u64) -> MockResponse {
+    let name: &str = ["Ada", "Grace", "Edsger", "Barbara"]
+        .into_iter()
+        .find(|name| prompt.contains(&format!("{} is 36", name)))
+        .unwrap_or("Nobody");
+    std::thread::sleep(Duration::from_millis(delay(name)));
+    success(&format!(r#"{{"name": "{}", "age": 36}}"#, name))
+}
+
+/// A server that answers Ada last, however early her request arrives.
+fn slow_first_server() -> MockServer {
+    MockServer::start(|request| {
+        answer(
+            &request.prompt(),
+            |name| if name == "Ada" { 400 } else { 0 },
+        )
+    })
+}
+
+#[tokio::test]
+async fn results_arrive_as_they_complete() {
+    let server = slow_first_server();
+    let llm = server.llm();
+    let task = Person::new();
+
+    let results: Vec<(usize, Result<Person, BoxedError>)> = llm
+        .stream_generate_data_batch(&task, targets(), Vec::new(), 4)
+        .collect()
+        .await;
+
+    let order: Vec<usize> = results.iter().map(|(index, _)| *index).collect();
+    assert_eq!(order.len(), 4);
+    assert_eq!(order.last(), Some(&0));
+    for (index, result) in results {
+        assert_eq!(
+            format!("{} is 36 years old.", result.unwrap().name),
+            targets()[index]
+        );
+    }
+}
+
+#[tokio::test]
+async fn concurrency_of_one_keeps_the_input_order() {
+    let server = slow_first_server();
+    let llm = server.llm();
+    let task = Person::new();
+
+    let order: Vec<usize> = llm
+        .stream_generate_data_batch(&task, targets(), Vec::new(), 1)
+        .map(|(index, result)| {
+            assert!(result.is_ok());
+            index
+        })
+        .collect()
+        .await;
+
+    assert_eq!(order, vec![0, 1, 2, 3]);
+}
+
+#[tokio::test]
+async fn requests_in_flight_respect_the_concurrency() {
+    let in_flight: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(0));
+    let most_in_flight: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(0));
+    let (current, most) = (in_flight.clone(), most_in_flight.clone());
+    let server = MockServer::start(move |request| {
+        let running: usize = current.fetch_add(1, Ordering::SeqCst) + 1;
+        most.fetch_max(running, Ordering::SeqCst);
+        let response: MockResponse = answer(&request.prompt(), |_| 100);
+        current.fetch_sub(1, Ordering::SeqCst);
+        response
+    });
+    let llm = server.llm();
+    let task = Person::new();
+
+    let results: Vec<(usize, Result<Person, BoxedError>)> = llm
+        .stream_generate_data_batch(&task, targets(), Vec::new(), 2)
+        .collect()
+        .await;
+
+    assert_eq!(results.len(), 4);
+    assert!(results.iter().all(|(_, result)| result.is_ok()));
+    assert_eq!(most_in_flight.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn failures_are_yielded_with_their_index() {
+    let server = MockServer::start(|request| {
+        if request.prompt().contains("Grace is 36") {
+            error_body()
+        } else {
+            answer(&request.prompt(), |_| 0)
+        }
+    });
+    let llm = server.llm();
+    let task = Person::new();
+
+    let mut results: Vec<(usize, Result<Person, BoxedError>)> = llm
+        .stream_generate_data_batch(&task, targets(), Vec::new(), 4)
+        .collect()
+        .await;
+    results.sort_by_key(|(index, _)| *index);
+
+    assert_eq!(results[0].1.as_ref().unwrap().name, "Ada");
+    assert!(results[1].1.is_err());
+    assert_eq!(results[2].1.as_ref().unwrap().name, "Edsger");
+    assert_eq!(results[3].1.as_ref().unwrap().name, "Barbara");
+}
+
+#[tokio::test]
+async fn dropping_the_stream_starts_no_more_requests() {
+    let server = slow_first_server();
+    let llm = server.llm();
+    let task = Person::new();
+    let many_targets: Vec<String> = targets().into_iter().cycle().take(12).collect();
+
+    let first: Vec<(usize, Result<Person, BoxedError>)> = llm
+        .stream_generate_data_batch(&task, many_targets, Vec::new(), 2)
+        .take(1)
+        .collect()
+        .await;
+    assert_eq!(first[0].0, 1);
+
+    // Let the abandoned request in flight reach the server
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert_eq!(server.requests().len(), 2);
+}
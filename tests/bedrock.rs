@@ -0,0 +1,323 @@
+//! The Bedrock provider against recorded Converse response bodies and a fake signer.
+
+#![allow(clippy::needless_borrows_for_generic_args)]
+#![cfg(feature = "aws")]
+
+mod support;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use reqwest::header::{AUTHORIZATION, HeaderMap, HeaderValue};
+use secretary::SecretaryError;
+use secretary::constants::JSON_ONLY_INSTRUCTION;
+use secretary::credentials::Credentials;
+use secretary::llm_providers::bedrock::{BedrockLLM, SignableRequest};
+use secretary::llm_providers::rate_limit::RetryPolicy;
+use secretary::request::RequestOptions;
+use secretary::traits::{AsyncGenerateData, GenerateData, IsLLM};
+use serde_json::json;
+
+use support::fixtures::{
+    converse_max_tokens, converse_reasoning, converse_success, converse_throttled,
+    converse_validation_error,
+};
+use support::{
+    ADA_JSON, MockResponse, MockServer, Person, TARGET, ada, assert_age_failed,
+    assert_malformed_json, assert_no_response, secretary_error,
+};
+
+const MODEL_ID: &str = "anthropic.claude-3-haiku-20240307-v1:0";
+const CONVERSE_PATH: &str = "/model/anthropic.claude-3-haiku-20240307-v1%3A0/converse";
+const SIGNATURE: &str =
+    "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20240101/us-east-1/bedrock/aws4_request";
+
+/// A request as the fake signer saw it.
+#[derive(Debug, Clone)]
+struct Signed {
+    method: String,
+    url: String,
+    content_type: Option<String>,
+    body: Vec<u8>,
+}
+
+/// Returns a provider pointed at the server whose signer records every request it signs.
+fn bedrock(server: &MockServer) -> (BedrockLLM, Arc<Mutex<Vec<Signed>>>) {
+    let signed: Arc<Mutex<Vec<Signed>>> = Arc::new(Mutex::new(Vec::new()));
+    let recorder = signed.clone();
+    let llm = BedrockLLM::new(
+        "us-east-1",
+        MODEL_ID,
+        move |request: &SignableRequest<'_>| {
+            recorder.lock().unwrap().push(Signed {
+                method: request.method.to_string(),
+                url: request.url.to_string(),
+                content_type: request
+                    .headers
+                    .get("content-type")
+                    .and_then(|value| value.to_str().ok())
+                    .map(str::to_string),
+                body: request.body.to_vec(),
+            });
+
+            let mut headers = HeaderMap::new();
+            headers.insert(AUTHORIZATION, HeaderValue::from_static(SIGNATURE));
+            headers.insert("x-amz-date", HeaderValue::from_static("20240101T000000Z"));
+            Ok(headers)
+        },
+    )
+    .with_endpoint(server.address());
+
+    (llm, signed)
+}
+
+/// A server answering the name request with Ada and the age request with `age`.
+fn converse_fields_server(age: MockResponse) -> MockServer {
+    MockServer::by_instruction(
+        vec![
+            (
+                "Extract the person's name",
+                converse_success("<result>Ada</result>"),
+            ),
+            ("Extract the age as a number", age),
+        ],
+        converse_validation_error(),
+    )
+}
+
+#[test]
+fn default_endpoint_is_the_regional_runtime() {
+    let llm = BedrockLLM::new("eu-west-3", MODEL_ID, |_: &SignableRequest<'_>| {
+        Ok(HeaderMap::new())
+    });
+
+    assert_eq!(llm.region(), "eu-west-3");
+    assert_eq!(
+        llm.get_chat_completion_request_url(),
+        format!(
+            "https://bedrock-runtime.eu-west-3.amazonaws.com{}",
+            CONVERSE_PATH
+        )
+    );
+}
+
+#[test]
+fn generate_data_sends_a_converse_request() {
+    let server = MockServer::always(converse_success(ADA_JSON));
+    let (llm, _) = bedrock(&server);
+
+    let person: Person = llm.generate_data(&Person::new(), TARGET, &vec![]).unwrap();
+
+    assert_eq!(person, ada());
+    let requests = server.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].path, CONVERSE_PATH);
+
+    let body = &requests[0].body;
+    assert!(body.get("response_format").is_none());
+    assert!(body.get("model").is_none());
+    assert!(
+        body["system"][0]["text"]
+            .as_str()
+            .unwrap()
+            .contains("Extract the person's name")
+    );
+    let messages = body["messages"].as_array().unwrap();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0]["role"], "user");
+    let text = messages[0]["content"][0]["text"].as_str().unwrap();
+    assert!(text.contains(TARGET));
+    assert!(text.ends_with(JSON_ONLY_INSTRUCTION));
+}
+
+#[test]
+fn the_signer_sees_the_final_body() {
+    let server = MockServer::always(converse_success(ADA_JSON));
+    let (llm, signed) = bedrock(&server);
+
+    llm.generate_data(&Person::new(), TARGET, &vec![]).unwrap();
+
+    let signed = signed.lock().unwrap().clone();
+    let requests = server.requests();
+    assert_eq!(signed.len(), 1);
+    assert_eq!(signed[0].method, "POST");
+    assert_eq!(
+        signed[0].url,
+        format!("{}{}", server.address(), CONVERSE_PATH)
+    );
+    assert_eq!(signed[0].content_type.as_deref(), Some("application/json"));
+    assert_eq!(signed[0].body, requests[0].raw_body.as_bytes());
+
+    // The signature headers are sent as returned
+    assert_eq!(requests[0].headers["authorization"], SIGNATURE);
+    assert_eq!(requests[0].headers["x-amz-date"], "20240101T000000Z");
+}
+
+#[test]
+fn every_attempt_is_signed() {
+    let attempts = Arc::new(AtomicUsize::new(0));
+    let counter = attempts.clone();
+    let server = MockServer::start(move |_| {
+        if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+            converse_throttled()
+        } else {
+            converse_success(ADA_JSON)
+        }
+    });
+    let (llm, signed) = bedrock(&server);
+    let llm = llm.with_retry_policy(RetryPolicy::new(1).with_base_delay(Duration::from_millis(10)));
+
+    let person: Person = llm.generate_data(&Person::new(), TARGET, &vec![]).unwrap();
+
+    assert_eq!(person, ada());
+    assert_eq!(server.requests().len(), 2);
+    assert_eq!(signed.lock().unwrap().len(), 2);
+}
+
+#[test]
+fn a_failing_signer_stops_the_request() {
+    let server = MockServer::always(converse_success(ADA_JSON));
+    let llm = BedrockLLM::new("us-east-1", MODEL_ID, |_: &SignableRequest<'_>| {
+        Err("the credentials have expired".into())
+    })
+    .with_endpoint(server.address());
+
+    let error = llm
+        .generate_data(&Person::new(), TARGET, &vec![])
+        .unwrap_err();
+
+    match secretary_error(&error) {
+        SecretaryError::BuildRequestError(message) => {
+            assert!(message.contains("the credentials have expired"))
+        }
+        other => panic!("unexpected error: {}", other),
+    }
+    assert!(server.requests().is_empty());
+}
+
+#[test]
+fn options_are_written_to_the_inference_config() {
+    let server = MockServer::always(converse_success(ADA_JSON));
+    let (llm, _) = bedrock(&server);
+    let llm = llm.with_extra_body(json!({"additionalModelRequestFields": {"top_k": 50}}));
+    let options = RequestOptions::default()
+        .with_temperature(0.2)
+        .with_max_tokens(512);
+
+    llm.generate_data_with_options(&Person::new(), TARGET, &vec![], &options)
+        .unwrap();
+
+    let body = &server.requests()[0].body;
+    assert_eq!(
+        body["inferenceConfig"],
+        json!({"temperature": 0.2, "maxTokens": 512})
+    );
+    assert_eq!(body["additionalModelRequestFields"], json!({"top_k": 50}));
+    assert!(body.get("temperature").is_none());
+}
+
+#[test]
+fn reasoning_blocks_are_skipped() {
+    let server = MockServer::always(converse_reasoning(ADA_JSON));
+    let (llm, _) = bedrock(&server);
+
+    let person: Person = llm.generate_data(&Person::new(), TARGET, &vec![]).unwrap();
+
+    assert_eq!(person, ada());
+}
+
+#[test]
+fn generate_data_reports_failures() {
+    let server = MockServer::always(converse_validation_error());
+    let (llm, _) = bedrock(&server);
+    assert_no_response(llm.generate_data(&Person::new(), TARGET, &vec![]));
+
+    let server = MockServer::always(converse_max_tokens());
+    let (llm, _) = bedrock(&server);
+    let raw_content = assert_malformed_json(llm.generate_data(&Person::new(), TARGET, &vec![]));
+    assert_eq!(raw_content, r#"{"name": "Ada", "ag"#);
+}
+
+#[test]
+fn per_call_credentials_are_rejected_before_sending() {
+    let server = MockServer::always(converse_success(ADA_JSON));
+    let (llm, signed) = bedrock(&server);
+
+    let error = llm
+        .generate_data_with_options(
+            &Person::new(),
+            TARGET,
+            vec![],
+            &RequestOptions::default().with_credentials(Credentials::new("sk-tenant")),
+        )
+        .unwrap_err();
+
+    assert!(matches!(
+        secretary_error(&error),
+        SecretaryError::BuildRequestError(_)
+    ));
+    assert_eq!(signed.lock().unwrap().len(), 1);
+    assert!(server.requests().is_empty());
+}
+
+#[test]
+fn fields_generate_data_sends_one_converse_request_per_field() {
+    let server = converse_fields_server(converse_success("<result>36</result>"));
+    let (llm, signed) = bedrock(&server);
+
+    let person: Person = llm
+        .fields_generate_data(&Person::new(), TARGET, &vec![])
+        .unwrap();
+
+    assert_eq!(person, ada());
+    assert_eq!(server.requests().len(), 2);
+    assert_eq!(signed.lock().unwrap().len(), 2);
+
+    let server = converse_fields_server(converse_max_tokens());
+    let (llm, _) = bedrock(&server);
+    assert_age_failed(llm.fields_generate_data(&Person::new(), TARGET, &vec![]));
+}
+
+#[test]
+fn health_check_sends_a_one_token_converse_request() {
+    let server = MockServer::always(converse_success("pong"));
+    let (llm, signed) = bedrock(&server);
+
+    let report = llm.health_check().unwrap();
+
+    assert!(report.is_healthy());
+    let requests = server.requests();
+    assert_eq!(requests[0].path, CONVERSE_PATH);
+    assert_eq!(requests[0].body["inferenceConfig"]["maxTokens"], 1);
+    assert_eq!(
+        signed.lock().unwrap()[0].body,
+        requests[0].raw_body.as_bytes()
+    );
+}
+
+#[tokio::test]
+async fn async_generate_data_signs_and_parses_converse() {
+    let server = MockServer::always(converse_success(ADA_JSON));
+    let (llm, signed) = bedrock(&server);
+
+    let person: Person = llm
+        .async_generate_data(&Person::new(), TARGET, &vec![])
+        .await
+        .unwrap();
+
+    assert_eq!(person, ada());
+    let requests = server.requests();
+    assert_eq!(
+        signed.lock().unwrap()[0].body,
+        requests[0].raw_body.as_bytes()
+    );
+
+    let server = converse_fields_server(converse_success("<result>36</result>"));
+    let (llm, _) = bedrock(&server);
+    let person: Person = llm
+        .async_fields_generate_data(&Person::new(), TARGET, &vec![])
+        .await
+        .unwrap();
+    assert_eq!(person, ada());
+}
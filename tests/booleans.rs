@@ -0,0 +1,349 @@
+//! Boolean fields read the words, symbols and ratings models answer with, only where the
+//! field is a `bool`, and settle uncertain answers by the profile's policy.
+
+mod support;
+
+use secretary::Task;
+use secretary::booleans::{BooleanReading, BooleanSynonyms};
+use secretary::leniency::{LeniencyProfile, UncertainBooleans};
+use secretary::traits::GenerateData;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use support::MockServer;
+use support::fixtures::{empty_choices, field_result, success};
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Inspection {
+    #[task(instruction = "Extract the inspected unit")]
+    pub unit: String,
+    #[task(instruction = "Is the smoke detector working")]
+    pub smoke_detector: bool,
+    #[task(instruction = "Is the unit furnished")]
+    pub furnished: bool,
+    #[task(instruction = "Are pets allowed, if mentioned")]
+    pub pets: Option<bool>,
+    #[task(instruction = "Is parking included", default_value = "true")]
+    pub parking: bool,
+    #[task(instruction = "Extract the inspector's verdict")]
+    pub verdict: String,
+    #[task(instruction = "List whether each window closes")]
+    pub windows: Vec<bool>,
+}
+
+/// Applies a profile to a single field and returns its coerced value.
+fn coerce(profile: &LeniencyProfile, field: &str, input: Value) -> Value {
+    let mut value: Value = json!({ field: input });
+    profile.apply::<Inspection>(&mut value);
+    value[field].clone()
+}
+
+/// `aggressive()` with the built-in synonym table.
+fn lenient() -> LeniencyProfile {
+    LeniencyProfile::aggressive().with_boolean_synonyms(BooleanSynonyms::builtin())
+}
+
+fn answer(smoke_detector: &str, furnished: &str, pets: &str, parking: &str) -> String {
+    json!({
+        "unit": "4B",
+        "smoke_detector": smoke_detector,
+        "furnished": furnished,
+        "pets": pets,
+        "parking": parking,
+        "verdict": "✓",
+        "windows": []
+    })
+    .to_string()
+}
+
+#[test]
+fn answers_read_across_scripts_and_symbols() {
+    let strict = LeniencyProfile::strict();
+    let standard = LeniencyProfile::standard();
+    let words = LeniencyProfile::standard().with_boolean_synonyms(BooleanSynonyms::builtin());
+    let aggressive = LeniencyProfile::aggressive();
+    let lenient = lenient();
+
+    // (profile, field, model output, coerced value)
+    let cases: Vec<(&LeniencyProfile, &str, Value, Value)> = vec![
+        // Words in other languages
+        (&words, "furnished", json!("Ja."), json!(true)),
+        (&words, "furnished", json!("oui"), json!(true)),
+        (&words, "furnished", json!("Sí"), json!(true)),
+        (&words, "furnished", json!("да"), json!(true)),
+        (&words, "furnished", json!("はい"), json!(true)),
+        (&words, "furnished", json!("是的"), json!(true)),
+        (&words, "furnished", json!("نعم"), json!(true)),
+        (&words, "furnished", json!("Nein!"), json!(false)),
+        (&words, "furnished", json!("non"), json!(false)),
+        (&words, "furnished", json!("нет"), json!(false)),
+        (&words, "furnished", json!("いいえ"), json!(false)),
+        (&words, "furnished", json!("没有"), json!(false)),
+        (&words, "furnished", json!("  Of   course "), json!(true)),
+        // Symbols
+        (&words, "furnished", json!("✓"), json!(true)),
+        (&words, "furnished", json!("✅"), json!(true)),
+        (&words, "furnished", json!("[x]"), json!(true)),
+        (&words, "furnished", json!("✗"), json!(false)),
+        (&words, "furnished", json!("❌"), json!(false)),
+        (&words, "furnished", json!("☐"), json!(false)),
+        // The synonyms are opt-in, also in aggressive
+        (&standard, "furnished", json!("Ja."), json!("Ja.")),
+        (&aggressive, "furnished", json!("✓"), json!("✓")),
+        (&standard, "furnished", json!("yes"), json!(true)),
+        // Ratings, only in aggressive, on their own scale
+        (&standard, "furnished", json!("4/5"), json!("4/5")),
+        (&aggressive, "furnished", json!("4/5"), json!(true)),
+        (&aggressive, "furnished", json!("1/5"), json!(false)),
+        (&aggressive, "furnished", json!("2 out of 5"), json!(false)),
+        (&aggressive, "furnished", json!("9 of 10"), json!(true)),
+        (&aggressive, "furnished", json!("3/5"), json!("3/5")),
+        (&aggressive, "furnished", json!("6/5"), json!("6/5")),
+        // Bare numbers have no scale, so only 1 and 0 are read unless nonzero is true
+        (&standard, "furnished", json!("1"), json!(true)),
+        (&standard, "furnished", json!(1), json!(true)),
+        (&standard, "furnished", json!(0), json!(false)),
+        (&standard, "furnished", json!(2), json!(2)),
+        (&standard, "furnished", json!(0.5), json!(0.5)),
+        (&standard, "furnished", json!("4"), json!("4")),
+        (&standard, "furnished", json!("-1"), json!("-1")),
+        (&aggressive, "furnished", json!(0.5), json!(true)),
+        (&aggressive, "furnished", json!("4"), json!(true)),
+        (&aggressive, "furnished", json!("0"), json!(false)),
+        // Optional fields and list items
+        (&words, "pets", json!("oui"), json!(true)),
+        (
+            &words,
+            "windows",
+            json!(["✓", "✗", "ja"]),
+            json!([true, false, true]),
+        ),
+        (&lenient, "windows", json!("✓"), json!([true])),
+        // Uncertain answers are left for serde to reject
+        (&lenient, "furnished", json!("maybe"), json!("maybe")),
+        (&lenient, "furnished", json!("N/A"), json!("N/A")),
+        (&lenient, "furnished", json!("purple"), json!("purple")),
+        // String fields keep the literal answer
+        (&lenient, "verdict", json!("✓"), json!("✓")),
+        (&lenient, "verdict", json!("4/5"), json!("4/5")),
+        (&lenient, "unit", json!("oui"), json!("oui")),
+        // Strict leaves everything alone
+        (&strict, "furnished", json!("✓"), json!("✓")),
+        (&strict, "furnished", json!(1), json!(1)),
+    ];
+
+    for (profile, field, input, expected) in cases {
+        assert_eq!(
+            coerce(profile, field, input.clone()),
+            expected,
+            "{:?} {} {}",
+            profile,
+            field,
+            input
+        );
+    }
+}
+
+#[test]
+fn nonzero_numbers_are_true_when_the_profile_reads_numbers() {
+    let numbers = LeniencyProfile {
+        booleans_from_numbers: true,
+        ..LeniencyProfile::strict()
+    };
+
+    assert_eq!(coerce(&numbers, "furnished", json!(2)), json!(true));
+    assert_eq!(coerce(&numbers, "furnished", json!(-1)), json!(true));
+    assert_eq!(coerce(&numbers, "furnished", json!("-1")), json!(true));
+    assert_eq!(coerce(&numbers, "furnished", json!(0)), json!(false));
+    assert_eq!(
+        coerce(&numbers, "windows", json!([2, 0])),
+        json!([true, false])
+    );
+    // String fields keep the number
+    assert_eq!(coerce(&numbers, "verdict", json!("2")), json!("2"));
+
+    // Without the flag, 2 and -1 are left for serde to reject
+    let standard = LeniencyProfile::standard();
+    assert_eq!(coerce(&standard, "furnished", json!(2)), json!(2));
+    assert_eq!(coerce(&standard, "furnished", json!(-1)), json!(-1));
+}
+
+#[test]
+fn uncertain_answers_take_the_default_of_their_field() {
+    let profile: LeniencyProfile = lenient().with_uncertain_booleans(UncertainBooleans::UseDefault);
+
+    assert_eq!(coerce(&profile, "furnished", json!("maybe")), json!(false));
+    assert_eq!(coerce(&profile, "furnished", json!("3/5")), json!(false));
+    assert_eq!(coerce(&profile, "pets", json!("not sure")), json!(null));
+    assert_eq!(coerce(&profile, "parking", json!("🤷")), json!(true));
+    assert_eq!(
+        coerce(&profile, "windows", json!(["ja", "?"])),
+        json!([true, false])
+    );
+    // Answers the profile cannot read at all are still left alone
+    assert_eq!(
+        coerce(&profile, "furnished", json!("purple")),
+        json!("purple")
+    );
+}
+
+#[test]
+fn uncertain_answers_are_rejected_by_default() {
+    let output: String = answer("ja", "maybe", "n/a", "✓");
+    let profile: LeniencyProfile =
+        LeniencyProfile::standard().with_boolean_synonyms(BooleanSynonyms::builtin());
+
+    let rejected = profile.from_str::<Inspection>(&output);
+    assert!(rejected.is_err());
+
+    let inspection: Inspection = profile
+        .with_uncertain_booleans(UncertainBooleans::UseDefault)
+        .from_str(&output)
+        .unwrap();
+    assert!(inspection.smoke_detector);
+    assert!(!inspection.furnished);
+    assert_eq!(inspection.pets, None);
+    assert!(inspection.parking);
+    assert_eq!(inspection.verdict, "✓");
+}
+
+#[test]
+fn each_profile_reads_its_own_table() {
+    let builtin: LeniencyProfile = lenient();
+    let mut extended: LeniencyProfile = LeniencyProfile::aggressive().with_boolean_synonyms(
+        BooleanSynonyms::builtin().with_token("Yessir", BooleanReading::True),
+    );
+
+    assert_eq!(
+        coerce(&builtin, "furnished", json!("yessir")),
+        json!("yessir")
+    );
+    assert_eq!(coerce(&extended, "furnished", json!("yessir")), json!(true));
+
+    // Extended at runtime, for this profile only
+    if let Some(synonyms) = extended.boolean_synonyms.as_mut() {
+        synonyms.add("jo", BooleanReading::True);
+        synonyms.remove("yessir");
+    }
+    assert_eq!(coerce(&extended, "furnished", json!("jo")), json!(true));
+    assert_eq!(
+        coerce(&extended, "furnished", json!("yessir")),
+        json!("yessir")
+    );
+    assert_eq!(coerce(&builtin, "furnished", json!("jo")), json!("jo"));
+}
+
+#[test]
+fn clones_are_extended_apart() {
+    let table: BooleanSynonyms = BooleanSynonyms::empty().with_token("jo", BooleanReading::True);
+    let mut clone: BooleanSynonyms = table.clone();
+    clone.add("na ja", BooleanReading::Uncertain);
+
+    assert_eq!(table.lookup("JO"), Some(BooleanReading::True));
+    assert_eq!(table.lookup("Na  ja."), None);
+    assert_eq!(clone.lookup("Na  ja."), Some(BooleanReading::Uncertain));
+    assert_eq!(table.lookup("yes"), None);
+    assert_eq!(BooleanSynonyms::builtin().lookup("jo"), None);
+}
+
+#[test]
+fn profiles_keep_their_table_through_serde() {
+    let profile: LeniencyProfile = LeniencyProfile::standard().with_boolean_synonyms(
+        BooleanSynonyms::empty().with_token("Jawohl!", BooleanReading::True),
+    );
+
+    let restored: LeniencyProfile =
+        serde_json::from_value(serde_json::to_value(&profile).unwrap()).unwrap();
+
+    assert_eq!(restored, profile);
+    assert_eq!(coerce(&restored, "furnished", json!("jawohl")), json!(true));
+}
+
+#[test]
+fn a_lenient_provider_reads_messy_answers() {
+    let server = MockServer::always(success(&answer("✅", "Nein.", "sí", "0")));
+    let llm = server.llm().with_leniency(lenient());
+
+    let inspection: Inspection = llm
+        .generate_data(&Inspection::new(), "Unit 4B", vec![])
+        .unwrap();
+
+    assert!(inspection.smoke_detector);
+    assert!(!inspection.furnished);
+    assert_eq!(inspection.pets, Some(true));
+    assert!(!inspection.parking);
+    assert_eq!(inspection.verdict, "✓");
+}
+
+#[test]
+fn distributed_generation_reads_messy_answers() {
+    let server = MockServer::by_instruction(
+        vec![
+            ("Extract the inspected unit", field_result("4B")),
+            ("Is the smoke detector working", field_result("✓")),
+            ("Is the unit furnished", field_result("Ja.")),
+            ("Are pets allowed", field_result("4/5")),
+            ("Is parking included", field_result("0")),
+            ("Extract the inspector's verdict", field_result("✓")),
+            (
+                "List whether each window closes",
+                field_result("<item>yes</item><item>✗</item><item>1</item>"),
+            ),
+        ],
+        empty_choices(),
+    );
+    let llm = server.llm().with_leniency(lenient());
+
+    let inspection: Inspection = llm
+        .fields_generate_data(&Inspection::new(), "Unit 4B", vec![])
+        .unwrap();
+
+    assert_eq!(
+        inspection,
+        Inspection {
+            unit: "4B".to_string(),
+            smoke_detector: true,
+            furnished: true,
+            pets: Some(true),
+            parking: false,
+            verdict: "✓".to_string(),
+            windows: vec![true, false, true],
+        }
+    );
+}
+
+#[test]
+fn distributed_uncertain_answers_follow_the_policy() {
+    let server = MockServer::by_instruction(
+        vec![
+            ("Extract the inspected unit", field_result("4B")),
+            ("Is the smoke detector working", field_result("unknown")),
+            ("Is the unit furnished", field_result("3/5")),
+            ("Are pets allowed", field_result("vielleicht")),
+            ("Is parking included", field_result("?")),
+            ("Extract the inspector's verdict", field_result("maybe")),
+            (
+                "List whether each window closes",
+                field_result("<item>?</item>"),
+            ),
+        ],
+        empty_choices(),
+    );
+    let strict_policy = server.llm().with_leniency(lenient());
+    let defaults = server
+        .llm()
+        .with_leniency(lenient().with_uncertain_booleans(UncertainBooleans::UseDefault));
+
+    let rejected = strict_policy.fields_generate_data(&Inspection::new(), "Unit 4B", vec![]);
+    assert!(rejected.is_err());
+
+    let inspection: Inspection = defaults
+        .fields_generate_data(&Inspection::new(), "Unit 4B", vec![])
+        .unwrap();
+    assert!(!inspection.smoke_detector);
+    assert!(!inspection.furnished);
+    assert_eq!(inspection.pets, None);
+    assert!(inspection.parking);
+    assert_eq!(inspection.verdict, "maybe");
+    assert_eq!(inspection.windows, vec![false]);
+}
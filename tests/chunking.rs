@@ -0,0 +1,639 @@
+//! The results of chunks are merged field by field under every merge policy, and the sources
+//! and conflicts of the merge are reported with the result.
+
+mod support;
+
+use proptest::prelude::*;
+use secretary::Task;
+use secretary::chunking::{
+    ChunkOptions, ChunkValue, MergeConflict, MergePolicy, MergeRationale, apply_adjudication,
+    merge_chunk_values, merge_results, take_confidence,
+};
+use secretary::traits::{AsyncGenerateData, GenerateData};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use support::MockServer;
+use support::fixtures::success;
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Vendor {
+    #[task(instruction = "Extract the vendor name")]
+    pub name: String,
+    #[task(instruction = "Extract the vendor city")]
+    pub city: String,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Invoice {
+    #[task(instruction = "Extract the invoice number")]
+    pub invoice_number: String,
+    #[task(instruction = "Extract the total")]
+    pub total: Option<f64>,
+    #[task(instruction = "Extract the line items")]
+    pub items: Vec<String>,
+    pub vendor: Vendor,
+}
+
+/// A document of three chunks: the header, the body and a footnote naming another invoice.
+const DOCUMENT: &str = "INVOICE INV-7 from ACME\n\n\
+                        Widgets, bolts. Total 120.50.\n\n\
+                        Footnote: replaces INV-3.";
+
+/// The chunk answers to `DOCUMENT`, with confidences, and the answer `adjudication` to the
+/// adjudication request.
+fn invoice_server(adjudication: Value) -> MockServer {
+    MockServer::start(move |request| {
+        let prompt: String = request.prompt();
+        let answer: Value = if prompt.contains("the chunks disagree") {
+            adjudication.clone()
+        } else if prompt.contains("INVOICE INV-7") {
+            json!({
+                "invoice_number": "INV-7",
+                "total": null,
+                "items": [],
+                "vendor": {"name": "ACME", "city": ""},
+                "_confidence": {"invoice_number": 0.9, "vendor": 0.8}
+            })
+        } else if prompt.contains("Widgets") {
+            json!({
+                "invoice_number": "",
+                "total": 120.5,
+                "items": ["widgets", "bolts"],
+                "vendor": {"name": "", "city": ""}
+            })
+        } else {
+            json!({
+                "invoice_number": "INV-3",
+                "total": null,
+                "items": [],
+                "vendor": {"name": "", "city": ""},
+                "_confidence": {"invoice_number": "40%"}
+            })
+        };
+        success(&answer.to_string())
+    })
+}
+
+fn options(policy: MergePolicy) -> ChunkOptions {
+    ChunkOptions::default()
+        .with_chunk_size(30)
+        .with_overlap(0)
+        .with_merge_policy(policy)
+        .with_confidence(true)
+}
+
+fn defaults() -> Value {
+    json!({"number": "", "total": null, "items": [], "vendor": {"name": "", "city": ""}})
+}
+
+/// Chunks giving `number` the values in order, with the confidences given, if any.
+fn numbers(values: &[(&str, Option<f32>)]) -> Vec<ChunkValue> {
+    values
+        .iter()
+        .map(|(number, confidence)| {
+            let chunk = ChunkValue::new(json!({ "number": number }), format!("Number {}.", number));
+            match confidence {
+                Some(confidence) => chunk.with_confidence("number", *confidence),
+                None => chunk,
+            }
+        })
+        .collect()
+}
+
+/// Returns the single conflict of a merge.
+fn only_conflict(conflicts: &[MergeConflict]) -> &MergeConflict {
+    assert_eq!(conflicts.len(), 1, "{:?}", conflicts);
+    &conflicts[0]
+}
+
+#[test]
+fn every_policy_picks_its_winner() {
+    // INV-3 comes first, INV-7 is given by two chunks and the highest confidence
+    let chunks: Vec<ChunkValue> = numbers(&[
+        ("INV-3", Some(0.4)),
+        ("INV-7", Some(0.9)),
+        ("INV-7", Some(0.6)),
+    ]);
+    let cases: [(MergePolicy, &str, MergeRationale); 5] = [
+        (
+            MergePolicy::FirstNonDefault,
+            "INV-3",
+            MergeRationale::EarliestChunk,
+        ),
+        (
+            MergePolicy::HighestConfidence,
+            "INV-7",
+            MergeRationale::HighestConfidence { confidence: 0.9 },
+        ),
+        (
+            MergePolicy::MostFrequent,
+            "INV-7",
+            MergeRationale::MostFrequent { chunks: 2 },
+        ),
+        (
+            MergePolicy::Adjudicate,
+            "INV-3",
+            MergeRationale::EarliestChunk,
+        ),
+        (MergePolicy::Llm, "INV-3", MergeRationale::EarliestChunk),
+    ];
+
+    for (policy, expected, rationale) in cases {
+        let merge = merge_chunk_values(&defaults(), &chunks, policy);
+        assert_eq!(merge.value["number"], expected, "{:?}", policy);
+
+        let conflict: &MergeConflict = only_conflict(&merge.conflicts);
+        assert_eq!(conflict.field, "number");
+        assert_eq!(conflict.winning_value(), expected);
+        assert_eq!(conflict.rationale, rationale, "{:?}", policy);
+        assert_eq!(conflict.candidates.len(), 2);
+        assert_eq!(conflict.candidates[0].chunks, vec![0]);
+        assert_eq!(conflict.candidates[1].chunks, vec![1, 2]);
+        assert_eq!(conflict.candidates[1].confidence, Some(0.9));
+        assert_eq!(merge.sources["number"], vec![0, 1, 2]);
+    }
+}
+
+#[test]
+fn ties_and_missing_confidences_fall_back_to_the_earliest_value() {
+    let cases: [(MergePolicy, Vec<ChunkValue>, &str, MergeRationale); 5] = [
+        (
+            MergePolicy::HighestConfidence,
+            numbers(&[("A", None), ("B", None)]),
+            "A",
+            MergeRationale::EarliestChunk,
+        ),
+        (
+            MergePolicy::HighestConfidence,
+            numbers(&[("A", Some(0.5)), ("B", Some(0.5))]),
+            "A",
+            MergeRationale::EarliestChunk,
+        ),
+        // A value with a confidence beats one without
+        (
+            MergePolicy::HighestConfidence,
+            numbers(&[("A", None), ("B", Some(0.1))]),
+            "B",
+            MergeRationale::HighestConfidence { confidence: 0.1 },
+        ),
+        (
+            MergePolicy::MostFrequent,
+            numbers(&[("A", None), ("B", Some(1.0))]),
+            "A",
+            MergeRationale::EarliestChunk,
+        ),
+        (
+            MergePolicy::MostFrequent,
+            numbers(&[
+                ("A", None),
+                ("B", None),
+                ("A", None),
+                ("B", None),
+                ("C", None),
+            ]),
+            "A",
+            MergeRationale::EarliestChunk,
+        ),
+    ];
+
+    for (policy, chunks, expected, rationale) in cases {
+        let merge = merge_chunk_values(&defaults(), &chunks, policy);
+        assert_eq!(merge.value["number"], expected, "{:?}", policy);
+        assert_eq!(only_conflict(&merge.conflicts).rationale, rationale);
+    }
+}
+
+#[test]
+fn agreeing_chunks_and_defaults_are_not_conflicts() {
+    let chunks: Vec<ChunkValue> = vec![
+        ChunkValue::new(json!({"number": "INV-7", "total": null}), ""),
+        ChunkValue::new(json!({"number": "", "total": 0}), ""),
+        ChunkValue::new(json!({"number": "INV-7", "total": null}), ""),
+    ];
+
+    let merge = merge_chunk_values(&defaults(), &chunks, MergePolicy::MostFrequent);
+
+    assert!(merge.conflicts.is_empty());
+    assert_eq!(merge.value["number"], "INV-7");
+    assert_eq!(merge.value["total"], 0);
+    assert_eq!(merge.sources["number"], vec![0, 2]);
+    assert_eq!(merge.sources["total"], vec![1]);
+    // Fields no chunk filled keep their default and have no source
+    assert_eq!(merge.value["vendor"], json!({"name": "", "city": ""}));
+    assert!(!merge.sources.contains_key("vendor.name"));
+    assert!(!merge.sources.contains_key("items"));
+}
+
+#[test]
+fn lists_are_concatenated_without_conflicts() {
+    let chunks: Vec<ChunkValue> = vec![
+        ChunkValue::new(json!({"items": ["desk", "chair"]}), ""),
+        ChunkValue::new(json!({"items": []}), ""),
+        ChunkValue::new(json!({"items": ["chair", "lamp"]}), ""),
+    ];
+
+    let merge = merge_chunk_values(&defaults(), &chunks, MergePolicy::HighestConfidence);
+
+    assert_eq!(merge.value["items"], json!(["desk", "chair", "lamp"]));
+    assert_eq!(merge.sources["items"], vec![0, 2]);
+    assert!(merge.conflicts.is_empty());
+}
+
+#[test]
+fn nested_objects_are_merged_field_by_field() {
+    let chunks: Vec<ChunkValue> = vec![
+        ChunkValue::new(
+            json!({"vendor": {"name": "ACME", "city": ""}}),
+            "ACME Corp, head office",
+        )
+        .with_confidence("vendor", 0.3),
+        ChunkValue::new(
+            json!({"vendor": {"name": "ACME Corp", "city": "Springfield"}}),
+            "Sold by ACME Corp of Springfield",
+        )
+        .with_confidence("vendor", 0.5)
+        .with_confidence("vendor.name", 0.2),
+    ];
+
+    let merge = merge_chunk_values(&defaults(), &chunks, MergePolicy::HighestConfidence);
+
+    assert_eq!(
+        merge.value["vendor"],
+        json!({"name": "ACME", "city": "Springfield"})
+    );
+    assert_eq!(merge.sources["vendor.name"], vec![0, 1]);
+    assert_eq!(merge.sources["vendor.city"], vec![1]);
+    // The first chunk's confidence in the vendor applies to its name
+    let conflict: &MergeConflict = only_conflict(&merge.conflicts);
+    assert_eq!(conflict.field, "vendor.name");
+    assert_eq!(conflict.candidates[0].confidence, Some(0.3));
+    assert_eq!(conflict.candidates[1].confidence, Some(0.2));
+    assert_eq!(
+        conflict.rationale,
+        MergeRationale::HighestConfidence { confidence: 0.3 }
+    );
+}
+
+#[test]
+fn values_of_different_types_conflict_as_a_whole() {
+    let chunks: Vec<ChunkValue> = vec![
+        ChunkValue::new(json!({"total": "about 120"}), ""),
+        ChunkValue::new(json!({"total": 120.5}), ""),
+        ChunkValue::new(json!({"total": 120.5}), ""),
+    ];
+
+    let merge = merge_chunk_values(&defaults(), &chunks, MergePolicy::MostFrequent);
+
+    assert_eq!(merge.value["total"], 120.5);
+    let conflict: &MergeConflict = only_conflict(&merge.conflicts);
+    assert_eq!(conflict.candidates[0].value, json!("about 120"));
+    assert_eq!(
+        conflict.rationale,
+        MergeRationale::MostFrequent { chunks: 2 }
+    );
+}
+
+#[test]
+fn conflicts_carry_the_excerpts_their_values_were_read_from() {
+    let header: String = format!("{}Invoice number inv-7 issued today", "x".repeat(100));
+    let chunks: Vec<ChunkValue> = vec![
+        ChunkValue::new(json!({"number": "INV-7", "total": 12}), header),
+        ChunkValue::new(json!({"number": "INV-9", "total": 13}), "Nothing here"),
+        ChunkValue::new(json!({"number": "", "total": 13}), "A total of 13 dollars"),
+    ];
+
+    let merge = merge_chunk_values(&defaults(), &chunks, MergePolicy::FirstNonDefault);
+
+    let number: &MergeConflict = &merge.conflicts[0];
+    // Found ignoring case, with up to 60 characters of context on each side
+    assert_eq!(
+        number.candidates[0].excerpt.as_deref(),
+        Some(format!("{}Invoice number inv-7 issued today", "x".repeat(45)).as_str())
+    );
+    assert_eq!(number.candidates[1].excerpt, None);
+    // Numbers are looked up in every chunk that gave them
+    let total: &MergeConflict = &merge.conflicts[1];
+    assert_eq!(total.field, "total");
+    assert_eq!(total.candidates[1].chunks, vec![1, 2]);
+    assert_eq!(
+        total.candidates[1].excerpt.as_deref(),
+        Some("A total of 13 dollars")
+    );
+}
+
+#[test]
+fn adjudication_answers_pick_the_winners() {
+    let chunks = || {
+        vec![
+            ChunkValue::new(
+                json!({"number": "INV-3", "vendor": {"name": "ACME", "city": ""}}),
+                "",
+            ),
+            ChunkValue::new(
+                json!({"number": "INV-7", "vendor": {"name": "Globex", "city": ""}}),
+                "",
+            ),
+        ]
+    };
+    let cases: [(Value, &str, &str); 5] = [
+        (
+            json!({"number": {"choice": 2, "reason": "In the header"}, "vendor.name": 2}),
+            "INV-7",
+            "Globex",
+        ),
+        (json!({"number": "2"}), "INV-7", "ACME"),
+        // Choices out of range and fields left out keep the earliest value
+        (
+            json!({"number": {"choice": 3}, "vendor.name": 0}),
+            "INV-3",
+            "ACME",
+        ),
+        (json!({"number": {"reason": "Unsure"}}), "INV-3", "ACME"),
+        (json!([2, 2]), "INV-3", "ACME"),
+    ];
+
+    for (answer, number, vendor) in cases.clone() {
+        let mut merge = merge_chunk_values(&defaults(), &chunks(), MergePolicy::Adjudicate);
+        apply_adjudication(&mut merge, &answer);
+
+        assert_eq!(merge.value["number"], number, "{}", answer);
+        assert_eq!(merge.value["vendor"]["name"], vendor, "{}", answer);
+        assert_eq!(merge.conflicts[0].winning_value(), number);
+        assert_eq!(merge.conflicts[1].winning_value(), vendor);
+    }
+
+    let mut merge = merge_chunk_values(&defaults(), &chunks(), MergePolicy::Adjudicate);
+    apply_adjudication(&mut merge, &cases[0].0);
+    assert_eq!(
+        merge.conflicts[0].rationale,
+        MergeRationale::Adjudicated {
+            reason: Some("In the header".to_string())
+        }
+    );
+    assert_eq!(
+        merge.conflicts[1].rationale,
+        MergeRationale::Adjudicated { reason: None }
+    );
+}
+
+#[test]
+fn confidences_are_taken_out_of_the_content() {
+    let (content, confidence) = take_confidence(
+        r#"{"name": "Ada", "_confidence": {"name": 1.7, "age": "-5", "city": null, "vendor.name": "95 %"}}"#,
+    );
+    assert_eq!(content, r#"{"name":"Ada"}"#);
+    assert_eq!(confidence.len(), 3);
+    assert_eq!(confidence["name"], 1.0);
+    assert_eq!(confidence["age"], 0.0);
+    assert_eq!(confidence["vendor.name"], 0.95);
+
+    for content in [
+        r#"{"name": "Ada"}"#,
+        "[1, 2]",
+        "not json",
+        r#"{"_confidence": 1"#,
+    ] {
+        assert_eq!(
+            take_confidence(content),
+            (content.to_string(), Default::default())
+        );
+    }
+}
+
+#[test]
+fn confident_values_win_and_conflicts_are_reported() {
+    let server = invoice_server(json!({}));
+
+    let result = server
+        .llm()
+        .generate_data_chunked(
+            &Invoice::new(),
+            DOCUMENT,
+            vec![],
+            &options(MergePolicy::HighestConfidence),
+        )
+        .unwrap();
+
+    assert_eq!(result.data.invoice_number, "INV-7");
+    assert_eq!(result.data.total, Some(120.5));
+    assert_eq!(result.data.items, vec!["widgets", "bolts"]);
+    assert_eq!(result.data.vendor.name, "ACME");
+    let requests = server.requests();
+    assert_eq!(requests.len(), 3);
+    assert!(
+        requests
+            .iter()
+            .all(|request| request.prompt().contains("\"_confidence\""))
+    );
+
+    let metadata = result.metadata;
+    assert_eq!(metadata.chunk_sources["invoice_number"], vec![0, 2]);
+    assert_eq!(metadata.chunk_sources["total"], vec![1]);
+    assert_eq!(metadata.chunk_sources["vendor.name"], vec![0]);
+    let conflict: &MergeConflict = only_conflict(&metadata.merge_conflicts);
+    assert_eq!(conflict.field, "invoice_number");
+    assert_eq!(conflict.candidates[1].value, json!("INV-3"));
+    assert_eq!(conflict.candidates[1].confidence, Some(0.4));
+    assert_eq!(
+        conflict.rationale,
+        MergeRationale::HighestConfidence { confidence: 0.9 }
+    );
+}
+
+#[test]
+fn adjudication_shows_the_model_the_conflicting_values() {
+    let server = invoice_server(json!({
+        "invoice_number": {"choice": 2, "reason": "The footnote replaces the number"}
+    }));
+
+    let result = server
+        .llm()
+        .generate_data_chunked(
+            &Invoice::new(),
+            DOCUMENT,
+            vec![],
+            &options(MergePolicy::Adjudicate),
+        )
+        .unwrap();
+
+    assert_eq!(result.data.invoice_number, "INV-3");
+    assert_eq!(result.data.total, Some(120.5));
+    let requests = server.requests();
+    assert_eq!(requests.len(), 4);
+    let prompt: String = requests
+        .iter()
+        .map(|request| request.prompt())
+        .find(|prompt| prompt.contains("the chunks disagree"))
+        .unwrap();
+    assert!(prompt.ends_with(
+        "\"invoice_number\":\n\
+         1. \"INV-7\", from chunk 1, read from: \"INVOICE INV-7 from ACME\"\n\
+         2. \"INV-3\", from chunk 3, read from: \"Footnote: replaces INV-3.\""
+    ));
+    assert_eq!(
+        result.metadata.merge_conflicts[0].rationale,
+        MergeRationale::Adjudicated {
+            reason: Some("The footnote replaces the number".to_string())
+        }
+    );
+}
+
+#[tokio::test]
+async fn agreeing_chunks_are_not_adjudicated() {
+    let server = invoice_server(json!({}));
+    let document: &str = "INVOICE INV-7 from ACME\n\nWidgets, bolts. Total 120.50.";
+
+    let result = server
+        .llm()
+        .async_generate_data_chunked(
+            &Invoice::new(),
+            document,
+            vec![],
+            &options(MergePolicy::Adjudicate),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(result.data.invoice_number, "INV-7");
+    assert_eq!(server.requests().len(), 2);
+    assert!(result.metadata.merge_conflicts.is_empty());
+    assert_eq!(result.metadata.chunk_sources["items"], vec![1]);
+}
+
+#[tokio::test]
+async fn the_reduce_request_reports_sources_without_conflicts() {
+    let server = MockServer::start(|request| {
+        let prompt: String = request.prompt();
+        if prompt.contains("Merge the partial results") {
+            success(
+                r#"{"invoice_number": "INV-7", "total": 120.5, "items": [], "vendor": {"name": "ACME", "city": ""}}"#,
+            )
+        } else if prompt.contains("INVOICE INV-7") {
+            success(
+                r#"{"invoice_number": "INV-7", "total": null, "items": [], "vendor": {"name": "ACME", "city": ""}}"#,
+            )
+        } else if prompt.contains("Widgets") {
+            success(
+                r#"{"invoice_number": "", "total": 120.5, "items": [], "vendor": {"name": "", "city": ""}}"#,
+            )
+        } else {
+            success(
+                r#"{"invoice_number": "INV-3", "total": null, "items": [], "vendor": {"name": "", "city": ""}}"#,
+            )
+        }
+    });
+
+    let result = server
+        .llm()
+        .async_generate_data_chunked(
+            &Invoice::new(),
+            DOCUMENT,
+            vec![],
+            &options(MergePolicy::Llm).with_confidence(false),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(result.data.invoice_number, "INV-7");
+    assert_eq!(result.metadata.chunk_sources["invoice_number"], vec![0, 2]);
+    assert!(result.metadata.merge_conflicts.is_empty());
+    assert!(
+        server
+            .requests()
+            .iter()
+            .all(|request| !request.prompt().contains("\"_confidence\""))
+    );
+}
+
+/// A value drawn from a few that collide often, including the defaults.
+fn small_value() -> impl Strategy<Value = Value> {
+    prop_oneof![
+        Just(json!("")),
+        Just(json!("INV-3")),
+        Just(json!("INV-7")),
+        Just(json!(null)),
+        Just(json!(0)),
+        Just(json!(120.5)),
+    ]
+}
+
+fn chunk_value() -> impl Strategy<Value = Value> {
+    (
+        small_value(),
+        small_value(),
+        proptest::collection::vec(small_value(), 0..3),
+        small_value(),
+    )
+        .prop_map(|(number, total, items, name)| {
+            json!({
+                "invoice_number": number.as_str().unwrap_or(""),
+                "total": total.as_f64(),
+                "items": items.iter().filter_map(Value::as_str).collect::<Vec<&str>>(),
+                "vendor": {"name": name.as_str().unwrap_or(""), "city": ""}
+            })
+        })
+}
+
+proptest! {
+    #[test]
+    fn the_earliest_chunk_wins_as_in_merge_results(
+        values in proptest::collection::vec(chunk_value(), 0..6),
+    ) {
+        let invoices: Vec<Invoice> = values
+            .iter()
+            .map(|value| serde_json::from_value(value.clone()).unwrap())
+            .collect();
+        let chunks: Vec<ChunkValue> = values
+            .into_iter()
+            .map(|value| ChunkValue::new(value, ""))
+            .collect();
+        let defaults: Value = serde_json::to_value(Invoice::default()).unwrap();
+
+        let merge = merge_chunk_values(&defaults, &chunks, MergePolicy::FirstNonDefault);
+
+        let merged: Invoice = serde_json::from_value(merge.value).unwrap();
+        prop_assert_eq!(merged, merge_results(&invoices).unwrap());
+        for conflict in &merge.conflicts {
+            prop_assert_eq!(conflict.winner, 0);
+            prop_assert!(conflict.candidates.len() > 1);
+        }
+    }
+
+    #[test]
+    fn every_policy_picks_one_of_the_candidates(
+        values in proptest::collection::vec(chunk_value(), 1..6),
+        confidences in proptest::collection::vec(proptest::option::of(0.0f32..=1.0), 6),
+    ) {
+        let chunks: Vec<ChunkValue> = values
+            .into_iter()
+            .zip(&confidences)
+            .map(|(value, confidence)| {
+                let chunk = ChunkValue::new(value, "");
+                match confidence {
+                    Some(confidence) => chunk.with_confidence("invoice_number", *confidence),
+                    None => chunk,
+                }
+            })
+            .collect();
+        let defaults: Value = serde_json::to_value(Invoice::default()).unwrap();
+
+        for policy in [
+            MergePolicy::FirstNonDefault,
+            MergePolicy::HighestConfidence,
+            MergePolicy::MostFrequent,
+            MergePolicy::Adjudicate,
+        ] {
+            let merge = merge_chunk_values(&defaults, &chunks, policy);
+            for conflict in &merge.conflicts {
+                let merged: &Value = conflict
+                    .field
+                    .split('.')
+                    .fold(&merge.value, |value, name| &value[name]);
+                prop_assert_eq!(merged, conflict.winning_value());
+                let chunk_count: usize = conflict.candidates.iter().map(|candidate| candidate.chunks.len()).sum();
+                prop_assert_eq!(&merge.sources[&conflict.field].len(), &chunk_count);
+            }
+        }
+    }
+}
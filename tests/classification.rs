@@ -0,0 +1,244 @@
+//! Multi-label classification lists the labels once and checks every answer against them.
+
+mod support;
+
+use secretary::SecretaryError;
+use secretary::classification::{Classifier, LabelScore, MissingLabelPolicy, parse_label_scores};
+use serde_json::json;
+
+use support::fixtures::success;
+use support::{MockServer, secretary_error};
+
+const LABELS: [&str; 4] = ["billing", "bug", "feature request", "praise"];
+
+const TICKET: &str = "The export crashes every time and I was charged twice this month.";
+
+/// An answer covering every label.
+fn answer() -> String {
+    json!({"labels": [
+        {"label": "billing", "applies": true, "confidence": 0.8},
+        {"label": "bug", "applies": true, "confidence": 0.95},
+        {"label": "feature request", "applies": false, "confidence": 0.1},
+        {"label": "praise", "applies": false, "confidence": 0.0}
+    ]})
+    .to_string()
+}
+
+fn parse(content: &str, policy: MissingLabelPolicy) -> Result<Vec<LabelScore>, SecretaryError> {
+    parse_label_scores(content, &LABELS, policy)
+}
+
+#[test]
+fn the_prompt_lists_every_label_once() {
+    let server = MockServer::always(success(&answer()));
+
+    let scores: Vec<LabelScore> = Classifier::new()
+        .classify_multi_label(
+            &server.llm(),
+            &LABELS,
+            TICKET,
+            &["Tickets are in English".to_string()],
+        )
+        .unwrap();
+
+    assert_eq!(
+        scores,
+        vec![
+            LabelScore::new("billing", true, 0.8),
+            LabelScore::new("bug", true, 0.95),
+            LabelScore::new("feature request", false, 0.1),
+            LabelScore::new("praise", false, 0.0),
+        ]
+    );
+    let requests = server.requests();
+    assert_eq!(requests.len(), 1);
+    assert!(requests[0].is_json_mode());
+    let prompt: String = requests[0].prompt();
+    assert!(prompt.contains("- billing\n- bug\n- feature request\n- praise\n"));
+    assert!(prompt.contains("exactly one entry for every label"));
+    assert!(prompt.contains("Any number of labels"));
+    assert!(prompt.contains("Tickets are in English"));
+    assert!(prompt.contains(TICKET));
+}
+
+#[test]
+fn no_labels_send_no_request() {
+    let server = MockServer::always(success(&answer()));
+    let classifier: Classifier = Classifier::new();
+
+    let scores = classifier
+        .classify_multi_label(&server.llm(), &[], TICKET, &[])
+        .unwrap();
+    assert!(scores.is_empty());
+
+    let error = classifier
+        .classify(&server.llm(), &[], TICKET, &[])
+        .unwrap_err();
+    assert!(matches!(
+        secretary_error(&error),
+        SecretaryError::BuildRequestError(_)
+    ));
+    assert!(server.requests().is_empty());
+}
+
+#[test]
+fn classify_returns_the_most_likely_label() {
+    let server = MockServer::always(success(&answer()));
+
+    let best: LabelScore = Classifier::new()
+        .classify(&server.llm(), &LABELS, TICKET, &[])
+        .unwrap();
+
+    assert_eq!(best, LabelScore::new("bug", true, 0.95));
+    assert!(
+        server.requests()[0]
+            .prompt()
+            .contains("Exactly one label applies")
+    );
+}
+
+#[test]
+fn ties_go_to_the_first_listed_label() {
+    let content = json!([
+        {"label": "praise", "applies": true, "confidence": 0.5},
+        {"label": "bug", "applies": true, "confidence": 0.5}
+    ])
+    .to_string();
+    let server = MockServer::always(success(&content));
+
+    let best: LabelScore = Classifier::new()
+        .with_missing_labels(MissingLabelPolicy::Fill)
+        .classify(&server.llm(), &LABELS, TICKET, &[])
+        .unwrap();
+
+    assert_eq!(best.label, "bug");
+}
+
+#[test]
+fn missing_labels_fail_or_are_filled_per_policy() {
+    let content = r#"{"labels": [{"label": "bug", "applies": true, "confidence": 0.9}]}"#;
+
+    assert!(matches!(
+        parse(content, MissingLabelPolicy::Error),
+        Err(SecretaryError::MissingLabels { labels })
+            if labels == vec!["billing", "feature request", "praise"]
+    ));
+
+    let scores: Vec<LabelScore> = parse(content, MissingLabelPolicy::Fill).unwrap();
+    assert_eq!(scores.len(), LABELS.len());
+    assert_eq!(scores[0], LabelScore::new("billing", false, 0.0));
+    assert_eq!(scores[1], LabelScore::new("bug", true, 0.9));
+}
+
+#[test]
+fn duplicates_keep_their_first_entry() {
+    let content = json!([
+        {"label": "bug", "applies": true, "confidence": 0.9},
+        {"label": "billing", "applies": false, "confidence": 0.2},
+        {"label": " BUG ", "applies": false, "confidence": 0.1},
+        {"label": "feature request", "applies": false, "confidence": 0.3},
+        {"label": "praise.", "applies": false, "confidence": 0.0}
+    ])
+    .to_string();
+
+    let scores: Vec<LabelScore> = parse(&content, MissingLabelPolicy::Error).unwrap();
+
+    assert_eq!(scores[1], LabelScore::new("bug", true, 0.9));
+    assert_eq!(scores[3].label, "praise");
+}
+
+#[test]
+fn extra_labels_are_rejected() {
+    let content = json!([
+        {"label": "billing", "applies": true, "confidence": 0.8},
+        {"label": "bugs and crashes", "applies": true, "confidence": 0.9}
+    ])
+    .to_string();
+
+    match parse(&content, MissingLabelPolicy::Fill) {
+        Err(SecretaryError::ValueNotAllowed {
+            field_path,
+            value,
+            allowed,
+        }) => {
+            assert_eq!(field_path, "labels[1].label");
+            assert_eq!(value, "bugs and crashes");
+            assert_eq!(allowed, LABELS);
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn sloppy_scores_are_read_and_clamped() {
+    let content = json!({"labels": [
+        {"label": "billing", "applies": "yes", "confidence": "85%"},
+        {"label": "bug", "applies": true, "confidence": 1.7},
+        {"label": "feature request", "confidence": "0.7"},
+        {"label": "praise", "applies": "no", "confidence": -0.2}
+    ]})
+    .to_string();
+
+    let scores: Vec<LabelScore> = parse(&content, MissingLabelPolicy::Error).unwrap();
+
+    assert_eq!(
+        scores,
+        vec![
+            LabelScore::new("billing", true, 0.85),
+            LabelScore::new("bug", true, 1.0),
+            LabelScore::new("feature request", true, 0.7),
+            LabelScore::new("praise", false, 0.0),
+        ]
+    );
+
+    // Either of `applies` and `confidence` fills in the other
+    let content = r#"[{"label": "bug", "applies": true}, {"label": "praise", "confidence": 0.2}]"#;
+    let scores: Vec<LabelScore> = parse(content, MissingLabelPolicy::Fill).unwrap();
+    assert_eq!(scores[1], LabelScore::new("bug", true, 1.0));
+    assert_eq!(scores[3], LabelScore::new("praise", false, 0.2));
+}
+
+#[test]
+fn answers_that_are_not_entries_are_parse_errors() {
+    for content in [
+        "bug, billing",
+        r#"{"scores": {"bug": 0.9}}"#,
+        r#"[{"applies": true, "confidence": 0.9}]"#,
+        r#"["bug"]"#,
+    ] {
+        assert!(
+            matches!(
+                parse(content, MissingLabelPolicy::Fill),
+                Err(SecretaryError::JsonParsingError { raw_content, .. }) if raw_content == content
+            ),
+            "accepted: {}",
+            content
+        );
+    }
+
+    assert!(matches!(
+        parse(
+            "I'm sorry, but I can't help with that.",
+            MissingLabelPolicy::Fill
+        ),
+        Err(SecretaryError::Refused { .. })
+    ));
+}
+
+#[tokio::test]
+async fn async_classification_matches_the_sync_one() {
+    let server = MockServer::always(success(&answer()));
+    let classifier: Classifier = Classifier::new();
+
+    let scores: Vec<LabelScore> = classifier
+        .async_classify_multi_label(&server.llm(), &LABELS, TICKET, &[])
+        .await
+        .unwrap();
+    assert_eq!(scores[1], LabelScore::new("bug", true, 0.95));
+
+    let best: LabelScore = classifier
+        .async_classify(&server.llm(), &LABELS, TICKET, &[])
+        .await
+        .unwrap();
+    assert_eq!(best.label, "bug");
+}
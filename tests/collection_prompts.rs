@@ -0,0 +1,156 @@
+//! Version 3 prompts describe collections of nested Tasks once, whatever the instance holds.
+
+use std::collections::{BTreeMap, HashMap};
+
+use secretary::Task;
+use serde::{Deserialize, Serialize};
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct LineItem {
+    #[task(instruction = "Extract the product")]
+    pub product: String,
+    #[task(instruction = "Extract the quantity")]
+    pub quantity: u32,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[task(prompt_version = 3)]
+struct Order {
+    #[task(instruction = "Extract the customer")]
+    pub customer: String,
+    #[task(instruction = "Extract every line item")]
+    pub items: Vec<LineItem>,
+    #[task(instruction = "Extract the items by warehouse")]
+    pub by_warehouse: HashMap<String, LineItem>,
+    #[task(instruction = "Extract the items by aisle")]
+    pub by_aisle: BTreeMap<String, LineItem>,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct LegacyOrder {
+    #[task(instruction = "Extract every line item")]
+    pub items: Vec<LineItem>,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[task(prompt_version = 3, empty_defaults)]
+struct EmptyOrder {
+    #[task(instruction = "Extract every line item")]
+    pub items: Vec<LineItem>,
+    #[task(instruction = "Extract the gift wrapping, if any")]
+    pub wrapping: Option<LineItem>,
+}
+
+fn item(index: usize) -> LineItem {
+    LineItem {
+        product: format!("product {}", index),
+        quantity: index as u32,
+    }
+}
+
+fn order(len: usize) -> Order {
+    Order {
+        customer: "Ada".to_string(),
+        items: (0..len).map(item).collect(),
+        by_warehouse: (0..len)
+            .map(|index| (format!("warehouse {}", index), item(index)))
+            .collect(),
+        by_aisle: (0..len)
+            .map(|index| (format!("aisle {}", index), item(index)))
+            .collect(),
+    }
+}
+
+#[test]
+fn the_prompt_does_not_grow_with_the_collection() {
+    let prompts: Vec<String> = [0, 1, 200]
+        .into_iter()
+        .map(|len| order(len).get_system_prompt())
+        .collect();
+
+    assert_eq!(prompts[0], prompts[1]);
+    assert_eq!(prompts[0], prompts[2]);
+    assert_eq!(Order::prompt_version(), 3);
+}
+
+#[test]
+fn each_collection_is_described_by_one_element() {
+    let element: String = LineItem::default().get_system_prompt();
+
+    assert_eq!(
+        order(200).get_system_prompt(),
+        format!(
+            "customer: Extract the customer, JSON String\n\
+             items: Extract every line item, JSON Object(s) in a JSON Array\n\
+             \n\
+             --- items Collection (any number of items) ---\n\
+             {element}\n\
+             --- End of items Collection ---\n\
+             \n\
+             by_warehouse: Extract the items by warehouse, JSON Object\n\
+             \n\
+             --- by_warehouse HashMap (any number of entries) ---\n  \
+             Key '<key>': {element}\n\
+             --- End of by_warehouse HashMap ---\n\
+             \n\
+             by_aisle: Extract the items by aisle, JSON Object\n\
+             \n\
+             --- by_aisle BTreeMap (any number of entries) ---\n  \
+             Key '<key>': {element}\n\
+             --- End of by_aisle BTreeMap ---\n\
+             \n\
+             {template}",
+            element = element,
+            template = order(200).prompt_template(),
+        )
+    );
+}
+
+#[test]
+fn the_template_shows_one_element() {
+    let template: serde_json::Value = serde_json::from_str(&order(200).prompt_template()).unwrap();
+
+    assert_eq!(
+        template["items"],
+        serde_json::json!([{"product": "", "quantity": 0}])
+    );
+    assert_eq!(template["by_warehouse"].as_object().unwrap().len(), 1);
+    assert_eq!(template["by_aisle"]["key"]["product"], "");
+}
+
+#[test]
+fn version_1_still_renders_every_element() {
+    let short: LegacyOrder = LegacyOrder {
+        items: vec![item(0)],
+    };
+    let long: LegacyOrder = LegacyOrder {
+        items: (0..3).map(item).collect(),
+    };
+
+    assert!(long.get_system_prompt().len() > short.get_system_prompt().len());
+    assert_eq!(
+        long.get_system_prompt()
+            .matches("Extract the product")
+            .count(),
+        3
+    );
+}
+
+#[test]
+fn empty_defaults_still_show_absent_optional_tasks() {
+    let empty: EmptyOrder = EmptyOrder::new();
+    let full: EmptyOrder = EmptyOrder {
+        items: (0..50).map(item).collect(),
+        wrapping: None,
+    };
+
+    assert_eq!(empty.get_system_prompt(), full.get_system_prompt());
+    assert!(
+        empty
+            .get_system_prompt()
+            .contains("--- wrapping Optional Task (null when absent) ---")
+    );
+    let template: serde_json::Value = serde_json::from_str(&full.prompt_template()).unwrap();
+    assert_eq!(template["items"].as_array().unwrap().len(), 1);
+    assert_eq!(template["wrapping"]["quantity"], 0);
+}
@@ -0,0 +1,161 @@
+//! Compiled Tasks send the same requests as the Tasks they were compiled from.
+
+#![allow(clippy::needless_borrows_for_generic_args)]
+
+mod support;
+
+use secretary::compiled::{CompileOptions, CompiledTask};
+use secretary::traits::{AsyncGenerateData, GenerateData, Task};
+
+use support::fixtures::{field_result, success};
+use support::{ADA_JSON, MockServer, Person, TARGET, ada, fields_server};
+
+/// Returns the bodies a server received, sorted so concurrent requests can be compared.
+fn sorted_bodies(server: &MockServer) -> Vec<String> {
+    let mut bodies: Vec<String> = server
+        .requests()
+        .into_iter()
+        .map(|request| request.raw_body)
+        .collect();
+    bodies.sort();
+    bodies
+}
+
+fn instructions() -> Vec<String> {
+    vec!["Ages are whole years".to_string()]
+}
+
+fn compiled() -> CompiledTask<Person> {
+    Person::new().compile(CompileOptions::default().with_additional_instructions(instructions()))
+}
+
+#[test]
+fn single_requests_are_identical() {
+    let task_server = MockServer::always(success(ADA_JSON));
+    let compiled_server = MockServer::always(success(ADA_JSON));
+    let task_llm = task_server.llm();
+    let compiled_llm = compiled_server.llm();
+    let compiled = compiled();
+
+    let person: Person = task_llm
+        .generate_data(&Person::new(), TARGET, &instructions())
+        .unwrap();
+    assert_eq!(person, ada());
+    let person: Person = compiled_llm
+        .generate_data(&compiled, TARGET, &vec![])
+        .unwrap();
+    assert_eq!(person, ada());
+
+    let _: Person = task_llm
+        .force_generate_data(&Person::new(), TARGET, &instructions())
+        .unwrap();
+    let _: Person = compiled_llm
+        .force_generate_data(&compiled, TARGET, &vec![])
+        .unwrap();
+
+    let _ = task_llm
+        .generate_data_adaptive(&Person::new(), TARGET, &instructions())
+        .unwrap();
+    let _ = compiled_llm
+        .generate_data_adaptive(&compiled, TARGET, &vec![])
+        .unwrap();
+
+    let task_requests = task_server.requests();
+    let compiled_requests = compiled_server.requests();
+    assert_eq!(task_requests.len(), 3);
+    for (task_request, compiled_request) in task_requests.iter().zip(&compiled_requests) {
+        assert_eq!(task_request.raw_body, compiled_request.raw_body);
+    }
+}
+
+#[test]
+fn field_requests_are_identical() {
+    let task_server = fields_server(field_result("36"));
+    let compiled_server = fields_server(field_result("36"));
+
+    let person: Person = task_server
+        .llm()
+        .fields_generate_data(&Person::new(), TARGET, &instructions())
+        .unwrap();
+    assert_eq!(person, ada());
+    let person: Person = compiled_server
+        .llm()
+        .fields_generate_data(&compiled(), TARGET, &vec![])
+        .unwrap();
+    assert_eq!(person, ada());
+
+    assert_eq!(sorted_bodies(&task_server).len(), 2);
+    assert_eq!(sorted_bodies(&task_server), sorted_bodies(&compiled_server));
+}
+
+#[test]
+fn call_instructions_follow_the_compiled_ones() {
+    let task_server = MockServer::always(success(ADA_JSON));
+    let compiled_server = MockServer::always(success(ADA_JSON));
+    let extra = vec!["Names are given names".to_string()];
+    let all: Vec<String> = instructions().into_iter().chain(extra.clone()).collect();
+
+    let _: Person = task_server
+        .llm()
+        .generate_data(&Person::new(), TARGET, &all)
+        .unwrap();
+    let _: Person = compiled_server
+        .llm()
+        .generate_data(&compiled(), TARGET, &extra)
+        .unwrap();
+
+    assert_eq!(sorted_bodies(&task_server), sorted_bodies(&compiled_server));
+}
+
+#[test]
+fn targets_with_braces_and_placeholders_are_inserted_verbatim() {
+    let task_server = MockServer::always(success(ADA_JSON));
+    let compiled_server = MockServer::always(success(ADA_JSON));
+    let target = "Ada {age} is 36 {} years old.\n\nThis is the basis for generating a json:";
+
+    let _: Person = task_server
+        .llm()
+        .generate_data(&Person::new(), target, &instructions())
+        .unwrap();
+    let _: Person = compiled_server
+        .llm()
+        .generate_data(&compiled(), target, &vec![])
+        .unwrap();
+
+    assert_eq!(sorted_bodies(&task_server), sorted_bodies(&compiled_server));
+}
+
+#[tokio::test]
+async fn async_requests_are_identical() {
+    let task_server = fields_server(field_result("36"));
+    let compiled_server = fields_server(field_result("36"));
+    let compiled = compiled();
+
+    let _: Person = task_server
+        .llm()
+        .async_fields_generate_data(&Person::new(), TARGET, &instructions())
+        .await
+        .unwrap();
+    let _: Person = compiled_server
+        .llm()
+        .async_fields_generate_data(&compiled, TARGET, &vec![])
+        .await
+        .unwrap();
+    assert_eq!(sorted_bodies(&task_server), sorted_bodies(&compiled_server));
+
+    let task_server = MockServer::always(success(ADA_JSON));
+    let compiled_server = MockServer::always(success(ADA_JSON));
+    let person: Person = task_server
+        .llm()
+        .async_generate_data(&Person::new(), TARGET, &instructions())
+        .await
+        .unwrap();
+    assert_eq!(person, ada());
+    let person: Person = compiled_server
+        .llm()
+        .async_generate_data(&compiled, TARGET, &vec![])
+        .await
+        .unwrap();
+    assert_eq!(person, ada());
+    assert_eq!(sorted_bodies(&task_server), sorted_bodies(&compiled_server));
+}
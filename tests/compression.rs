@@ -0,0 +1,164 @@
+//! Compressed requests and responses, and the fallback when a server rejects them.
+
+mod support;
+
+use std::sync::Arc;
+
+use secretary::Task;
+use secretary::llm_providers::http::CompressionConfig;
+use secretary::llm_providers::openai::OpenAILLM;
+use secretary::metrics::{CountingSink, MetricEvent};
+use secretary::traits::{AsyncGenerateData, GenerateData};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use support::fixtures::success;
+use support::{ADA_JSON, MockResponse, MockServer, Person, TARGET, ada};
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Transcript {
+    #[task(instruction = "Extract the title of the meeting")]
+    pub title: String,
+    #[task(instruction = "Summarize the meeting")]
+    pub summary: String,
+}
+
+/// A target of about 1 MB with quotes, escapes and non-ASCII text.
+fn long_target() -> String {
+    "Ada said \"the figures\\totals are up\"\nand Grace agreed — 👍. ".repeat(16 * 1024)
+}
+
+fn compressing_llm(server: &MockServer, threshold: usize) -> OpenAILLM {
+    server
+        .llm()
+        .with_compression(CompressionConfig::default().with_gzip_requests_above(threshold))
+}
+
+#[test]
+fn requests_above_the_threshold_are_gzipped() {
+    let server = MockServer::always(success(ADA_JSON));
+    let sink = Arc::new(CountingSink::default());
+    let llm = compressing_llm(&server, 1024).with_metrics_sink(sink.clone());
+    let target: String = format!("{} {}", TARGET, "Ada likes maths. ".repeat(200));
+
+    let person: Person = llm.generate_data(&Person::new(), &target, vec![]).unwrap();
+
+    assert_eq!(person, ada());
+    let request = &server.requests()[0];
+    assert_eq!(request.headers["content-encoding"], "gzip");
+    assert!(request.prompt().contains(&target));
+    assert!(request.sent_bytes < request.raw_body.len());
+    assert_eq!(
+        sink.events()
+            .into_iter()
+            .filter(|event| matches!(event, MetricEvent::RequestCompressed { .. }))
+            .collect::<Vec<MetricEvent>>(),
+        vec![MetricEvent::RequestCompressed {
+            original_bytes: request.raw_body.len(),
+            compressed_bytes: request.sent_bytes,
+        }]
+    );
+}
+
+#[test]
+fn requests_below_the_threshold_are_sent_plain() {
+    let server = MockServer::always(success(ADA_JSON));
+    let sink = Arc::new(CountingSink::default());
+    let llm = compressing_llm(&server, 1024 * 1024).with_metrics_sink(sink.clone());
+
+    let _: Person = llm.generate_data(&Person::new(), TARGET, vec![]).unwrap();
+
+    let request = &server.requests()[0];
+    assert!(!request.headers.contains_key("content-encoding"));
+    assert_eq!(request.sent_bytes, request.raw_body.len());
+    assert_eq!(sink.count("request_compressed"), 0);
+}
+
+#[test]
+fn requests_are_sent_plain_by_default() {
+    let server = MockServer::always(success(ADA_JSON));
+
+    let _: Person = server
+        .llm()
+        .generate_data(&Person::new(), &long_target(), vec![])
+        .unwrap();
+
+    assert!(
+        !server.requests()[0]
+            .headers
+            .contains_key("content-encoding")
+    );
+}
+
+#[test]
+fn rejected_compressed_requests_are_resent_plain() {
+    let server = MockServer::start(|request| {
+        if request.headers.contains_key("content-encoding") {
+            MockResponse::new(415, json!({"error": {"message": "unsupported encoding"}}))
+        } else {
+            success(ADA_JSON)
+        }
+    });
+    let llm = compressing_llm(&server, 0);
+
+    let first: Person = llm.generate_data(&Person::new(), TARGET, vec![]).unwrap();
+    let second: Person = llm
+        .clone()
+        .generate_data(&Person::new(), TARGET, vec![])
+        .unwrap();
+
+    assert_eq!((first, second), (ada(), ada()));
+    let encodings: Vec<Option<String>> = server
+        .requests()
+        .iter()
+        .map(|request| request.headers.get("content-encoding").cloned())
+        .collect();
+    assert_eq!(encodings, vec![Some("gzip".to_string()), None, None]);
+}
+
+#[test]
+fn gzipped_responses_are_decoded() {
+    let server = MockServer::always(success(ADA_JSON).gzipped());
+
+    let person: Person = server
+        .llm()
+        .generate_data(&Person::new(), TARGET, vec![])
+        .unwrap();
+
+    assert_eq!(person, ada());
+    assert!(server.requests()[0].headers["accept-encoding"].contains("gzip"));
+}
+
+#[test]
+fn compressed_responses_are_not_requested_when_disabled() {
+    let server = MockServer::always(success(ADA_JSON));
+    let llm = server
+        .llm()
+        .with_compression(CompressionConfig::default().with_response_decompression(false));
+
+    let _: Person = llm.generate_data(&Person::new(), TARGET, vec![]).unwrap();
+
+    assert!(!server.requests()[0].headers.contains_key("accept-encoding"));
+}
+
+#[tokio::test]
+async fn megabyte_payloads_survive_compression_both_ways() {
+    let transcript = Transcript {
+        title: "Quarterly review".to_string(),
+        summary: long_target(),
+    };
+    let content: String = serde_json::to_string(&transcript).unwrap();
+    let server = MockServer::always(success(&content).gzipped());
+    let llm = compressing_llm(&server, 64 * 1024);
+
+    let result: Transcript = llm
+        .async_generate_data(&Transcript::new(), &long_target(), vec![])
+        .await
+        .unwrap();
+
+    assert_eq!(result, transcript);
+    let request = &server.requests()[0];
+    assert_eq!(request.headers["content-encoding"], "gzip");
+    assert!(request.prompt().contains(&long_target()));
+    assert!(request.sent_bytes * 10 < request.raw_body.len());
+}
@@ -0,0 +1,201 @@
+//! Self-consistency extraction: several choices in one request, voted on field by field.
+
+mod support;
+
+use secretary::SecretaryError;
+use secretary::Task;
+use secretary::metadata::{GenerationResult, GenerationWarning};
+use secretary::request::RequestOptions;
+use secretary::traits::{AsyncGenerateData, GenerateData};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use support::fixtures::{choices, success};
+use support::{ADA_JSON, MockServer, Person, TARGET, ada, assert_malformed_json, secretary_error};
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Address {
+    #[task(instruction = "Extract the street")]
+    pub street: String,
+    #[task(instruction = "Extract the city")]
+    pub city: String,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Customer {
+    #[task(instruction = "Extract the customer's name")]
+    pub name: String,
+    pub address: Address,
+}
+
+fn sampling() -> RequestOptions {
+    RequestOptions::default().with_temperature(0.7)
+}
+
+#[test]
+fn fields_take_the_majority_value_and_ties_the_first() {
+    let server = MockServer::always(choices(&[
+        r#"{"name": "Ada", "age": 36}"#,
+        r#"{"name": "Ava", "age": 63}"#,
+        r#"{"name": "Ada", "age": 63}"#,
+        r#"{"name": "Ava", "age": 36}"#,
+        r#"{"name": "Ada", "age": 40}"#,
+    ]));
+
+    let result: GenerationResult<Person> = server
+        .llm()
+        .generate_data_consistent(&Person::new(), TARGET, vec![], 5, &sampling())
+        .unwrap();
+
+    assert_eq!(result.data, ada());
+    assert_eq!(result.metadata.voted_choices, Some(5));
+    assert_eq!(result.metadata.field_agreement["name"], 0.6);
+    assert_eq!(result.metadata.field_agreement["age"], 0.4);
+    assert!(result.metadata.warnings.is_empty());
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].body["n"], json!(5));
+    assert_eq!(requests[0].body["temperature"], json!(0.7));
+}
+
+#[test]
+fn nested_task_fields_are_voted_on_separately() {
+    let server = MockServer::always(choices(&[
+        r#"{"name": "Ada", "address": {"street": "1 Main St", "city": "Londn"}}"#,
+        r#"{"name": "Ada", "address": {"street": "1 Main Street", "city": "London"}}"#,
+        r#"{"name": "Ada", "address": {"street": "1 Main St", "city": "London"}}"#,
+    ]));
+
+    let result: GenerationResult<Customer> = server
+        .llm()
+        .generate_data_consistent(&Customer::new(), TARGET, vec![], 3, &sampling())
+        .unwrap();
+
+    assert_eq!(result.data.address.street, "1 Main St");
+    assert_eq!(result.data.address.city, "London");
+    let agreement: Vec<(&str, f64)> = result
+        .metadata
+        .field_agreement
+        .iter()
+        .map(|(path, ratio)| (path.as_str(), *ratio))
+        .collect();
+    assert_eq!(
+        agreement,
+        vec![
+            ("address.city", 2.0 / 3.0),
+            ("address.street", 2.0 / 3.0),
+            ("name", 1.0),
+        ]
+    );
+}
+
+#[test]
+fn choices_that_do_not_parse_do_not_vote() {
+    let server = MockServer::always(choices(&[
+        "I could not find a person.",
+        r#"{"name": "Ava", "age": 36}"#,
+        r#"{"name": "Ada", "age": 36}"#,
+        r#"{"name": "Ada", "age": 36}"#,
+    ]));
+
+    let result: GenerationResult<Person> = server
+        .llm()
+        .generate_data_consistent(&Person::new(), TARGET, vec![], 4, &sampling())
+        .unwrap();
+
+    assert_eq!(result.data, ada());
+    assert_eq!(result.metadata.voted_choices, Some(3));
+    assert_eq!(result.metadata.field_agreement["name"], 2.0 / 3.0);
+    assert_eq!(result.metadata.field_agreement["age"], 1.0);
+}
+
+#[test]
+fn no_parsable_choice_reports_the_first_failure() {
+    let server = MockServer::always(choices(&["first", "second"]));
+
+    let result: Result<GenerationResult<Person>, _> =
+        server
+            .llm()
+            .generate_data_consistent(&Person::new(), TARGET, vec![], 2, &sampling());
+
+    assert_eq!(assert_malformed_json(result), "first");
+}
+
+#[test]
+fn a_single_choice_degrades_to_a_normal_extraction() {
+    let server = MockServer::always(success(ADA_JSON));
+
+    let result: GenerationResult<Person> = server
+        .llm()
+        .generate_data_consistent(&Person::new(), TARGET, vec![], 5, &sampling())
+        .unwrap();
+
+    assert_eq!(result.data, ada());
+    assert_eq!(result.metadata.voted_choices, Some(1));
+    assert_eq!(
+        result.metadata.warnings,
+        vec![GenerationWarning::ChoicesIgnored {
+            requested: 5,
+            returned: 1,
+        }]
+    );
+}
+
+#[test]
+fn sampling_without_a_positive_temperature_is_rejected_before_sending() {
+    let server = MockServer::always(success(ADA_JSON));
+    let llm = server.llm();
+
+    for options in [
+        RequestOptions::default(),
+        RequestOptions::default().with_temperature(0.0),
+    ] {
+        let error = llm
+            .generate_data_consistent(&Person::new(), TARGET, vec![], 3, &options)
+            .unwrap_err();
+        assert!(matches!(
+            secretary_error(&error),
+            SecretaryError::BuildRequestError(message) if message.contains("temperature")
+        ));
+    }
+    let error = llm
+        .generate_data_consistent(&Person::new(), TARGET, vec![], 0, &sampling())
+        .unwrap_err();
+    assert!(matches!(
+        secretary_error(&error),
+        SecretaryError::BuildRequestError(_)
+    ));
+
+    assert!(server.requests().is_empty());
+}
+
+#[test]
+fn other_extractions_do_not_send_n() {
+    let server = MockServer::always(success(ADA_JSON));
+
+    let _: Person = server
+        .llm()
+        .generate_data_with_options(&Person::new(), TARGET, vec![], &sampling())
+        .unwrap();
+
+    assert!(server.requests()[0].body.get("n").is_none());
+}
+
+#[tokio::test]
+async fn async_extraction_votes_on_the_choices() {
+    let server = MockServer::always(choices(&[
+        r#"{"name": "Ava", "age": 36}"#,
+        r#"{"name": "Ada", "age": 36}"#,
+        r#"{"name": "Ada", "age": 36}"#,
+    ]));
+
+    let result: GenerationResult<Person> = server
+        .llm()
+        .async_generate_data_consistent(&Person::new(), TARGET, vec![], 3, &sampling())
+        .await
+        .unwrap();
+
+    assert_eq!(result.data, ada());
+    assert_eq!(result.metadata.field_agreement["name"], 2.0 / 3.0);
+}
@@ -0,0 +1,218 @@
+//! Tasks convert to and from the `ContextualTask` envelope of earlier releases.
+
+mod support;
+
+use secretary::SecretaryError;
+use secretary::Task;
+use secretary::contextual::{ContextMeta, Contextual, from_contextual_json, to_contextual_json};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use support::{Person, ada};
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Address {
+    #[task(instruction = "Extract the city")]
+    pub city: String,
+    #[task(instruction = "Extract the postal code")]
+    pub postal_code: String,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Contact {
+    #[task(instruction = "Extract the name")]
+    pub name: String,
+    #[task(instruction = "Extract the age as a number")]
+    pub age: u32,
+    #[task(instruction = "Is the contact a customer")]
+    pub customer: bool,
+    #[task(instruction = "Extract the score, if given")]
+    pub score: Option<f64>,
+    #[task(instruction = "List the languages spoken")]
+    pub languages: Vec<String>,
+    pub address: Address,
+}
+
+fn contact() -> Contact {
+    Contact {
+        name: "Ada Lovelace".to_string(),
+        age: 36,
+        customer: true,
+        score: None,
+        languages: vec!["English".to_string(), "French".to_string()],
+        address: Address {
+            city: "London".to_string(),
+            postal_code: "01234".to_string(),
+        },
+    }
+}
+
+/// A blob written by the typed envelope, with JSON values under `data_structure`.
+const TYPED_FIXTURE: &str = r#"{
+    "reasoning": "All fields are stated in the signature.",
+    "notes": ["No score was given"],
+    "data_structure": {
+        "name": "Ada Lovelace",
+        "age": 36,
+        "customer": true,
+        "score": null,
+        "languages": ["English", "French"],
+        "address": {"city": "London", "postal_code": "01234"}
+    },
+    "content": "Ada Lovelace, 36, London 01234. Customer. Speaks English and French.",
+    "batch": 7
+}"#;
+
+/// A blob of the stringly era, where `data_structure` was a `HashMap<String, String>`.
+const STRINGLY_FIXTURE: &str = r#"{
+    "reasoning": "All fields are stated in the signature.",
+    "notes": "No score was given",
+    "data_structure": {
+        "name": "Ada Lovelace",
+        "age": "36",
+        "customer": "yes",
+        "score": "N/A",
+        "languages": "English, French",
+        "address": "{\"city\": \"London\", \"postal_code\": \"01234\"}"
+    }
+}"#;
+
+/// A stringly blob that flattened nested fields into dotted keys.
+const DOTTED_FIXTURE: &str = r#"{
+    "reasoning": "",
+    "notes": [],
+    "data_structure": {
+        "name": "Ada Lovelace",
+        "age": "36",
+        "customer": "true",
+        "score": "",
+        "languages": "[\"English\", \"French\"]",
+        "address.city": "London",
+        "address.postal_code": "01234"
+    }
+}"#;
+
+fn fixture(text: &str) -> Value {
+    serde_json::from_str(text).unwrap()
+}
+
+#[test]
+fn typed_blobs_round_trip() {
+    let legacy: Value = fixture(TYPED_FIXTURE);
+
+    let (data, meta): (Contact, ContextMeta) = from_contextual_json(&legacy).unwrap();
+    assert_eq!(data, contact());
+    assert_eq!(meta.reasoning, "All fields are stated in the signature.");
+    assert_eq!(meta.notes, vec!["No score was given"]);
+    assert_eq!(
+        meta.content.as_deref(),
+        Some("Ada Lovelace, 36, London 01234. Customer. Speaks English and French.")
+    );
+    assert_eq!(meta.extra.get("batch"), Some(&json!(7)));
+
+    assert_eq!(to_contextual_json(&data, &meta), legacy);
+}
+
+#[test]
+fn stringly_blobs_are_coerced() {
+    let (data, meta): (Contact, ContextMeta) =
+        from_contextual_json(&fixture(STRINGLY_FIXTURE)).unwrap();
+
+    assert_eq!(data, contact());
+    assert_eq!(meta.notes, vec!["No score was given"]);
+    assert_eq!(meta.content, None);
+}
+
+#[test]
+fn stringly_blobs_are_written_back_typed() {
+    let (data, meta): (Contact, ContextMeta) =
+        from_contextual_json(&fixture(STRINGLY_FIXTURE)).unwrap();
+
+    let written: Value = to_contextual_json(&data, &meta);
+    assert_eq!(written["data_structure"]["age"], json!(36));
+    assert_eq!(
+        written["data_structure"]["address"]["postal_code"],
+        json!("01234")
+    );
+    assert_eq!(written["notes"], json!(["No score was given"]));
+    assert!(written.get("content").is_none());
+
+    let (again, _): (Contact, ContextMeta) = from_contextual_json(&written).unwrap();
+    assert_eq!(again, data);
+}
+
+#[test]
+fn dotted_keys_fill_nested_tasks() {
+    let (data, meta): (Contact, ContextMeta) =
+        from_contextual_json(&fixture(DOTTED_FIXTURE)).unwrap();
+
+    assert_eq!(data, contact());
+    assert_eq!(meta, ContextMeta::default());
+}
+
+#[test]
+fn data_structure_stored_as_text_is_parsed() {
+    let legacy: Value = json!({
+        "reasoning": "Stated directly.",
+        "notes": [],
+        "data_structure": r#"{"name": "Ada", "age": 36}"#
+    });
+
+    let (person, _): (Person, ContextMeta) = from_contextual_json(&legacy).unwrap();
+    assert_eq!(person, ada());
+}
+
+#[test]
+fn new_extractions_round_trip_through_the_envelope() {
+    let meta = ContextMeta {
+        reasoning: "Extracted with generate_data.".to_string(),
+        notes: vec!["migrated".to_string()],
+        content: Some("Ada is 36 years old.".to_string()),
+        ..ContextMeta::default()
+    };
+    let contextual = Contextual { data: ada(), meta };
+
+    let value: Value = contextual.clone().into();
+    assert_eq!(value["data_structure"], json!({"name": "Ada", "age": 36}));
+
+    let read: Contextual<Person> = Contextual::try_from(&value).unwrap();
+    assert_eq!(read, contextual);
+}
+
+#[test]
+fn values_that_are_not_envelopes_are_rejected() {
+    let cases: Vec<Value> = vec![
+        json!([1, 2]),
+        json!({"reasoning": "no data"}),
+        json!({"data_structure": 42}),
+        json!({"data_structure": "not json"}),
+    ];
+
+    for value in cases {
+        let error = from_contextual_json::<Person>(&value).unwrap_err();
+        assert!(
+            matches!(error, SecretaryError::InvalidContextualJson { .. }),
+            "{}: {}",
+            value,
+            error
+        );
+    }
+}
+
+#[test]
+fn fields_that_cannot_be_coerced_are_reported() {
+    let legacy: Value = json!({
+        "reasoning": "",
+        "notes": [],
+        "data_structure": {"name": "Ada", "age": "thirty-six"}
+    });
+
+    match from_contextual_json::<Person>(&legacy).unwrap_err() {
+        SecretaryError::FieldDeserializationError(error) => {
+            assert_eq!(error.failed_fields, vec!["age"]);
+            assert_eq!(error.successful_fields, vec!["name"]);
+            assert_eq!(error.raw_field_contents["age"], "thirty-six");
+        }
+        other => panic!("unexpected error: {}", other),
+    }
+}
@@ -0,0 +1,262 @@
+//! Every call runs under a request ID that reaches its metrics, dead letter, error, metadata
+//! and the provider.
+
+mod support;
+
+use std::sync::{Arc, Mutex};
+
+use secretary::SecretaryError;
+use secretary::correlation::{CLIENT_REQUEST_ID_HEADER, TracedError, request_id_of};
+use secretary::deadletter::{DeadLetter, DeadLetterSink};
+use secretary::metrics::{CountingSink, MetricEvent};
+use secretary::request::RequestOptions;
+use secretary::traits::{AsyncGenerateData, GenerateData};
+
+use support::fixtures::{field_result, success};
+use support::{
+    ADA_JSON, BoxedError, MockServer, Person, TARGET, assert_malformed_json, fields_server,
+};
+
+/// A dead letter sink that keeps every dead letter in memory.
+#[derive(Debug, Default)]
+struct MemorySink {
+    dead_letters: Mutex<Vec<DeadLetter>>,
+}
+
+impl MemorySink {
+    fn dead_letters(&self) -> Vec<DeadLetter> {
+        self.dead_letters.lock().unwrap().clone()
+    }
+}
+
+impl DeadLetterSink for MemorySink {
+    fn capture(&self, item: DeadLetter) -> Result<(), BoxedError> {
+        self.dead_letters.lock().unwrap().push(item);
+        Ok(())
+    }
+}
+
+/// Returns the `TracedError` behind a boxed error.
+fn traced(error: &BoxedError) -> &TracedError {
+    error
+        .downcast_ref::<TracedError>()
+        .unwrap_or_else(|| panic!("not a TracedError: {}", error))
+}
+
+/// Returns the names of events, in order.
+fn names(events: &[MetricEvent]) -> Vec<&'static str> {
+    events.iter().map(MetricEvent::name).collect()
+}
+
+#[test]
+fn a_failing_call_shares_its_id_with_the_error_dead_letter_and_metrics() {
+    let server = MockServer::always(
+        success("I could not find a person.").with_header("x-request-id", "req_mock_1"),
+    );
+    let metrics = Arc::new(CountingSink::default());
+    let dead_letters = Arc::new(MemorySink::default());
+    let llm = server
+        .llm()
+        .with_metrics_sink(metrics.clone())
+        .with_dead_letter_sink(dead_letters.clone())
+        .with_traced_errors(true);
+
+    let error: BoxedError = llm
+        .generate_data(&Person::new(), TARGET, vec![])
+        .unwrap_err();
+
+    let traced: &TracedError = traced(&error);
+    let request_id: &str = &traced.request_id;
+    assert_eq!(request_id.len(), 36);
+    assert_eq!(traced.provider_request_id.as_deref(), Some("req_mock_1"));
+    assert!(matches!(
+        traced.secretary_error(),
+        Some(SecretaryError::JsonParsingError { .. })
+    ));
+    assert_eq!(request_id_of(error.as_ref()), Some(request_id));
+    assert!(error.to_string().contains(request_id));
+
+    let dead_letter: DeadLetter = dead_letters.dead_letters().remove(0);
+    assert_eq!(dead_letter.request_id.as_deref(), Some(request_id));
+    assert_eq!(
+        dead_letter.provider_request_id.as_deref(),
+        Some("req_mock_1")
+    );
+    // The dead letter describes the error itself, not the wrapper
+    assert!(!dead_letter.error[0].contains(request_id));
+
+    assert_eq!(
+        names(&metrics.events_for_request(request_id)),
+        vec!["request_started", "request_completed"]
+    );
+    assert_eq!(metrics.request_ids(), vec![request_id.to_string()]);
+
+    assert_eq!(
+        server.requests()[0].headers[CLIENT_REQUEST_ID_HEADER],
+        request_id
+    );
+}
+
+#[test]
+fn every_call_gets_a_new_id_unless_the_caller_sets_one() {
+    let server = MockServer::always(success("no JSON here"));
+    let dead_letters = Arc::new(MemorySink::default());
+    let llm = server.llm().with_dead_letter_sink(dead_letters.clone());
+
+    // Errors are left unwrapped by default
+    assert_malformed_json(llm.generate_data(&Person::new(), TARGET, vec![]));
+    assert_malformed_json(llm.force_generate_data(&Person::new(), TARGET, vec![]));
+    assert_malformed_json(llm.generate_data_with_options(
+        &Person::new(),
+        TARGET,
+        vec![],
+        &RequestOptions::default().with_request_id("order-17"),
+    ));
+
+    let ids: Vec<String> = dead_letters
+        .dead_letters()
+        .into_iter()
+        .map(|dead_letter| dead_letter.request_id.unwrap())
+        .collect();
+    assert_ne!(ids[0], ids[1]);
+    assert_eq!(ids[2], "order-17");
+    let sent: Vec<String> = server
+        .requests()
+        .iter()
+        .map(|request| request.headers[CLIENT_REQUEST_ID_HEADER].clone())
+        .collect();
+    assert_eq!(sent, ids);
+}
+
+#[test]
+fn metadata_holds_both_ids() {
+    let server = MockServer::always(success(ADA_JSON).with_header("x-request-id", "req_mock_2"));
+
+    let result = server
+        .llm()
+        .generate_data_adaptive_with_options(
+            &Person::new(),
+            TARGET,
+            vec![],
+            &RequestOptions::default().with_request_id("order-18"),
+        )
+        .unwrap();
+
+    assert_eq!(result.metadata.request_id.as_deref(), Some("order-18"));
+    assert_eq!(
+        result.metadata.provider_request_id.as_deref(),
+        Some("req_mock_2")
+    );
+
+    let result = server
+        .llm()
+        .generate_data_adaptive(&Person::new(), TARGET, vec![])
+        .unwrap();
+    assert!(result.metadata.request_id.is_some());
+}
+
+#[test]
+fn field_requests_get_child_ids() {
+    let server = fields_server(field_result("thirty-six"));
+    let metrics = Arc::new(CountingSink::default());
+    let dead_letters = Arc::new(MemorySink::default());
+    let llm = server
+        .llm()
+        .with_metrics_sink(metrics.clone())
+        .with_dead_letter_sink(dead_letters.clone())
+        .with_traced_errors(true);
+
+    let error: BoxedError = llm
+        .fields_generate_data_with_options(
+            &Person::new(),
+            TARGET,
+            vec![],
+            &RequestOptions::default().with_request_id("call-1"),
+        )
+        .unwrap_err();
+
+    assert_eq!(traced(&error).request_id, "call-1");
+    assert_eq!(traced(&error).provider_request_id, None);
+    assert_eq!(
+        dead_letters.dead_letters()[0].request_id.as_deref(),
+        Some("call-1")
+    );
+
+    let mut sent: Vec<String> = server
+        .requests()
+        .iter()
+        .map(|request| request.headers[CLIENT_REQUEST_ID_HEADER].clone())
+        .collect();
+    sent.sort();
+    assert_eq!(sent, vec!["call-1/age", "call-1/name"]);
+
+    let mut request_ids: Vec<String> = metrics.request_ids();
+    request_ids.sort();
+    assert_eq!(request_ids, sent);
+    assert_eq!(metrics.events_for_request("call-1").len(), 4);
+    assert_eq!(
+        names(&metrics.events_for_request("call-1/age")),
+        vec!["request_started", "request_completed"]
+    );
+}
+
+#[test]
+fn request_ids_must_be_valid_header_values() {
+    let server = MockServer::always(success(ADA_JSON));
+
+    let error: BoxedError = server
+        .llm()
+        .generate_data_with_options(
+            &Person::new(),
+            TARGET,
+            vec![],
+            &RequestOptions::default().with_request_id("line\nbreak"),
+        )
+        .unwrap_err();
+
+    assert!(matches!(
+        error.downcast_ref::<SecretaryError>(),
+        Some(SecretaryError::BuildRequestError(_))
+    ));
+    assert!(server.requests().is_empty());
+}
+
+#[tokio::test]
+async fn async_calls_are_traced_the_same_way() {
+    let server = MockServer::always(
+        success("I could not find a person.").with_header("x-request-id", "req_mock_3"),
+    );
+    let metrics = Arc::new(CountingSink::default());
+    let dead_letters = Arc::new(MemorySink::default());
+    let llm = server
+        .llm()
+        .with_metrics_sink(metrics.clone())
+        .with_dead_letter_sink(dead_letters.clone())
+        .with_traced_errors(true);
+
+    let error: BoxedError = llm
+        .async_generate_data(&Person::new(), TARGET, vec![])
+        .await
+        .unwrap_err();
+
+    let request_id: String = traced(&error).request_id.clone();
+    assert_eq!(
+        traced(&error).provider_request_id.as_deref(),
+        Some("req_mock_3")
+    );
+    assert_eq!(
+        dead_letters.dead_letters()[0].request_id.as_deref(),
+        Some(request_id.as_str())
+    );
+    assert_eq!(metrics.events_for_request(&request_id).len(), 2);
+
+    let error: BoxedError = llm
+        .async_force_generate_data(&Person::new(), TARGET, vec![])
+        .await
+        .unwrap_err();
+    assert_ne!(request_id_of(error.as_ref()), Some(request_id.as_str()));
+    assert_eq!(
+        server.requests()[1].headers[CLIENT_REQUEST_ID_HEADER],
+        traced(&error).request_id
+    );
+}
@@ -0,0 +1,177 @@
+//! Calls sent with their own API key, and API keys rotated while calls are in flight.
+
+mod support;
+
+use std::sync::Arc;
+
+use secretary::credentials::{Credentials, key_id};
+use secretary::llm_providers::azure::AzureOpenAILLM;
+use secretary::metrics::{CountingSink, MetricEvent};
+use secretary::request::RequestOptions;
+use secretary::traits::{AsyncGenerateData, GenerateData};
+use serde_json::json;
+
+use support::fixtures::{field_result, success};
+use support::{ADA_JSON, MockServer, Person, TARGET, ada};
+
+/// A server answering with a person named after the `Authorization` header of the request.
+fn echo_server() -> MockServer {
+    MockServer::start(|request| {
+        let authorization: &str = request
+            .headers
+            .get("authorization")
+            .map(String::as_str)
+            .unwrap_or_default();
+        success(&json!({"name": authorization, "age": 36}).to_string())
+    })
+}
+
+fn tenant(index: usize) -> RequestOptions {
+    RequestOptions::default().with_credentials(
+        Credentials::new(&format!("sk-tenant-{}", index)).with_tenant(&format!("tenant-{}", index)),
+    )
+}
+
+fn authorizations(server: &MockServer) -> Vec<String> {
+    server
+        .requests()
+        .iter()
+        .map(|request| request.headers["authorization"].clone())
+        .collect()
+}
+
+#[test]
+fn interleaved_calls_on_one_provider_carry_their_own_keys() {
+    let server = echo_server();
+    let llm = server.llm();
+
+    std::thread::scope(|scope| {
+        for index in 0..8 {
+            let llm = &llm;
+            scope.spawn(move || {
+                for _ in 0..3 {
+                    let person: Person = llm
+                        .generate_data_with_options(&Person::new(), TARGET, vec![], &tenant(index))
+                        .unwrap();
+                    assert_eq!(person.name, format!("Bearer sk-tenant-{}", index));
+                }
+            });
+        }
+    });
+
+    let person: Person = llm.generate_data(&Person::new(), TARGET, vec![]).unwrap();
+    assert_eq!(person.name, "Bearer test-key");
+    assert_eq!(server.requests().len(), 25);
+}
+
+#[tokio::test]
+async fn interleaved_async_calls_carry_their_own_keys() {
+    let server = echo_server();
+    let llm = server.llm();
+    let task = Person::new();
+    let (first, second) = (tenant(1), tenant(2));
+
+    let (first, second): (Person, Person) = tokio::try_join!(
+        llm.async_generate_data_with_options(&task, TARGET, vec![], &first),
+        llm.async_generate_data_with_options(&task, TARGET, vec![], &second),
+    )
+    .unwrap();
+
+    assert_eq!(first.name, "Bearer sk-tenant-1");
+    assert_eq!(second.name, "Bearer sk-tenant-2");
+}
+
+#[test]
+fn every_field_request_carries_the_call_key() {
+    let server = MockServer::by_instruction(
+        vec![
+            ("Extract the person's name", field_result("Ada")),
+            ("Extract the age as a number", field_result("36")),
+        ],
+        success(ADA_JSON),
+    );
+
+    let person: Person = server
+        .llm()
+        .fields_generate_data_with_options(&Person::new(), TARGET, vec![], &tenant(3))
+        .unwrap();
+
+    assert_eq!(person, ada());
+    assert_eq!(authorizations(&server), vec!["Bearer sk-tenant-3"; 2]);
+}
+
+#[test]
+fn metrics_record_the_tenant_and_a_hash_of_the_key() {
+    let server = MockServer::always(success(ADA_JSON));
+    let sink = Arc::new(CountingSink::default());
+    let llm = server.llm().with_metrics_sink(sink.clone());
+
+    let _: Person = llm
+        .generate_data_with_options(&Person::new(), TARGET, vec![], &tenant(4))
+        .unwrap();
+    let _: Person = llm.generate_data(&Person::new(), TARGET, vec![]).unwrap();
+
+    let events: Vec<MetricEvent> = sink.events();
+    assert_eq!(
+        events[0],
+        MetricEvent::CredentialsOverridden {
+            key_id: key_id("sk-tenant-4"),
+            tenant: Some("tenant-4".to_string()),
+        }
+    );
+    assert_eq!(sink.count("credentials_overridden"), 1);
+    assert!(!format!("{:?}", events).contains("sk-tenant-4"));
+}
+
+#[test]
+fn rotated_keys_are_used_by_fresh_calls_and_clones() {
+    let server = echo_server();
+    let llm = server.llm();
+    let clone = llm.clone();
+
+    let before: Person = llm.generate_data(&Person::new(), TARGET, vec![]).unwrap();
+    llm.rotate_api_key("sk-rotated");
+    let after: Person = clone.generate_data(&Person::new(), TARGET, vec![]).unwrap();
+
+    assert_eq!(before.name, "Bearer test-key");
+    assert_eq!(after.name, "Bearer sk-rotated");
+    assert!(!format!("{:?}", llm).contains("sk-rotated"));
+}
+
+#[test]
+fn rotation_during_concurrent_calls_sends_whole_keys() {
+    let server = echo_server();
+    let llm = server.llm();
+
+    std::thread::scope(|scope| {
+        for _ in 0..4 {
+            let llm = &llm;
+            scope.spawn(move || {
+                for _ in 0..5 {
+                    let person: Person = llm.generate_data(&Person::new(), TARGET, vec![]).unwrap();
+                    assert!(
+                        ["Bearer test-key", "Bearer sk-rotated"].contains(&person.name.as_str()),
+                        "{}",
+                        person.name
+                    );
+                }
+            });
+        }
+        llm.rotate_api_key("sk-rotated");
+    });
+
+    let person: Person = llm.generate_data(&Person::new(), TARGET, vec![]).unwrap();
+    assert_eq!(person.name, "Bearer sk-rotated");
+}
+
+#[test]
+fn azure_sends_the_call_key_as_it_is() {
+    let server = echo_server();
+    let llm = AzureOpenAILLM::new(server.address(), "azure-key", "gpt-4o", "2024-06-01");
+
+    let person: Person = llm
+        .generate_data_with_options(&Person::new(), TARGET, vec![], &tenant(5))
+        .unwrap();
+
+    assert_eq!(person.name, "sk-tenant-5");
+}
@@ -0,0 +1,204 @@
+//! Failed extractions are written to the provider's dead letter sink and can be replayed.
+
+mod support;
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use secretary::SecretaryError;
+use secretary::Task;
+use secretary::deadletter::{DeadLetter, FileDeadLetterSink, replay_dead_letter};
+use secretary::metrics::{CountingSink, GenerationMode, MetricEvent};
+use secretary::traits::{AsyncGenerateData, GenerateData};
+use serde::{Deserialize, Serialize};
+
+use support::fixtures::{field_result, success};
+use support::{
+    ADA_JSON, MockServer, Person, TARGET, ada, assert_malformed_json, fields_server,
+    secretary_error,
+};
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Company {
+    #[task(instruction = "Extract the company name")]
+    pub name: String,
+}
+
+/// Returns an empty directory of its own under the system's temporary directory.
+fn temp_dir(name: &str) -> PathBuf {
+    let directory: PathBuf = std::env::temp_dir().join(format!(
+        "secretary-dead-letters-{}-{}",
+        name,
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&directory);
+    directory
+}
+
+/// Returns the dead letters written to a directory, oldest first.
+fn dead_letters(directory: &PathBuf) -> Vec<(PathBuf, DeadLetter)> {
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(directory)
+        .map(|entries| entries.map(|entry| entry.unwrap().path()).collect())
+        .unwrap_or_default();
+    paths.sort();
+    paths
+        .into_iter()
+        .map(|path| {
+            let dead_letter: DeadLetter = DeadLetter::load(&path).unwrap();
+            (path, dead_letter)
+        })
+        .collect()
+}
+
+#[test]
+fn a_malformed_response_is_written_with_its_input_and_content() {
+    let directory: PathBuf = temp_dir("malformed");
+    let sink = Arc::new(FileDeadLetterSink::new(&directory));
+    let server = MockServer::always(success("I could not find a person."));
+    let llm = server.llm().with_dead_letter_sink(sink.clone());
+
+    let result: Result<Person, _> =
+        llm.generate_data(&Person::new(), TARGET, vec!["Be brief".to_string()]);
+
+    assert_eq!(assert_malformed_json(result), "I could not find a person.");
+    assert_eq!(sink.captured(), 1);
+    let letters = dead_letters(&directory);
+    assert_eq!(letters.len(), 1);
+    let (path, letter) = &letters[0];
+    assert!(path.to_str().unwrap().ends_with("-Person.json"));
+    assert_eq!(letter.task, std::any::type_name::<Person>());
+    assert_eq!(letter.mode, GenerationMode::Json);
+    assert_eq!(letter.input, TARGET);
+    assert_eq!(letter.additional_instructions, vec!["Be brief".to_string()]);
+    assert!(letter.error[0].contains("malformed json"));
+    assert_eq!(
+        letter.raw_response.as_deref(),
+        Some("I could not find a person.")
+    );
+    assert!(letter.captured_at > 0);
+}
+
+#[test]
+fn distributed_field_failures_are_written_with_each_field() {
+    let directory: PathBuf = temp_dir("distributed");
+    let sink = Arc::new(FileDeadLetterSink::new(&directory));
+    let server = fields_server(field_result("thirty-six"));
+    let llm = server.llm().with_dead_letter_sink(sink.clone());
+
+    let error = llm
+        .fields_generate_data::<Person>(&Person::new(), TARGET, vec![])
+        .unwrap_err();
+
+    assert!(matches!(
+        secretary_error(&error),
+        SecretaryError::FieldDeserializationError(_)
+    ));
+    let letters = dead_letters(&directory);
+    assert_eq!(letters.len(), 1);
+    let letter = &letters[0].1;
+    assert_eq!(letter.mode, GenerationMode::Distributed);
+    assert_eq!(letter.failed_fields, vec!["age"]);
+    assert_eq!(letter.raw_field_contents["age"], "thirty-six");
+    assert_eq!(letter.raw_field_contents["name"], "Ada");
+}
+
+#[test]
+fn a_dead_letter_replays_against_a_healthy_provider() {
+    let directory: PathBuf = temp_dir("replay");
+    let sink = Arc::new(FileDeadLetterSink::new(&directory));
+    let failing = MockServer::always(success("not json"));
+    let _ = failing
+        .llm()
+        .with_dead_letter_sink(sink.clone())
+        .generate_data::<Person>(&Person::new(), TARGET, vec!["Be brief".to_string()]);
+    let (path, _) = dead_letters(&directory).remove(0);
+
+    let healthy = MockServer::always(success(ADA_JSON));
+    let person: Person = replay_dead_letter(&healthy.llm(), &path).unwrap();
+
+    assert_eq!(person, ada());
+    let prompt: String = healthy.requests()[0].prompt();
+    assert!(prompt.contains(TARGET));
+    assert!(prompt.contains("Be brief"));
+}
+
+#[test]
+fn a_dead_letter_of_another_task_is_not_replayed() {
+    let directory: PathBuf = temp_dir("other-task");
+    let sink = Arc::new(FileDeadLetterSink::new(&directory));
+    let server = MockServer::always(success("not json"));
+    let _ = server
+        .llm()
+        .with_dead_letter_sink(sink.clone())
+        .force_generate_data::<Person>(&Person::new(), TARGET, vec![]);
+    let (path, letter) = dead_letters(&directory).remove(0);
+    assert_eq!(letter.mode, GenerationMode::Force);
+
+    let error = replay_dead_letter::<Company, _>(&server.llm(), &path).unwrap_err();
+
+    assert!(matches!(
+        secretary_error(&error),
+        SecretaryError::BuildRequestError(_)
+    ));
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[test]
+fn a_sink_that_cannot_write_keeps_the_original_error() {
+    let directory: PathBuf = temp_dir("unwritable");
+    // A file where the directory should be
+    std::fs::write(&directory, "").unwrap();
+    let sink = Arc::new(FileDeadLetterSink::new(&directory));
+    let server = MockServer::always(success("not json"));
+    let metrics = Arc::new(CountingSink::default());
+    let llm = server
+        .llm()
+        .with_dead_letter_sink(sink.clone())
+        .with_metrics_sink(metrics.clone());
+
+    let result: Result<Person, _> = llm.generate_data(&Person::new(), TARGET, vec![]);
+
+    assert_eq!(assert_malformed_json(result), "not json");
+    assert_eq!(sink.captured(), 0);
+    let failures: Vec<MetricEvent> = metrics
+        .events()
+        .into_iter()
+        .filter(|event| matches!(event, MetricEvent::DeadLetterFailed { .. }))
+        .collect();
+    assert_eq!(failures.len(), 1);
+    let MetricEvent::DeadLetterFailed { error } = &failures[0] else {
+        unreachable!()
+    };
+    assert!(error.contains("could not write a dead letter"), "{}", error);
+    std::fs::remove_file(&directory).unwrap();
+}
+
+#[test]
+fn successful_calls_are_not_captured() {
+    let directory: PathBuf = temp_dir("success");
+    let sink = Arc::new(FileDeadLetterSink::new(&directory));
+    let server = MockServer::always(success(ADA_JSON));
+    let llm = server.llm().with_dead_letter_sink(sink.clone());
+
+    let _: Person = llm.generate_data(&Person::new(), TARGET, vec![]).unwrap();
+
+    assert_eq!(sink.captured(), 0);
+    assert!(!directory.exists());
+}
+
+#[tokio::test]
+async fn async_failures_are_captured() {
+    let directory: PathBuf = temp_dir("async");
+    let sink = Arc::new(FileDeadLetterSink::new(&directory));
+    let server = MockServer::always(success("not json"));
+    let llm = server.llm().with_dead_letter_sink(sink.clone());
+
+    let result: Result<Person, _> = llm
+        .async_generate_data(&Person::new(), TARGET, vec![])
+        .await;
+
+    assert_eq!(assert_malformed_json(result), "not json");
+    let letters = dead_letters(&directory);
+    assert_eq!(letters.len(), 1);
+    assert_eq!(letters[0].1.input, TARGET);
+}
@@ -0,0 +1,109 @@
+//! A deadline bounds the field requests of distributed generation, failing the call or
+//! reporting the fields that did not complete in time.
+
+#![allow(clippy::needless_borrows_for_generic_args)]
+
+mod support;
+
+use std::time::{Duration, Instant};
+
+use secretary::SecretaryError;
+use secretary::Task;
+use secretary::deadline::Deadline;
+use secretary::partial::PartialData;
+use secretary::request::RequestOptions;
+use secretary::traits::{AsyncGenerateData, GenerateData};
+use serde::{Deserialize, Serialize};
+
+use support::fixtures::field_result;
+use support::{MockServer, secretary_error};
+
+#[derive(Task, Serialize, Deserialize, Debug)]
+struct Contact {
+    #[task(instruction = "Extract the name")]
+    pub name: String,
+    #[task(instruction = "Extract the email address")]
+    pub email: Option<String>,
+}
+
+/// A server that answers the name at once and the email address after five seconds.
+fn slow_email_server() -> MockServer {
+    MockServer::start(|request| {
+        if request.prompt().contains("Extract the email address") {
+            std::thread::sleep(Duration::from_secs(5));
+            field_result("ada@example.com")
+        } else {
+            field_result("Ada")
+        }
+    })
+}
+
+fn options() -> RequestOptions {
+    RequestOptions::default().with_deadline(Deadline::after(Duration::from_millis(500)))
+}
+
+#[test]
+fn the_call_fails_once_the_deadline_passes() {
+    let server = slow_email_server();
+
+    let started = Instant::now();
+    let error = server
+        .llm()
+        .fields_generate_data_with_options(
+            &Contact::new(),
+            "Ada, ada@example.com",
+            &vec![],
+            &options(),
+        )
+        .unwrap_err();
+
+    assert!(started.elapsed() < Duration::from_secs(2));
+    match secretary_error(&error) {
+        SecretaryError::DeadlineExceeded { incomplete_fields } => {
+            assert_eq!(incomplete_fields, &vec!["email"]);
+        }
+        other => panic!("unexpected error: {}", other),
+    }
+}
+
+#[test]
+fn partial_results_keep_the_fields_that_completed_in_time() {
+    let server = slow_email_server();
+
+    let started = Instant::now();
+    let partial: PartialData<Contact> = server
+        .llm()
+        .fields_generate_partial_data_with_options(
+            &Contact::new(),
+            "Ada, ada@example.com",
+            &vec![],
+            &options(),
+        )
+        .unwrap();
+
+    assert!(started.elapsed() < Duration::from_secs(2));
+    assert_eq!(partial.data.name, "Ada");
+    assert_eq!(partial.data.email, None);
+    assert_eq!(partial.incomplete_fields, vec!["email"]);
+}
+
+#[tokio::test]
+async fn async_field_requests_are_abandoned_at_the_deadline() {
+    let server = slow_email_server();
+
+    let started = Instant::now();
+    let partial: PartialData<Contact> = server
+        .llm()
+        .async_fields_generate_partial_data_with_options(
+            &Contact::new(),
+            "Ada",
+            &vec![],
+            &options(),
+        )
+        .await
+        .unwrap();
+
+    assert!(started.elapsed() < Duration::from_secs(2));
+    assert_eq!(partial.data.name, "Ada");
+    assert_eq!(partial.incomplete_fields, vec!["email"]);
+}
@@ -0,0 +1,200 @@
+//! Fields with a `default_value` take it when the model finds nothing for them.
+
+mod support;
+
+use secretary::SecretaryError;
+use secretary::Task;
+use secretary::leniency::LeniencyProfile;
+use secretary::partial::PartialData;
+use secretary::traits::{AsyncGenerateData, GenerateData};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use support::fixtures::{empty_choices, field_result, success};
+use support::{MockServer, secretary_error};
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Parcel {
+    #[task(instruction = "Extract the tracking number")]
+    pub tracking: String,
+    #[task(instruction = "Extract the carrier", default_value = "\"unknown\"")]
+    pub carrier: String,
+    #[task(instruction = "Extract the number of boxes", default_value = "1")]
+    pub boxes: u32,
+    #[task(
+        instruction = "List the handling labels",
+        default_value = "[\"standard\"]"
+    )]
+    pub labels: Vec<String>,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Delivery {
+    #[task(instruction = "Extract the recipient")]
+    pub recipient: String,
+    #[task(instruction = "Extract the weight in kg")]
+    pub weight: f64,
+    pub parcel: Parcel,
+}
+
+const PARCEL: &str = "Parcel ZX-42 is on its way.";
+
+fn parcel(carrier: &str, boxes: u32, labels: &[&str]) -> Parcel {
+    Parcel {
+        tracking: "ZX-42".to_string(),
+        carrier: carrier.to_string(),
+        boxes,
+        labels: labels.iter().map(|label| label.to_string()).collect(),
+    }
+}
+
+#[test]
+fn prompts_ask_for_the_default() {
+    let prompt: String = Parcel::new().get_system_prompt();
+
+    assert!(prompt.contains(
+        "Extract the number of boxes If the text does not give this information, output exactly 1."
+    ));
+    assert!(prompt.contains("output exactly [\"standard\"]."));
+    assert!(
+        !Parcel::field_descriptors()[0]
+            .instruction
+            .contains("output exactly")
+    );
+}
+
+#[test]
+fn missing_null_and_malformed_fields_take_their_default() {
+    let server = MockServer::always(success(
+        r#"{"tracking": "ZX-42", "boxes": "several", "labels": null}"#,
+    ));
+
+    let result: Parcel = server
+        .llm()
+        .generate_data(&Parcel::new(), PARCEL, vec![])
+        .unwrap();
+
+    assert_eq!(result, parcel("unknown", 1, &["standard"]));
+}
+
+#[test]
+fn values_the_model_found_are_kept() {
+    let server = MockServer::always(success(
+        r#"{"tracking": "ZX-42", "carrier": "DHL", "boxes": 3, "labels": ["fragile"]}"#,
+    ));
+
+    let result: Parcel = server
+        .llm()
+        .with_leniency(LeniencyProfile::standard())
+        .generate_data(&Parcel::new(), PARCEL, vec![])
+        .unwrap();
+
+    assert_eq!(result, parcel("DHL", 3, &["fragile"]));
+}
+
+#[test]
+fn fields_without_a_default_still_fail() {
+    let content: &str = r#"{"carrier": "DHL", "boxes": 2}"#;
+    let server = MockServer::always(success(content));
+
+    let error = server
+        .llm()
+        .generate_data(&Parcel::new(), PARCEL, vec![])
+        .unwrap_err();
+
+    match secretary_error(&error) {
+        SecretaryError::JsonParsingError {
+            message,
+            raw_content,
+        } => {
+            assert!(message.contains("tracking"), "{}", message);
+            assert_eq!(raw_content, content);
+        }
+        other => panic!("unexpected error: {}", other),
+    }
+}
+
+#[test]
+fn nested_tasks_take_their_defaults() {
+    let server = MockServer::always(success(
+        r#"{"recipient": "Ada", "weight": 2.5, "parcel": {"tracking": "ZX-42", "boxes": null}}"#,
+    ));
+
+    let result: Delivery = server
+        .llm()
+        .generate_data(&Delivery::new(), PARCEL, vec![])
+        .unwrap();
+
+    assert_eq!(result.parcel, parcel("unknown", 1, &["standard"]));
+}
+
+#[test]
+fn force_generation_takes_the_defaults() {
+    let server = MockServer::always(success(
+        "Here it is:\n```json\n{\"tracking\": \"ZX-42\", \"carrier\": \"UPS\"}\n```",
+    ));
+
+    let result: Parcel = server
+        .llm()
+        .force_generate_data(&Parcel::new(), PARCEL, vec![])
+        .unwrap();
+
+    assert_eq!(result, parcel("UPS", 1, &["standard"]));
+}
+
+#[test]
+fn distributed_fields_take_their_defaults() {
+    let server = MockServer::by_instruction(
+        vec![
+            ("Extract the tracking number", field_result("ZX-42")),
+            ("Extract the carrier", field_result("null")),
+            ("Extract the number of boxes", field_result("a couple")),
+            (
+                "List the handling labels",
+                field_result("<item>fragile</item>"),
+            ),
+        ],
+        empty_choices(),
+    );
+
+    let result: Parcel = server
+        .llm()
+        .fields_generate_data(&Parcel::new(), PARCEL, vec![])
+        .unwrap();
+
+    assert_eq!(result, parcel("unknown", 1, &["fragile"]));
+}
+
+#[test]
+fn partial_extraction_only_replaces_fields_without_a_default() {
+    let server = MockServer::always(success(
+        &json!({
+            "recipient": "Ada",
+            "weight": "heavy",
+            "parcel": {"tracking": "ZX-42", "boxes": "many"}
+        })
+        .to_string(),
+    ));
+
+    let partial: PartialData<Delivery> = server
+        .llm()
+        .generate_partial_data(&Delivery::new(), PARCEL, vec![])
+        .unwrap();
+
+    assert_eq!(partial.failed_fields, vec!["weight"]);
+    assert_eq!(partial.data.weight, 0.0);
+    assert_eq!(partial.data.parcel, parcel("unknown", 1, &["standard"]));
+}
+
+#[tokio::test]
+async fn async_generation_takes_the_defaults() {
+    let server = MockServer::always(success(r#"{"tracking": "ZX-42", "boxes": -4}"#));
+
+    let result: Parcel = server
+        .llm()
+        .async_generate_data(&Parcel::new(), PARCEL, vec![])
+        .await
+        .unwrap();
+
+    assert_eq!(result, parcel("unknown", 1, &["standard"]));
+}
@@ -0,0 +1,49 @@
+//! A hand-written `TaskDefinition` extracts a `serde_json::Value` through the provider.
+
+#![allow(clippy::needless_borrows_for_generic_args)]
+
+mod support;
+
+use secretary::definition::TaskDefinition;
+use secretary::traits::GenerateData;
+use serde_json::{Value, json};
+
+use support::MockServer;
+use support::fixtures::success;
+
+const INVOICE: &str = r#"
+name: Invoice
+fields:
+  - name: vendor
+    type: string
+    instruction: Extract the vendor name
+    examples: [ACME Corp]
+  - name: total
+    type: number
+    instruction: Extract the grand total
+  - name: due_date
+    type: string
+    instruction: Extract the due date as YYYY-MM-DD
+    optional: true
+"#;
+
+#[test]
+fn a_definition_extracts_a_value() {
+    let definition = TaskDefinition::load_yaml(INVOICE).unwrap();
+    let server = MockServer::always(success(
+        r#"{"vendor": "Globex", "total": 120.5, "due_date": null}"#,
+    ));
+
+    let value: Value = server
+        .llm()
+        .generate_value(&definition, "Globex billed 120.50", &vec![])
+        .unwrap();
+
+    assert_eq!(
+        value,
+        json!({"vendor": "Globex", "total": 120.5, "due_date": null})
+    );
+    let prompt: String = server.requests()[0].prompt();
+    assert!(prompt.contains("vendor: Extract the vendor name"));
+    assert!(prompt.contains("Globex billed 120.50"));
+}
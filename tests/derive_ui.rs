@@ -0,0 +1,8 @@
+//! What `#[derive(Task)]` accepts and the errors it reports otherwise.
+
+#[test]
+fn derive_ui() {
+    let cases = trybuild::TestCases::new();
+    cases.pass("tests/ui/pass/*.rs");
+    cases.compile_fail("tests/ui/fail/*.rs");
+}
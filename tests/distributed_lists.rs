@@ -0,0 +1,117 @@
+//! Distributed generation parses list answers of primitive collection fields.
+
+#![allow(clippy::needless_borrows_for_generic_args)]
+
+mod support;
+
+use std::collections::HashSet;
+
+use secretary::Task;
+use secretary::traits::{AsyncGenerateData, GenerateData};
+use serde::{Deserialize, Serialize};
+
+use support::MockServer;
+use support::fixtures::{empty_choices, field_result};
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Article {
+    #[task(instruction = "Extract the title")]
+    pub title: String,
+    #[task(instruction = "List the keywords")]
+    pub keywords: Vec<String>,
+    #[task(instruction = "List the tags")]
+    pub tags: HashSet<String>,
+    #[task(instruction = "List the years mentioned")]
+    pub years: Option<Vec<u32>>,
+}
+
+const TARGET: &str = "Rust and Go in 2024 and 2025: a comparison.";
+
+fn server() -> MockServer {
+    MockServer::by_instruction(
+        vec![
+            ("Extract the title", field_result("Rust and Go")),
+            (
+                "List the keywords",
+                field_result("Here are the keywords:\n- rust\n- go\n- 2024"),
+            ),
+            (
+                "List the tags",
+                field_result("languages, comparison, languages"),
+            ),
+            (
+                "List the years mentioned",
+                field_result("<item>2024</item><item>2025</item>"),
+            ),
+        ],
+        empty_choices(),
+    )
+}
+
+fn expected() -> Article {
+    Article {
+        title: "Rust and Go".to_string(),
+        keywords: vec!["rust".to_string(), "go".to_string(), "2024".to_string()],
+        tags: HashSet::from(["languages".to_string(), "comparison".to_string()]),
+        years: Some(vec![2024, 2025]),
+    }
+}
+
+#[test]
+fn list_fields_are_asked_for_item_tags() {
+    let prompts = Article::new().get_distributed_field_prompts();
+
+    assert!(!prompts[0].prompt.contains("<item></item>"));
+    assert!(
+        prompts[1]
+            .prompt
+            .contains("Put each value in its own <item></item> tag")
+    );
+    assert!(
+        prompts[2]
+            .prompt
+            .contains("Put each distinct value in its own <item></item> tag")
+    );
+    assert!(
+        prompts[3]
+            .prompt
+            .contains("Put each value in its own <item></item> tag")
+    );
+}
+
+#[test]
+fn fields_generate_data_parses_list_answers() {
+    let server = server();
+
+    let article: Article = server
+        .llm()
+        .fields_generate_data(&Article::new(), TARGET, &vec![])
+        .unwrap();
+
+    assert_eq!(article, expected());
+}
+
+#[test]
+fn update_data_parses_list_answers() {
+    let server = server();
+
+    let article: Article = server
+        .llm()
+        .update_data(&Article::new(), TARGET, &vec![])
+        .unwrap();
+
+    assert_eq!(article, expected());
+}
+
+#[tokio::test]
+async fn async_fields_generate_data_parses_list_answers() {
+    let server = server();
+
+    let article: Article = server
+        .llm()
+        .async_fields_generate_data(&Article::new(), TARGET, &vec![])
+        .await
+        .unwrap();
+
+    assert_eq!(article, expected());
+}
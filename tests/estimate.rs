@@ -0,0 +1,250 @@
+//! Pre-flight estimates count the requests, prompt tokens, cost and latency an extraction
+//! would take, without sending anything.
+
+mod support;
+
+use std::time::Duration;
+
+use secretary::Task;
+use secretary::estimate::{DEFAULT_REQUEST_LATENCY, ExtractionEstimate, Pricing};
+use secretary::mode::GenerationMode;
+use secretary::tokens::estimate_tokens;
+use secretary::traits::GenerateData;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use support::fixtures::{field_result, success};
+use support::{MockServer, RecordedRequest};
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Address {
+    #[task(instruction = "Extract the city")]
+    pub city: String,
+    #[task(instruction = "Extract the country")]
+    pub country: String,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Company {
+    #[task(instruction = "Extract the company name")]
+    pub name: String,
+    pub headquarters: Address,
+    #[task(instruction = "Extract the number of employees")]
+    pub employees: u32,
+}
+
+const TARGET: &str = "Acme, based in Berlin, Germany, employs 120 people.";
+
+const ACME_JSON: &str = r#"{"name": "Acme", "headquarters": {"city": "Berlin", "country": "Germany"}, "employees": 120}"#;
+
+fn fields(concurrency: Option<usize>) -> GenerationMode {
+    GenerationMode::Fields { concurrency }
+}
+
+/// The estimated tokens of the messages a request sent, counted message by message.
+fn sent_tokens(request: &RecordedRequest) -> usize {
+    request.body["messages"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|message| estimate_tokens(message["content"].as_str().unwrap()))
+        .sum()
+}
+
+#[test]
+fn modes_differ_in_requests_and_tokens() {
+    let server = MockServer::always(success(ACME_JSON));
+    let llm = server.llm();
+    let task = Company::new();
+
+    let json: ExtractionEstimate = llm
+        .plan(&task, TARGET, vec![], GenerationMode::JsonMode)
+        .unwrap();
+    let force: ExtractionEstimate = llm
+        .plan(&task, TARGET, vec![], GenerationMode::Force)
+        .unwrap();
+    let fields: ExtractionEstimate = llm.plan(&task, TARGET, vec![], fields(None)).unwrap();
+
+    assert_eq!(json.request_count, 1);
+    assert_eq!(force.request_count, 1);
+    assert_eq!(json.requests[0].label, "task");
+    let labels: Vec<&str> = fields
+        .requests
+        .iter()
+        .map(|request| request.label.as_str())
+        .collect();
+    assert_eq!(
+        labels,
+        vec![
+            "name",
+            "headquarters.city",
+            "headquarters.country",
+            "employees"
+        ]
+    );
+    assert_eq!(fields.request_count, 4);
+    // Every field request repeats the target, so the mode sends more tokens in total
+    assert!(fields.prompt_tokens > json.prompt_tokens);
+    assert!(
+        fields
+            .requests
+            .iter()
+            .all(|request| request.prompt_tokens >= estimate_tokens(TARGET))
+    );
+
+    // Nothing was sent
+    assert!(server.requests().is_empty());
+}
+
+#[test]
+fn totals_add_up() {
+    let server = MockServer::always(success(ACME_JSON));
+    let llm = server.llm().with_pricing(Pricing::new(2.0));
+
+    for mode in [
+        GenerationMode::JsonMode,
+        GenerationMode::Force,
+        fields(Some(3)),
+    ] {
+        let estimate: ExtractionEstimate = llm.plan(&Company::new(), TARGET, vec![], mode).unwrap();
+
+        let tokens: usize = estimate
+            .requests
+            .iter()
+            .map(|request| request.prompt_tokens)
+            .sum();
+        assert_eq!(estimate.prompt_tokens, tokens, "{:?}", mode);
+        assert_eq!(estimate.request_count, estimate.requests.len());
+        let cost: f64 = estimate.estimated_cost.unwrap();
+        assert!((cost - tokens as f64 * 2.0 / 1_000_000.0).abs() < 1e-12);
+    }
+
+    let without_pricing: ExtractionEstimate = server
+        .llm()
+        .plan(&Company::new(), TARGET, vec![], GenerationMode::JsonMode)
+        .unwrap();
+    assert_eq!(without_pricing.estimated_cost, None);
+}
+
+#[test]
+fn latency_follows_the_concurrency() {
+    let llm = MockServer::always(success(ACME_JSON)).llm();
+    let task = Company::new();
+    let second: Duration = Duration::from_secs(1);
+
+    // (mode, concurrency, rounds of requests) for the four fields of the Task
+    let cases: Vec<(GenerationMode, usize, u32)> = vec![
+        (GenerationMode::JsonMode, 1, 1),
+        (fields(None), 4, 1),
+        (fields(Some(1)), 1, 4),
+        (fields(Some(3)), 3, 2),
+        (fields(Some(4)), 4, 1),
+        (fields(Some(10)), 4, 1),
+        (fields(Some(0)), 1, 4),
+    ];
+    for (mode, concurrency, rounds) in cases {
+        let estimate: ExtractionEstimate = llm.plan(&task, TARGET, vec![], mode).unwrap();
+        assert_eq!(estimate.concurrency, concurrency, "{:?}", mode);
+        assert_eq!(estimate.request_latency, DEFAULT_REQUEST_LATENCY);
+        assert_eq!(
+            estimate.estimated_latency,
+            DEFAULT_REQUEST_LATENCY * rounds,
+            "{:?}",
+            mode
+        );
+
+        let estimate: ExtractionEstimate = estimate.with_request_latency(second);
+        assert_eq!(estimate.estimated_latency, second * rounds, "{:?}", mode);
+    }
+}
+
+#[test]
+fn estimates_match_the_prompts_that_are_sent() {
+    let server = MockServer::always(success(ACME_JSON));
+    let llm = server.llm();
+    let task = Company::new();
+    let instructions: Vec<String> = vec!["Use the legal name".to_string()];
+
+    let estimate: ExtractionEstimate = llm
+        .plan(
+            &task,
+            TARGET,
+            instructions.clone(),
+            GenerationMode::JsonMode,
+        )
+        .unwrap();
+    let _: Company = llm.generate_data(&task, TARGET, instructions).unwrap();
+
+    assert_eq!(
+        estimate.requests[0].prompt_tokens,
+        sent_tokens(&server.requests()[0])
+    );
+
+    let server = MockServer::by_instruction(
+        vec![
+            ("Extract the company name", field_result("Acme")),
+            ("Extract the city", field_result("Berlin")),
+            ("Extract the country", field_result("Germany")),
+            ("Extract the number of employees", field_result("120")),
+        ],
+        field_result(""),
+    );
+    let llm = server.llm();
+
+    let estimate: ExtractionEstimate = llm.plan(&task, TARGET, vec![], fields(None)).unwrap();
+    let _: Company = llm.fields_generate_data(&task, TARGET, vec![]).unwrap();
+
+    let mut sent: Vec<usize> = server.requests().iter().map(sent_tokens).collect();
+    let mut estimated: Vec<usize> = estimate
+        .requests
+        .iter()
+        .map(|request| request.prompt_tokens)
+        .collect();
+    sent.sort();
+    estimated.sort();
+    assert_eq!(estimated, sent);
+}
+
+#[test]
+fn most_expensive_request_and_display() {
+    let llm = MockServer::always(success(ACME_JSON))
+        .llm()
+        .with_pricing(Pricing::new(5.0));
+
+    let estimate: ExtractionEstimate = llm
+        .plan(&Company::new(), TARGET, vec![], fields(Some(2)))
+        .unwrap();
+
+    let most_expensive = estimate.most_expensive().unwrap();
+    assert!(
+        estimate
+            .requests
+            .iter()
+            .all(|request| request.prompt_tokens <= most_expensive.prompt_tokens)
+    );
+
+    let text: String = estimate.to_string();
+    let lines: Vec<&str> = text.lines().collect();
+    assert!(lines[0].starts_with("fields mode: 4 request(s), ~"));
+    assert!(lines[0].contains("2 at once"));
+    assert!(lines[0].contains("for the prompts"));
+    assert_eq!(lines.len(), 5);
+    assert!(lines[2].trim_start().starts_with("headquarters.city"));
+    assert!(lines[4].ends_with(&format!("{} tokens", estimate.requests[3].prompt_tokens)));
+}
+
+#[test]
+fn estimates_serialize() {
+    let llm = MockServer::always(success(ACME_JSON)).llm();
+
+    let estimate: ExtractionEstimate = llm
+        .plan(&Company::new(), TARGET, vec![], GenerationMode::JsonMode)
+        .unwrap();
+    let value: Value = serde_json::to_value(&estimate).unwrap();
+
+    assert_eq!(value["mode"], json!("JsonMode"));
+    assert_eq!(value["request_count"], json!(1));
+    assert_eq!(value["requests"][0]["label"], json!("task"));
+    assert_eq!(value["estimated_cost"], Value::Null);
+    assert_eq!(value["estimated_latency"], json!({"secs": 2, "nanos": 0}));
+}
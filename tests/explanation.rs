@@ -0,0 +1,277 @@
+//! Extracted values are explained field by field, with quotes located in the text and the
+//! model's assessment of the value.
+
+mod support;
+
+use secretary::explanation::Explanation;
+use secretary::provenance::QuoteMatch;
+use secretary::traits::{AsyncGenerateData, GenerateData};
+use secretary::{SecretaryError, Task};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use support::fixtures::{empty_choices, success};
+use support::{MockServer, secretary_error};
+
+const INVOICE: &str = "Invoice 2024-117 from Acme Ltd. Total due: 120.50 EUR by 1 March.";
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Invoice {
+    #[task(instruction = "Extract the name of the supplier")]
+    pub supplier: String,
+    #[task(instruction = "Extract the total amount due")]
+    pub total: f64,
+    #[task(instruction = "Extract the currency code")]
+    pub currency: Option<String>,
+    #[task(instruction = "Extract the reference numbers")]
+    pub references: Vec<String>,
+    pub payment: Payment,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Payment {
+    #[task(instruction = "Extract the due date")]
+    pub due: String,
+    #[task(instruction = "Extract whether the invoice is already paid")]
+    pub paid: bool,
+}
+
+fn invoice() -> Invoice {
+    Invoice {
+        supplier: "Acme Ltd".to_string(),
+        total: 12.05,
+        currency: None,
+        references: vec!["2024-117".to_string()],
+        payment: Payment {
+            due: "1 March".to_string(),
+            paid: false,
+        },
+    }
+}
+
+fn answer(answer: Value) -> MockServer {
+    MockServer::always(success(&answer.to_string()))
+}
+
+#[test]
+fn a_correct_value_is_justified_with_located_quotes() {
+    let server = answer(json!({
+        "quotes": ["from Acme Ltd", "  "],
+        "justification": "The invoice names Acme Ltd as its sender.",
+        "looks_correct": true,
+        "suggested_correction": null
+    }));
+
+    let explanation = server
+        .llm()
+        .explain_field(&Invoice::new(), INVOICE, &invoice(), "supplier")
+        .unwrap();
+
+    assert_eq!(explanation.field_path, "supplier");
+    assert_eq!(explanation.instruction, "Extract the name of the supplier");
+    assert_eq!(explanation.value, json!("Acme Ltd"));
+    assert_eq!(explanation.quotes.len(), 1);
+    let quote = &explanation.quotes[0];
+    assert_eq!(quote.field_path, "supplier");
+    assert_eq!(quote.match_kind, QuoteMatch::Exact);
+    assert_eq!(&INVOICE[quote.char_range.clone().unwrap()], "from Acme Ltd");
+    assert!(explanation.looks_correct);
+    assert_eq!(explanation.suggested_correction, None);
+    assert!(!explanation.needs_review());
+
+    // One focused request with the field, its instruction, its value and the text
+    let requests = server.requests();
+    assert_eq!(requests.len(), 1);
+    let prompt: String = requests[0].prompt();
+    assert!(requests[0].is_json_mode());
+    assert!(prompt.contains("Field: supplier"));
+    assert!(prompt.contains("Instruction: Extract the name of the supplier"));
+    assert!(prompt.contains("Extracted value: \"Acme Ltd\""));
+    assert!(prompt.contains(INVOICE));
+}
+
+#[test]
+fn a_wrong_value_is_admitted_with_a_correction() {
+    let server = answer(json!({
+        "quotes": ["Total due: 120.50 EUR", "Total due: 12.05"],
+        "justification": "The digits were transposed, the text says 120.50.",
+        "looks_correct": false,
+        "suggested_correction": 120.5
+    }));
+
+    let explanation = server
+        .llm()
+        .explain_field(&Invoice::new(), INVOICE, &invoice(), "total")
+        .unwrap();
+
+    assert_eq!(explanation.value, json!(12.05));
+    assert!(!explanation.looks_correct);
+    assert_eq!(explanation.suggested_correction, Some(json!(120.5)));
+    assert_eq!(explanation.quotes[0].match_kind, QuoteMatch::Exact);
+    // A quote that is not in the text is kept and flagged
+    assert_eq!(explanation.quotes[1].match_kind, QuoteMatch::NotFound);
+    assert_eq!(explanation.quotes[1].char_range, None);
+    assert!(explanation.needs_review());
+}
+
+#[test]
+fn corrections_equal_to_the_value_are_dropped() {
+    let server = answer(json!({
+        "quotes": [],
+        "justification": "The date is stated.",
+        "suggested_correction": "1 March"
+    }));
+
+    let explanation = server
+        .llm()
+        .explain_field(&Invoice::new(), INVOICE, &invoice(), "payment.due")
+        .unwrap();
+
+    assert_eq!(explanation.instruction, "Extract the due date");
+    // An answer without an assessment keeps the value
+    assert!(explanation.looks_correct);
+    assert_eq!(explanation.suggested_correction, None);
+    // but without a quote it still needs a reviewer
+    assert!(explanation.needs_review());
+}
+
+#[test]
+fn paths_are_checked_against_the_descriptors() {
+    let server = MockServer::always(empty_choices());
+    let llm = server.llm();
+
+    for path in [
+        "vendor",
+        "payment.amount",
+        "supplier.name",
+        "references[3]",
+        "",
+    ] {
+        let error = llm
+            .explain_field(&Invoice::new(), INVOICE, &invoice(), path)
+            .unwrap_err();
+        assert!(
+            matches!(secretary_error(&error), SecretaryError::InvalidFieldPath(invalid) if invalid == path),
+            "{}",
+            path
+        );
+    }
+    // No request is sent for an invalid path
+    assert!(server.requests().is_empty());
+}
+
+#[test]
+fn elements_of_lists_are_explained() {
+    let server = answer(json!({
+        "quotes": ["Invoice 2024-117"],
+        "justification": "The invoice number.",
+        "looks_correct": true
+    }));
+
+    let explanation = server
+        .llm()
+        .explain_field(&Invoice::new(), INVOICE, &invoice(), "references[0]")
+        .unwrap();
+
+    assert_eq!(explanation.instruction, "Extract the reference numbers");
+    assert_eq!(explanation.value, json!("2024-117"));
+}
+
+#[test]
+fn every_field_that_is_not_at_its_default_is_explained() {
+    let server = MockServer::start(|request| {
+        let prompt: String = request.prompt();
+        let looks_correct: bool = !prompt.contains("Field: total");
+        success(
+            &json!({
+                "quotes": ["Acme Ltd"],
+                "justification": "See the quote.",
+                "looks_correct": looks_correct
+            })
+            .to_string(),
+        )
+    });
+
+    let explanations = server
+        .llm()
+        .explain_all(&Invoice::new(), INVOICE, &invoice(), 2)
+        .unwrap();
+
+    let paths: Vec<&str> = explanations
+        .iter()
+        .map(|explanation| explanation.field_path.as_str())
+        .collect();
+    assert_eq!(
+        paths,
+        vec!["supplier", "total", "references", "payment.due"]
+    );
+    assert_eq!(server.requests().len(), 4);
+    let flagged: Vec<&str> = explanations
+        .iter()
+        .filter(|explanation| !explanation.looks_correct)
+        .map(|explanation| explanation.field_path.as_str())
+        .collect();
+    assert_eq!(flagged, vec!["total"]);
+}
+
+#[test]
+fn async_explanations_match_the_sync_ones() {
+    let server = answer(json!({
+        "quotes": ["Total due: 120.50 EUR"],
+        "justification": "The digits were transposed.",
+        "looks_correct": false,
+        "suggested_correction": 120.5
+    }));
+    let llm = server.llm();
+    // The blocking client cannot run inside the runtime, so the sync explanation comes first
+    let expected = llm
+        .explain_field(&Invoice::new(), INVOICE, &invoice(), "total")
+        .unwrap();
+
+    tokio::runtime::Runtime::new().unwrap().block_on(async {
+        let explanation = llm
+            .async_explain_field(&Invoice::new(), INVOICE, &invoice(), "total")
+            .await
+            .unwrap();
+        assert_eq!(explanation, expected);
+
+        let explanations = llm
+            .async_explain_all(&Invoice::new(), INVOICE, &invoice(), 3)
+            .await
+            .unwrap();
+        assert_eq!(explanations.len(), 4);
+        assert_eq!(explanations[3].field_path, "payment.due");
+
+        let error = llm
+            .async_explain_field(&Invoice::new(), INVOICE, &invoice(), "payment.amount")
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            secretary_error(&error),
+            SecretaryError::InvalidFieldPath(_)
+        ));
+    });
+}
+
+#[test]
+fn explanations_serialize_for_review_tools() {
+    let server = answer(json!({
+        "quotes": ["Total due: 120.50 EUR"],
+        "justification": "The digits were transposed.",
+        "looks_correct": false,
+        "suggested_correction": 120.5
+    }));
+    let explanation = server
+        .llm()
+        .explain_field(&Invoice::new(), INVOICE, &invoice(), "total")
+        .unwrap();
+
+    let serialized: Value = serde_json::to_value(&explanation).unwrap();
+    assert_eq!(serialized["field_path"], "total");
+    assert_eq!(serialized["looks_correct"], false);
+    assert_eq!(serialized["suggested_correction"], 120.5);
+    assert_eq!(serialized["quotes"][0]["quote"], "Total due: 120.50 EUR");
+
+    let restored: Explanation = serde_json::from_value(serialized).unwrap();
+    assert_eq!(explanation, restored);
+}
@@ -0,0 +1,52 @@
+//! Extra body parameters of the provider and of a call are merged into the request, with the
+//! call's values winning and the messages kept.
+
+#![allow(clippy::needless_borrows_for_generic_args)]
+
+mod support;
+
+use secretary::request::RequestOptions;
+use secretary::traits::GenerateData;
+use serde_json::{Value, json};
+
+use support::fixtures::success;
+use support::{ADA_JSON, MockServer, Person, TARGET, ada};
+
+#[test]
+fn call_values_win_over_the_providers() {
+    let server = MockServer::always(success(ADA_JSON));
+    // An OpenRouter-style routing block set on the provider is passed through
+    let llm = server.llm().with_extra_body(json!({
+        "provider": {"order": ["openai", "azure"], "allow_fallbacks": false},
+        "reasoning_effort": "low"
+    }));
+
+    llm.generate_data(&Person::new(), TARGET, &vec![]).unwrap();
+
+    let body: &Value = &server.requests()[0].body;
+    assert_eq!(
+        body["provider"],
+        json!({"order": ["openai", "azure"], "allow_fallbacks": false})
+    );
+    assert_eq!(body["reasoning_effort"], json!("low"));
+    assert_eq!(body["response_format"], json!({"type": "json_object"}));
+
+    // Per-call values win over the provider's, and the messages cannot be replaced
+    let options = RequestOptions::default().with_extra_body(json!({
+        "provider": {"allow_fallbacks": true},
+        "reasoning_effort": "high",
+        "messages": []
+    }));
+    let person = llm
+        .generate_data_with_options(&Person::new(), TARGET, &vec![], &options)
+        .unwrap();
+    assert_eq!(person, ada());
+
+    let body: &Value = &server.requests()[1].body;
+    assert_eq!(
+        body["provider"],
+        json!({"order": ["openai", "azure"], "allow_fallbacks": true})
+    );
+    assert_eq!(body["reasoning_effort"], json!("high"));
+    assert_eq!(body["messages"].as_array().unwrap().len(), 2);
+}
@@ -0,0 +1,232 @@
+//! Fields with local extractors are filled without asking the LLM.
+
+#![allow(clippy::needless_borrows_for_generic_args)]
+
+mod support;
+
+use secretary::SecretaryError;
+use secretary::Task;
+use secretary::extractors::Extractor;
+use secretary::traits::{AsyncGenerateData, GenerateData};
+use serde::{Deserialize, Serialize};
+
+use support::fixtures::{empty_choices, field_result, success};
+use support::{MockServer, secretary_error};
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Lead {
+    #[task(instruction = "Extract the contact's name")]
+    pub name: String,
+    #[task(
+        instruction = "Extract the contact's email address",
+        extractor = "email"
+    )]
+    pub email: Option<String>,
+    #[task(
+        instruction = "Extract the contact's phone number",
+        extractor = "phone"
+    )]
+    pub phone: Option<String>,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Ticket {
+    #[task(
+        instruction = "Extract the ticket number",
+        extractor = r"regex:TICKET-(\d+)",
+        ambiguous = "first"
+    )]
+    pub number: String,
+    #[task(
+        instruction = "Extract the reporter's email",
+        extractor = "email",
+        ambiguous = "error"
+    )]
+    pub reporter: String,
+}
+
+const LEAD_TARGET: &str =
+    "Ada Lovelace wrote from ada@example.com and asked us to call +44 20 7946 0958.";
+
+fn ada_lead() -> Lead {
+    Lead {
+        name: "Ada Lovelace".to_string(),
+        email: Some("ada@example.com".to_string()),
+        phone: Some("+44 20 7946 0958".to_string()),
+    }
+}
+
+#[test]
+fn extractors_find_distinct_values_in_order() {
+    assert_eq!(
+        Extractor::Email.candidates("a@example.com, b@example.org and a@example.com again"),
+        vec!["a@example.com", "b@example.org"]
+    );
+    assert_eq!(
+        Extractor::Url.candidates("Read (https://example.com/a?b=1). Then www.example.org!"),
+        vec!["https://example.com/a?b=1", "www.example.org"]
+    );
+    assert_eq!(
+        Extractor::Phone.candidates("Call (020) 7946-0000 on 2024-05-01, order 123456789."),
+        vec!["(020) 7946-0000"]
+    );
+    assert_eq!(
+        Extractor::Regex(r"#(\d+)".to_string()).candidates("Issues #12 and #7"),
+        vec!["12", "7"]
+    );
+    assert_eq!(
+        Extractor::Regex(r"[A-Z]{3}-\d".to_string()).candidates("ABC-1 then XYZ-2"),
+        vec!["ABC-1", "XYZ-2"]
+    );
+    assert!(Extractor::Email.candidates("no address here").is_empty());
+}
+
+#[test]
+fn generate_data_leaves_extracted_fields_out_of_the_prompt() {
+    let server = MockServer::always(success(r#"{"name": "Ada Lovelace"}"#));
+
+    let lead: Lead = server
+        .llm()
+        .generate_data(&Lead::new(), LEAD_TARGET, &vec![])
+        .unwrap();
+
+    assert_eq!(lead, ada_lead());
+    let requests = server.requests();
+    assert_eq!(requests.len(), 1);
+    let prompt: String = requests[0].prompt();
+    assert!(prompt.contains("Extract the contact's name"));
+    assert!(!prompt.contains("Extract the contact's email address"));
+    assert!(!prompt.contains("Extract the contact's phone number"));
+}
+
+#[test]
+fn generate_data_asks_the_llm_when_nothing_is_found() {
+    let server = MockServer::always(success(
+        r#"{"name": "Ada Lovelace", "email": null, "phone": "+44 20 7946 0958"}"#,
+    ));
+
+    let lead: Lead = server
+        .llm()
+        .generate_data(&Lead::new(), "Ada Lovelace, +44 20 7946 0958", &vec![])
+        .unwrap();
+
+    assert_eq!(lead.email, None);
+    let prompt: String = server.requests()[0].prompt();
+    assert!(prompt.contains("Extract the contact's email address"));
+    assert!(!prompt.contains("Extract the contact's phone number"));
+}
+
+#[test]
+fn fields_generate_data_sends_no_request_for_extracted_fields() {
+    let server = MockServer::by_instruction(
+        vec![("Extract the contact's name", field_result("Ada Lovelace"))],
+        empty_choices(),
+    );
+
+    let lead: Lead = server
+        .llm()
+        .fields_generate_data(&Lead::new(), LEAD_TARGET, &vec![])
+        .unwrap();
+
+    assert_eq!(lead, ada_lead());
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[test]
+fn generate_data_adaptive_reports_the_extracted_fields() {
+    let server = MockServer::always(success(r#"{"name": "Ada Lovelace"}"#));
+
+    let result = server
+        .llm()
+        .generate_data_adaptive(&Lead::new(), LEAD_TARGET, &vec![])
+        .unwrap();
+
+    assert_eq!(result.data, ada_lead());
+    assert_eq!(result.metadata.locally_extracted, vec!["email", "phone"]);
+}
+
+#[test]
+fn ambiguity_policies_decide_between_several_values() {
+    let server = MockServer::always(success(r#"{"name": "Ada Lovelace"}"#));
+    let target: &str = "Ada Lovelace, ada@example.com or ada@work.example.com, +44 20 7946 0958";
+
+    // The default policy asks the LLM
+    let _: Result<Lead, _> = server.llm().generate_data(&Lead::new(), target, &vec![]);
+    assert!(
+        server.requests()[0]
+            .prompt()
+            .contains("Extract the contact's email address")
+    );
+
+    let server = MockServer::always(success("{}"));
+    let error = server
+        .llm()
+        .generate_data::<Ticket>(
+            &Ticket::new(),
+            "TICKET-12 and TICKET-13 from a@example.com and b@example.com",
+            &vec![],
+        )
+        .unwrap_err();
+    match secretary_error(&error) {
+        SecretaryError::AmbiguousLocalExtraction {
+            field_path,
+            candidates,
+        } => {
+            assert_eq!(field_path, "reporter");
+            assert_eq!(candidates, &vec!["a@example.com", "b@example.com"]);
+        }
+        other => panic!("unexpected error: {}", other),
+    }
+    assert!(server.requests().is_empty());
+
+    let ticket: Ticket = server
+        .llm()
+        .generate_data(
+            &Ticket::new(),
+            "TICKET-12 and TICKET-13 from a@example.com",
+            &vec![],
+        )
+        .unwrap();
+    assert_eq!(
+        ticket,
+        Ticket {
+            number: "12".to_string(),
+            reporter: "a@example.com".to_string(),
+        }
+    );
+}
+
+#[tokio::test]
+async fn async_generate_data_leaves_extracted_fields_out_of_the_prompt() {
+    let server = MockServer::always(success(r#"{"name": "Ada Lovelace"}"#));
+
+    let lead: Lead = server
+        .llm()
+        .async_generate_data(&Lead::new(), LEAD_TARGET, &vec![])
+        .await
+        .unwrap();
+
+    assert_eq!(lead, ada_lead());
+    assert!(
+        !server.requests()[0]
+            .prompt()
+            .contains("Extract the contact's email address")
+    );
+}
+
+#[tokio::test]
+async fn async_fields_generate_data_sends_no_request_for_extracted_fields() {
+    let server = MockServer::by_instruction(
+        vec![("Extract the contact's name", field_result("Ada Lovelace"))],
+        empty_choices(),
+    );
+
+    let lead: Lead = server
+        .llm()
+        .async_fields_generate_data(&Lead::new(), LEAD_TARGET, &vec![])
+        .await
+        .unwrap();
+
+    assert_eq!(lead, ada_lead());
+    assert_eq!(server.requests().len(), 1);
+}
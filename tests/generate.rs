@@ -0,0 +1,399 @@
+//! The synchronous generate methods against the canonical response shapes.
+
+#![allow(clippy::needless_borrows_for_generic_args)]
+
+mod support;
+
+use secretary::SecretaryError;
+use secretary::adaptive::PromptStrategy;
+use secretary::chunking::{ChunkOptions, MergePolicy};
+use secretary::provenance::QuoteMatch;
+use secretary::request::RequestOptions;
+use secretary::review::Either;
+use secretary::traits::GenerateData;
+use serde_json::json;
+
+use support::fixtures::{
+    content_filter, empty_choices, error_body, fenced_json, field_result, success, think_blocks,
+    truncated,
+};
+use support::{
+    ADA_JSON, MockServer, Person, TARGET, ada, assert_age_failed, assert_malformed_json,
+    assert_no_response, fields_server, secretary_error,
+};
+
+#[test]
+fn generate_data_requests_json_mode() {
+    let server = MockServer::always(success(ADA_JSON));
+
+    let person: Person = server
+        .llm()
+        .generate_data(&Person::new(), TARGET, &vec![])
+        .unwrap();
+
+    assert_eq!(person, ada());
+    let requests = server.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].path, "/chat/completions");
+    assert!(requests[0].is_json_mode());
+    assert!(requests[0].prompt().contains("Extract the person's name"));
+    assert!(requests[0].prompt().contains(TARGET));
+}
+
+#[test]
+fn generate_data_reports_missing_content() {
+    for response in [empty_choices(), error_body(), content_filter()] {
+        let server = MockServer::always(response);
+        assert_no_response(server.llm().generate_data(&Person::new(), TARGET, &vec![]));
+    }
+}
+
+#[test]
+fn generate_data_keeps_the_raw_content_of_malformed_json() {
+    let server = MockServer::always(truncated());
+    let raw_content =
+        assert_malformed_json(server.llm().generate_data(&Person::new(), TARGET, &vec![]));
+    assert_eq!(raw_content, r#"{"name": "Ada", "ag"#);
+
+    // JSON mode expects a bare object, so surrounding text is not searched
+    for response in [think_blocks(ADA_JSON), fenced_json(ADA_JSON)] {
+        let server = MockServer::always(response);
+        let raw_content =
+            assert_malformed_json(server.llm().generate_data(&Person::new(), TARGET, &vec![]));
+        assert!(raw_content.contains(ADA_JSON));
+    }
+}
+
+#[test]
+fn generate_data_with_options_applies_the_options() {
+    let server = MockServer::always(success(ADA_JSON));
+    let options = RequestOptions::default().with_temperature(0.2);
+
+    let person: Person = server
+        .llm()
+        .generate_data_with_options(&Person::new(), TARGET, &vec![], &options)
+        .unwrap();
+
+    assert_eq!(person, ada());
+    assert_eq!(server.requests()[0].body["temperature"], json!(0.2));
+}
+
+#[test]
+fn force_generate_data_requests_plain_text() {
+    let server = MockServer::always(success(ADA_JSON));
+
+    let person: Person = server
+        .llm()
+        .force_generate_data(&Person::new(), TARGET, &vec![])
+        .unwrap();
+
+    assert_eq!(person, ada());
+    assert!(!server.requests()[0].is_json_mode());
+}
+
+#[test]
+fn force_generate_data_finds_json_in_mixed_text() {
+    for response in [think_blocks(ADA_JSON), fenced_json(ADA_JSON)] {
+        let server = MockServer::always(response);
+        let person: Person = server
+            .llm()
+            .force_generate_data(&Person::new(), TARGET, &vec![])
+            .unwrap();
+        assert_eq!(person, ada());
+    }
+}
+
+#[test]
+fn force_generate_data_reports_failures() {
+    for response in [empty_choices(), error_body(), content_filter()] {
+        let server = MockServer::always(response);
+        assert_no_response(
+            server
+                .llm()
+                .force_generate_data(&Person::new(), TARGET, &vec![]),
+        );
+    }
+
+    let server = MockServer::always(truncated());
+    assert_malformed_json(
+        server
+            .llm()
+            .force_generate_data(&Person::new(), TARGET, &vec![]),
+    );
+}
+
+#[test]
+fn fields_generate_data_sends_one_request_per_field() {
+    let server = fields_server(field_result("36"));
+
+    let person: Person = server
+        .llm()
+        .fields_generate_data(&Person::new(), TARGET, &vec![])
+        .unwrap();
+
+    assert_eq!(person, ada());
+    let requests = server.requests();
+    assert_eq!(requests.len(), 2);
+    assert!(requests.iter().all(|request| !request.is_json_mode()));
+}
+
+#[test]
+fn fields_generate_data_reports_failures() {
+    for response in [empty_choices(), error_body(), content_filter()] {
+        let server = fields_server(response);
+        assert_no_response(
+            server
+                .llm()
+                .fields_generate_data(&Person::new(), TARGET, &vec![]),
+        );
+    }
+
+    for response in [truncated(), think_blocks("36"), fenced_json("36")] {
+        let server = fields_server(response);
+        assert_age_failed(
+            server
+                .llm()
+                .fields_generate_data(&Person::new(), TARGET, &vec![]),
+        );
+    }
+}
+
+#[test]
+fn fields_generate_data_with_options_applies_the_options() {
+    let server = fields_server(field_result("36"));
+    let options = RequestOptions::default().with_temperature(0.2);
+
+    let person: Person = server
+        .llm()
+        .fields_generate_data_with_options(&Person::new(), TARGET, &vec![], &options)
+        .unwrap();
+
+    assert_eq!(person, ada());
+    assert!(
+        server
+            .requests()
+            .iter()
+            .all(|request| request.body["temperature"] == json!(0.2))
+    );
+}
+
+#[test]
+fn generate_partial_data_replaces_failed_fields() {
+    let server = MockServer::always(success(r#"{"name": "Ada", "age": "unknown"}"#));
+
+    let partial = server
+        .llm()
+        .generate_partial_data(&Person::new(), TARGET, &vec![])
+        .unwrap();
+
+    assert_eq!(partial.failed_fields, vec!["age"]);
+    assert_eq!(partial.data.name, "Ada");
+    assert_eq!(partial.data.age, 0);
+}
+
+#[test]
+fn fields_generate_partial_data_replaces_failed_fields() {
+    let server = fields_server(truncated());
+
+    let partial = server
+        .llm()
+        .fields_generate_partial_data(&Person::new(), TARGET, &vec![])
+        .unwrap();
+
+    assert_eq!(partial.failed_fields, vec!["age"]);
+    assert_eq!(partial.data.name, "Ada");
+
+    let partial = server
+        .llm()
+        .fields_generate_partial_data_with_options(
+            &Person::new(),
+            TARGET,
+            &vec![],
+            &RequestOptions::default(),
+        )
+        .unwrap();
+    assert_eq!(partial.failed_fields, vec!["age"]);
+}
+
+#[test]
+fn generate_data_or_review_returns_review_items() {
+    let server = MockServer::always(success(ADA_JSON));
+    match server
+        .llm()
+        .generate_data_or_review(&Person::new(), TARGET, &vec![])
+        .unwrap()
+    {
+        Either::Left(person) => assert_eq!(person, ada()),
+        Either::Right(item) => panic!("unexpected review item: {}", item.error),
+    }
+
+    let server = MockServer::always(success(r#"{"name": "Ada", "age": "unknown"}"#));
+    match server
+        .llm()
+        .generate_data_or_review(&Person::new(), TARGET, &vec![])
+        .unwrap()
+    {
+        Either::Left(person) => panic!("unexpected data: {:?}", person),
+        Either::Right(item) => {
+            assert_eq!(item.target, TARGET);
+            assert_eq!(item.extracted.name, "Ada");
+        }
+    }
+
+    let server = MockServer::always(empty_choices());
+    assert_no_response(
+        server
+            .llm()
+            .generate_data_or_review(&Person::new(), TARGET, &vec![]),
+    );
+}
+
+#[test]
+fn generate_data_with_provenance_locates_the_evidence() {
+    let content = json!({
+        "name": {"value": "Ada", "evidence": "Ada"},
+        "age": {"value": 36, "evidence": "36 years old"}
+    });
+    let server = MockServer::always(success(&content.to_string()));
+
+    let result = server
+        .llm()
+        .generate_data_with_provenance(&Person::new(), TARGET, &vec![])
+        .unwrap();
+
+    assert_eq!(result.data, ada());
+    assert!(
+        result
+            .provenance
+            .iter()
+            .all(|provenance| provenance.match_kind == QuoteMatch::Exact)
+    );
+}
+
+#[test]
+fn generate_data_from_reader_and_path_read_the_target() {
+    let server = MockServer::always(success(ADA_JSON));
+
+    let person: Person = server
+        .llm()
+        .generate_data_from_reader(&Person::new(), TARGET.as_bytes(), &vec![])
+        .unwrap();
+    assert_eq!(person, ada());
+
+    let path = std::env::temp_dir().join(format!("secretary-target-{}.txt", std::process::id()));
+    std::fs::write(&path, format!("\u{FEFF}{}", TARGET)).unwrap();
+    let person: Person = server
+        .llm()
+        .generate_data_from_path(&Person::new(), &path, &vec![])
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(person, ada());
+
+    assert!(
+        server
+            .requests()
+            .iter()
+            .all(|request| request.prompt().contains(&format!("json:\n{}", TARGET)))
+    );
+}
+
+#[test]
+fn oversized_targets_are_refused_before_any_request() {
+    let server = MockServer::always(success(ADA_JSON));
+    let oversized: Vec<u8> = vec![b'a'; secretary::input::DEFAULT_MAX_INPUT_BYTES + 1];
+
+    let error = server
+        .llm()
+        .generate_data_from_reader(&Person::new(), oversized.as_slice(), &vec![])
+        .unwrap_err();
+
+    assert!(matches!(
+        secretary_error(&error),
+        SecretaryError::InputTooLarge { .. }
+    ));
+    assert!(server.requests().is_empty());
+}
+
+#[test]
+fn generate_data_adaptive_reports_the_strategy() {
+    let server = MockServer::always(success(ADA_JSON));
+
+    let result = server
+        .llm()
+        .generate_data_adaptive(&Person::new(), TARGET, &vec![])
+        .unwrap();
+
+    assert_eq!(result.data, ada());
+    assert_eq!(result.metadata.prompt_strategy, Some(PromptStrategy::Full));
+}
+
+#[test]
+fn generate_data_chunked_merges_the_chunks() {
+    let server = MockServer::start(|request| {
+        if request.prompt().contains("Ada is") {
+            success(r#"{"name": "Ada", "age": 0}"#)
+        } else {
+            success(r#"{"name": "", "age": 36}"#)
+        }
+    });
+    let options = ChunkOptions::default()
+        .with_chunk_size(30)
+        .with_overlap(0)
+        .with_merge_policy(MergePolicy::FirstNonDefault);
+
+    let person: Person = server
+        .llm()
+        .generate_data_chunked(
+            &Person::new(),
+            "Ada is a mathematician.\n\nShe turned 36 this year.",
+            &vec![],
+            &options,
+        )
+        .unwrap()
+        .data;
+
+    assert_eq!(person, ada());
+    assert_eq!(server.requests().len(), 2);
+}
+
+#[test]
+fn update_data_only_requests_missing_fields() {
+    let server = fields_server(field_result("36"));
+    let existing = Person {
+        name: "Ada".to_string(),
+        age: 0,
+    };
+
+    let person: Person = server
+        .llm()
+        .update_data(&existing, TARGET, &vec![])
+        .unwrap();
+
+    assert_eq!(person, ada());
+    let requests = server.requests();
+    assert_eq!(requests.len(), 1);
+    assert!(requests[0].prompt().contains("Extract the age as a number"));
+}
+
+#[test]
+fn generate_value_returns_json() {
+    let server = MockServer::always(success(ADA_JSON));
+    let value = server
+        .llm()
+        .generate_value(&Person::new(), TARGET, &vec![])
+        .unwrap();
+    assert_eq!(value, json!({"name": "Ada", "age": 36}));
+
+    let server = MockServer::always(content_filter());
+    assert_no_response(server.llm().generate_value(&Person::new(), TARGET, &vec![]));
+}
+
+#[test]
+fn fields_generate_value_returns_json() {
+    let server = fields_server(field_result("36"));
+    let value = server
+        .llm()
+        .fields_generate_value(&Person::new(), TARGET, &vec![])
+        .unwrap();
+    assert_eq!(value, json!({"name": "Ada", "age": 36}));
+}
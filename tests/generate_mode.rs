@@ -0,0 +1,260 @@
+//! `generate` sends the same requests in each mode as the method named after the mode.
+
+mod support;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use secretary::Task;
+use secretary::metrics;
+use secretary::mode::GenerationMode;
+use secretary::traits::{AsyncGenerateData, GenerateData};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use support::fixtures::{field_result, success};
+use support::{ADA_JSON, MockServer, Person, TARGET, ada, fields_server};
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Survey {
+    #[task(instruction = "Extract the first answer")]
+    pub first: String,
+    #[task(instruction = "Extract the second answer")]
+    pub second: String,
+    #[task(instruction = "Extract the third answer")]
+    pub third: String,
+    #[task(instruction = "Extract the fourth answer")]
+    pub fourth: String,
+}
+
+/// Returns the request bodies received so far, sorted so that concurrent requests compare.
+fn sorted_bodies(server: &MockServer) -> Vec<String> {
+    let mut bodies: Vec<String> = server
+        .requests()
+        .iter()
+        .map(|request| request.body.to_string())
+        .collect();
+    bodies.sort();
+    bodies
+}
+
+#[test]
+fn json_mode_sends_the_generate_data_request() {
+    let legacy = MockServer::always(success(ADA_JSON));
+    let unified = MockServer::always(success(ADA_JSON));
+
+    let expected: Person = legacy
+        .llm()
+        .generate_data(&Person::new(), TARGET, vec!["Be precise".to_string()])
+        .unwrap();
+    let person: Person = unified
+        .llm()
+        .generate(
+            &Person::new(),
+            TARGET,
+            vec!["Be precise".to_string()],
+            GenerationMode::JsonMode,
+        )
+        .unwrap();
+
+    assert_eq!((expected, person), (ada(), ada()));
+    assert_eq!(legacy.requests()[0].body, unified.requests()[0].body);
+    assert!(unified.requests()[0].is_json_mode());
+}
+
+#[test]
+fn force_mode_sends_the_force_generate_data_request() {
+    let response = success(&format!("Here you go:\n```json\n{}\n```", ADA_JSON));
+    let legacy = MockServer::always(response.clone());
+    let unified = MockServer::always(response);
+
+    let expected: Person = legacy
+        .llm()
+        .force_generate_data(&Person::new(), TARGET, vec![])
+        .unwrap();
+    let person: Person = unified
+        .llm()
+        .generate(&Person::new(), TARGET, vec![], GenerationMode::Force)
+        .unwrap();
+
+    assert_eq!((expected, person), (ada(), ada()));
+    assert_eq!(legacy.requests()[0].body, unified.requests()[0].body);
+    assert!(!unified.requests()[0].is_json_mode());
+}
+
+#[test]
+fn fields_mode_sends_the_fields_generate_data_requests() {
+    let legacy = fields_server(field_result("36"));
+    let unified = fields_server(field_result("36"));
+    let limited = fields_server(field_result("36"));
+
+    let expected: Person = legacy
+        .llm()
+        .fields_generate_data(&Person::new(), TARGET, vec![])
+        .unwrap();
+    let person: Person = unified
+        .llm()
+        .generate(
+            &Person::new(),
+            TARGET,
+            vec![],
+            GenerationMode::Fields { concurrency: None },
+        )
+        .unwrap();
+    let limited_person: Person = limited
+        .llm()
+        .generate(
+            &Person::new(),
+            TARGET,
+            vec![],
+            GenerationMode::Fields {
+                concurrency: Some(1),
+            },
+        )
+        .unwrap();
+
+    assert_eq!(expected, ada());
+    assert_eq!((person, limited_person), (ada(), ada()));
+    assert_eq!(sorted_bodies(&legacy).len(), 2);
+    assert_eq!(sorted_bodies(&legacy), sorted_bodies(&unified));
+    assert_eq!(sorted_bodies(&legacy), sorted_bodies(&limited));
+}
+
+/// A server answering every field after a pause, counting the most requests it had in flight.
+fn counting_server() -> (MockServer, Arc<AtomicUsize>) {
+    let in_flight: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(0));
+    let most: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(0));
+
+    let most_in_flight = most.clone();
+    let server = MockServer::start(move |_| {
+        let now: usize = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        most_in_flight.fetch_max(now, Ordering::SeqCst);
+        std::thread::sleep(Duration::from_millis(50));
+        in_flight.fetch_sub(1, Ordering::SeqCst);
+        field_result("yes")
+    });
+
+    (server, most)
+}
+
+fn answered() -> Survey {
+    Survey {
+        first: "yes".to_string(),
+        second: "yes".to_string(),
+        third: "yes".to_string(),
+        fourth: "yes".to_string(),
+    }
+}
+
+#[test]
+fn fields_concurrency_bounds_requests_in_flight() {
+    let (server, most) = counting_server();
+
+    let survey: Survey = server
+        .llm()
+        .generate(
+            &Survey::new(),
+            "Yes to everything.",
+            vec![],
+            GenerationMode::Fields {
+                concurrency: Some(2),
+            },
+        )
+        .unwrap();
+
+    assert_eq!(survey, answered());
+    assert_eq!(server.requests().len(), 4);
+    assert_eq!(most.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn async_fields_concurrency_bounds_requests_in_flight() {
+    let (server, most) = counting_server();
+
+    let survey: Survey = server
+        .llm()
+        .async_generate(
+            &Survey::new(),
+            "Yes to everything.",
+            vec![],
+            GenerationMode::Fields {
+                concurrency: Some(1),
+            },
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(survey, answered());
+    assert_eq!(most.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn async_modes_send_the_legacy_requests() {
+    let legacy = MockServer::always(success(ADA_JSON));
+    let unified = MockServer::always(success(ADA_JSON));
+
+    let expected: Person = legacy
+        .llm()
+        .async_generate_data(&Person::new(), TARGET, vec![])
+        .await
+        .unwrap();
+    let _: Person = legacy
+        .llm()
+        .async_force_generate_data(&Person::new(), TARGET, vec![])
+        .await
+        .unwrap();
+    let person: Person = unified
+        .llm()
+        .async_generate(&Person::new(), TARGET, vec![], GenerationMode::JsonMode)
+        .await
+        .unwrap();
+    let _: Person = unified
+        .llm()
+        .async_generate(&Person::new(), TARGET, vec![], GenerationMode::Force)
+        .await
+        .unwrap();
+
+    assert_eq!(expected, person);
+    let legacy_bodies: Vec<Value> = legacy.requests().into_iter().map(|r| r.body).collect();
+    let unified_bodies: Vec<Value> = unified.requests().into_iter().map(|r| r.body).collect();
+    assert_eq!(legacy_bodies, unified_bodies);
+
+    let legacy = fields_server(field_result("36"));
+    let unified = fields_server(field_result("36"));
+    let _: Person = legacy
+        .llm()
+        .async_fields_generate_data(&Person::new(), TARGET, vec![])
+        .await
+        .unwrap();
+    let _: Person = unified
+        .llm()
+        .async_generate(
+            &Person::new(),
+            TARGET,
+            vec![],
+            GenerationMode::Fields { concurrency: None },
+        )
+        .await
+        .unwrap();
+    assert_eq!(sorted_bodies(&legacy), sorted_bodies(&unified));
+}
+
+#[test]
+fn modes_key_metrics_like_the_legacy_methods() {
+    assert_eq!(
+        GenerationMode::JsonMode.metric_mode(),
+        metrics::GenerationMode::Json
+    );
+    assert_eq!(
+        GenerationMode::Force.metric_mode(),
+        metrics::GenerationMode::Force
+    );
+    assert_eq!(
+        GenerationMode::Fields {
+            concurrency: Some(3)
+        }
+        .metric_mode(),
+        metrics::GenerationMode::Distributed
+    );
+}
@@ -0,0 +1,45 @@
+//! Generic Tasks are extracted like any other once their parameters are concrete.
+
+#![allow(clippy::needless_borrows_for_generic_args)]
+
+mod support;
+
+use secretary::Task;
+use secretary::traits::GenerateData;
+use serde::{Deserialize, Serialize};
+
+use support::MockServer;
+use support::fixtures::success;
+
+#[derive(Task, Serialize, Deserialize, Debug)]
+struct Address {
+    #[task(instruction = "Extract the city")]
+    pub city: String,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug)]
+struct Labeled<T> {
+    #[task(instruction = "Extract a short label")]
+    pub label: String,
+    pub payload: T,
+}
+
+#[test]
+fn a_nested_task_payload_is_extracted() {
+    let server = MockServer::always(success(
+        r#"{"label": "office", "payload": {"city": "Oslo"}}"#,
+    ));
+
+    let result: Labeled<Address> = server
+        .llm()
+        .generate_data(&Labeled::<Address>::new(), "Our office is in Oslo", &vec![])
+        .unwrap();
+
+    assert_eq!(result.label, "office");
+    assert_eq!(result.payload.city, "Oslo");
+    assert!(
+        server.requests()[0]
+            .prompt()
+            .contains("city: Extract the city")
+    );
+}
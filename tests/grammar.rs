@@ -0,0 +1,284 @@
+//! Providers that take a GBNF grammar are sent the grammar of the Task, and their answers,
+//! which always match it, are deserialized without coercions.
+
+mod support;
+
+use std::collections::HashMap;
+
+use secretary::Task;
+use secretary::grammar::{gbnf_grammar, gbnf_grammar_without_fields};
+use secretary::leniency::LeniencyProfile;
+use secretary::llm_providers::llama_cpp::LlamaCppLLM;
+use secretary::request::RequestOptions;
+use secretary::traits::{AsyncGenerateData, GenerateData};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use support::fixtures::success;
+use support::{MockServer, TARGET};
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Invoice {
+    #[task(instruction = "Extract the invoice number")]
+    pub number: String,
+    #[task(instruction = "Extract the number of items")]
+    pub quantity: u32,
+    #[task(instruction = "Extract the balance")]
+    pub balance: i64,
+    #[task(instruction = "Extract the total amount")]
+    pub total: f64,
+    #[task(instruction = "Extract whether the invoice is paid")]
+    pub paid: bool,
+    #[task(instruction = "Extract the currency", one_of = "EUR, USD")]
+    pub currency: String,
+    #[task(instruction = "Extract the discount, if any")]
+    pub discount: Option<f64>,
+    #[task(instruction = "Extract the tags")]
+    pub tags: Vec<String>,
+    pub customer: Customer,
+    #[task(instruction = "Extract every line of the invoice")]
+    pub lines: Vec<Line>,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Customer {
+    #[task(instruction = "Extract the customer name")]
+    pub name: String,
+    #[task(instruction = "Extract the VAT ID, if any")]
+    pub vat_id: Option<String>,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Line {
+    #[task(instruction = "Extract the product")]
+    pub product: String,
+    #[task(instruction = "Extract the unit price")]
+    pub price: f64,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug)]
+#[task(key_case = "camelCase")]
+struct Survey {
+    #[task(
+        instruction = "Extract the answer",
+        one_of = "say \"yes\", C:\\dir, naïve"
+    )]
+    pub answer: Option<String>,
+    #[task(instruction = "Extract the respondent's name")]
+    pub respondent_name: Option<String>,
+    #[task(instruction = "Extract the respondent's home address, if any")]
+    pub home_address: Option<Address>,
+    #[task(instruction = "Extract the scores by category")]
+    pub scores: HashMap<String, Score>,
+    #[task(instruction = "Extract the other answers by question")]
+    pub extra: HashMap<String, String>,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug)]
+struct Address {
+    #[task(instruction = "Extract the street")]
+    pub street: String,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug)]
+struct Score {
+    #[task(instruction = "Extract the score")]
+    pub value: u8,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug)]
+struct Summary(#[task(instruction = "Summarize the review in one sentence")] String);
+
+#[derive(Task, Serialize, Deserialize, Debug)]
+struct Rating(
+    #[task(instruction = "Extract the score out of 10")] u32,
+    #[task(instruction = "Extract the number of votes")] u32,
+);
+
+#[derive(Task, Serialize, Deserialize, Debug)]
+struct Review {
+    #[task(instruction = "Extract the film title")]
+    pub title: String,
+    pub summary: Summary,
+    pub rating: Rating,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug)]
+struct Scored {
+    pub value: Score,
+}
+
+/// An answer that matches the grammar of `Invoice`, as a constrained model writes it.
+fn constrained_answer() -> Value {
+    json!({
+        "number": "A-17",
+        "quantity": 3,
+        "balance": -20,
+        "total": 59.9,
+        "paid": false,
+        "currency": "EUR",
+        "discount": null,
+        "tags": ["urgent"],
+        "customer": {"name": "Ada Lovelace", "vat_id": null},
+        "lines": [{"product": "Notebook", "price": 19.9}]
+    })
+}
+
+fn invoice() -> Invoice {
+    Invoice {
+        number: "A-17".to_string(),
+        quantity: 3,
+        balance: -20,
+        total: 59.9,
+        paid: false,
+        currency: "EUR".to_string(),
+        discount: None,
+        tags: vec!["urgent".to_string()],
+        customer: Customer {
+            name: "Ada Lovelace".to_string(),
+            vat_id: None,
+        },
+        lines: vec![Line {
+            product: "Notebook".to_string(),
+            price: 19.9,
+        }],
+    }
+}
+
+#[test]
+fn grammars_match_their_fixtures() {
+    assert_eq!(
+        gbnf_grammar(&Invoice::field_descriptors()),
+        include_str!("fixtures/grammar/invoice.gbnf")
+    );
+    // Cased keys, escaped allowed values, maps and the rules of plain objects
+    assert_eq!(
+        gbnf_grammar(&Survey::field_descriptors()),
+        include_str!("fixtures/grammar/survey.gbnf")
+    );
+}
+
+#[test]
+fn newtypes_and_tuple_structs_are_their_field_and_arrays() {
+    assert_eq!(
+        gbnf_grammar(&Review::field_descriptors()),
+        [
+            r#"root ::= "{" ws "\"title\":" ws string ws "," ws "\"summary\":" ws string ws "," ws "\"rating\":" ws rating ws "}""#,
+            r#"rating ::= "[" ws unsigned ws "," ws unsigned ws "]""#,
+            r#"string ::= "\"" ( [^"\\\x7F\x00-\x1F] | "\\" ( ["\\/bfnrt] | "u" [0-9a-fA-F]{4} ) )* "\"""#,
+            r#"unsigned ::= "0" | [1-9] [0-9]{0,15}"#,
+            r#"ws ::= | " " | "\n" [ \t]{0,20}"#,
+            "",
+        ]
+        .join("\n")
+    );
+}
+
+#[test]
+fn rules_are_not_named_like_primitives() {
+    let grammar: String = gbnf_grammar(&Scored::field_descriptors());
+
+    assert!(grammar.starts_with(
+        r#"root ::= "{" ws "\"value\":" ws value-2 ws "}"
+value-2 ::= "{" ws "\"value\":" ws unsigned ws "}"
+"#
+    ));
+    assert!(!grammar.contains("\nvalue ::="));
+}
+
+#[test]
+fn skipped_fields_are_left_out() {
+    let grammar: String = gbnf_grammar_without_fields(
+        &Invoice::field_descriptors(),
+        &["tags".to_string(), "customer.vat_id".to_string()],
+    );
+
+    assert!(!grammar.contains("tags"));
+    assert!(!grammar.contains("vat_id"));
+    assert!(grammar.contains(r#"customer ::= "{" ws "\"name\":" ws string ws "}""#));
+    assert!(grammar.contains(r#""\"discount\":" ws ( number | null ) ws "," ws "\"customer\":""#));
+}
+
+#[test]
+fn a_constrained_answer_round_trips() {
+    let server = MockServer::always(success(&constrained_answer().to_string()));
+    let llm = LlamaCppLLM::from_openai(server.llm());
+
+    let extracted: Invoice = llm.generate_data(&Invoice::new(), TARGET, vec![]).unwrap();
+
+    assert_eq!(extracted, invoice());
+    let requests = server.requests();
+    let body: &Value = &requests[0].body;
+    assert_eq!(
+        body["grammar"],
+        json!(gbnf_grammar(&Invoice::field_descriptors()))
+    );
+    assert!(body.get("response_format").is_none());
+}
+
+#[test]
+fn the_grammar_of_the_call_is_sent_instead() {
+    let server = MockServer::always(success(&constrained_answer().to_string()));
+    let llm = LlamaCppLLM::new(server.address(), "", "test-model").unwrap();
+    let grammar: String = gbnf_grammar(&Invoice::field_descriptors()).replace("\"EUR\"", "\"CHF\"");
+
+    let _: Invoice = llm
+        .generate_data_with_options(
+            &Invoice::new(),
+            TARGET,
+            vec![],
+            &RequestOptions::default().with_grammar(grammar.clone()),
+        )
+        .unwrap();
+
+    assert_eq!(server.requests()[0].body["grammar"], json!(grammar));
+}
+
+#[test]
+fn other_providers_ignore_grammars() {
+    let server = MockServer::always(success(&constrained_answer().to_string()));
+    let options: RequestOptions = RequestOptions::default().with_grammar("root ::= \"{}\"");
+
+    let extracted: Invoice = server
+        .llm()
+        .generate_data_with_options(&Invoice::new(), TARGET, vec![], &options)
+        .unwrap();
+
+    assert_eq!(extracted, invoice());
+    let requests = server.requests();
+    let request = &requests[0];
+    assert!(request.body.get("grammar").is_none());
+    assert!(request.is_json_mode());
+}
+
+#[test]
+fn constrained_answers_are_not_coerced() {
+    let mut answer: Value = constrained_answer();
+    answer["quantity"] = json!("3");
+    let server = MockServer::always(success(&answer.to_string()));
+    let lenient = server.llm().with_leniency(LeniencyProfile::standard());
+
+    // The provider's leniency still applies to unconstrained answers
+    let extracted: Invoice = lenient
+        .generate_data(&Invoice::new(), TARGET, vec![])
+        .unwrap();
+    assert_eq!(extracted.quantity, 3);
+
+    let result: Result<Invoice, _> =
+        LlamaCppLLM::from_openai(lenient).generate_data(&Invoice::new(), TARGET, vec![]);
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn async_extraction_sends_the_grammar() {
+    let server = MockServer::always(success(&constrained_answer().to_string()));
+    let llm = LlamaCppLLM::from_openai(server.llm());
+
+    let extracted: Invoice = llm
+        .async_generate_data(&Invoice::new(), TARGET, vec![])
+        .await
+        .unwrap();
+
+    assert_eq!(extracted, invoice());
+    assert!(server.requests()[0].body["grammar"].is_string());
+}
@@ -0,0 +1,173 @@
+//! Distributed generation extracts the fields of a group with one request.
+
+mod support;
+
+use secretary::Task;
+use secretary::traits::{AsyncGenerateData, GenerateData};
+use serde::{Deserialize, Serialize};
+
+use support::fixtures::{empty_choices, field_result};
+use support::{MockServer, secretary_error};
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Filing {
+    #[task(instruction = "Extract the company name")]
+    pub company: String,
+    #[task(instruction = "Extract the revenue in millions", group = "finance")]
+    pub revenue: u32,
+    #[task(instruction = "Extract the currency code", group = "finance")]
+    pub currency: String,
+    #[task(instruction = "Extract the auditor", group = "people")]
+    pub auditor: Option<String>,
+    #[task(instruction = "Extract the chief executive", group = "people")]
+    pub ceo: String,
+    #[task(instruction = "Extract the fiscal year")]
+    pub year: u32,
+}
+
+const TARGET: &str = "Acme reported 120 million EUR of revenue in 2024 under CEO Jane Roe.";
+
+fn server(people: &str) -> MockServer {
+    MockServer::by_instruction(
+        vec![
+            ("Extract the company name", field_result("Acme")),
+            (
+                "Extract the revenue in millions",
+                field_result(r#"{"revenue": 120, "currency": "EUR"}"#),
+            ),
+            ("Extract the auditor", field_result(people)),
+            ("Extract the fiscal year", field_result("2024")),
+        ],
+        empty_choices(),
+    )
+}
+
+fn expected() -> Filing {
+    Filing {
+        company: "Acme".to_string(),
+        revenue: 120,
+        currency: "EUR".to_string(),
+        auditor: None,
+        ceo: "Jane Roe".to_string(),
+        year: 2024,
+    }
+}
+
+#[test]
+fn groups_share_a_request_entry() {
+    let requests = Filing::new().get_distributed_request_prompts();
+
+    let paths: Vec<&str> = requests
+        .iter()
+        .map(|request| request.field_path.as_str())
+        .collect();
+    assert_eq!(paths, vec!["company", "finance", "people", "year"]);
+    assert_eq!(requests[1].field_paths(), vec!["revenue", "currency"]);
+    assert!(
+        requests[1]
+            .prompt
+            .contains(r#"exactly the keys "revenue", "currency""#)
+    );
+    assert!(requests[1].prompt.contains("Extract the currency code"));
+    assert!(!requests[0].is_group());
+
+    let entries = Filing::new().get_system_prompts_for_distributed_generation();
+    assert_eq!(entries.len(), 4);
+    assert_eq!(entries[2].0, "people");
+}
+
+#[test]
+fn one_request_is_sent_per_group_and_ungrouped_field() {
+    let server = server(r#"{"auditor": null, "ceo": "Jane Roe"}"#);
+
+    let filing: Filing = server
+        .llm()
+        .fields_generate_data(&Filing::new(), TARGET, vec![])
+        .unwrap();
+
+    assert_eq!(filing, expected());
+    // Two groups and two ungrouped fields
+    assert_eq!(server.requests().len(), 4);
+}
+
+#[test]
+fn missing_optional_member_is_none() {
+    let server = server(r#"Here you go: {"ceo": "Jane Roe"}"#);
+
+    let filing: Filing = server
+        .llm()
+        .fields_generate_data(&Filing::new(), TARGET, vec![])
+        .unwrap();
+
+    assert_eq!(filing, expected());
+}
+
+#[test]
+fn missing_required_member_is_reported() {
+    let server = server(r#"{"auditor": "KPMG"}"#);
+
+    let error = server
+        .llm()
+        .fields_generate_data(&Filing::new(), TARGET, vec![])
+        .unwrap_err();
+
+    match secretary_error(&error) {
+        secretary::SecretaryError::FieldDeserializationError(error) => {
+            assert_eq!(error.failed_fields, vec!["ceo"]);
+        }
+        other => panic!("expected a field deserialization error, got {:?}", other),
+    }
+
+    let partial = server
+        .llm()
+        .fields_generate_partial_data(&Filing::new(), TARGET, vec![])
+        .unwrap();
+    assert_eq!(partial.failed_fields, vec!["ceo"]);
+    assert_eq!(partial.data.auditor.as_deref(), Some("KPMG"));
+    assert_eq!(partial.data.revenue, 120);
+}
+
+#[tokio::test]
+async fn async_groups_are_split_into_fields() {
+    let server = server(r#"{"auditor": null, "ceo": "Jane Roe"}"#);
+
+    let filing: Filing = server
+        .llm()
+        .async_fields_generate_data(&Filing::new(), TARGET, vec![])
+        .await
+        .unwrap();
+
+    assert_eq!(filing, expected());
+    assert_eq!(server.requests().len(), 4);
+}
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Address {
+    #[task(instruction = "Extract the street", group = "location")]
+    pub street: String,
+    #[task(instruction = "Extract the city", group = "location")]
+    pub city: String,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Office {
+    #[task(instruction = "Extract the office name", group = "location")]
+    pub name: String,
+    pub address: Address,
+}
+
+#[test]
+fn groups_stay_within_their_struct() {
+    let requests = Office::new().get_distributed_request_prompts();
+
+    let paths: Vec<&str> = requests
+        .iter()
+        .map(|request| request.field_path.as_str())
+        .collect();
+    // A group of one field keeps its own request
+    assert_eq!(paths, vec!["name", "address.location"]);
+    assert_eq!(
+        requests[1].field_paths(),
+        vec!["address.street", "address.city"]
+    );
+}
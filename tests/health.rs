@@ -0,0 +1,120 @@
+//! Health checks probe the model route or a one-token chat completion, and tell network
+//! failures apart from rejected credentials and unknown models.
+
+mod support;
+
+use std::net::TcpListener;
+
+use secretary::llm_providers::azure::AzureOpenAILLM;
+use secretary::llm_providers::health::{HealthFailure, HealthReport};
+use secretary::llm_providers::openai::OpenAILLM;
+use secretary::traits::IsLLM;
+use serde_json::json;
+
+use support::fixtures::success;
+use support::{MockResponse, MockServer};
+
+#[test]
+fn openai_looks_the_model_up_without_spending_tokens() {
+    let server = MockServer::always(MockResponse::new(
+        200,
+        json!({"id": "test-model", "object": "model"}),
+    ));
+
+    let report: HealthReport = server.llm().health_check().unwrap();
+
+    assert!(report.is_healthy());
+    assert!(report.reachable && report.model_available);
+    let requests = server.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].path, "/models/test-model");
+}
+
+#[test]
+fn rejected_credentials_and_unknown_models_are_told_apart() {
+    let server = MockServer::always(MockResponse::new(
+        401,
+        json!({"error": {"message": "Incorrect API key provided", "code": "invalid_api_key"}}),
+    ));
+    let report: HealthReport = server.llm().health_check().unwrap();
+    assert_eq!(report.failure, Some(HealthFailure::Authentication));
+    assert!(report.reachable && !report.model_available);
+    assert!(report.detail.unwrap().contains("Incorrect API key"));
+
+    let server = MockServer::always(MockResponse::new(
+        404,
+        json!({"error": {"message": "The model `gpt-9` does not exist", "code": "model_not_found"}}),
+    ));
+    let report: HealthReport = OpenAILLM::new(server.address(), "test-key", "gpt-9")
+        .unwrap()
+        .health_check()
+        .unwrap();
+    assert_eq!(report.failure, Some(HealthFailure::UnknownModel));
+    assert!(report.reachable && !report.model_available);
+}
+
+#[test]
+fn nothing_listening_is_a_network_failure() {
+    let address: String = {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        format!("http://{}", listener.local_addr().unwrap())
+    };
+
+    let report: HealthReport = OpenAILLM::new(&address, "test-key", "test-model")
+        .unwrap()
+        .health_check()
+        .unwrap();
+
+    assert_eq!(report.failure, Some(HealthFailure::Network));
+    assert!(!report.reachable && !report.model_available);
+}
+
+#[test]
+fn servers_without_the_model_route_get_a_one_token_completion() {
+    let server = MockServer::start(|request| {
+        if request.path.starts_with("/models/") {
+            MockResponse {
+                status: 404,
+                body: b"Not Found".to_vec(),
+                headers: Vec::new(),
+                events: None,
+            }
+        } else {
+            success("p")
+        }
+    });
+
+    let report: HealthReport = server.llm().health_check().unwrap();
+
+    assert!(report.is_healthy());
+    let requests = server.requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[1].path, "/chat/completions");
+    assert_eq!(requests[1].body["max_tokens"], 1);
+}
+
+#[tokio::test]
+async fn azure_probes_the_deployment_through_its_chat_route() {
+    let server = MockServer::always(MockResponse::new(
+        404,
+        json!({"error": {
+            "code": "DeploymentNotFound",
+            "message": "The API deployment for this resource does not exist."
+        }}),
+    ));
+    let llm = AzureOpenAILLM::new(
+        server.address(),
+        "test-key",
+        "missing-deployment",
+        "2024-02-15-preview",
+    );
+
+    let report: HealthReport = llm.async_health_check().await.unwrap();
+
+    assert_eq!(report.failure, Some(HealthFailure::UnknownModel));
+    assert!(
+        server.requests()[0]
+            .path
+            .starts_with("/openai/deployments/missing-deployment/chat/completions")
+    );
+}
@@ -0,0 +1,220 @@
+//! Hinted field values are listed in the prompt, kept in the result and not requested.
+
+mod support;
+
+use std::collections::HashMap;
+
+use secretary::Task;
+use secretary::hints::{HintConflict, Hints};
+use secretary::metadata::GenerationResult;
+use secretary::partial::PartialData;
+use secretary::request::RequestOptions;
+use secretary::traits::{AsyncGenerateData, GenerateData};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use support::fixtures::{empty_choices, field_result, success};
+use support::{MockServer, Person, TARGET};
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Vendor {
+    #[task(instruction = "Extract the vendor's name")]
+    pub name: String,
+    #[task(instruction = "Extract the vendor's country")]
+    pub country: String,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Invoice {
+    #[task(instruction = "Extract the invoice number")]
+    pub number: String,
+    #[task(instruction = "Extract the total amount")]
+    pub total: f64,
+    pub vendor: Vendor,
+}
+
+const INVOICE: &str = "Invoice INV-7 from Acme GmbH, Germany, over 120.50 EUR.";
+
+fn acme() -> Vendor {
+    Vendor {
+        name: "Acme".to_string(),
+        country: "DE".to_string(),
+    }
+}
+
+fn invoice_server() -> MockServer {
+    MockServer::by_instruction(
+        vec![
+            ("Extract the invoice number", field_result("INV-7")),
+            ("Extract the total amount", field_result("120.5")),
+            ("Extract the vendor's name", field_result("Acme GmbH")),
+            ("Extract the vendor's country", field_result("Germany")),
+        ],
+        empty_choices(),
+    )
+}
+
+fn john() -> RequestOptions {
+    RequestOptions::default().with_hints(Person::hints().name("John Smith").build_hints())
+}
+
+#[test]
+fn the_builder_sets_one_value_per_field() {
+    let hints: Hints<Invoice> = Invoice::hints()
+        .number("INV-7")
+        .vendor(acme())
+        .build_hints();
+
+    assert_eq!(hints.values().len(), 2);
+    assert_eq!(hints.values()["number"], json!("INV-7"));
+    assert_eq!(
+        hints.values()["vendor"],
+        json!({"name": "Acme", "country": "DE"})
+    );
+    assert!(Invoice::hints().build_hints().is_empty());
+}
+
+#[test]
+fn hinted_values_are_listed_and_override_the_model() {
+    let server = MockServer::always(success(r#"{"name": "Ada", "age": 36}"#));
+
+    let person: Person = server
+        .llm()
+        .generate_data_with_options(&Person::new(), TARGET, vec![], &john())
+        .unwrap();
+
+    assert_eq!(person.name, "John Smith");
+    assert_eq!(person.age, 36);
+    assert!(
+        server.requests()[0]
+            .prompt()
+            .contains("- Known values (do not change these): name = \"John Smith\"")
+    );
+}
+
+#[test]
+fn hinted_fields_the_model_left_out_do_not_fail() {
+    let server = MockServer::always(success(r#"{"age": 36}"#));
+
+    let person: Person = server
+        .llm()
+        .generate_data_with_options(&Person::new(), TARGET, vec![], &john())
+        .unwrap();
+
+    assert_eq!(person.name, "John Smith");
+}
+
+#[test]
+fn distributed_generation_skips_hinted_fields() {
+    let server = invoice_server();
+    let options = RequestOptions::default().with_hints(
+        Invoice::hints()
+            .number("INV-0007")
+            .vendor(acme())
+            .build_hints(),
+    );
+
+    let invoice: Invoice = server
+        .llm()
+        .fields_generate_data_with_options(&Invoice::new(), INVOICE, vec![], &options)
+        .unwrap();
+
+    assert_eq!(
+        invoice,
+        Invoice {
+            number: "INV-0007".to_string(),
+            total: 120.5,
+            vendor: acme(),
+        }
+    );
+    let requests = server.requests();
+    assert_eq!(requests.len(), 1);
+    assert!(requests[0].prompt().contains("Extract the total amount"));
+    assert!(requests[0].prompt().contains("number = \"INV-0007\""));
+}
+
+#[test]
+fn paths_into_nested_tasks_skip_only_their_field() {
+    let server = invoice_server();
+    let options = RequestOptions::default()
+        .with_hints(Hints::<Invoice>::new().with_value("vendor.country", "DE"));
+
+    let invoice: Invoice = server
+        .llm()
+        .fields_generate_data_with_options(&Invoice::new(), INVOICE, vec![], &options)
+        .unwrap();
+
+    assert_eq!(invoice.vendor.name, "Acme GmbH");
+    assert_eq!(invoice.vendor.country, "DE");
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[test]
+fn partial_extraction_skips_and_sets_hinted_fields() {
+    let server = invoice_server();
+    let options = RequestOptions::default().with_hints(Invoice::hints().total(99.0).build_hints());
+
+    let partial: PartialData<Invoice> = server
+        .llm()
+        .fields_generate_partial_data_with_options(&Invoice::new(), INVOICE, vec![], &options)
+        .unwrap();
+
+    assert!(partial.failed_fields.is_empty());
+    assert_eq!(partial.data.total, 99.0);
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[test]
+fn adaptive_generation_reports_conflicts() {
+    let server = MockServer::always(success(r#"{"name": "Ada", "age": 36}"#));
+    let options = RequestOptions::default()
+        .with_hints(Person::hints().name("John Smith").age(36u32).build_hints());
+
+    let result: GenerationResult<Person> = server
+        .llm()
+        .generate_data_adaptive_with_options(&Person::new(), TARGET, vec![], &options)
+        .unwrap();
+
+    assert_eq!(result.data.name, "John Smith");
+    assert_eq!(
+        result.metadata.hint_conflicts,
+        vec![HintConflict {
+            field_path: "name".to_string(),
+            hinted: json!("John Smith"),
+            returned: json!("Ada"),
+        }]
+    );
+}
+
+#[test]
+fn hinted_braces_are_not_rendered_as_placeholders() {
+    let server = MockServer::always(success(r#"{"name": "Ada", "age": 36}"#));
+    let options = RequestOptions::default()
+        .with_template_vars(HashMap::new())
+        .with_hints(Person::hints().name("{nickname}").build_hints());
+
+    let person: Person = server
+        .llm()
+        .generate_data_with_options(&Person::new(), TARGET, vec![], &options)
+        .unwrap();
+
+    assert_eq!(person.name, "{nickname}");
+    assert!(
+        server.requests()[0]
+            .prompt()
+            .contains("name = \"{nickname}\"")
+    );
+}
+
+#[tokio::test]
+async fn async_generation_keeps_hinted_values() {
+    let server = MockServer::always(success(r#"{"name": "Ada", "age": 36}"#));
+
+    let person: Person = server
+        .llm()
+        .async_generate_data_with_options(&Person::new(), TARGET, vec![], &john())
+        .await
+        .unwrap();
+
+    assert_eq!(person.name, "John Smith");
+}
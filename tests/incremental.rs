@@ -0,0 +1,188 @@
+//! Incremental re-extraction sends only the changed excerpts of an edited target.
+
+mod support;
+
+use secretary::Task;
+use secretary::incremental::{RegenerateOptions, RegenerationPath};
+use secretary::metadata::GenerationResult;
+use secretary::traits::{AsyncGenerateData, GenerateData};
+use serde::{Deserialize, Serialize};
+
+use support::MockServer;
+use support::fixtures::success;
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Contract {
+    #[task(instruction = "Extract the contract title")]
+    pub title: String,
+    #[task(instruction = "Extract the parties")]
+    pub parties: Vec<String>,
+    #[task(instruction = "Extract the notice period in days")]
+    pub notice_days: u32,
+}
+
+const UPDATED_JSON: &str =
+    r#"{"title": "Service Agreement", "parties": ["Acme", "Globex"], "notice_days": 60}"#;
+
+fn contract(notice: &str) -> String {
+    let mut lines: Vec<String> = vec![
+        "Service Agreement".to_string(),
+        "Between Acme and Globex.".to_string(),
+    ];
+    lines.extend((1..=16).map(|clause| format!("Clause {}: standard terms apply.", clause)));
+    lines.insert(
+        10,
+        format!("Either party may terminate with {} days notice.", notice),
+    );
+    lines.join("\n")
+}
+
+fn previous() -> Contract {
+    Contract {
+        title: "Service Agreement".to_string(),
+        parties: vec!["Acme".to_string(), "Globex".to_string()],
+        notice_days: 30,
+    }
+}
+
+#[test]
+fn a_small_edit_sends_only_the_changed_excerpt() {
+    let server = MockServer::always(success(UPDATED_JSON));
+
+    let result: GenerationResult<Contract> = server
+        .llm()
+        .regenerate_data(
+            &Contract::new(),
+            &contract("30"),
+            &contract("60"),
+            &previous(),
+            vec![],
+            &RegenerateOptions::default().with_context_lines(1),
+        )
+        .unwrap();
+
+    assert_eq!(result.data.notice_days, 60);
+    assert_eq!(result.data.title, "Service Agreement");
+    assert_eq!(result.data.parties, vec!["Acme", "Globex"]);
+    assert_eq!(
+        result.metadata.regeneration_path,
+        Some(RegenerationPath::Incremental)
+    );
+    // 2 of the 38 lines of both versions changed
+    assert_eq!(result.metadata.changed_ratio, Some(2.0 / 38.0));
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 1);
+    let prompt: String = requests[0].prompt();
+    assert!(prompt.contains("Extract the notice period in days"));
+    assert!(prompt.contains("\"notice_days\": 30"));
+    assert!(prompt.contains(
+        "@@ line 10 @@\n  Clause 8: standard terms apply.\n- Either party may terminate with 30 days notice.\n+ Either party may terminate with 60 days notice.\n  Clause 9: standard terms apply.\n"
+    ));
+    // Unchanged lines away from the edit are not sent
+    assert!(!prompt.contains("Between Acme and Globex."));
+    assert!(!prompt.contains("Clause 16"));
+}
+
+#[test]
+fn a_rewrite_falls_back_to_a_full_extraction() {
+    let server = MockServer::always(success(UPDATED_JSON));
+    let rewritten: &str = "Service Agreement\nBetween Acme and Globex.\nNotice: 60 days.";
+
+    let result: GenerationResult<Contract> = server
+        .llm()
+        .regenerate_data(
+            &Contract::new(),
+            &contract("30"),
+            rewritten,
+            &previous(),
+            vec![],
+            &RegenerateOptions::default(),
+        )
+        .unwrap();
+
+    assert_eq!(result.data.notice_days, 60);
+    assert_eq!(
+        result.metadata.regeneration_path,
+        Some(RegenerationPath::Full)
+    );
+    assert!(result.metadata.changed_ratio.unwrap() > 0.2);
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 1);
+    assert!(requests[0].prompt().contains(rewritten));
+    assert!(!requests[0].prompt().contains("Previous result"));
+}
+
+#[test]
+fn an_unchanged_target_returns_the_previous_result_without_a_request() {
+    let server = MockServer::always(success(UPDATED_JSON));
+
+    let result: GenerationResult<Contract> = server
+        .llm()
+        .regenerate_data(
+            &Contract::new(),
+            &contract("30"),
+            &contract("30"),
+            &previous(),
+            vec![],
+            &RegenerateOptions::default(),
+        )
+        .unwrap();
+
+    assert_eq!(result.data, previous());
+    assert_eq!(
+        result.metadata.regeneration_path,
+        Some(RegenerationPath::Unchanged)
+    );
+    assert_eq!(result.metadata.changed_ratio, Some(0.0));
+    assert!(server.requests().is_empty());
+}
+
+#[test]
+fn the_ratio_threshold_is_configurable() {
+    let server = MockServer::always(success(UPDATED_JSON));
+
+    let result: GenerationResult<Contract> = server
+        .llm()
+        .regenerate_data(
+            &Contract::new(),
+            &contract("30"),
+            &contract("60"),
+            &previous(),
+            vec![],
+            &RegenerateOptions::default().with_max_changed_ratio(0.01),
+        )
+        .unwrap();
+
+    assert_eq!(
+        result.metadata.regeneration_path,
+        Some(RegenerationPath::Full)
+    );
+    assert!(server.requests()[0].prompt().contains("Clause 16"));
+}
+
+#[tokio::test]
+async fn async_regeneration_sends_only_the_changed_excerpt() {
+    let server = MockServer::always(success(UPDATED_JSON));
+
+    let result: GenerationResult<Contract> = server
+        .llm()
+        .async_regenerate_data(
+            &Contract::new(),
+            &contract("30"),
+            &contract("60"),
+            &previous(),
+            vec![],
+            &RegenerateOptions::default(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(result.data.notice_days, 60);
+    assert_eq!(
+        result.metadata.regeneration_path,
+        Some(RegenerationPath::Incremental)
+    );
+    assert!(!server.requests()[0].prompt().contains("Clause 16"));
+}
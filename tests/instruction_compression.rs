@@ -0,0 +1,206 @@
+//! Verbose instructions are shortened once by the LLM and applied only through a `CompiledTask`.
+
+mod support;
+
+use secretary::Task;
+use secretary::compiled::{CompileOptions, CompiledTask, ExtractionPlan};
+use secretary::optimize::{CompressionReport, compress_instructions};
+use secretary::tokens::estimate_tokens;
+use secretary::traits::GenerateData;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use support::fixtures::success;
+use support::{MockServer, secretary_error};
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct LineItem {
+    #[task(
+        instruction = "Please carefully extract the full name of the product exactly as it is written on the line of the invoice"
+    )]
+    pub product: String,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Invoice {
+    #[task(
+        instruction = "Please extract the complete legal name of the vendor that issued this invoice, as printed in the header"
+    )]
+    pub vendor: String,
+    #[task(instruction = "Extract the total")]
+    pub total: f64,
+    #[task(
+        instruction = "Please list every single line item of the invoice, in the order in which they appear"
+    )]
+    pub items: Vec<LineItem>,
+}
+
+/// The canned answer: two shorter instructions, one that is not shorter, an empty one and
+/// one for a field that does not exist.
+fn compressed() -> String {
+    format!(
+        "<result>{}</result>",
+        json!({
+            "vendor": "Vendor's legal name from the header",
+            "total": "Extract the invoice's grand total amount",
+            "items": "",
+            "items[].product": "Product name as written",
+            "currency": "The currency"
+        })
+    )
+}
+
+fn replacements() -> Vec<(String, String)> {
+    vec![
+        (
+            "vendor".to_string(),
+            "Vendor's legal name from the header".to_string(),
+        ),
+        (
+            "items[].product".to_string(),
+            "Product name as written".to_string(),
+        ),
+    ]
+}
+
+#[tokio::test]
+async fn shorter_instructions_are_proposed_in_one_request() {
+    let server = MockServer::always(success(&compressed()));
+
+    let proposed: Vec<(String, String)> = compress_instructions::<Invoice, _>(&server.llm(), 0.5)
+        .await
+        .unwrap();
+
+    assert_eq!(proposed, replacements());
+    let requests = server.requests();
+    assert_eq!(requests.len(), 1);
+    let prompt: String = requests[0].prompt();
+    assert!(prompt.contains("about 50% of its length"));
+    assert!(prompt.contains("\"items[].product\": \"Please carefully extract"));
+    assert!(prompt.contains("\"total\": \"Extract the total\""));
+}
+
+#[tokio::test]
+async fn an_answer_that_is_not_an_object_is_an_error() {
+    let server = MockServer::always(success("<result>shorter, I promise</result>"));
+
+    let error = compress_instructions::<Invoice, _>(&server.llm(), 0.5)
+        .await
+        .unwrap_err();
+
+    assert!(matches!(
+        secretary_error(&error),
+        secretary::SecretaryError::JsonParsingError { raw_content, .. }
+            if raw_content == "shorter, I promise"
+    ));
+}
+
+#[test]
+fn the_report_measures_the_savings() {
+    let report: CompressionReport = CompressionReport::measure::<Invoice>(&replacements());
+
+    assert_eq!(report.replacements(), replacements());
+    assert_eq!(report.fields[0].path, "vendor");
+    assert_eq!(
+        report.fields[0].original_tokens,
+        estimate_tokens(
+            "Please extract the complete legal name of the vendor that issued this invoice, as printed in the header"
+        )
+    );
+    assert_eq!(
+        report.fields[0].compressed_tokens,
+        estimate_tokens("Vendor's legal name from the header")
+    );
+
+    let original: CompiledTask<Invoice> = Invoice::new().compile(CompileOptions::default());
+    assert_eq!(
+        report.system_prompt_tokens,
+        estimate_tokens(original.system_prompt())
+    );
+    let saved: usize = report
+        .fields
+        .iter()
+        .map(|field| field.original_tokens - field.compressed_tokens)
+        .sum();
+    // Each replaced instruction is in the system prompt at least once, give or take the
+    // rounding of the estimate
+    assert!(report.saved_tokens() + report.fields.len() >= saved);
+    assert!(report.compressed_field_prompt_tokens < report.field_prompt_tokens);
+    assert!(report.ratio() < 1.0);
+
+    // Unknown paths are not measured
+    let unknown = vec![("currency".to_string(), "The currency".to_string())];
+    let report: CompressionReport = CompressionReport::measure::<Invoice>(&unknown);
+    assert!(report.fields.is_empty());
+    assert_eq!(report.saved_tokens(), 0);
+    assert_eq!(report.ratio(), 1.0);
+}
+
+#[test]
+fn overrides_apply_only_to_the_compiled_task() {
+    let compiled: CompiledTask<Invoice> = Invoice::new()
+        .compile(CompileOptions::default().with_instruction_overrides(replacements()));
+
+    assert_eq!(compiled.instruction_overrides(), replacements().as_slice());
+    assert!(
+        compiled
+            .system_prompt()
+            .contains("vendor: Vendor's legal name from the header, JSON String")
+    );
+    assert!(!compiled.system_prompt().contains("Please extract"));
+    assert_eq!(
+        compiled.json_schema()["properties"]["vendor"]["description"],
+        "Vendor's legal name from the header"
+    );
+    for (field_prompt, message) in compiled.field_requests("an invoice", &[]) {
+        assert!(!field_prompt.prompt.contains("Please extract"));
+        assert!(!message.content.as_str().contains("Please extract"));
+    }
+
+    // The Task itself keeps its instructions
+    assert!(
+        Invoice::new()
+            .get_system_prompt()
+            .contains("Please extract")
+    );
+}
+
+#[test]
+fn the_target_is_never_rewritten() {
+    let compiled: CompiledTask<Invoice> = Invoice::new()
+        .compile(CompileOptions::default().with_instruction_overrides(replacements()));
+    let target: &str = "vendor: Please extract the complete legal name of the vendor that issued this invoice, as printed in the header, ACME";
+    let more: Vec<String> = vec!["Amounts are in EUR".to_string()];
+
+    for instructions in [Vec::new(), more] {
+        let messages = compiled.prompt_messages(target, &instructions);
+        let user: &str = messages.last().unwrap().content.as_str();
+        assert!(user.contains(target));
+        assert!(
+            messages[0]
+                .content
+                .as_str()
+                .contains("Vendor's legal name from the header")
+        );
+    }
+}
+
+#[test]
+fn a_compiled_task_extracts_with_the_overrides() {
+    let server = MockServer::always(success(
+        r#"{"vendor": "ACME", "total": 12.5, "items": [{"product": "Anvil"}]}"#,
+    ));
+    let compiled: CompiledTask<Invoice> = Invoice::new()
+        .compile(CompileOptions::default().with_instruction_overrides(replacements()));
+
+    let invoice: Invoice = server
+        .llm()
+        .generate_data(&compiled, "ACME: Anvil, 12.50", vec![])
+        .unwrap();
+
+    assert_eq!(invoice.vendor, "ACME");
+    let prompt: String = server.requests()[0].prompt();
+    assert!(prompt.contains("Product name as written"));
+    assert!(!prompt.contains("Please carefully extract"));
+    assert!(prompt.contains("Please list every single line item"));
+}
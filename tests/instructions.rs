@@ -0,0 +1,204 @@
+//! Additional instructions are merged from several layers and reach the prompt unchanged.
+
+mod support;
+
+use secretary::instructions::{DEFAULT_SOURCE, Instruction, InstructionSet};
+use secretary::metadata::{GenerationResult, GenerationWarning};
+use secretary::traits::{AsyncGenerateData, GenerateData};
+use secretary::utilities::format_additional_instructions;
+
+use support::fixtures::success;
+use support::{ADA_JSON, MockServer, Person, TARGET, ada};
+
+fn texts(instructions: &InstructionSet) -> Vec<&str> {
+    instructions
+        .entries()
+        .iter()
+        .map(|instruction| instruction.text.as_str())
+        .collect()
+}
+
+#[test]
+fn merging_orders_by_priority_then_insertion() {
+    let low = InstructionSet::new()
+        .with_instruction("first low", "tenant", 1)
+        .with_instruction("second low", "tenant", 1);
+    let mixed = InstructionSet::new()
+        .with_instruction("high", "policy", 9)
+        .with_instruction("third low", "call", 1)
+        .with_instruction("negative", "call", -3);
+
+    let merged: InstructionSet = low.merge(mixed);
+
+    assert_eq!(
+        texts(&merged),
+        vec!["high", "first low", "second low", "third low", "negative"]
+    );
+    assert_eq!(merged.entries()[0], Instruction::new("high", "policy", 9));
+}
+
+#[test]
+fn merging_drops_exact_and_whitespace_duplicates() {
+    let policy = InstructionSet::new()
+        .with_instruction("Use ISO 8601 dates.", "policy", 0)
+        .with_instruction("Round prices to cents.", "policy", 0);
+    let call = InstructionSet::new()
+        .with_instruction("Use ISO 8601 dates.", "call", 5)
+        .with_instruction("  Round prices\tto\n cents. ", "call", 0)
+        .with_instruction("round prices to cents.", "call", 0);
+
+    let merged: InstructionSet = policy.merge(call);
+
+    // The higher priority copy is kept, and case still matters
+    assert_eq!(
+        texts(&merged),
+        vec![
+            "Use ISO 8601 dates.",
+            "Round prices to cents.",
+            "round prices to cents."
+        ]
+    );
+    assert_eq!(merged.entries()[0].source, "call");
+    assert_eq!(merged.entries()[1].source, "policy");
+    assert_eq!(merged.len(), 3);
+}
+
+#[test]
+fn conversions_keep_every_instruction_in_order() {
+    let strings: Vec<String> = vec!["b".to_string(), "a".to_string(), "b".to_string()];
+
+    for converted in [
+        InstructionSet::from(strings.clone()),
+        InstructionSet::from(&strings),
+        InstructionSet::from(&["b", "a", "b"][..]),
+        InstructionSet::from(&["b", "a", "b"]),
+    ] {
+        assert_eq!(converted.to_vec(), strings);
+        assert!(
+            converted.entries().iter().all(
+                |instruction| instruction.source == DEFAULT_SOURCE && instruction.priority == 0
+            )
+        );
+    }
+
+    let set = InstructionSet::from(&strings);
+    assert_eq!(InstructionSet::from(&set), set);
+    assert!(InstructionSet::from(Vec::new()).is_empty());
+}
+
+#[test]
+fn rendering_matches_the_plain_list() {
+    let strings: Vec<String> = vec!["Be precise".to_string(), "Use metric units".to_string()];
+
+    assert_eq!(
+        InstructionSet::from(&strings).render(),
+        format_additional_instructions(&strings)
+    );
+    assert_eq!(InstructionSet::new().render(), "");
+}
+
+#[test]
+fn always_and_never_the_same_thing_conflict() {
+    let instructions = InstructionSet::new()
+        .with_instruction("Always include the currency symbol.", "policy", 0)
+        .with_instruction("Never guess missing values.", "policy", 0)
+        .with_instruction("never INCLUDE the   currency symbol", "call", 0)
+        .with_instruction("Never include the tax, always round up.", "call", 0);
+
+    let conflicts: Vec<(&str, &str)> = instructions
+        .conflicts()
+        .into_iter()
+        .map(|(always, never)| (always.text.as_str(), never.text.as_str()))
+        .collect();
+
+    assert_eq!(
+        conflicts,
+        vec![(
+            "Always include the currency symbol.",
+            "never INCLUDE the   currency symbol"
+        )]
+    );
+}
+
+#[test]
+fn sets_and_plain_lists_send_the_same_prompt() {
+    let server = MockServer::always(success(ADA_JSON));
+    let llm = server.llm();
+    let strings: Vec<String> = vec!["Be precise".to_string()];
+
+    let from_strings: Person = llm.generate_data(&Person::new(), TARGET, &strings).unwrap();
+    let from_slice: Person = llm
+        .generate_data(&Person::new(), TARGET, &["Be precise"])
+        .unwrap();
+    let from_set: Person = llm
+        .generate_data(
+            &Person::new(),
+            TARGET,
+            InstructionSet::new().with_instruction("Be precise", "policy", 3),
+        )
+        .unwrap();
+
+    assert_eq!((from_strings, from_slice, from_set), (ada(), ada(), ada()));
+    let requests = server.requests();
+    assert!(
+        requests[0]
+            .prompt()
+            .contains("Additional instructions:\n- Be precise\n")
+    );
+    assert_eq!(requests[0].body, requests[1].body);
+    assert_eq!(requests[0].body, requests[2].body);
+}
+
+#[test]
+fn conflicting_instructions_are_flagged() {
+    let server = MockServer::always(success(ADA_JSON));
+    let instructions = InstructionSet::new()
+        .with_instruction("Always report the age.", "policy", 0)
+        .with_instruction("Never report the age.", "call", 0);
+
+    let result: GenerationResult<Person> = server
+        .llm()
+        .generate_data_adaptive(&Person::new(), TARGET, &instructions)
+        .unwrap();
+
+    assert_eq!(result.data, ada());
+    assert_eq!(
+        result.metadata.warnings,
+        vec![GenerationWarning::ConflictingInstructions {
+            always: "Always report the age.".to_string(),
+            never: "Never report the age.".to_string(),
+        }]
+    );
+
+    let result: GenerationResult<Person> = server
+        .llm()
+        .generate_data_adaptive(
+            &Person::new(),
+            TARGET,
+            vec!["Always report the age.".to_string()],
+        )
+        .unwrap();
+    assert!(result.metadata.warnings.is_empty());
+}
+
+#[tokio::test]
+async fn async_generation_accepts_sets() {
+    let server = MockServer::always(success(ADA_JSON));
+    let instructions = InstructionSet::new()
+        .with_instruction("Always report the age.", "policy", 0)
+        .with_instruction("Never report the age.", "call", 0);
+
+    let result: GenerationResult<Person> = server
+        .llm()
+        .async_generate_data_adaptive(&Person::new(), TARGET, &instructions)
+        .await
+        .unwrap();
+
+    assert_eq!(result.data, ada());
+    assert_eq!(result.metadata.warnings.len(), 1);
+    assert!(
+        server.requests()[0]
+            .prompt()
+            .contains("- Always report the age.\n- Never report the age.\n")
+    );
+}
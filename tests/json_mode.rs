@@ -0,0 +1,83 @@
+//! JSON output is requested with `response_format`, in the prompt, or with whichever of the
+//! two the server accepts.
+
+#![allow(clippy::needless_borrows_for_generic_args)]
+
+mod support;
+
+use secretary::constants::JSON_ONLY_INSTRUCTION;
+use secretary::llm_providers::json_mode::JsonModeStrategy;
+use secretary::traits::GenerateData;
+use serde_json::json;
+
+use support::fixtures::success;
+use support::{ADA_JSON, MockResponse, MockServer, Person, RecordedRequest, TARGET, ada};
+
+#[test]
+fn prompt_only_asks_for_json_in_the_prompt() {
+    let server = MockServer::always(success(ADA_JSON));
+    let llm = server
+        .llm()
+        .with_json_mode_strategy(JsonModeStrategy::PromptOnly);
+
+    assert_eq!(
+        llm.generate_data(&Person::new(), TARGET, &vec![]).unwrap(),
+        ada()
+    );
+
+    let request: &RecordedRequest = &server.requests()[0];
+    assert!(!request.is_json_mode());
+    assert!(
+        request.body["messages"][1]["content"]
+            .as_str()
+            .unwrap()
+            .ends_with(JSON_ONLY_INSTRUCTION)
+    );
+}
+
+#[test]
+fn auto_falls_back_once_response_format_is_rejected() {
+    let server = MockServer::start(|request| {
+        if request.is_json_mode() {
+            MockResponse::new(
+                400,
+                json!({"error": {"message": "Unrecognized request argument supplied: response_format"}}),
+            )
+        } else {
+            success(ADA_JSON)
+        }
+    });
+    let llm = server.llm().with_json_mode_strategy(JsonModeStrategy::Auto);
+
+    assert_eq!(
+        llm.generate_data(&Person::new(), TARGET, &vec![]).unwrap(),
+        ada()
+    );
+    assert_eq!(
+        llm.generate_data(&Person::new(), TARGET, &vec![]).unwrap(),
+        ada()
+    );
+
+    // The rejection is remembered, so the second call is not rejected again
+    let json_mode: Vec<bool> = server
+        .requests()
+        .iter()
+        .map(RecordedRequest::is_json_mode)
+        .collect();
+    assert_eq!(json_mode, vec![true, false, false]);
+}
+
+#[test]
+fn native_is_the_default() {
+    let server = MockServer::always(success(ADA_JSON));
+
+    server
+        .llm()
+        .generate_data(&Person::new(), TARGET, &vec![])
+        .unwrap();
+
+    assert_eq!(
+        server.requests()[0].body["response_format"],
+        json!({"type": "json_object"})
+    );
+}
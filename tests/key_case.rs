@@ -0,0 +1,308 @@
+//! Structs with `#[task(key_case = "...")]` show the model their keys in that case, and the
+//! keys of the answer are converted back to the field names before deserialization.
+
+mod support;
+
+use std::collections::HashMap;
+
+use secretary::Task;
+use secretary::casing::{KeyCase, to_native_keys, to_prompt_keys};
+use secretary::response_format::ResponseFormat;
+use secretary::schema::json_schema;
+use secretary::traits::{AsyncGenerateData, GenerateData};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use support::MockServer;
+use support::fixtures::{field_result, success};
+
+const CASES: [KeyCase; 4] = [
+    KeyCase::Snake,
+    KeyCase::Camel,
+    KeyCase::Kebab,
+    KeyCase::Pascal,
+];
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[task(key_case = "camelCase")]
+struct LineItem {
+    #[task(instruction = "Extract the product name")]
+    pub product_name: String,
+    #[task(instruction = "Extract the quantity as a number")]
+    pub unit_count: u32,
+}
+
+/// A nested Task keeps its own case.
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Address {
+    #[task(instruction = "Extract the postal code of the billing address")]
+    pub postal_code: String,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[task(key_case = "camelCase", empty_defaults)]
+struct Invoice {
+    #[task(instruction = "Extract the invoice number")]
+    pub invoice_number: String,
+    #[task(instruction = "Extract the label of the second line")]
+    pub line_item_2: String,
+    pub billing_address: Address,
+    #[task(instruction = "Extract every line of the invoice")]
+    pub line_items: Vec<LineItem>,
+    #[task(instruction = "Extract the lines of the invoice by their SKU")]
+    pub items_by_sku: HashMap<String, LineItem>,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[task(key_case = "kebab-case")]
+struct Contact {
+    #[task(instruction = "Extract the first name", group = "name")]
+    pub first_name: String,
+    #[task(instruction = "Extract the last name", group = "name")]
+    pub last_name: String,
+    #[task(instruction = "Extract the email address")]
+    pub email_address: String,
+}
+
+const TARGET: &str = "Invoice INV-7 to SW1A 1AA: 3 widgets, freight, and 1 gadget (SKU sku_001).";
+
+const INVOICE_JSON: &str = r#"{
+    "invoiceNumber": "INV-7",
+    "lineItem2": "Freight",
+    "billingAddress": {"postal_code": "SW1A 1AA"},
+    "lineItems": [{"productName": "Widget", "unitCount": 3}],
+    "itemsBySku": {"sku_001": {"productName": "Gadget", "unitCount": 1}}
+}"#;
+
+fn invoice() -> Invoice {
+    Invoice {
+        invoice_number: "INV-7".to_string(),
+        line_item_2: "Freight".to_string(),
+        billing_address: Address {
+            postal_code: "SW1A 1AA".to_string(),
+        },
+        line_items: vec![LineItem {
+            product_name: "Widget".to_string(),
+            unit_count: 3,
+        }],
+        items_by_sku: HashMap::from([(
+            "sku_001".to_string(),
+            LineItem {
+                product_name: "Gadget".to_string(),
+                unit_count: 1,
+            },
+        )]),
+    }
+}
+
+#[test]
+fn snake_names_are_converted_into_every_case() {
+    let expected: [(&str, [&str; 4]); 8] = [
+        ("name", ["name", "name", "name", "Name"]),
+        (
+            "line_item",
+            ["line_item", "lineItem", "line-item", "LineItem"],
+        ),
+        (
+            "line_item_2",
+            ["line_item_2", "lineItem2", "line-item-2", "LineItem2"],
+        ),
+        ("field_0", ["field_0", "field0", "field-0", "Field0"]),
+        ("http_url", ["http_url", "httpUrl", "http-url", "HttpUrl"]),
+        ("a_b_c", ["a_b_c", "aBC", "a-b-c", "ABC"]),
+        ("x2_y", ["x_2_y", "x2Y", "x-2-y", "X2Y"]),
+        ("", ["", "", "", ""]),
+    ];
+
+    for (name, keys) in expected {
+        for (case, key) in CASES.iter().zip(keys) {
+            assert_eq!(case.apply(name), key, "{} in {}", name, case.name());
+        }
+    }
+}
+
+#[test]
+fn names_in_any_case_are_split_into_words() {
+    let expected: [(&str, &str); 12] = [
+        ("lineItem2", "line_item_2"),
+        ("LineItem2", "line_item_2"),
+        ("line-item-2", "line_item_2"),
+        ("HTTPServer", "http_server"),
+        ("parseHTTPResponse", "parse_http_response"),
+        ("userID", "user_id"),
+        ("IDs", "i_ds"),
+        ("sha256Hash", "sha_256_hash"),
+        ("v2beta", "v_2beta"),
+        ("already_snake", "already_snake"),
+        ("__leading__and__trailing__", "leading_and_trailing"),
+        ("with spaces", "with_spaces"),
+    ];
+
+    for (name, snake) in expected {
+        assert_eq!(KeyCase::Snake.apply(name), snake, "{}", name);
+    }
+    assert_eq!(KeyCase::Camel.apply("größe_über"), "größeÜber");
+    assert_eq!(KeyCase::Snake.apply("größeÜber"), "größe_über");
+}
+
+#[test]
+fn snake_names_round_trip_through_every_case() {
+    let names: [&str; 8] = [
+        "name",
+        "line_item",
+        "line_item_2",
+        "field_0",
+        "http_url",
+        "customer_address_line_1",
+        "a_b",
+        "x_a_b",
+    ];
+    // Single-letter words that meet run together into one uppercase word, the lossy case
+    // documented on `KeyCase::apply`
+    let lossy: [(&str, KeyCase, &str); 3] = [
+        ("a_b", KeyCase::Pascal, "ab"),
+        ("x_a_b", KeyCase::Camel, "x_ab"),
+        ("x_a_b", KeyCase::Pascal, "xab"),
+    ];
+
+    for name in names {
+        for case in CASES {
+            let key: String = case.apply(name);
+            let lost: Option<&str> = lossy
+                .iter()
+                .find(|(lossy_name, lossy_case, _)| *lossy_name == name && *lossy_case == case)
+                .map(|(_, _, read_back)| *read_back);
+            assert_eq!(
+                KeyCase::Snake.apply(&key),
+                lost.unwrap_or(name),
+                "{} through {}",
+                name,
+                case.name()
+            );
+            if lost.is_none() {
+                assert_eq!(case.apply(&key), key);
+            }
+        }
+    }
+}
+
+#[test]
+fn case_names_are_parsed() {
+    for case in CASES {
+        assert_eq!(KeyCase::from_name(case.name()), Some(case));
+    }
+    assert_eq!(KeyCase::from_name("camelcase"), None);
+    assert_eq!(KeyCase::from_name("SCREAMING_SNAKE_CASE"), None);
+}
+
+#[test]
+fn keys_are_converted_by_the_descriptors_and_map_keys_are_left_alone() {
+    let fields = Invoice::field_descriptors();
+    let native: Value = serde_json::to_value(invoice()).unwrap();
+
+    let mut prompt: Value = native.clone();
+    to_prompt_keys(&fields, &mut prompt);
+    assert_eq!(prompt, serde_json::from_str::<Value>(INVOICE_JSON).unwrap());
+
+    to_native_keys(&fields, &mut prompt);
+    assert_eq!(prompt, native);
+
+    // Keys in the field's own case are kept, unknown keys are left as they are
+    let mut mixed: Value = json!({"invoice_number": "INV-8", "invoiceNumber": "INV-9", "note": 1});
+    to_native_keys(&fields, &mut mixed);
+    assert_eq!(mixed, json!({"invoice_number": "INV-8", "note": 1}));
+}
+
+#[test]
+fn camel_case_answers_deserialize_into_snake_case_fields() {
+    let server = MockServer::always(success(INVOICE_JSON));
+
+    let result: Invoice = server
+        .llm()
+        .generate_data(&Invoice::new(), TARGET, vec![])
+        .unwrap();
+
+    assert_eq!(result, invoice());
+    let prompt: String = server.requests()[0].prompt();
+    assert!(prompt.contains("invoiceNumber: Extract the invoice number"));
+    assert!(prompt.contains("lineItem2: Extract the label of the second line"));
+    assert!(prompt.contains("productName: Extract the product name"));
+    assert!(prompt.contains("postal_code: Extract the postal code"));
+    assert!(prompt.contains("\"lineItems\""));
+    assert!(prompt.contains("\"unitCount\""));
+    assert!(!prompt.contains("invoice_number"));
+    assert!(!prompt.contains("unit_count"));
+}
+
+#[tokio::test]
+async fn forced_and_async_generation_convert_the_keys() {
+    let server = MockServer::always(success(&format!("Sure, here it is: {}", INVOICE_JSON)));
+
+    let result: Invoice = server
+        .llm()
+        .async_force_generate_data(&Invoice::new(), TARGET, vec![])
+        .await
+        .unwrap();
+
+    assert_eq!(result, invoice());
+}
+
+#[test]
+fn json_schema_and_yaml_answers_use_the_cased_keys() {
+    let schema: Value = json_schema(&Invoice::field_descriptors());
+    assert!(schema["properties"].get("lineItem2").is_some());
+    assert!(
+        schema["required"]
+            .as_array()
+            .unwrap()
+            .contains(&json!("itemsBySku"))
+    );
+    assert!(
+        schema["properties"]["billingAddress"]["properties"]
+            .get("postal_code")
+            .is_some()
+    );
+
+    let answer = "invoiceNumber: INV-7\nlineItem2: Freight\nbillingAddress:\n  postal_code: SW1A 1AA\n\
+        lineItems:\n  - productName: Widget\n    unitCount: 3\n\
+        itemsBySku:\n  sku_001:\n    productName: Gadget\n    unitCount: 1\n";
+    let json = ResponseFormat::Yaml
+        .to_json_content(answer, &Invoice::field_descriptors())
+        .unwrap();
+    assert_eq!(serde_json::from_str::<Invoice>(&json).unwrap(), invoice());
+}
+
+#[test]
+fn distributed_prompts_and_groups_use_the_cased_keys() {
+    let requests = Contact::new().get_distributed_request_prompts();
+    assert!(
+        requests[0]
+            .prompt
+            .contains(r#"exactly the keys "first-name", "last-name""#)
+    );
+    assert!(
+        requests[1]
+            .prompt
+            .contains("- email-address: Extract the email address")
+    );
+
+    let server = MockServer::by_instruction(
+        vec![
+            (
+                "Extract the first name",
+                field_result(r#"{"first-name": "Ada", "last-name": "Lovelace"}"#),
+            ),
+            ("Extract the email address", field_result("ada@example.com")),
+        ],
+        success(""),
+    );
+
+    let contact: Contact = server
+        .llm()
+        .fields_generate_data(&Contact::new(), TARGET, vec![])
+        .unwrap();
+
+    assert_eq!(contact.first_name, "Ada");
+    assert_eq!(contact.last_name, "Lovelace");
+    assert_eq!(contact.email_address, "ada@example.com");
+}
@@ -0,0 +1,159 @@
+//! Output languages are stated in every prompt and checked after extraction.
+
+#![allow(clippy::needless_borrows_for_generic_args)]
+
+mod support;
+
+use secretary::Task;
+use secretary::message::Message;
+use secretary::metadata::GenerationResult;
+use secretary::traits::GenerateData;
+use serde::{Deserialize, Serialize};
+
+use support::MockServer;
+use support::fixtures::success;
+
+const ENGLISH: &str = "Write the value in English, translating it if needed.";
+const SOURCE: &str = "Keep the value in the language of the source text, do not translate it.";
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Seller {
+    #[task(instruction = "Extract the seller's name", output_language = "source")]
+    pub name: String,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[task(output_language = "en")]
+struct Listing {
+    #[task(instruction = "Categorize the listing")]
+    pub category: String,
+    #[task(instruction = "Describe the flat")]
+    pub description: String,
+    #[task(instruction = "Extract the street address", output_language = "source")]
+    pub address: String,
+    #[task(instruction = "List the amenities", output_language = "de")]
+    pub amenities: Vec<String>,
+    #[task(instruction = "Extract the rent")]
+    pub rent: u32,
+    pub seller: Seller,
+}
+
+const TARGET: &str = "Helle Wohnung mit Balkon in der Lindenstraße 5. Die Wohnung liegt in einer ruhigen Seitenstraße nahe dem Bahnhof. Miete 900 Euro. Anbieter: Hausverwaltung Müller.";
+
+#[test]
+fn field_languages_override_the_struct_default() {
+    let fields = Listing::field_descriptors();
+    let languages: Vec<Option<&str>> = fields
+        .iter()
+        .map(|field| field.output_language.as_deref())
+        .collect();
+
+    assert_eq!(
+        languages,
+        vec![
+            Some("en"),
+            Some("en"),
+            Some("source"),
+            Some("de"),
+            None,
+            None
+        ]
+    );
+    assert_eq!(
+        fields[3].instruction,
+        "List the amenities Write the value in German, translating it if needed."
+    );
+    assert_eq!(fields[4].instruction, "Extract the rent");
+    // Nested Tasks keep their own languages
+    assert_eq!(
+        fields[5].children[0].output_language.as_deref(),
+        Some("source")
+    );
+}
+
+#[test]
+fn combined_prompts_state_the_languages() {
+    let prompt: String = Listing::new().get_system_prompt();
+
+    assert!(prompt.contains(&format!("category: Categorize the listing {}", ENGLISH)));
+    assert!(prompt.contains(&format!("address: Extract the street address {}", SOURCE)));
+    assert!(prompt.contains(&format!("name: Extract the seller's name {}", SOURCE)));
+    assert!(prompt.contains("rent: Extract the rent, "));
+}
+
+#[test]
+fn distributed_prompts_state_the_languages() {
+    let prompts: Vec<(String, Message)> =
+        Listing::new().make_dstributed_generation_prompts(TARGET, &[]);
+    let prompt = |path: &str| -> String {
+        prompts
+            .iter()
+            .find(|(field_path, _)| field_path == path)
+            .map(|(_, message)| message.content.as_str().to_string())
+            .unwrap()
+    };
+
+    assert!(prompt("description").contains(ENGLISH));
+    assert!(prompt("address").contains(SOURCE));
+    assert!(prompt("seller.name").contains(SOURCE));
+    assert!(!prompt("rent").contains("translat"));
+}
+
+fn listing_json(description: &str, address: &str) -> String {
+    serde_json::json!({
+        "category": "Apartment",
+        "description": description,
+        "address": address,
+        "amenities": ["Balkon"],
+        "rent": 900,
+        "seller": {"name": "Hausverwaltung Müller"},
+    })
+    .to_string()
+}
+
+#[cfg(feature = "language-detection")]
+#[test]
+fn values_in_the_wrong_language_are_flagged() {
+    let server = MockServer::always(success(&listing_json(
+        "Helle Wohnung mit Balkon, die in einer ruhigen Seitenstraße nahe dem Bahnhof liegt.",
+        "The flat is on a quiet side street close to the station, at number five.",
+    )));
+
+    let result: GenerationResult<Listing> = server
+        .llm()
+        .generate_data_adaptive(&Listing::new(), TARGET, &vec![])
+        .unwrap();
+
+    let flagged: Vec<(&str, &str, &str)> = result
+        .metadata
+        .language_violations
+        .iter()
+        .map(|violation| {
+            (
+                violation.path.as_str(),
+                violation.expected.as_str(),
+                violation.detected.as_str(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        flagged,
+        vec![("description", "en", "de"), ("address", "de", "en")]
+    );
+}
+
+#[test]
+fn values_in_the_required_language_pass() {
+    let server = MockServer::always(success(&listing_json(
+        "A bright flat with a balcony on a quiet side street close to the station.",
+        "Lindenstraße 5, in einer ruhigen Seitenstraße nahe dem Bahnhof",
+    )));
+
+    let result: GenerationResult<Listing> = server
+        .llm()
+        .generate_data_adaptive(&Listing::new(), TARGET, &vec![])
+        .unwrap();
+
+    assert_eq!(result.data.rent, 900);
+    assert!(result.metadata.language_violations.is_empty());
+}
@@ -0,0 +1,135 @@
+//! Large responses parse to the same result, and keep their content on failure.
+
+mod support;
+
+use secretary::SecretaryError;
+use secretary::Task;
+use secretary::traits::{AsyncGenerateData, GenerateData};
+use secretary::utilities::extract_text_content_from_llm_response;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use support::fixtures::success;
+use support::{MockResponse, MockServer, assert_malformed_json, assert_no_response};
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Transcript {
+    #[task(instruction = "Extract the title of the meeting")]
+    pub title: String,
+    #[task(instruction = "Summarize the meeting")]
+    pub summary: String,
+}
+
+const TARGET: &str = "The quarterly review, led by Ada.";
+
+/// A summary of about 1 MB with quotes, escapes and non-ASCII text.
+fn summary() -> String {
+    "Ada said \"the figures\\totals are up\"\nand Grace agreed — 👍. ".repeat(16 * 1024)
+}
+
+fn transcript() -> Transcript {
+    Transcript {
+        title: "Quarterly review".to_string(),
+        summary: summary(),
+    }
+}
+
+#[test]
+fn megabyte_contents_parse_unchanged() {
+    let content: String = serde_json::to_string(&transcript()).unwrap();
+    assert!(content.len() > 1024 * 1024);
+    let server = MockServer::always(success(&content));
+
+    let result: Transcript = server
+        .llm()
+        .generate_data(&Transcript::new(), TARGET, vec![])
+        .unwrap();
+
+    assert_eq!(result, transcript());
+}
+
+#[tokio::test]
+async fn megabyte_contents_parse_unchanged_async() {
+    let content: String = serde_json::to_string(&transcript()).unwrap();
+    let server = MockServer::always(success(&content));
+
+    let result: Transcript = server
+        .llm()
+        .async_generate_data(&Transcript::new(), TARGET, vec![])
+        .await
+        .unwrap();
+
+    assert_eq!(result, transcript());
+}
+
+#[test]
+fn malformed_megabyte_contents_are_kept_whole() {
+    let content: String = format!(
+        r#"{{"title": "Quarterly review", "summary": "{}"#,
+        "x".repeat(1024 * 1024)
+    );
+    let server = MockServer::always(success(&content));
+
+    let raw_content: String = assert_malformed_json(server.llm().generate_data(
+        &Transcript::new(),
+        TARGET,
+        vec![],
+    ));
+
+    assert_eq!(raw_content, content);
+}
+
+#[test]
+fn escaped_and_plain_contents_are_extracted_alike() {
+    for content in ["plain text", "line\nbreak, \"quotes\" and \\ — ✓", ""] {
+        let response: String = json!({"choices": [{"message": {"content": content}}]}).to_string();
+        assert_eq!(
+            extract_text_content_from_llm_response(&response).unwrap(),
+            content
+        );
+    }
+}
+
+#[test]
+fn unexpected_envelopes_still_have_no_response() {
+    for response in [
+        json!({"choices": []}),
+        json!({"choices": null}),
+        json!({"choices": {"0": {"message": {"content": "text"}}}}),
+        json!({"choices": [{"message": null}]}),
+        json!({"choices": [{"message": {"content": null}}]}),
+        json!({"choices": [{"message": {"content": 42}}]}),
+        json!({"choices": ["text"]}),
+        json!([]),
+    ] {
+        let error = extract_text_content_from_llm_response(&response.to_string()).unwrap_err();
+        assert!(
+            matches!(
+                error.downcast_ref::<SecretaryError>(),
+                Some(SecretaryError::NoLLMResponse)
+            ),
+            "{} gave {}",
+            response,
+            error
+        );
+    }
+
+    assert!(extract_text_content_from_llm_response("not json").is_err());
+}
+
+#[test]
+fn missing_content_is_reported_for_large_envelopes() {
+    let server = MockServer::always(MockResponse::new(
+        200,
+        json!({
+            "choices": [{"message": {"role": "assistant", "content": null}}],
+            "padding": "x".repeat(1024 * 1024),
+        }),
+    ));
+
+    assert_no_response(
+        server
+            .llm()
+            .generate_data(&Transcript::new(), TARGET, vec![]),
+    );
+}
@@ -0,0 +1,195 @@
+//! `Lazy` fields are left out of the extraction and requested on first access.
+
+mod support;
+
+use std::time::Duration;
+
+use secretary::traits::{AsyncGenerateData, GenerateData};
+use secretary::{Lazy, SecretaryError, Task};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use support::fixtures::{field_result, success};
+use support::{MockServer, secretary_error};
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Author {
+    #[task(instruction = "Extract the author's name")]
+    pub name: String,
+    #[task(instruction = "Write a short biography of the author", lazy)]
+    pub biography: Lazy<String>,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Report {
+    #[task(instruction = "Extract the title")]
+    pub title: String,
+    #[task(instruction = "Summarize the report in a few paragraphs", lazy)]
+    pub summary: Lazy<String>,
+    #[task(instruction = "Count the pages", lazy)]
+    pub pages: Lazy<u32>,
+    pub author: Author,
+}
+
+const TARGET: &str = "Quarterly report by Ada Lovelace, 12 pages. Sales grew by a third.";
+
+const REPORT: &str = r#"{"title": "Quarterly report", "author": {"name": "Ada Lovelace"}}"#;
+
+/// Answers the lazy fields' requests after a delay, so that concurrent readers overlap, the
+/// other field requests with their field, and anything else with the report.
+fn report_server() -> MockServer {
+    MockServer::start(|request| {
+        let prompt: String = request.prompt();
+        if prompt.contains("Summarize the report") {
+            std::thread::sleep(Duration::from_millis(100));
+            return field_result("Sales grew by a third.");
+        }
+        if prompt.contains("Count the pages") {
+            return field_result("12");
+        }
+        if prompt.contains("short biography") {
+            return field_result("Ada Lovelace wrote the report.");
+        }
+        if !prompt.contains("<result>") {
+            return success(REPORT);
+        }
+        if prompt.contains("Extract the author's name") {
+            field_result("Ada Lovelace")
+        } else {
+            field_result("Quarterly report")
+        }
+    })
+}
+
+#[test]
+fn unread_lazy_fields_send_no_request() {
+    let server = report_server();
+
+    let report: Report = server
+        .llm()
+        .generate_data(&Report::new(), TARGET, vec![])
+        .unwrap();
+
+    assert_eq!(report.title, "Quarterly report");
+    assert!(report.summary.is_bound());
+    assert!(report.summary.peek().is_none());
+    assert!(report.author.biography.is_bound());
+    assert_eq!(server.requests().len(), 1);
+
+    let prompt: String = server.requests()[0].prompt();
+    assert!(!prompt.contains("Summarize the report"));
+    assert!(!prompt.contains("short biography"));
+    assert!(!prompt.contains("\"summary\""));
+    assert!(!prompt.contains("\"biography\""));
+
+    let json: serde_json::Value = serde_json::to_value(&report).unwrap();
+    assert_eq!(json["summary"], json!({"lazy": "unevaluated"}));
+    assert_eq!(json["author"]["biography"], json!({"lazy": "unevaluated"}));
+}
+
+#[test]
+fn lazy_fields_are_requested_once_on_first_access() {
+    let server = report_server();
+    let report: Report = server
+        .llm()
+        .generate_data(&Report::new(), TARGET, vec![])
+        .unwrap();
+
+    assert_eq!(report.summary.get().unwrap(), "Sales grew by a third.");
+    assert_eq!(report.summary.get().unwrap(), "Sales grew by a third.");
+    assert_eq!(*report.pages.get().unwrap(), 12);
+    assert_eq!(
+        report.author.biography.get().unwrap(),
+        "Ada Lovelace wrote the report."
+    );
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 4);
+    let prompt: String = requests[1].prompt();
+    assert!(prompt.contains("Summarize the report in a few paragraphs"));
+    assert!(prompt.contains(TARGET));
+    assert!(!requests[1].is_json_mode());
+    assert_eq!(
+        serde_json::to_value(&report).unwrap()["summary"],
+        "Sales grew by a third."
+    );
+}
+
+#[test]
+fn concurrent_readers_share_one_request() {
+    let server = report_server();
+    let report: Report = server
+        .llm()
+        .fields_generate_data(&Report::new(), TARGET, vec![])
+        .unwrap();
+    // The title and the author's name, the lazy fields have no request
+    assert_eq!(server.requests().len(), 2);
+
+    std::thread::scope(|scope| {
+        let readers: Vec<_> = (0..8)
+            .map(|_| scope.spawn(|| report.summary.get().unwrap().clone()))
+            .collect();
+        for reader in readers {
+            assert_eq!(reader.join().unwrap(), "Sales grew by a third.");
+        }
+    });
+
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[tokio::test]
+async fn concurrent_async_readers_share_one_request() {
+    let server = report_server();
+    let report: Report = server
+        .llm()
+        .async_generate_data(&Report::new(), TARGET, vec![])
+        .await
+        .unwrap();
+
+    let summaries = futures::future::join_all((0..8).map(|_| report.summary.get_async())).await;
+
+    for summary in summaries {
+        assert_eq!(summary.unwrap(), "Sales grew by a third.");
+    }
+    assert_eq!(server.requests().len(), 2);
+}
+
+#[test]
+fn unbound_lazy_fields_fail_to_read() {
+    let report: Report = serde_json::from_str(REPORT).unwrap();
+
+    let error = report.summary.get().unwrap_err();
+
+    assert!(matches!(
+        secretary_error(&error),
+        SecretaryError::LazyFieldUnbound
+    ));
+    assert_eq!(*Lazy::new(3).get().unwrap(), 3);
+}
+
+#[test]
+fn unevaluated_fields_serialize_apart_from_extracted_nulls() {
+    let extracted: Lazy<Option<String>> = Lazy::new(None);
+    let unevaluated: Lazy<Option<String>> = Lazy::default();
+
+    let extracted_json: String = serde_json::to_string(&extracted).unwrap();
+    let unevaluated_json: String = serde_json::to_string(&unevaluated).unwrap();
+    assert_eq!(extracted_json, "null");
+    assert_eq!(unevaluated_json, r#"{"lazy":"unevaluated"}"#);
+    assert_ne!(extracted_json, unevaluated_json);
+
+    // Both forms read back as they were written
+    let read: Lazy<Option<String>> = serde_json::from_str(&unevaluated_json).unwrap();
+    assert!(!read.is_extracted());
+    assert_eq!(serde_json::to_string(&read).unwrap(), unevaluated_json);
+    let read: Lazy<String> = serde_json::from_str(r#""Sales grew""#).unwrap();
+    assert_eq!(read.peek().unwrap(), "Sales grew");
+    assert_eq!(serde_json::to_string(&read).unwrap(), r#""Sales grew""#);
+
+    // A report round-trips with its unread fields still unread
+    let report: Report = serde_json::from_str(REPORT).unwrap();
+    let json: String = serde_json::to_string(&report).unwrap();
+    let read: Report = serde_json::from_str(&json).unwrap();
+    assert!(!read.summary.is_extracted());
+    assert_eq!(serde_json::to_string(&read).unwrap(), json);
+}
@@ -0,0 +1,337 @@
+//! Output limits bound what an extraction accepts from the model.
+
+mod support;
+
+use secretary::SecretaryError;
+use secretary::Task;
+use secretary::limits::{ClippedValue, LimitPolicy, OutputLimit, OutputLimits};
+use secretary::metadata::GenerationResult;
+use secretary::request::RequestOptions;
+use secretary::traits::{AsyncGenerateData, GenerateData};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use support::fixtures::{empty_choices, field_result, success};
+use support::{ADA_JSON, BoxedError, MockServer, Person, TARGET, ada, secretary_error};
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Seller {
+    #[task(instruction = "Extract the seller's name")]
+    pub name: String,
+    #[task(instruction = "List the seller's tags")]
+    pub tags: Vec<String>,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Listing {
+    #[task(instruction = "Extract the title")]
+    pub title: String,
+    #[task(instruction = "List the keywords")]
+    pub keywords: Vec<String>,
+    pub seller: Seller,
+}
+
+const LISTING: &str = "Used bike, red, fast, sold by Bob's Bikes (verified, local).";
+
+const LISTING_JSON: &str = r#"{
+    "title": "Used bike",
+    "keywords": ["bike", "red", "fast"],
+    "seller": {"name": "Bob's Bikes", "tags": ["verified", "local"]}
+}"#;
+
+/// Asserts that a call failed on `limit` at `path`, and returns the limit and the actual size.
+fn assert_exceeded<T: std::fmt::Debug>(
+    result: Result<T, BoxedError>,
+    expected_limit: OutputLimit,
+    expected_path: &str,
+) -> (usize, usize) {
+    let error: BoxedError = result.unwrap_err();
+    match secretary_error(&error) {
+        SecretaryError::OutputLimitExceeded {
+            limit,
+            path,
+            max,
+            actual,
+        } => {
+            assert_eq!(*limit, expected_limit);
+            assert_eq!(path, expected_path);
+            (*max, *actual)
+        }
+        other => panic!("unexpected error: {}", other),
+    }
+}
+
+fn listing_server() -> MockServer {
+    MockServer::always(success(LISTING_JSON))
+}
+
+#[test]
+fn long_arrays_fail_with_their_path() {
+    let server = listing_server();
+    let llm = server
+        .llm()
+        .with_output_limits(OutputLimits::default().with_max_array_len(2));
+
+    let result = llm.generate_data(&Listing::new(), LISTING, vec![]);
+
+    assert_eq!(
+        assert_exceeded(result, OutputLimit::ArrayLength, "keywords"),
+        (2, 3)
+    );
+}
+
+#[test]
+fn nested_values_report_the_full_path() {
+    let server = listing_server();
+    let llm = server.llm().with_output_limits(
+        OutputLimits::default()
+            .with_max_array_len(3)
+            .with_max_string_len(10),
+    );
+
+    let result = llm.generate_data(&Listing::new(), LISTING, vec![]);
+
+    assert_eq!(
+        assert_exceeded(result, OutputLimit::StringLength, "seller.name"),
+        (10, 11)
+    );
+
+    let mut value = json!({"offers": [{"keywords": []}, {"keywords": ["a", "b", "c"]}]});
+    let error = OutputLimits::default()
+        .with_max_array_len(2)
+        .enforce(&mut value)
+        .unwrap_err();
+    assert!(matches!(
+        error,
+        SecretaryError::OutputLimitExceeded { ref path, .. } if path == "offers[1].keywords"
+    ));
+}
+
+#[test]
+fn long_strings_fail() {
+    let server = MockServer::always(success(r#"{"name": "Ada Lovelace", "age": 36}"#));
+    let llm = server
+        .llm()
+        .with_output_limits(OutputLimits::default().with_max_string_len(5));
+
+    let result = llm.generate_data(&Person::new(), TARGET, vec![]);
+
+    assert_eq!(
+        assert_exceeded(result, OutputLimit::StringLength, "name"),
+        (5, 12)
+    );
+}
+
+#[test]
+fn large_json_fails_before_parsing() {
+    let server = listing_server();
+    let llm = server
+        .llm()
+        .with_output_limits(OutputLimits::default().with_max_total_json_bytes(64));
+
+    let result = llm.generate_data(&Listing::new(), LISTING, vec![]);
+
+    let (max, actual) = assert_exceeded(result, OutputLimit::TotalJsonBytes, "");
+    assert_eq!((max, actual), (64, LISTING_JSON.len()));
+}
+
+#[test]
+fn deep_nesting_fails() {
+    let server = listing_server();
+    let llm = server
+        .llm()
+        .with_output_limits(OutputLimits::default().with_max_nesting_depth(2));
+
+    let result = llm.generate_data(&Listing::new(), LISTING, vec![]);
+    assert_eq!(
+        assert_exceeded(result, OutputLimit::NestingDepth, "seller.tags"),
+        (2, 3)
+    );
+
+    let llm = server
+        .llm()
+        .with_output_limits(OutputLimits::default().with_max_nesting_depth(3));
+    let listing: Listing = llm.generate_data(&Listing::new(), LISTING, vec![]).unwrap();
+    assert_eq!(listing.seller.tags, vec!["verified", "local"]);
+}
+
+#[test]
+fn large_responses_fail_before_they_are_read() {
+    let server = MockServer::always(success(ADA_JSON));
+    let llm = server
+        .llm()
+        .with_output_limits(OutputLimits::default().with_max_response_bytes(64));
+
+    let (max, actual) = assert_exceeded(
+        llm.generate_data(&Person::new(), TARGET, vec![]),
+        OutputLimit::ResponseBytes,
+        "",
+    );
+    assert_eq!(max, 64);
+    assert!(actual > 64);
+
+    let llm = server
+        .llm()
+        .with_output_limits(OutputLimits::default().with_max_response_bytes(4096));
+    assert_eq!(
+        llm.generate_data(&Person::new(), TARGET, vec![]).unwrap(),
+        ada()
+    );
+}
+
+#[test]
+fn truncation_clips_and_reports_what_it_clipped() {
+    let server = listing_server();
+    let llm = server.llm().with_output_limits(
+        OutputLimits::default()
+            .with_max_array_len(1)
+            .with_max_string_len(8)
+            .with_policy(LimitPolicy::Truncate),
+    );
+
+    let result: GenerationResult<Listing> = llm
+        .generate_data_adaptive(&Listing::new(), LISTING, vec![])
+        .unwrap();
+
+    // Object keys are visited in alphabetical order
+    assert_eq!(result.data.title, "Used bik");
+    assert_eq!(result.data.keywords, vec!["bike"]);
+    assert_eq!(result.data.seller.name, "Bob's Bi");
+    assert_eq!(result.data.seller.tags, vec!["verified"]);
+    assert_eq!(
+        result.metadata.clipped_values,
+        vec![
+            ClippedValue {
+                path: "keywords".to_string(),
+                limit: OutputLimit::ArrayLength,
+                original_len: 3,
+                kept_len: 1,
+            },
+            ClippedValue {
+                path: "seller.name".to_string(),
+                limit: OutputLimit::StringLength,
+                original_len: 11,
+                kept_len: 8,
+            },
+            ClippedValue {
+                path: "seller.tags".to_string(),
+                limit: OutputLimit::ArrayLength,
+                original_len: 2,
+                kept_len: 1,
+            },
+            ClippedValue {
+                path: "title".to_string(),
+                limit: OutputLimit::StringLength,
+                original_len: 9,
+                kept_len: 8,
+            },
+        ]
+    );
+}
+
+#[test]
+fn truncation_does_not_apply_to_sizes() {
+    let server = listing_server();
+    let llm = server.llm().with_output_limits(
+        OutputLimits::default()
+            .with_max_total_json_bytes(64)
+            .with_policy(LimitPolicy::Truncate),
+    );
+
+    let result = llm.generate_data(&Listing::new(), LISTING, vec![]);
+
+    assert_exceeded(result, OutputLimit::TotalJsonBytes, "");
+}
+
+#[test]
+fn call_limits_replace_the_providers() {
+    let server = listing_server();
+    let llm = server
+        .llm()
+        .with_output_limits(OutputLimits::default().with_max_array_len(1));
+
+    let listing: Listing = llm
+        .generate_data_with_options(
+            &Listing::new(),
+            LISTING,
+            vec![],
+            &RequestOptions::default().with_output_limits(OutputLimits::default()),
+        )
+        .unwrap();
+    assert_eq!(listing.keywords.len(), 3);
+
+    let result = server.llm().generate_data_with_options(
+        &Listing::new(),
+        LISTING,
+        vec![],
+        &RequestOptions::default()
+            .with_output_limits(OutputLimits::default().with_max_array_len(2)),
+    );
+    assert_exceeded(result, OutputLimit::ArrayLength, "keywords");
+}
+
+#[test]
+fn distributed_generation_checks_the_assembled_fields() {
+    let server = MockServer::by_instruction(
+        vec![
+            ("Extract the title", field_result("Used bike")),
+            (
+                "List the keywords",
+                field_result("<item>bike</item><item>red</item><item>fast</item>"),
+            ),
+            ("Extract the seller's name", field_result("Bob's Bikes")),
+            (
+                "List the seller's tags",
+                field_result("<item>verified</item>"),
+            ),
+        ],
+        empty_choices(),
+    );
+    let limits = OutputLimits::default().with_max_array_len(2);
+
+    let result = server
+        .llm()
+        .with_output_limits(limits)
+        .fields_generate_data(&Listing::new(), LISTING, vec![]);
+    assert_exceeded(result, OutputLimit::ArrayLength, "keywords");
+
+    let listing: Listing = server
+        .llm()
+        .with_output_limits(limits.with_policy(LimitPolicy::Truncate))
+        .fields_generate_data(&Listing::new(), LISTING, vec![])
+        .unwrap();
+    assert_eq!(listing.keywords, vec!["bike", "red"]);
+    assert_eq!(listing.seller.tags, vec!["verified"]);
+}
+
+#[tokio::test]
+async fn async_generation_is_limited() {
+    let server = listing_server();
+
+    let result = server
+        .llm()
+        .with_output_limits(OutputLimits::default().with_max_array_len(2))
+        .async_generate_data(&Listing::new(), LISTING, vec![])
+        .await;
+    assert_exceeded(result, OutputLimit::ArrayLength, "keywords");
+
+    let result = server
+        .llm()
+        .with_output_limits(OutputLimits::default().with_max_response_bytes(64))
+        .async_generate_data_adaptive(&Listing::new(), LISTING, vec![])
+        .await;
+    assert_exceeded(result, OutputLimit::ResponseBytes, "");
+
+    let result: GenerationResult<Listing> = server
+        .llm()
+        .with_output_limits(
+            OutputLimits::default()
+                .with_max_array_len(2)
+                .with_policy(LimitPolicy::Truncate),
+        )
+        .async_generate_data_adaptive(&Listing::new(), LISTING, vec![])
+        .await
+        .unwrap();
+    assert_eq!(result.data.keywords, vec!["bike", "red"]);
+    assert_eq!(result.metadata.clipped_values.len(), 1);
+}
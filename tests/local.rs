@@ -0,0 +1,84 @@
+//! Async extraction of Tasks that are not `Send` on a single-threaded executor.
+#![cfg(feature = "local")]
+
+mod support;
+
+use std::rc::Rc;
+
+use secretary::Task;
+use secretary::traits::AsyncGenerateDataLocal;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use tokio::task::LocalSet;
+
+use support::fixtures::{empty_choices, field_result, success};
+use support::{MockServer, TARGET};
+
+/// A string shared through an `Rc`, as an interner hands them out.
+#[derive(Debug, Default, Clone, PartialEq)]
+struct Interned(Rc<str>);
+
+impl Serialize for Interned {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Interned {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Interned(Rc::from(String::deserialize(deserializer)?)))
+    }
+}
+
+#[derive(Task, Serialize, Deserialize, Debug)]
+struct Person {
+    #[task(plain, instruction = "Extract the person's name")]
+    pub name: Interned,
+    #[task(instruction = "Extract the age as a number")]
+    pub age: u32,
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn non_send_tasks_are_extracted_on_a_local_set() {
+    let server = MockServer::always(success(r#"{"name": "Ada", "age": 36}"#));
+    let llm = server.llm();
+
+    // The future holds `Rc`s, so it can only be spawned on the local set
+    let person: Person = LocalSet::new()
+        .run_until(async move {
+            tokio::task::spawn_local(async move {
+                llm.async_generate_data(&Person::new(), TARGET, vec![])
+                    .await
+                    .unwrap()
+            })
+            .await
+            .unwrap()
+        })
+        .await;
+
+    assert_eq!(&*person.name.0, "Ada");
+    assert_eq!(person.age, 36);
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn fields_are_requested_concurrently_on_one_thread() {
+    let server = MockServer::by_instruction(
+        vec![
+            ("Extract the person's name", field_result("\"Ada\"")),
+            ("Extract the age as a number", field_result("36")),
+        ],
+        empty_choices(),
+    );
+    let llm = server.llm();
+
+    let person: Person = LocalSet::new()
+        .run_until(async {
+            llm.async_fields_generate_data(&Person::new(), TARGET, vec![])
+                .await
+                .unwrap()
+        })
+        .await;
+
+    assert_eq!(&*person.name.0, "Ada");
+    assert_eq!(person.age, 36);
+    assert_eq!(server.requests().len(), 2);
+}
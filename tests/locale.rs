@@ -0,0 +1,307 @@
+//! Targets are read in the number and date conventions of their locale: the prompt describes
+//! them, and answers that still follow them are converted before deserialization.
+
+mod support;
+
+use secretary::Task;
+use secretary::locale::{DateOrder, ExtractionLocale};
+use secretary::request::RequestOptions;
+use secretary::traits::{AsyncGenerateData, GenerateData};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use support::fixtures::{empty_choices, field_result, success};
+use support::{MockServer, TARGET};
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Invoice {
+    #[task(instruction = "Extract the invoice number")]
+    pub number: String,
+    #[task(instruction = "Extract the invoice date", date)]
+    pub date: String,
+    #[task(instruction = "Extract the total amount")]
+    pub total: f64,
+    #[task(instruction = "Extract the number of items")]
+    pub quantity: u32,
+    #[task(instruction = "Extract the discount, if any")]
+    pub discount: Option<f64>,
+    #[task(instruction = "Extract the delivery dates", date)]
+    pub deliveries: Vec<String>,
+    #[task(instruction = "Extract the invoice lines")]
+    pub lines: Vec<Line>,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Line {
+    #[task(instruction = "Extract the product")]
+    pub product: String,
+    #[task(instruction = "Extract the unit price")]
+    pub price: f64,
+}
+
+/// An answer that follows German conventions instead of the ones the prompt asks for.
+fn german_answer() -> Value {
+    json!({
+        "number": "2024.117",
+        "date": "13.04.2024",
+        "total": "1.234,56 €",
+        "quantity": "1.200",
+        "discount": "5,5",
+        "deliveries": ["01.03.2024", "2024-03-15"],
+        "lines": [
+            {"product": "Schrauben", "price": "0,25"},
+            {"product": "Muttern", "price": 1.5}
+        ]
+    })
+}
+
+fn german_invoice() -> Invoice {
+    Invoice {
+        number: "2024.117".to_string(),
+        date: "2024-04-13".to_string(),
+        total: 1234.56,
+        quantity: 1200,
+        discount: Some(5.5),
+        deliveries: vec!["2024-03-01".to_string(), "2024-03-15".to_string()],
+        lines: vec![
+            Line {
+                product: "Schrauben".to_string(),
+                price: 0.25,
+            },
+            Line {
+                product: "Muttern".to_string(),
+                price: 1.5,
+            },
+        ],
+    }
+}
+
+#[test]
+fn numbers_are_read_in_the_conventions_of_the_locale() {
+    let german = ExtractionLocale::de_de();
+    let american = ExtractionLocale::en_us();
+    let swiss = ExtractionLocale::default().with_currency_default("CHF");
+
+    // (locale, text, number)
+    let cases: Vec<(&ExtractionLocale, &str, Option<Value>)> = vec![
+        (&german, "1.234,56", Some(json!(1234.56))),
+        (&german, "1.234", Some(json!(1234))),
+        (&german, "1.234.567", Some(json!(1234567))),
+        (&german, "1 234,5", Some(json!(1234.5))),
+        (&german, "1\u{202F}234,5", Some(json!(1234.5))),
+        (&german, "-0,75", Some(json!(-0.75))),
+        (&german, "12", Some(json!(12))),
+        (&german, "€ 19,99", Some(json!(19.99))),
+        (&german, "19,99 EUR", Some(json!(19.99))),
+        // A dot that separates no thousands is read as a decimal point
+        (&german, "1.5", Some(json!(1.5))),
+        (&german, "1234.56", Some(json!(1234.56))),
+        (&german, "1,234.56", None),
+        (&german, "12.34.56", None),
+        (&american, "1.234", Some(json!(1.234))),
+        (&american, "1,234", Some(json!(1234))),
+        (&american, "1,234,567.89", Some(json!(1234567.89))),
+        (&american, "$1,234.56", Some(json!(1234.56))),
+        (&american, "+42", Some(json!(42))),
+        (&american, "1,5", None),
+        (&american, "12,34.5", None),
+        (&swiss, "CHF 1'234.50", Some(json!(1234.5))),
+        (&american, "", None),
+        (&american, "many", None),
+        (&american, "1e5", None),
+    ];
+
+    for (locale, text, expected) in cases {
+        assert_eq!(locale.parse_number(text), expected, "{:?} {}", locale, text);
+    }
+}
+
+#[test]
+fn dates_are_read_in_the_order_of_the_locale() {
+    let german = ExtractionLocale::de_de();
+    let american = ExtractionLocale::en_us();
+    let british = ExtractionLocale::en_gb();
+    let iso = ExtractionLocale::default();
+
+    // (locale, text, date)
+    let cases: Vec<(&ExtractionLocale, &str, Option<&str>)> = vec![
+        (&german, "13.04.2024", Some("2024-04-13")),
+        (&german, "1.3.2024", Some("2024-03-01")),
+        (&german, "13.04.2024.", Some("2024-04-13")),
+        (&german, "13.04.24", Some("2024-04-13")),
+        (&german, "04/05/2024", Some("2024-05-04")),
+        (&british, "04/05/2024", Some("2024-05-04")),
+        (&american, "04/05/2024", Some("2024-04-05")),
+        (&american, "12-31-99", Some("1999-12-31")),
+        (&iso, "24/04/13", Some("2024-04-13")),
+        // A four-digit year first is read year, month, day in every locale
+        (&american, "2024-04-13", Some("2024-04-13")),
+        (&german, "2024/4/3", Some("2024-04-03")),
+        // Invalid dates
+        (&american, "13/04/2024", None),
+        (&german, "31.04.2024", None),
+        (&german, "29.02.2023", None),
+        (&german, "29.02.2024", Some("2024-02-29")),
+        (&german, "29.02.1900", None),
+        (&iso, "13/04/2024", None),
+        // Not dates
+        (&german, "13.04-2024", None),
+        (&german, "1.2.3", None),
+        (&german, "13.04.2024 10:00", None),
+        (&german, "April 13, 2024", None),
+        (&german, "2024", None),
+    ];
+
+    for (locale, text, expected) in cases {
+        assert_eq!(
+            locale.parse_date(text).as_deref(),
+            expected,
+            "{:?} {}",
+            locale.date_order,
+            text
+        );
+    }
+}
+
+#[test]
+fn values_are_localized_by_their_field_type() {
+    let fields = Invoice::field_descriptors();
+    let mut value: Value = german_answer();
+
+    assert!(ExtractionLocale::de_de().localize_values(&fields, &mut value));
+
+    assert_eq!(value["number"], "2024.117");
+    assert_eq!(value["date"], "2024-04-13");
+    assert_eq!(value["total"], json!(1234.56));
+    assert_eq!(value["quantity"], json!(1200));
+    assert_eq!(value["deliveries"], json!(["2024-03-01", "2024-03-15"]));
+    assert_eq!(value["lines"][0]["price"], json!(0.25));
+    assert_eq!(value["lines"][1]["price"], json!(1.5));
+
+    // JSON numbers and values in the answer's conventions are left as they are
+    let mut value: Value = json!({"total": 1.234, "date": "2024-04-13", "quantity": 3});
+    assert!(!ExtractionLocale::de_de().localize_values(&fields, &mut value));
+    assert_eq!(value["total"], json!(1.234));
+
+    // Only fields marked as dates are read as dates
+    let mut value: Value = json!({"number": "12-05-10", "date": "12-05-10"});
+    assert!(ExtractionLocale::default().localize_values(&fields, &mut value));
+    assert_eq!(value["number"], "12-05-10");
+    assert_eq!(value["date"], "2012-05-10");
+}
+
+#[test]
+fn the_prompt_note_describes_both_conventions() {
+    let note: String = ExtractionLocale::de_de()
+        .with_timezone("Europe/Berlin")
+        .prompt_note();
+
+    assert!(note.contains("decimal comma"));
+    assert!(note.contains("day, month, year"));
+    assert!(note.contains("Europe/Berlin"));
+    assert!(note.contains("amounts without a currency are in EUR"));
+    assert!(note.contains("dates as YYYY-MM-DD"));
+
+    let note: String = ExtractionLocale::default()
+        .with_date_order(DateOrder::Mdy)
+        .prompt_note();
+    assert!(note.contains("decimal point and commas between thousands"));
+    assert!(note.contains("month, day, year"));
+    assert!(!note.contains("time zone"));
+}
+
+#[test]
+fn a_german_answer_is_extracted_with_the_provider_locale() {
+    let server = MockServer::always(success(&german_answer().to_string()));
+    let llm = server.llm().with_locale(ExtractionLocale::de_de());
+
+    let invoice: Invoice = llm.generate_data(&Invoice::new(), TARGET, vec![]).unwrap();
+
+    assert_eq!(invoice, german_invoice());
+    let prompt: String = server.requests()[0].prompt();
+    assert!(prompt.contains(&ExtractionLocale::de_de().prompt_note()));
+}
+
+#[test]
+fn without_a_locale_nothing_is_converted() {
+    let server = MockServer::always(success(&german_answer().to_string()));
+
+    let result: Result<Invoice, _> = server.llm().generate_data(&Invoice::new(), TARGET, vec![]);
+
+    assert!(result.is_err());
+    assert!(!server.requests()[0].prompt().contains("decimal comma"));
+}
+
+#[test]
+fn the_locale_of_the_call_wins_and_is_recorded() {
+    let answer: Value = json!({
+        "number": "A-1",
+        "date": "04/05/2024",
+        "total": "1.234",
+        "quantity": 2,
+        "discount": null,
+        "deliveries": [],
+        "lines": []
+    });
+    let server = MockServer::always(success(&answer.to_string()));
+    let llm = server.llm().with_locale(ExtractionLocale::de_de());
+
+    let result = llm
+        .generate_data_adaptive_with_options(
+            &Invoice::new(),
+            TARGET,
+            vec![],
+            &RequestOptions::default().with_locale(ExtractionLocale::en_us()),
+        )
+        .unwrap();
+
+    assert_eq!(result.data.date, "2024-04-05");
+    assert_eq!(result.data.total, 1.234);
+    assert_eq!(result.metadata.locale, Some(ExtractionLocale::en_us()));
+    assert!(server.requests()[0].prompt().contains("month, day, year"));
+
+    let result = llm
+        .generate_data_adaptive(&Invoice::new(), TARGET, vec![])
+        .unwrap();
+    assert_eq!(result.data.date, "2024-05-04");
+    assert_eq!(result.data.total, 1234.0);
+    assert_eq!(result.metadata.locale, Some(ExtractionLocale::de_de()));
+}
+
+#[test]
+fn distributed_answers_are_localized() {
+    let server = MockServer::by_instruction(
+        vec![
+            ("Extract the product", field_result("Schrauben")),
+            ("Extract the unit price", field_result("1.234,5")),
+        ],
+        empty_choices(),
+    );
+    let llm = server.llm().with_locale(ExtractionLocale::de_de());
+
+    let line: Line = llm
+        .fields_generate_data(&Line::new(), TARGET, vec![])
+        .unwrap();
+
+    assert_eq!(line.price, 1234.5);
+    assert!(
+        server
+            .requests()
+            .iter()
+            .all(|request| request.prompt().contains("decimal comma"))
+    );
+}
+
+#[tokio::test]
+async fn async_extraction_reads_the_locale() {
+    let server = MockServer::always(success(&german_answer().to_string()));
+    let llm = server.llm();
+    let options: RequestOptions = RequestOptions::default().with_locale(ExtractionLocale::de_de());
+
+    let invoice: Invoice = llm
+        .async_generate_data_with_options(&Invoice::new(), TARGET, vec![], &options)
+        .await
+        .unwrap();
+
+    assert_eq!(invoice, german_invoice());
+}
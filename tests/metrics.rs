@@ -0,0 +1,82 @@
+//! Every request and every response that fails to parse is reported to the metrics sink.
+
+#![allow(clippy::needless_borrows_for_generic_args)]
+
+mod support;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use secretary::metrics::{CountingSink, GenerationMode, MetricEvent};
+use secretary::traits::GenerateData;
+use serde_json::json;
+
+use support::{ADA_JSON, MockResponse, MockServer, Person, TARGET};
+
+fn completion(content: &str) -> MockResponse {
+    MockResponse::new(
+        200,
+        json!({
+            "choices": [{"message": {"role": "assistant", "content": content}}],
+            "usage": {"total_tokens": 42}
+        }),
+    )
+}
+
+fn completed(status: u16, tokens: Option<u64>) -> MetricEvent {
+    MetricEvent::RequestCompleted {
+        status: Some(status),
+        latency: Duration::ZERO,
+        tokens,
+    }
+}
+
+#[test]
+fn requests_and_parse_failures_are_reported_in_order() {
+    let responses: Vec<MockResponse> = vec![
+        completion(ADA_JSON),
+        MockResponse::new(500, json!({"error": {"message": "overloaded"}})),
+        completion("Ada, 36 years old"),
+    ];
+    let next = AtomicUsize::new(0);
+    let server = MockServer::start(move |_| responses[next.fetch_add(1, Ordering::SeqCst)].clone());
+    let sink = Arc::new(CountingSink::default());
+    let llm = server.llm().with_metrics_sink(sink.clone());
+
+    let task = Person::new();
+    assert!(llm.generate_data(&task, TARGET, &vec![]).is_ok());
+    assert!(llm.generate_data(&task, TARGET, &vec![]).is_err());
+    assert!(llm.generate_data(&task, TARGET, &vec![]).is_err());
+
+    // Latency varies between runs, so compare everything else
+    let events: Vec<MetricEvent> = sink
+        .events()
+        .into_iter()
+        .map(|event| match event {
+            MetricEvent::RequestCompleted { status, tokens, .. } => MetricEvent::RequestCompleted {
+                status,
+                latency: Duration::ZERO,
+                tokens,
+            },
+            event => event,
+        })
+        .collect();
+    assert_eq!(
+        events,
+        vec![
+            MetricEvent::RequestStarted,
+            completed(200, Some(42)),
+            MetricEvent::RequestStarted,
+            completed(500, None),
+            MetricEvent::RequestStarted,
+            completed(200, Some(42)),
+            MetricEvent::ParseFailed {
+                mode: GenerationMode::Json,
+                field_count_failed: 2,
+            },
+        ]
+    );
+    assert_eq!(sink.count("request_started"), 3);
+    assert_eq!(sink.count("parse_failed"), 1);
+}
@@ -0,0 +1,321 @@
+//! The model registry resolves aliases, reports deprecated, retired and unknown models, and
+//! learns the models a provider lists.
+
+mod support;
+
+use secretary::SecretaryError;
+use secretary::llm_providers::config::ProviderConfig;
+use secretary::llm_providers::models::{
+    ConfigWarning, ModelEntry, ModelInfo, ModelRegistry, ModelStatus, REGISTRY_VERSION,
+};
+use secretary::llm_providers::openai::OpenAILLM;
+use serde_json::json;
+
+use support::{MockResponse, MockServer};
+
+const API_BASE: &str = "https://api.openai.com/v1";
+
+fn status_on(model: &str, date: &str) -> ModelStatus {
+    ModelRegistry::builtin().lookup_on(model, date).status
+}
+
+#[test]
+fn aliases_resolve_to_their_snapshot() {
+    let registry = ModelRegistry::builtin();
+
+    assert_eq!(
+        registry.lookup_on("gpt-4o", "2025-01-01"),
+        ModelInfo {
+            model: "gpt-4o".to_string(),
+            canonical: "gpt-4o-2024-08-06".to_string(),
+            status: ModelStatus::Active,
+        }
+    );
+    assert_eq!(
+        registry.lookup_on("gpt-4o-mini", "2025-01-01").canonical,
+        "gpt-4o-mini-2024-07-18"
+    );
+    // Snapshots and names that are no alias are their own canonical name
+    let snapshot = registry.lookup_on("gpt-4o-2024-11-20", "2025-01-01");
+    assert_eq!(snapshot.canonical, "gpt-4o-2024-11-20");
+    assert_eq!(snapshot.status, ModelStatus::Active);
+
+    // An alias takes the status of its snapshot
+    assert!(matches!(
+        registry.lookup_on("gpt-4-32k", "2025-01-01").status,
+        ModelStatus::Retired { .. }
+    ));
+}
+
+#[test]
+fn exact_names_and_longer_patterns_take_precedence() {
+    assert_eq!(status_on("gpt-4-0613", "2025-01-01"), ModelStatus::Active);
+    assert_eq!(
+        status_on("gpt-4-0314", "2025-01-01"),
+        ModelStatus::Retired {
+            retired_on: "2024-06-13".to_string(),
+            replacement: Some("gpt-4o".to_string()),
+        }
+    );
+    assert!(matches!(
+        status_on("gpt-4-32k-0314", "2025-01-01"),
+        ModelStatus::Retired { .. }
+    ));
+    assert_eq!(
+        status_on("gpt-3.5-turbo-instruct", "2025-01-01"),
+        ModelStatus::Active
+    );
+    assert!(matches!(
+        status_on("gpt-3.5-turbo-0613", "2025-01-01"),
+        ModelStatus::Retired { .. }
+    ));
+}
+
+#[test]
+fn deprecated_models_are_retired_once_their_shutdown_date_passed() {
+    let deprecated = ModelStatus::Deprecated {
+        shutdown_date: "2025-07-14".to_string(),
+        replacement: Some("gpt-4.1".to_string()),
+    };
+
+    assert_eq!(status_on("gpt-4.5-preview", "2025-06-01"), deprecated);
+    assert_eq!(
+        status_on("gpt-4.5-preview-2025-02-27", "2025-07-13"),
+        deprecated
+    );
+    assert_eq!(
+        status_on("gpt-4.5-preview", "2025-07-14"),
+        ModelStatus::Retired {
+            retired_on: "2025-07-14".to_string(),
+            replacement: Some("gpt-4.1".to_string()),
+        }
+    );
+}
+
+#[test]
+fn unknown_models_are_reported_with_the_registry_version() {
+    let registry = ModelRegistry::builtin();
+
+    assert_eq!(
+        registry.lookup_on("llama3:8b", "2025-01-01"),
+        ModelInfo {
+            model: "llama3:8b".to_string(),
+            canonical: "llama3:8b".to_string(),
+            status: ModelStatus::Unknown,
+        }
+    );
+    assert_eq!(
+        registry.check_on("llama3:8b", "2025-01-01").unwrap(),
+        vec![ConfigWarning::UnknownModel {
+            model: "llama3:8b".to_string(),
+            registry_version: REGISTRY_VERSION,
+        }]
+    );
+    // Every name is unknown to an empty registry
+    assert_eq!(
+        ModelRegistry::empty()
+            .lookup_on("gpt-4o", "2025-01-01")
+            .status,
+        ModelStatus::Unknown
+    );
+}
+
+#[test]
+fn checks_warn_about_deprecated_and_retired_models() {
+    let registry = ModelRegistry::builtin();
+
+    assert_eq!(registry.check_on("gpt-4o", "2025-01-01").unwrap(), vec![]);
+    let warnings = registry.check_on("o1-mini", "2025-06-01").unwrap();
+    assert_eq!(
+        warnings,
+        vec![ConfigWarning::DeprecatedModel {
+            model: "o1-mini".to_string(),
+            shutdown_date: "2025-10-27".to_string(),
+            replacement: Some("o4-mini".to_string()),
+        }]
+    );
+    assert_eq!(
+        warnings[0].to_string(),
+        "The model o1-mini is deprecated and shuts down on 2025-10-27, use o4-mini instead"
+    );
+
+    let warnings = registry.check_on("gpt-4-0314", "2025-06-01").unwrap();
+    assert_eq!(
+        warnings,
+        vec![ConfigWarning::RetiredModel {
+            model: "gpt-4-0314".to_string(),
+            retired_on: "2024-06-13".to_string(),
+            replacement: Some("gpt-4o".to_string()),
+        }]
+    );
+}
+
+#[test]
+fn strict_registries_refuse_retired_models() {
+    let registry = ModelRegistry::builtin().with_strict(true);
+    assert!(registry.is_strict());
+
+    let error = registry.check_on("gpt-4-0314", "2025-06-01").unwrap_err();
+    assert!(matches!(
+        &error,
+        SecretaryError::RetiredModel { model, retired_on, replacement }
+            if model == "gpt-4-0314"
+                && retired_on == "2024-06-13"
+                && replacement.as_deref() == Some("gpt-4o")
+    ));
+    assert_eq!(
+        error.to_string(),
+        "The model gpt-4-0314 was retired on 2024-06-13, use gpt-4o instead"
+    );
+
+    // Deprecated and unknown models are still only warned about
+    assert_eq!(registry.check_on("o1-mini", "2025-06-01").unwrap().len(), 1);
+    assert_eq!(
+        registry.check_on("llama3:8b", "2025-06-01").unwrap().len(),
+        1
+    );
+
+    let error =
+        OpenAILLM::new_with_registry(API_BASE, "key", "text-davinci-003", &registry).unwrap_err();
+    assert!(matches!(error, SecretaryError::RetiredModel { .. }));
+}
+
+#[test]
+fn providers_keep_the_warnings_of_their_model() {
+    let registry = ModelRegistry::builtin().with_model(ModelEntry::deprecated(
+        "legacy-extractor",
+        "2999-01-01",
+        None,
+    ));
+
+    let (llm, warnings) =
+        OpenAILLM::new_with_registry(API_BASE, "key", "legacy-extractor", &registry).unwrap();
+    assert_eq!(
+        warnings,
+        vec![ConfigWarning::DeprecatedModel {
+            model: "legacy-extractor".to_string(),
+            shutdown_date: "2999-01-01".to_string(),
+            replacement: None,
+        }]
+    );
+    assert_eq!(llm.config_warnings(), &warnings[..]);
+
+    // Retired models are built by a lenient registry, with a warning
+    let (_, warnings) =
+        OpenAILLM::new_with_registry(API_BASE, "key", "gpt-4-0314", &registry).unwrap();
+    assert!(matches!(
+        &warnings[..],
+        [ConfigWarning::RetiredModel { .. }]
+    ));
+
+    // `new` checks the shared registry and never fails on the model
+    let llm = OpenAILLM::new(API_BASE, "key", "gpt-4-0314").unwrap();
+    assert!(matches!(
+        llm.config_warnings(),
+        [ConfigWarning::RetiredModel { .. }]
+    ));
+    let llm = OpenAILLM::new(API_BASE, "key", "gpt-4o").unwrap();
+    assert!(llm.config_warnings().is_empty());
+}
+
+#[test]
+fn registries_are_extended_with_models_and_aliases() {
+    let registry = ModelRegistry::empty()
+        .with_model(ModelEntry::active("acme-*"))
+        .with_model(ModelEntry::retired("acme-1", "2024-01-01", Some("acme-2")))
+        .with_alias("acme", "acme-2");
+
+    assert_eq!(registry.lookup_on("acme", "2025-01-01").canonical, "acme-2");
+    assert_eq!(
+        registry.lookup_on("acme", "2025-01-01").status,
+        ModelStatus::Active
+    );
+    assert!(matches!(
+        registry.lookup_on("acme-1", "2025-01-01").status,
+        ModelStatus::Retired { .. }
+    ));
+
+    // An entry with the same pattern replaces the previous one, in every clone
+    let clone = registry.clone();
+    registry.add_model(ModelEntry::active("acme-1"));
+    assert_eq!(
+        clone.lookup_on("acme-1", "2025-01-01").status,
+        ModelStatus::Active
+    );
+}
+
+#[tokio::test]
+async fn refreshing_adds_the_listed_models_that_are_unknown() {
+    let server = MockServer::always(MockResponse::new(
+        200,
+        json!({
+            "object": "list",
+            "data": [
+                {"id": "gpt-4o", "object": "model"},
+                {"id": "o1-mini", "object": "model"},
+                {"id": "ft:gpt-4o:acme::abc123", "object": "model"},
+                {"id": "acme-embedder", "object": "model"}
+            ]
+        }),
+    ));
+    let registry =
+        ModelRegistry::builtin().with_model(ModelEntry::deprecated("ft:*", "2999-01-01", None));
+    assert_eq!(
+        registry.lookup("acme-embedder").status,
+        ModelStatus::Unknown
+    );
+
+    let added = registry.refresh_from_api(&server.llm()).await.unwrap();
+
+    assert_eq!(added, vec!["acme-embedder".to_string()]);
+    assert_eq!(registry.lookup("acme-embedder").status, ModelStatus::Active);
+    // Known models keep their status
+    assert!(matches!(
+        registry.lookup("ft:gpt-4o:acme::abc123").status,
+        ModelStatus::Deprecated { .. }
+    ));
+    assert!(matches!(
+        registry.lookup("o1-mini").status,
+        ModelStatus::Deprecated { .. } | ModelStatus::Retired { .. }
+    ));
+
+    let request = &server.requests()[0];
+    assert_eq!(request.path, "/models");
+    assert_eq!(request.headers["authorization"], "Bearer test-key");
+
+    // A second refresh has nothing to add
+    let added = registry.refresh_from_api(&server.llm()).await.unwrap();
+    assert!(added.is_empty());
+}
+
+#[tokio::test]
+async fn refreshing_fails_without_a_model_list() {
+    let registry = ModelRegistry::builtin();
+
+    let server = MockServer::always(MockResponse::new(
+        401,
+        json!({"error": {"message": "Incorrect API key provided"}}),
+    ));
+    let error = registry.refresh_from_api(&server.llm()).await.unwrap_err();
+    assert!(matches!(
+        &error,
+        SecretaryError::ModelListUnavailable { message } if message.contains("401")
+    ));
+
+    let server = MockServer::always(MockResponse::new(200, json!({"models": []})));
+    let error = registry.refresh_from_api(&server.llm()).await.unwrap_err();
+    assert!(matches!(error, SecretaryError::ModelListUnavailable { .. }));
+
+    // Azure deployments have no model list
+    let config: ProviderConfig = serde_json::from_value(json!({
+        "provider": "azure",
+        "endpoint": server.address(),
+        "deployment": "gpt-4o",
+        "api_version": "2024-06-01",
+        "api_key_env": "AZURE_OPENAI_KEY"
+    }))
+    .unwrap();
+    let llm = config.build_with(|_| Some("key".to_string())).unwrap();
+    let error = registry.refresh_from_api(&llm).await.unwrap_err();
+    assert!(matches!(error, SecretaryError::ModelListUnavailable { .. }));
+}
@@ -0,0 +1,280 @@
+//! A resume combines a nested Task, a list of Tasks holding a list and an optional Task, and
+//! is extracted whole and field by field.
+
+mod support;
+
+use secretary::Task;
+use secretary::assembly::assemble_from_fields;
+use secretary::traits::{AsyncGenerateData, GenerateData};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use support::fixtures::{field_result, success};
+use support::{MockResponse, MockServer};
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct ContactInfo {
+    #[task(instruction = "Extract the candidate's name")]
+    pub name: String,
+    #[task(instruction = "Extract the candidate's email address")]
+    pub email: Option<String>,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Experience {
+    #[task(instruction = "Extract the employer")]
+    pub employer: String,
+    #[task(instruction = "Extract the dates of employment")]
+    pub dates: String,
+    #[task(instruction = "List the achievements")]
+    pub bullets: Vec<String>,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Education {
+    #[task(instruction = "Extract the school")]
+    pub school: String,
+    #[task(instruction = "Extract the degree")]
+    pub degree: String,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Resume {
+    pub contact: ContactInfo,
+    #[task(instruction = "Extract every position held")]
+    pub experiences: Vec<Experience>,
+    #[task(instruction = "Extract the highest education, if any")]
+    pub education: Option<Education>,
+}
+
+const FIXTURE: &str = include_str!("fixtures/resume.json");
+
+const TARGET: &str = "Grace Hopper (grace@example.com). Harvard Computation Laboratory, \
+                      1944 - 1949: programmed the Mark I and wrote its operating manual. \
+                      Remington Rand, 1949 - 1967: built the A-0 compiler and led the \
+                      FLOW-MATIC team. PhD in Mathematics, Yale University.";
+
+/// The value of each field by its instruction, relative to the object holding it.
+const FIELDS: [(&str, &str); 7] = [
+    ("Extract the candidate's name", "/contact/name"),
+    ("Extract the candidate's email address", "/contact/email"),
+    ("Extract the employer", "/employer"),
+    ("Extract the dates of employment", "/dates"),
+    ("List the achievements", "/bullets"),
+    ("Extract the school", "/education/school"),
+    ("Extract the degree", "/education/degree"),
+];
+
+fn expected() -> Resume {
+    serde_json::from_str(FIXTURE).unwrap()
+}
+
+/// Answers field requests from the fixture and whole requests with all of it.
+fn answer(fixture: &Value, prompt: &str) -> MockResponse {
+    if !prompt.contains("<result></result>") {
+        return success(&fixture.to_string());
+    }
+    let (_, pointer) = FIELDS
+        .iter()
+        .find(|(instruction, _)| prompt.contains(instruction))
+        .unwrap();
+
+    let experience: Option<&Value> = (1..=fixture["experiences"].as_array().unwrap().len())
+        .find(|item| prompt.contains(&format!("item {} of experiences", item)))
+        .map(|item| &fixture["experiences"][item - 1]);
+    let value: &Value = match experience {
+        Some(experience) => experience.pointer(pointer).unwrap(),
+        None => fixture.pointer(pointer).unwrap(),
+    };
+
+    match value {
+        Value::String(value) => field_result(value),
+        Value::Array(values) => field_result(
+            &values
+                .iter()
+                .map(|value| format!("<item>{}</item>", value.as_str().unwrap()))
+                .collect::<String>(),
+        ),
+        value => field_result(&value.to_string()),
+    }
+}
+
+fn server(fixture: Value) -> MockServer {
+    MockServer::start(move |request| answer(&fixture, &request.prompt()))
+}
+
+#[test]
+fn the_prompt_describes_every_level() {
+    let mut absent: Resume = Resume::new();
+    absent.education = None;
+    let education: String = Education::new().get_system_prompt();
+
+    for resume in [Resume::new(), absent] {
+        let prompt: String = resume.get_system_prompt();
+        assert!(prompt.contains("--- contact Task Details ---"));
+        assert!(prompt.contains("List the achievements"));
+        // The optional task is described whether the instance holds it or not, and its block
+        // closes on a line of its own
+        assert!(prompt.contains(&format!(
+            "{}\n--- End of education Optional Task ---\n",
+            education
+        )));
+    }
+    assert!(
+        !Resume {
+            education: None,
+            ..Resume::new()
+        }
+        .get_system_prompt()
+        .contains("(Optional field is None)")
+    );
+}
+
+#[test]
+fn each_item_of_a_list_is_asked_for_separately() {
+    let prompts = Resume::new().get_distributed_field_prompts();
+    let paths: Vec<&str> = prompts
+        .iter()
+        .map(|prompt| prompt.field_path.as_str())
+        .collect();
+
+    assert_eq!(
+        paths,
+        vec![
+            "contact.name",
+            "contact.email",
+            "experiences[0].employer",
+            "experiences[0].dates",
+            "experiences[0].bullets",
+            "experiences[1].employer",
+            "experiences[1].dates",
+            "experiences[1].bullets",
+            "education.school",
+            "education.degree",
+        ]
+    );
+    assert_ne!(prompts[2].prompt, prompts[5].prompt);
+    assert!(
+        prompts[4]
+            .prompt
+            .contains("This field belongs to item 1 of experiences")
+    );
+    assert!(
+        prompts[7]
+            .prompt
+            .contains("This field belongs to item 2 of experiences")
+    );
+}
+
+#[test]
+fn generate_data_parses_the_whole_resume() {
+    let server = server(serde_json::from_str(FIXTURE).unwrap());
+
+    let resume: Resume = server
+        .llm()
+        .generate_data(&Resume::new(), TARGET, vec![])
+        .unwrap();
+
+    assert_eq!(resume, expected());
+    assert!(server.requests()[0].is_json_mode());
+}
+
+#[test]
+fn generate_data_reads_a_missing_education_as_none() {
+    let mut fixture: Value = serde_json::from_str(FIXTURE).unwrap();
+    fixture["education"] = Value::Null;
+    let server = server(fixture);
+
+    let resume: Resume = server
+        .llm()
+        .generate_data(&Resume::new(), TARGET, vec![])
+        .unwrap();
+
+    assert_eq!(
+        resume,
+        Resume {
+            education: None,
+            ..expected()
+        }
+    );
+}
+
+#[test]
+fn force_generate_data_parses_the_whole_resume() {
+    let server = server(serde_json::from_str(FIXTURE).unwrap());
+
+    let resume: Resume = server
+        .llm()
+        .force_generate_data(&Resume::new(), TARGET, vec![])
+        .unwrap();
+
+    assert_eq!(resume, expected());
+}
+
+#[test]
+fn fields_generate_data_reassembles_every_item() {
+    let server = server(serde_json::from_str(FIXTURE).unwrap());
+
+    let resume: Resume = server
+        .llm()
+        .fields_generate_data(&Resume::new(), TARGET, vec![])
+        .unwrap();
+
+    assert_eq!(resume, expected());
+    assert_eq!(server.requests().len(), 10);
+}
+
+#[test]
+fn field_answers_are_assembled_by_their_indexed_paths() {
+    let fields: Vec<(String, String)> = [
+        ("contact.name", "Grace Hopper"),
+        ("contact.email", "grace@example.com"),
+        ("experiences[1].employer", "Remington Rand"),
+        ("experiences[1].dates", "1949 - 1967"),
+        (
+            "experiences[1].bullets",
+            r#"["Built the A-0 compiler", "Led the FLOW-MATIC team"]"#,
+        ),
+        ("experiences[0].employer", "Harvard Computation Laboratory"),
+        ("experiences[0].dates", "1944 - 1949"),
+        (
+            "experiences[0].bullets",
+            r#"["Programmed the Mark I", "Wrote the Mark I operating manual"]"#,
+        ),
+        ("education.school", "Yale University"),
+        ("education.degree", "PhD in Mathematics"),
+    ]
+    .into_iter()
+    .map(|(path, content)| (path.to_string(), content.to_string()))
+    .collect();
+
+    let resume: Resume = assemble_from_fields(fields).unwrap();
+
+    assert_eq!(resume, expected());
+}
+
+#[tokio::test]
+async fn async_generate_data_parses_the_whole_resume() {
+    let server = server(serde_json::from_str(FIXTURE).unwrap());
+
+    let resume: Resume = server
+        .llm()
+        .async_generate_data(&Resume::new(), TARGET, vec![])
+        .await
+        .unwrap();
+
+    assert_eq!(resume, expected());
+}
+
+#[tokio::test]
+async fn async_fields_generate_data_reassembles_every_item() {
+    let server = server(serde_json::from_str(FIXTURE).unwrap());
+
+    let resume: Resume = server
+        .llm()
+        .async_fields_generate_data(&Resume::new(), TARGET, vec![])
+        .await
+        .unwrap();
+
+    assert_eq!(resume, expected());
+}
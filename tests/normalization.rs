@@ -0,0 +1,356 @@
+//! The strings of Tasks with `#[task(normalize_strings)]` are normalized before they are
+//! deserialized, in every generation mode, and other fields are left as they are.
+
+mod support;
+
+use std::collections::{BTreeMap, HashMap};
+
+use proptest::prelude::*;
+use secretary::Task;
+use secretary::leniency::LeniencyProfile;
+use secretary::normalization::{StringNormalization, normalize_strings, uses_string_normalization};
+use secretary::traits::{AsyncGenerateData, GenerateData};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use support::fixtures::{empty_choices, field_result, success};
+use support::{MockServer, TARGET};
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[task(normalize_strings)]
+struct Address {
+    #[task(instruction = "Extract the street and number")]
+    pub street: String,
+    #[task(instruction = "Extract the postal code")]
+    pub postal_code: Option<String>,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[task(normalize_strings)]
+struct Customer {
+    #[task(instruction = "Extract the customer's full name")]
+    pub name: String,
+    #[task(instruction = "Extract the nicknames the customer goes by")]
+    pub nicknames: Vec<String>,
+    #[task(instruction = "Extract the customer's labels by category")]
+    pub labels: HashMap<String, String>,
+    #[task(instruction = "Extract the customer's scores by category")]
+    pub scores: BTreeMap<String, f64>,
+    #[task(instruction = "Extract the number of orders as a number")]
+    pub orders: u32,
+    #[task(plain, instruction = "Extract the raw note exactly as written")]
+    pub note: Value,
+    pub address: Address,
+    #[task(instruction = "Extract the customer's previous addresses")]
+    pub previous_addresses: Vec<Address>,
+    pub contact: Contact,
+}
+
+/// A nested Task without the attribute, whose strings are kept.
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Contact {
+    #[task(instruction = "Extract the customer's email address")]
+    pub email: String,
+}
+
+/// A Task that keeps the whitespace inside its strings.
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[task(normalize_strings = "trim")]
+struct Poem {
+    #[task(instruction = "Extract the title of the poem")]
+    pub title: String,
+    #[task(instruction = "Extract the text of the poem with its line breaks")]
+    pub text: String,
+}
+
+fn messy_customer() -> Value {
+    json!({
+        "name": "  Ada \u{00A0}\t Lovelace \n",
+        "nicknames": [" Enchantress of\u{00A0}Numbers ", "\u{2003}Ada\u{202F}"],
+        "labels": {"  tier ": " gold  member "},
+        "scores": {" loyalty ": 4.5},
+        "orders": 3,
+        "note": "  kept  as is  ",
+        "address": {"street": " 12  St James's Square ", "postal_code": " SW1Y 4JH "},
+        "previous_addresses": [{"street": "\tMarylebone\n", "postal_code": null}],
+        "contact": {"email": " ada@example.com "}
+    })
+}
+
+fn clean_customer() -> Customer {
+    Customer {
+        name: "Ada Lovelace".to_string(),
+        nicknames: vec!["Enchantress of Numbers".to_string(), "Ada".to_string()],
+        labels: HashMap::from([("  tier ".to_string(), "gold member".to_string())]),
+        scores: BTreeMap::from([(" loyalty ".to_string(), 4.5)]),
+        orders: 3,
+        note: json!("  kept  as is  "),
+        address: Address {
+            street: "12 St James's Square".to_string(),
+            postal_code: Some("SW1Y 4JH".to_string()),
+        },
+        previous_addresses: vec![Address {
+            street: "Marylebone".to_string(),
+            postal_code: None,
+        }],
+        contact: Contact {
+            email: " ada@example.com ".to_string(),
+        },
+    }
+}
+
+#[test]
+fn texts_are_trimmed_and_their_whitespace_collapsed() {
+    let collapse = StringNormalization::Collapse;
+
+    assert_eq!(collapse.apply("  a \u{00A0}\t b\r\n c  "), "a b c");
+    assert_eq!(collapse.apply("\u{2009}\u{3000}wide\u{3000}"), "wide");
+    assert_eq!(collapse.apply(" \n\t "), "");
+    assert_eq!(collapse.apply("already clean"), "already clean");
+    // Zero-width spaces are not whitespace
+    assert_eq!(collapse.apply("a\u{200B}b"), "a\u{200B}b");
+}
+
+#[test]
+fn trimming_keeps_inner_whitespace_but_not_non_breaking_spaces() {
+    let trim = StringNormalization::Trim;
+
+    assert_eq!(
+        trim.apply("\n Roses  are red,\n\tviolets\u{00A0}blue \u{00A0}"),
+        "Roses  are red,\n\tviolets blue"
+    );
+    assert_eq!(trim.apply("a\u{202F}b\u{2007}c"), "a b c");
+    assert_eq!(StringNormalization::from_name("trim"), Some(trim));
+    assert_eq!(StringNormalization::from_name("nfc"), None);
+    assert_eq!(StringNormalization::Collapse.name(), "collapse");
+}
+
+#[test]
+fn the_walker_normalizes_only_the_strings_of_flagged_tasks() {
+    let fields = Customer::field_descriptors();
+    assert!(uses_string_normalization(&fields));
+    assert!(!uses_string_normalization(&Contact::field_descriptors()));
+    let mut value: Value = messy_customer();
+
+    normalize_strings(&fields, &mut value);
+
+    assert_eq!(value["name"], "Ada Lovelace");
+    assert_eq!(value["nicknames"], json!(["Enchantress of Numbers", "Ada"]));
+    // Map keys are data, their values are normalized
+    assert_eq!(value["labels"], json!({"  tier ": "gold member"}));
+    assert_eq!(value["scores"], json!({" loyalty ": 4.5}));
+    assert_eq!(value["note"], "  kept  as is  ");
+    assert_eq!(value["address"]["street"], "12 St James's Square");
+    assert_eq!(value["address"]["postal_code"], "SW1Y 4JH");
+    assert_eq!(value["previous_addresses"][0]["street"], "Marylebone");
+    assert_eq!(value["previous_addresses"][0]["postal_code"], Value::Null);
+    assert_eq!(value["contact"]["email"], " ada@example.com ");
+}
+
+#[test]
+fn the_walker_leaves_values_of_other_types_and_unknown_keys() {
+    let fields = Customer::field_descriptors();
+    let mut value: Value = json!({
+        "name": 42,
+        "nicknames": " not a list ",
+        "labels": [" not a map "],
+        "orders": " 3 ",
+        "extra": " unknown ",
+        "address": " not an object "
+    });
+    let expected: Value = value.clone();
+
+    normalize_strings(&fields, &mut value);
+    assert_eq!(value, expected);
+
+    let mut not_an_object: Value = json!(" text ");
+    normalize_strings(&fields, &mut not_an_object);
+    assert_eq!(not_an_object, " text ");
+}
+
+#[test]
+fn descriptors_carry_the_normalization_of_their_task() {
+    let fields = Customer::field_descriptors();
+    let normalization = |name: &str| {
+        fields
+            .iter()
+            .find(|field| field.name == name)
+            .unwrap()
+            .normalize_strings
+    };
+
+    assert_eq!(normalization("name"), Some(StringNormalization::Collapse));
+    assert_eq!(normalization("orders"), Some(StringNormalization::Collapse));
+    let contact = fields.iter().find(|field| field.name == "contact").unwrap();
+    assert_eq!(contact.children[0].normalize_strings, None);
+    assert_eq!(
+        Poem::field_descriptors()[0].normalize_strings,
+        Some(StringNormalization::Trim)
+    );
+    // Tasks without the attribute serialize their descriptors as before
+    let descriptor: Value = serde_json::to_value(&contact.children[0]).unwrap();
+    assert!(descriptor.get("normalize_strings").is_none());
+}
+
+#[test]
+fn every_leniency_profile_parses_normalized_strings() {
+    let answer: String = messy_customer().to_string();
+
+    for leniency in [LeniencyProfile::strict(), LeniencyProfile::aggressive()] {
+        let customer: Customer = leniency.from_str(&answer).unwrap();
+        assert_eq!(customer, clean_customer());
+    }
+
+    let poem: Poem = LeniencyProfile::strict()
+        .from_str(r#"{"title": " Roses ", "text": "Roses are red,\n  violets blue\n"}"#)
+        .unwrap();
+    assert_eq!(poem.title, "Roses");
+    assert_eq!(poem.text, "Roses are red,\n  violets blue");
+}
+
+#[test]
+fn single_request_extraction_normalizes_the_answer() {
+    let server = MockServer::always(success(&messy_customer().to_string()));
+
+    let customer: Customer = server
+        .llm()
+        .generate_data(&Customer::new(), TARGET, vec![])
+        .unwrap();
+
+    assert_eq!(customer, clean_customer());
+}
+
+#[tokio::test]
+async fn async_extraction_normalizes_the_answer() {
+    let server = MockServer::always(success(&messy_customer().to_string()));
+
+    let customer: Customer = server
+        .llm()
+        .async_generate_data(&Customer::new(), TARGET, vec![])
+        .await
+        .unwrap();
+
+    assert_eq!(customer, clean_customer());
+}
+
+#[test]
+fn distributed_extraction_normalizes_every_field() {
+    let server = MockServer::by_instruction(
+        vec![
+            (
+                "Extract the street and number",
+                field_result("  1  Main St "),
+            ),
+            ("Extract the postal code", field_result("\u{00A0}12345 ")),
+        ],
+        empty_choices(),
+    );
+
+    let address: Address = server
+        .llm()
+        .fields_generate_data(&Address::new(), TARGET, vec![])
+        .unwrap();
+
+    assert_eq!(
+        address,
+        Address {
+            street: "1 Main St".to_string(),
+            postal_code: Some("12345".to_string()),
+        }
+    );
+}
+
+#[cfg(feature = "unicode")]
+#[test]
+fn texts_are_composed_to_nfc() {
+    let decomposed: &str = "Cafe\u{0301}";
+    let composed: &str = "Caf\u{00E9}";
+
+    assert_eq!(StringNormalization::Collapse.apply(decomposed), composed);
+    assert_eq!(StringNormalization::Trim.apply(composed), composed);
+    // Canonical singletons are replaced too
+    assert_eq!(
+        StringNormalization::Trim.apply("\u{212B}ngstr\u{00F6}m"),
+        "\u{00C5}ngstr\u{00F6}m"
+    );
+
+    let address: Address = LeniencyProfile::strict()
+        .from_str(&json!({"street": " Rue du Cafe\u{0301} ", "postal_code": null}).to_string())
+        .unwrap();
+    assert_eq!(address.street, "Rue du Caf\u{00E9}");
+}
+
+/// Characters that exercise trimming, collapsing and composition.
+const PIECES: &[&str] = &[
+    "a",
+    "B",
+    "é",
+    "e\u{0301}",
+    "\u{0301}",
+    "\u{0327}",
+    "\u{212B}",
+    " ",
+    "  ",
+    "\t",
+    "\n",
+    "\r\n",
+    "\u{00A0}",
+    "\u{202F}",
+    "\u{2007}",
+    "\u{2000}",
+    "\u{3000}",
+    "\u{200B}",
+    "ß",
+    "1",
+];
+
+fn text() -> impl Strategy<Value = String> {
+    prop_oneof![
+        proptest::collection::vec(proptest::sample::select(PIECES), 0..24)
+            .prop_map(|pieces| pieces.concat()),
+        any::<String>(),
+    ]
+}
+
+proptest! {
+    #[test]
+    fn normalization_is_idempotent(text in text()) {
+        for normalization in [StringNormalization::Collapse, StringNormalization::Trim] {
+            let once: String = normalization.apply(&text);
+            prop_assert_eq!(&normalization.apply(&once), &once);
+            prop_assert_eq!(once.trim(), once.as_str());
+            prop_assert!(
+                !once.contains('\u{00A0}'),
+                "{:?} keeps a no-break space after {:?}",
+                once,
+                normalization
+            );
+        }
+        let collapsed: String = StringNormalization::Collapse.apply(&text);
+        prop_assert!(!collapsed.contains("  "));
+        prop_assert!(collapsed.chars().all(|character| character == ' ' || !character.is_whitespace()));
+    }
+
+    #[test]
+    fn walking_a_normalized_answer_changes_nothing(
+        name in text(),
+        nicknames in proptest::collection::vec(text(), 0..4),
+        street in text(),
+        email in text(),
+    ) {
+        let fields = Customer::field_descriptors();
+        let mut value: Value = json!({
+            "name": name,
+            "nicknames": nicknames,
+            "labels": {"k": street.clone()},
+            "address": {"street": street, "postal_code": null},
+            "contact": {"email": email.clone()}
+        });
+        normalize_strings(&fields, &mut value);
+        let once: Value = value.clone();
+        normalize_strings(&fields, &mut value);
+
+        prop_assert_eq!(&value, &once);
+        prop_assert_eq!(&value["contact"]["email"], &json!(email));
+    }
+}
@@ -0,0 +1,94 @@
+//! Labeled examples measure how often each field matches, and the least accurate fields get
+//! rewritten instructions.
+
+mod support;
+
+use secretary::Task;
+use secretary::optimize::{AccuracyReport, evaluate, suggest_instructions};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use support::MockServer;
+use support::fixtures::{field_result, success};
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Invoice {
+    #[task(instruction = "Extract the vendor name")]
+    pub vendor: String,
+    #[task(instruction = "Extract the total")]
+    pub total: f64,
+}
+
+fn examples() -> Vec<(String, Invoice)> {
+    vec![
+        (
+            "ACME invoice. Subtotal 90.00, tax 10.00, total 100.00".to_string(),
+            Invoice {
+                vendor: "ACME".to_string(),
+                total: 100.0,
+            },
+        ),
+        (
+            "Globex invoice. Subtotal 20.00, total 20.00".to_string(),
+            Invoice {
+                vendor: "Globex".to_string(),
+                total: 20.0,
+            },
+        ),
+    ]
+}
+
+/// A model that gets every vendor right but reads the subtotal instead of the total, and
+/// proposes a clearer instruction when asked.
+fn subtotal_server() -> MockServer {
+    MockServer::start(|request| {
+        let prompt: String = request.prompt();
+        if prompt.contains("Propose a revised instruction") {
+            field_result("Extract the grand total including tax, as a number")
+        } else {
+            let answer: Value = if prompt.contains("ACME") {
+                json!({"vendor": "ACME", "total": 90.0})
+            } else {
+                json!({"vendor": "Globex", "total": 20.0})
+            };
+            success(&answer.to_string())
+        }
+    })
+}
+
+#[tokio::test]
+async fn evaluate_reports_the_rate_of_each_field() {
+    let server = subtotal_server();
+
+    let report: AccuracyReport = evaluate(&server.llm(), &examples()).await.unwrap();
+
+    assert_eq!(report.examples, 2);
+    assert_eq!(report.fields["vendor"].rate(), 1.0);
+    assert_eq!(report.fields["total"].rate(), 0.5);
+    assert_eq!(report.overall_rate(), 0.75);
+}
+
+#[tokio::test]
+async fn suggestions_rewrite_the_least_accurate_field() {
+    let server = subtotal_server();
+
+    let suggestions: Vec<(String, String)> = suggest_instructions(&server.llm(), &examples())
+        .await
+        .unwrap();
+
+    assert_eq!(
+        suggestions,
+        vec![(
+            "total".to_string(),
+            "Extract the grand total including tax, as a number".to_string()
+        )]
+    );
+    let revision: String = server
+        .requests()
+        .iter()
+        .map(|request| request.prompt())
+        .find(|prompt| prompt.contains("Propose a revised instruction"))
+        .unwrap();
+    assert!(revision.contains("Extract the total"));
+    assert!(!revision.contains("Extract the vendor name"));
+}
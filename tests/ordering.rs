@@ -0,0 +1,275 @@
+//! Lists with `#[task(ordered)]` ask for the order of the target, and can be verified.
+
+mod support;
+
+use secretary::ordering::{
+    ORDER_REQUIREMENT, OrderCheck, OrderViolation, check_item_order, locate_items,
+};
+use secretary::request::RequestOptions;
+use secretary::traits::{AsyncGenerateData, GenerateData};
+use secretary::{SecretaryError, Task};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use support::fixtures::success;
+use support::{MockServer, secretary_error};
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Transaction {
+    #[task(instruction = "Extract the payee")]
+    pub payee: String,
+    #[task(instruction = "Extract the amount as a number")]
+    pub amount: f64,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Procedure {
+    #[task(instruction = "Extract the name of the procedure")]
+    pub name: String,
+    #[task(instruction = "List the steps", ordered)]
+    pub steps: Vec<String>,
+    #[task(instruction = "List the tools needed")]
+    pub tools: Vec<String>,
+    #[task(instruction = "Extract every transaction", ordered)]
+    pub transactions: Vec<Transaction>,
+}
+
+const TARGET: &str = "Changing a tyre. Loosen the nuts with the wrench, then jack up the car. \
+     Remove the wheel and fit the spare. Paid Ada's Garage 40.00 for the jack, \
+     then Tyre World 85.50 for the spare.";
+
+fn strings(items: &[&str]) -> Vec<String> {
+    items.iter().map(|item| item.to_string()).collect()
+}
+
+fn procedure(steps: &[&str], payees: &[&str]) -> Value {
+    json!({
+        "name": "Changing a tyre",
+        "steps": steps,
+        "tools": ["jack", "wrench"],
+        "transactions": payees
+            .iter()
+            .map(|payee| json!({"payee": payee, "amount": 1.0}))
+            .collect::<Vec<Value>>(),
+    })
+}
+
+const IN_ORDER: [&str; 4] = [
+    "Loosen the nuts",
+    "Jack up the car",
+    "Remove the wheel",
+    "Fit the spare",
+];
+
+#[test]
+fn items_are_located_in_order() {
+    let ranges = locate_items(TARGET, &strings(&IN_ORDER));
+
+    let starts: Vec<usize> = ranges
+        .iter()
+        .map(|range| range.clone().unwrap().start)
+        .collect();
+    assert!(starts.windows(2).all(|pair| pair[0] < pair[1]));
+    assert_eq!(&TARGET[ranges[1].clone().unwrap()], "jack up the car");
+}
+
+#[test]
+fn repeated_items_match_successive_occurrences() {
+    let source = "Stir. Rest. Stir. Rest. Serve.";
+    let ranges = locate_items(source, &strings(&["Stir", "Rest", "Stir", "Rest", "Serve"]));
+
+    assert_eq!(
+        ranges,
+        vec![
+            Some(0..4),
+            Some(6..10),
+            Some(12..16),
+            Some(18..22),
+            Some(24..29)
+        ]
+    );
+}
+
+#[test]
+fn repeated_items_found_once_are_not_violations() {
+    let fields = Procedure::field_descriptors();
+    let answer: Value = procedure(
+        &["Loosen the nuts", "Loosen the nuts", "Fit the spare"],
+        &[],
+    );
+
+    assert_eq!(check_item_order(&fields, &answer, TARGET), vec![]);
+}
+
+#[test]
+fn reworded_items_are_located_by_their_longest_word() {
+    let ranges = locate_items(
+        TARGET,
+        &strings(&["Loosen all wheel nuts", "Take the wheel off"]),
+    );
+
+    assert_eq!(&TARGET[ranges[0].clone().unwrap()], "Loosen");
+    assert_eq!(&TARGET[ranges[1].clone().unwrap()], "wheel");
+}
+
+#[test]
+fn paraphrased_items_are_not_located_nor_violations() {
+    let ranges = locate_items(TARGET, &strings(&["Fit the spare", "Unscrew bolts"]));
+    assert!(ranges[0].is_some());
+    assert_eq!(ranges[1], None);
+
+    let answer: Value = procedure(&["Fit the spare", "Unscrew bolts", "Remove the wheel"], &[]);
+    let violations = check_item_order(&Procedure::field_descriptors(), &answer, TARGET);
+    assert_eq!(violations.len(), 1);
+    assert_eq!(violations[0].path, "steps[2]");
+}
+
+#[test]
+fn case_whitespace_and_non_ascii_text_are_located() {
+    let source = "Étape 1 : préchauffer   le four. Étape 2 : mélanger.";
+    let ranges = locate_items(source, &strings(&["PRÉCHAUFFER LE FOUR", "Mélanger"]));
+
+    assert_eq!(&source[ranges[0].clone().unwrap()], "préchauffer   le four");
+    assert_eq!(&source[ranges[1].clone().unwrap()], "mélanger");
+}
+
+#[test]
+fn items_out_of_order_are_violations() {
+    let answer: Value = procedure(
+        &[
+            "Jack up the car",
+            "Loosen the nuts",
+            "Remove the wheel",
+            "Fit the spare",
+        ],
+        &["Tyre World", "Ada's Garage"],
+    );
+
+    let violations = check_item_order(&Procedure::field_descriptors(), &answer, TARGET);
+
+    assert_eq!(
+        violations,
+        vec![
+            OrderViolation {
+                path: "steps[1]".to_string(),
+                item: "Loosen the nuts".to_string(),
+                position: TARGET.find("Loosen").unwrap(),
+                previous_position: TARGET.find("jack up").unwrap(),
+            },
+            OrderViolation {
+                path: "transactions[1]".to_string(),
+                item: "Ada's Garage".to_string(),
+                position: TARGET.find("Ada's").unwrap(),
+                previous_position: TARGET.find("Tyre World").unwrap(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn lists_without_ordered_are_not_checked() {
+    let mut answer: Value = procedure(&IN_ORDER, &["Ada's Garage", "Tyre World"]);
+    answer["tools"] = json!(["wrench", "jack"]);
+
+    assert_eq!(
+        check_item_order(&Procedure::field_descriptors(), &answer, TARGET),
+        vec![]
+    );
+}
+
+#[test]
+fn the_prompt_asks_for_the_order_of_ordered_lists() {
+    let prompt: String = Procedure::new().get_system_prompt();
+
+    assert!(prompt.contains(&format!(
+        "steps: List the steps {}, JSON String(s) in a JSON Array\n",
+        ORDER_REQUIREMENT
+    )));
+    assert!(prompt.contains("tools: List the tools needed, JSON String(s) in a JSON Array\n"));
+    assert_eq!(prompt.matches(ORDER_REQUIREMENT).count(), 2);
+
+    let fields = Procedure::field_descriptors();
+    let ordered: Vec<bool> = fields.iter().map(|field| field.ordered).collect();
+    assert_eq!(ordered, vec![false, true, false, true]);
+}
+
+#[test]
+fn distributed_prompts_of_whole_lists_ask_for_the_order() {
+    let prompts = Procedure::new().get_system_prompts_for_distributed_generation();
+
+    // The transactions are requested element by element, in the order of the elements
+    for (field, prompt) in prompts {
+        assert_eq!(
+            prompt.contains(ORDER_REQUIREMENT),
+            field == "steps",
+            "{}",
+            field
+        );
+    }
+}
+
+fn shuffled_answer() -> String {
+    procedure(
+        &[
+            "Fit the spare",
+            "Loosen the nuts",
+            "Jack up the car",
+            "Remove the wheel",
+        ],
+        &["Ada's Garage", "Tyre World"],
+    )
+    .to_string()
+}
+
+#[test]
+fn order_violations_are_flagged_in_metadata() {
+    let server = MockServer::always(success(&shuffled_answer()));
+    let options = RequestOptions::default().with_order_check(OrderCheck::Flag);
+
+    let result = server
+        .llm()
+        .generate_data_adaptive_with_options(&Procedure::new(), TARGET, vec![], &options)
+        .unwrap();
+
+    let paths: Vec<&str> = result
+        .metadata
+        .order_violations
+        .iter()
+        .map(|violation| violation.path.as_str())
+        .collect();
+    assert_eq!(paths, vec!["steps[1]", "steps[2]", "steps[3]"]);
+    assert_eq!(result.data.steps[0], "Fit the spare");
+}
+
+#[test]
+fn order_violations_fail_under_the_error_check() {
+    let server = MockServer::always(success(&shuffled_answer()));
+    let options = RequestOptions::default().with_order_check(OrderCheck::Error);
+
+    let error = server
+        .llm()
+        .generate_data_adaptive_with_options(&Procedure::new(), TARGET, vec![], &options)
+        .unwrap_err();
+
+    match secretary_error(&error) {
+        SecretaryError::OrderViolated { path, item } => {
+            assert_eq!(path, "steps[1]");
+            assert_eq!(item, "Loosen the nuts");
+        }
+        other => panic!("unexpected error: {}", other),
+    }
+}
+
+#[tokio::test]
+async fn order_is_not_checked_by_default() {
+    let server = MockServer::always(success(&shuffled_answer()));
+
+    let result = server
+        .llm()
+        .async_generate_data_adaptive(&Procedure::new(), TARGET, vec![])
+        .await
+        .unwrap();
+
+    assert!(result.metadata.order_violations.is_empty());
+    assert!(server.requests()[0].prompt().contains(ORDER_REQUIREMENT));
+}
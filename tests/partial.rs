@@ -0,0 +1,54 @@
+//! Partial extraction replaces the nested subtree that failed to deserialize with its default
+//! and keeps the rest.
+
+#![allow(clippy::needless_borrows_for_generic_args)]
+
+mod support;
+
+use secretary::Task;
+use secretary::partial::PartialData;
+use secretary::traits::GenerateData;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use support::MockServer;
+use support::fixtures::success;
+
+#[derive(Task, Serialize, Deserialize, Debug, PartialEq)]
+struct Address {
+    #[task(instruction = "Extract the city")]
+    pub city: String,
+    #[task(instruction = "Extract the zip code as a number")]
+    pub zip: u32,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug, PartialEq)]
+struct Company {
+    #[task(instruction = "Extract the company name")]
+    pub name: String,
+    pub address: Address,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug, PartialEq)]
+struct Lead {
+    #[task(instruction = "Extract the lead score")]
+    pub score: u32,
+    pub company: Company,
+}
+
+#[test]
+fn a_broken_nested_object_is_replaced_by_its_default() {
+    let server = MockServer::always(success(
+        &json!({"score": 7, "company": {"name": "ACME", "address": "Oslo"}}).to_string(),
+    ));
+
+    let partial: PartialData<Lead> = server
+        .llm()
+        .generate_partial_data(&Lead::new(), "ACME, Oslo, score 7", &vec![])
+        .unwrap();
+
+    assert_eq!(partial.failed_fields, vec!["company.address"]);
+    assert_eq!(partial.data.score, 7);
+    assert_eq!(partial.data.company.name, "ACME");
+    assert_eq!(partial.data.company.address, Address::default());
+}
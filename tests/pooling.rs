@@ -0,0 +1,50 @@
+//! Requests of a provider and its clones reuse pooled keep-alive connections.
+
+#![allow(clippy::needless_borrows_for_generic_args)]
+
+mod support;
+
+use std::time::Duration;
+
+use secretary::llm_providers::http::PoolConfig;
+use secretary::traits::{AsyncGenerateData, GenerateData};
+
+use support::fixtures::success;
+use support::{ADA_JSON, MockServer, Person, TARGET, ada};
+
+#[test]
+fn sequential_requests_and_clones_reuse_one_connection() {
+    let server = MockServer::keep_alive(|_| success(ADA_JSON));
+    let llm = server
+        .llm()
+        .with_pool_config(PoolConfig::default().with_idle_timeout(Duration::from_secs(30)));
+    let task = Person::new();
+
+    for _ in 0..20 {
+        assert_eq!(llm.generate_data(&task, TARGET, &vec![]).unwrap(), ada());
+    }
+    llm.clone().generate_data(&task, TARGET, &vec![]).unwrap();
+
+    assert_eq!(server.requests().len(), 21);
+    assert_eq!(server.connections(), 1);
+}
+
+#[tokio::test]
+async fn the_async_client_keeps_its_own_pool() {
+    let server = MockServer::keep_alive(|_| success(ADA_JSON));
+    let llm = server.llm();
+    let task = Person::new();
+
+    for _ in 0..20 {
+        llm.async_generate_data(&task, TARGET, &vec![])
+            .await
+            .unwrap();
+    }
+    llm.clone()
+        .async_generate_data(&task, TARGET, &vec![])
+        .await
+        .unwrap();
+
+    assert_eq!(server.requests().len(), 21);
+    assert_eq!(server.connections(), 1);
+}
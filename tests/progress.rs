@@ -0,0 +1,284 @@
+//! Chunked, distributed and routed extractions report each of their stages to the reporter
+//! attached to the call, in order and timed from the start of the call.
+
+mod support;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use secretary::chunking::{ChunkOptions, MergePolicy};
+use secretary::llm_providers::openai::OpenAILLM;
+use secretary::llm_providers::rate_limit::RetryPolicy;
+use secretary::llm_providers::router::{Router, RoutingPolicy};
+use secretary::progress::{ProgressEvent, ProgressKind, RecordingReporter};
+use secretary::request::RequestOptions;
+use secretary::traits::{AsyncGenerateData, GenerateData};
+use serde_json::json;
+
+use support::fixtures::{field_result, success, truncated};
+use support::{ADA_JSON, MockResponse, MockServer, Person, TARGET, ada};
+
+/// A target split into two chunks by `chunk_options`.
+const LONG_TARGET: &str = "Ada is a mathematician.\n\nShe turned 36 this year.";
+
+/// A server answering the first chunk with the name and the second with the age.
+fn chunks_server() -> MockServer {
+    MockServer::start(|request| {
+        if request.prompt().contains("Ada is") {
+            success(r#"{"name": "Ada", "age": 0}"#)
+        } else {
+            success(r#"{"name": "", "age": 36}"#)
+        }
+    })
+}
+
+/// A server answering the field requests of a Person, throttling the first age request.
+fn throttling_fields_server() -> MockServer {
+    let age_requests = AtomicUsize::new(0);
+    MockServer::start(move |request| {
+        let prompt: String = request.prompt();
+        if prompt.contains("Extract the person's name") {
+            field_result("Ada")
+        } else if age_requests.fetch_add(1, Ordering::SeqCst) == 0 {
+            MockResponse::new(429, json!({"error": {"message": "Rate limit reached"}}))
+        } else {
+            field_result("36")
+        }
+    })
+}
+
+fn retrying(llm: OpenAILLM) -> OpenAILLM {
+    llm.with_retry_policy(RetryPolicy::new(1).with_base_delay(Duration::from_millis(1)))
+}
+
+fn chunk_options(reporter: &Arc<RecordingReporter>) -> ChunkOptions {
+    ChunkOptions::default()
+        .with_chunk_size(30)
+        .with_overlap(0)
+        .with_concurrency(1)
+        .with_merge_policy(MergePolicy::FirstNonDefault)
+        .with_progress(reporter.clone())
+}
+
+fn field_options(reporter: &Arc<RecordingReporter>) -> RequestOptions {
+    RequestOptions::default()
+        .with_concurrency(1)
+        .with_progress(reporter.clone())
+}
+
+fn chunked_stages() -> Vec<ProgressKind> {
+    vec![
+        ProgressKind::ChunkStarted { index: 0, total: 2 },
+        ProgressKind::ChunkFinished {
+            index: 0,
+            completed: 1,
+            total: 2,
+        },
+        ProgressKind::ChunkStarted { index: 1, total: 2 },
+        ProgressKind::ChunkFinished {
+            index: 1,
+            completed: 2,
+            total: 2,
+        },
+        ProgressKind::MergeStarted { chunks: 2 },
+        ProgressKind::Completed,
+    ]
+}
+
+fn distributed_stages() -> Vec<ProgressKind> {
+    let started = |path: &str| ProgressKind::FieldStarted {
+        path: path.to_string(),
+        total: 2,
+    };
+    let finished = |path: &str, completed: usize| ProgressKind::FieldFinished {
+        path: path.to_string(),
+        completed,
+        total: 2,
+    };
+    vec![
+        started("name"),
+        finished("name", 1),
+        started("age"),
+        ProgressKind::RetryScheduled { attempt: 1 },
+        finished("age", 2),
+        ProgressKind::Completed,
+    ]
+}
+
+/// Asserts that the events of each call are timed from its start.
+fn assert_timed_in_order(events: &[ProgressEvent]) {
+    for pair in events.windows(2) {
+        if pair[0].kind != ProgressKind::Completed {
+            assert!(pair[0].elapsed <= pair[1].elapsed, "{:?}", pair);
+        }
+    }
+}
+
+#[test]
+fn a_chunked_and_distributed_run_reports_every_stage() {
+    let reporter = Arc::new(RecordingReporter::default());
+    let chunks = chunks_server();
+    let fields = throttling_fields_server();
+
+    let chunked: Person = chunks
+        .llm()
+        .generate_data_chunked(
+            &Person::new(),
+            LONG_TARGET,
+            vec![],
+            &chunk_options(&reporter),
+        )
+        .unwrap()
+        .data;
+    let distributed: Person = retrying(fields.llm())
+        .fields_generate_data_with_options(
+            &Person::new(),
+            TARGET,
+            vec![],
+            &field_options(&reporter),
+        )
+        .unwrap();
+
+    assert_eq!(chunked, ada());
+    assert_eq!(distributed, ada());
+    assert_eq!(
+        reporter.kinds(),
+        [chunked_stages(), distributed_stages()].concat()
+    );
+    assert_timed_in_order(&reporter.events());
+}
+
+#[tokio::test]
+async fn async_runs_report_the_same_stages() {
+    let reporter = Arc::new(RecordingReporter::default());
+    let chunks = chunks_server();
+    let fields = throttling_fields_server();
+
+    chunks
+        .llm()
+        .async_generate_data_chunked(
+            &Person::new(),
+            LONG_TARGET,
+            vec![],
+            &chunk_options(&reporter),
+        )
+        .await
+        .unwrap();
+    retrying(fields.llm())
+        .async_fields_generate_data_with_options(
+            &Person::new(),
+            TARGET,
+            vec![],
+            &field_options(&reporter),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        reporter.kinds(),
+        [chunked_stages(), distributed_stages()].concat()
+    );
+    assert_timed_in_order(&reporter.events());
+}
+
+#[test]
+fn a_target_of_one_chunk_is_reported_as_one() {
+    let reporter = Arc::new(RecordingReporter::default());
+    let server = MockServer::always(success(ADA_JSON));
+
+    server
+        .llm()
+        .generate_data_chunked(&Person::new(), TARGET, vec![], &chunk_options(&reporter))
+        .unwrap();
+
+    assert_eq!(
+        reporter.kinds(),
+        vec![
+            ProgressKind::ChunkStarted { index: 0, total: 1 },
+            ProgressKind::ChunkFinished {
+                index: 0,
+                completed: 1,
+                total: 1,
+            },
+            ProgressKind::Completed,
+        ]
+    );
+}
+
+#[test]
+fn answers_sent_to_the_next_tier_are_repair_rounds() {
+    let reporter = Arc::new(RecordingReporter::default());
+    let cheap = MockServer::always(truncated());
+    let medium = MockServer::always(truncated());
+    let large = MockServer::always(success(ADA_JSON));
+    let llm = |server: &MockServer, model: &str| {
+        OpenAILLM::new(server.address(), "test-key", model).unwrap()
+    };
+    let router = Router::new(llm(&cheap, "cheap-model"))
+        .with_tier(llm(&medium, "medium-model"))
+        .with_tier(llm(&large, "large-model"))
+        .with_policy(RoutingPolicy::new().with_parse_failure_retry(true));
+
+    let person: Person = router
+        .generate_data_with_options(
+            &Person::new(),
+            TARGET,
+            vec![],
+            &RequestOptions::default().with_progress(reporter.clone()),
+        )
+        .unwrap();
+
+    assert_eq!(person, ada());
+    assert_eq!(
+        reporter.kinds(),
+        vec![
+            ProgressKind::RepairRoundStarted { round: 1 },
+            ProgressKind::RepairRoundStarted { round: 2 },
+        ]
+    );
+    assert_timed_in_order(&reporter.events());
+}
+
+#[test]
+fn calls_without_a_reporter_report_nothing() {
+    let reporter = Arc::new(RecordingReporter::default());
+    let server = chunks_server();
+    let options: ChunkOptions = ChunkOptions {
+        progress: None,
+        ..chunk_options(&reporter)
+    };
+
+    server
+        .llm()
+        .generate_data_chunked(&Person::new(), LONG_TARGET, vec![], &options)
+        .unwrap();
+
+    assert!(reporter.events().is_empty());
+    assert_eq!(server.requests().len(), 2);
+}
+
+#[cfg(feature = "progress-channel")]
+#[tokio::test]
+async fn a_channel_reporter_streams_the_events() {
+    use secretary::progress::ChannelReporter;
+
+    let server = throttling_fields_server();
+    let (reporter, mut events) = ChannelReporter::new();
+    let options: RequestOptions = RequestOptions::default()
+        .with_concurrency(1)
+        .with_progress(Arc::new(reporter));
+
+    retrying(server.llm())
+        .async_fields_generate_data_with_options(&Person::new(), TARGET, vec![], &options)
+        .await
+        .unwrap();
+    // The channel closes once the last reporter is dropped
+    drop(options);
+
+    let mut stages: Vec<ProgressKind> = Vec::new();
+    while let Some(event) = events.recv().await {
+        stages.push(event.kind);
+    }
+    assert_eq!(stages, distributed_stages());
+}
@@ -0,0 +1,94 @@
+//! The prompt prefix is identical across targets, is marked for caching when the provider
+//! asks for it, and the cached tokens are reported.
+
+#![allow(clippy::needless_borrows_for_generic_args)]
+
+mod support;
+
+use secretary::Task;
+use secretary::llm_providers::capabilities::ProviderCapabilities;
+use secretary::llm_providers::prompt_cache::PromptCaching;
+use secretary::traits::GenerateData;
+use serde_json::{Value, json};
+
+use support::{ADA_JSON, MockResponse, MockServer, Person};
+
+/// A completion that reports 1024 of its prompt tokens as cached.
+fn cached_completion() -> MockResponse {
+    MockResponse::new(
+        200,
+        json!({
+            "choices": [{"message": {"role": "assistant", "content": ADA_JSON}}],
+            "usage": {
+                "prompt_tokens": 1200,
+                "total_tokens": 1210,
+                "prompt_tokens_details": {"cached_tokens": 1024}
+            }
+        }),
+    )
+}
+
+fn instructions() -> Vec<String> {
+    vec!["Use the full name".to_string()]
+}
+
+#[test]
+fn the_prefix_is_identical_across_targets() {
+    let server = MockServer::always(cached_completion());
+    let llm = server.llm();
+
+    llm.generate_data(&Person::new(), "Ada Lovelace", &instructions())
+        .unwrap();
+    llm.generate_data(&Person::new(), "Grace Hopper", &instructions())
+        .unwrap();
+
+    let requests = server.requests();
+    let (first, second): (&Value, &Value) = (&requests[0].body, &requests[1].body);
+    assert_eq!(
+        first["messages"][0].to_string(),
+        second["messages"][0].to_string()
+    );
+    assert_ne!(first["messages"][1], second["messages"][1]);
+    // OpenAI caches the prefix without an annotation
+    assert!(first["messages"][0]["content"].is_string());
+    assert!(
+        first["messages"][1]["content"]
+            .as_str()
+            .unwrap()
+            .contains("Ada Lovelace")
+    );
+}
+
+#[test]
+fn cache_control_marks_only_the_prefix() {
+    let server = MockServer::always(cached_completion());
+    let llm = server.llm().with_capabilities(
+        ProviderCapabilities::default().with_prompt_caching(PromptCaching::CacheControl),
+    );
+    let task = Person::new();
+
+    llm.generate_data(&task, "Ada Lovelace", &instructions())
+        .unwrap();
+    let result = llm
+        .generate_data_adaptive(&task, "Grace Hopper", &instructions())
+        .unwrap();
+    assert_eq!(result.metadata.cached_prompt_tokens, Some(1024));
+
+    let requests = server.requests();
+    let (first, second): (&Value, &Value) = (&requests[0].body, &requests[1].body);
+    assert_eq!(
+        first["messages"][0].to_string(),
+        second["messages"][0].to_string()
+    );
+    assert_eq!(
+        first["messages"][0]["content"][0]["cache_control"],
+        json!({"type": "ephemeral"})
+    );
+    assert_eq!(
+        first["messages"][0]["content"][0]["text"].as_str().unwrap(),
+        task.make_prompt_messages("Ada Lovelace", &instructions())[0]
+            .content
+            .as_str()
+    );
+    assert!(first["messages"][1]["content"].is_string());
+}
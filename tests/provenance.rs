@@ -0,0 +1,99 @@
+//! The quotes of a provenance answer are located in the target exactly or ignoring case and
+//! whitespace, and quotes that cannot be found are flagged rather than dropped.
+
+#![allow(clippy::needless_borrows_for_generic_args)]
+
+mod support;
+
+use secretary::Task;
+use secretary::provenance::{ProvenanceResult, QuoteMatch};
+use secretary::traits::GenerateData;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use support::MockServer;
+use support::fixtures::success;
+
+#[derive(Task, Serialize, Deserialize, Debug)]
+struct Address {
+    #[task(instruction = "Extract the city")]
+    pub city: String,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug)]
+struct Order {
+    #[task(instruction = "Extract the customer name")]
+    pub customer: String,
+    #[task(instruction = "Extract the order total")]
+    pub total: f64,
+    #[task(instruction = "Extract the coupon code")]
+    pub coupon: Option<String>,
+    #[task(instruction = "Extract the delivery date")]
+    pub delivery: Option<String>,
+    pub address: Address,
+}
+
+const TARGET: &str = "Order from Ada Lovelace, shipping to London.\nTotal:   120.50 EUR";
+
+#[test]
+fn quotes_are_located_in_the_target() {
+    let server = MockServer::always(success(
+        &json!({
+            "customer": {"value": "Ada Lovelace", "evidence": "Ada Lovelace"},
+            "total": {"value": 120.5, "evidence": "total: 120.50"},
+            "coupon": {"value": "SPRING24", "evidence": "coupon SPRING24"},
+            "delivery": {"value": null, "evidence": ""},
+            "address": {"city": {"value": "London", "evidence": "shipping to London"}}
+        })
+        .to_string(),
+    ));
+
+    let result: ProvenanceResult<Order> = server
+        .llm()
+        .generate_data_with_provenance(&Order::new(), TARGET, &vec![])
+        .unwrap();
+
+    assert_eq!(result.data.customer, "Ada Lovelace");
+    assert_eq!(result.data.total, 120.5);
+    assert_eq!(result.data.address.city, "London");
+    let paths: Vec<&str> = result
+        .provenance
+        .iter()
+        .map(|provenance| provenance.field_path.as_str())
+        .collect();
+    assert_eq!(
+        paths,
+        vec!["customer", "total", "coupon", "delivery", "address.city"]
+    );
+
+    // An exact quote
+    let customer = &result.provenance[0];
+    assert_eq!(customer.match_kind, QuoteMatch::Exact);
+    assert_eq!(
+        &TARGET[customer.char_range.clone().unwrap()],
+        "Ada Lovelace"
+    );
+
+    // A quote that only matches ignoring case and whitespace
+    let total = &result.provenance[1];
+    assert_eq!(total.match_kind, QuoteMatch::Normalized);
+    assert_eq!(
+        &TARGET[total.char_range.clone().unwrap()],
+        "Total:   120.50"
+    );
+
+    // A fabricated quote is flagged, not dropped
+    let coupon = &result.provenance[2];
+    assert_eq!(coupon.match_kind, QuoteMatch::NotFound);
+    assert_eq!(coupon.quote, "coupon SPRING24");
+    assert_eq!(coupon.char_range, None);
+
+    assert_eq!(result.provenance[3].match_kind, QuoteMatch::Missing);
+    assert_eq!(result.provenance[4].match_kind, QuoteMatch::Exact);
+
+    let unlocated: Vec<&str> = result
+        .unlocated()
+        .map(|provenance| provenance.field_path.as_str())
+        .collect();
+    assert_eq!(unlocated, vec!["coupon"]);
+}
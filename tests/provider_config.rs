@@ -0,0 +1,282 @@
+//! Providers are built from configuration files, with keys read from the environment.
+
+mod support;
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use secretary::llm_providers::config::{
+    AzureConfig, ConfiguredLLM, OpenAIConfig, ProviderConfig, ProviderConfigError, ProviderOptions,
+    RetryConfig,
+};
+use secretary::llm_providers::json_mode::JsonModeStrategy;
+use secretary::message::SystemRoleStrategy;
+use secretary::traits::{AsyncGenerateData, GenerateData, IsLLM};
+use secretary::trimming::UnknownKeys;
+use serde_json::json;
+
+use support::fixtures::{responses_success, success};
+use support::{ADA_JSON, MockServer, Person, TARGET, ada};
+
+const FIXTURE: &str = include_str!("fixtures/providers.toml");
+
+fn providers() -> BTreeMap<String, ProviderConfig> {
+    toml::from_str(FIXTURE).unwrap()
+}
+
+/// Looks keys up in a fixed environment.
+fn environment(name: &str) -> Option<String> {
+    match name {
+        "AZURE_OPENAI_KEY" => Some("azure-key".to_string()),
+        "OPENAI_KEY" => Some("openai-key".to_string()),
+        _ => None,
+    }
+}
+
+fn azure() -> ProviderConfig {
+    ProviderConfig::Azure(
+        AzureConfig::new(
+            "https://contoso.openai.azure.com",
+            "gpt-4o",
+            "2024-06-01",
+            "AZURE_OPENAI_KEY",
+        )
+        .with_options(ProviderOptions {
+            retry: Some(RetryConfig {
+                max_retries: 2,
+                base_delay_ms: Some(100),
+                max_delay_ms: None,
+                retry_refusals: false,
+            }),
+            extra_body: Some(json!({"temperature": 0.0, "seed": 7})),
+            ..ProviderOptions::default()
+        }),
+    )
+}
+
+#[test]
+fn configs_survive_a_round_trip() {
+    let configs: Vec<ProviderConfig> = vec![
+        azure(),
+        ProviderConfig::OpenAI(OpenAIConfig::new("gpt-4o-mini", "OPENAI_KEY")),
+        ProviderConfig::Responses(
+            OpenAIConfig::new("gpt-4.1-mini", "OPENAI_KEY").with_api_base("http://localhost:8080"),
+        ),
+    ];
+
+    for config in configs.into_iter().chain(providers().into_values()) {
+        let json: String = serde_json::to_string(&config).unwrap();
+        assert_eq!(
+            serde_json::from_str::<ProviderConfig>(&json).unwrap(),
+            config
+        );
+        let toml: String = toml::to_string(&config).unwrap();
+        assert_eq!(toml::from_str::<ProviderConfig>(&toml).unwrap(), config);
+    }
+
+    // Unset options are left out and the key is only referenced by name
+    let json = serde_json::to_value(ProviderConfig::OpenAI(OpenAIConfig::new(
+        "gpt-4o-mini",
+        "OPENAI_KEY",
+    )))
+    .unwrap();
+    assert_eq!(
+        json,
+        json!({
+            "provider": "openai",
+            "api_base": "https://api.openai.com/v1",
+            "model": "gpt-4o-mini",
+            "api_key_env": "OPENAI_KEY"
+        })
+    );
+}
+
+#[test]
+fn providers_are_built_from_a_toml_file() {
+    let providers: BTreeMap<String, ProviderConfig> = providers();
+
+    let primary: ConfiguredLLM = providers["primary"].build_with(environment).unwrap();
+    assert!(matches!(primary, ConfiguredLLM::Azure(_)));
+    assert_eq!(
+        primary.get_chat_completion_request_url(),
+        "https://contoso.openai.azure.com/openai/deployments/gpt-4o/chat/completions?api-version=2024-06-01"
+    );
+    assert_eq!(primary.get_model_ref(), "gpt-4o");
+    let retry = primary.get_retry_policy();
+    assert_eq!(retry.max_retries, 3);
+    assert_eq!(retry.base_delay, Duration::from_millis(250));
+    assert_eq!(retry.max_delay, Duration::from_secs(10));
+    assert_eq!(
+        primary.get_extra_body(),
+        Some(&json!({"temperature": 0.2, "top_p": 0.9}))
+    );
+    assert_eq!(primary.get_json_mode().strategy(), JsonModeStrategy::Auto);
+    assert_eq!(primary.get_unknown_keys(), UnknownKeys::Trim);
+    assert!(primary.get_traced_errors());
+    assert_eq!(primary.get_authorization_credentials(), "azure-key");
+
+    let fallback: ConfiguredLLM = providers["fallback"].build_with(environment).unwrap();
+    assert!(matches!(fallback, ConfiguredLLM::OpenAI(_)));
+    assert_eq!(
+        fallback.get_chat_completion_request_url(),
+        "https://api.openai.com/v1/chat/completions"
+    );
+    assert_eq!(
+        fallback.get_system_role_strategy(),
+        SystemRoleStrategy::Developer
+    );
+    assert_eq!(
+        fallback.get_pricing().unwrap().prompt_per_million_tokens,
+        0.15
+    );
+    assert_eq!(
+        fallback.get_authorization_credentials(),
+        "Bearer openai-key"
+    );
+    assert!(!fallback.get_traced_errors());
+
+    let responses: ConfiguredLLM = providers["responses"].build_with(environment).unwrap();
+    assert_eq!(
+        responses.get_chat_completion_request_url(),
+        "https://api.openai.com/v1/responses"
+    );
+}
+
+#[test]
+fn invalid_configs_are_reported_by_field() {
+    let providers: BTreeMap<String, ProviderConfig> = providers();
+
+    assert_eq!(
+        providers["local"].build_with(environment).unwrap_err(),
+        ProviderConfigError::MissingEnvVar {
+            variable: "LOCAL_KEY".to_string()
+        }
+    );
+    // An empty variable counts as unset
+    assert!(matches!(
+        providers["fallback"].build_with(|_| Some(String::new())),
+        Err(ProviderConfigError::MissingEnvVar { .. })
+    ));
+
+    let config = ProviderConfig::OpenAI(
+        OpenAIConfig::new("gpt-4o-mini", "OPENAI_KEY").with_api_base("api.openai.com/v1"),
+    );
+    match config.build_with(environment).unwrap_err() {
+        ProviderConfigError::InvalidUrl { field, value, .. } => {
+            assert_eq!(field, "api_base");
+            assert_eq!(value, "api.openai.com/v1");
+        }
+        other => panic!("unexpected error: {}", other),
+    }
+
+    let config = ProviderConfig::Azure(AzureConfig::new(
+        "ftp://contoso.openai.azure.com",
+        "gpt-4o",
+        "2024-06-01",
+        "AZURE_OPENAI_KEY",
+    ));
+    let error: ProviderConfigError = config.build_with(environment).unwrap_err();
+    assert!(matches!(
+        error,
+        ProviderConfigError::InvalidUrl {
+            field: "endpoint",
+            ..
+        }
+    ));
+    assert!(error.to_string().contains("unsupported scheme ftp"));
+
+    let config = ProviderConfig::Azure(AzureConfig::new(
+        "https://contoso.openai.azure.com",
+        " ",
+        "2024-06-01",
+        "AZURE_OPENAI_KEY",
+    ));
+    assert_eq!(
+        config.build_with(environment).unwrap_err(),
+        ProviderConfigError::EmptyField {
+            field: "deployment"
+        }
+    );
+
+    // Unknown providers are rejected when the file is read
+    assert!(
+        toml::from_str::<ProviderConfig>(
+            "provider = \"gemini\"\nmodel = \"x\"\napi_key_env = \"K\""
+        )
+        .is_err()
+    );
+}
+
+#[test]
+fn equality_tells_whether_a_reload_changed_anything() {
+    let running: ProviderConfig = azure();
+
+    let same: ProviderConfig =
+        serde_json::from_str(&serde_json::to_string(&running).unwrap()).unwrap();
+    assert_eq!(same, running);
+
+    let mut sampling: ProviderConfig = running.clone();
+    if let ProviderConfig::Azure(config) = &mut sampling {
+        config.options.extra_body = Some(json!({"temperature": 0.3, "seed": 7}));
+    }
+    assert_ne!(sampling, running);
+
+    let mut deployment: ProviderConfig = running.clone();
+    if let ProviderConfig::Azure(config) = &mut deployment {
+        config.deployment = "gpt-4o-mini".to_string();
+    }
+    assert_ne!(deployment, running);
+
+    let mut retry: ProviderConfig = running.clone();
+    if let ProviderConfig::Azure(config) = &mut retry {
+        config.options.retry.as_mut().unwrap().max_delay_ms = Some(1_000);
+    }
+    assert_ne!(retry, running);
+
+    // A new key in the environment is not a configuration change, the variable name is
+    let mut key: ProviderConfig = running.clone();
+    if let ProviderConfig::Azure(config) = &mut key {
+        config.api_key_env = "AZURE_OPENAI_KEY_2".to_string();
+    }
+    assert_ne!(key, running);
+    assert_eq!(key.options(), running.options());
+}
+
+#[test]
+fn a_configured_provider_extracts_data() {
+    let server = MockServer::always(success(ADA_JSON));
+    let config = ProviderConfig::OpenAI(
+        OpenAIConfig::new("test-model", "OPENAI_KEY")
+            .with_api_base(server.address())
+            .with_options(ProviderOptions {
+                extra_body: Some(json!({"temperature": 0.1})),
+                ..ProviderOptions::default()
+            }),
+    );
+    let llm: ConfiguredLLM = config.build_with(environment).unwrap();
+
+    let person: Person = llm.generate_data(&Person::new(), TARGET, vec![]).unwrap();
+
+    assert_eq!(person, ada());
+    let request = &server.requests()[0];
+    assert_eq!(request.headers["authorization"], "Bearer openai-key");
+    assert_eq!(request.body["model"], "test-model");
+    assert_eq!(request.body["temperature"], 0.1);
+}
+
+#[tokio::test]
+async fn a_configured_provider_extracts_data_asynchronously() {
+    let server = MockServer::always(responses_success("resp_1", ADA_JSON));
+    let config = ProviderConfig::Responses(
+        OpenAIConfig::new("test-model", "OPENAI_KEY").with_api_base(server.address()),
+    );
+    let llm: ConfiguredLLM = config.build_with(environment).unwrap();
+
+    let person: Person = llm
+        .async_generate_data(&Person::new(), TARGET, vec![])
+        .await
+        .unwrap();
+
+    assert_eq!(person, ada());
+    assert_eq!(server.requests()[0].path, "/responses");
+}
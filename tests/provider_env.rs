@@ -0,0 +1,178 @@
+//! Providers built from environment variables report every missing or malformed variable.
+
+use std::sync::{Mutex, MutexGuard};
+
+use secretary::SecretaryError;
+use secretary::llm_providers::azure::AzureOpenAILLM;
+use secretary::llm_providers::config::ConfiguredLLM;
+use secretary::llm_providers::env::{ConfigurationIssue, provider_from_env_with_prefix};
+use secretary::llm_providers::openai::OpenAILLM;
+use secretary::traits::IsLLM;
+
+/// Serializes the tests, which change the process environment.
+static ENVIRONMENT: Mutex<()> = Mutex::new(());
+
+/// Sets the variables, unsetting those without a value, for as long as the guard lives.
+fn environment(variables: &[(&str, Option<&str>)]) -> MutexGuard<'static, ()> {
+    let guard: MutexGuard<'static, ()> = ENVIRONMENT
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    for (variable, value) in variables {
+        // Safe while every test that touches the environment holds the lock
+        unsafe {
+            match value {
+                Some(value) => std::env::set_var(variable, value),
+                None => std::env::remove_var(variable),
+            }
+        }
+    }
+
+    guard
+}
+
+fn issues(error: SecretaryError) -> Vec<ConfigurationIssue> {
+    match error {
+        SecretaryError::MissingConfiguration { issues } => issues,
+        error => panic!("expected MissingConfiguration, got {:?}", error),
+    }
+}
+
+fn variables(issues: &[ConfigurationIssue]) -> Vec<&str> {
+    issues.iter().map(|issue| issue.variable.as_str()).collect()
+}
+
+#[test]
+fn complete_openai_configuration_builds_the_provider() {
+    let _guard = environment(&[
+        ("COMPLETE_OPENAI_API_BASE", Some("http://localhost:8080/v1")),
+        ("COMPLETE_OPENAI_API_KEY", Some("key")),
+        ("COMPLETE_OPENAI_MODEL", Some("local-model")),
+    ]);
+
+    let llm: OpenAILLM = OpenAILLM::from_env_with_prefix("COMPLETE").unwrap();
+
+    assert_eq!(llm.get_model_ref(), "local-model");
+    assert_eq!(
+        llm.get_chat_completion_request_url(),
+        "http://localhost:8080/v1/chat/completions"
+    );
+}
+
+#[test]
+fn api_base_defaults_to_openai() {
+    let _guard = environment(&[
+        ("DEFAULTED_OPENAI_API_BASE", None),
+        ("DEFAULTED_OPENAI_API_KEY", Some("key")),
+        ("DEFAULTED_OPENAI_MODEL", Some("gpt-4o")),
+    ]);
+
+    let llm: OpenAILLM = OpenAILLM::from_env_with_prefix("DEFAULTED").unwrap();
+
+    assert!(
+        llm.get_chat_completion_request_url()
+            .starts_with("https://api.openai.com/")
+    );
+}
+
+#[test]
+fn every_missing_variable_is_reported_at_once() {
+    let _guard = environment(&[
+        (
+            "PARTIAL_AZURE_OPENAI_ENDPOINT",
+            Some("https://contoso.openai.azure.com"),
+        ),
+        ("PARTIAL_AZURE_OPENAI_API_KEY", Some("  ")),
+        ("PARTIAL_AZURE_OPENAI_DEPLOYMENT_ID", None),
+        ("PARTIAL_AZURE_OPENAI_API_VERSION", Some("2024-06-01")),
+    ]);
+
+    let reported: Vec<ConfigurationIssue> =
+        issues(AzureOpenAILLM::from_env_with_prefix("PARTIAL").unwrap_err());
+
+    assert_eq!(
+        variables(&reported),
+        [
+            "PARTIAL_AZURE_OPENAI_API_KEY",
+            "PARTIAL_AZURE_OPENAI_DEPLOYMENT_ID"
+        ]
+    );
+    assert!(reported[0].hint.contains("API key"));
+}
+
+#[test]
+fn malformed_urls_are_reported_with_the_missing_variables() {
+    let _guard = environment(&[
+        ("MALFORMED_OPENAI_API_BASE", Some("localhost:8080/v1")),
+        ("MALFORMED_OPENAI_API_KEY", Some("key")),
+        ("MALFORMED_OPENAI_MODEL", None),
+    ]);
+
+    let error: SecretaryError = OpenAILLM::from_env_with_prefix("MALFORMED").unwrap_err();
+    assert!(error.to_string().contains("MALFORMED_OPENAI_MODEL"));
+
+    let reported: Vec<ConfigurationIssue> = issues(error);
+    assert_eq!(
+        variables(&reported),
+        ["MALFORMED_OPENAI_API_BASE", "MALFORMED_OPENAI_MODEL"]
+    );
+    assert!(reported[0].hint.contains("not valid"));
+    assert!(!reported[0].hint.contains("key"));
+}
+
+#[test]
+fn provider_is_picked_by_the_variables_that_are_set() {
+    let _guard = environment(&[
+        ("PICKED_OPENAI_API_BASE", None),
+        ("PICKED_OPENAI_API_KEY", None),
+        ("PICKED_OPENAI_MODEL", None),
+        (
+            "PICKED_AZURE_OPENAI_ENDPOINT",
+            Some("https://contoso.openai.azure.com"),
+        ),
+        ("PICKED_AZURE_OPENAI_API_KEY", Some("key")),
+        ("PICKED_AZURE_OPENAI_DEPLOYMENT_ID", Some("gpt-4o")),
+        ("PICKED_AZURE_OPENAI_API_VERSION", Some("2024-06-01")),
+    ]);
+
+    let llm: ConfiguredLLM = provider_from_env_with_prefix("PICKED").unwrap();
+
+    assert!(matches!(llm, ConfiguredLLM::Azure(_)));
+    assert_eq!(llm.get_model_ref(), "gpt-4o");
+}
+
+#[test]
+fn incomplete_configurations_report_the_closest_provider() {
+    let _guard = environment(&[
+        ("CLOSEST_OPENAI_API_BASE", None),
+        ("CLOSEST_OPENAI_API_KEY", None),
+        ("CLOSEST_OPENAI_MODEL", None),
+        (
+            "CLOSEST_AZURE_OPENAI_ENDPOINT",
+            Some("https://contoso.openai.azure.com"),
+        ),
+        ("CLOSEST_AZURE_OPENAI_API_KEY", None),
+        ("CLOSEST_AZURE_OPENAI_DEPLOYMENT_ID", Some("gpt-4o")),
+        ("CLOSEST_AZURE_OPENAI_API_VERSION", None),
+        ("OPENAI_API_KEY", None),
+        ("OPENAI_MODEL", None),
+        ("OPENAI_API_BASE", None),
+        ("AZURE_OPENAI_ENDPOINT", None),
+        ("AZURE_OPENAI_API_KEY", None),
+        ("AZURE_OPENAI_DEPLOYMENT_ID", None),
+        ("AZURE_OPENAI_API_VERSION", None),
+    ]);
+
+    let reported: Vec<ConfigurationIssue> =
+        issues(provider_from_env_with_prefix("CLOSEST").unwrap_err());
+    assert_eq!(
+        variables(&reported),
+        [
+            "CLOSEST_AZURE_OPENAI_API_KEY",
+            "CLOSEST_AZURE_OPENAI_API_VERSION"
+        ]
+    );
+
+    // Without a prefix, the variables have no prefix either; with none set, OpenAI's are listed
+    let reported: Vec<ConfigurationIssue> = issues(provider_from_env_with_prefix("").unwrap_err());
+    assert_eq!(variables(&reported), ["OPENAI_API_KEY", "OPENAI_MODEL"]);
+}
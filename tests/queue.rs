@@ -0,0 +1,223 @@
+//! Requests admitted by priority through a shared `RequestQueue`.
+
+#![allow(clippy::needless_borrows_for_generic_args)]
+
+mod support;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use secretary::SecretaryError;
+use secretary::deadline::Deadline;
+use secretary::llm_providers::openai::OpenAILLM;
+use secretary::llm_providers::queue::{Priority, RequestQueue};
+use secretary::metrics::{CountingSink, MetricEvent};
+use secretary::request::RequestOptions;
+use secretary::traits::{AsyncGenerateData, GenerateData};
+
+use support::fixtures::{field_result, success};
+use support::{ADA_JSON, MockServer, Person, TARGET, ada, fields_server, secretary_error};
+
+const BULK_REQUESTS: usize = 12;
+
+/// A server that takes 100ms to answer every request.
+fn slow_server() -> MockServer {
+    MockServer::start(|_| {
+        std::thread::sleep(Duration::from_millis(100));
+        success(ADA_JSON)
+    })
+}
+
+/// Waits until `depth` requests of the priority are waiting.
+fn wait_for_depth(queue: &RequestQueue, priority: Priority, depth: usize) {
+    while queue.depth(priority) < depth {
+        std::thread::sleep(Duration::from_millis(5));
+    }
+}
+
+#[test]
+fn an_interactive_request_overtakes_the_bulk_backlog() {
+    let server = slow_server();
+    let queue = RequestQueue::new(2);
+    let llm = server.llm().with_request_queue(queue.clone());
+    let completed = Arc::new(AtomicUsize::new(0));
+
+    let bulk: Vec<std::thread::JoinHandle<()>> = (0..BULK_REQUESTS)
+        .map(|_| {
+            let llm: OpenAILLM = llm.clone();
+            let completed = completed.clone();
+            std::thread::spawn(move || {
+                let options = RequestOptions::default().with_priority(Priority::Bulk);
+                llm.generate_data_with_options(&Person::new(), TARGET, &vec![], &options)
+                    .unwrap();
+                completed.fetch_add(1, Ordering::SeqCst);
+            })
+        })
+        .collect();
+    wait_for_depth(&queue, Priority::Bulk, BULK_REQUESTS - 2);
+
+    let options = RequestOptions::default().with_priority(Priority::Interactive);
+    let person: Person = llm
+        .generate_data_with_options(&Person::new(), TARGET, &vec![], &options)
+        .unwrap();
+
+    assert_eq!(person, ada());
+    assert!(queue.depth(Priority::Bulk) > 0);
+    assert!(completed.load(Ordering::SeqCst) < BULK_REQUESTS - 2);
+
+    for handle in bulk {
+        handle.join().unwrap();
+    }
+    assert_eq!(server.requests().len(), BULK_REQUESTS + 1);
+    assert_eq!(queue.running(), 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn an_async_interactive_request_overtakes_the_bulk_backlog() {
+    let server = slow_server();
+    let queue = RequestQueue::new(2);
+    let llm = server.llm().with_request_queue(queue.clone());
+    let completed = Arc::new(AtomicUsize::new(0));
+
+    let bulk: Vec<tokio::task::JoinHandle<()>> = (0..BULK_REQUESTS)
+        .map(|_| {
+            let llm: OpenAILLM = llm.clone();
+            let completed = completed.clone();
+            tokio::spawn(async move {
+                let options = RequestOptions::default().with_priority(Priority::Bulk);
+                llm.async_generate_data_with_options(&Person::new(), TARGET, &vec![], &options)
+                    .await
+                    .unwrap();
+                completed.fetch_add(1, Ordering::SeqCst);
+            })
+        })
+        .collect();
+    while queue.depth(Priority::Bulk) < BULK_REQUESTS - 2 {
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+
+    let options = RequestOptions::default().with_priority(Priority::Interactive);
+    let person: Person = llm
+        .async_generate_data_with_options(&Person::new(), TARGET, &vec![], &options)
+        .await
+        .unwrap();
+
+    assert_eq!(person, ada());
+    assert!(queue.depth(Priority::Bulk) > 0);
+    assert!(completed.load(Ordering::SeqCst) < BULK_REQUESTS - 2);
+
+    for handle in bulk {
+        handle.await.unwrap();
+    }
+    assert_eq!(server.requests().len(), BULK_REQUESTS + 1);
+    assert_eq!(queue.running(), 0);
+}
+
+#[test]
+fn the_queue_caps_requests_in_flight_across_providers() {
+    let in_flight = Arc::new(AtomicUsize::new(0));
+    let peak = Arc::new(AtomicUsize::new(0));
+    let (counter, maximum) = (in_flight.clone(), peak.clone());
+    let server = MockServer::start(move |_| {
+        let current: usize = counter.fetch_add(1, Ordering::SeqCst) + 1;
+        maximum.fetch_max(current, Ordering::SeqCst);
+        std::thread::sleep(Duration::from_millis(30));
+        counter.fetch_sub(1, Ordering::SeqCst);
+        success(ADA_JSON)
+    });
+    let queue = RequestQueue::new(2);
+
+    let handles: Vec<std::thread::JoinHandle<()>> = (0..8)
+        .map(|_| {
+            // Each thread has its own provider sharing the queue
+            let llm: OpenAILLM = server.llm().with_request_queue(queue.clone());
+            std::thread::spawn(move || {
+                llm.generate_data(&Person::new(), TARGET, &vec![]).unwrap();
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    assert_eq!(peak.load(Ordering::SeqCst), 2);
+    assert_eq!(server.requests().len(), 8);
+}
+
+#[test]
+fn field_requests_inherit_the_priority_and_report_the_queue() {
+    let server = fields_server(field_result("36"));
+    let sink = Arc::new(CountingSink::default());
+    let llm = server
+        .llm()
+        .with_request_queue(RequestQueue::new(1))
+        .with_metrics_sink(sink.clone());
+    let options = RequestOptions::default().with_priority(Priority::Bulk);
+
+    let person: Person = llm
+        .fields_generate_data_with_options(&Person::new(), TARGET, &vec![], &options)
+        .unwrap();
+
+    assert_eq!(person, ada());
+    assert_eq!(sink.count("request_queued"), 2);
+    assert_eq!(sink.count("request_dequeued"), 2);
+    assert!(sink.events().iter().all(|event| match event {
+        MetricEvent::RequestQueued { priority, .. }
+        | MetricEvent::RequestDequeued { priority, .. } => *priority == Priority::Bulk,
+        _ => true,
+    }));
+}
+
+#[test]
+fn the_deadline_bounds_the_wait_in_the_queue() {
+    let server = MockServer::always(success(ADA_JSON));
+    let queue = RequestQueue::new(1);
+    let sink = Arc::new(CountingSink::default());
+    let llm = server
+        .llm()
+        .with_request_queue(queue.clone())
+        .with_metrics_sink(sink.clone());
+    let _held = queue.acquire(Priority::Interactive);
+
+    let options =
+        RequestOptions::default().with_deadline(Deadline::after(Duration::from_millis(50)));
+    let error = llm
+        .generate_data_with_options(&Person::new(), TARGET, &vec![], &options)
+        .unwrap_err();
+
+    assert!(matches!(
+        secretary_error(&error),
+        SecretaryError::DeadlineExceeded { .. }
+    ));
+    assert!(server.requests().is_empty());
+    assert_eq!(queue.depth(Priority::Normal), 0);
+    assert_eq!(sink.count("request_dequeued"), 1);
+    assert_eq!(sink.count("request_started"), 0);
+}
+
+#[tokio::test]
+async fn an_async_wait_that_passes_the_deadline_leaves_the_queue() {
+    let server = MockServer::always(success(ADA_JSON));
+    let queue = RequestQueue::new(1);
+    let llm = server.llm().with_request_queue(queue.clone());
+    let held = queue.acquire(Priority::Interactive);
+
+    let options =
+        RequestOptions::default().with_deadline(Deadline::after(Duration::from_millis(50)));
+    let error = llm
+        .async_generate_data_with_options(&Person::new(), TARGET, &vec![], &options)
+        .await
+        .unwrap_err();
+
+    // The async path reports send failures as `BuildRequestError` with the message
+    assert!(error.to_string().contains("deadline passed"));
+    assert_eq!(queue.depth(Priority::Normal), 0);
+
+    drop(held);
+    let person: Person = llm
+        .async_generate_data(&Person::new(), TARGET, &vec![])
+        .await
+        .unwrap();
+    assert_eq!(person, ada());
+}
@@ -0,0 +1,444 @@
+//! Requests split between tenants by a shared `QuotaPartitioner`: the scheduling on a
+//! simulated clock, and tenants interleaving calls on one provider.
+
+mod support;
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use secretary::SecretaryError;
+use secretary::credentials::Credentials;
+use secretary::deadline::Deadline;
+use secretary::llm_providers::openai::OpenAILLM;
+use secretary::llm_providers::quota::{
+    Admission, QuotaMode, QuotaPartitioner, TenantQuota, TenantUsage,
+};
+use secretary::metrics::{CountingSink, MetricEvent};
+use secretary::request::RequestOptions;
+use secretary::traits::{AsyncGenerateData, GenerateData};
+
+use support::fixtures::success;
+use support::{ADA_JSON, MockServer, Person, TARGET, secretary_error};
+
+const WINDOW: Duration = Duration::from_secs(10);
+
+/// A partitioner of 4 requests per window, 3 of them for `search` and 1 for `backfill`.
+fn partitioner(mode: QuotaMode) -> QuotaPartitioner {
+    QuotaPartitioner::new(4, WINDOW)
+        .with_mode(mode)
+        .with_tenant("search", TenantQuota::weighted(3.0))
+        .with_tenant("backfill", TenantQuota::weighted(1.0))
+}
+
+/// Returns the instant `millis` after `start`.
+fn at(start: Instant, millis: u64) -> Instant {
+    start + Duration::from_millis(millis)
+}
+
+/// Admits requests of the tenant at `now` until one is told to wait, and returns how many
+/// were admitted.
+fn fill(partitioner: &QuotaPartitioner, tenant: &str, now: Instant) -> usize {
+    let mut admitted: usize = 0;
+    while partitioner.admit_at(tenant, now) == Admission::Admitted {
+        admitted += 1;
+    }
+    admitted
+}
+
+fn usage(partitioner: &QuotaPartitioner, tenant: &str) -> TenantUsage {
+    partitioner
+        .usage()
+        .into_iter()
+        .find(|usage| usage.tenant == tenant)
+        .unwrap_or_else(|| panic!("no usage for {}", tenant))
+}
+
+#[test]
+fn work_conserving_tenants_use_idle_capacity() {
+    let partitioner = partitioner(QuotaMode::WorkConserving);
+    let start = Instant::now();
+
+    assert_eq!(fill(&partitioner, "backfill", start), 4);
+    assert_eq!(
+        partitioner.admit_at("search", at(start, 1)),
+        Admission::Wait(WINDOW - Duration::from_millis(1))
+    );
+}
+
+#[test]
+fn isolated_tenants_never_exceed_their_share() {
+    let partitioner = partitioner(QuotaMode::Isolated);
+    let start = Instant::now();
+
+    assert_eq!(fill(&partitioner, "backfill", start), 1);
+    assert_eq!(fill(&partitioner, "search", start), 3);
+
+    // Unconfigured tenants count in the split once they sent a request, even when idle
+    let partitioner = QuotaPartitioner::new(4, WINDOW)
+        .with_mode(QuotaMode::Isolated)
+        .with_tenant("search", TenantQuota::weighted(3.0))
+        .with_default_quota(TenantQuota::weighted(1.0));
+    assert_eq!(fill(&partitioner, "other", start), 1);
+    assert_eq!(fill(&partitioner, "search", at(start, 10_000)), 3);
+}
+
+#[test]
+fn a_waiting_tenant_reclaims_its_share() {
+    let partitioner = partitioner(QuotaMode::WorkConserving);
+    let start = Instant::now();
+    for second in 0..4 {
+        assert_eq!(
+            partitioner.admit_at("search", at(start, second * 1000)),
+            Admission::Admitted
+        );
+    }
+
+    // The backfill waits for the oldest search request to leave the window
+    assert_eq!(
+        partitioner.admit_at("backfill", at(start, 4000)),
+        Admission::Wait(Duration::from_secs(6))
+    );
+
+    // Once it has, the search tenant is over its share while the backfill is owed its own
+    let freed = at(start, 10_000);
+    assert_eq!(
+        partitioner.admit_at("search", freed),
+        Admission::Wait(Duration::from_secs(1))
+    );
+    assert_eq!(partitioner.admit_at("backfill", freed), Admission::Admitted);
+    assert_eq!(
+        partitioner.admit_at("backfill", at(start, 11_000)),
+        Admission::Wait(Duration::from_secs(1)),
+        "the next freed slot is owed to the search tenant"
+    );
+}
+
+#[test]
+fn withdrawn_tenants_stop_holding_capacity() {
+    let partitioner = partitioner(QuotaMode::WorkConserving);
+    let start = Instant::now();
+    for second in 0..4 {
+        partitioner.admit_at("search", at(start, second * 1000));
+    }
+    assert!(matches!(
+        partitioner.admit_at("backfill", at(start, 4000)),
+        Admission::Wait(_)
+    ));
+
+    // A slot frees while the backfill waits: it is kept for the backfill...
+    let freed = at(start, 10_000);
+    assert_eq!(fill(&partitioner, "search", freed), 0);
+
+    // ...until the backfill gives up
+    partitioner.withdraw("backfill");
+    assert_eq!(fill(&partitioner, "search", freed), 1);
+}
+
+#[tokio::test]
+async fn dropped_acquires_stop_waiting() {
+    let partitioner = partitioner(QuotaMode::WorkConserving);
+    assert_eq!(fill(&partitioner, "search", Instant::now()), 4);
+
+    let mut acquire = Box::pin(partitioner.async_acquire_until("backfill", None));
+    assert!(futures::poll!(acquire.as_mut()).is_pending());
+    assert!(partitioner.is_waiting("backfill"));
+
+    // Dropped mid-wait, as by a deadline or a failed sibling request
+    drop(acquire);
+    assert!(!partitioner.is_waiting("backfill"));
+}
+
+#[test]
+fn stale_waits_expire_with_the_window() {
+    let partitioner = partitioner(QuotaMode::WorkConserving);
+    let start = Instant::now();
+    assert_eq!(fill(&partitioner, "search", start), 4);
+    assert!(matches!(
+        partitioner.admit_at("backfill", start),
+        Admission::Wait(_)
+    ));
+
+    // A waiter that never came back no longer counts a window later
+    assert_eq!(fill(&partitioner, "search", at(start, 10_000)), 4);
+}
+
+#[test]
+fn hard_caps_reject_instead_of_waiting() {
+    let partitioner = QuotaPartitioner::new(10, WINDOW)
+        .with_tenant("trial", TenantQuota::weighted(1.0).with_max_requests(2));
+    let start = Instant::now();
+
+    assert_eq!(partitioner.admit_at("trial", start), Admission::Admitted);
+    assert_eq!(
+        partitioner.admit_at("trial", at(start, 5000)),
+        Admission::Admitted
+    );
+    assert_eq!(
+        partitioner.admit_at("trial", at(start, 6000)),
+        Admission::Rejected { max_requests: 2 }
+    );
+
+    // The cap applies per window
+    assert_eq!(
+        partitioner.admit_at("trial", at(start, 10_000)),
+        Admission::Admitted
+    );
+    assert_eq!(
+        partitioner.admit_at("trial", at(start, 10_001)),
+        Admission::Rejected { max_requests: 2 }
+    );
+}
+
+#[test]
+fn saturated_tenants_share_the_quota_by_weight() {
+    for mode in [QuotaMode::WorkConserving, QuotaMode::Isolated] {
+        let partitioner = partitioner(mode);
+        let start = Instant::now();
+        let mut admitted: [usize; 2] = [0, 0];
+
+        // Both tenants always have work waiting, and take turns asking first
+        for step in 0..1000u64 {
+            let now = at(start, step * 1000);
+            let tenants: [usize; 2] = if step % 2 == 0 { [0, 1] } else { [1, 0] };
+            for tenant in tenants {
+                admitted[tenant] += fill(&partitioner, ["search", "backfill"][tenant], now);
+            }
+        }
+
+        // 100 windows of 4 requests
+        assert_eq!(admitted[0] + admitted[1], 400, "{:?}", mode);
+        assert!(admitted[0].abs_diff(300) <= 4, "{:?}: {:?}", mode, admitted);
+    }
+}
+
+#[test]
+fn clones_share_the_window_and_report_usage() {
+    let partitioner = QuotaPartitioner::new(2, WINDOW)
+        .with_tenant("trial", TenantQuota::weighted(1.0).with_max_requests(1));
+    let clone = partitioner.clone();
+
+    assert_eq!(clone.admit("trial"), Admission::Admitted);
+    assert!(matches!(
+        partitioner.admit("trial"),
+        Admission::Rejected { .. }
+    ));
+    assert_eq!(partitioner.admit("default"), Admission::Admitted);
+    assert!(matches!(clone.admit("default"), Admission::Wait(_)));
+    assert!(matches!(clone.admit("default"), Admission::Wait(_)));
+
+    assert_eq!(
+        partitioner.usage(),
+        vec![
+            TenantUsage {
+                tenant: "default".to_string(),
+                weight: 1.0,
+                in_window: 1,
+                admitted: 1,
+                throttled: 1,
+                rejected: 0,
+            },
+            TenantUsage {
+                tenant: "trial".to_string(),
+                weight: 1.0,
+                in_window: 1,
+                admitted: 1,
+                throttled: 0,
+                rejected: 1,
+            },
+        ]
+    );
+}
+
+/// The target of a tenant's calls, which tells its requests apart at the server.
+fn tenant_target(tenant: &str) -> String {
+    format!("{} (sent for {})", TARGET, tenant)
+}
+
+#[test]
+fn interleaved_tenants_are_served_by_weight() {
+    let server = MockServer::always(success(ADA_JSON));
+    let partitioner = QuotaPartitioner::new(8, Duration::from_millis(200))
+        .with_tenant("search", TenantQuota::weighted(3.0))
+        .with_tenant("backfill", TenantQuota::weighted(1.0));
+    let llm = server.llm().with_quota_partitioner(partitioner.clone());
+    let started = Instant::now();
+    let stop = started + Duration::from_secs(2);
+
+    // More requests at a time than either share, so both tenants keep requests waiting
+    let threads: Vec<std::thread::JoinHandle<()>> = ["search", "backfill"]
+        .into_iter()
+        .flat_map(|tenant| std::iter::repeat_n(tenant, 8))
+        .map(|tenant| {
+            let llm: OpenAILLM = llm.clone();
+            std::thread::spawn(move || {
+                let options = RequestOptions::default().with_tenant(tenant);
+                while Instant::now() < stop {
+                    let _: Person = llm
+                        .generate_data_with_options(
+                            &Person::new(),
+                            &tenant_target(tenant),
+                            vec![],
+                            &options,
+                        )
+                        .unwrap();
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    let sent = |tenant: &str| {
+        server
+            .requests()
+            .iter()
+            .filter(|request| request.prompt().contains(&tenant_target(tenant)))
+            .count()
+    };
+    let (search, backfill) = (sent("search"), sent("backfill"));
+    let ratio: f64 = search as f64 / backfill as f64;
+    assert!(
+        (1.8..=4.5).contains(&ratio),
+        "search {}, backfill {}",
+        search,
+        backfill
+    );
+    // 8 requests per 200ms, including the requests still waiting when the threads stop
+    let windows: usize = started.elapsed().as_millis() as usize / 200 + 1;
+    assert!(search + backfill <= 8 * windows, "{}", search + backfill);
+    assert_eq!(usage(&partitioner, "search").admitted as usize, search);
+    assert!(usage(&partitioner, "backfill").throttled > 0);
+}
+
+#[test]
+fn the_tenant_of_the_credentials_is_used_by_default() {
+    let server = MockServer::always(success(ADA_JSON));
+    let partitioner = QuotaPartitioner::new(10, WINDOW);
+    let llm = server.llm().with_quota_partitioner(partitioner.clone());
+
+    let credentials = Credentials::new("sk-tenant").with_tenant("acme");
+    let _: Person = llm
+        .generate_data_with_options(
+            &Person::new(),
+            TARGET,
+            vec![],
+            &RequestOptions::default().with_credentials(credentials.clone()),
+        )
+        .unwrap();
+    let _: Person = llm
+        .generate_data_with_options(
+            &Person::new(),
+            TARGET,
+            vec![],
+            &RequestOptions::default()
+                .with_credentials(credentials)
+                .with_tenant("globex"),
+        )
+        .unwrap();
+    let _: Person = llm.generate_data(&Person::new(), TARGET, vec![]).unwrap();
+
+    let tenants: Vec<(String, usize)> = partitioner
+        .usage()
+        .into_iter()
+        .map(|usage| (usage.tenant, usage.in_window))
+        .collect();
+    assert_eq!(
+        tenants,
+        vec![
+            ("acme".to_string(), 1),
+            ("default".to_string(), 1),
+            ("globex".to_string(), 1),
+        ]
+    );
+}
+
+#[test]
+fn a_capped_tenant_fails_without_sending() {
+    let server = MockServer::always(success(ADA_JSON));
+    let sink = Arc::new(CountingSink::default());
+    let partitioner = QuotaPartitioner::new(10, WINDOW)
+        .with_tenant("trial", TenantQuota::weighted(1.0).with_max_requests(1));
+    let llm = server
+        .llm()
+        .with_quota_partitioner(partitioner)
+        .with_metrics_sink(sink.clone());
+    let options = RequestOptions::default().with_tenant("trial");
+
+    let _: Person = llm
+        .generate_data_with_options(&Person::new(), TARGET, vec![], &options)
+        .unwrap();
+    let result: Result<Person, _> =
+        llm.generate_data_with_options(&Person::new(), TARGET, vec![], &options);
+
+    let error = result.unwrap_err();
+    assert!(matches!(
+        secretary_error(&error),
+        SecretaryError::TenantQuotaExceeded { tenant, max_requests: 1, window }
+            if tenant == "trial" && *window == WINDOW
+    ));
+    assert_eq!(server.requests().len(), 1);
+    assert!(sink.events().contains(&MetricEvent::TenantQuotaExceeded {
+        tenant: "trial".to_string(),
+        max_requests: 1,
+    }));
+}
+
+#[test]
+fn the_deadline_bounds_the_wait_for_a_share() {
+    let server = MockServer::always(success(ADA_JSON));
+    let sink = Arc::new(CountingSink::default());
+    let partitioner = QuotaPartitioner::new(1, WINDOW);
+    let llm = server
+        .llm()
+        .with_quota_partitioner(partitioner.clone())
+        .with_metrics_sink(sink.clone());
+    let _: Person = llm.generate_data(&Person::new(), TARGET, vec![]).unwrap();
+
+    let options =
+        RequestOptions::default().with_deadline(Deadline::after(Duration::from_millis(100)));
+    let started = Instant::now();
+    let result: Result<Person, _> =
+        llm.generate_data_with_options(&Person::new(), TARGET, vec![], &options);
+
+    assert!(started.elapsed() < Duration::from_secs(2));
+    assert!(matches!(
+        secretary_error(&result.unwrap_err()),
+        SecretaryError::DeadlineExceeded { .. }
+    ));
+    assert_eq!(server.requests().len(), 1);
+    assert!(sink.events().iter().any(|event| matches!(
+        event,
+        MetricEvent::TenantThrottled { tenant, admitted: false, waited }
+            if tenant == "default" && *waited >= Duration::from_millis(90)
+    )));
+    // Waiting counts once, however often the request was told to wait
+    assert_eq!(usage(&partitioner, "default").throttled, 1);
+}
+
+#[tokio::test]
+async fn async_calls_wait_for_their_share() {
+    let server = MockServer::always(success(ADA_JSON));
+    let sink = Arc::new(CountingSink::default());
+    let partitioner = QuotaPartitioner::new(1, Duration::from_millis(150));
+    let llm = server
+        .llm()
+        .with_quota_partitioner(partitioner)
+        .with_metrics_sink(sink.clone());
+    let options = RequestOptions::default().with_tenant("search");
+
+    let started = Instant::now();
+    for _ in 0..2 {
+        let _: Person = llm
+            .async_generate_data_with_options(&Person::new(), TARGET, vec![], &options)
+            .await
+            .unwrap();
+    }
+
+    assert!(started.elapsed() >= Duration::from_millis(140));
+    assert_eq!(server.requests().len(), 2);
+    assert!(sink.events().iter().any(|event| matches!(
+        event,
+        MetricEvent::TenantThrottled { tenant, admitted: true, .. } if tenant == "search"
+    )));
+}
@@ -0,0 +1,90 @@
+//! A throttled request is retried after the wait the server asks for, and the rate limits of
+//! the final response are reported in the metadata.
+
+#![allow(clippy::needless_borrows_for_generic_args)]
+
+mod support;
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use secretary::llm_providers::rate_limit::{RateLimitInfo, RetryPolicy};
+use secretary::metrics::CountingSink;
+use secretary::traits::GenerateData;
+use serde_json::json;
+
+use support::fixtures::success;
+use support::{ADA_JSON, MockResponse, MockServer, Person, TARGET, ada};
+
+#[test]
+fn throttled_requests_wait_as_long_as_the_server_asks() {
+    let attempts = Arc::new(AtomicUsize::new(0));
+    let counter = attempts.clone();
+    let server = MockServer::start(move |_| {
+        if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+            MockResponse::new(429, json!({"error": {"message": "slow down"}}))
+                .with_header("retry-after-ms", "200")
+        } else {
+            success(ADA_JSON)
+                .with_header("x-ratelimit-remaining-requests", "59")
+                .with_header("x-ratelimit-reset-requests", "1s")
+        }
+    });
+    let sink = Arc::new(CountingSink::default());
+    let llm = server
+        .llm()
+        .with_retry_policy(RetryPolicy::new(2))
+        .with_metrics_sink(sink.clone());
+
+    let started = Instant::now();
+    let result = llm
+        .generate_data_adaptive(&Person::new(), TARGET, &vec![])
+        .unwrap();
+
+    assert!(started.elapsed() >= Duration::from_millis(200));
+    assert_eq!(result.data, ada());
+    let rate_limit = result.metadata.rate_limit.unwrap();
+    assert_eq!(rate_limit.remaining_requests, Some(59));
+    assert_eq!(rate_limit.reset_requests, Some(Duration::from_secs(1)));
+    assert_eq!(sink.count("retry_scheduled"), 1);
+    assert_eq!(sink.count("request_started"), 2);
+    assert_eq!(server.requests().len(), 2);
+}
+
+#[test]
+fn waits_too_long_for_a_duration_are_ignored() {
+    let headers = BTreeMap::from([
+        ("retry-after".to_string(), "1e30".to_string()),
+        (
+            "x-ratelimit-reset-requests".to_string(),
+            "99999999999999999999h".to_string(),
+        ),
+        (
+            "x-ratelimit-remaining-requests".to_string(),
+            "0".to_string(),
+        ),
+    ]);
+
+    let info = RateLimitInfo::from_headers(&headers).unwrap();
+
+    assert_eq!(info.retry_after, None);
+    assert_eq!(info.reset_requests, None);
+    assert_eq!(info.suggested_wait(), None);
+}
+
+#[test]
+fn the_server_wait_is_capped_at_the_max_delay() {
+    let info = RateLimitInfo {
+        retry_after: Some(Duration::from_secs(365 * 24 * 60 * 60)),
+        ..RateLimitInfo::default()
+    };
+    let policy = RetryPolicy::new(1).with_max_delay(Duration::from_secs(30));
+
+    assert_eq!(policy.delay_for(1, Some(&info)), Duration::from_secs(30));
+    assert_eq!(
+        RetryPolicy::new(1).delay_for(1, Some(&info)),
+        RetryPolicy::NONE.max_delay
+    );
+}
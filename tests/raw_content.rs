@@ -0,0 +1,104 @@
+//! Parse failures carry the whole content the model returned, and `redacted` describes them
+//! without it.
+
+#![allow(clippy::needless_borrows_for_generic_args)]
+
+mod support;
+
+use secretary::SecretaryError;
+use secretary::traits::{AsyncGenerateData, GenerateData};
+
+use support::fixtures::{field_result, success};
+use support::{MockServer, Person, secretary_error};
+
+const REFUSAL: &str = "I'm sorry, I can only answer in prose: Ada is thirty-six.";
+
+/// Single requests get an answer in prose, field requests a word where a number belongs.
+fn prose_server() -> MockServer {
+    MockServer::start(|request| {
+        let prompt: String = request.prompt();
+        if !prompt.contains("<result>") {
+            success(REFUSAL)
+        } else if prompt.contains("Extract the age") {
+            field_result("thirty-six")
+        } else {
+            field_result("Ada")
+        }
+    })
+}
+
+#[test]
+fn single_requests_keep_the_whole_answer() {
+    let server = prose_server();
+    let llm = server.llm();
+    let task = Person::new();
+
+    for error in [
+        llm.generate_data(&task, "Ada, 36", &vec![]).unwrap_err(),
+        llm.force_generate_data(&task, "Ada, 36", &vec![])
+            .unwrap_err(),
+    ] {
+        let error: &SecretaryError = secretary_error(&error);
+        match error {
+            SecretaryError::JsonParsingError { raw_content, .. } => {
+                assert_eq!(raw_content, REFUSAL);
+            }
+            other => panic!("unexpected error: {}", other),
+        }
+        assert!(error.to_string().contains(REFUSAL));
+        assert!(!error.redacted().contains("thirty-six"));
+        assert!(
+            error
+                .redacted()
+                .contains(&format!("<{} characters redacted>", REFUSAL.len()))
+        );
+    }
+}
+
+#[test]
+fn distributed_generation_keeps_the_content_of_every_field() {
+    let server = prose_server();
+
+    let error = server
+        .llm()
+        .fields_generate_data(&Person::new(), "Ada, 36", &vec![])
+        .unwrap_err();
+
+    let error: &SecretaryError = secretary_error(&error);
+    match error {
+        SecretaryError::FieldDeserializationError(details) => {
+            assert_eq!(details.failed_fields, vec!["age"]);
+            assert_eq!(details.raw_field_contents["age"], "thirty-six");
+            assert_eq!(details.raw_field_contents["name"], "Ada");
+        }
+        other => panic!("unexpected error: {}", other),
+    }
+    assert!(error.to_string().contains(r#"age: "thirty-six""#));
+    assert!(!error.redacted().contains("thirty-six"));
+}
+
+#[tokio::test]
+async fn async_requests_keep_the_answers() {
+    let server = prose_server();
+    let llm = server.llm();
+    let task = Person::new();
+
+    let error = llm
+        .async_generate_data(&task, "Ada, 36", &vec![])
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        secretary_error(&error),
+        SecretaryError::JsonParsingError { raw_content, .. } if raw_content == REFUSAL
+    ));
+
+    let error = llm
+        .async_fields_generate_data(&task, "Ada, 36", &vec![])
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        secretary_error(&error),
+        SecretaryError::FieldDeserializationError(details)
+            if details.raw_field_contents["age"] == "thirty-six"
+    ));
+}
@@ -0,0 +1,138 @@
+//! Reasoning reported apart from the answer is kept out of the parsed content and surfaced in
+//! the metadata, with the reasoning tokens in the usage.
+
+mod support;
+
+use secretary::llm_providers::responses::ResponsesApiLLM;
+use secretary::metadata::GenerationResult;
+use secretary::reasoning::{ReasoningEffort, TokenUsage, strip_inline_reasoning};
+use secretary::request::RequestOptions;
+use secretary::traits::{AsyncGenerateData, GenerateData};
+
+use support::fixtures::{deepseek_reasoning, o_series, responses_success, success};
+use support::{ADA_JSON, MockServer, Person, TARGET, ada};
+
+/// Reasoning with JSON of its own, which would be parsed if it reached the deserializer.
+const REASONING: &str = "The text names Ada. An example answer would be {\"name\": \"Grace\", \"age\": 85}, \
+    but the age given is 36.";
+
+#[test]
+fn deepseek_reasoning_is_reported_in_the_metadata() {
+    let server = MockServer::always(deepseek_reasoning(REASONING, ADA_JSON));
+
+    let result: GenerationResult<Person> = server
+        .llm()
+        .generate_data_adaptive(&Person::new(), TARGET, vec![])
+        .unwrap();
+
+    assert_eq!(result.data, ada());
+    assert_eq!(result.metadata.reasoning.as_deref(), Some(REASONING));
+    let usage: TokenUsage = result.metadata.usage.unwrap();
+    assert_eq!(usage.reasoning_tokens, Some(120));
+    assert_eq!(usage.completion_tokens, Some(130));
+    assert_eq!(usage.answer_tokens(), Some(10));
+}
+
+#[test]
+fn reasoning_never_reaches_the_deserializer() {
+    let server = MockServer::always(deepseek_reasoning(REASONING, ADA_JSON));
+
+    let person: Person = server
+        .llm()
+        .force_generate_data(&Person::new(), TARGET, vec![])
+        .unwrap();
+    assert_eq!(person, ada());
+
+    let person: Person = server
+        .llm()
+        .generate_data(&Person::new(), TARGET, vec![])
+        .unwrap();
+    assert_eq!(person, ada());
+}
+
+#[test]
+fn o_series_reasoning_tokens_are_counted() {
+    let server = MockServer::always(o_series(ADA_JSON));
+    let options: RequestOptions =
+        RequestOptions::default().with_reasoning_effort(ReasoningEffort::Low);
+
+    let result: GenerationResult<Person> = server
+        .llm()
+        .generate_data_adaptive_with_options(&Person::new(), TARGET, vec![], &options)
+        .unwrap();
+
+    assert_eq!(result.data, ada());
+    assert_eq!(result.metadata.reasoning, None);
+    let usage: TokenUsage = result.metadata.usage.unwrap();
+    assert_eq!(usage.reasoning_tokens, Some(512));
+    assert_eq!(usage.answer_tokens(), Some(10));
+    assert_eq!(usage.total_tokens, Some(572));
+    assert_eq!(server.requests()[0].body["reasoning_effort"], "low");
+}
+
+#[tokio::test]
+async fn hidden_reasoning_is_left_out_of_the_metadata() {
+    let server = MockServer::always(deepseek_reasoning(REASONING, ADA_JSON));
+    let options: RequestOptions = RequestOptions::default().with_hidden_reasoning();
+
+    let result: GenerationResult<Person> = server
+        .llm()
+        .async_generate_data_adaptive_with_options(&Person::new(), TARGET, vec![], &options)
+        .await
+        .unwrap();
+
+    assert_eq!(result.data, ada());
+    assert_eq!(result.metadata.reasoning, None);
+    assert_eq!(result.metadata.usage.unwrap().reasoning_tokens, Some(120));
+    assert!(server.requests()[0].body.get("reasoning_effort").is_none());
+}
+
+#[test]
+fn inline_thinking_blocks_are_removed_without_structured_reasoning() {
+    let server = MockServer::always(success(&format!(
+        "<think>\nThe age is 36.\n</think>\n{}",
+        ADA_JSON
+    )));
+
+    let result: GenerationResult<Person> = server
+        .llm()
+        .generate_data_adaptive(&Person::new(), TARGET, vec![])
+        .unwrap();
+
+    assert_eq!(result.data, ada());
+    assert_eq!(result.metadata.reasoning, None);
+}
+
+#[test]
+fn structured_reasoning_takes_precedence_over_inline_cleanup() {
+    let content: &str = "<think>\nquoted from the target\n</think>";
+    let reasoning = deepseek_reasoning(REASONING, content);
+    let with_reasoning: String = String::from_utf8(reasoning.body).unwrap();
+    let without_reasoning: String = String::from_utf8(success(content).body).unwrap();
+
+    assert_eq!(
+        strip_inline_reasoning(content.to_string(), &with_reasoning),
+        content
+    );
+    assert_eq!(
+        strip_inline_reasoning(content.to_string(), &without_reasoning),
+        ""
+    );
+}
+
+#[test]
+fn responses_api_sends_the_effort_as_a_reasoning_object() {
+    let server = MockServer::always(responses_success("resp_1", ADA_JSON));
+    let llm = ResponsesApiLLM::new(server.address(), "test-key", "test-model").unwrap();
+    let options: RequestOptions =
+        RequestOptions::default().with_reasoning_effort(ReasoningEffort::High);
+
+    let person: Person = llm
+        .generate_data_with_options(&Person::new(), TARGET, vec![], &options)
+        .unwrap();
+
+    assert_eq!(person, ada());
+    let body = &server.requests()[0].body;
+    assert_eq!(body["reasoning"]["effort"], "high");
+    assert!(body.get("reasoning_effort").is_none());
+}
@@ -0,0 +1,239 @@
+//! Requests recorded with `RecordingLLM` are served back by `ReplayLLM` without the network.
+
+#![cfg(feature = "record")]
+
+mod support;
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use secretary::SecretaryError;
+use secretary::llm_providers::openai::OpenAILLM;
+use secretary::llm_providers::record::{
+    FixtureRequest, RecordingLLM, ReplayLLM, ReplayMode, request_hash,
+};
+use secretary::metrics::{CountingSink, MetricEvent};
+use secretary::traits::{AsyncGenerateData, GenerateData};
+use serde_json::{Value, json};
+
+use support::fixtures::success;
+use support::{ADA_JSON, MockServer, Person, TARGET, ada, secretary_error};
+
+const GRACE_JSON: &str = r#"{"name": "Grace", "age": 85}"#;
+
+const GRACE_TARGET: &str = "Grace is 85 years old.";
+
+/// Returns an empty directory of its own under the system's temporary directory.
+fn temp_dir(name: &str) -> PathBuf {
+    let directory: PathBuf = std::env::temp_dir().join(format!(
+        "secretary-fixtures-{}-{}",
+        name,
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&directory);
+    directory
+}
+
+/// Returns the names of the files in a directory, sorted.
+fn file_names(directory: &PathBuf) -> Vec<String> {
+    let mut names: Vec<String> = std::fs::read_dir(directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    names
+}
+
+/// Returns a provider whose requests fail, since nothing listens at its address.
+fn offline_llm() -> OpenAILLM {
+    OpenAILLM::new("http://127.0.0.1:9", "other-key", "test-model").unwrap()
+}
+
+/// Answers each target with the person it names.
+fn people_server() -> MockServer {
+    MockServer::start(|request| {
+        if request.prompt().contains("Grace") {
+            success(GRACE_JSON)
+        } else {
+            success(ADA_JSON)
+        }
+    })
+}
+
+#[test]
+fn recorded_calls_are_replayed_without_the_network() {
+    let directory: PathBuf = temp_dir("replay");
+    let server = people_server();
+    let recording = RecordingLLM::new(server.llm(), &directory);
+
+    let first: Person = recording
+        .generate_data(&Person::new(), TARGET, vec![])
+        .unwrap();
+    let second: Person = recording
+        .generate_data(&Person::new(), GRACE_TARGET, vec![])
+        .unwrap();
+    assert_eq!(recording.recorded(), 2);
+    assert_eq!(server.requests().len(), 2);
+    drop(server);
+
+    let replay = ReplayLLM::new(offline_llm(), &directory).unwrap();
+    assert_eq!(replay.len(), 2);
+    let replayed_first: Person = replay
+        .generate_data(&Person::new(), TARGET, vec![])
+        .unwrap();
+    let replayed_second: Person = replay
+        .generate_data(&Person::new(), GRACE_TARGET, vec![])
+        .unwrap();
+
+    assert_eq!(replayed_first, first);
+    assert_eq!(replayed_first, ada());
+    assert_eq!(replayed_second, second);
+    assert_eq!(replayed_second.name, "Grace");
+}
+
+#[test]
+fn each_call_is_written_to_numbered_files_with_secrets_redacted() {
+    let directory: PathBuf = temp_dir("files");
+    let server = MockServer::always(success(ADA_JSON));
+    let llm = server
+        .llm()
+        .with_extra_body(json!({"api_key": "sk-live-123", "max_tokens": 64}));
+    let recording = RecordingLLM::new(llm, &directory);
+
+    let _: Person = recording
+        .generate_data(&Person::new(), TARGET, vec![])
+        .unwrap();
+
+    let names: Vec<String> = file_names(&directory);
+    assert_eq!(names.len(), 2);
+    assert!(names[0].starts_with("0001-") && names[0].ends_with(".request.json"));
+    assert!(names[1].starts_with("0001-") && names[1].ends_with(".response.json"));
+
+    let request: String = std::fs::read_to_string(directory.join(&names[0])).unwrap();
+    assert!(!request.contains("sk-live-123"));
+    assert!(!request.contains("test-key"));
+    let request: FixtureRequest = serde_json::from_str(&request).unwrap();
+    assert_eq!(request.body["api_key"], "<redacted>");
+    assert_eq!(request.body["max_tokens"], 64);
+    assert_eq!(request.model, "test-model");
+    assert_eq!(request.hash, request_hash(&server.requests()[0].body));
+    assert!(names[0].contains(&request.hash));
+
+    // Numbering continues after the files already recorded
+    let _: Person = RecordingLLM::new(server.llm(), &directory)
+        .generate_data(&Person::new(), TARGET, vec![])
+        .unwrap();
+    assert!(file_names(&directory)[2].starts_with("0002-"));
+}
+
+#[test]
+fn a_recording_that_cannot_be_written_is_reported_as_a_metric() {
+    let directory: PathBuf = temp_dir("unwritable");
+    // A file where the directory should be
+    std::fs::write(&directory, "").unwrap();
+    let server = MockServer::always(success(ADA_JSON));
+    let metrics = Arc::new(CountingSink::default());
+    let recording = RecordingLLM::new(server.llm().with_metrics_sink(metrics.clone()), &directory);
+
+    let person: Person = recording
+        .generate_data(&Person::new(), TARGET, vec![])
+        .unwrap();
+
+    assert_eq!(person, ada());
+    assert_eq!(recording.recorded(), 0);
+    let failures: Vec<MetricEvent> = metrics
+        .events()
+        .into_iter()
+        .filter(|event| matches!(event, MetricEvent::RecordingFailed { .. }))
+        .collect();
+    assert_eq!(failures.len(), 1);
+    let MetricEvent::RecordingFailed { error } = &failures[0] else {
+        unreachable!()
+    };
+    assert!(error.contains("could not record a request"), "{}", error);
+    std::fs::remove_file(&directory).unwrap();
+}
+
+#[test]
+fn requests_are_matched_after_redaction() {
+    let original: Value = json!({"model": "m", "api_key": "sk-1", "messages": []});
+    let rotated: Value = json!({"messages": [], "api_key": "sk-2", "model": "m"});
+    let other: Value = json!({"model": "m", "api_key": "sk-1", "messages": [{}]});
+
+    assert_eq!(request_hash(&original), request_hash(&rotated));
+    assert_ne!(request_hash(&original), request_hash(&other));
+    assert_eq!(request_hash(&original).len(), 16);
+}
+
+#[test]
+fn strict_replay_rejects_unrecorded_requests_and_lenient_replay_sends_them() {
+    let directory: PathBuf = temp_dir("modes");
+    let server = MockServer::always(success(ADA_JSON));
+    let _: Person = RecordingLLM::new(server.llm(), &directory)
+        .generate_data(&Person::new(), TARGET, vec![])
+        .unwrap();
+
+    let strict = ReplayLLM::new(offline_llm(), &directory).unwrap();
+    let result: Result<Person, _> = strict.generate_data(&Person::new(), GRACE_TARGET, vec![]);
+    let error = result.unwrap_err();
+    assert!(matches!(
+        secretary_error(&error),
+        SecretaryError::UnmatchedRequest { hash } if hash.len() == 16
+    ));
+
+    let delegate = MockServer::always(success(GRACE_JSON));
+    let lenient = ReplayLLM::new(delegate.llm(), &directory)
+        .unwrap()
+        .with_mode(ReplayMode::Lenient);
+    let recorded: Person = lenient
+        .generate_data(&Person::new(), TARGET, vec![])
+        .unwrap();
+    let delegated: Person = lenient
+        .generate_data(&Person::new(), GRACE_TARGET, vec![])
+        .unwrap();
+
+    assert_eq!(recorded, ada());
+    assert_eq!(delegated.name, "Grace");
+    assert_eq!(delegate.requests().len(), 1);
+}
+
+#[test]
+fn a_missing_directory_cannot_be_replayed() {
+    let error = ReplayLLM::new(offline_llm(), temp_dir("missing")).unwrap_err();
+
+    assert!(matches!(error, SecretaryError::InputRead(_)));
+}
+
+#[tokio::test]
+async fn async_calls_are_recorded_and_repeated_requests_replayed_in_order() {
+    let directory: PathBuf = temp_dir("async");
+    let server = MockServer::start({
+        let count = std::sync::atomic::AtomicUsize::new(0);
+        move |_| match count.fetch_add(1, std::sync::atomic::Ordering::SeqCst) {
+            0 => success(ADA_JSON),
+            _ => success(r#"{"name": "Ada", "age": 37}"#),
+        }
+    });
+    let recording = RecordingLLM::new(server.llm(), &directory);
+
+    for _ in 0..2 {
+        let _: Person = recording
+            .async_generate_data(&Person::new(), TARGET, vec![])
+            .await
+            .unwrap();
+    }
+    drop(server);
+
+    let replay = ReplayLLM::new(offline_llm(), &directory).unwrap();
+    let mut ages: Vec<u32> = Vec::new();
+    for _ in 0..3 {
+        let person: Person = replay
+            .async_generate_data(&Person::new(), TARGET, vec![])
+            .await
+            .unwrap();
+        ages.push(person.age);
+    }
+
+    // The last recorded response is served once the others were
+    assert_eq!(ages, vec![36, 37, 37]);
+}
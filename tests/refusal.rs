@@ -0,0 +1,215 @@
+//! Refusals fail with `SecretaryError::Refused`, are not retried unless the policy says so,
+//! and are reported per field in distributed generation.
+
+mod support;
+
+use std::time::Duration;
+
+use secretary::SecretaryError;
+use secretary::llm_providers::config::RetryConfig;
+use secretary::llm_providers::openai::OpenAILLM;
+use secretary::llm_providers::rate_limit::RetryPolicy;
+use secretary::partial::PartialData;
+use secretary::refusal::detect_refusal;
+use secretary::traits::{AsyncGenerateData, GenerateData};
+use serde_json::json;
+
+use support::fixtures::{field_result, success};
+use support::{ADA_JSON, MockResponse, MockServer, Person, TARGET, fields_server, secretary_error};
+
+const REFUSAL: &str = "I'm sorry, but I can't help with extracting personal information.";
+
+/// Answers that are refusals.
+const REFUSALS: [&str; 8] = [
+    REFUSAL,
+    "I cannot assist with that request.",
+    "I’m sorry, I can’t comply with this request.",
+    "Sorry, I'm unable to help with that.",
+    "I apologize, but I am not able to provide that information.",
+    "Unfortunately, I must decline this request as it involves private data.",
+    "As an AI language model, I won't help with this.",
+    "<think>\nThe user wants personal data.\n</think>\nI can't help with that.",
+];
+
+/// Answers that are not refusals, although some apologize or say what they cannot do.
+const ANSWERS: [&str; 10] = [
+    "I'm sorry, I can only answer in prose: Ada is thirty-six.",
+    "I cannot find a date in the text.",
+    "Sorry for the delay, here it is: Ada, 36.",
+    "The customer said: I can't help feeling this product is great.",
+    r#"{"name": "Ada", "note": "Sorry, I can't help with the age"}"#,
+    r#"Sorry, I can't help with everything, but: {"name": "Ada"}"#,
+    r#"["I cannot assist with that request."]"#,
+    "<result>I can't help with that</result>",
+    "",
+    "Ada",
+];
+
+/// A completion whose message has a `refusal` and no content, as OpenAI returns it.
+fn provider_refusal(refusal: &str) -> MockResponse {
+    MockResponse::new(
+        200,
+        json!({
+            "id": "chatcmpl-mock",
+            "object": "chat.completion",
+            "model": "test-model",
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": null, "refusal": refusal},
+                "finish_reason": "stop"
+            }],
+            "usage": {"prompt_tokens": 50, "completion_tokens": 10, "total_tokens": 60}
+        }),
+    )
+}
+
+fn assert_refused<T: std::fmt::Debug>(result: Result<T, support::BoxedError>, expected: &str) {
+    let error = result.unwrap_err();
+    match secretary_error(&error) {
+        SecretaryError::Refused { message } => assert_eq!(message, expected),
+        other => panic!("unexpected error: {}", other),
+    }
+}
+
+#[test]
+fn refusals_are_detected() {
+    for refusal in REFUSALS {
+        assert!(detect_refusal(refusal).is_some(), "missed: {}", refusal);
+    }
+    assert_eq!(
+        detect_refusal(REFUSALS[7]).as_deref(),
+        Some("I can't help with that.")
+    );
+}
+
+#[test]
+fn answers_are_not_taken_for_refusals() {
+    for answer in ANSWERS {
+        assert_eq!(detect_refusal(answer), None, "misread: {}", answer);
+    }
+    let long: String = format!("{} {}", REFUSAL, "Ada is 36 years old. ".repeat(40));
+    assert_eq!(detect_refusal(&long), None);
+}
+
+#[test]
+fn a_refusal_is_not_retried_by_default() {
+    let server = MockServer::always(success(REFUSAL));
+    let llm: OpenAILLM = server
+        .llm()
+        .with_retry_policy(RetryPolicy::new(3).with_base_delay(Duration::from_millis(1)));
+
+    assert_refused(llm.generate_data(&Person::new(), TARGET, vec![]), REFUSAL);
+
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[test]
+fn force_mode_reports_refusals_too() {
+    let server = MockServer::always(success(REFUSAL));
+
+    assert_refused(
+        server
+            .llm()
+            .force_generate_data(&Person::new(), TARGET, vec![]),
+        REFUSAL,
+    );
+}
+
+#[test]
+fn the_providers_refusal_is_reported_as_it_is() {
+    let refusal: &str = "This request goes against the content policy.";
+    let server = MockServer::always(provider_refusal(refusal));
+
+    assert_refused(
+        server.llm().generate_data(&Person::new(), TARGET, vec![]),
+        refusal,
+    );
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[test]
+fn refusals_are_retried_when_the_policy_says_so() {
+    let server = MockServer::start({
+        let attempts = std::sync::atomic::AtomicUsize::new(0);
+        move |_| {
+            if attempts.fetch_add(1, std::sync::atomic::Ordering::SeqCst) < 2 {
+                success(REFUSAL)
+            } else {
+                success(ADA_JSON)
+            }
+        }
+    });
+    let retry: RetryConfig = serde_json::from_value(json!({
+        "max_retries": 3,
+        "base_delay_ms": 1,
+        "retry_refusals": true
+    }))
+    .unwrap();
+    assert!(retry.policy().retry_refusals);
+    let llm: OpenAILLM = server.llm().with_retry_policy(retry.policy());
+
+    let person: Person = llm.generate_data(&Person::new(), TARGET, vec![]).unwrap();
+
+    assert_eq!(person, support::ada());
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[tokio::test]
+async fn async_generation_reports_refusals() {
+    let server = MockServer::always(success(REFUSAL));
+    let llm: OpenAILLM = server
+        .llm()
+        .with_retry_policy(RetryPolicy::new(2).with_base_delay(Duration::from_millis(1)));
+
+    assert_refused(
+        llm.async_generate_data(&Person::new(), TARGET, vec![])
+            .await,
+        REFUSAL,
+    );
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[test]
+fn field_mode_marks_the_refused_field() {
+    let server = fields_server(success(REFUSAL));
+
+    let error = server
+        .llm()
+        .fields_generate_data(&Person::new(), TARGET, vec![])
+        .unwrap_err();
+
+    match secretary_error(&error) {
+        SecretaryError::FieldDeserializationError(details) => {
+            assert_eq!(details.refused_fields, vec!["age"]);
+            assert_eq!(details.failed_fields, vec!["age"]);
+            assert_eq!(details.successful_fields, vec!["name"]);
+            assert_eq!(details.raw_field_contents["age"], REFUSAL);
+            assert!(
+                error
+                    .to_string()
+                    .contains("The model refused 1 field(s): [age]")
+            );
+        }
+        other => panic!("unexpected error: {}", other),
+    }
+}
+
+#[test]
+fn a_refused_text_field_is_not_taken_as_its_value() {
+    let server = MockServer::by_instruction(
+        vec![
+            ("Extract the person's name", provider_refusal(REFUSAL)),
+            ("Extract the age as a number", field_result("36")),
+        ],
+        success(""),
+    );
+
+    let partial: PartialData<Person> = server
+        .llm()
+        .fields_generate_partial_data(&Person::new(), TARGET, vec![])
+        .unwrap();
+
+    assert_eq!(partial.data.name, "");
+    assert_eq!(partial.data.age, 36);
+    assert_eq!(partial.failed_fields, vec!["name"]);
+}
@@ -0,0 +1,252 @@
+//! Seeds are sent with every request and fingerprints are recorded in the metadata.
+
+#![allow(clippy::needless_borrows_for_generic_args)]
+
+mod support;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use secretary::adaptive::PromptStrategy;
+use secretary::llm_providers::capabilities::ProviderCapabilities;
+use secretary::llm_providers::openai::OpenAILLM;
+use secretary::llm_providers::responses::ResponsesApiLLM;
+use secretary::message::Message;
+use secretary::metadata::{GenerationResult, GenerationWarning};
+use secretary::reproducibility::ReproducibilityReport;
+use secretary::request::RequestOptions;
+use secretary::tokens::estimate_tokens;
+use secretary::traits::{AsyncGenerateData, GenerateData, Task};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use support::fixtures::{field_result, fingerprinted, responses_success, success};
+use support::{ADA_JSON, MockServer, Person, TARGET, ada, fields_server};
+
+const SEED: u64 = 7;
+
+fn seeded() -> RequestOptions {
+    RequestOptions::default().with_seed(SEED)
+}
+
+/// A Task whose full and compact prompts are larger than any of its per-field prompts.
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Profile {
+    #[task(instruction = "Extract the name")]
+    pub full_legal_name_as_written: String,
+    #[task(instruction = "Extract the age")]
+    pub age_in_completed_years: u32,
+    #[task(instruction = "Extract the city")]
+    pub city_of_current_residence: String,
+    #[task(instruction = "Extract the occupation")]
+    pub occupation_or_job_title: String,
+}
+
+/// The field instructions of `Profile` with their answers.
+const PROFILE_ANSWERS: [(&str, &str); 4] = [
+    ("Extract the name", "Ada"),
+    ("Extract the age", "36"),
+    ("Extract the city", "London"),
+    ("Extract the occupation", "Mathematician"),
+];
+
+fn profile() -> Profile {
+    Profile {
+        full_legal_name_as_written: "Ada".to_string(),
+        age_in_completed_years: 36,
+        city_of_current_residence: "London".to_string(),
+        occupation_or_job_title: "Mathematician".to_string(),
+    }
+}
+
+/// A per-field server answering the fields of `Profile`, the n-th one with the n-th
+/// fingerprint.
+fn profile_server(fingerprints: [&'static str; 4]) -> MockServer {
+    MockServer::start(move |request| {
+        let prompt: String = request.prompt();
+        let index: usize = PROFILE_ANSWERS
+            .iter()
+            .position(|(instruction, _)| prompt.contains(instruction))
+            .unwrap();
+        fingerprinted(field_result(PROFILE_ANSWERS[index].1), fingerprints[index])
+    })
+}
+
+/// A provider whose context window only fits the per-field requests of `Profile`.
+fn distributed_llm(server: &MockServer) -> OpenAILLM {
+    let field_tokens: usize = Profile::new()
+        .make_dstributed_generation_prompts(TARGET, &[])
+        .iter()
+        .map(|(_, message): &(String, Message)| estimate_tokens(message.content.as_str()))
+        .max()
+        .unwrap();
+
+    server
+        .llm()
+        .with_capabilities(ProviderCapabilities::default().with_max_context_tokens(field_tokens))
+}
+
+#[test]
+fn the_seed_is_sent_and_the_fingerprint_recorded() {
+    let server = MockServer::always(fingerprinted(success(ADA_JSON), "fp_44709d6fcb"));
+
+    let result: GenerationResult<Person> = server
+        .llm()
+        .generate_data_adaptive_with_options(&Person::new(), TARGET, &vec![], &seeded())
+        .unwrap();
+
+    assert_eq!(result.data, ada());
+    assert_eq!(server.requests()[0].body["seed"], json!(SEED));
+    assert_eq!(result.metadata.seed, Some(SEED));
+    assert_eq!(
+        result.metadata.system_fingerprint.as_deref(),
+        Some("fp_44709d6fcb")
+    );
+    assert!(result.metadata.warnings.is_empty());
+}
+
+#[test]
+fn every_field_request_carries_the_seed() {
+    let server = fields_server(fingerprinted(field_result("36"), "fp_1"));
+
+    let person: Person = server
+        .llm()
+        .fields_generate_data_with_options(&Person::new(), TARGET, &vec![], &seeded())
+        .unwrap();
+
+    assert_eq!(person, ada());
+    let requests = server.requests();
+    assert_eq!(requests.len(), 2);
+    assert!(
+        requests
+            .iter()
+            .all(|request| request.body["seed"] == json!(SEED))
+    );
+}
+
+#[test]
+fn distributed_generation_records_the_fingerprint_of_its_fields() {
+    let server = profile_server(["fp_1"; 4]);
+
+    let result: GenerationResult<Profile> = distributed_llm(&server)
+        .generate_data_adaptive_with_options(&Profile::new(), TARGET, &vec![], &seeded())
+        .unwrap();
+
+    assert_eq!(result.data, profile());
+    assert_eq!(
+        result.metadata.prompt_strategy,
+        Some(PromptStrategy::Distributed)
+    );
+    assert_eq!(server.requests().len(), 4);
+    assert!(
+        server
+            .requests()
+            .iter()
+            .all(|request| request.body["seed"] == json!(SEED))
+    );
+    assert_eq!(result.metadata.system_fingerprint.as_deref(), Some("fp_1"));
+    assert!(result.metadata.warnings.is_empty());
+}
+
+#[test]
+fn fields_served_by_different_backends_are_flagged() {
+    let server = profile_server(["fp_1", "fp_2", "fp_1", "fp_1"]);
+
+    let result: GenerationResult<Profile> = distributed_llm(&server)
+        .generate_data_adaptive_with_options(&Profile::new(), TARGET, &vec![], &seeded())
+        .unwrap();
+
+    match &result.metadata.warnings[..] {
+        [GenerationWarning::FingerprintsDiffer { fingerprints }] => {
+            let mut fingerprints: Vec<String> = fingerprints.clone();
+            fingerprints.sort();
+            assert_eq!(fingerprints, vec!["fp_1", "fp_2"]);
+        }
+        other => panic!("unexpected warnings: {:?}", other),
+    }
+}
+
+#[test]
+fn ignored_seeds_are_flagged() {
+    let server = MockServer::always(success(ADA_JSON));
+
+    let result: GenerationResult<Person> = server
+        .llm()
+        .generate_data_adaptive_with_options(&Person::new(), TARGET, &vec![], &seeded())
+        .unwrap();
+    assert_eq!(result.metadata.system_fingerprint, None);
+    assert_eq!(
+        result.metadata.warnings,
+        vec![GenerationWarning::SeedIgnored { seed: SEED }]
+    );
+
+    let unseeded: GenerationResult<Person> = server
+        .llm()
+        .generate_data_adaptive(&Person::new(), TARGET, &vec![])
+        .unwrap();
+    assert!(server.requests()[1].body.get("seed").is_none());
+    assert_eq!(unseeded.metadata.seed, None);
+    assert!(unseeded.metadata.warnings.is_empty());
+}
+
+#[test]
+fn providers_without_a_seed_parameter_do_not_send_it() {
+    let server = MockServer::always(responses_success("resp_1", ADA_JSON));
+    let llm = ResponsesApiLLM::new(server.address(), "test-key", "test-model").unwrap();
+
+    let result: GenerationResult<Person> = llm
+        .generate_data_adaptive_with_options(&Person::new(), TARGET, &vec![], &seeded())
+        .unwrap();
+
+    assert!(server.requests()[0].body.get("seed").is_none());
+    assert_eq!(
+        result.metadata.warnings,
+        vec![GenerationWarning::SeedIgnored { seed: SEED }]
+    );
+}
+
+#[test]
+fn runs_that_differ_with_the_same_seed_are_nondeterministic() {
+    let answers: [&str; 2] = [ADA_JSON, r#"{"name": "Ada", "age": 63}"#];
+    let turn = AtomicUsize::new(0);
+    let server = MockServer::start(move |_| {
+        fingerprinted(
+            success(answers[turn.fetch_add(1, Ordering::SeqCst)]),
+            "fp_1",
+        )
+    });
+    let llm = server.llm();
+    let run = || {
+        llm.generate_data_adaptive_with_options(&Person::new(), TARGET, &vec![], &seeded())
+            .unwrap()
+    };
+    let (first, second): (GenerationResult<Person>, GenerationResult<Person>) = (run(), run());
+
+    let report = ReproducibilityReport::compare(&first, &second);
+    assert!(report.same_seed);
+    assert!(report.same_fingerprint);
+    assert!(report.is_nondeterministic());
+    assert_eq!(report.diffs[0].path, "age");
+    assert!(report.report().contains("~ age: 36 -> 63"));
+
+    let report = ReproducibilityReport::compare(&first, &first.clone());
+    assert!(report.is_reproducible());
+}
+
+#[tokio::test]
+async fn async_field_requests_carry_the_seed() {
+    let server = profile_server(["fp_1"; 4]);
+
+    let result: GenerationResult<Profile> = distributed_llm(&server)
+        .async_generate_data_adaptive_with_options(&Profile::new(), TARGET, &vec![], &seeded())
+        .await
+        .unwrap();
+
+    assert_eq!(result.data, profile());
+    assert!(
+        server
+            .requests()
+            .iter()
+            .all(|request| request.body["seed"] == json!(SEED))
+    );
+    assert_eq!(result.metadata.system_fingerprint.as_deref(), Some("fp_1"));
+}
@@ -0,0 +1,253 @@
+//! Answers in YAML and XML are converted into JSON before deserialization.
+
+mod support;
+
+use secretary::request::RequestOptions;
+use secretary::response_format::ResponseFormat;
+#[cfg(feature = "xml")]
+use secretary::traits::AsyncGenerateData;
+use secretary::traits::GenerateData;
+use secretary::{SecretaryError, Task};
+use serde::{Deserialize, Serialize};
+
+use support::fixtures::success;
+use support::{MockServer, secretary_error};
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Address {
+    #[task(instruction = "Extract the street")]
+    pub street: String,
+    #[task(instruction = "Extract the postcode")]
+    pub postcode: String,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Letter {
+    #[task(instruction = "Extract the sender")]
+    pub sender: String,
+    #[task(instruction = "Extract the body, keeping its line breaks")]
+    pub body: String,
+    #[task(instruction = "Extract the number of pages")]
+    pub pages: u32,
+    #[task(instruction = "Is the letter signed")]
+    pub signed: bool,
+    #[task(instruction = "List the people in copy")]
+    pub copies: Vec<String>,
+    #[task(instruction = "Extract the reply-to name, if any")]
+    pub reply_to: Option<String>,
+    pub address: Address,
+}
+
+const TARGET: &str =
+    "From Ada & Co, 12 Main St, 01234. Dear Grace, see you soon. Signed, 2 pages, cc Bob.";
+
+fn letter(body: &str, sender: &str) -> Letter {
+    Letter {
+        sender: sender.to_string(),
+        body: body.to_string(),
+        pages: 2,
+        signed: true,
+        copies: vec!["Bob".to_string()],
+        reply_to: None,
+        address: Address {
+            street: "12 Main St".to_string(),
+            postcode: "01234".to_string(),
+        },
+    }
+}
+
+fn formatted(response_format: ResponseFormat) -> RequestOptions {
+    RequestOptions::default().with_response_format(response_format)
+}
+
+const YAML_ANSWER: &str = "Sure, here is the letter:\n\
+    ```yaml\n\
+    sender: Ada & Co\n\
+    body: |\n  Dear Grace,\n  see you soon.\n\
+    pages: 2\n\
+    signed: true\n\
+    copies:\n  - Bob\n\
+    reply_to:\n\
+    address:\n  street: 12 Main St\n  postcode: \"01234\"\n\
+    ```\n\
+    Let me know if you need anything else.";
+
+#[test]
+fn yaml_answers_with_block_scalars_are_parsed() {
+    let server = MockServer::always(success(YAML_ANSWER));
+
+    let result: Letter = server
+        .llm()
+        .generate_data_with_options(
+            &Letter::new(),
+            TARGET,
+            vec![],
+            &formatted(ResponseFormat::Yaml),
+        )
+        .unwrap();
+
+    assert_eq!(result, letter("Dear Grace,\nsee you soon.\n", "Ada & Co"));
+    let request = &server.requests()[0];
+    assert!(!request.is_json_mode());
+    assert!(
+        request
+            .prompt()
+            .contains("Answer with a YAML document instead of JSON")
+    );
+    assert!(request.prompt().contains("address:\n  "));
+    assert!(request.prompt().contains("\n  street: ''\n"));
+}
+
+#[test]
+fn yaml_scalars_follow_the_field_types() {
+    let answer = "sender: Ada & Co\nbody: Hi\npages: '2'\nsigned: 'true'\ncopies: [Bob]\n\
+        address:\n  street: 12 Main St\n  postcode: 1234\n";
+
+    let json = ResponseFormat::Yaml
+        .to_json_content(answer, &Letter::field_descriptors())
+        .unwrap();
+    let result: Letter = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(result.pages, 2);
+    assert!(result.signed);
+    assert_eq!(result.address.postcode, "1234");
+    assert_eq!(result.reply_to, None);
+}
+
+#[test]
+fn yaml_documents_are_found_between_markers() {
+    let answer = "<think>\nThe sender is Ada.\n</think>\n---\nsender: Ada\n---\n\
+        sender: Ada & Co\nbody: Hi\npages: 2\nsigned: true\ncopies: []\n\
+        address: {street: 12 Main St, postcode: '01234'}\n...\n";
+
+    let json = ResponseFormat::Yaml
+        .to_json_content(answer, &Letter::field_descriptors())
+        .unwrap();
+    let result: Letter = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(result.sender, "Ada & Co");
+    assert!(result.copies.is_empty());
+}
+
+#[test]
+fn force_mode_scans_yaml_code_blocks() {
+    let server = MockServer::always(success(YAML_ANSWER));
+
+    let result: Letter = server
+        .llm()
+        .force_generate_data_with_options(
+            &Letter::new(),
+            TARGET,
+            vec![],
+            &formatted(ResponseFormat::Yaml),
+        )
+        .unwrap();
+
+    assert_eq!(result.address.postcode, "01234");
+    assert_eq!(result.body, "Dear Grace,\nsee you soon.\n");
+}
+
+#[test]
+fn answers_without_a_yaml_mapping_fail() {
+    let server = MockServer::always(success("The letter is from Ada and has two pages."));
+
+    let error = server
+        .llm()
+        .generate_data_with_options(
+            &Letter::new(),
+            TARGET,
+            vec![],
+            &formatted(ResponseFormat::Yaml),
+        )
+        .unwrap_err();
+
+    assert!(matches!(
+        secretary_error(&error),
+        SecretaryError::JsonParsingError { message, .. } if message.contains("YAML")
+    ));
+}
+
+#[test]
+fn json_stays_the_default() {
+    let server = MockServer::always(success(
+        &serde_json::to_string(&letter("Hi", "Ada")).unwrap(),
+    ));
+
+    let result: Letter = server
+        .llm()
+        .generate_data_with_options(&Letter::new(), TARGET, vec![], &RequestOptions::default())
+        .unwrap();
+
+    assert_eq!(result, letter("Hi", "Ada"));
+    let request = &server.requests()[0];
+    assert!(request.is_json_mode());
+    assert!(!request.prompt().contains("instead of JSON"));
+}
+
+#[cfg(feature = "xml")]
+const XML_ANSWER: &str = "```xml\n\
+    <?xml version=\"1.0\"?>\n\
+    <response>\n\
+      <sender>Ada &amp; Co &lt;ada@example.com&gt;</sender>\n\
+      <body><![CDATA[Dear Grace,\nsee you soon.]]></body>\n\
+      <pages>2</pages>\n\
+      <signed>true</signed>\n\
+      <copies><item>Bob</item></copies>\n\
+      <reply_to/>\n\
+      <address>\n\
+        <street>12 Main St</street>\n\
+        <postcode>01234</postcode>\n\
+      </address>\n\
+    </response>\n\
+    ```";
+
+#[cfg(feature = "xml")]
+#[tokio::test]
+async fn xml_answers_with_escaped_entities_are_parsed() {
+    let server = MockServer::always(success(XML_ANSWER));
+
+    let result: Letter = server
+        .llm()
+        .async_generate_data_with_options(
+            &Letter::new(),
+            TARGET,
+            vec![],
+            &formatted(ResponseFormat::Xml),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        result,
+        letter("Dear Grace,\nsee you soon.", "Ada & Co <ada@example.com>")
+    );
+    let prompt: String = server.requests()[0].prompt();
+    assert!(prompt.contains("Answer with an XML document instead of JSON"));
+    assert!(prompt.contains("\n  <address>\n"));
+    assert!(prompt.contains("\n    <street></street>\n"));
+}
+
+#[cfg(feature = "xml")]
+#[tokio::test]
+async fn force_mode_finds_the_xml_root_in_prose() {
+    let answer = "The letter, as requested: <response><sender>Ada</sender><body>Hi</body>\
+        <pages>2</pages><signed>false</signed><copies/><address><street>Main St</street>\
+        <postcode>01234</postcode></address></response> Hope this helps!";
+    let server = MockServer::always(success(answer));
+
+    let result: Letter = server
+        .llm()
+        .async_force_generate_data_with_options(
+            &Letter::new(),
+            TARGET,
+            vec![],
+            &formatted(ResponseFormat::Xml),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(result.sender, "Ada");
+    assert!(!result.signed);
+    assert!(result.copies.is_empty());
+    assert_eq!(result.address.postcode, "01234");
+}
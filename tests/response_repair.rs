@@ -0,0 +1,154 @@
+//! Responses with invalid UTF-8 or invalid JSON escapes are repaired instead of lost.
+
+mod support;
+
+use secretary::decoding::DecodingPolicy;
+use secretary::traits::{AsyncGenerateData, GenerateData};
+use secretary::utilities::{decode_utf8_lossy, repair_json_escapes};
+use serde_json::Value;
+
+use support::fixtures::success;
+use support::{MockResponse, MockServer, Person, TARGET, assert_malformed_json};
+
+/// A completion whose body has a lone surrogate escape in the message content itself.
+fn lone_surrogate_body() -> MockResponse {
+    MockResponse {
+        status: 200,
+        body: r#"{"choices": [{"index": 0, "message": {"role": "assistant", "content": "{\"name\": \"Ada \ud83d\", \"age\": 36}"}, "finish_reason": "stop"}]}"#.as_bytes().to_vec(),
+        headers: Vec::new(),
+        events: None,
+    }
+}
+
+#[test]
+fn malformed_escapes_are_repaired_to_parseable_json() {
+    // (payload, repaired payload, repaired sequences)
+    let cases: Vec<(&str, &str, usize)> = vec![
+        (r#"{"a": "x\ud83d"}"#, r#"{"a": "x\ufffd"}"#, 1),
+        (r#"{"a": "\ud83dA"}"#, r#"{"a": "\ufffdA"}"#, 1),
+        (r#"{"a": "\ud83d\u0041"}"#, r#"{"a": "\ufffd\u0041"}"#, 1),
+        (r#"{"a": "\ude00\ud83d"}"#, r#"{"a": "\ufffd\ufffd"}"#, 2),
+        (r#"{"a": "\ud83d\ude00"}"#, r#"{"a": "\ud83d\ude00"}"#, 0),
+        (r#"{"a": "\u00"}"#, r#"{"a": "\ufffd00"}"#, 1),
+        (r#"{"a": "\uZZZZ"}"#, r#"{"a": "\ufffdZZZZ"}"#, 1),
+        (r#"{"a": "\q\x41"}"#, r#"{"a": "\ufffd\ufffd41"}"#, 2),
+        (r#"{"a": "\\ud83d"}"#, r#"{"a": "\\ud83d"}"#, 0),
+        (r#"{"a": "\"\ud83d\""}"#, r#"{"a": "\"\ufffd\""}"#, 1),
+        (r#"{"a": "\/\b\f\n\r\t"}"#, r#"{"a": "\/\b\f\n\r\t"}"#, 0),
+    ];
+
+    for (payload, expected, count) in cases {
+        let (repaired, repaired_count) = repair_json_escapes(payload);
+        assert_eq!(repaired, expected, "{}", payload);
+        assert_eq!(repaired_count, count, "{}", payload);
+        serde_json::from_str::<Value>(&repaired).unwrap();
+    }
+
+    // Backslashes outside strings are not escapes
+    assert_eq!(
+        repair_json_escapes(r#"{"a": 1} \ud83d"#),
+        (r#"{"a": 1} \ud83d"#.to_string(), 0)
+    );
+    // A backslash at the very end of an unterminated string
+    assert_eq!(
+        repair_json_escapes(r#"{"a": "x\"#),
+        (r#"{"a": "x\ufffd"#.to_string(), 1)
+    );
+}
+
+#[test]
+fn invalid_utf8_is_decoded_lossily() {
+    // (bytes, decoded text, replaced sequences)
+    let cases: Vec<(&[u8], &str, usize)> = vec![
+        (b"plain", "plain", 0),
+        ("Zoë 😀".as_bytes(), "Zoë 😀", 0),
+        (b"\xFFabc", "\u{FFFD}abc", 1),
+        (b"abc\xF0\x9F\x98", "abc\u{FFFD}", 1),
+        (b"\xC0\xAF", "\u{FFFD}\u{FFFD}", 2),
+        (b"\xED\xA0\xBD!", "\u{FFFD}\u{FFFD}\u{FFFD}!", 3),
+    ];
+
+    for (bytes, expected, count) in cases {
+        assert_eq!(
+            decode_utf8_lossy(bytes),
+            (expected.to_string(), count),
+            "{:?}",
+            bytes
+        );
+    }
+
+    assert_eq!(
+        DecodingPolicy::Repair
+            .decode_body(b"{\"a\": \"\xFF\\ud83d\"}")
+            .unwrap(),
+        ("{\"a\": \"\u{FFFD}\\ufffd\"}".to_string(), 2)
+    );
+    assert!(DecodingPolicy::Strict.decode_body(b"\xFF").is_err());
+}
+
+#[test]
+fn lone_surrogate_in_the_body_is_repaired() {
+    let server = MockServer::always(lone_surrogate_body());
+
+    let result = server
+        .llm()
+        .generate_data_adaptive(&Person::new(), TARGET, vec![])
+        .unwrap();
+
+    assert_eq!(result.data.name, "Ada \u{FFFD}");
+    assert_eq!(result.data.age, 36);
+    assert_eq!(result.metadata.repaired_sequences, 1);
+}
+
+#[test]
+fn lone_surrogate_in_the_content_is_repaired() {
+    let server = MockServer::always(success(r#"{"name": "Ada \ud83d", "age": 36}"#));
+
+    let person: Person = server
+        .llm()
+        .generate_data(&Person::new(), TARGET, vec![])
+        .unwrap();
+    assert_eq!(person.name, "Ada \u{FFFD}");
+
+    let result = server
+        .llm()
+        .generate_data_adaptive(&Person::new(), TARGET, vec![])
+        .unwrap();
+    assert_eq!(result.metadata.repaired_sequences, 1);
+}
+
+#[test]
+fn clean_responses_report_no_repairs() {
+    let server = MockServer::always(success(r#"{"name": "Ada 😀", "age": 36}"#));
+
+    let result = server
+        .llm()
+        .generate_data_adaptive(&Person::new(), TARGET, vec![])
+        .unwrap();
+
+    assert_eq!(result.data.name, "Ada 😀");
+    assert_eq!(result.metadata.repaired_sequences, 0);
+}
+
+#[test]
+fn strict_policy_fails_on_invalid_escapes() {
+    let server = MockServer::always(success(r#"{"name": "Ada \ud83d", "age": 36}"#));
+    let llm = server.llm().with_decoding_policy(DecodingPolicy::Strict);
+
+    let raw_content = assert_malformed_json(llm.generate_data(&Person::new(), TARGET, vec![]));
+    assert!(raw_content.contains(r"\ud83d"));
+}
+
+#[tokio::test]
+async fn async_lone_surrogate_is_repaired() {
+    let server = MockServer::always(lone_surrogate_body());
+
+    let result = server
+        .llm()
+        .async_generate_data_adaptive(&Person::new(), TARGET, vec![])
+        .await
+        .unwrap();
+
+    assert_eq!(result.data.name, "Ada \u{FFFD}");
+    assert_eq!(result.metadata.repaired_sequences, 1);
+}
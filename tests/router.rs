@@ -0,0 +1,312 @@
+//! Extractions are routed between model tiers by their recent failure rates, and return to
+//! the cheap tier once its failures decay.
+
+mod support;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use secretary::Task;
+use secretary::llm_providers::openai::OpenAILLM;
+use secretary::llm_providers::router::{CallOutcome, Router, RoutingPolicy};
+use secretary::traits::{AsyncGenerateData, GenerateData};
+use serde::{Deserialize, Serialize};
+
+use support::fixtures::{error_body, field_result, success, truncated};
+use support::{ADA_JSON, MockResponse, MockServer, Person, TARGET, ada, fields_server};
+
+/// A Person whose age is critical.
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct CheckedPerson {
+    #[task(instruction = "Extract the person's name")]
+    pub name: String,
+    #[task(instruction = "Extract the age as a number", importance = "critical")]
+    pub age: u32,
+}
+
+/// How long outcomes count in the tests, short enough to wait for.
+const DECAY: Duration = Duration::from_millis(300);
+
+/// One server per tier: the cheap one answering with `cheap`, the others with Ada.
+struct Tiers {
+    cheap: MockServer,
+    medium: MockServer,
+    large: MockServer,
+}
+
+impl Tiers {
+    fn start(cheap: MockResponse) -> Self {
+        Self::start_with(MockServer::always(cheap))
+    }
+
+    fn start_with(cheap: MockServer) -> Self {
+        Self {
+            cheap,
+            medium: MockServer::always(success(ADA_JSON)),
+            large: MockServer::always(success(ADA_JSON)),
+        }
+    }
+
+    fn router(&self, policy: RoutingPolicy) -> Router<OpenAILLM> {
+        let llm = |server: &MockServer, model: &str| {
+            OpenAILLM::new(server.address(), "test-key", model).unwrap()
+        };
+        Router::new(llm(&self.cheap, "cheap-model"))
+            .with_tier(llm(&self.medium, "medium-model"))
+            .with_tier(llm(&self.large, "large-model"))
+            .with_policy(policy)
+    }
+
+    /// Returns the number of requests each tier received.
+    fn requests(&self) -> [usize; 3] {
+        [
+            self.cheap.requests().len(),
+            self.medium.requests().len(),
+            self.large.requests().len(),
+        ]
+    }
+}
+
+fn policy() -> RoutingPolicy {
+    RoutingPolicy::new()
+        .with_min_samples(2)
+        .with_failure_threshold(0.5)
+        .with_decay(DECAY)
+        .with_sticky_window(Duration::ZERO)
+}
+
+#[test]
+fn healthy_extractions_stay_on_the_default_tier() {
+    let tiers = Tiers::start(success(ADA_JSON));
+    let router = tiers.router(policy());
+
+    for _ in 0..3 {
+        let person: Person = router
+            .generate_data(&Person::new(), TARGET, vec![])
+            .unwrap();
+        assert_eq!(person, ada());
+    }
+    let result = router
+        .generate_data_adaptive(&Person::new(), TARGET, vec![])
+        .unwrap();
+
+    assert_eq!(result.metadata.routed_model.as_deref(), Some("cheap-model"));
+    assert_eq!(tiers.requests(), [4, 0, 0]);
+    let stats = router.stats();
+    assert_eq!(stats[0].successes, 4);
+    assert!(!stats[0].escalated);
+}
+
+#[test]
+fn parse_failures_escalate_until_they_decay() {
+    // The cheap model fails twice, then recovers
+    let answered = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&answered);
+    let tiers = Tiers::start_with(MockServer::start(move |_| {
+        match counter.fetch_add(1, Ordering::SeqCst) {
+            0 | 1 => truncated(),
+            _ => success(ADA_JSON),
+        }
+    }));
+    let router = tiers.router(policy());
+
+    for _ in 0..2 {
+        let result: Result<Person, _> = router.generate_data(&Person::new(), TARGET, vec![]);
+        assert!(result.is_err());
+    }
+    assert_eq!(router.stats()[0].parse_failures, 2);
+    assert_eq!(router.stats()[0].failure_rate(), 1.0);
+
+    // Escalated while the failures are recent
+    let result = router
+        .generate_data_adaptive(&Person::new(), TARGET, vec![])
+        .unwrap();
+    assert_eq!(
+        result.metadata.routed_model.as_deref(),
+        Some("medium-model")
+    );
+    assert_eq!(result.data, ada());
+    assert_eq!(tiers.requests(), [2, 1, 0]);
+    assert!(router.stats()[0].escalated);
+
+    // Back on the cheap tier once they decayed
+    std::thread::sleep(DECAY + Duration::from_millis(50));
+    let result = router
+        .generate_data_adaptive(&Person::new(), TARGET, vec![])
+        .unwrap();
+    assert_eq!(result.metadata.routed_model.as_deref(), Some("cheap-model"));
+    assert_eq!(tiers.requests(), [3, 1, 0]);
+    assert_eq!(router.stats()[0].successes, 1);
+    assert_eq!(router.stats()[0].parse_failures, 0);
+}
+
+#[test]
+fn escalation_sticks_for_the_sticky_window() {
+    let tiers = Tiers::start(truncated());
+    let router = tiers.router(
+        policy()
+            .with_decay(Duration::from_millis(100))
+            .with_sticky_window(Duration::from_millis(600)),
+    );
+    for _ in 0..2 {
+        let _ = router.generate_data::<Person>(&Person::new(), TARGET, vec![]);
+    }
+    assert_eq!(router.select_tier(false), 1);
+
+    // The failures decayed, but the tier is still skipped
+    std::thread::sleep(Duration::from_millis(200));
+    assert_eq!(router.stats()[0].parse_failures, 0);
+    assert!(router.stats()[0].escalated);
+    router
+        .generate_data::<Person>(&Person::new(), TARGET, vec![])
+        .unwrap();
+    assert_eq!(tiers.requests(), [2, 1, 0]);
+
+    std::thread::sleep(Duration::from_millis(500));
+    assert_eq!(router.select_tier(false), 0);
+}
+
+#[test]
+fn http_errors_count_as_failures() {
+    let mut tiers = Tiers::start(error_body());
+    tiers.medium = fields_server(field_result("36"));
+    let router = tiers.router(policy());
+
+    for _ in 0..2 {
+        let _ = router.force_generate_data::<Person>(&Person::new(), TARGET, vec![]);
+    }
+    let stats = router.stats();
+    assert_eq!(stats[0].http_errors, 2);
+    assert_eq!(stats[0].parse_failures, 0);
+
+    let person: Person = router
+        .fields_generate_data(&Person::new(), TARGET, vec![])
+        .unwrap();
+    assert_eq!(person, ada());
+    assert_eq!(tiers.requests(), [2, 2, 0]);
+}
+
+#[test]
+fn failing_tiers_are_skipped_in_turn() {
+    let tiers = Tiers::start(truncated());
+    let router = tiers.router(policy());
+    router.record(1, CallOutcome::HttpError);
+    router.record(1, CallOutcome::HttpError);
+    router.record(0, CallOutcome::ParseFailure);
+    router.record(0, CallOutcome::Success);
+    router.record(0, CallOutcome::ParseFailure);
+
+    let result = router
+        .generate_data_adaptive(&Person::new(), TARGET, vec![])
+        .unwrap();
+
+    assert_eq!(result.metadata.routed_model.as_deref(), Some("large-model"));
+    assert_eq!(tiers.requests(), [0, 0, 1]);
+}
+
+#[test]
+fn critical_tasks_start_one_tier_up() {
+    let tiers = Tiers::start(success(ADA_JSON));
+    let router = tiers.router(policy());
+
+    let checked: CheckedPerson = router
+        .generate_data(&CheckedPerson::new(), TARGET, vec![])
+        .unwrap();
+    assert_eq!(checked.age, 36);
+    assert_eq!(tiers.requests(), [0, 1, 0]);
+
+    let router = tiers.router(policy().with_critical_escalation(false));
+    router
+        .generate_data::<CheckedPerson>(&CheckedPerson::new(), TARGET, vec![])
+        .unwrap();
+    assert_eq!(tiers.requests(), [1, 1, 0]);
+}
+
+#[test]
+fn parse_failures_are_retried_on_the_next_tier() {
+    let tiers = Tiers::start(truncated());
+    let router = tiers.router(policy().with_parse_failure_retry(true));
+
+    let result = router
+        .generate_data_adaptive(&Person::new(), TARGET, vec![])
+        .unwrap();
+
+    assert_eq!(result.data, ada());
+    assert_eq!(
+        result.metadata.routed_model.as_deref(),
+        Some("medium-model")
+    );
+    assert_eq!(tiers.requests(), [1, 1, 0]);
+    let stats = router.stats();
+    assert_eq!(stats[0].parse_failures, 1);
+    assert_eq!(stats[1].successes, 1);
+
+    // Without the option, the failure is returned
+    let router = tiers.router(policy());
+    assert!(
+        router
+            .generate_data::<Person>(&Person::new(), TARGET, vec![])
+            .is_err()
+    );
+    assert_eq!(tiers.requests(), [2, 1, 0]);
+}
+
+#[tokio::test]
+async fn async_extractions_are_routed_and_recorded() {
+    let tiers = Tiers::start(truncated());
+    let router = tiers.router(policy());
+
+    for _ in 0..2 {
+        let result: Result<Person, _> = router
+            .async_generate_data(&Person::new(), TARGET, vec![])
+            .await;
+        assert!(result.is_err());
+    }
+    let result = router
+        .async_generate_data_adaptive(&Person::new(), TARGET, vec![])
+        .await
+        .unwrap();
+
+    assert_eq!(
+        result.metadata.routed_model.as_deref(),
+        Some("medium-model")
+    );
+    assert_eq!(tiers.requests(), [2, 1, 0]);
+    assert_eq!(router.stats()[1].successes, 1);
+}
+
+#[test]
+fn the_ring_buffer_keeps_the_last_outcomes_of_all_threads() {
+    let tiers = Tiers::start(success(ADA_JSON));
+    let router =
+        Arc::new(tiers.router(policy().with_window(8).with_decay(Duration::from_secs(60))));
+
+    let threads: Vec<_> = (0..4)
+        .map(|_| {
+            let router = Arc::clone(&router);
+            std::thread::spawn(move || {
+                for _ in 0..10 {
+                    router.record(0, CallOutcome::ParseFailure);
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert_eq!(router.stats()[0].parse_failures, 8);
+
+    // Newer outcomes replace the oldest ones
+    for _ in 0..6 {
+        router.record(0, CallOutcome::Success);
+    }
+    let stats = router.stats();
+    assert_eq!((stats[0].successes, stats[0].parse_failures), (6, 2));
+    assert_eq!(router.select_tier(false), 0);
+
+    // Clones share the outcomes
+    let clone = (*router).clone();
+    clone.record(0, CallOutcome::HttpError);
+    assert_eq!(router.stats()[0].http_errors, 1);
+}
@@ -0,0 +1,307 @@
+//! Serde attributes that make a Task's representation differ from its prompt are found by
+//! path, before any request is sent.
+
+mod support;
+
+use std::collections::HashMap;
+
+use secretary::request::RequestOptions;
+use secretary::schema::JsonType;
+use secretary::schema_check::{MismatchKind, SchemaMismatch, assert_schema_consistency};
+use secretary::traits::{AsyncGenerateData, GenerateData};
+use secretary::{SecretaryError, Task};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use support::fixtures::success;
+use support::{MockServer, TARGET, secretary_error};
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[task(key_case = "camelCase")]
+struct Person {
+    #[task(instruction = "Extract the person's full name")]
+    pub full_name: String,
+    #[task(instruction = "Extract the age as a number")]
+    pub age: u32,
+    #[task(instruction = "Extract the email address if mentioned")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[task(instruction = "Extract the nicknames")]
+    #[serde(default)]
+    pub nicknames: Vec<String>,
+    pub address: Address,
+    #[task(instruction = "Extract the person's employers")]
+    pub employers: Vec<Address>,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Address {
+    #[task(instruction = "Extract the street")]
+    pub street: String,
+    #[task(instruction = "Extract the name on the door")]
+    pub name: String,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Renamed {
+    #[task(instruction = "Extract the first name")]
+    pub first_name: String,
+    #[task(instruction = "Extract the age")]
+    pub age: u32,
+    #[task(instruction = "Extract the last name")]
+    pub last_name: String,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug)]
+struct Skipped {
+    #[task(instruction = "Extract the title")]
+    pub title: String,
+    #[task(instruction = "Extract the notes")]
+    #[serde(skip)]
+    pub notes: String,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug)]
+struct Flattened {
+    #[task(instruction = "Extract the company name")]
+    pub name: String,
+    #[serde(flatten)]
+    pub address: Address,
+}
+
+mod as_string {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &u32, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&value.to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Task, Serialize, Deserialize, Debug)]
+struct Reshaped {
+    #[task(instruction = "Extract the number of items")]
+    #[serde(with = "as_string")]
+    pub count: u32,
+    #[task(instruction = "Extract the label")]
+    pub label: String,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug)]
+struct CatchAll {
+    #[task(instruction = "Extract the title")]
+    pub title: String,
+    #[task(plain, instruction = "Extract the remaining attributes")]
+    #[serde(flatten)]
+    pub attributes: Attributes,
+}
+
+/// Free-form attributes that default to a `source` entry.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(transparent)]
+struct Attributes(HashMap<String, String>);
+
+impl Default for Attributes {
+    fn default() -> Self {
+        Self(HashMap::from([(
+            "source".to_string(),
+            "manual".to_string(),
+        )]))
+    }
+}
+
+#[derive(Task, Serialize, Deserialize, Debug)]
+struct Line {
+    #[task(instruction = "Extract the product")]
+    pub product: String,
+    #[task(instruction = "Extract the unit price")]
+    #[serde(rename = "unitPrice")]
+    pub price: f64,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug)]
+struct Order {
+    #[task(instruction = "Extract the order number")]
+    pub number: String,
+    #[task(instruction = "Extract every line of the order")]
+    pub lines: Vec<Line>,
+    #[task(instruction = "Extract the skipped details if mentioned")]
+    pub skipped: Option<Skipped>,
+    #[task(instruction = "Extract the stock by warehouse")]
+    pub by_warehouse: HashMap<String, Reshaped>,
+}
+
+fn mismatch(path: &str, kind: MismatchKind) -> SchemaMismatch {
+    SchemaMismatch {
+        path: path.to_string(),
+        kind,
+    }
+}
+
+#[test]
+fn a_task_serde_agrees_with_passes() {
+    assert_eq!(Person::verify_schema_consistency(), Ok(()));
+    assert_eq!(Address::verify_schema_consistency(), Ok(()));
+    assert_schema_consistency::<Person>();
+}
+
+#[test]
+fn renamed_fields_are_paired_with_their_keys_in_order() {
+    assert_eq!(
+        Renamed::verify_schema_consistency(),
+        Err(vec![
+            mismatch(
+                "first_name",
+                MismatchKind::Renamed {
+                    key: "firstName".to_string()
+                }
+            ),
+            mismatch(
+                "last_name",
+                MismatchKind::Renamed {
+                    key: "lastName".to_string()
+                }
+            ),
+        ])
+    );
+}
+
+#[test]
+fn skipped_fields_are_reported() {
+    assert_eq!(
+        Skipped::verify_schema_consistency(),
+        Err(vec![mismatch("notes", MismatchKind::Skipped)])
+    );
+}
+
+#[test]
+fn flattened_tasks_and_their_collisions_are_reported() {
+    assert_eq!(
+        Flattened::verify_schema_consistency(),
+        Err(vec![
+            mismatch("address", MismatchKind::Flattened),
+            mismatch(
+                "address",
+                MismatchKind::FlattenCollision {
+                    key: "name".to_string()
+                }
+            ),
+        ])
+    );
+}
+
+#[test]
+fn fields_written_in_another_shape_are_reported() {
+    assert_eq!(
+        Reshaped::verify_schema_consistency(),
+        Err(vec![mismatch(
+            "count",
+            MismatchKind::ShapeMismatch {
+                expected: JsonType::Number,
+                found: JsonType::String,
+            }
+        )])
+    );
+}
+
+#[test]
+fn keys_that_are_no_field_are_reported() {
+    assert_eq!(
+        CatchAll::verify_schema_consistency(),
+        Err(vec![mismatch("source", MismatchKind::Unexpected)])
+    );
+}
+
+#[test]
+fn nested_tasks_are_checked_under_their_field() {
+    assert_eq!(
+        Order::verify_schema_consistency(),
+        Err(vec![
+            mismatch(
+                "lines[].price",
+                MismatchKind::Renamed {
+                    key: "unitPrice".to_string()
+                }
+            ),
+            mismatch("skipped.notes", MismatchKind::Skipped),
+            mismatch(
+                "by_warehouse[].count",
+                MismatchKind::ShapeMismatch {
+                    expected: JsonType::Number,
+                    found: JsonType::String,
+                }
+            ),
+        ])
+    );
+}
+
+#[test]
+#[should_panic(expected = "`notes` is neither written nor read")]
+fn the_assertion_panics_with_the_mismatches() {
+    assert_schema_consistency::<Skipped>();
+}
+
+#[test]
+fn strict_generation_fails_before_the_first_request() {
+    let server = MockServer::always(success(&json!({"title": "Minutes"}).to_string()));
+    let llm = server.llm();
+    let options: RequestOptions = RequestOptions::default().with_strict_schema(true);
+
+    for _ in 0..2 {
+        let error = llm
+            .generate_data_with_options(&Order::new(), TARGET, vec![], &options)
+            .unwrap_err();
+        assert!(matches!(
+            secretary_error(&error),
+            SecretaryError::SchemaInconsistent { task, mismatches }
+                if task.ends_with("Order") && mismatches.len() == 3
+        ));
+    }
+    let error = llm
+        .force_generate_data_with_options(&Skipped::default(), TARGET, vec![], &options)
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        format!(
+            "The serde representation of {} differs from its prompt in 1 place(s): [`notes` is neither written nor read]",
+            std::any::type_name::<Skipped>()
+        )
+    );
+    assert!(server.requests().is_empty());
+
+    // Without the option the Task is extracted, and loses the skipped field
+    let skipped: Skipped = llm
+        .generate_data(&Skipped::default(), TARGET, vec![])
+        .unwrap();
+    assert_eq!(skipped.title, "Minutes");
+    assert!(skipped.notes.is_empty());
+}
+
+#[tokio::test]
+async fn strict_generation_extracts_consistent_tasks() {
+    let answer = json!({
+        "fullName": "Ada Lovelace",
+        "age": 36,
+        "email": null,
+        "nicknames": [],
+        "address": {"street": "St James's Square", "name": "Lovelace"},
+        "employers": []
+    });
+    let server = MockServer::always(success(&answer.to_string()));
+    let options: RequestOptions = RequestOptions::default().with_strict_schema(true);
+
+    let person: Person = server
+        .llm()
+        .async_generate_data_with_options(&Person::default(), TARGET, vec![], &options)
+        .await
+        .unwrap();
+
+    assert_eq!(person.full_name, "Ada Lovelace");
+    assert_eq!(server.requests().len(), 1);
+}
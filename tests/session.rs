@@ -0,0 +1,221 @@
+//! Extraction sessions refine an extraction over several turns.
+
+#![allow(clippy::needless_borrows_for_generic_args)]
+
+mod support;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use secretary::llm_providers::openai::OpenAILLM;
+use secretary::llm_providers::responses::ResponsesApiLLM;
+use secretary::message::Role;
+use secretary::session::ExtractionSession;
+use secretary::traits::GenerateData;
+use serde_json::{Value, json};
+
+use support::fixtures::{responses_success, success};
+use support::{ADA_JSON, MockResponse, MockServer, Person, TARGET, ada};
+
+/// The answers of a three-turn refinement, from the first guess to the corrected age.
+const ANSWERS: [&str; 3] = [
+    r#"{"name": "ada", "age": 36}"#,
+    r#"{"name": "Ada", "age": 36}"#,
+    r#"{"name": "Ada", "age": 37}"#,
+];
+
+const FEEDBACK: [&str; 2] = ["Capitalize the name.", "She turned 37 last week."];
+
+/// A Responses API server answering the n-th request with the n-th answer and ID `resp_n`.
+fn responses_server() -> MockServer {
+    let turn = AtomicUsize::new(0);
+    MockServer::start(move |_| {
+        let index: usize = turn.fetch_add(1, Ordering::SeqCst);
+        responses_success(&format!("resp_{}", index + 1), ANSWERS[index])
+    })
+}
+
+/// A chat completions server answering the n-th request with the n-th answer.
+fn completions_server() -> MockServer {
+    let turn = AtomicUsize::new(0);
+    MockServer::start(move |_| success(ANSWERS[turn.fetch_add(1, Ordering::SeqCst)]))
+}
+
+fn responses_llm(server: &MockServer) -> ResponsesApiLLM {
+    ResponsesApiLLM::new(server.address(), "test-key", "test-model").unwrap()
+}
+
+fn roles(messages: &Value) -> Vec<&str> {
+    messages
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|message| message["role"].as_str().unwrap())
+        .collect()
+}
+
+#[test]
+fn responses_sessions_send_only_the_new_turn() {
+    let server = responses_server();
+    let llm = responses_llm(&server);
+    let task = Person::new();
+    let mut session = ExtractionSession::new(&llm, &task);
+
+    let person: Person = session.extract(TARGET, &vec![]).unwrap();
+    assert_eq!(person.name, "ada");
+    assert_eq!(session.response_id(), Some("resp_1"));
+    assert!(session.uses_server_state());
+    let person: Person = session.refine(FEEDBACK[0]).unwrap();
+    assert_eq!(person, ada());
+    let person: Person = session.refine(FEEDBACK[1]).unwrap();
+    assert_eq!(person.age, 37);
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 3);
+    assert!(requests.iter().all(|request| request.path == "/responses"));
+
+    let first: &Value = &requests[0].body;
+    assert_eq!(roles(&first["input"]), vec!["system", "user"]);
+    assert_eq!(first["text"], json!({"format": {"type": "json_object"}}));
+    assert!(first.get("previous_response_id").is_none());
+
+    for (turn, request) in requests[1..].iter().enumerate() {
+        assert_eq!(roles(&request.body["input"]), vec!["user"]);
+        assert_eq!(
+            request.body["previous_response_id"],
+            json!(format!("resp_{}", turn + 1))
+        );
+        let prompt: String = request.prompt();
+        assert!(prompt.contains(FEEDBACK[turn]));
+        assert!(!prompt.contains(TARGET));
+        assert!(!prompt.contains("Extract the person's name"));
+    }
+
+    assert_eq!(session.turns(), 3);
+    assert_eq!(session.response_id(), Some("resp_3"));
+    assert_eq!(session.history().len(), 7);
+    assert_eq!(session.history()[6].role, Role::Assistant);
+}
+
+#[test]
+fn other_providers_resend_the_conversation() {
+    let server = completions_server();
+    let llm = server.llm();
+    let task = Person::new();
+    let mut session = ExtractionSession::new(&llm, &task);
+
+    let _: Person = session.extract(TARGET, &vec![]).unwrap();
+    assert!(!session.uses_server_state());
+    let _: Person = session.refine(FEEDBACK[0]).unwrap();
+    let person: Person = session.refine(FEEDBACK[1]).unwrap();
+    assert_eq!(person.age, 37);
+
+    let requests = server.requests();
+    assert_eq!(
+        roles(&requests[2].body["messages"]),
+        vec!["system", "user", "assistant", "user", "assistant", "user"]
+    );
+    assert_eq!(
+        requests[2].body["messages"][2]["content"],
+        json!(ANSWERS[0])
+    );
+    assert!(
+        requests
+            .iter()
+            .all(|request| request.body.get("previous_response_id").is_none())
+    );
+    assert_eq!(session.response_id(), None);
+}
+
+#[test]
+fn responses_without_an_id_fall_back_to_the_conversation() {
+    let turn = AtomicUsize::new(0);
+    let server = MockServer::start(move |_| {
+        let content: &str = ANSWERS[turn.fetch_add(1, Ordering::SeqCst)];
+        let mut response: MockResponse = responses_success("", content);
+        let mut body: Value = serde_json::from_slice(&response.body).unwrap();
+        body.as_object_mut().unwrap().remove("id");
+        response.body = body.to_string().into_bytes();
+        response
+    });
+    let llm = responses_llm(&server);
+    let task = Person::new();
+    let mut session = ExtractionSession::new(&llm, &task);
+
+    let _: Person = session.extract(TARGET, &vec![]).unwrap();
+    assert!(!session.uses_server_state());
+    let person: Person = session.refine(FEEDBACK[0]).unwrap();
+    assert_eq!(person, ada());
+
+    let second: &Value = &server.requests()[1].body;
+    assert_eq!(
+        roles(&second["input"]),
+        vec!["system", "user", "assistant", "user"]
+    );
+    assert!(second.get("previous_response_id").is_none());
+}
+
+#[test]
+fn extract_starts_a_new_conversation() {
+    let server = responses_server();
+    let llm = responses_llm(&server);
+    let task = Person::new();
+    let mut session = ExtractionSession::new(&llm, &task);
+
+    let _: Person = session.extract(TARGET, &vec![]).unwrap();
+    let _: Person = session.extract(TARGET, &vec![]).unwrap();
+
+    assert!(
+        server.requests()[1]
+            .body
+            .get("previous_response_id")
+            .is_none()
+    );
+    assert_eq!(session.turns(), 1);
+    assert_eq!(session.response_id(), Some("resp_2"));
+}
+
+#[test]
+fn generate_data_reads_responses_api_output() {
+    let server = MockServer::always(responses_success("resp_1", ADA_JSON));
+
+    let person: Person = responses_llm(&server)
+        .generate_data(&Person::new(), TARGET, &vec![])
+        .unwrap();
+
+    assert_eq!(person, ada());
+    assert_eq!(server.requests()[0].path, "/responses");
+}
+
+#[tokio::test]
+async fn async_responses_sessions_send_only_the_new_turn() {
+    let server = responses_server();
+    let llm = responses_llm(&server);
+    let task = Person::new();
+    let mut session = ExtractionSession::new(&llm, &task);
+
+    let _: Person = session.async_extract(TARGET, &vec![]).await.unwrap();
+    let _: Person = session.async_refine(FEEDBACK[0]).await.unwrap();
+    let person: Person = session.async_refine(FEEDBACK[1]).await.unwrap();
+    assert_eq!(person.age, 37);
+
+    let requests = server.requests();
+    assert_eq!(roles(&requests[2].body["input"]), vec!["user"]);
+    assert_eq!(requests[2].body["previous_response_id"], json!("resp_2"));
+}
+
+#[tokio::test]
+async fn async_other_providers_resend_the_conversation() {
+    let server = completions_server();
+    let llm: OpenAILLM = server.llm();
+    let task = Person::new();
+    let mut session = ExtractionSession::new(&llm, &task);
+
+    let _: Person = session.async_extract(TARGET, &vec![]).await.unwrap();
+    let person: Person = session.async_refine(FEEDBACK[0]).await.unwrap();
+    assert_eq!(person, ada());
+
+    assert_eq!(
+        roles(&server.requests()[1].body["messages"]),
+        vec!["system", "user", "assistant", "user"]
+    );
+}
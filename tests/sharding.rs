@@ -0,0 +1,121 @@
+//! Items are split between shards by a hash of their ID that is the same in every process,
+//! and changing the number of shards only moves the items it has to.
+
+mod support;
+
+use std::collections::HashSet;
+
+use futures::StreamExt;
+use secretary::sharding::{MovedItem, Shard, moved_items, shard_of};
+use secretary::traits::AsyncGenerateData;
+
+use support::fixtures::success;
+use support::{BoxedError, MockServer, Person};
+
+fn ids(count: usize) -> Vec<String> {
+    (0..count).map(|index| format!("doc-{}", index)).collect()
+}
+
+#[test]
+fn shards_are_stable_across_runs_and_releases() {
+    // Pinned, so that a change of the algorithm cannot go unnoticed
+    let invoices: Vec<usize> = (1..=8)
+        .map(|index| shard_of(&format!("invoice-{}", index), 4))
+        .collect();
+    assert_eq!(invoices, vec![3, 0, 0, 2, 3, 2, 0, 3]);
+    let documents: Vec<usize> = ids(5).iter().map(|id| shard_of(id, 10)).collect();
+    assert_eq!(documents, vec![0, 8, 8, 8, 2]);
+
+    for id in ids(100) {
+        assert_eq!(shard_of(&id, 1), 0);
+        assert!(Shard::of(7, shard_of(&id, 7)).assign(&id));
+    }
+}
+
+#[test]
+fn every_item_belongs_to_exactly_one_shard() {
+    let ids: Vec<String> = ids(1000);
+
+    for count in [1, 2, 3, 5, 8] {
+        let shards: Vec<Shard> = (0..count).map(|index| Shard::of(count, index)).collect();
+        let mut seen: HashSet<&str> = HashSet::new();
+        for shard in &shards {
+            let selected: Vec<&String> = shard.select(&ids, |id| id.as_str());
+            // No shard is left with a small share of the items
+            assert!(selected.len() > 1000 / count / 2, "{:?}", shard);
+            for id in selected {
+                assert!(seen.insert(id.as_str()), "{} is in two shards", id);
+            }
+        }
+        assert_eq!(seen.len(), ids.len());
+    }
+}
+
+#[test]
+fn growing_moves_items_only_into_the_new_shards() {
+    let ids: Vec<String> = ids(1000);
+
+    let moved: Vec<MovedItem> = moved_items(4, 5, &ids);
+    assert!(moved.iter().all(|item| item.to == 4));
+    // About a fifth of the items move
+    assert!((150..250).contains(&moved.len()), "{}", moved.len());
+    for item in &moved {
+        assert_eq!(shard_of(&item.id, 4), item.from);
+        assert_eq!(shard_of(&item.id, 5), item.to);
+    }
+
+    // Shrinking moves the same items back
+    let back: Vec<MovedItem> = moved_items(5, 4, &ids);
+    assert_eq!(back.len(), moved.len());
+    assert!(back.iter().all(|item| item.from == 4));
+
+    assert!(moved_items(3, 3, &ids).is_empty());
+    assert_eq!(
+        moved_items(3, 4, &["invoice-1", "invoice-2", "invoice-5"]),
+        vec![
+            MovedItem {
+                id: "invoice-1".to_string(),
+                from: 2,
+                to: 3,
+            },
+            MovedItem {
+                id: "invoice-5".to_string(),
+                from: 1,
+                to: 3,
+            },
+        ]
+    );
+}
+
+#[test]
+#[should_panic(expected = "shard index 3 is out of range for 3 shard(s)")]
+fn shards_are_numbered_from_zero() {
+    Shard::of(3, 3);
+}
+
+#[tokio::test]
+async fn a_worker_extracts_only_the_targets_of_its_shard() {
+    let server = MockServer::always(success(r#"{"name": "Someone", "age": 36}"#));
+    let llm = server.llm();
+    let task = Person::new();
+    let targets: Vec<String> = ["Ada", "Grace", "Edsger", "Barbara"]
+        .iter()
+        .map(|name| format!("{} is 36 years old.", name))
+        .collect();
+
+    let shard: Shard = Shard::of(2, 0);
+    let selected: Vec<String> = shard.select(targets.clone(), |target| target.as_str());
+    assert_eq!(selected, targets[2..].to_vec());
+
+    let results: Vec<(usize, Result<Person, BoxedError>)> = llm
+        .stream_generate_data_batch(&task, selected, Vec::new(), 2)
+        .collect()
+        .await;
+
+    assert_eq!(results.len(), 2);
+    assert_eq!(server.requests().len(), 2);
+    for request in server.requests() {
+        let prompt: String = request.prompt();
+        assert!(prompt.contains("Edsger") || prompt.contains("Barbara"));
+    }
+}
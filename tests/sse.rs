@@ -0,0 +1,164 @@
+//! Event streams as misbehaving gateways send them are read into the same events however
+//! they are split, and no bytes make the parser panic.
+
+use proptest::prelude::*;
+use secretary::sse::{SseEvent, SseParser};
+
+/// Streams captured from gateways and proxies, with the data of the events they hold.
+const GATEWAY_STREAMS: &[(&str, &[u8], &[&str])] = &[
+    (
+        "keep-alive comments between events",
+        b": keep-alive\n\ndata: {\"n\":1}\n\n: keep-alive\n\n: keep-alive\n\ndata: [DONE]\n\n",
+        &["{\"n\":1}", "[DONE]"],
+    ),
+    (
+        "CRLF line ends",
+        b"data: {\"n\":1}\r\n\r\ndata: {\"n\":2}\r\n\r\ndata: [DONE]\r\n\r\n",
+        &["{\"n\":1}", "{\"n\":2}", "[DONE]"],
+    ),
+    (
+        "lone CR line ends",
+        b"data: {\"n\":1}\r\rdata: [DONE]\r\r",
+        &["{\"n\":1}", "[DONE]"],
+    ),
+    (
+        "empty data events",
+        b"data:\n\ndata: {\"n\":1}\n\ndata: \n\n",
+        &["", "{\"n\":1}", ""],
+    ),
+    (
+        "duplicate done",
+        b"data: {\"n\":1}\n\ndata: [DONE]\n\ndata: [DONE]\n\n",
+        &["{\"n\":1}", "[DONE]", "[DONE]"],
+    ),
+    (
+        "missing done and final blank line",
+        b"data: {\"n\":1}\n\ndata: {\"n\":2}",
+        &["{\"n\":1}", "{\"n\":2}"],
+    ),
+    (
+        "multi-line data with a comment inside",
+        b"data: {\"n\":\n: keep-alive\ndata: 1}\n\n",
+        &["{\"n\":\n1}"],
+    ),
+    (
+        "runs of blank lines",
+        b"\n\n\r\n\ndata: {\"n\":1}\n\n\n\n",
+        &["{\"n\":1}"],
+    ),
+    (
+        "byte order mark",
+        b"\xEF\xBB\xBFdata: {\"n\":1}\n\n",
+        &["{\"n\":1}"],
+    ),
+    (
+        "retry, id and unknown fields",
+        b"retry: 3000\nid: 7\nx-gateway: cf\ndata\ndata:{\"n\":1}\n\n",
+        &["\n{\"n\":1}"],
+    ),
+];
+
+/// Returns the events of a stream fed in the given chunks, with those left at its end.
+fn parse(chunks: &[&[u8]]) -> Vec<SseEvent> {
+    let mut parser = SseParser::new();
+    let mut events: Vec<SseEvent> = chunks.iter().flat_map(|chunk| parser.feed(chunk)).collect();
+    events.extend(parser.finish());
+    events
+}
+
+fn data(events: &[SseEvent]) -> Vec<String> {
+    events
+        .iter()
+        .map(|event| String::from_utf8(event.data.clone()).unwrap())
+        .collect()
+}
+
+#[test]
+fn gateway_streams_are_read_into_their_events() {
+    for (name, stream, expected) in GATEWAY_STREAMS {
+        assert_eq!(data(&parse(&[stream])), *expected, "{}", name);
+    }
+}
+
+#[test]
+fn gateway_streams_are_read_the_same_at_every_split() {
+    for (name, stream, _) in GATEWAY_STREAMS {
+        let whole: Vec<SseEvent> = parse(&[stream]);
+        for split in 0..=stream.len() {
+            assert_eq!(
+                parse(&[&stream[..split], &stream[split..]]),
+                whole,
+                "{} split at {}",
+                name,
+                split
+            );
+        }
+        let bytes: Vec<&[u8]> = stream.chunks(1).collect();
+        assert_eq!(parse(&bytes), whole, "{} byte by byte", name);
+    }
+}
+
+#[test]
+fn events_keep_their_names_and_ids() {
+    let events: Vec<SseEvent> = parse(&[
+        b"event: ping\ndata: {}\n\nid: 41\ndata: {\"n\":1}\n\nevent:\ndata: {\"n\":2}\n\n",
+    ]);
+
+    assert_eq!(events[0].name(), "ping");
+    assert_eq!(events[0].id, None);
+    assert_eq!(events[1].name(), "message");
+    assert_eq!(events[1].id.as_deref(), Some("41"));
+    // An empty name is a message, and the last ID carries over
+    assert_eq!(events[2].name(), "message");
+    assert_eq!(events[2].id.as_deref(), Some("41"));
+}
+
+#[test]
+fn events_without_data_are_not_dispatched() {
+    assert!(parse(&[b"event: ping\n\nid: 3\n\n: comment\n\n"]).is_empty());
+    // The name of an event without data does not carry over to the next one
+    let events: Vec<SseEvent> = parse(&[b"event: ping\n\ndata: {}\n\n"]);
+    assert_eq!(events[0].name(), "message");
+}
+
+/// Pieces of event streams, so that arbitrary sequences reach every state of the parser.
+const TOKENS: &[&[u8]] = &[
+    b"data:",
+    b"data: ",
+    b"event: ping",
+    b"id: 1",
+    b": keep-alive",
+    b"[DONE]",
+    b"{\"n\":1}",
+    b"\r",
+    b"\n",
+    b"\r\n",
+    b":",
+    b" ",
+    b"\xEF\xBB\xBF",
+    b"\xFF\xFE",
+    b"\0",
+];
+
+proptest! {
+    #[test]
+    fn arbitrary_bytes_never_panic_and_split_anywhere(
+        bytes in proptest::collection::vec(any::<u8>(), 0..512),
+        split in 0usize..512,
+    ) {
+        let split: usize = split.min(bytes.len());
+        prop_assert_eq!(parse(&[&bytes[..split], &bytes[split..]]), parse(&[&bytes]));
+    }
+
+    #[test]
+    fn arbitrary_streams_never_panic_and_split_anywhere(
+        tokens in proptest::collection::vec(proptest::sample::select(TOKENS), 0..64),
+        split in 0usize..1024,
+    ) {
+        let bytes: Vec<u8> = tokens.concat();
+        let split: usize = split.min(bytes.len());
+        let events: Vec<SseEvent> = parse(&[&bytes[..split], &bytes[split..]]);
+        prop_assert_eq!(&events, &parse(&[&bytes]));
+        prop_assert!(events.iter().all(|event| !event.data.contains(&b'\r')));
+    }
+}
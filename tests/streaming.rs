@@ -0,0 +1,274 @@
+//! Streamed responses are checked against the Task as they arrive and abandoned at the first
+//! key or value type that departs from it.
+
+mod support;
+
+use secretary::SecretaryError;
+use secretary::Task;
+use secretary::leniency::LeniencyProfile;
+use secretary::request::RequestOptions;
+use secretary::streaming::IncrementalValidator;
+use secretary::traits::{AsyncGenerateData, GenerateData};
+use secretary::validation::ViolationKind;
+
+use support::fixtures::{streamed, success};
+use support::{MockResponse, MockServer, Person, TARGET, ada, secretary_error};
+
+/// The number of filler events streamed after the answer goes wrong.
+const FILLER: usize = 100;
+
+/// Returns the answer split into pieces of a few characters, as a model streams it.
+fn pieces(answer: &str, size: usize) -> Vec<String> {
+    answer
+        .chars()
+        .collect::<Vec<char>>()
+        .chunks(size)
+        .map(|chunk| chunk.iter().collect())
+        .collect()
+}
+
+/// Streams `answer` in pieces of three characters, followed by a long filler value.
+fn streamed_answer(answer: &str) -> MockServer {
+    let mut answer_pieces: Vec<String> = pieces(answer, 3);
+    answer_pieces.extend((0..FILLER).map(|_| "lorem ipsum ".to_string()));
+    answer_pieces.push("\"}".to_string());
+    let pieces: Vec<&str> = answer_pieces.iter().map(String::as_str).collect();
+    MockServer::always(streamed(&pieces))
+}
+
+#[test]
+fn valid_streams_are_assembled_into_the_result() {
+    let server = MockServer::always(streamed(&[
+        "{\"na",
+        "me\": \"Ada {the \\\"first\\\"} [programmer]\", ",
+        "\"age\"",
+        ": 3",
+        "6}",
+    ]));
+
+    let person: Person = server
+        .llm()
+        .generate_data_with_options(
+            &Person::new(),
+            TARGET,
+            vec![],
+            &RequestOptions::default().with_streaming(),
+        )
+        .unwrap();
+
+    assert_eq!(person.name, "Ada {the \"first\"} [programmer]");
+    assert_eq!(person.age, 36);
+    assert_eq!(server.requests()[0].body["stream"], true);
+}
+
+#[test]
+fn an_unknown_key_abandons_the_stream_after_a_few_chunks() {
+    let server = streamed_answer(r#"{"name": "Ada", "nickname": ""#);
+
+    let result: Result<Person, _> = server.llm().generate_data_with_options(
+        &Person::new(),
+        TARGET,
+        vec![],
+        &RequestOptions::default().with_streaming(),
+    );
+
+    let error = result.unwrap_err();
+    let SecretaryError::SchemaViolation {
+        violations,
+        raw_content,
+    } = secretary_error(&error)
+    else {
+        panic!("expected a schema violation, got {:?}", error);
+    };
+    assert_eq!(violations.len(), 1);
+    assert_eq!(violations[0].pointer, "/nickname");
+    assert_eq!(violations[0].kind, ViolationKind::AdditionalProperty);
+    assert!(violations[0].message.contains("nickname"));
+    assert!(raw_content.contains("\"nickname\""));
+    // The key is split across chunks, and the connection is dropped well before the filler
+    assert!(
+        server.events_sent() < 20,
+        "{} events sent",
+        server.events_sent()
+    );
+}
+
+#[tokio::test]
+async fn a_value_of_the_wrong_type_abandons_the_stream() {
+    let server = streamed_answer(r#"{"name": "Ada", "age": "thirty"#);
+
+    let result: Result<Person, _> = server
+        .llm()
+        .async_generate_data_with_options(
+            &Person::new(),
+            TARGET,
+            vec![],
+            &RequestOptions::default().with_streaming(),
+        )
+        .await;
+
+    let error = result.unwrap_err();
+    let SecretaryError::SchemaViolation { violations, .. } = secretary_error(&error) else {
+        panic!("expected a schema violation, got {:?}", error);
+    };
+    assert_eq!(violations[0].pointer, "/age");
+    assert_eq!(violations[0].kind, ViolationKind::Type);
+    assert!(
+        server.events_sent() < 20,
+        "{} events sent",
+        server.events_sent()
+    );
+}
+
+#[tokio::test]
+async fn async_streams_are_assembled_into_the_result() {
+    let server = MockServer::always(streamed(&["{\"name\": \"Ada\",", " \"age\": 36}"]));
+
+    let person: Person = server
+        .llm()
+        .async_generate_data_with_options(
+            &Person::new(),
+            TARGET,
+            vec![],
+            &RequestOptions::default().with_streaming(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(person, ada());
+}
+
+/// Sends a streamed response the way a misbehaving gateway would: with CRLF line ends, each
+/// event split in two writes and preceded by keep-alive comments, pings and empty events,
+/// and a repeated `[DONE]` followed by an event that must be ignored.
+fn through_gateway(response: MockResponse) -> MockResponse {
+    let noise: [&[u8]; 3] = [
+        b": keep-alive\r\n\r\n",
+        b"event: ping\r\ndata: {\"type\": \"ping\"}\r\n\r\n",
+        b"data:\r\n\r\n",
+    ];
+    let mut events: Vec<Vec<u8>> = Vec::new();
+    for event in response.events.unwrap() {
+        events.extend(noise.iter().map(|noise| noise.to_vec()));
+        let event: Vec<u8> = String::from_utf8(event)
+            .unwrap()
+            .replace('\n', "\r\n")
+            .into_bytes();
+        let (head, tail) = event.split_at(event.len() / 2);
+        events.push(head.to_vec());
+        events.push(tail.to_vec());
+    }
+    events.push(b"data: [DONE]\r\n\r\n".to_vec());
+    events.push(b"data: {\"choices\": [{\"index\": 0, \"delta\": {\"content\": \"}\"}}]}".to_vec());
+
+    MockResponse {
+        events: Some(events),
+        ..response
+    }
+}
+
+#[test]
+fn gateway_noise_is_skipped() {
+    let server = MockServer::always(through_gateway(streamed(&[
+        "{\"name\": \"Ada\",",
+        " \"age\": 36}",
+    ])));
+
+    let person: Person = server
+        .llm()
+        .generate_data_with_options(
+            &Person::new(),
+            TARGET,
+            vec![],
+            &RequestOptions::default().with_streaming(),
+        )
+        .unwrap();
+
+    assert_eq!(person, ada());
+}
+
+#[tokio::test]
+async fn streams_closed_without_done_are_assembled() {
+    let mut response: MockResponse = streamed(&["{\"name\": \"Ada\",", " \"age\": 36}"]);
+    let events: &mut Vec<Vec<u8>> = response.events.as_mut().unwrap();
+    // Neither `[DONE]` nor the blank line ending the last event
+    events.pop();
+    let last: &mut Vec<u8> = events.last_mut().unwrap();
+    last.truncate(last.len() - 2);
+    let server = MockServer::always(response);
+
+    let person: Person = server
+        .llm()
+        .async_generate_data_with_options(
+            &Person::new(),
+            TARGET,
+            vec![],
+            &RequestOptions::default().with_streaming(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(person, ada());
+}
+
+#[test]
+fn responses_that_are_not_streamed_are_read_as_usual() {
+    let server = MockServer::always(success(r#"{"name": "Ada", "age": 36}"#));
+
+    let person: Person = server
+        .llm()
+        .generate_data_with_options(
+            &Person::new(),
+            TARGET,
+            vec![],
+            &RequestOptions::default().with_streaming(),
+        )
+        .unwrap();
+
+    assert_eq!(person, ada());
+    assert_eq!(server.requests()[0].body["stream"], true);
+}
+
+#[test]
+fn values_the_parser_coerces_are_accepted() {
+    let fields = Person::field_descriptors();
+
+    let mut strict = IncrementalValidator::new(&fields, LeniencyProfile::strict());
+    assert!(strict.feed(r#"{"age": "36""#).is_err());
+
+    let mut standard = IncrementalValidator::new(&fields, LeniencyProfile::standard());
+    standard.feed(r#"{"age": "36", "name": "Ada"}"#).unwrap();
+    assert!(standard.is_complete());
+}
+
+#[test]
+fn keys_are_followed_through_escapes_and_nested_values() {
+    let fields = Person::field_descriptors();
+
+    // A `\u` escape split across chunks spells a known key
+    let mut validator = IncrementalValidator::new(&fields, LeniencyProfile::strict());
+    for chunk in [r#"{"n\u00"#, r#"61me": "Ada", "a"#, r#"ge": 36"#, "}"] {
+        validator.feed(chunk).unwrap();
+    }
+    assert!(validator.is_complete());
+
+    // Text before the object, such as a code fence, is skipped
+    let mut validator = IncrementalValidator::new(&fields, LeniencyProfile::strict());
+    for chunk in ["```js", "on\n{\"name\": \"Ada\", ", "\"age\": 36}\n```"] {
+        validator.feed(chunk).unwrap();
+    }
+    assert!(validator.is_complete());
+
+    // Quotes and braces inside strings are not structure
+    let mut validator = IncrementalValidator::new(&fields, LeniencyProfile::strict());
+    let violation = validator
+        .feed(r#"{"name": "\"}, \"age\": 1, {\"", "extra": 1}"#)
+        .unwrap_err();
+    assert_eq!(violation.pointer, "/extra");
+
+    // Nothing is checked after a violation or outside the top-level object
+    assert!(validator.feed(r#", "more": 2}"#).is_ok());
+    let mut validator = IncrementalValidator::new(&fields, LeniencyProfile::strict());
+    assert!(validator.feed("Sorry, I cannot help with that.").is_ok());
+    assert!(!validator.is_complete());
+}
@@ -0,0 +1,314 @@
+//! The canonical chat completions response shapes.
+
+use serde_json::json;
+
+use super::MockResponse;
+
+/// A successful completion whose message content is `content`.
+pub fn success(content: &str) -> MockResponse {
+    MockResponse::new(
+        200,
+        json!({
+            "id": "chatcmpl-mock",
+            "object": "chat.completion",
+            "model": "test-model",
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": content, "refusal": null},
+                "finish_reason": "stop"
+            }],
+            "usage": {"prompt_tokens": 50, "completion_tokens": 10, "total_tokens": 60}
+        }),
+    )
+}
+
+/// A successful completion with one choice per content, as returned for a request with `n`.
+pub fn choices(contents: &[&str]) -> MockResponse {
+    let choices: Vec<serde_json::Value> = contents
+        .iter()
+        .enumerate()
+        .map(|(index, content)| {
+            json!({
+                "index": index,
+                "message": {"role": "assistant", "content": content, "refusal": null},
+                "finish_reason": "stop"
+            })
+        })
+        .collect();
+    MockResponse::new(
+        200,
+        json!({
+            "id": "chatcmpl-mock",
+            "object": "chat.completion",
+            "model": "test-model",
+            "choices": choices,
+            "usage": {"prompt_tokens": 50, "completion_tokens": 30, "total_tokens": 80}
+        }),
+    )
+}
+
+/// A successful completion answering a field request with `value` in result tags.
+pub fn field_result(value: &str) -> MockResponse {
+    success(&format!("<result>{}</result>", value))
+}
+
+/// A successful response without any choices.
+pub fn empty_choices() -> MockResponse {
+    MockResponse::new(
+        200,
+        json!({
+            "id": "chatcmpl-mock",
+            "object": "chat.completion",
+            "model": "test-model",
+            "choices": []
+        }),
+    )
+}
+
+/// A rejected request with an OpenAI error body.
+pub fn error_body() -> MockResponse {
+    MockResponse::new(
+        400,
+        json!({
+            "error": {
+                "message": "This model's maximum context length is 8192 tokens.",
+                "type": "invalid_request_error",
+                "param": "messages",
+                "code": "context_length_exceeded"
+            }
+        }),
+    )
+}
+
+/// A completion withheld by the provider's content filter.
+pub fn content_filter() -> MockResponse {
+    MockResponse::new(
+        200,
+        json!({
+            "id": "chatcmpl-mock",
+            "object": "chat.completion",
+            "model": "test-model",
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": null},
+                "finish_reason": "content_filter"
+            }]
+        }),
+    )
+}
+
+/// A completion cut off at the token limit in the middle of the JSON.
+pub fn truncated() -> MockResponse {
+    MockResponse::new(
+        200,
+        json!({
+            "id": "chatcmpl-mock",
+            "object": "chat.completion",
+            "model": "test-model",
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": r#"{"name": "Ada", "ag"#},
+                "finish_reason": "length"
+            }]
+        }),
+    )
+}
+
+/// A reasoning model's completion with its thinking before the JSON.
+pub fn think_blocks(json: &str) -> MockResponse {
+    success(&format!(
+        "<think>The text names {{the person}} and gives an age.</think>\n{}",
+        json
+    ))
+}
+
+/// A DeepSeek reasoning completion, with the reasoning as `reasoning_content` apart from the
+/// answer.
+pub fn deepseek_reasoning(reasoning: &str, content: &str) -> MockResponse {
+    MockResponse::new(
+        200,
+        json!({
+            "id": "chatcmpl-mock",
+            "object": "chat.completion",
+            "model": "deepseek-reasoner",
+            "choices": [{
+                "index": 0,
+                "message": {
+                    "role": "assistant",
+                    "content": content,
+                    "reasoning_content": reasoning
+                },
+                "finish_reason": "stop"
+            }],
+            "usage": {
+                "prompt_tokens": 50,
+                "completion_tokens": 130,
+                "total_tokens": 180,
+                "completion_tokens_details": {"reasoning_tokens": 120}
+            }
+        }),
+    )
+}
+
+/// An o-series completion, which counts its hidden reasoning in the usage only.
+pub fn o_series(content: &str) -> MockResponse {
+    MockResponse::new(
+        200,
+        json!({
+            "id": "chatcmpl-mock",
+            "object": "chat.completion",
+            "model": "o3-mini",
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": content, "refusal": null},
+                "finish_reason": "stop"
+            }],
+            "usage": {
+                "prompt_tokens": 50,
+                "completion_tokens": 522,
+                "total_tokens": 572,
+                "prompt_tokens_details": {"cached_tokens": 0},
+                "completion_tokens_details": {"reasoning_tokens": 512}
+            }
+        }),
+    )
+}
+
+/// A completion with the JSON in a markdown fence.
+pub fn fenced_json(json: &str) -> MockResponse {
+    success(&format!("Here is the result:\n```json\n{}\n```", json))
+}
+
+/// A successful Bedrock Converse response whose message text is `content`.
+pub fn converse_success(content: &str) -> MockResponse {
+    MockResponse::new(
+        200,
+        json!({
+            "output": {
+                "message": {"role": "assistant", "content": [{"text": content}]}
+            },
+            "stopReason": "end_turn",
+            "usage": {"inputTokens": 50, "outputTokens": 10, "totalTokens": 60},
+            "metrics": {"latencyMs": 412}
+        }),
+    )
+}
+
+/// A Converse response of a reasoning model, with a reasoning block before the text.
+pub fn converse_reasoning(content: &str) -> MockResponse {
+    MockResponse::new(
+        200,
+        json!({
+            "output": {
+                "message": {
+                    "role": "assistant",
+                    "content": [
+                        {
+                            "reasoningContent": {
+                                "reasoningText": {
+                                    "text": "The text names the person and gives an age.",
+                                    "signature": "ErUBCkgIAhABGAIiQJ"
+                                }
+                            }
+                        },
+                        {"text": content}
+                    ]
+                }
+            },
+            "stopReason": "end_turn",
+            "usage": {"inputTokens": 50, "outputTokens": 40, "totalTokens": 90},
+            "metrics": {"latencyMs": 1630}
+        }),
+    )
+}
+
+/// A Converse response cut off at the token limit in the middle of the JSON.
+pub fn converse_max_tokens() -> MockResponse {
+    MockResponse::new(
+        200,
+        json!({
+            "output": {
+                "message": {"role": "assistant", "content": [{"text": r#"{"name": "Ada", "ag"#}]}
+            },
+            "stopReason": "max_tokens",
+            "usage": {"inputTokens": 50, "outputTokens": 8, "totalTokens": 58},
+            "metrics": {"latencyMs": 233}
+        }),
+    )
+}
+
+/// A Converse request rejected by Bedrock's validation.
+pub fn converse_validation_error() -> MockResponse {
+    MockResponse::new(
+        400,
+        json!({
+            "message": "The model returned the following errors: Input is too long for requested model."
+        }),
+    )
+}
+
+/// A Converse request throttled by Bedrock.
+pub fn converse_throttled() -> MockResponse {
+    MockResponse::new(
+        429,
+        json!({"message": "Too many requests, please wait before trying again."}),
+    )
+}
+
+/// A successful Responses API response with the ID `id` whose output text is `content`.
+pub fn responses_success(id: &str, content: &str) -> MockResponse {
+    MockResponse::new(
+        200,
+        json!({
+            "id": id,
+            "object": "response",
+            "status": "completed",
+            "model": "test-model",
+            "output": [
+                {"type": "reasoning", "id": "rs_mock", "summary": []},
+                {
+                    "type": "message",
+                    "id": "msg_mock",
+                    "role": "assistant",
+                    "status": "completed",
+                    "content": [{"type": "output_text", "text": content, "annotations": []}]
+                }
+            ],
+            "usage": {"input_tokens": 50, "output_tokens": 10, "total_tokens": 60}
+        }),
+    )
+}
+
+/// A response that reports the backend configuration that served it as `system_fingerprint`.
+pub fn fingerprinted(response: MockResponse, fingerprint: &str) -> MockResponse {
+    let mut body: serde_json::Value = serde_json::from_slice(&response.body).unwrap();
+    body["system_fingerprint"] = json!(fingerprint);
+    MockResponse::new(response.status, body)
+}
+
+/// A streamed completion whose message content arrives in `pieces`, one event each, followed
+/// by the event with the finish reason and `[DONE]`.
+pub fn streamed(pieces: &[&str]) -> MockResponse {
+    let chunk = |delta: serde_json::Value, finish_reason: serde_json::Value| {
+        let event = json!({
+            "id": "chatcmpl-mock",
+            "object": "chat.completion.chunk",
+            "model": "test-model",
+            "choices": [{"index": 0, "delta": delta, "finish_reason": finish_reason}]
+        });
+        format!("data: {}\n\n", event)
+    };
+
+    let mut events: Vec<String> = vec![chunk(
+        json!({"role": "assistant", "content": ""}),
+        json!(null),
+    )];
+    events.extend(
+        pieces
+            .iter()
+            .map(|piece| chunk(json!({"content": piece}), json!(null))),
+    );
+    events.push(chunk(json!({}), json!("stop")));
+    events.push("data: [DONE]\n\n".to_string());
+    MockResponse::streamed(events)
+}
@@ -0,0 +1,442 @@
+//! A mock chat completions server for the integration tests.
+//!
+//! `MockServer` answers every request on its own thread with the `MockResponse` its responder
+//! picks for the recorded request, so concurrent field requests can be answered by what they
+//! ask for. The canonical response shapes are in `fixtures`.
+
+#![allow(dead_code)]
+
+pub mod fixtures;
+
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use secretary::SecretaryError;
+use secretary::Task;
+use secretary::llm_providers::openai::OpenAILLM;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// The Task most tests extract.
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Person {
+    #[task(instruction = "Extract the person's name")]
+    pub name: String,
+    #[task(instruction = "Extract the age as a number")]
+    pub age: u32,
+}
+
+/// The JSON a model returns for Ada, 36.
+pub const ADA_JSON: &str = r#"{"name": "Ada", "age": 36}"#;
+
+/// Ada, 36.
+pub fn ada() -> Person {
+    Person {
+        name: "Ada".to_string(),
+        age: 36,
+    }
+}
+
+/// The error type of the generate methods.
+pub type BoxedError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+/// The text most tests extract from.
+pub const TARGET: &str = "Ada is 36 years old.";
+
+/// Returns the `SecretaryError` behind a boxed error.
+pub fn secretary_error(error: &BoxedError) -> &SecretaryError {
+    error
+        .downcast_ref::<SecretaryError>()
+        .unwrap_or_else(|| panic!("not a SecretaryError: {}", error))
+}
+
+/// Asserts that a call failed because the response carried no message content.
+pub fn assert_no_response<T: std::fmt::Debug>(result: Result<T, BoxedError>) {
+    let error: BoxedError = result.unwrap_err();
+    assert!(
+        matches!(secretary_error(&error), SecretaryError::NoLLMResponse),
+        "unexpected error: {}",
+        error
+    );
+}
+
+/// Asserts that a call failed to parse the JSON and returns the raw content it kept.
+pub fn assert_malformed_json<T: std::fmt::Debug>(result: Result<T, BoxedError>) -> String {
+    let error: BoxedError = result.unwrap_err();
+    match secretary_error(&error) {
+        SecretaryError::JsonParsingError { raw_content, .. } => raw_content.clone(),
+        other => panic!("unexpected error: {}", other),
+    }
+}
+
+/// Asserts that a field by field call failed on exactly the `age` field.
+pub fn assert_age_failed<T: std::fmt::Debug>(result: Result<T, BoxedError>) {
+    let error: BoxedError = result.unwrap_err();
+    match secretary_error(&error) {
+        SecretaryError::FieldDeserializationError(error) => {
+            assert_eq!(error.failed_fields, vec!["age"]);
+            assert_eq!(error.successful_fields, vec!["name"]);
+        }
+        other => panic!("unexpected error: {}", other),
+    }
+}
+
+/// A server answering the name request with Ada and the age request with `age`.
+pub fn fields_server(age: MockResponse) -> MockServer {
+    MockServer::by_instruction(
+        vec![
+            ("Extract the person's name", fixtures::field_result("Ada")),
+            ("Extract the age as a number", age),
+        ],
+        fixtures::empty_choices(),
+    )
+}
+
+/// A request received by the mock server.
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    /// The request path, e.g. `/chat/completions`.
+    pub path: String,
+    /// The headers, with lowercase names.
+    pub headers: BTreeMap<String, String>,
+    /// The JSON body.
+    pub body: Value,
+    /// The body as it was sent, decompressed when it was sent gzip-compressed.
+    pub raw_body: String,
+    /// The size of the body in bytes as it was sent.
+    pub sent_bytes: usize,
+}
+
+impl RecordedRequest {
+    /// Returns whether the request asks for the provider's JSON mode.
+    pub fn is_json_mode(&self) -> bool {
+        self.body.get("response_format").is_some()
+    }
+
+    /// Returns the contents of all messages, one after the other.
+    ///
+    /// Messages whose content is a list of blocks, as in Bedrock's Converse API, contribute
+    /// the text of each block, and Converse system blocks come first. The `input` messages of
+    /// the Responses API are read like `messages`.
+    pub fn prompt(&self) -> String {
+        let mut texts: Vec<&str> = block_texts(&self.body["system"]);
+        let messages: &Value = match self.body.get("input") {
+            Some(input) => input,
+            None => &self.body["messages"],
+        };
+        if let Some(messages) = messages.as_array() {
+            for message in messages {
+                match message["content"].as_str() {
+                    Some(content) => texts.push(content),
+                    None => texts.extend(block_texts(&message["content"])),
+                }
+            }
+        }
+
+        texts.join("\n")
+    }
+}
+
+/// Returns the text of each block in a list of content blocks.
+fn block_texts(blocks: &Value) -> Vec<&str> {
+    blocks
+        .as_array()
+        .map(|blocks| {
+            blocks
+                .iter()
+                .filter_map(|block| block["text"].as_str())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// A response of the mock server.
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    /// The HTTP status code.
+    pub status: u16,
+    /// The body, sent as `application/json`.
+    pub body: Vec<u8>,
+    /// Headers sent besides the content type and length.
+    pub headers: Vec<(String, String)>,
+    /// The events of a streamed response, sent as `text/event-stream` one at a time, with a
+    /// pause after each, instead of the body.
+    pub events: Option<Vec<Vec<u8>>>,
+}
+
+impl MockResponse {
+    /// Creates a response with a JSON body.
+    pub fn new(status: u16, body: Value) -> Self {
+        Self {
+            status,
+            body: body.to_string().into_bytes(),
+            headers: Vec::new(),
+            events: None,
+        }
+    }
+
+    /// Creates a successful streamed response that sends the events one at a time.
+    pub fn streamed(events: Vec<String>) -> Self {
+        Self {
+            status: 200,
+            body: Vec::new(),
+            headers: Vec::new(),
+            events: Some(events.into_iter().map(String::into_bytes).collect()),
+        }
+    }
+
+    /// Sends the body gzip-compressed with `Content-Encoding: gzip`.
+    pub fn gzipped(mut self) -> Self {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&self.body).unwrap();
+        self.body = encoder.finish().unwrap();
+        self.with_header("Content-Encoding", "gzip")
+    }
+
+    /// Adds a header to the response.
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+type Responder = dyn Fn(&RecordedRequest) -> MockResponse + Send + Sync;
+
+/// A local chat completions server.
+pub struct MockServer {
+    address: String,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+    events_sent: Arc<AtomicUsize>,
+    connections: Arc<AtomicUsize>,
+}
+
+impl MockServer {
+    /// Starts a server that answers each request with what `responder` returns for it.
+    pub fn start<F>(responder: F) -> Self
+    where
+        F: Fn(&RecordedRequest) -> MockResponse + Send + Sync + 'static,
+    {
+        Self::serve(responder, false)
+    }
+
+    /// Starts a server like `start` that keeps each connection open for further requests.
+    pub fn keep_alive<F>(responder: F) -> Self
+    where
+        F: Fn(&RecordedRequest) -> MockResponse + Send + Sync + 'static,
+    {
+        Self::serve(responder, true)
+    }
+
+    fn serve<F>(responder: F, keep_alive: bool) -> Self
+    where
+        F: Fn(&RecordedRequest) -> MockResponse + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        let requests: Arc<Mutex<Vec<RecordedRequest>>> = Arc::new(Mutex::new(Vec::new()));
+        let responder: Arc<Responder> = Arc::new(responder);
+        let events_sent: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(0));
+        let connections: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(0));
+
+        let recorded = requests.clone();
+        let sent = events_sent.clone();
+        let accepted = connections.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else { continue };
+                accepted.fetch_add(1, Ordering::SeqCst);
+                let recorded = recorded.clone();
+                let responder = responder.clone();
+                let sent = sent.clone();
+                std::thread::spawn(move || {
+                    handle(stream, &recorded, responder.as_ref(), &sent, keep_alive)
+                });
+            }
+        });
+
+        Self {
+            address,
+            requests,
+            events_sent,
+            connections,
+        }
+    }
+
+    /// Starts a server that answers every request with the same response.
+    pub fn always(response: MockResponse) -> Self {
+        Self::start(move |_| response.clone())
+    }
+
+    /// Starts a server that answers field requests by their instruction.
+    ///
+    /// Each request is answered with the content of the first pair whose instruction appears
+    /// in its prompt, and with `fallback` when none does.
+    pub fn by_instruction(
+        answers: Vec<(&'static str, MockResponse)>,
+        fallback: MockResponse,
+    ) -> Self {
+        Self::start(move |request| {
+            let prompt: String = request.prompt();
+            answers
+                .iter()
+                .find(|(instruction, _)| prompt.contains(instruction))
+                .map(|(_, response)| response.clone())
+                .unwrap_or_else(|| fallback.clone())
+        })
+    }
+
+    /// Returns the base URL of the server.
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Returns an OpenAI provider pointed at the server.
+    pub fn llm(&self) -> OpenAILLM {
+        OpenAILLM::new(&self.address, "test-key", "test-model").unwrap()
+    }
+
+    /// Returns the requests received so far, in the order they were read.
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Returns how many events of streamed responses were written before the client stopped
+    /// reading, over all requests.
+    pub fn events_sent(&self) -> usize {
+        self.events_sent.load(Ordering::SeqCst)
+    }
+
+    /// Returns how many connections the server accepted.
+    pub fn connections(&self) -> usize {
+        self.connections.load(Ordering::SeqCst)
+    }
+}
+
+/// Reads requests from a connection and writes the response chosen for each, until the
+/// connection is closed or, without `keep_alive`, after the first.
+fn handle(
+    mut stream: TcpStream,
+    recorded: &Mutex<Vec<RecordedRequest>>,
+    responder: &Responder,
+    events_sent: &AtomicUsize,
+    keep_alive: bool,
+) {
+    let mut pending: Vec<u8> = Vec::new();
+    while let Some((head, sent_body)) = read_request(&mut stream, &mut pending) {
+        let request: RecordedRequest = parse_request(head, sent_body);
+        let response: MockResponse = responder(&request);
+        recorded.lock().unwrap().push(request);
+        if !respond(&mut stream, &response, events_sent, keep_alive) || !keep_alive {
+            return;
+        }
+    }
+}
+
+/// Reads the head and body of the next request, keeping any bytes after it in `pending`.
+fn read_request(stream: &mut TcpStream, pending: &mut Vec<u8>) -> Option<(String, Vec<u8>)> {
+    let mut buffer = [0u8; 4096];
+    loop {
+        if let Some(header_end) = pending.windows(4).position(|window| window == b"\r\n\r\n") {
+            let head: String = String::from_utf8_lossy(&pending[..header_end]).to_string();
+            let length: usize = head
+                .lines()
+                .find_map(|line| {
+                    line.to_lowercase()
+                        .strip_prefix("content-length:")
+                        .map(|value| value.trim().parse().unwrap())
+                })
+                .unwrap_or(0);
+            if pending.len() >= header_end + 4 + length {
+                let sent_body: Vec<u8> = pending[header_end + 4..header_end + 4 + length].to_vec();
+                pending.drain(..header_end + 4 + length);
+                return Some((head, sent_body));
+            }
+        }
+        match stream.read(&mut buffer) {
+            Ok(0) | Err(_) => return None,
+            Ok(read) => pending.extend_from_slice(&buffer[..read]),
+        }
+    }
+}
+
+/// Parses the head and body of a request.
+fn parse_request(head: String, sent_body: Vec<u8>) -> RecordedRequest {
+    let headers: BTreeMap<String, String> = head
+        .lines()
+        .skip(1)
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_lowercase(), value.trim().to_string()))
+        .collect();
+    let body: String = if headers.get("content-encoding").map(String::as_str) == Some("gzip") {
+        let mut decoded = String::new();
+        GzDecoder::new(sent_body.as_slice())
+            .read_to_string(&mut decoded)
+            .unwrap();
+        decoded
+    } else {
+        String::from_utf8_lossy(&sent_body).to_string()
+    };
+    RecordedRequest {
+        path: head
+            .split_whitespace()
+            .nth(1)
+            .unwrap_or_default()
+            .to_string(),
+        headers,
+        body: serde_json::from_str(&body).unwrap_or(Value::Null),
+        raw_body: body,
+        sent_bytes: sent_body.len(),
+    }
+}
+
+/// Writes a response and returns whether the connection can take another request.
+fn respond(
+    stream: &mut TcpStream,
+    response: &MockResponse,
+    events_sent: &AtomicUsize,
+    keep_alive: bool,
+) -> bool {
+    let headers: String = response
+        .headers
+        .iter()
+        .map(|(name, value)| format!("{}: {}\r\n", name, value))
+        .collect();
+    if let Some(events) = &response.events {
+        let _ = write!(
+            stream,
+            "HTTP/1.1 {} Mock\r\nContent-Type: text/event-stream\r\n{}Connection: close\r\n\r\n",
+            response.status, headers,
+        );
+        for event in events {
+            if stream
+                .write_all(event)
+                .and_then(|_| stream.flush())
+                .is_err()
+            {
+                return false;
+            }
+            events_sent.fetch_add(1, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        return false;
+    }
+
+    let connection: &str = if keep_alive { "keep-alive" } else { "close" };
+    write!(
+        stream,
+        "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\n{}Connection: {}\r\n\r\n",
+        response.status,
+        response.body.len(),
+        headers,
+        connection,
+    )
+    .and_then(|_| stream.write_all(&response.body))
+    .is_ok()
+}
@@ -0,0 +1,148 @@
+//! System messages are sent with the role, or merged into the user message, that the model
+//! accepts.
+
+mod support;
+
+use secretary::llm_providers::openai::OpenAILLM;
+use secretary::llm_providers::responses::ResponsesApiLLM;
+use secretary::message::{Role, SystemRoleStrategy};
+use secretary::session::ExtractionSession;
+use secretary::traits::{AsyncGenerateData, GenerateData};
+use serde_json::Value;
+
+use support::fixtures::{responses_success, success};
+use support::{ADA_JSON, MockServer, Person, TARGET, ada};
+
+fn llm(server: &MockServer, model: &str) -> OpenAILLM {
+    OpenAILLM::new(server.address(), "test-key", model).unwrap()
+}
+
+fn roles(messages: &Value) -> Vec<&str> {
+    messages
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|message| message["role"].as_str().unwrap())
+        .collect()
+}
+
+/// Extracts Ada with `llm` and returns the messages it sent.
+fn sent_messages(server: &MockServer, llm: &OpenAILLM) -> Value {
+    let person: Person = llm.generate_data(&Person::new(), TARGET, vec![]).unwrap();
+    assert_eq!(person, ada());
+    server.requests().last().unwrap().body["messages"].clone()
+}
+
+#[test]
+fn system_strategy_keeps_the_system_message() {
+    let server = MockServer::always(success(ADA_JSON));
+
+    let messages: Value = sent_messages(&server, &server.llm());
+
+    assert_eq!(roles(&messages), vec!["system", "user"]);
+    assert!(
+        messages[0]["content"]
+            .as_str()
+            .unwrap()
+            .contains("Extract the person's name")
+    );
+}
+
+#[test]
+fn developer_strategy_sends_a_developer_message() {
+    let server = MockServer::always(success(ADA_JSON));
+    let llm = server
+        .llm()
+        .with_system_role_strategy(SystemRoleStrategy::Developer);
+
+    let messages: Value = sent_messages(&server, &llm);
+
+    assert_eq!(roles(&messages), vec!["developer", "user"]);
+}
+
+#[test]
+fn prepend_strategy_merges_the_system_prompt_into_the_user_message() {
+    let server = MockServer::always(success(ADA_JSON));
+    let llm = server
+        .llm()
+        .with_system_role_strategy(SystemRoleStrategy::PrependToUser);
+
+    let messages: Value = sent_messages(&server, &llm);
+
+    assert_eq!(roles(&messages), vec!["user"]);
+    let content: &str = messages[0]["content"].as_str().unwrap();
+    let instruction: usize = content.find("Extract the person's name").unwrap();
+    let target: usize = content.find(TARGET).unwrap();
+    assert!(instruction < target);
+    assert!(content.contains("\n\n"));
+}
+
+#[test]
+fn strategy_is_chosen_from_the_model_name() {
+    let server = MockServer::always(success(ADA_JSON));
+
+    let cases: Vec<(&str, Vec<&str>)> = vec![
+        ("o1-mini", vec!["user"]),
+        ("o1-mini-2024-09-12", vec!["user"]),
+        ("openai/o1-preview", vec!["user"]),
+        ("o1", vec!["developer", "user"]),
+        ("o3-mini", vec!["developer", "user"]),
+        ("gpt-4o", vec!["system", "user"]),
+        ("o1x", vec!["system", "user"]),
+    ];
+    for (model, expected) in cases {
+        let messages: Value = sent_messages(&server, &llm(&server, model));
+        assert_eq!(roles(&messages), expected, "{}", model);
+    }
+
+    // An explicit strategy overrides the model name
+    let llm = llm(&server, "o1-mini").with_system_role_strategy(SystemRoleStrategy::System);
+    assert_eq!(roles(&sent_messages(&server, &llm)), vec!["system", "user"]);
+}
+
+#[test]
+fn conversations_are_rewritten_on_every_turn() {
+    let server = MockServer::always(success(ADA_JSON));
+    let llm = llm(&server, "o1-mini");
+    let task = Person::new();
+    let mut session = ExtractionSession::new(&llm, &task);
+
+    let _: Person = session.extract(TARGET, &vec![]).unwrap();
+    let _: Person = session.refine("Capitalize the name.").unwrap();
+
+    let requests = server.requests();
+    assert_eq!(
+        roles(&requests[1].body["messages"]),
+        vec!["user", "assistant", "user"]
+    );
+    // The history keeps the system message, which is only rewritten on the wire
+    assert_eq!(session.history()[0].role, Role::System);
+}
+
+#[test]
+fn responses_api_input_uses_the_strategy() {
+    let server = MockServer::always(responses_success("resp_1", ADA_JSON));
+    let llm = ResponsesApiLLM::new(server.address(), "test-key", "o3-mini").unwrap();
+
+    let person: Person = llm.generate_data(&Person::new(), TARGET, vec![]).unwrap();
+
+    assert_eq!(person, ada());
+    assert_eq!(
+        roles(&server.requests()[0].body["input"]),
+        vec!["developer", "user"]
+    );
+}
+
+#[tokio::test]
+async fn async_requests_use_the_strategy() {
+    let server = MockServer::always(success(ADA_JSON));
+    let llm = llm(&server, "o1-mini");
+
+    let person: Person = llm
+        .async_generate_data(&Person::new(), TARGET, vec![])
+        .await
+        .unwrap();
+
+    assert_eq!(person, ada());
+    assert_eq!(roles(&server.requests()[0].body["messages"]), vec!["user"]);
+}
@@ -0,0 +1,143 @@
+//! Tables are normalized before they are sent, and long tables are split between rows with
+//! the header repeated in every chunk.
+
+mod support;
+
+use secretary::Task;
+use secretary::tabular::{TableOptions, async_extract_table, extract_table};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use support::fixtures::success;
+use support::{MockServer, RecordedRequest};
+
+#[derive(Task, Serialize, Deserialize, Debug, PartialEq)]
+struct LineItem {
+    #[task(instruction = "The item name")]
+    pub item: String,
+    #[task(instruction = "The quantity as a number")]
+    pub quantity: u32,
+    #[task(instruction = "The unit, if given")]
+    pub unit: Option<String>,
+}
+
+/// A model that turns every `item | quantity` line after the header into a row, and leaves
+/// out the unit cell.
+fn table_server() -> MockServer {
+    MockServer::start(|request| {
+        let table: &str = request.body["messages"][1]["content"].as_str().unwrap();
+        let rows: Vec<Value> = table
+            .lines()
+            .filter(|line| line.contains(" | ") && !line.starts_with("Item"))
+            .map(|line| {
+                let cells: Vec<&str> = line.split(" | ").collect();
+                json!({"item": cells[0], "quantity": cells[1].parse::<u32>().unwrap()})
+            })
+            .collect();
+        success(&json!({ "rows": rows }).to_string())
+    })
+}
+
+/// A table of 300 parts, numbered from 1.
+fn long_table() -> String {
+    let mut table = String::from("Item | Quantity");
+    for number in 1..=300 {
+        table.push_str(&format!("\nPart {} | {}", number, number));
+    }
+    table
+}
+
+#[test]
+fn markdown_tables_are_normalized_before_they_are_sent() {
+    let server = table_server();
+    let table = "Order 1042:\n\n\
+                 | Item   |  Quantity |\n\
+                 |:-------|----------:|\n\
+                 | Bolts  |        20 |\n\
+                 | Nuts   |         5 |";
+
+    let rows: Vec<LineItem> =
+        extract_table(&server.llm(), table, &[], &TableOptions::default()).unwrap();
+
+    assert_eq!(
+        rows,
+        vec![
+            LineItem {
+                item: "Bolts".to_string(),
+                quantity: 20,
+                unit: None
+            },
+            LineItem {
+                item: "Nuts".to_string(),
+                quantity: 5,
+                unit: None
+            },
+        ]
+    );
+    let body: &Value = &server.requests()[0].body;
+    assert!(
+        body["messages"][0]["content"]
+            .as_str()
+            .unwrap()
+            .contains(r#"{"rows": [...]}"#)
+    );
+    assert_eq!(
+        body["messages"][1]["content"],
+        "This is the text containing the table:\nOrder 1042:\n\nItem | Quantity\nBolts | 20\nNuts | 5"
+    );
+}
+
+#[test]
+fn a_table_without_data_rows_has_no_rows() {
+    let server = table_server();
+
+    let rows: Vec<LineItem> = extract_table(
+        &server.llm(),
+        "| Item | Quantity |\n|---|---|",
+        &[],
+        &TableOptions::default(),
+    )
+    .unwrap();
+
+    assert!(rows.is_empty());
+}
+
+#[test]
+fn long_tables_are_split_with_the_header_in_every_chunk() {
+    let server = table_server();
+    let options = TableOptions::default()
+        .with_chunk_size(1000)
+        .with_concurrency(3);
+
+    let rows: Vec<LineItem> = extract_table(&server.llm(), &long_table(), &[], &options).unwrap();
+
+    assert_eq!(rows.len(), 300);
+    assert!(
+        rows.iter()
+            .enumerate()
+            .all(|(index, row)| row.quantity == index as u32 + 1)
+    );
+    let requests: Vec<RecordedRequest> = server.requests();
+    assert!(requests.len() > 1);
+    assert!(
+        requests
+            .iter()
+            .all(|request| request.raw_body.contains(r#"table:\nItem | Quantity\n"#))
+    );
+}
+
+#[tokio::test]
+async fn async_tables_are_split_in_the_same_way() {
+    let server = table_server();
+    let options = TableOptions::default()
+        .with_chunk_size(1000)
+        .with_concurrency(3);
+
+    let rows: Vec<LineItem> = async_extract_table(&server.llm(), &long_table(), &[], &options)
+        .await
+        .unwrap();
+
+    assert_eq!(rows.len(), 300);
+    assert_eq!(rows[299].item, "Part 300");
+    assert!(server.requests().len() > 1);
+}
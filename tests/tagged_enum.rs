@@ -0,0 +1,267 @@
+//! Enums of Tasks, internally tagged by serde, are extracted in one request that picks the
+//! variant and extracts its fields.
+
+mod support;
+
+use secretary::SecretaryError;
+use secretary::Task;
+use secretary::schema::JsonType;
+use secretary::traits::{AsyncGenerateData, GenerateData};
+use serde::{Deserialize, Serialize};
+
+use support::fixtures::success;
+use support::{MockServer, secretary_error};
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Invoice {
+    #[task(instruction = "Extract the invoice number")]
+    pub invoice_number: String,
+    #[task(instruction = "Extract the amount due")]
+    pub amount_due: f64,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Receipt {
+    #[task(instruction = "Extract the merchant name")]
+    pub merchant: String,
+    #[task(instruction = "Extract the total paid")]
+    pub total: f64,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct PurchaseOrder {
+    #[task(instruction = "Extract the purchase order number")]
+    pub order_number: String,
+    #[task(instruction = "Extract the names of the ordered items")]
+    pub items: Vec<String>,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "doc_type", rename_all = "snake_case")]
+enum Document {
+    #[task(instruction = "A bill from a supplier that asks for payment")]
+    Invoice(Invoice),
+    #[task(instruction = "A proof of a payment already made")]
+    Receipt(Receipt),
+    PurchaseOrder(PurchaseOrder),
+}
+
+/// Tags set by hand win over `rename_all`, as in serde.
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind")]
+#[task(preamble = "You sort the mail of an accounts department.")]
+enum Mail {
+    #[serde(rename = "bill")]
+    Invoice(Invoice),
+    Receipt(Receipt),
+}
+
+const INVOICE_TARGET: &str = "INVOICE INV-7: please pay 120.50 within 30 days.";
+
+const RECEIPT_TARGET: &str = "RECEIPT - Corner Shop - paid 4.20, thank you!";
+
+const ORDER_TARGET: &str = "PURCHASE ORDER PO-19: 2 desks, 4 chairs.";
+
+/// Answers each target with the variant it is a document of.
+fn documents_server() -> MockServer {
+    MockServer::start(|request| {
+        let prompt: String = request.prompt();
+        if prompt.contains(INVOICE_TARGET) {
+            success(r#"{"doc_type": "invoice", "invoice_number": "INV-7", "amount_due": 120.5}"#)
+        } else if prompt.contains(RECEIPT_TARGET) {
+            success(r#"{"doc_type": "receipt", "merchant": "Corner Shop", "total": 4.2}"#)
+        } else {
+            success(
+                r#"{"doc_type": "purchase_order", "order_number": "PO-19", "items": ["desk", "chair"]}"#,
+            )
+        }
+    })
+}
+
+/// Returns the section of the prompt describing a variant, from its header to its end line.
+fn variant_section<'a>(prompt: &'a str, tag: &str) -> &'a str {
+    let start: usize = prompt
+        .find(&format!("--- \"{}\" Type", tag))
+        .unwrap_or_else(|| panic!("no section for {}", tag));
+    let end_line: String = format!("--- End of \"{}\" Type ---", tag);
+    let end: usize = prompt[start..].find(&end_line).unwrap() + start + end_line.len();
+    &prompt[start..end]
+}
+
+#[test]
+fn the_prompt_lists_every_variant_and_asks_for_the_tag() {
+    let prompt: String = Document::new().get_system_prompt();
+
+    assert!(prompt.starts_with(
+        "The text is one of the following 3 types. Pick exactly one of them, and extract the fields of that type only:\n\
+         - \"invoice\": A bill from a supplier that asks for payment\n\
+         - \"receipt\": A proof of a payment already made\n\
+         - \"purchase_order\"\n\n\
+         Answer with one JSON object holding the key \"doc_type\", set to the tag of the type you picked"
+    ));
+    // The prompt does not depend on the variant the instance holds
+    let receipt = Document::Receipt(Receipt {
+        merchant: "Shop".to_string(),
+        total: 1.0,
+    });
+    assert_eq!(receipt.get_system_prompt(), prompt);
+}
+
+#[test]
+fn each_variant_is_described_by_the_prompt_of_its_task() {
+    let prompt: String = Document::new().get_system_prompt();
+    let snapshots: [(&str, &str); 3] = [
+        ("invoice", include_str!("fixtures/tagged_enum/invoice.txt")),
+        ("receipt", include_str!("fixtures/tagged_enum/receipt.txt")),
+        (
+            "purchase_order",
+            include_str!("fixtures/tagged_enum/purchase_order.txt"),
+        ),
+    ];
+
+    for (tag, snapshot) in snapshots {
+        assert_eq!(
+            variant_section(&prompt, tag),
+            snapshot.trim_end(),
+            "{}",
+            tag
+        );
+    }
+    assert!(
+        variant_section(&prompt, "receipt").contains(Receipt::new().get_system_prompt().trim_end())
+    );
+}
+
+#[test]
+fn renamed_variants_and_framing_are_used() {
+    let prompt: String = Mail::new().get_system_prompt();
+
+    assert!(prompt.starts_with("You sort the mail of an accounts department.\n\n"));
+    assert!(prompt.contains("- \"bill\"\n- \"Receipt\"\n"));
+    assert!(prompt.contains("--- \"bill\" Type, with \"kind\": \"bill\" ---"));
+    assert_eq!(
+        serde_json::to_value(Mail::new()).unwrap()["kind"],
+        serde_json::json!("bill")
+    );
+}
+
+#[test]
+fn canned_answers_select_their_variants() {
+    let server = documents_server();
+    let llm = server.llm();
+
+    let invoice: Document = llm
+        .generate_data(&Document::new(), INVOICE_TARGET, vec![])
+        .unwrap();
+    let receipt: Document = llm
+        .generate_data(&Document::new(), RECEIPT_TARGET, vec![])
+        .unwrap();
+    let order: Document = llm
+        .generate_data(&Document::new(), ORDER_TARGET, vec![])
+        .unwrap();
+
+    assert_eq!(
+        invoice,
+        Document::Invoice(Invoice {
+            invoice_number: "INV-7".to_string(),
+            amount_due: 120.5,
+        })
+    );
+    assert_eq!(
+        receipt,
+        Document::Receipt(Receipt {
+            merchant: "Corner Shop".to_string(),
+            total: 4.2,
+        })
+    );
+    assert_eq!(
+        order,
+        Document::PurchaseOrder(PurchaseOrder {
+            order_number: "PO-19".to_string(),
+            items: vec!["desk".to_string(), "chair".to_string()],
+        })
+    );
+    assert!(
+        server.requests()[0]
+            .prompt()
+            .contains("--- \"receipt\" Type")
+    );
+}
+
+#[tokio::test]
+async fn async_generation_selects_the_variant() {
+    let server = documents_server();
+
+    let receipt: Document = server
+        .llm()
+        .async_generate_data(&Document::new(), RECEIPT_TARGET, vec![])
+        .await
+        .unwrap();
+
+    assert!(matches!(receipt, Document::Receipt(Receipt { total, .. }) if total == 4.2));
+}
+
+#[test]
+fn an_unknown_tag_fails_to_parse() {
+    let server = MockServer::always(success(r#"{"doc_type": "letter", "merchant": "Shop"}"#));
+
+    let result: Result<Document, _> =
+        server
+            .llm()
+            .generate_data(&Document::new(), RECEIPT_TARGET, vec![]);
+
+    let error = result.unwrap_err();
+    assert!(matches!(
+        secretary_error(&error),
+        SecretaryError::JsonParsingError { message, .. } if message.contains("letter")
+    ));
+}
+
+#[test]
+fn distributed_generation_is_unsupported() {
+    let server = documents_server();
+
+    let result: Result<Document, _> =
+        server
+            .llm()
+            .fields_generate_data(&Document::new(), INVOICE_TARGET, vec![]);
+
+    let error = result.unwrap_err();
+    assert!(matches!(
+        secretary_error(&error),
+        SecretaryError::UnsupportedGeneration { message } if message.contains("Document")
+    ));
+    assert!(server.requests().is_empty());
+    assert!(!Document::supports_distributed_generation());
+    assert!(Invoice::supports_distributed_generation());
+}
+
+#[test]
+fn descriptors_are_the_tag_and_the_fields_of_every_variant() {
+    let descriptors = Document::field_descriptors();
+    let names: Vec<&str> = descriptors
+        .iter()
+        .map(|descriptor| descriptor.name.as_str())
+        .collect();
+
+    assert_eq!(
+        names,
+        vec![
+            "doc_type",
+            "invoice_number",
+            "amount_due",
+            "merchant",
+            "total",
+            "order_number",
+            "items",
+        ]
+    );
+    assert_eq!(descriptors[0].json_type, JsonType::String);
+    assert!(!descriptors[0].optional);
+    assert!(
+        descriptors[1..]
+            .iter()
+            .all(|descriptor| descriptor.optional)
+    );
+    assert_eq!(descriptors[6].json_type, JsonType::Array);
+}
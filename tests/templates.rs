@@ -0,0 +1,223 @@
+//! `{placeholder}`s in instructions are rendered with the template variables of the call.
+
+mod support;
+
+use std::collections::HashMap;
+use std::time::SystemTime;
+
+use secretary::SecretaryError;
+use secretary::Task;
+use secretary::compiled::CompileOptions;
+use secretary::request::RequestOptions;
+use secretary::traits::{AsyncGenerateData, GenerateData};
+use secretary::utilities::builtin_template_vars;
+use serde::{Deserialize, Serialize};
+
+use support::fixtures::{empty_choices, field_result, success};
+use support::{ADA_JSON, MockServer, Person, TARGET, ada, secretary_error};
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Meeting {
+    #[task(instruction = "Extract the date as YYYY-MM-DD, relative to {today}")]
+    pub date: String,
+    #[task(instruction = "Extract the budget in {tenant_currency}")]
+    pub budget: String,
+}
+
+const MEETING: &str = "Let's meet tomorrow, with 200 to spend.";
+
+fn vars(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+    pairs
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect()
+}
+
+fn tenant_options() -> RequestOptions {
+    RequestOptions::default()
+        .with_template_vars(vars(&[("today", "2024-02-28"), ("tenant_currency", "EUR")]))
+}
+
+#[test]
+fn date_relative_instructions_are_answered_from_the_rendered_date() {
+    let server = MockServer::by_instruction(
+        vec![
+            ("relative to 2024-02-28", field_result("2024-02-29")),
+            ("Extract the budget in EUR", field_result("200 EUR")),
+        ],
+        empty_choices(),
+    );
+
+    let meeting: Meeting = server
+        .llm()
+        .fields_generate_data_with_options(&Meeting::new(), MEETING, vec![], &tenant_options())
+        .unwrap();
+
+    assert_eq!(
+        meeting,
+        Meeting {
+            date: "2024-02-29".to_string(),
+            budget: "200 EUR".to_string(),
+        }
+    );
+    assert!(
+        server
+            .requests()
+            .iter()
+            .all(|request| !request.prompt().contains("{today}"))
+    );
+}
+
+#[test]
+fn attribute_and_additional_instructions_are_rendered() {
+    let server = MockServer::always(success(r#"{"date": "2024-02-29", "budget": "200"}"#));
+
+    let _: Meeting = server
+        .llm()
+        .generate_data_with_options(
+            &Meeting::new(),
+            MEETING,
+            vec!["Amounts are in {tenant_currency}".to_string()],
+            &tenant_options(),
+        )
+        .unwrap();
+
+    let prompt: String = server.requests()[0].prompt();
+    assert!(prompt.contains("relative to 2024-02-28"));
+    assert!(prompt.contains("Extract the budget in EUR"));
+    assert!(prompt.contains("- Amounts are in EUR"));
+    assert!(!prompt.contains('\u{0}'));
+}
+
+#[test]
+fn today_and_now_utc_are_provided() {
+    let server = MockServer::always(success(ADA_JSON));
+    let expected: HashMap<String, String> = builtin_template_vars(SystemTime::now());
+
+    let person: Person = server
+        .llm()
+        .generate_data_with_options(
+            &Person::new(),
+            TARGET,
+            vec!["Ages are as of {today}, now is {now_utc}".to_string()],
+            &RequestOptions::default().with_template_vars(HashMap::new()),
+        )
+        .unwrap();
+
+    assert_eq!(person, ada());
+    let prompt: String = server.requests()[0].prompt();
+    assert!(prompt.contains(&format!("Ages are as of {}, now is ", expected["today"])));
+    assert!(!prompt.contains("{now_utc}"));
+}
+
+#[test]
+fn missing_vars_fail_before_any_request() {
+    let server = MockServer::always(success(ADA_JSON));
+
+    let error = server
+        .llm()
+        .generate_data_with_options(
+            &Meeting::new(),
+            MEETING,
+            vec![],
+            &RequestOptions::default().with_template_vars(HashMap::new()),
+        )
+        .unwrap_err();
+    assert!(matches!(
+        secretary_error(&error),
+        SecretaryError::MissingTemplateVar { name } if name == "tenant_currency"
+    ));
+
+    let error = server
+        .llm()
+        .generate_data_with_options(
+            &Person::new(),
+            TARGET,
+            vec!["Amounts are in {currency}".to_string()],
+            &RequestOptions::default().with_template_vars(HashMap::new()),
+        )
+        .unwrap_err();
+    assert!(matches!(
+        secretary_error(&error),
+        SecretaryError::MissingTemplateVar { name } if name == "currency"
+    ));
+
+    assert!(server.requests().is_empty());
+}
+
+#[test]
+fn escaped_braces_and_the_target_are_sent_as_they_are() {
+    let server = MockServer::always(success(ADA_JSON));
+    let target: &str = "Ada {today} is 36 {{years}} old.";
+
+    let _: Person = server
+        .llm()
+        .generate_data_with_options(
+            &Person::new(),
+            target,
+            vec!["Ignore {{today}} placeholders".to_string()],
+            &RequestOptions::default().with_template_vars(HashMap::new()),
+        )
+        .unwrap();
+
+    let prompt: String = server.requests()[0].prompt();
+    assert!(prompt.contains("- Ignore {today} placeholders"));
+    assert!(prompt.contains(target));
+}
+
+#[test]
+fn instructions_are_not_rendered_without_template_vars() {
+    let server = MockServer::always(success(ADA_JSON));
+
+    let _: Person = server
+        .llm()
+        .generate_data(&Person::new(), TARGET, vec!["Keep {this}".to_string()])
+        .unwrap();
+
+    assert!(server.requests()[0].prompt().contains("- Keep {this}"));
+}
+
+#[test]
+fn compiled_tasks_are_rendered_per_call() {
+    let server = MockServer::always(success(r#"{"date": "2024-02-29", "budget": "200"}"#));
+    let compiled =
+        Meeting::new()
+            .compile(CompileOptions::default().with_additional_instructions(vec![
+                "Amounts are in {tenant_currency}".to_string(),
+            ]));
+
+    let _: Meeting = server
+        .llm()
+        .generate_data_with_options(&compiled, MEETING, vec![], &tenant_options())
+        .unwrap();
+    let _: Meeting = server
+        .llm()
+        .generate_data_with_options(
+            &compiled,
+            MEETING,
+            vec![],
+            &RequestOptions::default()
+                .with_template_vars(vars(&[("today", "2025-01-01"), ("tenant_currency", "USD")])),
+        )
+        .unwrap();
+
+    let requests = server.requests();
+    assert!(requests[0].prompt().contains("- Amounts are in EUR"));
+    assert!(requests[1].prompt().contains("- Amounts are in USD"));
+    assert!(requests[1].prompt().contains("relative to 2025-01-01"));
+}
+
+#[tokio::test]
+async fn async_generation_renders_instructions() {
+    let server = MockServer::always(success(r#"{"date": "2024-02-29", "budget": "200"}"#));
+
+    let _: Meeting = server
+        .llm()
+        .async_generate_data_with_options(&Meeting::new(), MEETING, vec![], &tenant_options())
+        .await
+        .unwrap();
+
+    let prompt: String = server.requests()[0].prompt();
+    assert!(prompt.contains("relative to 2024-02-28"));
+    assert!(prompt.contains("Extract the budget in EUR"));
+}
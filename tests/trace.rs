@@ -0,0 +1,163 @@
+//! Distributed extractions can be traced, saved and replayed without the LLM.
+
+mod support;
+
+use secretary::SecretaryError;
+use secretary::Task;
+use secretary::leniency::LeniencyProfile;
+use secretary::trace::{FieldTrace, assemble_from_trace};
+use secretary::traits::{AsyncGenerateData, GenerateData};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use support::fixtures::{field_result, success};
+use support::{MockServer, Person, TARGET, ada, fields_server};
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Contact {
+    #[task(instruction = "Extract the contact's name")]
+    pub name: String,
+    #[task(
+        instruction = "Extract the contact's email address",
+        extractor = "email"
+    )]
+    pub email: Option<String>,
+}
+
+/// Writes the trace as JSON and reads it back, as a trace saved to disk would be.
+fn reload(trace: &FieldTrace) -> FieldTrace {
+    serde_json::from_str(&serde_json::to_string_pretty(trace).unwrap()).unwrap()
+}
+
+#[test]
+fn every_field_is_traced() {
+    let server = fields_server(success(
+        "<think>Born 36 years ago</think><result> 36 </result>",
+    ));
+
+    let (person, trace): (Person, FieldTrace) = server
+        .llm()
+        .fields_generate_data_traced(&Person::new(), TARGET, vec![])
+        .unwrap();
+
+    assert_eq!(person, ada());
+    assert_eq!(trace.fields.len(), 2);
+    let age = &trace.fields["age"];
+    assert!(age.prompt.contains("Extract the age as a number"));
+    assert!(age.prompt.contains(TARGET));
+    assert_eq!(
+        age.raw_response,
+        "<think>Born 36 years ago</think><result> 36 </result>"
+    );
+    assert_eq!(age.content, "36");
+    assert_eq!(age.value, json!(36));
+    assert_eq!(trace.fields["name"].value, json!("Ada"));
+    assert!(trace.total_latency() >= age.latency);
+    assert!(trace.local_values.is_empty());
+}
+
+#[test]
+fn a_saved_trace_replays_to_the_same_result() {
+    let server = fields_server(field_result("36"));
+
+    let (person, trace): (Person, FieldTrace) = server
+        .llm()
+        .fields_generate_data_traced(&Person::new(), TARGET, vec![])
+        .unwrap();
+    let requests: usize = server.requests().len();
+
+    let reloaded: FieldTrace = reload(&trace);
+    assert_eq!(reloaded, trace);
+    assert_eq!(assemble_from_trace::<Person>(&reloaded).unwrap(), person);
+    assert_eq!(server.requests().len(), requests);
+}
+
+#[test]
+fn edited_traces_replay_with_their_new_content() {
+    let server = fields_server(field_result("36"));
+    let (_, mut trace): (Person, FieldTrace) = server
+        .llm()
+        .fields_generate_data_traced(&Person::new(), TARGET, vec![])
+        .unwrap();
+
+    trace.fields.get_mut("age").unwrap().content = "37".to_string();
+    assert_eq!(assemble_from_trace::<Person>(&trace).unwrap().age, 37);
+
+    trace.fields.get_mut("age").unwrap().content = "thirty-seven".to_string();
+    match assemble_from_trace::<Person>(&trace).unwrap_err() {
+        SecretaryError::FieldDeserializationError(error) => {
+            assert_eq!(error.failed_fields, vec!["age"]);
+            assert_eq!(error.raw_field_contents["age"], "thirty-seven");
+        }
+        other => panic!("unexpected error: {}", other),
+    }
+}
+
+#[test]
+fn replays_use_the_recorded_leniency() {
+    let server = fields_server(field_result(r#""36""#));
+    let llm = server.llm().with_leniency(LeniencyProfile::standard());
+
+    let (person, trace): (Person, FieldTrace) = llm
+        .fields_generate_data_traced(&Person::new(), TARGET, vec![])
+        .unwrap();
+
+    assert_eq!(person, ada());
+    assert_eq!(trace.fields["age"].value, json!("36"));
+    assert_eq!(trace.leniency, LeniencyProfile::standard());
+    assert_eq!(
+        assemble_from_trace::<Person>(&reload(&trace)).unwrap(),
+        ada()
+    );
+
+    let strict: FieldTrace = FieldTrace {
+        leniency: LeniencyProfile::strict(),
+        ..trace
+    };
+    assert!(assemble_from_trace::<Person>(&strict).is_err());
+}
+
+#[test]
+fn locally_extracted_fields_are_replayed() {
+    let server = MockServer::always(field_result("Ada Lovelace"));
+
+    let (contact, trace): (Contact, FieldTrace) = server
+        .llm()
+        .fields_generate_data_traced(
+            &Contact::new(),
+            "Ada Lovelace wrote from ada@example.com.",
+            vec![],
+        )
+        .unwrap();
+
+    assert_eq!(contact.email.as_deref(), Some("ada@example.com"));
+    assert_eq!(trace.fields.keys().collect::<Vec<_>>(), vec!["name"]);
+    assert_eq!(trace.local_values["email"], "ada@example.com");
+    assert_eq!(
+        assemble_from_trace::<Contact>(&reload(&trace)).unwrap(),
+        contact
+    );
+}
+
+#[tokio::test]
+async fn async_extractions_are_traced() {
+    let server = fields_server(field_result("36"));
+
+    let (person, trace): (Person, FieldTrace) = server
+        .llm()
+        .async_fields_generate_data_traced(&Person::new(), TARGET, vec![])
+        .await
+        .unwrap();
+
+    assert_eq!(person, ada());
+    assert_eq!(trace.fields["age"].content, "36");
+    assert!(
+        trace.fields["name"]
+            .prompt
+            .contains("Extract the person's name")
+    );
+    assert_eq!(
+        assemble_from_trace::<Person>(&reload(&trace)).unwrap(),
+        person
+    );
+}
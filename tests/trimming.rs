@@ -0,0 +1,228 @@
+//! Keys that are not fields of the Task are trimmed before deserialization under
+//! `UnknownKeys::Trim`.
+
+mod support;
+
+use std::collections::{BTreeMap, HashMap};
+
+use secretary::Task;
+use secretary::metadata::GenerationWarning;
+use secretary::request::RequestOptions;
+use secretary::traits::{AsyncGenerateData, GenerateData};
+use secretary::trimming::{TrimmedKey, UnknownKeys, trim_unknown_keys};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use support::fixtures::success;
+use support::{MockServer, assert_malformed_json};
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct Address {
+    #[task(instruction = "Extract the city")]
+    pub city: String,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct Branch {
+    #[task(instruction = "Extract the branch name")]
+    pub name: String,
+    #[task(instruction = "Extract the branch address, if mentioned")]
+    pub address: Option<Address>,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct Company {
+    #[task(instruction = "Extract the company name")]
+    pub name: String,
+    #[task(instruction = "Extract the labels as key-value pairs")]
+    pub labels: HashMap<String, String>,
+    pub headquarters: Address,
+    #[task(instruction = "Extract every branch")]
+    pub branches: Vec<Branch>,
+    #[task(instruction = "Extract the offices by region")]
+    pub offices: BTreeMap<String, Address>,
+}
+
+const TARGET: &str = "Acme, based in Berlin, has a branch in Lyon and an office in Oslo.";
+
+/// The JSON of Acme, with a chatty key on every level.
+fn chatty() -> Value {
+    json!({
+        "name": "Acme",
+        "explanation": "The name is in the first sentence",
+        "labels": {"sector": "retail", "explanation": "kept"},
+        "headquarters": {"city": "Berlin", "confidence": 0.9},
+        "branches": [
+            {"name": "South", "address": {"city": "Lyon", "source": "second clause"}},
+            {"name": "North", "address": null, "note": "no address"}
+        ],
+        "offices": {"nordic": {"city": "Oslo", "country": "NO"}}
+    })
+}
+
+fn acme() -> Company {
+    Company {
+        name: "Acme".to_string(),
+        labels: HashMap::from([
+            ("sector".to_string(), "retail".to_string()),
+            ("explanation".to_string(), "kept".to_string()),
+        ]),
+        headquarters: Address {
+            city: "Berlin".to_string(),
+        },
+        branches: vec![
+            Branch {
+                name: "South".to_string(),
+                address: Some(Address {
+                    city: "Lyon".to_string(),
+                }),
+            },
+            Branch {
+                name: "North".to_string(),
+                address: None,
+            },
+        ],
+        offices: BTreeMap::from([(
+            "nordic".to_string(),
+            Address {
+                city: "Oslo".to_string(),
+            },
+        )]),
+    }
+}
+
+#[test]
+fn unknown_keys_are_trimmed_recursively() {
+    let mut value: Value = chatty();
+    assert!(serde_json::from_value::<Company>(value.clone()).is_err());
+
+    let trimmed: Vec<TrimmedKey> = trim_unknown_keys(&Company::field_descriptors(), &mut value);
+
+    let paths: Vec<&str> = trimmed.iter().map(|key| key.path.as_str()).collect();
+    assert_eq!(
+        paths,
+        vec![
+            "explanation",
+            "headquarters.confidence",
+            "branches[0].address.source",
+            "branches[1].note",
+            "offices.nordic.country",
+        ]
+    );
+    assert_eq!(trimmed[1].value, json!(0.9));
+    assert_eq!(serde_json::from_value::<Company>(value).unwrap(), acme());
+}
+
+#[test]
+fn map_fields_keep_their_keys() {
+    let mut value: Value = json!({"labels": {"explanation": "kept", "x": "y"}});
+
+    let trimmed: Vec<TrimmedKey> = trim_unknown_keys(&Company::field_descriptors(), &mut value);
+
+    assert!(trimmed.is_empty());
+    assert_eq!(value["labels"], json!({"explanation": "kept", "x": "y"}));
+}
+
+#[test]
+fn values_that_are_not_objects_are_left_alone() {
+    for mut value in [json!(null), json!([1, 2]), json!("text")] {
+        let original: Value = value.clone();
+        assert!(trim_unknown_keys(&Company::field_descriptors(), &mut value).is_empty());
+        assert_eq!(value, original);
+    }
+
+    let (content, trimmed) =
+        UnknownKeys::Trim.trim_content(&Company::field_descriptors(), "not json".to_string());
+    assert_eq!(content, "not json");
+    assert!(trimmed.is_empty());
+
+    let content: String = chatty().to_string();
+    assert_eq!(
+        UnknownKeys::Keep.trim_content(&Company::field_descriptors(), content.clone()),
+        (content, Vec::new())
+    );
+}
+
+#[test]
+fn provider_policy_trims_before_deserializing() {
+    let server = MockServer::always(success(&chatty().to_string()));
+
+    assert_malformed_json(server.llm().generate_data(&Company::new(), TARGET, vec![]));
+
+    let company: Company = server
+        .llm()
+        .with_unknown_keys(UnknownKeys::Trim)
+        .generate_data(&Company::new(), TARGET, vec![])
+        .unwrap();
+    assert_eq!(company, acme());
+}
+
+#[test]
+fn call_policy_overrides_the_provider() {
+    let server = MockServer::always(success(&chatty().to_string()));
+
+    let company: Company = server
+        .llm()
+        .generate_data_with_options(
+            &Company::new(),
+            TARGET,
+            vec![],
+            &RequestOptions::default().with_unknown_keys(UnknownKeys::Trim),
+        )
+        .unwrap();
+    assert_eq!(company, acme());
+
+    assert_malformed_json(
+        server
+            .llm()
+            .with_unknown_keys(UnknownKeys::Trim)
+            .generate_data_with_options(
+                &Company::new(),
+                TARGET,
+                vec![],
+                &RequestOptions::default().with_unknown_keys(UnknownKeys::Keep),
+            ),
+    );
+}
+
+#[test]
+fn trimmed_keys_are_reported_as_warnings() {
+    let server = MockServer::always(success(&chatty().to_string()));
+
+    let result = server
+        .llm()
+        .with_unknown_keys(UnknownKeys::Trim)
+        .generate_data_adaptive(&Company::new(), TARGET, vec![])
+        .unwrap();
+
+    assert_eq!(result.data, acme());
+    assert_eq!(result.metadata.warnings.len(), 5);
+    assert_eq!(
+        result.metadata.warnings[0],
+        GenerationWarning::UnknownKeyTrimmed {
+            path: "explanation".to_string(),
+            value: json!("The name is in the first sentence"),
+        }
+    );
+}
+
+#[tokio::test]
+async fn async_extraction_trims_unknown_keys() {
+    let server = MockServer::always(success(&chatty().to_string()));
+    let llm = server.llm().with_unknown_keys(UnknownKeys::Trim);
+
+    let company: Company = llm
+        .async_generate_data(&Company::new(), TARGET, vec![])
+        .await
+        .unwrap();
+    assert_eq!(company, acme());
+
+    let result = llm
+        .async_generate_data_adaptive(&Company::new(), TARGET, vec![])
+        .await
+        .unwrap();
+    assert_eq!(result.metadata.warnings.len(), 5);
+}
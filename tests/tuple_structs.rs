@@ -0,0 +1,168 @@
+//! Tuple structs and newtypes are extracted through their positional names.
+
+#![allow(clippy::needless_borrows_for_generic_args)]
+
+mod support;
+
+use secretary::Task;
+use secretary::assembly::assemble_from_fields;
+use secretary::schema::{FieldKind, JsonType, json_schema};
+use secretary::traits::{AsyncGenerateData, GenerateData};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use support::MockServer;
+use support::fixtures::{field_result, success};
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Summary(#[task(instruction = "Summarize the review in one sentence")] String);
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Rating(
+    #[task(instruction = "Extract the score out of 10")] u32,
+    #[task(instruction = "Extract the number of votes")] u32,
+);
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Review {
+    #[task(instruction = "Extract the film title")]
+    pub title: String,
+    pub summary: Summary,
+    pub rating: Rating,
+}
+
+const TARGET: &str = "Metropolis: a landmark of silent film. 8/10 from 1,200 votes.";
+
+const SUMMARY: &str = "A landmark of silent film.";
+
+fn review() -> Review {
+    Review {
+        title: "Metropolis".to_string(),
+        summary: Summary(SUMMARY.to_string()),
+        rating: Rating(8, 1200),
+    }
+}
+
+/// A per-field server answering the fields of `Review`.
+fn review_server() -> MockServer {
+    MockServer::by_instruction(
+        vec![
+            ("Extract the film title", field_result("Metropolis")),
+            ("Summarize the review", field_result(SUMMARY)),
+            ("Extract the score", field_result("8")),
+            ("Extract the number of votes", field_result("1,200")),
+        ],
+        field_result(""),
+    )
+}
+
+#[test]
+fn newtypes_and_tuple_structs_are_extracted_field_by_field() {
+    let server = review_server();
+
+    let extracted: Review = server
+        .llm()
+        .fields_generate_data(&Review::new(), TARGET, &vec![])
+        .unwrap();
+
+    assert_eq!(extracted, review());
+    let prompts: Vec<String> = server
+        .requests()
+        .iter()
+        .map(|request| request.prompt())
+        .collect();
+    assert_eq!(prompts.len(), 4);
+    assert!(
+        prompts
+            .iter()
+            .any(|prompt| prompt.contains("value: Summarize the review in one sentence"))
+    );
+    assert!(
+        prompts
+            .iter()
+            .any(|prompt| prompt.contains("field_1: Extract the number of votes"))
+    );
+}
+
+#[tokio::test]
+async fn async_field_requests_assemble_tuple_structs() {
+    let server = review_server();
+
+    let extracted: Review = server
+        .llm()
+        .async_fields_generate_data(&Review::new(), TARGET, &vec![])
+        .await
+        .unwrap();
+
+    assert_eq!(extracted, review());
+}
+
+#[test]
+fn single_requests_use_serde_representation() {
+    let server = MockServer::always(success(
+        r#"{"title": "Metropolis", "summary": "A landmark of silent film.", "rating": [8, 1200]}"#,
+    ));
+
+    let extracted: Review = server
+        .llm()
+        .generate_data(&Review::new(), TARGET, &vec![])
+        .unwrap();
+
+    assert_eq!(extracted, review());
+    let prompt: String = server.requests()[0].prompt();
+    assert!(prompt.contains("field_0: Extract the score out of 10"));
+    assert!(prompt.contains(r#""summary": """#));
+}
+
+#[test]
+fn top_level_tuple_structs_and_newtypes_are_extracted() {
+    let server = review_server();
+    let rating: Rating = server
+        .llm()
+        .fields_generate_data(&Rating::new(), TARGET, &vec![])
+        .unwrap();
+    assert_eq!(rating, Rating(8, 1200));
+
+    let summary: Summary = server
+        .llm()
+        .fields_generate_data(&Summary::new(), TARGET, &vec![])
+        .unwrap();
+    assert_eq!(summary, Summary(SUMMARY.to_string()));
+}
+
+#[test]
+fn positional_paths_are_assembled() {
+    let fields: Vec<(String, String)> = [
+        ("title", "Metropolis"),
+        ("summary", SUMMARY),
+        ("rating.field_1", "1,200"),
+        ("rating.field_0", "8"),
+    ]
+    .iter()
+    .map(|(path, content)| (path.to_string(), content.to_string()))
+    .collect();
+
+    let assembled: Review = assemble_from_fields(fields).unwrap();
+    assert_eq!(assembled, review());
+}
+
+#[test]
+fn descriptors_follow_serde_representation() {
+    let fields = Review::field_descriptors();
+
+    assert_eq!(fields[1].name, "summary");
+    assert_eq!(fields[1].kind, FieldKind::Normal);
+    assert_eq!(fields[1].json_type, JsonType::String);
+    assert_eq!(fields[2].json_type, JsonType::Array);
+
+    let schema = json_schema(&fields);
+    assert_eq!(
+        schema["properties"]["summary"],
+        json!({"type": "string", "description": "Summarize the review in one sentence"})
+    );
+    assert_eq!(schema["properties"]["rating"]["type"], "array");
+    assert_eq!(
+        schema["properties"]["rating"]["prefixItems"][1],
+        json!({"type": "number", "description": "Extract the number of votes"})
+    );
+}
@@ -0,0 +1,10 @@
+use secretary::Task;
+use serde::{Deserialize, Serialize};
+
+#[derive(Task, Serialize, Deserialize)]
+struct Invoice {
+    #[task(instruction = "Extract the due date", date)]
+    pub due: u32,
+}
+
+fn main() {}
@@ -0,0 +1,28 @@
+use secretary::Task;
+use serde::{Deserialize, Serialize};
+
+#[derive(Task, Serialize, Deserialize)]
+struct Order {
+    #[task(instruction = "Extract the quantity", default_value = "\"none\"")]
+    pub quantity: u32,
+}
+
+#[derive(Task, Serialize, Deserialize)]
+struct Reading {
+    #[task(instruction = "Extract the offset", default_value = "-1")]
+    pub offset: u8,
+}
+
+#[derive(Task, Serialize, Deserialize)]
+struct Tags {
+    #[task(instruction = "List the tags", default_value = "[\"new\", 2]")]
+    pub tags: Vec<String>,
+}
+
+#[derive(Task, Serialize, Deserialize)]
+struct Note {
+    #[task(instruction = "Extract the note", default_value = "unknown")]
+    pub note: String,
+}
+
+fn main() {}
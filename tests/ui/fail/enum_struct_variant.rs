@@ -0,0 +1,17 @@
+use secretary::Task;
+use serde::{Deserialize, Serialize};
+
+#[derive(Task, Serialize, Deserialize, Debug)]
+struct Invoice {
+    #[task(instruction = "Extract the invoice number")]
+    pub invoice_number: String,
+}
+
+#[derive(Task, Serialize, Deserialize)]
+#[serde(tag = "doc_type")]
+enum Document {
+    Invoice(Invoice),
+    Receipt { merchant: String },
+}
+
+fn main() {}
@@ -0,0 +1,16 @@
+use secretary::Task;
+use serde::{Deserialize, Serialize};
+
+#[derive(Task, Serialize, Deserialize, Debug)]
+struct Invoice {
+    #[task(instruction = "Extract the invoice number")]
+    pub invoice_number: String,
+}
+
+#[derive(Task, Serialize, Deserialize)]
+#[serde(untagged)]
+enum Document {
+    Invoice(Invoice),
+}
+
+fn main() {}
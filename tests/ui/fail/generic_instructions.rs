@@ -0,0 +1,17 @@
+use secretary::Task;
+use serde::{Deserialize, Serialize};
+
+#[derive(Task, Serialize, Deserialize)]
+#[task(deny_generic_instructions)]
+struct Order {
+    #[task(instruction = "Extract the value")]
+    pub reference: String,
+    #[task(instruction = "Extract the customer field from the input")]
+    pub customer: String,
+    #[task(instruction = "Extract how many items were ordered")]
+    pub quantity: u32,
+    #[task(instruction = "Extract if the order was paid in advance")]
+    pub prepaid: Option<bool>,
+}
+
+fn main() {}
@@ -0,0 +1,18 @@
+use secretary::Task;
+use serde::{Deserialize, Serialize};
+
+#[derive(Task, Serialize, Deserialize, Debug)]
+struct Address {
+    #[task(instruction = "Extract the city", group = "location")]
+    pub city: String,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug)]
+struct Person {
+    #[task(instruction = "Extract the name", group = "location")]
+    pub name: String,
+    #[task(group = "location")]
+    pub address: Address,
+}
+
+fn main() {}
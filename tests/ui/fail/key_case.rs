@@ -0,0 +1,11 @@
+use secretary::Task;
+use serde::{Deserialize, Serialize};
+
+#[derive(Task, Serialize, Deserialize)]
+#[task(key_case = "SCREAMING_SNAKE_CASE")]
+struct Listing {
+    #[task(instruction = "Extract the monthly rent as a number")]
+    pub monthly_rent: u32,
+}
+
+fn main() {}
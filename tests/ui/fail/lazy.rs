@@ -0,0 +1,16 @@
+use secretary::{Lazy, Task};
+use serde::{Deserialize, Serialize};
+
+#[derive(Task, Serialize, Deserialize)]
+struct Report {
+    #[task(instruction = "Summarize the report", lazy)]
+    pub summary: String,
+}
+
+#[derive(Task, Serialize, Deserialize)]
+struct Review {
+    #[task(instruction = "Summarize the review")]
+    pub summary: Lazy<String>,
+}
+
+fn main() {}
@@ -0,0 +1,18 @@
+use secretary::Task;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct Address {
+    pub city: String,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug)]
+struct Person {
+    #[task(instruction = "Extract the name")]
+    pub name: String,
+    pub address: Address,
+    #[task(instruction = "Extract the previous addresses")]
+    pub previous: Vec<Address>,
+}
+
+fn main() {}
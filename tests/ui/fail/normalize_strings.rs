@@ -0,0 +1,11 @@
+use secretary::Task;
+use serde::{Deserialize, Serialize};
+
+#[derive(Task, Serialize, Deserialize)]
+#[task(normalize_strings = "nfc")]
+struct Product {
+    #[task(instruction = "Extract the product name")]
+    pub name: String,
+}
+
+fn main() {}
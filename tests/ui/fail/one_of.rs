@@ -0,0 +1,28 @@
+use secretary::Task;
+use serde::{Deserialize, Serialize};
+
+#[derive(Task, Serialize, Deserialize)]
+struct Rating {
+    #[task(instruction = "Rate the product", one_of = "1, 2, 3")]
+    pub stars: u32,
+}
+
+#[derive(Task, Serialize, Deserialize)]
+struct Review {
+    #[task(instruction = "Rate the sentiment", one_of = "high, , low")]
+    pub sentiment: String,
+}
+
+#[derive(Task, Serialize, Deserialize)]
+struct Ticket {
+    #[task(instruction = "Extract the priority", one_of = "High, low, high")]
+    pub priority: String,
+}
+
+#[derive(Task, Serialize, Deserialize)]
+struct Labels {
+    #[task(instruction = "List the labels", one_of = "bug, feature")]
+    pub labels: Vec<String>,
+}
+
+fn main() {}
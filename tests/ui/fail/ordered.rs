@@ -0,0 +1,17 @@
+use secretary::Task;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+#[derive(Task, Serialize, Deserialize)]
+struct Recipe {
+    #[task(instruction = "Extract the first step", ordered)]
+    pub first_step: String,
+}
+
+#[derive(Task, Serialize, Deserialize)]
+struct Pantry {
+    #[task(instruction = "List the ingredients", ordered)]
+    pub ingredients: HashSet<String>,
+}
+
+fn main() {}
@@ -0,0 +1,17 @@
+use secretary::Task;
+use serde::{Deserialize, Serialize};
+
+#[derive(Task, Serialize, Deserialize)]
+struct Listing {
+    #[task(instruction = "Extract the rent", output_language = "en")]
+    pub rent: u32,
+}
+
+#[derive(Task, Serialize, Deserialize)]
+#[task(output_language = "English")]
+struct Flat {
+    #[task(instruction = "Describe the flat")]
+    pub description: String,
+}
+
+fn main() {}
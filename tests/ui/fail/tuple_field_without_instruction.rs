@@ -0,0 +1,7 @@
+use secretary::Task;
+use serde::{Deserialize, Serialize};
+
+#[derive(Task, Serialize, Deserialize)]
+struct Range(#[task(instruction = "Extract the lower bound")] u32, u32);
+
+fn main() {}
@@ -0,0 +1,7 @@
+use secretary::Task;
+use serde::{Deserialize, Serialize};
+
+#[derive(Task, Serialize, Deserialize)]
+struct Marker;
+
+fn main() {}
@@ -0,0 +1,30 @@
+use std::collections::HashMap;
+
+use secretary::Task;
+use serde::{Deserialize, Serialize};
+
+#[derive(Task, Serialize, Deserialize, Debug)]
+struct Shipment {
+    #[task(instruction = "Extract the carrier", default_value = "\"unknown\"")]
+    pub carrier: String,
+    #[task(instruction = "Extract the number of parcels", default_value = "1")]
+    pub parcels: u16,
+    #[task(instruction = "Extract the weight in kg", default_value = "0.5")]
+    pub weight: f64,
+    #[task(instruction = "Is it insured", default_value = "false")]
+    pub insured: bool,
+    #[task(instruction = "Extract the reference", default_value = "null")]
+    pub reference: Option<String>,
+    #[task(instruction = "List the labels", default_value = "[\"fragile\"]")]
+    pub labels: Vec<String>,
+    #[task(instruction = "Map each parcel to its weight", default_value = "{}")]
+    pub weights: HashMap<String, f64>,
+    #[task(instruction = "Extract the grade", default_value = "\"B\"")]
+    pub grade: char,
+}
+
+fn main() {
+    let descriptors = Shipment::field_descriptors();
+    assert_eq!(descriptors[1].default_value, Some(serde_json::json!(1)));
+    assert!(descriptors[4].default_value.as_ref().unwrap().is_null());
+}
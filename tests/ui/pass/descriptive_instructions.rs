@@ -0,0 +1,27 @@
+use secretary::Task;
+use serde::{Deserialize, Serialize};
+
+#[derive(Task, Serialize, Deserialize)]
+#[task(deny_generic_instructions)]
+struct Order {
+    #[task(instruction = "Extract the order reference printed after \"Order #\"")]
+    pub reference: String,
+    #[task(instruction = "Extract the number of items ordered, as an integer")]
+    pub quantity: u32,
+    #[task(instruction = "Answer true if the order was paid in advance, false otherwise")]
+    pub prepaid: Option<bool>,
+    pub shipping: Address,
+}
+
+// Nested Tasks have no instruction to check, and are only checked where they opt in
+#[derive(Task, Serialize, Deserialize)]
+struct Address {
+    #[task(instruction = "Extract the city")]
+    pub city: String,
+    #[task(instruction = "Extract the zip")]
+    pub zip: u32,
+}
+
+fn main() {
+    assert!(Order::new().get_system_prompt().contains("as an integer"));
+}
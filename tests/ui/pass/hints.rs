@@ -0,0 +1,29 @@
+use secretary::Task;
+use secretary::hints::Hints;
+use serde::{Deserialize, Serialize};
+
+#[derive(Task, Serialize, Deserialize, Debug)]
+struct Measurement<T> {
+    #[task(instruction = "Extract the unit")]
+    pub unit: String,
+    #[task(instruction = "Extract the measured value")]
+    pub value: T,
+}
+
+// Tuple structs get a builder without setters
+#[derive(Task, Serialize, Deserialize, Debug)]
+struct Range(
+    #[task(instruction = "Extract the lower bound")] u32,
+    #[task(instruction = "Extract the upper bound")] u32,
+);
+
+fn main() {
+    let hints: Hints<Measurement<f64>> = Measurement::<f64>::hints()
+        .unit("kg")
+        .value(2.5)
+        .build_hints();
+    assert_eq!(hints.values().len(), 2);
+
+    let hints: Hints<Range> = Range::hints().build_hints().with_value("field_0", 1);
+    assert_eq!(hints.values().len(), 1);
+}
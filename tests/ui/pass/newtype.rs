@@ -0,0 +1,22 @@
+use secretary::Task;
+use serde::{Deserialize, Serialize};
+
+#[derive(Task, Serialize, Deserialize, Debug)]
+struct Summary(#[task(instruction = "One sentence summary")] String);
+
+#[derive(Task, Serialize, Deserialize, Debug)]
+struct Person {
+    #[task(instruction = "Extract the name")]
+    pub name: String,
+}
+
+// A newtype around a Task needs no instruction, like a nested Task field
+#[derive(Task, Serialize, Deserialize, Debug)]
+struct Author(Person);
+
+fn main() {
+    assert_eq!(Summary::new().0, "");
+    assert_eq!(Summary::field_descriptors()[0].name, "");
+    assert!(Summary::new().get_system_prompt().contains("value: One sentence summary"));
+    assert_eq!(Author::new().0.name, "");
+}
@@ -0,0 +1,29 @@
+use secretary::Task;
+use secretary::schema::{FieldKind, JsonType};
+use serde::{Deserialize, Serialize};
+
+// A type from another crate, or one that should be extracted as a single value
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+struct Money {
+    pub amount: f64,
+    pub currency: String,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug)]
+struct Invoice {
+    #[task(instruction = "Extract the invoice number")]
+    pub number: String,
+    #[task(plain, instruction = "Extract the total as {\"amount\": number, \"currency\": code}")]
+    pub total: Money,
+    #[task(plain, instruction = "Extract every tax line as an amount and currency")]
+    pub taxes: Vec<Money>,
+}
+
+fn main() {
+    let fields = Invoice::field_descriptors();
+    assert_eq!(fields[1].kind, FieldKind::Normal);
+    assert_eq!(fields[1].json_type, JsonType::Any);
+    assert!(fields[1].children.is_empty());
+    assert_eq!(Invoice::new().total, Money::default());
+    assert!(Invoice::new().get_system_prompt().contains("Extract every tax line"));
+}
@@ -0,0 +1,32 @@
+use secretary::Task;
+use serde::{Deserialize, Serialize};
+
+#[derive(Task, Serialize, Deserialize, Debug)]
+struct Invoice {
+    #[task(instruction = "Extract the invoice number")]
+    pub invoice_number: String,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug)]
+struct Receipt {
+    #[task(instruction = "Extract the merchant name")]
+    pub merchant: String,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug)]
+#[serde(tag = "doc_type", rename_all = "kebab-case")]
+#[task(preamble = "Sort the document first.")]
+enum Document {
+    #[task(instruction = "A bill that asks for payment")]
+    Invoice(Invoice),
+    #[serde(rename = "till-receipt")]
+    Receipt(Receipt),
+}
+
+fn main() {
+    assert!(matches!(Document::new(), Document::Invoice(_)));
+    let prompt = Document::new().get_system_prompt();
+    assert!(prompt.starts_with("Sort the document first."));
+    assert!(prompt.contains("- \"invoice\": A bill that asks for payment\n- \"till-receipt\"\n"));
+    assert!(!Document::supports_distributed_generation());
+}
@@ -0,0 +1,15 @@
+use secretary::Task;
+use serde::{Deserialize, Serialize};
+
+#[derive(Task, Serialize, Deserialize, Debug)]
+struct Range(
+    #[task(instruction = "Extract the lower bound")] u32,
+    #[task(instruction = "Extract the upper bound")] u32,
+);
+
+fn main() {
+    let range = Range::new();
+    assert_eq!((range.0, range.1), (0, 0));
+    assert_eq!(Range::field_descriptors()[1].name, "field_1");
+    assert!(range.get_system_prompt().contains("field_0: Extract the lower bound"));
+}
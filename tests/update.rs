@@ -0,0 +1,78 @@
+//! Updating a struct requests its missing fields and the always refreshed ones, with the
+//! known values as context.
+
+#![allow(clippy::needless_borrows_for_generic_args)]
+
+mod support;
+
+use secretary::Task;
+use secretary::traits::GenerateData;
+use serde::{Deserialize, Serialize};
+
+use support::MockServer;
+use support::fixtures::field_result;
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone)]
+struct Contact {
+    #[task(instruction = "Extract the name")]
+    pub name: String,
+    #[task(instruction = "Extract the email address")]
+    pub email: Option<String>,
+    #[task(instruction = "Extract the age as a number")]
+    pub age: u32,
+    #[task(instruction = "Summarize the latest interaction", always_refresh)]
+    pub notes: String,
+}
+
+#[test]
+fn missing_and_always_refreshed_fields_are_requested() {
+    let server = MockServer::start(|request| {
+        let prompt: String = request.prompt();
+        if prompt.contains("Extract the email address") {
+            field_result("ada@example.com")
+        } else if prompt.contains("Extract the age") {
+            field_result("36")
+        } else {
+            field_result("Asked about pricing")
+        }
+    });
+    let existing = Contact {
+        name: "Ada Lovelace".to_string(),
+        email: None,
+        age: 0,
+        notes: "Signed up".to_string(),
+    };
+
+    let updated: Contact = server
+        .llm()
+        .update_data(
+            &existing,
+            "Ada (36, ada@example.com) asked about pricing",
+            &vec![],
+        )
+        .unwrap();
+
+    assert_eq!(updated.name, "Ada Lovelace");
+    assert_eq!(updated.email.as_deref(), Some("ada@example.com"));
+    assert_eq!(updated.age, 36);
+    assert_eq!(updated.notes, "Asked about pricing");
+
+    // The known name is given as context and not requested again
+    let prompts: Vec<String> = server
+        .requests()
+        .iter()
+        .map(|request| request.prompt())
+        .collect();
+    assert_eq!(prompts.len(), 3);
+    assert!(prompts.iter().all(|prompt| prompt.contains("Ada Lovelace")));
+    assert!(
+        prompts
+            .iter()
+            .all(|prompt| !prompt.contains("Extract the name"))
+    );
+
+    // A filled struct only asks for the always refreshed field
+    let requests = updated.make_update_requests("...", &[]);
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].0.field_path, "notes");
+}
@@ -0,0 +1,70 @@
+//! Responses are validated against the Task's JSON Schema before deserializing, reporting
+//! every violation at once.
+#![cfg(feature = "schema-validation")]
+#![allow(clippy::needless_borrows_for_generic_args)]
+
+mod support;
+
+use secretary::SecretaryError;
+use secretary::Task;
+use secretary::request::RequestOptions;
+use secretary::traits::GenerateData;
+use secretary::validation::SchemaValidation;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use support::fixtures::success;
+use support::{MockServer, secretary_error};
+
+#[derive(Task, Serialize, Deserialize, Debug)]
+struct Product {
+    #[task(instruction = "Extract the product name")]
+    pub name: String,
+    #[task(instruction = "Extract the price as a number")]
+    pub price: f64,
+    #[task(instruction = "Extract the age of the product in years")]
+    pub age: u32,
+}
+
+#[test]
+fn strict_validation_reports_every_violation() {
+    let server = MockServer::always(success(
+        &json!({"name": "Lamp", "pricee": 12.5, "age": "two"}).to_string(),
+    ));
+    let options = RequestOptions::default().with_schema_validation(SchemaValidation::Strict);
+
+    let error = server
+        .llm()
+        .generate_data_with_options(&Product::new(), "Lamp, 12.50", &vec![], &options)
+        .unwrap_err();
+
+    let error: &SecretaryError = secretary_error(&error);
+    match error {
+        SecretaryError::SchemaViolation { violations, .. } => {
+            let pointers: Vec<&str> = violations
+                .iter()
+                .map(|violation| violation.pointer.as_str())
+                .collect();
+            assert_eq!(pointers, vec!["/age", "/price", "/pricee"]);
+        }
+        other => panic!("unexpected error: {}", other),
+    }
+    assert!(error.to_string().contains("\"two\""));
+    assert!(!error.redacted().contains("two"));
+}
+
+#[test]
+fn additional_properties_can_be_let_through() {
+    let server = MockServer::always(success(
+        &json!({"name": "Lamp", "price": 12.5, "age": 2, "color": "red"}).to_string(),
+    ));
+    let options = RequestOptions::default()
+        .with_schema_validation(SchemaValidation::AllowAdditionalProperties);
+
+    let product: Product = server
+        .llm()
+        .generate_data_with_options(&Product::new(), "Lamp, 12.50", &vec![], &options)
+        .unwrap();
+
+    assert_eq!(product.age, 2);
+}
@@ -0,0 +1,391 @@
+//! Answers of `one_of` fields are normalized to the closest allowed value.
+
+mod support;
+
+use secretary::request::RequestOptions;
+use secretary::traits::{AsyncGenerateData, GenerateData};
+use secretary::vocabulary::{
+    AllowedValueMatch, MatchKind, NormalizedValue, OneOfPolicy, match_allowed_value,
+};
+use secretary::{SecretaryError, Task};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use support::fixtures::{field_result, success};
+use support::{BoxedError, MockServer, secretary_error};
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Comment {
+    #[task(instruction = "Rate the tone", one_of = "positive, negative, neutral")]
+    pub tone: String,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Triage {
+    #[task(instruction = "Rate the sentiment", one_of = "high, mid, low")]
+    pub sentiment: String,
+    #[task(instruction = "Extract the urgency, if any", one_of = "now, later")]
+    pub urgency: Option<String>,
+    pub summary: Comment,
+}
+
+#[derive(Task, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Review {
+    #[task(instruction = "Extract the reviewer's name")]
+    pub reviewer: String,
+    #[task(instruction = "Rate the sentiment", one_of = "high, mid, low")]
+    pub sentiment: String,
+    #[task(instruction = "Extract the urgency, if any", one_of = "now, later")]
+    pub urgency: Option<String>,
+    #[task(instruction = "Extract every comment")]
+    pub comments: Vec<Comment>,
+}
+
+const TARGET: &str = "Ann is fairly happy. She loved the box, hated the manual.";
+
+fn messy() -> Value {
+    json!({
+        "reviewer": "Ann",
+        "sentiment": "Mid-level",
+        "urgency": null,
+        "comments": [{"tone": "NEGATIVE."}, {"tone": "neutral"}, {"tone": "posiitve"}]
+    })
+}
+
+fn ann() -> Review {
+    Review {
+        reviewer: "Ann".to_string(),
+        sentiment: "mid".to_string(),
+        urgency: None,
+        comments: vec![
+            Comment {
+                tone: "negative".to_string(),
+            },
+            Comment {
+                tone: "neutral".to_string(),
+            },
+            Comment {
+                tone: "positive".to_string(),
+            },
+        ],
+    }
+}
+
+fn values(allowed: &str) -> Vec<String> {
+    allowed.split(", ").map(str::to_string).collect()
+}
+
+fn matched(value: &str, kind: MatchKind) -> AllowedValueMatch {
+    AllowedValueMatch::Matched {
+        value: value.to_string(),
+        kind,
+    }
+}
+
+fn ambiguous(candidates: &[&str]) -> AllowedValueMatch {
+    AllowedValueMatch::Ambiguous {
+        candidates: candidates.iter().map(|value| value.to_string()).collect(),
+    }
+}
+
+fn assert_not_allowed<T: std::fmt::Debug>(result: Result<T, BoxedError>, path: &str) {
+    let error: BoxedError = result.unwrap_err();
+    match secretary_error(&error) {
+        SecretaryError::ValueNotAllowed { field_path, .. } => assert_eq!(field_path, path),
+        other => panic!("unexpected error: {}", other),
+    }
+}
+
+#[test]
+fn answers_match_in_stages() {
+    // (allowed values, answer, expected match)
+    let cases: Vec<(&str, &str, AllowedValueMatch)> = vec![
+        ("high, mid, low", "high", matched("high", MatchKind::Exact)),
+        ("high, mid, low", "HIGH", matched("high", MatchKind::Exact)),
+        ("high, mid, low", " Low. ", matched("low", MatchKind::Exact)),
+        (
+            "high, mid, low",
+            "\"mid\"",
+            matched("mid", MatchKind::Exact),
+        ),
+        (
+            "high, mid, low",
+            "**low**!",
+            matched("low", MatchKind::Exact),
+        ),
+        (
+            "Positive, Negative",
+            "positive",
+            matched("Positive", MatchKind::Exact),
+        ),
+        (
+            "high, mid, low",
+            "Mid-level",
+            matched("mid", MatchKind::Prefix),
+        ),
+        ("high, mid, low", "hi", matched("high", MatchKind::Prefix)),
+        (
+            "high, mid, low",
+            "lowest",
+            matched("low", MatchKind::Prefix),
+        ),
+        (
+            "high, mid, low",
+            "very high",
+            matched("high", MatchKind::Substring),
+        ),
+        (
+            "high, mid, low",
+            "medium-high",
+            matched("high", MatchKind::Substring),
+        ),
+        (
+            "high, mid, low",
+            "hgh",
+            matched("high", MatchKind::EditDistance),
+        ),
+        ("high, mid, low", "lwo", AllowedValueMatch::NoMatch),
+        (
+            "low, medium, high",
+            "meduim",
+            matched("medium", MatchKind::EditDistance),
+        ),
+        ("low, medium, high", "mdeuim", AllowedValueMatch::NoMatch),
+        ("high, mid, low", "furious", AllowedValueMatch::NoMatch),
+        ("high, mid, low", "", AllowedValueMatch::NoMatch),
+        ("high, mid, low", " ... ", AllowedValueMatch::NoMatch),
+        // The longest of several matches is the most specific one
+        (
+            "high, highest",
+            "highest-ever",
+            matched("highest", MatchKind::Prefix),
+        ),
+        (
+            "high, mid, low",
+            "high or low",
+            matched("high", MatchKind::Prefix),
+        ),
+        (
+            "high, mid, low",
+            "rather low",
+            matched("low", MatchKind::Substring),
+        ),
+        (
+            "positive, negative, neutral",
+            "pos",
+            matched("positive", MatchKind::Prefix),
+        ),
+        (
+            "positive, negative, neutral",
+            "tral",
+            matched("neutral", MatchKind::Substring),
+        ),
+        // Ties are ambiguous, whatever later stages would find
+        ("max, mid, min", "m", ambiguous(&["max", "mid", "min"])),
+        (
+            "high, mid, low",
+            "either mid or low",
+            ambiguous(&["mid", "low"]),
+        ),
+        (
+            "positive, negative, neutral",
+            "n",
+            ambiguous(&["negative", "neutral"]),
+        ),
+        (
+            "positive, negative, neutral",
+            "ive",
+            ambiguous(&["positive", "negative"]),
+        ),
+        ("cat, car, dog", "cab", ambiguous(&["cat", "car"])),
+        ("cat, car, dog", "dgo", AllowedValueMatch::NoMatch),
+    ];
+
+    for (allowed, answer, expected) in cases {
+        assert_eq!(
+            match_allowed_value(answer, &values(allowed)),
+            expected,
+            "{:?} in [{}]",
+            answer,
+            allowed
+        );
+    }
+}
+
+#[test]
+fn allowed_values_are_stated_in_the_instruction() {
+    let descriptors = Review::field_descriptors();
+
+    assert_eq!(descriptors[1].one_of, vec!["high", "mid", "low"]);
+    assert_eq!(
+        descriptors[1].instruction,
+        r#"Rate the sentiment Answer with exactly one of "high", "mid", "low"."#
+    );
+    assert!(descriptors[0].one_of.is_empty());
+    assert_eq!(
+        descriptors[3].children[0].one_of,
+        vec!["positive", "negative", "neutral"]
+    );
+
+    // The descriptor only serializes the values when there are any
+    let serialized: Value = serde_json::to_value(&descriptors).unwrap();
+    assert!(serialized[0].get("one_of").is_none());
+    assert_eq!(serialized[2]["one_of"], json!(["now", "later"]));
+}
+
+#[test]
+fn json_mode_normalizes_messy_answers() {
+    let server = MockServer::always(success(&messy().to_string()));
+
+    let review: Review = server
+        .llm()
+        .generate_data(&Review::new(), TARGET, vec![])
+        .unwrap();
+
+    assert_eq!(review, ann());
+    let prompt: String = server.requests()[0].prompt();
+    assert!(prompt.contains(r#"one of "high", "mid", "low""#));
+    assert!(prompt.contains(r#"one of "positive", "negative", "neutral""#));
+}
+
+#[test]
+fn normalizations_are_reported_in_metadata() {
+    let server = MockServer::always(success(&messy().to_string()));
+
+    let result = server
+        .llm()
+        .generate_data_adaptive(&Review::new(), TARGET, vec![])
+        .unwrap();
+
+    assert_eq!(result.data, ann());
+    assert_eq!(
+        result.metadata.normalized_values,
+        vec![
+            NormalizedValue {
+                path: "sentiment".to_string(),
+                original: "Mid-level".to_string(),
+                normalized: json!("mid"),
+            },
+            NormalizedValue {
+                path: "comments[0].tone".to_string(),
+                original: "NEGATIVE.".to_string(),
+                normalized: json!("negative"),
+            },
+            NormalizedValue {
+                path: "comments[2].tone".to_string(),
+                original: "posiitve".to_string(),
+                normalized: json!("positive"),
+            },
+        ]
+    );
+}
+
+#[test]
+fn fields_mode_normalizes_each_field() {
+    let server = MockServer::by_instruction(
+        vec![
+            ("Rate the sentiment", field_result("LOW.")),
+            ("Extract the urgency", field_result("right now")),
+            ("Rate the tone", field_result("Neutral")),
+        ],
+        field_result(""),
+    );
+
+    let triage: Triage = server
+        .llm()
+        .fields_generate_data(&Triage::new(), TARGET, vec![])
+        .unwrap();
+
+    assert_eq!(triage.sentiment, "low");
+    assert_eq!(triage.urgency.as_deref(), Some("now"));
+    assert_eq!(triage.summary.tone, "neutral");
+    let sentiment_prompt: String = server
+        .requests()
+        .iter()
+        .map(|request| request.prompt())
+        .find(|prompt| prompt.contains("Rate the sentiment"))
+        .unwrap();
+    assert!(sentiment_prompt.contains(r#"one of "high", "mid", "low""#));
+}
+
+#[test]
+fn unmatched_answers_follow_the_policy() {
+    let mut content: Value = messy();
+    content["sentiment"] = json!("furious");
+    content["urgency"] = json!("whenever");
+    let server = MockServer::always(success(&content.to_string()));
+
+    assert_not_allowed(
+        server.llm().generate_data(&Review::new(), TARGET, vec![]),
+        "sentiment",
+    );
+
+    let result = server
+        .llm()
+        .with_one_of_policy(OneOfPolicy::Default)
+        .generate_data_adaptive(&Review::new(), TARGET, vec![])
+        .unwrap();
+    // The first allowed value for a required field, null for an optional one
+    assert_eq!(result.data.sentiment, "high");
+    assert_eq!(result.data.urgency, None);
+    assert_eq!(result.metadata.normalized_values[1].normalized, Value::Null);
+
+    // The call's policy overrides the provider's
+    assert_not_allowed(
+        server
+            .llm()
+            .with_one_of_policy(OneOfPolicy::Default)
+            .generate_data_with_options(
+                &Review::new(),
+                TARGET,
+                vec![],
+                &RequestOptions::default().with_one_of_policy(OneOfPolicy::Error),
+            ),
+        "sentiment",
+    );
+}
+
+#[test]
+fn ambiguous_answers_are_not_guessed() {
+    let mut content: Value = messy();
+    content["comments"][1]["tone"] = json!("n");
+    let server = MockServer::always(success(&content.to_string()));
+
+    let error: BoxedError = server
+        .llm()
+        .generate_data(&Review::new(), TARGET, vec![])
+        .unwrap_err();
+
+    match secretary_error(&error) {
+        SecretaryError::ValueNotAllowed {
+            field_path,
+            value,
+            allowed,
+        } => {
+            assert_eq!(field_path, "comments[1].tone");
+            assert_eq!(value, "n");
+            assert_eq!(allowed, &vec!["positive", "negative", "neutral"]);
+        }
+        other => panic!("unexpected error: {}", other),
+    }
+    assert!(!error.to_string().is_empty());
+}
+
+#[tokio::test]
+async fn async_extraction_normalizes_answers() {
+    let server = MockServer::always(success(&messy().to_string()));
+
+    let review: Review = server
+        .llm()
+        .async_generate_data(&Review::new(), TARGET, vec![])
+        .await
+        .unwrap();
+    assert_eq!(review, ann());
+
+    let result = server
+        .llm()
+        .async_generate_data_adaptive(&Review::new(), TARGET, vec![])
+        .await
+        .unwrap();
+    assert_eq!(result.metadata.normalized_values.len(), 3);
+}